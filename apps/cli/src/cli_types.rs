@@ -0,0 +1,179 @@
+//! Machine-readable CLI output (`--output json`)
+//!
+//! Commands that support JSON print exactly one [`JsonOutput`] object to
+//! stdout: `{"schema_version": 1, "kind": "...", "data": {...}}`. `kind`
+//! names the struct in `data`. Fields are only ever added within a schema
+//! version; renames or removals bump [`SCHEMA_VERSION`]. Warnings and logs
+//! keep going to stderr so stdout stays parseable.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use craftnet_ipc_client::{ExitNodeInfo, NodeStatsResult};
+
+/// Version of the JSON schemas below
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// `--output` values
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// One versioned JSON object on stdout
+    Json,
+}
+
+/// Envelope around every JSON output
+#[derive(Debug, Serialize)]
+pub struct JsonOutput<'a, T: Serialize> {
+    pub schema_version: u32,
+    pub kind: &'a str,
+    pub data: &'a T,
+}
+
+/// Print `data` as a [`JsonOutput`] of `kind`
+pub fn print_json<T: Serialize>(kind: &str, data: &T) -> Result<()> {
+    let output = JsonOutput { schema_version: SCHEMA_VERSION, kind, data };
+    println!("{}", serde_json::to_string_pretty(&output)?);
+    Ok(())
+}
+
+/// `craftnet status` (kind `status`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatusOutput {
+    pub state: String,
+    pub connected: bool,
+    pub mode: Option<String>,
+    pub privacy_level: Option<String>,
+    /// Exit node in use (hex pubkey)
+    pub exit_node: Option<String>,
+    pub peer_count: Option<u64>,
+    pub shards_relayed: Option<u64>,
+    pub requests_exited: Option<u64>,
+    pub credits: Option<u64>,
+}
+
+/// `craftnet credits` (kind `credits`)
+#[derive(Debug, Clone, Serialize)]
+pub struct CreditsOutput {
+    pub credits: u64,
+}
+
+/// `craftnet credits buy` (kind `credit_purchase`)
+#[derive(Debug, Clone, Serialize)]
+pub struct CreditPurchaseOutput {
+    pub amount: u64,
+    /// Daemon's purchase result, passed through as-is
+    pub result: serde_json::Value,
+}
+
+/// One exit in [`ExitListOutput`]
+#[derive(Debug, Clone, Serialize)]
+pub struct ExitOutput {
+    /// Hex pubkey
+    pub pubkey: String,
+    pub peer_id: Option<String>,
+    pub region: String,
+    pub country_code: Option<String>,
+    pub city: Option<String>,
+    pub score: u8,
+    /// Percent
+    pub load: u8,
+    pub latency_ms: Option<u64>,
+}
+
+impl From<&ExitNodeInfo> for ExitOutput {
+    fn from(exit: &ExitNodeInfo) -> Self {
+        Self {
+            pubkey: exit.pubkey.clone(),
+            peer_id: exit.peer_id.clone(),
+            region: exit.region.clone(),
+            country_code: exit.country_code.clone(),
+            city: exit.city.clone(),
+            score: exit.score,
+            load: exit.load,
+            latency_ms: exit.latency_ms,
+        }
+    }
+}
+
+/// `craftnet exits` (kind `exit_list`)
+#[derive(Debug, Clone, Serialize)]
+pub struct ExitListOutput {
+    pub exits: Vec<ExitOutput>,
+}
+
+/// `craftnet stats` (kind `node_stats`)
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeStatsOutput {
+    pub shards_relayed: u64,
+    pub requests_exited: u64,
+    pub peers_connected: usize,
+    pub credits_earned: u64,
+    pub credits_spent: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub bytes_relayed: u64,
+    pub proof_queue_depth: usize,
+    /// None: unlimited
+    pub bandwidth_limit_kbps: Option<u64>,
+}
+
+impl From<&NodeStatsResult> for NodeStatsOutput {
+    fn from(stats: &NodeStatsResult) -> Self {
+        Self {
+            shards_relayed: stats.shards_relayed,
+            requests_exited: stats.requests_exited,
+            peers_connected: stats.peers_connected,
+            credits_earned: stats.credits_earned,
+            credits_spent: stats.credits_spent,
+            bytes_sent: stats.bytes_sent,
+            bytes_received: stats.bytes_received,
+            bytes_relayed: stats.bytes_relayed,
+            proof_queue_depth: stats.proof_queue_depth,
+            bandwidth_limit_kbps: stats.bandwidth_limit_kbps,
+        }
+    }
+}
+
+/// `craftnet aggregator export` (kind `aggregator_export`)
+#[derive(Debug, Clone, Serialize)]
+pub struct AggregatorExportOutput {
+    pub rows: u64,
+    /// Output file
+    pub path: String,
+    /// `csv` or `parquet`
+    pub format: String,
+    /// `hourly`, `daily`, `weekly` or `monthly`
+    pub granularity: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_envelope() {
+        let data = CreditsOutput { credits: 42 };
+        let output = JsonOutput { schema_version: SCHEMA_VERSION, kind: "credits", data: &data };
+        let value = serde_json::to_value(&output).unwrap();
+        assert_eq!(value, serde_json::json!({
+            "schema_version": 1,
+            "kind": "credits",
+            "data": { "credits": 42 },
+        }));
+
+        // Status parses the daemon's reply, tolerating missing fields
+        let status: StatusOutput = serde_json::from_value(serde_json::json!({
+            "state": "connected",
+            "connected": true,
+            "peer_count": 3,
+            "unrelated": "ignored",
+        }))
+        .unwrap();
+        assert_eq!(status.peer_count, Some(3));
+        assert!(status.mode.is_none());
+    }
+}
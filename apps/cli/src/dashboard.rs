@@ -0,0 +1,267 @@
+//! Live node dashboard (`craftnet node dashboard`)
+//!
+//! A terminal UI over the daemon's IPC socket, so it attaches to a node that
+//! is already running instead of starting one. Every refresh it polls
+//! `get_node_stats`, `list_peers`, `get_throughput_series` and
+//! `get_recent_logs`; rates (shards/s, credits/h) come from the difference
+//! between two consecutive stats polls. Press `q` or Esc to quit.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Gauge, List, ListItem, Paragraph, Sparkline};
+use ratatui::{DefaultTerminal, Frame};
+
+use craftnet_ipc_client::{IpcClient, LogLineResult, NodeStatsResult, PeerConnectionResult, ThroughputSampleResult};
+
+use crate::format_bytes;
+
+/// Log lines kept on screen
+const MAX_LOG_LINES: usize = 200;
+
+/// Throughput window shown in the sparkline (seconds)
+const THROUGHPUT_WINDOW_SECS: u64 = 60;
+
+/// Everything the dashboard shows, updated from IPC polls
+#[derive(Default)]
+pub struct DashboardState {
+    stats: Option<NodeStatsResult>,
+    polled_at: Option<Instant>,
+    shards_per_sec: f64,
+    credits_per_hour: f64,
+    peers: Vec<PeerConnectionResult>,
+    throughput: Vec<ThroughputSampleResult>,
+    logs: VecDeque<LogLineResult>,
+    last_log_seq: Option<u64>,
+    error: Option<String>,
+}
+
+impl DashboardState {
+    /// Record a stats poll taken at `now` and update the rates
+    pub fn update_stats(&mut self, stats: NodeStatsResult, now: Instant) {
+        if let (Some(previous), Some(polled_at)) = (&self.stats, self.polled_at) {
+            let elapsed = now.saturating_duration_since(polled_at).as_secs_f64();
+            if elapsed > 0.0 {
+                let shards = stats.shards_relayed.saturating_sub(previous.shards_relayed);
+                let credits = stats.credits_earned.saturating_sub(previous.credits_earned);
+                self.shards_per_sec = shards as f64 / elapsed;
+                self.credits_per_hour = credits as f64 / elapsed * 3600.0;
+            }
+        }
+        self.stats = Some(stats);
+        self.polled_at = Some(now);
+    }
+
+    /// Append new log lines, keeping the newest [`MAX_LOG_LINES`]
+    pub fn push_logs(&mut self, lines: Vec<LogLineResult>) {
+        for line in lines {
+            self.last_log_seq = Some(line.seq);
+            if self.logs.len() >= MAX_LOG_LINES {
+                self.logs.pop_front();
+            }
+            self.logs.push_back(line);
+        }
+    }
+
+    /// Total traffic of the newest throughput sample in kbps
+    pub fn current_kbps(&self) -> u64 {
+        self.throughput.last().map(|s| sample_bytes(s) * 8 / 1000).unwrap_or(0)
+    }
+}
+
+fn sample_bytes(sample: &ThroughputSampleResult) -> u64 {
+    sample.client_up + sample.client_down + sample.relay_up + sample.relay_down + sample.exit_up + sample.exit_down
+}
+
+/// Run the dashboard until the user quits
+pub async fn run(client: &IpcClient, refresh: Duration) -> Result<()> {
+    // Fail before taking over the terminal if the daemon isn't there
+    client.get_node_stats().await?;
+
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, client, refresh).await;
+    ratatui::restore();
+    result
+}
+
+async fn event_loop(terminal: &mut DefaultTerminal, client: &IpcClient, refresh: Duration) -> Result<()> {
+    let mut state = DashboardState::default();
+    loop {
+        poll(client, &mut state).await;
+        terminal.draw(|frame| draw(frame, &state))?;
+
+        let deadline = Instant::now() + refresh;
+        while Instant::now() < deadline {
+            if !event::poll(Duration::from_millis(100))? {
+                continue;
+            }
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+async fn poll(client: &IpcClient, state: &mut DashboardState) {
+    let stats = match client.get_node_stats().await {
+        Ok(stats) => stats,
+        Err(e) => {
+            state.error = Some(e.to_string());
+            return;
+        }
+    };
+    state.update_stats(stats, Instant::now());
+    state.error = None;
+
+    // The rest is best effort: older daemons may not serve every method
+    if let Ok(peers) = client.list_peers().await {
+        state.peers = peers.peers;
+    }
+    if let Ok(series) = client.get_throughput_series(Some(THROUGHPUT_WINDOW_SECS)).await {
+        state.throughput = series.samples;
+    }
+    if let Ok(logs) = client.get_recent_logs(state.last_log_seq, Some(MAX_LOG_LINES)).await {
+        state.push_logs(logs.lines);
+    }
+}
+
+fn draw(frame: &mut Frame, state: &DashboardState) {
+    let [header, middle, bandwidth, logs] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Min(8),
+        Constraint::Length(7),
+        Constraint::Percentage(35),
+    ])
+    .areas(frame.area());
+
+    let status = match &state.error {
+        Some(e) => Span::styled(format!(" daemon unreachable: {}", e), Style::default().fg(Color::Red)),
+        None => Span::styled(" connected", Style::default().fg(Color::Green)),
+    };
+    frame.render_widget(
+        Paragraph::new(Line::from(vec![status, Span::raw("  (q to quit)")]))
+            .block(Block::bordered().title(" CraftNet node ")),
+        header,
+    );
+
+    let [counters, peers] = Layout::horizontal([Constraint::Percentage(45), Constraint::Percentage(55)]).areas(middle);
+
+    let mut lines = Vec::new();
+    if let Some(stats) = &state.stats {
+        lines.push(Line::from(format!("Shards relayed:    {} ({:.1}/s)", stats.shards_relayed, state.shards_per_sec)));
+        lines.push(Line::from(format!("Requests exited:   {}", stats.requests_exited)));
+        lines.push(Line::from(format!("Proof queue:       {}", stats.proof_queue_depth)));
+        lines.push(Line::from(format!("Credits earned:    {}", stats.credits_earned)));
+        lines.push(Line::from(format!("Earning rate:      ~{:.0} credits/h", state.credits_per_hour)));
+        lines.push(Line::from(format!("Bytes relayed:     {}", format_bytes(stats.bytes_relayed))));
+    }
+    frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(" Node ")), counters);
+
+    let peer_items: Vec<ListItem> = state
+        .peers
+        .iter()
+        .map(|p| {
+            ListItem::new(format!(
+                "{} {:<6} {:>5}s  ↑{} ↓{}",
+                short_peer_id(&p.peer_id),
+                p.transport,
+                p.age_secs,
+                format_bytes(p.bytes_sent),
+                format_bytes(p.bytes_received),
+            ))
+        })
+        .collect();
+    frame.render_widget(
+        List::new(peer_items).block(Block::bordered().title(format!(" Peers ({}) ", state.peers.len()))),
+        peers,
+    );
+
+    let [gauge, sparkline] = Layout::vertical([Constraint::Length(3), Constraint::Min(3)]).areas(bandwidth);
+    let kbps = state.current_kbps();
+    let limit = state.stats.as_ref().and_then(|s| s.bandwidth_limit_kbps);
+    let gauge_widget = match limit {
+        Some(limit) if limit > 0 => Gauge::default()
+            .ratio((kbps as f64 / limit as f64).min(1.0))
+            .label(format!("{} / {} kbps", kbps, limit)),
+        _ => Gauge::default().ratio(0.0).label(format!("{} kbps (no cap)", kbps)),
+    };
+    frame.render_widget(
+        gauge_widget.gauge_style(Style::default().fg(Color::Cyan)).block(Block::bordered().title(" Bandwidth cap ")),
+        gauge,
+    );
+    let samples: Vec<u64> = state.throughput.iter().map(sample_bytes).collect();
+    frame.render_widget(
+        Sparkline::default()
+            .data(&samples)
+            .block(Block::bordered().title(format!(" Throughput (last {}s) ", THROUGHPUT_WINDOW_SECS))),
+        sparkline,
+    );
+
+    let visible = logs.height.saturating_sub(2) as usize;
+    let log_items: Vec<ListItem> = state
+        .logs
+        .iter()
+        .skip(state.logs.len().saturating_sub(visible))
+        .map(|l| {
+            let color = match l.level.as_str() {
+                "ERROR" => Color::Red,
+                "WARN" => Color::Yellow,
+                _ => Color::Reset,
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{:<5} ", l.level), Style::default().fg(color)),
+                Span::raw(l.message.clone()),
+            ]))
+        })
+        .collect();
+    frame.render_widget(List::new(log_items).block(Block::bordered().title(" Recent logs ")), logs);
+}
+
+fn short_peer_id(peer_id: &str) -> String {
+    if peer_id.len() > 12 {
+        format!("…{}", &peer_id[peer_id.len() - 12..])
+    } else {
+        peer_id.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(shards_relayed: u64, credits_earned: u64) -> NodeStatsResult {
+        serde_json::from_value(serde_json::json!({
+            "shards_relayed": shards_relayed,
+            "credits_earned": credits_earned,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_dashboard_rates() {
+        let start = Instant::now();
+        let mut state = DashboardState::default();
+        state.update_stats(stats(100, 10), start);
+        assert_eq!(state.shards_per_sec, 0.0);
+
+        state.update_stats(stats(150, 12), start + Duration::from_secs(2));
+        assert_eq!(state.shards_per_sec, 25.0);
+        assert_eq!(state.credits_per_hour, 3600.0);
+
+        state.push_logs(vec![LogLineResult {
+            seq: 7,
+            timestamp_ms: 0,
+            level: "INFO".to_string(),
+            target: "craftnet".to_string(),
+            message: "started".to_string(),
+        }]);
+        assert_eq!(state.last_log_seq, Some(7));
+    }
+}
@@ -13,9 +13,19 @@ use tracing::info;
 use craftec_app::{AppBuilder, AppType};
 use craftnet_client::{Capabilities, NodeConfig, CraftNetNode};
 use craftnet_core::HopMode;
-use craftnet_ipc_client::{IpcClient, DEFAULT_SOCKET_PATH};
+use craftnet_core::{BuildManifest, CraftNetConfig, UpdateChannel};
+use craftnet_ipc_client::{IpcClient, RequestOptions, DEFAULT_SOCKET_PATH};
 use craftec_keystore::expand_path;
 
+mod cli_types;
+mod dashboard;
+mod service;
+mod shell;
+mod update;
+
+use cli_types::{print_json, OutputFormat};
+use service::{ServiceProfile, ServiceSpec};
+
 /// CraftNet - Decentralized Trustless VPN
 #[derive(Parser)]
 #[command(name = "craftnet")]
@@ -29,6 +39,11 @@ struct Cli {
     #[arg(short, long)]
     verbose: bool,
 
+    /// Output format (json: one versioned object on stdout, for `status`,
+    /// `stats`, `exits`, `credits` and `aggregator` commands)
+    #[arg(long, global = true, value_enum, default_value = "text")]
+    output: OutputFormat,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -44,6 +59,11 @@ enum Commands {
         /// Preferred exit region (na, eu, ap, sa, af, me, oc)
         #[arg(long)]
         exit_region: Option<String>,
+
+        /// Route all device traffic through the tunnel (TUN interface;
+        /// needs a daemon built with the `tun` feature and admin rights)
+        #[arg(long)]
+        full_device: bool,
     },
 
     /// Disconnect from the network
@@ -147,7 +167,15 @@ enum Commands {
     },
 
     /// Show connection history
-    History,
+    History {
+        /// Show only the newest N sessions
+        #[arg(short, long)]
+        limit: Option<usize>,
+
+        /// Delete the recorded history instead of showing it
+        #[arg(long)]
+        clear: bool,
+    },
 
     /// Show earnings history
     Earnings,
@@ -166,6 +194,42 @@ enum Commands {
         #[command(subcommand)]
         action: KeyAction,
     },
+
+    /// Exit abuse log: request volume per destination, throttled pools and
+    /// operator blocks (exit nodes)
+    Abuse {
+        #[command(subcommand)]
+        action: AbuseAction,
+    },
+
+    /// Developer and diagnostics tools
+    Dev {
+        #[command(subcommand)]
+        action: DevAction,
+    },
+
+    /// Aggregator data tools (work on the files an aggregator node writes)
+    Aggregator {
+        #[command(subcommand)]
+        action: AggregatorAction,
+    },
+
+    /// Check for and install node binary updates
+    Update {
+        #[command(subcommand)]
+        action: UpdateAction,
+    },
+
+    /// Install or manage the daemon / a node as a system service
+    /// (systemd, launchd, Windows service)
+    Service {
+        #[command(subcommand)]
+        action: ServiceAction,
+    },
+
+    /// Interactive prompt over one daemon connection, with history and
+    /// tab completion
+    Shell,
 }
 
 #[derive(Subcommand)]
@@ -245,6 +309,12 @@ enum NodeSubcommand {
         #[arg(long, default_value = "~/.craftnet/node.key")]
         keyfile: PathBuf,
     },
+    /// Live terminal dashboard of the node behind the daemon socket
+    Dashboard {
+        /// Refresh interval in milliseconds
+        #[arg(long, default_value = "1000")]
+        refresh_ms: u64,
+    },
 }
 
 #[derive(Subcommand)]
@@ -280,6 +350,246 @@ enum KeyAction {
     },
 }
 
+#[derive(Subcommand)]
+enum AbuseAction {
+    /// Show the busiest destinations (by hash), throttled pools and blocks
+    Report {
+        /// Destinations to list
+        #[arg(short, long, default_value = "20")]
+        limit: usize,
+    },
+    /// Refuse every request to a destination, or from a pool
+    Block {
+        /// Hostname, or destination hash from `abuse report`
+        #[arg(required_unless_present = "pool", conflicts_with = "pool")]
+        destination: Option<String>,
+
+        /// Pool pubkey (hex)
+        #[arg(long)]
+        pool: Option<String>,
+    },
+    /// Lift a block
+    Unblock {
+        /// Hostname, or destination hash from `abuse report`
+        #[arg(required_unless_present = "pool", conflicts_with = "pool")]
+        destination: Option<String>,
+
+        /// Pool pubkey (hex)
+        #[arg(long)]
+        pool: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum DevAction {
+    /// Show the live network topology (nodes, roles, links)
+    Topology {
+        /// Emit a Graphviz DOT graph instead of JSON
+        #[arg(long)]
+        dot: bool,
+    },
+    /// List connected peers (address, direction, transport, age, bytes)
+    Peers {
+        /// Also print the protocols each peer announced
+        #[arg(long)]
+        protocols: bool,
+    },
+    /// Inspect the Kademlia DHT (routing tables and registry providers by default)
+    Dht {
+        #[command(subcommand)]
+        action: Option<DhtAction>,
+    },
+    /// Send a traced test request and show its per-hop latency breakdown
+    Trace {
+        /// URL to request (GET)
+        url: String,
+
+        /// Privacy level for the request (direct, single, double, triple, quad)
+        #[arg(long)]
+        hop_mode: Option<String>,
+    },
+    /// Print this binary's build manifest and check the daemon's build
+    /// against the canonical manifests in the network parameters
+    Attest {
+        /// Only print the local manifest (don't ask the daemon)
+        #[arg(long)]
+        local: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum DhtAction {
+    /// Add a peer address to the routing tables
+    AddAddress {
+        /// Multiaddr ending in /p2p/<peer_id>
+        addr: String,
+    },
+    /// Start a bootstrap query
+    Bootstrap {
+        /// DHT to bootstrap (main, registry)
+        #[arg(long, default_value = "registry")]
+        table: String,
+    },
+    /// Look up one record
+    GetRecord {
+        /// Record key (e.g. /craftnet/exits/<peer_id>)
+        key: String,
+
+        /// DHT to query (main, registry)
+        #[arg(long, default_value = "registry")]
+        table: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum AggregatorAction {
+    /// Export bandwidth time series per relay/pool to CSV or Parquet
+    Export {
+        /// Aggregator history file (aggregator-history-<peer_id>.bin)
+        history: PathBuf,
+
+        /// Output file
+        #[arg(short, long)]
+        out: PathBuf,
+
+        /// Output format (csv, parquet)
+        #[arg(long, default_value = "csv")]
+        format: craftnet_aggregator::ExportFormat,
+
+        /// Bucket size (hourly, daily, weekly, monthly)
+        #[arg(long, default_value = "hourly")]
+        granularity: craftnet_aggregator::Granularity,
+
+        /// First bucket to include (unix seconds)
+        #[arg(long)]
+        start: Option<u64>,
+
+        /// Last bucket to include (unix seconds)
+        #[arg(long)]
+        end: Option<u64>,
+
+        /// Only this relay (hex pubkey)
+        #[arg(long)]
+        relay: Option<String>,
+
+        /// Only this pool (hex pubkey)
+        #[arg(long)]
+        pool: Option<String>,
+    },
+    /// Write the golden hashing vectors (Merkle leaves/roots, receipt and
+    /// proof signing bytes) shared by the prover, guests and program tests
+    TestVectors {
+        /// Output file (default: stdout)
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum UpdateAction {
+    /// Show whether a newer release is available
+    Check {
+        #[command(flatten)]
+        source: UpdateSource,
+    },
+    /// Install the newer release, if any, over this binary
+    Apply {
+        #[command(flatten)]
+        source: UpdateSource,
+    },
+}
+
+/// Overrides for the `update` section of the settings file
+#[derive(clap::Args)]
+struct UpdateSource {
+    /// Release channel (stable, beta)
+    #[arg(long)]
+    channel: Option<UpdateChannel>,
+
+    /// Signed manifest URL (`{channel}` is replaced by the channel name)
+    #[arg(long)]
+    url: Option<String>,
+
+    /// Download directly instead of through the tunnel
+    #[arg(long)]
+    direct: bool,
+
+    /// Bootstrap peer for the tunnel (format: <peer_id>@<multiaddr>)
+    #[arg(short, long)]
+    bootstrap: Vec<String>,
+}
+
+#[derive(Subcommand)]
+enum ServiceAction {
+    /// Write the service definition, register it and start it
+    Install {
+        /// What to run (daemon, relay, exit, full)
+        #[arg(long, default_value = "daemon")]
+        profile: ServiceProfile,
+
+        /// Per-user service (systemd --user, LaunchAgent) instead of system-wide
+        #[arg(long)]
+        user: bool,
+
+        /// Account a system service runs as (default: root / LocalSystem)
+        #[arg(long)]
+        run_as: Option<String>,
+
+        /// Environment for the service (KEY=VALUE, repeatable)
+        #[arg(short, long)]
+        env: Vec<String>,
+
+        /// Log directory (default: /var/log/craftnet, /Library/Logs/CraftNet,
+        /// %ProgramData%\CraftNet\logs, or ~/.craftnet/logs with --user)
+        #[arg(long)]
+        log_dir: Option<PathBuf>,
+
+        /// Print the definition instead of installing it
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Extra arguments for the profile command (after --)
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+    /// Stop and remove the service
+    Uninstall {
+        /// Which service (daemon, relay, exit, full)
+        #[arg(long, default_value = "daemon")]
+        profile: ServiceProfile,
+
+        /// Per-user service
+        #[arg(long)]
+        user: bool,
+    },
+    /// Show the service definition, log location and state
+    Status {
+        /// Which service (daemon, relay, exit, full)
+        #[arg(long, default_value = "daemon")]
+        profile: ServiceProfile,
+
+        /// Per-user service
+        #[arg(long)]
+        user: bool,
+
+        /// Log directory the service was installed with
+        #[arg(long)]
+        log_dir: Option<PathBuf>,
+    },
+    /// Windows service entry point (run by the service control manager)
+    #[command(hide = true)]
+    Run {
+        #[arg(long)]
+        profile: ServiceProfile,
+
+        #[arg(long)]
+        log_file: PathBuf,
+
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
@@ -287,6 +597,7 @@ async fn main() -> Result<()> {
     // Initialize app with standard startup sequence
     let app_type = match &cli.command {
         Commands::Daemon { .. } => AppType::Daemon,
+        Commands::Node { mode: NodeSubcommand::Dashboard { .. } } => AppType::Cli,
         Commands::Node { .. } => AppType::Node,
         _ => AppType::Cli,
     };
@@ -297,33 +608,42 @@ async fn main() -> Result<()> {
         .build()
         .map_err(|e| anyhow::anyhow!("{}", e))?;
 
-    match cli.command {
-        Commands::Connect { hops, exit_region } => {
-            connect(&cli.socket, hops, exit_region).await?;
+    if let Commands::Shell = cli.command {
+        return shell::run(&cli.socket, cli.output).await;
+    }
+    let client = IpcClient::new(cli.socket.clone());
+    run_command(cli.command, &client, cli.output).await
+}
+
+/// Run one parsed command (from the command line or a `craftnet shell` line)
+async fn run_command(command: Commands, client: &IpcClient, output: OutputFormat) -> Result<()> {
+    match command {
+        Commands::Connect { hops, exit_region, full_device } => {
+            connect(client, hops, exit_region, full_device).await?;
         }
         Commands::Disconnect => {
-            disconnect(&cli.socket).await?;
+            disconnect(client).await?;
         }
         Commands::Status => {
-            status(&cli.socket).await?;
+            status(client, output).await?;
         }
         Commands::Stats => {
-            stats(&cli.socket).await?;
+            stats(client, output).await?;
         }
         Commands::Mode { mode } => {
-            mode_cmd(&cli.socket, mode).await?;
+            mode_cmd(client, mode).await?;
         }
         Commands::Exits => {
-            exits(&cli.socket).await?;
+            exits(client, output).await?;
         }
         Commands::Privacy { level } => {
-            privacy_cmd(&cli.socket, level).await?;
+            privacy_cmd(client, level).await?;
         }
         Commands::Discovery { state } => {
-            discovery_cmd(&cli.socket, state).await?;
+            discovery_cmd(client, state).await?;
         }
         Commands::Credits { action } => {
-            credits(&cli.socket, action).await?;
+            credits(client, action, output).await?;
         }
         Commands::Request {
             method,
@@ -331,7 +651,7 @@ async fn main() -> Result<()> {
             body,
             header,
         } => {
-            request(&cli.socket, &method, &url, body, header).await?;
+            request(client, &method, &url, body, header).await?;
         }
         Commands::Daemon { bootstrap, port } => {
             run_daemon(bootstrap, port).await?;
@@ -350,23 +670,44 @@ async fn main() -> Result<()> {
         } => {
             fetch_standalone(&url, hops, bootstrap).await?;
         }
+        Commands::Node { mode: NodeSubcommand::Dashboard { refresh_ms } } => {
+            dashboard::run(client, Duration::from_millis(refresh_ms.max(100))).await?;
+        }
         Commands::Node { mode } => {
             run_node(mode).await?;
         }
-        Commands::History => {
-            history(&cli.socket).await?;
+        Commands::History { limit, clear } => {
+            history(client, limit, clear).await?;
         }
         Commands::Earnings => {
-            earnings_history(&cli.socket).await?;
+            earnings_history(client).await?;
         }
         Commands::Speedtest => {
-            speedtest(&cli.socket).await?;
+            speedtest(client).await?;
         }
         Commands::Bandwidth { limit } => {
-            bandwidth_cmd(&cli.socket, limit).await?;
+            bandwidth_cmd(client, limit).await?;
         }
         Commands::Key { action } => {
-            key_cmd(&cli.socket, action).await?;
+            key_cmd(client, action).await?;
+        }
+        Commands::Abuse { action } => {
+            abuse_cmd(client, action).await?;
+        }
+        Commands::Dev { action } => {
+            dev_cmd(client, action).await?;
+        }
+        Commands::Aggregator { action } => {
+            aggregator_cmd(action, output)?;
+        }
+        Commands::Update { action } => {
+            update_cmd(action).await?;
+        }
+        Commands::Service { action } => {
+            service_cmd(action)?;
+        }
+        Commands::Shell => {
+            anyhow::bail!("Already in the shell");
         }
     }
 
@@ -378,11 +719,9 @@ async fn main() -> Result<()> {
 // IPC Commands (using shared ipc-client crate)
 // ============================================================================
 
-async fn connect(socket: &Path, hops: u8, exit_region: Option<String>) -> Result<()> {
+async fn connect(client: &IpcClient, hops: u8, exit_region: Option<String>, full_device: bool) -> Result<()> {
     info!("Connecting to CraftNet network with {} hops...", hops);
 
-    let client = IpcClient::new(socket.to_path_buf());
-
     // Set exit region preference before connecting
     if let Some(ref region) = exit_region {
         client.set_exit_node(region, None, None).await
@@ -390,10 +729,17 @@ async fn connect(socket: &Path, hops: u8, exit_region: Option<String>) -> Result
         println!("Exit region set to: {}", region);
     }
 
-    let result = client.connect_vpn(hops).await?;
+    let result = if full_device {
+        client.connect_full_device(hops).await?
+    } else {
+        client.connect_vpn(hops).await?
+    };
 
     if result.connected {
         println!("Connected to CraftNet network");
+        if result.full_device {
+            println!("All device traffic is routed through the tunnel");
+        }
         if let Some(exit) = result.exit_node {
             println!("Exit node: {}", exit);
         }
@@ -404,47 +750,52 @@ async fn connect(socket: &Path, hops: u8, exit_region: Option<String>) -> Result
     Ok(())
 }
 
-async fn disconnect(socket: &Path) -> Result<()> {
+async fn disconnect(client: &IpcClient) -> Result<()> {
     info!("Disconnecting from CraftNet network...");
 
-    let client = IpcClient::new(socket.to_path_buf());
     client.disconnect().await?;
 
     println!("Disconnected from CraftNet network");
     Ok(())
 }
 
-async fn status(socket: &Path) -> Result<()> {
-    let client = IpcClient::new(socket.to_path_buf());
-
+async fn status(client: &IpcClient, output: OutputFormat) -> Result<()> {
     // Use raw send_request to get the full status including new fields
     let result = client.send_request("status", None).await?;
+    let status: cli_types::StatusOutput = serde_json::from_value(result)
+        .context("Invalid status response")?;
 
-    println!("CraftNet Status");
-    println!("==================");
-    println!("State:         {}", result.get("state").and_then(|v| v.as_str()).unwrap_or("unknown"));
-    println!("Connected:     {}", result.get("connected").and_then(|v| v.as_bool()).unwrap_or(false));
+    if output == OutputFormat::Json {
+        print_json("status", &status)?;
+    } else {
+        println!("CraftNet Status");
+        println!("==================");
+        println!("State:         {}", if status.state.is_empty() { "unknown" } else { &status.state });
+        println!("Connected:     {}", status.connected);
 
-    if let Some(mode) = result.get("mode").and_then(|v| v.as_str()) {
-        println!("Mode:          {}", mode);
-    }
-    if let Some(privacy) = result.get("privacy_level").and_then(|v| v.as_str()) {
-        println!("Privacy:       {}", privacy);
-    }
-    if let Some(exit) = result.get("exit_node").and_then(|v| v.as_str()) {
-        println!("Exit node:     {}", exit);
-    }
-    if let Some(peers) = result.get("peer_count").and_then(|v| v.as_u64()) {
-        println!("Peers:         {}", peers);
-    }
-    if let Some(shards) = result.get("shards_relayed").and_then(|v| v.as_u64()) {
-        println!("Shards relayed:{}", shards);
-    }
-    if let Some(exited) = result.get("requests_exited").and_then(|v| v.as_u64()) {
-        println!("Requests exited:{}", exited);
+        if let Some(mode) = &status.mode {
+            println!("Mode:          {}", mode);
+        }
+        if let Some(privacy) = &status.privacy_level {
+            println!("Privacy:       {}", privacy);
+        }
+        if let Some(exit) = &status.exit_node {
+            println!("Exit node:     {}", exit);
+        }
+        if let Some(peers) = status.peer_count {
+            println!("Peers:         {}", peers);
+        }
+        if let Some(shards) = status.shards_relayed {
+            println!("Shards relayed:{}", shards);
+        }
+        if let Some(exited) = status.requests_exited {
+            println!("Requests exited:{}", exited);
+        }
+        if let Some(credits) = status.credits {
+            println!("Credits:       {}", credits);
+        }
     }
-    if let Some(credits) = result.get("credits").and_then(|v| v.as_u64()) {
-        println!("Credits:       {}", credits);
+    if let Some(credits) = status.credits {
         if credits <= 20 {
             eprintln!("\x1b[31mCRITICAL: Credit balance critically low!\x1b[0m");
         } else if credits <= 100 {
@@ -455,10 +806,13 @@ async fn status(socket: &Path) -> Result<()> {
     Ok(())
 }
 
-async fn stats(socket: &Path) -> Result<()> {
-    let client = IpcClient::new(socket.to_path_buf());
+async fn stats(client: &IpcClient, output: OutputFormat) -> Result<()> {
     let result = client.get_node_stats().await?;
 
+    if output == OutputFormat::Json {
+        return print_json("node_stats", &cli_types::NodeStatsOutput::from(&result));
+    }
+
     println!("CraftNet Node Statistics");
     println!("===========================");
     println!("Shards relayed:   {}", result.shards_relayed);
@@ -486,9 +840,7 @@ fn format_bytes(bytes: u64) -> String {
     format!("{:.2} GB", bytes as f64 / (1024.0 * 1024.0 * 1024.0))
 }
 
-async fn mode_cmd(socket: &Path, mode: Option<String>) -> Result<()> {
-    let client = IpcClient::new(socket.to_path_buf());
-
+async fn mode_cmd(client: &IpcClient, mode: Option<String>) -> Result<()> {
     match mode {
         Some(new_mode) => {
             client.set_mode(&new_mode).await
@@ -507,10 +859,14 @@ async fn mode_cmd(socket: &Path, mode: Option<String>) -> Result<()> {
     Ok(())
 }
 
-async fn exits(socket: &Path) -> Result<()> {
-    let client = IpcClient::new(socket.to_path_buf());
+async fn exits(client: &IpcClient, output: OutputFormat) -> Result<()> {
     let result = client.get_available_exits().await?;
 
+    if output == OutputFormat::Json {
+        let exits = result.exits.iter().map(cli_types::ExitOutput::from).collect();
+        return print_json("exit_list", &cli_types::ExitListOutput { exits });
+    }
+
     if result.exits.is_empty() {
         println!("No exit nodes available. Connect to the network first.");
         return Ok(());
@@ -542,9 +898,7 @@ async fn exits(socket: &Path) -> Result<()> {
     Ok(())
 }
 
-async fn privacy_cmd(socket: &Path, level: Option<String>) -> Result<()> {
-    let client = IpcClient::new(socket.to_path_buf());
-
+async fn privacy_cmd(client: &IpcClient, level: Option<String>) -> Result<()> {
     match level {
         Some(new_level) => {
             client.set_privacy_level(&new_level).await
@@ -570,9 +924,7 @@ async fn privacy_cmd(socket: &Path, level: Option<String>) -> Result<()> {
     Ok(())
 }
 
-async fn discovery_cmd(socket: &Path, state: Option<String>) -> Result<()> {
-    let client = IpcClient::new(socket.to_path_buf());
-
+async fn discovery_cmd(client: &IpcClient, state: Option<String>) -> Result<()> {
     match state {
         Some(s) => {
             let enabled = match s.to_lowercase().as_str() {
@@ -595,18 +947,24 @@ async fn discovery_cmd(socket: &Path, state: Option<String>) -> Result<()> {
     Ok(())
 }
 
-async fn credits(socket: &Path, action: Option<CreditsAction>) -> Result<()> {
-    let client = IpcClient::new(socket.to_path_buf());
-
+async fn credits(client: &IpcClient, action: Option<CreditsAction>, output: OutputFormat) -> Result<()> {
     match action {
         Some(CreditsAction::Buy { amount }) => {
             info!("Purchasing {} credits...", amount);
             let result = client.purchase_credits(amount).await?;
-            println!("Purchase result: {}", result);
+            if output == OutputFormat::Json {
+                print_json("credit_purchase", &cli_types::CreditPurchaseOutput { amount, result })?;
+            } else {
+                println!("Purchase result: {}", result);
+            }
         }
         Some(CreditsAction::Show) | None => {
             let result = client.get_credits().await?;
-            println!("Current credits: {}", result.credits);
+            if output == OutputFormat::Json {
+                print_json("credits", &cli_types::CreditsOutput { credits: result.credits })?;
+            } else {
+                println!("Current credits: {}", result.credits);
+            }
 
             if result.credits <= 20 {
                 eprintln!("\x1b[31mCRITICAL: Credit balance is critically low! Purchase credits to continue using the network.\x1b[0m");
@@ -620,7 +978,7 @@ async fn credits(socket: &Path, action: Option<CreditsAction>) -> Result<()> {
 }
 
 async fn request(
-    socket: &Path,
+    client: &IpcClient,
     method: &str,
     url: &str,
     body: Option<String>,
@@ -628,8 +986,6 @@ async fn request(
 ) -> Result<()> {
     info!("Making {} request to {}", method, url);
 
-    let client = IpcClient::new(socket.to_path_buf());
-
     // Build headers map
     let headers_map: std::collections::HashMap<String, String> = headers
         .iter()
@@ -666,9 +1022,13 @@ async fn request(
 // New Feature Commands
 // ============================================================================
 
-async fn history(socket: &Path) -> Result<()> {
-    let client = IpcClient::new(socket.to_path_buf());
-    let result = client.get_connection_history().await?;
+async fn history(client: &IpcClient, limit: Option<usize>, clear: bool) -> Result<()> {
+    if clear {
+        let cleared = client.clear_connection_history().await?;
+        println!("Cleared {} connection history entries", cleared);
+        return Ok(());
+    }
+    let result = client.get_connection_history_limited(limit).await?;
 
     println!("Connection History");
     println!("==================");
@@ -678,28 +1038,35 @@ async fn history(socket: &Path) -> Result<()> {
         return Ok(());
     }
 
-    println!("{:<4} {:<20} {:<12} {:<12} {:<12}", "ID", "Connected", "Duration", "Sent", "Received");
-    println!("{}", "-".repeat(60));
+    println!("{:<4} {:<20} {:<12} {:<8} {:<18} {:<12} {:<12}", "ID", "Connected", "Duration", "Hops", "Exit", "Sent", "Received");
+    println!("{}", "-".repeat(92));
 
     for entry in &result.entries {
         let duration = entry.duration_secs
             .map(|d| format!("{}s", d))
             .unwrap_or_else(|| "active".to_string());
-        println!("{:<4} {:<20} {:<12} {:<12} {:<12}",
+        let exit = entry.exit.as_deref()
+            .map(|e| e.chars().take(16).collect::<String>())
+            .unwrap_or_else(|| "-".to_string());
+        println!("{:<4} {:<20} {:<12} {:<8} {:<18} {:<12} {:<12}",
             entry.id,
             entry.connected_at,
             duration,
+            entry.hop_mode.as_deref().unwrap_or("-"),
+            exit,
             format_bytes(entry.bytes_sent),
             format_bytes(entry.bytes_received),
         );
+        if let Some(ref reason) = entry.failure_reason {
+            println!("     failed: {}", reason);
+        }
     }
 
     println!("\n{} connection(s)", result.entries.len());
     Ok(())
 }
 
-async fn earnings_history(socket: &Path) -> Result<()> {
-    let client = IpcClient::new(socket.to_path_buf());
+async fn earnings_history(client: &IpcClient) -> Result<()> {
     let result = client.get_earnings_history().await?;
 
     println!("Earnings History");
@@ -707,30 +1074,59 @@ async fn earnings_history(socket: &Path) -> Result<()> {
 
     if result.entries.is_empty() {
         println!("No earnings yet.");
-        return Ok(());
-    }
-
-    println!("{:<4} {:<12} {:<10} {:<12} {:<8}", "ID", "Timestamp", "Type", "Credits", "Shards");
-    println!("{}", "-".repeat(50));
+    } else {
+        println!("{:<4} {:<12} {:<10} {:<12} {:<8}", "ID", "Timestamp", "Type", "Credits", "Shards");
+        println!("{}", "-".repeat(50));
+
+        for entry in &result.entries {
+            println!("{:<4} {:<12} {:<10} {:<12} {:<8}",
+                entry.id,
+                entry.timestamp,
+                entry.entry_type,
+                entry.credits_earned,
+                entry.shards_count,
+            );
+        }
 
-    for entry in &result.entries {
-        println!("{:<4} {:<12} {:<10} {:<12} {:<8}",
-            entry.id,
-            entry.timestamp,
-            entry.entry_type,
-            entry.credits_earned,
-            entry.shards_count,
-        );
+        println!("\n{} earning(s)", result.entries.len());
     }
 
-    println!("\n{} earning(s)", result.entries.len());
+    if !result.pools.is_empty() {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        println!("\nPool Claims");
+        println!("===========");
+        println!("{:<18} {:<12} {:<10} {}", "Pool", "Balance", "Phase", "Status");
+        println!("{}", "-".repeat(60));
+
+        for pool in &result.pools {
+            let status = match (pool.status.as_str(), pool.claimable_at) {
+                ("claimable_at", Some(at)) => {
+                    let wait = at.saturating_sub(now);
+                    format!("claimable at {} (in {}h {}m)", at, wait / 3600, wait % 3600 / 60)
+                }
+                ("claimable_now", _) => "claimable now".to_string(),
+                ("awaiting_distribution", _) => "waiting for distribution".to_string(),
+                ("claimed", _) => "already claimed".to_string(),
+                (other, _) => other.replace('_', " "),
+            };
+            println!("{:<18} {:<12} {:<10} {}",
+                &pool.pool[..pool.pool.len().min(16)],
+                pool.balance,
+                pool.phase.as_deref().unwrap_or("-"),
+                status,
+            );
+        }
+    }
     Ok(())
 }
 
-async fn speedtest(socket: &Path) -> Result<()> {
+async fn speedtest(client: &IpcClient) -> Result<()> {
     println!("Running speed test...");
 
-    let client = IpcClient::new(socket.to_path_buf());
     let result = client.run_speed_test().await?;
 
     println!("Speed Test Results");
@@ -743,9 +1139,7 @@ async fn speedtest(socket: &Path) -> Result<()> {
     Ok(())
 }
 
-async fn bandwidth_cmd(socket: &Path, limit: Option<u64>) -> Result<()> {
-    let client = IpcClient::new(socket.to_path_buf());
-
+async fn bandwidth_cmd(client: &IpcClient, limit: Option<u64>) -> Result<()> {
     match limit {
         Some(0) => {
             client.set_bandwidth_limit(None).await?;
@@ -765,9 +1159,7 @@ async fn bandwidth_cmd(socket: &Path, limit: Option<u64>) -> Result<()> {
     Ok(())
 }
 
-async fn key_cmd(socket: &Path, action: KeyAction) -> Result<()> {
-    let client = IpcClient::new(socket.to_path_buf());
-
+async fn key_cmd(client: &IpcClient, action: KeyAction) -> Result<()> {
     match action {
         KeyAction::Export { path, password } => {
             let result = client.export_key(&path, &password).await?;
@@ -785,6 +1177,331 @@ async fn key_cmd(socket: &Path, action: KeyAction) -> Result<()> {
     Ok(())
 }
 
+async fn abuse_cmd(client: &IpcClient, action: AbuseAction) -> Result<()> {
+    match action {
+        AbuseAction::Report { limit } => {
+            let report = client.get_exit_abuse(Some(limit)).await?;
+            if report.tracked_destinations == 0 {
+                println!("No exit requests logged.");
+            } else {
+                println!("{:<64} {:>10} {:>10} {:>10}", "Destination (SHA-256)", "Requests", "Refused", "Idle");
+                println!("{}", "-".repeat(97));
+                for d in &report.destinations {
+                    println!("{:<64} {:>10} {:>10} {:>9}s", d.destination, d.requests, d.refused, d.idle_secs);
+                }
+                println!("\n{} destination(s) logged", report.tracked_destinations);
+            }
+            if !report.throttled.is_empty() {
+                println!("\nThrottled:");
+                for t in &report.throttled {
+                    println!("  pool {} -> {} ({}s left)", t.pool, t.destination, t.remaining_secs);
+                }
+            }
+            for d in &report.blocked_destinations {
+                println!("Blocked destination: {}", d);
+            }
+            for p in &report.blocked_pools {
+                println!("Blocked pool: {}", p);
+            }
+        }
+        AbuseAction::Block { destination, pool } => {
+            let result = client.exit_block(destination.as_deref(), pool.as_deref()).await?;
+            println!("{}", if result.changed { "Blocked" } else { "Already blocked" });
+        }
+        AbuseAction::Unblock { destination, pool } => {
+            let result = client.exit_unblock(destination.as_deref(), pool.as_deref()).await?;
+            println!("{}", if result.changed { "Unblocked" } else { "Was not blocked" });
+        }
+    }
+
+    Ok(())
+}
+
+async fn dev_cmd(client: &IpcClient, action: DevAction) -> Result<()> {
+    match action {
+        DevAction::Topology { dot } => {
+            let topology = client.get_topology().await?;
+            if dot {
+                print!("{}", topology.to_dot());
+            } else {
+                println!("{}", serde_json::to_string_pretty(&topology)?);
+            }
+        }
+        DevAction::Peers { protocols } => {
+            let result = client.list_peers().await?;
+            if result.peers.is_empty() {
+                println!("No connected peers.");
+                return Ok(());
+            }
+
+            println!("{:<54} {:<9} {:<8} {:<10} {:<12} {:<12} Address", "Peer", "Dir", "Transport", "Age", "Sent", "Received");
+            println!("{}", "-".repeat(120));
+            for peer in &result.peers {
+                let age = format!("{}s", peer.age_secs);
+                println!("{:<54} {:<9} {:<8} {:<10} {:<12} {:<12} {}",
+                    peer.peer_id,
+                    peer.direction.as_deref().unwrap_or("-"),
+                    peer.transport,
+                    age,
+                    format_bytes(peer.bytes_sent),
+                    format_bytes(peer.bytes_received),
+                    peer.address.as_deref().unwrap_or("-"),
+                );
+                if protocols && !peer.protocols.is_empty() {
+                    println!("    {}", peer.protocols.join(", "));
+                }
+            }
+            println!("\n{} peer(s)", result.peers.len());
+        }
+        DevAction::Trace { url, hop_mode } => {
+            let options = RequestOptions { hop_mode, trace: true, ..Default::default() };
+            let started = std::time::Instant::now();
+            let result = client.request_with("GET", &url, None, None, &options).await?;
+            let total_ms = started.elapsed().as_millis();
+
+            println!("HTTP {} in {} ms ({} bytes)", result.status, total_ms, result.body.len());
+            if result.exit_failovers > 0 {
+                println!("Answered after {} exit failover(s)", result.exit_failovers);
+            }
+            if result.hop_timings.is_empty() {
+                println!("No hop timings returned (direct mode, or the hops don't support tracing).");
+                return Ok(());
+            }
+            // Gaps compare clocks of different hops, so they include clock skew
+            println!("\n{:<4} {:<6} {:>10} {:>14}", "Hop", "Role", "Held (ms)", "Gap (ms)");
+            let mut prev_sent: Option<u64> = None;
+            for (i, hop) in result.hop_timings.iter().enumerate() {
+                let gap = prev_sent
+                    .map(|sent| (hop.received_at_ms as i64 - sent as i64).to_string())
+                    .unwrap_or_else(|| "-".to_string());
+                println!("{:<4} {:<6} {:>10} {:>14}",
+                    i + 1,
+                    hop.role,
+                    hop.sent_at_ms.saturating_sub(hop.received_at_ms),
+                    gap,
+                );
+                prev_sent = Some(hop.sent_at_ms);
+            }
+        }
+        DevAction::Dht { action: None } => {
+            let dht = client.get_dht().await?;
+            for table in &dht.tables {
+                println!("{} table: {} peer(s) in {} bucket(s)", table.table, table.peer_count, table.buckets.len());
+                for bucket in &table.buckets {
+                    println!("  bucket {:>3}: {} peer(s)", bucket.index, bucket.peers.len());
+                    for peer in &bucket.peers {
+                        println!("    {} {}", peer.peer_id, peer.addresses.join(" "));
+                    }
+                }
+            }
+            if dht.providers.is_empty() {
+                println!("No registry providers known.");
+            } else {
+                println!("Registry providers:");
+                for key in &dht.providers {
+                    println!("  {} ({})", key.key, key.providers.len());
+                    for provider in &key.providers {
+                        println!("    {}", provider);
+                    }
+                }
+            }
+        }
+        DevAction::Dht { action: Some(DhtAction::AddAddress { addr }) } => {
+            client.dht_add_address(&addr).await?;
+            println!("Added {}", addr);
+        }
+        DevAction::Dht { action: Some(DhtAction::Bootstrap { table }) } => {
+            client.dht_bootstrap(Some(&table)).await?;
+            println!("Bootstrap of the {} DHT started", table);
+        }
+        DevAction::Attest { local } => {
+            let build = BuildManifest::current();
+            print_build_manifest(&build.version, &build.git_commit, &build.cargo_lock_sha256, &build.target, &build.rustc);
+            if local {
+                return Ok(());
+            }
+
+            let result = client.get_build_attestation().await?;
+            let daemon = &result.build;
+            if daemon.git_commit != build.git_commit || daemon.cargo_lock_sha256 != build.cargo_lock_sha256 {
+                println!("\nThe daemon runs a different build:");
+                print_build_manifest(&daemon.version, &daemon.git_commit, &daemon.cargo_lock_sha256, &daemon.target, &daemon.rustc);
+            }
+            let Some(attestation) = result.attestation else {
+                println!("\nNo network parameters yet (is the node running?); nothing to verify against.");
+                return Ok(());
+            };
+            let sequence = result.params_sequence.unwrap_or_default();
+            match attestation.status.as_str() {
+                "verified" => println!("\nVerified: matches the canonical build (network parameters #{})", sequence),
+                "unknown" => println!(
+                    "\nUnknown: network parameters #{} list no canonical build for {} on {}",
+                    sequence, daemon.version, daemon.target,
+                ),
+                _ => anyhow::bail!(
+                    "Build does not match the canonical {} build (network parameters #{}): {} differ",
+                    daemon.version, sequence, attestation.fields.join(", "),
+                ),
+            }
+        }
+        DevAction::Dht { action: Some(DhtAction::GetRecord { key, table }) } => {
+            let record = client.dht_get_record(&key, Some(&table)).await?;
+            if let Some(ref error) = record.error {
+                anyhow::bail!("Lookup of {} failed: {}", key, error);
+            }
+            if !record.found {
+                println!("No record for {}", key);
+                return Ok(());
+            }
+            println!("Publisher: {}", record.publisher.as_deref().unwrap_or("-"));
+            if let Some(ref rejection) = record.rejection {
+                println!("Rejected:  {}", rejection);
+            }
+            println!("{}", record.value.as_deref().unwrap_or(""));
+        }
+    }
+
+    Ok(())
+}
+
+fn print_build_manifest(version: &str, git_commit: &str, cargo_lock_sha256: &str, target: &str, rustc: &str) {
+    let or_unknown = |s: &str| if s.is_empty() { "unknown".to_string() } else { s.to_string() };
+    println!("Version:    {}", version);
+    println!("Commit:     {}", or_unknown(git_commit));
+    println!("Cargo.lock: {}", or_unknown(cargo_lock_sha256));
+    println!("Target:     {}", or_unknown(target));
+    println!("Compiler:   {}", or_unknown(rustc));
+}
+
+fn aggregator_cmd(action: AggregatorAction, output: OutputFormat) -> Result<()> {
+    use craftnet_aggregator::{Aggregator, ExportFilter, ExportFormat};
+
+    match action {
+        AggregatorAction::Export { history, out, format, granularity, start, end, relay, pool } => {
+            let parse_key = |hex_key: &str| -> Result<[u8; 32]> {
+                hex::decode(hex_key)
+                    .ok()
+                    .and_then(|b| b.try_into().ok())
+                    .with_context(|| format!("Invalid pubkey: {}", hex_key))
+            };
+            let filter = ExportFilter {
+                granularity,
+                start: start.unwrap_or(0),
+                end: end.unwrap_or(u64::MAX),
+                relay: relay.as_deref().map(parse_key).transpose()?,
+                pool: pool.as_deref().map(parse_key).transpose()?,
+            };
+
+            let history = expand_path(&history);
+            if !history.exists() {
+                anyhow::bail!("History file not found: {}", history.display());
+            }
+            let mut aggregator = Aggregator::new();
+            aggregator.rebuild_bandwidth_from_history(&history);
+
+            let out = expand_path(&out);
+            let file = std::fs::File::create(&out)
+                .with_context(|| format!("Failed to create {}", out.display()))?;
+            let rows = aggregator.bandwidth_index().export(file, format, &filter)?;
+            if output == OutputFormat::Json {
+                print_json("aggregator_export", &cli_types::AggregatorExportOutput {
+                    rows,
+                    path: out.display().to_string(),
+                    format: match format {
+                        ExportFormat::Csv => "csv",
+                        ExportFormat::Parquet => "parquet",
+                    }
+                    .to_string(),
+                    granularity: granularity.as_str().to_string(),
+                })?;
+            } else {
+                println!("Exported {} rows to {}", rows, out.display());
+            }
+        }
+        AggregatorAction::TestVectors { out } => {
+            let json = craftnet_aggregator::test_vectors::generate().to_json();
+            match out {
+                Some(out) => {
+                    let out = expand_path(&out);
+                    std::fs::write(&out, json)
+                        .with_context(|| format!("Failed to write {}", out.display()))?;
+                    println!("Wrote test vectors to {}", out.display());
+                }
+                None => print!("{}", json),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Settings file shared with the daemon (defaults if missing or unreadable)
+fn load_settings() -> CraftNetConfig {
+    craftec_settings::Settings::<CraftNetConfig>::load_or_default("craftnet", None)
+        .map(|s| s.config)
+        .unwrap_or_else(|e| {
+            tracing::warn!("Settings load failed ({}), using defaults", e);
+            CraftNetConfig::default()
+        })
+}
+
+async fn update_cmd(action: UpdateAction) -> Result<()> {
+    let (source, apply) = match action {
+        UpdateAction::Check { source } => (source, false),
+        UpdateAction::Apply { source } => (source, true),
+    };
+    let mut settings = load_settings().update;
+    if let Some(channel) = source.channel {
+        settings.channel = channel;
+    }
+    if source.url.is_some() {
+        settings.manifest_url = source.url;
+    }
+    if source.direct {
+        settings.via_tunnel = false;
+    }
+    let bootstrap_peers = parse_bootstrap_peers(&source.bootstrap)?;
+
+    let mut fetcher = update::Fetcher::connect(settings.via_tunnel, &bootstrap_peers).await;
+    let result = async {
+        match update::check(&settings, &mut fetcher).await? {
+            None => {
+                println!("Up to date ({} on {})", update::CURRENT_VERSION, settings.channel.as_str());
+                Ok(())
+            }
+            Some(manifest) => {
+                println!("Update available: {} -> {}", update::CURRENT_VERSION, manifest.version);
+                if let Some(notes) = &manifest.notes {
+                    println!("{}", notes);
+                }
+                if apply {
+                    let exe = update::install(&manifest, &mut fetcher).await?;
+                    println!("Installed {} at {}", manifest.version, exe.display());
+                    println!("Restart the service (craftnet service status) to run it");
+                }
+                Ok(())
+            }
+        }
+    }
+    .await;
+    fetcher.close().await;
+    result
+}
+
+fn service_cmd(action: ServiceAction) -> Result<()> {
+    match action {
+        ServiceAction::Install { profile, user, run_as, env, log_dir, dry_run, args } => {
+            let spec = ServiceSpec::new(profile, user, run_as, args, &env, log_dir)?;
+            service::install(&spec, dry_run)
+        }
+        ServiceAction::Uninstall { profile, user } => service::uninstall(profile, user),
+        ServiceAction::Status { profile, user, log_dir } => service::status(profile, user, log_dir),
+        ServiceAction::Run { profile, log_file, args } => {
+            service::run_windows_service(profile, log_file, args)
+        }
+    }
+}
+
 // ============================================================================
 // Daemon
 // ============================================================================
@@ -983,6 +1700,7 @@ async fn run_node(mode: NodeSubcommand) -> Result<()> {
             run_node_with_config(caps, &listen, &bootstrap, &keyfile, true, timeout).await
         }
         NodeSubcommand::Info { keyfile } => show_node_info(&keyfile),
+        NodeSubcommand::Dashboard { .. } => unreachable!("dashboard is dispatched in main"),
     }
 }
 
@@ -1020,6 +1738,7 @@ async fn run_node_with_config(
 
     // Parse bootstrap peers
     let bootstrap_peers = parse_bootstrap_peers(bootstrap)?;
+    let updates = update::watch(load_settings().update, bootstrap_peers.clone());
 
     // Derive data directory from keyfile location (sibling directory)
     let data_dir = expand_path(&keyfile.to_string_lossy())
@@ -1048,10 +1767,16 @@ async fn run_node_with_config(
         listen
     );
 
-    // Run the node event loop until Ctrl+C
+    // Run the node event loop until Ctrl+C, or until a self-update has
+    // replaced the binary
+    let mut restart = false;
     tokio::select! {
         _ = node.run() => {}
         _ = tokio::signal::ctrl_c() => {}
+        _ = updates => {
+            info!("Update installed, stopping for a restart");
+            restart = true;
+        }
     }
 
     // Print stats
@@ -1064,6 +1789,9 @@ async fn run_node_with_config(
     }
 
     node.stop().await;
+    if restart {
+        std::process::exit(update::RESTART_EXIT_CODE);
+    }
     Ok(())
 }
 
@@ -1113,6 +1841,14 @@ mod tests {
         assert!(matches.is_ok());
     }
 
+    #[test]
+    fn test_connect_full_device() {
+        use clap::CommandFactory;
+        let cmd = Cli::command();
+        let matches = cmd.try_get_matches_from(vec!["craftnet", "connect", "--full-device"]);
+        assert!(matches.is_ok());
+    }
+
     #[test]
     fn test_stats_command() {
         use clap::CommandFactory;
@@ -1121,6 +1857,14 @@ mod tests {
         assert!(matches.is_ok());
     }
 
+    #[test]
+    fn test_history_command() {
+        use clap::CommandFactory;
+        let cmd = Cli::command();
+        assert!(cmd.clone().try_get_matches_from(vec!["craftnet", "history", "--limit", "10"]).is_ok());
+        assert!(cmd.try_get_matches_from(vec!["craftnet", "history", "--clear"]).is_ok());
+    }
+
     #[test]
     fn test_mode_command() {
         use clap::CommandFactory;
@@ -1232,6 +1976,95 @@ mod tests {
         assert!(matches.is_ok());
     }
 
+    #[test]
+    fn test_dev_topology_dot() {
+        use clap::CommandFactory;
+        let cmd = Cli::command();
+        let matches = cmd.try_get_matches_from(vec!["craftnet", "dev", "topology", "--dot"]);
+        assert!(matches.is_ok());
+    }
+
+    #[test]
+    fn test_dev_peers() {
+        use clap::CommandFactory;
+        let cmd = Cli::command();
+        assert!(cmd.clone().try_get_matches_from(vec!["craftnet", "dev", "peers"]).is_ok());
+        assert!(cmd.try_get_matches_from(vec!["craftnet", "dev", "peers", "--protocols"]).is_ok());
+    }
+
+    #[test]
+    fn test_dev_dht() {
+        use clap::CommandFactory;
+        let cmd = Cli::command();
+        assert!(cmd.clone().try_get_matches_from(vec!["craftnet", "dev", "dht"]).is_ok());
+        assert!(cmd.clone().try_get_matches_from(vec!["craftnet", "dev", "dht", "bootstrap", "--table", "main"]).is_ok());
+        assert!(cmd.clone().try_get_matches_from(vec!["craftnet", "dev", "dht", "get-record", "/craftnet/exit-registry"]).is_ok());
+        assert!(cmd.try_get_matches_from(vec!["craftnet", "dev", "dht", "add-address"]).is_err());
+    }
+
+    #[test]
+    fn test_dev_trace() {
+        use clap::CommandFactory;
+        let cmd = Cli::command();
+        assert!(cmd.clone().try_get_matches_from(vec!["craftnet", "dev", "trace", "https://example.com", "--hop-mode", "triple"]).is_ok());
+        assert!(cmd.try_get_matches_from(vec!["craftnet", "dev", "trace"]).is_err());
+    }
+
+    #[test]
+    fn test_dev_attest() {
+        use clap::CommandFactory;
+        let cmd = Cli::command();
+        assert!(cmd.clone().try_get_matches_from(vec!["craftnet", "dev", "attest"]).is_ok());
+        assert!(cmd.try_get_matches_from(vec!["craftnet", "dev", "attest", "--local"]).is_ok());
+    }
+
+    #[test]
+    fn test_output_json_flag() {
+        use clap::CommandFactory;
+        let cmd = Cli::command();
+        assert!(cmd.clone().try_get_matches_from(vec!["craftnet", "--output", "json", "status"]).is_ok());
+        assert!(cmd.clone().try_get_matches_from(vec!["craftnet", "exits", "--output", "json"]).is_ok());
+        assert!(cmd.try_get_matches_from(vec!["craftnet", "stats", "--output", "yaml"]).is_err());
+    }
+
+    #[test]
+    fn test_shell() {
+        use clap::CommandFactory;
+        let cmd = Cli::command();
+        assert!(cmd.clone().try_get_matches_from(vec!["craftnet", "shell"]).is_ok());
+        assert!(cmd.try_get_matches_from(vec!["craftnet", "--output", "json", "shell"]).is_ok());
+    }
+
+    #[test]
+    fn test_node_dashboard() {
+        use clap::CommandFactory;
+        let cmd = Cli::command();
+        assert!(cmd.clone().try_get_matches_from(vec!["craftnet", "node", "dashboard"]).is_ok());
+        assert!(cmd.try_get_matches_from(vec!["craftnet", "node", "dashboard", "--refresh-ms", "500"]).is_ok());
+    }
+
+    #[test]
+    fn test_service_install_args() {
+        use clap::CommandFactory;
+        let cmd = Cli::command();
+        let matches = cmd.try_get_matches_from(vec![
+            "craftnet", "service", "install", "--profile", "relay", "--env", "RUST_LOG=info",
+            "--dry-run", "--", "--listen", "/ip4/0.0.0.0/tcp/9000",
+        ]);
+        assert!(matches.is_ok());
+    }
+
+    #[test]
+    fn test_update_check_channel() {
+        use clap::CommandFactory;
+        let cmd = Cli::command();
+        let matches = cmd.try_get_matches_from(vec!["craftnet", "update", "check", "--channel", "beta", "--direct"]);
+        assert!(matches.is_ok());
+        let cmd = Cli::command();
+        let matches = cmd.try_get_matches_from(vec!["craftnet", "update", "check", "--channel", "nightly"]);
+        assert!(matches.is_err());
+    }
+
     #[test]
     fn test_parse_bootstrap_peers() {
         let peers = vec![
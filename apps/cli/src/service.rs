@@ -0,0 +1,665 @@
+//! Platform service definitions (`craftnet service install|uninstall|status`)
+//!
+//! Generates and registers the service that keeps the daemon or a node
+//! profile running:
+//!
+//! - **Linux**: a systemd unit (`/etc/systemd/system`, or
+//!   `~/.config/systemd/user` with `--user`)
+//! - **macOS**: a launchd plist (`/Library/LaunchDaemons`, or
+//!   `~/Library/LaunchAgents` with `--user`)
+//! - **Windows**: a service registered with `sc.exe` that runs
+//!   `craftnet service run`, which supervises the profile as a child process
+//!
+//! Every definition restarts the process when it fails and appends
+//! stdout/stderr to `<log_dir>/craftnet-<profile>.log`.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+use anyhow::{Context, Result};
+
+use craftec_keystore::expand_path;
+
+/// Seconds to wait before restarting a failed service
+pub const RESTART_DELAY_SECS: u64 = 5;
+
+/// What the service runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ServiceProfile {
+    /// IPC daemon for the desktop app and CLI (`craftnet daemon`)
+    Daemon,
+    /// Relay node (`craftnet node relay`)
+    Relay,
+    /// Exit node (`craftnet node exit`)
+    Exit,
+    /// Relay + exit node (`craftnet node full`)
+    Full,
+}
+
+impl ServiceProfile {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Daemon => "daemon",
+            Self::Relay => "relay",
+            Self::Exit => "exit",
+            Self::Full => "full",
+        }
+    }
+
+    /// systemd unit / Windows service name
+    pub fn service_name(self) -> String {
+        format!("craftnet-{}", self.as_str())
+    }
+
+    /// launchd label
+    pub fn launchd_label(self) -> String {
+        format!("com.craftnet.{}", self.as_str())
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Self::Daemon => "CraftNet daemon",
+            Self::Relay => "CraftNet relay node",
+            Self::Exit => "CraftNet exit node",
+            Self::Full => "CraftNet node (relay + exit)",
+        }
+    }
+
+    /// `craftnet` arguments that run this profile
+    pub fn command_args(self, extra: &[String]) -> Vec<String> {
+        let mut args: Vec<String> = match self {
+            Self::Daemon => vec!["daemon".into()],
+            profile => vec!["node".into(), profile.as_str().into()],
+        };
+        args.extend(extra.iter().cloned());
+        args
+    }
+}
+
+/// Everything needed to write a service definition
+#[derive(Debug, Clone)]
+pub struct ServiceSpec {
+    pub profile: ServiceProfile,
+    /// Per-user service instead of a system-wide one
+    pub user: bool,
+    /// System services only: account to run as (None = root / LocalSystem)
+    pub run_as: Option<String>,
+    /// Binary to run (normally the current `craftnet` executable)
+    pub executable: PathBuf,
+    /// Extra arguments appended to the profile command
+    pub extra_args: Vec<String>,
+    /// `KEY=VALUE` environment for the service
+    pub env: Vec<(String, String)>,
+    pub log_dir: PathBuf,
+}
+
+impl ServiceSpec {
+    /// Spec for the running executable, with the platform's default log
+    /// directory unless `log_dir` is given
+    pub fn new(
+        profile: ServiceProfile,
+        user: bool,
+        run_as: Option<String>,
+        extra_args: Vec<String>,
+        env: &[String],
+        log_dir: Option<PathBuf>,
+    ) -> Result<Self> {
+        if user && run_as.is_some() {
+            anyhow::bail!("--run-as only applies to system services");
+        }
+        let env = env
+            .iter()
+            .map(|kv| {
+                kv.split_once('=')
+                    .filter(|(k, _)| !k.is_empty())
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .with_context(|| format!("Invalid --env '{}', expected KEY=VALUE", kv))
+            })
+            .collect::<Result<_>>()?;
+        let executable = std::env::current_exe().context("Cannot locate the craftnet executable")?;
+        Ok(Self {
+            profile,
+            user,
+            run_as,
+            executable,
+            extra_args,
+            env,
+            log_dir: log_dir.map(|d| expand_path(&d.to_string_lossy())).unwrap_or_else(|| default_log_dir(user)),
+        })
+    }
+
+    pub fn log_file(&self) -> PathBuf {
+        self.log_dir.join(format!("{}.log", self.profile.service_name()))
+    }
+
+    fn args(&self) -> Vec<String> {
+        self.profile.command_args(&self.extra_args)
+    }
+
+    /// systemd unit file contents
+    pub fn systemd_unit(&self) -> String {
+        let exec = std::iter::once(self.executable.to_string_lossy().into_owned())
+            .chain(self.args())
+            .map(|a| systemd_quote(&a))
+            .collect::<Vec<_>>()
+            .join(" ");
+        // append: takes the path verbatim (no quoting)
+        let log = self.log_file().to_string_lossy().replace('%', "%%");
+
+        let mut unit = String::new();
+        unit.push_str("[Unit]\n");
+        unit.push_str(&format!("Description={}\n", self.profile.description()));
+        unit.push_str("Wants=network-online.target\n");
+        unit.push_str("After=network-online.target\n");
+        unit.push_str("StartLimitIntervalSec=300\n");
+        unit.push_str("StartLimitBurst=10\n");
+        unit.push_str("\n[Service]\n");
+        unit.push_str("Type=simple\n");
+        unit.push_str(&format!("ExecStart={}\n", exec));
+        if let Some(user) = &self.run_as {
+            unit.push_str(&format!("User={}\n", user));
+        }
+        for (key, value) in &self.env {
+            unit.push_str(&format!("Environment={}\n", systemd_quote(&format!("{}={}", key, value))));
+        }
+        unit.push_str("Restart=on-failure\n");
+        unit.push_str(&format!("RestartSec={}\n", RESTART_DELAY_SECS));
+        // SIGTERM drains relays/exits; give in-flight shards time to finish
+        unit.push_str("KillSignal=SIGTERM\n");
+        unit.push_str("TimeoutStopSec=60\n");
+        unit.push_str(&format!("StandardOutput=append:{}\n", log));
+        unit.push_str(&format!("StandardError=append:{}\n", log));
+        unit.push_str("\n[Install]\n");
+        unit.push_str(if self.user { "WantedBy=default.target\n" } else { "WantedBy=multi-user.target\n" });
+        unit
+    }
+
+    /// launchd plist contents
+    pub fn launchd_plist(&self) -> String {
+        let args: String = std::iter::once(self.executable.to_string_lossy().into_owned())
+            .chain(self.args())
+            .map(|a| format!("        <string>{}</string>\n", xml_escape(&a)))
+            .collect();
+        let log = xml_escape(&self.log_file().to_string_lossy());
+
+        let mut plist = String::new();
+        plist.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        plist.push_str("<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n");
+        plist.push_str("<plist version=\"1.0\">\n<dict>\n");
+        plist.push_str(&format!("    <key>Label</key>\n    <string>{}</string>\n", self.profile.launchd_label()));
+        plist.push_str(&format!("    <key>ProgramArguments</key>\n    <array>\n{}    </array>\n", args));
+        if let Some(user) = &self.run_as {
+            plist.push_str(&format!("    <key>UserName</key>\n    <string>{}</string>\n", xml_escape(user)));
+        }
+        if !self.env.is_empty() {
+            plist.push_str("    <key>EnvironmentVariables</key>\n    <dict>\n");
+            for (key, value) in &self.env {
+                plist.push_str(&format!(
+                    "        <key>{}</key>\n        <string>{}</string>\n",
+                    xml_escape(key),
+                    xml_escape(value)
+                ));
+            }
+            plist.push_str("    </dict>\n");
+        }
+        plist.push_str("    <key>RunAtLoad</key>\n    <true/>\n");
+        // Restart after crashes, not after a clean stop
+        plist.push_str("    <key>KeepAlive</key>\n    <dict>\n        <key>SuccessfulExit</key>\n        <false/>\n    </dict>\n");
+        plist.push_str(&format!("    <key>ThrottleInterval</key>\n    <integer>{}</integer>\n", RESTART_DELAY_SECS));
+        plist.push_str("    <key>ExitTimeOut</key>\n    <integer>60</integer>\n");
+        plist.push_str(&format!("    <key>StandardOutPath</key>\n    <string>{}</string>\n", log));
+        plist.push_str(&format!("    <key>StandardErrorPath</key>\n    <string>{}</string>\n", log));
+        plist.push_str("</dict>\n</plist>\n");
+        plist
+    }
+
+    /// `sc.exe` invocations that register the Windows service
+    pub fn windows_commands(&self) -> Vec<Vec<String>> {
+        let name = self.profile.service_name();
+        let mut bin_path = format!(
+            "{} service run --profile {} --log-file {}",
+            windows_quote(&self.executable.to_string_lossy()),
+            self.profile.as_str(),
+            windows_quote(&self.log_file().to_string_lossy()),
+        );
+        if !self.extra_args.is_empty() {
+            bin_path.push_str(" --");
+            for arg in &self.extra_args {
+                bin_path.push(' ');
+                bin_path.push_str(&windows_quote(arg));
+            }
+        }
+        let mut create = vec![
+            "create".to_string(),
+            name.clone(),
+            "binPath=".into(),
+            bin_path,
+            "start=".into(),
+            "auto".into(),
+            "DisplayName=".into(),
+            self.profile.description().into(),
+        ];
+        if let Some(user) = &self.run_as {
+            create.extend(["obj=".into(), user.clone()]);
+        }
+        let delay_ms = RESTART_DELAY_SECS * 1000;
+        vec![
+            create,
+            vec!["description".into(), name.clone(), format!("{} (logs: {})", self.profile.description(), self.log_file().display())],
+            vec![
+                "failure".into(),
+                name.clone(),
+                "reset=".into(),
+                "86400".into(),
+                "actions=".into(),
+                format!("restart/{}/restart/{}/restart/{}", delay_ms, delay_ms, delay_ms * 12),
+            ],
+            // Apply the failure actions when the service stops with an error code too
+            vec!["failureflag".into(), name, "1".into()],
+        ]
+    }
+
+    /// Where the definition file is written (None on Windows: the service
+    /// lives in the registry)
+    pub fn definition_path(&self) -> Option<PathBuf> {
+        definition_path(self.profile, self.user)
+    }
+}
+
+/// Default log directory: `~/.craftnet/logs` for user services, otherwise
+/// the platform's system log location
+pub fn default_log_dir(user: bool) -> PathBuf {
+    if user {
+        expand_path("~/.craftnet/logs")
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from("/Library/Logs/CraftNet")
+    } else if cfg!(windows) {
+        let program_data = std::env::var("ProgramData").unwrap_or_else(|_| "C:\\ProgramData".into());
+        PathBuf::from(program_data).join("CraftNet").join("logs")
+    } else {
+        PathBuf::from("/var/log/craftnet")
+    }
+}
+
+fn definition_path(profile: ServiceProfile, user: bool) -> Option<PathBuf> {
+    if cfg!(target_os = "macos") {
+        let file = format!("{}.plist", profile.launchd_label());
+        Some(if user {
+            expand_path("~/Library/LaunchAgents").join(file)
+        } else {
+            PathBuf::from("/Library/LaunchDaemons").join(file)
+        })
+    } else if cfg!(windows) {
+        None
+    } else {
+        let file = format!("{}.service", profile.service_name());
+        Some(if user {
+            expand_path("~/.config/systemd/user").join(file)
+        } else {
+            PathBuf::from("/etc/systemd/system").join(file)
+        })
+    }
+}
+
+/// Quote a systemd command-line word (and escape `%` specifiers)
+fn systemd_quote(word: &str) -> String {
+    let escaped = word.replace('%', "%%");
+    if !escaped.is_empty() && !escaped.contains(|c: char| c.is_whitespace() || c == '"' || c == '\\' || c == '\'') {
+        return escaped;
+    }
+    format!("\"{}\"", escaped.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Quote a Windows command-line argument
+fn windows_quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains(|c: char| c.is_whitespace() || c == '"') {
+        return arg.to_string();
+    }
+    format!("\"{}\"", arg.replace('"', "\\\""))
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Run a service manager command, failing with its stderr
+fn run(program: &str, args: &[String]) -> Result<()> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .with_context(|| format!("Failed to run {}", program))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{} {} failed: {}",
+            program,
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+fn run_args(program: &str, args: &[&str]) -> Result<()> {
+    run(program, &args.iter().map(|a| a.to_string()).collect::<Vec<_>>())
+}
+
+fn systemctl(user: bool, args: &[&str]) -> Vec<String> {
+    let mut all: Vec<String> = Vec::new();
+    if user {
+        all.push("--user".into());
+    }
+    all.extend(args.iter().map(|a| a.to_string()));
+    all
+}
+
+#[cfg(unix)]
+fn launchd_domain(user: bool) -> String {
+    if user {
+        format!("gui/{}", craftnet_daemon::current_uid())
+    } else {
+        "system".to_string()
+    }
+}
+
+/// Write, register and start the service. With `dry_run` only print the
+/// definition.
+pub fn install(spec: &ServiceSpec, dry_run: bool) -> Result<()> {
+    let definition = if cfg!(target_os = "macos") {
+        spec.launchd_plist()
+    } else if cfg!(windows) {
+        spec.windows_commands()
+            .iter()
+            .map(|args| format!("sc.exe {}\n", args.iter().map(|a| windows_quote(a)).collect::<Vec<_>>().join(" ")))
+            .collect()
+    } else {
+        spec.systemd_unit()
+    };
+    if dry_run {
+        print!("{}", definition);
+        return Ok(());
+    }
+
+    std::fs::create_dir_all(&spec.log_dir)
+        .with_context(|| format!("Failed to create log directory {}", spec.log_dir.display()))?;
+    if let Some(path) = spec.definition_path() {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, &definition)
+            .with_context(|| format!("Failed to write {} (system services need root)", path.display()))?;
+        println!("Wrote {}", path.display());
+    }
+
+    let name = spec.profile.service_name();
+    if cfg!(target_os = "macos") {
+        #[cfg(unix)]
+        {
+            let path = spec.definition_path().unwrap_or_default();
+            run_args("launchctl", &["bootstrap", &launchd_domain(spec.user), &path.to_string_lossy()])?;
+        }
+    } else if cfg!(windows) {
+        for args in spec.windows_commands() {
+            run("sc.exe", &args)?;
+        }
+        run_args("sc.exe", &["start", &name])?;
+    } else {
+        run("systemctl", &systemctl(spec.user, &["daemon-reload"]))?;
+        run("systemctl", &systemctl(spec.user, &["enable", "--now", &name]))?;
+    }
+
+    println!("Installed and started {}", name);
+    println!("Logs: {}", spec.log_file().display());
+    Ok(())
+}
+
+/// Stop, unregister and remove the service definition
+pub fn uninstall(profile: ServiceProfile, user: bool) -> Result<()> {
+    let name = profile.service_name();
+    if cfg!(target_os = "macos") {
+        #[cfg(unix)]
+        {
+            let target = format!("{}/{}", launchd_domain(user), profile.launchd_label());
+            if let Err(e) = run_args("launchctl", &["bootout", &target]) {
+                tracing::warn!("{}", e);
+            }
+        }
+    } else if cfg!(windows) {
+        // Not running is fine; a missing service is reported by delete
+        let _ = run_args("sc.exe", &["stop", &name]);
+        run_args("sc.exe", &["delete", &name])?;
+    } else if let Err(e) = run("systemctl", &systemctl(user, &["disable", "--now", &name])) {
+        tracing::warn!("{}", e);
+    }
+
+    if let Some(path) = definition_path(profile, user) {
+        match std::fs::remove_file(&path) {
+            Ok(()) => println!("Removed {}", path.display()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                anyhow::bail!("{} is not installed ({} not found)", name, path.display());
+            }
+            Err(e) => return Err(e).with_context(|| format!("Failed to remove {}", path.display())),
+        }
+        if !cfg!(target_os = "macos") {
+            run("systemctl", &systemctl(user, &["daemon-reload"]))?;
+        }
+    }
+
+    println!("Uninstalled {}", name);
+    Ok(())
+}
+
+/// Print where the service is defined, where it logs and what the service
+/// manager reports
+pub fn status(profile: ServiceProfile, user: bool, log_dir: Option<PathBuf>) -> Result<()> {
+    let name = profile.service_name();
+    println!("Service: {}", name);
+    if let Some(path) = definition_path(profile, user) {
+        let state = if path.exists() { "" } else { " (not installed)" };
+        println!("Definition: {}{}", path.display(), state);
+    }
+    let log_dir = log_dir.map(|d| expand_path(&d.to_string_lossy())).unwrap_or_else(|| default_log_dir(user));
+    println!("Logs: {}", log_dir.join(format!("{}.log", name)).display());
+    println!();
+
+    // The manager's own report; non-zero exit just means "not running"
+    let mut command = if cfg!(target_os = "macos") {
+        let mut c = Command::new("launchctl");
+        #[cfg(unix)]
+        c.args(["print", &format!("{}/{}", launchd_domain(user), profile.launchd_label())]);
+        c
+    } else if cfg!(windows) {
+        let mut c = Command::new("sc.exe");
+        c.args(["query", &name]);
+        c
+    } else {
+        let mut c = Command::new("systemctl");
+        c.args(systemctl(user, &["status", "--no-pager", &name]));
+        c
+    };
+    command.status().context("Failed to query the service manager")?;
+    Ok(())
+}
+
+/// Windows service entry point (`craftnet service run`): reports to the
+/// service control manager and supervises the profile as a child process,
+/// appending its output to `log_file`. Stopping the service terminates the
+/// child.
+#[cfg(windows)]
+pub fn run_windows_service(profile: ServiceProfile, log_file: PathBuf, extra_args: Vec<String>) -> Result<()> {
+    windows::run(profile, log_file, extra_args)
+}
+
+#[cfg(not(windows))]
+pub fn run_windows_service(_profile: ServiceProfile, _log_file: PathBuf, _extra_args: Vec<String>) -> Result<()> {
+    anyhow::bail!("`craftnet service run` is only used by Windows services")
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::ffi::OsString;
+    use std::path::PathBuf;
+    use std::process::{Command, Stdio};
+    use std::sync::{mpsc, OnceLock};
+    use std::time::Duration;
+
+    use anyhow::{Context, Result};
+    use windows_service::service::{
+        ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus, ServiceType,
+    };
+    use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
+    use windows_service::{define_windows_service, service_dispatcher};
+
+    use super::ServiceProfile;
+
+    struct Job {
+        profile: ServiceProfile,
+        log_file: PathBuf,
+        extra_args: Vec<String>,
+    }
+
+    static JOB: OnceLock<Job> = OnceLock::new();
+
+    define_windows_service!(ffi_service_main, service_main);
+
+    pub fn run(profile: ServiceProfile, log_file: PathBuf, extra_args: Vec<String>) -> Result<()> {
+        let _ = JOB.set(Job { profile, log_file, extra_args });
+        service_dispatcher::start(profile.service_name(), ffi_service_main)
+            .context("Not started by the service control manager")?;
+        Ok(())
+    }
+
+    fn service_main(_arguments: Vec<OsString>) {
+        if let Err(e) = supervise() {
+            tracing::error!("Service failed: {:#}", e);
+        }
+    }
+
+    fn status(state: ServiceState, exit_code: u32) -> ServiceStatus {
+        ServiceStatus {
+            service_type: ServiceType::OWN_PROCESS,
+            current_state: state,
+            controls_accepted: if state == ServiceState::Running {
+                ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN
+            } else {
+                ServiceControlAccept::empty()
+            },
+            exit_code: ServiceExitCode::Win32(exit_code),
+            checkpoint: 0,
+            wait_hint: Duration::default(),
+            process_id: None,
+        }
+    }
+
+    fn supervise() -> Result<()> {
+        let job = JOB.get().context("Service job not set")?;
+        let (stop_tx, stop_rx) = mpsc::channel();
+        let handle = service_control_handler::register(job.profile.service_name(), move |control| match control {
+            ServiceControl::Stop | ServiceControl::Shutdown => {
+                let _ = stop_tx.send(());
+                ServiceControlHandlerResult::NoError
+            }
+            ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+            _ => ServiceControlHandlerResult::NotImplemented,
+        })?;
+
+        let log = std::fs::OpenOptions::new().create(true).append(true).open(&job.log_file)?;
+        let mut child = Command::new(std::env::current_exe()?)
+            .args(job.profile.command_args(&job.extra_args))
+            .stdin(Stdio::null())
+            .stdout(log.try_clone()?)
+            .stderr(log)
+            .spawn()?;
+        handle.set_service_status(status(ServiceState::Running, 0))?;
+
+        // Exit code 1 makes the SCM apply the failure (restart) actions
+        let exit_code = loop {
+            if stop_rx.recv_timeout(Duration::from_secs(1)).is_ok() {
+                let _ = child.kill();
+                let _ = child.wait();
+                break 0;
+            }
+            if let Some(exit) = child.try_wait()? {
+                break if exit.success() { 0 } else { 1 };
+            }
+        };
+        handle.set_service_status(status(ServiceState::Stopped, exit_code))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_spec(profile: ServiceProfile, user: bool) -> ServiceSpec {
+        ServiceSpec {
+            profile,
+            user,
+            run_as: None,
+            executable: PathBuf::from("/usr/local/bin/craftnet"),
+            extra_args: Vec::new(),
+            env: Vec::new(),
+            log_dir: PathBuf::from("/var/log/craftnet"),
+        }
+    }
+
+    #[test]
+    fn test_systemd_unit() {
+        let mut spec = test_spec(ServiceProfile::Relay, false);
+        spec.extra_args = vec!["--listen".into(), "/ip4/0.0.0.0/tcp/9000".into()];
+        spec.run_as = Some("craftnet".into());
+        spec.env = vec![("CRAFTNET_HEALTH_ADDR".into(), "127.0.0.1:9100".into())];
+        let unit = spec.systemd_unit();
+
+        assert!(unit.contains("ExecStart=/usr/local/bin/craftnet node relay --listen /ip4/0.0.0.0/tcp/9000\n"));
+        assert!(unit.contains("User=craftnet\n"));
+        assert!(unit.contains("Environment=CRAFTNET_HEALTH_ADDR=127.0.0.1:9100\n"));
+        assert!(unit.contains("Restart=on-failure\n"));
+        assert!(unit.contains("StandardOutput=append:/var/log/craftnet/craftnet-relay.log\n"));
+        assert!(unit.contains("WantedBy=multi-user.target\n"));
+
+        let user_unit = test_spec(ServiceProfile::Daemon, true).systemd_unit();
+        assert!(user_unit.contains("ExecStart=/usr/local/bin/craftnet daemon\n"));
+        assert!(user_unit.contains("WantedBy=default.target\n"));
+    }
+
+    #[test]
+    fn test_launchd_plist() {
+        let mut spec = test_spec(ServiceProfile::Daemon, true);
+        spec.env = vec![("A".into(), "x<y".into())];
+        let plist = spec.launchd_plist();
+
+        assert!(plist.contains("<string>com.craftnet.daemon</string>"));
+        assert!(plist.contains("<string>/usr/local/bin/craftnet</string>\n        <string>daemon</string>"));
+        assert!(plist.contains("<key>SuccessfulExit</key>\n        <false/>"));
+        assert!(plist.contains("<string>x&lt;y</string>"));
+        assert!(plist.contains("<key>StandardErrorPath</key>\n    <string>/var/log/craftnet/craftnet-daemon.log</string>"));
+    }
+
+    #[test]
+    fn test_windows_commands() {
+        let mut spec = test_spec(ServiceProfile::Exit, false);
+        spec.executable = PathBuf::from("C:\\Program Files\\CraftNet\\craftnet.exe");
+        spec.extra_args = vec!["--timeout".into(), "60".into()];
+        let commands = spec.windows_commands();
+
+        assert_eq!(commands[0][0], "create");
+        assert_eq!(commands[0][1], "craftnet-exit");
+        assert!(commands[0][3].starts_with("\"C:\\Program Files\\CraftNet\\craftnet.exe\" service run --profile exit"));
+        assert!(commands[0][3].ends_with(" -- --timeout 60"));
+        assert_eq!(commands[2][5], "restart/5000/restart/5000/restart/60000");
+    }
+
+    #[test]
+    fn test_quoting() {
+        assert_eq!(systemd_quote("plain"), "plain");
+        assert_eq!(systemd_quote("with space"), "\"with space\"");
+        assert_eq!(systemd_quote("100%"), "100%%");
+        assert_eq!(windows_quote("a b"), "\"a b\"");
+        assert_eq!(xml_escape("a&b"), "a&amp;b");
+    }
+}
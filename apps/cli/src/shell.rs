@@ -0,0 +1,175 @@
+//! Interactive shell (`craftnet shell`)
+//!
+//! Reads CLI subcommands line by line (`status`, `dev peers`, ...) and runs
+//! them over one persistent daemon connection instead of reconnecting per
+//! command. Lines are split like a POSIX shell, so quoted arguments work.
+//! History is kept in `~/.craftnet/shell_history`; Tab completes
+//! subcommand names and long flags from the clap definitions.
+
+use std::path::Path;
+
+use anyhow::Result;
+use clap::{CommandFactory, FromArgMatches};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::{Context, Editor, Helper, Highlighter, Hinter, Validator};
+
+use craftec_keystore::expand_path;
+use craftnet_ipc_client::IpcClient;
+
+use crate::cli_types::OutputFormat;
+use crate::{run_command, Cli, Commands, NodeSubcommand};
+
+const HISTORY_FILE: &str = "~/.craftnet/shell_history";
+
+/// Words the shell handles itself
+const BUILTINS: [&str; 3] = ["help", "exit", "quit"];
+
+#[derive(Helper, Hinter, Highlighter, Validator)]
+struct ShellHelper {
+    command: clap::Command,
+}
+
+impl Completer for ShellHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(complete(&self.command, &line[..pos]))
+    }
+}
+
+/// Completions for the last word of `line`: subcommands of the command the
+/// earlier words select, or its long flags when the word starts with `-`.
+/// Returns where the completed word starts.
+fn complete(root: &clap::Command, line: &str) -> (usize, Vec<String>) {
+    let start = line.rfind(char::is_whitespace).map(|i| i + 1).unwrap_or(0);
+    let partial = &line[start..];
+
+    let mut command = root;
+    for word in line[..start].split_whitespace() {
+        if let Some(sub) = command.find_subcommand(word) {
+            command = sub;
+        }
+    }
+
+    let mut candidates: Vec<String> = if partial.starts_with('-') {
+        command
+            .get_arguments()
+            .filter_map(|a| a.get_long())
+            .map(|long| format!("--{}", long))
+            .collect()
+    } else {
+        let mut names: Vec<String> = command
+            .get_subcommands()
+            .map(|s| s.get_name().to_string())
+            .filter(|name| name != "help")
+            .collect();
+        if std::ptr::eq(command, root) {
+            names.extend(BUILTINS.iter().map(|b| b.to_string()));
+        }
+        names
+    };
+    candidates.retain(|c| c.starts_with(partial));
+    candidates.sort();
+    (start, candidates)
+}
+
+/// Commands that run a node in the foreground and would never return
+fn runs_node(command: &Commands) -> bool {
+    matches!(
+        command,
+        Commands::Daemon { .. }
+            | Commands::Run { .. }
+            | Commands::Shell
+            | Commands::Node { mode: NodeSubcommand::Relay { .. } | NodeSubcommand::Exit { .. } | NodeSubcommand::Full { .. } }
+    )
+}
+
+/// Run the shell until `exit`, `quit` or Ctrl-D
+pub async fn run(socket: &Path, output: OutputFormat) -> Result<()> {
+    let client = IpcClient::persistent(socket).await?;
+
+    let mut editor: Editor<ShellHelper, DefaultHistory> = Editor::new()?;
+    editor.set_helper(Some(ShellHelper { command: Cli::command() }));
+    let history = expand_path(HISTORY_FILE);
+    let _ = editor.load_history(&history);
+
+    println!("Connected to {}. Tab completes commands; `help` lists them, `exit` quits.", socket.display());
+    loop {
+        let line = match tokio::task::block_in_place(|| editor.readline("craftnet> ")) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let _ = editor.add_history_entry(line);
+
+        match line {
+            "exit" | "quit" => break,
+            "help" => {
+                Cli::command().print_help()?;
+                continue;
+            }
+            _ => {}
+        }
+
+        let Some(words) = shlex::split(line) else {
+            eprintln!("Unbalanced quotes");
+            continue;
+        };
+        // The shell's --output is the default; a --output on the line wins
+        let output_flag = match output {
+            OutputFormat::Text => "text",
+            OutputFormat::Json => "json",
+        };
+        let args = ["craftnet", "--output", output_flag].into_iter().map(String::from).chain(words);
+        let command = match Cli::command().try_get_matches_from(args).and_then(|m| Cli::from_arg_matches(&m)) {
+            Ok(cli) => cli,
+            Err(e) => {
+                let _ = e.print();
+                continue;
+            }
+        };
+        if runs_node(&command.command) {
+            eprintln!("That command runs in the foreground; start it outside the shell");
+            continue;
+        }
+        if let Err(e) = run_command(command.command, &client, command.output).await {
+            eprintln!("Error: {:#}", e);
+        }
+    }
+
+    if let Some(parent) = history.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    let _ = editor.save_history(&history);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_complete() {
+        let cmd = Cli::command();
+        let (start, candidates) = complete(&cmd, "sta");
+        assert_eq!(start, 0);
+        assert_eq!(candidates, vec!["stats", "status"]);
+
+        let (start, candidates) = complete(&cmd, "dev pe");
+        assert_eq!(start, 4);
+        assert_eq!(candidates, vec!["peers"]);
+
+        let (_, candidates) = complete(&cmd, "node dashboard --ref");
+        assert_eq!(candidates, vec!["--refresh-ms"]);
+
+        assert!(complete(&cmd, "").1.contains(&"exit".to_string()));
+        assert!(!complete(&cmd, "dev ").1.contains(&"exit".to_string()));
+    }
+}
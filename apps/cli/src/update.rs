@@ -0,0 +1,211 @@
+//! Node binary self-update (`craftnet update`, and `update.enabled` in
+//! settings for running nodes)
+//!
+//! The channel's signed manifest is fetched through the tunnel (a short-lived
+//! client node on the operator's bootstrap peers) when `update.via_tunnel`
+//! is set, falling back to a direct download. The manifest must verify
+//! against the embedded release keys; the binary must match its SHA-256.
+//! The new binary then replaces the running one atomically and the node
+//! exits with [`RESTART_EXIT_CODE`] so its service manager (see
+//! `craftnet service`) restarts it into the new version.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use libp2p::{Multiaddr, PeerId};
+use tracing::{info, warn};
+
+use craftnet_client::{Capabilities, CraftNetNode, NodeConfig};
+use craftnet_core::config::UpdateSettings;
+use craftnet_core::{current_target, release_keys, verify_artifact, ReleaseManifest, SignedManifest};
+
+/// Exit code after installing an update; non-zero so systemd
+/// (`Restart=on-failure`), launchd (`KeepAlive`) and the Windows service
+/// wrapper all restart the process
+pub const RESTART_EXIT_CODE: i32 = 75;
+
+/// Version of the running binary
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Biggest manifest or binary accepted from a direct download
+const MAX_DOWNLOAD_BYTES: usize = 256 * 1024 * 1024;
+
+/// Where manifests and binaries come from
+pub enum Fetcher {
+    /// Through a client node on the network
+    Tunnel(Box<CraftNetNode>),
+    /// Plain HTTPS
+    Direct(reqwest::Client),
+}
+
+impl Fetcher {
+    /// Tunnel fetcher when asked for and reachable, otherwise direct
+    pub async fn connect(via_tunnel: bool, bootstrap_peers: &[(PeerId, Multiaddr)]) -> Self {
+        if via_tunnel && !bootstrap_peers.is_empty() {
+            match tunnel_node(bootstrap_peers).await {
+                Ok(node) => return Self::Tunnel(Box::new(node)),
+                Err(e) => warn!("Update check cannot use the tunnel ({}), downloading directly", e),
+            }
+        }
+        Self::Direct(reqwest::Client::new())
+    }
+
+    pub async fn get(&mut self, url: &str) -> Result<Vec<u8>> {
+        match self {
+            Self::Tunnel(node) => {
+                let response = node.get(url).await?;
+                if response.status != 200 {
+                    anyhow::bail!("{} returned HTTP {}", url, response.status);
+                }
+                Ok(response.body)
+            }
+            Self::Direct(client) => {
+                let response = client.get(url).send().await?.error_for_status()?;
+                if response.content_length().is_some_and(|len| len as usize > MAX_DOWNLOAD_BYTES) {
+                    anyhow::bail!("{} is larger than {} bytes", url, MAX_DOWNLOAD_BYTES);
+                }
+                Ok(response.bytes().await?.to_vec())
+            }
+        }
+    }
+
+    pub async fn close(self) {
+        if let Self::Tunnel(mut node) = self {
+            node.stop().await;
+        }
+    }
+}
+
+async fn tunnel_node(bootstrap_peers: &[(PeerId, Multiaddr)]) -> Result<CraftNetNode> {
+    let config = NodeConfig {
+        capabilities: Capabilities::CLIENT,
+        bootstrap_peers: bootstrap_peers.to_vec(),
+        ..Default::default()
+    };
+    let mut node = CraftNetNode::new(config)?;
+    node.start(None).await?;
+    if let Err(e) = node.wait_for_exit(Duration::from_secs(15)).await {
+        node.stop().await;
+        return Err(e.into());
+    }
+    Ok(node)
+}
+
+/// Fetch and verify the channel's manifest; Some if it is newer than this
+/// binary
+pub async fn check(settings: &UpdateSettings, fetcher: &mut Fetcher) -> Result<Option<ReleaseManifest>> {
+    let url = settings
+        .manifest_url()
+        .context("No update.manifest_url configured")?;
+    let keys = release_keys();
+    if keys.is_empty() {
+        anyhow::bail!("This build has no release keys (CRAFTNET_RELEASE_KEYS), cannot verify updates");
+    }
+
+    let bytes = fetcher.get(&url).await?;
+    let signed: SignedManifest = serde_json::from_slice(&bytes).context("Malformed manifest")?;
+    let manifest = signed.verify(&keys, settings.channel)?;
+    Ok(manifest.is_newer_than(CURRENT_VERSION).then_some(manifest))
+}
+
+/// Download the release for this platform and swap it in for the running
+/// binary. Returns the path that was replaced.
+pub async fn install(manifest: &ReleaseManifest, fetcher: &mut Fetcher) -> Result<PathBuf> {
+    let target = current_target();
+    let artifact = manifest
+        .artifact(&target)
+        .with_context(|| format!("Release {} has no build for {}", manifest.version, target))?;
+    let bytes = fetcher.get(&artifact.url).await?;
+    verify_artifact(artifact, &bytes)?;
+
+    let exe = std::env::current_exe().context("Cannot locate the running binary")?;
+    replace_binary(&exe, &bytes).with_context(|| format!("Failed to replace {}", exe.display()))?;
+    info!("Installed CraftNet {} at {}", manifest.version, exe.display());
+    Ok(exe)
+}
+
+/// Atomically replace `exe` with `bytes`: write a sibling file, then rename
+/// it over the original. Windows cannot overwrite a running executable, so
+/// the old one is first moved aside to `<exe>.old`.
+pub fn replace_binary(exe: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let staged = exe.with_extension("update");
+    {
+        let mut file = std::fs::File::create(&staged)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(exe).map(|m| m.permissions().mode()).unwrap_or(0o755);
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(mode))?;
+    }
+    #[cfg(windows)]
+    {
+        let old = exe.with_extension("old");
+        let _ = std::fs::remove_file(&old);
+        std::fs::rename(exe, &old)?;
+    }
+    std::fs::rename(&staged, exe)
+}
+
+/// Check, download and install once. True if a new binary is in place.
+pub async fn update_once(settings: &UpdateSettings, bootstrap_peers: &[(PeerId, Multiaddr)]) -> Result<bool> {
+    let mut fetcher = Fetcher::connect(settings.via_tunnel, bootstrap_peers).await;
+    let result = async {
+        match check(settings, &mut fetcher).await? {
+            Some(manifest) => {
+                info!("CraftNet {} available on {} (running {})", manifest.version, settings.channel.as_str(), CURRENT_VERSION);
+                install(&manifest, &mut fetcher).await.map(|_| true)
+            }
+            None => Ok(false),
+        }
+    }
+    .await;
+    fetcher.close().await;
+    result
+}
+
+/// Background checker for a running node: resolves once an update has been
+/// installed, so the caller can stop the node and exit for a restart.
+/// Never resolves when updates are disabled or cannot be verified.
+pub async fn watch(settings: UpdateSettings, bootstrap_peers: Vec<(PeerId, Multiaddr)>) {
+    if !settings.enabled {
+        return std::future::pending().await;
+    }
+    if settings.manifest_url().is_none() || release_keys().is_empty() {
+        warn!("Auto-update enabled but no manifest URL or release keys, not checking");
+        return std::future::pending().await;
+    }
+    let mut interval = tokio::time::interval(Duration::from_secs(settings.check_interval_secs.max(60)));
+    loop {
+        interval.tick().await;
+        match update_once(&settings, &bootstrap_peers).await {
+            Ok(true) => return,
+            Ok(false) => {}
+            Err(e) => warn!("Update check failed: {:#}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replace_binary() {
+        let dir = std::env::temp_dir().join(format!("craftnet-update-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let exe = dir.join("craftnet");
+        std::fs::write(&exe, b"old").unwrap();
+
+        replace_binary(&exe, b"new").unwrap();
+        assert_eq!(std::fs::read(&exe).unwrap(), b"new");
+        assert!(!exe.with_extension("update").exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
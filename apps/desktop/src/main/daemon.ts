@@ -62,9 +62,9 @@ export class DaemonManager {
         });
 
         // Poll for socket existence + connectivity instead of fixed timeout
-        const socketPath = process.platform === 'darwin'
-          ? '/tmp/craftnet.sock'
-          : (process.env.XDG_RUNTIME_DIR || '/tmp') + '/craftnet.sock';
+        const socketPath = process.platform === 'linux' && process.env.XDG_RUNTIME_DIR
+          ? process.env.XDG_RUNTIME_DIR + '/craftnet.sock'
+          : `/tmp/craftnet-${process.getuid?.() ?? 0}.sock`;
         const maxAttempts = 50; // 50 * 100ms = 5 seconds max
         let attempts = 0;
 
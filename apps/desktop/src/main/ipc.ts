@@ -29,10 +29,11 @@ export class IPCClient extends EventEmitter {
     if (process.platform === 'win32') {
       return '\\\\.\\pipe\\craftnet';
     }
-    if (process.platform === 'darwin') {
-      return '/tmp/craftnet.sock';
+    // Per-user socket, matching the daemon's default
+    if (process.platform === 'linux' && process.env.XDG_RUNTIME_DIR) {
+      return process.env.XDG_RUNTIME_DIR + '/craftnet.sock';
     }
-    return (process.env.XDG_RUNTIME_DIR || '/tmp') + '/craftnet.sock';
+    return `/tmp/craftnet-${process.getuid?.() ?? 0}.sock`;
   }
 
   async connect(): Promise<void> {
@@ -0,0 +1,273 @@
+//! Analytical queries over the bandwidth index
+//!
+//! Rankings, percentiles, growth and churn computed from the same buckets
+//! the time-series queries use, so dashboards get the
+//! numbers without re-implementing the math over raw buckets.
+//!
+//! Windows are half-open `[start, end)` in unix seconds. A bucket counts
+//! toward a window when its start falls inside it, so downsampled history
+//! only lines up with windows on its bucket boundaries.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use craftnet_core::PublicKey;
+
+use crate::{BandwidthIndex, BandwidthTimeSeries};
+
+/// One week in seconds, the period of growth and churn queries
+pub const WEEK_SECS: u64 = 7 * 24 * 3600;
+
+/// A relay and the bytes it carried in a window
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayRank {
+    #[serde(with = "hex_key")]
+    pub relay: PublicKey,
+    pub bytes: u64,
+}
+
+/// Hourly throughput distribution of one pool
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolThroughput {
+    #[serde(with = "hex_key")]
+    pub pool: PublicKey,
+    /// Hours with traffic in the window (idle hours are not counted)
+    pub hours: usize,
+    /// Median bytes per active hour
+    pub p50: u64,
+    /// 95th percentile bytes per active hour
+    pub p95: u64,
+}
+
+/// Bytes of the last week against the week before
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WeeklyGrowth {
+    pub this_week_bytes: u64,
+    pub last_week_bytes: u64,
+    /// `this / last - 1` (None when last week had no traffic)
+    pub rate: Option<f64>,
+}
+
+/// Relays active in the week before but not in the last week, and vice versa
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayChurn {
+    pub active_this_week: usize,
+    pub active_last_week: usize,
+    /// Active last week, silent this week
+    #[serde(with = "hex_keys")]
+    pub churned: Vec<PublicKey>,
+    /// Active this week, silent last week
+    #[serde(with = "hex_keys")]
+    pub joined: Vec<PublicKey>,
+}
+
+/// Sum of the bytes of the buckets (of any granularity) starting in `[start, end)`
+fn window_bytes(series: &BandwidthTimeSeries, start: u64, end: u64) -> u64 {
+    if start >= end {
+        return 0;
+    }
+    series
+        .tiers()
+        .flat_map(|(_, buckets)| buckets.range(start..end))
+        .map(|(_, b)| b.bytes)
+        .sum()
+}
+
+/// Nearest-rank percentile of sorted values
+fn percentile(sorted: &[u64], pct: u64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (pct as usize * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+impl BandwidthIndex {
+    /// Bytes per relay (across pools) in `[start, end)`
+    fn relay_bytes(&self, start: u64, end: u64) -> HashMap<PublicKey, u64> {
+        let mut totals: HashMap<PublicKey, u64> = HashMap::new();
+        for ((relay, _, _), series) in &self.series {
+            let bytes = window_bytes(series, start, end);
+            if bytes > 0 {
+                *totals.entry(*relay).or_default() += bytes;
+            }
+        }
+        totals
+    }
+
+    /// The `n` relays that carried the most bytes in `[start, end)`,
+    /// largest first
+    pub fn top_relays(&self, start: u64, end: u64, n: usize) -> Vec<RelayRank> {
+        let mut ranks: Vec<RelayRank> = self
+            .relay_bytes(start, end)
+            .into_iter()
+            .map(|(relay, bytes)| RelayRank { relay, bytes })
+            .collect();
+        ranks.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.relay.cmp(&b.relay)));
+        ranks.truncate(n);
+        ranks
+    }
+
+    /// P50/P95 of each pool's hourly throughput in `[start, end)`.
+    ///
+    /// Uses hourly buckets only: compacted history has no hourly
+    /// resolution left.
+    pub fn pool_throughput_percentiles(&self, start: u64, end: u64) -> Vec<PoolThroughput> {
+        let mut per_pool: HashMap<PublicKey, BTreeMap<u64, u64>> = HashMap::new();
+        if start < end {
+            for ((_, pool, _), series) in &self.series {
+                let hours = per_pool.entry(*pool).or_default();
+                for (&hour, bucket) in series.hourly.range(start..end) {
+                    *hours.entry(hour).or_default() += bucket.bytes;
+                }
+            }
+        }
+
+        let mut result: Vec<PoolThroughput> = per_pool
+            .into_iter()
+            .filter(|(_, hours)| !hours.is_empty())
+            .map(|(pool, hours)| {
+                let mut values: Vec<u64> = hours.into_values().collect();
+                values.sort_unstable();
+                PoolThroughput {
+                    pool,
+                    hours: values.len(),
+                    p50: percentile(&values, 50),
+                    p95: percentile(&values, 95),
+                }
+            })
+            .collect();
+        result.sort_by(|a, b| a.pool.cmp(&b.pool));
+        result
+    }
+
+    /// Network bytes of the week ending at `now` against the week before
+    pub fn weekly_growth(&self, now: u64) -> WeeklyGrowth {
+        let this_start = now.saturating_sub(WEEK_SECS);
+        let last_start = now.saturating_sub(2 * WEEK_SECS);
+        let this_week_bytes = window_bytes(&self.network, this_start, now);
+        let last_week_bytes = window_bytes(&self.network, last_start, this_start);
+        let rate = (last_week_bytes > 0).then(|| this_week_bytes as f64 / last_week_bytes as f64 - 1.0);
+        WeeklyGrowth { this_week_bytes, last_week_bytes, rate }
+    }
+
+    /// Relays that went silent (or appeared) in the week ending at `now`
+    pub fn relay_churn(&self, now: u64) -> RelayChurn {
+        let this_start = now.saturating_sub(WEEK_SECS);
+        let last_start = now.saturating_sub(2 * WEEK_SECS);
+        let this_week: HashSet<PublicKey> = self.relay_bytes(this_start, now).into_keys().collect();
+        let last_week: HashSet<PublicKey> = self.relay_bytes(last_start, this_start).into_keys().collect();
+
+        let mut churned: Vec<PublicKey> = last_week.difference(&this_week).copied().collect();
+        let mut joined: Vec<PublicKey> = this_week.difference(&last_week).copied().collect();
+        churned.sort();
+        joined.sort();
+        RelayChurn {
+            active_this_week: this_week.len(),
+            active_last_week: last_week.len(),
+            churned,
+            joined,
+        }
+    }
+}
+
+mod hex_key {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(key: &[u8; 32], s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&hex::encode(key))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<[u8; 32], D::Error> {
+        let s = String::deserialize(d)?;
+        hex::decode(&s)
+            .ok()
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid public key: {}", s)))
+    }
+}
+
+mod hex_keys {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(keys: &[[u8; 32]], s: S) -> Result<S::Ok, S::Error> {
+        s.collect_seq(keys.iter().map(hex::encode))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<[u8; 32]>, D::Error> {
+        Vec::<String>::deserialize(d)?
+            .into_iter()
+            .map(|s| {
+                hex::decode(&s)
+                    .ok()
+                    .and_then(|b| b.try_into().ok())
+                    .ok_or_else(|| serde::de::Error::custom(format!("invalid public key: {}", s)))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use craftnet_network::PoolType;
+
+    const HOUR: u64 = 3600;
+
+    #[test]
+    fn test_top_relays() {
+        let mut index = BandwidthIndex::new();
+        index.record_proof(&[1; 32], &[9; 32], PoolType::Subscribed, 100, HOUR);
+        index.record_proof(&[1; 32], &[8; 32], PoolType::Free, 100, HOUR);
+        index.record_proof(&[2; 32], &[9; 32], PoolType::Subscribed, 300, HOUR);
+        index.record_proof(&[3; 32], &[9; 32], PoolType::Subscribed, 50, HOUR);
+        index.record_proof(&[3; 32], &[9; 32], PoolType::Subscribed, 1000, 10 * HOUR);
+
+        let top = index.top_relays(0, 2 * HOUR, 2);
+        assert_eq!(top, vec![
+            RelayRank { relay: [2; 32], bytes: 300 },
+            RelayRank { relay: [1; 32], bytes: 200 },
+        ]);
+        assert_eq!(index.top_relays(0, u64::MAX, 1)[0].relay, [3; 32]);
+    }
+
+    #[test]
+    fn test_pool_percentiles() {
+        let mut index = BandwidthIndex::new();
+        for (i, bytes) in (1..=20u64).enumerate() {
+            index.record_proof(&[1; 32], &[9; 32], PoolType::Subscribed, bytes * 10, i as u64 * HOUR);
+        }
+        // A second relay in the same hour adds to that hour
+        index.record_proof(&[2; 32], &[9; 32], PoolType::Subscribed, 5, 0);
+
+        let stats = index.pool_throughput_percentiles(0, u64::MAX);
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].hours, 20);
+        assert_eq!(stats[0].p50, 100);
+        assert_eq!(stats[0].p95, 190);
+        assert!(index.pool_throughput_percentiles(100 * HOUR, 200 * HOUR).is_empty());
+    }
+
+    #[test]
+    fn test_growth_and_churn() {
+        let now = 3 * WEEK_SECS;
+        let mut index = BandwidthIndex::new();
+        // Last week: relays 1 and 2; this week: relays 2 and 3
+        index.record_proof(&[1; 32], &[9; 32], PoolType::Subscribed, 100, now - WEEK_SECS - HOUR);
+        index.record_proof(&[2; 32], &[9; 32], PoolType::Subscribed, 100, now - WEEK_SECS - HOUR);
+        index.record_proof(&[2; 32], &[9; 32], PoolType::Subscribed, 200, now - HOUR);
+        index.record_proof(&[3; 32], &[9; 32], PoolType::Subscribed, 100, now - HOUR);
+
+        let growth = index.weekly_growth(now);
+        assert_eq!((growth.this_week_bytes, growth.last_week_bytes), (300, 200));
+        assert_eq!(growth.rate, Some(0.5));
+
+        let churn = index.relay_churn(now);
+        assert_eq!((churn.active_this_week, churn.active_last_week), (2, 2));
+        assert_eq!(churn.churned, vec![[1; 32]]);
+        assert_eq!(churn.joined, vec![[3; 32]]);
+
+        assert_eq!(BandwidthIndex::new().weekly_growth(now).rate, None);
+    }
+}
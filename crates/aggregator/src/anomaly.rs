@@ -0,0 +1,222 @@
+//! Anomaly detection on relay-reported bandwidth
+//!
+//! Relays report their own batch sizes, so a relay can inflate its share
+//! of a pool by claiming more bytes than it forwarded. Before a proof is
+//! applied it is checked against two signals:
+//!
+//! - **Spike**: `batch_bytes` far above the rolling mean of the relay's
+//!   recent batches in the same pool.
+//! - **Impossible rate**: more bytes than `max_bytes_per_sec` allows in the
+//!   time since the relay's previous proof in the pool.
+//!
+//! Flagged proofs are quarantined instead of applied. The relay's chain
+//! for that pool stalls behind them (later proofs wait in the pending
+//! buffer) until the proof is released — by a valid ZK proof from a
+//! configured [`BatchProofVerifier`], by confirmations from other
+//! aggregators, or by an operator — or rejected.
+
+use std::collections::{HashMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use craftnet_core::PublicKey;
+use craftnet_network::{PoolType, ProofMessage};
+
+/// Thresholds of the anomaly detector
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnomalyConfig {
+    /// Check proofs at all. Default: true.
+    pub enabled: bool,
+    /// Recent batches per (relay, pool) the baseline averages over. Default: 32.
+    pub baseline_window: usize,
+    /// Batches needed before spikes are checked. Default: 8.
+    pub min_baseline_proofs: usize,
+    /// A batch this many times the baseline mean is a spike. Default: 10.
+    pub spike_factor: f64,
+    /// Batches smaller than this are never spikes. Default: 1 MiB.
+    pub min_spike_bytes: u64,
+    /// Most bytes per second a relay can plausibly forward for one pool.
+    /// Default: 1.25 GB/s (10 Gbit/s).
+    pub max_bytes_per_sec: u64,
+    /// Distinct aggregators that must confirm a quarantined proof before
+    /// it is applied. Default: 2.
+    pub confirmations_required: usize,
+    /// Most proofs held in quarantine; beyond it new anomalies are
+    /// rejected outright. Default: 1024.
+    pub max_quarantined: usize,
+}
+
+impl Default for AnomalyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            baseline_window: 32,
+            min_baseline_proofs: 8,
+            spike_factor: 10.0,
+            min_spike_bytes: 1024 * 1024,
+            max_bytes_per_sec: 1_250_000_000,
+            confirmations_required: 2,
+            max_quarantined: 1024,
+        }
+    }
+}
+
+/// Why a proof was flagged
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AnomalyReason {
+    /// Batch far above the relay's rolling baseline for the pool
+    Spike { batch_bytes: u64, baseline_bytes: u64 },
+    /// More bytes than the relay could forward since its previous proof
+    ImpossibleRate { batch_bytes: u64, elapsed_secs: u64 },
+}
+
+/// How a quarantined proof got applied
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReleaseReason {
+    /// A configured verifier accepted the proof's ZK proof
+    ZkProof,
+    /// Enough other aggregators confirmed it
+    Confirmed,
+    /// An operator released it
+    Operator,
+}
+
+/// A proof held back by the anomaly detector
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantinedProof {
+    pub proof: ProofMessage,
+    pub reason: AnomalyReason,
+    /// Unix time the proof was quarantined
+    pub quarantined_at: u64,
+    /// Aggregators that confirmed the proof so far
+    pub confirmations: Vec<PublicKey>,
+}
+
+/// Anomaly detector events, for operators (see `Aggregator::take_anomaly_events`)
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnomalyEvent {
+    Quarantined {
+        relay: PublicKey,
+        pool: PublicKey,
+        pool_type: PoolType,
+        new_root: [u8; 32],
+        reason: AnomalyReason,
+    },
+    Released {
+        relay: PublicKey,
+        pool: PublicKey,
+        pool_type: PoolType,
+        new_root: [u8; 32],
+        via: ReleaseReason,
+    },
+    /// Dropped by an operator, or because the quarantine was full
+    Rejected {
+        relay: PublicKey,
+        pool: PublicKey,
+        pool_type: PoolType,
+        new_root: [u8; 32],
+        reason: AnomalyReason,
+    },
+}
+
+/// Checks the ZK proof bytes carried by a flagged proof message
+pub trait BatchProofVerifier: Send + Sync {
+    /// True if `msg.proof` proves `msg.batch_bytes` for its receipts
+    fn verify(&self, msg: &ProofMessage) -> bool;
+}
+
+/// Rolling per-(relay, pool) batch baselines
+#[derive(Debug, Default)]
+pub(crate) struct AnomalyDetector {
+    pub(crate) config: AnomalyConfig,
+    baselines: HashMap<(PublicKey, PublicKey, PoolType), VecDeque<u64>>,
+}
+
+impl AnomalyDetector {
+    /// Check `msg` against the relay's baseline and, when it has a
+    /// previous proof in the pool, the time since it (`prev_timestamp`)
+    pub(crate) fn check(&self, msg: &ProofMessage, prev_timestamp: Option<u64>) -> Option<AnomalyReason> {
+        if !self.config.enabled {
+            return None;
+        }
+
+        if let Some(prev) = prev_timestamp {
+            let elapsed_secs = msg.timestamp.saturating_sub(prev).max(1);
+            if msg.batch_bytes / elapsed_secs > self.config.max_bytes_per_sec {
+                return Some(AnomalyReason::ImpossibleRate { batch_bytes: msg.batch_bytes, elapsed_secs });
+            }
+        }
+
+        let key = (msg.relay_pubkey, msg.pool_pubkey, msg.pool_type);
+        let recent = self.baselines.get(&key)?;
+        if recent.len() < self.config.min_baseline_proofs || msg.batch_bytes < self.config.min_spike_bytes {
+            return None;
+        }
+        let baseline_bytes = recent.iter().sum::<u64>() / recent.len() as u64;
+        if msg.batch_bytes as f64 > baseline_bytes as f64 * self.config.spike_factor {
+            return Some(AnomalyReason::Spike { batch_bytes: msg.batch_bytes, baseline_bytes });
+        }
+        None
+    }
+
+    /// Add an applied proof to its baseline
+    pub(crate) fn observe(&mut self, msg: &ProofMessage) {
+        let window = self.config.baseline_window.max(1);
+        let recent = self.baselines.entry((msg.relay_pubkey, msg.pool_pubkey, msg.pool_type)).or_default();
+        if recent.len() >= window {
+            recent.pop_front();
+        }
+        recent.push_back(msg.batch_bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(batch_bytes: u64, timestamp: u64) -> ProofMessage {
+        ProofMessage {
+            relay_pubkey: [1; 32],
+            pool_pubkey: [2; 32],
+            pool_type: PoolType::Subscribed,
+            batch_bytes,
+            cumulative_bytes: batch_bytes,
+            prev_root: [0; 32],
+            new_root: [0; 32],
+            proof: vec![],
+            timestamp,
+            signature: vec![],
+        }
+    }
+
+    #[test]
+    fn test_spike_needs_baseline() {
+        let mut detector = AnomalyDetector::default();
+        let big = msg(100 * 1024 * 1024, 1000);
+        assert_eq!(detector.check(&big, None), None);
+
+        for _ in 0..8 {
+            detector.observe(&msg(2 * 1024 * 1024, 0));
+        }
+        assert_eq!(
+            detector.check(&big, None),
+            Some(AnomalyReason::Spike { batch_bytes: 100 * 1024 * 1024, baseline_bytes: 2 * 1024 * 1024 }),
+        );
+        assert_eq!(detector.check(&msg(10 * 1024 * 1024, 1000), None), None);
+    }
+
+    #[test]
+    fn test_impossible_rate() {
+        let detector = AnomalyDetector::default();
+        // 5 GB in 2 seconds
+        assert_eq!(
+            detector.check(&msg(5_000_000_000, 1002), Some(1000)),
+            Some(AnomalyReason::ImpossibleRate { batch_bytes: 5_000_000_000, elapsed_secs: 2 }),
+        );
+        // The same batch over an hour is fine
+        assert_eq!(detector.check(&msg(5_000_000_000, 4600), Some(1000)), None);
+
+        let disabled = AnomalyDetector { config: AnomalyConfig { enabled: false, ..Default::default() }, ..Default::default() };
+        assert_eq!(disabled.check(&msg(5_000_000_000, 1002), Some(1000)), None);
+    }
+}
@@ -0,0 +1,641 @@
+//! Read-only REST/JSON API (feature `api`)
+//!
+//! Lets explorers and dashboards query an aggregator over HTTP without
+//! linking Rust. Every endpoint is a GET returning JSON; public keys are hex.
+//!
+//! | Endpoint | Returns |
+//! |----------|---------|
+//! | `/stats` | network totals and history height |
+//! | `/pools?offset=&limit=` | pools with relay count and total bytes |
+//! | `/pools/{pubkey}/usage?pool_type=&offset=&limit=` | bytes per relay in a pool |
+//! | `/relays/{pubkey}/bandwidth?start=&end=&granularity=` | a relay's bandwidth buckets (hourly to monthly) |
+//! | `/history?since_seq=&limit=` | history log entries from `since_seq` on |
+//! | `/disputes?status=&offset=&limit=` | distribution disputes, newest first (`open`, `resolved`, `unresolved`) |
+//! | `/analytics/top-relays?start=&end=&n=` | relays ranked by bytes in a window |
+//! | `/analytics/pool-throughput?start=&end=` | per-pool P50/P95 bytes per hour |
+//! | `/analytics/growth?now=` | network bytes week-over-week |
+//! | `/analytics/churn?now=` | relays gone silent or new this week |
+//!
+//! Analytics windows default to the last week; `now` defaults to the
+//! current time.
+//!
+//! List endpoints are paginated with `offset`/`limit` (at most
+//! `MAX_PAGE_SIZE` items per page). When an auth token is set, requests
+//! must carry `Authorization: Bearer <token>`.
+
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+
+use craftnet_core::PublicKey;
+use craftnet_network::PoolType;
+
+use crate::{
+    Aggregator, BandwidthBucket, DisputeStatus, Granularity, HistoryEntry, NetworkStats, PoolThroughput, RelayChurn, RelayRank,
+    WeeklyGrowth, WEEK_SECS,
+};
+
+/// Items per page when `limit` isn't given
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Largest page a client may ask for
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+/// What the API serves from
+#[derive(Clone)]
+pub struct ApiState {
+    aggregator: Arc<RwLock<Aggregator>>,
+    /// History file backing `/history` (None = endpoint unavailable)
+    history_path: Option<PathBuf>,
+    auth_token: Option<Arc<str>>,
+}
+
+impl ApiState {
+    pub fn new(aggregator: Arc<RwLock<Aggregator>>) -> Self {
+        Self {
+            aggregator,
+            history_path: None,
+            auth_token: None,
+        }
+    }
+
+    /// Serve `/history` from this history file
+    pub fn with_history_path(mut self, path: PathBuf) -> Self {
+        self.history_path = Some(path);
+        self
+    }
+
+    /// Require `Authorization: Bearer <token>` on every request
+    pub fn with_auth_token(mut self, token: impl Into<String>) -> Self {
+        self.auth_token = Some(token.into().into());
+        self
+    }
+}
+
+/// Router with all endpoints
+pub fn router(state: ApiState) -> Router {
+    Router::new()
+        .route("/stats", get(stats))
+        .route("/pools", get(pools))
+        .route("/pools/{pubkey}/usage", get(pool_usage))
+        .route("/relays/{pubkey}/bandwidth", get(relay_bandwidth))
+        .route("/history", get(history))
+        .route("/disputes", get(disputes))
+        .route("/analytics/top-relays", get(top_relays))
+        .route("/analytics/pool-throughput", get(pool_throughput))
+        .route("/analytics/growth", get(growth))
+        .route("/analytics/churn", get(churn))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_token))
+        .with_state(state)
+}
+
+/// Serve the API on `listener` until the task is dropped
+pub async fn serve(listener: TcpListener, state: ApiState) -> std::io::Result<()> {
+    if let Ok(addr) = listener.local_addr() {
+        tracing::info!("Aggregator API listening on http://{}", addr);
+    }
+    axum::serve(listener, router(state)).await
+}
+
+/// Error response: `{"error": "..."}` with a status code
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self { status: StatusCode::BAD_REQUEST, message: message.into() }
+    }
+
+    fn not_found(message: impl Into<String>) -> Self {
+        Self { status: StatusCode::NOT_FOUND, message: message.into() }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(serde_json::json!({ "error": self.message }))).into_response()
+    }
+}
+
+type ApiResult<T> = Result<Json<T>, ApiError>;
+
+async fn require_token(State(state): State<ApiState>, request: Request, next: Next) -> Response {
+    if let Some(token) = &state.auth_token {
+        let presented = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .unwrap_or_default();
+        if !constant_time_eq(presented.as_bytes(), token.as_bytes()) {
+            return ApiError {
+                status: StatusCode::UNAUTHORIZED,
+                message: "missing or invalid auth token".to_string(),
+            }
+            .into_response();
+        }
+    }
+    next.run(request).await
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// One page of a list endpoint
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Items across all pages
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct PageParams {
+    offset: Option<usize>,
+    limit: Option<usize>,
+}
+
+impl PageParams {
+    fn page<T>(&self, items: Vec<T>) -> Page<T> {
+        let offset = self.offset.unwrap_or(0);
+        let limit = self.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+        let total = items.len();
+        let items = items.into_iter().skip(offset).take(limit).collect();
+        Page { items, total, offset, limit }
+    }
+}
+
+fn parse_pubkey(hex_key: &str) -> Result<PublicKey, ApiError> {
+    hex::decode(hex_key)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| ApiError::bad_request(format!("invalid public key: {}", hex_key)))
+}
+
+fn parse_pool_type(s: &str) -> Result<PoolType, ApiError> {
+    match s.to_ascii_lowercase().as_str() {
+        "subscribed" => Ok(PoolType::Subscribed),
+        "free" => Ok(PoolType::Free),
+        _ => Err(ApiError::bad_request(format!("invalid pool_type: {}", s))),
+    }
+}
+
+fn pool_type_name(pool_type: PoolType) -> &'static str {
+    match pool_type {
+        PoolType::Subscribed => "subscribed",
+        PoolType::Free => "free",
+    }
+}
+
+fn read(state: &ApiState) -> std::sync::RwLockReadGuard<'_, Aggregator> {
+    // A panicked writer leaves consistent-enough data for read-only queries
+    state.aggregator.read().unwrap_or_else(|e| e.into_inner())
+}
+
+#[derive(Debug, Serialize)]
+struct StatsResponse {
+    #[serde(flatten)]
+    network: NetworkStats,
+    pool_count: usize,
+    history_height: u64,
+}
+
+async fn stats(State(state): State<ApiState>) -> ApiResult<StatsResponse> {
+    let aggregator = read(&state);
+    Ok(Json(StatsResponse {
+        network: aggregator.get_network_stats(),
+        pool_count: aggregator.pool_count(),
+        history_height: aggregator.history_height(),
+    }))
+}
+
+#[derive(Debug, Serialize)]
+struct PoolSummary {
+    pubkey: String,
+    pool_type: &'static str,
+    relays: usize,
+    total_bytes: u64,
+}
+
+async fn pools(State(state): State<ApiState>, Query(page): Query<PageParams>) -> ApiResult<Page<PoolSummary>> {
+    let aggregator = read(&state);
+    let mut summaries: Vec<PoolSummary> = aggregator
+        .all_pool_keys()
+        .into_iter()
+        .map(|key| {
+            let usage = aggregator.get_pool_usage(&key);
+            PoolSummary {
+                pubkey: hex::encode(key.0),
+                pool_type: pool_type_name(key.1),
+                relays: usage.len(),
+                total_bytes: usage.iter().map(|(_, bytes)| bytes).sum(),
+            }
+        })
+        .collect();
+    summaries.sort_by(|a, b| {
+        b.total_bytes.cmp(&a.total_bytes).then_with(|| (&a.pubkey, a.pool_type).cmp(&(&b.pubkey, b.pool_type)))
+    });
+    Ok(Json(page.page(summaries)))
+}
+
+#[derive(Debug, Deserialize)]
+struct UsageParams {
+    pool_type: Option<String>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct RelayUsage {
+    relay: String,
+    pool_type: &'static str,
+    bytes: u64,
+}
+
+async fn pool_usage(
+    State(state): State<ApiState>,
+    Path(pubkey): Path<String>,
+    Query(params): Query<UsageParams>,
+) -> ApiResult<Page<RelayUsage>> {
+    let pool = parse_pubkey(&pubkey)?;
+    let pool_types = match &params.pool_type {
+        Some(s) => vec![parse_pool_type(s)?],
+        None => vec![PoolType::Subscribed, PoolType::Free],
+    };
+
+    let aggregator = read(&state);
+    let mut usage: Vec<RelayUsage> = pool_types
+        .into_iter()
+        .flat_map(|pool_type| {
+            aggregator
+                .get_pool_usage(&(pool, pool_type))
+                .into_iter()
+                .map(move |(relay, bytes)| RelayUsage {
+                    relay: hex::encode(relay),
+                    pool_type: pool_type_name(pool_type),
+                    bytes,
+                })
+        })
+        .collect();
+    if usage.is_empty() {
+        return Err(ApiError::not_found(format!("unknown pool: {}", pubkey)));
+    }
+    usage.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.relay.cmp(&b.relay)));
+    let page = PageParams { offset: params.offset, limit: params.limit };
+    Ok(Json(page.page(usage)))
+}
+
+#[derive(Debug, Deserialize)]
+struct BandwidthParams {
+    start: Option<u64>,
+    end: Option<u64>,
+    granularity: Option<String>,
+}
+
+async fn relay_bandwidth(
+    State(state): State<ApiState>,
+    Path(pubkey): Path<String>,
+    Query(params): Query<BandwidthParams>,
+) -> ApiResult<Vec<BandwidthBucket>> {
+    let relay = parse_pubkey(&pubkey)?;
+    let granularity = match params.granularity.as_deref() {
+        None => Granularity::Hourly,
+        Some(s) => s.parse().map_err(ApiError::bad_request)?,
+    };
+    let start = params.start.unwrap_or(0);
+    let end = params.end.unwrap_or(u64::MAX);
+    if start > end {
+        return Err(ApiError::bad_request("start is after end"));
+    }
+    Ok(Json(read(&state).get_relay_total_bandwidth(&relay, start, end, granularity)))
+}
+
+#[derive(Debug, Deserialize)]
+struct HistoryParams {
+    since_seq: Option<u64>,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistoryPage {
+    pub items: Vec<HistoryEntry>,
+    /// `since_seq` of the next page (None = no more entries yet)
+    pub next_seq: Option<u64>,
+}
+
+async fn history(State(state): State<ApiState>, Query(params): Query<HistoryParams>) -> ApiResult<HistoryPage> {
+    let Some(path) = state.history_path.clone() else {
+        return Err(ApiError::not_found("history is not available on this aggregator"));
+    };
+    let since = params.since_seq.unwrap_or(0);
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+    // The history file can be large; scan it off the async workers
+    let mut items = tokio::task::spawn_blocking(move || Aggregator::history_since(&path, since))
+        .await
+        .map_err(|e| ApiError {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message: e.to_string(),
+        })?;
+    let more = items.len() > limit;
+    items.truncate(limit);
+    let next_seq = if more { items.last().map(|e| e.seq + 1) } else { None };
+    Ok(Json(HistoryPage { items, next_seq }))
+}
+
+#[derive(Debug, Deserialize)]
+struct DisputeParams {
+    status: Option<String>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+}
+
+fn parse_dispute_status(s: &str) -> Result<DisputeStatus, ApiError> {
+    match s.to_ascii_lowercase().as_str() {
+        "open" => Ok(DisputeStatus::Open),
+        "resolved" => Ok(DisputeStatus::Resolved),
+        "unresolved" => Ok(DisputeStatus::Unresolved),
+        _ => Err(ApiError::bad_request(format!("invalid status: {}", s))),
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct DisputeSummary {
+    relay: String,
+    pool: String,
+    pool_type: &'static str,
+    distribution_root: String,
+    counted_bytes: u64,
+    claimed_bytes: u64,
+    opened_at: u64,
+    deadline: u64,
+    status: DisputeStatus,
+    closed_at: Option<u64>,
+}
+
+async fn disputes(State(state): State<ApiState>, Query(params): Query<DisputeParams>) -> ApiResult<Page<DisputeSummary>> {
+    let status = params.status.as_deref().map(parse_dispute_status).transpose()?;
+    let items: Vec<DisputeSummary> = read(&state)
+        .disputes()
+        .rev()
+        .filter(|d| status.is_none_or(|s| d.status == s))
+        .map(|d| DisputeSummary {
+            relay: hex::encode(d.relay),
+            pool: hex::encode(d.pool),
+            pool_type: pool_type_name(d.pool_type),
+            distribution_root: hex::encode(d.distribution_root),
+            counted_bytes: d.counted_bytes,
+            claimed_bytes: d.claimed_bytes,
+            opened_at: d.opened_at,
+            deadline: d.deadline,
+            status: d.status,
+            closed_at: d.closed_at,
+        })
+        .collect();
+    let page = PageParams { offset: params.offset, limit: params.limit };
+    Ok(Json(page.page(items)))
+}
+
+#[derive(Debug, Deserialize)]
+struct WindowParams {
+    start: Option<u64>,
+    end: Option<u64>,
+    n: Option<usize>,
+}
+
+impl WindowParams {
+    /// `[start, end)`, defaulting to the week before `now`
+    fn window(&self, now: u64) -> Result<(u64, u64), ApiError> {
+        let end = self.end.unwrap_or(now);
+        let start = self.start.unwrap_or(end.saturating_sub(WEEK_SECS));
+        if start > end {
+            return Err(ApiError::bad_request("start is after end"));
+        }
+        Ok((start, end))
+    }
+}
+
+async fn top_relays(State(state): State<ApiState>, Query(params): Query<WindowParams>) -> ApiResult<Vec<RelayRank>> {
+    let aggregator = read(&state);
+    let (start, end) = params.window(aggregator.clock().unix_now())?;
+    let n = params.n.unwrap_or(10).clamp(1, MAX_PAGE_SIZE);
+    Ok(Json(aggregator.bandwidth_index().top_relays(start, end, n)))
+}
+
+async fn pool_throughput(
+    State(state): State<ApiState>,
+    Query(params): Query<WindowParams>,
+) -> ApiResult<Vec<PoolThroughput>> {
+    let aggregator = read(&state);
+    let (start, end) = params.window(aggregator.clock().unix_now())?;
+    Ok(Json(aggregator.bandwidth_index().pool_throughput_percentiles(start, end)))
+}
+
+#[derive(Debug, Deserialize)]
+struct NowParams {
+    now: Option<u64>,
+}
+
+async fn growth(State(state): State<ApiState>, Query(params): Query<NowParams>) -> ApiResult<WeeklyGrowth> {
+    let aggregator = read(&state);
+    let now = params.now.unwrap_or_else(|| aggregator.clock().unix_now());
+    Ok(Json(aggregator.bandwidth_index().weekly_growth(now)))
+}
+
+async fn churn(State(state): State<ApiState>, Query(params): Query<NowParams>) -> ApiResult<RelayChurn> {
+    let aggregator = read(&state);
+    let now = params.now.unwrap_or_else(|| aggregator.clock().unix_now());
+    Ok(Json(aggregator.bandwidth_index().relay_churn(now)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    use craftnet_network::ProofMessage;
+
+    fn proof(relay: u8, pool: u8, batch: u64) -> ProofMessage {
+        let keypair = craftec_crypto::SigningKeypair::from_secret_bytes(&[relay; 32]);
+        let mut msg = ProofMessage {
+            relay_pubkey: keypair.public_key_bytes(),
+            pool_pubkey: [pool; 32],
+            pool_type: PoolType::Subscribed,
+            batch_bytes: batch,
+            cumulative_bytes: batch,
+            prev_root: [0u8; 32],
+            new_root: [relay; 32],
+            proof: vec![],
+            timestamp: 1700000000,
+            signature: vec![],
+        };
+        msg.signature = craftec_crypto::sign_data(&keypair, &msg.signable_data()).to_vec();
+        msg
+    }
+
+    fn state() -> ApiState {
+        let mut aggregator = Aggregator::new();
+        aggregator.handle_proof(proof(1, 9, 100)).unwrap();
+        aggregator.handle_proof(proof(2, 9, 300)).unwrap();
+        ApiState::new(Arc::new(RwLock::new(aggregator)))
+    }
+
+    async fn get_json(app: Router, uri: &str, token: Option<&str>) -> (StatusCode, serde_json::Value) {
+        let mut request = Request::get(uri);
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let response = app.oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_stats_and_pools() {
+        let app = router(state());
+        let (status, stats) = get_json(app.clone(), "/stats", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(stats["total_bytes"], 400);
+        assert_eq!(stats["active_relays"], 2);
+
+        let (_, pools) = get_json(app, "/pools", None).await;
+        assert_eq!(pools["total"], 1);
+        assert_eq!(pools["items"][0]["pubkey"], hex::encode([9u8; 32]));
+        assert_eq!(pools["items"][0]["relays"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_pool_usage_paginated() {
+        let app = router(state());
+        let uri = format!("/pools/{}/usage?pool_type=subscribed&limit=1&offset=1", hex::encode([9u8; 32]));
+        let (status, page) = get_json(app.clone(), &uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(page["total"], 2);
+        assert_eq!(page["items"].as_array().unwrap().len(), 1);
+        // Sorted by bytes, so the second page holds the smaller relay
+        assert_eq!(page["items"][0]["bytes"], 100);
+
+        let (status, _) = get_json(app.clone(), &format!("/pools/{}/usage", hex::encode([8u8; 32])), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = get_json(app, "/pools/zz/usage", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_relay_bandwidth() {
+        let app = router(state());
+        let relay = craftec_crypto::SigningKeypair::from_secret_bytes(&[2u8; 32]).public_key_bytes();
+        let uri = format!("/relays/{}/bandwidth?start=0&end=1800000000&granularity=hourly", hex::encode(relay));
+        let (status, buckets) = get_json(app.clone(), &uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(buckets[0]["bytes"], 300);
+
+        let uri = format!("/relays/{}/bandwidth?granularity=yearly", hex::encode(relay));
+        assert_eq!(get_json(app, &uri, None).await.0, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_history_pages() {
+        let path = std::env::temp_dir().join(format!("craftnet-api-history-{}.bin", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut aggregator = Aggregator::new();
+        for i in 0..3 {
+            aggregator.record_distribution_posted([i; 32], [0u8; 32], 10);
+        }
+        aggregator.flush_history(&path);
+        let state = ApiState::new(Arc::new(RwLock::new(aggregator))).with_history_path(path.clone());
+        let app = router(state);
+
+        let (_, page) = get_json(app.clone(), "/history?limit=2", None).await;
+        assert_eq!(page["items"].as_array().unwrap().len(), 2);
+        assert_eq!(page["next_seq"], 2);
+        let (_, page) = get_json(app, "/history?since_seq=2", None).await;
+        assert_eq!(page["items"][0]["seq"], 2);
+        assert!(page["next_seq"].is_null());
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_analytics() {
+        let app = router(state());
+        let relay = craftec_crypto::SigningKeypair::from_secret_bytes(&[2u8; 32]).public_key_bytes();
+        let (status, top) = get_json(app.clone(), "/analytics/top-relays?start=0&end=1800000000&n=1", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(top.as_array().unwrap().len(), 1);
+        assert_eq!(top[0]["relay"], hex::encode(relay));
+        assert_eq!(top[0]["bytes"], 300);
+
+        let (_, pools) = get_json(app.clone(), "/analytics/pool-throughput?start=0&end=1800000000", None).await;
+        assert_eq!(pools[0]["p95"], 400);
+
+        let (_, churn) = get_json(app.clone(), "/analytics/churn?now=1700100000", None).await;
+        assert_eq!(churn["active_this_week"], 2);
+        assert_eq!(churn["joined"].as_array().unwrap().len(), 2);
+
+        let (status, _) = get_json(app, "/analytics/top-relays?start=10&end=5", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_disputes() {
+        let state = state();
+        {
+            let mut aggregator = state.aggregator.write().unwrap();
+            let pool_key = ([9; 32], PoolType::Subscribed);
+            let root = aggregator.build_distribution(&pool_key).unwrap().root;
+            aggregator.open_challenge_window(pool_key, root, 1000);
+            let keypair = craftec_crypto::SigningKeypair::from_secret_bytes(&[1; 32]);
+            let mut challenge = craftnet_network::DistributionChallenge {
+                relay_pubkey: keypair.public_key_bytes(),
+                aggregator_pubkey: [0; 32],
+                pool_pubkey: [9; 32],
+                pool_type: PoolType::Subscribed,
+                distribution_root: root,
+                counted_bytes: 100,
+                claimed_bytes: 200,
+                claimed_root: [7; 32],
+                timestamp: 1000,
+                signature: vec![],
+            };
+            challenge.signature = craftec_crypto::sign_data(&keypair, &challenge.signable_data()).to_vec();
+            aggregator.handle_challenge(&challenge, 1001).unwrap();
+        }
+        let app = router(state);
+
+        let (status, page) = get_json(app.clone(), "/disputes?status=open", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(page["total"], 1);
+        assert_eq!(page["items"][0]["claimed_bytes"], 200);
+        assert_eq!(page["items"][0]["pool"], hex::encode([9u8; 32]));
+        let (_, page) = get_json(app.clone(), "/disputes?status=resolved", None).await;
+        assert_eq!(page["total"], 0);
+        assert_eq!(get_json(app, "/disputes?status=bogus", None).await.0, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_auth_token_required() {
+        let app = router(state().with_auth_token("secret"));
+        assert_eq!(get_json(app.clone(), "/stats", None).await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(get_json(app.clone(), "/stats", Some("wrong")).await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(get_json(app, "/stats", Some("secret")).await.0, StatusCode::OK);
+    }
+}
@@ -0,0 +1,78 @@
+//! Standalone read-only aggregator API
+//!
+//! Serves the REST/JSON API from the state and history files an aggregator
+//! node persists, so explorers can run next to a node without embedding it.
+//! The state is reloaded periodically.
+//!
+//! Configuration (environment):
+//! - `CRAFTNET_AGGREGATOR_STATE`: aggregator state JSON file (required)
+//! - `CRAFTNET_AGGREGATOR_HISTORY`: history file (enables `/history` and bandwidth)
+//! - `CRAFTNET_AGGREGATOR_API_ADDR`: listen address (default `127.0.0.1:8420`)
+//! - `CRAFTNET_AGGREGATOR_API_TOKEN`: bearer token required on every request
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use craftnet_aggregator::api::{serve, ApiState};
+use craftnet_aggregator::Aggregator;
+use tracing_subscriber::{fmt, prelude::*, EnvFilter};
+
+const DEFAULT_ADDR: &str = "127.0.0.1:8420";
+
+/// How often the state and history files are reloaded
+const RELOAD_INTERVAL: Duration = Duration::from_secs(30);
+
+fn load(state_path: &Path, history_path: Option<&Path>) -> std::io::Result<Aggregator> {
+    let (mut aggregator, _posted) = Aggregator::load_from_file(state_path)?;
+    if let Some(path) = history_path {
+        aggregator.set_history_seq(Aggregator::recover_history_seq(path));
+        aggregator.rebuild_bandwidth_from_history(path);
+    }
+    Ok(aggregator)
+}
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    tracing_subscriber::registry()
+        .with(fmt::layer())
+        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+        .init();
+
+    let Some(state_path) = std::env::var_os("CRAFTNET_AGGREGATOR_STATE").map(PathBuf::from) else {
+        eprintln!("CRAFTNET_AGGREGATOR_STATE must point at an aggregator state file");
+        std::process::exit(2);
+    };
+    let history_path = std::env::var_os("CRAFTNET_AGGREGATOR_HISTORY").map(PathBuf::from);
+    let addr: SocketAddr = std::env::var("CRAFTNET_AGGREGATOR_API_ADDR")
+        .unwrap_or_else(|_| DEFAULT_ADDR.to_string())
+        .parse()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+
+    let aggregator = Arc::new(RwLock::new(load(&state_path, history_path.as_deref())?));
+    let mut state = ApiState::new(aggregator.clone());
+    if let Some(path) = &history_path {
+        state = state.with_history_path(path.clone());
+    }
+    if let Ok(token) = std::env::var("CRAFTNET_AGGREGATOR_API_TOKEN") {
+        state = state.with_auth_token(token);
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RELOAD_INTERVAL);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let (state_path, history_path) = (state_path.clone(), history_path.clone());
+            match tokio::task::spawn_blocking(move || load(&state_path, history_path.as_deref())).await {
+                Ok(Ok(fresh)) => *aggregator.write().unwrap_or_else(|e| e.into_inner()) = fresh,
+                Ok(Err(e)) => tracing::warn!("Failed to reload aggregator state: {}", e),
+                Err(e) => tracing::warn!("Aggregator reload task failed: {}", e),
+            }
+        }
+    });
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    serve(listener, state).await
+}
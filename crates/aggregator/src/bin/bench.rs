@@ -0,0 +1,413 @@
+//! Aggregator ingest benchmark
+//!
+//! Synthesizes signed proof streams from a set of relays serving a set of
+//! pools, feeds them through [`Aggregator::handle_proof`] the way the
+//! gossip handler would, and reports ingest throughput, memory footprint,
+//! pending-buffer churn and history flush latency.
+//!
+//! A configurable share of proofs is delivered after its successor (so it
+//! goes through the pending buffer), and a share of forged copies with a
+//! corrupted signature is mixed in. Proofs are signed before the clock
+//! starts; signing is the relay's cost, not the aggregator's.
+//!
+//! Usage (build with `--release` for meaningful numbers):
+//!
+//! ```text
+//! bench [--relays N] [--pools N] [--pools-per-relay N] [--proofs-per-chain N]
+//!       [--out-of-order RATIO] [--bad-signatures RATIO] [--flush-every N]
+//!       [--history PATH] [--seed N] [--json]
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use craftec_crypto::SigningKeypair;
+use craftnet_aggregator::Aggregator;
+use craftnet_network::{PoolType, ProofMessage};
+use sha2::{Digest, Sha256};
+
+/// Timestamp of the first synthetic proof
+const START_TIMESTAMP: u64 = 1_700_000_000;
+
+/// Seconds between consecutive proofs of a chain
+const PROOF_INTERVAL_SECS: u64 = 60;
+
+/// Batch sizes stay under the anomaly detector's spike floor (1 MiB)
+const MIN_BATCH_BYTES: u64 = 16 * 1024;
+const MAX_BATCH_BYTES: u64 = 1024 * 1024 - 1;
+
+struct Config {
+    relays: usize,
+    pools: usize,
+    pools_per_relay: usize,
+    proofs_per_chain: usize,
+    out_of_order: f64,
+    bad_signatures: f64,
+    flush_every: usize,
+    history: Option<PathBuf>,
+    seed: u64,
+    json: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            relays: 200,
+            pools: 50,
+            pools_per_relay: 10,
+            proofs_per_chain: 20,
+            out_of_order: 0.05,
+            bad_signatures: 0.01,
+            flush_every: 5_000,
+            history: None,
+            seed: 1,
+            json: false,
+        }
+    }
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: bench [--relays N] [--pools N] [--pools-per-relay N] [--proofs-per-chain N]\n\
+         \x20            [--out-of-order RATIO] [--bad-signatures RATIO] [--flush-every N]\n\
+         \x20            [--history PATH] [--seed N] [--json]"
+    );
+    std::process::exit(2);
+}
+
+fn parse_args() -> Config {
+    fn value<T: std::str::FromStr>(args: &mut impl Iterator<Item = String>, flag: &str) -> T {
+        let Some(raw) = args.next() else {
+            eprintln!("{} needs a value", flag);
+            usage();
+        };
+        raw.parse().unwrap_or_else(|_| {
+            eprintln!("invalid value for {}: {}", flag, raw);
+            usage();
+        })
+    }
+
+    let mut config = Config::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--relays" => config.relays = value(&mut args, &flag),
+            "--pools" => config.pools = value(&mut args, &flag),
+            "--pools-per-relay" => config.pools_per_relay = value(&mut args, &flag),
+            "--proofs-per-chain" => config.proofs_per_chain = value(&mut args, &flag),
+            "--out-of-order" => config.out_of_order = value(&mut args, &flag),
+            "--bad-signatures" => config.bad_signatures = value(&mut args, &flag),
+            "--flush-every" => config.flush_every = value(&mut args, &flag),
+            "--history" => config.history = Some(value(&mut args, &flag)),
+            "--seed" => config.seed = value(&mut args, &flag),
+            "--json" => config.json = true,
+            "-h" | "--help" => usage(),
+            _ => {
+                eprintln!("unknown argument: {}", flag);
+                usage();
+            }
+        }
+    }
+    if config.relays == 0 || config.pools == 0 || config.proofs_per_chain == 0 {
+        eprintln!("--relays, --pools and --proofs-per-chain must be positive");
+        usage();
+    }
+    config.pools_per_relay = config.pools_per_relay.clamp(1, config.pools);
+    config.flush_every = config.flush_every.max(1);
+    config
+}
+
+/// xorshift64*: deterministic per seed, no dependency needed
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    fn chance(&mut self, ratio: f64) -> bool {
+        let unit = (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+        unit < ratio
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i as u64 + 1) as usize);
+        }
+    }
+}
+
+/// Proofs in delivery order, plus what was injected
+struct Stream {
+    messages: Vec<ProofMessage>,
+    chains: usize,
+    proofs: usize,
+    out_of_order: usize,
+    bad_signatures: usize,
+}
+
+/// One relay's proofs for one pool, in chain order
+fn chain(keypair: &SigningKeypair, pool: [u8; 32], len: usize, rng: &mut Rng) -> Vec<ProofMessage> {
+    let relay = keypair.public_key_bytes();
+    let mut prev_root = [0u8; 32];
+    let mut cumulative = 0u64;
+    (0..len)
+        .map(|i| {
+            let batch_bytes = MIN_BATCH_BYTES + rng.below(MAX_BATCH_BYTES - MIN_BATCH_BYTES + 1);
+            cumulative += batch_bytes;
+            let new_root: [u8; 32] = Sha256::new()
+                .chain_update(prev_root)
+                .chain_update(relay)
+                .chain_update(cumulative.to_le_bytes())
+                .finalize()
+                .into();
+            let mut msg = ProofMessage {
+                relay_pubkey: relay,
+                pool_pubkey: pool,
+                pool_type: PoolType::Subscribed,
+                batch_bytes,
+                cumulative_bytes: cumulative,
+                prev_root,
+                new_root,
+                proof: vec![],
+                timestamp: START_TIMESTAMP + i as u64 * PROOF_INTERVAL_SECS,
+                signature: vec![],
+            };
+            msg.signature = craftec_crypto::sign_data(keypair, &msg.signable_data()).to_vec();
+            prev_root = new_root;
+            msg
+        })
+        .collect()
+}
+
+fn synthesize(config: &Config, rng: &mut Rng) -> Stream {
+    let pools: Vec<[u8; 32]> = (0..config.pools)
+        .map(|i| Sha256::digest(format!("bench-pool-{}", i)).into())
+        .collect();
+
+    let mut chains = Vec::new();
+    let mut out_of_order = 0;
+    for r in 0..config.relays {
+        let secret: [u8; 32] = Sha256::digest(format!("bench-relay-{}", r)).into();
+        let keypair = SigningKeypair::from_secret_bytes(&secret);
+        let mut served: Vec<usize> = (0..config.pools).collect();
+        rng.shuffle(&mut served);
+        for &pool in &served[..config.pools_per_relay] {
+            let mut proofs = chain(&keypair, pools[pool], config.proofs_per_chain, rng);
+            // Deliver a proof after its successor. The first proof of a
+            // chain stays first: without it the chain has no anchor.
+            let mut i = 1;
+            while i + 1 < proofs.len() {
+                if rng.chance(config.out_of_order) {
+                    proofs.swap(i, i + 1);
+                    out_of_order += 1;
+                    i += 2;
+                } else {
+                    i += 1;
+                }
+            }
+            chains.push(proofs);
+        }
+    }
+
+    // Interleave chains round by round, each round in a fresh order, the
+    // way proofs from many relays arrive over gossip
+    let proofs = chains.iter().map(Vec::len).sum();
+    let mut messages = Vec::with_capacity(proofs + proofs / 50);
+    let mut bad_signatures = 0;
+    let mut order: Vec<usize> = (0..chains.len()).collect();
+    for round in 0..config.proofs_per_chain {
+        rng.shuffle(&mut order);
+        for &c in &order {
+            let msg = &chains[c][round];
+            if rng.chance(config.bad_signatures) {
+                let mut forged = msg.clone();
+                forged.cumulative_bytes += 1;
+                forged.signature[0] ^= 0xff;
+                messages.push(forged);
+                bad_signatures += 1;
+            }
+            messages.push(msg.clone());
+        }
+    }
+
+    Stream { messages, chains: chains.len(), proofs, out_of_order, bad_signatures }
+}
+
+/// Resident and peak memory in bytes (Linux only)
+fn memory() -> Option<(u64, u64)> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let field = |name: &str| -> Option<u64> {
+        let line = status.lines().find(|l| l.starts_with(name))?;
+        let kib: u64 = line[name.len()..].trim().trim_end_matches("kB").trim().parse().ok()?;
+        Some(kib * 1024)
+    };
+    Some((field("VmRSS:")?, field("VmHWM:")?))
+}
+
+fn mib(bytes: Option<u64>) -> String {
+    bytes.map_or_else(|| "n/a".to_string(), |b| format!("{:.1} MiB", b as f64 / (1024.0 * 1024.0)))
+}
+
+/// `q`-quantile of sorted samples
+fn quantile(sorted: &[Duration], q: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    sorted[((sorted.len() - 1) as f64 * q).round() as usize]
+}
+
+fn millis(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+fn main() {
+    let config = parse_args();
+    let mut rng = Rng::new(config.seed);
+
+    let generate_start = Instant::now();
+    let stream = synthesize(&config, &mut rng);
+    let generate_time = generate_start.elapsed();
+
+    let history_path = config.history.clone().unwrap_or_else(|| {
+        std::env::temp_dir().join(format!("craftnet-aggregator-bench-{}.history", std::process::id()))
+    });
+    let _ = std::fs::remove_file(&history_path);
+
+    let memory_before = memory();
+    let mut aggregator = Aggregator::new();
+    let mut rejected: BTreeMap<String, u64> = BTreeMap::new();
+    let mut accepted = 0u64;
+    let mut pending_high = 0usize;
+    let mut buffered = 0u64;
+    let mut drained = 0u64;
+    let mut flushes: Vec<Duration> = Vec::new();
+    let mut ingest_time = Duration::ZERO;
+
+    let total = stream.messages.len();
+    let mut pending = 0usize;
+    for (i, msg) in stream.messages.into_iter().enumerate() {
+        let start = Instant::now();
+        let result = aggregator.handle_proof(msg);
+        ingest_time += start.elapsed();
+        match result {
+            Ok(()) => accepted += 1,
+            Err(e) => *rejected.entry(e.to_string()).or_default() += 1,
+        }
+
+        let now_pending = aggregator.pending_count();
+        if now_pending > pending {
+            buffered += (now_pending - pending) as u64;
+        } else {
+            drained += (pending - now_pending) as u64;
+        }
+        pending = now_pending;
+        pending_high = pending_high.max(pending);
+
+        if (i + 1) % config.flush_every == 0 || i + 1 == total {
+            let start = Instant::now();
+            aggregator.flush_history(&history_path);
+            flushes.push(start.elapsed());
+        }
+    }
+    let memory_after = memory();
+    let history_bytes = std::fs::metadata(&history_path).map_or(0, |m| m.len());
+    if config.history.is_none() {
+        let _ = std::fs::remove_file(&history_path);
+    }
+
+    let throughput = total as f64 / ingest_time.as_secs_f64().max(f64::EPSILON);
+    let mut sorted = flushes.clone();
+    sorted.sort();
+    let flush_total: Duration = flushes.iter().sum();
+    let flush_mean = flush_total / flushes.len().max(1) as u32;
+
+    if config.json {
+        let report = serde_json::json!({
+            "relays": config.relays,
+            "pools": config.pools,
+            "chains": stream.chains,
+            "proofs": stream.proofs,
+            "out_of_order": stream.out_of_order,
+            "bad_signatures": stream.bad_signatures,
+            "messages": total,
+            "accepted": accepted,
+            "rejected": rejected,
+            "generate_secs": generate_time.as_secs_f64(),
+            "ingest_secs": ingest_time.as_secs_f64(),
+            "messages_per_sec": throughput,
+            "rss_before_bytes": memory_before.map(|m| m.0),
+            "rss_after_bytes": memory_after.map(|m| m.0),
+            "rss_peak_bytes": memory_after.map(|m| m.1),
+            "pending_high_water": pending_high,
+            "pending_buffered": buffered,
+            "pending_drained": drained,
+            "pending_left": pending,
+            "spilled": aggregator.spilled_count(),
+            "flushes": flushes.len(),
+            "history_bytes": history_bytes,
+            "flush_mean_ms": millis(flush_mean),
+            "flush_p50_ms": millis(quantile(&sorted, 0.5)),
+            "flush_p99_ms": millis(quantile(&sorted, 0.99)),
+            "flush_max_ms": millis(sorted.last().copied().unwrap_or_default()),
+        });
+        println!("{}", serde_json::to_string_pretty(&report).unwrap_or_default());
+        return;
+    }
+
+    println!("Aggregator ingest benchmark (seed {})", config.seed);
+    println!();
+    println!(
+        "Stream:   {} relays x {} pools each ({} pools total) = {} chains",
+        config.relays, config.pools_per_relay, config.pools, stream.chains
+    );
+    println!(
+        "          {} proofs, {} out of order, {} bad signatures ({} messages, signed in {:.2}s)",
+        stream.proofs,
+        stream.out_of_order,
+        stream.bad_signatures,
+        total,
+        generate_time.as_secs_f64()
+    );
+    println!();
+    println!("Ingest:   {:.0} msg/s ({} messages in {:.3}s)", throughput, total, ingest_time.as_secs_f64());
+    println!("          {} accepted", accepted);
+    for (reason, count) in &rejected {
+        println!("          {} rejected: {}", count, reason);
+    }
+    println!(
+        "Memory:   RSS {} before ingest, {} after, peak {}",
+        mib(memory_before.map(|m| m.0)),
+        mib(memory_after.map(|m| m.0)),
+        mib(memory_after.map(|m| m.1)),
+    );
+    println!(
+        "Pending:  high-water {}, {} buffered, {} drained, {} left, {} spilled",
+        pending_high,
+        buffered,
+        drained,
+        pending,
+        aggregator.spilled_count()
+    );
+    println!(
+        "History:  {} flushes, {} written, mean {:.2}ms, p50 {:.2}ms, p99 {:.2}ms, max {:.2}ms",
+        flushes.len(),
+        mib(Some(history_bytes)),
+        millis(flush_mean),
+        millis(quantile(&sorted, 0.5)),
+        millis(quantile(&sorted, 0.99)),
+        millis(sorted.last().copied().unwrap_or_default()),
+    );
+}
@@ -0,0 +1,240 @@
+//! Distribution disputes
+//!
+//! A built distribution is announced with a challenge deadline before it
+//! is proven and posted. Relays that find their bytes under-counted submit
+//! a signed challenge within the window; the aggregator answers with the
+//! proof chain it recorded for the relay. A dispute is resolved when that
+//! chain reaches the root and count the relay claims, and unresolved when
+//! it doesn't or the window closes without an answer. Both outcomes are
+//! written to the history log and listed by the query API.
+
+use std::collections::{HashMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use craftnet_core::PublicKey;
+use craftnet_network::{ChallengeResponse, DistributionChallenge, PoolType};
+
+/// Default time relays have to challenge an announced distribution
+pub const DEFAULT_CHALLENGE_WINDOW_SECS: u64 = 3600;
+
+/// Disputes kept in memory for queries (oldest dropped first)
+const MAX_DISPUTES: usize = 10_000;
+
+/// Where a dispute stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DisputeStatus {
+    /// Challenge received, not answered yet
+    Open,
+    /// The aggregator's proof chain backs the relay's claim
+    Resolved,
+    /// The chain doesn't back the claim, or the window closed unanswered
+    Unresolved,
+}
+
+/// A relay's challenge against an announced distribution
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Dispute {
+    pub relay: PublicKey,
+    pub pool: PublicKey,
+    pub pool_type: PoolType,
+    pub distribution_root: [u8; 32],
+    /// Bytes the distribution counts for the relay
+    pub counted_bytes: u64,
+    /// Bytes the relay says its proof chain reaches
+    pub claimed_bytes: u64,
+    pub claimed_root: [u8; 32],
+    pub opened_at: u64,
+    /// End of the challenge window
+    pub deadline: u64,
+    pub status: DisputeStatus,
+    #[serde(default)]
+    pub closed_at: Option<u64>,
+}
+
+/// Open challenge window of one announced distribution
+#[derive(Debug, Clone, Copy)]
+struct ChallengeWindow {
+    root: [u8; 32],
+    deadline: u64,
+}
+
+/// Challenge windows and the disputes raised in them
+#[derive(Debug)]
+pub(crate) struct DisputeTracker {
+    window_secs: u64,
+    windows: HashMap<(PublicKey, PoolType), ChallengeWindow>,
+    disputes: VecDeque<Dispute>,
+}
+
+impl DisputeTracker {
+    pub(crate) fn new() -> Self {
+        Self {
+            window_secs: DEFAULT_CHALLENGE_WINDOW_SECS,
+            windows: HashMap::new(),
+            disputes: VecDeque::new(),
+        }
+    }
+
+    pub(crate) fn set_window_secs(&mut self, secs: u64) {
+        self.window_secs = secs;
+    }
+
+    pub(crate) fn window_secs(&self) -> u64 {
+        self.window_secs
+    }
+
+    /// Open (or move) the window of a pool's distribution; returns its deadline
+    pub(crate) fn open_window(&mut self, pool_key: (PublicKey, PoolType), root: [u8; 32], now: u64) -> u64 {
+        let deadline = now + self.window_secs;
+        self.windows.insert(pool_key, ChallengeWindow { root, deadline });
+        deadline
+    }
+
+    pub(crate) fn deadline(&self, pool_key: &(PublicKey, PoolType)) -> Option<u64> {
+        self.windows.get(pool_key).map(|w| w.deadline)
+    }
+
+    /// Whether `challenge` targets an announced root within its window
+    pub(crate) fn accepts(&self, challenge: &DistributionChallenge, now: u64) -> bool {
+        self.windows
+            .get(&(challenge.pool_pubkey, challenge.pool_type))
+            .is_some_and(|w| w.root == challenge.distribution_root && now <= w.deadline)
+    }
+
+    /// Record a challenge; a relay's repeated challenge replaces its open one
+    pub(crate) fn open(&mut self, challenge: &DistributionChallenge, now: u64) -> &Dispute {
+        let deadline = self.deadline(&(challenge.pool_pubkey, challenge.pool_type)).unwrap_or(now);
+        self.disputes.retain(|d| {
+            !(d.status == DisputeStatus::Open && d.relay == challenge.relay_pubkey && d.pool == challenge.pool_pubkey
+                && d.pool_type == challenge.pool_type && d.distribution_root == challenge.distribution_root)
+        });
+        if self.disputes.len() >= MAX_DISPUTES {
+            self.disputes.pop_front();
+        }
+        self.disputes.push_back(Dispute {
+            relay: challenge.relay_pubkey,
+            pool: challenge.pool_pubkey,
+            pool_type: challenge.pool_type,
+            distribution_root: challenge.distribution_root,
+            counted_bytes: challenge.counted_bytes,
+            claimed_bytes: challenge.claimed_bytes,
+            claimed_root: challenge.claimed_root,
+            opened_at: now,
+            deadline,
+            status: DisputeStatus::Open,
+            closed_at: None,
+        });
+        self.disputes.back().expect("just pushed")
+    }
+
+    /// Close the open dispute `response` answers
+    pub(crate) fn close(
+        &mut self,
+        challenge: &DistributionChallenge,
+        response: &ChallengeResponse,
+        now: u64,
+    ) -> Option<&Dispute> {
+        let dispute = self.disputes.iter_mut().rev().find(|d| {
+            d.status == DisputeStatus::Open && d.relay == challenge.relay_pubkey && d.pool == challenge.pool_pubkey
+                && d.pool_type == challenge.pool_type && d.distribution_root == challenge.distribution_root
+        })?;
+        dispute.status = if response.backs_claim(challenge) { DisputeStatus::Resolved } else { DisputeStatus::Unresolved };
+        dispute.closed_at = Some(now);
+        Some(dispute)
+    }
+
+    /// Mark open disputes past their deadline unresolved and drop closed
+    /// windows; returns the disputes that expired
+    pub(crate) fn expire(&mut self, now: u64) -> Vec<Dispute> {
+        self.windows.retain(|_, w| now <= w.deadline);
+        let mut expired = Vec::new();
+        for dispute in self.disputes.iter_mut() {
+            if dispute.status == DisputeStatus::Open && now > dispute.deadline {
+                dispute.status = DisputeStatus::Unresolved;
+                dispute.closed_at = Some(now);
+                expired.push(dispute.clone());
+            }
+        }
+        expired
+    }
+
+    pub(crate) fn disputes(&self) -> impl Iterator<Item = &Dispute> {
+        self.disputes.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use craftnet_network::ProofChainLink;
+
+    fn challenge(root: u8) -> DistributionChallenge {
+        DistributionChallenge {
+            relay_pubkey: [1; 32],
+            aggregator_pubkey: [9; 32],
+            pool_pubkey: [2; 32],
+            pool_type: PoolType::Subscribed,
+            distribution_root: [root; 32],
+            counted_bytes: 100,
+            claimed_bytes: 150,
+            claimed_root: [5; 32],
+            timestamp: 0,
+            signature: vec![],
+        }
+    }
+
+    fn response(chain: Vec<ProofChainLink>, counted_bytes: u64) -> ChallengeResponse {
+        ChallengeResponse {
+            aggregator_pubkey: [9; 32],
+            relay_pubkey: [1; 32],
+            pool_pubkey: [2; 32],
+            pool_type: PoolType::Subscribed,
+            distribution_root: [7; 32],
+            counted_bytes,
+            chain,
+            timestamp: 0,
+            signature: vec![],
+        }
+    }
+
+    #[test]
+    fn test_window_bounds_challenges() {
+        let mut tracker = DisputeTracker::new();
+        tracker.set_window_secs(60);
+        let deadline = tracker.open_window(([2; 32], PoolType::Subscribed), [7; 32], 1000);
+        assert_eq!(deadline, 1060);
+
+        assert!(tracker.accepts(&challenge(7), 1060));
+        assert!(!tracker.accepts(&challenge(7), 1061));
+        // Not the announced root
+        assert!(!tracker.accepts(&challenge(8), 1000));
+    }
+
+    #[test]
+    fn test_dispute_outcomes() {
+        let mut tracker = DisputeTracker::new();
+        tracker.open_window(([2; 32], PoolType::Subscribed), [7; 32], 1000);
+
+        tracker.open(&challenge(7), 1001);
+        let link = |prev: u8, new: u8, batch, cumulative| ProofChainLink {
+            prev_root: [prev; 32], new_root: [new; 32], batch_bytes: batch, cumulative_bytes: cumulative, timestamp: 0,
+        };
+        let backed = response(vec![link(0, 4, 100, 100), link(4, 5, 50, 150)], 150);
+        assert_eq!(tracker.close(&challenge(7), &backed, 1002).unwrap().status, DisputeStatus::Resolved);
+
+        // Aggregator never saw the relay's last proof
+        tracker.open(&challenge(7), 1003);
+        let short = response(vec![link(0, 4, 100, 100)], 100);
+        assert_eq!(tracker.close(&challenge(7), &short, 1004).unwrap().status, DisputeStatus::Unresolved);
+
+        // Never answered
+        tracker.open(&challenge(7), 1005);
+        assert!(tracker.expire(1000 + DEFAULT_CHALLENGE_WINDOW_SECS).is_empty());
+        let expired = tracker.expire(1001 + DEFAULT_CHALLENGE_WINDOW_SECS);
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].status, DisputeStatus::Unresolved);
+        assert_eq!(tracker.disputes().count(), 3);
+    }
+}
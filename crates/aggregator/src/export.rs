@@ -0,0 +1,317 @@
+//! Bandwidth time series export
+//!
+//! Writes the buckets of a [`BandwidthIndex`] as one row per
+//! (relay, pool, pool_type, bucket) to CSV, or to Parquet with the
+//! `parquet` feature, for analysis in notebooks. Rows are streamed series
+//! by series: memory stays bounded by one series (CSV) or one row group
+//! (Parquet), not by the length of the export.
+//!
+//! Columns: `timestamp` (bucket start, unix seconds), `granularity`,
+//! `relay` and `pool` (hex), `pool_type`, `bytes`, `batch_count`.
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use craftnet_core::PublicKey;
+use craftnet_network::PoolType;
+
+use crate::{BandwidthBucket, BandwidthIndex, Granularity};
+
+/// Rows per Parquet row group
+#[cfg(feature = "parquet")]
+const PARQUET_ROW_GROUP: usize = 64 * 1024;
+
+/// Output format of an export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    /// Needs the `parquet` feature
+    Parquet,
+}
+
+impl std::str::FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "parquet" => Ok(Self::Parquet),
+            _ => Err(format!("unknown export format: {} (csv, parquet)", s)),
+        }
+    }
+}
+
+/// Which buckets an export includes
+#[derive(Debug, Clone)]
+pub struct ExportFilter {
+    pub granularity: Granularity,
+    /// First bucket start included (unix seconds)
+    pub start: u64,
+    /// Last bucket start included (unix seconds)
+    pub end: u64,
+    /// Only this relay (None = all)
+    pub relay: Option<PublicKey>,
+    /// Only this pool (None = all)
+    pub pool: Option<PublicKey>,
+}
+
+impl Default for ExportFilter {
+    fn default() -> Self {
+        Self {
+            granularity: Granularity::Hourly,
+            start: 0,
+            end: u64::MAX,
+            relay: None,
+            pool: None,
+        }
+    }
+}
+
+/// One exported row
+struct Row<'a> {
+    relay: &'a PublicKey,
+    pool: &'a PublicKey,
+    pool_type: PoolType,
+    bucket: BandwidthBucket,
+}
+
+fn pool_type_name(pool_type: PoolType) -> &'static str {
+    match pool_type {
+        PoolType::Subscribed => "subscribed",
+        PoolType::Free => "free",
+    }
+}
+
+impl BandwidthIndex {
+    /// Feed every row matching `filter` to `emit`, in (relay, pool,
+    /// pool_type, timestamp) order
+    fn for_each_row<F>(&self, filter: &ExportFilter, mut emit: F) -> io::Result<()>
+    where
+        F: FnMut(Row<'_>) -> io::Result<()>,
+    {
+        let mut keys: Vec<&(PublicKey, PublicKey, PoolType)> = self
+            .series
+            .keys()
+            .filter(|(relay, pool, _)| {
+                filter.relay.as_ref().is_none_or(|r| r == relay) && filter.pool.as_ref().is_none_or(|p| p == pool)
+            })
+            .collect();
+        keys.sort_by_key(|(relay, pool, pool_type)| (*relay, *pool, pool_type_name(*pool_type)));
+
+        for key in keys {
+            let (relay, pool, pool_type) = key;
+            let series = &self.series[key];
+            let mut buckets: BTreeMap<u64, BandwidthBucket> = BTreeMap::new();
+            Self::merge_series_into(series, filter.granularity, filter.start, filter.end, &mut buckets);
+            for bucket in buckets.into_values() {
+                emit(Row { relay, pool, pool_type: *pool_type, bucket })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the buckets matching `filter` as CSV (with a header row).
+    /// Returns the number of data rows written.
+    pub fn export_csv<W: Write>(&self, writer: W, filter: &ExportFilter) -> io::Result<u64> {
+        let mut out = io::BufWriter::new(writer);
+        writeln!(out, "timestamp,granularity,relay,pool,pool_type,bytes,batch_count")?;
+        let granularity = filter.granularity.as_str();
+        let mut rows = 0u64;
+        self.for_each_row(filter, |row| {
+            rows += 1;
+            writeln!(
+                out,
+                "{},{},{},{},{},{},{}",
+                row.bucket.timestamp,
+                granularity,
+                hex::encode(row.relay),
+                hex::encode(row.pool),
+                pool_type_name(row.pool_type),
+                row.bucket.bytes,
+                row.bucket.batch_count,
+            )
+        })?;
+        out.flush()?;
+        Ok(rows)
+    }
+
+    /// Write the buckets matching `filter` as Parquet.
+    /// Returns the number of rows written.
+    #[cfg(feature = "parquet")]
+    pub fn export_parquet<W: Write + Send>(&self, writer: W, filter: &ExportFilter) -> io::Result<u64> {
+        parquet_export::write(self, writer, filter)
+    }
+
+    /// Write the buckets matching `filter` in `format`.
+    /// Returns the number of data rows written.
+    pub fn export<W: Write + Send>(&self, writer: W, format: ExportFormat, filter: &ExportFilter) -> io::Result<u64> {
+        match format {
+            ExportFormat::Csv => self.export_csv(writer, filter),
+            #[cfg(feature = "parquet")]
+            ExportFormat::Parquet => self.export_parquet(writer, filter),
+            #[cfg(not(feature = "parquet"))]
+            ExportFormat::Parquet => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Parquet export needs the `parquet` feature",
+            )),
+        }
+    }
+}
+
+#[cfg(feature = "parquet")]
+mod parquet_export {
+    use std::io::{self, Write};
+    use std::sync::Arc;
+
+    use parquet::data_type::{ByteArray, ByteArrayType, Int32Type, Int64Type};
+    use parquet::errors::ParquetError;
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+
+    use super::{pool_type_name, ExportFilter, PARQUET_ROW_GROUP};
+    use crate::BandwidthIndex;
+
+    const SCHEMA: &str = "
+        message bandwidth {
+            REQUIRED INT64 timestamp;
+            REQUIRED BYTE_ARRAY granularity (UTF8);
+            REQUIRED BYTE_ARRAY relay (UTF8);
+            REQUIRED BYTE_ARRAY pool (UTF8);
+            REQUIRED BYTE_ARRAY pool_type (UTF8);
+            REQUIRED INT64 bytes;
+            REQUIRED INT32 batch_count;
+        }
+    ";
+
+    fn to_io(e: ParquetError) -> io::Error {
+        io::Error::other(e)
+    }
+
+    /// Columns of one row group
+    #[derive(Default)]
+    struct Columns {
+        timestamp: Vec<i64>,
+        relay: Vec<ByteArray>,
+        pool: Vec<ByteArray>,
+        pool_type: Vec<ByteArray>,
+        bytes: Vec<i64>,
+        batch_count: Vec<i32>,
+    }
+
+    impl Columns {
+        fn len(&self) -> usize {
+            self.timestamp.len()
+        }
+
+        fn flush<W: Write + Send>(&mut self, writer: &mut SerializedFileWriter<W>, granularity: &str) -> io::Result<()> {
+            if self.len() == 0 {
+                return Ok(());
+            }
+            let granularity = vec![ByteArray::from(granularity); self.len()];
+            let mut row_group = writer.next_row_group().map_err(to_io)?;
+            let mut index = 0;
+            while let Some(mut column) = row_group.next_column().map_err(to_io)? {
+                match index {
+                    0 => column.typed::<Int64Type>().write_batch(&self.timestamp, None, None),
+                    1 => column.typed::<ByteArrayType>().write_batch(&granularity, None, None),
+                    2 => column.typed::<ByteArrayType>().write_batch(&self.relay, None, None),
+                    3 => column.typed::<ByteArrayType>().write_batch(&self.pool, None, None),
+                    4 => column.typed::<ByteArrayType>().write_batch(&self.pool_type, None, None),
+                    5 => column.typed::<Int64Type>().write_batch(&self.bytes, None, None),
+                    _ => column.typed::<Int32Type>().write_batch(&self.batch_count, None, None),
+                }
+                .map_err(to_io)?;
+                column.close().map_err(to_io)?;
+                index += 1;
+            }
+            row_group.close().map_err(to_io)?;
+            *self = Self::default();
+            Ok(())
+        }
+    }
+
+    pub(super) fn write<W: Write + Send>(index: &BandwidthIndex, writer: W, filter: &ExportFilter) -> io::Result<u64> {
+        let schema = Arc::new(parse_message_type(SCHEMA).map_err(to_io)?);
+        let props = Arc::new(WriterProperties::builder().build());
+        let mut file = SerializedFileWriter::new(writer, schema, props).map_err(to_io)?;
+        let granularity = filter.granularity.as_str();
+
+        let mut columns = Columns::default();
+        let mut rows = 0u64;
+        index.for_each_row(filter, |row| {
+            columns.timestamp.push(row.bucket.timestamp as i64);
+            columns.relay.push(ByteArray::from(hex::encode(row.relay).as_str()));
+            columns.pool.push(ByteArray::from(hex::encode(row.pool).as_str()));
+            columns.pool_type.push(ByteArray::from(pool_type_name(row.pool_type)));
+            columns.bytes.push(row.bucket.bytes as i64);
+            columns.batch_count.push(row.bucket.batch_count as i32);
+            rows += 1;
+            if columns.len() >= PARQUET_ROW_GROUP {
+                columns.flush(&mut file, granularity)?;
+            }
+            Ok(())
+        })?;
+        columns.flush(&mut file, granularity)?;
+        file.close().map_err(to_io)?;
+        Ok(rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index() -> BandwidthIndex {
+        let mut index = BandwidthIndex::new();
+        // Two hours of relay 1 on pool 9, one hour of relay 2
+        index.record_proof(&[1; 32], &[9; 32], PoolType::Subscribed, 100, 7200);
+        index.record_proof(&[1; 32], &[9; 32], PoolType::Subscribed, 50, 7300);
+        index.record_proof(&[1; 32], &[9; 32], PoolType::Subscribed, 25, 10800);
+        index.record_proof(&[2; 32], &[9; 32], PoolType::Free, 10, 7200);
+        index
+    }
+
+    fn csv(index: &BandwidthIndex, filter: &ExportFilter) -> (u64, Vec<String>) {
+        let mut out = Vec::new();
+        let rows = index.export_csv(&mut out, filter).unwrap();
+        (rows, String::from_utf8(out).unwrap().lines().map(str::to_string).collect())
+    }
+
+    #[test]
+    fn test_csv_hourly_rows() {
+        let (rows, lines) = csv(&index(), &ExportFilter::default());
+        assert_eq!(rows, 3);
+        assert_eq!(lines[0], "timestamp,granularity,relay,pool,pool_type,bytes,batch_count");
+        assert_eq!(
+            lines[1],
+            format!("7200,hourly,{},{},subscribed,150,2", hex::encode([1u8; 32]), hex::encode([9u8; 32])),
+        );
+        assert!(lines[2].starts_with("10800,hourly,"));
+        assert!(lines[3].contains(",free,10,1"));
+    }
+
+    #[test]
+    fn test_csv_filters() {
+        let index = index();
+        let filter = ExportFilter { relay: Some([2; 32]), ..Default::default() };
+        assert_eq!(csv(&index, &filter).0, 1);
+
+        let filter = ExportFilter { start: 10000, end: 20000, ..Default::default() };
+        let (rows, lines) = csv(&index, &filter);
+        assert_eq!(rows, 1);
+        assert!(lines[1].starts_with("10800,"));
+
+        let filter = ExportFilter { granularity: Granularity::Daily, relay: Some([1; 32]), ..Default::default() };
+        let (rows, lines) = csv(&index, &filter);
+        assert_eq!(rows, 1);
+        assert!(lines[1].starts_with("0,daily,") && lines[1].ends_with(",175,3"));
+    }
+
+    #[test]
+    fn test_format_parse() {
+        assert_eq!("CSV".parse::<ExportFormat>(), Ok(ExportFormat::Csv));
+        assert_eq!("parquet".parse::<ExportFormat>(), Ok(ExportFormat::Parquet));
+        assert!("xlsx".parse::<ExportFormat>().is_err());
+    }
+}
@@ -10,13 +10,35 @@
 use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::io::{Read as _, Write};
 use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 
 use serde::{Serialize, Deserialize};
 use tracing::{debug, info, warn};
 
-use craftnet_core::PublicKey;
-use craftnet_network::{ProofMessage, PoolType};
-use craftnet_prover::{MerkleProof, MerkleTree};
+use craftnet_core::{system_clock, PublicKey, SharedClock};
+use craftnet_network::{
+    ChallengeResponse, DistributionChallenge, ProofBackfillRequest, ProofChainLink, ProofMessage, PoolType,
+};
+use craftnet_prover::{MerkleMultiProof, MerkleProof, MerkleTree};
+
+use anomaly::AnomalyDetector;
+use dispute::DisputeTracker;
+
+mod analytics;
+mod anomaly;
+mod dispute;
+mod spill;
+pub mod test_vectors;
+#[cfg(feature = "api")]
+pub mod api;
+mod export;
+
+pub use analytics::{PoolThroughput, RelayChurn, RelayRank, WeeklyGrowth, WEEK_SECS};
+pub use anomaly::{AnomalyConfig, AnomalyEvent, AnomalyReason, BatchProofVerifier, QuarantinedProof, ReleaseReason};
+pub use dispute::{Dispute, DisputeStatus, DEFAULT_CHALLENGE_WINDOW_SECS};
+pub use spill::PendingSpill;
+pub use export::{ExportFilter, ExportFormat};
 
 /// Maximum number of pending (out-of-order) proofs per relay per pool.
 /// Prevents unbounded memory growth from misbehaving relays.
@@ -25,6 +47,14 @@ const MAX_PENDING_PER_CHAIN: usize = 16;
 /// Maximum total pending proofs across all chains.
 const MAX_PENDING_TOTAL: usize = 4096;
 
+/// Proofs remembered to recognize copies arriving by another path
+/// (gossip vs direct submission).
+const MAX_RECENT_PROOFS: usize = 65_536;
+
+/// Chains outside the pool filter whose latest proof is kept, so widening
+/// the filter can pick them up without waiting for their next proof
+const MAX_UNFOLLOWED_HEADS: usize = 16_384;
+
 // =========================================================================
 // History ledger types (append-only log)
 // =========================================================================
@@ -70,6 +100,23 @@ user_pubkey: [u8; 32],
 distribution_root: [u8; 32],
         total_bytes: u64,
     },
+    /// A relay challenged its count in an announced distribution
+    DisputeOpened {
+        relay_pubkey: [u8; 32],
+        pool_pubkey: [u8; 32],
+        pool_type: PoolType,
+        distribution_root: [u8; 32],
+        counted_bytes: u64,
+        claimed_bytes: u64,
+    },
+    /// A dispute was answered or its window ran out
+    DisputeClosed {
+        relay_pubkey: [u8; 32],
+        pool_pubkey: [u8; 32],
+        pool_type: PoolType,
+        distribution_root: [u8; 32],
+        status: DisputeStatus,
+    },
 }
 
 /// Append-only history write buffer.
@@ -95,11 +142,7 @@ impl HistoryLog {
         Self { next_seq, buffer: Vec::new() }
     }
 
-    fn append(&mut self, event: HistoryEvent) {
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
+    fn append(&mut self, event: HistoryEvent, now: u64) {
         let entry = HistoryEntry {
             seq: self.next_seq,
             recorded_at: now,
@@ -118,8 +161,7 @@ struct ProofClaim {
     cumulative_bytes: u64,
     /// Latest Merkle root
     latest_root: [u8; 32],
-    /// Unix timestamp of last proof received (used for staleness checks)
-    #[allow(dead_code)]
+    /// Unix timestamp of last proof received (staleness and rate checks)
     last_updated: u64,
 }
 
@@ -152,10 +194,48 @@ impl Distribution {
         let proof = self.tree.proof(index)?;
         Some((proof, index as u32))
     }
+
+    /// Generate one compressed multiproof for several relays.
+    ///
+    /// Relays not in the distribution are skipped. Returns `None` if none
+    /// of them are present.
+    pub fn batch_proof_for_relays(&self, relays: &[PublicKey]) -> Option<DistributionBatchProof> {
+        let indices: Vec<usize> = relays.iter()
+            .filter_map(|relay| self.entries.iter().position(|(r, _)| r == relay))
+            .collect();
+        let proof = self.tree.batch_proofs(&indices)?;
+        let entries = proof.leaf_indices.iter().map(|&i| self.entries[i]).collect();
+        Some(DistributionBatchProof {
+            root: self.root,
+            entries,
+            proof,
+        })
+    }
+}
+
+/// Multiproof for several relays' claims against one distribution root
+#[derive(Debug, Clone)]
+pub struct DistributionBatchProof {
+    /// Distribution Merkle root
+    pub root: [u8; 32],
+    /// Proven (relay_pubkey, cumulative_bytes) entries, in `proof.leaf_indices` order
+    pub entries: Vec<(PublicKey, u64)>,
+    /// Compressed sibling hashes for all entries
+    pub proof: MerkleMultiProof,
+}
+
+impl DistributionBatchProof {
+    /// Check every entry against the root
+    pub fn verify(&self) -> bool {
+        let leaves: Vec<[u8; 32]> = self.entries.iter()
+            .map(|(relay, bytes)| craftnet_prover::merkle_leaf(relay, *bytes))
+            .collect();
+        MerkleTree::verify_batch(&self.root, &leaves, &self.proof)
+    }
 }
 
 /// Network-wide statistics
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct NetworkStats {
     /// Total payload bytes tracked (subscribed + free)
     pub total_bytes: u64,
@@ -167,8 +247,24 @@ pub struct NetworkStats {
     pub subscribed_bytes: u64,
     /// Total free-tier payload bytes
     pub free_bytes: u64,
+    /// Active relays per protocol version (0 = not announced)
+    pub relay_versions: BTreeMap<u16, usize>,
 }
 
+/// Progress of a bandwidth index rebuild from history
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RebuildProgress {
+    /// History file bytes replayed so far
+    pub bytes_read: u64,
+    /// Size of the history file when the rebuild started
+    pub total_bytes: u64,
+    /// Accepted proofs replayed into the index so far
+    pub proofs: u64,
+}
+
+/// Proofs replayed between two progress reports of a rebuild
+const REBUILD_PROGRESS_EVERY: u64 = 100_000;
+
 /// Key identifying a single relay's proof chain within a pool.
 type ChainKey = (PublicKey, PublicKey, PoolType); // (relay, pool, pool_type)
 
@@ -180,6 +276,8 @@ struct AggregatorStateFile {
     pending: HashMap<String, Vec<ProofMessage>>,
     #[serde(default)]
     posted_distributions: Vec<PostedEntry>,
+    #[serde(default)]
+    quarantine: Vec<QuarantinedProof>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -250,18 +348,150 @@ fn parse_chain_key(s: &str) -> Option<ChainKey> {
 // =========================================================================
 
 /// Time-series granularity for bandwidth queries
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Granularity {
-    /// Hourly buckets (kept for 30 days)
+    /// Hourly buckets (kept for `RetentionPolicy::hourly`)
     Hourly,
-    /// Daily buckets (kept indefinitely)
+    /// Daily buckets (kept for `RetentionPolicy::daily`)
     Daily,
+    /// Weekly buckets, starting Monday 00:00 UTC (kept for `RetentionPolicy::weekly`)
+    Weekly,
+    /// Calendar-month buckets (kept for `RetentionPolicy::monthly`)
+    Monthly,
+}
+
+impl Granularity {
+    /// All granularities, finest first
+    pub const ALL: [Granularity; 4] = [Self::Hourly, Self::Daily, Self::Weekly, Self::Monthly];
+
+    /// Start of the bucket containing `ts` (unix seconds, UTC)
+    pub fn floor(self, ts: u64) -> u64 {
+        match self {
+            Self::Hourly => ts - (ts % 3600),
+            Self::Daily => ts - (ts % 86400),
+            // 1970-01-01 was a Thursday; Mondays are 3 days before that grid
+            Self::Weekly => ts.saturating_sub((ts + 3 * 86400) % (7 * 86400)),
+            Self::Monthly => {
+                let day = Self::Daily.floor(ts);
+                day - (day_of_month(day / 86400) - 1) * 86400
+            }
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Hourly => "hourly",
+            Self::Daily => "daily",
+            Self::Weekly => "weekly",
+            Self::Monthly => "monthly",
+        }
+    }
+}
+
+impl std::str::FromStr for Granularity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|g| g.as_str().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("invalid granularity: {} (hourly, daily, weekly, monthly)", s))
+    }
+}
+
+/// Day of the month (1-based) of a day counted from 1970-01-01
+fn day_of_month(days: u64) -> u64 {
+    // Civil-from-days (H. Hinnant), with years starting in March
+    let z = days + 719_468;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    doy - (153 * mp + 2) / 5 + 1
+}
+
+/// How long each bandwidth granularity is kept before it is downsampled
+/// into the next coarser one. Ages count back from the time of compaction.
+///
+/// A bucket moves to the coarser bucket its start falls in, so a week
+/// spanning two months is counted in the month it starts in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Hourly buckets older than this become daily. Default: 30 days.
+    pub hourly: Duration,
+    /// Daily buckets older than this become weekly. Default: 180 days.
+    pub daily: Duration,
+    /// Weekly buckets older than this become monthly. Default: 2 years.
+    pub weekly: Duration,
+    /// Monthly buckets older than this are deleted (None = kept forever).
+    /// Default: None.
+    pub monthly: Option<Duration>,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        const DAY: u64 = 86400;
+        Self {
+            hourly: Duration::from_secs(30 * DAY),
+            daily: Duration::from_secs(180 * DAY),
+            weekly: Duration::from_secs(730 * DAY),
+            monthly: None,
+        }
+    }
+}
+
+impl RetentionPolicy {
+    /// Oldest bucket start kept at `now` (0 = everything is kept)
+    pub fn horizon(&self, now: u64) -> u64 {
+        self.monthly.map_or(0, |age| now.saturating_sub(age.as_secs()))
+    }
+
+    /// Per-tier cutoffs at `now`, never later for a coarser tier
+    fn cutoffs(&self, now: u64) -> [u64; 3] {
+        let hourly = now.saturating_sub(self.hourly.as_secs());
+        let daily = now.saturating_sub(self.daily.as_secs()).min(hourly);
+        let weekly = now.saturating_sub(self.weekly.as_secs()).min(daily);
+        [hourly, daily, weekly]
+    }
+}
+
+/// Which pools an aggregator follows.
+///
+/// Proofs for pools outside the filter are verified and dropped at ingest,
+/// so they take no pool tracker, pending slot, history entry or bandwidth
+/// bucket.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum PoolFilter {
+    /// Every pool on the network
+    #[default]
+    All,
+    /// Only these pool pubkeys
+    Pools(HashSet<PublicKey>),
+}
+
+impl PoolFilter {
+    /// Whether proofs for `pool` are followed
+    pub fn allows(&self, pool: &PublicKey) -> bool {
+        match self {
+            Self::All => true,
+            Self::Pools(pools) => pools.contains(pool),
+        }
+    }
+
+    /// Whether every pool `other` follows is followed here too
+    pub fn covers(&self, other: &PoolFilter) -> bool {
+        match (self, other) {
+            (Self::All, _) => true,
+            (Self::Pools(_), Self::All) => false,
+            (Self::Pools(ours), Self::Pools(theirs)) => theirs.is_subset(ours),
+        }
+    }
 }
 
 /// A single bandwidth time bucket
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BandwidthBucket {
-    /// Bucket start timestamp (floored to hour or day boundary, unix seconds)
+    /// Bucket start timestamp (floored to its granularity, unix seconds)
     pub timestamp: u64,
     /// Total payload bytes in this bucket
     pub bytes: u64,
@@ -269,28 +499,61 @@ pub struct BandwidthBucket {
     pub batch_count: u32,
 }
 
-/// Time-series bandwidth data for a single (relay, pool, pool_type) key.
+/// Time-series bandwidth data, one bucket map per granularity.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 struct BandwidthTimeSeries {
-    /// Hourly buckets (last 30 days, compacted to daily after)
+    /// Hourly buckets (recent; downsampled to daily per the retention policy)
     hourly: BTreeMap<u64, BandwidthBucket>,
-    /// Daily buckets (indefinite retention)
+    /// Daily buckets
     daily: BTreeMap<u64, BandwidthBucket>,
+    /// Weekly buckets
+    #[serde(default)]
+    weekly: BTreeMap<u64, BandwidthBucket>,
+    /// Monthly buckets
+    #[serde(default)]
+    monthly: BTreeMap<u64, BandwidthBucket>,
+}
+
+impl BandwidthTimeSeries {
+    fn tier(&self, granularity: Granularity) -> &BTreeMap<u64, BandwidthBucket> {
+        match granularity {
+            Granularity::Hourly => &self.hourly,
+            Granularity::Daily => &self.daily,
+            Granularity::Weekly => &self.weekly,
+            Granularity::Monthly => &self.monthly,
+        }
+    }
+
+    /// Buckets of every granularity, finest first
+    fn tiers(&self) -> impl Iterator<Item = (Granularity, &BTreeMap<u64, BandwidthBucket>)> + '_ {
+        Granularity::ALL.into_iter().map(|g| (g, self.tier(g)))
+    }
+
+    /// Downsample per the tier cutoffs and drop buckets before `horizon`
+    fn apply_retention(&mut self, cutoffs: [u64; 3], horizon: u64) {
+        BandwidthIndex::compact_series(&mut self.hourly, &mut self.daily, cutoffs[0], Granularity::Daily);
+        BandwidthIndex::compact_series(&mut self.daily, &mut self.weekly, cutoffs[1], Granularity::Weekly);
+        BandwidthIndex::compact_series(&mut self.weekly, &mut self.monthly, cutoffs[2], Granularity::Monthly);
+        self.monthly = self.monthly.split_off(&horizon);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.tiers().all(|(_, buckets)| buckets.is_empty())
+    }
 }
 
 /// In-memory bandwidth index for fast time-series queries.
 ///
 /// Records bandwidth per (relay, pool, pool_type) and at the network level.
-/// Hourly buckets older than 30 days are compacted into daily buckets.
+/// Buckets are downsampled hourly → daily → weekly → monthly as they age
+/// (see [`RetentionPolicy`]).
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct BandwidthIndex {
     /// Per-(relay, pool, pool_type) time series
     #[serde(skip)]
     series: HashMap<(PublicKey, PublicKey, PoolType), BandwidthTimeSeries>,
-    /// Network-wide hourly buckets
-    network_hourly: BTreeMap<u64, BandwidthBucket>,
-    /// Network-wide daily buckets
-    network_daily: BTreeMap<u64, BandwidthBucket>,
+    /// Network-wide buckets
+    network: BandwidthTimeSeries,
 }
 
 impl BandwidthIndex {
@@ -300,12 +563,12 @@ impl BandwidthIndex {
 
     /// Floor a timestamp to the start of its hour (3600-second boundary).
     fn floor_hour(ts: u64) -> u64 {
-        ts - (ts % 3600)
+        Granularity::Hourly.floor(ts)
     }
 
     /// Floor a timestamp to the start of its day (86400-second boundary).
     fn floor_day(ts: u64) -> u64 {
-        ts - (ts % 86400)
+        Granularity::Daily.floor(ts)
     }
 
     /// Record a proof's bandwidth into the index.
@@ -319,13 +582,13 @@ impl BandwidthIndex {
     ) {
         let hour = Self::floor_hour(proof_timestamp);
 
-        // Update per-key series (hourly only; daily is populated via compact())
+        // Update per-key series (hourly only; coarser tiers are populated by compaction)
         let series = self.series.entry((*relay, *pool, pool_type))
             .or_default();
         Self::upsert_bucket(&mut series.hourly, hour, batch_bytes);
 
         // Update network-wide (hourly only)
-        Self::upsert_bucket(&mut self.network_hourly, hour, batch_bytes);
+        Self::upsert_bucket(&mut self.network.hourly, hour, batch_bytes);
     }
 
     /// Upsert a bucket: increment bytes + batch_count if exists, create otherwise.
@@ -343,28 +606,41 @@ impl BandwidthIndex {
     /// Removes compacted hourly entries.
     pub fn compact(&mut self, cutoff: u64) {
         for series in self.series.values_mut() {
-            Self::compact_series(&mut series.hourly, &mut series.daily, cutoff);
+            Self::compact_series(&mut series.hourly, &mut series.daily, cutoff, Granularity::Daily);
         }
-        Self::compact_series(&mut self.network_hourly, &mut self.network_daily, cutoff);
+        Self::compact_series(&mut self.network.hourly, &mut self.network.daily, cutoff, Granularity::Daily);
     }
 
+    /// Downsample every tier as of `now` per `policy` (hourly → daily →
+    /// weekly → monthly) and delete monthly buckets beyond its horizon.
+    pub fn compact_with_policy(&mut self, now: u64, policy: &RetentionPolicy) {
+        let cutoffs = policy.cutoffs(now);
+        let horizon = policy.horizon(now);
+        for series in self.series.values_mut() {
+            series.apply_retention(cutoffs, horizon);
+        }
+        self.series.retain(|_, series| !series.is_empty());
+        self.network.apply_retention(cutoffs, horizon);
+    }
+
+    /// Move buckets of `fine` starting before `cutoff` into the `coarse`
+    /// buckets of `granularity` they fall in.
     fn compact_series(
-        hourly: &mut BTreeMap<u64, BandwidthBucket>,
-        daily: &mut BTreeMap<u64, BandwidthBucket>,
+        fine: &mut BTreeMap<u64, BandwidthBucket>,
+        coarse: &mut BTreeMap<u64, BandwidthBucket>,
         cutoff: u64,
+        granularity: Granularity,
     ) {
-        let old_keys: Vec<u64> = hourly.range(..cutoff).map(|(&k, _)| k).collect();
-        for key in old_keys {
-            if let Some(bucket) = hourly.remove(&key) {
-                let day = Self::floor_day(key);
-                let daily_bucket = daily.entry(day).or_insert(BandwidthBucket {
-                    timestamp: day,
-                    bytes: 0,
-                    batch_count: 0,
-                });
-                daily_bucket.bytes += bucket.bytes;
-                daily_bucket.batch_count += bucket.batch_count;
-            }
+        let kept = fine.split_off(&cutoff);
+        for (key, bucket) in std::mem::replace(fine, kept) {
+            let start = granularity.floor(key);
+            let coarse_bucket = coarse.entry(start).or_insert(BandwidthBucket {
+                timestamp: start,
+                bytes: 0,
+                batch_count: 0,
+            });
+            coarse_bucket.bytes += bucket.bytes;
+            coarse_bucket.batch_count += bucket.batch_count;
         }
     }
 
@@ -389,7 +665,7 @@ impl BandwidthIndex {
                     continue;
                 }
             }
-            Self::merge_series_into(&series.hourly, &series.daily, granularity, start, end, &mut result);
+            Self::merge_series_into(series, granularity, start, end, &mut result);
         }
 
         result.into_values().collect()
@@ -403,7 +679,7 @@ impl BandwidthIndex {
         granularity: Granularity,
     ) -> Vec<BandwidthBucket> {
         let mut result: BTreeMap<u64, BandwidthBucket> = BTreeMap::new();
-        Self::merge_series_into(&self.network_hourly, &self.network_daily, granularity, start, end, &mut result);
+        Self::merge_series_into(&self.network, granularity, start, end, &mut result);
         result.into_values().collect()
     }
 
@@ -423,7 +699,7 @@ impl BandwidthIndex {
                 continue;
             }
             let mut merged: BTreeMap<u64, BandwidthBucket> = BTreeMap::new();
-            Self::merge_series_into(&series.hourly, &series.daily, granularity, start, end, &mut merged);
+            Self::merge_series_into(series, granularity, start, end, &mut merged);
             let buckets: Vec<BandwidthBucket> = merged.into_values().collect();
             if !buckets.is_empty() {
                 result.insert(*relay, buckets);
@@ -447,60 +723,40 @@ impl BandwidthIndex {
             if r != relay {
                 continue;
             }
-            Self::merge_series_into(&series.hourly, &series.daily, granularity, start, end, &mut result);
+            Self::merge_series_into(series, granularity, start, end, &mut result);
         }
 
         result.into_values().collect()
     }
 
-    /// Merge hourly + daily data into a result map for the requested granularity.
-    /// For Hourly: returns hourly buckets directly.
-    /// For Daily: merges compacted daily buckets with non-compacted hourly (aggregated by day).
+    /// Merge a series into a result map for the requested granularity.
+    ///
+    /// Buckets of the requested granularity and of every finer one are
+    /// aggregated into buckets of the requested granularity. Coarser tiers
+    /// can't be split, so e.g. an hourly query doesn't see compacted data.
     fn merge_series_into(
-        hourly: &BTreeMap<u64, BandwidthBucket>,
-        daily: &BTreeMap<u64, BandwidthBucket>,
+        series: &BandwidthTimeSeries,
         granularity: Granularity,
         start: u64,
         end: u64,
         result: &mut BTreeMap<u64, BandwidthBucket>,
     ) {
-        match granularity {
-            Granularity::Hourly => {
-                for (_, bucket) in hourly.range(start..=end) {
-                    let entry = result.entry(bucket.timestamp).or_insert(BandwidthBucket {
-                        timestamp: bucket.timestamp,
-                        bytes: 0,
-                        batch_count: 0,
-                    });
-                    entry.bytes += bucket.bytes;
-                    entry.batch_count += bucket.batch_count;
-                }
+        for (tier, buckets) in series.tiers() {
+            if tier > granularity {
+                break;
             }
-            Granularity::Daily => {
-                // First: compacted daily buckets
-                for (_, bucket) in daily.range(start..=end) {
-                    let entry = result.entry(bucket.timestamp).or_insert(BandwidthBucket {
-                        timestamp: bucket.timestamp,
-                        bytes: 0,
-                        batch_count: 0,
-                    });
-                    entry.bytes += bucket.bytes;
-                    entry.batch_count += bucket.batch_count;
-                }
-                // Then: non-compacted hourly buckets, aggregated by day
-                for (_, bucket) in hourly.range(start..=end) {
-                    let day = Self::floor_day(bucket.timestamp);
-                    if day < start || day > end {
-                        continue;
-                    }
-                    let entry = result.entry(day).or_insert(BandwidthBucket {
-                        timestamp: day,
-                        bytes: 0,
-                        batch_count: 0,
-                    });
-                    entry.bytes += bucket.bytes;
-                    entry.batch_count += bucket.batch_count;
+            for (_, bucket) in buckets.range(start..=end) {
+                let ts = granularity.floor(bucket.timestamp);
+                if ts < start || ts > end {
+                    continue;
                 }
+                let entry = result.entry(ts).or_insert(BandwidthBucket {
+                    timestamp: ts,
+                    bytes: 0,
+                    batch_count: 0,
+                });
+                entry.bytes += bucket.bytes;
+                entry.batch_count += bucket.batch_count;
             }
         }
     }
@@ -521,10 +777,41 @@ pub struct Aggregator {
     pending: HashMap<ChainKey, VecDeque<ProofMessage>>,
     /// Total count of pending proofs across all chains (for global cap).
     pending_total: usize,
+    /// Unix time each chain with pending proofs last made progress
+    stalled_since: HashMap<ChainKey, u64>,
     /// Append-only history log (the aggregator's "blockchain")
     history: HistoryLog,
-    /// In-memory bandwidth time-series index (hourly + daily buckets)
+    /// In-memory bandwidth time-series index (hourly → monthly buckets)
     bandwidth: BandwidthIndex,
+    /// How long each bandwidth granularity (and the history) is kept
+    retention: RetentionPolicy,
+    /// Rolling batch baselines and thresholds for anomalous proofs
+    anomaly: AnomalyDetector,
+    /// Anomalous proofs held back, at most one per chain
+    quarantine: HashMap<ChainKey, QuarantinedProof>,
+    /// Anomaly events not yet taken by `take_anomaly_events`
+    anomaly_events: Vec<AnomalyEvent>,
+    /// Checks the ZK proofs carried by flagged proofs (None = no ZK release)
+    batch_proof_verifier: Option<Arc<dyn BatchProofVerifier>>,
+    /// Latest protocol version each relay announced with its proofs
+    relay_versions: HashMap<PublicKey, u16>,
+    /// Challenge windows of announced distributions and their disputes
+    disputes: DisputeTracker,
+    /// Digests of recently accepted proofs, for dedup
+    recent_proofs: HashSet<[u8; 32]>,
+    /// `recent_proofs` in arrival order, oldest evicted first
+    recent_order: VecDeque<[u8; 32]>,
+    /// Disk overflow for out-of-order proofs beyond the in-memory caps
+    /// (None = drop or reject them)
+    spill: Option<PendingSpill>,
+    /// Pools whose proofs are indexed
+    pool_filter: PoolFilter,
+    /// Latest proof of each chain outside `pool_filter`
+    unfollowed_heads: HashMap<ChainKey, ProofMessage>,
+    /// `unfollowed_heads` keys, oldest evicted first
+    unfollowed_order: VecDeque<ChainKey>,
+    /// Time source for stall tracking, quarantine, history and compaction
+    clock: SharedClock,
 }
 
 impl Aggregator {
@@ -534,33 +821,233 @@ impl Aggregator {
             pools: HashMap::new(),
             pending: HashMap::new(),
             pending_total: 0,
+            stalled_since: HashMap::new(),
             history: HistoryLog::new(),
             bandwidth: BandwidthIndex::new(),
+            retention: RetentionPolicy::default(),
+            anomaly: AnomalyDetector::default(),
+            quarantine: HashMap::new(),
+            anomaly_events: Vec::new(),
+            batch_proof_verifier: None,
+            relay_versions: HashMap::new(),
+            disputes: DisputeTracker::new(),
+            recent_proofs: HashSet::new(),
+            recent_order: VecDeque::new(),
+            spill: None,
+            pool_filter: PoolFilter::All,
+            unfollowed_heads: HashMap::new(),
+            unfollowed_order: VecDeque::new(),
+            clock: system_clock(),
         }
     }
 
-    /// Handle an incoming proof message from gossipsub.
+    /// Handle an incoming proof message from gossipsub or a direct
+    /// submission.
     ///
     /// Verifies the relay signature, ZK proof (if present), and proof chain
     /// (prev_root matches last known root), then updates the pool tracker.
     ///
     /// Out-of-order proofs (prev_root doesn't match yet) are buffered and
     /// automatically replayed when the missing link arrives — like orphan
-    /// block handling in blockchains.
+    /// block handling in blockchains. A proof that was already received
+    /// returns `Duplicate` and changes nothing.
     pub fn handle_proof(&mut self, msg: ProofMessage) -> Result<(), AggregatorError> {
         // Validate signature upfront (reject bad proofs before buffering)
         Self::verify_proof(&msg)?;
 
+        if !self.pool_filter.allows(&msg.pool_pubkey) {
+            self.hold_unfollowed(msg);
+            return Err(AggregatorError::PoolNotFollowed);
+        }
+
+        // A copy of a proof we already hold would look out of order and
+        // sit in the pending buffer for good
+        let proof_id = Self::proof_id(&msg);
+        if self.recent_proofs.contains(&proof_id) || self.is_chain_head(&msg) {
+            return Err(AggregatorError::Duplicate);
+        }
+        let chain_key = (msg.relay_pubkey, msg.pool_pubkey, msg.pool_type);
+        let new_root = msg.new_root;
+        self.apply_or_buffer(msg)?;
+        // A quarantined proof may be resubmitted with a ZK proof to release it
+        if !self.quarantine.get(&chain_key).is_some_and(|q| q.proof.new_root == new_root) {
+            self.remember_proof(proof_id);
+        }
+        Ok(())
+    }
+
+    /// Keep the latest proof of a chain outside the pool filter
+    fn hold_unfollowed(&mut self, msg: ProofMessage) {
+        let chain_key = (msg.relay_pubkey, msg.pool_pubkey, msg.pool_type);
+        if let Some(held) = self.unfollowed_heads.get_mut(&chain_key) {
+            if msg.cumulative_bytes > held.cumulative_bytes {
+                *held = msg;
+            }
+            return;
+        }
+        if self.unfollowed_order.len() >= MAX_UNFOLLOWED_HEADS {
+            if let Some(oldest) = self.unfollowed_order.pop_front() {
+                self.unfollowed_heads.remove(&oldest);
+            }
+        }
+        self.unfollowed_heads.insert(chain_key, msg);
+        self.unfollowed_order.push_back(chain_key);
+    }
+
+    /// Follow only the pools `filter` allows.
+    ///
+    /// Narrowing drops the claims, pending proofs and quarantine of pools
+    /// no longer followed. Widening re-syncs newly followed chains from
+    /// the latest proof seen for each (a proof carries the cumulative
+    /// total, so one is enough); chains without one catch up on their
+    /// next proof. Returns the number of chains re-synced.
+    pub fn set_pool_filter(&mut self, filter: PoolFilter) -> usize {
+        let widened = !self.pool_filter.covers(&filter);
+        self.pool_filter = filter;
+
+        self.pools.retain(|(pool, _), _| self.pool_filter.allows(pool));
+        let mut dropped = 0;
+        self.pending.retain(|(_, pool, _), queue| {
+            let keep = self.pool_filter.allows(pool);
+            if !keep {
+                dropped += queue.len();
+            }
+            keep
+        });
+        self.pending_total = self.pending_total.saturating_sub(dropped);
+        self.quarantine.retain(|(_, pool, _), _| self.pool_filter.allows(pool));
+        let spill = self.spill.as_ref();
+        self.stalled_since.retain(|key, _| {
+            self.pending.contains_key(key) || spill.is_some_and(|s| s.first_pending(key).is_some())
+        });
+
+        if !widened {
+            return 0;
+        }
+        let resync: Vec<ChainKey> = self.unfollowed_order.iter()
+            .filter(|(_, pool, _)| self.pool_filter.allows(pool))
+            .copied()
+            .collect();
+        self.unfollowed_order.retain(|(_, pool, _)| !self.pool_filter.allows(pool));
+        let mut resynced = 0;
+        for chain_key in resync {
+            let Some(msg) = self.unfollowed_heads.remove(&chain_key) else { continue };
+            match self.apply_or_buffer(msg) {
+                Ok(()) => resynced += 1,
+                Err(e) => debug!("Re-sync of relay {} failed: {}", hex::encode(&chain_key.0[..8]), e),
+            }
+        }
+        info!("Pool filter widened: re-synced {} chains", resynced);
+        resynced
+    }
+
+    /// Pools whose proofs are indexed
+    pub fn pool_filter(&self) -> &PoolFilter {
+        &self.pool_filter
+    }
+
+    /// Digest identifying a signed proof (the ZK proof bytes aren't signed
+    /// and don't count)
+    fn proof_id(msg: &ProofMessage) -> [u8; 32] {
+        use sha2::{Digest, Sha256};
+        Sha256::digest(msg.signable_data()).into()
+    }
+
+    /// Whether `msg` is the proof the relay's chain currently ends with
+    fn is_chain_head(&self, msg: &ProofMessage) -> bool {
+        self.pools
+            .get(&(msg.pool_pubkey, msg.pool_type))
+            .and_then(|t| t.relay_claims.get(&msg.relay_pubkey))
+            .is_some_and(|claim| claim.latest_root == msg.new_root)
+    }
+
+    fn remember_proof(&mut self, proof_id: [u8; 32]) {
+        if self.recent_order.len() >= MAX_RECENT_PROOFS {
+            if let Some(oldest) = self.recent_order.pop_front() {
+                self.recent_proofs.remove(&oldest);
+            }
+        }
+        if self.recent_proofs.insert(proof_id) {
+            self.recent_order.push_back(proof_id);
+        }
+    }
+
+    /// Read time from `clock` (stall tracking, quarantine, history
+    /// timestamps, compaction and pruning) instead of the system clock
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+    }
+
+    /// The clock the aggregator reads time from
+    pub fn clock(&self) -> &SharedClock {
+        &self.clock
+    }
+
+    /// Spill out-of-order proofs to `spill` once the in-memory pending
+    /// buffer is full, instead of dropping or rejecting them. Proofs an
+    /// earlier run spilled are replayed as their chains catch up.
+    pub fn set_pending_spill(&mut self, spill: PendingSpill) {
+        let now = self.clock.unix_now();
+        for chain_key in spill.chains() {
+            self.stalled_since.entry(*chain_key).or_insert(now);
+        }
+        self.spill = Some(spill);
+    }
+
+    /// Number of out-of-order proofs buffered in memory
+    pub fn pending_count(&self) -> usize {
+        self.pending_total
+    }
+
+    /// Number of proofs spilled to disk
+    pub fn spilled_count(&self) -> usize {
+        self.spill.as_ref().map_or(0, PendingSpill::len)
+    }
+
+    /// Write the spill index, so a restart finds spilled proofs without
+    /// rescanning segments
+    pub fn flush_pending_spill(&mut self) {
+        if let Some(ref mut spill) = self.spill {
+            if let Err(e) = spill.flush() {
+                warn!("Failed to write pending spill index: {}", e);
+            }
+        }
+    }
+
+    /// Apply a verified proof, or buffer it if it is out of order
+    fn apply_or_buffer(&mut self, msg: ProofMessage) -> Result<(), AggregatorError> {
         // Try to apply. If out-of-order, buffer it.
         let chain_key = (msg.relay_pubkey, msg.pool_pubkey, msg.pool_type);
-        match self.try_apply_proof(&msg) {
+        match self.try_apply_proof(&msg, true) {
             Ok(()) => {
                 // Success — drain any pending proofs that now chain from this one
                 self.drain_pending(chain_key);
                 Ok(())
             }
+            // Held for confirmation — like buffering, not a rejection
+            Err(AggregatorError::Quarantined) => Ok(()),
             Err(AggregatorError::ChainBreak) => {
-                // Out of order — buffer for later replay
+                // Out of order — buffer for later replay, on disk once
+                // memory is full
+                let now = self.clock.unix_now();
+                let queued = self.pending.get(&chain_key).map_or(0, VecDeque::len);
+                if queued >= MAX_PENDING_PER_CHAIN || self.pending_total >= MAX_PENDING_TOTAL {
+                    if let Some(ref mut spill) = self.spill {
+                        match spill.push(&msg) {
+                            Ok(()) => {
+                                debug!(
+                                    "Spilled out-of-order proof for relay {} on pool {} ({} on disk)",
+                                    hex::encode(&msg.relay_pubkey[..8]),
+                                    hex::encode(&msg.pool_pubkey[..8]),
+                                    spill.len(),
+                                );
+                                self.stalled_since.entry(chain_key).or_insert(now);
+                                return Ok(());
+                            }
+                            Err(e) => warn!("Failed to spill pending proof: {}", e),
+                        }
+                    }
+                }
                 let queue = self.pending.entry(chain_key).or_insert_with(VecDeque::new);
                 if queue.len() >= MAX_PENDING_PER_CHAIN {
                     warn!(
@@ -584,6 +1071,7 @@ impl Aggregator {
                 );
                 queue.push_back(msg);
                 self.pending_total += 1;
+                self.stalled_since.entry(chain_key).or_insert(now);
                 Ok(())
             }
             Err(e) => Err(e),
@@ -616,13 +1104,14 @@ impl Aggregator {
     ///
     /// Returns `ChainBreak` if prev_root doesn't match (caller decides
     /// whether to buffer or reject).
-    fn try_apply_proof(&mut self, msg: &ProofMessage) -> Result<(), AggregatorError> {
+    /// Proofs flagged by the anomaly detector are quarantined instead
+    /// (`Quarantined`) unless `check_anomaly` is false or their ZK proof
+    /// verifies.
+    fn try_apply_proof(&mut self, msg: &ProofMessage, check_anomaly: bool) -> Result<(), AggregatorError> {
         let pool_key = (msg.pool_pubkey, msg.pool_type);
-        let pool = self.pools.entry(pool_key).or_insert_with(|| PoolTracker {
-            relay_claims: HashMap::new(),
-        });
+        let existing = self.pools.get(&pool_key).and_then(|t| t.relay_claims.get(&msg.relay_pubkey));
 
-        if let Some(existing) = pool.relay_claims.get(&msg.relay_pubkey) {
+        let prev_timestamp = if let Some(existing) = existing {
             if existing.latest_root != msg.prev_root {
                 return Err(AggregatorError::ChainBreak);
             }
@@ -639,6 +1128,7 @@ impl Aggregator {
                 );
                 return Err(AggregatorError::NonIncreasingCount);
             }
+            Some(existing.last_updated)
         } else {
             // First proof from this relay for this pool — prev_root should be zeros
             if msg.prev_root != [0u8; 32] && msg.cumulative_bytes != msg.batch_bytes {
@@ -648,9 +1138,44 @@ impl Aggregator {
                 );
                 // Accept anyway — we can't verify history we didn't see
             }
+            None
+        };
+
+        let chain_key = (msg.relay_pubkey, msg.pool_pubkey, msg.pool_type);
+        if check_anomaly {
+            if let Some(reason) = self.anomaly.check(msg, prev_timestamp) {
+                let zk_verified = !msg.proof.is_empty()
+                    && self.batch_proof_verifier.as_ref().is_some_and(|v| v.verify(msg));
+                if !zk_verified {
+                    return Err(self.quarantine_proof(msg, reason));
+                }
+                self.anomaly_events.push(AnomalyEvent::Released {
+                    relay: msg.relay_pubkey,
+                    pool: msg.pool_pubkey,
+                    pool_type: msg.pool_type,
+                    new_root: msg.new_root,
+                    via: ReleaseReason::ZkProof,
+                });
+            }
+        }
+        // The chain moves past whatever was quarantined at this link
+        if let Some(held) = self.quarantine.remove(&chain_key) {
+            if held.proof.new_root != msg.new_root {
+                self.anomaly_events.push(AnomalyEvent::Rejected {
+                    relay: msg.relay_pubkey,
+                    pool: msg.pool_pubkey,
+                    pool_type: msg.pool_type,
+                    new_root: held.proof.new_root,
+                    reason: held.reason,
+                });
+            }
         }
+        self.anomaly.observe(msg);
 
         // Update relay claim
+        let pool = self.pools.entry(pool_key).or_insert_with(|| PoolTracker {
+            relay_claims: HashMap::new(),
+        });
         pool.relay_claims.insert(msg.relay_pubkey, ProofClaim {
             cumulative_bytes: msg.cumulative_bytes,
             latest_root: msg.new_root,
@@ -668,7 +1193,7 @@ impl Aggregator {
             prev_root: msg.prev_root,
             new_root: msg.new_root,
             proof_timestamp: msg.timestamp,
-        });
+        }, self.clock.unix_now());
 
         // Record bandwidth in time-series index
         self.bandwidth.record_proof(
@@ -690,6 +1215,157 @@ impl Aggregator {
         Ok(())
     }
 
+    /// Hold an anomalous proof; returns the error `try_apply_proof` reports
+    fn quarantine_proof(&mut self, msg: &ProofMessage, reason: AnomalyReason) -> AggregatorError {
+        let chain_key = (msg.relay_pubkey, msg.pool_pubkey, msg.pool_type);
+        if self.quarantine.get(&chain_key).is_some_and(|q| q.proof.new_root == msg.new_root) {
+            return AggregatorError::Quarantined; // resubmission
+        }
+        if !self.quarantine.contains_key(&chain_key) && self.quarantine.len() >= self.anomaly.config.max_quarantined {
+            warn!("Quarantine full ({}) — rejecting anomalous proof", self.quarantine.len());
+            self.anomaly_events.push(AnomalyEvent::Rejected {
+                relay: msg.relay_pubkey,
+                pool: msg.pool_pubkey,
+                pool_type: msg.pool_type,
+                new_root: msg.new_root,
+                reason,
+            });
+            return AggregatorError::QuarantineFull;
+        }
+
+        warn!(
+            "Quarantined anomalous proof from relay {} on pool {} ({:?}): {:?}",
+            hex::encode(&msg.relay_pubkey[..8]),
+            hex::encode(&msg.pool_pubkey[..8]),
+            msg.pool_type,
+            reason,
+        );
+        self.anomaly_events.push(AnomalyEvent::Quarantined {
+            relay: msg.relay_pubkey,
+            pool: msg.pool_pubkey,
+            pool_type: msg.pool_type,
+            new_root: msg.new_root,
+            reason: reason.clone(),
+        });
+        let quarantined_at = self.clock.unix_now();
+        self.quarantine.insert(chain_key, QuarantinedProof {
+            proof: msg.clone(),
+            reason,
+            quarantined_at,
+            confirmations: Vec::new(),
+        });
+        AggregatorError::Quarantined
+    }
+
+    /// Apply a quarantined proof regardless of the detector
+    fn release_quarantined_proof(&mut self, chain_key: ChainKey, via: ReleaseReason) -> bool {
+        let Some(held) = self.quarantine.remove(&chain_key) else {
+            return false;
+        };
+        match self.try_apply_proof(&held.proof, false) {
+            Ok(()) => {
+                info!(
+                    "Released quarantined proof from relay {} ({:?})",
+                    hex::encode(&chain_key.0[..8]),
+                    via,
+                );
+                self.anomaly_events.push(AnomalyEvent::Released {
+                    relay: chain_key.0,
+                    pool: chain_key.1,
+                    pool_type: chain_key.2,
+                    new_root: held.proof.new_root,
+                    via,
+                });
+                self.drain_pending(chain_key);
+                true
+            }
+            Err(e) => {
+                warn!("Quarantined proof from relay {} no longer applies: {}", hex::encode(&chain_key.0[..8]), e);
+                self.anomaly_events.push(AnomalyEvent::Rejected {
+                    relay: chain_key.0,
+                    pool: chain_key.1,
+                    pool_type: chain_key.2,
+                    new_root: held.proof.new_root,
+                    reason: held.reason,
+                });
+                false
+            }
+        }
+    }
+
+    /// Record that another aggregator (`confirmer`) accepted the quarantined
+    /// proof with `new_root`. Applies it once `confirmations_required`
+    /// distinct aggregators agree; returns true if it was applied.
+    pub fn confirm_quarantined(
+        &mut self,
+        relay: &PublicKey,
+        pool: &PublicKey,
+        pool_type: PoolType,
+        new_root: &[u8; 32],
+        confirmer: PublicKey,
+    ) -> bool {
+        let chain_key = (*relay, *pool, pool_type);
+        let Some(held) = self.quarantine.get_mut(&chain_key) else {
+            return false;
+        };
+        if held.proof.new_root != *new_root {
+            return false;
+        }
+        if !held.confirmations.contains(&confirmer) {
+            held.confirmations.push(confirmer);
+        }
+        if held.confirmations.len() < self.anomaly.config.confirmations_required {
+            return false;
+        }
+        self.release_quarantined_proof(chain_key, ReleaseReason::Confirmed)
+    }
+
+    /// Operator override: apply the proof quarantined for a relay's chain.
+    /// Returns true if it was applied.
+    pub fn release_quarantined(&mut self, relay: &PublicKey, pool: &PublicKey, pool_type: PoolType) -> bool {
+        self.release_quarantined_proof((*relay, *pool, pool_type), ReleaseReason::Operator)
+    }
+
+    /// Operator override: drop the proof quarantined for a relay's chain.
+    /// The relay's later proofs in the pool can't chain until it resubmits.
+    pub fn reject_quarantined(&mut self, relay: &PublicKey, pool: &PublicKey, pool_type: PoolType) -> bool {
+        let Some(held) = self.quarantine.remove(&(*relay, *pool, pool_type)) else {
+            return false;
+        };
+        self.anomaly_events.push(AnomalyEvent::Rejected {
+            relay: *relay,
+            pool: *pool,
+            pool_type,
+            new_root: held.proof.new_root,
+            reason: held.reason,
+        });
+        true
+    }
+
+    /// Proofs currently held by the anomaly detector
+    pub fn quarantined(&self) -> impl Iterator<Item = &QuarantinedProof> {
+        self.quarantine.values()
+    }
+
+    /// Anomaly events since the last call
+    pub fn take_anomaly_events(&mut self) -> Vec<AnomalyEvent> {
+        std::mem::take(&mut self.anomaly_events)
+    }
+
+    pub fn set_anomaly_config(&mut self, config: AnomalyConfig) {
+        self.anomaly.config = config;
+    }
+
+    pub fn anomaly_config(&self) -> &AnomalyConfig {
+        &self.anomaly.config
+    }
+
+    /// Let flagged proofs carrying a ZK proof that `verifier` accepts be
+    /// applied without quarantine
+    pub fn set_batch_proof_verifier(&mut self, verifier: Arc<dyn BatchProofVerifier>) {
+        self.batch_proof_verifier = Some(verifier);
+    }
+
     /// Drain pending proofs that now chain from the current head.
     ///
     /// After a proof is successfully applied, its `new_root` becomes the
@@ -700,26 +1376,34 @@ impl Aggregator {
         loop {
             // Get current chain head
             let pool_key = (pool, pool_type);
-            let current_root = match self.pools.get(&pool_key)
+            let (current_root, current_cumulative) = match self.pools.get(&pool_key)
                 .and_then(|t| t.relay_claims.get(&relay))
             {
-                Some(claim) => claim.latest_root,
+                Some(claim) => (claim.latest_root, claim.cumulative_bytes),
                 None => break,
             };
 
             // Find and remove the first pending proof whose prev_root matches
-            let queue = match self.pending.get_mut(&chain_key) {
-                Some(q) if !q.is_empty() => q,
-                _ => break,
+            let buffered = self.pending.get_mut(&chain_key).and_then(|queue| {
+                let idx = queue.iter().position(|p| p.prev_root == current_root)?;
+                queue.remove(idx)
+            });
+            let msg = match buffered {
+                Some(msg) => {
+                    self.pending_total = self.pending_total.saturating_sub(1);
+                    msg
+                }
+                // Not in memory: maybe spilled to disk
+                None => match self.take_spilled(&chain_key, &current_root, current_cumulative) {
+                    Some(msg) => msg,
+                    None => break,
+                },
             };
 
-            let pos = queue.iter().position(|p| p.prev_root == current_root);
-            let Some(idx) = pos else { break };
-            let msg = queue.remove(idx).unwrap();
-            self.pending_total = self.pending_total.saturating_sub(1);
-
             // Try to apply — should succeed since we matched prev_root
-            match self.try_apply_proof(&msg) {
+            match self.try_apply_proof(&msg, true) {
+                // Held back; the chain waits behind it
+                Err(AggregatorError::Quarantined) => break,
                 Ok(()) => {
                     debug!(
                         "Replayed buffered proof for relay {} on pool {} (cumulative={})",
@@ -744,6 +1428,58 @@ impl Aggregator {
         if self.pending.get(&chain_key).map_or(false, |q| q.is_empty()) {
             self.pending.remove(&chain_key);
         }
+        // The chain moved: whatever is still pending waits afresh
+        let spilled = self.spill.as_ref().is_some_and(|s| s.first_pending(&chain_key).is_some());
+        if self.pending.contains_key(&chain_key) || spilled {
+            self.stalled_since.insert(chain_key, self.clock.unix_now());
+        } else {
+            self.stalled_since.remove(&chain_key);
+        }
+    }
+
+    /// Take the spilled proof of `chain_key` that chains from `root`
+    fn take_spilled(&mut self, chain_key: &ChainKey, root: &[u8; 32], head_cumulative: u64) -> Option<ProofMessage> {
+        let spill = self.spill.as_mut()?;
+        match spill.take_link(chain_key, root, head_cumulative) {
+            Ok(msg) => msg,
+            Err(e) => {
+                warn!("Failed to read spilled proof: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Gaps that have held proofs back for at least `min_stall` as of
+    /// unix time `now`, as requests for the missing segments.
+    ///
+    /// Each request runs from the chain head to the `prev_root` of the
+    /// lowest held-back proof, so applying the answer unblocks the queue.
+    pub fn chain_gaps(&self, now: u64, min_stall: Duration) -> Vec<ProofBackfillRequest> {
+        let mut gaps = Vec::new();
+        for (chain_key, since) in &self.stalled_since {
+            if now.saturating_sub(*since) < min_stall.as_secs() {
+                continue;
+            }
+            let (relay, pool, pool_type) = *chain_key;
+            let Some(head) = self.pools.get(&(pool, pool_type)).and_then(|t| t.relay_claims.get(&relay)) else {
+                continue;
+            };
+            let buffered = self.pending.get(chain_key)
+                .and_then(|q| q.iter().min_by_key(|p| p.cumulative_bytes))
+                .map(|p| (p.cumulative_bytes, p.prev_root));
+            let spilled = self.spill.as_ref().and_then(|s| s.first_pending(chain_key));
+            let Some((_, to_root)) = buffered.into_iter().chain(spilled).min_by_key(|(cumulative, _)| *cumulative) else {
+                continue;
+            };
+            gaps.push(ProofBackfillRequest {
+                relay_pubkey: relay,
+                pool_pubkey: pool,
+                pool_type,
+                from_root: head.latest_root,
+                to_root,
+            });
+        }
+        gaps
     }
 
     /// Build a Merkle distribution for a pool.
@@ -807,6 +1543,19 @@ impl Aggregator {
             .collect()
     }
 
+    /// Get a compressed Merkle multiproof for several relays' claims in a
+    /// pool's current distribution.
+    ///
+    /// Lets one response cover thousands of claimants instead of one full
+    /// sibling path each. Relays without a claim in the pool are skipped.
+    pub fn get_distribution_batch_proof(
+        &self,
+        pool_key: &(PublicKey, PoolType),
+        relays: &[PublicKey],
+    ) -> Option<DistributionBatchProof> {
+        self.build_distribution(pool_key)?.batch_proof_for_relays(relays)
+    }
+
     /// Get a relay's latest chain state for a specific pool.
     ///
     /// Used for chain recovery: a relay that lost its proof state can query
@@ -841,9 +1590,21 @@ impl Aggregator {
         }
 
         stats.active_relays = all_relays.len();
+        for relay in &all_relays {
+            let version = self.relay_versions.get(relay).copied().unwrap_or(0);
+            *stats.relay_versions.entry(version).or_default() += 1;
+        }
         stats
     }
 
+    /// Record the protocol version a relay announced with a proof.
+    ///
+    /// The version travels in an unsigned extension of the proof message,
+    /// so it only feeds statistics.
+    pub fn record_relay_version(&mut self, relay: PublicKey, version: u16) {
+        self.relay_versions.insert(relay, version);
+    }
+
     /// Get free-tier relay statistics (for ecosystem reward distribution)
     pub fn get_free_tier_stats(&self) -> Vec<(PublicKey, u64)> {
         let mut relay_totals: HashMap<PublicKey, u64> = HashMap::new();
@@ -908,14 +1669,21 @@ impl Aggregator {
         self.bandwidth.get_relay_total_bandwidth(relay, start, end, granularity)
     }
 
-    /// Compact hourly bandwidth buckets older than 30 days into daily buckets.
+    /// Downsample aged bandwidth buckets (hourly → daily → weekly →
+    /// monthly) and drop those beyond the retention policy.
     pub fn compact_bandwidth(&mut self) {
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
-        let cutoff = now.saturating_sub(30 * 24 * 3600); // 30 days ago
-        self.bandwidth.compact(cutoff);
+        let now = self.clock.unix_now();
+        self.bandwidth.compact_with_policy(now, &self.retention);
+    }
+
+    /// Set how long bandwidth buckets and history entries are kept.
+    /// Takes effect at the next `compact_bandwidth` / `prune_history`.
+    pub fn set_retention_policy(&mut self, policy: RetentionPolicy) {
+        self.retention = policy;
+    }
+
+    pub fn retention_policy(&self) -> &RetentionPolicy {
+        &self.retention
     }
 
     /// Get a reference to the bandwidth index (for direct access).
@@ -942,7 +1710,7 @@ impl Aggregator {
             distribution_root,
             total_bytes,
             num_relays,
-        });
+        }, self.clock.unix_now());
     }
 
     /// Record a distribution-posted event in the history log.
@@ -956,7 +1724,123 @@ impl Aggregator {
             user_pubkey,
             distribution_root,
             total_bytes,
-        });
+        }, self.clock.unix_now());
+    }
+
+    // =========================================================================
+    // Disputes
+    // =========================================================================
+
+    /// Set how long relays may challenge an announced distribution
+    pub fn set_challenge_window(&mut self, window: Duration) {
+        self.disputes.set_window_secs(window.as_secs());
+    }
+
+    /// How long relays may challenge an announced distribution
+    pub fn challenge_window(&self) -> Duration {
+        Duration::from_secs(self.disputes.window_secs())
+    }
+
+    /// Open the challenge window of a distribution being announced at
+    /// unix time `now`. Returns the challenge deadline.
+    pub fn open_challenge_window(&mut self, pool_key: (PublicKey, PoolType), root: [u8; 32], now: u64) -> u64 {
+        self.disputes.open_window(pool_key, root, now)
+    }
+
+    /// Challenge deadline of a pool's announced distribution (None once
+    /// the window is closed and expired)
+    pub fn challenge_deadline(&self, pool_key: &(PublicKey, PoolType)) -> Option<u64> {
+        self.disputes.deadline(pool_key)
+    }
+
+    /// Accept a relay's signed challenge against an announced distribution
+    /// and record it. The caller answers it with [`Self::proof_chain`] and
+    /// then [`Self::close_dispute`].
+    pub fn handle_challenge(&mut self, challenge: &DistributionChallenge, now: u64) -> Result<(), AggregatorError> {
+        let Ok(sig) = <[u8; 64]>::try_from(challenge.signature.as_slice()) else {
+            return Err(AggregatorError::InvalidSignature);
+        };
+        if !craftec_crypto::verify_signature(&challenge.relay_pubkey, &challenge.signable_data(), &sig) {
+            return Err(AggregatorError::InvalidSignature);
+        }
+        if !self.disputes.accepts(challenge, now) {
+            return Err(AggregatorError::ChallengeWindowClosed);
+        }
+        let dispute = self.disputes.open(challenge, now);
+        info!(
+            "Relay {} disputes its count on pool {}: counted {}, claims {}",
+            hex::encode(&dispute.relay[..8]), hex::encode(&dispute.pool[..8]),
+            dispute.counted_bytes, dispute.claimed_bytes,
+        );
+        self.history.append(HistoryEvent::DisputeOpened {
+            relay_pubkey: challenge.relay_pubkey,
+            pool_pubkey: challenge.pool_pubkey,
+            pool_type: challenge.pool_type,
+            distribution_root: challenge.distribution_root,
+            counted_bytes: challenge.counted_bytes,
+            claimed_bytes: challenge.claimed_bytes,
+        }, self.clock.unix_now());
+        Ok(())
+    }
+
+    /// Close the dispute `response` answers: resolved when the chain backs
+    /// the relay's claim, unresolved otherwise
+    pub fn close_dispute(
+        &mut self,
+        challenge: &DistributionChallenge,
+        response: &ChallengeResponse,
+        now: u64,
+    ) -> Option<DisputeStatus> {
+        let status = self.disputes.close(challenge, response, now)?.status;
+        self.history.append(HistoryEvent::DisputeClosed {
+            relay_pubkey: challenge.relay_pubkey,
+            pool_pubkey: challenge.pool_pubkey,
+            pool_type: challenge.pool_type,
+            distribution_root: challenge.distribution_root,
+            status,
+        }, self.clock.unix_now());
+        Some(status)
+    }
+
+    /// Mark disputes whose window closed unanswered as unresolved
+    pub fn expire_disputes(&mut self, now: u64) {
+        for dispute in self.disputes.expire(now) {
+            warn!(
+                "Dispute of relay {} on pool {} expired unresolved",
+                hex::encode(&dispute.relay[..8]), hex::encode(&dispute.pool[..8]),
+            );
+            self.history.append(HistoryEvent::DisputeClosed {
+                relay_pubkey: dispute.relay,
+                pool_pubkey: dispute.pool,
+                pool_type: dispute.pool_type,
+                distribution_root: dispute.distribution_root,
+                status: dispute.status,
+            }, self.clock.unix_now());
+        }
+    }
+
+    /// Disputes seen since start, oldest first
+    pub fn disputes(&self) -> impl Iterator<Item = &Dispute> {
+        self.disputes.disputes()
+    }
+
+    /// The accepted proofs of a relay's chain for a pool, oldest first, as
+    /// recorded in the history file (flush first to include recent ones)
+    pub fn proof_chain(path: &Path, relay: &PublicKey, pool: &PublicKey, pool_type: PoolType) -> Vec<ProofChainLink> {
+        let (relay, pool) = (*relay, *pool);
+        Self::scan_history(path, |e| matches!(
+            e.event,
+            HistoryEvent::ProofAccepted { relay_pubkey, pool_pubkey, pool_type: t, .. }
+                if relay_pubkey == relay && pool_pubkey == pool && t == pool_type
+        ))
+        .into_iter()
+        .filter_map(|e| match e.event {
+            HistoryEvent::ProofAccepted { prev_root, new_root, batch_bytes, cumulative_bytes, proof_timestamp, .. } => {
+                Some(ProofChainLink { prev_root, new_root, batch_bytes, cumulative_bytes, timestamp: proof_timestamp })
+            }
+            _ => None,
+        })
+        .collect()
     }
 
     /// Current history log height (next sequence number to be assigned).
@@ -1039,29 +1923,44 @@ impl Aggregator {
     fn scan_history<F>(path: &Path, filter: F) -> Vec<HistoryEntry>
     where
         F: Fn(&HistoryEntry) -> bool,
+    {
+        let mut results = Vec::new();
+        Self::for_each_history_entry(path, |entry, _| {
+            if filter(&entry) {
+                results.push(entry);
+            }
+        });
+        results
+    }
+
+    /// Stream every decodable entry of the binary history file to `f`,
+    /// together with the file offset just past it. Nothing is buffered
+    /// beyond the current record.
+    fn for_each_history_entry<F>(path: &Path, mut f: F)
+    where
+        F: FnMut(HistoryEntry, u64),
     {
         let mut file = match std::fs::File::open(path) {
-            Ok(f) => f,
-            Err(_) => return Vec::new(),
+            Ok(f) => std::io::BufReader::new(f),
+            Err(_) => return,
         };
-        let mut results = Vec::new();
+        let mut offset = 0u64;
         let mut len_buf = [0u8; 4];
+        let mut payload = Vec::new();
         loop {
             if file.read_exact(&mut len_buf).is_err() {
                 break; // EOF or read error
             }
             let len = u32::from_le_bytes(len_buf) as usize;
-            let mut payload = vec![0u8; len];
+            payload.resize(len, 0);
             if file.read_exact(&mut payload).is_err() {
                 break; // truncated record
             }
+            offset += 4 + len as u64;
             if let Ok(entry) = bincode::deserialize::<HistoryEntry>(&payload) {
-                if filter(&entry) {
-                    results.push(entry);
-                }
+                f(entry, offset);
             }
         }
-        results
     }
 
     // =========================================================================
@@ -1130,6 +2029,102 @@ impl Aggregator {
         }
     }
 
+    /// Delete the oldest history entries: those recorded before the
+    /// retention horizon, then as many more as needed for the file to fit
+    /// in `max_bytes` (None = no size limit).
+    ///
+    /// Entries keep their sequence numbers. Bandwidth of pruned proofs is
+    /// kept by the live index but is lost to the next rebuild from history.
+    /// Returns the number of bytes removed.
+    pub fn prune_history(&self, path: &Path, max_bytes: Option<u64>) -> std::io::Result<u64> {
+        let Ok(total) = std::fs::metadata(path).map(|m| m.len()) else {
+            return Ok(0);
+        };
+        let now = self.clock.unix_now();
+        let horizon = self.retention.horizon(now);
+        let min_keep_from = max_bytes.map_or(0, |max| total.saturating_sub(max));
+        if horizon == 0 && min_keep_from == 0 {
+            return Ok(0);
+        }
+
+        // First record boundary that satisfies both limits
+        let mut keep_from = None;
+        let mut record_start = 0u64;
+        Self::for_each_history_entry(path, |entry, end| {
+            if keep_from.is_none() && record_start >= min_keep_from && entry.recorded_at >= horizon {
+                keep_from = Some(record_start);
+            }
+            record_start = end;
+        });
+        let keep_from = keep_from.unwrap_or(record_start);
+        if keep_from == 0 {
+            return Ok(0);
+        }
+
+        let mut src = std::fs::File::open(path)?;
+        std::io::Seek::seek(&mut src, std::io::SeekFrom::Start(keep_from))?;
+        let tmp_path = path.with_extension("bin.tmp");
+        let mut tmp = std::fs::File::create(&tmp_path)?;
+        std::io::copy(&mut src, &mut tmp)?;
+        tmp.sync_all()?;
+        std::fs::rename(&tmp_path, path)?;
+        info!("Pruned {} bytes of history from {}", keep_from, path.display());
+        Ok(keep_from)
+    }
+
+    /// Rebuild the bandwidth index from the accepted proofs in a history file.
+    ///
+    /// The index isn't persisted with the state file; a process that only
+    /// loads state (e.g. the standalone API server) replays it from history.
+    pub fn rebuild_bandwidth_from_history(&mut self, path: &Path) {
+        self.rebuild_bandwidth_from_history_with_progress(path, |_| {});
+    }
+
+    /// Like [`rebuild_bandwidth_from_history`](Self::rebuild_bandwidth_from_history),
+    /// reporting progress while the file is replayed.
+    ///
+    /// Buckets depend only on the proofs' own timestamps, so replaying the
+    /// full history gives the same per-relay series the index had before a
+    /// restart. Entries still in the in-memory history buffer (not yet
+    /// flushed) are replayed too. `progress` is called every
+    /// `REBUILD_PROGRESS_EVERY` proofs and once at the end.
+    /// Returns the number of proofs replayed.
+    pub fn rebuild_bandwidth_from_history_with_progress<F>(&mut self, path: &Path, mut progress: F) -> u64
+    where
+        F: FnMut(RebuildProgress),
+    {
+        let total_bytes = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        let mut bandwidth = BandwidthIndex::new();
+        let mut status = RebuildProgress { bytes_read: 0, total_bytes, proofs: 0 };
+
+        let replay = |event: &HistoryEvent, bandwidth: &mut BandwidthIndex, status: &mut RebuildProgress| {
+            if let HistoryEvent::ProofAccepted {
+                relay_pubkey, pool_pubkey, pool_type, batch_bytes, proof_timestamp, ..
+            } = event {
+                bandwidth.record_proof(relay_pubkey, pool_pubkey, *pool_type, *batch_bytes, *proof_timestamp);
+                status.proofs += 1;
+                true
+            } else {
+                false
+            }
+        };
+
+        Self::for_each_history_entry(path, |entry, offset| {
+            status.bytes_read = offset;
+            if replay(&entry.event, &mut bandwidth, &mut status) && status.proofs % REBUILD_PROGRESS_EVERY == 0 {
+                progress(status);
+            }
+        });
+        for entry in &self.history.buffer {
+            replay(&entry.event, &mut bandwidth, &mut status);
+        }
+        progress(status);
+
+        self.bandwidth = bandwidth;
+        self.compact_bandwidth();
+        status.proofs
+    }
+
     /// Set the history sequence counter (call after recover_history_seq on startup).
     pub fn set_history_seq(&mut self, next_seq: u64) {
         self.history = HistoryLog::with_seq(next_seq);
@@ -1171,6 +2166,7 @@ impl Aggregator {
             pools: pools_map,
             pending: pending_map,
             posted_distributions: posted_entries,
+            quarantine: self.quarantine.values().cloned().collect(),
         };
 
         let json = match serde_json::to_string_pretty(&state_file) {
@@ -1250,21 +2246,46 @@ impl Aggregator {
             posted.insert(pubkey);
         }
 
+        let quarantine: HashMap<ChainKey, QuarantinedProof> = state_file.quarantine.into_iter()
+            .map(|q| ((q.proof.relay_pubkey, q.proof.pool_pubkey, q.proof.pool_type), q))
+            .collect();
+
         info!(
-            "Loaded aggregator state: {} pools, {} pending chains ({} proofs), {} posted distributions from {}",
+            "Loaded aggregator state: {} pools, {} pending chains ({} proofs), {} quarantined, {} posted distributions from {}",
             pools.len(),
             pending.len(),
             pending_total,
+            quarantine.len(),
             posted.len(),
             path.display(),
         );
 
+        // Gaps found in a loaded state count from now
+        let clock = system_clock();
+        let now = clock.unix_now();
+        let stalled_since = pending.keys().map(|key| (*key, now)).collect();
+
         let agg = Self {
             pools,
             pending,
             pending_total,
+            stalled_since,
             history: HistoryLog::new(),
             bandwidth: BandwidthIndex::new(),
+            retention: RetentionPolicy::default(),
+            anomaly: AnomalyDetector::default(),
+            quarantine,
+            anomaly_events: Vec::new(),
+            batch_proof_verifier: None,
+            relay_versions: HashMap::new(),
+            disputes: DisputeTracker::new(),
+            recent_proofs: HashSet::new(),
+            recent_order: VecDeque::new(),
+            spill: None,
+            pool_filter: PoolFilter::All,
+            unfollowed_heads: HashMap::new(),
+            unfollowed_order: VecDeque::new(),
+            clock,
         };
 
         Ok((agg, posted))
@@ -1321,6 +2342,21 @@ pub enum AggregatorError {
 
     #[error("Invalid relay signature")]
     InvalidSignature,
+
+    #[error("Proof quarantined as anomalous")]
+    Quarantined,
+
+    #[error("Anomalous proof rejected: quarantine full")]
+    QuarantineFull,
+
+    #[error("Challenge outside the distribution's challenge window")]
+    ChallengeWindowClosed,
+
+    #[error("Proof already received")]
+    Duplicate,
+
+    #[error("Pool not followed by this aggregator")]
+    PoolNotFollowed,
 }
 
 #[cfg(test)]
@@ -1460,6 +2496,114 @@ mod tests {
         assert_eq!(usage[0].1, 100);
     }
 
+    #[test]
+    fn test_duplicate_proof_ignored() {
+        let mut agg = new_agg();
+
+        let msg1 = make_proof(1, 2, PoolType::Subscribed, 100, 100, [0u8; 32], [0xAA; 32]);
+        let msg2 = make_proof(1, 2, PoolType::Subscribed, 50, 150, [0xAA; 32], [0xBB; 32]);
+        agg.handle_proof(msg1.clone()).unwrap();
+        agg.handle_proof(msg2.clone()).unwrap();
+
+        // Both arrive again by the other path: neither is buffered as out of order
+        assert!(matches!(agg.handle_proof(msg1.clone()), Err(AggregatorError::Duplicate)));
+        assert!(matches!(agg.handle_proof(msg2.clone()), Err(AggregatorError::Duplicate)));
+        assert_eq!(agg.pending_total, 0);
+        assert_eq!(agg.get_pool_usage(&([2u8; 32], PoolType::Subscribed))[0].1, 150);
+
+        // After a restart the chain head is still recognized
+        agg.recent_proofs.clear();
+        agg.recent_order.clear();
+        assert!(matches!(agg.handle_proof(msg2), Err(AggregatorError::Duplicate)));
+    }
+
+    #[test]
+    fn test_chain_gap_reported_after_stall() {
+        let mut agg = new_agg();
+        agg.handle_proof(make_proof(1, 2, PoolType::Subscribed, 100, 100, [0u8; 32], [0xAA; 32])).unwrap();
+        // [0xAA] -> [0xBB] went missing; the next two wait behind the gap
+        agg.handle_proof(make_proof(1, 2, PoolType::Subscribed, 50, 250, [0xCC; 32], [0xDD; 32])).unwrap();
+        agg.handle_proof(make_proof(1, 2, PoolType::Subscribed, 50, 200, [0xBB; 32], [0xCC; 32])).unwrap();
+
+        let now = system_clock().unix_now();
+        assert!(agg.chain_gaps(now, Duration::from_secs(60)).is_empty());
+        let gaps = agg.chain_gaps(now + 60, Duration::from_secs(60));
+        assert_eq!(gaps.len(), 1);
+        assert_eq!(gaps[0].from_root, [0xAA; 32]);
+        assert_eq!(gaps[0].to_root, [0xBB; 32]);
+
+        // The backfilled link unblocks the queue and closes the gap
+        agg.handle_proof(make_proof(1, 2, PoolType::Subscribed, 50, 150, [0xAA; 32], [0xBB; 32])).unwrap();
+        assert_eq!(agg.get_pool_usage(&([2u8; 32], PoolType::Subscribed))[0].1, 250);
+        assert!(agg.chain_gaps(now + 3600, Duration::from_secs(60)).is_empty());
+    }
+
+    #[test]
+    fn test_sim_clock_drives_stalls_and_history() {
+        let dir = std::env::temp_dir().join(format!("craftnet-agg-simclock-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let clock = Arc::new(craftnet_core::SimClock::new(1_000_000));
+        let mut agg = new_agg();
+        agg.set_clock(clock.clone());
+        agg.handle_proof(make_proof(1, 2, PoolType::Subscribed, 100, 100, [0u8; 32], [0xAA; 32])).unwrap();
+        agg.handle_proof(make_proof(1, 2, PoolType::Subscribed, 50, 200, [0xBB; 32], [0xCC; 32])).unwrap();
+
+        // The gap opened at virtual time, not wall-clock time
+        assert!(agg.chain_gaps(1_000_059, Duration::from_secs(60)).is_empty());
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(agg.chain_gaps(clock.unix_now(), Duration::from_secs(60)).len(), 1);
+
+        let path = dir.join("history.bin");
+        agg.flush_history(&path);
+        assert_eq!(Aggregator::get_volume_history(&path, 1_000_000, 1_000_000).len(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_pending_overflow_spills_to_disk() {
+        let dir = std::env::temp_dir().join(format!("craftnet-agg-spill-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut agg = new_agg();
+        agg.set_pending_spill(PendingSpill::open(dir.clone()).unwrap());
+        agg.handle_proof(make_proof(1, 2, PoolType::Subscribed, 100, 100, [0u8; 32], [0xAA; 32])).unwrap();
+        // [0xAA] -> [1] is missing; more proofs wait behind it than memory holds
+        for i in (1..=20u8).rev() {
+            agg.handle_proof(make_proof(1, 2, PoolType::Subscribed, 50, 150 + 50 * i as u64, [i; 32], [i + 1; 32])).unwrap();
+        }
+        assert_eq!(agg.pending_total, MAX_PENDING_PER_CHAIN);
+        assert_eq!(agg.spilled_count(), 20 - MAX_PENDING_PER_CHAIN);
+        // The lowest held-back proof is among the spilled ones
+        let gaps = agg.chain_gaps(system_clock().unix_now() + 60, Duration::from_secs(60));
+        assert_eq!(gaps[0].to_root, [1; 32]);
+
+        agg.flush_pending_spill();
+        agg.handle_proof(make_proof(1, 2, PoolType::Subscribed, 50, 150, [0xAA; 32], [1; 32])).unwrap();
+        assert_eq!(agg.get_pool_usage(&([2u8; 32], PoolType::Subscribed))[0].1, 1150);
+        assert_eq!(agg.pending_total, 0);
+        assert_eq!(agg.spilled_count(), 0);
+        assert!(agg.chain_gaps(system_clock().unix_now() + 3600, Duration::from_secs(60)).is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_pool_filter_drops_and_resyncs() {
+        let mut agg = new_agg();
+        agg.handle_proof(make_proof(1, 2, PoolType::Subscribed, 100, 100, [0u8; 32], [0xAA; 32])).unwrap();
+        agg.set_pool_filter(PoolFilter::Pools(HashSet::from([[3u8; 32]])));
+        // Narrowing dropped pool 2
+        assert!(agg.get_pool_usage(&([2u8; 32], PoolType::Subscribed)).is_empty());
+
+        let result = agg.handle_proof(make_proof(1, 2, PoolType::Subscribed, 50, 150, [0xAA; 32], [0xBB; 32]));
+        assert!(matches!(result, Err(AggregatorError::PoolNotFollowed)));
+        agg.handle_proof(make_proof(1, 3, PoolType::Subscribed, 70, 70, [0u8; 32], [0xCC; 32])).unwrap();
+
+        // Widening picks pool 2 up again from its latest proof
+        assert_eq!(agg.set_pool_filter(PoolFilter::All), 1);
+        assert_eq!(agg.get_pool_usage(&([2u8; 32], PoolType::Subscribed))[0].1, 150);
+        assert_eq!(agg.get_pool_usage(&([3u8; 32], PoolType::Subscribed))[0].1, 70);
+        agg.handle_proof(make_proof(1, 2, PoolType::Subscribed, 50, 200, [0xBB; 32], [0xDD; 32])).unwrap();
+    }
+
     #[test]
     fn test_non_increasing_count_rejected() {
         let mut agg = new_agg();
@@ -1525,6 +2669,31 @@ mod tests {
         assert_eq!(dist1.root, dist2.root);
     }
 
+    #[test]
+    fn test_distribution_batch_proof() {
+        let mut agg = new_agg();
+        for relay in 1..=5u8 {
+            let msg = make_proof(relay, 10, PoolType::Subscribed, relay as u64 * 10, relay as u64 * 10, [0u8; 32], [relay; 32]);
+            agg.handle_proof(msg).unwrap();
+        }
+
+        let pool_key = ([10u8; 32], PoolType::Subscribed);
+        let relays = [relay_pubkey(4), relay_pubkey(1), relay_pubkey(99)];
+        let batch = agg.get_distribution_batch_proof(&pool_key, &relays).unwrap();
+
+        assert_eq!(batch.root, agg.build_distribution(&pool_key).unwrap().root);
+        assert_eq!(batch.entries.len(), 2);
+        assert!(batch.entries.contains(&(relay_pubkey(1), 10)));
+        assert!(batch.entries.contains(&(relay_pubkey(4), 40)));
+        assert!(batch.verify());
+
+        let mut forged = batch.clone();
+        forged.entries[0].1 += 1;
+        assert!(!forged.verify());
+
+        assert!(agg.get_distribution_batch_proof(&pool_key, &[relay_pubkey(99)]).is_none());
+    }
+
     #[test]
     fn test_network_stats() {
         let mut agg = new_agg();
@@ -1542,6 +2711,11 @@ mod tests {
         assert_eq!(stats.subscribed_bytes, 100);
         assert_eq!(stats.free_bytes, 50);
         assert_eq!(stats.total_bytes, 150);
+        assert_eq!(stats.relay_versions, BTreeMap::from([(0, 2)]));
+
+        agg.record_relay_version(relay_pubkey(1), 3);
+        let stats = agg.get_network_stats();
+        assert_eq!(stats.relay_versions, BTreeMap::from([(0, 1), (3, 1)]));
     }
 
     #[test]
@@ -1845,6 +3019,149 @@ mod tests {
         history_cleanup(&dir, &path);
     }
 
+    #[test]
+    fn test_bandwidth_rebuild_matches_live_index() {
+        let mut agg = new_agg();
+        let (dir, path) = history_tmp("rebuild-bandwidth");
+
+        agg.handle_proof(make_proof(1, 2, PoolType::Subscribed, 100, 100, [0u8; 32], [0xAA; 32])).unwrap();
+        agg.handle_proof(make_proof(3, 2, PoolType::Free, 40, 40, [0u8; 32], [0xCC; 32])).unwrap();
+        agg.flush_history(&path);
+        // Accepted after the last flush: only in the history buffer
+        agg.handle_proof(make_proof(1, 2, PoolType::Subscribed, 50, 150, [0xAA; 32], [0xBB; 32])).unwrap();
+
+        let (mut restarted, _) = {
+            let state = dir.join("state.json");
+            agg.save_to_file(&state, &HashSet::new());
+            Aggregator::load_from_file(&state).unwrap()
+        };
+        restarted.history.buffer = agg.history.buffer.clone();
+        assert!(restarted.get_relay_total_bandwidth(&relay_pubkey(1), 0, u64::MAX, Granularity::Hourly).is_empty());
+
+        let mut reports = Vec::new();
+        let proofs = restarted.rebuild_bandwidth_from_history_with_progress(&path, |p| reports.push(p));
+        assert_eq!(proofs, 3);
+        let last = reports.last().unwrap();
+        assert_eq!(last.bytes_read, last.total_bytes);
+        assert_eq!(last.proofs, 3);
+
+        for relay in [relay_pubkey(1), relay_pubkey(3)] {
+            for granularity in [Granularity::Hourly, Granularity::Daily] {
+                let before = agg.get_relay_total_bandwidth(&relay, 0, u64::MAX, granularity);
+                let after = restarted.get_relay_total_bandwidth(&relay, 0, u64::MAX, granularity);
+                assert_eq!(
+                    before.iter().map(|b| (b.timestamp, b.bytes, b.batch_count)).collect::<Vec<_>>(),
+                    after.iter().map(|b| (b.timestamp, b.bytes, b.batch_count)).collect::<Vec<_>>(),
+                );
+            }
+        }
+
+        let _ = std::fs::remove_file(dir.join("state.json"));
+        history_cleanup(&dir, &path);
+    }
+
+    #[test]
+    fn test_history_prune_to_size() {
+        let mut agg = new_agg();
+        let (dir, path) = history_tmp("prune-size");
+        for i in 0..10u64 {
+            agg.record_distribution_posted([i as u8; 32], [0u8; 32], i);
+        }
+        agg.flush_history(&path);
+        let total = std::fs::metadata(&path).unwrap().len();
+
+        // No limit and no monthly horizon: nothing to do
+        assert_eq!(agg.prune_history(&path, None).unwrap(), 0);
+
+        let removed = agg.prune_history(&path, Some(total / 2)).unwrap();
+        assert!(removed >= total / 2);
+        let kept = Aggregator::history_since(&path, 0);
+        assert!(!kept.is_empty() && kept.len() <= 5);
+        // Sequence numbers survive, so recovery continues where it was
+        assert_eq!(kept.last().unwrap().seq, 9);
+        assert_eq!(Aggregator::recover_history_seq(&path), 10);
+
+        // Everything is older than a zero-length retention horizon
+        agg.set_retention_policy(RetentionPolicy { monthly: Some(Duration::ZERO), ..Default::default() });
+        std::thread::sleep(Duration::from_millis(1100));
+        agg.prune_history(&path, None).unwrap();
+        assert!(Aggregator::history_since(&path, 0).is_empty());
+
+        history_cleanup(&dir, &path);
+    }
+
+    #[test]
+    fn test_anomalous_proof_quarantined_until_confirmed() {
+        const MIB: u64 = 1024 * 1024;
+        let mut agg = new_agg();
+        let root = |i: u8| [i; 32];
+
+        // Baseline: 8 batches of 2 MiB
+        for i in 0..8u8 {
+            let msg = make_proof(1, 2, PoolType::Subscribed, 2 * MIB, (i as u64 + 1) * 2 * MIB, if i == 0 { [0u8; 32] } else { root(i) }, root(i + 1));
+            agg.handle_proof(msg).unwrap();
+        }
+        // A 100 MiB batch is held back, and the chain waits behind it
+        agg.handle_proof(make_proof(1, 2, PoolType::Subscribed, 100 * MIB, 116 * MIB, root(8), root(9))).unwrap();
+        agg.handle_proof(make_proof(1, 2, PoolType::Subscribed, 2 * MIB, 118 * MIB, root(9), root(10))).unwrap();
+        let relay = relay_pubkey(1);
+        let usage = |agg: &Aggregator| agg.get_pool_usage(&([2u8; 32], PoolType::Subscribed));
+        assert_eq!(usage(&agg), vec![(relay, 16 * MIB)]);
+        assert_eq!(agg.quarantined().count(), 1);
+        assert!(matches!(
+            agg.take_anomaly_events().as_slice(),
+            [AnomalyEvent::Quarantined { reason: AnomalyReason::Spike { .. }, .. }],
+        ));
+
+        // Survives a restart
+        let (dir, _) = history_tmp("quarantine-state");
+        let state = dir.join("state.json");
+        agg.save_to_file(&state, &HashSet::new());
+        let (mut agg, _) = Aggregator::load_from_file(&state).unwrap();
+        let _ = std::fs::remove_file(&state);
+        let _ = std::fs::remove_dir(&dir);
+        assert_eq!(agg.quarantined().count(), 1);
+
+        // One confirmation isn't enough, the same confirmer twice doesn't count
+        assert!(!agg.confirm_quarantined(&relay, &[2u8; 32], PoolType::Subscribed, &root(9), [0xA1; 32]));
+        assert!(!agg.confirm_quarantined(&relay, &[2u8; 32], PoolType::Subscribed, &root(9), [0xA1; 32]));
+        assert!(agg.confirm_quarantined(&relay, &[2u8; 32], PoolType::Subscribed, &root(9), [0xA2; 32]));
+        assert_eq!(usage(&agg), vec![(relay, 118 * MIB)]);
+        assert_eq!(agg.quarantined().count(), 0);
+        assert!(matches!(
+            agg.take_anomaly_events().as_slice(),
+            [AnomalyEvent::Released { via: ReleaseReason::Confirmed, .. }],
+        ));
+    }
+
+    #[test]
+    fn test_anomalous_proof_released_by_zk_proof() {
+        struct AcceptZk;
+        impl BatchProofVerifier for AcceptZk {
+            fn verify(&self, msg: &ProofMessage) -> bool {
+                msg.proof == b"zk"
+            }
+        }
+
+        let mut agg = new_agg();
+        agg.handle_proof(make_proof(1, 2, PoolType::Subscribed, 100, 100, [0u8; 32], [0xAA; 32])).unwrap();
+        // 5 GB within the same second: impossible
+        let mut msg = make_proof(1, 2, PoolType::Subscribed, 5_000_000_000, 5_000_000_100, [0xAA; 32], [0xBB; 32]);
+        agg.handle_proof(msg.clone()).unwrap();
+        assert_eq!(agg.quarantined().count(), 1);
+
+        // Resubmitted with a ZK proof (not covered by the signature), but
+        // no verifier is configured
+        msg.proof = b"zk".to_vec();
+        agg.handle_proof(msg.clone()).unwrap();
+        assert_eq!(agg.quarantined().count(), 1);
+
+        agg.set_batch_proof_verifier(Arc::new(AcceptZk));
+        agg.handle_proof(msg).unwrap();
+        assert_eq!(agg.quarantined().count(), 0);
+        assert_eq!(agg.get_pool_usage(&([2u8; 32], PoolType::Subscribed))[0].1, 5_000_000_100);
+    }
+
     #[test]
     fn test_history_nonexistent_file() {
         let path = std::path::Path::new("/tmp/nonexistent-craftnet-history.jsonl");
@@ -1911,6 +3228,56 @@ mod tests {
         assert_eq!(BandwidthIndex::floor_day(86400), 86400);
     }
 
+    #[test]
+    fn test_granularity_floor_week_and_month() {
+        // 2023-11-14T22:13:20Z is a Tuesday
+        assert_eq!(Granularity::Weekly.floor(1700000000), 1699833600); // Mon 2023-11-13
+        assert_eq!(Granularity::Weekly.floor(1699833600), 1699833600);
+        assert_eq!(Granularity::Monthly.floor(1700000000), 1698796800); // 2023-11-01
+        assert_eq!(Granularity::Monthly.floor(1709251199), 1706745600); // 2024-02-29 → 2024-02-01
+        assert_eq!(Granularity::Monthly.floor(0), 0);
+        assert_eq!("Weekly".parse::<Granularity>(), Ok(Granularity::Weekly));
+        assert!("yearly".parse::<Granularity>().is_err());
+    }
+
+    #[test]
+    fn test_bandwidth_retention_tiers() {
+        const DAY: u64 = 86400;
+        let policy = RetentionPolicy {
+            hourly: Duration::from_secs(2 * DAY),
+            daily: Duration::from_secs(14 * DAY),
+            weekly: Duration::from_secs(60 * DAY),
+            monthly: Some(Duration::from_secs(365 * DAY)),
+        };
+        let now = 1700000000u64;
+        let relay = [1u8; 32];
+        let pool = [2u8; 32];
+        let mut idx = BandwidthIndex::new();
+        // Ages: 1 hour, 5 days, 30 days, 100 days, 400 days
+        for age in [3600, 5 * DAY, 30 * DAY, 100 * DAY, 400 * DAY] {
+            idx.record_proof(&relay, &pool, PoolType::Subscribed, 10, now - age);
+        }
+
+        idx.compact_with_policy(now, &policy);
+        let series = &idx.series[&(relay, pool, PoolType::Subscribed)];
+        assert_eq!(series.hourly.len(), 1);
+        assert_eq!(series.daily.len(), 1);
+        assert_eq!(series.weekly.len(), 1);
+        assert_eq!(series.monthly.len(), 1); // the 400-day-old bucket is gone
+        assert_eq!(idx.network.monthly.len(), 1);
+
+        // A monthly query sees every remaining tier
+        let monthly = idx.get_relay_total_bandwidth(&relay, 0, u64::MAX, Granularity::Monthly);
+        assert_eq!(monthly.iter().map(|b| b.bytes).sum::<u64>(), 40);
+        // An hourly query only sees what is still hourly
+        let hourly = idx.get_relay_total_bandwidth(&relay, 0, u64::MAX, Granularity::Hourly);
+        assert_eq!(hourly.len(), 1);
+
+        // Everything past the horizon: the series disappears
+        idx.compact_with_policy(now + 2 * 365 * DAY, &policy);
+        assert!(idx.series.is_empty());
+    }
+
     #[test]
     fn test_bandwidth_record_and_query() {
         let mut idx = BandwidthIndex::new();
@@ -2072,4 +3439,60 @@ mod tests {
         let bytes: u64 = result.iter().map(|b| b.bytes).sum();
         assert_eq!(bytes, 300); // 100 + 200
     }
+
+    #[test]
+    fn test_challenge_answered_from_history() {
+        let (dir, path) = history_tmp("dispute");
+        let mut agg = new_agg();
+        agg.handle_proof(make_proof(1, 10, PoolType::Subscribed, 70, 70, [0u8; 32], [0xAA; 32])).unwrap();
+        agg.handle_proof(make_proof(1, 10, PoolType::Subscribed, 30, 100, [0xAA; 32], [0xBB; 32])).unwrap();
+        let pool_key = ([10u8; 32], PoolType::Subscribed);
+        let dist = agg.build_distribution(&pool_key).unwrap();
+        let deadline = agg.open_challenge_window(pool_key, dist.root, 1000);
+        assert_eq!(agg.challenge_deadline(&pool_key), Some(deadline));
+
+        let keypair = craftec_crypto::SigningKeypair::from_secret_bytes(&[1; 32]);
+        let mut challenge = DistributionChallenge {
+            relay_pubkey: relay_pubkey(1),
+            aggregator_pubkey: [9; 32],
+            pool_pubkey: [10; 32],
+            pool_type: PoolType::Subscribed,
+            distribution_root: dist.root,
+            counted_bytes: 100,
+            claimed_bytes: 130,
+            claimed_root: [0xCC; 32],
+            timestamp: 1000,
+            signature: vec![0; 64],
+        };
+        assert!(matches!(agg.handle_challenge(&challenge, 1001), Err(AggregatorError::InvalidSignature)));
+        challenge.signature = craftec_crypto::sign_data(&keypair, &challenge.signable_data()).to_vec();
+        assert!(matches!(agg.handle_challenge(&challenge, deadline + 1), Err(AggregatorError::ChallengeWindowClosed)));
+        agg.handle_challenge(&challenge, 1001).unwrap();
+
+        agg.flush_history(&path);
+        let chain = Aggregator::proof_chain(&path, &relay_pubkey(1), &[10; 32], PoolType::Subscribed);
+        assert_eq!(chain.len(), 2);
+        let response = ChallengeResponse {
+            aggregator_pubkey: [9; 32],
+            relay_pubkey: relay_pubkey(1),
+            pool_pubkey: [10; 32],
+            pool_type: PoolType::Subscribed,
+            distribution_root: dist.root,
+            counted_bytes: 100,
+            chain,
+            timestamp: 1002,
+            signature: vec![],
+        };
+        // The relay claims a proof we never received
+        assert_eq!(agg.close_dispute(&challenge, &response, 1002), Some(DisputeStatus::Unresolved));
+        assert_eq!(agg.disputes().next().unwrap().status, DisputeStatus::Unresolved);
+
+        agg.flush_history(&path);
+        let closed = Aggregator::history_since(&path, 0).into_iter().any(|e| matches!(
+            e.event,
+            HistoryEvent::DisputeClosed { status: DisputeStatus::Unresolved, .. }
+        ));
+        assert!(closed);
+        history_cleanup(&dir, &path);
+    }
 }
@@ -0,0 +1,318 @@
+//! Disk overflow of the pending proof buffer
+//!
+//! Out-of-order proofs wait in memory for the proofs they chain from, up to
+//! `MAX_PENDING_PER_CHAIN` per chain and `MAX_PENDING_TOTAL` overall. A long
+//! partition can pile up more than that; with a [`PendingSpill`] the
+//! overflow goes to disk instead of being dropped, and each spilled proof is
+//! replayed once its chain reaches the root it starts from.
+//!
+//! Proofs are appended to segment files (`<n>.seg`, records of
+//! `[len:4 BE][bincode ProofMessage]`) and found through an index
+//! (`index.bin`) of each chain's records and the roots they chain from.
+//! The index is written on [`PendingSpill::flush`]; records appended after
+//! the last flush are recovered by scanning segment tails on open. A
+//! segment is deleted once every proof in it was taken back.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+use craftnet_network::ProofMessage;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::ChainKey;
+
+/// Size at which a new segment is started
+const SEGMENT_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Largest record accepted when scanning a segment
+const MAX_RECORD_BYTES: u32 = 16 * 1024 * 1024;
+
+const INDEX_FILE: &str = "index.bin";
+
+/// Where a spilled proof is and what it chains from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SpillEntry {
+    segment: u64,
+    offset: u64,
+    len: u32,
+    prev_root: [u8; 32],
+    cumulative_bytes: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SpillIndex {
+    chains: HashMap<ChainKey, Vec<SpillEntry>>,
+    /// Bytes of each segment the index covers
+    segments: BTreeMap<u64, u64>,
+}
+
+/// On-disk overflow of out-of-order proofs
+#[derive(Debug)]
+pub struct PendingSpill {
+    dir: PathBuf,
+    chains: HashMap<ChainKey, Vec<SpillEntry>>,
+    /// Bytes written per segment
+    segments: BTreeMap<u64, u64>,
+    /// Segment new records go to
+    current: u64,
+    /// Index changed since the last flush
+    dirty: bool,
+}
+
+impl PendingSpill {
+    /// Open (or create) the spill directory, recovering what an earlier
+    /// run left in it
+    pub fn open(dir: PathBuf) -> io::Result<Self> {
+        std::fs::create_dir_all(&dir)?;
+        let index: SpillIndex = std::fs::read(dir.join(INDEX_FILE))
+            .ok()
+            .and_then(|bytes| bincode::deserialize(&bytes).ok())
+            .unwrap_or_default();
+        let mut spill = Self { dir, chains: index.chains, segments: BTreeMap::new(), current: 0, dirty: false };
+
+        let mut on_disk = BTreeMap::new();
+        for entry in std::fs::read_dir(&spill.dir)?.flatten() {
+            let name = entry.file_name();
+            let Some(segment) = name.to_str().and_then(|n| n.strip_suffix(".seg")).and_then(|n| n.parse::<u64>().ok()) else {
+                continue;
+            };
+            on_disk.insert(segment, entry.metadata()?.len());
+        }
+
+        // Entries of vanished segments are gone
+        for entries in spill.chains.values_mut() {
+            entries.retain(|e| on_disk.contains_key(&e.segment));
+        }
+        // Records appended after the index was written
+        for (&segment, &len) in &on_disk {
+            let indexed = index.segments.get(&segment).copied().unwrap_or(0).min(len);
+            if len > indexed {
+                spill.recover(segment, indexed)?;
+            }
+            spill.segments.insert(segment, len);
+        }
+        spill.chains.retain(|_, entries| !entries.is_empty());
+        spill.current = spill.segments.keys().next_back().copied().unwrap_or(0);
+        spill.remove_dead_segments();
+        if !spill.is_empty() {
+            debug!("Recovered {} spilled pending proofs from {}", spill.len(), spill.dir.display());
+        }
+        Ok(spill)
+    }
+
+    fn segment_path(&self, segment: u64) -> PathBuf {
+        self.dir.join(format!("{}.seg", segment))
+    }
+
+    /// Index the records of `segment` from `offset` on
+    fn recover(&mut self, segment: u64, mut offset: u64) -> io::Result<()> {
+        let mut file = File::open(self.segment_path(segment))?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut reader = io::BufReader::new(file);
+        loop {
+            let mut len_bytes = [0u8; 4];
+            if reader.read_exact(&mut len_bytes).is_err() {
+                break;
+            }
+            let len = u32::from_be_bytes(len_bytes);
+            if len > MAX_RECORD_BYTES {
+                warn!("Corrupt spill segment {} at {}", segment, offset);
+                break;
+            }
+            let mut data = vec![0u8; len as usize];
+            if reader.read_exact(&mut data).is_err() {
+                break; // torn write
+            }
+            if let Ok(msg) = bincode::deserialize::<ProofMessage>(&data) {
+                self.chains.entry(chain_key(&msg)).or_default().push(SpillEntry {
+                    segment,
+                    offset,
+                    len,
+                    prev_root: msg.prev_root,
+                    cumulative_bytes: msg.cumulative_bytes,
+                });
+                self.dirty = true;
+            }
+            offset += 4 + len as u64;
+        }
+        Ok(())
+    }
+
+    /// Set a proof aside until its chain catches up
+    pub fn push(&mut self, msg: &ProofMessage) -> io::Result<()> {
+        let data = bincode::serialize(msg).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        let written = self.segments.get(&self.current).copied().unwrap_or(0);
+        if written > 0 && written + 4 + data.len() as u64 > SEGMENT_BYTES {
+            self.current += 1;
+        }
+        let offset = self.segments.get(&self.current).copied().unwrap_or(0);
+        let mut file = OpenOptions::new().create(true).append(true).open(self.segment_path(self.current))?;
+        file.write_all(&(data.len() as u32).to_be_bytes())?;
+        file.write_all(&data)?;
+
+        self.segments.insert(self.current, offset + 4 + data.len() as u64);
+        self.chains.entry(chain_key(msg)).or_default().push(SpillEntry {
+            segment: self.current,
+            offset,
+            len: data.len() as u32,
+            prev_root: msg.prev_root,
+            cumulative_bytes: msg.cumulative_bytes,
+        });
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Take back the spilled proof of `chain` that starts at `root`.
+    /// Proofs the chain already passed (`head_cumulative`) are discarded.
+    pub fn take_link(&mut self, chain: &ChainKey, root: &[u8; 32], head_cumulative: u64) -> io::Result<Option<ProofMessage>> {
+        let Some(entries) = self.chains.get_mut(chain) else {
+            return Ok(None);
+        };
+        let before = entries.len();
+        entries.retain(|e| e.cumulative_bytes > head_cumulative);
+        let entry = entries.iter().position(|e| &e.prev_root == root).map(|i| entries.remove(i));
+        if entries.is_empty() {
+            self.chains.remove(chain);
+        }
+        let changed = entry.is_some() || self.chains.get(chain).map_or(0, Vec::len) != before;
+        if changed {
+            self.dirty = true;
+            self.remove_dead_segments();
+        }
+        let Some(entry) = entry else {
+            return Ok(None);
+        };
+
+        let mut file = File::open(self.segment_path(entry.segment))?;
+        file.seek(SeekFrom::Start(entry.offset + 4))?;
+        let mut data = vec![0u8; entry.len as usize];
+        file.read_exact(&mut data)?;
+        bincode::deserialize(&data)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+    }
+
+    /// (cumulative bytes, prev root) of the lowest spilled proof of `chain`
+    pub fn first_pending(&self, chain: &ChainKey) -> Option<(u64, [u8; 32])> {
+        self.chains
+            .get(chain)?
+            .iter()
+            .min_by_key(|e| e.cumulative_bytes)
+            .map(|e| (e.cumulative_bytes, e.prev_root))
+    }
+
+    /// Chains with spilled proofs
+    pub fn chains(&self) -> impl Iterator<Item = &ChainKey> {
+        self.chains.keys()
+    }
+
+    /// Number of spilled proofs
+    pub fn len(&self) -> usize {
+        self.chains.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chains.is_empty()
+    }
+
+    /// Write the index if it changed
+    pub fn flush(&mut self) -> io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        let index = SpillIndex { chains: self.chains.clone(), segments: self.segments.clone() };
+        let bytes = bincode::serialize(&index).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        let path = self.dir.join(INDEX_FILE);
+        let tmp_path = path.with_extension("bin.tmp");
+        std::fs::write(&tmp_path, bytes)?;
+        std::fs::rename(&tmp_path, &path)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Delete segments no entry points into (except the one being written)
+    fn remove_dead_segments(&mut self) {
+        let live: std::collections::HashSet<u64> =
+            self.chains.values().flatten().map(|e| e.segment).collect();
+        let dead: Vec<u64> = self.segments.keys().copied().filter(|s| *s != self.current && !live.contains(s)).collect();
+        for segment in dead {
+            if let Err(e) = std::fs::remove_file(self.segment_path(segment)) {
+                warn!("Failed to remove spill segment {}: {}", segment, e);
+            }
+            self.segments.remove(&segment);
+            self.dirty = true;
+        }
+    }
+}
+
+fn chain_key(msg: &ProofMessage) -> ChainKey {
+    (msg.relay_pubkey, msg.pool_pubkey, msg.pool_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use craftnet_network::PoolType;
+
+    fn proof(prev: u8, new: u8, cumulative: u64) -> ProofMessage {
+        ProofMessage {
+            relay_pubkey: [1; 32],
+            pool_pubkey: [2; 32],
+            pool_type: PoolType::Subscribed,
+            batch_bytes: 10,
+            cumulative_bytes: cumulative,
+            prev_root: [prev; 32],
+            new_root: [new; 32],
+            proof: vec![],
+            timestamp: 0,
+            signature: vec![7; 64],
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("craftnet-spill-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_spill_take_links_in_chain_order() {
+        let dir = temp_dir("order");
+        let chain = ([1; 32], [2; 32], PoolType::Subscribed);
+        let mut spill = PendingSpill::open(dir.clone()).unwrap();
+        spill.push(&proof(2, 3, 30)).unwrap();
+        spill.push(&proof(1, 2, 20)).unwrap();
+        assert_eq!(spill.len(), 2);
+        assert_eq!(spill.first_pending(&chain), Some((20, [1; 32])));
+
+        assert!(spill.take_link(&chain, &[9; 32], 10).unwrap().is_none());
+        assert_eq!(spill.take_link(&chain, &[1; 32], 10).unwrap().unwrap().new_root, [2; 32]);
+        assert_eq!(spill.take_link(&chain, &[2; 32], 20).unwrap().unwrap().new_root, [3; 32]);
+        assert!(spill.is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_spill_recovers_unindexed_records() {
+        let dir = temp_dir("recover");
+        let chain = ([1; 32], [2; 32], PoolType::Subscribed);
+        {
+            let mut spill = PendingSpill::open(dir.clone()).unwrap();
+            spill.push(&proof(1, 2, 20)).unwrap();
+            spill.flush().unwrap();
+            // Appended after the last index write, then a crash
+            spill.push(&proof(2, 3, 30)).unwrap();
+        }
+
+        let mut spill = PendingSpill::open(dir.clone()).unwrap();
+        assert_eq!(spill.len(), 2);
+        // Proofs the chain already passed are dropped
+        assert!(spill.take_link(&chain, &[5; 32], 20).unwrap().is_none());
+        assert_eq!(spill.len(), 1);
+        assert_eq!(spill.take_link(&chain, &[2; 32], 20).unwrap().unwrap().cumulative_bytes, 30);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
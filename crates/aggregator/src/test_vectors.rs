@@ -0,0 +1,346 @@
+//! Golden vectors for the hashing shared across components
+//!
+//! The distribution leaf `SHA256(relay || bytes_le)`, the Merkle tree built
+//! from it, the receipt leaf, the distribution public values and the
+//! signed layouts of receipts and proof messages are computed in several
+//! places: the prover, the zkVM guests, the aggregator, the settlement
+//! client and the on-chain program. [`generate`] computes them once from
+//! fixed inputs; the result is checked in as `test-vectors/hashing.json`
+//! at the repository root, and each of those components has a unit test
+//! reading that file, so a formula drifting in one place fails its tests
+//! instead of failing claims on chain.
+//!
+//! Regenerate the file after an intended change with
+//! `craftnet aggregator test-vectors --out test-vectors/hashing.json`.
+//! Byte strings are lowercase hex; every number fits in 53 bits so the
+//! file can be read as plain JSON numbers by the program's TS tests.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use craftnet_core::ForwardReceipt;
+use craftnet_network::{PoolType, ProofMessage};
+use craftnet_prover::{distribution_public_values, merkle_leaf, MerkleTree, ReceiptCompressor};
+
+/// Layout version; bump when fields are added or change meaning
+pub const TEST_VECTORS_VERSION: u32 = 1;
+
+/// Largest integer JSON readers without 64-bit integers keep exactly
+const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+/// All vectors, as stored in `test-vectors/hashing.json`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestVectors {
+    pub version: u32,
+    /// Merkle trees over entries in the given order
+    pub merkle: Vec<MerkleVector>,
+    /// Distributions as the guests and the aggregator build them
+    pub distributions: Vec<DistributionVector>,
+    pub receipts: Vec<ReceiptVector>,
+    /// Merkle root over the leaves of `receipts`, in order
+    pub receipt_batch_root: String,
+    pub proof_messages: Vec<ProofMessageVector>,
+}
+
+/// One distribution entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryVector {
+    pub relay: String,
+    pub bytes: u64,
+}
+
+/// Inclusion proof of one leaf
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofVector {
+    pub relay: String,
+    pub bytes: u64,
+    pub leaf_index: usize,
+    /// Sibling hashes, leaf level first
+    pub siblings: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleVector {
+    pub name: String,
+    pub entries: Vec<EntryVector>,
+    /// `merkle_leaf` of each entry (before padding)
+    pub leaves: Vec<String>,
+    pub root: String,
+    /// One proof per entry
+    pub proofs: Vec<ProofVector>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DistributionVector {
+    pub name: String,
+    pub pool: String,
+    /// Entries in submission order (unsorted)
+    pub entries: Vec<EntryVector>,
+    /// Root of the tree over the entries sorted by relay
+    pub root: String,
+    pub total_bytes: u64,
+    pub entry_count: u32,
+    /// 76-byte guest commitment
+    pub public_values: String,
+    /// Claim proofs, leaf indices into the sorted entries
+    pub claims: Vec<ProofVector>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReceiptVector {
+    pub shard_id: String,
+    pub sender_pubkey: String,
+    pub receiver_pubkey: String,
+    pub pool_pubkey: String,
+    pub payload_size: u32,
+    pub timestamp: u64,
+    /// `ForwardReceipt::signable_data`
+    pub signable: String,
+    /// `ReceiptCompressor::receipt_leaf`
+    pub leaf: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofMessageVector {
+    pub relay_pubkey: String,
+    pub pool_pubkey: String,
+    /// "subscribed" or "free"
+    pub pool_type: String,
+    pub batch_bytes: u64,
+    pub cumulative_bytes: u64,
+    pub prev_root: String,
+    pub new_root: String,
+    pub timestamp: u64,
+    /// `ProofMessage::signable_data`
+    pub signable: String,
+}
+
+impl TestVectors {
+    pub fn to_json(&self) -> String {
+        let mut json = serde_json::to_string_pretty(self).expect("test vectors serialize");
+        json.push('\n');
+        json
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+/// Deterministic 32-byte value for a label
+fn key(label: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"craftnet-test-vector:");
+    hasher.update(label.as_bytes());
+    hasher.finalize().into()
+}
+
+fn entries(labels: &[(&str, u64)]) -> Vec<([u8; 32], u64)> {
+    labels.iter().map(|(label, bytes)| (key(label), *bytes)).collect()
+}
+
+fn entry_vectors(entries: &[([u8; 32], u64)]) -> Vec<EntryVector> {
+    entries.iter().map(|(relay, bytes)| EntryVector { relay: hex::encode(relay), bytes: *bytes }).collect()
+}
+
+fn proof_vectors(tree: &MerkleTree, entries: &[([u8; 32], u64)]) -> Vec<ProofVector> {
+    entries
+        .iter()
+        .enumerate()
+        .map(|(i, (relay, bytes))| ProofVector {
+            relay: hex::encode(relay),
+            bytes: *bytes,
+            leaf_index: i,
+            siblings: tree.proof(i).expect("leaf in range").siblings.iter().map(hex::encode).collect(),
+        })
+        .collect()
+}
+
+fn merkle_vector(name: &str, entries: Vec<([u8; 32], u64)>) -> MerkleVector {
+    let tree = MerkleTree::from_entries(&entries);
+    MerkleVector {
+        name: name.to_string(),
+        leaves: entries.iter().map(|(relay, bytes)| hex::encode(merkle_leaf(relay, *bytes))).collect(),
+        root: hex::encode(tree.root()),
+        proofs: proof_vectors(&tree, &entries),
+        entries: entry_vectors(&entries),
+    }
+}
+
+fn distribution_vector(name: &str, pool: &str, entries: Vec<([u8; 32], u64)>) -> DistributionVector {
+    let pool = key(pool);
+    let mut sorted = entries.clone();
+    sorted.sort_by(|a, b| a.0.cmp(&b.0));
+    let tree = MerkleTree::from_entries(&sorted);
+    let total_bytes: u64 = sorted.iter().map(|(_, bytes)| bytes).sum();
+    DistributionVector {
+        name: name.to_string(),
+        pool: hex::encode(pool),
+        entries: entry_vectors(&entries),
+        root: hex::encode(tree.root()),
+        total_bytes,
+        entry_count: sorted.len() as u32,
+        public_values: hex::encode(distribution_public_values(&tree.root(), total_bytes, sorted.len() as u32, &pool)),
+        claims: proof_vectors(&tree, &sorted),
+    }
+}
+
+fn receipt(label: &str, payload_size: u32, timestamp: u64) -> ForwardReceipt {
+    ForwardReceipt {
+        shard_id: key(&format!("{label}/shard")),
+        sender_pubkey: key(&format!("{label}/sender")),
+        receiver_pubkey: key(&format!("{label}/receiver")),
+        pool_pubkey: key("pool-a"),
+        payload_size,
+        timestamp,
+        signature: [0u8; 64],
+    }
+}
+
+fn receipt_vector(receipt: &ForwardReceipt) -> ReceiptVector {
+    ReceiptVector {
+        shard_id: hex::encode(receipt.shard_id),
+        sender_pubkey: hex::encode(receipt.sender_pubkey),
+        receiver_pubkey: hex::encode(receipt.receiver_pubkey),
+        pool_pubkey: hex::encode(receipt.pool_pubkey),
+        payload_size: receipt.payload_size,
+        timestamp: receipt.timestamp,
+        signable: hex::encode(ForwardReceipt::signable_data(
+            &receipt.shard_id,
+            &receipt.sender_pubkey,
+            &receipt.receiver_pubkey,
+            &receipt.pool_pubkey,
+            receipt.payload_size,
+            receipt.timestamp,
+        )),
+        leaf: hex::encode(ReceiptCompressor::receipt_leaf(receipt)),
+    }
+}
+
+fn proof_message_vector(msg: &ProofMessage) -> ProofMessageVector {
+    ProofMessageVector {
+        relay_pubkey: hex::encode(msg.relay_pubkey),
+        pool_pubkey: hex::encode(msg.pool_pubkey),
+        pool_type: match msg.pool_type {
+            PoolType::Subscribed => "subscribed",
+            PoolType::Free => "free",
+        }
+        .to_string(),
+        batch_bytes: msg.batch_bytes,
+        cumulative_bytes: msg.cumulative_bytes,
+        prev_root: hex::encode(msg.prev_root),
+        new_root: hex::encode(msg.new_root),
+        timestamp: msg.timestamp,
+        signable: hex::encode(msg.signable_data()),
+    }
+}
+
+/// Compute every vector from the fixed inputs
+pub fn generate() -> TestVectors {
+    let merkle = vec![
+        merkle_vector("single", entries(&[("relay-1", 1024)])),
+        merkle_vector("pair", entries(&[("relay-1", 1024), ("relay-2", 0)])),
+        // Three leaves pad to four with zero hashes
+        merkle_vector("padded", entries(&[("relay-1", 1024), ("relay-2", 2048), ("relay-3", MAX_SAFE_INTEGER)])),
+        merkle_vector(
+            "five",
+            entries(&[("relay-1", 1), ("relay-2", 2), ("relay-3", 3), ("relay-4", 4), ("relay-5", 5)]),
+        ),
+    ];
+
+    let distributions = vec![
+        distribution_vector("single", "pool-a", entries(&[("relay-1", 4096)])),
+        distribution_vector(
+            "unsorted",
+            "pool-b",
+            entries(&[("relay-3", 300_000), ("relay-1", 100_000), ("relay-2", 200_000)]),
+        ),
+    ];
+
+    let batch = [
+        receipt("receipt-1", 1024, 1_700_000_000),
+        receipt("receipt-2", 3, 1_700_000_060),
+        receipt("receipt-3", u32::MAX, MAX_SAFE_INTEGER),
+    ];
+    let receipt_batch_root = MerkleTree::from_leaves(batch.iter().map(ReceiptCompressor::receipt_leaf).collect()).root();
+
+    let first = ProofMessage {
+        relay_pubkey: key("relay-1"),
+        pool_pubkey: key("pool-a"),
+        pool_type: PoolType::Subscribed,
+        batch_bytes: 4096,
+        cumulative_bytes: 4096,
+        prev_root: [0u8; 32],
+        new_root: receipt_batch_root,
+        proof: Vec::new(),
+        timestamp: 1_700_000_100,
+        signature: Vec::new(),
+    };
+    let second = ProofMessage {
+        pool_type: PoolType::Free,
+        batch_bytes: 512,
+        cumulative_bytes: 4608,
+        prev_root: receipt_batch_root,
+        new_root: key("root-2"),
+        timestamp: 1_700_000_200,
+        ..first.clone()
+    };
+
+    TestVectors {
+        version: TEST_VECTORS_VERSION,
+        merkle,
+        distributions,
+        receipts: batch.iter().map(receipt_vector).collect(),
+        receipt_batch_root: hex::encode(receipt_batch_root),
+        proof_messages: vec![proof_message_vector(&first), proof_message_vector(&second)],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Aggregator, PoolTracker, ProofClaim};
+
+    const GOLDEN: &str = include_str!("../../../test-vectors/hashing.json");
+
+    fn bytes32(hex_str: &str) -> [u8; 32] {
+        hex::decode(hex_str).unwrap().try_into().unwrap()
+    }
+
+    #[test]
+    fn test_golden_file_is_current() {
+        let golden = TestVectors::from_json(GOLDEN).unwrap();
+        assert_eq!(
+            golden,
+            generate(),
+            "test-vectors/hashing.json is stale; regenerate it with `craftnet aggregator test-vectors --out test-vectors/hashing.json`",
+        );
+    }
+
+    #[test]
+    fn test_aggregator_distribution_matches_vectors() {
+        let golden = TestVectors::from_json(GOLDEN).unwrap();
+        for vector in &golden.distributions {
+            let pool_key = (bytes32(&vector.pool), PoolType::Subscribed);
+            let mut aggregator = Aggregator::new();
+            let relay_claims = vector.entries.iter()
+                .map(|entry| (bytes32(&entry.relay), ProofClaim {
+                    cumulative_bytes: entry.bytes,
+                    latest_root: [0u8; 32],
+                    last_updated: 0,
+                }))
+                .collect();
+            aggregator.pools.insert(pool_key, PoolTracker { relay_claims });
+
+            let dist = aggregator.build_distribution(&pool_key).unwrap();
+            assert_eq!(hex::encode(dist.root), vector.root, "{}", vector.name);
+            assert_eq!(dist.total, vector.total_bytes);
+            for claim in &vector.claims {
+                let (proof, index) = dist.proof_for_relay(&bytes32(&claim.relay)).unwrap();
+                assert_eq!(index as usize, claim.leaf_index, "{}", vector.name);
+                let siblings: Vec<String> = proof.siblings.iter().map(hex::encode).collect();
+                assert_eq!(siblings, claim.siblings, "{}", vector.name);
+            }
+        }
+    }
+}
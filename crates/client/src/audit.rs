@@ -0,0 +1,390 @@
+//! Opt-in local request audit log
+//!
+//! Records one entry per request that went through the tunnel — time,
+//! destination host, bytes up/down, hop count, exit and outcome — for users
+//! who want a local record of their traffic. Nothing is recorded unless
+//! `NodeConfig::audit_log` is set.
+//!
+//! Each entry is a JSON object sealed with ChaCha20-Poly1305 under a key
+//! supplied by the caller and stored as one hex line (`nonce || ciphertext`),
+//! so the file is useless without the key. When the file would grow past
+//! `max_file_bytes` it is rotated to `{path}.1` (older files shift up to
+//! `{path}.{max_files}`, the oldest is deleted).
+
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use chacha20poly1305::aead::Aead;
+use chacha20poly1305::{ChaCha20Poly1305, KeyInit, Nonce};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// Default size at which the audit log is rotated (1 MiB)
+pub const DEFAULT_AUDIT_MAX_FILE_BYTES: u64 = 1024 * 1024;
+
+/// Default number of rotated files kept next to the live one
+pub const DEFAULT_AUDIT_MAX_FILES: u32 = 4;
+
+const NONCE_LEN: usize = 12;
+
+/// Where and how the audit log is written
+#[derive(Debug, Clone)]
+pub struct AuditLogConfig {
+    /// Live log file; rotated files get a `.1`, `.2`, ... suffix
+    pub path: PathBuf,
+    /// ChaCha20-Poly1305 key the entries are sealed with
+    pub key: [u8; 32],
+    /// Rotate once the live file would exceed this many bytes
+    pub max_file_bytes: u64,
+    /// Rotated files kept (0 = truncate instead of rotating)
+    pub max_files: u32,
+}
+
+impl AuditLogConfig {
+    pub fn new(path: PathBuf, key: [u8; 32]) -> Self {
+        Self {
+            path,
+            key,
+            max_file_bytes: DEFAULT_AUDIT_MAX_FILE_BYTES,
+            max_files: DEFAULT_AUDIT_MAX_FILES,
+        }
+    }
+}
+
+/// How a recorded request ended
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum AuditOutcome {
+    /// The exit answered with this HTTP status
+    Response { status: u16 },
+    /// The request failed before a response arrived
+    Error { code: String },
+}
+
+impl AuditOutcome {
+    fn as_csv(&self) -> String {
+        match self {
+            Self::Response { status } => status.to_string(),
+            Self::Error { code } => code.clone(),
+        }
+    }
+}
+
+/// One audited request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Unix time (seconds) the request completed
+    pub timestamp: u64,
+    /// Destination host (and port, if the URL had one)
+    pub host: String,
+    pub bytes_up: u64,
+    pub bytes_down: u64,
+    /// Relay hops between client and exit
+    pub hops: u8,
+    /// Exit pubkey (hex), if one was selected
+    pub exit: Option<String>,
+    pub outcome: AuditOutcome,
+}
+
+/// Export format of [`AuditLog::export`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditExportFormat {
+    Jsonl,
+    Csv,
+}
+
+impl std::str::FromStr for AuditExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "jsonl" | "json" => Ok(Self::Jsonl),
+            "csv" => Ok(Self::Csv),
+            other => Err(format!("unknown audit export format: {}", other)),
+        }
+    }
+}
+
+/// Host part of a URL (`scheme://[user@]host[:port]/...`)
+pub fn audit_host(url: &str) -> String {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or("");
+    authority.rsplit_once('@').map_or(authority, |(_, host)| host).to_string()
+}
+
+/// Encrypted, size-rotated audit log
+pub struct AuditLog {
+    config: AuditLogConfig,
+    cipher: ChaCha20Poly1305,
+}
+
+impl std::fmt::Debug for AuditLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuditLog").field("path", &self.config.path).finish()
+    }
+}
+
+impl AuditLog {
+    pub fn new(config: AuditLogConfig) -> Self {
+        let cipher = ChaCha20Poly1305::new((&config.key[..]).into());
+        Self { config, cipher }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.config.path
+    }
+
+    /// Append an entry, rotating first if the live file is full
+    pub fn record(&self, entry: &AuditEntry) -> std::io::Result<()> {
+        let line = self.seal(entry)?;
+        let len = fs::metadata(&self.config.path).map(|m| m.len()).unwrap_or(0);
+        if len > 0 && len + line.len() as u64 + 1 > self.config.max_file_bytes {
+            self.rotate()?;
+        }
+        if let Some(parent) = self.config.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.config.path)?;
+        writeln!(file, "{}", line)
+    }
+
+    /// Entries completed at or after `since`, oldest first, at most the
+    /// `limit` newest ones
+    pub fn query(&self, since: Option<u64>, limit: Option<usize>) -> Vec<AuditEntry> {
+        let mut entries: Vec<AuditEntry> = self.files_oldest_first()
+            .iter()
+            .flat_map(|path| self.read_file(path))
+            .filter(|e| since.map_or(true, |since| e.timestamp >= since))
+            .collect();
+        if let Some(limit) = limit {
+            let skip = entries.len().saturating_sub(limit);
+            entries.drain(..skip);
+        }
+        entries
+    }
+
+    /// Decrypted entries (see [`Self::query`]) in the given format
+    pub fn export(&self, format: AuditExportFormat, since: Option<u64>) -> String {
+        let entries = self.query(since, None);
+        let mut out = String::new();
+        match format {
+            AuditExportFormat::Jsonl => {
+                for entry in &entries {
+                    if let Ok(json) = serde_json::to_string(entry) {
+                        out.push_str(&json);
+                        out.push('\n');
+                    }
+                }
+            }
+            AuditExportFormat::Csv => {
+                out.push_str("timestamp,host,bytes_up,bytes_down,hops,exit,outcome\n");
+                for e in &entries {
+                    out.push_str(&format!(
+                        "{},{},{},{},{},{},{}\n",
+                        e.timestamp,
+                        csv_field(&e.host),
+                        e.bytes_up,
+                        e.bytes_down,
+                        e.hops,
+                        e.exit.as_deref().unwrap_or(""),
+                        csv_field(&e.outcome.as_csv()),
+                    ));
+                }
+            }
+        }
+        out
+    }
+
+    /// Delete the live and all rotated files
+    pub fn clear(&self) -> std::io::Result<()> {
+        for path in self.files_oldest_first() {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    fn rotated_path(&self, n: u32) -> PathBuf {
+        let mut name = self.config.path.as_os_str().to_os_string();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&self) -> std::io::Result<()> {
+        if self.config.max_files == 0 {
+            return fs::remove_file(&self.config.path);
+        }
+        let oldest = self.rotated_path(self.config.max_files);
+        if oldest.exists() {
+            fs::remove_file(&oldest)?;
+        }
+        for n in (1..self.config.max_files).rev() {
+            let from = self.rotated_path(n);
+            if from.exists() {
+                fs::rename(&from, self.rotated_path(n + 1))?;
+            }
+        }
+        fs::rename(&self.config.path, self.rotated_path(1))
+    }
+
+    fn files_oldest_first(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = (1..=self.config.max_files)
+            .rev()
+            .map(|n| self.rotated_path(n))
+            .collect();
+        files.push(self.config.path.clone());
+        files.retain(|p| p.exists());
+        files
+    }
+
+    fn seal(&self, entry: &AuditEntry) -> std::io::Result<String> {
+        let plaintext = serde_json::to_vec(entry)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        let nonce_bytes: [u8; NONCE_LEN] = rand::random();
+        let ciphertext = self.cipher
+            .encrypt(Nonce::from_slice(&nonce_bytes), plaintext.as_slice())
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "audit entry encryption failed"))?;
+        let mut sealed = nonce_bytes.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        Ok(hex::encode(sealed))
+    }
+
+    fn open(&self, line: &str) -> Option<AuditEntry> {
+        let sealed = hex::decode(line.trim()).ok()?;
+        if sealed.len() <= NONCE_LEN {
+            return None;
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let plaintext = self.cipher.decrypt(Nonce::from_slice(nonce), ciphertext).ok()?;
+        serde_json::from_slice(&plaintext).ok()
+    }
+
+    fn read_file(&self, path: &Path) -> Vec<AuditEntry> {
+        let file = match fs::File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                warn!("Failed to open audit log {}: {}", path.display(), e);
+                return Vec::new();
+            }
+        };
+        let mut skipped = 0;
+        let entries = BufReader::new(file)
+            .lines()
+            .map_while(|line| line.ok())
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| {
+                let entry = self.open(&line);
+                if entry.is_none() {
+                    skipped += 1;
+                }
+                entry
+            })
+            .collect();
+        if skipped > 0 {
+            warn!("Skipped {} unreadable audit entries in {}", skipped, path.display());
+        }
+        entries
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_log(max_file_bytes: u64, max_files: u32) -> AuditLog {
+        let mut path = std::env::temp_dir();
+        path.push(format!("craftnet_audit_{}", rand::random::<u64>()));
+        fs::create_dir_all(&path).unwrap();
+        path.push("audit.log");
+        AuditLog::new(AuditLogConfig { path, key: [7u8; 32], max_file_bytes, max_files })
+    }
+
+    fn entry(timestamp: u64, host: &str) -> AuditEntry {
+        AuditEntry {
+            timestamp,
+            host: host.to_string(),
+            bytes_up: 10,
+            bytes_down: 2048,
+            hops: 2,
+            exit: Some("ab".repeat(32)),
+            outcome: AuditOutcome::Response { status: 200 },
+        }
+    }
+
+    #[test]
+    fn test_audit_host() {
+        assert_eq!(audit_host("https://example.com/path?q=1"), "example.com");
+        assert_eq!(audit_host("http://user:pw@example.com:8080/"), "example.com:8080");
+        assert_eq!(audit_host("example.com"), "example.com");
+    }
+
+    #[test]
+    fn test_record_and_query_round_trip() {
+        let log = temp_log(DEFAULT_AUDIT_MAX_FILE_BYTES, DEFAULT_AUDIT_MAX_FILES);
+        log.record(&entry(100, "a.example")).unwrap();
+        log.record(&entry(200, "b.example")).unwrap();
+
+        assert_eq!(log.query(None, None), vec![entry(100, "a.example"), entry(200, "b.example")]);
+        assert_eq!(log.query(Some(150), None), vec![entry(200, "b.example")]);
+        assert_eq!(log.query(None, Some(1)), vec![entry(200, "b.example")]);
+
+        // Hosts are not stored in the clear
+        let raw = fs::read_to_string(log.path()).unwrap();
+        assert!(!raw.contains("a.example"));
+    }
+
+    #[test]
+    fn test_wrong_key_reads_nothing() {
+        let log = temp_log(DEFAULT_AUDIT_MAX_FILE_BYTES, DEFAULT_AUDIT_MAX_FILES);
+        log.record(&entry(100, "a.example")).unwrap();
+
+        let other = AuditLog::new(AuditLogConfig::new(log.path().to_path_buf(), [8u8; 32]));
+        assert!(other.query(None, None).is_empty());
+    }
+
+    #[test]
+    fn test_rotation_keeps_max_files() {
+        let log = temp_log(300, 2);
+        for i in 0..20 {
+            log.record(&entry(i, "rotate.example")).unwrap();
+        }
+        assert!(log.rotated_path(1).exists());
+        assert!(log.rotated_path(2).exists());
+        assert!(!log.rotated_path(3).exists());
+
+        let entries = log.query(None, None);
+        assert!(entries.len() < 20);
+        assert_eq!(entries.last().unwrap().timestamp, 19);
+        assert!(entries.windows(2).all(|w| w[0].timestamp < w[1].timestamp));
+    }
+
+    #[test]
+    fn test_export_formats() {
+        let log = temp_log(DEFAULT_AUDIT_MAX_FILE_BYTES, DEFAULT_AUDIT_MAX_FILES);
+        log.record(&entry(100, "a.example")).unwrap();
+        log.record(&AuditEntry {
+            outcome: AuditOutcome::Error { code: "TIMEOUT".to_string() },
+            exit: None,
+            ..entry(200, "b.example")
+        }).unwrap();
+
+        let jsonl = log.export(AuditExportFormat::Jsonl, None);
+        let parsed: Vec<AuditEntry> = jsonl.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(parsed.len(), 2);
+
+        let csv = log.export(AuditExportFormat::Csv, None);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "timestamp,host,bytes_up,bytes_down,hops,exit,outcome");
+        assert!(lines[1].ends_with(",200"));
+        assert_eq!(lines[2], "200,b.example,10,2048,2,,TIMEOUT");
+    }
+}
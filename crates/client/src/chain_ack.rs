@@ -0,0 +1,285 @@
+//! Relay chain ack bookkeeping
+//!
+//! Both ends of `craftnet_core::chain_ack` live here. As a relay, the node
+//! keeps [`ChainAckRoutes`]: for each shard it forwarded with an ack
+//! request, acks arriving from the next hop are passed on to the previous
+//! one. As a client, [`ChainAckTracker`] holds the [`ShardAckChain`] of every
+//! shard it sent and checks the acks coming back against the relays it
+//! picked. Once all of a shard's relays acked, or [`CHAIN_ACK_TIMEOUT`]
+//! passed, the chain settles into a [`ChainOutcome`] that feeds stats and
+//! relay scoring.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use libp2p::PeerId;
+
+use craftnet_core::{verify_chain_ack, ChainAck, Id, PublicKey};
+
+use crate::shard_builder::ShardAckChain;
+
+/// How long acks for a shard are waited for (and routes kept)
+pub const CHAIN_ACK_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Most ack routes a relay keeps; the oldest are dropped past this
+pub const MAX_CHAIN_ACK_ROUTES: usize = 65_536;
+
+/// Most shard chains a client tracks at once
+pub const MAX_TRACKED_CHAINS: usize = 16_384;
+
+/// Where a relay passes on acks for one forwarded shard
+#[derive(Debug, Clone)]
+struct AckRoute {
+    /// Peer we forwarded the shard to; only it may send acks on this route
+    downstream: PeerId,
+    /// Peer we got the shard from
+    upstream: PeerId,
+    up_ref: Id,
+    /// Acks still expected from relays past us
+    remaining: u8,
+    added_at: Instant,
+}
+
+/// Relay-side routes for acks coming back from the next hop
+#[derive(Debug, Default)]
+pub struct ChainAckRoutes {
+    routes: HashMap<Id, AckRoute>,
+    /// Insertion order, for expiry
+    order: VecDeque<Id>,
+}
+
+impl ChainAckRoutes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// We forwarded a shard to `downstream` with `downstream_relays` relays
+    /// after us: acks arriving tagged `down_ref` go to `upstream` as `up_ref`
+    pub fn insert(&mut self, down_ref: Id, downstream: PeerId, upstream: PeerId, up_ref: Id, downstream_relays: u8, now: Instant) {
+        self.expire(now);
+        if downstream_relays == 0 {
+            return;
+        }
+        while self.routes.len() >= MAX_CHAIN_ACK_ROUTES {
+            let Some(oldest) = self.order.pop_front() else { break };
+            self.routes.remove(&oldest);
+        }
+        let route = AckRoute { downstream, upstream, up_ref, remaining: downstream_relays, added_at: now };
+        if self.routes.insert(down_ref, route).is_none() {
+            self.order.push_back(down_ref);
+        }
+    }
+
+    /// The ack `from` sent, re-tagged for the peer to pass it to (None if
+    /// it isn't on a route of ours, or the route has carried all its acks)
+    pub fn route(&mut self, from: &PeerId, ack: &ChainAck) -> Option<(PeerId, ChainAck)> {
+        let route = self.routes.get_mut(&ack.ack_ref).filter(|r| r.downstream == *from && r.remaining > 0)?;
+        route.remaining -= 1;
+        let routed = (route.upstream, ChainAck { ack_ref: route.up_ref, signature: ack.signature });
+        if route.remaining == 0 {
+            self.routes.remove(&ack.ack_ref);
+        }
+        Some(routed)
+    }
+
+    pub fn len(&self) -> usize {
+        self.routes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while let Some(oldest) = self.order.front() {
+            if self.routes.get(oldest).is_some_and(|r| now.duration_since(r.added_at) < CHAIN_ACK_TIMEOUT) {
+                break;
+            }
+            // Expired, or already removed once all its acks passed
+            if let Some(oldest) = self.order.pop_front() {
+                self.routes.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// How one shard's path did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainOutcome {
+    /// Relays whose ack verified
+    pub verified: Vec<PublicKey>,
+    /// Relays that never acked
+    pub missing: Vec<PublicKey>,
+    /// Relay the shard was lost at: the last one that acked (it didn't get
+    /// the shard to the next), or the gateway if none did
+    pub blamed: Option<PublicKey>,
+}
+
+#[derive(Debug)]
+struct TrackedChain {
+    chain: ShardAckChain,
+    acked: Vec<bool>,
+    sent_at: Instant,
+}
+
+impl TrackedChain {
+    fn outcome(&self) -> ChainOutcome {
+        let mut outcome = ChainOutcome { verified: Vec::new(), missing: Vec::new(), blamed: None };
+        for (hop, &acked) in self.chain.hops.iter().zip(&self.acked) {
+            if acked {
+                outcome.verified.push(hop.relay_pubkey);
+            } else {
+                outcome.missing.push(hop.relay_pubkey);
+            }
+        }
+        if !outcome.missing.is_empty() {
+            let last_acked = self.acked.iter().rposition(|&a| a).unwrap_or(0);
+            outcome.blamed = self.chain.hops.get(last_acked).map(|h| h.relay_pubkey);
+        }
+        outcome
+    }
+}
+
+/// Client-side chains of the shards we sent, by the ref their acks reach us with
+#[derive(Debug, Default)]
+pub struct ChainAckTracker {
+    chains: HashMap<Id, TrackedChain>,
+}
+
+impl ChainAckTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A shard with `chain` was sent at `now`
+    pub fn track(&mut self, chain: ShardAckChain, now: Instant) {
+        if chain.hops.is_empty() || self.chains.len() >= MAX_TRACKED_CHAINS {
+            return;
+        }
+        let acked = vec![false; chain.hops.len()];
+        self.chains.insert(chain.ack_ref, TrackedChain { chain, acked, sent_at: now });
+    }
+
+    /// Whether acks tagged `ack_ref` are ours
+    pub fn contains(&self, ack_ref: &Id) -> bool {
+        self.chains.contains_key(ack_ref)
+    }
+
+    /// Check an ack against the relays of its chain. Returns the chain's
+    /// outcome once every relay acked; acks that verify against none of
+    /// them are ignored.
+    pub fn record(&mut self, ack: &ChainAck) -> Option<ChainOutcome> {
+        let tracked = self.chains.get_mut(&ack.ack_ref)?;
+        let hop = tracked.chain.hops.iter().position(|h| verify_chain_ack(&h.relay_pubkey, &h.nonce, &h.shard_id, ack))?;
+        tracked.acked[hop] = true;
+        if !tracked.acked.iter().all(|&a| a) {
+            return None;
+        }
+        self.chains.remove(&ack.ack_ref).map(|t| t.outcome())
+    }
+
+    /// Settle chains that stopped waiting for acks at `now`
+    pub fn expire(&mut self, now: Instant) -> Vec<ChainOutcome> {
+        let expired: Vec<Id> = self.chains
+            .iter()
+            .filter(|(_, t)| now.duration_since(t.sent_at) >= CHAIN_ACK_TIMEOUT)
+            .map(|(ack_ref, _)| *ack_ref)
+            .collect();
+        expired.iter().filter_map(|r| self.chains.remove(r)).map(|t| t.outcome()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.chains.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chains.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use craftec_crypto::SigningKeypair;
+    use craftnet_core::{sign_chain_ack, ChainAckRequest};
+
+    use crate::shard_builder::ExpectedAck;
+
+    /// A 3-relay chain plus each relay's ack
+    fn chain() -> (ShardAckChain, Vec<ChainAck>) {
+        let mut hops = Vec::new();
+        let mut acks = Vec::new();
+        for k in 0..3u8 {
+            let relay = SigningKeypair::generate();
+            let hop = ExpectedAck { relay_pubkey: relay.public_key_bytes(), shard_id: [k; 32], nonce: [k + 10; 32] };
+            // By the time they reach us, every ack carries the first ref
+            let request = ChainAckRequest { up_ref: [1u8; 32], down_ref: None, nonce: hop.nonce };
+            acks.push(sign_chain_ack(&relay, &request, &hop.shard_id));
+            hops.push(hop);
+        }
+        (ShardAckChain { ack_ref: [1u8; 32], hops }, acks)
+    }
+
+    #[test]
+    fn test_full_chain_verifies() {
+        let (chain, acks) = chain();
+        let relays: Vec<_> = chain.hops.iter().map(|h| h.relay_pubkey).collect();
+        let mut tracker = ChainAckTracker::new();
+        tracker.track(chain, Instant::now());
+
+        assert_eq!(tracker.record(&acks[2]), None);
+        // A forged signature counts for nobody
+        assert_eq!(tracker.record(&ChainAck { ack_ref: [1u8; 32], signature: [0u8; 64] }), None);
+        assert_eq!(tracker.record(&acks[0]), None);
+        let outcome = tracker.record(&acks[1]).unwrap();
+        assert_eq!(outcome.verified.len(), 3);
+        assert!(outcome.verified.iter().all(|r| relays.contains(r)));
+        assert_eq!(outcome.blamed, None);
+        assert!(tracker.is_empty());
+    }
+
+    #[test]
+    fn test_missing_hops_blame_last_acked_relay() {
+        let start = Instant::now();
+        let (chain, acks) = chain();
+        let relays: Vec<_> = chain.hops.iter().map(|h| h.relay_pubkey).collect();
+        let mut tracker = ChainAckTracker::new();
+        tracker.track(chain.clone(), start);
+        tracker.record(&acks[0]);
+
+        assert!(tracker.expire(start + CHAIN_ACK_TIMEOUT / 2).is_empty());
+        let outcomes = tracker.expire(start + CHAIN_ACK_TIMEOUT);
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!(outcomes[0].verified, vec![relays[0]]);
+        assert_eq!(outcomes[0].missing, vec![relays[1], relays[2]]);
+        assert_eq!(outcomes[0].blamed, Some(relays[0]));
+
+        // Nothing came back: the gateway dropped it
+        tracker.track(chain, start);
+        let outcomes = tracker.expire(start + CHAIN_ACK_TIMEOUT);
+        assert_eq!(outcomes[0].blamed, Some(relays[0]));
+        assert_eq!(outcomes[0].missing.len(), 3);
+    }
+
+    #[test]
+    fn test_routes_pass_acks_upstream() {
+        let now = Instant::now();
+        let (upstream, downstream) = (PeerId::random(), PeerId::random());
+        let mut routes = ChainAckRoutes::new();
+        routes.insert([2u8; 32], downstream, upstream, [1u8; 32], 2, now);
+
+        let ack = ChainAck { ack_ref: [2u8; 32], signature: [5u8; 64] };
+        // Only the peer we forwarded to may use the route
+        assert_eq!(routes.route(&PeerId::random(), &ack), None);
+        assert_eq!(routes.route(&downstream, &ack), Some((upstream, ChainAck { ack_ref: [1u8; 32], signature: [5u8; 64] })));
+        assert!(routes.route(&downstream, &ack).is_some());
+        // Two relays past us, two acks
+        assert_eq!(routes.route(&downstream, &ack), None);
+        assert!(routes.is_empty());
+
+        // Routes expire
+        routes.insert([3u8; 32], downstream, upstream, [1u8; 32], 1, now);
+        routes.insert([4u8; 32], downstream, upstream, [1u8; 32], 1, now + CHAIN_ACK_TIMEOUT);
+        assert_eq!(routes.len(), 1);
+    }
+}
@@ -0,0 +1,210 @@
+//! Coalescing of small requests into shared assemblies
+//!
+//! Every request pays for its own erasure coding, onion wrapping and
+//! response assembly, which dominates the cost of tiny API calls. Requests
+//! handed to `CraftNetNode::fetch_many` are grouped by [`plan_batches`]: runs
+//! of small requests for the same exit travel as one `PAYLOAD_MODE_HTTP_BATCH`
+//! request, and [`split_batch_response`] hands each caller its own response
+//! back. Large requests still go alone.
+//!
+//! A batch succeeds or times out as a whole; the exit reports per-request
+//! failures (blocked host, upstream error) inside the batch response.
+
+use craftnet_core::{BatchResponse, SubResult, BATCH_RESPONSE_HEADER, MAX_BATCH_REQUESTS};
+
+use crate::{ClientError, RequestBuilder, Result, TunnelResponse};
+
+/// Default requests per coalesced assembly
+pub const DEFAULT_COALESCE_MAX_REQUESTS: usize = 8;
+
+/// Default serialized bytes per coalesced assembly
+pub const DEFAULT_COALESCE_MAX_BYTES: usize = 16 * 1024;
+
+/// Default largest request that is coalesced at all
+pub const DEFAULT_COALESCE_MAX_REQUEST_BYTES: usize = 2 * 1024;
+
+/// How `fetch_many` groups requests
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoalescePolicy {
+    /// Requests per assembly (capped at `MAX_BATCH_REQUESTS`)
+    pub max_requests: usize,
+    /// Serialized request bytes per assembly
+    pub max_bytes: usize,
+    /// Requests larger than this are sent alone
+    pub max_request_bytes: usize,
+}
+
+impl Default for CoalescePolicy {
+    fn default() -> Self {
+        Self {
+            max_requests: DEFAULT_COALESCE_MAX_REQUESTS,
+            max_bytes: DEFAULT_COALESCE_MAX_BYTES,
+            max_request_bytes: DEFAULT_COALESCE_MAX_REQUEST_BYTES,
+        }
+    }
+}
+
+/// One request for `CraftNetNode::fetch_many`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueuedRequest {
+    pub method: String,
+    pub url: String,
+    pub body: Option<Vec<u8>>,
+    pub headers: Option<Vec<(String, String)>>,
+}
+
+impl QueuedRequest {
+    /// A request without body or headers
+    pub fn new(method: &str, url: &str) -> Self {
+        Self { method: method.to_string(), url: url.to_string(), body: None, headers: None }
+    }
+
+    /// The request as a [`RequestBuilder`] (for a coalesced request)
+    pub(crate) fn builder(&self) -> RequestBuilder {
+        let mut builder = RequestBuilder::new(&self.method, &self.url);
+        for (key, value) in self.headers.iter().flatten() {
+            builder = builder.header(key, value);
+        }
+        if let Some(body) = &self.body {
+            builder = builder.body(body.clone());
+        }
+        builder
+    }
+
+    /// Approximate serialized size, as [`plan_batches`] counts it
+    pub fn size(&self) -> usize {
+        let headers: usize = self
+            .headers
+            .iter()
+            .flatten()
+            .map(|(k, v)| k.len() + v.len() + 3)
+            .sum();
+        self.method.len() + self.url.len() + headers + self.body.as_ref().map_or(0, Vec::len) + 16
+    }
+}
+
+/// Group requests (by their `sizes`) into assemblies, keeping their order.
+///
+/// Returns the request indices of each assembly. Consecutive small requests
+/// share an assembly until it reaches the policy's request or byte limit;
+/// a request over `max_request_bytes` gets an assembly of its own.
+pub fn plan_batches(sizes: &[usize], policy: &CoalescePolicy) -> Vec<Vec<usize>> {
+    let max_requests = policy.max_requests.clamp(1, MAX_BATCH_REQUESTS);
+    let mut batches: Vec<Vec<usize>> = Vec::new();
+    let mut current: Vec<usize> = Vec::new();
+    let mut current_bytes = 0;
+    for (i, &size) in sizes.iter().enumerate() {
+        if size > policy.max_request_bytes {
+            if !current.is_empty() {
+                batches.push(std::mem::take(&mut current));
+                current_bytes = 0;
+            }
+            batches.push(vec![i]);
+            continue;
+        }
+        if !current.is_empty() && (current.len() >= max_requests || current_bytes + size > policy.max_bytes) {
+            batches.push(std::mem::take(&mut current));
+            current_bytes = 0;
+        }
+        current.push(i);
+        current_bytes += size;
+    }
+    if !current.is_empty() {
+        batches.push(current);
+    }
+    batches
+}
+
+/// Split the response to a coalesced request of `count` sub-requests into
+/// one result per sub-request, in sub-request order.
+///
+/// Fails as a whole if the response is not a well-formed batch answering
+/// every sub-request exactly once.
+pub fn split_batch_response(response: &TunnelResponse, count: usize) -> Result<Vec<Result<TunnelResponse>>> {
+    let is_batch = response.headers.keys().any(|k| k.eq_ignore_ascii_case(BATCH_RESPONSE_HEADER));
+    if response.status != 200 || !is_batch {
+        return Err(ClientError::RequestFailed(format!(
+            "exit answered a coalesced request with status {}",
+            response.status,
+        )));
+    }
+    let batch = BatchResponse::from_bytes(&response.body).map_err(|_| ClientError::InvalidResponse)?;
+
+    let mut results: Vec<Option<Result<TunnelResponse>>> = (0..count).map(|_| None).collect();
+    for sub in batch.responses {
+        let slot = results.get_mut(sub.sub_id as usize).ok_or(ClientError::InvalidResponse)?;
+        if slot.is_some() {
+            return Err(ClientError::InvalidResponse);
+        }
+        *slot = Some(match sub.result {
+            SubResult::Response(data) => TunnelResponse::from_bytes(&data).map(|mut r| {
+                r.hop_timings = response.hop_timings.clone();
+                r
+            }),
+            SubResult::Error(e) => Err(ClientError::RequestFailed(e)),
+        });
+    }
+    results
+        .into_iter()
+        .map(|r| r.ok_or(ClientError::InvalidResponse))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    use craftnet_core::SubResponse;
+
+    #[test]
+    fn test_plan_batches() {
+        let policy = CoalescePolicy { max_requests: 3, max_bytes: 100, max_request_bytes: 50 };
+        // Count limit, then a large request breaking the run, then the byte limit
+        let sizes = [10, 10, 10, 10, 80, 40, 40, 40];
+        assert_eq!(
+            plan_batches(&sizes, &policy),
+            vec![vec![0, 1, 2], vec![3], vec![4], vec![5, 6], vec![7]],
+        );
+        assert!(plan_batches(&[], &policy).is_empty());
+
+        // The request limit never exceeds what an exit accepts
+        let policy = CoalescePolicy { max_requests: 1000, max_bytes: usize::MAX, max_request_bytes: 50 };
+        assert_eq!(plan_batches(&[1; 40], &policy)[0].len(), MAX_BATCH_REQUESTS);
+    }
+
+    fn batch_response(responses: Vec<SubResponse>) -> TunnelResponse {
+        TunnelResponse {
+            status: 200,
+            headers: HashMap::from([(BATCH_RESPONSE_HEADER.to_string(), responses.len().to_string())]),
+            body: BatchResponse { responses }.to_bytes().unwrap(),
+            hop_timings: Vec::new(),
+            exit_failovers: 0,
+        }
+    }
+
+    #[test]
+    fn test_split_batch_response() {
+        let response = batch_response(vec![
+            SubResponse { sub_id: 1, result: SubResult::Error("blocked".to_string()) },
+            SubResponse { sub_id: 0, result: SubResult::Response(b"201\n0\n2\nok".to_vec()) },
+        ]);
+        let results = split_batch_response(&response, 2).unwrap();
+        let first = results[0].as_ref().unwrap();
+        assert_eq!((first.status, first.body.as_slice()), (201, b"ok".as_slice()));
+        assert!(matches!(&results[1], Err(ClientError::RequestFailed(e)) if e == "blocked"));
+
+        // A missing, unknown or repeated sub_id fails the whole batch
+        assert!(split_batch_response(&response, 3).is_err());
+        assert!(split_batch_response(&response, 1).is_err());
+        let repeated = batch_response(vec![
+            SubResponse { sub_id: 0, result: SubResult::Error("a".to_string()) },
+            SubResponse { sub_id: 0, result: SubResult::Error("b".to_string()) },
+        ]);
+        assert!(split_batch_response(&repeated, 2).is_err());
+
+        // A plain response (an exit without batch support) is refused
+        let plain = TunnelResponse { status: 400, headers: HashMap::new(), body: Vec::new(), hop_timings: Vec::new(), exit_failovers: 0 };
+        assert!(split_batch_response(&plain, 2).is_err());
+    }
+}
@@ -0,0 +1,453 @@
+//! Opt-in cookie jar for stateful browsing through the tunnel
+//!
+//! By default every request through the tunnel is stateless: the client
+//! never sends a cookie, so nothing links two requests beyond what the
+//! network already sees. Users who want to stay logged in can enable
+//! `NodeConfig::cookie_jar`; `fetch()` then stores the `Set-Cookie` headers
+//! of responses and sends matching cookies back.
+//!
+//! Cookies are kept per browsing identity (see `identity`), so identities
+//! stay unlinkable to origins too. The jar is persisted as one JSON document
+//! sealed with ChaCha20-Poly1305 (`nonce || ciphertext`, hex encoded) and
+//! rewritten whenever it changes; without a path it lives in memory only.
+//!
+//! Matching follows RFC 6265 closely enough for ordinary sites: domain and
+//! host-only cookies, path prefixes, `Secure`, `Max-Age` and `Expires`.
+//! Public-suffix checks are not done; a cookie is only accepted for the
+//! host that set it or a parent domain of it.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use chacha20poly1305::aead::Aead;
+use chacha20poly1305::{ChaCha20Poly1305, KeyInit, Nonce};
+use craftnet_core::{system_clock, SharedClock};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// ChaCha20-Poly1305 nonce length (bytes)
+const NONCE_LEN: usize = 12;
+
+/// Most cookies kept per identity (oldest are evicted first)
+pub const MAX_COOKIES_PER_IDENTITY: usize = 1000;
+
+/// Jar key of requests made without an identity
+const DEFAULT_IDENTITY: &str = "";
+
+/// Where and how the cookie jar is kept
+#[derive(Debug, Clone)]
+pub struct CookieJarConfig {
+    /// Sealed jar file (None = cookies live in memory only)
+    pub path: Option<PathBuf>,
+    /// ChaCha20-Poly1305 key the file is sealed with
+    pub key: [u8; 32],
+}
+
+impl CookieJarConfig {
+    /// A jar persisted to `path`, sealed with `key`
+    pub fn persistent(path: PathBuf, key: [u8; 32]) -> Self {
+        Self { path: Some(path), key }
+    }
+
+    /// A jar forgotten when the node stops
+    pub fn in_memory() -> Self {
+        Self { path: None, key: [0u8; 32] }
+    }
+}
+
+/// One stored cookie
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    /// Lowercase domain, without a leading dot
+    pub domain: String,
+    /// Only sent to `domain` itself (no `Domain` attribute was given)
+    pub host_only: bool,
+    pub path: String,
+    /// Unix second the cookie expires at (None = session cookie)
+    pub expires: Option<u64>,
+    pub secure: bool,
+    pub http_only: bool,
+    /// Unix second the cookie was stored (eviction order)
+    pub created: u64,
+}
+
+/// Scheme, host and path of a request URL
+struct RequestUrl {
+    secure: bool,
+    host: String,
+    path: String,
+}
+
+impl RequestUrl {
+    fn parse(url: &str) -> Option<Self> {
+        let (scheme, rest) = url.split_once("://")?;
+        let secure = match scheme.to_ascii_lowercase().as_str() {
+            "https" | "wss" => true,
+            "http" | "ws" => false,
+            _ => return None,
+        };
+        let (authority, path) = match rest.find(['/', '?', '#']) {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+        let host_port = authority.rsplit('@').next().unwrap_or(authority);
+        let host = if let Some(end) = host_port.find(']') {
+            &host_port[..=end]
+        } else {
+            host_port.split(':').next().unwrap_or(host_port)
+        };
+        if host.is_empty() {
+            return None;
+        }
+        let path = path.split(['?', '#']).next().unwrap_or("/");
+        let path = if path.starts_with('/') { path } else { "/" };
+        Some(Self { secure, host: host.to_ascii_lowercase(), path: path.to_string() })
+    }
+
+    /// Default cookie path: the request path up to its last `/`
+    fn default_path(&self) -> String {
+        match self.path.rfind('/') {
+            Some(0) | None => "/".to_string(),
+            Some(i) => self.path[..i].to_string(),
+        }
+    }
+}
+
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain
+        || (host.ends_with(domain)
+            && host.as_bytes()[host.len() - domain.len() - 1] == b'.'
+            && !host.starts_with('['))
+}
+
+fn path_matches(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || (request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || request_path.as_bytes()[cookie_path.len()] == b'/'))
+}
+
+/// Split a header value that may combine several `Set-Cookie` headers
+/// (joined with `, `) without breaking on the comma inside `Expires`
+pub fn split_set_cookie(value: &str) -> Vec<&str> {
+    let mut cookies = Vec::new();
+    let mut start = 0;
+    for (i, _) in value.match_indices(',') {
+        // A new cookie starts with `name=` before any `;`
+        let next = &value[i + 1..];
+        let head = next.split(';').next().unwrap_or(next);
+        let starts_cookie = head
+            .split_once('=')
+            .is_some_and(|(name, _)| !name.trim().is_empty() && !name.trim().contains(' '));
+        if starts_cookie {
+            cookies.push(value[start..i].trim());
+            start = i + 1;
+        }
+    }
+    cookies.push(value[start..].trim());
+    cookies.retain(|c| !c.is_empty());
+    cookies
+}
+
+/// Parse a cookie date (`Expires`), tolerating the dashed form
+fn parse_cookie_date(value: &str) -> Option<u64> {
+    let parsed = httpdate::parse_http_date(value)
+        .or_else(|_| httpdate::parse_http_date(&value.replace('-', " ")))
+        .ok()?;
+    Some(parsed.duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs()))
+}
+
+/// Parse one `Set-Cookie` value received for `url` at `now`.
+///
+/// Returns None for malformed cookies and ones the URL may not set.
+fn parse_set_cookie(value: &str, url: &RequestUrl, now: u64) -> Option<Cookie> {
+    let mut parts = value.split(';');
+    let (name, cookie_value) = parts.next()?.split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+    let mut cookie = Cookie {
+        name: name.to_string(),
+        value: cookie_value.trim().to_string(),
+        domain: url.host.clone(),
+        host_only: true,
+        path: url.default_path(),
+        expires: None,
+        secure: false,
+        http_only: false,
+        created: now,
+    };
+    let mut max_age = None;
+    for attribute in parts {
+        let (key, attr_value) = attribute.split_once('=').unwrap_or((attribute, ""));
+        let attr_value = attr_value.trim();
+        match key.trim().to_ascii_lowercase().as_str() {
+            "domain" if !attr_value.is_empty() => {
+                let domain = attr_value.trim_start_matches('.').to_ascii_lowercase();
+                if domain.is_empty() || !domain_matches(&url.host, &domain) {
+                    return None;
+                }
+                cookie.domain = domain;
+                cookie.host_only = false;
+            }
+            "path" if attr_value.starts_with('/') => cookie.path = attr_value.to_string(),
+            "max-age" => {
+                if let Ok(seconds) = attr_value.parse::<i64>() {
+                    max_age = Some(seconds);
+                }
+            }
+            "expires" => {
+                if let Some(at) = parse_cookie_date(attr_value) {
+                    cookie.expires = cookie.expires.or(Some(at));
+                }
+            }
+            "secure" => cookie.secure = true,
+            "httponly" => cookie.http_only = true,
+            _ => {}
+        }
+    }
+    // Max-Age wins over Expires
+    if let Some(seconds) = max_age {
+        cookie.expires = Some(if seconds <= 0 { 0 } else { now.saturating_add(seconds as u64) });
+    }
+    if cookie.secure && !url.secure {
+        return None;
+    }
+    Some(cookie)
+}
+
+/// Cookies of every identity, optionally persisted encrypted
+pub struct CookieJar {
+    config: CookieJarConfig,
+    cipher: ChaCha20Poly1305,
+    /// Identity name ("" = no identity) → cookies
+    identities: HashMap<String, Vec<Cookie>>,
+    clock: SharedClock,
+}
+
+impl std::fmt::Debug for CookieJar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CookieJar").field("path", &self.config.path).finish_non_exhaustive()
+    }
+}
+
+impl CookieJar {
+    /// Open the jar, reading its file if there is one.
+    ///
+    /// A file that can't be read or opened with the key starts an empty jar.
+    pub fn load(config: CookieJarConfig) -> Self {
+        let cipher = ChaCha20Poly1305::new(&config.key.into());
+        let mut jar = Self { config, cipher, identities: HashMap::new(), clock: system_clock() };
+        if let Some(path) = jar.config.path.clone().filter(|p| p.exists()) {
+            match fs::read_to_string(&path).ok().and_then(|sealed| jar.open(&sealed)) {
+                Some(identities) => jar.identities = identities,
+                None => warn!("Unreadable cookie jar {} — starting empty", path.display()),
+            }
+        }
+        jar
+    }
+
+    /// Drop expired cookies on save by `clock` instead of the system clock
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+    }
+
+    /// The `Cookie` header for a request to `url` by `identity`
+    pub fn header_for(&self, identity: Option<&str>, url: &str, now: u64) -> Option<String> {
+        let url = RequestUrl::parse(url)?;
+        let cookies = self.identities.get(identity.unwrap_or(DEFAULT_IDENTITY))?;
+        let mut matching: Vec<&Cookie> = cookies
+            .iter()
+            .filter(|c| c.expires.is_none_or(|at| at > now))
+            .filter(|c| if c.host_only { url.host == c.domain } else { domain_matches(&url.host, &c.domain) })
+            .filter(|c| path_matches(&url.path, &c.path))
+            .filter(|c| !c.secure || url.secure)
+            .collect();
+        if matching.is_empty() {
+            return None;
+        }
+        // Longer paths first, then older cookies (RFC 6265 §5.4)
+        matching.sort_by(|a, b| b.path.len().cmp(&a.path.len()).then(a.created.cmp(&b.created)));
+        Some(
+            matching
+                .iter()
+                .map(|c| format!("{}={}", c.name, c.value))
+                .collect::<Vec<_>>()
+                .join("; "),
+        )
+    }
+
+    /// Store the `Set-Cookie` values of a response to `url` for `identity`.
+    ///
+    /// Returns whether the jar changed (and was saved).
+    pub fn store(&mut self, identity: Option<&str>, url: &str, set_cookies: &[&str], now: u64) -> bool {
+        let Some(url) = RequestUrl::parse(url) else { return false };
+        let cookies = self.identities.entry(identity.unwrap_or(DEFAULT_IDENTITY).to_string()).or_default();
+        let mut changed = false;
+        for value in set_cookies {
+            let Some(mut cookie) = parse_set_cookie(value, &url, now) else { continue };
+            let existing = cookies
+                .iter()
+                .position(|c| c.name == cookie.name && c.domain == cookie.domain && c.path == cookie.path);
+            if let Some(i) = existing {
+                cookie.created = cookies[i].created;
+                cookies.remove(i);
+            }
+            // An expiry in the past deletes the cookie
+            if cookie.expires.is_none_or(|at| at > now) {
+                cookies.push(cookie);
+            }
+            changed = true;
+        }
+        if cookies.len() > MAX_COOKIES_PER_IDENTITY {
+            cookies.sort_by_key(|c| c.created);
+            let excess = cookies.len() - MAX_COOKIES_PER_IDENTITY;
+            cookies.drain(..excess);
+        }
+        if changed {
+            self.save();
+        }
+        changed
+    }
+
+    /// Forget the cookies of `identity` (None = every identity)
+    pub fn clear(&mut self, identity: Option<&str>) {
+        match identity {
+            Some(name) => {
+                self.identities.remove(name);
+            }
+            None => self.identities.clear(),
+        }
+        self.save();
+    }
+
+    /// Cookies stored for `identity` (None = requests without one)
+    pub fn cookies(&self, identity: Option<&str>) -> &[Cookie] {
+        self.identities
+            .get(identity.unwrap_or(DEFAULT_IDENTITY))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Rewrite the jar file without session and expired cookies
+    fn save(&self) {
+        let Some(ref path) = self.config.path else { return };
+        let now = self.clock.unix_now();
+        let persistent: HashMap<&String, Vec<&Cookie>> = self.identities
+            .iter()
+            .map(|(name, cookies)| {
+                (name, cookies.iter().filter(|c| c.expires.is_some_and(|at| at > now)).collect())
+            })
+            .collect();
+        let result = self.seal(&persistent).and_then(|sealed| {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(path, sealed)
+        });
+        if let Err(e) = result {
+            warn!("Failed to write cookie jar {}: {}", path.display(), e);
+        }
+    }
+
+    fn seal(&self, identities: &HashMap<&String, Vec<&Cookie>>) -> std::io::Result<String> {
+        let plaintext = serde_json::to_vec(identities)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        let nonce_bytes: [u8; NONCE_LEN] = rand::random();
+        let ciphertext = self.cipher
+            .encrypt(Nonce::from_slice(&nonce_bytes), plaintext.as_slice())
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "cookie jar encryption failed"))?;
+        let mut sealed = nonce_bytes.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        Ok(hex::encode(sealed))
+    }
+
+    fn open(&self, sealed: &str) -> Option<HashMap<String, Vec<Cookie>>> {
+        let sealed = hex::decode(sealed.trim()).ok()?;
+        if sealed.len() <= NONCE_LEN {
+            return None;
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let plaintext = self.cipher.decrypt(Nonce::from_slice(nonce), ciphertext).ok()?;
+        serde_json::from_slice(&plaintext).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("craftnet-cookies-{}-{}", name, rand::random::<u64>()));
+        dir.join("cookies.jar")
+    }
+
+    #[test]
+    fn test_cookie_matching() {
+        let mut jar = CookieJar::load(CookieJarConfig::in_memory());
+        let stored = jar.store(None, "https://www.example.com/account/login", &[
+            "sid=abc; Path=/; Secure; HttpOnly",
+            "pref=dark; Domain=.example.com; Path=/",
+            "tab=2",
+            "evil=1; Domain=other.com",
+        ], NOW);
+        assert!(stored);
+        assert_eq!(jar.cookies(None).len(), 3);
+
+        assert_eq!(jar.header_for(None, "https://www.example.com/account/x", NOW).unwrap(), "tab=2; sid=abc; pref=dark");
+        // Host-only, Secure and path scoping
+        assert_eq!(jar.header_for(None, "https://api.example.com/", NOW).unwrap(), "pref=dark");
+        assert_eq!(jar.header_for(None, "http://www.example.com/", NOW).unwrap(), "pref=dark");
+        assert!(jar.header_for(None, "https://example.org/", NOW).is_none());
+
+        // Identities don't see each other's cookies
+        assert!(jar.header_for(Some("work"), "https://www.example.com/", NOW).is_none());
+
+        // Max-Age=0 deletes, Expires in the past too
+        jar.store(None, "https://www.example.com/", &["sid=; Path=/; Max-Age=0"], NOW);
+        jar.store(None, "https://www.example.com/", &["pref=x; Domain=example.com; Path=/; Expires=Thu, 01 Jan 1970 00:00:00 GMT"], NOW);
+        assert_eq!(jar.header_for(None, "https://www.example.com/account/", NOW).unwrap(), "tab=2");
+    }
+
+    #[test]
+    fn test_split_set_cookie() {
+        assert_eq!(
+            split_set_cookie("a=1; Expires=Wed, 21 Oct 2037 07:28:00 GMT; Path=/, b=2"),
+            vec!["a=1; Expires=Wed, 21 Oct 2037 07:28:00 GMT; Path=/", "b=2"],
+        );
+        assert_eq!(parse_cookie_date("Wed, 21-Oct-2037 07:28:00 GMT"), Some(2_139_722_880));
+    }
+
+    #[test]
+    fn test_persisted_encrypted_per_identity() {
+        let path = temp_path("persist");
+        let config = CookieJarConfig::persistent(path.clone(), [7u8; 32]);
+        let mut jar = CookieJar::load(config.clone());
+        let now = system_clock().unix_now();
+        jar.store(Some("work"), "https://example.com/", &["keep=1; Max-Age=3600", "session=1"], now);
+        jar.store(None, "https://example.com/", &["other=1; Max-Age=3600"], now);
+
+        let raw = fs::read_to_string(&path).unwrap();
+        assert!(!raw.contains("keep"));
+
+        // Session cookies are not persisted
+        let reloaded = CookieJar::load(config.clone());
+        assert_eq!(reloaded.header_for(Some("work"), "https://example.com/", now).unwrap(), "keep=1");
+
+        // The wrong key reads nothing
+        let wrong = CookieJar::load(CookieJarConfig::persistent(path.clone(), [8u8; 32]));
+        assert!(wrong.cookies(Some("work")).is_empty());
+
+        let mut jar = reloaded;
+        jar.clear(Some("work"));
+        let reloaded = CookieJar::load(config);
+        assert!(reloaded.cookies(Some("work")).is_empty());
+        assert_eq!(reloaded.cookies(None).len(), 1);
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,208 @@
+//! Cover traffic for idle circuits
+//!
+//! When a first-hop relay has seen no real shards for a while, the client
+//! sends dummy shards to it at a fixed rate so link-level observers can't
+//! tell idle from active circuits by timing.
+//!
+//! Cover shards are ordinary one-hop onion shards whose settlement layer has
+//! `cover = true`. The relay peels the layer, acks the shard and drops it
+//! without signing a ForwardReceipt, so cover traffic never enters proofs,
+//! distributions or credits.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use craftec_crypto::EncryptionKeypair;
+use craftnet_core::onion_crypto::{build_onion_header, encrypt_routing_tag};
+use craftnet_core::{OnionSettlement, Shard};
+use craftnet_erasure::chunker::CHUNK_SIZE;
+use craftnet_erasure::DATA_SHARDS;
+use libp2p::PeerId;
+use rand::RngCore;
+
+use crate::path::random_id;
+use crate::{ClientError, Result};
+
+/// Payload size of a cover shard: one erasure piece of a full chunk,
+/// the most common size of real shards
+pub const COVER_PAYLOAD_SIZE: usize = CHUNK_SIZE / DATA_SHARDS;
+
+/// Cover traffic settings
+#[derive(Debug, Clone)]
+pub struct CoverTrafficConfig {
+    /// Time between cover shards on one idle circuit
+    pub interval: Duration,
+    /// A circuit is idle once no real shard was sent on it for this long
+    pub idle_after: Duration,
+    /// Circuits idle for longer than this are forgotten (no more cover)
+    pub max_idle: Duration,
+}
+
+impl Default for CoverTrafficConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(2),
+            idle_after: Duration::from_secs(5),
+            max_idle: Duration::from_secs(300),
+        }
+    }
+}
+
+/// Tracks circuit activity and decides when cover shards are due
+#[derive(Debug)]
+pub struct CoverTraffic {
+    config: CoverTrafficConfig,
+    /// First-hop peer → last real shard sent
+    last_activity: HashMap<PeerId, Instant>,
+    /// First-hop peer → last cover shard sent
+    last_cover: HashMap<PeerId, Instant>,
+}
+
+impl CoverTraffic {
+    pub fn new(config: CoverTrafficConfig) -> Self {
+        Self {
+            config,
+            last_activity: HashMap::new(),
+            last_cover: HashMap::new(),
+        }
+    }
+
+    /// Record a real shard sent to a first-hop peer
+    pub fn record_activity(&mut self, peer: PeerId, now: Instant) {
+        self.last_activity.insert(peer, now);
+    }
+
+    /// Stop sending cover to a peer (disconnected)
+    pub fn forget(&mut self, peer: &PeerId) {
+        self.last_activity.remove(peer);
+        self.last_cover.remove(peer);
+    }
+
+    /// Idle circuits that should get a cover shard now.
+    ///
+    /// Marks the returned peers as covered at `now`.
+    pub fn due(&mut self, now: Instant) -> Vec<PeerId> {
+        let max_idle = self.config.max_idle;
+        self.last_activity.retain(|_, last| now.duration_since(*last) < max_idle);
+        let active = &self.last_activity;
+        self.last_cover.retain(|peer, _| active.contains_key(peer));
+
+        let mut due = Vec::new();
+        for (peer, last) in &self.last_activity {
+            if now.duration_since(*last) < self.config.idle_after {
+                continue;
+            }
+            let cover_due = self.last_cover
+                .get(peer)
+                .map_or(true, |t| now.duration_since(*t) >= self.config.interval);
+            if cover_due {
+                due.push(*peer);
+            }
+        }
+        for peer in &due {
+            self.last_cover.insert(*peer, now);
+        }
+        due
+    }
+}
+
+/// Build a one-hop cover shard for a first-hop relay.
+///
+/// The payload and routing tag are random / throwaway-encrypted so the shard
+/// is the same shape as a real one; only the relay's onion layer marks it
+/// as cover.
+pub fn build_cover_shard(relay_peer_id: &[u8], relay_encryption_pubkey: &[u8; 32]) -> Result<Shard> {
+    let settlement = OnionSettlement {
+        shard_id: random_id(),
+        payload_size: COVER_PAYLOAD_SIZE as u32,
+        pool_pubkey: [0u8; 32],
+        cover: true,
+        trace_pubkey: None,
+        chain_ack: None,
+    };
+
+    // Destination is never reached — the relay drops cover shards
+    let destination = EncryptionKeypair::generate();
+    let (header, ephemeral) = build_onion_header(
+        &[(relay_peer_id, relay_encryption_pubkey)],
+        (&random_id()[..], &destination.public_key_bytes()),
+        &[settlement],
+        None,
+    ).map_err(|e| ClientError::CryptoError(e.to_string()))?;
+
+    let routing_tag = encrypt_routing_tag(
+        &destination.public_key_bytes(),
+        &random_id(),
+        0,
+        DATA_SHARDS as u8,
+        0,
+        1,
+        &[0u8; 32],
+    ).map_err(|e| ClientError::CryptoError(e.to_string()))?;
+
+    let mut payload = vec![0u8; COVER_PAYLOAD_SIZE];
+    rand::thread_rng().fill_bytes(&mut payload);
+
+    Ok(Shard::new(ephemeral, header, payload, routing_tag, 1, 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use craftnet_core::onion_crypto::peel_onion_layer;
+
+    fn config() -> CoverTrafficConfig {
+        CoverTrafficConfig {
+            interval: Duration::from_secs(2),
+            idle_after: Duration::from_secs(5),
+            max_idle: Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    fn test_active_circuit_gets_no_cover() {
+        let mut cover = CoverTraffic::new(config());
+        let peer = PeerId::random();
+        let t0 = Instant::now();
+        cover.record_activity(peer, t0);
+
+        assert!(cover.due(t0 + Duration::from_secs(1)).is_empty());
+    }
+
+    #[test]
+    fn test_idle_circuit_gets_cover_at_interval() {
+        let mut cover = CoverTraffic::new(config());
+        let peer = PeerId::random();
+        let t0 = Instant::now();
+        cover.record_activity(peer, t0);
+
+        assert_eq!(cover.due(t0 + Duration::from_secs(6)), vec![peer]);
+        // Not again until the interval elapses
+        assert!(cover.due(t0 + Duration::from_secs(7)).is_empty());
+        assert_eq!(cover.due(t0 + Duration::from_secs(8)), vec![peer]);
+    }
+
+    #[test]
+    fn test_long_idle_circuit_forgotten() {
+        let mut cover = CoverTraffic::new(config());
+        let peer = PeerId::random();
+        let t0 = Instant::now();
+        cover.record_activity(peer, t0);
+
+        assert!(cover.due(t0 + Duration::from_secs(61)).is_empty());
+    }
+
+    #[test]
+    fn test_cover_shard_marked_for_relay_only() {
+        let relay = EncryptionKeypair::generate();
+        let relay_pid = PeerId::random().to_bytes();
+        let shard = build_cover_shard(&relay_pid, &relay.public_key_bytes()).unwrap();
+
+        assert_eq!(shard.payload.len(), COVER_PAYLOAD_SIZE);
+        assert_eq!(shard.total_hops, 1);
+
+        let layer = peel_onion_layer(&relay.secret_key_bytes(), &shard.ephemeral_pubkey, &shard.header).unwrap();
+        assert!(layer.settlement.cover);
+        assert!(layer.remaining_header.is_empty());
+    }
+}
@@ -0,0 +1,226 @@
+//! Response assembly decoding
+//!
+//! Turns the response shards of one assembly back into the exit's plaintext:
+//! erasure-decode every chunk, reassemble, strip the 4-byte length frame,
+//! decrypt with the client's response key, and undo zstd compression if the
+//! routing tag says so. Nothing here touches the network or the runtime, so
+//! the native node and the WASM client share it.
+
+use std::collections::{BTreeMap, HashMap};
+
+use craftnet_core::{open_hop_timing, HopTiming, PayloadCompression, RoutingTag, TAG_FLAG_ZSTD};
+use craftnet_erasure::chunker::reassemble;
+use craftnet_erasure::{ErasureCoder, DATA_SHARDS, TOTAL_SHARDS};
+
+use crate::{ClientError, Result};
+
+/// Largest response a compressed payload may expand to (exit default max + headroom)
+pub const MAX_DECOMPRESSED_RESPONSE: usize = 64 * 1024 * 1024;
+
+/// Whether every chunk of a response assembly has enough shards to decode
+pub fn response_chunks_ready(shards: &HashMap<(u16, u8), Vec<u8>>, total_chunks: u16) -> bool {
+    if total_chunks == 0 {
+        return false;
+    }
+
+    let mut chunk_counts: HashMap<u16, usize> = HashMap::new();
+    for &(chunk_idx, _) in shards.keys() {
+        *chunk_counts.entry(chunk_idx).or_default() += 1;
+    }
+
+    if chunk_counts.len() < total_chunks as usize {
+        return false;
+    }
+    chunk_counts.values().all(|&count| count >= DATA_SHARDS)
+}
+
+/// Erasure-decode, unframe and decrypt one response assembly
+pub fn decode_response_payload(
+    erasure: &ErasureCoder,
+    shards: &HashMap<(u16, u8), Vec<u8>>,
+    total_chunks: u16,
+    exit_enc_pubkey: &[u8; 32],
+    response_secret: &[u8; 32],
+) -> Result<Vec<u8>> {
+    // Group shard payloads by chunk_index
+    let mut chunks_by_index: HashMap<u16, Vec<(u8, &Vec<u8>)>> = HashMap::new();
+    for (&(chunk_idx, shard_idx), payload) in shards {
+        chunks_by_index
+            .entry(chunk_idx)
+            .or_default()
+            .push((shard_idx, payload));
+    }
+
+    // Reconstruct each chunk independently
+    let mut reconstructed_chunks: BTreeMap<u16, Vec<u8>> = BTreeMap::new();
+
+    for chunk_idx in 0..total_chunks {
+        let chunk_shards = chunks_by_index.get(&chunk_idx);
+        let mut shard_data: Vec<Option<Vec<u8>>> = vec![None; TOTAL_SHARDS];
+        let mut shard_size = 0usize;
+
+        if let Some(payloads) = chunk_shards {
+            for &(shard_idx, payload) in payloads {
+                let idx = shard_idx as usize;
+                if idx < TOTAL_SHARDS {
+                    shard_size = payload.len();
+                    shard_data[idx] = Some(payload.clone());
+                }
+            }
+        }
+
+        let max_len = shard_size * DATA_SHARDS;
+        let chunk_data = erasure
+            .decode(&mut shard_data, max_len)
+            .map_err(|e| ClientError::ErasureError(e.to_string()))?;
+
+        reconstructed_chunks.insert(chunk_idx, chunk_data);
+    }
+
+    // Reassemble
+    let total_possible = reconstructed_chunks.values().map(|c| c.len()).sum();
+    let framed_data = reassemble(&reconstructed_chunks, total_chunks, total_possible)
+        .map_err(|e| ClientError::ErasureError(e.to_string()))?;
+
+    // Strip length-prefixed framing (4-byte LE u32 original length)
+    if framed_data.len() < 4 {
+        return Err(ClientError::InvalidResponse);
+    }
+    let original_len = u32::from_le_bytes(
+        framed_data[..4].try_into().unwrap()
+    ) as usize;
+    if framed_data.len() < 4 + original_len {
+        return Err(ClientError::InvalidResponse);
+    }
+    let encrypted_data = &framed_data[4..4 + original_len];
+
+    // Decrypt the response using the exit's encryption pubkey stored at request time
+    craftec_crypto::decrypt_from_sender(
+        exit_enc_pubkey,
+        response_secret,
+        encrypted_data,
+    ).map_err(|e| ClientError::CryptoError(format!("Response decrypt failed: {}", e)))
+}
+
+/// Undo payload compression according to the routing tag flags.
+///
+/// Compressed payloads are recorded in `stats` when given.
+pub fn decompress_response(
+    data: Vec<u8>,
+    flags: u8,
+    stats: Option<&PayloadCompression>,
+) -> Result<Vec<u8>> {
+    if flags & TAG_FLAG_ZSTD == 0 {
+        return Ok(data);
+    }
+    let plain = craftnet_core::decompress(&data, MAX_DECOMPRESSED_RESPONSE)
+        .map_err(|e| ClientError::RequestFailed(format!("Response decompression failed: {}", e)))?;
+    if let Some(stats) = stats {
+        stats.record(plain.len(), data.len());
+    }
+    Ok(plain)
+}
+
+/// Open the sealed hop timings of a traced request with its trace key,
+/// keeping their order and skipping blobs that don't open
+pub fn open_trace(trace_secret: &[u8; 32], blobs: &[Vec<u8>]) -> Vec<HopTiming> {
+    blobs.iter().filter_map(|blob| open_hop_timing(trace_secret, blob)).collect()
+}
+
+/// Response shards collected for one assembly
+#[derive(Debug, Default)]
+pub struct ResponseAssembly {
+    shards: HashMap<(u16, u8), Vec<u8>>,
+    total_chunks: u16,
+    flags: u8,
+}
+
+impl ResponseAssembly {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add one shard payload, using its decrypted routing tag for placement
+    pub fn insert(&mut self, tag: &RoutingTag, payload: Vec<u8>) {
+        if self.total_chunks == 0 {
+            self.total_chunks = tag.total_chunks;
+        }
+        self.flags = tag.flags;
+        self.shards.insert((tag.chunk_index, tag.shard_index), payload);
+    }
+
+    /// Whether every chunk can be decoded
+    pub fn is_ready(&self) -> bool {
+        response_chunks_ready(&self.shards, self.total_chunks)
+    }
+
+    /// Shards collected so far
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Decode, decrypt and decompress the assembly
+    pub fn decode(
+        &self,
+        erasure: &ErasureCoder,
+        exit_enc_pubkey: &[u8; 32],
+        response_secret: &[u8; 32],
+    ) -> Result<Vec<u8>> {
+        let data = decode_response_payload(
+            erasure,
+            &self.shards,
+            self.total_chunks,
+            exit_enc_pubkey,
+            response_secret,
+        )?;
+        decompress_response(data, self.flags, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunks_ready_needs_data_shards_per_chunk() {
+        let mut shards = HashMap::new();
+        assert!(!response_chunks_ready(&shards, 0));
+        for shard in 0..DATA_SHARDS as u8 {
+            shards.insert((0u16, shard), vec![0u8; 4]);
+        }
+        assert!(response_chunks_ready(&shards, 1));
+        assert!(!response_chunks_ready(&shards, 2));
+        shards.insert((1, 0), vec![0u8; 4]);
+        assert!(!response_chunks_ready(&shards, 2));
+    }
+
+    #[test]
+    fn test_open_trace_keeps_path_order() {
+        use craftec_crypto::EncryptionKeypair;
+        use craftnet_core::{seal_hop_timing, HopRole};
+
+        let keys = EncryptionKeypair::generate();
+        let timing = |role, at| HopTiming { role, received_at_ms: at, sent_at_ms: at + 2 };
+        let mut blobs: Vec<Vec<u8>> = [timing(HopRole::Relay, 10), timing(HopRole::Exit, 20), timing(HopRole::Relay, 90)]
+            .iter()
+            .map(|t| seal_hop_timing(&keys.public_key_bytes(), t).unwrap())
+            .collect();
+        blobs.insert(1, vec![0u8; 80]);
+
+        let timings = open_trace(&keys.secret_key_bytes(), &blobs);
+        assert_eq!(timings.iter().map(|t| t.role).collect::<Vec<_>>(), vec![HopRole::Relay, HopRole::Exit, HopRole::Relay]);
+        assert_eq!(timings[2].received_at_ms, 90);
+    }
+
+    #[test]
+    fn test_decompress_response() {
+        let data = b"plain".to_vec();
+        assert_eq!(decompress_response(data.clone(), 0, None).unwrap(), data);
+
+        let stats = PayloadCompression::default();
+        let body = b"compress me ".repeat(100);
+        let compressed = craftnet_core::compress(&body).unwrap();
+        assert_eq!(decompress_response(compressed, TAG_FLAG_ZSTD, Some(&stats)).unwrap(), body);
+        assert_eq!(stats.payloads(), 1);
+    }
+}
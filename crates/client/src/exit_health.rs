@@ -0,0 +1,244 @@
+//! Exit health monitoring and failover
+//!
+//! Heartbeats only say an exit is up; they don't say its requests succeed.
+//! [`ExitHealth`] tracks the outcome and latency of the last requests sent
+//! through an exit (passive) plus the answers to small probes the node
+//! sends the selected exit when traffic is idle (active). When the selected
+//! exit's health crosses an [`ExitHealthPolicy`] threshold the node demotes
+//! it for a while, fails over to the next-best exit and reports the switch
+//! with its [`FailoverReason`].
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// Thresholds of exit health tracking
+#[derive(Debug, Clone)]
+pub struct ExitHealthPolicy {
+    /// Request outcomes kept per exit
+    pub window: usize,
+    /// Outcomes needed before error rate and latency are judged
+    pub min_samples: usize,
+    /// Error rate (0.0-1.0) above which the exit is failed over
+    pub max_error_rate: f64,
+    /// Average latency of successful requests above which the exit is
+    /// failed over
+    pub max_latency: Duration,
+    /// Consecutive unanswered probes after which the exit is failed over
+    pub max_probe_failures: u32,
+    /// How often the selected exit is probed when no request went through it
+    pub probe_interval: Duration,
+    /// How long a failed-over exit is passed over by exit selection
+    pub demotion: Duration,
+}
+
+impl Default for ExitHealthPolicy {
+    fn default() -> Self {
+        Self {
+            window: 20,
+            min_samples: 5,
+            max_error_rate: 0.5,
+            max_latency: Duration::from_secs(8),
+            max_probe_failures: 3,
+            probe_interval: Duration::from_secs(30),
+            demotion: Duration::from_secs(300),
+        }
+    }
+}
+
+/// Why the node moved off an exit
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FailoverReason {
+    /// Too many recent requests failed
+    ErrorRate { error_rate: f64, samples: usize },
+    /// Recent requests were too slow
+    Latency { avg_latency_ms: u64 },
+    /// Probes went unanswered
+    ProbeFailures { consecutive: u32 },
+}
+
+/// The node switched exits because the selected one went bad
+#[derive(Debug, Clone, Serialize)]
+pub struct ExitFailoverEvent {
+    /// Exit moved off (signing pubkey, hex)
+    pub from_exit: String,
+    /// Exit selected instead (None = no other exit available)
+    pub to_exit: Option<String>,
+    pub reason: FailoverReason,
+}
+
+/// Recent request outcomes and probe results of one exit
+#[derive(Debug, Clone, Default)]
+pub struct ExitHealth {
+    /// Latency of each recent request, None for a failure (oldest first)
+    outcomes: VecDeque<Option<Duration>>,
+    consecutive_probe_failures: u32,
+    last_activity: Option<Instant>,
+    demoted_until: Option<Instant>,
+}
+
+impl ExitHealth {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(&mut self, outcome: Option<Duration>, policy: &ExitHealthPolicy) {
+        self.outcomes.push_back(outcome);
+        while self.outcomes.len() > policy.window.max(1) {
+            self.outcomes.pop_front();
+        }
+        self.last_activity = Some(Instant::now());
+    }
+
+    /// A request through the exit completed after `latency`
+    pub fn record_success(&mut self, latency: Duration, policy: &ExitHealthPolicy) {
+        self.push(Some(latency), policy);
+        self.consecutive_probe_failures = 0;
+    }
+
+    /// A request through the exit failed or timed out
+    pub fn record_failure(&mut self, policy: &ExitHealthPolicy) {
+        self.push(None, policy);
+    }
+
+    /// A probe went unanswered. Probes are requests too: their outcome is
+    /// recorded like any other, this only counts the failures in a row.
+    pub fn record_probe_failure(&mut self) {
+        self.consecutive_probe_failures = self.consecutive_probe_failures.saturating_add(1);
+    }
+
+    /// Fraction of recent requests that failed
+    pub fn error_rate(&self) -> f64 {
+        if self.outcomes.is_empty() {
+            return 0.0;
+        }
+        let failures = self.outcomes.iter().filter(|o| o.is_none()).count();
+        failures as f64 / self.outcomes.len() as f64
+    }
+
+    /// Average latency of recent successful requests
+    pub fn avg_latency(&self) -> Option<Duration> {
+        let latencies: Vec<Duration> = self.outcomes.iter().flatten().copied().collect();
+        if latencies.is_empty() {
+            return None;
+        }
+        Some(latencies.iter().sum::<Duration>() / latencies.len() as u32)
+    }
+
+    /// Whether the exit has been idle long enough to need a probe
+    pub fn probe_due(&self, policy: &ExitHealthPolicy, now: Instant) -> bool {
+        self.last_activity
+            .is_none_or(|at| now.saturating_duration_since(at) >= policy.probe_interval)
+    }
+
+    /// Why the exit should be failed over, if it should
+    pub fn verdict(&self, policy: &ExitHealthPolicy) -> Option<FailoverReason> {
+        if self.consecutive_probe_failures >= policy.max_probe_failures {
+            return Some(FailoverReason::ProbeFailures { consecutive: self.consecutive_probe_failures });
+        }
+        if self.outcomes.len() < policy.min_samples {
+            return None;
+        }
+        let error_rate = self.error_rate();
+        if error_rate > policy.max_error_rate {
+            return Some(FailoverReason::ErrorRate { error_rate, samples: self.outcomes.len() });
+        }
+        match self.avg_latency() {
+            Some(avg) if avg > policy.max_latency => {
+                Some(FailoverReason::Latency { avg_latency_ms: avg.as_millis() as u64 })
+            }
+            _ => None,
+        }
+    }
+
+    /// Pass the exit over until `demotion` has passed, starting its health
+    /// record afresh for when it is tried again
+    pub fn demote(&mut self, policy: &ExitHealthPolicy, now: Instant) {
+        self.outcomes.clear();
+        self.consecutive_probe_failures = 0;
+        self.demoted_until = Some(now + policy.demotion);
+    }
+
+    /// Whether exit selection should pass the exit over
+    pub fn is_demoted(&self, now: Instant) -> bool {
+        self.demoted_until.is_some_and(|until| now < until)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_healthy_exit_has_no_verdict() {
+        let policy = ExitHealthPolicy::default();
+        let mut health = ExitHealth::new();
+        for _ in 0..10 {
+            health.record_success(Duration::from_millis(200), &policy);
+        }
+        health.record_failure(&policy);
+        assert!(health.verdict(&policy).is_none());
+        assert_eq!(health.avg_latency(), Some(Duration::from_millis(200)));
+    }
+
+    #[test]
+    fn test_error_rate_needs_min_samples() {
+        let policy = ExitHealthPolicy::default();
+        let mut health = ExitHealth::new();
+        for _ in 0..policy.min_samples - 1 {
+            health.record_failure(&policy);
+        }
+        assert!(health.verdict(&policy).is_none());
+        health.record_failure(&policy);
+        assert!(matches!(health.verdict(&policy), Some(FailoverReason::ErrorRate { .. })));
+    }
+
+    #[test]
+    fn test_window_forgets_old_failures() {
+        let policy = ExitHealthPolicy { window: 4, min_samples: 2, ..Default::default() };
+        let mut health = ExitHealth::new();
+        for _ in 0..4 {
+            health.record_failure(&policy);
+        }
+        for _ in 0..4 {
+            health.record_success(Duration::from_millis(100), &policy);
+        }
+        assert_eq!(health.error_rate(), 0.0);
+    }
+
+    #[test]
+    fn test_slow_exit_fails_over() {
+        let policy = ExitHealthPolicy::default();
+        let mut health = ExitHealth::new();
+        for _ in 0..policy.min_samples {
+            health.record_success(policy.max_latency * 2, &policy);
+        }
+        assert!(matches!(health.verdict(&policy), Some(FailoverReason::Latency { .. })));
+    }
+
+    #[test]
+    fn test_probe_failures_and_demotion() {
+        let policy = ExitHealthPolicy::default();
+        let mut health = ExitHealth::new();
+        let now = Instant::now();
+        assert!(health.probe_due(&policy, now));
+
+        for _ in 0..policy.max_probe_failures {
+            health.record_failure(&policy);
+            health.record_probe_failure();
+        }
+        assert_eq!(
+            health.verdict(&policy),
+            Some(FailoverReason::ProbeFailures { consecutive: policy.max_probe_failures }),
+        );
+
+        assert!(!health.probe_due(&policy, Instant::now()));
+
+        health.demote(&policy, now);
+        assert!(health.is_demoted(now));
+        assert!(!health.is_demoted(now + policy.demotion));
+        assert!(health.verdict(&policy).is_none());
+    }
+}
@@ -0,0 +1,248 @@
+//! Browsing identities
+//!
+//! Requests made under different identities must not be linkable by the
+//! network. Each named identity gets its own X25519 key for response onion
+//! layers, its own exit and its own pool of gateway circuits; the gateways
+//! one identity uses are avoided by every other identity while alternatives
+//! exist. Requests without an identity keep using the node's defaults.
+//!
+//! The request signing key and pool pubkey stay the node's: they are what
+//! the exit bills against, and are only visible to the exit.
+
+use std::collections::{HashMap, HashSet};
+
+use craftec_crypto::EncryptionKeypair;
+use craftnet_core::{onion_crypto::decrypt_routing_tag, PublicKey, RoutingTag};
+use libp2p::PeerId;
+
+use crate::{ClientError, CraftNetNode, Result, TunnelResponse};
+
+/// Maximum length of an identity name
+pub const MAX_IDENTITY_NAME_LEN: usize = 64;
+
+/// One browsing identity
+pub struct Identity {
+    /// Key the exit encrypts responses to
+    encryption_keypair: EncryptionKeypair,
+    /// Exit pinned for this identity (signing pubkey)
+    exit: Option<PublicKey>,
+    /// Gateways this identity's circuits went through
+    gateways: HashSet<PeerId>,
+}
+
+impl Identity {
+    fn new() -> Self {
+        Self {
+            encryption_keypair: EncryptionKeypair::generate(),
+            exit: None,
+            gateways: HashSet::new(),
+        }
+    }
+
+    pub fn encryption_keypair(&self) -> &EncryptionKeypair {
+        &self.encryption_keypair
+    }
+
+    pub fn exit(&self) -> Option<PublicKey> {
+        self.exit
+    }
+
+    pub fn gateways(&self) -> &HashSet<PeerId> {
+        &self.gateways
+    }
+}
+
+/// Named identities of a node
+#[derive(Default)]
+pub struct IdentityRegistry {
+    identities: HashMap<String, Identity>,
+}
+
+impl IdentityRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an identity with fresh key material
+    pub fn create(&mut self, name: &str) -> Result<()> {
+        if name.is_empty() || name.len() > MAX_IDENTITY_NAME_LEN {
+            return Err(ClientError::RequestFailed(format!("invalid identity name: {:?}", name)));
+        }
+        if self.identities.contains_key(name) {
+            return Err(ClientError::RequestFailed(format!("identity {} already exists", name)));
+        }
+        self.identities.insert(name.to_string(), Identity::new());
+        Ok(())
+    }
+
+    /// Forget an identity; responses still in flight for it are dropped
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.identities.remove(name).is_some()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Identity> {
+        self.identities.get(name)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.identities.is_empty()
+    }
+
+    /// Identity names, sorted
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.identities.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Pin `exit` for `name`
+    pub fn set_exit(&mut self, name: &str, exit: Option<PublicKey>) {
+        if let Some(identity) = self.identities.get_mut(name) {
+            identity.exit = exit;
+        }
+    }
+
+    /// Unpin `exit` from every identity (e.g. after it misbehaved)
+    pub fn unpin_exit(&mut self, exit: &PublicKey) {
+        for identity in self.identities.values_mut() {
+            if identity.exit.as_ref() == Some(exit) {
+                identity.exit = None;
+            }
+        }
+    }
+
+    /// Record that `name` built a circuit through `gateway`
+    pub fn add_gateway(&mut self, name: &str, gateway: PeerId) {
+        if let Some(identity) = self.identities.get_mut(name) {
+            identity.gateways.insert(gateway);
+        }
+    }
+
+    /// Forget a gateway that went away, for every identity
+    pub fn remove_gateway(&mut self, gateway: &PeerId) {
+        for identity in self.identities.values_mut() {
+            identity.gateways.remove(gateway);
+        }
+    }
+
+    /// Gateways used by identities other than `name`
+    pub fn gateways_of_others(&self, name: &str) -> HashSet<PeerId> {
+        self.identities
+            .iter()
+            .filter(|(n, _)| n.as_str() != name)
+            .flat_map(|(_, identity)| identity.gateways.iter().copied())
+            .collect()
+    }
+
+    /// Exits pinned by identities other than `name`
+    pub fn exits_of_others(&self, name: &str) -> HashSet<PublicKey> {
+        self.identities
+            .iter()
+            .filter(|(n, _)| n.as_str() != name)
+            .filter_map(|(_, identity)| identity.exit)
+            .collect()
+    }
+
+    /// Decrypt a response routing tag with any identity key, returning the
+    /// tag and the secret that opened it
+    pub fn decrypt_routing_tag(&self, tag: &[u8]) -> Option<(RoutingTag, [u8; 32])> {
+        self.identities.values().find_map(|identity| {
+            let secret = identity.encryption_keypair.secret_key_bytes();
+            decrypt_routing_tag(&secret, tag).ok().map(|t| (t, secret))
+        })
+    }
+}
+
+/// Requests made under one identity, see [`CraftNetNode::with_identity`]
+pub struct IdentityScope<'a> {
+    pub(crate) node: &'a mut CraftNetNode,
+    pub(crate) name: String,
+}
+
+impl IdentityScope<'_> {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Make an HTTP request through this identity's circuits
+    pub async fn fetch(
+        &mut self,
+        method: &str,
+        url: &str,
+        body: Option<Vec<u8>>,
+        headers: Option<Vec<(String, String)>>,
+    ) -> Result<TunnelResponse> {
+        self.node.fetch_as_identity(&self.name, method, url, body, headers).await
+    }
+
+    /// Make an HTTP GET request through this identity's circuits
+    pub async fn get(&mut self, url: &str) -> Result<TunnelResponse> {
+        self.fetch("GET", url, None, None).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use craftnet_core::onion_crypto::encrypt_routing_tag;
+
+    #[test]
+    fn test_create_and_remove() {
+        let mut registry = IdentityRegistry::new();
+        registry.create("work").unwrap();
+        registry.create("personal").unwrap();
+        assert!(registry.create("work").is_err());
+        assert!(registry.create("").is_err());
+        assert_eq!(registry.names(), vec!["personal".to_string(), "work".to_string()]);
+
+        assert!(registry.remove("work"));
+        assert!(!registry.remove("work"));
+        assert!(registry.get("work").is_none());
+    }
+
+    #[test]
+    fn test_identities_get_distinct_keys() {
+        let mut registry = IdentityRegistry::new();
+        registry.create("a").unwrap();
+        registry.create("b").unwrap();
+        let a = registry.get("a").unwrap().encryption_keypair().public_key_bytes();
+        let b = registry.get("b").unwrap().encryption_keypair().public_key_bytes();
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_gateways_and_exits_partitioned() {
+        let mut registry = IdentityRegistry::new();
+        registry.create("a").unwrap();
+        registry.create("b").unwrap();
+        let gw_a = PeerId::random();
+        let gw_b = PeerId::random();
+        registry.add_gateway("a", gw_a);
+        registry.add_gateway("b", gw_b);
+        registry.set_exit("a", Some([1u8; 32]));
+
+        assert_eq!(registry.gateways_of_others("a"), HashSet::from([gw_b]));
+        assert_eq!(registry.exits_of_others("b"), HashSet::from([[1u8; 32]]));
+        assert!(registry.exits_of_others("a").is_empty());
+
+        registry.remove_gateway(&gw_b);
+        assert!(registry.gateways_of_others("a").is_empty());
+    }
+
+    #[test]
+    fn test_decrypt_routing_tag_with_identity_key() {
+        let mut registry = IdentityRegistry::new();
+        registry.create("a").unwrap();
+        registry.create("b").unwrap();
+        let b_pub = registry.get("b").unwrap().encryption_keypair().public_key_bytes();
+        let tag = encrypt_routing_tag(&b_pub, &[7u8; 32], 0, 5, 0, 1, &[0u8; 32]).unwrap();
+
+        let (decoded, secret) = registry.decrypt_routing_tag(&tag).unwrap();
+        assert_eq!(decoded.assembly_id, [7u8; 32]);
+        assert_eq!(secret, registry.get("b").unwrap().encryption_keypair().secret_key_bytes());
+
+        let stranger = EncryptionKeypair::generate();
+        let tag = encrypt_routing_tag(&stranger.public_key_bytes(), &[7u8; 32], 0, 5, 0, 1, &[0u8; 32]).unwrap();
+        assert!(registry.decrypt_routing_tag(&tag).is_none());
+    }
+}
@@ -0,0 +1,193 @@
+//! Kill switch state machine
+//!
+//! When the tunnel drops without the user asking for it, traffic would
+//! silently fall back to the raw network. The kill switch tracks whether
+//! that fallback must be blocked; platform integrations (firewall rules,
+//! VpnService / Network Extension on-demand rules) watch its transitions and
+//! install or remove the actual block.
+//!
+//! ```text
+//!            unexpected disconnect / engage()
+//!   Armed ───────────────────────────────────▶ Engaged
+//!     ▲                                          │
+//!     │ reconnect                     release()  │
+//!     └──────────── Released ◀───────────────────┘
+//! ```
+//!
+//! A user-initiated disconnect never engages it, and a disabled kill switch
+//! only engages when asked explicitly.
+
+use serde::{Deserialize, Serialize};
+
+/// Kill switch state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KillSwitchState {
+    /// Not blocking; will engage if the tunnel drops unexpectedly (when enabled)
+    Armed,
+    /// Blocking all traffic outside the tunnel
+    Engaged,
+    /// Released by the user while the tunnel is still down
+    Released,
+}
+
+/// Snapshot of the kill switch for IPC/FFI
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KillSwitchStatus {
+    /// Engage automatically on unexpected disconnects
+    pub enabled: bool,
+    pub state: KillSwitchState,
+    /// Why it last engaged
+    pub reason: Option<String>,
+    /// Unix time (seconds) it last engaged
+    pub engaged_at: Option<u64>,
+}
+
+/// Kill switch state machine; every method returns the new status if the
+/// state changed, so the caller can notify platform integrations
+#[derive(Debug, Clone)]
+pub struct KillSwitch {
+    enabled: bool,
+    state: KillSwitchState,
+    reason: Option<String>,
+    engaged_at: Option<u64>,
+}
+
+impl Default for KillSwitch {
+    fn default() -> Self {
+        Self::new(false)
+    }
+}
+
+impl KillSwitch {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            state: KillSwitchState::Armed,
+            reason: None,
+            engaged_at: None,
+        }
+    }
+
+    pub fn status(&self) -> KillSwitchStatus {
+        KillSwitchStatus {
+            enabled: self.enabled,
+            state: self.state,
+            reason: self.reason.clone(),
+            engaged_at: self.engaged_at,
+        }
+    }
+
+    pub fn state(&self) -> KillSwitchState {
+        self.state
+    }
+
+    pub fn is_engaged(&self) -> bool {
+        self.state == KillSwitchState::Engaged
+    }
+
+    /// Turn automatic engagement on or off (does not release an engaged switch)
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// The tunnel came up: release the block
+    pub fn on_connected(&mut self) -> Option<KillSwitchStatus> {
+        self.transition(KillSwitchState::Armed, None)
+    }
+
+    /// The user disconnected on purpose: never block
+    pub fn on_user_disconnect(&mut self) -> Option<KillSwitchStatus> {
+        self.transition(KillSwitchState::Armed, None)
+    }
+
+    /// The tunnel dropped without the user asking for it
+    pub fn on_unexpected_disconnect(&mut self, reason: &str) -> Option<KillSwitchStatus> {
+        if !self.enabled || self.state != KillSwitchState::Armed {
+            return None;
+        }
+        self.transition(KillSwitchState::Engaged, Some(reason.to_string()))
+    }
+
+    /// Block traffic now, whether or not automatic engagement is enabled
+    pub fn engage(&mut self, reason: &str) -> Option<KillSwitchStatus> {
+        self.transition(KillSwitchState::Engaged, Some(reason.to_string()))
+    }
+
+    /// User override: stop blocking until the tunnel is back
+    pub fn release(&mut self) -> Option<KillSwitchStatus> {
+        if self.state != KillSwitchState::Engaged {
+            return None;
+        }
+        self.transition(KillSwitchState::Released, None)
+    }
+
+    fn transition(&mut self, state: KillSwitchState, reason: Option<String>) -> Option<KillSwitchStatus> {
+        if self.state == state {
+            return None;
+        }
+        self.state = state;
+        if state == KillSwitchState::Engaged {
+            self.reason = reason;
+            self.engaged_at = Some(
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs(),
+            );
+        }
+        Some(self.status())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_engages_only_on_unexpected_disconnect_when_enabled() {
+        let mut ks = KillSwitch::new(false);
+        assert!(ks.on_unexpected_disconnect("peer lost").is_none());
+
+        ks.set_enabled(true);
+        assert!(ks.on_user_disconnect().is_none());
+        assert_eq!(ks.state(), KillSwitchState::Armed);
+
+        let status = ks.on_unexpected_disconnect("peer lost").unwrap();
+        assert_eq!(status.state, KillSwitchState::Engaged);
+        assert_eq!(status.reason.as_deref(), Some("peer lost"));
+        assert!(status.engaged_at.is_some());
+        // Already engaged
+        assert!(ks.on_unexpected_disconnect("again").is_none());
+    }
+
+    #[test]
+    fn test_release_until_reconnect() {
+        let mut ks = KillSwitch::new(true);
+        assert!(ks.release().is_none());
+
+        ks.on_unexpected_disconnect("exit gone");
+        assert_eq!(ks.release().unwrap().state, KillSwitchState::Released);
+        // Stays released while the tunnel is down
+        assert!(ks.on_unexpected_disconnect("still down").is_none());
+
+        assert_eq!(ks.on_connected().unwrap().state, KillSwitchState::Armed);
+        assert!(ks.on_unexpected_disconnect("dropped").is_some());
+    }
+
+    #[test]
+    fn test_manual_engage_and_user_disconnect_releases() {
+        let mut ks = KillSwitch::new(false);
+        assert!(ks.engage("user").is_some());
+        assert!(ks.is_engaged());
+        assert_eq!(ks.on_user_disconnect().unwrap().state, KillSwitchState::Armed);
+    }
+
+    #[test]
+    fn test_status_serialization() {
+        let ks = KillSwitch::new(true);
+        let json = serde_json::to_value(ks.status()).unwrap();
+        assert_eq!(json["state"], "armed");
+        assert_eq!(json["enabled"], true);
+    }
+}
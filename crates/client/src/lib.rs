@@ -41,37 +41,196 @@
 //! let stats = node.stats();
 //! println!("Shards relayed: {}", stats.shards_relayed);
 //! ```
+//!
+//! ## Targets
+//!
+//! The `native` feature (on by default) builds the full node on tokio and
+//! libp2p. Without it, only the platform-independent request path is built —
+//! path selection, shard construction, onion encryption, erasure coding and
+//! response decoding — which compiles for `wasm32-unknown-unknown`. The
+//! `wasm` feature adds [`wasm::WasmClient`], a JS-facing client that fetches
+//! through a relay over WebTransport:
+//!
+//! ```text
+//! RUSTFLAGS=--cfg=web_sys_unstable_apis \
+//!     cargo build -p craftnet-client --target wasm32-unknown-unknown \
+//!     --no-default-features --features wasm
+//! ```
+//!
+//! The `tun` feature adds [`tun::TunDevice`], a full-device VPN interface
+//! for desktop platforms that carries all TCP traffic through the tunnel.
 
+#[cfg(feature = "native")]
+pub mod audit;
+#[cfg(feature = "native")]
+pub mod chain_ack;
+pub mod coalesce;
+#[cfg(feature = "native")]
+pub mod cookie_jar;
+#[cfg(feature = "native")]
+pub mod cover;
 mod credits;
+pub mod decoder;
+#[cfg(feature = "native")]
+pub mod exit_health;
+#[cfg(feature = "native")]
+pub mod identity;
+pub mod kill_switch;
+#[cfg(feature = "native")]
 mod node;
 pub mod path;
+#[cfg(feature = "native")]
+pub mod pool_claims;
+#[cfg(feature = "native")]
+pub mod proof_jobs;
+#[cfg(feature = "native")]
+pub mod proof_publish;
+#[cfg(feature = "native")]
+pub mod receipt_compaction;
+mod quota;
+pub mod range;
+#[cfg(feature = "native")]
+pub mod reconnect;
+#[cfg(feature = "native")]
+pub mod record_cache;
 mod request;
 mod response;
+pub mod retry;
 pub mod shard_builder;
+#[cfg(feature = "native")]
 pub mod socks5;
+pub mod throughput;
 mod tunnel;
+#[cfg(feature = "tun")]
+pub mod tun;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "native")]
+pub mod websocket;
 
 // Unified node (the single networking implementation)
-pub use node::{NodeConfig, NodeStats, NodeStatus, CompressionStatus, CraftNetNode, SwarmHandles};
-// Re-export Capabilities from core
-pub use craftnet_core::Capabilities;
+#[cfg(feature = "native")]
+pub use node::{NodeConfig, NodeStats, NodeStatus, CompressionStatus, CraftNetNode, RequestOptions, SwarmHandles, DrainStatus, ExitTamperEvent, AggregatorPools, DEFAULT_DRAIN_TIMEOUT};
+#[cfg(feature = "native")]
+pub use node::{TopologySnapshot, TopologyNodeInfo};
+// Connection table rows returned by `CraftNetNode::peer_connections`
+#[cfg(feature = "native")]
+pub use craftnet_network::{ConnectionDirection, ConnectionTransport, PeerConnectionInfo};
+// Traffic accounting through `CraftNetNode::peer_traffic` / `traffic_totals`
+#[cfg(feature = "native")]
+pub use craftnet_network::{FreeloaderPolicy, PeerTraffic, TrafficCounters, TrafficProtocol};
+// Kademlia inspection through `CraftNetNode::dht_handle`
+#[cfg(feature = "native")]
+pub use craftnet_network::{DhtHandle, DhtRecordLookup, DhtSnapshot, DhtTable, parse_bootstrap_addr};
+// Distribution multiproofs through `CraftNetNode::aggregator_batch_proof`
+#[cfg(feature = "native")]
+pub use craftnet_network::PoolType;
+#[cfg(feature = "native")]
+pub use craftnet_aggregator::DistributionBatchProof;
+#[cfg(feature = "native")]
+pub use exit_health::{ExitFailoverEvent, ExitHealthPolicy, FailoverReason};
+// Exit abuse log and operator blocks through `CraftNetNode::exit_abuse`
+#[cfg(feature = "native")]
+pub use craftnet_exit::{parse_destination, AbuseConfig, AbuseReport, BlockTarget};
+// Exit content filters for `CraftNetNode::set_exit_content_filter`
+#[cfg(feature = "native")]
+pub use craftnet_exit::{BlocklistFilter, BodyScanner, ContentFilter, FilterVerdict, NoopFilter};
+#[cfg(feature = "native")]
+pub use proof_publish::ProofPublishPolicy;
+#[cfg(feature = "native")]
+pub use receipt_compaction::HourlyReceipts;
+// Re-export Capabilities and error codes from core
+pub use craftnet_core::{Capabilities, ErrorCode};
 
 // Credit management
 pub use credits::CreditManager;
 
+// Free-tier quota tokens
+pub use quota::QuotaWallet;
+
+// Request audit log
+#[cfg(feature = "native")]
+pub use audit::{AuditEntry, AuditExportFormat, AuditLog, AuditLogConfig, AuditOutcome};
+
+// Relay chain acks
+#[cfg(feature = "native")]
+pub use chain_ack::{ChainAckRoutes, ChainAckTracker, ChainOutcome};
+pub use shard_builder::{ExpectedAck, ShardAckChain};
+
+// Cover traffic
+#[cfg(feature = "native")]
+pub use cover::{CoverTraffic, CoverTrafficConfig};
+
+// Distribution proof jobs
+#[cfg(feature = "native")]
+pub use proof_jobs::{ProofJob, ProofJobEvent, ProofJobQueue, ProofJobState};
+
+// On-chain claim status of earning pools
+#[cfg(feature = "native")]
+pub use pool_claims::{ClaimStatus, PoolClaimStatus, PoolClaims};
+
+// Exit/relay record cache
+#[cfg(feature = "native")]
+pub use record_cache::{CachedRecordState, RecordCache};
+
+// Reconnect supervision
+#[cfg(feature = "native")]
+pub use reconnect::{ReconnectEvent, ReconnectPolicy, ReconnectSupervisor};
+
+// Browsing identities
+#[cfg(feature = "native")]
+pub use identity::{Identity, IdentityRegistry, IdentityScope};
+
+// Cookie jar
+#[cfg(feature = "native")]
+pub use cookie_jar::{Cookie, CookieJar, CookieJarConfig};
+
+// Kill switch
+pub use kill_switch::{KillSwitch, KillSwitchState, KillSwitchStatus};
+
+// Coalescing of small requests
+pub use coalesce::{CoalescePolicy, QueuedRequest};
+
+// Range splitting for large GETs
+pub use range::{ContentRange, RangedDownload};
+
+// Adaptive timeouts and retries
+pub use retry::{RetryPolicy, RttEstimator};
+
+// Live bandwidth graphs
+pub use throughput::{ThroughputSample, ThroughputSeries, TrafficClass};
+
 // Path selection and topology (onion routing)
 pub use path::{PathHop, OnionPath, PathSelector, TopologyGraph, TopologyRelay, random_id};
+pub use path::{GeoConstraints, GeoFallback, GeoFilter, HopLocation};
+pub use path::{build_gateway_paths, derive_tunnel_id, ed25519_peer_id};
+
+// Response reassembly
+pub use decoder::ResponseAssembly;
 
 // Request builder
 pub use request::RequestBuilder;
 
 // Tunnel response
 pub use response::TunnelResponse;
+#[cfg(feature = "native")]
+pub use response::ResponseStream;
 
 // Tunnel mode (SOCKS5 proxy)
-pub use tunnel::build_tunnel_shards;
+pub use tunnel::{build_tunnel_shards, build_tunnel_shards_with_tokens};
+#[cfg(feature = "native")]
 pub use node::TunnelBurst;
-pub use socks5::Socks5Server;
+#[cfg(feature = "native")]
+pub use socks5::{SharedSplitTunnelRules, Socks5Server};
+
+// WebSocket sessions through the exit
+#[cfg(feature = "native")]
+pub use websocket::WebSocket;
+pub use craftnet_core::{WsClose, WsMessage};
+
+// Full-device VPN (desktop TUN interface)
+#[cfg(feature = "tun")]
+pub use self::tun::{TunConfig, TunDevice, TunStatus};
 
 use thiserror::Error;
 
@@ -106,6 +265,38 @@ pub enum ClientError {
 
     #[error("Crypto error: {0}")]
     CryptoError(String),
+
+    #[error("Exit unreachable: {0}")]
+    ExitUnreachable(String),
+
+    #[error("Response signature from exit {0} did not verify")]
+    ResponseTampered(String),
+
+    #[error("Request too large: exceeds the exit's {limit} byte limit")]
+    RequestTooLarge { limit: u64 },
+
+    #[error("Response too large: exceeds the exit's {limit} byte limit")]
+    ResponseTooLarge { limit: u64 },
+}
+
+impl ClientError {
+    /// Machine-readable code of this error
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::NotConnected => ErrorCode::NotConnected,
+            Self::ConnectionFailed(_) => ErrorCode::ConnectionFailed,
+            Self::RequestFailed(_) => ErrorCode::RequestFailed,
+            Self::Timeout => ErrorCode::Timeout,
+            Self::InsufficientCredits { .. } => ErrorCode::InsufficientCredits,
+            Self::ErasureError(_) | Self::InvalidResponse => ErrorCode::InvalidResponse,
+            Self::NoExitNodes | Self::NoExitsInRegion(_) => ErrorCode::NoExitNodes,
+            Self::CryptoError(_) => ErrorCode::CryptoError,
+            Self::ExitUnreachable(_) => ErrorCode::ExitUnreachable,
+            Self::ResponseTampered(_) => ErrorCode::ResponseTampered,
+            Self::RequestTooLarge { .. } => ErrorCode::RequestTooLarge,
+            Self::ResponseTooLarge { .. } => ErrorCode::ResponseTooLarge,
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, ClientError>;
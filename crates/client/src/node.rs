@@ -8,7 +8,7 @@
 //! The VPN extension runs in all modes for persistent P2P connectivity,
 //! but traffic routing is only active in Client/Both modes.
 
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::io::{BufRead, Write as IoWrite};
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -22,66 +22,106 @@ use parking_lot::RwLock;
 use tokio::sync::mpsc;
 use tracing::{debug, error, info, warn};
 
-use craftnet_core::{Capabilities, ExitInfo, ExitRegion, ForwardReceipt, HopMode, Id, PublicKey, RelayInfo, Shard, SubscriptionTier, TunnelMetadata};
+use craftnet_core::{verify_exit_response, BandwidthClass, Capabilities, GeoIpRecord, GeoIpResolver, ExitCapabilities, ExitInfo, ExitRegion, ForwardReceipt, HopMode, Id, PayloadCompression, PeerBinding, PublicKey, RelayCapacity, RelayInfo, ResponseSegment, RoutingTag, Shard, SubscriptionTier, TunnelMetadata, segment_assembly_id};
+use craftnet_core::{quota_epoch, PendingQuotaBatch, QuotaIssueResponse, QUOTA_ISSUE_URL};
+use craftnet_core::{CreditLedger, CreditTotals, PricingRules, EXIT_PROBE_URL};
+use craftnet_core::{WsAction, WsMessage, WsReply, WsRequest, PAYLOAD_MODE_TUNNEL, PAYLOAD_MODE_WEBSOCKET};
+use craftnet_core::{BuildAttestation, BuildManifest, ChainAck, MAX_SHARD_TRACE_ENTRIES};
+use craftnet_core::{system_clock, SharedClock};
 use craftec_crypto::{SigningKeypair, EncryptionKeypair};
 
 use craftnet_erasure::{ErasureCoder, DATA_SHARDS, TOTAL_SHARDS};
 use craftnet_erasure::chunker::reassemble;
-use craftnet_exit::{ExitConfig, ExitHandler};
+use craftnet_exit::{AbuseConfig, AbuseMonitor, ContentFilter, ExitConfig, ExitHandler, FetchPoolStats, PoolQueueStats};
 use craftnet_network::{
     build_swarm, NetworkConfig, ShardResponse, CraftNetBehaviour,
     CraftNetBehaviourEvent, CraftNetExt, ExitStatusMessage, ExitStatusType,
-    RelayStatusMessage, RelayStatusType,
+    RelayStatusMessage, RelayStatusType, OnionKeyOffer,
     ProofMessage, PoolType,
     SubscriptionAnnouncement, SUBSCRIPTION_TOPIC,
+    sign_peer_binding, PeerBindings, PEER_BINDING_MAX_AGE,
+    DhtRecordValidators, RecordPenalties, SignedDhtRecord,
+    DnsSeedCache,
+    NetworkParameters, NoticeSeverity, NETWORK_PARAMS_KEY, NETWORK_PARAMS_REFRESH_INTERVAL, PROTOCOL_VERSION,
+    AggregatorRegistry, AGGREGATOR_REGISTRY_KEY, aggregator_epoch,
+    RegistryClient, RegistryKind, RegistryStore, RegistrySyncRequest, RegistrySyncResponse,
+    REGISTRY_SHARDS, REGISTRY_SYNC_PROTOCOL, registry_shard, parse_registry_shard_key,
+    serve_registry_sync,
+    InboundProofSubmission, ProofSubmitClient, ProofSubmitStatus, serve_proof_submissions,
+    PROOF_SUBMIT_PROTOCOL, MAX_SUBMITTED_PROOFS,
+    ProofArchive, ProofBackfillClient, ProofBackfillRequest, serve_proof_backfill, PROOF_BACKFILL_PROTOCOL,
+    PeerConnectionInfo, SharedConnectionTable,
+    FreeloaderPolicy, NetworkEvent, PeerTraffic, SharedTrafficAccounting, TrafficCounters, TrafficProtocol,
+    DhtHandle, DhtQuery, DhtTable, PendingDhtLookups, serve_dht_query,
+    GossipDedup, StatusFreshness, StatusFreshnessPolicy, StatusRejection,
 
     EXIT_HEARTBEAT_INTERVAL, EXIT_OFFLINE_THRESHOLD,
     RELAY_HEARTBEAT_INTERVAL, RELAY_OFFLINE_THRESHOLD,
     EXIT_STATUS_TOPIC, RELAY_STATUS_TOPIC, PROOF_TOPIC,
+    PROOF_TOPIC_SHARDS, proof_shard_topic, proof_topic_for_pool, proof_topic_shards, is_proof_topic, parse_proof_shard_topic,
     AGGREGATOR_SYNC_TOPIC, HistorySyncRequest, HistorySyncResponse,
-    StreamManager, InboundShard, OutboundShard,
+    DISPUTE_TOPIC, DisputeMessage, DistributionAnnouncement, DistributionChallenge, ChallengeResponse,
+    StreamManager, InboundShard, OutboundShard, PeerVersion, MAX_SHARD_SIZE,
 };
-use craftnet_aggregator::Aggregator;
-use craftnet_prover::{ReceiptCompression, ReceiptCompressor};
-use craftnet_relay::{RelayConfig, RelayHandler};
+use craftnet_aggregator::{Aggregator, DisputeStatus, Distribution, PendingSpill, PoolFilter};
+use craftnet_prover::{compress_chunked, CompressedChunk, ReceiptCompression, ReceiptCompressor, DEFAULT_RECEIPT_CHUNK_SIZE};
+use craftnet_relay::{QosClass, QosClassStats, QosConfig, RelayChainAck, RelayConfig, RelayError, RelayHandler, TierQueues};
 use craftnet_settlement::{SettlementClient, SettlementConfig};
-#[cfg(feature = "sp1")]
-use craftnet_settlement::PostDistribution;
-
-use sha2::{Sha256, Digest};
-
-use crate::path::PathHop;
-use crate::{ClientError, RequestBuilder, Result, TunnelResponse};
-
-/// Derive a deterministic tunnel_id from two peer IDs.
-/// Both sides of a connection can compute this independently.
-/// `tunnel_id = SHA256(client_peer_id || gateway_peer_id || "tunnel")`
+#[cfg(any(feature = "sp1", feature = "risc0"))]
+use craftnet_settlement::{PostDistribution, SettlementError};
+
+use crate::audit::{audit_host, AuditEntry, AuditLog, AuditLogConfig, AuditOutcome};
+use crate::chain_ack::{ChainAckRoutes, ChainAckTracker, ChainOutcome};
+use crate::coalesce::{plan_batches, split_batch_response, CoalescePolicy, QueuedRequest};
+use crate::cookie_jar::{split_set_cookie, CookieJar, CookieJarConfig};
+use crate::exit_health::{ExitFailoverEvent, ExitHealth, ExitHealthPolicy};
+use crate::proof_publish::{GossipBudget, ProofOutbox, ProofPublishPolicy};
+use crate::receipt_compaction::{HourlyReceipts, ReceiptCompactor, ReceiptSpill};
+use crate::reconnect::{ReconnectEvent, ReconnectPolicy, ReconnectSupervisor};
+use crate::identity::{IdentityRegistry, IdentityScope};
+use crate::cover::{build_cover_shard, CoverTraffic, CoverTrafficConfig};
+use crate::decoder::{decode_response_payload, decompress_response, open_trace, response_chunks_ready};
+use crate::range::{range_header, range_validator, ContentRange, RangedDownload, DEFAULT_RANGE_MAX_BYTES, DEFAULT_RANGE_PARALLELISM};
+use crate::record_cache::{CachedRecordState, RecordCache};
+use crate::retry::{RetryPolicy, RttEstimator, DEFAULT_MIN_REQUEST_TIMEOUT};
+use crate::pool_claims::{PoolClaimStatus, PoolClaims};
+use crate::proof_jobs::{ProofJob, ProofJobEvent, ProofJobQueue, DEFAULT_MAX_CONCURRENT_PROOFS};
+use crate::quota::QuotaWallet;
+use crate::throughput::{ThroughputSample, ThroughputSeries, TrafficClass};
+use crate::websocket::{WebSocket, WebSocketBurst};
+use crate::path::{GeoConstraints, GeoFallback, GeoFilter, HopLocation, PathHop};
+use crate::shard_builder::ShardAckChain;
+use crate::{ClientError, RequestBuilder, ResponseStream, Result, TunnelResponse};
+
+/// Tunnel ID for a client/gateway pair (see [`crate::path::derive_tunnel_id`])
 fn derive_tunnel_id(client_peer_id: &PeerId, gateway_peer_id: &PeerId) -> Id {
-    let mut hasher = Sha256::new();
-    hasher.update(client_peer_id.to_bytes());
-    hasher.update(gateway_peer_id.to_bytes());
-    hasher.update(b"tunnel");
-    let result = hasher.finalize();
-    let mut id = [0u8; 32];
-    id.copy_from_slice(&result);
-    id
+    crate::path::derive_tunnel_id(&client_peer_id.to_bytes(), &gateway_peer_id.to_bytes())
 }
 
 /// Result from async receipt compression (spawn_blocking)
 struct CompressionResult {
     pool_key: (PublicKey, PoolType),
     batch: Vec<ForwardReceipt>,
-    output: std::result::Result<craftnet_prover::CompressedBatch, craftnet_prover::CompressionError>,
+    output: std::result::Result<Vec<CompressedChunk>, craftnet_prover::CompressionError>,
     start: Instant,
 }
 
+/// Progress report from a distribution proving thread (spawn_blocking)
+enum ProofJobUpdate {
+    Verifying(u64),
+    Done { id: u64, proof_bytes: Vec<u8>, public_values: Vec<u8>, cycles: Option<u64> },
+    Failed { id: u64, error: String },
+}
+
 // === Proof state persistence types ===
 
-/// On-disk proof state: pool_roots + pending receipts
+/// On-disk proof state: pool_roots + pending receipts + compacted receipts
 #[derive(serde::Serialize, serde::Deserialize)]
 struct ProofStateFile {
     pool_roots: HashMap<String, PoolRootState>,
     pending_receipts: Vec<PendingReceiptEntry>,
+    #[serde(default)]
+    compacted: Vec<CompactedHourEntry>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -96,6 +136,13 @@ struct PendingReceiptEntry {
     receipt: ForwardReceipt,
 }
 
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CompactedHourEntry {
+    pool_key: String,
+    hour: u64,
+    receipts: HourlyReceipts,
+}
+
 /// Format a pool key as "hex_pubkey:PoolType" for serialization
 fn format_pool_key(pubkey: &PublicKey, pool_type: &PoolType) -> String {
     format!("{}:{:?}", hex::encode(pubkey), pool_type)
@@ -117,24 +164,138 @@ fn parse_pool_key(s: &str) -> Option<(PublicKey, PoolType)> {
     Some((pubkey, pool_type))
 }
 
+/// Request builder for a plain HTTP request
+fn http_request_builder(
+    method: &str,
+    url: &str,
+    body: Option<Vec<u8>>,
+    headers: Option<Vec<(String, String)>>,
+) -> RequestBuilder {
+    let mut builder = RequestBuilder::new(method, url);
+    for (key, value) in headers.into_iter().flatten() {
+        builder = builder.header(&key, &value);
+    }
+    if let Some(body_data) = body {
+        builder = builder.body(body_data);
+    }
+    builder
+}
+
+/// IP address of a multiaddr string, if it is publicly routable
+fn public_ip_of(addr: &str) -> Option<std::net::IpAddr> {
+    use libp2p::multiaddr::Protocol;
+    use std::net::IpAddr;
+
+    let ip = addr.parse::<Multiaddr>().ok()?.iter().find_map(|p| match p {
+        Protocol::Ip4(ip) => Some(IpAddr::V4(ip)),
+        Protocol::Ip6(ip) => Some(IpAddr::V6(ip)),
+        _ => None,
+    })?;
+    let public = match ip {
+        IpAddr::V4(v4) => !(v4.is_private()
+            || v4.is_loopback()
+            || v4.is_link_local()
+            || v4.is_unspecified()
+            || v4.is_broadcast()
+            || v4.is_documentation()),
+        // Not loopback, unspecified, unique local (fc00::/7) or link local (fe80::/10)
+        IpAddr::V6(v6) => !(v6.is_loopback()
+            || v6.is_unspecified()
+            || (v6.segments()[0] & 0xfe00) == 0xfc00
+            || (v6.segments()[0] & 0xffc0) == 0xfe80),
+    };
+    public.then_some(ip)
+}
+
 /// Maximum time receipts can sit in the proof queue before forcing a prove,
 /// regardless of batch size. Ensures low-traffic relays still settle.
 const PROOF_DEADLINE: Duration = Duration::from_secs(15 * 60); // 15 minutes
 
+/// Proven receipts dropped from memory before the receipts file is
+/// rewritten without them
+const RECEIPT_FILE_REWRITE_THRESHOLD: usize = 10_000;
+
+/// How often poll_once() checks readiness for the reconnect supervisor
+const READINESS_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Default time a draining node waits for in-flight work before stopping
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
 /// How often to run batch on-chain subscription verification (60 seconds)
 const SUBSCRIPTION_VERIFY_INTERVAL: Duration = Duration::from_secs(60);
 
 /// Max users to verify per batch (avoid RPC rate limits)
 const SUBSCRIPTION_VERIFY_BATCH_SIZE: usize = 10;
 
+/// How long an on-chain check that confirmed an announcement holds
+const SUBSCRIPTION_REVERIFY_INTERVAL: Duration = Duration::from_secs(300);
+
+/// How long an announcement the chain contradicted is ignored before it
+/// is checked again (negative cache)
+const SUBSCRIPTION_NEGATIVE_TTL: Duration = Duration::from_secs(1800);
+
+/// Announcements waiting for their first on-chain check
+const MAX_SUBSCRIPTION_CHECK_QUEUE: usize = 1024;
+
+/// How long a pool's on-chain balance and claim status stay fresh
+const POOL_CLAIM_REFRESH_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Pools whose claim status is fetched per maintenance round
+const POOL_CLAIM_BATCH_SIZE: usize = 10;
+
+/// How often the aggregator downsamples bandwidth and prunes its history
+const AGGREGATOR_RETENTION_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Body chunks a `ResponseStream` buffers before the node holds chunks back
+const STREAM_CHANNEL_CAPACITY: usize = 16;
+
+/// How many segments past the next undelivered one a stream accepts shards for
+const STREAM_LOOKAHEAD: u32 = 64;
+
+/// Body chunks a stream holds beyond its channel; while this many wait, no
+/// further segments are taken and the lookahead window stops sliding
+const STREAM_READY_LIMIT: usize = 16;
+
+/// Streamed-response segment batches an exit buffers before pausing its upstream read
+const EXIT_STREAM_BUFFER: usize = 16;
+
+/// How often the GeoIP database file is checked for changes
+const GEOIP_REFRESH_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Concurrent streams a relay advertises when `NodeConfig::relay_capacity` is unset
+const DEFAULT_RELAY_MAX_STREAMS: u32 = 256;
+
+/// Requests whose body exceeds this are routed as bulk transfers
+const BULK_REQUEST_BYTES: usize = 64 * 1024;
+
+/// Throughput samples through a relay before its advertised bandwidth
+/// class is checked against what we measured
+const CAPACITY_VERIFY_SAMPLES: u32 = 10;
+
+/// A relay whose best measured throughput is below 1/this of its
+/// advertised class minimum is treated as the class it measured
+const CAPACITY_OVERCLAIM_FACTOR: u32 = 4;
+
+/// Settled chain acks through a relay before dropped shards count
+/// against its score
+const FORWARD_VERIFY_SAMPLES: u32 = 10;
+
+/// Score points a relay that drops every shard it gets is penalised
+const FORWARD_FAILURE_PENALTY: u32 = 40;
+
 /// Cached subscription entry for a user
 #[derive(Debug, Clone)]
 struct SubscriptionEntry {
-    /// Subscription tier (0=Basic, 1=Standard, 2=Premium, 255=None/Free)
+    /// Verified subscription tier (0=Basic, 1=Standard, 2=Premium,
+    /// 255=None/Free, and until the chain confirmed the announcement)
     tier: u8,
+    /// Tier the latest announcement claimed
+    claimed_tier: u8,
+    /// Expiry the latest announcement claimed
+    claimed_expires_at: u64,
     /// On-chain start_date (from verification)
     start_date: u64,
-    /// Claimed expiry (from announcement)
+    /// Expiry: on-chain once verified, claimed before
     expires_at: u64,
     /// Whether this has been verified on-chain
     verified: bool,
@@ -144,6 +305,18 @@ struct SubscriptionEntry {
     last_seen: std::time::Instant,
 }
 
+impl SubscriptionEntry {
+    /// Whether the announcement should be checked on-chain at `now`: never
+    /// checked, confirmed but stale, or contradicted and past the
+    /// negative cache
+    fn needs_check(&self, now: std::time::Instant) -> bool {
+        let Some(at) = self.verified_at else { return true };
+        let confirmed = self.tier == self.claimed_tier && self.expires_at == self.claimed_expires_at;
+        let ttl = if confirmed { SUBSCRIPTION_REVERIFY_INTERVAL } else { SUBSCRIPTION_NEGATIVE_TTL };
+        now.duration_since(at) >= ttl
+    }
+}
+
 /// Configuration for the unified node
 #[derive(Debug, Clone)]
 pub struct NodeConfig {
@@ -156,24 +329,106 @@ pub struct NodeConfig {
     /// Bootstrap peers
     pub bootstrap_peers: Vec<(PeerId, Multiaddr)>,
 
+    /// Domain whose `_dnsaddr` TXT records list bootstrap nodes, merged
+    /// with the built-in defaults when no explicit `bootstrap_peers` are
+    /// set. The domain's owner picks the peers this node joins through, so
+    /// opt in only with a domain you trust, e.g.
+    /// `dns_seed: Some("seed.example.org".to_string())`. Default: None
+    /// (built-in bootstrap nodes only).
+    pub dns_seed: Option<String>,
+
     /// Privacy level (hop count)
     pub hop_mode: HopMode,
 
-    /// Request timeout
+    /// Request idle timeout. Once a circuit has completed requests, the
+    /// timeout adapts to its measured RTT and the request size; this is the
+    /// upper bound (and the timeout of circuits not measured yet).
     pub request_timeout: Duration,
 
+    /// Lower bound of the adaptive request timeout. Default: 2 seconds.
+    pub min_request_timeout: Duration,
+
+    /// Retries of failed requests over a different circuit. Default: 2
+    /// retries, idempotent methods only.
+    pub retry_policy: RetryPolicy,
+
+    /// Bytes per Range sub-request when splitting large GETs. Default: 0
+    /// (off; every GET goes out as one request). To opt in set it, e.g. to
+    /// [`DEFAULT_RANGE_CHUNK_SIZE`](crate::range::DEFAULT_RANGE_CHUNK_SIZE);
+    /// each piece is then a separate request, retried and charged on its own.
+    pub range_chunk_size: usize,
+
+    /// Range sub-requests in flight at once for one GET
+    pub range_parallelism: usize,
+
+    /// Largest resource a split GET downloads; a larger `Content-Range`
+    /// total fails the request. Default: 1 GiB.
+    pub range_max_bytes: u64,
+
+    /// Coalesce runs of small requests passed to
+    /// [`CraftNetNode::fetch_many`] into shared assemblies (None: send each
+    /// alone). Exits that predate coalescing refuse such requests.
+    /// Default: None.
+    pub coalesce: Option<CoalescePolicy>,
+
+    /// Negotiate zstd compression of request/response payloads with the exit
+    /// (skipped for already-compressed content). Requests are only
+    /// compressed for exits advertising `ExitInfo::zstd_requests`, and never
+    /// when they carry `Authorization` or `Cookie`. Default: true.
+    pub payload_compression: bool,
+
+    /// Ask relays to ack request shards back up the path, and score relays
+    /// that lose them worse (see `craftnet_core::chain_ack`). Default: true.
+    pub chain_acks: bool,
+
+    /// Fetch a daily batch of quota tokens from exits that meter the free
+    /// tier and pay for requests with them. Turn off when the pool has a
+    /// subscription (the exit ignores tokens then). Default: true.
+    pub quota_tokens: bool,
+
+    /// How requests sent, shards relayed and requests served are priced in
+    /// the credit ledger. Default: `PricingRules::default()`.
+    pub credit_pricing: PricingRules,
+
+    /// Keys whose network parameter records (bootstrap list, minimum
+    /// protocol version, notices) are trusted. Default: the maintainer keys
+    /// embedded at build time.
+    pub maintainer_keys: Vec<PublicKey>,
+
+    /// Only trust distributions posted by aggregators in the
+    /// maintainer-signed aggregator registry for the current epoch: relays
+    /// don't reconcile credits against other posters, and an aggregator not
+    /// in the registry doesn't prove or post. Default: false.
+    pub registered_aggregators_only: bool,
+
     /// Allow being last hop before exit (relay config)
     pub allow_last_hop: bool,
 
     /// Exit node region (auto-detected or configured)
     pub exit_region: ExitRegion,
 
-    /// Exit node country code (ISO 3166-1 alpha-2, e.g., "US", "DE")
+    /// Exit node country code (ISO 3166-1 alpha-2, e.g., "US", "DE").
+    /// Also announced in the relay record.
     pub exit_country_code: Option<String>,
 
     /// Exit node city
     pub exit_city: Option<String>,
 
+    /// Autonomous System of this node (e.g. `GeoLocation::as_number`),
+    /// announced in the relay and exit records. Default: None (looked up
+    /// in `geoip_database`, if any).
+    pub as_number: Option<String>,
+
+    /// GeoIP database (ipinfo CSV, or MaxMind `.mmdb` with the `maxmind`
+    /// feature) used to self-report this node's AS and country, and to
+    /// look up peers that announce none. Reloaded when the file changes.
+    /// Default: None.
+    pub geoip_database: Option<PathBuf>,
+
+    /// Public IP of this node, looked up in `geoip_database`. Default:
+    /// None (the IP of `listen_addr`, if public).
+    pub public_ip: Option<std::net::IpAddr>,
+
     /// Settlement configuration (defaults to devnet)
     pub settlement_config: SettlementConfig,
 
@@ -198,6 +453,36 @@ pub struct NodeConfig {
     /// Default: false (SSRF protection). Set to true for localhost testing.
     pub exit_allow_private_ips: bool,
 
+    /// Refuse TLS tunnels whose ClientHello carries no SNI hostname, so the
+    /// blocked domains list can't be sidestepped by omitting it.
+    /// Default: false.
+    pub exit_require_sni: bool,
+
+    /// Cache shareable GET responses at the exit (bounded LRU honouring
+    /// Cache-Control). Default: false.
+    pub exit_response_cache: bool,
+
+    /// Refuse exit requests from pools without an active on-chain
+    /// subscription instead of serving them at free-tier limits.
+    /// Default: false.
+    pub exit_require_subscription: bool,
+
+    /// Meter requests from pools without a subscription with quota tokens,
+    /// issuing each pool a daily batch. Spent tokens are recorded in
+    /// `data_dir`, if set, so a restart can't spend them again.
+    /// Default: false.
+    pub exit_quota_tokens: bool,
+
+    /// Self-test the exit's upstream on startup and advertise only the
+    /// capabilities that work; an exit that can't serve HTTP at all is not
+    /// announced. Default: true.
+    pub exit_self_test: bool,
+
+    /// Thresholds of the exit's per-destination request log: a pool sending
+    /// one destination more than the threshold within the window is
+    /// throttled for it.
+    pub exit_abuse: AbuseConfig,
+
     /// Proof batch size: minimum receipts before triggering compression.
     /// Lower values cause more frequent (smaller) batches. Default: 10,000.
     /// The runtime value adapts based on compression speed, but starts here.
@@ -207,10 +492,138 @@ pub struct NodeConfig {
     /// even if the batch is not full. Default: 15 minutes.
     pub proof_deadline: Duration,
 
+    /// Pacing of proof batches (shortest interval per pool, payload bytes
+    /// that make a batch ready) and the uplink budget of proof gossip.
+    /// Default: [`ProofPublishPolicy::default`].
+    pub proof_publish: ProofPublishPolicy,
+
+    /// Receipts compressed per chunk: a batch larger than this is compressed
+    /// chunk by chunk, each chunk publishing its own chained proof
+    /// (0 = never split). Default: [`DEFAULT_RECEIPT_CHUNK_SIZE`].
+    pub proof_chunk_size: usize,
+
     /// Maintenance interval: how often `poll_once()` runs background housekeeping
     /// (heartbeats, discovery, cleanup, subscription verification, distribution posting).
     /// Default: 30 seconds.
     pub maintenance_interval: Duration,
+
+    /// Pad every shard this node sends to a fixed size bucket
+    /// (see `craftnet_core::SHARD_SIZE_BUCKETS`). Default: false.
+    pub shard_padding: bool,
+
+    /// Offer sealed (AEAD-encrypted) shard stream frames to peers, and accept
+    /// them, so a relay process only sees the frames addressed to it.
+    /// Peers before protocol v2 keep plaintext frames. Default: true.
+    pub frame_encryption: bool,
+
+    /// Extra WebRTC-direct listen address for browser clients, e.g.
+    /// `/ip4/0.0.0.0/udp/9001/webrtc-direct` (standalone swarm, `webrtc`
+    /// feature). The certificate is kept in `{data_dir}/webrtc-cert.pem`.
+    pub webrtc_listen_addr: Option<Multiaddr>,
+
+    /// Send cover shards on idle circuits (client mode). Default: None (off).
+    pub cover_traffic: Option<CoverTrafficConfig>,
+
+    /// Maximum distribution proofs generated at once (aggregator mode). Default: 1.
+    pub max_concurrent_proofs: usize,
+
+    /// How long `drain()` waits for in-flight assemblies, acks and proof
+    /// batches before stopping anyway. Default: 30 seconds.
+    pub drain_timeout: Duration,
+
+    /// Record every tunnelled request to an encrypted local audit log
+    /// (client mode). Default: None (off).
+    pub audit_log: Option<AuditLogConfig>,
+
+    /// Store cookies from responses and send them back on `fetch()`,
+    /// per identity (client mode). Default: None (stateless requests).
+    pub cookie_jar: Option<CookieJarConfig>,
+
+    /// Reconnect automatically when a ready client loses readiness
+    /// (client mode). Default: on; None disables it.
+    pub reconnect: Option<ReconnectPolicy>,
+
+    /// Shard ids a relay remembers per replay window (replay protection).
+    /// Default: 500,000.
+    pub relay_replay_capacity: usize,
+
+    /// Replay window of a relay; shard ids are remembered for one to two
+    /// windows. Default: 5 minutes.
+    pub relay_replay_window: Duration,
+
+    /// Weights, rate limits and queue depth of the per-tier queues a relay
+    /// forwards shards through. Default: weights 16/8/4/2/1 (Ultra to
+    /// Free), no rate limits.
+    pub relay_qos: QosConfig,
+
+    /// Peers a relay treats as freeloaders (see [`FreeloaderPolicy`]): the
+    /// shards they hand us are forwarded in the Free class whatever their
+    /// pool's tier. None turns detection off. Default: 64 MiB pushed with
+    /// less than 5% taken back.
+    pub relay_freeloader: Option<FreeloaderPolicy>,
+
+    /// Clock bounds on signed exit and relay heartbeats (see
+    /// [`StatusFreshnessPolicy`]). Unsigned heartbeats are always dropped.
+    /// Default: timestamps within 60s of the sender's tracked clock offset,
+    /// offsets up to an hour.
+    pub status_freshness: StatusFreshnessPolicy,
+
+    /// How far a peer binding's signed timestamp may be from our clock.
+    /// Nodes re-sign theirs with every announcement. Default: one hour.
+    pub peer_binding_max_age: Duration,
+
+    /// How often a relay generates a new onion key and announces it in its
+    /// heartbeat; keys are dropped after the grace period, so recorded
+    /// traffic can't be opened later. None keeps only the static encryption
+    /// key. Default: 1 hour.
+    pub onion_key_rotation: Option<Duration>,
+
+    /// How long a relay keeps peeling shards sealed to its previous onion
+    /// key after rotating. Default: 10 minutes.
+    pub onion_key_grace: Duration,
+
+    /// Reject responses the exit didn't sign (older exits don't). Responses
+    /// with a signature that doesn't verify are always rejected. Streamed
+    /// responses are not signed. Default: false.
+    pub require_exit_signatures: bool,
+
+    /// How long the aggregator keeps hourly, daily, weekly and monthly
+    /// bandwidth buckets (and history entries) before downsampling or
+    /// deleting them. Default: 30 days / 180 days / 2 years / forever.
+    pub aggregator_retention: craftnet_aggregator::RetentionPolicy,
+
+    /// Largest the aggregator history file may grow; the oldest entries
+    /// are deleted beyond it. Default: None (unbounded).
+    pub aggregator_history_max_bytes: Option<u64>,
+
+    /// How long relays may challenge a distribution the aggregator
+    /// announced before it is proven and posted (zero = post without a
+    /// challenge window). Default: one hour.
+    pub distribution_challenge_window: Duration,
+
+    /// Pools the aggregator follows; proofs for other pools are dropped
+    /// at ingest, and only the proof topic shards of followed pools are
+    /// subscribed to. Default: all pools.
+    pub aggregator_pools: AggregatorPools,
+
+    /// Lowest protocol version a peer may announce (in its shard-stream
+    /// hello) for us to route through it. Peers that predate version
+    /// negotiation count as version 0. Default: 0 (any peer).
+    pub min_peer_protocol_version: u16,
+
+    /// Capacity this node advertises as a relay (DHT record and
+    /// heartbeats). Default: None (bandwidth class from measured
+    /// throughput, `MAX_SHARD_SIZE`, 256 streams).
+    pub relay_capacity: Option<RelayCapacity>,
+
+    /// Location constraints on the hops of client circuits (distinct
+    /// countries and ASes, required exit country, excluded countries).
+    /// Default: [`GeoConstraints::default`].
+    pub circuit_geo: GeoConstraints,
+
+    /// When the selected exit is probed and failed over for errors, latency
+    /// or unanswered probes. Default: [`ExitHealthPolicy::default`].
+    pub exit_health: ExitHealthPolicy,
 }
 
 impl Default for NodeConfig {
@@ -219,23 +632,184 @@ impl Default for NodeConfig {
             capabilities: Capabilities::CLIENT,
             listen_addr: "/ip4/0.0.0.0/tcp/0".parse().unwrap(),
             bootstrap_peers: Vec::new(),
+            dns_seed: None,
             hop_mode: HopMode::Triple,
             request_timeout: Duration::from_secs(5),
+            min_request_timeout: DEFAULT_MIN_REQUEST_TIMEOUT,
+            retry_policy: RetryPolicy::default(),
+            range_chunk_size: 0,
+            range_parallelism: DEFAULT_RANGE_PARALLELISM,
+            range_max_bytes: DEFAULT_RANGE_MAX_BYTES,
+            coalesce: None,
+            payload_compression: true,
+            chain_acks: true,
+            quota_tokens: true,
+            credit_pricing: PricingRules::default(),
+            maintainer_keys: craftnet_network::maintainer_keys(),
+            registered_aggregators_only: false,
             allow_last_hop: true,
             exit_region: ExitRegion::Auto,
             exit_country_code: None,
             exit_city: None,
+            as_number: None,
+            geoip_database: None,
+            public_ip: None,
             settlement_config: SettlementConfig::devnet_default(),
             signing_secret: None,
             libp2p_keypair: None,
             data_dir: None,
             exit_blocked_domains: None,
             exit_allow_private_ips: false,
+            exit_require_sni: false,
+            exit_response_cache: false,
+            exit_require_subscription: false,
+            exit_quota_tokens: false,
+            exit_self_test: true,
+            exit_abuse: AbuseConfig::default(),
             proof_batch_size: 10_000,
             proof_deadline: PROOF_DEADLINE,
+            proof_publish: ProofPublishPolicy::default(),
+            proof_chunk_size: DEFAULT_RECEIPT_CHUNK_SIZE,
             maintenance_interval: Duration::from_secs(30),
+            shard_padding: false,
+            frame_encryption: true,
+            webrtc_listen_addr: None,
+            cover_traffic: None,
+            max_concurrent_proofs: DEFAULT_MAX_CONCURRENT_PROOFS,
+            drain_timeout: DEFAULT_DRAIN_TIMEOUT,
+            audit_log: None,
+            cookie_jar: None,
+            reconnect: Some(ReconnectPolicy::default()),
+            relay_replay_capacity: craftnet_relay::DEFAULT_REPLAY_CAPACITY,
+            relay_replay_window: craftnet_relay::DEFAULT_REPLAY_WINDOW,
+            relay_qos: QosConfig::default(),
+            relay_freeloader: Some(FreeloaderPolicy::default()),
+            status_freshness: StatusFreshnessPolicy::default(),
+            peer_binding_max_age: PEER_BINDING_MAX_AGE,
+            onion_key_rotation: Some(craftnet_core::DEFAULT_ONION_KEY_ROTATION),
+            onion_key_grace: craftnet_core::DEFAULT_ONION_KEY_GRACE,
+            require_exit_signatures: false,
+            aggregator_retention: craftnet_aggregator::RetentionPolicy::default(),
+            aggregator_history_max_bytes: None,
+            distribution_challenge_window: Duration::from_secs(craftnet_aggregator::DEFAULT_CHALLENGE_WINDOW_SECS),
+            aggregator_pools: AggregatorPools::All,
+            min_peer_protocol_version: 0,
+            relay_capacity: None,
+            circuit_geo: GeoConstraints::default(),
+            exit_health: ExitHealthPolicy::default(),
+        }
+    }
+}
+
+/// Per-request overrides of the node's defaults (see
+/// [`CraftNetNode::fetch_with`])
+///
+/// Lets one session use `Direct` for latency-sensitive calls and `Quad`
+/// for sensitive ones without touching the global hop mode.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequestOptions {
+    /// Hop mode for this request (None = `NodeConfig::hop_mode`)
+    pub hop_mode: Option<HopMode>,
+    /// Exit for this request, by signing pubkey (None = the selected exit)
+    pub exit: Option<PublicKey>,
+    /// Idle timeout for this request (None = the adaptive per-circuit timeout)
+    pub timeout: Option<Duration>,
+    /// Record per-hop timings, returned in `TunnelResponse::hop_timings`.
+    /// Diagnostics only: traced shards are larger and share a trace key
+    /// across hops (see `craftnet_core::trace`). Disables Range splitting.
+    pub trace: bool,
+}
+
+/// Pools an aggregator node follows (see `NodeConfig::aggregator_pools`)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum AggregatorPools {
+    /// Every pool on the network
+    #[default]
+    All,
+    /// This node's own pool (its wallet's pubkey)
+    Own,
+    /// These pool pubkeys
+    Only(HashSet<PublicKey>),
+}
+
+impl AggregatorPools {
+    /// The aggregator filter, with `own` as this node's pool pubkey
+    fn filter(&self, own: PublicKey) -> PoolFilter {
+        match self {
+            Self::All => PoolFilter::All,
+            Self::Own => PoolFilter::Pools(HashSet::from([own])),
+            Self::Only(pools) => PoolFilter::Pools(pools.clone()),
         }
     }
+
+    /// Proof topic shards carrying the followed pools' proofs
+    fn proof_shards(&self, own: PublicKey) -> Vec<u8> {
+        match self {
+            Self::All => (0..PROOF_TOPIC_SHARDS).collect(),
+            Self::Own => proof_topic_shards([&own]),
+            Self::Only(pools) => proof_topic_shards(pools),
+        }
+    }
+}
+
+/// An exit sent a response whose signature didn't verify
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ExitTamperEvent {
+    /// Exit signing pubkey (hex)
+    pub exit_pubkey: String,
+    /// Request the response belonged to (hex)
+    pub request_id: String,
+    /// Bad responses seen from this exit so far
+    pub tampered_responses: u32,
+}
+
+/// Progress of a distribution dispute this node took part in
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DisputeEvent {
+    /// We challenged a distribution that under-counts our bytes
+    Challenged { aggregator: String, pool: String, counted_bytes: u64, claimed_bytes: u64 },
+    /// The aggregator answered our challenge; `resolved` when its proof
+    /// chain reaches our claimed root and count
+    Answered { aggregator: String, pool: String, counted_bytes: u64, resolved: bool },
+    /// We answered a relay's challenge as aggregator
+    Responded { relay: String, pool: String, status: DisputeStatus },
+}
+
+/// In-flight work a draining node is waiting on
+#[derive(Debug, Clone, Default)]
+pub struct DrainStatus {
+    /// Drain has been requested
+    pub draining: bool,
+    /// Exit assemblies still collecting shards
+    pub pending_assemblies: usize,
+    /// Exit requests being fetched, plus shards queued behind them
+    pub exit_tasks: usize,
+    /// Receipts waiting to be compressed into a proof batch
+    pub queued_receipts: usize,
+    /// A proof batch is being compressed
+    pub compressing: bool,
+    /// Compressed proofs not yet published
+    pub unpublished_proofs: usize,
+    /// Receipts not yet written to disk
+    pub unflushed_receipts: usize,
+    /// Response shards still queued for the outbound writer
+    pub outbound_queued: usize,
+    /// Time left before the drain gives up waiting (None = not draining)
+    pub remaining: Option<Duration>,
+}
+
+impl DrainStatus {
+    /// Nothing left in flight
+    pub fn is_idle(&self) -> bool {
+        self.pending_assemblies == 0
+            && self.exit_tasks == 0
+            && self.queued_receipts == 0
+            && !self.compressing
+            && self.unpublished_proofs == 0
+            && self.unflushed_receipts == 0
+            && self.outbound_queued == 0
+    }
 }
 
 /// Proof pipeline status for monitoring
@@ -244,8 +818,24 @@ pub struct CompressionStatus {
     pub queued: usize,
     pub compressing: bool,
     pub batches_compressed: u64,
+    /// Chunks those batches were compressed in (one published proof each)
+    pub chunks_compressed: u64,
     pub compressions_failed: u64,
     pub last_proof_duration_ms: Option<u64>,
+    /// (pool, hour) accumulators holding proven receipts
+    pub compacted_hours: usize,
+    /// Receipts folded into those accumulators since startup
+    pub receipts_compacted: u64,
+    /// Receipts waiting on disk behind full proof queues
+    pub spilled_receipts: usize,
+    /// Tunnel payloads sent or received zstd compressed
+    pub payloads_compressed: u64,
+    /// Uncompressed bytes of those payloads
+    pub payload_raw_bytes: u64,
+    /// Wire bytes of those payloads
+    pub payload_compressed_bytes: u64,
+    /// `payload_raw_bytes / payload_compressed_bytes` (None until something was compressed)
+    pub payload_compression_ratio: Option<f64>,
 }
 
 /// Statistics for the node
@@ -274,6 +864,40 @@ pub struct NodeStats {
 
     /// Bytes relayed for others
     pub bytes_relayed: u64,
+
+    /// Cover shards sent on idle circuits (never billed)
+    pub cover_shards_sent: u64,
+
+    /// Cover shards received and dropped as a relay (no receipt signed)
+    pub cover_shards_dropped: u64,
+
+    /// Replayed shards rejected as a relay (not forwarded, no receipt)
+    pub replays_rejected: u64,
+
+    /// Relay hops of our request shards that acked with a valid signature
+    pub chain_hops_verified: u64,
+
+    /// Relay hops of our request shards that never acked
+    pub chain_hops_missing: u64,
+
+    /// Forward receipts waiting to be proven, across pools (snapshot)
+    pub proof_queue_depth: usize,
+
+    /// Exit request queue depth per pool (snapshot from the exit handler)
+    pub exit_queues: Vec<PoolQueueStats>,
+
+    /// Upstream connection reuse at the exit (snapshot from the exit handler)
+    pub exit_fetch_pool: FetchPoolStats,
+
+    /// Credit ledger totals (`credits_earned`/`credits_spent` mirror them)
+    pub credit_ledger: CreditTotals,
+
+    /// Relay forwarding per tier, highest first (snapshot)
+    pub relay_qos: Vec<QosClassStats>,
+
+    /// On-chain balance and claim status of pools we earned from, as of
+    /// the last background check
+    pub pool_claims: Vec<PoolClaimStatus>,
 }
 
 /// Status of the unified node
@@ -318,13 +942,23 @@ struct PendingRequest {
     exit_pubkey: [u8; 32],
     /// Exit X25519 encryption pubkey (stored at request time for response decryption)
     exit_enc_pubkey: [u8; 32],
+    /// Our X25519 secret the response is encrypted to (node or identity key)
+    response_secret: [u8; 32],
     /// Request size in bytes (for throughput calculation)
     request_bytes: usize,
+    /// First hop of the request's circuit (for RTT measurement)
+    first_hop: Option<PeerId>,
     /// Time when request was sent
     sent_at: std::time::Instant,
+    /// Routing tag flags of the response shards (payload compression)
+    flags: u8,
+    /// Secret of the request's trace key (None = untraced)
+    trace_secret: Option<[u8; 32]>,
+    /// Distinct sealed hop timings from the response shards, in path order
+    trace: Vec<Vec<u8>>,
 }
 
-/// Pending tunnel request state (for SOCKS5 tunnel mode)
+/// Pending tunnel request state (for SOCKS5 tunnel mode and WebSocket sessions)
 #[allow(dead_code)]
 struct PendingTunnelRequest {
     /// Collected shard payloads indexed by (chunk_index, shard_index)
@@ -337,6 +971,55 @@ struct PendingTunnelRequest {
     exit_enc_pubkey: [u8; 32],
     /// Time when request was sent
     sent_at: std::time::Instant,
+    /// Tunnel session, for quota accounting of the response
+    session_id: Id,
+}
+
+/// A request whose shards are being sent, see `CraftNetNode::start_request`
+struct InFlightRequest {
+    request_id: Id,
+    response_rx: mpsc::Receiver<Result<TunnelResponse>>,
+    /// Shards not yet handed to the outbound queue, with their first hop
+    send_queue: VecDeque<(Shard, PeerId)>,
+    send_count: usize,
+    sent: usize,
+    /// First hop of the request, for trace logging
+    first_hop: Option<PeerId>,
+    send_start: Instant,
+    /// Exit the request goes through (health tracking)
+    exit_pubkey: PublicKey,
+    /// Idle timeout for this request (adapted to its circuit and size)
+    timeout: Duration,
+    /// Last time a response shard arrived (idle timeout)
+    last_progress: Instant,
+    last_shard_count: usize,
+    /// Chain acks owed for queued shards, by shard ephemeral pubkey
+    /// (tracked once the shard is handed to the writer)
+    ack_chains: HashMap<[u8; 32], ShardAckChain>,
+}
+
+/// Streamed response state (client mode, see `fetch_stream`)
+struct PendingStream {
+    /// Shards of segments still being collected: seq → (shards, total_chunks)
+    segments: HashMap<u32, (HashMap<(u16, u8), Vec<u8>>, u16)>,
+    /// Decoded segments waiting for an earlier one
+    decoded: BTreeMap<u32, ResponseSegment>,
+    /// Next segment to hand to the caller
+    next_seq: u32,
+    /// Receives status + headers once segment 0 is decoded
+    head_tx: Option<mpsc::Sender<Result<(u16, HashMap<String, String>)>>>,
+    /// Body chunks the caller's channel had no room for yet (at most
+    /// `STREAM_READY_LIMIT`)
+    ready: VecDeque<Result<Vec<u8>>>,
+    chunk_tx: mpsc::Sender<Result<Vec<u8>>>,
+    /// Exit X25519 encryption pubkey (stored at request time for response decryption)
+    exit_enc_pubkey: [u8; 32],
+    /// Last time a shard for this stream arrived (idle timeout)
+    last_progress: Instant,
+    /// Body bytes delivered so far (checked against the End segment)
+    body_bytes: u64,
+    /// End or Error reached; removed once `ready` drains
+    finished: bool,
 }
 
 /// A burst of TCP data from a SOCKS5 connection to be sent through the tunnel
@@ -398,6 +1081,9 @@ struct ExitNodeStatus {
     /// Last measurement timestamp
     last_measurement: Option<std::time::Instant>,
 
+    /// Responses whose signature didn't verify (reputation)
+    tampered_responses: u32,
+
     // === Combined score ===
     /// Selection score (0-100, lower = better)
     /// Starts at 50, adjusted by measurements
@@ -406,8 +1092,7 @@ struct ExitNodeStatus {
 
 impl ExitNodeStatus {
     /// Create a new exit status with base score
-    fn new(info: ExitInfo) -> Self {
-        let now = std::time::Instant::now();
+    fn new(info: ExitInfo, now: Instant) -> Self {
         Self {
             info,
             peer_id: None,
@@ -425,20 +1110,27 @@ impl ExitNodeStatus {
             measured_downlink_kbps: None,
             measurement_samples: 0,
             last_measurement: None,
+            tampered_responses: 0,
             score: EXIT_BASE_SCORE,
         }
     }
 
+    /// The exit sent a response with a bad signature
+    fn record_tampered_response(&mut self) {
+        self.tampered_responses = self.tampered_responses.saturating_add(1);
+        self.recalculate_score();
+    }
+
     /// Update announced values from heartbeat
     fn update_from_heartbeat(
         &mut self,
+        now: Instant,
         load_percent: u8,
         uplink_kbps: u32,
         downlink_kbps: u32,
         uptime_secs: u64,
         region: Option<String>,
     ) {
-        let now = std::time::Instant::now();
         self.last_heartbeat = Some(now);
 
         // Track when exit came back online (for observed uptime)
@@ -501,12 +1193,13 @@ impl ExitNodeStatus {
     /// - Throughput: 0-40 points (40% weight)
     /// - Uptime: 0-20 points (20% weight, longer = lower score = better)
     /// - Trust penalty: +10 if announced > 3x measured
+    /// - Tamper penalty: +50 per response whose signature didn't verify
     fn recalculate_score(&mut self) {
         // Use minimum of announced uptime and observed uptime for reliability
         let uptime_secs = self.announced_uptime_secs.min(self.observed_uptime_secs());
 
         // Even without traffic measurements, we can score based on uptime
-        let mut score = 0u32;
+        let mut score = self.tampered_responses.saturating_mul(50);
 
         // Load factor (0-100 → 0-15 points)
         let load_score = (self.announced_load_percent as u32) * 15 / 100;
@@ -559,6 +1252,36 @@ impl ExitNodeStatus {
     }
 }
 
+/// What a request needs from its relays, matched against advertised capacity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RequestProfile {
+    bandwidth: BandwidthClass,
+    /// Largest shard the request sends
+    shard_size: u32,
+}
+
+impl RequestProfile {
+    /// Small request/response traffic: any relay will do
+    fn interactive() -> Self {
+        Self { bandwidth: BandwidthClass::Low, shard_size: MAX_SHARD_SIZE as u32 }
+    }
+
+    /// Large uploads, ranged and streamed downloads
+    fn bulk() -> Self {
+        Self { bandwidth: BandwidthClass::High, shard_size: MAX_SHARD_SIZE as u32 }
+    }
+
+    fn for_request(body: Option<&[u8]>, headers: Option<&[(String, String)]>) -> Self {
+        let large_body = body.is_some_and(|b| b.len() > BULK_REQUEST_BYTES);
+        let ranged = headers.is_some_and(|h| h.iter().any(|(k, _)| k.eq_ignore_ascii_case("range")));
+        if large_body || ranged {
+            Self::bulk()
+        } else {
+            Self::interactive()
+        }
+    }
+}
+
 /// Relay node status tracked via DHT + gossipsub heartbeats
 ///
 /// Scoring formula (lower = better):
@@ -566,6 +1289,9 @@ impl ExitNodeStatus {
 /// - queue: 20% weight (0-20 points)
 /// - bandwidth: 30% weight (0-30 points, inverted — high bw = low score)
 /// - uptime: 20% weight (0-20 points, inverted — long uptime = low score)
+///
+/// Once enough chain acks through it settled, shards lost at the relay add
+/// up to `FORWARD_FAILURE_PENALTY` points on top, scaled by the loss rate.
 #[derive(Debug, Clone)]
 struct RelayNodeStatus {
     info: RelayInfo,
@@ -580,12 +1306,20 @@ struct RelayNodeStatus {
     uptime_secs: u64,
     last_heartbeat: Option<std::time::Instant>,
     last_dht_seen: std::time::Instant,
+    /// Self-reported capacity (DHT record, refreshed by heartbeats)
+    capacity: Option<RelayCapacity>,
+    /// Best throughput we measured through this relay as first hop
+    peak_measured_kbps: u32,
+    measurement_samples: u32,
+    /// Settled chain acks: shards it forwarded, and shards lost at it
+    forwards_verified: u32,
+    forward_failures: u32,
 }
 
 impl RelayNodeStatus {
-    fn new(info: RelayInfo, peer_id: PeerId) -> Self {
-        let now = std::time::Instant::now();
+    fn new(info: RelayInfo, peer_id: PeerId, now: Instant) -> Self {
         Self {
+            capacity: info.capacity,
             info,
             peer_id,
             online: true,
@@ -597,18 +1331,65 @@ impl RelayNodeStatus {
             uptime_secs: 0,
             last_heartbeat: Some(now), // Treat discovery as initial heartbeat
             last_dht_seen: now,
+            peak_measured_kbps: 0,
+            measurement_samples: 0,
+            forwards_verified: 0,
+            forward_failures: 0,
+        }
+    }
+
+    /// Record whether a shard we sent through this relay got past it
+    fn record_forward(&mut self, forwarded: bool) {
+        if forwarded {
+            self.forwards_verified = self.forwards_verified.saturating_add(1);
+        } else {
+            self.forward_failures = self.forward_failures.saturating_add(1);
+        }
+        self.recalculate_score();
+    }
+
+    /// Record throughput measured over a circuit through this relay
+    fn record_measurement(&mut self, kbps: u32) {
+        self.peak_measured_kbps = self.peak_measured_kbps.max(kbps);
+        self.measurement_samples = self.measurement_samples.saturating_add(1);
+    }
+
+    /// Advertised bandwidth class, downgraded to the measured one once
+    /// enough samples show the relay cannot come close to its claim
+    fn effective_bandwidth_class(&self) -> Option<BandwidthClass> {
+        let claimed = self.capacity?.bandwidth_class;
+        let overclaimed = self.measurement_samples >= CAPACITY_VERIFY_SAMPLES
+            && self.peak_measured_kbps.saturating_mul(CAPACITY_OVERCLAIM_FACTOR) < claimed.min_kbps();
+        if overclaimed {
+            Some(BandwidthClass::from_kbps(self.peak_measured_kbps))
+        } else {
+            Some(claimed)
+        }
+    }
+
+    /// How well the relay suits `profile` (lower = better): 0 = meets it,
+    /// 1 = no capacity advertised, 2 = too small
+    fn capacity_fit(&self, profile: &RequestProfile) -> u8 {
+        let (Some(capacity), Some(class)) = (self.capacity, self.effective_bandwidth_class()) else {
+            return 1;
+        };
+        if class < profile.bandwidth || capacity.max_shard_size < profile.shard_size {
+            2
+        } else {
+            0
         }
     }
 
     fn update_from_heartbeat(
         &mut self,
+        now: Instant,
         load_percent: u8,
         active_connections: u32,
         queue_depth: u32,
         bandwidth_kbps: u32,
         uptime_secs: u64,
     ) {
-        self.last_heartbeat = Some(std::time::Instant::now());
+        self.last_heartbeat = Some(now);
         if !self.online {
             self.online = true;
         }
@@ -639,6 +1420,12 @@ impl RelayNodeStatus {
         let uptime_capped = self.uptime_secs.min(86400) as u32;
         score += 20u32.saturating_sub(uptime_capped * 20 / 86400);
 
+        // Forward failures (chain acks that stopped at this relay)
+        let forwards = self.forwards_verified.saturating_add(self.forward_failures);
+        if forwards >= FORWARD_VERIFY_SAMPLES {
+            score += (self.forward_failures as u64 * FORWARD_FAILURE_PENALTY as u64 / forwards as u64) as u32;
+        }
+
         self.score = score.min(100) as u8;
     }
 }
@@ -658,6 +1445,9 @@ enum NatStatus {
 #[allow(dead_code)]
 struct ExitTaskResult {
     handler: ExitHandler,
+    assembly_id: Id,
+    /// Pool the request was billed to
+    pool: Option<PublicKey>,
     shard_pairs: Vec<(Shard, Option<Vec<u8>>)>,
     process_ms: u128,
 }
@@ -665,10 +1455,21 @@ struct ExitTaskResult {
 /// Internal state that needs synchronization
 struct NodeState {
     stats: NodeStats,
+    /// Per-second bytes by capability, for live graphs
+    throughput: ThroughputSeries,
     relay_handler: Option<RelayHandler>,
     exit_handler: Option<ExitHandler>,
 }
 
+/// Outcome of one registry sync: (peer, registry, records or error)
+type RegistrySyncResult = (PeerId, RegistryKind, std::result::Result<RegistrySyncResponse, String>);
+
+/// Proofs of a direct submission that no aggregator answered
+type UnansweredProofs = Vec<Vec<u8>>;
+
+/// Outcome of one backfill: (request, answering peer and its encoded proofs, or error)
+type BackfillResult = (ProofBackfillRequest, std::result::Result<(PeerId, Vec<Vec<u8>>), String>);
+
 /// Handles for communicating with a shared libp2p swarm
 pub struct SwarmHandles {
     pub cmd_tx: mpsc::Sender<craftec_network::SharedSwarmCommand>,
@@ -707,6 +1508,20 @@ pub struct CraftNetNode {
     /// Cached connection state for simple checks
     connected_peers: HashSet<PeerId>,
 
+    /// Per-peer connection details and shard bytes (filled by the swarm
+    /// driver and stream manager)
+    connection_table: SharedConnectionTable,
+
+    /// Bytes per peer and protocol, kept after disconnects (filled by the
+    /// swarm driver and stream manager)
+    traffic: SharedTrafficAccounting,
+
+    /// Clock offsets and accepted nonces of exit/relay heartbeat signers
+    status_freshness: StatusFreshness,
+
+    /// Kademlia inspection (standalone swarm only)
+    dht_handle: Option<DhtHandle>,
+
     /// Our local peer ID (set after start)
     local_peer_id: Option<PeerId>,
 
@@ -723,8 +1538,40 @@ pub struct CraftNetNode {
     /// Selected exit node
     selected_exit: Option<ExitInfo>,
 
+    /// Opt-in request audit log (client mode)
+    audit_log: Option<AuditLog>,
+    /// Opt-in cookie jar (client mode)
+    cookie_jar: Option<CookieJar>,
+
+    /// Readiness supervision and reconnect backoff (client mode)
+    reconnect: Option<ReconnectSupervisor>,
+    /// Reconnect progress not yet drained by take_reconnect_events()
+    reconnect_events: Vec<ReconnectEvent>,
+    /// Bad exit signatures not yet drained by take_exit_tamper_events()
+    exit_tamper_events: Vec<ExitTamperEvent>,
+    /// Request size limits exits reported in error frames, below what they
+    /// advertise (e.g. for our pool's tier)
+    exit_request_limits: HashMap<PublicKey, u64>,
+    /// Distributions announced for challenges: (pool, pool type) →
+    /// (announced root, challenge deadline)
+    announced_distributions: HashMap<(PublicKey, PoolType), ([u8; 32], u64)>,
+    /// Challenges we sent as relay, by (pool, pool type, distribution root)
+    sent_challenges: HashMap<(PublicKey, PoolType, [u8; 32]), DistributionChallenge>,
+    /// Dispute progress not yet drained by take_dispute_events()
+    dispute_events: Vec<DisputeEvent>,
+    /// Request outcomes and probe results per exit (client mode)
+    exit_health: HashMap<PublicKey, ExitHealth>,
+    /// Exit failovers not yet drained by take_exit_failover_events()
+    exit_failover_events: Vec<ExitFailoverEvent>,
+    /// Last readiness check (throttles is_ready() in poll_once)
+    last_readiness_check: Instant,
+
     /// Pending requests (client mode)
     pending: HashMap<Id, PendingRequest>,
+    /// Streamed responses (client mode): request_id → state
+    pending_streams: HashMap<Id, PendingStream>,
+    /// Segment assembly_id → (request_id, seq) within each stream's lookahead window
+    stream_segments: HashMap<Id, (Id, u32)>,
 
     /// Erasure coder
     erasure: ErasureCoder,
@@ -750,12 +1597,48 @@ pub struct CraftNetNode {
     last_relay_discovery: Option<std::time::Instant>,
     /// Last exit discovery time (throttle DHT queries)
     last_exit_discovery: Option<std::time::Instant>,
+    /// Next exit/relay registry shard to query for providers
+    exit_shard_cursor: u8,
+    relay_shard_cursor: u8,
+
+    /// Validated exit/relay records, served to peers over registry sync
+    registry_store: Arc<std::sync::Mutex<RegistryStore>>,
+    /// Time source for heartbeats, record TTLs, proof deadlines and
+    /// compaction (see `set_clock`)
+    clock: SharedClock,
+    /// Registry sync client (set after start)
+    registry_client: Option<RegistryClient>,
+    /// Finished registry syncs from background tasks
+    registry_sync_tx: mpsc::UnboundedSender<RegistrySyncResult>,
+    registry_sync_rx: mpsc::UnboundedReceiver<RegistrySyncResult>,
+    /// Peer's `server_time` from our last sync with it, per registry
+    registry_sync_since: HashMap<(PeerId, RegistryKind), u64>,
+    /// Last registry sync round
+    last_registry_sync: Option<std::time::Instant>,
+    /// Accepted exit/relay records, persisted for fast restarts
+    record_cache: RecordCache,
+    /// Last time the record cache was written
+    last_record_cache_save: Option<std::time::Instant>,
+    /// Proofs and status heartbeats already handled, persisted so a
+    /// restart doesn't handle re-forwarded copies again
+    gossip_dedup: GossipDedup,
+    /// Last time the gossip dedup cache was written
+    last_gossip_dedup_save: Option<std::time::Instant>,
+    /// When a requested drain stops waiting for in-flight work
+    drain_deadline: Option<Instant>,
+    /// Peers with inbound streams when the drain began (their in-flight
+    /// shards are still served; everyone else gets a `Draining` nack)
+    drain_peers: HashSet<PeerId>,
+    /// Versions peers announced on their shard streams
+    peer_versions: HashMap<PeerId, PeerVersion>,
 
     /// Shared state (for async access)
     state: Arc<RwLock<NodeState>>,
 
     /// Last exit announcement time (for periodic re-announcement)
     last_exit_announcement: Option<std::time::Instant>,
+    /// What the exit self-test found working (None = not run)
+    exit_capabilities: Option<ExitCapabilities>,
     /// Last heartbeat sent time (for exits)
     last_heartbeat_sent: Option<std::time::Instant>,
     /// Active request count (for load calculation)
@@ -800,6 +1683,17 @@ pub struct CraftNetNode {
     /// Shards waiting for DHT destination lookup (pubkey → buffered shards)
     pending_destination: HashMap<[u8; 32], Vec<Shard>>,
 
+    /// Settlement pubkey → PeerId, only from fresh bindings whose
+    /// signatures verified (the newest per pubkey)
+    verified_bindings: PeerBindings,
+    /// Checks exit/relay DHT records before they are used
+    record_validators: DhtRecordValidators,
+
+    /// Aggregator: proofs from relays whose binding is not yet known
+    /// (relay pubkey → proofs in arrival order), replayed once the
+    /// peer record resolves
+    pending_binding_proofs: HashMap<[u8; 32], Vec<ProofMessage>>,
+
     /// Forward receipts collected from peers proving they received our shards.
     /// Key: request_id, Value: receipts for that request's shards.
     /// Used for on-chain settlement (each receipt proves work done).
@@ -820,10 +1714,50 @@ pub struct CraftNetNode {
     /// Maximum time receipts can sit in the proof queue before forcing compression.
     /// Defaults to 15 minutes. Configurable for testing.
     proof_deadline: Duration,
+    /// Receipt payload bytes queued per pool (byte trigger of `proof_publish`)
+    proof_queue_bytes: HashMap<(PublicKey, PoolType), u64>,
+    /// When each pool's last batch was cut (`proof_publish.min_interval`)
+    proof_last_batch: HashMap<(PublicKey, PoolType), Instant>,
+    /// Compressed proofs not yet published (offline or over budget)
+    proof_outbox: ProofOutbox,
+    /// Uplink budget of proof gossip
+    proof_gossip_budget: GossipBudget,
+    /// Proof topic shards we're subscribed to (aggregator mode)
+    proof_shards_subscribed: Vec<u8>,
+    /// Measured gossip bytes out already paid for from the proof gossip
+    /// budget (published proofs count as soon as they're sent)
+    gossip_out_charged: u64,
+    /// Proofs the swarm couldn't gossip for lack of subscribed peers
+    /// (reported by the standalone swarm only)
+    undelivered_proof_tx: mpsc::UnboundedSender<Vec<u8>>,
+    undelivered_proof_rx: mpsc::UnboundedReceiver<Vec<u8>>,
+    /// Direct proof submission client (set after start)
+    proof_submit_client: Option<ProofSubmitClient>,
+    /// Finished direct submissions from background tasks
+    proof_submit_tx: mpsc::UnboundedSender<UnansweredProofs>,
+    proof_submit_rx: mpsc::UnboundedReceiver<UnansweredProofs>,
+    /// Proofs relays submitted to us directly (aggregator mode)
+    inbound_proof_rx: Option<mpsc::Receiver<InboundProofSubmission>>,
+    /// Outbox publication paused until then after no aggregator could be reached
+    proof_retry_at: Option<Instant>,
+    /// Recent signed proofs (published as relay, accepted as aggregator),
+    /// served to aggregators backfilling a gap
+    proof_archive: Arc<std::sync::Mutex<ProofArchive>>,
+    /// Proof backfill client (set after start)
+    backfill_client: Option<ProofBackfillClient>,
+    /// Finished backfills from background tasks
+    backfill_tx: mpsc::UnboundedSender<BackfillResult>,
+    backfill_rx: mpsc::UnboundedReceiver<BackfillResult>,
+    /// When a backfill was last requested per (relay, pool, pool type) chain
+    backfill_requested: HashMap<([u8; 32], PublicKey, PoolType), Instant>,
+    /// Last check for stalled proof chains (aggregator mode)
+    last_gap_check: Option<Instant>,
     /// Compressor busy flag (set while compressing, cleared when done)
     compressor_busy: bool,
     /// Number of receipt batches compressed successfully
     batches_compressed: u64,
+    /// Number of chunks those batches were compressed in
+    chunks_compressed: u64,
     /// Number of receipt compressions that failed
     compressions_failed: u64,
     /// Last compression duration (for adaptive batch sizing)
@@ -832,6 +1766,12 @@ pub struct CraftNetNode {
     receipt_file: Option<PathBuf>,
     /// Path to proof state file for persistence (None = in-memory only)
     proof_state_file: Option<PathBuf>,
+    /// Proven receipts folded into per-(pool, hour) accumulators
+    receipt_compactor: ReceiptCompactor,
+    /// Overflow of full proof queues (None = no data dir, overflow is dropped)
+    receipt_spill: Option<ReceiptSpill>,
+    /// Proven receipts dropped from memory since the receipts file was last rewritten
+    receipts_compacted_since_rewrite: usize,
     /// Counter for debouncing proof state saves after enqueue (save every 100 receipts)
     proof_enqueue_since_save: u64,
     /// Timestamp of the oldest uncompressed receipt per pool (for deadline flush)
@@ -851,15 +1791,33 @@ pub struct CraftNetNode {
     stream_receipt_rx: Option<mpsc::Receiver<ForwardReceipt>>,
     /// Data plane channel: outbound shards written by background writer task
     outbound_tx: Option<mpsc::Sender<OutboundShard>>,
+    /// Relayed shards waiting for `outbound_tx`, one queue per pool tier
+    relay_queues: TierQueues<OutboundShard>,
     /// Buffered receipts pending batch disk flush (avoids per-receipt file I/O)
     receipt_buffer: Vec<ForwardReceipt>,
     /// In-flight async flush result from spawn_blocking
     flush_result_rx: Option<tokio::sync::oneshot::Receiver<std::io::Result<usize>>>,
+    /// Measured RTT per circuit, keyed by first hop (client mode)
+    circuit_rtt: HashMap<PeerId, RttEstimator>,
+    /// Named browsing identities with their own keys, exits and gateways
+    identities: IdentityRegistry,
+    /// Identity the request being built belongs to (None = node defaults)
+    active_identity: Option<String>,
+    /// Overrides for the request being built (default = node defaults)
+    request_options: RequestOptions,
+    /// Measured RTT across all circuits, for circuits not measured yet
+    overall_rtt: RttEstimator,
     /// Channel for receiving results from spawned exit processing tasks
     exit_task_tx: mpsc::Sender<ExitTaskResult>,
     exit_task_rx: mpsc::Receiver<ExitTaskResult>,
     /// Shards queued for exit processing while handler is busy (async HTTP fetch)
     exit_shard_queue: VecDeque<Shard>,
+    /// Streamed response segments from the exit handler (bounded, so a
+    /// backed-up outbound queue pauses the exit's upstream read)
+    exit_stream_tx: mpsc::Sender<Vec<(Shard, Option<Vec<u8>>)>>,
+    exit_stream_rx: mpsc::Receiver<Vec<(Shard, Option<Vec<u8>>)>>,
+    /// Segment batch waiting for room in the outbound queue
+    exit_stream_backlog: Option<Vec<(Shard, Option<Vec<u8>>)>>,
 
     /// Aggregator service (collects proof messages, builds distributions)
     aggregator: Option<Aggregator>,
@@ -869,15 +1827,26 @@ pub struct CraftNetNode {
     compressor: Arc<dyn ReceiptCompression>,
     /// Stub compressor for free-tier receipts (instant)
     stub_compressor: Arc<ReceiptCompressor>,
-    /// SP1 Groth16 distribution prover (lazy-initialized, requires `sp1` feature)
-    #[cfg(feature = "sp1")]
-    distribution_prover: Option<craftnet_prover::DistributionProver>,
+    /// Groth16 distribution prover (lazy-initialized, requires `sp1` or `risc0` feature).
+    /// Backend chosen by `CRAFTNET_PROVER_BACKEND`.
+    #[cfg(any(feature = "sp1", feature = "risc0"))]
+    distribution_prover: Option<Arc<dyn craftnet_prover::DistributionProving>>,
+    /// Distribution proof jobs, persisted so interrupted proofs restart after a crash
+    proof_jobs: ProofJobQueue,
+    /// Progress reports from proving threads
+    proof_job_tx: mpsc::UnboundedSender<ProofJobUpdate>,
+    proof_job_rx: mpsc::UnboundedReceiver<ProofJobUpdate>,
     /// Channel for receiving compression results from spawn_blocking
     compression_result_rx: Option<tokio::sync::oneshot::Receiver<CompressionResult>>,
     /// Path for persisting aggregator state to disk
     aggregator_state_file: Option<PathBuf>,
     /// Path for the append-only history JSONL log
     aggregator_history_file: Option<PathBuf>,
+    /// Last bandwidth downsampling / history pruning pass
+    last_aggregator_retention: Option<Instant>,
+    /// GeoIP database (`NodeConfig::geoip_database`)
+    geoip: Option<GeoIpResolver>,
+    last_geoip_refresh: Option<Instant>,
     /// Whether on-chain reconciliation has been performed after loading aggregator from disk
     aggregator_reconciled: bool,
 
@@ -888,6 +1857,10 @@ pub struct CraftNetNode {
     settlement_client: Option<Arc<SettlementClient>>,
     /// Last time we ran batch subscription verification
     last_subscription_verify: Option<std::time::Instant>,
+    /// Announced pools waiting for their on-chain check
+    subscription_checks: VecDeque<PublicKey>,
+    /// On-chain balance and claim status of the pools we earned from
+    pool_claims: PoolClaims,
 
     /// NAT status detected by AutoNAT
     nat_status: NatStatus,
@@ -902,20 +1875,71 @@ pub struct CraftNetNode {
     pending_tunnel: HashMap<Id, PendingTunnelRequest>,
     /// Channel for receiving tunnel bursts from SOCKS5 server
     tunnel_burst_rx: Option<mpsc::Receiver<TunnelBurst>>,
+    /// Bursts of open WebSocket sessions (senders live in each `WebSocket`)
+    websocket_burst_tx: mpsc::Sender<WebSocketBurst>,
+    websocket_burst_rx: mpsc::Receiver<WebSocketBurst>,
 
     /// Topology graph for onion path selection (populated from relay/exit discovery)
     topology: crate::path::TopologyGraph,
+    /// Verified rotating onion keys from relay heartbeats: relay signing
+    /// pubkey → (onion pubkey, unix expiry)
+    relay_onion_keys: HashMap<PublicKey, ([u8; 32], u64)>,
 
     /// Maintenance interval (from config)
     maintenance_interval: Duration,
     /// Last time maintenance was run (for auto-maintenance in poll_once)
     last_maintenance: Instant,
+
+    /// Cover traffic scheduler (None when disabled)
+    cover_traffic: Option<CoverTraffic>,
+
+    /// Chain acks owed for request shards we sent (client mode)
+    chain_acks: ChainAckTracker,
+    /// Where to pass on chain acks for shards we forwarded (relay mode)
+    chain_ack_routes: ChainAckRoutes,
+
+    /// Payload compression totals (client requests/responses and exit side)
+    payload_compression: Arc<PayloadCompression>,
+
+    /// Exit request log, throttles and operator blocks, shared with the
+    /// exit handler (kept here so it can be reached while a request runs)
+    exit_abuse: Arc<AbuseMonitor>,
+
+    /// Operator's content filter for the exit handler (None = no filtering)
+    exit_content_filter: Option<Arc<dyn ContentFilter>>,
+
+    /// Free-tier quota tokens for metering exits
+    quota_wallet: QuotaWallet,
+
+    /// Credits spent, earned and reconciled, persisted across restarts
+    credit_ledger: CreditLedger,
+    /// Last time the credit ledger was written
+    last_credit_ledger_save: Option<std::time::Instant>,
+    /// Tier of our own announced subscription (prices our requests)
+    own_subscription_tier: Option<SubscriptionTier>,
+
+    /// Latest maintainer-signed network parameters (highest sequence seen)
+    network_params: Option<NetworkParameters>,
+    /// Where the signed parameter record is kept for the next start
+    network_params_file: Option<PathBuf>,
+    /// Last DHT lookup of the parameter record
+    last_network_params_fetch: Option<std::time::Instant>,
+    /// Latest maintainer-signed aggregator registry (highest sequence seen)
+    aggregator_registry: Option<AggregatorRegistry>,
+    /// Where the signed registry record is kept for the next start
+    aggregator_registry_file: Option<PathBuf>,
+    /// Last DHT lookup of the registry record
+    last_aggregator_registry_fetch: Option<std::time::Instant>,
+    /// Bootstrap nodes resolved from the DNS seed, by domain
+    dns_seed_cache: DnsSeedCache,
 }
 
 /// Snapshot of a known CraftNet peer (relay or exit node) for the UI.
 #[derive(Debug, Clone)]
 pub struct CraftNetPeerInfo {
     pub peer_id: String,
+    /// PeerId is bound to the node's settlement pubkey by a verified binding record
+    pub binding_verified: bool,
     /// "relay", "exit"
     pub role: String,
     pub online: bool,
@@ -930,17 +1954,46 @@ pub struct CraftNetPeerInfo {
     pub region: String,
 }
 
+/// A node in the locally observed network topology.
+#[derive(Debug, Clone)]
+pub struct TopologyNodeInfo {
+    pub peer_id: String,
+    /// Roles seen for this peer: "client", "relay", "exit", "aggregator".
+    /// Empty for peers only known as a link endpoint of another relay.
+    pub roles: Vec<String>,
+    /// Seconds since the peer was last seen via heartbeat/DHT/topology gossip
+    pub last_seen_secs: u64,
+}
+
+/// Snapshot of the network topology as seen by this node.
+///
+/// Built from relay heartbeats (`connected_peers`), DHT-discovered
+/// relays/exits, and our own stream connections.
+#[derive(Debug, Clone, Default)]
+pub struct TopologySnapshot {
+    /// Our own peer ID (None if not started)
+    pub local_peer_id: Option<String>,
+    pub nodes: Vec<TopologyNodeInfo>,
+    /// Undirected links, each stored once with the smaller peer ID first
+    pub edges: Vec<(String, String)>,
+}
+
 impl CraftNetNode {
     /// Create a new unified node
     pub fn new(config: NodeConfig) -> Result<Self> {
         let enable_aggregator = config.capabilities.is_aggregator();
+        let aggregator_retention = config.aggregator_retention;
+        let challenge_window = config.distribution_challenge_window;
+        let relay_qos = config.relay_qos.clone();
         let proof_batch_size = config.proof_batch_size;
         let proof_deadline = config.proof_deadline;
         let maintenance_interval = config.maintenance_interval;
+        let cover_traffic = config.cover_traffic.clone().map(CoverTraffic::new);
         let keypair = match config.signing_secret {
             Some(ref secret) => SigningKeypair::from_secret_bytes(secret),
             None => SigningKeypair::generate(),
         };
+        let aggregator_pool_filter = config.aggregator_pools.filter(keypair.public_key_bytes());
         let encryption_keypair = EncryptionKeypair::generate();
         let libp2p_keypair = config.libp2p_keypair.clone().unwrap_or_else(Keypair::generate_ed25519);
         let erasure =
@@ -948,11 +2001,17 @@ impl CraftNetNode {
 
         let state = Arc::new(RwLock::new(NodeState {
             stats: NodeStats::default(),
+            throughput: ThroughputSeries::new(),
             relay_handler: None,
             exit_handler: None,
         }));
 
         let (exit_task_tx, exit_task_rx) = mpsc::channel(4);
+        let (registry_sync_tx, registry_sync_rx) = mpsc::unbounded_channel();
+        let (undelivered_proof_tx, undelivered_proof_rx) = mpsc::unbounded_channel();
+        let (proof_submit_tx, proof_submit_rx) = mpsc::unbounded_channel();
+        let (backfill_tx, backfill_rx) = mpsc::unbounded_channel();
+        let (exit_stream_tx, exit_stream_rx) = mpsc::channel(EXIT_STREAM_BUFFER);
 
         // Set up receipt and proof state persistence (unique files per peer ID)
         let peer_id = PeerId::from(libp2p_keypair.public());
@@ -968,6 +2027,33 @@ impl CraftNetNode {
         let aggregator_history_file = config.data_dir.as_ref().map(|dir| {
             dir.join(format!("aggregator-history-{}.bin", peer_id))
         });
+        let aggregator_spill_dir = config.data_dir.as_ref().map(|dir| {
+            dir.join(format!("aggregator-spill-{}", peer_id))
+        });
+        let proof_jobs_file = config.data_dir.as_ref().map(|dir| {
+            dir.join(format!("proof-jobs-{}.json", peer_id))
+        });
+        let proof_jobs = ProofJobQueue::load(config.max_concurrent_proofs, proof_jobs_file);
+        let record_cache = RecordCache::load(config.data_dir.as_ref().map(|dir| {
+            dir.join(format!("record-cache-{}.json", peer_id))
+        }));
+        let gossip_dedup = GossipDedup::load(config.data_dir.as_ref().map(|dir| {
+            dir.join(format!("gossip-dedup-{}.bin", peer_id))
+        }));
+        let credit_ledger = match config.data_dir.as_ref() {
+            Some(dir) => {
+                let path = dir.join(format!("credit-ledger-{}.json", peer_id));
+                CreditLedger::load(config.credit_pricing.clone(), path.clone()).unwrap_or_else(|e| {
+                    warn!("Failed to load credit ledger {}: {} — starting empty", path.display(), e);
+                    CreditLedger::new(config.credit_pricing.clone(), Some(path))
+                })
+            }
+            None => CreditLedger::new(config.credit_pricing.clone(), None),
+        };
+        let network_params_file = config.data_dir.as_ref().map(|dir| dir.join("network-params.json"));
+        let aggregator_registry_file = config.data_dir.as_ref().map(|dir| dir.join("aggregator-registry.json"));
+        let (proof_job_tx, proof_job_rx) = mpsc::unbounded_channel();
+        let (websocket_burst_tx, websocket_burst_rx) = mpsc::channel(64);
 
         // Load existing receipts from disk
         let mut forward_receipts: HashMap<Id, Vec<ForwardReceipt>> = HashMap::new();
@@ -998,6 +2084,7 @@ impl CraftNetNode {
         // Load proof state (pool_roots + pending receipts) from disk
         let mut proof_queue: HashMap<(PublicKey, PoolType), VecDeque<ForwardReceipt>> = HashMap::new();
         let mut pool_roots: HashMap<(PublicKey, PoolType), ([u8; 32], u64)> = HashMap::new();
+        let mut receipt_compactor = ReceiptCompactor::default();
         if let Some(ref path) = proof_state_file {
             if path.exists() {
                 match std::fs::read_to_string(path) {
@@ -1019,6 +2106,11 @@ impl CraftNetNode {
                                     proof_queue.entry(pool_key).or_default().push_back(pending.receipt.clone());
                                 }
                             }
+                            for entry in state.compacted {
+                                if let Some(pool_key) = parse_pool_key(&entry.pool_key) {
+                                    receipt_compactor.insert(pool_key, entry.hour, entry.receipts);
+                                }
+                            }
                             info!(
                                 "Loaded proof state: {} pool roots, {} pending receipts from {}",
                                 pool_roots.len(),
@@ -1032,6 +2124,24 @@ impl CraftNetNode {
             }
         }
 
+        // Receipts spilled by an earlier run refill the queues they overflowed
+        let proof_queue_limit = 100_000;
+        let mut receipt_spill = config.data_dir.as_ref().map(|dir| {
+            ReceiptSpill::open(dir.join(format!("proof-spill-{}", peer_id)))
+        });
+        if let Some(ref mut spill) = receipt_spill {
+            for pool_key in spill.pools() {
+                let queue = proof_queue.entry(pool_key).or_default();
+                match spill.take(&pool_key, proof_queue_limit.saturating_sub(queue.len())) {
+                    Ok(receipts) => queue.extend(receipts),
+                    Err(e) => warn!("Failed to read spilled receipts: {}", e),
+                }
+            }
+            if spill.total() > 0 {
+                info!("{} spilled receipts still waiting on disk", spill.total());
+            }
+        }
+
         // Detect pools that need chain recovery: have queued receipts but no pool_roots entry
         let needs_chain_recovery: Vec<(PublicKey, PoolType)> = proof_queue.keys()
             .filter(|key| !pool_roots.contains_key(key))
@@ -1045,6 +2155,9 @@ impl CraftNetNode {
         }
 
         // Seed deadline tracker for any pools restored with pending receipts
+        let proof_queue_bytes: HashMap<(PublicKey, PoolType), u64> = proof_queue.iter()
+            .map(|(k, q)| (*k, q.iter().map(|r| r.payload_size as u64).sum()))
+            .collect();
         let proof_oldest_receipt: HashMap<(PublicKey, PoolType), Instant> = proof_queue.iter()
             .filter(|(_, q)| !q.is_empty())
             .map(|(k, _)| (*k, Instant::now()))
@@ -1053,6 +2166,11 @@ impl CraftNetNode {
         // Will be populated if aggregator state is loaded from disk
         let mut loaded_posted_distributions: Option<HashSet<[u8; 32]>> = None;
 
+        let audit_log = config.audit_log.clone().map(AuditLog::new);
+        let cookie_jar = config.cookie_jar.clone().map(CookieJar::load);
+        let geoip = config.geoip_database.clone().map(GeoIpResolver::new);
+        let exit_abuse = Arc::new(AbuseMonitor::new(config.exit_abuse.clone()));
+
         Ok(Self {
             capabilities: config.capabilities,
             config,
@@ -1062,12 +2180,30 @@ impl CraftNetNode {
             swarm_cmd_tx: None,
             swarm_evt_rx: None,
             connected_peers: HashSet::new(),
+            connection_table: SharedConnectionTable::default(),
+            traffic: SharedTrafficAccounting::default(),
+            status_freshness: StatusFreshness::new(config.status_freshness),
+            dht_handle: None,
             local_peer_id: None,
             connected: false,
             credits: 0,
             exit_nodes: HashMap::new(),
             selected_exit: None,
+            audit_log,
+            cookie_jar,
+            reconnect: config.reconnect.map(ReconnectSupervisor::new),
+            reconnect_events: Vec::new(),
+            exit_tamper_events: Vec::new(),
+            exit_request_limits: HashMap::new(),
+            announced_distributions: HashMap::new(),
+            sent_challenges: HashMap::new(),
+            dispute_events: Vec::new(),
+            exit_health: HashMap::new(),
+            exit_failover_events: Vec::new(),
+            last_readiness_check: Instant::now(),
             pending: HashMap::new(),
+            pending_streams: HashMap::new(),
+            stream_segments: HashMap::new(),
             erasure,
             relay_nodes: HashMap::new(),
             unverified_relay_peers: Vec::new(),
@@ -1078,8 +2214,25 @@ impl CraftNetNode {
             pending_relay_record_queries: HashSet::new(),
             last_relay_discovery: None,
             last_exit_discovery: None,
+            exit_shard_cursor: 0,
+            relay_shard_cursor: 0,
+            registry_store: Arc::new(std::sync::Mutex::new(RegistryStore::new())),
+            clock: system_clock(),
+            registry_client: None,
+            registry_sync_tx,
+            registry_sync_rx,
+            registry_sync_since: HashMap::new(),
+            last_registry_sync: None,
+            record_cache,
+            last_record_cache_save: None,
+            gossip_dedup,
+            last_gossip_dedup_save: None,
+            drain_deadline: None,
+            drain_peers: HashSet::new(),
+            peer_versions: HashMap::new(),
             state,
             last_exit_announcement: None,
+            exit_capabilities: None,
             last_heartbeat_sent: None,
             active_requests: 0,
             exit_bytes_up: 0,
@@ -1096,19 +2249,45 @@ impl CraftNetNode {
             known_peers: HashMap::new(),
             last_peer_announcement: None,
             pending_destination: HashMap::new(),
+            verified_bindings: PeerBindings::new(config.peer_binding_max_age),
+            record_validators: DhtRecordValidators::with_maintainer_keys(config.maintainer_keys.clone()),
+            pending_binding_proofs: HashMap::new(),
             forward_receipts,
             proof_queue,
-            proof_queue_limit: 100_000,
+            proof_queue_limit,
             request_user: HashMap::new(),
             pool_roots,
             proof_batch_size,
             proof_deadline,
+            proof_queue_bytes,
+            proof_last_batch: HashMap::new(),
+            proof_outbox: ProofOutbox::new(),
+            proof_gossip_budget: GossipBudget::new(None),
+            proof_shards_subscribed: Vec::new(),
+            gossip_out_charged: 0,
+            undelivered_proof_tx,
+            undelivered_proof_rx,
+            proof_submit_client: None,
+            proof_submit_tx,
+            proof_submit_rx,
+            inbound_proof_rx: None,
+            proof_retry_at: None,
+            proof_archive: Arc::new(std::sync::Mutex::new(ProofArchive::new())),
+            backfill_client: None,
+            backfill_tx,
+            backfill_rx,
+            backfill_requested: HashMap::new(),
+            last_gap_check: None,
             compressor_busy: false,
             batches_compressed: 0,
+            chunks_compressed: 0,
             compressions_failed: 0,
             last_proof_duration: None,
             receipt_file,
             proof_state_file,
+            receipt_compactor,
+            receipt_spill,
+            receipts_compacted_since_rewrite: 0,
             proof_enqueue_since_save: 0,
             proof_oldest_receipt,
             needs_chain_recovery,
@@ -1118,11 +2297,20 @@ impl CraftNetNode {
             incoming_stream_rx: None,
             stream_receipt_rx: None,
             outbound_tx: None,
+            relay_queues: TierQueues::new(relay_qos),
             receipt_buffer: Vec::new(),
             flush_result_rx: None,
+            circuit_rtt: HashMap::new(),
+            identities: IdentityRegistry::new(),
+            active_identity: None,
+            request_options: RequestOptions::default(),
+            overall_rtt: RttEstimator::new(),
             exit_task_tx,
             exit_task_rx,
             exit_shard_queue: VecDeque::new(),
+            exit_stream_tx,
+            exit_stream_rx,
+            exit_stream_backlog: None,
             aggregator: if enable_aggregator {
                 // Try loading from disk first
                 let mut agg = if let Some(ref path) = aggregator_state_file {
@@ -1143,35 +2331,86 @@ impl CraftNetNode {
                 } else {
                     Aggregator::new()
                 };
+                agg.set_retention_policy(aggregator_retention);
+                agg.set_challenge_window(challenge_window);
+                agg.set_pool_filter(aggregator_pool_filter);
+                // Out-of-order proofs beyond the memory caps go to disk; an
+                // earlier run's replay as their chains catch up
+                if let Some(dir) = aggregator_spill_dir {
+                    match PendingSpill::open(dir) {
+                        Ok(spill) => agg.set_pending_spill(spill),
+                        Err(e) => warn!("Failed to open aggregator pending spill: {}", e),
+                    }
+                }
                 // Recover history sequence number from binary file (doesn't load into memory)
                 if let Some(ref path) = aggregator_history_file {
                     let next_seq = Aggregator::recover_history_seq(path);
                     if next_seq > 0 {
                         agg.set_history_seq(next_seq);
                     }
+                    // The bandwidth index isn't in the state file; replay it so
+                    // per-relay bandwidth queries survive the restart
+                    let proofs = agg.rebuild_bandwidth_from_history_with_progress(path, |p| {
+                        if p.total_bytes > 0 {
+                            info!(
+                                "Rebuilding bandwidth index: {} proofs, {}% of history",
+                                p.proofs,
+                                p.bytes_read * 100 / p.total_bytes,
+                            );
+                        }
+                    });
+                    debug!("Bandwidth index rebuilt from {} proofs", proofs);
                 }
                 Some(agg)
             } else { None },
             posted_distributions: loaded_posted_distributions.unwrap_or_default(),
             compressor: Arc::new(ReceiptCompressor::new()),
             stub_compressor: Arc::new(ReceiptCompressor::new()),
-            #[cfg(feature = "sp1")]
+            #[cfg(any(feature = "sp1", feature = "risc0"))]
             distribution_prover: None,
+            proof_jobs,
+            proof_job_tx,
+            proof_job_rx,
             compression_result_rx: None,
             aggregator_state_file,
             aggregator_history_file,
+            last_aggregator_retention: None,
+            geoip,
+            last_geoip_refresh: None,
             aggregator_reconciled: false,
             subscription_cache: HashMap::new(),
             settlement_client: None,
             last_subscription_verify: None,
+            subscription_checks: VecDeque::new(),
+            pool_claims: PoolClaims::new(POOL_CLAIM_REFRESH_INTERVAL, POOL_CLAIM_BATCH_SIZE),
             nat_status: NatStatus::Unknown,
             bootstrap_peer_ids: Vec::new(),
             last_bootstrap_check: None,
             pending_tunnel: HashMap::new(),
             tunnel_burst_rx: None,
+            websocket_burst_tx,
+            websocket_burst_rx,
             topology: crate::path::TopologyGraph::new(),
+            relay_onion_keys: HashMap::new(),
             maintenance_interval,
             last_maintenance: Instant::now(),
+            cover_traffic,
+            chain_acks: ChainAckTracker::new(),
+            chain_ack_routes: ChainAckRoutes::new(),
+            payload_compression: Arc::default(),
+            exit_abuse,
+            exit_content_filter: None,
+            quota_wallet: QuotaWallet::new(),
+            credit_ledger,
+            last_credit_ledger_save: None,
+            own_subscription_tier: None,
+            network_params: None,
+            network_params_file,
+            last_network_params_fetch: None,
+            aggregator_registry: None,
+            aggregator_registry_file,
+            last_aggregator_registry_fetch: None,
+            dns_seed_cache: DnsSeedCache::new(),
         })
     }
 
@@ -1195,6 +2434,10 @@ impl CraftNetNode {
             if caps.is_relay() && state.relay_handler.is_none() {
                 let relay_config = RelayConfig {
                     can_be_last_hop: self.config.allow_last_hop,
+                    replay_cache_capacity: self.config.relay_replay_capacity,
+                    replay_window: self.config.relay_replay_window,
+                    onion_key_rotation: self.config.onion_key_rotation,
+                    onion_key_grace: self.config.onion_key_grace,
                 };
                 state.relay_handler =
                     Some(RelayHandler::with_config(
@@ -1209,8 +2452,18 @@ impl CraftNetNode {
                 let mut exit_config = ExitConfig {
                     timeout: self.config.request_timeout,
                     allow_private_ips: self.config.exit_allow_private_ips,
+                    require_sni: self.config.exit_require_sni,
+                    response_cache: self.config.exit_response_cache,
+                    require_subscription: self.config.exit_require_subscription,
+                    quota_tokens: self.config.exit_quota_tokens,
+                    quota_spent_file: self.config.data_dir.as_ref().map(|dir| {
+                        dir.join(format!("quota-spent-{}.bin", hex::encode(self.keypair.public_key_bytes())))
+                    }),
                     ..Default::default()
                 };
+                if !self.config.exit_self_test {
+                    exit_config.self_test = None;
+                }
                 if let Some(ref blocked) = self.config.exit_blocked_domains {
                     exit_config.blocked_domains = blocked.clone();
                 }
@@ -1225,6 +2478,12 @@ impl CraftNetNode {
                 ) {
                     Ok(mut handler) => {
                         handler.set_settlement_client(settlement_client);
+                        handler.set_stream_sink(self.exit_stream_tx.clone());
+                        handler.set_compression_stats(self.payload_compression.clone());
+                        handler.set_abuse_monitor(self.exit_abuse.clone());
+                        if let Some(ref filter) = self.exit_content_filter {
+                            handler.set_content_filter(filter.clone());
+                        }
                         state.exit_handler = Some(handler);
                         info!("Exit handler initialized with devnet settlement");
                     }
@@ -1232,6 +2491,8 @@ impl CraftNetNode {
                 }
             }
         }
+
+        self.sync_proof_subscriptions();
     }
 
     /// Get our peer ID
@@ -1251,21 +2512,32 @@ impl CraftNetNode {
     pub async fn start(&mut self, handles: Option<SwarmHandles>) -> Result<()> {
         info!("Starting CraftNetNode with capabilities {:?}", self.capabilities);
 
+        // Registry sync is accepted before the swarm is driven, for the same
+        // reason the shard protocol is accepted inside `build_swarm`.
+        let mut registry_incoming = None;
+        let mut proof_incoming = None;
+        let mut backfill_incoming = None;
         let handles = if let Some(h) = handles {
             h
         } else {
             // Standalone mode: build local swarm and bridge it over channels.
             // Use the node's configured listen address and bootstrap peers so
             // other nodes can dial us at the expected address.
+            let mut listen_addrs = vec![self.config.listen_addr.clone()];
+            listen_addrs.extend(self.config.webrtc_listen_addr.clone());
             let net_config = craftnet_network::NetworkConfig {
-                listen_addrs: vec![self.config.listen_addr.clone()],
+                listen_addrs,
                 bootstrap_peers: self.config.bootstrap_peers.clone(),
+                webrtc_cert_path: self.config.data_dir.as_ref().map(|d| d.join("webrtc-cert.pem")),
             };
             let (swarm, peer_id, mut incoming) = build_swarm(self.libp2p_keypair.clone(), net_config)
                 .await
                 .map_err(|e| ClientError::ConnectionFailed(e.to_string()))?;
 
-            let stream_control = swarm.behaviour().stream_control();
+            let mut stream_control = swarm.behaviour().stream_control();
+            registry_incoming = stream_control.accept(REGISTRY_SYNC_PROTOCOL).ok();
+            proof_incoming = stream_control.accept(PROOF_SUBMIT_PROTOCOL).ok();
+            backfill_incoming = stream_control.accept(PROOF_BACKFILL_PROTOCOL).ok();
             let (cmd_tx, cmd_rx) = mpsc::channel(256);
             let (evt_tx, evt_rx) = mpsc::channel(1024);
             let (incoming_tx, incoming_rx) = mpsc::channel(256);
@@ -1281,7 +2553,13 @@ impl CraftNetNode {
             });
 
             // Start standalone swarm driver
-            tokio::spawn(run_standalone_swarm(swarm, cmd_rx, evt_tx));
+            let validators = DhtRecordValidators::with_maintainer_keys(self.config.maintainer_keys.clone());
+            let (dht_handle, dht_rx) = DhtHandle::channel();
+            self.dht_handle = Some(dht_handle);
+            tokio::spawn(run_standalone_swarm(
+                swarm, cmd_rx, evt_tx, dht_rx, validators, self.connection_table.clone(),
+                self.traffic.clone(), self.undelivered_proof_tx.clone(), self.clock.clone(),
+            ));
 
             SwarmHandles {
                 cmd_tx,
@@ -1294,8 +2572,44 @@ impl CraftNetNode {
 
         info!("Node started with peer ID: {}", handles.local_peer_id);
 
-        let (stream_mgr, high_rx, low_rx, receipt_rx, outbound_tx) =
-            StreamManager::new(handles.stream_control);
+        let registry_incoming = match registry_incoming {
+            Some(incoming) => Some(incoming),
+            None => handles.stream_control.clone().accept(REGISTRY_SYNC_PROTOCOL)
+                .map_err(|e| warn!("Registry sync not served: {}", e))
+                .ok(),
+        };
+        if let Some(incoming) = registry_incoming {
+            tokio::spawn(serve_registry_sync(incoming, self.registry_store.clone()));
+        }
+        self.registry_client = Some(RegistryClient::new(handles.stream_control.clone()));
+
+        let proof_incoming = match proof_incoming {
+            Some(incoming) => Some(incoming),
+            None => handles.stream_control.clone().accept(PROOF_SUBMIT_PROTOCOL)
+                .map_err(|e| warn!("Direct proof submission not served: {}", e))
+                .ok(),
+        };
+        if let Some(incoming) = proof_incoming {
+            let (tx, rx) = mpsc::channel(64);
+            tokio::spawn(serve_proof_submissions(incoming, tx));
+            self.inbound_proof_rx = Some(rx);
+        }
+        self.proof_submit_client = Some(ProofSubmitClient::new(handles.stream_control.clone()));
+
+        let backfill_incoming = match backfill_incoming {
+            Some(incoming) => Some(incoming),
+            None => handles.stream_control.clone().accept(PROOF_BACKFILL_PROTOCOL)
+                .map_err(|e| warn!("Proof backfill not served: {}", e))
+                .ok(),
+        };
+        if let Some(incoming) = backfill_incoming {
+            tokio::spawn(serve_proof_backfill(incoming, self.proof_archive.clone()));
+        }
+        self.backfill_client = Some(ProofBackfillClient::new(handles.stream_control.clone()));
+
+        let (mut stream_mgr, high_rx, low_rx, receipt_rx, outbound_tx) =
+            StreamManager::with_accounting(handles.stream_control, self.connection_table.clone(), self.traffic.clone());
+        stream_mgr.set_frame_encryption(self.config.frame_encryption);
         self.stream_manager = Some(stream_mgr);
         self.inbound_high_rx = Some(high_rx);
         self.inbound_low_rx = Some(low_rx);
@@ -1307,9 +2621,16 @@ impl CraftNetNode {
         self.swarm_cmd_tx = Some(handles.cmd_tx);
         self.swarm_evt_rx = Some(handles.evt_rx);
 
-        // Initialize handlers based on mode
+        // Initialize handlers based on mode (and follow the proof topics)
+        self.proof_shards_subscribed.clear();
         self.set_capabilities(self.capabilities);
 
+        // Use cached exit/relay records now instead of waiting on the DHT
+        self.warm_from_record_cache();
+        // Last known network parameters (extra bootstrap nodes among them)
+        self.load_network_params();
+        self.load_aggregator_registry();
+
         // Immediately announce any capabilities that were set before the swarm connected.
         // Without this, relay/exit activation before Connect would silently skip the
         // first DHT announce (announce_as_relay guards on local_peer_id being Some).
@@ -1330,27 +2651,32 @@ impl CraftNetNode {
         self.send_swarm_cmd(craftec_network::SharedSwarmCommand::ListenOn(
             self.config.listen_addr.clone()
         ));
+        if let Some(ref addr) = self.config.webrtc_listen_addr {
+            info!("[node] Listening for browser clients on {}", addr);
+            self.send_swarm_cmd(craftec_network::SharedSwarmCommand::ListenOn(addr.clone()));
+        }
 
         // Connect to bootstrap peers
         self.connect_bootstrap().await?;
 
         // Record bootstrap peer IDs for reconnection
-        let bootstrap_peers = if !self.config.bootstrap_peers.is_empty() {
-            self.config.bootstrap_peers.clone()
-        } else {
-            craftnet_network::default_bootstrap_peers()
-        };
+        let bootstrap_peers = self.default_or_configured_bootstrap_peers();
         self.bootstrap_peer_ids = bootstrap_peers.iter().map(|(pid, _)| *pid).collect();
+        if let Some(ref params) = self.network_params {
+            self.bootstrap_peer_ids.extend(params.bootstrap_peers().into_iter().map(|(pid, _)| pid));
+        }
 
         // Bootstrap the Kademlia DHT so we discover peers and exit nodes
         self.send_swarm_cmd(craftec_network::SharedSwarmCommand::BootstrapSecondary);
+        self.fetch_network_params();
+        self.fetch_aggregator_registry();
 
         // Subscribe to gossipsub topics
         let topics = vec![
             EXIT_STATUS_TOPIC,
-            PROOF_TOPIC,
             RELAY_STATUS_TOPIC,
             SUBSCRIPTION_TOPIC,
+            DISPUTE_TOPIC,
         ];
         for topic in topics {
             self.send_swarm_cmd(craftec_network::SharedSwarmCommand::SubscribeGossipsub(topic.to_string()));
@@ -1366,10 +2692,16 @@ impl CraftNetNode {
                 self.config.settlement_config.clone(),
                 &self.keypair.secret_key_bytes(),
             )));
+            self.apply_aggregator_allowlist();
         }
 
-        // Announce as exit node if enabled
+        // Self-reported AS and country come from the GeoIP database
+        self.maybe_refresh_geoip();
+
+        // Announce as exit node if enabled, once the self-test has shown
+        // what the upstream can do
         if self.capabilities.is_exit() {
+            self.run_exit_self_test().await;
             self.announce_as_exit();
         }
 
@@ -1394,6 +2726,92 @@ impl CraftNetNode {
         }
     }
 
+    /// Proof topic shards this node follows: relays and exits carry every
+    /// shard so proofs reach aggregators they have no mesh link to, an
+    /// aggregator alone only the shards of the pools it follows.
+    fn wanted_proof_shards(&self) -> Vec<u8> {
+        if self.capabilities.is_relay() || self.capabilities.is_exit() {
+            (0..PROOF_TOPIC_SHARDS).collect()
+        } else if self.aggregator.is_some() {
+            self.config.aggregator_pools.proof_shards(self.keypair.public_key_bytes())
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Subscribe to the proof topic shards we want, and leave the ones we
+    /// no longer need. Anyone following proofs also follows the unsharded
+    /// topic older relays and aggregators still use.
+    fn sync_proof_subscriptions(&mut self) {
+        if self.swarm_cmd_tx.is_none() {
+            return;
+        }
+        let wanted = self.wanted_proof_shards();
+        if self.proof_shards_subscribed.is_empty() && !wanted.is_empty() {
+            self.send_swarm_cmd(craftec_network::SharedSwarmCommand::SubscribeGossipsub(PROOF_TOPIC.to_string()));
+        } else if !self.proof_shards_subscribed.is_empty() && wanted.is_empty() {
+            self.send_swarm_cmd(craftec_network::SharedSwarmCommand::UnsubscribeGossipsub(PROOF_TOPIC.to_string()));
+        }
+        for &shard in self.proof_shards_subscribed.iter().filter(|s| !wanted.contains(s)) {
+            self.send_swarm_cmd(craftec_network::SharedSwarmCommand::UnsubscribeGossipsub(proof_shard_topic(shard)));
+        }
+        for &shard in wanted.iter().filter(|s| !self.proof_shards_subscribed.contains(s)) {
+            self.send_swarm_cmd(craftec_network::SharedSwarmCommand::SubscribeGossipsub(proof_shard_topic(shard)));
+        }
+        debug!("Following proof topic shards {:?}", wanted);
+        self.proof_shards_subscribed = wanted;
+    }
+
+    /// Pad an outbound shard to its size bucket when shard padding is enabled
+    fn padded(&self, mut shard: Shard) -> Shard {
+        if self.config.shard_padding {
+            shard.pad_to_bucket();
+        }
+        shard
+    }
+
+    /// Send cover shards to first-hop relays whose circuits have gone idle.
+    ///
+    /// Cover shards are acked without a ForwardReceipt, so they are counted
+    /// only in `cover_shards_sent` — never in bytes_sent or credits.
+    fn maybe_send_cover_traffic(&mut self) {
+        let Some(ref mut cover) = self.cover_traffic else {
+            return;
+        };
+        let due = cover.due(Instant::now());
+        if due.is_empty() {
+            return;
+        }
+
+        let mut sent = 0u64;
+        for peer in due {
+            // Only relays can peel (and drop) a cover shard
+            let Some(enc_pubkey) = self.relay_nodes.values()
+                .find(|s| s.peer_id == peer)
+                .and_then(|s| s.info.encryption_pubkey)
+            else {
+                continue;
+            };
+            let shard = match build_cover_shard(&peer.to_bytes(), &enc_pubkey) {
+                Ok(shard) => self.padded(shard),
+                Err(e) => {
+                    debug!("Failed to build cover shard: {}", e);
+                    continue;
+                }
+            };
+            if let Some(ref tx) = self.outbound_tx {
+                if tx.try_send(OutboundShard { peer, shard }).is_ok() {
+                    sent += 1;
+                }
+            }
+        }
+
+        if sent > 0 {
+            self.state.write().stats.cover_shards_sent += sent;
+            debug!("Sent {} cover shards on idle circuits", sent);
+        }
+    }
+
     /// Connect to bootstrap peers
     async fn connect_bootstrap(&mut self) -> Result<()> {
         if self.swarm_cmd_tx.is_none() {
@@ -1405,11 +2823,12 @@ impl CraftNetNode {
         // block on connection for explicit peers — nodes using defaults may be
         // bootstrap nodes themselves (or defaults may be unreachable).
         let has_explicit_peers = !self.config.bootstrap_peers.is_empty();
-        let bootstrap_peers = if has_explicit_peers {
-            self.config.bootstrap_peers.clone()
-        } else {
-            craftnet_network::default_bootstrap_peers()
-        };
+        if !has_explicit_peers {
+            if let Some(domain) = self.config.dns_seed.clone() {
+                self.dns_seed_cache.resolve(&domain).await;
+            }
+        }
+        let bootstrap_peers = self.default_or_configured_bootstrap_peers();
         // Don't try to dial ourselves
         let local_peer = self.local_peer_id;
         let bootstrap_peers: Vec<_> = bootstrap_peers
@@ -1561,6 +2980,16 @@ impl CraftNetNode {
             self.announce_relay_offline();
         }
 
+        self.save_proof_state();
+        self.record_cache.save();
+        self.gossip_dedup.save();
+        self.save_credit_ledger();
+        self.drain_deadline = None;
+        self.drain_peers.clear();
+        if let Some(ref mut sup) = self.reconnect {
+            sup.reset();
+        }
+
         self.connected = false;
         self.pending.clear();
         self.relay_nodes.clear();
@@ -1573,10 +3002,12 @@ impl CraftNetNode {
     /// Announce going offline via gossipsub (for exits)
     fn announce_offline(&mut self) {
         let peer_id_str = self.local_peer_id.map(|p| p.to_string()).unwrap_or_default();
-        let msg = ExitStatusMessage::offline(
+        let mut msg = ExitStatusMessage::offline(
             self.keypair.public_key_bytes(),
             &peer_id_str,
         );
+        msg.timestamp = self.clock.unix_now();
+        msg.sign(&self.keypair, rand::random());
         self.send_swarm_cmd(craftec_network::SharedSwarmCommand::PublishGossipsub {
             topic: EXIT_STATUS_TOPIC.to_string(),
             data: msg.to_bytes(),
@@ -1584,17 +3015,200 @@ impl CraftNetNode {
         debug!("Announced offline status");
     }
 
-    /// Publish heartbeat via gossipsub (for exits)
-    fn publish_heartbeat(&mut self) {
-        if !self.capabilities.is_exit() {
+    /// Stop providing the exit registry keys in the DHT
+    fn stop_providing_exit_registry(&mut self) {
+        self.send_swarm_cmd(craftec_network::SharedSwarmCommand::StopProvidingSecondary(
+            libp2p::kad::RecordKey::new(&craftnet_network::EXIT_REGISTRY_KEY),
+        ));
+        self.send_swarm_cmd(craftec_network::SharedSwarmCommand::StopProvidingSecondary(
+            libp2p::kad::RecordKey::new(&craftnet_network::exit_registry_shard_key(
+                registry_shard(&self.keypair.public_key_bytes()),
+            )),
+        ));
+    }
+
+    // =========================================================================
+    // Graceful drain
+    // =========================================================================
+
+    /// Start draining ahead of a shutdown.
+    ///
+    /// The node stops advertising itself (offline status + DHT provider
+    /// records withdrawn, no re-announcements or heartbeats), nacks shards
+    /// that would start new work with `NACK_DRAINING`, and keeps serving
+    /// in-flight assemblies and circuits until they finish or `timeout`
+    /// (default: `NodeConfig::drain_timeout`) passes. Queued receipts are
+    /// compressed without waiting for a full batch.
+    ///
+    /// Poll until `is_drained()`, then `stop()` — or use `drain()`.
+    pub fn begin_drain(&mut self, timeout: Option<Duration>) {
+        if self.drain_deadline.is_some() {
             return;
         }
+        let timeout = timeout.unwrap_or(self.config.drain_timeout);
+        info!("Draining node (timeout {:?})", timeout);
+        self.drain_deadline = Some(Instant::now() + timeout);
+        self.drain_peers = self.stream_manager.as_ref()
+            .map(|sm| sm.inbound_peers().into_iter().collect())
+            .unwrap_or_default();
 
-        // Calculate throughput from measurement window
-        self.calculate_exit_throughput();
-
-        // Calculate load percentage (0-100)
-        // For now, use active_requests as a simple metric
+        if self.capabilities.is_exit() {
+            self.announce_offline();
+            self.stop_providing_exit_registry();
+        }
+        if self.capabilities.is_relay() {
+            self.announce_relay_offline();
+        }
+    }
+
+    /// Whether a drain has been requested
+    pub fn is_draining(&self) -> bool {
+        self.drain_deadline.is_some()
+    }
+
+    /// In-flight work the drain is waiting on
+    pub fn drain_status(&self) -> DrainStatus {
+        let (pending_assemblies, handler_busy) = {
+            let state = self.state.read();
+            match state.exit_handler {
+                Some(ref handler) => (handler.pending_count(), false),
+                None => (0, self.capabilities.is_exit()),
+            }
+        };
+        let outbound_queued = self.outbound_tx.as_ref()
+            .map(|tx| tx.max_capacity() - tx.capacity())
+            .unwrap_or(0);
+        DrainStatus {
+            draining: self.is_draining(),
+            pending_assemblies,
+            exit_tasks: handler_busy as usize + self.exit_shard_queue.len(),
+            queued_receipts: self.proof_queue.iter()
+                .filter(|(k, _)| !self.needs_chain_recovery.contains(k))
+                .map(|(_, q)| q.len())
+                .sum(),
+            compressing: self.compressor_busy,
+            unpublished_proofs: self.proof_outbox.len(),
+            unflushed_receipts: self.receipt_buffer.len() + self.flush_result_rx.is_some() as usize,
+            outbound_queued,
+            remaining: self.drain_deadline.map(|d| d.saturating_duration_since(Instant::now())),
+        }
+    }
+
+    /// Whether a requested drain has finished (idle, or out of time)
+    pub fn is_drained(&self) -> bool {
+        let Some(deadline) = self.drain_deadline else {
+            return false;
+        };
+        Instant::now() >= deadline || self.drain_status().is_idle()
+    }
+
+    /// Drain in-flight work, then stop the node.
+    ///
+    /// Drives `poll_once()` itself; callers that already run the poll loop
+    /// should call `begin_drain()` and check `is_drained()` instead.
+    pub async fn drain(&mut self, timeout: Option<Duration>) -> DrainStatus {
+        self.begin_drain(timeout);
+        while !self.is_drained() {
+            self.poll_once().await;
+        }
+        let status = self.drain_status();
+        self.stop().await;
+        status
+    }
+
+    /// Whether a shard arriving during a drain may still be served
+    fn accept_while_draining(&self, shard: &Shard, source_peer: &PeerId, assembly_id: Option<&Id>) -> bool {
+        if shard.header.is_empty() {
+            // Exit: only assemblies that were already being collected
+            let state = self.state.read();
+            return match (&state.exit_handler, assembly_id) {
+                (Some(handler), Some(id)) => handler.has_pending(id),
+                // Handler busy with a fetch: can't tell, queue it
+                (None, Some(_)) => true,
+                (_, None) => false,
+            };
+        }
+        // Relay: circuits whose previous hop was streaming to us already
+        self.drain_peers.contains(source_peer)
+    }
+
+    /// Route around peers that nacked us with `NACK_DRAINING`
+    fn handle_draining_peers(&mut self, peers: HashSet<PeerId>) {
+        for peer in &peers {
+            info!("Peer {} is draining, routing around it", peer);
+        }
+        self.route_around_peers(peers);
+    }
+
+    /// Record the protocol versions peers announced and route around
+    /// those below `min_peer_protocol_version`
+    fn handle_peer_versions(&mut self, versions: Vec<(PeerId, PeerVersion)>) {
+        let mut outdated = HashSet::new();
+        for (peer, version) in versions {
+            self.peer_versions.insert(peer, version);
+            if self.is_outdated_peer(&peer) {
+                warn!(
+                    "Peer {} speaks protocol v{} (minimum v{}), not routing through it",
+                    peer, self.peer_versions[&peer].protocol_version, self.config.min_peer_protocol_version
+                );
+                outdated.insert(peer);
+            }
+        }
+        if !outdated.is_empty() {
+            self.route_around_peers(outdated);
+        }
+    }
+
+    /// Whether `peer` announced a protocol version below the configured minimum
+    fn is_outdated_peer(&self, peer: &PeerId) -> bool {
+        self.peer_versions
+            .get(peer)
+            .is_some_and(|v| v.protocol_version < self.config.min_peer_protocol_version)
+    }
+
+    /// Protocol version counts of the peers we have streams with
+    pub fn peer_version_distribution(&self) -> BTreeMap<u16, usize> {
+        let mut counts = BTreeMap::new();
+        for version in self.peer_versions.values() {
+            *counts.entry(version.protocol_version).or_default() += 1;
+        }
+        counts
+    }
+
+    /// Drop `peers` from relay selection and take exits among them offline
+    fn route_around_peers(&mut self, peers: HashSet<PeerId>) {
+        for peer in peers {
+            self.unverified_relay_peers.retain(|p| *p != peer);
+            self.relay_nodes.retain(|_, s| s.peer_id != peer);
+
+            let peer_str = peer.to_string();
+            let mut reselect = false;
+            for status in self.exit_nodes.values_mut() {
+                let is_peer = status.peer_id == Some(peer)
+                    || status.info.peer_id.as_deref() == Some(peer_str.as_str());
+                if status.online && is_peer {
+                    status.online = false;
+                    reselect |= self.selected_exit.as_ref().map(|e| e.pubkey) == Some(status.info.pubkey);
+                }
+            }
+            if reselect {
+                warn!("Selected exit {} is no longer routable, selecting new exit", peer);
+                self.select_best_exit();
+            }
+        }
+    }
+
+    /// Publish heartbeat via gossipsub (for exits)
+    fn publish_heartbeat(&mut self) {
+        if !self.capabilities.is_exit() || self.is_draining() {
+            return;
+        }
+
+        // Calculate throughput from measurement window
+        self.calculate_exit_throughput();
+
+        // Calculate load percentage (0-100)
+        // For now, use active_requests as a simple metric
         // Could be enhanced with CPU/memory/bandwidth metrics
         let load_percent = (self.active_requests.min(100) as u8).min(100);
 
@@ -1620,7 +3234,9 @@ impl CraftNetNode {
             region,
             connected_peers,
         );
+        msg.timestamp = self.clock.unix_now();
         msg.encryption_pubkey = Some(hex::encode(self.encryption_keypair.public_key_bytes()));
+        msg.sign(&self.keypair, rand::random());
         
         self.send_swarm_cmd(craftec_network::SharedSwarmCommand::PublishGossipsub {
             topic: EXIT_STATUS_TOPIC.to_string(),
@@ -1630,7 +3246,7 @@ impl CraftNetNode {
             "Published heartbeat (load: {}%, uplink: {}KB/s, downlink: {}KB/s, uptime: {}s, peers: {})",
             load_percent, self.exit_uplink_kbps, self.exit_downlink_kbps, uptime_secs, msg.connected_peers.len()
         );
-        self.last_heartbeat_sent = Some(std::time::Instant::now());
+        self.last_heartbeat_sent = Some(self.clock.now());
     }
 
     /// Calculate exit throughput from measurement window
@@ -1660,7 +3276,7 @@ impl CraftNetNode {
 
         let should_send = match self.last_heartbeat_sent {
             None => true,
-            Some(last) => last.elapsed() >= EXIT_HEARTBEAT_INTERVAL,
+            Some(last) => self.clock.now().duration_since(last) >= EXIT_HEARTBEAT_INTERVAL,
         };
 
         if should_send {
@@ -1668,6 +3284,19 @@ impl CraftNetNode {
         }
     }
 
+    /// Check a status message's signature result, then its timestamp and
+    /// nonce against the signer's tracked clock
+    fn check_status(
+        &mut self,
+        signer: &PublicKey,
+        verified: std::result::Result<(), StatusRejection>,
+        timestamp: u64,
+        nonce: u64,
+    ) -> std::result::Result<(), StatusRejection> {
+        verified?;
+        self.status_freshness.check(signer, timestamp, nonce, self.clock.unix_now())
+    }
+
     /// Handle incoming exit status message from gossipsub
     fn handle_exit_status(&mut self, data: &[u8], source: Option<PeerId>) {
         let Some(msg) = ExitStatusMessage::from_bytes(data) else {
@@ -1679,9 +3308,17 @@ impl CraftNetNode {
             debug!("Invalid pubkey in exit status message");
             return;
         };
+        if let Err(reason) = self.check_status(&pubkey, msg.verify(), msg.timestamp, msg.nonce) {
+            debug!("Rejected exit status from {}: {}", msg.peer_id, reason);
+            return;
+        }
 
         match msg.status {
+            ExitStatusType::Heartbeat if msg.peer_id.parse::<PeerId>().is_ok_and(|p| self.is_outdated_peer(&p)) => {
+                debug!("Ignoring heartbeat from exit {} below the minimum protocol version", msg.peer_id);
+            }
             ExitStatusType::Heartbeat => {
+                let now = self.clock.now();
                 // Update exit node status with announced values
                 if let Some(status) = self.exit_nodes.get_mut(&pubkey) {
                     // Track PeerId from gossipsub source
@@ -1693,6 +3330,7 @@ impl CraftNetNode {
                         }
                     }
                     status.update_from_heartbeat(
+                        now,
                         msg.load_percent,
                         msg.uplink_kbps,
                         msg.downlink_kbps,
@@ -1726,6 +3364,11 @@ impl CraftNetNode {
                                 let peer_id_bytes = msg.peer_id.parse::<PeerId>()
                                     .map(|p| p.to_bytes())
                                     .unwrap_or_default();
+                                // An exit that also relays announces a rotated
+                                // onion key in its relay heartbeat; prefer it
+                                if let Some(onion_key) = self.current_onion_key(&pubkey) {
+                                    enc_key = onion_key;
+                                }
                                 if !peer_id_bytes.is_empty() {
                                     self.topology.update_relay(TopologyRelay {
                                         peer_id: peer_id_bytes,
@@ -1756,21 +3399,38 @@ impl CraftNetNode {
         }
     }
 
-    /// Select the best available exit (online, lowest score, matching geo preference)
-    ///
-    /// Score combines: load (20%), latency (30%), throughput (50%)
-    /// Lower score = better exit.
-    /// When a geo preference is set (region != Auto, or country/city specified),
-    /// only exits matching the preference are considered.
-    fn select_best_exit(&mut self) {
-        let has_geo_preference = self.exit_preference_region != ExitRegion::Auto
+    /// Whether a region, country or city preference restricts exit selection
+    fn has_exit_geo_preference(&self) -> bool {
+        self.exit_preference_region != ExitRegion::Auto
             || self.exit_preference_country.is_some()
-            || self.exit_preference_city.is_some();
+            || self.exit_preference_city.is_some()
+    }
 
-        let candidates = self
+    /// Exits selection may pick: online, not outdated, allowed by the circuit
+    /// geo constraints and the geo preference, and not demoted by health
+    /// tracking while a healthy exit remains
+    fn exit_candidates(&self) -> Vec<&ExitNodeStatus> {
+        let has_geo_preference = self.has_exit_geo_preference();
+        let circuit_geo = &self.config.circuit_geo;
+        let geo_allowed = self
             .exit_nodes
+            .values()
+            .any(|s| s.online && circuit_geo.allows_exit(s.info.country_code.as_deref()));
+        let enforce_circuit_geo = geo_allowed || circuit_geo.fallback != GeoFallback::Ignore;
+
+        // Exits demoted by health tracking are passed over while others remain
+        let now = Instant::now();
+        let demoted = |s: &&ExitNodeStatus| {
+            self.exit_health.get(&s.info.pubkey).is_some_and(|h| h.is_demoted(now))
+        };
+        let any_healthy = self.exit_nodes.values().filter(|s| s.online).any(|s| !demoted(&s));
+
+        self.exit_nodes
             .values()
             .filter(|s| s.online)
+            .filter(|s| !any_healthy || !demoted(s))
+            .filter(|s| !s.peer_id.is_some_and(|p| self.is_outdated_peer(&p)))
+            .filter(|s| !enforce_circuit_geo || circuit_geo.allows_exit(s.info.country_code.as_deref()))
             .filter(|s| {
                 if !has_geo_preference {
                     return true;
@@ -1794,11 +3454,24 @@ impl CraftNetNode {
                     }
                 }
                 true
-            });
+            })
+            .collect()
+    }
+
+    /// Select the best available exit (online, lowest score, matching geo preference)
+    ///
+    /// Score combines: load (20%), latency (30%), throughput (50%)
+    /// Lower score = better exit.
+    /// When a geo preference is set (region != Auto, or country/city specified),
+    /// only exits matching the preference are considered. Exits must also
+    /// pass `NodeConfig::circuit_geo` (required and excluded countries),
+    /// unless its fallback is [`GeoFallback::Ignore`] and none does.
+    fn select_best_exit(&mut self) {
+        let has_geo_preference = self.has_exit_geo_preference();
 
         // Collect all candidates with the best (lowest) score, then pick one
         // based on local_peer_id hash so different clients spread across exits.
-        let mut all: Vec<_> = candidates.collect();
+        let mut all = self.exit_candidates();
         if let Some(min_score) = all.iter().map(|s| s.score).min() {
             all.retain(|s| s.score == min_score);
         }
@@ -1839,9 +3512,20 @@ impl CraftNetNode {
         }
     }
 
+    /// Best-scored exit selection may pick that a request has not tried yet
+    /// (see [`RetryPolicy::max_exit_failovers`])
+    fn failover_exit(&self, tried: &HashSet<PublicKey>) -> Option<PublicKey> {
+        self.exit_candidates()
+            .into_iter()
+            .filter(|s| !tried.contains(&s.info.pubkey))
+            .filter(|s| s.info.encryption_pubkey.is_some_and(|k| k != [0u8; 32]))
+            .min_by_key(|s| s.score)
+            .map(|s| s.info.pubkey)
+    }
+
     /// Mark exits as offline if no heartbeat received recently
     fn check_exit_timeouts(&mut self) {
-        let now = std::time::Instant::now();
+        let now = self.clock.now();
         let mut any_changed = false;
 
         for (_pubkey, status) in self.exit_nodes.iter_mut() {
@@ -1873,6 +3557,15 @@ impl CraftNetNode {
     /// Shorter interval optimized for mobile churn
     const EXIT_ANNOUNCE_INTERVAL: Duration = Duration::from_secs(120);
 
+    /// Run the exit handler's upstream self-test and keep the result for
+    /// the exit record
+    async fn run_exit_self_test(&mut self) {
+        let handler = self.state.write().exit_handler.take();
+        let Some(mut handler) = handler else { return };
+        self.exit_capabilities = handler.self_test().await;
+        self.state.write().exit_handler = Some(handler);
+    }
+
     /// Announce this node as an exit to the DHT
     fn announce_as_exit(&mut self) {
         let local_peer_id = match self.local_peer_id {
@@ -1882,22 +3575,38 @@ impl CraftNetNode {
                 return;
             }
         };
+        if let Some(capabilities) = self.exit_capabilities.filter(|c| !c.can_serve_http()) {
+            warn!(
+                "Not announcing exit: self-test found no working upstream (ipv4={}, ipv6={}, dns={})",
+                capabilities.ipv4, capabilities.ipv6, capabilities.dns,
+            );
+            return;
+        }
 
         // Build exit info
+        let located = self.self_geoip().unwrap_or_default();
+        let size_limits = self.state.read().exit_handler.as_ref().map(|h| h.size_limits());
         let exit_info = ExitInfo {
             pubkey: self.keypair.public_key_bytes(),
             address: self.config.listen_addr.to_string(),
             region: self.config.exit_region,
-            country_code: self.config.exit_country_code.clone(),
+            country_code: self.config.exit_country_code.clone().or(located.country_code),
             city: self.config.exit_city.clone(),
             reputation: 0, // New node starts with 0 reputation
             latency_ms: 0, // Will be measured by clients
             encryption_pubkey: Some(self.encryption_keypair.public_key_bytes()),
             peer_id: self.local_peer_id.map(|p| p.to_string()),
+            peer_binding: sign_peer_binding(&self.libp2p_keypair, &self.keypair),
+            as_number: self.config.as_number.clone().or(located.as_number),
+            quota: self.state.read().exit_handler.as_ref().and_then(|h| h.quota_terms()),
+            capabilities: self.exit_capabilities,
+            max_request_bytes: size_limits.map(|(request, _)| request as u64),
+            max_response_bytes: size_limits.map(|(_, response)| response as u64),
+            zstd_requests: true,
         };
 
         // Serialize to JSON
-        let record = match serde_json::to_vec(&exit_info) {
+        let record = match serde_json::to_string(&exit_info) {
             Ok(data) => data,
             Err(e) => {
                 warn!("Failed to serialize exit info: {}", e);
@@ -1905,13 +3614,21 @@ impl CraftNetNode {
             }
         };
 
-        // Announce to DHT
+        // Announce to DHT: our registry shard, plus the unsharded key for
+        // nodes that predate sharding
         self.send_swarm_cmd(craftec_network::SharedSwarmCommand::StartProvidingSecondary(
             libp2p::kad::RecordKey::new(&craftnet_network::EXIT_REGISTRY_KEY),
         ));
+        self.send_swarm_cmd(craftec_network::SharedSwarmCommand::StartProvidingSecondary(
+            libp2p::kad::RecordKey::new(&craftnet_network::exit_registry_shard_key(
+                registry_shard(&exit_info.pubkey),
+            )),
+        ));
 
-        let record_value = record;
-        let key = libp2p::kad::RecordKey::new(&craftnet_network::exit_dht_key(&self.local_peer_id.unwrap()));
+        let key_bytes = craftnet_network::exit_dht_key(&local_peer_id);
+        let record_value = SignedDhtRecord::sign_at(&self.keypair, &key_bytes, record, self.clock.unix_now()).to_bytes();
+        self.remember_registry_record(&key_bytes, &record_value);
+        let key = libp2p::kad::RecordKey::new(&key_bytes);
         let record = libp2p::kad::Record {
             key,
             value: record_value,
@@ -1928,7 +3645,7 @@ impl CraftNetNode {
 
     /// Check if exit re-announcement is needed and do it
     fn maybe_reannounce_exit(&mut self) {
-        if !self.capabilities.is_exit() || !self.connected {
+        if !self.capabilities.is_exit() || !self.connected || self.is_draining() {
             return;
         }
 
@@ -1943,16 +3660,22 @@ impl CraftNetNode {
     }
 
     /// Announce this node's signing pubkey → PeerId in DHT
-    /// so relays can route response shards to us by destination lookup
+    /// so relays can route response shards to us by destination lookup.
+    /// The record value is a signed `PeerBinding` so readers can check
+    /// that both keys belong to the same node.
     fn announce_as_peer(&mut self) {
         let Some(local_peer_id) = self.local_peer_id else {
             return;
         };
+        let Some(binding) = sign_peer_binding(&self.libp2p_keypair, &self.keypair) else {
+            warn!("Cannot announce peer record: failed to sign peer binding");
+            return;
+        };
 
         let pubkey = self.keypair.public_key_bytes();
         let record = libp2p::kad::Record {
             key: libp2p::kad::RecordKey::new(&craftnet_network::peer_dht_key(&pubkey)),
-            value: local_peer_id.to_bytes(),
+            value: binding.to_bytes(),
             publisher: Some(local_peer_id),
             expires: Some(std::time::Instant::now() + craftnet_network::PEER_RECORD_TTL),
         };
@@ -2006,11 +3729,28 @@ impl CraftNetNode {
         self.select_best_exit();
     }
 
+    /// Set the location constraints on circuit hops (see `NodeConfig::circuit_geo`)
+    pub fn set_circuit_geo(&mut self, constraints: GeoConstraints) {
+        info!("Circuit geo constraints set: {:?}", constraints);
+        self.config.circuit_geo = constraints;
+        self.select_best_exit();
+    }
+
     /// Check if connected
     pub fn is_connected(&self) -> bool {
         self.connected
     }
 
+    /// Whether the node has joined the network: connected to a bootstrap
+    /// peer, or to any peer when no bootstrap peers are configured
+    pub fn is_bootstrapped(&self) -> bool {
+        if self.bootstrap_peer_ids.is_empty() {
+            !self.connected_peers.is_empty()
+        } else {
+            self.bootstrap_peer_ids.iter().any(|pid| self.connected_peers.contains(pid))
+        }
+    }
+
     /// Check if traffic routing is active
     pub fn is_routing_active(&self) -> bool {
         self.capabilities.is_client() && self.connected
@@ -2023,7 +3763,7 @@ impl CraftNetNode {
 
     /// Get current status
     pub fn status(&self) -> NodeStatus {
-        let state = self.state.read();
+        let exit_active = self.capabilities.is_exit() && self.state.read().exit_handler.is_some();
         NodeStatus {
             capabilities: self.capabilities,
             peer_id: self
@@ -2035,14 +3775,82 @@ impl CraftNetNode {
             credits: self.credits,
             routing_active: self.is_routing_active(),
             relay_active: self.is_relay_active(),
-            exit_active: self.capabilities.is_exit() && state.exit_handler.is_some(),
-            stats: state.stats.clone(),
+            exit_active,
+            stats: self.stats(),
+        }
+    }
+
+    /// Bytes moved per second over the last `window_secs` seconds, oldest
+    /// first, split into client, relay and exit traffic
+    pub fn throughput_series(&self, window_secs: u64) -> Vec<ThroughputSample> {
+        self.state.read().throughput.window(window_secs)
+    }
+
+    /// Kademlia inspection and manual actions (None on a shared swarm, whose
+    /// owner drives the DHT)
+    pub fn dht_handle(&self) -> Option<DhtHandle> {
+        self.dht_handle.clone()
+    }
+
+    /// Connected peers with address, direction, transport, protocols and
+    /// shard bytes exchanged, longest connected first
+    pub fn peer_connections(&self) -> Vec<PeerConnectionInfo> {
+        self.connection_table.read().unwrap().peers()
+    }
+
+    /// Bytes exchanged with each tracked peer per protocol, most bytes first
+    pub fn peer_traffic(&self) -> Vec<PeerTraffic> {
+        self.traffic.read().unwrap().peers()
+    }
+
+    /// Bytes exchanged per protocol over all peers since the node started
+    pub fn traffic_totals(&self) -> Vec<(TrafficProtocol, TrafficCounters)> {
+        let traffic = self.traffic.read().unwrap();
+        TrafficProtocol::ALL.iter().map(|&p| (p, traffic.total(p))).collect()
+    }
+
+    /// Current traffic rows as a [`NetworkEvent::TrafficReport`]
+    pub fn traffic_report(&self) -> NetworkEvent {
+        self.traffic.read().unwrap().report()
+    }
+
+    /// Tracked peers that are freeloaders under `NodeConfig::relay_freeloader`
+    /// (empty when freeloader detection is off)
+    pub fn freeloaders(&self) -> Vec<PeerId> {
+        match self.config.relay_freeloader {
+            Some(policy) => self.traffic.read().unwrap().freeloaders(&policy),
+            None => Vec::new(),
         }
     }
 
+    /// Clock offset of an exit or relay learned from its signed heartbeats,
+    /// in seconds (positive = its clock is ahead of ours)
+    pub fn status_clock_skew(&self, pubkey: &PublicKey) -> Option<i64> {
+        self.status_freshness.skew(pubkey)
+    }
+
     /// Get statistics
     pub fn stats(&self) -> NodeStats {
-        self.state.read().stats.clone()
+        let mut stats = self.state.read().stats.clone();
+        let credits = self.credit_ledger.totals();
+        stats.credits_earned = credits.earned;
+        stats.credits_spent = credits.spent;
+        stats.credit_ledger = credits;
+        stats.proof_queue_depth = self.proof_queue_depth();
+        stats.relay_qos = self.relay_queues.stats();
+        stats.pool_claims = self.pool_claims.snapshot();
+        stats
+    }
+
+    /// Credits spent, earned and reconciled by this node
+    pub fn credit_ledger(&self) -> &CreditLedger {
+        &self.credit_ledger
+    }
+
+    /// Exit request log by destination, throttled pools and operator
+    /// blocks (empty unless this node is an exit)
+    pub fn exit_abuse(&self) -> &AbuseMonitor {
+        &self.exit_abuse
     }
 
     /// Set available credits
@@ -2067,6 +3875,38 @@ impl CraftNetNode {
         self.proof_deadline = deadline;
     }
 
+    /// Run heartbeat, timeout, record TTL, proof deadline and compaction
+    /// timers, and the timestamps of cached and persisted state, on `clock`
+    /// instead of the system clock. With a
+    /// [`SimClock`](craftnet_core::SimClock) a test advances them
+    /// deterministically. Set before `start`.
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.registry_store.lock().unwrap_or_else(|e| e.into_inner()).set_clock(clock.clone());
+        if let Some(aggregator) = self.aggregator.as_mut() {
+            aggregator.set_clock(clock.clone());
+        }
+        self.gossip_dedup.set_clock(clock.clone());
+        self.record_cache.set_clock(clock.clone());
+        self.proof_jobs.set_clock(clock.clone());
+        self.credit_ledger.set_clock(clock.clone());
+        self.state.write().throughput.set_clock(clock.clone());
+        if let Some(jar) = self.cookie_jar.as_mut() {
+            jar.set_clock(clock.clone());
+        }
+        self.clock = clock;
+    }
+
+    /// Check exit request URLs and response bodies with `filter`.
+    ///
+    /// Set it before exit mode starts: a handler busy with a request when
+    /// this is called keeps its old filter.
+    pub fn set_exit_content_filter(&mut self, filter: Arc<dyn ContentFilter>) {
+        if let Some(handler) = self.state.write().exit_handler.as_mut() {
+            handler.set_content_filter(filter.clone());
+        }
+        self.exit_content_filter = Some(filter);
+    }
+
     /// Get total number of stored forward receipts
     pub fn receipt_count(&self) -> usize {
         self.forward_receipts.values().map(|v| v.len()).sum()
@@ -2081,7 +3921,7 @@ impl CraftNetNode {
 
     /// Add an exit node manually (for client mode)
     pub fn add_exit_node(&mut self, exit: ExitInfo) {
-        let status = ExitNodeStatus::new(exit.clone());
+        let status = ExitNodeStatus::new(exit.clone(), self.clock.now());
         self.exit_nodes.insert(exit.pubkey, status);
         if self.selected_exit.is_none() {
             self.selected_exit = Some(exit);
@@ -2093,6 +3933,11 @@ impl CraftNetNode {
         self.selected_exit = Some(exit);
     }
 
+    /// Exit requests currently go through (None = none selected yet)
+    pub fn selected_exit(&self) -> Option<&ExitInfo> {
+        self.selected_exit.as_ref()
+    }
+
     // =========================================================================
     // Client functionality (traffic routing)
     // =========================================================================
@@ -2151,7 +3996,36 @@ impl CraftNetNode {
         self.fetch("POST", url, Some(body), None).await
     }
 
-    /// Make an HTTP request through the tunnel
+    /// Make an HTTP GET request with per-request overrides
+    pub async fn get_with(&mut self, url: &str, options: RequestOptions) -> Result<TunnelResponse> {
+        self.fetch_with("GET", url, None, None, options).await
+    }
+
+    /// [`Self::fetch`] with per-request hop mode, exit and timeout
+    /// overrides; the node's defaults are untouched
+    pub async fn fetch_with(
+        &mut self,
+        method: &str,
+        url: &str,
+        body: Option<Vec<u8>>,
+        headers: Option<Vec<(String, String)>>,
+        options: RequestOptions,
+    ) -> Result<TunnelResponse> {
+        self.request_options = options;
+        let result = self.fetch(method, url, body, headers).await;
+        self.request_options = RequestOptions::default();
+        result
+    }
+
+    /// Hop mode of the request being built
+    fn request_hop_mode(&self) -> HopMode {
+        self.request_options.hop_mode.unwrap_or(self.config.hop_mode)
+    }
+
+    /// Make an HTTP request through the tunnel.
+    ///
+    /// Plain GETs are fetched as parallel Range sub-requests when
+    /// `NodeConfig::range_chunk_size` is set (see [`Self::fetch_ranged`]).
     pub async fn fetch(
         &mut self,
         method: &str,
@@ -2159,75 +4033,898 @@ impl CraftNetNode {
         body: Option<Vec<u8>>,
         headers: Option<Vec<(String, String)>>,
     ) -> Result<TunnelResponse> {
-        // Check mode
-        if !self.capabilities.is_client() {
-            return Err(ClientError::NotConnected);
-        }
+        self.active_identity = None;
+        self.fetch_audited(method, url, body, headers).await
+    }
 
-        if !self.connected {
-            return Err(ClientError::NotConnected);
+    /// [`Self::fetch`] under identity `name` (see [`Self::with_identity`])
+    pub(crate) async fn fetch_as_identity(
+        &mut self,
+        name: &str,
+        method: &str,
+        url: &str,
+        body: Option<Vec<u8>>,
+        headers: Option<Vec<(String, String)>>,
+    ) -> Result<TunnelResponse> {
+        if self.identities.get(name).is_none() {
+            return Err(ClientError::RequestFailed(format!("unknown identity {}", name)));
         }
+        self.active_identity = Some(name.to_string());
+        let result = self.fetch_audited(method, url, body, headers).await;
+        self.active_identity = None;
+        result
+    }
 
-        let exit_info = self
-            .selected_exit
-            .as_ref()
-            .ok_or(ClientError::NoExitNodes)?
-            .clone();
+    async fn fetch_audited(
+        &mut self,
+        method: &str,
+        url: &str,
+        body: Option<Vec<u8>>,
+        headers: Option<Vec<(String, String)>>,
+    ) -> Result<TunnelResponse> {
+        let bytes_up = body.as_ref().map_or(0, |b| b.len() as u64);
+        let headers = self.with_cookies(url, headers);
+        let result = self.fetch_unaudited(method, url, body, headers).await;
+        if let Ok(ref response) = result {
+            self.store_cookies(url, response);
+        }
+        self.record_audit(url, bytes_up, &result);
+        result
+    }
 
-        // Build exit PathHop from selected exit info
-        let exit_peer_id = self.known_peers.get(&exit_info.pubkey).copied();
-        let exit_peer_id_bytes = exit_peer_id
-            .map(|p| p.to_bytes())
-            .unwrap_or_default();
-        let exit_hop = PathHop {
-            peer_id: exit_peer_id_bytes,
-            signing_pubkey: exit_info.pubkey,
-            encryption_pubkey: exit_info.encryption_pubkey.unwrap_or([0u8; 32]),
+    /// Add the jar's cookies for `url` unless the caller set its own
+    fn with_cookies(&self, url: &str, headers: Option<Vec<(String, String)>>) -> Option<Vec<(String, String)>> {
+        let Some(ref jar) = self.cookie_jar else { return headers };
+        if headers.iter().flatten().any(|(k, _)| k.eq_ignore_ascii_case("cookie")) {
+            return headers;
+        }
+        let Some(cookie) = jar.header_for(self.active_identity.as_deref(), url, self.clock.unix_now()) else {
+            return headers;
         };
+        let mut headers = headers.unwrap_or_default();
+        headers.push(("Cookie".to_string(), cookie));
+        Some(headers)
+    }
+
+    /// Keep the cookies a response set, for the identity that requested it
+    fn store_cookies(&mut self, url: &str, response: &TunnelResponse) {
+        let now = self.clock.unix_now();
+        let Some(ref mut jar) = self.cookie_jar else { return };
+        let set_cookies: Vec<&str> = response.headers
+            .iter()
+            .filter(|(k, _)| k.eq_ignore_ascii_case("set-cookie"))
+            .flat_map(|(_, v)| split_set_cookie(v))
+            .collect();
+        if !set_cookies.is_empty() {
+            jar.store(self.active_identity.as_deref(), url, &set_cookies, now);
+        }
+    }
 
-        // Build topology-based paths and LeaseSet
-        let (paths, first_hops, lease_set) = self.build_request_paths(&exit_hop)?;
+    /// Enable (Some) or disable (None) the cookie jar.
+    ///
+    /// Disabling keeps the jar file; see [`Self::clear_session_data`].
+    pub fn set_cookie_jar(&mut self, config: Option<CookieJarConfig>) {
+        self.cookie_jar = config.clone().map(CookieJar::load);
+        if let Some(jar) = self.cookie_jar.as_mut() {
+            jar.set_clock(self.clock.clone());
+        }
+        self.config.cookie_jar = config;
+    }
 
-        // Build request
-        let mut builder = RequestBuilder::new(method, url);
-        if let Some(hdrs) = headers {
-            for (key, value) in hdrs {
-                builder = builder.header(&key, &value);
-            }
+    /// The cookie jar, if enabled
+    pub fn cookie_jar(&self) -> Option<&CookieJar> {
+        self.cookie_jar.as_ref()
+    }
+
+    /// Forget stored cookies of `identity` (None = of every identity),
+    /// on disk too
+    pub fn clear_session_data(&mut self, identity: Option<&str>) {
+        if let Some(ref mut jar) = self.cookie_jar {
+            jar.clear(identity);
         }
-        if let Some(body_data) = body {
-            builder = builder.body(body_data);
+    }
+
+    async fn fetch_unaudited(
+        &mut self,
+        method: &str,
+        url: &str,
+        body: Option<Vec<u8>>,
+        headers: Option<Vec<(String, String)>>,
+    ) -> Result<TunnelResponse> {
+        let has_range = headers.as_ref().map_or(false, |h| {
+            h.iter().any(|(k, _)| k.eq_ignore_ascii_case("range"))
+        });
+        if self.config.range_chunk_size > 0
+            && method.eq_ignore_ascii_case("GET")
+            && body.is_none()
+            && !has_range
+            && !self.request_options.trace
+        {
+            return self.fetch_ranged(url, headers).await;
         }
+        self.fetch_once(method, url, body, headers).await
+    }
 
-        // Send our long-term encryption pubkey so exit can encrypt responses for us.
-        // Response decryption uses exit_enc_pubkey (stored from request path).
-        let (request_id, shards) = builder.build_onion_with_enc_key(
-            &self.keypair,
-            &exit_hop,
-            &paths,
-            &lease_set,
-            self.encryption_keypair.public_key_bytes(), // response encryption key
-            self.keypair.public_key_bytes(), // pool_pubkey — always user pubkey (tracks subscription or free usage)
-        )?;
+    /// Append a finished request to the audit log, if enabled
+    fn record_audit(&self, url: &str, bytes_up: u64, result: &Result<TunnelResponse>) {
+        let Some(ref log) = self.audit_log else { return };
+        let (bytes_down, outcome) = match result {
+            Ok(response) => (
+                response.body.len() as u64,
+                AuditOutcome::Response { status: response.status },
+            ),
+            Err(e) => (0, AuditOutcome::Error { code: e.code().as_str().to_string() }),
+        };
+        let entry = AuditEntry {
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            host: audit_host(url),
+            bytes_up,
+            bytes_down,
+            hops: self.request_hop_mode().hop_count(),
+            exit: self.request_options.exit.or(self.selected_exit.as_ref().map(|e| e.pubkey)).map(hex::encode),
+            outcome,
+        };
+        if let Err(e) = log.record(&entry) {
+            warn!("Failed to write audit log {}: {}", log.path().display(), e);
+        }
+    }
 
-        // Calculate request size for throughput measurement
-        let request_bytes: usize = shards.iter().map(|s| s.payload.len()).sum();
+    /// Enable (Some) or disable (None) the request audit log.
+    ///
+    /// Disabling only stops recording; existing files are kept.
+    pub fn set_audit_log(&mut self, config: Option<AuditLogConfig>) {
+        self.audit_log = config.clone().map(AuditLog::new);
+        self.config.audit_log = config;
+    }
 
-        info!(
-            "Sending request={} url={} shards={} gateway={:?} exit_enc={}",
-            hex::encode(&request_id[..8]),
-            url,
-            shards.len(),
-            first_hops.first().map(|p| p.to_string()),
-            hex::encode(&exit_hop.encryption_pubkey[..8]),
-        );
+    /// The request audit log, if enabled
+    pub fn audit_log(&self) -> Option<&AuditLog> {
+        self.audit_log.as_ref()
+    }
+
+    // =========================================================================
+    // Browsing identities
+    // =========================================================================
+
+    /// Create a browsing identity with its own response key, exit and
+    /// gateway circuits. Requests made through [`Self::with_identity`] can't
+    /// be correlated with other identities by the circuits they share.
+    pub fn new_identity(&mut self, name: &str) -> Result<()> {
+        self.identities.create(name)
+    }
+
+    /// Forget an identity and its cookies; its in-flight responses are dropped
+    pub fn remove_identity(&mut self, name: &str) -> bool {
+        let removed = self.identities.remove(name);
+        if removed {
+            self.clear_session_data(Some(name));
+        }
+        removed
+    }
+
+    /// Names of all identities
+    pub fn identities(&self) -> Vec<String> {
+        self.identities.names()
+    }
+
+    /// Make requests as identity `name`:
+    /// `node.with_identity("work")?.fetch("GET", url, None, None).await`
+    pub fn with_identity(&mut self, name: &str) -> Result<IdentityScope<'_>> {
+        if self.identities.get(name).is_none() {
+            return Err(ClientError::RequestFailed(format!("unknown identity {}", name)));
+        }
+        Ok(IdentityScope { node: self, name: name.to_string() })
+    }
+
+    /// Key responses to the request being built are encrypted to: the
+    /// active identity's, else the node's
+    fn response_keypair(&self) -> &EncryptionKeypair {
+        self.active_identity
+            .as_deref()
+            .and_then(|name| self.identities.get(name))
+            .map_or(&self.encryption_keypair, |identity| identity.encryption_keypair())
+    }
+
+    /// Exit for identity `name`: its pinned exit while online, else a random
+    /// one not used by the default route or other identities (shared only
+    /// when nothing else is online)
+    fn identity_exit(&mut self, name: &str) -> Result<ExitInfo> {
+        use rand::Rng;
+
+        let usable = |s: &ExitNodeStatus| {
+            s.online && s.info.encryption_pubkey.is_some_and(|k| k != [0u8; 32])
+        };
+        let pinned = self.identities.get(name).and_then(|identity| identity.exit());
+        if let Some(status) = pinned.and_then(|pk| self.exit_nodes.get(&pk)).filter(|s| usable(*s)) {
+            return Ok(status.info.clone());
+        }
+
+        let mut taken = self.identities.exits_of_others(name);
+        if let Some(ref exit) = self.selected_exit {
+            taken.insert(exit.pubkey);
+        }
+        let candidates: Vec<&ExitNodeStatus> = self.exit_nodes.values().filter(|s| usable(*s)).collect();
+        let fresh: Vec<&ExitNodeStatus> = candidates
+            .iter()
+            .copied()
+            .filter(|s| !taken.contains(&s.info.pubkey))
+            .collect();
+        let pool = if fresh.is_empty() { &candidates } else { &fresh };
+        if pool.is_empty() {
+            return Err(ClientError::NoExitNodes);
+        }
+        let info = pool[rand::thread_rng().gen_range(0..pool.len())].info.clone();
+        debug!("Identity {} pinned exit {}", name, hex::encode(&info.pubkey[..8]));
+        self.identities.set_exit(name, Some(info.pubkey));
+        Ok(info)
+    }
+
+    /// Send one HTTP request through the tunnel, retrying per
+    /// `NodeConfig::retry_policy`
+    async fn fetch_once(
+        &mut self,
+        method: &str,
+        url: &str,
+        body: Option<Vec<u8>>,
+        headers: Option<Vec<(String, String)>>,
+    ) -> Result<TunnelResponse> {
+        let policy = self.config.retry_policy;
+        self.fetch_with_policy(method, url, body, headers, policy).await
+    }
+
+    /// Make an HTTP request through the tunnel with an explicit retry policy.
+    ///
+    /// A request that times out or comes back undecodable is sent again as a
+    /// new request over a different circuit (gateways that failed are tried
+    /// last), at most `policy.max_retries` times. Non-idempotent methods are
+    /// only retried with `policy.retry_non_idempotent`. No Range splitting.
+    ///
+    /// When the failures point at the exit (see
+    /// [`RetryPolicy::should_fail_over`]), the retry goes to the best exit
+    /// the request hasn't tried instead; the payload is encoded once and only
+    /// re-encrypted for the new exit. Requests pinned to an exit (by
+    /// `RequestOptions::exit` or an identity) never fail over. The response
+    /// reports the failovers in `TunnelResponse::exit_failovers`.
+    pub async fn fetch_with_policy(
+        &mut self,
+        method: &str,
+        url: &str,
+        body: Option<Vec<u8>>,
+        headers: Option<Vec<(String, String)>>,
+        policy: RetryPolicy,
+    ) -> Result<TunnelResponse> {
+        let profile = RequestProfile::for_request(body.as_deref(), headers.as_deref());
+        let mut builder = http_request_builder(method, url, body, headers);
+        if self.config.payload_compression {
+            builder = builder.compressed(self.payload_compression.clone());
+        }
+        let builder = builder.encoded();
+
+        let pinned = self.request_options.exit;
+        let can_fail_over = pinned.is_none() && self.active_identity.is_none();
+        let result = self.fetch_failing_over(method, &builder, profile, policy, can_fail_over).await;
+        self.request_options.exit = pinned;
+        result
+    }
+
+    /// Retry loop of [`Self::fetch_with_policy`]. Failing over pins the
+    /// request to the new exit through `request_options.exit`, which the
+    /// caller restores.
+    async fn fetch_failing_over(
+        &mut self,
+        method: &str,
+        builder: &RequestBuilder,
+        profile: RequestProfile,
+        policy: RetryPolicy,
+        can_fail_over: bool,
+    ) -> Result<TunnelResponse> {
+        let mut failed_hops: HashSet<PeerId> = HashSet::new();
+        let mut tried_exits: HashSet<PublicKey> = HashSet::new();
+        let mut attempt = 0;
+        let mut failovers = 0;
+        let mut exit_failures = 0;
+        let mut last_exit = None;
+        loop {
+            // The exit changes when we fail over, or when health tracking
+            // moved the node off the selected exit meanwhile
+            let exit = self.request_options.exit.or(self.selected_exit.as_ref().map(|e| e.pubkey));
+            if last_exit.is_some() && exit != last_exit {
+                failovers += 1;
+                exit_failures = 0;
+                failed_hops.clear();
+            }
+            last_exit = exit;
+            tried_exits.extend(exit);
+            let (request_id, first_hop, result) = self
+                .send_built_request(builder.clone(), profile, &failed_hops)
+                .await;
+            let e = match result {
+                Ok(mut response) => {
+                    response.exit_failovers = failovers;
+                    return Ok(response);
+                }
+                Err(e) => e,
+            };
+            let req_id_hex = request_id.map(|id| hex::encode(&id[..8])).unwrap_or_default();
+            exit_failures += 1;
+
+            let next_exit = if can_fail_over && policy.should_fail_over(method, attempt, exit_failures, failovers, &e) {
+                self.failover_exit(&tried_exits)
+            } else {
+                None
+            };
+            if let Some(next) = next_exit {
+                warn!(
+                    "[TRACE] CLIENT EXIT_FAILOVER request={} method={} attempt={}/{} err={} from={} to={}",
+                    req_id_hex, method, attempt + 1, policy.max_retries + 1, e,
+                    exit.map(|x| hex::encode(&x[..8])).unwrap_or_default(),
+                    hex::encode(&next[..8]),
+                );
+                self.request_options.exit = Some(next);
+                attempt += 1;
+                continue;
+            }
+
+            if !policy.should_retry(method, attempt, &e) {
+                let reason = if attempt >= policy.max_retries {
+                    "retry budget exhausted"
+                } else if !crate::retry::is_retryable(&e) {
+                    "error not retryable"
+                } else {
+                    "method not idempotent"
+                };
+                warn!(
+                    "[TRACE] CLIENT GIVE_UP request={} method={} attempt={}/{} err={} reason={}",
+                    req_id_hex, method, attempt + 1, policy.max_retries + 1, e, reason,
+                );
+                return Err(e);
+            }
+
+            if let Some(hop) = first_hop {
+                failed_hops.insert(hop);
+            }
+            attempt += 1;
+            warn!(
+                "[TRACE] CLIENT RETRY request={} method={} attempt={}/{} err={} avoiding={} gateway(s)",
+                req_id_hex, method, attempt + 1, policy.max_retries + 1, e, failed_hops.len(),
+            );
+        }
+    }
+
+    /// Make several HTTP requests through the tunnel.
+    ///
+    /// With `NodeConfig::coalesce` set, runs of small requests share one
+    /// assembly each (see [`crate::coalesce`]); large requests, and all of
+    /// them without it, go through [`Self::fetch`] one by one. Returns one
+    /// result per request, in request order.
+    pub async fn fetch_many(&mut self, requests: Vec<QueuedRequest>) -> Vec<Result<TunnelResponse>> {
+        self.active_identity = None;
+        let batches = match self.config.coalesce {
+            Some(policy) => {
+                let sizes: Vec<usize> = requests.iter().map(QueuedRequest::size).collect();
+                plan_batches(&sizes, &policy)
+            }
+            None => (0..requests.len()).map(|i| vec![i]).collect(),
+        };
+
+        let mut results = Vec::with_capacity(requests.len());
+        for batch in batches {
+            if let [i] = batch[..] {
+                let r = requests[i].clone();
+                results.push(self.fetch_audited(&r.method, &r.url, r.body, r.headers).await);
+                continue;
+            }
+            let batch: Vec<&QueuedRequest> = batch.iter().map(|&i| &requests[i]).collect();
+            results.extend(self.fetch_coalesced(&batch).await);
+        }
+        results
+    }
+
+    /// Send `requests` as one coalesced request and split the response.
+    ///
+    /// Retried per `NodeConfig::retry_policy` like a single request; the
+    /// batch counts as idempotent only if every request in it is. If the
+    /// batch as a whole fails, every request fails with its error.
+    async fn fetch_coalesced(&mut self, requests: &[&QueuedRequest]) -> Vec<Result<TunnelResponse>> {
+        let policy = self.config.retry_policy;
+        let method = requests
+            .iter()
+            .map(|r| r.method.as_str())
+            .find(|m| !crate::retry::is_idempotent(m))
+            .unwrap_or("GET");
+        let mut failed_hops: HashSet<PeerId> = HashSet::new();
+        let mut attempt = 0;
+        let outcome = loop {
+            let started = RequestBuilder::coalesced(requests.iter().map(|r| r.builder()).collect())
+                .and_then(|builder| self.start_built_request(builder, RequestProfile::interactive(), &failed_hops));
+            let (request_id, first_hop, result) = match started {
+                Ok(request) => self.drive_request(request).await,
+                Err(e) => (None, None, Err(e)),
+            };
+            let e = match result.and_then(|response| split_batch_response(&response, requests.len())) {
+                Ok(results) => break Ok(results),
+                Err(e) => e,
+            };
+            if !policy.should_retry(method, attempt, &e) {
+                break Err(e);
+            }
+            if let Some(hop) = first_hop {
+                failed_hops.insert(hop);
+            }
+            attempt += 1;
+            warn!(
+                "[TRACE] CLIENT RETRY coalesced request={} requests={} attempt={}/{} err={}",
+                request_id.map(|id| hex::encode(&id[..8])).unwrap_or_default(),
+                requests.len(), attempt + 1, policy.max_retries + 1, e,
+            );
+        };
+
+        let results: Vec<Result<TunnelResponse>> = match outcome {
+            Ok(results) => results,
+            Err(e) => {
+                warn!("Coalesced request of {} requests failed: {}", requests.len(), e);
+                requests
+                    .iter()
+                    .map(|_| Err(ClientError::RequestFailed(format!("coalesced request failed: {}", e))))
+                    .collect()
+            }
+        };
+        for (request, result) in requests.iter().zip(&results) {
+            let bytes_up = request.body.as_ref().map_or(0, |b| b.len() as u64);
+            self.record_audit(&request.url, bytes_up, result);
+        }
+        results
+    }
+
+    /// Fetch the day's quota token batch from the selected exit, if it
+    /// meters the free tier and the batch is still due. Returns the tokens
+    /// held for the exit.
+    ///
+    /// Not called by the request path; run it periodically (the daemon
+    /// does) so tokens are at hand when requests are built.
+    pub async fn refresh_quota_tokens(&mut self) -> Result<usize> {
+        let Some(terms) = self.selected_exit.as_ref().and_then(|e| e.quota.clone()) else {
+            return Ok(0);
+        };
+        if !self.config.quota_tokens {
+            return Ok(0);
+        }
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let epoch = quota_epoch(now);
+        self.quota_wallet.prune(epoch);
+
+        if self.quota_wallet.needs_batch(&terms.issuer_key, epoch) {
+            let (pending, request) = PendingQuotaBatch::new(epoch, terms.tokens_per_epoch as usize);
+            let body = request.to_bytes().map_err(|e| ClientError::RequestFailed(e.to_string()))?;
+            let (_, _, result) = self
+                .send_request("POST", QUOTA_ISSUE_URL, Some(body), None, &HashSet::new())
+                .await;
+            let response = result?;
+            if response.status != 200 {
+                return Err(ClientError::RequestFailed(format!(
+                    "quota batch refused with status {}",
+                    response.status,
+                )));
+            }
+            let issued = QuotaIssueResponse::from_bytes(&response.body).map_err(|_| ClientError::InvalidResponse)?;
+            let tokens = pending
+                .finalize(&terms.issuer_key, &issued)
+                .map_err(|e| ClientError::CryptoError(e.to_string()))?;
+            info!("Received {} quota tokens for epoch {}", tokens.len(), epoch);
+            self.quota_wallet.add(terms.issuer_key, epoch, tokens);
+        }
+        Ok(self.quota_wallet.balance(&terms.issuer_key))
+    }
+
+    /// Send one request and wait for its response.
+    ///
+    /// Returns the request id and first hop (None if the request could not
+    /// be built) alongside the result, for retry decisions.
+    async fn send_request(
+        &mut self,
+        method: &str,
+        url: &str,
+        body: Option<Vec<u8>>,
+        headers: Option<Vec<(String, String)>>,
+        avoid_hops: &HashSet<PeerId>,
+    ) -> (Option<Id>, Option<PeerId>, Result<TunnelResponse>) {
+        match self.start_request(method, url, body, headers, avoid_hops) {
+            Ok(request) => self.drive_request(request).await,
+            Err(e) => (None, None, Err(e)),
+        }
+    }
+
+    /// [`Self::send_request`] for an already built request
+    async fn send_built_request(
+        &mut self,
+        builder: RequestBuilder,
+        profile: RequestProfile,
+        avoid_hops: &HashSet<PeerId>,
+    ) -> (Option<Id>, Option<PeerId>, Result<TunnelResponse>) {
+        match self.start_built_request(builder, profile, avoid_hops) {
+            Ok(request) => self.drive_request(request).await,
+            Err(e) => (None, None, Err(e)),
+        }
+    }
+
+    /// Drive a started request until its response arrives or it fails
+    async fn drive_request(&mut self, mut request: InFlightRequest) -> (Option<Id>, Option<PeerId>, Result<TunnelResponse>) {
+        let has_stream_to_gw = request.first_hop.map_or(false, |gw| {
+            self.stream_manager.as_ref().map_or(false, |sm| sm.has_stream(&gw))
+        });
+        warn!(
+            "[TRACE] CLIENT SEND_START request={} shards={} gateway={:?} has_stream={} timeout={:?} ({})",
+            hex::encode(&request.request_id[..8]),
+            request.send_count,
+            request.first_hop.map(|p| { let s = p.to_string(); s[s.len().saturating_sub(6)..].to_string() }),
+            has_stream_to_gw,
+            request.timeout,
+            if request.timeout < self.config.request_timeout { "adaptive" } else { "max" },
+        );
+
+        // Combined send + response loop.
+        // Each iteration: poll the swarm, collect opened streams, try to send
+        // shards, and check for response. Stream opens happen in background tasks
+        // (spawned by StreamManager::ensure_opening) that complete as the swarm
+        // is polled. poll_open_streams() collects their results.
+        let result = loop {
+            if let Some(result) = self.pump_request(&mut request) {
+                break result;
+            }
+
+            tokio::select! {
+                response = request.response_rx.recv() => {
+                    break match response {
+                        Some(r) => r,
+                        None => Err(ClientError::Timeout),
+                    };
+                }
+                _ = self.poll_once() => {}
+            }
+        };
+        if result.is_err() {
+            self.pending.remove(&request.request_id);
+        }
+        // A size refusal is an answer: the exit is healthy
+        let answered = matches!(
+            result,
+            Ok(_) | Err(ClientError::RequestTooLarge { .. } | ClientError::ResponseTooLarge { .. })
+        );
+        self.record_exit_outcome(&request.exit_pubkey, request.send_start.elapsed(), answered);
+        (Some(request.request_id), request.first_hop, result)
+    }
+
+    /// Record a request outcome in the exit's health and fail over if the
+    /// selected exit went bad
+    fn record_exit_outcome(&mut self, exit_pubkey: &PublicKey, elapsed: Duration, ok: bool) {
+        let policy = &self.config.exit_health;
+        let health = self.exit_health.entry(*exit_pubkey).or_default();
+        if ok {
+            health.record_success(elapsed, policy);
+        } else {
+            health.record_failure(policy);
+        }
+        self.check_selected_exit_health();
+    }
+
+    /// Probe the selected exit if no request went through it for a while
+    /// (`ExitHealthPolicy::probe_interval`), failing over after repeated
+    /// unanswered probes. Run periodically while the tunnel is up.
+    pub async fn probe_selected_exit(&mut self) {
+        if !self.connected || !self.capabilities.is_client() {
+            return;
+        }
+        let Some(exit_pubkey) = self.selected_exit.as_ref().map(|e| e.pubkey) else { return };
+        let due = self.exit_health
+            .get(&exit_pubkey)
+            .is_none_or(|h| h.probe_due(&self.config.exit_health, Instant::now()));
+        if !due {
+            return;
+        }
+        let (_, _, result) = self
+            .send_request("HEAD", EXIT_PROBE_URL, None, None, &HashSet::new())
+            .await;
+        if let Err(e) = result {
+            debug!("Probe of exit {} failed: {}", hex::encode(&exit_pubkey[..8]), e);
+            self.exit_health.entry(exit_pubkey).or_default().record_probe_failure();
+            self.check_selected_exit_health();
+        }
+    }
+
+    /// Fail over from the selected exit if its health crossed a threshold:
+    /// demote it, select the next-best exit and queue an event with the reason
+    fn check_selected_exit_health(&mut self) {
+        let Some(from) = self.selected_exit.as_ref().map(|e| e.pubkey) else { return };
+        let policy = &self.config.exit_health;
+        let Some(health) = self.exit_health.get_mut(&from) else { return };
+        let Some(reason) = health.verdict(policy) else { return };
+        health.demote(policy, Instant::now());
+
+        self.select_best_exit();
+        let to = self.selected_exit.as_ref().map(|e| e.pubkey).filter(|to| *to != from);
+        warn!(
+            "Exit {} unhealthy ({:?}), failing over to {}",
+            hex::encode(&from[..8]),
+            reason,
+            to.map(|to| hex::encode(&to[..8])).unwrap_or_else(|| "none".to_string()),
+        );
+        self.exit_failover_events.push(ExitFailoverEvent {
+            from_exit: hex::encode(from),
+            to_exit: to.map(hex::encode),
+            reason,
+        });
+    }
+
+    /// Drain exit failovers since the last call (for IPC events)
+    pub fn take_exit_failover_events(&mut self) -> Vec<ExitFailoverEvent> {
+        std::mem::take(&mut self.exit_failover_events)
+    }
+
+    /// GET a large resource as HTTP Range sub-requests.
+    ///
+    /// The first `range_chunk_size` bytes are requested alone as a probe: a
+    /// server that ignores Range answers 200 with the full body, which is
+    /// returned unchanged. Otherwise the rest of the resource is requested
+    /// in `range_chunk_size` pieces (no larger than the exit's advertised
+    /// response limit), up to `range_parallelism` in flight, each over
+    /// freshly selected paths. Failed pieces are retried and short ones
+    /// resumed from the first missing byte (see [`RangedDownload`]).
+    ///
+    /// Pieces carry `If-Range` with the probe's validator so they all come
+    /// from one version of the resource; a resource without a validator is
+    /// fetched whole instead, and one over `range_max_bytes` fails.
+    async fn fetch_ranged(
+        &mut self,
+        url: &str,
+        headers: Option<Vec<(String, String)>>,
+    ) -> Result<TunnelResponse> {
+        // A piece must fit the exit's response limit
+        let max_response = self.selected_exit.as_ref().and_then(|e| e.max_response_bytes);
+        let chunk_size = (self.config.range_chunk_size as u64).min(max_response.unwrap_or(u64::MAX)).max(1);
+        let base_headers = headers.unwrap_or_default();
+        let with_range = |start: u64, end: u64, validator: Option<&str>| {
+            let mut h = base_headers.clone();
+            h.push(("Range".to_string(), range_header(start, end)));
+            if let Some(validator) = validator {
+                h.push(("If-Range".to_string(), validator.to_string()));
+            }
+            Some(h)
+        };
+
+        let mut head = self.fetch_once("GET", url, None, with_range(0, chunk_size - 1, None)).await?;
+        if head.status == 416 {
+            // Empty resource: a Range can't be satisfied, ask for it plainly
+            return self.fetch_once("GET", url, None, Some(base_headers.clone())).await;
+        }
+        if head.status != 206 {
+            return Ok(head);
+        }
+        let Some(total) = ContentRange::from_response(&head)
+            .filter(|r| r.start == 0)
+            .and_then(|r| r.total)
+        else {
+            return Ok(head);
+        };
+        if total > self.config.range_max_bytes {
+            return Err(ClientError::ResponseTooLarge { limit: self.config.range_max_bytes });
+        }
+        if total <= head.body.len() as u64 {
+            head.status = 200;
+            head.headers.retain(|k, _| !k.eq_ignore_ascii_case("content-range"));
+            return Ok(head);
+        }
+        let Some(validator) = range_validator(&head) else {
+            // Pieces without a validator could mix versions of the resource
+            debug!("Ranged GET {}: no validator, fetching whole", url);
+            return self.fetch_once("GET", url, None, Some(base_headers.clone())).await;
+        };
+
+        let mut download = RangedDownload::new(total, chunk_size as usize, validator, std::mem::take(&mut head.body));
+        let parallelism = self.config.range_parallelism.max(1);
+        let mut in_flight: Vec<(u64, u64, InFlightRequest)> = Vec::new();
+        debug!("Ranged GET {} total={} chunk={} parallelism={}", url, total, chunk_size, parallelism);
+
+        while !download.is_complete() {
+            while in_flight.len() < parallelism {
+                let Some((start, end)) = download.next_range() else {
+                    break;
+                };
+                let headers = with_range(start, end, Some(download.validator()));
+                match self.start_request("GET", url, None, headers, &HashSet::new()) {
+                    Ok(request) => in_flight.push((start, end, request)),
+                    Err(e) => {
+                        debug!("Range {}-{} not sent: {}", start, end, e);
+                        if let Err(e) = download.fail(start, end) {
+                            self.abandon_requests(&in_flight);
+                            return Err(e);
+                        }
+                    }
+                }
+            }
+            if in_flight.is_empty() {
+                return Err(ClientError::InvalidResponse);
+            }
+
+            self.poll_once().await;
+
+            let mut i = 0;
+            while i < in_flight.len() {
+                let result = match self.pump_request(&mut in_flight[i].2) {
+                    Some(result) => Some(result),
+                    None => in_flight[i].2.response_rx.try_recv().ok(),
+                };
+                let Some(result) = result else {
+                    i += 1;
+                    continue;
+                };
+                let (start, end, _) = in_flight.swap_remove(i);
+                let completed = result.and_then(|response| download.complete(start, end, response));
+                if let Err(e) = completed {
+                    debug!("Range {}-{} failed, retrying: {}", start, end, e);
+                    if let Err(e) = download.fail(start, end) {
+                        self.abandon_requests(&in_flight);
+                        return Err(e);
+                    }
+                }
+            }
+        }
+
+        // Present the reassembled body as a plain 200 response
+        head.status = 200;
+        head.headers.retain(|k, _| !k.eq_ignore_ascii_case("content-range") && !k.eq_ignore_ascii_case("content-length"));
+        head.headers.insert("content-length".to_string(), total.to_string());
+        head.body = download.into_body();
+        Ok(head)
+    }
+
+    /// Forget in-flight Range sub-requests of a failed download
+    fn abandon_requests(&mut self, in_flight: &[(u64, u64, InFlightRequest)]) {
+        for (_, _, request) in in_flight {
+            self.pending.remove(&request.request_id);
+        }
+    }
+
+    /// Build a request, register it as pending and queue its shards for sending.
+    ///
+    /// Gateways in `avoid_hops` are only used if no other is available.
+    fn start_request(
+        &mut self,
+        method: &str,
+        url: &str,
+        body: Option<Vec<u8>>,
+        headers: Option<Vec<(String, String)>>,
+        avoid_hops: &HashSet<PeerId>,
+    ) -> Result<InFlightRequest> {
+        let profile = RequestProfile::for_request(body.as_deref(), headers.as_deref());
+        let builder = http_request_builder(method, url, body, headers);
+        self.start_built_request(builder, profile, avoid_hops)
+    }
+
+    /// [`Self::start_request`] for an already built request
+    fn start_built_request(
+        &mut self,
+        mut builder: RequestBuilder,
+        profile: RequestProfile,
+        avoid_hops: &HashSet<PeerId>,
+    ) -> Result<InFlightRequest> {
+        // Check mode
+        if !self.capabilities.is_client() {
+            return Err(ClientError::NotConnected);
+        }
+
+        if !self.connected {
+            return Err(ClientError::NotConnected);
+        }
+
+        let identity = self.active_identity.clone();
+        let exit_info = match (&identity, self.request_options.exit) {
+            (Some(name), _) => self.identity_exit(name)?,
+            (None, Some(pubkey)) => self
+                .exit_nodes
+                .get(&pubkey)
+                .filter(|s| s.online && s.info.encryption_pubkey.is_some_and(|k| k != [0u8; 32]))
+                .map(|s| s.info.clone())
+                .ok_or_else(|| ClientError::ExitUnreachable(format!("exit {} is not online", hex::encode(&pubkey[..8]))))?,
+            (None, None) => self
+                .selected_exit
+                .as_ref()
+                .ok_or(ClientError::NoExitNodes)?
+                .clone(),
+        };
+
+        // Build exit PathHop from selected exit info
+        let exit_peer_id = self.known_peers.get(&exit_info.pubkey).copied();
+        let exit_peer_id_bytes = exit_peer_id
+            .map(|p| p.to_bytes())
+            .unwrap_or_default();
+        let exit_hop = PathHop {
+            peer_id: exit_peer_id_bytes,
+            signing_pubkey: exit_info.pubkey,
+            encryption_pubkey: exit_info.encryption_pubkey.unwrap_or([0u8; 32]),
+        };
+
+        // Build topology-based paths and LeaseSet. An identity tries the
+        // gateways of other identities last so their circuits stay apart.
+        let (paths, first_hops, lease_set) = match identity {
+            Some(ref name) => {
+                let mut avoid = avoid_hops.clone();
+                avoid.extend(self.identities.gateways_of_others(name));
+                let built = self.build_request_paths_avoiding(&exit_hop, &avoid, &profile)?;
+                if let Some(gateway) = built.1.first() {
+                    self.identities.add_gateway(name, *gateway);
+                }
+                built
+            }
+            None => self.build_request_paths_avoiding(&exit_hop, avoid_hops, &profile)?,
+        };
+        let first_hop = first_hops.first().copied().or(exit_peer_id);
+        if first_hop.is_none() {
+            // Direct mode needs the exit's PeerId to send to
+            return Err(ClientError::ExitUnreachable("exit peer id unknown".to_string()));
+        }
+
+        self.check_request_size(&exit_info, builder.payload_len())?;
+        if self.config.payload_compression {
+            builder = builder.compressed(self.payload_compression.clone());
+        }
+        builder = builder.exit_accepts_zstd(exit_info.zstd_requests);
+        // Pay a metering exit for the request and up to one token's worth of response
+        let url = builder.url().to_string();
+        if let Some(terms) = exit_info.quota.as_ref().filter(|_| self.config.quota_tokens && url != QUOTA_ISSUE_URL) {
+            let count = terms.tokens_for(builder.payload_len() as u64) + 1;
+            builder = builder.quota_tokens(self.quota_wallet.take(&terms.issuer_key, count));
+        }
+
+        // A traced request gets a one-off key every hop seals its timing for
+        let trace_keypair = self.request_options.trace.then(EncryptionKeypair::generate);
+        if let Some(ref keys) = trace_keypair {
+            builder = builder.traced(keys.public_key_bytes());
+        }
+
+        // Send our long-term (or the identity's) encryption pubkey so exit can
+        // encrypt responses for us.
+        // Response decryption uses exit_enc_pubkey (stored from request path).
+        let response_keypair = self.response_keypair();
+        let (response_pubkey, response_secret) =
+            (response_keypair.public_key_bytes(), response_keypair.secret_key_bytes());
+        let pool_pubkey = self.keypair.public_key_bytes(); // always user pubkey (tracks subscription or free usage)
+        let (request_id, shards, ack_chains) = if self.config.chain_acks {
+            builder.build_onion_acked(&self.keypair, &exit_hop, &paths, &lease_set, response_pubkey, pool_pubkey)?
+        } else {
+            let (request_id, shards) =
+                builder.build_onion_with_enc_key(&self.keypair, &exit_hop, &paths, &lease_set, response_pubkey, pool_pubkey)?;
+            (request_id, shards, Vec::new())
+        };
+        let ack_chains: HashMap<[u8; 32], ShardAckChain> = shards
+            .iter()
+            .zip(ack_chains)
+            .filter_map(|(shard, chain)| Some((shard.ephemeral_pubkey, chain?)))
+            .collect();
+
+        // Calculate request size for throughput measurement
+        let request_bytes: usize = shards.iter().map(|s| s.payload.len()).sum();
+
+        info!(
+            "Sending request={} url={} shards={} gateway={:?} exit_enc={}",
+            hex::encode(&request_id[..8]),
+            url,
+            shards.len(),
+            first_hops.first().map(|p| p.to_string()),
+            hex::encode(&exit_hop.encryption_pubkey[..8]),
+        );
 
         info!(
             "[SHARD-FLOW] CLIENT created {} shards for request={} ({} bytes, {} hops, gateway={:?})",
             shards.len(),
             hex::encode(&request_id[..8]),
             request_bytes,
-            self.config.hop_mode.min_relays(),
+            self.request_hop_mode().min_relays(),
             first_hops.first().map(|p| {
                 let s = p.to_string();
                 s[s.len().saturating_sub(6)..].to_string()
@@ -2235,7 +4932,7 @@ impl CraftNetNode {
         );
 
         // Create response channel
-        let (response_tx, mut response_rx) = mpsc::channel(1);
+        let (response_tx, response_rx) = mpsc::channel(1);
 
         // Store pending request with exit's encryption pubkey for response decryption
         self.pending.insert(
@@ -2246,16 +4943,30 @@ impl CraftNetNode {
                 response_tx,
                 exit_pubkey: exit_info.pubkey,
                 exit_enc_pubkey: exit_hop.encryption_pubkey,
+                response_secret,
                 request_bytes,
+                first_hop,
                 sent_at: std::time::Instant::now(),
+                flags: 0,
+                trace_secret: trace_keypair.map(|k| k.secret_key_bytes()),
+                trace: Vec::new(),
             },
         );
 
-        // Update stats
         {
             let mut state = self.state.write();
-            state.stats.credits_spent += 1;
+            state.stats.bytes_sent += request_bytes as u64;
+            state.throughput.record(TrafficClass::Client, request_bytes as u64, 0);
         }
+
+        // Book the request: relays plus the exit
+        self.credit_ledger.record_spent(
+            request_id,
+            self.keypair.public_key_bytes(),
+            self.own_subscription_tier,
+            request_bytes as u64,
+            self.request_hop_mode().min_relays() + 1,
+        );
         self.credits = self.credits.saturating_sub(1);
 
         // Prepare the send queue: list of (shard, target_peer) to send.
@@ -2278,119 +4989,269 @@ impl CraftNetNode {
             }
         }
 
-        // Combined send + response loop.
-        // Each iteration: poll the swarm, collect opened streams, try to send
-        // shards, and check for response. Stream opens happen in background tasks
-        // (spawned by StreamManager::ensure_opening) that complete as the swarm
-        // is polled. poll_open_streams() collects their results.
+        Ok(InFlightRequest {
+            request_id,
+            response_rx,
+            send_count: send_queue.len(),
+            first_hop: send_queue.front().map(|(_, peer)| *peer),
+            send_queue,
+            sent: 0,
+            send_start: Instant::now(),
+            exit_pubkey: exit_info.pubkey,
+            timeout: self.request_options.timeout.unwrap_or_else(|| self.request_timeout_for(first_hop, request_bytes)),
+            last_progress: Instant::now(),
+            last_shard_count: 0,
+            ack_chains,
+        })
+    }
+
+    /// Push queued shards of a request and enforce its idle timeout.
+    ///
+    /// Returns `Some` when the request is over without a response (timeout).
+    fn pump_request(&mut self, request: &mut InFlightRequest) -> Option<Result<TunnelResponse>> {
+        let request_id = request.request_id;
         let req_id_hex = hex::encode(&request_id[..8]);
-        let send_count = send_queue.len();
-        let mut sent = 0usize;
-        let send_start = std::time::Instant::now();
-        let has_stream_to_gw = first_hops.first().map_or(false, |gw| {
-            self.stream_manager.as_ref().map_or(false, |sm| sm.has_stream(gw))
-        });
-        warn!(
-            "[TRACE] CLIENT SEND_START request={} shards={} gateway={:?} has_stream={} timeout={:?}",
-            req_id_hex,
-            send_count,
-            first_hops.first().map(|p| { let s = p.to_string(); s[s.len().saturating_sub(6)..].to_string() }),
-            has_stream_to_gw,
-            self.config.request_timeout,
-        );
 
         // Progress-based timeout: resets every time a new response shard arrives.
         // This allows large transfers (1GB+) to complete as long as the pipeline
         // keeps making progress. Only triggers when NO shards arrive for the full
         // timeout window (idle stall = real failure).
-        let mut last_progress = Instant::now();
-        let mut last_shard_count = 0usize;
-        let response = loop {
-            // Check for idle timeout (no new shards received within window)
-            let current_shard_count = self.pending.get(&request_id)
-                .map(|p| p.shards.len())
-                .unwrap_or(0);
-            if current_shard_count > last_shard_count {
-                last_progress = Instant::now();
-                last_shard_count = current_shard_count;
-            }
-            if last_progress.elapsed() > self.config.request_timeout {
-                // Idle timeout — no progress
-                let elapsed_ms = send_start.elapsed().as_millis();
-                if let Some(pending) = self.pending.get(&request_id) {
-                    let mut chunk_coverage: HashMap<u16, usize> = HashMap::new();
-                    for &(chunk_idx, _) in pending.shards.keys() {
-                        *chunk_coverage.entry(chunk_idx).or_default() += 1;
-                    }
-                    let coverage_str: String = (0..pending.total_chunks.max(1))
-                        .map(|c| format!("c{}={}", c, chunk_coverage.get(&c).unwrap_or(&0)))
-                        .collect::<Vec<_>>()
-                        .join(" ");
-                    warn!(
-                        "[TRACE] CLIENT TIMEOUT request={} elapsed={}ms idle={}ms sent={}/{} collected={}/{} chunks={} coverage=[{}]",
-                        req_id_hex, elapsed_ms, self.config.request_timeout.as_millis(),
-                        sent, send_count,
-                        pending.shards.len(),
-                        if pending.total_chunks > 0 { pending.total_chunks as usize * DATA_SHARDS } else { 0 },
-                        pending.total_chunks,
-                        coverage_str,
-                    );
-                } else {
-                    warn!("[TRACE] CLIENT TIMEOUT request={} elapsed={}ms (no pending entry)", req_id_hex, elapsed_ms);
+        let current_shard_count = self.pending.get(&request_id)
+            .map(|p| p.shards.len())
+            .unwrap_or(0);
+        if current_shard_count > request.last_shard_count {
+            request.last_progress = Instant::now();
+            request.last_shard_count = current_shard_count;
+        }
+        if request.last_progress.elapsed() > request.timeout {
+            // Idle timeout — no progress
+            let elapsed_ms = request.send_start.elapsed().as_millis();
+            if let Some(pending) = self.pending.get(&request_id) {
+                let mut chunk_coverage: HashMap<u16, usize> = HashMap::new();
+                for &(chunk_idx, _) in pending.shards.keys() {
+                    *chunk_coverage.entry(chunk_idx).or_default() += 1;
                 }
-                self.pending.remove(&request_id);
-                return Err(ClientError::Timeout);
+                let coverage_str: String = (0..pending.total_chunks.max(1))
+                    .map(|c| format!("c{}={}", c, chunk_coverage.get(&c).unwrap_or(&0)))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                warn!(
+                    "[TRACE] CLIENT TIMEOUT request={} elapsed={}ms idle={}ms sent={}/{} collected={}/{} chunks={} coverage=[{}]",
+                    req_id_hex, elapsed_ms, request.timeout.as_millis(),
+                    request.sent, request.send_count,
+                    pending.shards.len(),
+                    if pending.total_chunks > 0 { pending.total_chunks as usize * DATA_SHARDS } else { 0 },
+                    pending.total_chunks,
+                    coverage_str,
+                );
+            } else {
+                warn!("[TRACE] CLIENT TIMEOUT request={} elapsed={}ms (no pending entry)", req_id_hex, elapsed_ms);
             }
+            self.pending.remove(&request_id);
+            return Some(Err(ClientError::Timeout));
+        }
+
+        // Collect any streams that finished opening in the background
+        if let Some(ref mut sm) = self.stream_manager {
+            sm.poll_open_streams();
+        }
 
-            // Collect any streams that finished opening in the background
+        // Push request shards to outbound channel (data plane).
+        // Ensure stream exists first so writer task can send them.
+        let was_pending = request.sent < request.send_count;
+        while let Some((shard, target)) = request.send_queue.pop_front() {
             if let Some(ref mut sm) = self.stream_manager {
-                sm.poll_open_streams();
+                if !sm.has_stream(&target) {
+                    sm.ensure_opening(target);
+                    // Re-queue — stream opening in background, will retry next cycle
+                    request.send_queue.push_back((shard, target));
+                    break;
+                }
+            }
+            if let Some(ref mut cover) = self.cover_traffic {
+                cover.record_activity(target, Instant::now());
+            }
+            let ack_chain = request.ack_chains.remove(&shard.ephemeral_pubkey);
+            let shard = self.padded(shard);
+            if let Some(ref tx) = self.outbound_tx {
+                let payload_len = shard.payload.len();
+                let _ = tx.try_send(OutboundShard { peer: target, shard });
+                request.sent += 1;
+                if let Some(chain) = ack_chain {
+                    self.chain_acks.track(chain, Instant::now());
+                }
+                let target_str = target.to_string();
+                warn!(
+                    "[TRACE] CLIENT SHARD_SENT request={} shard={}/{} target={} elapsed={}ms payload={}B",
+                    req_id_hex, request.sent, request.send_count,
+                    &target_str[target_str.len().saturating_sub(6)..],
+                    request.send_start.elapsed().as_millis(),
+                    payload_len,
+                );
+            }
+        }
+        if was_pending && request.sent == request.send_count && request.sent > 0 {
+            info!(
+                "[TRACE] CLIENT ALL_SENT request={} shards={} elapsed={}ms — waiting for response",
+                req_id_hex, request.sent, request.send_start.elapsed().as_millis(),
+            );
+        }
+
+        None
+    }
+
+    /// Make an HTTP request through the tunnel and stream the response body.
+    ///
+    /// Returns as soon as the response head (status + headers) arrives. The
+    /// exit sends the body back in independently decodable segments, and the
+    /// returned [`ResponseStream`] yields each one as it is decoded, so large
+    /// downloads never have to fit in memory at once. Keep polling the node
+    /// (`run()` / `poll_once()`) while reading the stream.
+    pub async fn fetch_stream(
+        &mut self,
+        method: &str,
+        url: &str,
+        body: Option<Vec<u8>>,
+        headers: Option<Vec<(String, String)>>,
+    ) -> Result<ResponseStream> {
+        if !self.capabilities.is_client() || !self.connected {
+            return Err(ClientError::NotConnected);
+        }
+
+        let exit_info = self
+            .selected_exit
+            .as_ref()
+            .ok_or(ClientError::NoExitNodes)?
+            .clone();
+
+        let exit_peer_id = self.known_peers.get(&exit_info.pubkey).copied();
+        let exit_hop = PathHop {
+            peer_id: exit_peer_id.map(|p| p.to_bytes()).unwrap_or_default(),
+            signing_pubkey: exit_info.pubkey,
+            encryption_pubkey: exit_info.encryption_pubkey.unwrap_or([0u8; 32]),
+        };
+
+        let (paths, first_hops, lease_set) = self.build_request_paths(&exit_hop, &RequestProfile::bulk())?;
+        if first_hops.is_empty() && exit_peer_id.is_none() {
+            return Err(ClientError::ExitUnreachable("exit peer id unknown".to_string()));
+        }
+
+        // Segments come back uncompressed; only the request itself is compressed
+        let mut builder = RequestBuilder::new(method, url).streaming();
+        if self.config.payload_compression {
+            builder = builder.compressed(self.payload_compression.clone()).exit_accepts_zstd(exit_info.zstd_requests);
+        }
+        if let Some(hdrs) = headers {
+            for (key, value) in hdrs {
+                builder = builder.header(&key, &value);
+            }
+        }
+        if let Some(body_data) = body {
+            builder = builder.body(body_data);
+        }
+        self.check_request_size(&exit_info, builder.payload_len())?;
+
+        let (request_id, shards) = builder.build_onion_with_enc_key(
+            &self.keypair,
+            &exit_hop,
+            &paths,
+            &lease_set,
+            self.encryption_keypair.public_key_bytes(),
+            self.keypair.public_key_bytes(),
+        )?;
+
+        info!(
+            "Sending stream request={} url={} shards={} exit_enc={}",
+            hex::encode(&request_id[..8]),
+            url,
+            shards.len(),
+            hex::encode(&exit_hop.encryption_pubkey[..8]),
+        );
+
+        let (head_tx, mut head_rx) = mpsc::channel(1);
+        let (chunk_tx, chunk_rx) = mpsc::channel(STREAM_CHANNEL_CAPACITY);
+        for seq in 0..STREAM_LOOKAHEAD {
+            self.stream_segments.insert(segment_assembly_id(&request_id, seq), (request_id, seq));
+        }
+        self.pending_streams.insert(
+            request_id,
+            PendingStream {
+                segments: HashMap::new(),
+                decoded: BTreeMap::new(),
+                next_seq: 0,
+                head_tx: Some(head_tx),
+                ready: VecDeque::new(),
+                chunk_tx,
+                exit_enc_pubkey: exit_hop.encryption_pubkey,
+                last_progress: Instant::now(),
+                body_bytes: 0,
+                finished: false,
+            },
+        );
+
+        let request_bytes: usize = shards.iter().map(|s| s.payload.len()).sum();
+        {
+            let mut state = self.state.write();
+            state.stats.bytes_sent += request_bytes as u64;
+            state.throughput.record(TrafficClass::Client, request_bytes as u64, 0);
+        }
+        self.credit_ledger.record_spent(
+            request_id,
+            self.keypair.public_key_bytes(),
+            self.own_subscription_tier,
+            request_bytes as u64,
+            self.config.hop_mode.min_relays() + 1,
+        );
+        self.credits = self.credits.saturating_sub(1);
+
+        let mut send_queue: VecDeque<(Shard, PeerId)> = VecDeque::new();
+        if first_hops.is_empty() {
+            if let Some(exit_pid) = exit_peer_id {
+                for shard in shards {
+                    send_queue.push_back((shard, exit_pid));
+                }
+            }
+        } else {
+            for (i, shard) in shards.into_iter().enumerate() {
+                let target = first_hops[i % first_hops.len()];
+                send_queue.push_back((shard, target));
             }
+        }
 
-            // Push request shards to outbound channel (data plane).
-            // Ensure stream exists first so writer task can send them.
+        // Send + wait for the head. Idle timeouts are enforced by
+        // poll_response_streams, which fails head_tx.
+        loop {
+            if let Some(ref mut sm) = self.stream_manager {
+                sm.poll_open_streams();
+            }
             while let Some((shard, target)) = send_queue.pop_front() {
                 if let Some(ref mut sm) = self.stream_manager {
                     if !sm.has_stream(&target) {
                         sm.ensure_opening(target);
-                        // Re-queue — stream opening in background, will retry next cycle
                         send_queue.push_back((shard, target));
                         break;
                     }
                 }
+                if let Some(ref mut cover) = self.cover_traffic {
+                    cover.record_activity(target, Instant::now());
+                }
+                let shard = self.padded(shard);
                 if let Some(ref tx) = self.outbound_tx {
-                    let payload_len = shard.payload.len();
                     let _ = tx.try_send(OutboundShard { peer: target, shard });
-                    sent += 1;
-                    let target_str = target.to_string();
-                    warn!(
-                        "[TRACE] CLIENT SHARD_SENT request={} shard={}/{} target={} elapsed={}ms payload={}B",
-                        req_id_hex, sent, send_count,
-                        &target_str[target_str.len().saturating_sub(6)..],
-                        send_start.elapsed().as_millis(),
-                        payload_len,
-                    );
                 }
             }
-            if sent == send_count && sent > 0 {
-                info!(
-                    "[TRACE] CLIENT ALL_SENT request={} shards={} elapsed={}ms — waiting for response",
-                    req_id_hex, sent, send_start.elapsed().as_millis(),
-                );
-            }
 
             tokio::select! {
-                response = response_rx.recv() => {
-                    match response {
-                        Some(r) => break r,
-                        None => return Err(ClientError::Timeout),
-                    }
+                head = head_rx.recv() => {
+                    return match head {
+                        Some(Ok((status, headers))) => Ok(ResponseStream::new(status, headers, chunk_rx)),
+                        Some(Err(e)) => Err(e),
+                        None => Err(ClientError::Timeout),
+                    };
                 }
                 _ = self.poll_once() => {}
             }
-        };
-
-        Ok(response?)
+        }
     }
 
     /// Send shards to peers.
@@ -2427,17 +5288,16 @@ impl CraftNetNode {
         // Important: exit nodes can also decrypt routing_tags on REQUEST shards (since
         // the client encrypted them with the exit's key). We distinguish by checking
         // whether the assembly_id matches something we're waiting for.
-        let tag_result = craftnet_core::onion_crypto::decrypt_routing_tag(
-            &self.encryption_keypair.secret_key_bytes(),
-            &shard.routing_tag,
-        );
+        let tag_result = self.decrypt_own_routing_tag(&shard.routing_tag);
         let tag_ok = tag_result.is_ok();
+        let tag_assembly_id = tag_result.as_ref().ok().map(|tag| tag.assembly_id);
         if let Ok(tag) = tag_result {
             let assembly_id = tag.assembly_id;
             let has_pending_request = self.pending.contains_key(&assembly_id);
             let has_pending_tunnel = self.pending_tunnel.contains_key(&assembly_id);
+            let has_pending_stream = self.stream_segments.contains_key(&assembly_id);
 
-            if has_pending_request || has_pending_tunnel {
+            if has_pending_request || has_pending_tunnel || has_pending_stream {
                 self.handle_response_shard(shard);
                 return ShardResponse::Accepted(None);
             }
@@ -2449,6 +5309,10 @@ impl CraftNetNode {
             return ShardResponse::Rejected("Not in relay mode".to_string());
         }
 
+        if self.is_draining() && !self.accept_while_draining(&shard, &source_peer, tag_assembly_id.as_ref()) {
+            return ShardResponse::Rejected(craftnet_network::NACK_DRAINING.to_string());
+        }
+
         if shard.header.is_empty() {
             // CRITICAL: empty header = exit processing. If tag decryption failed,
             // this shard is NOT a response for us and will hit the exit handler.
@@ -2473,6 +5337,7 @@ impl CraftNetNode {
     async fn process_as_exit(&mut self, shard: Shard, _source_peer: PeerId) -> ShardResponse {
         let local_id = self.local_peer_id.map(|p| p.to_string()).unwrap_or_default();
         let local_short = local_id[local_id.len().saturating_sub(6)..].to_string();
+        self.state.write().throughput.record(TrafficClass::Exit, 0, shard.payload.len() as u64);
 
         let exit_handler = {
             let mut state = self.state.write();
@@ -2485,104 +5350,158 @@ impl CraftNetNode {
             return ShardResponse::Accepted(None);
         };
 
-        // Fast path: collect shard into assembly (no I/O, microseconds)
+        // Fast path: collect shard into assembly (no I/O, microseconds).
+        // A completed assembly joins its pool's fair queue; restoring the
+        // handler starts whichever queued request is next in line.
         let collect_result = handler.collect_shard(shard);
+        self.restore_exit_handler(handler);
 
         match collect_result {
-            Ok(None) => {
-                // Still collecting — restore handler immediately
-                let mut state = self.state.write();
-                state.exit_handler = Some(handler);
-                return ShardResponse::Accepted(None);
+            Ok(_) => ShardResponse::Accepted(None),
+            Err(e) => {
+                // Collection failed (e.g., bad routing_tag or pool queue full)
+                warn!("[TRACE] node={} EXIT_ERROR err={}", local_short, e);
+                ShardResponse::Rejected(e.to_string())
             }
-            Ok(Some(assembly_id)) => {
-                // Assembly complete — spawn slow processing as background task.
-                // Handler is moved into the task and returned via channel when done.
-                // This prevents blocking poll_once during HTTP fetch.
-                let tx = self.exit_task_tx.clone();
-                let ls = local_short.clone();
-                tokio::spawn(async move {
-                    let start = std::time::Instant::now();
-                    let result = tokio::time::timeout(
-                        Duration::from_secs(15),
-                        handler.process_complete_assembly(assembly_id),
-                    ).await;
-                    let process_ms = start.elapsed().as_millis();
-
-                    let shard_pairs = match result {
-                        Ok(Ok(Some(pairs))) => {
-                            warn!(
-                                "[TRACE] node={} EXIT_COMPLETE response_shards={} process_ms={}",
-                                ls, pairs.len(), process_ms,
-                            );
-                            pairs
-                        }
-                        Ok(Ok(None)) => vec![],
-                        Ok(Err(e)) => {
-                            warn!("[TRACE] node={} EXIT_ERROR err={} process_ms={}", ls, e, process_ms);
-                            vec![]
-                        }
-                        Err(_) => {
-                            warn!("[TRACE] node={} EXIT_TIMEOUT process_ms={} — handler returned", ls, process_ms);
-                            vec![]
-                        }
-                    };
+        }
+    }
 
-                    let _ = tx.send(ExitTaskResult { handler, shard_pairs, process_ms }).await;
-                });
-                return ShardResponse::Accepted(None);
+    /// Hand the exit handler back after collecting or processing.
+    ///
+    /// If the fair queue has a request ready, the handler goes straight into
+    /// a background task for it instead; otherwise it is put back in state.
+    fn restore_exit_handler(&mut self, mut handler: ExitHandler) {
+        let next = handler.next_ready();
+        {
+            let mut state = self.state.write();
+            state.stats.exit_queues = handler.pool_queue_stats();
+            state.stats.exit_fetch_pool = handler.fetch_pool_stats();
+        }
+        match next {
+            Some(assembly_id) => self.spawn_exit_task(handler, assembly_id),
+            None => self.state.write().exit_handler = Some(handler),
+        }
+    }
+
+    /// Spawn the slow processing (HTTP fetch + response creation) of a
+    /// complete assembly. The handler is moved into the task and returned via
+    /// channel when done, so poll_once never blocks on upstream I/O.
+    fn spawn_exit_task(&self, mut handler: ExitHandler, assembly_id: Id) {
+        let tx = self.exit_task_tx.clone();
+        let local_id = self.local_peer_id.map(|p| p.to_string()).unwrap_or_default();
+        let ls = local_id[local_id.len().saturating_sub(6)..].to_string();
+        let pool = handler.pending_pool(&assembly_id);
+        tokio::spawn(async move {
+            let start = std::time::Instant::now();
+            let result = tokio::time::timeout(
+                Duration::from_secs(15),
+                handler.process_complete_assembly(assembly_id),
+            ).await;
+            let process_ms = start.elapsed().as_millis();
+
+            let shard_pairs = match result {
+                Ok(Ok(Some(pairs))) => {
+                    warn!(
+                        "[TRACE] node={} EXIT_COMPLETE response_shards={} process_ms={}",
+                        ls, pairs.len(), process_ms,
+                    );
+                    pairs
+                }
+                Ok(Ok(None)) => vec![],
+                Ok(Err(e)) => {
+                    warn!("[TRACE] node={} EXIT_ERROR err={} process_ms={}", ls, e, process_ms);
+                    vec![]
+                }
+                Err(_) => {
+                    warn!("[TRACE] node={} EXIT_TIMEOUT process_ms={} — handler returned", ls, process_ms);
+                    vec![]
+                }
+            };
+
+            let _ = tx.send(ExitTaskResult { handler, assembly_id, pool, shard_pairs, process_ms }).await;
+        });
+    }
+
+    /// Forward streamed response segments from the exit handler, as far as
+    /// the outbound queue has room for whole segments.
+    ///
+    /// Segments left in the channel are what pause the exit's upstream read,
+    /// so a backed-up path throttles the origin rather than exit memory.
+    fn drain_exit_stream_segments(&mut self) {
+        loop {
+            let batch = match self.exit_stream_backlog.take() {
+                Some(batch) => batch,
+                None => match self.exit_stream_rx.try_recv() {
+                    Ok(batch) => batch,
+                    Err(_) => break,
+                },
+            };
+            let fits = self.outbound_tx.as_ref().map_or(false, |tx| {
+                tx.capacity() >= batch.len().min(tx.max_capacity())
+            });
+            if !fits {
+                self.exit_stream_backlog = Some(batch);
+                break;
             }
-            Err(e) => {
-                // Collection failed (e.g., bad routing_tag) — restore handler
-                let mut state = self.state.write();
-                state.exit_handler = Some(handler);
-                warn!("[TRACE] node={} EXIT_ERROR err={}", local_short, e);
-                return ShardResponse::Rejected(e.to_string());
+            self.queue_exit_response_shards(batch);
+        }
+    }
+
+    /// Push exit response shards to the outbound channel (data plane).
+    ///
+    /// Returns how many shards were queued.
+    fn queue_exit_response_shards(&mut self, shard_pairs: Vec<(Shard, Option<Vec<u8>>)>) -> u32 {
+        // The background writer task writes them to TCP without blocking poll_once.
+        let local_id = self.local_peer_id.map(|p| p.to_string()).unwrap_or_default();
+        let local_short = &local_id[local_id.len().saturating_sub(6)..];
+        let mut queued = 0u32;
+        for (shard, gateway_bytes) in shard_pairs {
+            if let Some(target) = gateway_bytes.and_then(|gw| PeerId::from_bytes(&gw).ok()) {
+                // Ensure outbound stream is being opened for this target
+                if let Some(ref mut sm) = self.stream_manager {
+                    sm.ensure_opening(target);
+                }
+                let shard = self.padded(shard);
+                if let Some(ref tx) = self.outbound_tx {
+                    let shard_bytes = shard.payload.len() as u64;
+                    match tx.try_send(OutboundShard { peer: target, shard }) {
+                        Ok(()) => {
+                            queued += 1;
+                            let mut state = self.state.write();
+                            state.stats.bytes_relayed += shard_bytes;
+                            state.throughput.record(TrafficClass::Exit, shard_bytes, 0);
+                        }
+                        Err(e) => {
+                            warn!("[TRACE] node={} EXIT_RESPONSE_DROP target={} err={}", local_short, &target.to_string()[target.to_string().len().saturating_sub(6)..], e);
+                        }
+                    }
+                }
+            } else {
+                warn!("[TRACE] node={} EXIT_RESPONSE_NO_GATEWAY", local_short);
             }
         }
+        queued
     }
 
     /// Queue completed exit task results: restore handler, enqueue response shards.
     fn drain_exit_task_results(&mut self) {
         while let Ok(result) = self.exit_task_rx.try_recv() {
-            // Restore exit handler
-            {
-                let mut state = self.state.write();
-                if !result.shard_pairs.is_empty() {
-                    state.stats.requests_exited += 1;
+            if !result.shard_pairs.is_empty() {
+                self.state.write().stats.requests_exited += 1;
+                if let Some(pool) = result.pool {
+                    let bytes: usize = result.shard_pairs.iter().map(|(s, _)| s.payload.len()).sum();
+                    let tier = self.pool_tier(&pool);
+                    self.credit_ledger.record_served(result.assembly_id, pool, tier, bytes as u64);
                 }
-                state.exit_handler = Some(result.handler);
             }
+            // Restore exit handler (or start the next queued request)
+            self.restore_exit_handler(result.handler);
 
             // Push response shards to outbound channel (data plane).
-            // The background writer task writes them to TCP without blocking poll_once.
-            let local_id = self.local_peer_id.map(|p| p.to_string()).unwrap_or_default();
-            let local_short = &local_id[local_id.len().saturating_sub(6)..];
-            let mut queued = 0u32;
-            for (shard, gateway_bytes) in result.shard_pairs {
-                if let Some(target) = gateway_bytes.and_then(|gw| PeerId::from_bytes(&gw).ok()) {
-                    // Ensure outbound stream is being opened for this target
-                    if let Some(ref mut sm) = self.stream_manager {
-                        sm.ensure_opening(target);
-                    }
-                    if let Some(ref tx) = self.outbound_tx {
-                        let shard_bytes = shard.payload.len() as u64;
-                        match tx.try_send(OutboundShard { peer: target, shard }) {
-                            Ok(()) => {
-                                queued += 1;
-                                let mut state = self.state.write();
-                                state.stats.bytes_relayed += shard_bytes;
-                            }
-                            Err(e) => {
-                                warn!("[TRACE] node={} EXIT_RESPONSE_DROP target={} err={}", local_short, &target.to_string()[target.to_string().len().saturating_sub(6)..], e);
-                            }
-                        }
-                    }
-                } else {
-                    warn!("[TRACE] node={} EXIT_RESPONSE_NO_GATEWAY", local_short);
-                }
-            }
+            let queued = self.queue_exit_response_shards(result.shard_pairs);
             if queued > 0 {
+                let local_id = self.local_peer_id.map(|p| p.to_string()).unwrap_or_default();
+                let local_short = &local_id[local_id.len().saturating_sub(6)..];
                 warn!(
                     "[TRACE] node={} EXIT_RESPONSE queued={} process_ms={}",
                     local_short, queued, result.process_ms,
@@ -2594,54 +5513,27 @@ impl CraftNetNode {
         while self.state.read().exit_handler.is_some() && !self.exit_shard_queue.is_empty() {
             let shard = self.exit_shard_queue.pop_front().unwrap();
             // Fast path only: collect_shard is sync, won't block.
-            // If assembly completes, it spawns a new task (handler taken again → loop breaks).
+            // If a request becomes ready, restoring spawns a task for it
+            // (handler taken again → loop ends).
             let exit_handler = {
                 let mut state = self.state.write();
                 state.exit_handler.take()
             };
             let Some(mut handler) = exit_handler else { break; };
 
-            match handler.collect_shard(shard) {
-                Ok(None) => {
-                    self.state.write().exit_handler = Some(handler);
-                }
-                Ok(Some(assembly_id)) => {
-                    let tx = self.exit_task_tx.clone();
-                    let local_id = self.local_peer_id.map(|p| p.to_string()).unwrap_or_default();
-                    let ls = local_id[local_id.len().saturating_sub(6)..].to_string();
-                    tokio::spawn(async move {
-                        let start = std::time::Instant::now();
-                        let result = tokio::time::timeout(
-                            Duration::from_secs(15),
-                            handler.process_complete_assembly(assembly_id),
-                        ).await;
-                        let process_ms = start.elapsed().as_millis();
-                        let shard_pairs = match result {
-                            Ok(Ok(Some(pairs))) => {
-                                warn!("[TRACE] node={} EXIT_COMPLETE response_shards={} process_ms={}", ls, pairs.len(), process_ms);
-                                pairs
-                            }
-                            Ok(Ok(None)) => vec![],
-                            Ok(Err(e)) => { warn!("[TRACE] node={} EXIT_ERROR err={} process_ms={}", ls, e, process_ms); vec![] }
-                            Err(_) => { warn!("[TRACE] node={} EXIT_TIMEOUT process_ms={}", ls, process_ms); vec![] }
-                        };
-                        let _ = tx.send(ExitTaskResult { handler, shard_pairs, process_ms }).await;
-                    });
-                    break; // handler is in task, stop draining queue
-                }
-                Err(e) => {
-                    warn!("[TRACE] EXIT_QUEUE_ERROR err={}", e);
-                    self.state.write().exit_handler = Some(handler);
-                }
+            if let Err(e) = handler.collect_shard(shard) {
+                warn!("[TRACE] EXIT_QUEUE_ERROR err={}", e);
             }
+            self.restore_exit_handler(handler);
         }
     }
 
     /// Relay a shard by peeling one onion layer and forwarding to the next hop.
     ///
     /// The relay handler returns (modified_shard, next_peer_id_bytes, receipt).
-    /// We forward the modified shard to the specified next peer.
-    async fn relay_shard(&mut self, shard: Shard, _source_peer: Option<PeerId>) -> ShardResponse {
+    /// We forward the modified shard to the specified next peer, and send the
+    /// shard's chain ack (if the client asked for one) back to `source_peer`.
+    async fn relay_shard(&mut self, shard: Shard, source_peer: Option<PeerId>) -> ShardResponse {
         // Get sender_pubkey from libp2p connection (for ForwardReceipt anti-replay)
         let sender_pubkey = self.keypair.public_key_bytes(); // placeholder: use connection auth
 
@@ -2653,12 +5545,12 @@ impl CraftNetNode {
                 return ShardResponse::Rejected("Relay not active".to_string());
             };
 
-            relay_handler.handle_shard(shard, sender_pubkey)
+            relay_handler.handle_shard_with_ack(shard, sender_pubkey)
         };
         // Lock released here
 
         match relay_result {
-            Ok((mut modified_shard, next_peer_bytes, receipt, pool_pubkey)) => {
+            Ok((mut modified_shard, next_peer_bytes, receipt, pool_pubkey, chain_ack)) => {
                 // ── Tier enforcement ──
                 // 1. hops_remaining must be >= 1 (otherwise no honest relay should process it)
                 if modified_shard.hops_remaining < 1 {
@@ -2719,9 +5611,13 @@ impl CraftNetNode {
                 }
                 {
                     let mut state = self.state.write();
+                    let shard_bytes = modified_shard.payload.len() as u64;
                     state.stats.shards_relayed += 1;
-                    state.stats.bytes_relayed += modified_shard.payload.len() as u64;
+                    state.stats.bytes_relayed += shard_bytes;
+                    state.throughput.record(TrafficClass::Relay, shard_bytes, shard_bytes);
                 }
+                let tier = self.pool_tier(&pool_pubkey);
+                self.credit_ledger.record_relayed(pool_pubkey, tier, modified_shard.payload.len() as u64);
 
                 // Route receipt to the correct pool using pool_pubkey from onion layer.
                 // Check subscription_cache to determine if this user has an active subscription.
@@ -2758,20 +5654,129 @@ impl CraftNetNode {
                     if let Some(ref mut sm) = self.stream_manager {
                         sm.ensure_opening(next_peer);
                     }
-                    if let Some(ref tx) = self.outbound_tx {
-                        let _ = tx.try_send(OutboundShard { peer: next_peer, shard: modified_shard });
+                    if let (Some(chain_ack), Some(upstream)) = (chain_ack, source_peer) {
+                        self.send_chain_ack(upstream, next_peer, chain_ack, modified_shard.hops_remaining);
+                    }
+                    let modified_shard = self.padded(modified_shard);
+                    let bytes = modified_shard.payload.len();
+                    let class = match source_peer {
+                        Some(peer) if self.is_freeloader(&peer) => QosClass::Free,
+                        _ => QosClass::from_tier(tier),
+                    };
+                    let shard = OutboundShard { peer: next_peer, shard: modified_shard };
+                    if self.relay_queues.push(class, shard, bytes).is_err() {
+                        debug!("Relay queue for {} pools full — dropping shard", class.name());
                     }
+                    self.flush_relay_queues();
                 } else {
                     warn!("Could not parse next_peer PeerId from onion layer");
                 }
 
-                ShardResponse::Accepted(Some(Box::new(receipt)))
+                ShardResponse::Accepted(Some(Box::new(receipt)))
+            }
+            Err(RelayError::CoverShard) => {
+                // Cover traffic: ack like any shard so it looks the same on the
+                // wire, but no receipt, no forward, no relay stats
+                self.state.write().stats.cover_shards_dropped += 1;
+                ShardResponse::Accepted(None)
+            }
+            Err(RelayError::Replay(_)) => {
+                self.state.write().stats.replays_rejected += 1;
+                ShardResponse::Rejected(craftnet_network::NACK_REPLAY.to_string())
+            }
+            Err(e) => {
+                // Onion peel failed — could be wrong key (shard wasn't for us)
+                // or corrupted header. Try processing as exit instead.
+                debug!("Onion peel failed: {} — not relaying", e);
+                ShardResponse::Rejected(e.to_string())
+            }
+        }
+    }
+
+    /// Whether `peer` is a freeloader under `NodeConfig::relay_freeloader`
+    fn is_freeloader(&self, peer: &PeerId) -> bool {
+        self.config.relay_freeloader.is_some_and(|policy| self.traffic.read().unwrap().is_freeloader(peer, &policy))
+    }
+
+    /// Hand queued relay shards to the writer task, by tier, while it
+    /// has room
+    fn flush_relay_queues(&mut self) {
+        let Some(ref tx) = self.outbound_tx else { return };
+        let now = Instant::now();
+        while let Some((class, shard)) = self.relay_queues.pop(now) {
+            let bytes = shard.shard.payload.len();
+            match tx.try_send(shard) {
+                Ok(()) => {}
+                Err(mpsc::error::TrySendError::Full(shard)) => {
+                    self.relay_queues.unpop(class, shard, bytes);
+                    break;
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => break,
+            }
+        }
+    }
+
+    /// Replace the relay's per-tier weights and rate limits
+    pub fn set_relay_qos(&mut self, qos: QosConfig) {
+        self.relay_queues.set_config(qos.clone());
+        self.config.relay_qos = qos;
+    }
+
+    /// Ack a shard we forwarded to `downstream` back to `upstream`, and route
+    /// the acks of the `downstream_relays` relays after us the same way.
+    fn send_chain_ack(&mut self, upstream: PeerId, downstream: PeerId, chain_ack: RelayChainAck, downstream_relays: u8) {
+        if let Some(down_ref) = chain_ack.down_ref {
+            self.chain_ack_routes.insert(
+                down_ref,
+                downstream,
+                upstream,
+                chain_ack.ack.ack_ref,
+                downstream_relays,
+                Instant::now(),
+            );
+        }
+        if let Some(ref sm) = self.stream_manager {
+            sm.send_chain_ack(upstream, chain_ack.ack);
+        }
+    }
+
+    /// Chain acks read from our streams: settle our own request shards,
+    /// pass on the ones for shards we relayed, drop the rest.
+    fn handle_chain_acks(&mut self, acks: Vec<(PeerId, ChainAck)>) {
+        for (peer, ack) in acks {
+            if self.chain_acks.contains(&ack.ack_ref) {
+                if let Some(outcome) = self.chain_acks.record(&ack) {
+                    self.apply_chain_outcome(outcome);
+                }
+            } else if let Some((upstream, routed)) = self.chain_ack_routes.route(&peer, &ack) {
+                if let Some(ref sm) = self.stream_manager {
+                    sm.send_chain_ack(upstream, routed);
+                }
+            } else {
+                debug!("Dropping chain ack from {} for unknown ref {}", peer, hex::encode(&ack.ack_ref[..8]));
             }
-            Err(e) => {
-                // Onion peel failed — could be wrong key (shard wasn't for us)
-                // or corrupted header. Try processing as exit instead.
-                debug!("Onion peel failed: {} — not relaying", e);
-                ShardResponse::Rejected(e.to_string())
+        }
+    }
+
+    /// Count a settled shard chain and score its relays: relays that acked
+    /// forwarded the shard, except the one it was lost at.
+    fn apply_chain_outcome(&mut self, outcome: ChainOutcome) {
+        {
+            let mut state = self.state.write();
+            state.stats.chain_hops_verified += outcome.verified.len() as u64;
+            state.stats.chain_hops_missing += outcome.missing.len() as u64;
+        }
+        for relay in &outcome.verified {
+            if Some(*relay) != outcome.blamed {
+                if let Some(status) = self.relay_nodes.get_mut(relay) {
+                    status.record_forward(true);
+                }
+            }
+        }
+        if let Some(blamed) = outcome.blamed {
+            debug!("Shard lost at relay {}", hex::encode(&blamed[..8]));
+            if let Some(status) = self.relay_nodes.get_mut(&blamed) {
+                status.record_forward(false);
             }
         }
     }
@@ -2849,17 +5854,19 @@ impl CraftNetNode {
             if let Some((pool, pool_type)) = self.request_user.get(&receipt.shard_id) {
                 let key = (*pool, *pool_type);
                 let queue = self.proof_queue.entry(key).or_default();
-                if queue.len() < self.proof_queue_limit {
+                let spilling = self.receipt_spill.as_ref().is_some_and(|spill| spill.spilled(&key) > 0);
+                if queue.len() < self.proof_queue_limit && !spilling {
                     info!(
                         "Receipt queued for compression: pool={}, pool_type={:?}, queue_size={}",
                         hex::encode(&key.0[..8]),
                         key.1,
                         queue.len() + 1,
                     );
+                    *self.proof_queue_bytes.entry(key).or_default() += receipt.payload_size as u64;
                     queue.push_back(receipt);
 
                     // Track when the first receipt entered this pool's queue
-                    self.proof_oldest_receipt.entry(key).or_insert_with(Instant::now);
+                    self.proof_oldest_receipt.entry(key).or_insert_with(|| self.clock.now());
 
                     // Debounced persistence: save every 100 enqueued receipts
                     self.proof_enqueue_since_save += 1;
@@ -2867,6 +5874,17 @@ impl CraftNetNode {
                         self.proof_enqueue_since_save = 0;
                         self.save_proof_state();
                     }
+                } else if let Some(ref mut spill) = self.receipt_spill {
+                    // Queue full: set the receipt aside on disk, in arrival
+                    // order behind anything spilled before it
+                    if let Err(e) = spill.push(key, receipt) {
+                        warn!(
+                            "Failed to spill receipt for pool {} ({:?}): {} — receipt dropped",
+                            hex::encode(&key.0[..8]),
+                            key.1,
+                            e,
+                        );
+                    }
                 } else {
                     warn!(
                         "Proof queue full for pool {} ({:?}) — receipt dropped (backpressure)",
@@ -2878,6 +5896,24 @@ impl CraftNetNode {
         }
     }
 
+    /// Decrypt a routing tag with our encryption key, then with the keys of
+    /// our identities
+    fn decrypt_own_routing_tag(
+        &self,
+        routing_tag: &[u8],
+    ) -> std::result::Result<RoutingTag, craftec_crypto::EncryptError> {
+        let result = craftnet_core::onion_crypto::decrypt_routing_tag(
+            &self.encryption_keypair.secret_key_bytes(),
+            routing_tag,
+        );
+        if result.is_err() && !self.identities.is_empty() {
+            if let Some((tag, _)) = self.identities.decrypt_routing_tag(routing_tag) {
+                return Ok(tag);
+            }
+        }
+        result
+    }
+
     /// Handle response shard for our own request (onion-routed, multi-chunk aware)
     ///
     /// In onion mode, we decrypt the routing_tag with our encryption key to get
@@ -2885,10 +5921,7 @@ impl CraftNetNode {
     /// was populated when we sent the request.
     fn handle_response_shard(&mut self, shard: Shard) {
         // Decrypt routing_tag to get assembly_id + shard/chunk metadata
-        let tag = match craftnet_core::onion_crypto::decrypt_routing_tag(
-            &self.encryption_keypair.secret_key_bytes(),
-            &shard.routing_tag,
-        ) {
+        let tag = match self.decrypt_own_routing_tag(&shard.routing_tag) {
             Ok(tag) => tag,
             Err(_) => return,
         };
@@ -2903,6 +5936,11 @@ impl CraftNetNode {
             return;
         }
 
+        // Then streamed-response segments
+        if self.handle_stream_response_shard(&assembly_id, shard_index, chunk_index, total_chunks, &shard) {
+            return;
+        }
+
         // Find the request_id that corresponds to this assembly_id
         // We use assembly_id as the key for pending requests directly
         // (request_id tracking was populated at send time)
@@ -2916,7 +5954,17 @@ impl CraftNetNode {
             if pending.total_chunks == 0 {
                 pending.total_chunks = total_chunks;
             }
-            pending.shards.insert((chunk_index, shard_index), shard.payload);
+            pending.flags = tag.flags;
+            // Every response shard repeats the request's timings; the order
+            // of first appearance is path order
+            if pending.trace_secret.is_some() {
+                for blob in shard.trace {
+                    if pending.trace.len() < MAX_SHARD_TRACE_ENTRIES && !pending.trace.contains(&blob) {
+                        pending.trace.push(blob);
+                    }
+                }
+            }
+            pending.shards.insert((chunk_index, shard_index), shard.payload.into());
 
             let needed = pending.total_chunks as usize * DATA_SHARDS;
             info!(
@@ -2936,7 +5984,20 @@ impl CraftNetNode {
                 if let Some(pending) = self.pending.remove(&request_id) {
                     let response_tx = pending.response_tx.clone();
 
-                    let result = self.reconstruct_response(&pending);
+                    let result = self.reconstruct_response(&pending).and_then(|mut response| {
+                        self.verify_exit_signature(&request_id, &pending.exit_pubkey, &mut response)?;
+                        match response.exit_error() {
+                            Some(e) => Err(e),
+                            None => Ok(response),
+                        }
+                    });
+                    match result {
+                        Err(ClientError::ResponseTampered(_)) => self.report_tampered_exit(&pending.exit_pubkey, &request_id),
+                        Err(ClientError::RequestTooLarge { limit }) => {
+                            self.exit_request_limits.insert(pending.exit_pubkey, limit);
+                        }
+                        _ => {}
+                    }
                     match result {
                         Ok(response) => {
                             let response_bytes = response.body.len();
@@ -2951,6 +6012,7 @@ impl CraftNetNode {
                             {
                                 let mut state = self.state.write();
                                 state.stats.bytes_received += response_bytes as u64;
+                                state.throughput.record(TrafficClass::Client, 0, response_bytes as u64);
                             }
                             let _ = response_tx.try_send(Ok(response));
                         }
@@ -2979,24 +6041,29 @@ impl CraftNetNode {
         let Some(pending) = self.pending.get(request_id) else {
             return false;
         };
-        if pending.total_chunks == 0 {
-            return false;
-        }
-
-        let mut chunk_counts: HashMap<u16, usize> = HashMap::new();
-        for &(chunk_idx, _) in pending.shards.keys() {
-            *chunk_counts.entry(chunk_idx).or_default() += 1;
-        }
+        response_chunks_ready(&pending.shards, pending.total_chunks)
+    }
 
-        if chunk_counts.len() < pending.total_chunks as usize {
-            return false;
-        }
-        chunk_counts.values().all(|&count| count >= DATA_SHARDS)
+    /// Idle timeout for a request of `payload_bytes` over the circuit starting
+    /// at `first_hop`: adapted to its measured RTT (or the overall RTT for an
+    /// unmeasured circuit), between `min_request_timeout` and `request_timeout`
+    fn request_timeout_for(&self, first_hop: Option<PeerId>, payload_bytes: usize) -> Duration {
+        let estimator = first_hop
+            .and_then(|hop| self.circuit_rtt.get(&hop))
+            .unwrap_or(&self.overall_rtt);
+        estimator.timeout(payload_bytes, self.config.min_request_timeout, self.config.request_timeout)
     }
 
     /// Update exit node measurement after receiving response
     fn update_exit_measurement(&mut self, pending: &PendingRequest, response_bytes: usize) {
-        let elapsed_ms = pending.sent_at.elapsed().as_millis() as u32;
+        let elapsed = pending.sent_at.elapsed();
+        let moved = pending.request_bytes + response_bytes;
+        self.overall_rtt.observe(elapsed, moved);
+        if let Some(hop) = pending.first_hop {
+            self.circuit_rtt.entry(hop).or_default().observe(elapsed, moved);
+        }
+
+        let elapsed_ms = elapsed.as_millis() as u32;
         if elapsed_ms == 0 {
             return;
         }
@@ -3008,6 +6075,13 @@ impl CraftNetNode {
         let downlink_kbps = (response_bytes as u64 * 1000 / elapsed_ms as u64 / 1024) as u32;
         let latency_ms = elapsed_ms; // Round-trip time as proxy for latency
 
+        // Check the first relay's advertised capacity against what it carried
+        if let Some(hop) = pending.first_hop {
+            if let Some(relay) = self.relay_nodes.values_mut().find(|s| s.peer_id == hop) {
+                relay.record_measurement(downlink_kbps);
+            }
+        }
+
         // Update exit node status
         if let Some(status) = self.exit_nodes.get_mut(&pending.exit_pubkey) {
             status.update_measurement(latency_ms, uplink_kbps, downlink_kbps);
@@ -3018,71 +6092,290 @@ impl CraftNetNode {
         }
     }
 
+    /// Largest request payload `exit` accepts from us: the lower of what it
+    /// advertises and what its error frames reported (None = unknown)
+    fn request_limit(&self, exit: &ExitInfo) -> Option<u64> {
+        let learned = self.exit_request_limits.get(&exit.pubkey).copied();
+        match (exit.max_request_bytes, learned) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Refuse a request payload of `payload_len` bytes before it is sharded
+    /// if `exit` would refuse it
+    fn check_request_size(&self, exit: &ExitInfo, payload_len: usize) -> Result<()> {
+        match self.request_limit(exit) {
+            Some(limit) if payload_len as u64 > limit => Err(ClientError::RequestTooLarge { limit }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check and strip the exit's signature over a response.
+    ///
+    /// Unsigned responses (from older exits) pass unless
+    /// `NodeConfig::require_exit_signatures` is set.
+    fn verify_exit_signature(&self, request_id: &Id, exit_pubkey: &PublicKey, response: &mut TunnelResponse) -> Result<()> {
+        let header = response.headers
+            .keys()
+            .find(|k| k.eq_ignore_ascii_case(craftnet_core::EXIT_SIGNATURE_HEADER))
+            .cloned();
+        let tampered = || ClientError::ResponseTampered(hex::encode(&exit_pubkey[..8]));
+        let Some(signature) = header.and_then(|k| response.headers.remove(&k)) else {
+            if self.config.require_exit_signatures {
+                return Err(tampered());
+            }
+            return Ok(());
+        };
+        let signature: Option<[u8; 64]> = hex::decode(signature).ok().and_then(|b| b.try_into().ok());
+        match signature {
+            Some(sig) if verify_exit_response(exit_pubkey, request_id, response.status, &response.body, &sig) => Ok(()),
+            _ => Err(tampered()),
+        }
+    }
+
+    /// Penalise an exit that sent a badly signed response and move traffic
+    /// off it
+    fn report_tampered_exit(&mut self, exit_pubkey: &PublicKey, request_id: &Id) {
+        let Some(status) = self.exit_nodes.get_mut(exit_pubkey) else { return };
+        status.record_tampered_response();
+        let tampered_responses = status.tampered_responses;
+        warn!(
+            "Exit {} sent a response with a bad signature (request={}, {} so far)",
+            hex::encode(&exit_pubkey[..8]),
+            hex::encode(&request_id[..8]),
+            tampered_responses,
+        );
+        self.exit_tamper_events.push(ExitTamperEvent {
+            exit_pubkey: hex::encode(exit_pubkey),
+            request_id: hex::encode(request_id),
+            tampered_responses,
+        });
+        self.identities.unpin_exit(exit_pubkey);
+        if self.selected_exit.as_ref().is_some_and(|e| e.pubkey == *exit_pubkey) {
+            self.select_best_exit();
+        }
+    }
+
+    /// Drain bad exit signature reports since the last call (for IPC events)
+    pub fn take_exit_tamper_events(&mut self) -> Vec<ExitTamperEvent> {
+        std::mem::take(&mut self.exit_tamper_events)
+    }
+
     /// Reconstruct response from shard payloads (multi-chunk aware)
     fn reconstruct_response(&self, pending: &PendingRequest) -> Result<TunnelResponse> {
-        use std::collections::BTreeMap;
+        let data = decode_response_payload(
+            &self.erasure,
+            &pending.shards,
+            pending.total_chunks,
+            &pending.exit_enc_pubkey,
+            &pending.response_secret,
+        )?;
+        let data = decompress_response(data, pending.flags, Some(&self.payload_compression))?;
+        let mut response = TunnelResponse::from_bytes(&data)?;
+        if let Some(secret) = pending.trace_secret {
+            response.hop_timings = open_trace(&secret, &pending.trace);
+        }
+        Ok(response)
+    }
 
-        // Group shard payloads by chunk_index
-        let mut chunks_by_index: HashMap<u16, Vec<(u8, &Vec<u8>)>> = HashMap::new();
-        for (&(chunk_idx, shard_idx), payload) in &pending.shards {
-            chunks_by_index
-                .entry(chunk_idx)
-                .or_default()
-                .push((shard_idx, payload));
+    /// Erasure-decode, unframe and decrypt one response assembly
+    fn decode_response_payload(
+        &self,
+        shards: &HashMap<(u16, u8), Vec<u8>>,
+        total_chunks: u16,
+        exit_enc_pubkey: &[u8; 32],
+    ) -> Result<Vec<u8>> {
+        decode_response_payload(
+            &self.erasure,
+            shards,
+            total_chunks,
+            exit_enc_pubkey,
+            &self.encryption_keypair.secret_key_bytes(),
+        )
+    }
+
+    // =========================================================================
+    // Streamed responses
+    // =========================================================================
+
+    /// Handle a response shard for a streamed response segment.
+    ///
+    /// Returns true if the assembly_id belongs to one of our streams.
+    fn handle_stream_response_shard(
+        &mut self,
+        assembly_id: &Id,
+        shard_index: u8,
+        chunk_index: u16,
+        total_chunks: u16,
+        shard: &Shard,
+    ) -> bool {
+        let Some(&(request_id, seq)) = self.stream_segments.get(assembly_id) else {
+            return false;
+        };
+        let Some(stream) = self.pending_streams.get_mut(&request_id) else {
+            self.stream_segments.remove(assembly_id);
+            return true;
+        };
+
+        stream.last_progress = Instant::now();
+        let (shards, total) = stream.segments
+            .entry(seq)
+            .or_insert_with(|| (HashMap::new(), total_chunks));
+        shards.insert((chunk_index, shard_index), shard.payload.to_vec());
+        if !response_chunks_ready(shards, *total) {
+            return true;
         }
 
-        // Reconstruct each chunk independently
-        let mut reconstructed_chunks: BTreeMap<u16, Vec<u8>> = BTreeMap::new();
+        let Some((shards, total)) = stream.segments.remove(&seq) else {
+            return true;
+        };
+        let exit_enc_pubkey = stream.exit_enc_pubkey;
+        self.stream_segments.remove(assembly_id);
 
-        for chunk_idx in 0..pending.total_chunks {
-            let chunk_shards = chunks_by_index.get(&chunk_idx);
-            let mut shard_data: Vec<Option<Vec<u8>>> = vec![None; TOTAL_SHARDS];
-            let mut shard_size = 0usize;
+        let segment = self.decode_response_payload(&shards, total, &exit_enc_pubkey)
+            .and_then(|data| ResponseSegment::from_bytes(&data).map_err(|_| ClientError::InvalidResponse))
+            .unwrap_or_else(|e| ResponseSegment::Error(e.to_string()));
+        debug!(
+            "Stream request={} decoded segment {} ({} chunks)",
+            hex::encode(&request_id[..8]),
+            seq,
+            total,
+        );
 
-            if let Some(payloads) = chunk_shards {
-                for &(shard_idx, payload) in payloads {
-                    let idx = shard_idx as usize;
-                    if idx < TOTAL_SHARDS {
-                        shard_size = payload.len();
-                        shard_data[idx] = Some(payload.clone());
+        if let Some(stream) = self.pending_streams.get_mut(&request_id) {
+            stream.decoded.insert(seq, segment);
+        }
+        self.advance_stream(&request_id);
+        true
+    }
+
+    /// Hand decoded segments to the caller in order and slide the lookahead
+    /// window. A caller that stops reading holds the window in place: once
+    /// `STREAM_READY_LIMIT` chunks wait, segments stay decoded (at most a
+    /// window's worth) and no further assembly ids are accepted.
+    fn advance_stream(&mut self, request_id: &Id) {
+        loop {
+            // Make room first so a drained caller unblocks held-back segments
+            self.flush_stream(request_id);
+            let Some(next_seq) = self.pending_streams.get(request_id).map(|s| s.next_seq) else {
+                return;
+            };
+            self.take_decoded_segments(request_id);
+            let moved = self.pending_streams.get(request_id).is_some_and(|s| s.next_seq != next_seq);
+            if !moved {
+                return;
+            }
+        }
+    }
+
+    /// Move in-order decoded segments of a stream into `ready` while there
+    /// is room, registering the assembly id each one slides into the window
+    fn take_decoded_segments(&mut self, request_id: &Id) {
+        let Some(stream) = self.pending_streams.get_mut(request_id) else {
+            return;
+        };
+
+        while !stream.finished && stream.ready.len() < STREAM_READY_LIMIT {
+            let Some(segment) = stream.decoded.remove(&stream.next_seq) else {
+                break;
+            };
+            let window_end = stream.next_seq + STREAM_LOOKAHEAD;
+            self.stream_segments.insert(segment_assembly_id(request_id, window_end), (*request_id, window_end));
+            stream.next_seq += 1;
+
+            match segment {
+                ResponseSegment::Head { status, headers } => {
+                    if let Some(tx) = stream.head_tx.take() {
+                        let _ = tx.try_send(Ok((status, headers.into_iter().collect())));
+                    }
+                }
+                ResponseSegment::Body(data) => {
+                    stream.body_bytes += data.len() as u64;
+                    stream.ready.push_back(Ok(data));
+                }
+                ResponseSegment::End { total_bytes } => {
+                    if total_bytes != stream.body_bytes {
+                        warn!(
+                            "Stream request={} ended with {} body bytes, exit sent {}",
+                            hex::encode(&request_id[..8]),
+                            stream.body_bytes,
+                            total_bytes,
+                        );
+                        stream.ready.push_back(Err(ClientError::InvalidResponse));
+                    }
+                    stream.finished = true;
+                }
+                ResponseSegment::Error(msg) => {
+                    let err = ClientError::RequestFailed(msg);
+                    match stream.head_tx.take() {
+                        Some(tx) => {
+                            let _ = tx.try_send(Err(err));
+                        }
+                        None => stream.ready.push_back(Err(err)),
                     }
+                    stream.finished = true;
                 }
             }
+        }
+    }
 
-            let max_len = shard_size * DATA_SHARDS;
-            let chunk_data = self
-                .erasure
-                .decode(&mut shard_data, max_len)
-                .map_err(|e| ClientError::ErasureError(e.to_string()))?;
+    /// Move ready chunks into the caller's channel; drop finished streams
+    fn flush_stream(&mut self, request_id: &Id) {
+        let Some(stream) = self.pending_streams.get_mut(request_id) else {
+            return;
+        };
 
-            reconstructed_chunks.insert(chunk_idx, chunk_data);
+        while let Some(item) = stream.ready.pop_front() {
+            match stream.chunk_tx.try_send(item) {
+                // A reading caller counts as progress for the idle timeout
+                Ok(()) => stream.last_progress = Instant::now(),
+                Err(mpsc::error::TrySendError::Full(item)) => {
+                    // Caller is behind — keep the rest until next poll
+                    stream.ready.push_front(item);
+                    return;
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => {
+                    // Caller dropped the ResponseStream
+                    stream.ready.clear();
+                    stream.finished = true;
+                }
+            }
         }
 
-        // Reassemble
-        let total_possible = reconstructed_chunks.values().map(|c| c.len()).sum();
-        let framed_data = reassemble(&reconstructed_chunks, pending.total_chunks, total_possible)
-            .map_err(|e| ClientError::ErasureError(e.to_string()))?;
+        if stream.finished {
+            let body_bytes = stream.body_bytes;
+            self.pending_streams.remove(request_id);
+            self.stream_segments.retain(|_, (id, _)| id != request_id);
+            let mut state = self.state.write();
+            state.stats.bytes_received += body_bytes;
+            state.throughput.record(TrafficClass::Client, 0, body_bytes);
+        }
+    }
 
-        // Strip length-prefixed framing (4-byte LE u32 original length)
-        if framed_data.len() < 4 {
-            return Err(ClientError::InvalidResponse);
+    /// Retry held-back chunks and segments, and fail streams that stopped
+    /// making progress
+    fn poll_response_streams(&mut self) {
+        if self.pending_streams.is_empty() {
+            return;
         }
-        let original_len = u32::from_le_bytes(
-            framed_data[..4].try_into().unwrap()
-        ) as usize;
-        if framed_data.len() < 4 + original_len {
-            return Err(ClientError::InvalidResponse);
+        let timeout = self.config.request_timeout;
+        let ids: Vec<Id> = self.pending_streams.keys().copied().collect();
+        for request_id in ids {
+            if let Some(stream) = self.pending_streams.get_mut(&request_id) {
+                if !stream.finished && stream.last_progress.elapsed() > timeout {
+                    warn!("Stream request={} idle timeout", hex::encode(&request_id[..8]));
+                    match stream.head_tx.take() {
+                        Some(tx) => {
+                            let _ = tx.try_send(Err(ClientError::Timeout));
+                        }
+                        None => stream.ready.push_back(Err(ClientError::Timeout)),
+                    }
+                    stream.finished = true;
+                }
+            }
+            self.advance_stream(&request_id);
         }
-        let encrypted_data = &framed_data[4..4 + original_len];
-
-        // Decrypt the response using the exit's encryption pubkey stored at request time
-        let data = craftec_crypto::decrypt_from_sender(
-            &pending.exit_enc_pubkey,
-            &self.encryption_keypair.secret_key_bytes(),
-            encrypted_data,
-        ).map_err(|e| ClientError::CryptoError(format!("Response decrypt failed: {}", e)))?;
-
-        TunnelResponse::from_bytes(&data)
     }
 
     // =========================================================================
@@ -3094,12 +6387,122 @@ impl CraftNetNode {
         self.tunnel_burst_rx = Some(rx);
     }
 
+    // =========================================================================
+    // WebSocket sessions
+    // =========================================================================
+
+    /// Open a WebSocket to `url` (`ws://` or `wss://`) through the selected exit.
+    ///
+    /// The exit performs the upgrade against the origin and bridges the
+    /// session; see [`crate::websocket`]. Messages only flow while the
+    /// node's event loop runs (`run` or `poll_once`).
+    pub async fn open_websocket(&mut self, url: &str) -> Result<WebSocket> {
+        self.open_websocket_with(url, Vec::new(), Vec::new()).await
+    }
+
+    /// [`Self::open_websocket`] with extra handshake `headers` and offered
+    /// subprotocols
+    pub async fn open_websocket_with(
+        &mut self,
+        url: &str,
+        headers: Vec<(String, String)>,
+        protocols: Vec<String>,
+    ) -> Result<WebSocket> {
+        if !self.capabilities.is_client() || !self.connected {
+            return Err(ClientError::NotConnected);
+        }
+        if !url.starts_with("ws://") && !url.starts_with("wss://") {
+            return Err(ClientError::RequestFailed(format!("not a WebSocket URL: {}", url)));
+        }
+
+        let session_id = crate::path::random_id();
+        let (response_tx, mut response_rx) = mpsc::channel(1);
+        self.handle_websocket_burst(WebSocketBurst {
+            request: WsRequest {
+                session_id,
+                action: WsAction::Open { url: url.to_string(), headers, protocols },
+            },
+            response_tx,
+        });
+
+        // Drive the node until the exit answers the upgrade
+        let timeout = self.config.request_timeout;
+        let reply = tokio::time::timeout(timeout, async {
+            loop {
+                tokio::select! {
+                    reply = response_rx.recv() => break reply,
+                    _ = self.poll_once() => {}
+                }
+            }
+        })
+        .await
+        .map_err(|_| ClientError::Timeout)?
+        .ok_or(ClientError::Timeout)??;
+
+        match WsReply::from_bytes(&reply).map_err(|_| ClientError::InvalidResponse)? {
+            WsReply::Opened { protocol } => {
+                info!("WebSocket session {} open to {}", hex::encode(&session_id[..8]), url);
+                Ok(WebSocket::spawn(session_id, protocol, self.websocket_burst_tx.clone(), timeout))
+            }
+            WsReply::Error(e) => Err(ClientError::ConnectionFailed(e)),
+            WsReply::Messages { .. } => Err(ClientError::InvalidResponse),
+        }
+    }
+
     /// Handle a tunnel burst from the SOCKS5 server
     async fn handle_tunnel_burst(&mut self, burst: TunnelBurst) {
+        let payload = crate::tunnel::tunnel_payload(&burst.metadata, &burst.data);
+        self.send_session_burst(
+            PAYLOAD_MODE_TUNNEL,
+            burst.metadata.session_id,
+            payload,
+            burst.data.len(),
+            burst.metadata.is_close,
+            burst.response_tx,
+        );
+    }
+
+    /// Handle a WebSocket burst from an open [`WebSocket`]
+    fn handle_websocket_burst(&mut self, burst: WebSocketBurst) {
+        let payload = match burst.request.to_bytes() {
+            Ok(payload) => payload,
+            Err(e) => {
+                let _ = burst.response_tx.try_send(Err(ClientError::RequestFailed(e.to_string())));
+                return;
+            }
+        };
+        // The exit meters what it forwards: the handshake, then message bytes
+        let (metered_bytes, is_close) = match &burst.request.action {
+            WsAction::Open { .. } => (payload.len(), false),
+            WsAction::Send(messages) => (messages.iter().map(WsMessage::len).sum(), false),
+            WsAction::Close(_) => (0, true),
+        };
+        self.send_session_burst(
+            PAYLOAD_MODE_WEBSOCKET,
+            burst.request.session_id,
+            payload,
+            metered_bytes,
+            is_close,
+            burst.response_tx,
+        );
+    }
+
+    /// Send one burst of a long-lived exit session (TCP tunnel or
+    /// WebSocket) to the selected exit. The raw response bytes go to
+    /// `response_tx`.
+    fn send_session_burst(
+        &mut self,
+        mode: u8,
+        session_id: Id,
+        payload: Vec<u8>,
+        metered_bytes: usize,
+        is_close: bool,
+        response_tx: mpsc::Sender<std::result::Result<Vec<u8>, ClientError>>,
+    ) {
         let exit_info = match &self.selected_exit {
             Some(e) => e.clone(),
             None => {
-                let _ = burst.response_tx.try_send(Err(ClientError::NoExitNodes));
+                let _ = response_tx.try_send(Err(ClientError::NoExitNodes));
                 return;
             }
         };
@@ -3116,50 +6519,66 @@ impl CraftNetNode {
         };
 
         // Build topology-based paths and LeaseSet
-        let (paths, first_hops, lease_set) = match self.build_request_paths(&exit_hop) {
+        let (paths, first_hops, lease_set) = match self.build_request_paths(&exit_hop, &RequestProfile::interactive()) {
             Ok(v) => v,
             Err(e) => {
-                let _ = burst.response_tx.try_send(Err(e));
+                let _ = response_tx.try_send(Err(e));
                 return;
             }
         };
 
-        let result = crate::tunnel::build_tunnel_shards(
-            &burst.metadata,
-            &burst.data,
+        let quota_tokens = match exit_info.quota.as_ref().filter(|_| self.config.quota_tokens) {
+            Some(terms) => self.quota_wallet.tokens_for_frame(terms, session_id, metered_bytes, is_close),
+            None => Vec::new(),
+        };
+
+        let result = crate::shard_builder::build_onion_shards_with_tokens(
+            mode,
+            payload,
+            self.encryption_keypair.public_key_bytes(), // response_enc_pubkey — X25519 key for response encryption
             &self.keypair,
             &exit_hop,
             &paths,
             &lease_set,
-            self.encryption_keypair.public_key_bytes(), // response_enc_pubkey — X25519 key for response encryption
             self.keypair.public_key_bytes(), // pool_pubkey — always user pubkey (tracks subscription or free usage)
+            0,
+            quota_tokens,
         );
 
         let (request_id, shards) = match result {
             Ok(v) => v,
             Err(e) => {
-                let _ = burst.response_tx.try_send(Err(e));
+                let _ = response_tx.try_send(Err(e));
                 return;
             }
         };
 
         debug!(
-            "Tunnel burst: {} shards for session {}, request {} ({} hops)",
+            "Session burst (mode {}): {} shards for session {}, request {} ({} hops)",
+            mode,
             shards.len(),
-            hex::encode(&burst.metadata.session_id[..8]),
+            hex::encode(&session_id[..8]),
             hex::encode(&request_id[..8]),
             self.config.hop_mode.min_relays()
         );
 
+        {
+            let burst_bytes: u64 = shards.iter().map(|s| s.payload.len() as u64).sum();
+            let mut state = self.state.write();
+            state.stats.bytes_sent += burst_bytes;
+            state.throughput.record(TrafficClass::Client, burst_bytes, 0);
+        }
+
         // Store pending tunnel request
         self.pending_tunnel.insert(
             request_id,
             PendingTunnelRequest {
                 shards: HashMap::new(),
                 total_chunks: 0,
-                response_tx: burst.response_tx,
+                response_tx,
                 exit_enc_pubkey: exit_hop.encryption_pubkey,
                 sent_at: std::time::Instant::now(),
+                session_id,
             },
         );
 
@@ -3168,15 +6587,21 @@ impl CraftNetNode {
             if let Some(exit_pid) = exit_peer_id {
                 if let Some(ref tx) = self.outbound_tx {
                     for shard in shards {
-                        let _ = tx.try_send(OutboundShard { peer: exit_pid, shard });
+                        let _ = tx.try_send(OutboundShard { peer: exit_pid, shard: self.padded(shard) });
                     }
                 }
             }
         } else {
+            if let Some(ref mut cover) = self.cover_traffic {
+                let now = Instant::now();
+                for target in &first_hops {
+                    cover.record_activity(*target, now);
+                }
+            }
             if let Some(ref tx) = self.outbound_tx {
                 for (i, shard) in shards.into_iter().enumerate() {
                     let target = first_hops[i % first_hops.len()];
-                    let _ = tx.try_send(OutboundShard { peer: target, shard });
+                    let _ = tx.try_send(OutboundShard { peer: target, shard: self.padded(shard) });
                 }
             }
         }
@@ -3210,7 +6635,7 @@ impl CraftNetNode {
         if pending.total_chunks == 0 {
             pending.total_chunks = total_chunks;
         }
-        pending.shards.insert((chunk_index, shard_index), shard.payload.clone());
+        pending.shards.insert((chunk_index, shard_index), shard.payload.to_vec());
 
         // Check if all chunks have enough shards
         if !self.all_tunnel_response_chunks_ready(assembly_id) {
@@ -3222,6 +6647,12 @@ impl CraftNetNode {
 
             match self.reconstruct_tunnel_response(&pending) {
                 Ok(data) => {
+                    self.quota_wallet.charge_session(&pending.session_id, data.len());
+                    {
+                        let mut state = self.state.write();
+                        state.stats.bytes_received += data.len() as u64;
+                        state.throughput.record(TrafficClass::Client, 0, data.len() as u64);
+                    }
                     let _ = response_tx.try_send(Ok(data));
                 }
                 Err(e) => {
@@ -3255,8 +6686,6 @@ impl CraftNetNode {
 
     /// Reconstruct tunnel response as raw bytes (no HTTP parsing)
     fn reconstruct_tunnel_response(&self, pending: &PendingTunnelRequest) -> Result<Vec<u8>> {
-        use std::collections::BTreeMap;
-
         let mut chunks_by_index: HashMap<u16, Vec<(u8, &Vec<u8>)>> = HashMap::new();
         for (&(chunk_idx, shard_idx), payload) in &pending.shards {
             chunks_by_index
@@ -3325,7 +6754,9 @@ impl CraftNetNode {
         if self.capabilities.is_service_node() {
             self.poll_compression_result();
             self.try_compress();
+            self.flush_proof_outbox();
         }
+        self.poll_proof_jobs();
 
         let has_rx = self.swarm_evt_rx.is_some();
         if !has_rx {
@@ -3365,9 +6796,15 @@ impl CraftNetNode {
                     self.handle_tunnel_burst(burst).await;
                 }
             }
+            Some(burst) = self.websocket_burst_rx.recv() => {
+                self.handle_websocket_burst(burst);
+            }
             _ = tokio::time::sleep(Duration::from_millis(1)) => {}
         }
 
+        // Relayed shards held back by tier scheduling or a full writer channel
+        self.flush_relay_queues();
+
         // Accept inbound streams from the bridging task.
         // This runs outside the select! so it's processed every poll_once() cycle,
         // regardless of which select! branch won above.
@@ -3394,9 +6831,42 @@ impl CraftNetNode {
             // stream connections will be picked up at the next heartbeat cycle.
             let _ = newly_opened;
         }
+        let draining = self.stream_manager.as_mut()
+            .map(|sm| sm.take_draining_peers())
+            .unwrap_or_default();
+        if !draining.is_empty() {
+            self.handle_draining_peers(draining);
+        }
+        let versions = self.stream_manager.as_mut()
+            .map(|sm| sm.take_peer_versions())
+            .unwrap_or_default();
+        if !versions.is_empty() {
+            self.handle_peer_versions(versions);
+        }
+        let chain_acks = self.stream_manager.as_mut()
+            .map(|sm| sm.take_chain_acks())
+            .unwrap_or_default();
+        if !chain_acks.is_empty() {
+            self.handle_chain_acks(chain_acks);
+        }
 
         // Collect completed exit task results (restore handler, push response shards to outbound channel).
         self.drain_exit_task_results();
+        self.drain_exit_stream_segments();
+
+        // Records pulled from registry sync peers
+        self.drain_registry_syncs();
+
+        // Proofs gossip couldn't deliver, and proofs relays handed us directly
+        self.drain_undelivered_proofs();
+        self.drain_proof_submissions();
+
+        // Missing chain segments (aggregator mode)
+        self.maybe_backfill_proof_gaps();
+        self.drain_backfills();
+
+        // Deliver held-back stream chunks and expire idle streams (client mode)
+        self.poll_response_streams();
 
         // Priority-ordered stream shard processing:
         // 1. Drain high-priority (subscribed peers) first
@@ -3409,6 +6879,14 @@ impl CraftNetNode {
 
         // Batch-flush buffered receipts to disk (one file open/close per poll cycle)
         self.flush_receipts();
+        if let Some(ref mut spill) = self.receipt_spill {
+            if let Err(e) = spill.flush() {
+                warn!("Failed to write spilled receipts: {}", e);
+            }
+        }
+
+        // Cover shards for idle circuits (no-op unless enabled)
+        self.maybe_send_cover_traffic();
 
         // Auto-maintenance: run periodic housekeeping on a timer so callers
         // of poll_once() don't need to drive maintenance separately.
@@ -3417,6 +6895,69 @@ impl CraftNetNode {
             self.run_maintenance();
             self.run_async_maintenance().await;
         }
+
+        if self.last_readiness_check.elapsed() >= READINESS_CHECK_INTERVAL {
+            self.last_readiness_check = Instant::now();
+            self.supervise_readiness();
+        }
+    }
+
+    /// Feed readiness to the reconnect supervisor and run an attempt when due
+    fn supervise_readiness(&mut self) {
+        if !self.capabilities.is_client() || self.is_draining() || self.reconnect.is_none() {
+            return;
+        }
+        let ready = self.is_ready();
+        let Some(event) = self.reconnect.as_mut().and_then(|sup| sup.update(ready, Instant::now())) else {
+            return;
+        };
+        match &event {
+            ReconnectEvent::Lost => warn!("Tunnel lost readiness, reconnecting"),
+            ReconnectEvent::Reconnecting { attempt, retry_in_ms } => {
+                info!("Reconnect attempt {} (next in {}ms)", attempt, retry_in_ms);
+                self.reconnect_attempt();
+            }
+            ReconnectEvent::Reconnected { attempts, downtime_ms } => {
+                info!("Reconnected after {} attempt(s), {}ms down", attempts, downtime_ms);
+            }
+        }
+        self.reconnect_events.push(event);
+    }
+
+    /// One reconnect attempt: re-bootstrap, rediscover, re-select the exit
+    /// and reopen the gateway stream
+    fn reconnect_attempt(&mut self) {
+        self.last_bootstrap_check = None;
+        self.maybe_reconnect_bootstrap();
+        self.discover_exits();
+        self.discover_relays();
+
+        let exit_usable = self.selected_exit.as_ref().is_some_and(|e| {
+            self.exit_nodes.get(&e.pubkey).is_some_and(|s| s.online)
+        });
+        if !exit_usable {
+            self.selected_exit = None;
+        }
+        if self.selected_exit.is_none() {
+            self.select_best_exit();
+        }
+
+        let Some(our_bytes) = self.local_peer_id.map(|p| p.to_bytes()) else { return };
+        if let Some((gateway, _)) = self.select_gateway_relay(&our_bytes) {
+            if let Some(ref mut sm) = self.stream_manager {
+                sm.ensure_opening(gateway);
+            }
+        }
+    }
+
+    /// Drain reconnect progress since the last call (for IPC events)
+    pub fn take_reconnect_events(&mut self) -> Vec<ReconnectEvent> {
+        std::mem::take(&mut self.reconnect_events)
+    }
+
+    /// Current reconnect attempt while the tunnel is down (None when healthy)
+    pub fn reconnect_attempt_count(&self) -> Option<u32> {
+        self.reconnect.as_ref().and_then(|sup| sup.attempt())
     }
 
     /// Drain inbound shards from stream channels in priority order.
@@ -3550,6 +7091,13 @@ impl CraftNetNode {
         }
     }
 
+    /// Tier a pool's traffic is priced at (None = free or unknown)
+    fn pool_tier(&self, pool: &PublicKey) -> Option<SubscriptionTier> {
+        self.subscription_cache
+            .get(pool)
+            .and_then(|entry| SubscriptionTier::from_u8(entry.tier))
+    }
+
     /// Get a peer's subscription tier (0=free, 1+=subscribed).
     fn get_peer_tier(&self, _peer: &PeerId) -> u8 {
         // Both inbound channels are fully drained each poll_once() cycle,
@@ -3562,6 +7110,7 @@ impl CraftNetNode {
     /// Normally called automatically every 30s by `run()`. Call manually
     /// when using `poll_once()` in a custom event loop.
     pub fn run_maintenance(&mut self) {
+        self.maybe_refresh_geoip();
         self.maybe_reannounce_exit();
         self.maybe_reannounce_peer();
         self.maybe_send_heartbeat();
@@ -3569,13 +7118,21 @@ impl CraftNetNode {
         self.discover_exits();
         self.cleanup_stale_exits();
         self.maybe_reannounce_relay();
+        self.maybe_rotate_onion_key();
         self.maybe_send_relay_heartbeat();
         self.discover_relays();
+        self.maybe_sync_registry();
+        self.maybe_save_record_cache();
+        self.maybe_save_gossip_dedup();
+        self.maybe_save_credit_ledger();
         self.check_relay_timeouts();
         self.cleanup_stale_relays();
         self.maybe_reconnect_bootstrap();
+        self.maybe_fetch_network_params();
+        self.maybe_fetch_aggregator_registry();
         self.update_topology();
         self.refresh_and_evict_tunnels();
+        self.settle_chain_acks();
 
         // Clear stale exit handler assemblies and zombie tunnel sessions
         {
@@ -3586,6 +7143,13 @@ impl CraftNetNode {
         }
     }
 
+    /// Settle request shard chains whose acks stopped coming
+    fn settle_chain_acks(&mut self) {
+        for outcome in self.chain_acks.expire(Instant::now()) {
+            self.apply_chain_outcome(outcome);
+        }
+    }
+
     /// Reconnect to bootstrap peers if we have lost all connections to them
     fn maybe_reconnect_bootstrap(&mut self) {
         let should_check = self.last_bootstrap_check
@@ -3602,11 +7166,8 @@ impl CraftNetNode {
         if !connected_to_bootstrap && !self.bootstrap_peer_ids.is_empty() {
             warn!("Lost connection to all bootstrap peers, reconnecting...");
 
-            let bootstrap_peers = if !self.config.bootstrap_peers.is_empty() {
-                self.config.bootstrap_peers.clone()
-            } else {
-                craftnet_network::default_bootstrap_peers()
-            };
+            let mut bootstrap_peers = self.default_or_configured_bootstrap_peers();
+            bootstrap_peers.extend(self.network_params.iter().flat_map(|p| p.bootstrap_peers()));
 
             for (peer_id, addr) in &bootstrap_peers {
                 if self.swarm_cmd_tx.is_some() {
@@ -3620,6 +7181,20 @@ impl CraftNetNode {
         }
     }
 
+    /// Explicitly configured bootstrap peers, or else the built-in defaults
+    /// merged with the last DNS seed resolution (expired or not — it is
+    /// refreshed on the next `connect_bootstrap`)
+    fn default_or_configured_bootstrap_peers(&self) -> Vec<(PeerId, Multiaddr)> {
+        if !self.config.bootstrap_peers.is_empty() {
+            return self.config.bootstrap_peers.clone();
+        }
+        let seeded = self.config.dns_seed.as_deref()
+            .and_then(|domain| self.dns_seed_cache.last_known(domain))
+            .map(<[_]>::to_vec)
+            .unwrap_or_default();
+        craftnet_network::merge_bootstrap_peers([craftnet_network::default_bootstrap_peers(), seeded])
+    }
+
     /// Register with circuit relay (stubbed out for shared swarm)
     fn register_with_circuit_relay(&mut self) {
         // Circuit relay registration is handled by the shared swarm coordinator
@@ -3633,10 +7208,17 @@ impl CraftNetNode {
             self.reconcile_aggregator_state().await;
             self.aggregator_reconciled = true;
         }
+        self.probe_selected_exit().await;
         self.maybe_verify_subscriptions().await;
+        self.maybe_refresh_pool_claims().await;
         self.maybe_post_distributions().await;
         self.save_aggregator_state();
         self.flush_aggregator_history();
+        self.maybe_apply_aggregator_retention();
+        self.report_aggregator_anomalies();
+        if let Some(ref mut aggregator) = self.aggregator {
+            aggregator.expire_disputes(self.clock.unix_now());
+        }
     }
 
     /// Refresh tunnel registrations for all connected peers and evict expired ones.
@@ -3645,10 +7227,7 @@ impl CraftNetNode {
     fn refresh_and_evict_tunnels(&mut self) {
         if let Some(local_pid) = self.local_peer_id {
             let connected_peers: Vec<PeerId> = self.connected_peers.iter().cloned().collect();
-            let expires_at = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_secs() + 300;
+            let expires_at = self.clock.unix_now() + 300;
             let mut state = self.state.write();
             if let Some(ref mut relay_handler) = state.relay_handler {
                 for peer in connected_peers {
@@ -3672,7 +7251,7 @@ impl CraftNetNode {
         use crate::path::TopologyRelay;
 
         // Add relays that aren't already in topology (from DHT discovery)
-        for status in self.relay_nodes.values().filter(|s| s.online) {
+        for status in self.relay_nodes.values().filter(|s| s.online && !self.is_outdated_peer(&s.peer_id)) {
             let peer_bytes = status.peer_id.to_bytes();
             if self.topology.get_relay(&peer_bytes).is_none() {
                 // Only add if not already present (don't overwrite gossip data)
@@ -3717,8 +7296,25 @@ impl CraftNetNode {
     /// - `paths`: onion paths for each shard (relay hops + exit)
     /// - `first_hop_targets`: PeerId of the first relay for each path
     /// - `lease_set`: gateway info for response routing
-    fn build_request_paths(&self, exit_hop: &PathHop) -> Result<(Vec<crate::path::OnionPath>, Vec<PeerId>, craftnet_core::lease_set::LeaseSet)> {
-        use crate::path::{PathSelector, OnionPath, random_id};
+    ///
+    /// Gateways whose advertised capacity suits `profile` are preferred.
+    fn build_request_paths(
+        &self,
+        exit_hop: &PathHop,
+        profile: &RequestProfile,
+    ) -> Result<(Vec<crate::path::OnionPath>, Vec<PeerId>, craftnet_core::lease_set::LeaseSet)> {
+        self.build_request_paths_avoiding(exit_hop, &HashSet::new(), profile)
+    }
+
+    /// [`Self::build_request_paths`], preferring gateways not in `avoid`
+    /// (used to retry a request over a different circuit)
+    fn build_request_paths_avoiding(
+        &self,
+        exit_hop: &PathHop,
+        avoid: &HashSet<PeerId>,
+        profile: &RequestProfile,
+    ) -> Result<(Vec<crate::path::OnionPath>, Vec<PeerId>, craftnet_core::lease_set::LeaseSet)> {
+        use crate::path::{build_gateway_paths, OnionPath, random_id};
         use craftnet_core::lease_set::{LeaseSet, Lease};
 
         let our_peer_id = self.local_peer_id
@@ -3728,10 +7324,10 @@ impl CraftNetNode {
         // Direct mode (0 hops): client → exit with no relays.
         // Client puts itself in the LeaseSet as the "gateway" so exit can
         // send response shards directly back to us.
-        if self.config.hop_mode == HopMode::Direct {
+        if self.request_hop_mode() == HopMode::Direct {
             let lease = Lease {
                 gateway_peer_id: our_bytes.to_vec(),
-                gateway_encryption_pubkey: self.encryption_keypair.public_key_bytes(),
+                gateway_encryption_pubkey: self.response_keypair().public_key_bytes(),
                 tunnel_id: [0u8; 32], // sentinel: direct mode
                 expires_at: u64::MAX,
             };
@@ -3752,75 +7348,40 @@ impl CraftNetNode {
             return Ok((vec![path], vec![], lease_set));
         }
 
-        let extra_hops = self.config.hop_mode.extra_hops() as usize;
+        let extra_hops = self.request_hop_mode().extra_hops() as usize;
 
         // Select all eligible gateway relays. The primary gateway is the first
         // onion hop for this request's shards. Additional gateways are included
         // in the LeaseSet so the exit can pick any for response routing.
-        //
-        // Path: client → gateway → [extra_hops relays] → exit
-        let all_gateways = self.select_all_gateway_relays(&our_bytes);
-        let (gw_peer_id, gw_hop) = all_gateways.first().cloned()
+        let mut all_gateways = self.select_all_gateway_relays(&our_bytes, profile);
+        // Stable: keeps health order within the preferred and avoided groups
+        all_gateways.sort_by_key(|(pid, _)| avoid.contains(pid));
+        let gateway_hops: Vec<PathHop> = all_gateways.into_iter().map(|(_, hop)| hop).collect();
+
+        let (paths, lease_set) = build_gateway_paths(
+            &self.topology,
+            &our_bytes,
+            &gateway_hops,
+            extra_hops,
+            exit_hop,
+            &self.circuit_geo_filter(),
+        )?;
+        // The primary gateway is the one the geo constraints allowed first
+        let gw_peer_id = lease_set.leases.first()
+            .and_then(|lease| PeerId::from_bytes(&lease.gateway_peer_id).ok())
             .ok_or(ClientError::RequestFailed(
                 "No gateway relay available (not connected to any relay)".to_string(),
             ))?;
 
-        let tunnel_id = derive_tunnel_id(&our_peer_id, &gw_peer_id);
-
         info!(
             "Path built: client={} gateway={} tunnel_id={} enc_key={} (lease_set has {} gateways)",
             our_peer_id,
             gw_peer_id,
-            hex::encode(&tunnel_id[..8]),
-            hex::encode(&gw_hop.encryption_pubkey[..8]),
-            all_gateways.len(),
+            hex::encode(&lease_set.leases[0].tunnel_id[..8]),
+            hex::encode(&lease_set.leases[0].gateway_encryption_pubkey[..8]),
+            lease_set.leases.len(),
         );
 
-        // Build LeaseSet with all available gateways
-        let leases: Vec<Lease> = all_gateways.iter().map(|(pid, hop)| {
-            let tid = derive_tunnel_id(&our_peer_id, pid);
-            Lease {
-                gateway_peer_id: pid.to_bytes(),
-                gateway_encryption_pubkey: hop.encryption_pubkey,
-                tunnel_id: tid,
-                expires_at: u64::MAX,
-            }
-        }).collect();
-
-        let lease_set = LeaseSet {
-            session_id: random_id(),
-            leases,
-        };
-
-        // Build paths: gateway is always the first onion hop
-        let gw_bytes = gw_peer_id.to_bytes();
-
-        if extra_hops == 0 {
-            // Single hop: path = [gateway] → exit (1 onion hop)
-            let path = OnionPath {
-                hops: vec![gw_hop],
-                exit: exit_hop.clone(),
-            };
-            return Ok((vec![path], vec![gw_peer_id], lease_set));
-        }
-
-        // Multi-hop: select additional relay hops after gateway
-        // entry_peer = gateway, so first extra relay must be connected to gateway
-        let extra_paths = PathSelector::select_diverse_paths(
-            &self.topology,
-            extra_hops,
-            exit_hop,
-            craftnet_erasure::TOTAL_SHARDS,
-            Some(&gw_bytes),
-        )?;
-
-        // Prepend gateway to each path
-        let paths: Vec<OnionPath> = extra_paths.into_iter().map(|p| {
-            let mut hops = vec![gw_hop.clone()];
-            hops.extend(p.hops);
-            OnionPath { hops, exit: p.exit }
-        }).collect();
-
         // first_hops is always the gateway for all paths
         let first_hops = vec![gw_peer_id; paths.len()];
 
@@ -3913,22 +7474,78 @@ impl CraftNetNode {
         None
     }
 
+    /// `NodeConfig::circuit_geo` with the location of every known relay
+    /// and exit: as announced, or looked up in the GeoIP database by the
+    /// address in its record
+    fn circuit_geo_filter(&self) -> GeoFilter {
+        let relays = self.relay_nodes.values().map(|s| {
+            (s.peer_id.to_bytes(), &s.info.address, &s.info.country_code, &s.info.as_number)
+        });
+        let exits = self.exit_nodes.values().filter_map(|s| {
+            Some((s.peer_id?.to_bytes(), &s.info.address, &s.info.country_code, &s.info.as_number))
+        });
+        let locations = relays
+            .chain(exits)
+            .filter_map(|(peer_id, address, country, asn)| {
+                let looked_up = || {
+                    let ip = public_ip_of(address)?;
+                    self.geoip.as_ref()?.lookup(ip)
+                };
+                let (country, asn) = match (country, asn) {
+                    (Some(_), Some(_)) => (country.clone(), asn.clone()),
+                    _ => {
+                        let record = looked_up().unwrap_or_default();
+                        (country.clone().or(record.country_code), asn.clone().or(record.as_number))
+                    }
+                };
+                (country.is_some() || asn.is_some())
+                    .then(|| (peer_id, HopLocation::new(country.as_deref(), asn.as_deref())))
+            })
+            .collect();
+        GeoFilter { constraints: self.config.circuit_geo.clone(), locations }
+    }
+
+    /// Country and AS of this node's public IP in the GeoIP database
+    fn self_geoip(&self) -> Option<GeoIpRecord> {
+        let ip = self
+            .config
+            .public_ip
+            .or_else(|| public_ip_of(&self.config.listen_addr.to_string()))?;
+        self.geoip.as_ref()?.lookup(ip)
+    }
+
+    /// Load the GeoIP database, and reload it when the file changed, at
+    /// most once per `GEOIP_REFRESH_INTERVAL`
+    fn maybe_refresh_geoip(&mut self) {
+        if self.last_geoip_refresh.is_some_and(|t| t.elapsed() < GEOIP_REFRESH_INTERVAL) {
+            return;
+        }
+        let Some(ref mut geoip) = self.geoip else { return };
+        self.last_geoip_refresh = Some(Instant::now());
+        match geoip.refresh() {
+            Ok(true) => info!("Loaded GeoIP database {}", geoip.path().display()),
+            Ok(false) => {}
+            Err(e) => warn!("GeoIP database unavailable: {}", e),
+        }
+    }
+
     /// Select ALL eligible gateway relays for the LeaseSet.
     ///
     /// Returns a list of `(PeerId, PathHop)` sorted: topology-confirmed first,
     /// then fallback relays. The first entry is the primary gateway for this
     /// request. Additional entries go into the LeaseSet so the exit can pick
     /// any for response routing.
-    fn select_all_gateway_relays(&self, our_bytes: &[u8]) -> Vec<(PeerId, PathHop)> {
+    fn select_all_gateway_relays(&self, our_bytes: &[u8], profile: &RequestProfile) -> Vec<(PeerId, PathHop)> {
         let sm = self.stream_manager.as_ref();
 
         let mut results = Vec::new();
         let mut seen = HashSet::new();
 
-        // Sort relays by health score (lower = healthier) so healthier
-        // relays appear first in the LeaseSet
+        // Sort relays by capacity fit for the request, then health score
+        // (lower = healthier) so suitable, healthier relays appear first
+        // in the LeaseSet
         let mut sorted_relays: Vec<_> = self.relay_nodes.values().collect();
-        sorted_relays.sort_by_key(|s| s.score);
+        sorted_relays.sort_by_key(|s| (s.capacity_fit(profile), s.score));
 
         // First pass: topology-confirmed relays with stream (best quality)
         for relay_status in &sorted_relays {
@@ -4029,45 +7646,432 @@ impl CraftNetNode {
                     }
                 }
 
-                // Handle tunnel bursts from SOCKS5 proxy
-                burst = async {
-                    if let Some(ref mut rx) = self.tunnel_burst_rx {
-                        rx.recv().await
-                    } else {
-                        // No SOCKS5 server configured — pend forever
-                        std::future::pending().await
+                // Handle tunnel bursts from SOCKS5 proxy
+                burst = async {
+                    if let Some(ref mut rx) = self.tunnel_burst_rx {
+                        rx.recv().await
+                    } else {
+                        // No SOCKS5 server configured — pend forever
+                        std::future::pending().await
+                    }
+                } => {
+                    if let Some(burst) = burst {
+                        self.handle_tunnel_burst(burst).await;
+                    }
+                }
+
+                // Handle bursts of open WebSocket sessions
+                Some(burst) = self.websocket_burst_rx.recv() => {
+                    self.handle_websocket_burst(burst);
+                }
+
+                // Periodic maintenance tasks
+                _ = maintenance_interval.tick() => {
+                    self.maybe_refresh_geoip();
+                    self.maybe_reannounce_exit();
+                    self.maybe_reannounce_peer();
+                    self.maybe_send_heartbeat();
+                    self.check_exit_timeouts();
+                    self.discover_exits();
+                    self.cleanup_stale_exits();
+                    // Relay maintenance
+                    self.maybe_reannounce_relay();
+                    self.maybe_send_relay_heartbeat();
+                    self.discover_relays();
+                    self.check_relay_timeouts();
+                    self.cleanup_stale_relays();
+                    // Subscription verification
+                    self.maybe_verify_subscriptions().await;
+                    // Distribution proving + posting
+                    self.poll_proof_jobs();
+                    self.maybe_post_distributions().await;
+                    // NAT traversal
+                    self.maybe_reconnect_bootstrap();
+                    // Cover traffic
+                    self.maybe_send_cover_traffic();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Registry shards queried per discovery round once some nodes are known
+    const REGISTRY_SHARDS_PER_DISCOVERY: u8 = 4;
+
+    /// How often to delta-sync the registries from a connected peer
+    const REGISTRY_SYNC_INTERVAL: Duration = Duration::from_secs(60);
+
+    /// How often a changed record cache is written to disk
+    const RECORD_CACHE_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+    /// Replay cached exit/relay records so an exit can be picked before
+    /// the first DHT query returns.
+    ///
+    /// Fresh records are applied as if just fetched. Stale ones are checked
+    /// as of their signing time (signature and binding, not age), used
+    /// meanwhile, and looked up again in the DHT.
+    fn warm_from_record_cache(&mut self) {
+        let now = self.clock.unix_now();
+        let (mut fresh, mut stale) = (0, 0);
+        for (key, record, state) in self.record_cache.entries_at(now) {
+            let raw = record.to_bytes();
+            match state {
+                CachedRecordState::Fresh => {
+                    self.apply_dht_record(&key, &raw);
+                    fresh += 1;
+                }
+                CachedRecordState::Stale => {
+                    match self.record_validators.validate_at(&key, &raw, record.timestamp) {
+                        Ok(body) => self.use_dht_record(&key, &body),
+                        Err(e) => {
+                            debug!("Dropping cached record {}: {}", String::from_utf8_lossy(&key), e);
+                            continue;
+                        }
+                    }
+                    self.send_swarm_cmd(craftec_network::SharedSwarmCommand::GetRecordSecondary(
+                        libp2p::kad::RecordKey::new(&key),
+                    ));
+                    stale += 1;
+                }
+            }
+        }
+        if fresh + stale > 0 {
+            info!("Loaded {} fresh and {} stale exit/relay records from cache (revalidating stale)", fresh, stale);
+        }
+    }
+
+    /// Write the record cache if it changed, at most once per interval
+    fn maybe_save_record_cache(&mut self) {
+        if !self.record_cache.is_dirty()
+            || self.last_record_cache_save.is_some_and(|t| t.elapsed() < Self::RECORD_CACHE_SAVE_INTERVAL)
+        {
+            return;
+        }
+        self.record_cache.save();
+        self.last_record_cache_save = Some(std::time::Instant::now());
+    }
+
+    /// Write the gossip dedup cache if it changed, at most once per
+    /// interval, so a crash doesn't replay every recent message
+    fn maybe_save_gossip_dedup(&mut self) {
+        if !self.gossip_dedup.is_dirty()
+            || self.last_gossip_dedup_save.is_some_and(|t| t.elapsed() < Self::RECORD_CACHE_SAVE_INTERVAL)
+        {
+            return;
+        }
+        self.gossip_dedup.save();
+        self.last_gossip_dedup_save = Some(std::time::Instant::now());
+    }
+
+    /// Latest network parameters published by the maintainers
+    pub fn network_params(&self) -> Option<&NetworkParameters> {
+        self.network_params.as_ref()
+    }
+
+    /// This binary's build manifest checked against the canonical ones in
+    /// the network parameters, with the parameters' sequence (None before
+    /// any parameters arrived)
+    pub fn build_attestation(&self) -> Option<(u64, BuildAttestation)> {
+        self.network_params.as_ref().map(|p| (p.sequence, p.attest(&BuildManifest::current())))
+    }
+
+    /// Publish a maintainer-signed parameter record to the DHT
+    pub fn publish_network_params(&mut self, record: SignedDhtRecord) -> Result<()> {
+        let raw = record.to_bytes();
+        let body = self.record_validators.validate_at(NETWORK_PARAMS_KEY, &raw, self.clock.unix_now())
+            .map_err(|e| ClientError::RequestFailed(format!("network parameters rejected: {}", e)))?;
+        let dht_record = libp2p::kad::Record {
+            key: libp2p::kad::RecordKey::new(&NETWORK_PARAMS_KEY),
+            value: raw,
+            publisher: self.local_peer_id,
+            expires: Some(std::time::Instant::now() + craftnet_network::NETWORK_PARAMS_TTL),
+        };
+        self.send_swarm_cmd(craftec_network::SharedSwarmCommand::PutRecordSecondary(dht_record));
+        self.on_network_params(record, &body);
+        Ok(())
+    }
+
+    /// Look the network parameter record up in the DHT
+    fn fetch_network_params(&mut self) {
+        self.send_swarm_cmd(craftec_network::SharedSwarmCommand::GetRecordSecondary(
+            libp2p::kad::RecordKey::new(&NETWORK_PARAMS_KEY),
+        ));
+        self.last_network_params_fetch = Some(std::time::Instant::now());
+    }
+
+    fn maybe_fetch_network_params(&mut self) {
+        if !self.connected
+            || self.last_network_params_fetch.is_some_and(|t| t.elapsed() < NETWORK_PARAMS_REFRESH_INTERVAL)
+        {
+            return;
+        }
+        self.fetch_network_params();
+    }
+
+    /// Apply the parameter record saved by the last run, if still valid
+    fn load_network_params(&mut self) {
+        let Some(path) = self.network_params_file.clone() else { return };
+        let Ok(raw) = std::fs::read(&path) else { return };
+        match self.record_validators.validate_at(NETWORK_PARAMS_KEY, &raw, self.clock.unix_now()) {
+            Ok(body) => {
+                if let Some(record) = SignedDhtRecord::from_bytes(&raw) {
+                    self.on_network_params(record, &body);
+                }
+            }
+            Err(e) => debug!("Ignoring saved network parameters: {}", e),
+        }
+    }
+
+    /// Adopt a validated parameter record if it is newer than the current
+    /// one: dial its bootstrap nodes, warn when this build is below the
+    /// minimum protocol version, log its notices and save it
+    fn on_network_params(&mut self, record: SignedDhtRecord, body: &[u8]) {
+        let params: NetworkParameters = match serde_json::from_slice(body) {
+            Ok(params) => params,
+            Err(e) => {
+                warn!("Malformed network parameters: {}", e);
+                return;
+            }
+        };
+        if self.network_params.as_ref().is_some_and(|p| p.sequence >= params.sequence) {
+            return;
+        }
+        info!("Network parameters updated (sequence {})", params.sequence);
+
+        if params.requires_upgrade(PROTOCOL_VERSION) {
+            error!(
+                "This node speaks protocol version {} but the network requires {} or newer — upgrade CraftNet",
+                PROTOCOL_VERSION,
+                params.min_protocol_version.unwrap_or_default(),
+            );
+        }
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        for notice in params.active_notices(now) {
+            match notice.severity {
+                NoticeSeverity::Critical => error!("Network notice [{}]: {}", notice.id, notice.message),
+                NoticeSeverity::Warning => warn!("Network notice [{}]: {}", notice.id, notice.message),
+                NoticeSeverity::Info => info!("Network notice [{}]: {}", notice.id, notice.message),
+            }
+        }
+        for (peer_id, addr) in params.bootstrap_peers() {
+            if Some(peer_id) == self.local_peer_id || self.bootstrap_peer_ids.contains(&peer_id) {
+                continue;
+            }
+            info!("Adding bootstrap peer {} from network parameters", peer_id);
+            self.bootstrap_peer_ids.push(peer_id);
+            self.send_swarm_cmd(craftec_network::SharedSwarmCommand::AddAddress(peer_id, addr));
+            self.send_swarm_cmd(craftec_network::SharedSwarmCommand::Dial(peer_id));
+        }
+
+        if let Some(ref path) = self.network_params_file {
+            if let Err(e) = std::fs::write(path, record.to_bytes()) {
+                warn!("Failed to save network parameters to {}: {}", path.display(), e);
+            }
+        }
+        self.network_params = Some(params);
+    }
+
+    /// Latest aggregator registry published by the maintainers
+    pub fn aggregator_registry(&self) -> Option<&AggregatorRegistry> {
+        self.aggregator_registry.as_ref()
+    }
+
+    /// Publish a maintainer-signed aggregator registry to the DHT. This is
+    /// how the set is rotated: sign [`AggregatorRegistry::rotate`] of the
+    /// current registry with a maintainer key and publish it here.
+    pub fn publish_aggregator_registry(&mut self, record: SignedDhtRecord) -> Result<()> {
+        let raw = record.to_bytes();
+        let body = self.record_validators.validate_at(AGGREGATOR_REGISTRY_KEY, &raw, self.clock.unix_now())
+            .map_err(|e| ClientError::RequestFailed(format!("aggregator registry rejected: {}", e)))?;
+        let dht_record = libp2p::kad::Record {
+            key: libp2p::kad::RecordKey::new(&AGGREGATOR_REGISTRY_KEY),
+            value: raw,
+            publisher: self.local_peer_id,
+            expires: Some(std::time::Instant::now() + craftnet_network::AGGREGATOR_REGISTRY_TTL),
+        };
+        self.send_swarm_cmd(craftec_network::SharedSwarmCommand::PutRecordSecondary(dht_record));
+        self.on_aggregator_registry(record, &body);
+        Ok(())
+    }
+
+    /// Whether `pubkey` is a registered aggregator in the current epoch
+    pub fn is_registered_aggregator(&self, pubkey: &PublicKey) -> bool {
+        let epoch = aggregator_epoch(self.clock.unix_now());
+        self.aggregator_registry.as_ref().is_some_and(|r| r.is_registered(pubkey, epoch))
+    }
+
+    /// Look the aggregator registry up in the DHT
+    fn fetch_aggregator_registry(&mut self) {
+        self.send_swarm_cmd(craftec_network::SharedSwarmCommand::GetRecordSecondary(
+            libp2p::kad::RecordKey::new(&AGGREGATOR_REGISTRY_KEY),
+        ));
+        self.last_aggregator_registry_fetch = Some(std::time::Instant::now());
+    }
+
+    /// Refresh the registry on the parameter interval, and re-derive the
+    /// allowlist in case an epoch boundary passed
+    fn maybe_fetch_aggregator_registry(&mut self) {
+        if !self.connected
+            || self.last_aggregator_registry_fetch.is_some_and(|t| t.elapsed() < NETWORK_PARAMS_REFRESH_INTERVAL)
+        {
+            return;
+        }
+        self.fetch_aggregator_registry();
+        self.apply_aggregator_allowlist();
+    }
+
+    /// Apply the registry saved by the last run, if still valid
+    fn load_aggregator_registry(&mut self) {
+        let Some(path) = self.aggregator_registry_file.clone() else { return };
+        let Ok(raw) = std::fs::read(&path) else { return };
+        match self.record_validators.validate_at(AGGREGATOR_REGISTRY_KEY, &raw, self.clock.unix_now()) {
+            Ok(body) => {
+                if let Some(record) = SignedDhtRecord::from_bytes(&raw) {
+                    self.on_aggregator_registry(record, &body);
+                }
+            }
+            Err(e) => debug!("Ignoring saved aggregator registry: {}", e),
+        }
+    }
+
+    /// Adopt a validated registry record if it is newer than the current
+    /// one, save it and update the settlement allowlist
+    fn on_aggregator_registry(&mut self, record: SignedDhtRecord, body: &[u8]) {
+        let registry: AggregatorRegistry = match serde_json::from_slice(body) {
+            Ok(registry) => registry,
+            Err(e) => {
+                warn!("Malformed aggregator registry: {}", e);
+                return;
+            }
+        };
+        if self.aggregator_registry.as_ref().is_some_and(|r| r.sequence >= registry.sequence) {
+            return;
+        }
+        info!(
+            "Aggregator registry updated (sequence {}, {} active aggregators)",
+            registry.sequence,
+            registry.active(aggregator_epoch(self.clock.unix_now())).len(),
+        );
+        if let Some(ref path) = self.aggregator_registry_file {
+            if let Err(e) = std::fs::write(path, record.to_bytes()) {
+                warn!("Failed to save aggregator registry to {}: {}", path.display(), e);
+            }
+        }
+        self.aggregator_registry = Some(registry);
+        self.apply_aggregator_allowlist();
+    }
+
+    /// Hand the current epoch's registered aggregators to the settlement
+    /// client when only registered aggregators are trusted. Until a
+    /// registry is known nobody is trusted.
+    fn apply_aggregator_allowlist(&self) {
+        if !self.config.registered_aggregators_only {
+            return;
+        }
+        let Some(ref settlement) = self.settlement_client else { return };
+        let epoch = aggregator_epoch(self.clock.unix_now());
+        let allowlist = self.aggregator_registry.as_ref()
+            .map(|r| r.active(epoch).into_iter().collect())
+            .unwrap_or_default();
+        settlement.set_aggregator_allowlist(Some(allowlist));
+    }
+
+    /// Write the credit ledger if it changed, at most once per interval
+    fn maybe_save_credit_ledger(&mut self) {
+        if !self.credit_ledger.is_dirty()
+            || self.last_credit_ledger_save.is_some_and(|t| t.elapsed() < Self::RECORD_CACHE_SAVE_INTERVAL)
+        {
+            return;
+        }
+        self.save_credit_ledger();
+    }
+
+    fn save_credit_ledger(&mut self) {
+        if let Err(e) = self.credit_ledger.save() {
+            warn!("Failed to save credit ledger: {}", e);
+        }
+        self.last_credit_ledger_save = Some(std::time::Instant::now());
+    }
+
+    /// Keep a signed exit/relay record for serving over registry sync
+    fn remember_registry_record(&self, key: &[u8], value: &[u8]) {
+        let mut store = self.registry_store.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = store.insert(key, value) {
+            debug!("Not keeping registry record {}: {}", String::from_utf8_lossy(key), e);
+        }
+    }
+
+    /// Delta-sync both registries from the connected exit or relay we
+    /// synced with longest ago (or never).
+    ///
+    /// The peer returns only records it received since our last sync with
+    /// it and that aren't in our bloom filter; results arrive through
+    /// `drain_registry_syncs`.
+    fn maybe_sync_registry(&mut self) {
+        if self.last_registry_sync.is_some_and(|t| t.elapsed() < Self::REGISTRY_SYNC_INTERVAL) {
+            return;
+        }
+        let Some(client) = self.registry_client.clone() else {
+            return;
+        };
+        let candidates: HashSet<PeerId> = self.exit_nodes.values()
+            .filter_map(|s| s.peer_id)
+            .chain(self.relay_nodes.values().map(|s| s.peer_id))
+            .filter(|p| self.connected_peers.contains(p) && Some(*p) != self.local_peer_id)
+            .collect();
+        let last_synced = |peer: &PeerId| {
+            [RegistryKind::Exit, RegistryKind::Relay].iter()
+                .filter_map(|kind| self.registry_sync_since.get(&(*peer, *kind)).copied())
+                .min()
+                .unwrap_or(0)
+        };
+        let Some(peer) = candidates.into_iter().min_by_key(last_synced) else {
+            return;
+        };
+        self.last_registry_sync = Some(std::time::Instant::now());
+
+        let mut store = self.registry_store.lock().unwrap_or_else(|e| e.into_inner());
+        store.prune_at(self.clock.unix_now());
+        for kind in [RegistryKind::Exit, RegistryKind::Relay] {
+            let since = self.registry_sync_since.get(&(peer, kind)).copied().unwrap_or(0);
+            let request = RegistrySyncRequest::new(kind, since, Some(store.known(kind)));
+            let mut client = client.clone();
+            let tx = self.registry_sync_tx.clone();
+            tokio::spawn(async move {
+                let result = client.sync(peer, request).await.map_err(|e| e.to_string());
+                let _ = tx.send((peer, kind, result));
+            });
+        }
+        debug!("Registry sync started with {} (holding {} records)", peer, store.len());
+    }
+
+    /// Apply records from finished registry syncs
+    fn drain_registry_syncs(&mut self) {
+        while let Ok((peer, kind, result)) = self.registry_sync_rx.try_recv() {
+            match result {
+                Ok(response) => {
+                    debug!(
+                        "Registry sync with {}: {} {:?} records{}",
+                        peer, response.records.len(), kind,
+                        if response.next_cursor.is_some() { " (truncated)" } else { "" },
+                    );
+                    // A truncated sync restarts from the same point next time
+                    if response.next_cursor.is_none() {
+                        self.registry_sync_since.insert((peer, kind), response.server_time);
                     }
-                } => {
-                    if let Some(burst) = burst {
-                        self.handle_tunnel_burst(burst).await;
+                    for entry in response.records {
+                        if RegistryKind::of_record_key(&entry.key) == Some(kind) {
+                            self.apply_dht_record(&entry.key, &entry.value);
+                        }
                     }
                 }
-
-                // Periodic maintenance tasks
-                _ = maintenance_interval.tick() => {
-                    self.maybe_reannounce_exit();
-                    self.maybe_reannounce_peer();
-                    self.maybe_send_heartbeat();
-                    self.check_exit_timeouts();
-                    self.discover_exits();
-                    self.cleanup_stale_exits();
-                    // Relay maintenance
-                    self.maybe_reannounce_relay();
-                    self.maybe_send_relay_heartbeat();
-                    self.discover_relays();
-                    self.check_relay_timeouts();
-                    self.cleanup_stale_relays();
-                    // Subscription verification
-                    self.maybe_verify_subscriptions().await;
-                    // Distribution posting
-                    self.maybe_post_distributions().await;
-                    // NAT traversal
-                    self.maybe_reconnect_bootstrap();
-                }
+                Err(e) => debug!("Registry sync with {} failed: {}", peer, e),
             }
         }
-
-        Ok(())
     }
 
     /// Trigger exit discovery via DHT
@@ -4096,9 +8100,23 @@ impl CraftNetNode {
             self.last_exit_discovery.map(|t| t.elapsed().as_millis()));
         if self.swarm_cmd_tx.is_some() {
             info!("[discover_exits] querying DHT for exit providers (online={})", online_exits);
-            self.send_swarm_cmd(craftec_network::SharedSwarmCommand::GetProvidersSecondary(
-                libp2p::kad::RecordKey::new(&craftnet_network::EXIT_REGISTRY_KEY),
-            ));
+            // With no exits yet, ask every shard (and the unsharded key) at once;
+            // otherwise page through a few shards per round.
+            let shards = if online_exits == 0 {
+                self.send_swarm_cmd(craftec_network::SharedSwarmCommand::GetProvidersSecondary(
+                    libp2p::kad::RecordKey::new(&craftnet_network::EXIT_REGISTRY_KEY),
+                ));
+                REGISTRY_SHARDS
+            } else {
+                Self::REGISTRY_SHARDS_PER_DISCOVERY
+            };
+            for _ in 0..shards {
+                let shard = self.exit_shard_cursor;
+                self.exit_shard_cursor = (shard + 1) % REGISTRY_SHARDS;
+                self.send_swarm_cmd(craftec_network::SharedSwarmCommand::GetProvidersSecondary(
+                    libp2p::kad::RecordKey::new(&craftnet_network::exit_registry_shard_key(shard)),
+                ));
+            }
             self.last_exit_discovery = Some(std::time::Instant::now());
         }
     }
@@ -4110,6 +8128,9 @@ impl CraftNetNode {
             SharedSwarmEvent::ConnectionEstablished(peer_id) => {
                 debug!("Connected to peer: {}", peer_id);
                 self.connected_peers.insert(peer_id);
+                // The standalone swarm driver has already added the row with
+                // its address; a shared swarm only tells us the peer ID.
+                self.connection_table.write().unwrap().peer_connected(peer_id, self.clock.unix_now());
                 if !self.unverified_relay_peers.contains(&peer_id) {
                     self.unverified_relay_peers.push(peer_id);
                 }
@@ -4119,10 +8140,7 @@ impl CraftNetNode {
                 if let Some(local_pid) = self.local_peer_id {
                     let tunnel_id = derive_tunnel_id(&peer_id, &local_pid);
                     // 5-minute TTL — renewed periodically in refresh_and_evict_tunnels()
-                    let expires_at = std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap_or_default()
-                        .as_secs() + 300;
+                    let expires_at = self.clock.unix_now() + 300;
                     self.register_tunnel(tunnel_id, peer_id.to_bytes(), expires_at);
                 }
                 let mut state = self.state.write();
@@ -4140,6 +8158,10 @@ impl CraftNetNode {
             SharedSwarmEvent::ConnectionClosed(peer_id) => {
                     debug!("Connection closed to peer: {}", peer_id);
                     self.connected_peers.remove(&peer_id);
+                    self.connection_table.write().unwrap().connection_closed(&peer_id, 0);
+                    self.circuit_rtt.remove(&peer_id);
+                    self.identities.remove_gateway(&peer_id);
+                    self.peer_versions.remove(&peer_id);
                     let mut state = self.state.write();
                     state.stats.peers_connected = state.stats.peers_connected.saturating_sub(1);
                     drop(state);
@@ -4158,23 +8180,33 @@ impl CraftNetNode {
                 }
             SharedSwarmEvent::GossipsubMessage { topic, data, propagation_source } => {
                 use libp2p::gossipsub::IdentTopic;
-                use craftnet_network::{EXIT_STATUS_TOPIC, RELAY_STATUS_TOPIC, PROOF_TOPIC, SUBSCRIPTION_TOPIC, AGGREGATOR_SYNC_TOPIC};
+                use craftnet_network::{EXIT_STATUS_TOPIC, RELAY_STATUS_TOPIC, SUBSCRIPTION_TOPIC, AGGREGATOR_SYNC_TOPIC, DISPUTE_TOPIC};
                 let exit_hash = IdentTopic::new(EXIT_STATUS_TOPIC).hash();
                 let relay_hash = IdentTopic::new(RELAY_STATUS_TOPIC).hash();
-                let proof_hash = IdentTopic::new(PROOF_TOPIC).hash();
                 let sub_hash = IdentTopic::new(SUBSCRIPTION_TOPIC).hash();
                 let agg_sync_hash = IdentTopic::new(AGGREGATOR_SYNC_TOPIC).hash();
+                let dispute_hash = IdentTopic::new(DISPUTE_TOPIC).hash();
+
+                // Proofs and heartbeats are applied once, even when peers
+                // re-forward them after we restarted
+                let deduped = topic == exit_hash || topic == relay_hash || is_proof_topic(topic.as_str());
+                if deduped && !self.gossip_dedup.first_seen(&data) {
+                    debug!("Dropping already handled gossip message on {:?}", topic);
+                    return;
+                }
 
                 if topic == exit_hash {
                     self.handle_exit_status(&data, propagation_source);
                 } else if topic == relay_hash {
                     self.handle_relay_status(&data, propagation_source);
-                } else if topic == proof_hash {
+                } else if is_proof_topic(topic.as_str()) {
                     self.handle_proof_message(&data, propagation_source);
                 } else if topic == sub_hash {
                     self.handle_subscription_announcement(&data);
                 } else if topic == agg_sync_hash {
                     self.handle_aggregator_sync(&data);
+                } else if topic == dispute_hash {
+                    self.handle_dispute_message(&data);
                 } else {
                     debug!("Received gossipsub message on unknown topic: {:?}", topic);
                 }
@@ -4222,58 +8254,13 @@ impl CraftNetNode {
                 }
             }
             SharedSwarmEvent::KademliaSecondaryRecordFound { key, value } => {
-                use craftnet_network::{EXIT_DHT_KEY_PREFIX, RELAY_DHT_KEY_PREFIX, PEER_DHT_KEY_PREFIX};
-                let key_str = String::from_utf8_lossy(key.as_ref());
-                
-                if key_str.starts_with(EXIT_DHT_KEY_PREFIX) {
-                    // Parse PeerId from DHT key: /craftnet/exits/<peer_id>
-                    let exit_peer_id = key_str.strip_prefix(EXIT_DHT_KEY_PREFIX)
-                        .and_then(|pid_str| pid_str.parse::<PeerId>().ok());
-                    if let Ok(exit_info) = serde_json::from_slice::<ExitInfo>(&value) {
-                        self.on_exit_discovered(exit_info, exit_peer_id);
-                    }
-                } else if key_str.starts_with(RELAY_DHT_KEY_PREFIX) {
-                    // Parse PeerId from DHT key: /craftnet/relays/<peer_id>
-                    let relay_peer_id = key_str.strip_prefix(RELAY_DHT_KEY_PREFIX)
-                        .and_then(|pid_str| pid_str.parse::<PeerId>().ok());
-                    match serde_json::from_slice::<RelayInfo>(&value) {
-                        Ok(relay_info) => {
-                            info!("DHT relay record retrieved: peer_id={:?} pubkey={}", relay_peer_id, hex::encode(&relay_info.pubkey[..8]));
-                            self.on_relay_discovered(relay_info, relay_peer_id);
-                        }
-                        Err(e) => {
-                            warn!("DHT relay record deserialization failed: peer_id={:?} err={}", relay_peer_id, e);
-                        }
-                    }
-                } else if key_str.starts_with(PEER_DHT_KEY_PREFIX) {
-                    // Parse peer record: /craftnet/peers/<pubkey_hex> → PeerId bytes
-                    if let Some(pubkey_hex) = key_str.strip_prefix(PEER_DHT_KEY_PREFIX) {
-                        if let Ok(pubkey_bytes) = hex::decode(pubkey_hex) {
-                            if pubkey_bytes.len() == 32 {
-                                if let Ok(peer_id) = PeerId::from_bytes(&value) {
-                                    let mut pubkey = [0u8; 32];
-                                    pubkey.copy_from_slice(&pubkey_bytes);
-                                    info!("Resolved peer record: {} → {}", pubkey_hex, peer_id);
-                                    self.known_peers.insert(pubkey, peer_id);
-                                    if let Some(shards) = self.pending_destination.remove(&pubkey) {
-                                        let count = shards.len();
-                                        if let Some(ref tx) = self.outbound_tx {
-                                            for shard in shards {
-                                                let _ = tx.try_send(OutboundShard { peer: peer_id, shard });
-                                            }
-                                        }
-                                        info!("Queued {} buffered shards for peer {} via outbound channel", count, peer_id);
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
+                self.apply_dht_record(key.as_ref(), &value);
             }
              SharedSwarmEvent::KademliaSecondaryProvidersFound { key, providers } => {
                 use craftnet_network::{EXIT_REGISTRY_KEY, RELAY_REGISTRY_KEY};
                 let key_bytes = key.as_ref();
-                if key_bytes == RELAY_REGISTRY_KEY {
+                let sharded = parse_registry_shard_key(key_bytes).is_some();
+                if key_bytes == RELAY_REGISTRY_KEY || (sharded && key_bytes.starts_with(RELAY_REGISTRY_KEY)) {
                     info!("[discover] DHT found {} relay providers", providers.len());
                     for provider_id in providers {
                         // Fetch relay info record for ALL nodes — clients need to know relays
@@ -4282,7 +8269,7 @@ impl CraftNetNode {
                             libp2p::kad::RecordKey::new(&craftnet_network::relay_dht_key(&provider_id)),
                         ));
                     }
-                } else if key_bytes == EXIT_REGISTRY_KEY {
+                } else if key_bytes == EXIT_REGISTRY_KEY || (sharded && key_bytes.starts_with(EXIT_REGISTRY_KEY)) {
                     info!("[discover] DHT found {} exit providers", providers.len());
                     for provider_id in providers {
                         // Fetch exit info record for ALL nodes — clients need to know exits
@@ -4296,13 +8283,114 @@ impl CraftNetNode {
         }
     }
 
+    /// Use a record fetched from the DHT or a registry sync peer: exit,
+    /// relay and peer records update discovery state; exit/relay records
+    /// that fail validation are dropped.
+    fn apply_dht_record(&mut self, key: &[u8], raw: &[u8]) {
+        // Exit/relay records are signed envelopes; unwrap to the info body
+        let value = match self.record_validators.validate_at(key, raw, self.clock.unix_now()) {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("Rejected DHT record {}: {}", String::from_utf8_lossy(key), e);
+                return;
+            }
+        };
+
+        if key == NETWORK_PARAMS_KEY {
+            if let Some(record) = SignedDhtRecord::from_bytes(raw) {
+                self.on_network_params(record, &value);
+            }
+            return;
+        }
+        if key == AGGREGATOR_REGISTRY_KEY {
+            if let Some(record) = SignedDhtRecord::from_bytes(raw) {
+                self.on_aggregator_registry(record, &value);
+            }
+            return;
+        }
+
+        if RegistryKind::of_record_key(key).is_some() {
+            self.remember_registry_record(key, raw);
+            if let Some(record) = SignedDhtRecord::from_bytes(raw) {
+                self.record_cache.insert(key, record);
+            }
+        }
+        self.use_dht_record(key, &value);
+    }
+
+    /// Update discovery state from a validated record body
+    fn use_dht_record(&mut self, key: &[u8], value: &[u8]) {
+        use craftnet_network::{EXIT_DHT_KEY_PREFIX, RELAY_DHT_KEY_PREFIX, PEER_DHT_KEY_PREFIX};
+        let key_str = String::from_utf8_lossy(key);
+
+        if key_str.starts_with(EXIT_DHT_KEY_PREFIX) {
+            // Parse PeerId from DHT key: /craftnet/exits/<peer_id>
+            let exit_peer_id = key_str.strip_prefix(EXIT_DHT_KEY_PREFIX)
+                .and_then(|pid_str| pid_str.parse::<PeerId>().ok());
+            if let Ok(exit_info) = serde_json::from_slice::<ExitInfo>(value) {
+                self.on_exit_discovered(exit_info, exit_peer_id);
+            }
+        } else if key_str.starts_with(RELAY_DHT_KEY_PREFIX) {
+            // Parse PeerId from DHT key: /craftnet/relays/<peer_id>
+            let relay_peer_id = key_str.strip_prefix(RELAY_DHT_KEY_PREFIX)
+                .and_then(|pid_str| pid_str.parse::<PeerId>().ok());
+            match serde_json::from_slice::<RelayInfo>(value) {
+                Ok(relay_info) => {
+                    info!("DHT relay record retrieved: peer_id={:?} pubkey={}", relay_peer_id, hex::encode(&relay_info.pubkey[..8]));
+                    self.on_relay_discovered(relay_info, relay_peer_id);
+                }
+                Err(e) => {
+                    warn!("DHT relay record deserialization failed: peer_id={:?} err={}", relay_peer_id, e);
+                }
+            }
+        } else if key_str.starts_with(PEER_DHT_KEY_PREFIX) {
+            // Parse peer record: /craftnet/peers/<pubkey_hex> → PeerBinding
+            // (legacy records hold raw PeerId bytes and are not bound)
+            if let Some(pubkey_hex) = key_str.strip_prefix(PEER_DHT_KEY_PREFIX) {
+                if let Ok(pubkey_bytes) = hex::decode(pubkey_hex) {
+                    if pubkey_bytes.len() == 32 {
+                        let mut pubkey = [0u8; 32];
+                        pubkey.copy_from_slice(&pubkey_bytes);
+                        let resolved = match PeerBinding::from_bytes(value) {
+                            Ok(binding) => {
+                                let bound = self.record_peer_binding(&pubkey, &binding);
+                                if bound.is_none() {
+                                    warn!("Rejected peer record for {}: invalid binding", pubkey_hex);
+                                }
+                                bound
+                            }
+                            Err(_) => PeerId::from_bytes(value).ok(),
+                        };
+                        if let Some(peer_id) = resolved {
+                            info!("Resolved peer record: {} → {}", pubkey_hex, peer_id);
+                            self.known_peers.insert(pubkey, peer_id);
+                            if let Some(shards) = self.pending_destination.remove(&pubkey) {
+                                let count = shards.len();
+                                if let Some(ref tx) = self.outbound_tx {
+                                    for shard in shards {
+                                        let _ = tx.try_send(OutboundShard { peer: peer_id, shard });
+                                    }
+                                }
+                                info!("Queued {} buffered shards for peer {} via outbound channel", count, peer_id);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
     /// Called when a new exit node is discovered via DHT
     fn on_exit_discovered(&mut self, exit_info: ExitInfo, peer_id: Option<PeerId>) {
+        if !self.accept_record_binding(&exit_info.pubkey, exit_info.peer_binding.as_ref(), peer_id) {
+            return;
+        }
+
         let is_new = !self.exit_nodes.contains_key(&exit_info.pubkey);
 
         if is_new {
             // New exit - create status entry with base 50% score
-            let mut status = ExitNodeStatus::new(exit_info.clone());
+            let mut status = ExitNodeStatus::new(exit_info.clone(), self.clock.now());
             status.peer_id = peer_id;
             // Register in known_peers so destination_peer_id() works uniformly
             if let Some(pid) = peer_id {
@@ -4322,7 +8410,7 @@ impl CraftNetNode {
         } else {
             // Existing exit - update DHT timestamp and peer_id if available
             if let Some(status) = self.exit_nodes.get_mut(&exit_info.pubkey) {
-                status.last_dht_seen = std::time::Instant::now();
+                status.last_dht_seen = self.clock.now();
                 status.info = exit_info.clone();
                 if status.peer_id.is_none() && peer_id.is_some() {
                     status.peer_id = peer_id;
@@ -4340,7 +8428,7 @@ impl CraftNetNode {
     fn cleanup_stale_exits(&mut self) {
         use craftnet_network::EXIT_RECORD_TTL;
 
-        let now = std::time::Instant::now();
+        let now = self.clock.now();
         let before_count = self.exit_nodes.len();
 
         self.exit_nodes.retain(|_pubkey, status| {
@@ -4385,6 +8473,24 @@ impl CraftNetNode {
         }
     }
 
+    /// Public IPs of the peers the node connects to (bootstrap peers and
+    /// announced relays/exits), for routing them around a full-device
+    /// VPN interface
+    pub fn peer_ip_addrs(&self) -> Vec<std::net::IpAddr> {
+        let bootstrap = self.default_or_configured_bootstrap_peers()
+            .into_iter()
+            .chain(self.network_params.iter().flat_map(|p| p.bootstrap_peers()))
+            .map(|(_, addr)| addr.to_string());
+        let announced = self.relay_nodes.values().map(|s| s.info.address.clone())
+            .chain(self.exit_nodes.values().map(|s| s.info.address.clone()));
+        let mut ips: Vec<std::net::IpAddr> = bootstrap.chain(announced)
+            .filter_map(|addr| public_ip_of(&addr))
+            .collect();
+        ips.sort();
+        ips.dedup();
+        ips
+    }
+
     /// Return a snapshot of all known CraftNet peers (relays + exits).
     pub fn peers_info(&self) -> Vec<CraftNetPeerInfo> {
         let mut peers = Vec::new();
@@ -4395,6 +8501,7 @@ impl CraftNetNode {
                 .unwrap_or_else(|| status.last_dht_seen.elapsed().as_secs());
             peers.push(CraftNetPeerInfo {
                 peer_id: status.peer_id.to_string(),
+                binding_verified: self.verified_bindings.get(&status.info.pubkey) == Some(&status.peer_id),
                 role: "relay".to_string(),
                 online: status.online,
                 score: status.score,
@@ -4414,6 +8521,8 @@ impl CraftNetNode {
                 .unwrap_or_else(|| status.last_dht_seen.elapsed().as_secs());
             peers.push(CraftNetPeerInfo {
                 peer_id: status.info.peer_id.clone().unwrap_or_default(),
+                binding_verified: status.peer_id.is_some()
+                    && self.verified_bindings.get(&status.info.pubkey) == status.peer_id.as_ref(),
                 role: "exit".to_string(),
                 online: status.online,
                 score: status.score,
@@ -4430,6 +8539,93 @@ impl CraftNetNode {
         peers
     }
 
+    /// Return a snapshot of the observed network topology (nodes, roles, links).
+    pub fn topology_snapshot(&self) -> TopologySnapshot {
+        fn upsert(
+            nodes: &mut HashMap<String, TopologyNodeInfo>,
+            peer_id: String,
+            role: Option<&str>,
+            last_seen_secs: u64,
+        ) {
+            let node = nodes.entry(peer_id.clone()).or_insert_with(|| TopologyNodeInfo {
+                peer_id,
+                roles: Vec::new(),
+                last_seen_secs,
+            });
+            node.last_seen_secs = node.last_seen_secs.min(last_seen_secs);
+            if let Some(role) = role {
+                if !node.roles.iter().any(|r| r == role) {
+                    node.roles.push(role.to_string());
+                }
+            }
+        }
+
+        fn edge(a: String, b: String) -> (String, String) {
+            if a <= b { (a, b) } else { (b, a) }
+        }
+
+        let mut nodes: HashMap<String, TopologyNodeInfo> = HashMap::new();
+        let mut edges: HashSet<(String, String)> = HashSet::new();
+
+        // Ourselves, with links to every peer we have an active stream with
+        let local_peer_id = self.local_peer_id.map(|p| p.to_string());
+        if let Some(ref local) = local_peer_id {
+            let caps = self.capabilities;
+            let roles = [
+                (caps.is_client(), "client"),
+                (caps.is_relay(), "relay"),
+                (caps.is_exit(), "exit"),
+                (caps.is_aggregator(), "aggregator"),
+            ];
+            upsert(&mut nodes, local.clone(), None, 0);
+            for (enabled, role) in roles {
+                if enabled {
+                    upsert(&mut nodes, local.clone(), Some(role), 0);
+                }
+            }
+            for peer in self.connected_stream_peers() {
+                upsert(&mut nodes, peer.clone(), None, 0);
+                edges.insert(edge(local.clone(), peer));
+            }
+        }
+
+        // Relays from topology gossip (heartbeat connected_peers)
+        for relay in self.topology.relays() {
+            let Ok(peer_id) = PeerId::from_bytes(&relay.peer_id) else { continue };
+            let peer_id = peer_id.to_string();
+            let last_seen_secs = relay.last_seen.elapsed().as_secs();
+            upsert(&mut nodes, peer_id.clone(), Some("relay"), last_seen_secs);
+            for connected in &relay.connected_peers {
+                let Ok(other) = PeerId::from_bytes(connected) else { continue };
+                let other = other.to_string();
+                upsert(&mut nodes, other.clone(), None, last_seen_secs);
+                edges.insert(edge(peer_id.clone(), other));
+            }
+        }
+
+        // DHT-discovered relays and exits
+        for status in self.relay_nodes.values() {
+            let last_seen_secs = status.last_heartbeat
+                .map(|t| t.elapsed().as_secs())
+                .unwrap_or_else(|| status.last_dht_seen.elapsed().as_secs());
+            upsert(&mut nodes, status.peer_id.to_string(), Some("relay"), last_seen_secs);
+        }
+        for status in self.exit_nodes.values() {
+            let Some(ref peer_id) = status.info.peer_id else { continue };
+            let last_seen_secs = status.last_heartbeat
+                .map(|t| t.elapsed().as_secs())
+                .unwrap_or_else(|| status.last_dht_seen.elapsed().as_secs());
+            upsert(&mut nodes, peer_id.clone(), Some("exit"), last_seen_secs);
+        }
+
+        let mut nodes: Vec<TopologyNodeInfo> = nodes.into_values().collect();
+        nodes.sort_by(|a, b| a.peer_id.cmp(&b.peer_id));
+        let mut edges: Vec<(String, String)> = edges.into_iter().collect();
+        edges.sort();
+
+        TopologySnapshot { local_peer_id, nodes, edges }
+    }
+
     /// Get online exit nodes only
     pub fn online_exit_nodes(&self) -> Vec<&ExitInfo> {
         self.exit_nodes.values()
@@ -4532,21 +8728,37 @@ impl CraftNetNode {
             }
         };
 
+        let located = self.self_geoip().unwrap_or_default();
         let relay_info = RelayInfo {
             pubkey: self.keypair.public_key_bytes(),
             address: self.config.listen_addr.to_string(),
             allows_last_hop: self.config.allow_last_hop,
             reputation: 0,
             encryption_pubkey: Some(self.encryption_keypair.public_key_bytes()),
+            peer_binding: sign_peer_binding(&self.libp2p_keypair, &self.keypair),
+            capacity: Some(self.advertised_relay_capacity()),
+            country_code: self.config.exit_country_code.clone().or(located.country_code),
+            as_number: self.config.as_number.clone().or(located.as_number),
         };
 
-        let record_value = serde_json::to_vec(&relay_info).unwrap_or_default();
+        let key_bytes = craftnet_network::relay_dht_key(&peer_id);
+        let record_value = SignedDhtRecord::sign_at(
+            &self.keypair,
+            &key_bytes,
+            serde_json::to_string(&relay_info).unwrap_or_default(),
+            self.clock.unix_now(),
+        ).to_bytes();
+        self.remember_registry_record(&key_bytes, &record_value);
         self.send_swarm_cmd(craftec_network::SharedSwarmCommand::StartProvidingSecondary(
             libp2p::kad::RecordKey::new(&craftnet_network::RELAY_REGISTRY_KEY)
         ));
+        self.send_swarm_cmd(craftec_network::SharedSwarmCommand::StartProvidingSecondary(
+            libp2p::kad::RecordKey::new(&craftnet_network::relay_registry_shard_key(
+                registry_shard(&relay_info.pubkey),
+            )),
+        ));
 
-        let record_value = record_value;
-        let key = libp2p::kad::RecordKey::new(&craftnet_network::relay_dht_key(&self.local_peer_id.unwrap()));
+        let key = libp2p::kad::RecordKey::new(&key_bytes);
         let record = libp2p::kad::Record {
             key,
             value: record_value,
@@ -4560,9 +8772,19 @@ impl CraftNetNode {
         self.last_relay_announcement = Some(std::time::Instant::now());
     }
 
+    /// Capacity advertised as a relay: `NodeConfig::relay_capacity`, or
+    /// derived from measured throughput
+    fn advertised_relay_capacity(&self) -> RelayCapacity {
+        self.config.relay_capacity.unwrap_or(RelayCapacity {
+            bandwidth_class: BandwidthClass::from_kbps(self.exit_downlink_kbps),
+            max_concurrent_streams: DEFAULT_RELAY_MAX_STREAMS,
+            max_shard_size: MAX_SHARD_SIZE as u32,
+        })
+    }
+
     /// Re-announce as relay every 2 minutes (if in relay mode)
     fn maybe_reannounce_relay(&mut self) {
-        if !self.capabilities.is_relay() {
+        if !self.capabilities.is_relay() || self.is_draining() {
             return;
         }
         let should_reannounce = self.last_relay_announcement
@@ -4575,7 +8797,7 @@ impl CraftNetNode {
 
     /// Publish relay heartbeat via gossipsub
     fn publish_relay_heartbeat(&mut self) {
-        if !self.capabilities.is_relay() {
+        if !self.capabilities.is_relay() || self.is_draining() {
             return;
         }
 
@@ -4595,7 +8817,15 @@ impl CraftNetNode {
             uptime_secs,
             connected_peers,
         );
+        msg.timestamp = self.clock.unix_now();
         msg.encryption_pubkey = Some(hex::encode(self.encryption_keypair.public_key_bytes()));
+        let onion_key = self.state.read().relay_handler.as_ref().and_then(|h| h.onion_key());
+        msg.onion_key = onion_key.map(|key| {
+            let expires_at = msg.timestamp + key.valid_for.as_secs();
+            OnionKeyOffer::sign(&self.keypair, key.public_key, key.epoch, expires_at)
+        });
+        msg.capacity = Some(self.advertised_relay_capacity());
+        msg.sign(&self.keypair, rand::random());
         
         self.send_swarm_cmd(craftec_network::SharedSwarmCommand::PublishGossipsub {
             topic: RELAY_STATUS_TOPIC.to_string(),
@@ -4603,17 +8833,31 @@ impl CraftNetNode {
         });
     }
 
+    /// Rotate the relay's onion key once its epoch is over, announcing the
+    /// new key right away so clients move to it well within the grace period
+    fn maybe_rotate_onion_key(&mut self) {
+        let rotated = self.state.write()
+            .relay_handler
+            .as_mut()
+            .is_some_and(|relay_handler| relay_handler.maybe_rotate_onion_key(Instant::now()));
+        if rotated {
+            self.publish_relay_heartbeat();
+            self.last_relay_heartbeat_sent = Some(self.clock.now());
+        }
+    }
+
     /// Send relay heartbeat every RELAY_HEARTBEAT_INTERVAL (30s)
     fn maybe_send_relay_heartbeat(&mut self) {
         if !self.capabilities.is_relay() {
             return;
         }
+        let now = self.clock.now();
         let should_send = self.last_relay_heartbeat_sent
-            .map(|t| t.elapsed() >= RELAY_HEARTBEAT_INTERVAL)
+            .map(|t| now.duration_since(t) >= RELAY_HEARTBEAT_INTERVAL)
             .unwrap_or(true);
         if should_send {
             self.publish_relay_heartbeat();
-            self.last_relay_heartbeat_sent = Some(std::time::Instant::now());
+            self.last_relay_heartbeat_sent = Some(now);
         }
     }
 
@@ -4628,9 +8872,17 @@ impl CraftNetNode {
             debug!("Invalid pubkey in relay status message");
             return;
         };
+        if let Err(reason) = self.check_status(&pubkey, msg.verify(), msg.timestamp, msg.nonce) {
+            debug!("Rejected relay status from {}: {}", msg.peer_id, reason);
+            return;
+        }
 
         match msg.status {
+            RelayStatusType::Heartbeat if msg.peer_id.parse::<PeerId>().is_ok_and(|p| self.is_outdated_peer(&p)) => {
+                debug!("Ignoring heartbeat from relay {} below the minimum protocol version", msg.peer_id);
+            }
             RelayStatusType::Heartbeat => {
+                let now = self.clock.now();
                 if let Some(status) = self.relay_nodes.get_mut(&pubkey) {
                     if status.peer_id == PeerId::random() {
                         // Update peer_id from gossipsub source if we don't have one yet
@@ -4639,12 +8891,16 @@ impl CraftNetNode {
                         }
                     }
                     status.update_from_heartbeat(
+                        now,
                         msg.load_percent,
                         msg.active_connections,
                         msg.queue_depth,
                         msg.bandwidth_available_kbps,
                         msg.uptime_secs,
                     );
+                    if msg.capacity.is_some() {
+                        status.capacity = msg.capacity;
+                    }
                     debug!(
                         "Updated relay status for {}: load={}%, queue={}, bw={}KB/s, score={}",
                         msg.peer_id, msg.load_percent, msg.queue_depth, msg.bandwidth_available_kbps, status.score
@@ -4655,59 +8911,331 @@ impl CraftNetNode {
                 }
 
                 // Update topology from heartbeat's connected_peers
+                let enc_key = self.relay_onion_key(&msg, &pubkey);
                 if !msg.connected_peers.is_empty() {
-                    if let Some(enc_hex) = &msg.encryption_pubkey {
-                        if let Ok(enc_bytes) = hex::decode(enc_hex) {
-                            if enc_bytes.len() == 32 {
-                                use crate::path::TopologyRelay;
-                                let mut enc_key = [0u8; 32];
-                                enc_key.copy_from_slice(&enc_bytes);
-                                let connected: HashSet<Vec<u8>> = msg.connected_peers.iter()
-                                    .filter_map(|s| s.parse::<PeerId>().ok().map(|p| p.to_bytes()))
-                                    .collect();
-                                let peer_id_bytes = msg.peer_id.parse::<PeerId>()
-                                    .map(|p| p.to_bytes())
-                                    .unwrap_or_default();
-                                if !peer_id_bytes.is_empty() {
-                                    self.topology.update_relay(TopologyRelay {
-                                        peer_id: peer_id_bytes,
-                                        signing_pubkey: pubkey,
-                                        encryption_pubkey: enc_key,
-                                        connected_peers: connected,
-                                        last_seen: std::time::Instant::now(),
-                                    });
-                                }
-                            }
+                    if let Some(enc_key) = enc_key {
+                        use crate::path::TopologyRelay;
+                        let connected: HashSet<Vec<u8>> = msg.connected_peers.iter()
+                            .filter_map(|s| s.parse::<PeerId>().ok().map(|p| p.to_bytes()))
+                            .collect();
+                        let peer_id_bytes = msg.peer_id.parse::<PeerId>()
+                            .map(|p| p.to_bytes())
+                            .unwrap_or_default();
+                        if !peer_id_bytes.is_empty() {
+                            self.topology.update_relay(TopologyRelay {
+                                peer_id: peer_id_bytes,
+                                signing_pubkey: pubkey,
+                                encryption_pubkey: enc_key,
+                                connected_peers: connected,
+                                last_seen: std::time::Instant::now(),
+                            });
+                        }
+                    }
+                }
+            }
+            RelayStatusType::Offline => {
+                if let Some(status) = self.relay_nodes.get_mut(&pubkey) {
+                    status.online = false;
+                    info!("Relay {} went offline", msg.peer_id);
+                }
+            }
+        }
+    }
+
+    /// Key to seal a relay's onion layers to: its signed rotating key if the
+    /// offer verifies and hasn't expired, else its static encryption key
+    fn relay_onion_key(&mut self, msg: &RelayStatusMessage, relay_pubkey: &PublicKey) -> Option<[u8; 32]> {
+        if let Some(offer) = &msg.onion_key {
+            let now = self.clock.unix_now();
+            match offer.onion_pubkey_bytes() {
+                Some(onion_key) if offer.verify(relay_pubkey) && offer.is_valid_at(now) => {
+                    self.relay_onion_keys.retain(|_, (_, expires_at)| *expires_at > now);
+                    self.relay_onion_keys.insert(*relay_pubkey, (onion_key, offer.expires_at));
+                    return Some(onion_key);
+                }
+                _ => debug!("Ignoring invalid onion key offer from relay {}", msg.peer_id),
+            }
+        }
+        hex::decode(msg.encryption_pubkey.as_ref()?).ok()?.try_into().ok()
+    }
+
+    /// A relay's last verified onion key, if it hasn't expired
+    fn current_onion_key(&self, relay_pubkey: &PublicKey) -> Option<[u8; 32]> {
+        self.relay_onion_keys
+            .get(relay_pubkey)
+            .filter(|(_, expires_at)| *expires_at > self.clock.unix_now())
+            .map(|(onion_key, _)| *onion_key)
+    }
+
+    /// Max buffered proofs per relay while its binding is being resolved
+    const MAX_PENDING_BINDING_PROOFS: usize = 256;
+
+    /// Max relays with buffered proofs awaiting a binding
+    const MAX_PENDING_BINDING_RELAYS: usize = 1024;
+
+    /// Handle incoming proof gossipsub message
+    ///
+    /// Proofs are only accepted from relays whose settlement pubkey has a
+    /// verified PeerId binding. The gossipsub source is the mesh peer that
+    /// forwarded the message, not its author, so it is not compared against
+    /// the binding. Proofs from unbound relays are buffered and the relay's
+    /// peer record is looked up; they are replayed in order once it resolves.
+    fn handle_proof_message(&mut self, data: &[u8], _source: Option<PeerId>) {
+        if self.aggregator.is_none() {
+            return; // Not in aggregator mode
+        }
+        self.ingest_proof(data);
+    }
+
+    /// Hand an encoded proof (gossiped or submitted directly) to the
+    /// aggregator
+    fn ingest_proof(&mut self, data: &[u8]) -> ProofSubmitStatus {
+        if self.aggregator.is_none() {
+            return ProofSubmitStatus::Rejected("not an aggregator".to_string());
+        }
+
+        let (msg, extensions) = match ProofMessage::from_bytes_with_extensions(data) {
+            Ok(decoded) => decoded,
+            Err(e) => {
+                debug!("Failed to parse proof message: {:?}", e);
+                return ProofSubmitStatus::Rejected("malformed proof".to_string());
+            }
+        };
+
+        if !self.verified_bindings.contains_key(&msg.relay_pubkey) {
+            return if self.buffer_unbound_proof(msg) {
+                ProofSubmitStatus::Accepted
+            } else {
+                ProofSubmitStatus::Rejected("relay binding unknown".to_string())
+            };
+        }
+
+        let Some(ref mut aggregator) = self.aggregator else {
+            return ProofSubmitStatus::Rejected("not an aggregator".to_string());
+        };
+        let relay = msg.relay_pubkey;
+        match aggregator.handle_proof(msg.clone()) {
+            Ok(()) => {
+                aggregator.record_relay_version(relay, extensions.protocol_version().unwrap_or(0));
+                self.archive_proof(&msg);
+                ProofSubmitStatus::Accepted
+            }
+            Err(craftnet_aggregator::AggregatorError::Duplicate) => ProofSubmitStatus::Duplicate,
+            Err(e) => {
+                debug!("Aggregator rejected proof: {:?}", e);
+                ProofSubmitStatus::Rejected(e.to_string())
+            }
+        }
+    }
+
+    /// Answer proofs relays submitted directly
+    fn drain_proof_submissions(&mut self) {
+        let mut submissions = Vec::new();
+        if let Some(ref mut rx) = self.inbound_proof_rx {
+            while let Ok(submission) = rx.try_recv() {
+                submissions.push(submission);
+            }
+        }
+        for submission in submissions {
+            let results: Vec<ProofSubmitStatus> = submission.proofs.iter().map(|data| self.ingest_proof(data)).collect();
+            debug!(
+                "Direct proof submission from {}: {} of {} accepted",
+                submission.peer,
+                results.iter().filter(|r| **r == ProofSubmitStatus::Accepted).count(),
+                results.len(),
+            );
+            let _ = submission.reply.send(results);
+        }
+    }
+
+    /// How often an aggregator looks for stalled proof chains
+    const PROOF_GAP_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+    /// How long proofs wait behind a gap before it is backfilled
+    const PROOF_GAP_STALL: Duration = Duration::from_secs(60);
+
+    /// Shortest time between two backfills of one chain
+    const PROOF_BACKFILL_RETRY: Duration = Duration::from_secs(300);
+
+    /// Keep a signed proof for peers backfilling its chain
+    fn archive_proof(&self, msg: &ProofMessage) {
+        self.proof_archive.lock().unwrap_or_else(|e| e.into_inner()).insert(msg.clone());
+    }
+
+    /// Ask for the missing segments of chains whose proofs have waited
+    /// behind a gap for `PROOF_GAP_STALL`: the relay first, then the
+    /// other registered aggregators. Answers arrive through `drain_backfills`.
+    fn maybe_backfill_proof_gaps(&mut self) {
+        if self.last_gap_check.is_some_and(|t| t.elapsed() < Self::PROOF_GAP_CHECK_INTERVAL) {
+            return;
+        }
+        let Some(client) = self.backfill_client.clone() else {
+            return;
+        };
+        let Some(ref aggregator) = self.aggregator else {
+            return;
+        };
+        let gaps = aggregator.chain_gaps(self.clock.unix_now(), Self::PROOF_GAP_STALL);
+        self.last_gap_check = Some(Instant::now());
+        self.backfill_requested.retain(|_, at| at.elapsed() < Self::PROOF_BACKFILL_RETRY);
+        if gaps.is_empty() {
+            return;
+        }
+
+        let aggregators = self.known_aggregator_peers();
+        for gap in gaps {
+            let chain = (gap.relay_pubkey, gap.pool_pubkey, gap.pool_type);
+            if self.backfill_requested.contains_key(&chain) {
+                continue;
+            }
+            let mut peers: Vec<PeerId> = self.verified_bindings.get(&gap.relay_pubkey).copied().into_iter().collect();
+            for peer in &aggregators {
+                if !peers.contains(peer) {
+                    peers.push(*peer);
+                }
+            }
+            if peers.is_empty() {
+                continue;
+            }
+            self.backfill_requested.insert(chain, Instant::now());
+            debug!(
+                "Proofs of relay {} on pool {} stalled behind a gap, asking {} peers",
+                hex::encode(&gap.relay_pubkey[..8]), hex::encode(&gap.pool_pubkey[..8]), peers.len(),
+            );
+
+            let mut client = client.clone();
+            let tx = self.backfill_tx.clone();
+            tokio::spawn(async move {
+                let mut result = Err("no peer had the segment".to_string());
+                for peer in peers {
+                    match client.request(peer, &gap).await {
+                        Ok(response) if !response.proofs.is_empty() => {
+                            result = Ok((peer, response.proofs));
+                            break;
                         }
+                        Ok(_) => {}
+                        Err(e) => debug!("Proof backfill from {} failed: {}", peer, e),
                     }
                 }
-            }
-            RelayStatusType::Offline => {
-                if let Some(status) = self.relay_nodes.get_mut(&pubkey) {
-                    status.online = false;
-                    info!("Relay {} went offline", msg.peer_id);
+                let _ = tx.send((gap, result));
+            });
+        }
+    }
+
+    /// Apply the segments of finished backfills, in chain order
+    fn drain_backfills(&mut self) {
+        while let Ok((gap, result)) = self.backfill_rx.try_recv() {
+            match result {
+                Ok((peer, proofs)) => {
+                    let total = proofs.len();
+                    let accepted = proofs.iter()
+                        .filter(|data| self.ingest_proof(data) == ProofSubmitStatus::Accepted)
+                        .count();
+                    info!(
+                        "Backfilled {} of {} proofs of relay {} on pool {} from {}",
+                        accepted, total, hex::encode(&gap.relay_pubkey[..8]), hex::encode(&gap.pool_pubkey[..8]), peer,
+                    );
                 }
+                Err(e) => debug!("Backfill for relay {} failed: {}", hex::encode(&gap.relay_pubkey[..8]), e),
             }
         }
     }
 
-    /// Handle incoming proof gossipsub message
-    fn handle_proof_message(&mut self, data: &[u8], _source: Option<PeerId>) {
+    /// Hold a proof until the relay's peer binding is known. Returns false
+    /// if the proof was dropped.
+    fn buffer_unbound_proof(&mut self, msg: ProofMessage) -> bool {
+        let relay_pubkey = msg.relay_pubkey;
+        if !self.pending_binding_proofs.contains_key(&relay_pubkey) {
+            if self.pending_binding_proofs.len() >= Self::MAX_PENDING_BINDING_RELAYS {
+                debug!("Dropping proof from unbound relay {}: too many pending relays", hex::encode(&relay_pubkey[..8]));
+                return false;
+            }
+            debug!("Proof from unbound relay {}, looking up peer binding", hex::encode(&relay_pubkey[..8]));
+            self.send_swarm_cmd(craftec_network::SharedSwarmCommand::GetRecordSecondary(
+                libp2p::kad::RecordKey::new(&craftnet_network::peer_dht_key(&relay_pubkey)),
+            ));
+        }
+
+        let pending = self.pending_binding_proofs.entry(relay_pubkey).or_default();
+        if pending.len() >= Self::MAX_PENDING_BINDING_PROOFS {
+            debug!("Dropping proof from unbound relay {}: buffer full", hex::encode(&relay_pubkey[..8]));
+            return false;
+        }
+        pending.push(msg);
+        true
+    }
+
+    /// Verify a peer binding claimed for `pubkey` and remember it.
+    ///
+    /// Returns the bound PeerId, or `None` if either signature fails, the
+    /// binding is for a different settlement key, is older than the max age,
+    /// or is superseded by a newer binding to another PeerId.
+    fn record_peer_binding(&mut self, pubkey: &PublicKey, binding: &PeerBinding) -> Option<PeerId> {
+        match self.verified_bindings.accept(pubkey, binding, self.clock.unix_now()) {
+            Ok(peer_id) => {
+                self.replay_bound_proofs(*pubkey, peer_id);
+                Some(peer_id)
+            }
+            Err(e) => {
+                debug!("Peer binding for {} rejected: {}", hex::encode(&pubkey[..8]), e);
+                None
+            }
+        }
+    }
+
+    /// Replay any proofs waiting on a verified binding
+    fn replay_bound_proofs(&mut self, pubkey: PublicKey, peer_id: PeerId) {
+        let Some(proofs) = self.pending_binding_proofs.remove(&pubkey) else {
+            return;
+        };
         let Some(ref mut aggregator) = self.aggregator else {
-            return; // Not in aggregator mode
+            return;
         };
+        let count = proofs.len();
+        for msg in proofs {
+            if let Err(e) = aggregator.handle_proof(msg) {
+                debug!("Aggregator rejected proof: {:?}", e);
+            }
+        }
+        info!("Replayed {} buffered proofs for relay {} (bound to {})", count, hex::encode(&pubkey[..8]), peer_id);
+    }
 
-        let msg = match ProofMessage::from_bytes(data) {
-            Ok(msg) => msg,
+    /// Check the binding embedded in an exit/relay DHT record.
+    ///
+    /// Records without a binding are accepted (older nodes) but stay unbound.
+    /// Records whose binding fails verification, is stale or superseded, or
+    /// binds a PeerId other than the one in the DHT key, are rejected.
+    fn accept_record_binding(
+        &mut self,
+        pubkey: &PublicKey,
+        binding: Option<&PeerBinding>,
+        record_peer_id: Option<PeerId>,
+    ) -> bool {
+        let Some(binding) = binding else {
+            return true;
+        };
+        let bound = match self.verified_bindings.check(pubkey, binding, self.clock.unix_now()) {
+            Ok(bound) => bound,
             Err(e) => {
-                debug!("Failed to parse proof message: {:?}", e);
-                return;
+                warn!("Rejected DHT record for {}: peer binding {}", hex::encode(&pubkey[..8]), e);
+                return false;
             }
         };
-
-        if let Err(e) = aggregator.handle_proof(msg) {
-            debug!("Aggregator rejected proof: {:?}", e);
+        if record_peer_id.is_some_and(|pid| pid != bound) {
+            warn!(
+                "Rejected DHT record for {}: binding is for {} but record key is {:?}",
+                hex::encode(&pubkey[..8]), bound, record_peer_id
+            );
+            return false;
         }
+        self.verified_bindings.insert(*pubkey, bound, binding.timestamp);
+        self.replay_bound_proofs(*pubkey, bound);
+        true
+    }
+
+    /// PeerId bound to a settlement pubkey by a verified binding record
+    pub fn bound_peer_id(&self, pubkey: &PublicKey) -> Option<PeerId> {
+        self.verified_bindings.get(pubkey).copied()
     }
 
     /// Handle aggregator sync messages (request or response).
@@ -4804,47 +9332,115 @@ impl CraftNetNode {
             return;
         }
 
-        // Insert/update cache (unverified until on-chain check)
+        // The claim only counts once the chain confirms it: until then
+        // the pool is treated as free
         let now = std::time::Instant::now();
         let entry = self.subscription_cache.entry(msg.user_pubkey).or_insert(SubscriptionEntry {
-            tier: msg.tier,
+            tier: 255,
+            claimed_tier: msg.tier,
+            claimed_expires_at: msg.expires_at,
             start_date: 0,
             expires_at: msg.expires_at,
             verified: false,
             verified_at: None,
             last_seen: now,
         });
-        entry.tier = msg.tier;
-        entry.expires_at = msg.expires_at;
+        entry.claimed_tier = msg.tier;
+        entry.claimed_expires_at = msg.expires_at;
+        if !entry.verified {
+            entry.expires_at = msg.expires_at;
+        }
         entry.last_seen = now;
 
+        // Check new claims right away (at the next maintenance tick)
+        // rather than at the next verification round
+        let queue = entry.needs_check(now)
+            && !self.subscription_checks.contains(&msg.user_pubkey)
+            && self.subscription_checks.len() < MAX_SUBSCRIPTION_CHECK_QUEUE;
+        if queue {
+            self.subscription_checks.push_back(msg.user_pubkey);
+        }
+
         debug!(
-            "Cached subscription announcement: user={}, tier={}, expires={}",
+            "Cached subscription announcement: user={}, tier={}, expires={} (check queued: {})",
             hex::encode(&msg.user_pubkey[..8]),
             msg.tier,
             msg.expires_at,
+            queue,
         );
     }
 
-    /// Periodically verify recently-seen subscriptions on-chain in batches.
+    /// Fetch balance, phase and our claim receipt for pools we earned from.
+    ///
+    /// Pools are checked a batch at a time; answers stay fresh for
+    /// `POOL_CLAIM_REFRESH_INTERVAL` and claimed or drained pools aren't
+    /// checked again. Failed lookups keep the previous answer.
+    async fn maybe_refresh_pool_claims(&mut self) {
+        if !self.capabilities.is_service_node() {
+            return;
+        }
+        let Some(ref settlement) = self.settlement_client else {
+            return;
+        };
+        let settlement = Arc::clone(settlement);
+
+        let now_instant = std::time::Instant::now();
+        let earned: Vec<PublicKey> = self.credit_ledger.pools()
+            .filter(|(_, credits)| credits.pending_credits > 0 || credits.settled_credits > 0)
+            .map(|(pool, _)| *pool)
+            .collect();
+        let due = self.pool_claims.due(&earned, now_instant);
+        if due.is_empty() {
+            return;
+        }
+
+        debug!("Refreshing claim status of {} pools", due.len());
+        let our_key = self.keypair.public_key_bytes();
+        for pool in due {
+            let state = match settlement.get_subscription_state(pool).await {
+                Ok(state) => state,
+                Err(e) => {
+                    debug!("Pool {} state lookup failed: {}", hex::encode(&pool[..8]), e);
+                    continue;
+                }
+            };
+            let claimed = match settlement.has_claimed(pool, our_key).await {
+                Ok(claimed) => claimed,
+                Err(e) => {
+                    debug!("Pool {} claim lookup failed: {}", hex::encode(&pool[..8]), e);
+                    false
+                }
+            };
+            let status = PoolClaimStatus::new(pool, state.as_ref(), claimed, self.clock.unix_now());
+            debug!(
+                "Pool {}: balance {} status {}",
+                hex::encode(&pool[..8]), status.balance, status.status.name(),
+            );
+            self.pool_claims.record(status, now_instant);
+        }
+    }
+
+    /// Verify announced subscriptions on-chain in batches.
     ///
     /// Checks actual on-chain tier + active window (start_date, expires_at),
-    /// not just boolean existence. Downgrades to free if claimed tier doesn't
-    /// match on-chain. Re-verifies stale entries (>5 min since last check).
+    /// not just boolean existence. A pool only gets its tier (and with it
+    /// relay priority) once this confirmed it; a claim the chain doesn't
+    /// back leaves it free. Fresh announcements are checked at the next
+    /// call; confirmed entries are re-verified after 5 minutes and
+    /// contradicted ones after `SUBSCRIPTION_NEGATIVE_TTL`.
     async fn maybe_verify_subscriptions(&mut self) {
         // Only verify in relay mode
         if !self.capabilities.is_service_node() {
             return;
         }
 
-        let should_verify = match self.last_subscription_verify {
+        let round_due = match self.last_subscription_verify {
             None => true,
             Some(last) => last.elapsed() >= SUBSCRIPTION_VERIFY_INTERVAL,
         };
-        if !should_verify {
+        if !round_due && self.subscription_checks.is_empty() {
             return;
         }
-        self.last_subscription_verify = Some(std::time::Instant::now());
 
         let Some(ref settlement) = self.settlement_client else {
             return;
@@ -4852,19 +9448,25 @@ impl CraftNetNode {
         let settlement = Arc::clone(settlement);
 
         let now_instant = std::time::Instant::now();
-        let reverify_threshold = std::time::Duration::from_secs(300); // 5 minutes
-
-        // Collect entries needing verification:
-        // 1. Never verified (!verified)
-        // 2. Stale verification (verified_at older than 5 min)
-        let mut to_verify: Vec<PublicKey> = self.subscription_cache.iter()
-            .filter(|(_, entry)| {
-                !entry.verified ||
-                entry.verified_at.map_or(true, |at| now_instant.duration_since(at) >= reverify_threshold)
-            })
-            .map(|(pubkey, _)| *pubkey)
-            .collect();
-        to_verify.truncate(SUBSCRIPTION_VERIFY_BATCH_SIZE);
+
+        // Announcements just received first, then (once per interval)
+        // entries whose check went stale — see `SubscriptionEntry::needs_check`
+        let mut to_verify: Vec<PublicKey> = Vec::new();
+        while to_verify.len() < SUBSCRIPTION_VERIFY_BATCH_SIZE {
+            let Some(pubkey) = self.subscription_checks.pop_front() else { break };
+            if self.subscription_cache.get(&pubkey).is_some_and(|e| e.needs_check(now_instant)) {
+                to_verify.push(pubkey);
+            }
+        }
+        if round_due {
+            self.last_subscription_verify = Some(now_instant);
+            let stale: Vec<PublicKey> = self.subscription_cache.iter()
+                .filter(|(pubkey, entry)| entry.needs_check(now_instant) && !to_verify.contains(pubkey))
+                .map(|(pubkey, _)| *pubkey)
+                .collect();
+            let room = SUBSCRIPTION_VERIFY_BATCH_SIZE.saturating_sub(to_verify.len());
+            to_verify.extend(stale.into_iter().take(room));
+        }
 
         if to_verify.is_empty() {
             return;
@@ -4886,11 +9488,11 @@ impl CraftNetNode {
 
                     if let Some(entry) = self.subscription_cache.get_mut(&pubkey) {
                         // Downgrade if claimed tier doesn't match on-chain
-                        if entry.tier != verified_tier {
+                        if entry.claimed_tier != verified_tier {
                             debug!(
                                 "Tier mismatch for {}: claimed={}, on-chain={} (active={})",
                                 hex::encode(&pubkey[..8]),
-                                entry.tier,
+                                entry.claimed_tier,
                                 verified_tier,
                                 is_active,
                             );
@@ -4961,10 +9563,17 @@ impl CraftNetNode {
     ///
     /// Called periodically from the maintenance interval. For each subscribed pool
     /// the aggregator knows about, checks if the epoch has expired and builds the
-    /// distribution Merkle tree. The distribution is recorded but NOT posted
-    /// on-chain — an external prover must generate a Groth16 proof and post the
-    /// distribution + proof via the settlement client.
+    /// distribution Merkle tree. The distribution is handed to the proof job
+    /// queue; distributions whose Groth16 proof is done are posted on-chain
+    /// via the settlement client.
     async fn maybe_post_distributions(&mut self) {
+        // Post proofs finished since the last round (or before a restart)
+        #[cfg(any(feature = "sp1", feature = "risc0"))]
+        self.post_proven_distributions().await;
+        self.proof_jobs.prune_failed();
+        #[cfg(any(feature = "sp1", feature = "risc0"))]
+        self.start_proof_jobs();
+
         let Some(ref aggregator) = self.aggregator else { return };
 
         let pools = aggregator.subscribed_pools();
@@ -4972,171 +9581,507 @@ impl CraftNetNode {
             return;
         }
 
+        // Proving is wasted work if the chain-side allowlist would refuse us
+        if let Some(ref settlement) = self.settlement_client {
+            if !settlement.is_allowed_aggregator(settlement.signer_pubkey_bytes()) {
+                debug!("Not a registered aggregator this epoch — not building distributions");
+                return;
+            }
+        }
+
         let now_unix = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap_or_default()
             .as_secs();
 
-        for (user_pubkey, _pool_type) in &pools {
-            if self.posted_distributions.contains(user_pubkey) {
+        for (user_pubkey, pool_type) in &pools {
+            if self.posted_distributions.contains(user_pubkey) || self.proof_jobs.has_job(user_pubkey) {
+                continue;
+            }
+
+            // Only post distributions after the subscription epoch has expired
+            if let Some(entry) = self.subscription_cache.get(user_pubkey) {
+                if entry.expires_at > now_unix {
+                    info!(
+                        "Skipping distribution for pool {}: subscription still active (expires in {}s)",
+                        hex::encode(&user_pubkey[..8]),
+                        entry.expires_at - now_unix,
+                    );
+                    continue;
+                }
+                info!(
+                    "Pool {} epoch expired (expired {}s ago) — proceeding with distribution",
+                    hex::encode(&user_pubkey[..8]),
+                    now_unix - entry.expires_at,
+                );
+            } else {
+                // No subscription info — skip until we receive it via gossip
+                info!(
+                    "Skipping distribution for pool {}: no subscription info in cache",
+                    hex::encode(&user_pubkey[..8]),
+                );
+                continue;
+            }
+
+            let pool_key = (*user_pubkey, *pool_type);
+
+            // Relays may still be challenging the announced distribution
+            let announced = self.announced_distributions.get(&pool_key).copied();
+            if announced.is_some_and(|(_, deadline)| now_unix <= deadline) {
+                continue;
+            }
+
+            let Some(dist) = self.aggregator.as_ref().unwrap().build_distribution(&pool_key) else {
+                continue;
+            };
+
+            // Record distribution-built event in history (again only if
+            // late proofs changed it during the challenge window)
+            if announced.is_none_or(|(root, _)| root != dist.root) {
+                if let Some(ref mut agg) = self.aggregator {
+                    agg.record_distribution_built(
+                        *user_pubkey, *pool_type,
+                        dist.root, dist.total, dist.entries.len(),
+                    );
+                }
+
+                info!(
+                    "Distribution built for pool {}: {} entries, {} total bytes",
+                    hex::encode(&user_pubkey[..8]),
+                    dist.entries.len(),
+                    dist.total,
+                );
+            }
+
+            if announced.is_none() && !self.config.distribution_challenge_window.is_zero() {
+                self.announce_distribution(pool_key, &dist, now_unix);
+                continue;
+            }
+
+            // Verify on-chain subscription exists before expensive proving
+            if let Some(ref settlement) = self.settlement_client {
+                match settlement.get_subscription_state(*user_pubkey).await {
+                    Ok(Some(state)) => {
+                        if state.distribution_posted {
+                            info!("Distribution already posted on-chain for pool {} — skipping", hex::encode(&user_pubkey[..8]));
+                            if settlement.is_trusted_distribution(&state) {
+                                self.reconcile_credits(*user_pubkey, &dist, state.distribution_root, state.tier);
+                            } else {
+                                warn!(
+                                    "Distribution for pool {} was posted by an unregistered aggregator ({}) — credits left unreconciled",
+                                    hex::encode(&user_pubkey[..8]),
+                                    state.distribution_poster.map_or_else(|| "unknown".to_string(), |k| hex::encode(&k[..8])),
+                                );
+                            }
+                            self.posted_distributions.insert(*user_pubkey);
+                            continue;
+                        }
+                        info!("On-chain subscription confirmed for pool {} (balance: {})", hex::encode(&user_pubkey[..8]), state.pool_balance);
+                    }
+                    Ok(None) => {
+                        info!("No on-chain subscription for pool {} — skipping prove", hex::encode(&user_pubkey[..8]));
+                        self.posted_distributions.insert(*user_pubkey);
+                        continue;
+                    }
+                    Err(e) => {
+                        warn!("Failed to check subscription for pool {}: {} — skipping this round", hex::encode(&user_pubkey[..8]), e);
+                        continue;
+                    }
+                }
+            }
+
+            // Queue the proof (SP1 or RISC Zero feature required); proving runs on
+            // blocking threads and Done jobs are posted next maintenance round
+            #[cfg(not(any(feature = "sp1", feature = "risc0")))]
+            {
+                warn!("No prover backend enabled (sp1/risc0) — cannot generate distribution proof, skipping post");
                 continue;
             }
 
-            // Only post distributions after the subscription epoch has expired
-            if let Some(entry) = self.subscription_cache.get(user_pubkey) {
-                if entry.expires_at > now_unix {
+            #[cfg(any(feature = "sp1", feature = "risc0"))]
+            {
+                let entries: Vec<([u8; 32], u64)> = dist.entries.iter()
+                    .map(|(relay, bytes)| (*relay, *bytes))
+                    .collect();
+                if let Some(id) = self.proof_jobs.enqueue(*user_pubkey, *pool_type, dist.root, dist.total, entries) {
+                    info!("Queued distribution proof job {} for pool {}", id, hex::encode(&user_pubkey[..8]));
+                }
+            }
+        }
+
+        #[cfg(any(feature = "sp1", feature = "risc0"))]
+        self.start_proof_jobs();
+    }
+
+    /// Publish a built distribution on the dispute topic and open its
+    /// challenge window; it is proven once the window has passed
+    fn announce_distribution(&mut self, pool_key: (PublicKey, PoolType), dist: &Distribution, now: u64) {
+        let Some(ref mut aggregator) = self.aggregator else { return };
+        let deadline = aggregator.open_challenge_window(pool_key, dist.root, now);
+        let mut announcement = DistributionAnnouncement {
+            aggregator_pubkey: self.keypair.public_key_bytes(),
+            pool_pubkey: pool_key.0,
+            pool_type: pool_key.1,
+            distribution_root: dist.root,
+            total_bytes: dist.total,
+            entries: dist.entries.clone(),
+            built_at: now,
+            challenge_deadline: deadline,
+            signature: vec![],
+        };
+        announcement.signature = craftec_crypto::sign_data(&self.keypair, &announcement.signable_data()).to_vec();
+        self.send_swarm_cmd(craftec_network::SharedSwarmCommand::PublishGossipsub {
+            topic: DISPUTE_TOPIC.to_string(),
+            data: DisputeMessage::Announcement(announcement).to_bytes(),
+        });
+        self.announced_distributions.insert(pool_key, (dist.root, deadline));
+        info!(
+            "Announced distribution for pool {} — open for challenges for {}s",
+            hex::encode(&pool_key.0[..8]),
+            deadline.saturating_sub(now),
+        );
+    }
+
+    /// Handle a message on the dispute topic
+    fn handle_dispute_message(&mut self, data: &[u8]) {
+        match DisputeMessage::from_bytes(data) {
+            Ok(DisputeMessage::Announcement(announcement)) => self.on_distribution_announcement(announcement),
+            Ok(DisputeMessage::Challenge(challenge)) => self.on_distribution_challenge(challenge),
+            Ok(DisputeMessage::Response(response)) => self.on_challenge_response(response),
+            Err(e) => debug!("Failed to parse dispute message: {:?}", e),
+        }
+    }
+
+    /// As relay: challenge an announced distribution that counts fewer of
+    /// our bytes than our published proof chain covers
+    fn on_distribution_announcement(&mut self, announcement: DistributionAnnouncement) {
+        if !self.capabilities.is_relay() {
+            return;
+        }
+        let Ok(sig) = <[u8; 64]>::try_from(announcement.signature.as_slice()) else { return };
+        if !craftec_crypto::verify_signature(&announcement.aggregator_pubkey, &announcement.signable_data(), &sig) {
+            debug!("Dropping distribution announcement with a bad signature");
+            return;
+        }
+        let now = self.clock.unix_now();
+        if now > announcement.challenge_deadline {
+            return;
+        }
+        let pool_key = (announcement.pool_pubkey, announcement.pool_type);
+        let Some(&(claimed_root, claimed_bytes)) = self.pool_roots.get(&pool_key) else { return };
+        // Proofs we haven't published yet can't have been counted
+        if self.proof_outbox.pending_for(&announcement.pool_pubkey, announcement.pool_type) > 0 {
+            return;
+        }
+        let our_key = self.keypair.public_key_bytes();
+        let counted_bytes = announcement.counted_for(&our_key);
+        let challenge_key = (announcement.pool_pubkey, announcement.pool_type, announcement.distribution_root);
+        if counted_bytes >= claimed_bytes || self.sent_challenges.contains_key(&challenge_key) {
+            return;
+        }
+
+        let mut challenge = DistributionChallenge {
+            relay_pubkey: our_key,
+            aggregator_pubkey: announcement.aggregator_pubkey,
+            pool_pubkey: announcement.pool_pubkey,
+            pool_type: announcement.pool_type,
+            distribution_root: announcement.distribution_root,
+            counted_bytes,
+            claimed_bytes,
+            claimed_root,
+            timestamp: now,
+            signature: vec![],
+        };
+        challenge.signature = craftec_crypto::sign_data(&self.keypair, &challenge.signable_data()).to_vec();
+        warn!(
+            "Distribution for pool {} counts {} of our {} bytes — challenging aggregator {}",
+            hex::encode(&announcement.pool_pubkey[..8]), counted_bytes, claimed_bytes,
+            hex::encode(&announcement.aggregator_pubkey[..8]),
+        );
+        self.send_swarm_cmd(craftec_network::SharedSwarmCommand::PublishGossipsub {
+            topic: DISPUTE_TOPIC.to_string(),
+            data: DisputeMessage::Challenge(challenge.clone()).to_bytes(),
+        });
+        self.dispute_events.push(DisputeEvent::Challenged {
+            aggregator: hex::encode(announcement.aggregator_pubkey),
+            pool: hex::encode(announcement.pool_pubkey),
+            counted_bytes,
+            claimed_bytes,
+        });
+        self.sent_challenges.insert(challenge_key, challenge);
+    }
+
+    /// As aggregator: answer a challenge against one of our announcements
+    /// with the proof chain recorded for the relay
+    fn on_distribution_challenge(&mut self, challenge: DistributionChallenge) {
+        if challenge.aggregator_pubkey != self.keypair.public_key_bytes() {
+            return;
+        }
+        let now = self.clock.unix_now();
+        let Some(ref mut aggregator) = self.aggregator else { return };
+        if let Err(e) = aggregator.handle_challenge(&challenge, now) {
+            debug!("Ignoring challenge from relay {}: {}", hex::encode(&challenge.relay_pubkey[..8]), e);
+            return;
+        }
+        // Without a history file the dispute stays open and expires unresolved
+        let Some(ref path) = self.aggregator_history_file else {
+            warn!("No aggregator history file — cannot answer dispute");
+            return;
+        };
+        aggregator.flush_history(path);
+        let chain = Aggregator::proof_chain(path, &challenge.relay_pubkey, &challenge.pool_pubkey, challenge.pool_type);
+        let mut response = ChallengeResponse {
+            aggregator_pubkey: challenge.aggregator_pubkey,
+            relay_pubkey: challenge.relay_pubkey,
+            pool_pubkey: challenge.pool_pubkey,
+            pool_type: challenge.pool_type,
+            distribution_root: challenge.distribution_root,
+            counted_bytes: chain.last().map_or(0, |link| link.cumulative_bytes),
+            chain,
+            timestamp: now,
+            signature: vec![],
+        };
+        response.signature = craftec_crypto::sign_data(&self.keypair, &response.signable_data()).to_vec();
+        let status = aggregator.close_dispute(&challenge, &response, now).unwrap_or(DisputeStatus::Unresolved);
+        info!(
+            "Answered dispute of relay {} on pool {} with {} proofs ({:?})",
+            hex::encode(&challenge.relay_pubkey[..8]), hex::encode(&challenge.pool_pubkey[..8]),
+            response.chain.len(), status,
+        );
+        self.send_swarm_cmd(craftec_network::SharedSwarmCommand::PublishGossipsub {
+            topic: DISPUTE_TOPIC.to_string(),
+            data: DisputeMessage::Response(response).to_bytes(),
+        });
+        self.dispute_events.push(DisputeEvent::Responded {
+            relay: hex::encode(challenge.relay_pubkey),
+            pool: hex::encode(challenge.pool_pubkey),
+            status,
+        });
+    }
+
+    /// As relay: check the aggregator's answer to our challenge
+    fn on_challenge_response(&mut self, response: ChallengeResponse) {
+        let challenge_key = (response.pool_pubkey, response.pool_type, response.distribution_root);
+        let Some(challenge) = self.sent_challenges.get(&challenge_key) else { return };
+        if response.relay_pubkey != challenge.relay_pubkey || response.aggregator_pubkey != challenge.aggregator_pubkey {
+            return;
+        }
+        let Ok(sig) = <[u8; 64]>::try_from(response.signature.as_slice()) else { return };
+        if !craftec_crypto::verify_signature(&response.aggregator_pubkey, &response.signable_data(), &sig) {
+            debug!("Dropping challenge response with a bad signature");
+            return;
+        }
+        let resolved = response.backs_claim(challenge);
+        if resolved {
+            info!("Aggregator {} backed our claim on pool {}", hex::encode(&response.aggregator_pubkey[..8]), hex::encode(&response.pool_pubkey[..8]));
+        } else {
+            warn!(
+                "Aggregator {} counts {} bytes for us on pool {} and its proof chain doesn't reach our claim — dispute unresolved",
+                hex::encode(&response.aggregator_pubkey[..8]), response.counted_bytes, hex::encode(&response.pool_pubkey[..8]),
+            );
+        }
+        self.dispute_events.push(DisputeEvent::Answered {
+            aggregator: hex::encode(response.aggregator_pubkey),
+            pool: hex::encode(response.pool_pubkey),
+            counted_bytes: response.counted_bytes,
+            resolved,
+        });
+        self.sent_challenges.remove(&challenge_key);
+    }
+
+    /// Drain dispute progress since the last call (for IPC events)
+    pub fn take_dispute_events(&mut self) -> Vec<DisputeEvent> {
+        std::mem::take(&mut self.dispute_events)
+    }
+
+    /// Settle the credits booked against `pool` once its distribution is on
+    /// chain. Only a posted root matching our own distribution is trusted
+    /// for the bytes it pays us.
+    fn reconcile_credits(&mut self, pool: PublicKey, dist: &Distribution, posted_root: [u8; 32], tier: SubscriptionTier) {
+        if posted_root != dist.root {
+            warn!(
+                "Posted distribution root for pool {} differs from ours — credits left unreconciled",
+                hex::encode(&pool[..8]),
+            );
+            return;
+        }
+        let our_key = self.keypair.public_key_bytes();
+        let paid = dist.entries.iter().find(|(relay, _)| *relay == our_key).map_or(0, |(_, bytes)| *bytes);
+        if let Some(rec) = self.credit_ledger.reconcile(pool, posted_root, paid, Some(tier)) {
+            info!(
+                "Reconciled credits for pool {}: booked {} bytes, distribution pays {} (adjustment {})",
+                hex::encode(&pool[..8]), rec.booked_bytes, rec.distributed_bytes, rec.adjustment,
+            );
+        }
+    }
+
+    /// Apply progress reports from proving threads.
+    ///
+    /// Cheap and non-blocking — called every `poll_once()` so IPC progress
+    /// events are timely. Finished jobs free a slot for the next queued one.
+    fn poll_proof_jobs(&mut self) {
+        let mut finished = false;
+        while let Ok(update) = self.proof_job_rx.try_recv() {
+            match update {
+                ProofJobUpdate::Verifying(id) => self.proof_jobs.mark_verifying(id),
+                ProofJobUpdate::Done { id, proof_bytes, public_values, cycles } => {
                     info!(
-                        "Skipping distribution for pool {}: subscription still active (expires in {}s)",
-                        hex::encode(&user_pubkey[..8]),
-                        entry.expires_at - now_unix,
+                        "Distribution proof job {} done: {} proof bytes, {} public values, {} cycles",
+                        id, proof_bytes.len(), public_values.len(),
+                        cycles.map_or_else(|| "unknown".to_string(), |c| c.to_string()),
                     );
-                    continue;
+                    self.proof_jobs.mark_done(id, proof_bytes, public_values, cycles);
+                    finished = true;
+                }
+                ProofJobUpdate::Failed { id, error } => {
+                    error!("Distribution proof job {} failed: {}", id, error);
+                    self.proof_jobs.mark_failed(id, error);
+                    finished = true;
                 }
-                info!(
-                    "Pool {} epoch expired (expired {}s ago) — proceeding with distribution",
-                    hex::encode(&user_pubkey[..8]),
-                    now_unix - entry.expires_at,
-                );
-            } else {
-                // No subscription info — skip until we receive it via gossip
-                info!(
-                    "Skipping distribution for pool {}: no subscription info in cache",
-                    hex::encode(&user_pubkey[..8]),
-                );
-                continue;
             }
+        }
+        #[cfg(any(feature = "sp1", feature = "risc0"))]
+        if finished {
+            self.start_proof_jobs();
+        }
+        #[cfg(not(any(feature = "sp1", feature = "risc0")))]
+        let _ = finished;
+    }
 
-            let pool_key = (*user_pubkey, _pool_type.clone());
-            let Some(dist) = self.aggregator.as_ref().unwrap().build_distribution(&pool_key) else {
-                continue;
-            };
+    /// Start queued proof jobs while concurrency slots are free.
+    #[cfg(any(feature = "sp1", feature = "risc0"))]
+    fn start_proof_jobs(&mut self) {
+        if !self.proof_jobs.has_queued() {
+            return;
+        }
 
-            // Record distribution-built event in history
-            if let Some(ref mut agg) = self.aggregator {
-                agg.record_distribution_built(
-                    *user_pubkey, *_pool_type,
-                    dist.root, dist.total, dist.entries.len(),
-                );
+        // Lazy-init the distribution prover
+        if self.distribution_prover.is_none() {
+            let Some(backend) = craftnet_prover::backend_from_env() else {
+                error!("Unknown {} value — cannot generate distribution proof", craftnet_prover::PROVER_BACKEND_ENV);
+                return;
+            };
+            info!("Initializing {} distribution prover...", backend);
+            match craftnet_prover::configured_distribution_prover(backend) {
+                Ok(prover) => self.distribution_prover = Some(Arc::from(prover)),
+                Err(e) => {
+                    error!("Cannot initialize distribution prover: {}", e);
+                    return;
+                }
             }
+        }
+        let prover = Arc::clone(self.distribution_prover.as_ref().unwrap());
 
+        for job in self.proof_jobs.start_ready() {
             info!(
-                "Distribution built for pool {}: {} entries, {} total bytes",
-                hex::encode(&user_pubkey[..8]),
-                dist.entries.len(),
-                dist.total,
+                "Proving distribution for pool {} (job {}, attempt {}, {} entries)",
+                hex::encode(&job.pool_pubkey[..8]), job.id, job.attempts, job.entries.len(),
             );
+            let prover = Arc::clone(&prover);
+            let tx = self.proof_job_tx.clone();
 
-            // Verify on-chain subscription exists before expensive proving
-            if let Some(ref settlement) = self.settlement_client {
-                match settlement.get_subscription_state(*user_pubkey).await {
-                    Ok(Some(state)) => {
-                        if state.distribution_posted {
-                            info!("Distribution already posted on-chain for pool {} — skipping", hex::encode(&user_pubkey[..8]));
-                            self.posted_distributions.insert(*user_pubkey);
-                            continue;
+            // Proving takes minutes — keep it off the async event loop
+            tokio::task::spawn_blocking(move || {
+                let update = match prover.prove_distribution(&job.entries, job.pool_pubkey) {
+                    Ok(proof) => {
+                        let _ = tx.send(ProofJobUpdate::Verifying(job.id));
+                        match prover.verify_distribution(&proof) {
+                            Ok(()) => ProofJobUpdate::Done {
+                                id: job.id,
+                                proof_bytes: proof.proof_bytes,
+                                public_values: proof.public_values,
+                                cycles: proof.cycles,
+                            },
+                            Err(e) => ProofJobUpdate::Failed { id: job.id, error: e.to_string() },
                         }
-                        info!("On-chain subscription confirmed for pool {} (balance: {})", hex::encode(&user_pubkey[..8]), state.pool_balance);
-                    }
-                    Ok(None) => {
-                        info!("No on-chain subscription for pool {} — skipping prove", hex::encode(&user_pubkey[..8]));
-                        self.posted_distributions.insert(*user_pubkey);
-                        continue;
-                    }
-                    Err(e) => {
-                        warn!("Failed to check subscription for pool {}: {} — skipping this round", hex::encode(&user_pubkey[..8]), e);
-                        continue;
                     }
-                }
-            }
+                    Err(e) => ProofJobUpdate::Failed { id: job.id, error: e.to_string() },
+                };
+                let _ = tx.send(update);
+            });
+        }
+    }
 
-            // Generate Groth16 proof (SP1 feature required)
-            #[cfg(not(feature = "sp1"))]
-            {
-                warn!("SP1 feature not enabled — cannot generate distribution proof, skipping post");
-                continue;
-            }
+    /// Post distributions whose proof jobs are Done, dropping each job once
+    /// the distribution is on-chain (or can never be posted).
+    #[cfg(any(feature = "sp1", feature = "risc0"))]
+    async fn post_proven_distributions(&mut self) {
+        let done = self.proof_jobs.done_jobs();
+        if done.is_empty() {
+            return;
+        }
+        let Some(ref settlement) = self.settlement_client else {
+            warn!("No settlement client — cannot post distribution on-chain");
+            return;
+        };
+        let settlement = Arc::clone(settlement);
+
+        for job in done {
+            let user_pubkey = &job.pool_pubkey;
+            let post = PostDistribution {
+                pool_pubkey: job.pool_pubkey,
+                distribution_root: job.distribution_root,
+                total_bytes: job.total_bytes,
+                groth16_proof: job.proof_bytes,
+                sp1_public_inputs: job.public_values,
+            };
 
-            #[cfg(feature = "sp1")]
-            let (groth16_proof, sp1_public_inputs) = {
-                // Lazy-init the distribution prover
-                if self.distribution_prover.is_none() {
-                    info!("Initializing SP1 distribution prover...");
-                    self.distribution_prover = Some(craftnet_prover::DistributionProver::new());
+            match settlement.post_distribution(post).await {
+                Ok(sig) => {
+                    info!(
+                        "Distribution posted on-chain for pool {}: sig={}",
+                        hex::encode(&user_pubkey[..8]),
+                        hex::encode(sig),
+                    );
+                    self.posted_distributions.insert(*user_pubkey);
+                    self.proof_jobs.remove(job.id);
                 }
-                let prover = self.distribution_prover.as_ref().unwrap();
-                let entries: Vec<([u8; 32], u64)> = dist.entries.iter()
-                    .map(|(relay, bytes)| (*relay, *bytes))
-                    .collect();
-                match prover.prove_distribution(&entries, *user_pubkey) {
-                    Ok(proof) => {
+                Err(SettlementError::AggregatorNotRegistered) => {
+                    // Keep the proof: a later registry may include us
+                    warn!(
+                        "Not a registered aggregator — holding distribution for pool {}",
+                        hex::encode(&user_pubkey[..8]),
+                    );
+                }
+                Err(e) => {
+                    let err_str = format!("{}", e);
+                    if err_str.contains("already been posted") || err_str.contains("AlreadyPosted") {
                         info!(
-                            "Groth16 proof generated: {} proof bytes, {} public values, vkey={}",
-                            proof.proof_bytes.len(),
-                            proof.public_values.len(),
-                            proof.vkey_hash,
+                            "Distribution already posted for pool {} — marking done",
+                            hex::encode(&user_pubkey[..8]),
                         );
-                        (proof.proof_bytes, proof.public_values)
-                    }
-                    Err(e) => {
-                        error!("Groth16 distribution proof failed for pool {}: {}", hex::encode(&user_pubkey[..8]), e);
-                        continue;
-                    }
-                }
-            };
-
-            // Post on-chain via settlement client
-            #[cfg(feature = "sp1")]
-            {
-                let Some(ref settlement) = self.settlement_client else {
-                    warn!("No settlement client — cannot post distribution on-chain");
-                    continue;
-                };
-
-                let post = PostDistribution {
-                    pool_pubkey: *user_pubkey,
-                    distribution_root: dist.root,
-                    total_bytes: dist.total,
-                    groth16_proof,
-                    sp1_public_inputs,
-                };
-
-                match settlement.post_distribution(post).await {
-                    Ok(sig) => {
+                        self.posted_distributions.insert(*user_pubkey);
+                        self.proof_jobs.remove(job.id);
+                    } else if err_str.contains("AccountNotInitialized") || err_str.contains("not initialized") {
+                        // No on-chain subscription for this pool — skip permanently
                         info!(
-                            "Distribution posted on-chain for pool {}: sig={}",
+                            "No on-chain subscription for pool {} — skipping",
                             hex::encode(&user_pubkey[..8]),
-                            hex::encode(sig),
                         );
                         self.posted_distributions.insert(*user_pubkey);
-                    }
-                    Err(e) => {
-                        let err_str = format!("{}", e);
-                        if err_str.contains("already been posted") || err_str.contains("AlreadyPosted") {
-                            info!(
-                                "Distribution already posted for pool {} — marking done",
-                                hex::encode(&user_pubkey[..8]),
-                            );
-                            self.posted_distributions.insert(*user_pubkey);
-                        } else if err_str.contains("AccountNotInitialized") || err_str.contains("not initialized") {
-                            // No on-chain subscription for this pool — skip permanently
-                            info!(
-                                "No on-chain subscription for pool {} — skipping",
-                                hex::encode(&user_pubkey[..8]),
-                            );
-                            self.posted_distributions.insert(*user_pubkey);
-                        } else {
-                            error!(
-                                "Failed to post distribution for pool {}: {}",
-                                hex::encode(&user_pubkey[..8]),
-                                e,
-                            );
-                        }
+                        self.proof_jobs.remove(job.id);
+                    } else {
+                        error!(
+                            "Failed to post distribution for pool {}: {}",
+                            hex::encode(&user_pubkey[..8]),
+                            e,
+                        );
                     }
                 }
             }
         }
     }
 
+    /// Distribution proof jobs, oldest first
+    pub fn proof_jobs(&self) -> &[ProofJob] {
+        self.proof_jobs.jobs()
+    }
+
+    /// Drain proof job state changes since the last call (for IPC events)
+    pub fn take_proof_job_events(&mut self) -> Vec<ProofJobEvent> {
+        self.proof_jobs.take_events()
+    }
+
     /// Reconcile aggregator state with on-chain data after loading from disk.
     ///
     /// For each tracked pool, queries the on-chain subscription state to sync
@@ -5208,6 +10153,18 @@ impl CraftNetNode {
         );
     }
 
+    /// Follow only the pools `pools` selects (aggregator mode). Returns
+    /// the number of chains re-synced because the selection widened.
+    pub fn set_aggregator_pools(&mut self, pools: AggregatorPools) -> usize {
+        let filter = pools.filter(self.keypair.public_key_bytes());
+        self.config.aggregator_pools = pools;
+        let Some(ref mut aggregator) = self.aggregator else { return 0 };
+        let resynced = aggregator.set_pool_filter(filter);
+        info!("Aggregator pool filter set to {:?}", self.config.aggregator_pools);
+        self.sync_proof_subscriptions();
+        resynced
+    }
+
     /// Persist aggregator state (pools + pending proofs + posted_distributions) to disk.
     fn save_aggregator_state(&self) {
         let Some(ref aggregator) = self.aggregator else { return };
@@ -5215,13 +10172,52 @@ impl CraftNetNode {
         aggregator.save_to_file(path, &self.posted_distributions);
     }
 
-    /// Flush unflushed history entries to the append-only binary file.
+    /// Flush unflushed history entries to the append-only binary file
+    /// (and the pending spill index).
     fn flush_aggregator_history(&mut self) {
         let Some(ref mut aggregator) = self.aggregator else { return };
+        aggregator.flush_pending_spill();
         let Some(ref path) = self.aggregator_history_file else { return };
         aggregator.flush_history(path);
     }
 
+    /// Log the aggregator's anomaly detector events for operators
+    fn report_aggregator_anomalies(&mut self) {
+        let Some(ref mut aggregator) = self.aggregator else { return };
+        for event in aggregator.take_anomaly_events() {
+            match event {
+                craftnet_aggregator::AnomalyEvent::Quarantined { relay, pool, pool_type, reason, .. } => warn!(
+                    "Aggregator quarantined proof from relay {} on pool {} ({:?}): {:?}",
+                    hex::encode(&relay[..8]), hex::encode(&pool[..8]), pool_type, reason,
+                ),
+                craftnet_aggregator::AnomalyEvent::Released { relay, pool, pool_type, via, .. } => info!(
+                    "Aggregator released quarantined proof from relay {} on pool {} ({:?}) via {:?}",
+                    hex::encode(&relay[..8]), hex::encode(&pool[..8]), pool_type, via,
+                ),
+                craftnet_aggregator::AnomalyEvent::Rejected { relay, pool, pool_type, reason, .. } => warn!(
+                    "Aggregator rejected anomalous proof from relay {} on pool {} ({:?}): {:?}",
+                    hex::encode(&relay[..8]), hex::encode(&pool[..8]), pool_type, reason,
+                ),
+            }
+        }
+    }
+
+    /// Downsample aged bandwidth buckets and prune the history file per the
+    /// retention policy, at most once per `AGGREGATOR_RETENTION_INTERVAL`.
+    fn maybe_apply_aggregator_retention(&mut self) {
+        let now = self.clock.now();
+        if self.last_aggregator_retention.is_some_and(|t| now.duration_since(t) < AGGREGATOR_RETENTION_INTERVAL) {
+            return;
+        }
+        let Some(ref mut aggregator) = self.aggregator else { return };
+        self.last_aggregator_retention = Some(now);
+        aggregator.compact_bandwidth();
+        let Some(ref path) = self.aggregator_history_file else { return };
+        if let Err(e) = aggregator.prune_history(path, self.config.aggregator_history_max_bytes) {
+            warn!("Failed to prune aggregator history {}: {}", path.display(), e);
+        }
+    }
+
     /// Announce our subscription to the network (client mode)
     pub fn announce_subscription(&mut self, tier: u8, expires_at: u64) {
         let timestamp = std::time::SystemTime::now()
@@ -5239,6 +10235,7 @@ impl CraftNetNode {
 
         let signable = announcement.signable_data();
         announcement.signature = craftec_crypto::sign_data(&self.keypair, &signable).to_vec();
+        self.own_subscription_tier = if expires_at > timestamp { SubscriptionTier::from_u8(tier) } else { None };
 
         if self.swarm_cmd_tx.is_some() {
             self.send_swarm_cmd(craftec_network::SharedSwarmCommand::PublishGossipsub {
@@ -5265,9 +10262,21 @@ impl CraftNetNode {
         }
         if self.swarm_cmd_tx.is_some() {
             debug!("Starting relay discovery via DHT (existing relay_nodes={})", self.relay_nodes.len());
-            self.send_swarm_cmd(craftec_network::SharedSwarmCommand::GetProvidersSecondary(
-                libp2p::kad::RecordKey::new(&craftnet_network::RELAY_REGISTRY_KEY),
-            ));
+            let shards = if online_relays == 0 {
+                self.send_swarm_cmd(craftec_network::SharedSwarmCommand::GetProvidersSecondary(
+                    libp2p::kad::RecordKey::new(&craftnet_network::RELAY_REGISTRY_KEY),
+                ));
+                REGISTRY_SHARDS
+            } else {
+                Self::REGISTRY_SHARDS_PER_DISCOVERY
+            };
+            for _ in 0..shards {
+                let shard = self.relay_shard_cursor;
+                self.relay_shard_cursor = (shard + 1) % REGISTRY_SHARDS;
+                self.send_swarm_cmd(craftec_network::SharedSwarmCommand::GetProvidersSecondary(
+                    libp2p::kad::RecordKey::new(&craftnet_network::relay_registry_shard_key(shard)),
+                ));
+            }
             self.last_relay_discovery = Some(std::time::Instant::now());
         }
     }
@@ -5282,11 +10291,18 @@ impl CraftNetNode {
         }
 
         let pubkey = relay_info.pubkey;
+        if !self.accept_record_binding(&pubkey, relay_info.peer_binding.as_ref(), peer_id) {
+            return;
+        }
+        if peer_id.is_some_and(|p| self.is_outdated_peer(&p)) {
+            debug!("Ignoring relay {} below the minimum protocol version", hex::encode(&pubkey[..8]));
+            return;
+        }
         let is_new = !self.relay_nodes.contains_key(&pubkey);
 
         if is_new {
             let pid = peer_id.unwrap_or(PeerId::random());
-            let status = RelayNodeStatus::new(relay_info, pid);
+            let status = RelayNodeStatus::new(relay_info, pid, self.clock.now());
             self.relay_nodes.insert(pubkey, status);
 
             info!(
@@ -5319,7 +10335,10 @@ impl CraftNetNode {
         } else {
             // Update existing entry
             if let Some(status) = self.relay_nodes.get_mut(&pubkey) {
-                status.last_dht_seen = std::time::Instant::now();
+                status.last_dht_seen = self.clock.now();
+                if relay_info.capacity.is_some() {
+                    status.capacity = relay_info.capacity;
+                }
                 status.info = relay_info;
                 if let Some(pid) = peer_id {
                     status.peer_id = pid;
@@ -5330,7 +10349,7 @@ impl CraftNetNode {
 
     /// Mark relays as offline if no heartbeat for RELAY_OFFLINE_THRESHOLD
     fn check_relay_timeouts(&mut self) {
-        let now = std::time::Instant::now();
+        let now = self.clock.now();
         for status in self.relay_nodes.values_mut() {
             if status.online {
                 let last_seen = status.last_heartbeat
@@ -5347,7 +10366,7 @@ impl CraftNetNode {
     fn cleanup_stale_relays(&mut self) {
         use craftnet_network::RELAY_RECORD_TTL;
 
-        let now = std::time::Instant::now();
+        let now = self.clock.now();
         let before_count = self.relay_nodes.len();
 
         self.relay_nodes.retain(|_pubkey, status| {
@@ -5364,10 +10383,12 @@ impl CraftNetNode {
     fn announce_relay_offline(&mut self) {
         if self.swarm_cmd_tx.is_some() {
             let peer_id_str = self.local_peer_id.map(|p| p.to_string()).unwrap_or_default();
-            let msg = RelayStatusMessage::offline(
+            let mut msg = RelayStatusMessage::offline(
                 self.keypair.public_key_bytes(),
                 &peer_id_str,
             );
+            msg.timestamp = self.clock.unix_now();
+            msg.sign(&self.keypair, rand::random());
             self.send_swarm_cmd(craftec_network::SharedSwarmCommand::PublishGossipsub {
                 topic: craftnet_network::RELAY_STATUS_TOPIC.to_string(),
                 data: msg.to_bytes(),
@@ -5376,6 +10397,11 @@ impl CraftNetNode {
             self.send_swarm_cmd(craftec_network::SharedSwarmCommand::StopProvidingSecondary(
             libp2p::kad::RecordKey::new(&craftnet_network::RELAY_REGISTRY_KEY)
         ));
+            self.send_swarm_cmd(craftec_network::SharedSwarmCommand::StopProvidingSecondary(
+                libp2p::kad::RecordKey::new(&craftnet_network::relay_registry_shard_key(
+                    registry_shard(&self.keypair.public_key_bytes()),
+                )),
+            ));
         }
     }
 
@@ -5401,6 +10427,16 @@ impl CraftNetNode {
             .unwrap_or_default()
     }
 
+    /// Get one compressed Merkle multiproof for several relays' claims in a
+    /// pool's distribution (if aggregator is enabled)
+    pub fn aggregator_batch_proof(
+        &self,
+        pool_key: &(PublicKey, PoolType),
+        relays: &[PublicKey],
+    ) -> Option<craftnet_aggregator::DistributionBatchProof> {
+        self.aggregator.as_ref()?.get_distribution_batch_proof(pool_key, relays)
+    }
+
     /// Get all pool keys tracked by the aggregator (both Subscribed and Free)
     pub fn aggregator_pool_keys(&self) -> Vec<(PublicKey, PoolType)> {
         self.aggregator.as_ref()
@@ -5489,21 +10525,35 @@ impl CraftNetNode {
             return;
         }
 
-        let now = Instant::now();
+        let now = self.clock.now();
+        let draining = self.drain_deadline.is_some();
+        // Offline: proofs couldn't be published, so let receipts pile up
+        // and cut one larger batch per pool once peers are back
+        if self.connected_peers.is_empty() && !draining {
+            return;
+        }
 
         // Find pools that are ready to compress:
         // - queue_len >= proof_batch_size (batch full), OR
+        // - queued payload bytes >= proof_publish.batch_bytes, OR
         // - oldest receipt age >= proof_deadline (deadline expired)
+        // and whose last batch is at least proof_publish.min_interval old
+        let publish = self.config.proof_publish;
         let best_pool = self.proof_queue.iter()
             .filter(|(_, q)| !q.is_empty())
             .filter(|(k, _)| !self.needs_chain_recovery.contains(k))
             .filter(|(k, q)| {
-                let batch_ready = q.len() >= self.proof_batch_size;
+                let batch_ready = q.len() >= self.proof_batch_size
+                    || self.proof_queue_bytes.get(k).copied().unwrap_or(0) >= publish.batch_bytes;
                 let deadline_expired = self.proof_oldest_receipt
                     .get(k)
                     .map(|t| now.duration_since(*t) >= self.proof_deadline)
                     .unwrap_or(false);
-                batch_ready || deadline_expired
+                let interval_passed = self.proof_last_batch
+                    .get(k)
+                    .is_none_or(|t| now.duration_since(*t) >= publish.min_interval);
+                // Draining: flush whatever is queued
+                draining || (interval_passed && (batch_ready || deadline_expired))
             })
             .max_by_key(|(_, q)| q.len())
             .map(|(k, q)| (*k, q.len()));
@@ -5521,6 +10571,12 @@ impl CraftNetNode {
         let pool_key = (pool, pool_type);
         let queue = self.proof_queue.get_mut(&pool_key).unwrap();
         let batch: Vec<ForwardReceipt> = queue.drain(..batch_size).collect();
+        let batch_bytes: u64 = batch.iter().map(|r| r.payload_size as u64).sum();
+        if let Some(queued) = self.proof_queue_bytes.get_mut(&pool_key) {
+            *queued = queued.saturating_sub(batch_bytes);
+        }
+        self.proof_last_batch.insert(pool_key, now);
+        self.refill_from_spill(pool_key);
 
         self.compressor_busy = true;
 
@@ -5534,12 +10590,13 @@ impl CraftNetNode {
 
         // Spawn compression on a blocking thread to avoid starving the async event loop
         let batch_clone = batch.clone();
+        let chunk_size = self.config.proof_chunk_size;
         let (tx, rx) = tokio::sync::oneshot::channel();
         self.compression_result_rx = Some(rx);
 
         tokio::task::spawn_blocking(move || {
             let start = Instant::now();
-            let output = compressor.compress(&batch_clone);
+            let output = compress_chunked(compressor.as_ref(), &batch_clone, chunk_size);
             let _ = tx.send(CompressionResult {
                 pool_key,
                 batch: batch_clone,
@@ -5572,7 +10629,7 @@ impl CraftNetNode {
         let pool_key = result.pool_key;
         let (pool, pool_type) = pool_key;
 
-        let compressed = match result.output {
+        let chunks = match result.output {
             Ok(output) => output,
             Err(e) => {
                 warn!("Compressor failed: {:?}", e);
@@ -5580,6 +10637,8 @@ impl CraftNetNode {
                 self.compressions_failed += 1;
                 // Re-queue the batch
                 let queue = self.proof_queue.entry(pool_key).or_default();
+                let batch_bytes: u64 = result.batch.iter().map(|r| r.payload_size as u64).sum();
+                *self.proof_queue_bytes.entry(pool_key).or_default() += batch_bytes;
                 for receipt in result.batch.into_iter().rev() {
                     queue.push_front(receipt);
                 }
@@ -5587,59 +10646,67 @@ impl CraftNetNode {
             }
         };
 
-        let new_root = compressed.root;
-        let (prev_root, prev_bytes) = self.pool_roots.get(&pool_key)
+        // One chained proof per chunk: each chunk's root extends the chain
+        // where the previous one left it
+        let (mut prev_root, mut cumulative_bytes) = self.pool_roots.get(&pool_key)
             .copied()
             .unwrap_or(([0u8; 32], 0));
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let mut offset = 0;
+        for chunk in &chunks {
+            cumulative_bytes += chunk.bytes;
+
+            // Generate proof message for gossip
+            let mut msg = ProofMessage {
+                relay_pubkey: self.keypair.public_key_bytes(),
+                pool_pubkey: pool,
+                pool_type,
+                batch_bytes: chunk.bytes,
+                cumulative_bytes,
+                prev_root,
+                new_root: chunk.root,
+                proof: vec![],
+                timestamp,
+                signature: vec![], // placeholder, signed below
+            };
 
-        let batch_bytes_total: u64 = result.batch.iter().map(|r| r.payload_size as u64).sum();
-        let cumulative_bytes = prev_bytes + batch_bytes_total;
-
-        // Generate proof message for gossip
-        let mut msg = ProofMessage {
-            relay_pubkey: self.keypair.public_key_bytes(),
-            pool_pubkey: pool,
-            pool_type,
-            batch_bytes: batch_bytes_total,
-            cumulative_bytes,
-            prev_root,
-            new_root,
-            proof: vec![],
-            timestamp: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
-            signature: vec![], // placeholder, signed below
-        };
+            // Sign the proof message with relay's ed25519 keypair
+            let sig = craftec_crypto::sign_data(&self.keypair, &msg.signable_data());
+            msg.signature = sig.to_vec();
 
-        // Sign the proof message with relay's ed25519 keypair
-        let sig = craftec_crypto::sign_data(&self.keypair, &msg.signable_data());
-        msg.signature = sig.to_vec();
+            // Queue for gossip (merged with an unpublished proof of the same
+            // pool, if any)
+            let keypair = &self.keypair;
+            self.proof_outbox.push(msg, |m| craftec_crypto::sign_data(keypair, &m.signable_data()).to_vec());
 
-        // Publish to gossipsub
-        if self.swarm_cmd_tx.is_some() {
-            let data = msg.to_bytes();
-            self.send_swarm_cmd(craftec_network::SharedSwarmCommand::PublishGossipsub {
-                topic: craftnet_network::PROOF_TOPIC.to_string(),
-                data,
-            });
+            // The chunk is under a Merkle root now: keep only its per-hour totals
+            let receipts = &result.batch[offset..offset + chunk.receipts];
+            self.compact_receipts(pool_key, receipts, chunk.root);
+            offset += chunk.receipts;
+            prev_root = chunk.root;
+        }
+        if chunks.len() > 1 {
             debug!(
-                "Published proof for pool {} {:?} (batch_bytes: {}, cumulative_bytes: {})",
+                "Compressed {} receipts for pool {} in {} chunks",
+                result.batch.len(),
                 hex::encode(&pool[..8]),
-                pool_type,
-                batch_bytes_total,
-                cumulative_bytes,
+                chunks.len(),
             );
         }
+        self.chunks_compressed += chunks.len() as u64;
+        self.flush_proof_outbox();
 
         // Update pool roots
-        self.pool_roots.insert(pool_key, (new_root, cumulative_bytes));
+        self.pool_roots.insert(pool_key, (prev_root, cumulative_bytes));
 
         // Reset deadline tracker: if queue is now empty, remove; otherwise reset timer
         if self.proof_queue.get(&pool_key).is_none_or(|q| q.is_empty()) {
             self.proof_oldest_receipt.remove(&pool_key);
         } else {
-            self.proof_oldest_receipt.insert(pool_key, Instant::now());
+            self.proof_oldest_receipt.insert(pool_key, self.clock.now());
         }
 
         // Persist proof state after successful compression (also resets enqueue counter)
@@ -5656,6 +10723,238 @@ impl CraftNetNode {
         self.compressor_busy = false;
     }
 
+    /// Move spilled receipts of a pool back into its queue, as far as the
+    /// queue limit allows
+    fn refill_from_spill(&mut self, pool_key: (PublicKey, PoolType)) {
+        let Some(ref mut spill) = self.receipt_spill else { return };
+        let queue = self.proof_queue.entry(pool_key).or_default();
+        let room = self.proof_queue_limit.saturating_sub(queue.len());
+        match spill.take(&pool_key, room) {
+            Ok(receipts) if !receipts.is_empty() => {
+                let bytes: u64 = receipts.iter().map(|r| r.payload_size as u64).sum();
+                debug!(
+                    "Refilled {} spilled receipts for pool {} ({:?}), {} left on disk",
+                    receipts.len(),
+                    hex::encode(&pool_key.0[..8]),
+                    pool_key.1,
+                    spill.spilled(&pool_key),
+                );
+                queue.extend(receipts);
+                *self.proof_queue_bytes.entry(pool_key).or_default() += bytes;
+                self.proof_oldest_receipt.entry(pool_key).or_insert_with(|| self.clock.now());
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Failed to read spilled receipts for pool {}: {}", hex::encode(&pool_key.0[..8]), e),
+        }
+    }
+
+    /// Fold a compressed batch into the per-(pool, hour) accumulators and
+    /// drop its receipts from memory. Once enough have been dropped, the
+    /// receipts file is rewritten with what is still unproven so a restart
+    /// doesn't load them back.
+    fn compact_receipts(&mut self, pool_key: (PublicKey, PoolType), batch: &[ForwardReceipt], root: [u8; 32]) {
+        self.receipt_compactor.fold(pool_key, batch, root);
+        for receipt in batch {
+            if let Some(receipts) = self.forward_receipts.get_mut(&receipt.shard_id) {
+                receipts.retain(|r| r.signature != receipt.signature);
+                if receipts.is_empty() {
+                    self.forward_receipts.remove(&receipt.shard_id);
+                }
+            }
+        }
+        self.receipts_compacted_since_rewrite += batch.len();
+        if self.receipts_compacted_since_rewrite >= RECEIPT_FILE_REWRITE_THRESHOLD {
+            self.rewrite_receipt_file();
+        }
+    }
+
+    /// Replace the receipts file with the receipts still held in memory.
+    ///
+    /// Shares the in-flight slot of `flush_receipts()`, so no append can
+    /// land in the old file while the new one is being written; the
+    /// unflushed buffer is part of the rewrite.
+    fn rewrite_receipt_file(&mut self) {
+        let Some(ref path) = self.receipt_file else {
+            self.receipts_compacted_since_rewrite = 0;
+            return;
+        };
+        if self.flush_result_rx.is_some() {
+            return;
+        }
+        self.receipts_compacted_since_rewrite = 0;
+        self.receipt_buffer.clear();
+        let receipts: Vec<ForwardReceipt> = self.forward_receipts.values().flatten().cloned().collect();
+        let path = path.clone();
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        self.flush_result_rx = Some(rx);
+
+        tokio::task::spawn_blocking(move || {
+            let result = (|| -> std::io::Result<usize> {
+                let tmp_path = path.with_extension("jsonl.tmp");
+                let mut file = std::io::BufWriter::new(std::fs::File::create(&tmp_path)?);
+                for receipt in &receipts {
+                    if let Ok(json) = serde_json::to_string(receipt) {
+                        writeln!(file, "{}", json)?;
+                    }
+                }
+                file.flush()?;
+                drop(file);
+                std::fs::rename(&tmp_path, &path)?;
+                Ok(receipts.len())
+            })();
+            let _ = tx.send(result);
+        });
+    }
+
+    /// Publish queued proofs while connected and within the gossip budget.
+    ///
+    /// The budget follows our advertised bandwidth class unless
+    /// `proof_publish.gossip_budget` sets one, and also pays for the gossip
+    /// uplink traffic accounting measured beyond the proofs themselves; a
+    /// draining node publishes everything regardless.
+    fn flush_proof_outbox(&mut self) {
+        // Charged on every call so an idle outbox doesn't save up a burst
+        let now = self.clock.now();
+        let measured = self.traffic.read().unwrap().total(TrafficProtocol::Gossip).bytes_out;
+        self.proof_gossip_budget.charge(measured.saturating_sub(self.gossip_out_charged), now);
+        self.gossip_out_charged = self.gossip_out_charged.max(measured);
+
+        if self.proof_outbox.is_empty() || self.swarm_cmd_tx.is_none() || self.connected_peers.is_empty() {
+            return;
+        }
+        if self.proof_retry_at.is_some_and(|at| now < at) {
+            return;
+        }
+        self.proof_retry_at = None;
+        let rate = self.config.proof_publish.budget_for(self.advertised_relay_capacity().bandwidth_class);
+        self.proof_gossip_budget.set_rate(rate);
+        let draining = self.drain_deadline.is_some();
+
+        while let Some(msg) = self.proof_outbox.front() {
+            let data = msg.to_bytes();
+            if !draining && !self.proof_gossip_budget.try_spend(data.len(), now) {
+                debug!("Proof gossip budget spent, {} proofs waiting", self.proof_outbox.len());
+                break;
+            }
+            let Some(msg) = self.proof_outbox.pop() else { break };
+            self.gossip_out_charged += data.len() as u64;
+            self.archive_proof(&msg);
+            // Also on the unsharded topic while older aggregators only follow
+            // that one; receivers drop the second copy as already handled
+            self.send_swarm_cmd(craftec_network::SharedSwarmCommand::PublishGossipsub {
+                topic: PROOF_TOPIC.to_string(),
+                data: data.clone(),
+            });
+            self.send_swarm_cmd(craftec_network::SharedSwarmCommand::PublishGossipsub {
+                topic: proof_topic_for_pool(&msg.pool_pubkey),
+                data,
+            });
+            debug!(
+                "Published proof for pool {} {:?} (batch_bytes: {}, cumulative_bytes: {})",
+                hex::encode(&msg.pool_pubkey[..8]),
+                msg.pool_type,
+                msg.batch_bytes,
+                msg.cumulative_bytes,
+            );
+        }
+    }
+
+    /// Aggregators a relay hands proofs to directly when gossip can't
+    const DIRECT_PROOF_AGGREGATORS: usize = 3;
+
+    /// Pause of the proof outbox after no aggregator took undelivered proofs
+    const PROOF_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+    /// Submit proofs the swarm couldn't gossip to aggregators directly
+    fn drain_undelivered_proofs(&mut self) {
+        let mut proofs = Vec::new();
+        while let Ok(data) = self.undelivered_proof_rx.try_recv() {
+            proofs.push(data);
+        }
+        while let Ok(unanswered) = self.proof_submit_rx.try_recv() {
+            self.requeue_proofs(unanswered);
+        }
+        if !proofs.is_empty() {
+            self.submit_proofs_directly(proofs);
+        }
+    }
+
+    /// Peers of the registered aggregators we have a verified binding for,
+    /// in registry order. Bindings not known yet are looked up.
+    fn known_aggregator_peers(&mut self) -> Vec<PeerId> {
+        let epoch = aggregator_epoch(self.clock.unix_now());
+        let keys = self.aggregator_registry.as_ref().map(|r| r.active(epoch)).unwrap_or_default();
+        let mut peers = Vec::new();
+        for key in keys {
+            match self.verified_bindings.get(&key) {
+                Some(peer) if Some(*peer) != self.local_peer_id => peers.push(*peer),
+                Some(_) => {}
+                None => self.send_swarm_cmd(craftec_network::SharedSwarmCommand::GetRecordSecondary(
+                    libp2p::kad::RecordKey::new(&craftnet_network::peer_dht_key(&key)),
+                )),
+            }
+        }
+        peers
+    }
+
+    /// Push `proofs` to up to `DIRECT_PROOF_AGGREGATORS` known aggregators
+    /// (connected ones first). Proofs none of them answered go back to the
+    /// outbox; proofs they all rejected are dropped.
+    fn submit_proofs_directly(&mut self, proofs: Vec<Vec<u8>>) {
+        let mut peers = self.known_aggregator_peers();
+        peers.sort_by_key(|p| !self.connected_peers.contains(p));
+        peers.truncate(Self::DIRECT_PROOF_AGGREGATORS);
+        let Some(client) = self.proof_submit_client.clone().filter(|_| !peers.is_empty()) else {
+            debug!("{} proofs undelivered and no aggregator known, keeping them", proofs.len());
+            self.requeue_proofs(proofs);
+            return;
+        };
+        info!("Gossip has no proof subscribers, submitting {} proofs to {} aggregators", proofs.len(), peers.len());
+
+        let tx = self.proof_submit_tx.clone();
+        tokio::spawn(async move {
+            let mut unanswered = Vec::new();
+            for chunk in proofs.chunks(MAX_SUBMITTED_PROOFS) {
+                let mut answered = vec![false; chunk.len()];
+                let mut delivered = vec![false; chunk.len()];
+                for peer in &peers {
+                    match client.clone().submit(*peer, chunk.to_vec()).await {
+                        Ok(results) => {
+                            for (i, status) in results.iter().enumerate() {
+                                answered[i] = true;
+                                delivered[i] |= status.is_delivered();
+                                if let ProofSubmitStatus::Rejected(reason) = status {
+                                    debug!("Aggregator {} rejected submitted proof: {}", peer, reason);
+                                }
+                            }
+                        }
+                        Err(e) => debug!("Proof submission to {} failed: {}", peer, e),
+                    }
+                }
+                let rejected = answered.iter().zip(&delivered).filter(|(a, d)| **a && !**d).count();
+                if rejected > 0 {
+                    warn!("{} submitted proofs rejected by every aggregator", rejected);
+                }
+                unanswered.extend(chunk.iter().zip(&answered).filter(|(_, a)| !**a).map(|(p, _)| p.clone()));
+            }
+            let _ = tx.send(unanswered);
+        });
+    }
+
+    /// Put undelivered proofs back in the outbox and hold publication for a while
+    fn requeue_proofs(&mut self, proofs: Vec<Vec<u8>>) {
+        if proofs.is_empty() {
+            return;
+        }
+        for data in proofs.iter().rev() {
+            match ProofMessage::from_bytes(data) {
+                Ok(msg) => self.proof_outbox.requeue(msg),
+                Err(e) => debug!("Dropping undecodable proof: {:?}", e),
+            }
+        }
+        self.proof_retry_at = Some(self.clock.now() + Self::PROOF_RETRY_DELAY);
+    }
+
     /// Adjust batch size based on compression duration (adaptive).
     ///
     /// If proof took < 10s, increase batch size (up to 100K).
@@ -5674,8 +10973,9 @@ impl CraftNetNode {
     /// Persist proof state (pool_roots + pending receipts) to disk.
     ///
     /// Uses atomic write (tmp file + rename) to prevent corruption.
-    fn save_proof_state(&self) {
-        let Some(ref path) = self.proof_state_file else { return };
+    fn save_proof_state(&mut self) {
+        let Some(path) = self.proof_state_file.clone() else { return };
+        self.receipt_compactor.prune(self.clock.unix_now());
 
         let mut pool_roots_map = HashMap::new();
         for ((pubkey, pool_type), (root, cumulative_bytes)) in &self.pool_roots {
@@ -5697,9 +10997,18 @@ impl CraftNetNode {
             }
         }
 
+        let compacted = self.receipt_compactor.iter()
+            .map(|((pubkey, pool_type), hour, receipts)| CompactedHourEntry {
+                pool_key: format_pool_key(&pubkey, &pool_type),
+                hour,
+                receipts: receipts.clone(),
+            })
+            .collect();
+
         let state = ProofStateFile {
             pool_roots: pool_roots_map,
             pending_receipts,
+            compacted,
         };
 
         let json = match serde_json::to_string_pretty(&state) {
@@ -5716,7 +11025,7 @@ impl CraftNetNode {
             warn!("Failed to write proof state tmp file {}: {}", tmp_path.display(), e);
             return;
         }
-        if let Err(e) = std::fs::rename(&tmp_path, path) {
+        if let Err(e) = std::fs::rename(&tmp_path, &path) {
             warn!("Failed to rename proof state file {} -> {}: {}", tmp_path.display(), path.display(), e);
             return;
         }
@@ -5768,11 +11077,27 @@ impl CraftNetNode {
             queued: self.proof_queue_depth(),
             compressing: self.compressor_busy,
             batches_compressed: self.batches_compressed,
+            chunks_compressed: self.chunks_compressed,
             compressions_failed: self.compressions_failed,
             last_proof_duration_ms: self.last_proof_duration.map(|d| d.as_millis() as u64),
+            compacted_hours: self.receipt_compactor.len(),
+            receipts_compacted: self.receipt_compactor.folded(),
+            spilled_receipts: self.receipt_spill.as_ref().map_or(0, |s| s.total()),
+            payloads_compressed: self.payload_compression.payloads(),
+            payload_raw_bytes: self.payload_compression.raw_bytes(),
+            payload_compressed_bytes: self.payload_compression.compressed_bytes(),
+            payload_compression_ratio: self.payload_compression.ratio(),
         }
     }
 
+    /// Proven traffic of a pool per hour, oldest first
+    pub fn compacted_receipts(&self, pool: &PublicKey, pool_type: PoolType) -> Vec<(u64, HourlyReceipts)> {
+        self.receipt_compactor.hours_for(pool, pool_type)
+            .into_iter()
+            .map(|(hour, acc)| (hour, acc.clone()))
+            .collect()
+    }
+
     /// Get the current proof queue depth (for monitoring/debugging)
     pub fn proof_queue_depth(&self) -> usize {
         self.proof_queue.values().map(|q| q.len()).sum()
@@ -5786,15 +11111,27 @@ impl CraftNetNode {
 
 /// Runs a local libp2p Swarm for standalone CraftNet instances,
 /// bridging channels to and from it.
+#[allow(clippy::too_many_arguments)]
 async fn run_standalone_swarm(
     mut swarm: libp2p::Swarm<craftnet_network::CraftNetBehaviour>,
     mut cmd_rx: tokio::sync::mpsc::Receiver<craftec_network::SharedSwarmCommand>,
     evt_tx: tokio::sync::mpsc::Sender<craftec_network::SharedSwarmEvent>,
+    mut dht_rx: tokio::sync::mpsc::Receiver<DhtQuery>,
+    validators: DhtRecordValidators,
+    connections: SharedConnectionTable,
+    traffic: SharedTrafficAccounting,
+    undelivered_proofs: mpsc::UnboundedSender<Vec<u8>>,
+    clock: SharedClock,
 ) {
     use craftec_network::{SharedSwarmCommand, SharedSwarmEvent};
     use futures::StreamExt;
+    let mut penalties = RecordPenalties::default();
+    let mut dht_lookups = PendingDhtLookups::default();
     loop {
         tokio::select! {
+            Some(query) = dht_rx.recv() => {
+                serve_dht_query(&mut swarm, query, &mut dht_lookups);
+            }
             cmd = cmd_rx.recv() => {
                 let Some(cmd) = cmd else { break };
                 match cmd {
@@ -5802,8 +11139,28 @@ async fn run_standalone_swarm(
                     SharedSwarmCommand::Disconnect(peer_id) => { let _ = swarm.disconnect_peer_id(peer_id); }
                     SharedSwarmCommand::AddAddress(peer_id, addr) => { swarm.behaviour_mut().add_address(&peer_id, addr); }
                     SharedSwarmCommand::PublishGossipsub { topic, data } => {
+                        use libp2p::gossipsub::PublishError;
+                        // Proofs nobody received go to aggregators directly.
+                        // Each proof also goes out on the unsharded topic, so
+                        // only its shard publish falls back.
+                        let proof = parse_proof_shard_topic(&topic).is_some().then(|| data.clone());
                         let t = libp2p::gossipsub::IdentTopic::new(topic);
-                        let _ = swarm.behaviour_mut().gossipsub.publish(t, data);
+                        let topic_hash = t.hash();
+                        let len = data.len() as u64;
+                        match swarm.behaviour_mut().gossipsub.publish(t, data) {
+                            Ok(_) => {
+                                let mut traffic = traffic.write().unwrap();
+                                for peer in swarm.behaviour().gossipsub.mesh_peers(&topic_hash) {
+                                    traffic.record_out(*peer, TrafficProtocol::Gossip, len);
+                                }
+                            }
+                            Err(PublishError::NoPeersSubscribedToTopic | PublishError::AllQueuesFull(_)) => {
+                                if let Some(proof) = proof {
+                                    let _ = undelivered_proofs.send(proof);
+                                }
+                            }
+                            Err(_) => {}
+                        }
                     }
                     SharedSwarmCommand::SubscribeGossipsub(topic) => {
                         let t = libp2p::gossipsub::IdentTopic::new(topic);
@@ -5857,6 +11214,7 @@ async fn run_standalone_swarm(
                 use libp2p::swarm::SwarmEvent;
                 let shared_evt = match event {
                     SwarmEvent::ConnectionEstablished { peer_id, endpoint, .. } => {
+                        connections.write().unwrap().connection_established(peer_id, &endpoint, clock.unix_now());
                         // When a peer connects, ensure both DHTs know about this peer.
                         if let libp2p::core::ConnectedPoint::Dialer { address, .. } = &endpoint {
                             swarm.behaviour_mut().add_address(&peer_id, address.clone());
@@ -5881,6 +11239,7 @@ async fn run_standalone_swarm(
                         Some(SharedSwarmEvent::ConnectionEstablished(peer_id))
                     }
                     SwarmEvent::ConnectionClosed { peer_id, num_established, .. } => {
+                        connections.write().unwrap().connection_closed(&peer_id, num_established);
                         if num_established == 0 {
                             Some(SharedSwarmEvent::ConnectionClosed(peer_id))
                         } else {
@@ -5888,12 +11247,18 @@ async fn run_standalone_swarm(
                         }
                     }
                     SwarmEvent::Behaviour(craftnet_network::CraftNetBehaviourEvent::Gossipsub(libp2p::gossipsub::Event::Message { message, propagation_source, .. })) => {
+                        traffic.write().unwrap().record_in(propagation_source, TrafficProtocol::Gossip, message.data.len() as u64);
                         Some(SharedSwarmEvent::GossipsubMessage {
                             topic: message.topic,
                             data: message.data,
                             propagation_source: Some(propagation_source),
                         })
                     }
+                    SwarmEvent::Behaviour(craftnet_network::CraftNetBehaviourEvent::Identify(libp2p::identify::Event::Received { peer_id, info, .. })) => {
+                        let protocols = info.protocols.iter().map(|p| p.to_string()).collect();
+                        connections.write().unwrap().set_protocols(&peer_id, protocols);
+                        None
+                    }
                     SwarmEvent::Behaviour(craftnet_network::CraftNetBehaviourEvent::Mdns(libp2p::mdns::Event::Discovered(peers))) => {
                         Some(SharedSwarmEvent::MdnsDiscovered(peers.into_iter().collect()))
                     }
@@ -5909,6 +11274,17 @@ async fn run_standalone_swarm(
                         };
                         Some(SharedSwarmEvent::AutoNatStatusChanged(status))
                     }
+                    // Lookups started from `dht_rx` are answered there, not forwarded
+                    SwarmEvent::Behaviour(craftnet_network::CraftNetBehaviourEvent::Kademlia(libp2p::kad::Event::OutboundQueryProgressed { id, result, .. }))
+                        if dht_lookups.contains(DhtTable::Main, id) => {
+                        dht_lookups.complete(DhtTable::Main, id, &result, &validators);
+                        None
+                    }
+                    SwarmEvent::Behaviour(craftnet_network::CraftNetBehaviourEvent::KademliaSecondary(libp2p::kad::Event::OutboundQueryProgressed { id, result, .. }))
+                        if dht_lookups.contains(DhtTable::Registry, id) => {
+                        dht_lookups.complete(DhtTable::Registry, id, &result, &validators);
+                        None
+                    }
                     SwarmEvent::Behaviour(craftnet_network::CraftNetBehaviourEvent::Kademlia(libp2p::kad::Event::OutboundQueryProgressed { result, .. })) |
                     SwarmEvent::Behaviour(craftnet_network::CraftNetBehaviourEvent::KademliaSecondary(libp2p::kad::Event::OutboundQueryProgressed { result, .. })) => {
                         use libp2p::kad::QueryResult;
@@ -5916,10 +11292,25 @@ async fn run_standalone_swarm(
                         match result {
                             QueryResult::GetRecord(Ok(GetRecordOk::FoundRecord(record))) => {
                                 eprintln!("[swarm_evt] GetRecord FOUND key={:?}", String::from_utf8_lossy(record.record.key.as_ref()));
-                                Some(SharedSwarmEvent::KademliaSecondaryRecordFound {
-                                    key: record.record.key.clone(),
-                                    value: record.record.value.clone(),
-                                })
+                                if let Some(peer) = record.peer {
+                                    let len = (record.record.key.as_ref().len() + record.record.value.len()) as u64;
+                                    traffic.write().unwrap().record_in(peer, TrafficProtocol::Dht, len);
+                                }
+                                if let Err(e) = validators.validate(record.record.key.as_ref(), &record.record.value) {
+                                    warn!(
+                                        "Dropping invalid DHT record {} from {:?}: {}",
+                                        String::from_utf8_lossy(record.record.key.as_ref()), record.peer, e,
+                                    );
+                                    if let Some(peer) = record.peer {
+                                        penalize_record_peer(&mut swarm, &mut penalties, peer);
+                                    }
+                                    None
+                                } else {
+                                    Some(SharedSwarmEvent::KademliaSecondaryRecordFound {
+                                        key: record.record.key.clone(),
+                                        value: record.record.value.clone(),
+                                    })
+                                }
                             }
                             QueryResult::GetRecord(Ok(GetRecordOk::FinishedWithNoAdditionalRecord { cache_candidates })) => {
                                 eprintln!("[swarm_evt] GetRecord FINISHED_NO_ADDITIONAL cache={}", cache_candidates.len());
@@ -5955,6 +11346,23 @@ async fn run_standalone_swarm(
                             }
                         }
                     }
+                    SwarmEvent::NewListenAddr { address, .. } if craftnet_network::is_webrtc_addr(&address) => {
+                        // The WebRTC transport reports the address with its
+                        // certhash; this is what browser bootstrap lists need.
+                        info!(
+                            "[standalone] Browser clients can dial {}/p2p/{}",
+                            address, swarm.local_peer_id(),
+                        );
+                        None
+                    }
+                    SwarmEvent::Behaviour(craftnet_network::CraftNetBehaviourEvent::Kademlia(libp2p::kad::Event::InboundRequest { request })) => {
+                        store_inbound_kad_request(&mut swarm, false, request, &validators, &mut penalties, &traffic);
+                        None
+                    }
+                    SwarmEvent::Behaviour(craftnet_network::CraftNetBehaviourEvent::KademliaSecondary(libp2p::kad::Event::InboundRequest { request })) => {
+                        store_inbound_kad_request(&mut swarm, true, request, &validators, &mut penalties, &traffic);
+                        None
+                    }
                     _ => None,
                 };
                 if let Some(evt) = shared_evt {
@@ -5967,6 +11375,85 @@ async fn run_standalone_swarm(
     }
 }
 
+/// Store an inbound DHT record or provider announcement.
+///
+/// The swarm is built with record filtering, so nothing reaches the
+/// Kademlia store unless it is put there here. Records that fail
+/// validation are dropped and count against the peer that pushed them.
+/// Pushed records count as DHT traffic from the peer either way.
+fn store_inbound_kad_request(
+    swarm: &mut libp2p::Swarm<CraftNetBehaviour>,
+    secondary: bool,
+    request: libp2p::kad::InboundRequest,
+    validators: &DhtRecordValidators,
+    penalties: &mut RecordPenalties,
+    traffic: &SharedTrafficAccounting,
+) {
+    use libp2p::kad::store::RecordStore;
+    use libp2p::kad::InboundRequest;
+
+    let behaviour = swarm.behaviour_mut();
+    let kad = if secondary {
+        behaviour.kademlia_secondary.as_mut()
+    } else {
+        Some(&mut behaviour.kademlia)
+    };
+    let Some(kad) = kad else {
+        return;
+    };
+
+    match request {
+        InboundRequest::PutRecord { source, record: Some(record), .. } => {
+            let len = (record.key.as_ref().len() + record.value.len()) as u64;
+            traffic.write().unwrap().record_in(source, TrafficProtocol::Dht, len);
+            match validators.validate(record.key.as_ref(), &record.value) {
+                Ok(_) => {
+                    if let Err(e) = kad.store_mut().put(record) {
+                        debug!("DHT store rejected record from {}: {:?}", source, e);
+                    }
+                }
+                Err(e) => {
+                    warn!(
+                        "Rejected DHT record {} from {}: {}",
+                        String::from_utf8_lossy(record.key.as_ref()), source, e,
+                    );
+                    penalize_record_peer(swarm, penalties, source);
+                }
+            }
+        }
+        InboundRequest::AddProvider { record: Some(provider) } => {
+            let len = provider.key.as_ref().len() as u64;
+            traffic.write().unwrap().record_in(provider.provider, TrafficProtocol::Dht, len);
+            if let Err(e) = kad.store_mut().add_provider(provider) {
+                debug!("DHT store rejected provider record: {:?}", e);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Count an invalid record against `peer`; once it reaches the strike limit,
+/// drop it from both routing tables and disconnect.
+fn penalize_record_peer(
+    swarm: &mut libp2p::Swarm<CraftNetBehaviour>,
+    penalties: &mut RecordPenalties,
+    peer: PeerId,
+) {
+    if !penalties.strike(peer) {
+        return;
+    }
+    warn!(
+        "Dropping peer {} after {} invalid DHT records",
+        peer, craftnet_network::RECORD_STRIKE_LIMIT,
+    );
+    let behaviour = swarm.behaviour_mut();
+    behaviour.kademlia.remove_peer(&peer);
+    if let Some(kad) = behaviour.kademlia_secondary.as_mut() {
+        kad.remove_peer(&peer);
+    }
+    let _ = swarm.disconnect_peer_id(peer);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -5988,6 +11475,32 @@ mod tests {
         assert!(!node.is_connected());
     }
 
+    #[test]
+    fn test_request_timeout_per_circuit() {
+        let config = NodeConfig {
+            request_timeout: Duration::from_secs(10),
+            min_request_timeout: Duration::from_millis(100),
+            ..Default::default()
+        };
+        let mut node = CraftNetNode::new(config).unwrap();
+        let (fast, slow) = (PeerId::random(), PeerId::random());
+
+        // Nothing measured yet: the configured maximum
+        assert_eq!(node.request_timeout_for(Some(fast), 0), Duration::from_secs(10));
+
+        for _ in 0..10 {
+            node.circuit_rtt.entry(fast).or_default().observe(Duration::from_millis(150), 0);
+            node.circuit_rtt.entry(slow).or_default().observe(Duration::from_secs(2), 0);
+            node.overall_rtt.observe(Duration::from_secs(1), 0);
+        }
+        assert!(node.request_timeout_for(Some(fast), 0) < Duration::from_secs(1));
+        assert!(node.request_timeout_for(Some(slow), 0) > Duration::from_secs(2));
+
+        // An unmeasured circuit falls back to the overall estimate
+        let unknown = node.request_timeout_for(Some(PeerId::random()), 0);
+        assert!(unknown > Duration::from_secs(1) && unknown < Duration::from_secs(10));
+    }
+
     #[test]
     fn test_capabilities_switching() {
         let config = NodeConfig::default();
@@ -6022,4 +11535,862 @@ mod tests {
         node.set_credits(100);
         assert_eq!(node.credits(), 100);
     }
+
+    #[test]
+    fn test_begin_drain() {
+        let config = NodeConfig::default();
+        let mut node = CraftNetNode::new(config).unwrap();
+        node.set_capabilities(Capabilities::RELAY | Capabilities::EXIT);
+
+        assert!(!node.is_draining());
+        assert!(!node.is_drained());
+
+        node.begin_drain(Some(Duration::from_secs(60)));
+        assert!(node.is_draining());
+        let status = node.drain_status();
+        assert!(status.draining);
+        assert!(status.is_idle());
+        assert!(status.remaining.unwrap() <= Duration::from_secs(60));
+        // Nothing in flight, so the drain completes without waiting
+        assert!(node.is_drained());
+    }
+
+    #[test]
+    fn test_routes_around_outdated_peers() {
+        let config = NodeConfig { min_peer_protocol_version: 2, ..Default::default() };
+        let mut node = CraftNetNode::new(config).unwrap();
+        let relay_info = |seed: u8| RelayInfo {
+            pubkey: [seed; 32],
+            address: String::new(),
+            allows_last_hop: true,
+            reputation: 0,
+            encryption_pubkey: None,
+            peer_binding: None,
+            capacity: None,
+            country_code: None,
+            as_number: None,
+        };
+        let (old, new) = (PeerId::random(), PeerId::random());
+        node.on_relay_discovered(relay_info(1), Some(old));
+        node.on_relay_discovered(relay_info(2), Some(new));
+        assert_eq!(node.relay_nodes.len(), 2);
+
+        node.handle_peer_versions(vec![
+            (old, PeerVersion::legacy()),
+            (new, PeerVersion { protocol_version: 2, user_agent: None, frame_key: None }),
+        ]);
+        assert_eq!(node.relay_nodes.len(), 1);
+        assert!(node.relay_nodes.contains_key(&[2; 32]));
+        assert_eq!(node.peer_version_distribution(), BTreeMap::from([(0, 1), (2, 1)]));
+
+        // Rediscovery does not bring the outdated relay back
+        node.on_relay_discovered(relay_info(1), Some(old));
+        assert_eq!(node.relay_nodes.len(), 1);
+    }
+
+    #[test]
+    fn test_geoip_fills_in_peer_locations() {
+        let path = std::env::temp_dir().join(format!("craftnet-geoip-{}.csv", std::process::id()));
+        std::fs::write(&path, "start_ip,end_ip,country,asn,as_name\n8.8.8.0,8.8.8.255,US,AS15169,Google LLC\n").unwrap();
+        let config = NodeConfig {
+            geoip_database: Some(path.clone()),
+            public_ip: Some("8.8.8.8".parse().unwrap()),
+            ..Default::default()
+        };
+        let mut node = CraftNetNode::new(config).unwrap();
+        assert!(node.self_geoip().is_none());
+        node.maybe_refresh_geoip();
+        assert_eq!(node.self_geoip().unwrap().as_number.as_deref(), Some("AS15169"));
+
+        let relay = |seed: u8, address: &str| RelayInfo {
+            pubkey: [seed; 32],
+            address: address.to_string(),
+            allows_last_hop: true,
+            reputation: 0,
+            encryption_pubkey: None,
+            peer_binding: None,
+            capacity: None,
+            country_code: None,
+            as_number: None,
+        };
+        let (looked_up, private) = (PeerId::random(), PeerId::random());
+        node.on_relay_discovered(relay(1, "/ip4/8.8.8.4/tcp/9000"), Some(looked_up));
+        node.on_relay_discovered(relay(2, "/ip4/10.0.0.1/tcp/9000"), Some(private));
+        let geo = node.circuit_geo_filter();
+        assert_eq!(
+            geo.locations.get(&looked_up.to_bytes()),
+            Some(&HopLocation::new(Some("US"), Some("AS15169"))),
+        );
+        assert!(!geo.locations.contains_key(&private.to_bytes()));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_circuit_geo_exit_selection() {
+        let mut node = CraftNetNode::new(NodeConfig::default()).unwrap();
+        let exit = |seed: u8, country: &str| ExitInfo {
+            pubkey: [seed; 32],
+            address: String::new(),
+            region: ExitRegion::Auto,
+            country_code: Some(country.to_string()),
+            city: None,
+            reputation: 0,
+            latency_ms: 0,
+            encryption_pubkey: None,
+            peer_id: None,
+            peer_binding: None,
+            as_number: None,
+            quota: None,
+            capabilities: None,
+            max_request_bytes: None,
+            max_response_bytes: None,
+            zstd_requests: false,
+        };
+        node.add_exit_node(exit(1, "RU"));
+        node.add_exit_node(exit(2, "DE"));
+
+        node.set_circuit_geo(GeoConstraints { excluded_countries: vec!["RU".to_string()], ..Default::default() });
+        assert_eq!(node.selected_exit.as_ref().map(|e| e.pubkey), Some([2; 32]));
+
+        let required_us = GeoConstraints { exit_country: Some("US".to_string()), ..Default::default() };
+        node.set_circuit_geo(required_us.clone());
+        assert!(node.selected_exit.is_none());
+
+        node.set_circuit_geo(GeoConstraints { fallback: GeoFallback::Ignore, ..required_us });
+        assert!(node.selected_exit.is_some());
+    }
+
+    #[test]
+    fn test_exit_failover_on_errors() {
+        let mut node = CraftNetNode::new(NodeConfig::default()).unwrap();
+        let exit = |seed: u8| ExitInfo {
+            pubkey: [seed; 32],
+            address: String::new(),
+            region: ExitRegion::Auto,
+            country_code: None,
+            city: None,
+            reputation: 0,
+            latency_ms: 0,
+            encryption_pubkey: None,
+            peer_id: None,
+            peer_binding: None,
+            as_number: None,
+            quota: None,
+            capabilities: None,
+            max_request_bytes: None,
+            max_response_bytes: None,
+            zstd_requests: false,
+        };
+        node.add_exit_node(exit(1));
+        node.add_exit_node(exit(2));
+        let first = node.selected_exit.as_ref().map(|e| e.pubkey).unwrap();
+
+        for _ in 0..node.config.exit_health.min_samples {
+            node.record_exit_outcome(&first, Duration::from_millis(100), false);
+        }
+        let second = node.selected_exit.as_ref().map(|e| e.pubkey).unwrap();
+        assert_ne!(first, second);
+
+        let events = node.take_exit_failover_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].from_exit, hex::encode(first));
+        assert_eq!(events[0].to_exit, Some(hex::encode(second)));
+        assert!(matches!(events[0].reason, crate::exit_health::FailoverReason::ErrorRate { .. }));
+
+        // The demoted exit is passed over while the other one is healthy
+        node.select_best_exit();
+        assert_eq!(node.selected_exit.as_ref().map(|e| e.pubkey), Some(second));
+    }
+
+    #[test]
+    fn test_aggregator_pools_proof_shards() {
+        let own = [0x21; 32];
+        assert_eq!(AggregatorPools::All.proof_shards(own).len(), PROOF_TOPIC_SHARDS as usize);
+        assert_eq!(AggregatorPools::Own.proof_shards(own), vec![0x21 % PROOF_TOPIC_SHARDS]);
+        let pools = AggregatorPools::Only(HashSet::from([[0x03; 32], [0x13; 32], [0x04; 32]]));
+        assert_eq!(pools.proof_shards(own), vec![3, 4]);
+    }
+
+    #[test]
+    fn test_relay_proofs_reach_aggregators_through_relays() {
+        let config = NodeConfig { capabilities: Capabilities::RELAY, ..Default::default() };
+        let mut node = CraftNetNode::new(config).unwrap();
+        let (tx, mut rx) = mpsc::channel(256);
+        node.swarm_cmd_tx = Some(tx);
+
+        // A relay follows every shard, so it forwards proofs of relays that
+        // have no aggregator in their mesh
+        node.set_capabilities(Capabilities::RELAY);
+        let mut subscribed = HashSet::new();
+        while let Ok(cmd) = rx.try_recv() {
+            if let craftec_network::SharedSwarmCommand::SubscribeGossipsub(topic) = cmd {
+                subscribed.insert(topic);
+            }
+        }
+        assert!(subscribed.contains(PROOF_TOPIC));
+        assert!((0..PROOF_TOPIC_SHARDS).all(|shard| subscribed.contains(&proof_shard_topic(shard))));
+
+        // Its own proofs go out on the shard and the unsharded topic
+        let msg = ProofMessage {
+            relay_pubkey: node.keypair.public_key_bytes(),
+            pool_pubkey: [7; 32],
+            pool_type: PoolType::Free,
+            batch_bytes: 100,
+            cumulative_bytes: 100,
+            prev_root: [0; 32],
+            new_root: [0xAA; 32],
+            proof: vec![],
+            timestamp: system_clock().unix_now(),
+            signature: vec![],
+        };
+        let keypair = node.keypair.clone();
+        node.proof_outbox.push(msg, |m| craftec_crypto::sign_data(&keypair, &m.signable_data()).to_vec());
+        node.connected_peers.insert(PeerId::random());
+        node.drain_deadline = Some(Instant::now());
+        node.flush_proof_outbox();
+        let mut published = Vec::new();
+        while let Ok(cmd) = rx.try_recv() {
+            if let craftec_network::SharedSwarmCommand::PublishGossipsub { topic, .. } = cmd {
+                published.push(topic);
+            }
+        }
+        assert_eq!(published, vec![PROOF_TOPIC.to_string(), proof_topic_for_pool(&[7; 32])]);
+
+        // A client-only node leaves the proof topics again
+        node.set_capabilities(Capabilities::CLIENT);
+        let mut left = 0;
+        while let Ok(cmd) = rx.try_recv() {
+            if matches!(cmd, craftec_network::SharedSwarmCommand::UnsubscribeGossipsub(_)) {
+                left += 1;
+            }
+        }
+        assert_eq!(left, PROOF_TOPIC_SHARDS as usize + 1);
+    }
+
+    #[test]
+    fn test_stream_window_waits_for_slow_caller() {
+        let mut node = CraftNetNode::new(NodeConfig::default()).unwrap();
+        let request_id = [9; 32];
+        let (head_tx, _head_rx) = mpsc::channel(1);
+        let (chunk_tx, mut chunk_rx) = mpsc::channel(STREAM_CHANNEL_CAPACITY);
+        let mut decoded = BTreeMap::new();
+        decoded.insert(0, ResponseSegment::Head { status: 200, headers: vec![] });
+        for seq in 1..STREAM_LOOKAHEAD {
+            decoded.insert(seq, ResponseSegment::Body(vec![0; 100]));
+        }
+        node.pending_streams.insert(
+            request_id,
+            PendingStream {
+                segments: HashMap::new(),
+                decoded,
+                next_seq: 0,
+                head_tx: Some(head_tx),
+                ready: VecDeque::new(),
+                chunk_tx,
+                exit_enc_pubkey: [0; 32],
+                last_progress: Instant::now(),
+                body_bytes: 0,
+                finished: false,
+            },
+        );
+
+        // Nobody reads: the channel fills, then `ready`, then the window stops
+        node.advance_stream(&request_id);
+        let stream = &node.pending_streams[&request_id];
+        assert_eq!(stream.ready.len(), STREAM_READY_LIMIT);
+        let next_seq = stream.next_seq;
+        assert_eq!(next_seq as usize, 1 + STREAM_CHANNEL_CAPACITY + STREAM_READY_LIMIT);
+        let registered = node.stream_segments.len();
+        node.advance_stream(&request_id);
+        node.poll_response_streams();
+        assert_eq!(node.pending_streams[&request_id].ready.len(), STREAM_READY_LIMIT);
+        assert_eq!(node.pending_streams[&request_id].next_seq, next_seq);
+        assert_eq!(node.stream_segments.len(), registered);
+        assert!(node.stream_segments.values().all(|(_, seq)| *seq < next_seq + STREAM_LOOKAHEAD));
+
+        // Once the caller drains, held-back segments move on
+        while chunk_rx.try_recv().is_ok() {}
+        node.poll_response_streams();
+        let stream = &node.pending_streams[&request_id];
+        assert!(stream.next_seq > next_seq);
+        assert!(stream.ready.len() <= STREAM_READY_LIMIT);
+    }
+
+    #[test]
+    fn test_relay_freeloader_detection() {
+        let mut node = CraftNetNode::new(NodeConfig::default()).unwrap();
+        let (taker, fair) = (PeerId::random(), PeerId::random());
+        {
+            let mut traffic = node.traffic.write().unwrap();
+            traffic.record_in(taker, TrafficProtocol::ShardStream, 100 * 1024 * 1024);
+            traffic.record_in(fair, TrafficProtocol::ShardStream, 100 * 1024 * 1024);
+            traffic.record_out(fair, TrafficProtocol::ShardStream, 80 * 1024 * 1024);
+        }
+        assert!(node.is_freeloader(&taker));
+        assert!(!node.is_freeloader(&fair));
+        assert_eq!(node.freeloaders(), vec![taker]);
+        assert_eq!(node.peer_traffic()[0].peer_id, fair);
+
+        node.config.relay_freeloader = None;
+        assert!(!node.is_freeloader(&taker));
+        assert!(node.freeloaders().is_empty());
+    }
+
+    #[test]
+    fn test_failover_exit_skips_tried_exits() {
+        let mut node = CraftNetNode::new(NodeConfig::default()).unwrap();
+        let exit = |seed: u8, encryption_pubkey: Option<[u8; 32]>| ExitInfo {
+            pubkey: [seed; 32],
+            address: String::new(),
+            region: ExitRegion::Auto,
+            country_code: None,
+            city: None,
+            reputation: 0,
+            latency_ms: 0,
+            encryption_pubkey,
+            peer_id: None,
+            peer_binding: None,
+            as_number: None,
+            quota: None,
+            capabilities: None,
+            max_request_bytes: None,
+            max_response_bytes: None,
+            zstd_requests: false,
+        };
+        node.add_exit_node(exit(1, Some([1; 32])));
+        node.add_exit_node(exit(2, Some([2; 32])));
+        node.add_exit_node(exit(3, Some([3; 32])));
+        node.add_exit_node(exit(4, None));
+        node.exit_nodes.get_mut(&[3; 32]).unwrap().score = 0;
+
+        let tried = HashSet::from([[1; 32]]);
+        assert_eq!(node.failover_exit(&tried), Some([3; 32]));
+        let tried = HashSet::from([[1; 32], [3; 32]]);
+        assert_eq!(node.failover_exit(&tried), Some([2; 32]));
+
+        // Exits without an encryption key can't take the request
+        let tried = HashSet::from([[1; 32], [2; 32], [3; 32]]);
+        assert_eq!(node.failover_exit(&tried), None);
+    }
+
+    #[test]
+    fn test_request_size_checked_against_exit_limits() {
+        let mut node = CraftNetNode::new(NodeConfig::default()).unwrap();
+        let mut exit = ExitInfo {
+            pubkey: [1; 32],
+            address: String::new(),
+            region: ExitRegion::Auto,
+            country_code: None,
+            city: None,
+            reputation: 0,
+            latency_ms: 0,
+            encryption_pubkey: None,
+            peer_id: None,
+            peer_binding: None,
+            as_number: None,
+            quota: None,
+            capabilities: None,
+            max_request_bytes: None,
+            max_response_bytes: None,
+            zstd_requests: false,
+        };
+        // Exits that don't advertise a limit get everything
+        assert!(node.check_request_size(&exit, usize::MAX).is_ok());
+
+        exit.max_request_bytes = Some(1000);
+        assert!(node.check_request_size(&exit, 1000).is_ok());
+        assert!(matches!(node.check_request_size(&exit, 1001), Err(ClientError::RequestTooLarge { limit: 1000 })));
+
+        // A lower limit from an error frame wins over the advertised one
+        node.exit_request_limits.insert([1; 32], 500);
+        assert_eq!(node.request_limit(&exit), Some(500));
+        assert!(matches!(node.check_request_size(&exit, 501), Err(ClientError::RequestTooLarge { limit: 500 })));
+    }
+
+    #[test]
+    fn test_sim_clock_drives_exit_timeouts() {
+        let mut node = CraftNetNode::new(NodeConfig::default()).unwrap();
+        let clock = Arc::new(craftnet_core::SimClock::new(1_700_000_000));
+        node.set_clock(clock.clone());
+        node.add_exit_node(ExitInfo {
+            pubkey: [1; 32],
+            address: String::new(),
+            region: ExitRegion::Auto,
+            country_code: None,
+            city: None,
+            reputation: 0,
+            latency_ms: 0,
+            encryption_pubkey: None,
+            peer_id: None,
+            peer_binding: None,
+            as_number: None,
+            quota: None,
+            capabilities: None,
+            max_request_bytes: None,
+            max_response_bytes: None,
+            zstd_requests: false,
+        });
+
+        // No wall-clock time matters: only virtual time moves the threshold
+        clock.advance(EXIT_OFFLINE_THRESHOLD);
+        node.check_exit_timeouts();
+        assert!(node.exit_nodes[&[1u8; 32]].online);
+        clock.advance(Duration::from_secs(1));
+        node.check_exit_timeouts();
+        assert!(!node.exit_nodes[&[1u8; 32]].online);
+
+        // Past the record TTL the exit is forgotten
+        clock.advance(craftnet_network::EXIT_RECORD_TTL);
+        node.cleanup_stale_exits();
+        assert!(node.exit_nodes.is_empty());
+    }
+
+    #[test]
+    fn test_relay_capacity_fit() {
+        let info = RelayInfo {
+            pubkey: [1; 32],
+            address: String::new(),
+            allows_last_hop: true,
+            reputation: 0,
+            encryption_pubkey: None,
+            peer_binding: None,
+            capacity: Some(RelayCapacity {
+                bandwidth_class: BandwidthClass::High,
+                max_concurrent_streams: 64,
+                max_shard_size: MAX_SHARD_SIZE as u32,
+            }),
+            country_code: None,
+            as_number: None,
+        };
+        let mut status = RelayNodeStatus::new(info.clone(), PeerId::random(), Instant::now());
+        assert_eq!(status.capacity_fit(&RequestProfile::bulk()), 0);
+        assert_eq!(status.capacity_fit(&RequestProfile::interactive()), 0);
+
+        let unknown = RelayNodeStatus::new(RelayInfo { capacity: None, ..info }, PeerId::random(), Instant::now());
+        assert_eq!(unknown.capacity_fit(&RequestProfile::bulk()), 1);
+
+        // Measured far below the claimed class: downgraded once verified
+        for _ in 0..CAPACITY_VERIFY_SAMPLES - 1 {
+            status.record_measurement(200);
+        }
+        assert_eq!(status.effective_bandwidth_class(), Some(BandwidthClass::High));
+        status.record_measurement(200);
+        assert_eq!(status.effective_bandwidth_class(), Some(BandwidthClass::Low));
+        assert_eq!(status.capacity_fit(&RequestProfile::bulk()), 2);
+        assert_eq!(status.capacity_fit(&RequestProfile::interactive()), 0);
+
+        let large = vec![0u8; BULK_REQUEST_BYTES + 1];
+        assert_eq!(RequestProfile::for_request(Some(&large), None), RequestProfile::bulk());
+        let range = [("Range".to_string(), "bytes=0-99".to_string())];
+        assert_eq!(RequestProfile::for_request(None, Some(&range)), RequestProfile::bulk());
+        assert_eq!(RequestProfile::for_request(Some(b"hi"), None), RequestProfile::interactive());
+    }
+
+    #[test]
+    fn test_chain_outcome_scores_relays() {
+        let mut node = CraftNetNode::new(NodeConfig::default()).unwrap();
+        let relay = |pubkey: [u8; 32]| RelayInfo {
+            pubkey,
+            address: String::new(),
+            allows_last_hop: true,
+            reputation: 0,
+            encryption_pubkey: None,
+            peer_binding: None,
+            capacity: None,
+            country_code: None,
+            as_number: None,
+        };
+        let (gateway, middle) = ([1u8; 32], [2u8; 32]);
+        for pubkey in [gateway, middle] {
+            node.relay_nodes.insert(pubkey, RelayNodeStatus::new(relay(pubkey), PeerId::random(), Instant::now()));
+        }
+
+        // The gateway acked but the middle relay never did: lost at the gateway
+        let lost = ChainOutcome { verified: vec![gateway], missing: vec![middle], blamed: Some(gateway) };
+        for _ in 0..FORWARD_VERIFY_SAMPLES {
+            node.apply_chain_outcome(lost.clone());
+        }
+        node.apply_chain_outcome(ChainOutcome { verified: vec![gateway, middle], missing: vec![], blamed: None });
+
+        let stats = node.stats();
+        assert_eq!(stats.chain_hops_verified, FORWARD_VERIFY_SAMPLES as u64 + 2);
+        assert_eq!(stats.chain_hops_missing, FORWARD_VERIFY_SAMPLES as u64);
+        let gateway_status = &node.relay_nodes[&gateway];
+        assert_eq!((gateway_status.forwards_verified, gateway_status.forward_failures), (1, FORWARD_VERIFY_SAMPLES));
+        // Relays past the loss learn nothing from it
+        assert_eq!((node.relay_nodes[&middle].forwards_verified, node.relay_nodes[&middle].forward_failures), (1, 0));
+        assert!(gateway_status.score > node.relay_nodes[&middle].score);
+    }
+
+    #[test]
+    fn test_relay_heartbeat_onion_key_offer() {
+        let mut node = CraftNetNode::new(NodeConfig::default()).unwrap();
+        let relay = SigningKeypair::generate();
+        let relay_peer = PeerId::random();
+        let heartbeat = |offer: Option<OnionKeyOffer>| {
+            let mut msg = RelayStatusMessage::heartbeat(
+                relay.public_key_bytes(), &relay_peer.to_string(), 0, 0, 0, 0, 0,
+                vec![PeerId::random().to_string()],
+            );
+            msg.encryption_pubkey = Some(hex::encode([1u8; 32]));
+            msg.onion_key = offer;
+            msg.sign(&relay, rand::random());
+            msg.to_bytes()
+        };
+        let topology_key = |node: &CraftNetNode| node.topology.get_relay(&relay_peer.to_bytes()).unwrap().encryption_pubkey;
+        let expires_at = system_clock().unix_now() + 600;
+
+        // A signed offer replaces the static key for path building
+        node.handle_relay_status(&heartbeat(Some(OnionKeyOffer::sign(&relay, [2u8; 32], 1, expires_at))), None);
+        assert_eq!(topology_key(&node), [2u8; 32]);
+
+        // Offers signed by someone else, or expired, are ignored
+        let forged = OnionKeyOffer::sign(&SigningKeypair::generate(), [3u8; 32], 2, expires_at);
+        node.handle_relay_status(&heartbeat(Some(forged)), None);
+        assert_eq!(topology_key(&node), [1u8; 32]);
+        let expired = OnionKeyOffer::sign(&relay, [4u8; 32], 3, system_clock().unix_now() - 1);
+        node.handle_relay_status(&heartbeat(Some(expired)), None);
+        assert_eq!(topology_key(&node), [1u8; 32]);
+    }
+
+    #[test]
+    fn test_exit_heartbeat_signature_and_skew() {
+        let mut node = CraftNetNode::new(NodeConfig::default()).unwrap();
+        let clock = Arc::new(craftnet_core::SimClock::new(1_700_000_000));
+        node.set_clock(clock.clone());
+        let exit = SigningKeypair::generate();
+        node.add_exit_node(ExitInfo {
+            pubkey: exit.public_key_bytes(),
+            address: String::new(),
+            region: ExitRegion::Auto,
+            country_code: None,
+            city: None,
+            reputation: 0,
+            latency_ms: 0,
+            encryption_pubkey: None,
+            peer_id: None,
+            peer_binding: None,
+            as_number: None,
+            quota: None,
+            capabilities: None,
+            max_request_bytes: None,
+            max_response_bytes: None,
+            zstd_requests: false,
+        });
+        let heartbeat = |load: u8, timestamp: u64, nonce: u64| {
+            let mut msg = ExitStatusMessage::heartbeat(exit.public_key_bytes(), "exit", load, 0, 0, 0, 0, None, vec![]);
+            msg.timestamp = timestamp;
+            msg.sign(&exit, nonce);
+            msg
+        };
+        let load = |node: &CraftNetNode| node.exit_nodes[&exit.public_key_bytes()].announced_load_percent;
+
+        // Unsigned heartbeats are dropped
+        let mut unsigned = heartbeat(10, clock.unix_now(), 1);
+        unsigned.signature = None;
+        node.handle_exit_status(&unsigned.to_bytes(), None);
+        assert_eq!(load(&node), 50);
+
+        // An exit whose clock runs ten minutes fast stays online
+        let skewed = heartbeat(20, clock.unix_now() + 600, 2);
+        node.handle_exit_status(&skewed.to_bytes(), None);
+        assert_eq!(load(&node), 20);
+        for nonce in 3..6 {
+            clock.advance(EXIT_HEARTBEAT_INTERVAL);
+            node.handle_exit_status(&heartbeat(30, clock.unix_now() + 600, nonce).to_bytes(), None);
+            node.check_exit_timeouts();
+            assert!(node.exit_nodes[&exit.public_key_bytes()].online);
+        }
+        assert_eq!(node.status_clock_skew(&exit.public_key_bytes()), Some(600));
+        assert_eq!(load(&node), 30);
+
+        // A replayed heartbeat doesn't count
+        node.handle_exit_status(&skewed.to_bytes(), None);
+        assert_eq!(load(&node), 30);
+    }
+
+    #[test]
+    fn test_subscription_announcement_waits_for_chain() {
+        let mut node = CraftNetNode::new(NodeConfig::default()).unwrap();
+        let user = SigningKeypair::generate();
+        let pool = user.public_key_bytes();
+        let announce = |node: &mut CraftNetNode, tier: u8| {
+            let mut msg = SubscriptionAnnouncement {
+                user_pubkey: pool,
+                tier,
+                expires_at: system_clock().unix_now() + 3600,
+                timestamp: system_clock().unix_now(),
+                signature: vec![],
+            };
+            msg.signature = craftec_crypto::sign_data(&user, &msg.signable_data()).to_vec();
+            node.handle_subscription_announcement(&msg.to_bytes());
+        };
+
+        // The claimed tier doesn't count before the chain confirms it
+        announce(&mut node, 2);
+        announce(&mut node, 2);
+        assert!(node.pool_tier(&pool).is_none());
+        assert_eq!(node.subscription_cache_summary(), vec![(255, 1)]);
+        assert_eq!(node.subscription_checks.len(), 1);
+
+        // The chain found no subscription: the same claim is negatively
+        // cached instead of checked again
+        node.subscription_checks.clear();
+        let entry = node.subscription_cache.get_mut(&pool).unwrap();
+        entry.verified = true;
+        entry.verified_at = Some(std::time::Instant::now());
+        announce(&mut node, 2);
+        assert!(node.subscription_checks.is_empty());
+        assert!(node.pool_tier(&pool).is_none());
+    }
+
+    #[test]
+    fn test_identity_response_keys() {
+        let mut node = CraftNetNode::new(NodeConfig::default()).unwrap();
+        let node_key = node.encryption_keypair.public_key_bytes();
+        assert!(node.with_identity("work").is_err());
+
+        node.new_identity("work").unwrap();
+        assert!(node.new_identity("work").is_err());
+        assert_eq!(node.identities(), vec!["work".to_string()]);
+        assert_eq!(node.with_identity("work").unwrap().name(), "work");
+
+        node.active_identity = Some("work".to_string());
+        let identity_key = node.response_keypair().public_key_bytes();
+        assert_ne!(identity_key, node_key);
+        // No exits known yet
+        assert!(matches!(node.identity_exit("work"), Err(ClientError::NoExitNodes)));
+
+        node.active_identity = None;
+        assert_eq!(node.response_keypair().public_key_bytes(), node_key);
+        assert!(node.remove_identity("work"));
+    }
+
+    #[test]
+    fn test_cookie_jar_per_identity() {
+        let config = NodeConfig { cookie_jar: Some(CookieJarConfig::in_memory()), ..Default::default() };
+        let mut node = CraftNetNode::new(config).unwrap();
+        node.new_identity("work").unwrap();
+        let response = TunnelResponse {
+            status: 200,
+            headers: HashMap::from([("set-cookie".to_string(), "sid=1; Path=/, lang=en".to_string())]),
+            body: Vec::new(),
+            hop_timings: Vec::new(),
+            exit_failovers: 0,
+        };
+
+        node.active_identity = Some("work".to_string());
+        node.store_cookies("https://example.com/", &response);
+        let headers = node.with_cookies("https://example.com/a", None).unwrap();
+        assert_eq!(headers, vec![("Cookie".to_string(), "sid=1; lang=en".to_string())]);
+        // A caller-supplied Cookie header wins
+        let own = vec![("cookie".to_string(), "mine=1".to_string())];
+        assert_eq!(node.with_cookies("https://example.com/a", Some(own.clone())), Some(own));
+
+        // Requests without the identity don't carry its cookies
+        node.active_identity = None;
+        assert!(node.with_cookies("https://example.com/a", None).is_none());
+
+        assert!(node.remove_identity("work"));
+        assert!(node.cookie_jar().unwrap().cookies(Some("work")).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_request_options_apply_to_one_request() {
+        let mut node = CraftNetNode::new(NodeConfig { hop_mode: HopMode::Double, ..Default::default() }).unwrap();
+        node.request_options = RequestOptions { hop_mode: Some(HopMode::Direct), ..Default::default() };
+        assert_eq!(node.request_hop_mode(), HopMode::Direct);
+
+        let options = RequestOptions {
+            hop_mode: Some(HopMode::Quad),
+            exit: Some([7u8; 32]),
+            timeout: Some(Duration::from_secs(1)),
+            trace: true,
+        };
+        let result = node.get_with("https://example.com", options).await;
+        assert!(matches!(result, Err(ClientError::NotConnected)));
+        // Back to the node's defaults afterwards
+        assert_eq!(node.request_options, RequestOptions::default());
+        assert_eq!(node.request_hop_mode(), HopMode::Double);
+    }
+
+    #[test]
+    fn test_network_params_keep_highest_sequence() {
+        let maintainer = SigningKeypair::generate();
+        let config = NodeConfig { maintainer_keys: vec![maintainer.public_key_bytes()], ..Default::default() };
+        let mut node = CraftNetNode::new(config).unwrap();
+        let params = |sequence: u64| NetworkParameters { sequence, min_protocol_version: Some(PROTOCOL_VERSION), ..Default::default() };
+
+        node.apply_dht_record(NETWORK_PARAMS_KEY, &params(2).sign(&maintainer).to_bytes());
+        assert_eq!(node.network_params().map(|p| p.sequence), Some(2));
+
+        // Replayed older record and a stranger's record are ignored
+        node.apply_dht_record(NETWORK_PARAMS_KEY, &params(1).sign(&maintainer).to_bytes());
+        node.apply_dht_record(NETWORK_PARAMS_KEY, &params(9).sign(&SigningKeypair::generate()).to_bytes());
+        assert_eq!(node.network_params().map(|p| p.sequence), Some(2));
+
+        assert!(node.publish_network_params(params(3).sign(&maintainer)).is_ok());
+        assert_eq!(node.network_params().map(|p| p.sequence), Some(3));
+    }
+
+    #[tokio::test]
+    async fn test_pool_claim_status_refresh() {
+        use crate::pool_claims::ClaimStatus;
+
+        let config = NodeConfig { capabilities: Capabilities::RELAY, ..Default::default() };
+        let mut node = CraftNetNode::new(config).unwrap();
+        let settlement = Arc::new(SettlementClient::new(SettlementConfig::mock(), [1; 32]));
+        node.settlement_client = Some(Arc::clone(&settlement));
+
+        let now = system_clock().unix_now();
+        let (running, expired) = ([7u8; 32], [8u8; 32]);
+        settlement.add_mock_subscription_with_expiry(running, SubscriptionTier::Standard, 1_000, now - 60, now + 3600).unwrap();
+        settlement.add_mock_subscription_with_expiry(expired, SubscriptionTier::Standard, 1_000, now - 7200, now - 3600).unwrap();
+        node.credit_ledger.record_relayed(running, Some(SubscriptionTier::Standard), 4096);
+        node.credit_ledger.record_relayed(expired, Some(SubscriptionTier::Standard), 4096);
+
+        node.maybe_refresh_pool_claims().await;
+        let claims = node.stats().pool_claims;
+        assert_eq!(claims.len(), 2);
+        let status = |pool: &PublicKey| claims.iter().find(|c| c.pool == *pool).unwrap().status;
+        assert_eq!(status(&running), ClaimStatus::ClaimableAt(now + 3600 + craftnet_settlement::GRACE_PERIOD_SECS));
+        assert_eq!(status(&expired), ClaimStatus::AwaitingDistribution);
+        assert_eq!(claims[0].balance, 1_000);
+    }
+
+    #[test]
+    fn test_aggregator_registry_drives_allowlist() {
+        let maintainer = SigningKeypair::generate();
+        let config = NodeConfig {
+            maintainer_keys: vec![maintainer.public_key_bytes()],
+            registered_aggregators_only: true,
+            ..Default::default()
+        };
+        let mut node = CraftNetNode::new(config).unwrap();
+        let settlement = Arc::new(SettlementClient::new(SettlementConfig::mock(), [1; 32]));
+        node.settlement_client = Some(Arc::clone(&settlement));
+
+        // No registry yet: nobody is trusted
+        node.apply_aggregator_allowlist();
+        assert!(!settlement.is_allowed_aggregator(&[1; 32]));
+
+        let epoch = aggregator_epoch(system_clock().unix_now());
+        let registry = AggregatorRegistry::default().rotate(epoch, &[([1; 32], "a".to_string())], &[]);
+        node.apply_dht_record(AGGREGATOR_REGISTRY_KEY, &registry.sign(&maintainer).to_bytes());
+        assert!(node.is_registered_aggregator(&[1; 32]));
+        assert!(settlement.is_allowed_aggregator(&[1; 32]));
+
+        // Rotated out from this epoch on
+        let rotated = registry.rotate(epoch, &[([2; 32], "b".to_string())], &[[1; 32]]);
+        assert!(node.publish_aggregator_registry(rotated.sign(&maintainer)).is_ok());
+        assert!(!settlement.is_allowed_aggregator(&[1; 32]));
+        assert!(settlement.is_allowed_aggregator(&[2; 32]));
+
+        // A stranger's registry is rejected
+        let forged = rotated.rotate(epoch, &[([1; 32], "a".to_string())], &[]);
+        assert!(node.publish_aggregator_registry(forged.sign(&SigningKeypair::generate())).is_err());
+        assert_eq!(node.aggregator_registry().map(|r| r.sequence), Some(2));
+    }
+
+    #[test]
+    fn test_direct_proof_submission() {
+        let maintainer = SigningKeypair::generate();
+        let config = NodeConfig {
+            capabilities: Capabilities::RELAY | Capabilities::AGGREGATOR,
+            maintainer_keys: vec![maintainer.public_key_bytes()],
+            ..Default::default()
+        };
+        let mut node = CraftNetNode::new(config).unwrap();
+
+        // Only registered aggregators with a known binding are submitted to
+        let epoch = aggregator_epoch(system_clock().unix_now());
+        let registry = AggregatorRegistry::default()
+            .rotate(epoch, &[([1; 32], "a".to_string()), ([2; 32], "b".to_string())], &[]);
+        node.apply_dht_record(AGGREGATOR_REGISTRY_KEY, &registry.sign(&maintainer).to_bytes());
+        let bound = PeerId::random();
+        node.verified_bindings.insert([2; 32], bound, system_clock().unix_now());
+        assert_eq!(node.known_aggregator_peers(), vec![bound]);
+
+        // Nobody to submit to yet: the proof waits in the outbox
+        let relay = SigningKeypair::generate();
+        let mut msg = ProofMessage {
+            relay_pubkey: relay.public_key_bytes(),
+            pool_pubkey: [7; 32],
+            pool_type: PoolType::Free,
+            batch_bytes: 100,
+            cumulative_bytes: 100,
+            prev_root: [0; 32],
+            new_root: [0xAA; 32],
+            proof: vec![],
+            timestamp: system_clock().unix_now(),
+            signature: vec![],
+        };
+        msg.signature = craftec_crypto::sign_data(&relay, &msg.signable_data()).to_vec();
+        node.submit_proofs_directly(vec![msg.to_bytes()]);
+        assert_eq!(node.proof_outbox.len(), 1);
+        assert!(node.proof_retry_at.is_some());
+
+        // Aggregator side: a proof arriving by gossip and directly counts once
+        node.verified_bindings.insert(msg.relay_pubkey, PeerId::random(), system_clock().unix_now());
+        assert_eq!(node.ingest_proof(&msg.to_bytes()), ProofSubmitStatus::Accepted);
+        assert_eq!(node.ingest_proof(&msg.to_bytes()), ProofSubmitStatus::Duplicate);
+        // Kept for aggregators backfilling this chain
+        assert_eq!(node.proof_archive.lock().unwrap().len(), 1);
+        assert!(matches!(node.ingest_proof(b"garbage"), ProofSubmitStatus::Rejected(_)));
+    }
+
+    #[test]
+    fn test_relay_challenges_undercounting_announcement() {
+        let config = NodeConfig { capabilities: Capabilities::RELAY, ..Default::default() };
+        let mut node = CraftNetNode::new(config).unwrap();
+        let aggregator = SigningKeypair::generate();
+        let our_key = node.keypair.public_key_bytes();
+        let pool_key = ([3; 32], PoolType::Subscribed);
+        node.pool_roots.insert(pool_key, ([5; 32], 150));
+
+        let now = system_clock().unix_now();
+        let mut announcement = DistributionAnnouncement {
+            aggregator_pubkey: aggregator.public_key_bytes(),
+            pool_pubkey: pool_key.0,
+            pool_type: pool_key.1,
+            distribution_root: [7; 32],
+            total_bytes: 100,
+            entries: vec![(our_key, 100)],
+            built_at: now,
+            challenge_deadline: now + 60,
+            signature: vec![],
+        };
+        announcement.signature = craftec_crypto::sign_data(&aggregator, &announcement.signable_data()).to_vec();
+        node.handle_dispute_message(&DisputeMessage::Announcement(announcement.clone()).to_bytes());
+        assert!(matches!(
+            node.take_dispute_events().as_slice(),
+            [DisputeEvent::Challenged { counted_bytes: 100, claimed_bytes: 150, .. }]
+        ));
+
+        // Challenged once per distribution
+        node.handle_dispute_message(&DisputeMessage::Announcement(announcement).to_bytes());
+        assert!(node.take_dispute_events().is_empty());
+
+        let link = |prev: u8, new: u8, batch, cumulative| craftnet_network::ProofChainLink {
+            prev_root: [prev; 32], new_root: [new; 32], batch_bytes: batch, cumulative_bytes: cumulative, timestamp: 0,
+        };
+        let mut response = ChallengeResponse {
+            aggregator_pubkey: aggregator.public_key_bytes(),
+            relay_pubkey: our_key,
+            pool_pubkey: pool_key.0,
+            pool_type: pool_key.1,
+            distribution_root: [7; 32],
+            counted_bytes: 150,
+            chain: vec![link(0, 4, 100, 100), link(4, 5, 50, 150)],
+            timestamp: now,
+            signature: vec![],
+        };
+        response.signature = craftec_crypto::sign_data(&aggregator, &response.signable_data()).to_vec();
+        node.handle_dispute_message(&DisputeMessage::Response(response).to_bytes());
+        assert!(matches!(
+            node.take_dispute_events().as_slice(),
+            [DisputeEvent::Answered { resolved: true, .. }]
+        ));
+        assert!(node.sent_challenges.is_empty());
+    }
 }
@@ -2,13 +2,19 @@
 //!
 //! Uses the topology graph to select valid multi-hop paths where each
 //! consecutive hop is connected in the topology.
+//!
+//! Paths can be further constrained by the self-reported location of each
+//! hop ([`GeoConstraints`]): hops in distinct countries and Autonomous
+//! Systems, no hops in excluded countries. Hops with no known location
+//! pass every check.
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use rand::seq::SliceRandom;
 use rand::Rng;
+use sha2::{Digest, Sha256};
 
-use craftnet_core::{Id, PublicKey};
+use craftnet_core::{normalize_asn, Id, Lease, LeaseSet, PublicKey};
 use crate::{ClientError, Result};
 
 /// A single hop in an onion path
@@ -41,6 +47,142 @@ pub struct TopologyRelay {
     pub last_seen: std::time::Instant,
 }
 
+/// What to do when no circuit satisfies the [`GeoConstraints`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GeoFallback {
+    /// Fail the request
+    Fail,
+    /// Drop the diversity rules, keep the country exclusions
+    #[default]
+    RelaxDiversity,
+    /// Build the circuit without any geo constraint
+    Ignore,
+}
+
+/// Location constraints on the hops of a circuit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeoConstraints {
+    /// No two hops (relays and exit) in the same country. Default: true.
+    pub distinct_countries: bool,
+    /// No two hops in the same Autonomous System. Default: true.
+    pub distinct_asns: bool,
+    /// Only use exits in this country (ISO 3166-1 alpha-2). Default: None.
+    pub exit_country: Option<String>,
+    /// Never route through relays or exits in these countries. Default: empty.
+    pub excluded_countries: Vec<String>,
+    /// Default: [`GeoFallback::RelaxDiversity`]
+    pub fallback: GeoFallback,
+}
+
+impl Default for GeoConstraints {
+    fn default() -> Self {
+        Self {
+            distinct_countries: true,
+            distinct_asns: true,
+            exit_country: None,
+            excluded_countries: Vec::new(),
+            fallback: GeoFallback::default(),
+        }
+    }
+}
+
+impl GeoConstraints {
+    /// No constraint at all
+    pub fn none() -> Self {
+        Self {
+            distinct_countries: false,
+            distinct_asns: false,
+            ..Default::default()
+        }
+    }
+
+    /// True if `country` is in `excluded_countries`
+    pub fn excludes(&self, country: Option<&str>) -> bool {
+        country.is_some_and(|c| self.excluded_countries.iter().any(|x| x.eq_ignore_ascii_case(c)))
+    }
+
+    /// True if an exit in `country` may be used (an exit of unknown
+    /// country never matches a required `exit_country`)
+    pub fn allows_exit(&self, country: Option<&str>) -> bool {
+        if self.excludes(country) {
+            return false;
+        }
+        match &self.exit_country {
+            Some(required) => country.is_some_and(|c| c.eq_ignore_ascii_case(required)),
+            None => true,
+        }
+    }
+
+    fn is_unconstrained(&self) -> bool {
+        !self.distinct_countries && !self.distinct_asns && self.excluded_countries.is_empty()
+    }
+}
+
+/// Self-reported location of a hop
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HopLocation {
+    pub country_code: Option<String>,
+    pub as_number: Option<String>,
+}
+
+impl HopLocation {
+    pub fn new(country_code: Option<&str>, as_number: Option<&str>) -> Self {
+        Self {
+            country_code: country_code.map(str::to_ascii_uppercase),
+            as_number: as_number.and_then(normalize_asn),
+        }
+    }
+}
+
+/// [`GeoConstraints`] with the locations of the hops they apply to
+#[derive(Debug, Clone)]
+pub struct GeoFilter {
+    pub constraints: GeoConstraints,
+    /// Keyed by peer id bytes
+    pub locations: HashMap<Vec<u8>, HopLocation>,
+}
+
+impl GeoFilter {
+    /// No constraint at all
+    pub fn none() -> Self {
+        Self { constraints: GeoConstraints::none(), locations: HashMap::new() }
+    }
+
+    /// True if `candidate` may join a circuit that already holds `hops`
+    pub fn allows(&self, hops: &[&[u8]], candidate: &[u8]) -> bool {
+        let Some(location) = self.locations.get(candidate) else {
+            return true;
+        };
+        if self.constraints.excludes(location.country_code.as_deref()) {
+            return false;
+        }
+        hops.iter().filter_map(|h| self.locations.get(*h)).all(|other| {
+            let same = |a: &Option<String>, b: &Option<String>| a.is_some() && a == b;
+            !(self.constraints.distinct_countries && same(&location.country_code, &other.country_code))
+                && !(self.constraints.distinct_asns && same(&location.as_number, &other.as_number))
+        })
+    }
+
+    /// The filter to retry with under the fallback policy, if any
+    fn fallback(&self) -> Option<Self> {
+        if self.constraints.is_unconstrained() {
+            return None;
+        }
+        match self.constraints.fallback {
+            GeoFallback::Fail => None,
+            GeoFallback::RelaxDiversity => Some(Self {
+                constraints: GeoConstraints {
+                    distinct_countries: false,
+                    distinct_asns: false,
+                    ..self.constraints.clone()
+                },
+                locations: self.locations.clone(),
+            }),
+            GeoFallback::Ignore => Some(Self::none()),
+        }
+    }
+}
+
 /// Topology graph built from gossipsub topology messages
 pub struct TopologyGraph {
     relays: Vec<TopologyRelay>,
@@ -89,6 +231,11 @@ impl TopologyGraph {
         self.relays.iter().find(|r| r.peer_id == peer_id)
     }
 
+    /// Get all relays in the topology
+    pub fn relays(&self) -> &[TopologyRelay] {
+        &self.relays
+    }
+
     /// Get all relays with encryption pubkeys
     pub fn relays_with_encryption(&self) -> Vec<&TopologyRelay> {
         self.relays.iter().filter(|r| r.encryption_pubkey != [0u8; 32]).collect()
@@ -128,6 +275,19 @@ impl PathSelector {
         exit: &PathHop,
         exclude: &HashSet<Vec<u8>>,
         entry_peer: Option<&[u8]>,
+    ) -> Result<OnionPath> {
+        Self::select_geo_path(topology, hop_count, exit, exclude, entry_peer, &GeoFilter::none())
+    }
+
+    /// [`Self::select_path`] where every hop must also pass `geo`, together
+    /// with the exit and `entry_peer`
+    pub fn select_geo_path(
+        topology: &TopologyGraph,
+        hop_count: usize,
+        exit: &PathHop,
+        exclude: &HashSet<Vec<u8>>,
+        entry_peer: Option<&[u8]>,
+        geo: &GeoFilter,
     ) -> Result<OnionPath> {
         if hop_count == 0 {
             return Ok(OnionPath {
@@ -143,6 +303,7 @@ impl PathSelector {
             // Exclude the exit itself — it cannot relay for its own circuit
             // (shard would arrive with non-empty header, get relayed to self, and dropped)
             .filter(|r| r.peer_id != exit.peer_id)
+            .filter(|r| geo.allows(&[], &r.peer_id))
             .collect();
 
         if eligible.len() < hop_count {
@@ -171,6 +332,14 @@ impl PathSelector {
                     if used.contains(&relay.peer_id) {
                         return false;
                     }
+                    let circuit: Vec<&[u8]> = entry_peer
+                        .into_iter()
+                        .chain(std::iter::once(exit.peer_id.as_slice()))
+                        .chain(path.iter().map(|h| h.peer_id.as_slice()))
+                        .collect();
+                    if !geo.allows(&circuit, &relay.peer_id) {
+                        return false;
+                    }
                     if i == 0 {
                         // First hop: must be connected to entry_peer (gateway)
                         if let Some(entry) = entry_peer {
@@ -226,13 +395,25 @@ impl PathSelector {
         exit: &PathHop,
         count: usize,
         entry_peer: Option<&[u8]>,
+    ) -> Result<Vec<OnionPath>> {
+        Self::select_diverse_geo_paths(topology, hop_count, exit, count, entry_peer, &GeoFilter::none())
+    }
+
+    /// [`Self::select_diverse_paths`] where every hop must also pass `geo`
+    pub fn select_diverse_geo_paths(
+        topology: &TopologyGraph,
+        hop_count: usize,
+        exit: &PathHop,
+        count: usize,
+        entry_peer: Option<&[u8]>,
+        geo: &GeoFilter,
     ) -> Result<Vec<OnionPath>> {
         let mut paths = Vec::new();
         let mut used_relays: HashSet<Vec<u8>> = HashSet::new();
 
         for _ in 0..count {
             // Try with excluding previously used relays first
-            match Self::select_path(topology, hop_count, exit, &used_relays, entry_peer) {
+            match Self::select_geo_path(topology, hop_count, exit, &used_relays, entry_peer, geo) {
                 Ok(path) => {
                     for hop in &path.hops {
                         used_relays.insert(hop.peer_id.clone());
@@ -241,7 +422,7 @@ impl PathSelector {
                 }
                 Err(_) => {
                     // Fallback: allow relay reuse
-                    let path = Self::select_path(topology, hop_count, exit, &HashSet::new(), entry_peer)?;
+                    let path = Self::select_geo_path(topology, hop_count, exit, &HashSet::new(), entry_peer, geo)?;
                     paths.push(path);
                 }
             }
@@ -293,6 +474,128 @@ pub fn random_id() -> Id {
     id
 }
 
+/// Derive a deterministic tunnel_id from two peer IDs (as bytes).
+/// Both sides of a connection can compute this independently.
+/// `tunnel_id = SHA256(client_peer_id || gateway_peer_id || "tunnel")`
+pub fn derive_tunnel_id(client_peer_id: &[u8], gateway_peer_id: &[u8]) -> Id {
+    let mut hasher = Sha256::new();
+    hasher.update(client_peer_id);
+    hasher.update(gateway_peer_id);
+    hasher.update(b"tunnel");
+    let result = hasher.finalize();
+    let mut id = [0u8; 32];
+    id.copy_from_slice(&result);
+    id
+}
+
+/// libp2p PeerId bytes for an Ed25519 public key.
+///
+/// An Ed25519 PeerId is the identity multihash of the protobuf-encoded
+/// public key; computing it here lets non-libp2p clients (the WASM client)
+/// present the same peer id a libp2p node with this key would have.
+pub fn ed25519_peer_id(pubkey: &PublicKey) -> Vec<u8> {
+    // identity multihash (0x00, len 36) of PublicKey { Type: Ed25519, Data: pubkey }
+    let mut bytes = vec![0x00, 0x24, 0x08, 0x01, 0x12, 0x20];
+    bytes.extend_from_slice(pubkey);
+    bytes
+}
+
+/// Build the onion paths and LeaseSet for a request entering the network
+/// through the first of `gateways` that `geo` allows alongside the exit.
+///
+/// Every allowed gateway goes into the LeaseSet (primary first) so the exit
+/// can pick any of them for the response. The primary gateway is the first
+/// onion hop of every path, followed by `extra_hops` relays chosen from the
+/// topology.
+///
+/// When no circuit satisfies `geo`, its [`GeoFallback`] decides whether to
+/// retry with relaxed constraints or fail.
+///
+/// Path: client → gateway → [extra_hops relays] → exit
+pub fn build_gateway_paths(
+    topology: &TopologyGraph,
+    client_peer_id: &[u8],
+    gateways: &[PathHop],
+    extra_hops: usize,
+    exit: &PathHop,
+    geo: &GeoFilter,
+) -> Result<(Vec<OnionPath>, LeaseSet)> {
+    match build_geo_gateway_paths(topology, client_peer_id, gateways, extra_hops, exit, geo) {
+        Ok(built) => Ok(built),
+        Err(e) => match geo.fallback() {
+            Some(relaxed) => build_geo_gateway_paths(topology, client_peer_id, gateways, extra_hops, exit, &relaxed),
+            None if geo.constraints.is_unconstrained() => Err(e),
+            None => Err(ClientError::RequestFailed(format!(
+                "No circuit satisfies the geo constraints: {}",
+                e
+            ))),
+        },
+    }
+}
+
+fn build_geo_gateway_paths(
+    topology: &TopologyGraph,
+    client_peer_id: &[u8],
+    gateways: &[PathHop],
+    extra_hops: usize,
+    exit: &PathHop,
+    geo: &GeoFilter,
+) -> Result<(Vec<OnionPath>, LeaseSet)> {
+    if gateways.is_empty() {
+        return Err(ClientError::RequestFailed(
+            "No gateway relay available (not connected to any relay)".to_string(),
+        ));
+    }
+    let mut allowed: Vec<&PathHop> = gateways.iter().filter(|hop| geo.allows(&[], &hop.peer_id)).collect();
+    let primary = allowed
+        .iter()
+        .position(|hop| geo.allows(&[exit.peer_id.as_slice()], &hop.peer_id))
+        .ok_or(ClientError::RequestFailed(
+            "No gateway relay satisfies the geo constraints".to_string(),
+        ))?;
+    let gateway = allowed.remove(primary);
+    allowed.insert(0, gateway);
+
+    let leases = allowed.iter().map(|hop| Lease {
+        gateway_peer_id: hop.peer_id.clone(),
+        gateway_encryption_pubkey: hop.encryption_pubkey,
+        tunnel_id: derive_tunnel_id(client_peer_id, &hop.peer_id),
+        expires_at: u64::MAX,
+    }).collect();
+    let lease_set = LeaseSet {
+        session_id: random_id(),
+        leases,
+    };
+
+    if extra_hops == 0 {
+        // Single hop: path = [gateway] → exit (1 onion hop)
+        let path = OnionPath {
+            hops: vec![gateway.clone()],
+            exit: exit.clone(),
+        };
+        return Ok((vec![path], lease_set));
+    }
+
+    // Multi-hop: entry_peer = gateway, so the first extra relay must be
+    // connected to the gateway
+    let extra_paths = PathSelector::select_diverse_geo_paths(
+        topology,
+        extra_hops,
+        exit,
+        craftnet_erasure::TOTAL_SHARDS,
+        Some(&gateway.peer_id),
+        geo,
+    )?;
+
+    let paths = extra_paths.into_iter().map(|p| {
+        let mut hops = vec![gateway.clone()];
+        hops.extend(p.hops);
+        OnionPath { hops, exit: p.exit }
+    }).collect();
+
+    Ok((paths, lease_set))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -544,4 +847,96 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_build_gateway_paths() {
+        let mut graph = TopologyGraph::new();
+        for i in 1u8..=4 {
+            let mut relay = make_relay(i);
+            for j in 1u8..=4 {
+                if i != j { relay.connected_peers.insert(vec![j]); }
+            }
+            relay.connected_peers.insert(vec![10]);
+            graph.update_relay(relay);
+        }
+        let exit = make_exit(10);
+        let client = vec![42u8];
+        let gateways = vec![make_exit(1), make_exit(2)];
+
+        let (paths, lease_set) = build_gateway_paths(&graph, &client, &gateways, 0, &exit, &GeoFilter::none()).unwrap();
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].hops.len(), 1);
+        assert_eq!(lease_set.leases.len(), 2);
+        assert_eq!(lease_set.leases[1].tunnel_id, derive_tunnel_id(&client, &[2u8]));
+
+        let (paths, _) = build_gateway_paths(&graph, &client, &gateways, 1, &exit, &GeoFilter::none()).unwrap();
+        assert_eq!(paths.len(), craftnet_erasure::TOTAL_SHARDS);
+        for p in &paths {
+            assert_eq!(p.hops[0].peer_id, vec![1u8]);
+            assert_eq!(p.hops.len(), 2);
+        }
+
+        assert!(build_gateway_paths(&graph, &client, &[], 0, &exit, &GeoFilter::none()).is_err());
+    }
+
+    #[test]
+    fn test_geo_constrained_paths() {
+        let mut graph = TopologyGraph::new();
+        for i in 1u8..=4 {
+            let mut relay = make_relay(i);
+            for j in 1u8..=4 {
+                if i != j { relay.connected_peers.insert(vec![j]); }
+            }
+            relay.connected_peers.insert(vec![10]);
+            graph.update_relay(relay);
+        }
+        let exit = make_exit(10);
+        let client = vec![42u8];
+        let gateways = vec![make_exit(1), make_exit(2)];
+
+        // Gateway 1 shares the exit's country, relay 4 is in an excluded
+        // country: the only valid circuit is 2 → 3 → exit
+        let mut geo = GeoFilter {
+            constraints: GeoConstraints {
+                excluded_countries: vec!["ru".to_string()],
+                ..Default::default()
+            },
+            locations: HashMap::from([
+                (vec![1], HopLocation::new(Some("de"), None)),
+                (vec![2], HopLocation::new(Some("NL"), None)),
+                (vec![3], HopLocation::new(Some("FR"), Some("AS1299 Arelion"))),
+                (vec![4], HopLocation::new(Some("RU"), None)),
+                (vec![10], HopLocation::new(Some("DE"), Some("3320"))),
+            ]),
+        };
+        let (paths, lease_set) = build_gateway_paths(&graph, &client, &gateways, 1, &exit, &geo).unwrap();
+        assert_eq!(lease_set.leases[0].gateway_peer_id, vec![2u8]);
+        assert!(paths.iter().all(|p| p.hops[0].peer_id == vec![2u8] && p.hops[1].peer_id == vec![3u8]));
+
+        // Relay 3 in the exit's AS leaves no valid circuit
+        geo.locations.insert(vec![3], HopLocation::new(Some("FR"), Some("AS3320 Deutsche Telekom")));
+        geo.constraints.fallback = GeoFallback::Fail;
+        assert!(build_gateway_paths(&graph, &client, &gateways, 1, &exit, &geo).is_err());
+
+        // Relaxed diversity still keeps the exclusions
+        geo.constraints.fallback = GeoFallback::RelaxDiversity;
+        let (paths, _) = build_gateway_paths(&graph, &client, &gateways, 1, &exit, &geo).unwrap();
+        assert!(paths.iter().all(|p| p.hops[1].peer_id != vec![4u8]));
+
+        assert!(geo.constraints.allows_exit(Some("DE")));
+        assert!(!geo.constraints.allows_exit(Some("RU")));
+        geo.constraints.exit_country = Some("US".to_string());
+        assert!(!geo.constraints.allows_exit(Some("DE")));
+        assert!(!geo.constraints.allows_exit(None));
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn test_ed25519_peer_id_matches_libp2p() {
+        let keypair = craftec_crypto::SigningKeypair::generate();
+        let pubkey = keypair.public_key_bytes();
+        let libp2p_key = libp2p::identity::ed25519::PublicKey::try_from_bytes(&pubkey).unwrap();
+        let peer_id = libp2p::PeerId::from_public_key(&libp2p::identity::PublicKey::from(libp2p_key));
+        assert_eq!(ed25519_peer_id(&pubkey), peer_id.to_bytes());
+    }
 }
@@ -0,0 +1,207 @@
+//! On-chain claim status of the pools a node earned from
+//!
+//! A relay or exit books credits per pool, but whether it can turn them
+//! into a payout depends on the pool's subscription account: the pool
+//! must be past its grace period, have a posted distribution, still hold
+//! a balance, and the node must not have claimed before. [`PoolClaims`]
+//! keeps the last answer per pool and hands out the pools due for a
+//! refresh, a few at a time, so the node can poll the chain in the
+//! background and show "claimable now / claimable at / already claimed".
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use craftnet_core::PublicKey;
+use craftnet_settlement::{EpochPhase, SubscriptionState, GRACE_PERIOD_SECS};
+
+/// Where our payout from a pool stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClaimStatus {
+    /// Subscription still running or in grace; claims open at this Unix time
+    ClaimableAt(u64),
+    /// Claims are open but no distribution was posted yet
+    AwaitingDistribution,
+    /// Distribution posted and the pool still holds funds
+    ClaimableNow,
+    /// This node already claimed its share
+    Claimed,
+    /// Pool drained before this node claimed
+    Closed,
+    /// No subscription account found on chain
+    NotFound,
+}
+
+impl ClaimStatus {
+    /// Status of a pool given its account (None = missing) and whether we
+    /// hold a claim receipt for it
+    pub fn of(state: Option<&SubscriptionState>, claimed: bool, now: u64) -> Self {
+        if claimed {
+            return ClaimStatus::Claimed;
+        }
+        let Some(state) = state else { return ClaimStatus::NotFound };
+        match state.phase(now) {
+            EpochPhase::Active | EpochPhase::Grace => {
+                ClaimStatus::ClaimableAt(state.expires_at + GRACE_PERIOD_SECS)
+            }
+            EpochPhase::Claimable if state.distribution_posted => ClaimStatus::ClaimableNow,
+            EpochPhase::Claimable => ClaimStatus::AwaitingDistribution,
+            EpochPhase::Closed => ClaimStatus::Closed,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClaimStatus::ClaimableAt(_) => "claimable_at",
+            ClaimStatus::AwaitingDistribution => "awaiting_distribution",
+            ClaimStatus::ClaimableNow => "claimable_now",
+            ClaimStatus::Claimed => "claimed",
+            ClaimStatus::Closed => "closed",
+            ClaimStatus::NotFound => "not_found",
+        }
+    }
+
+    /// Unix time claims open (only for `ClaimableAt`)
+    pub fn claimable_at(&self) -> Option<u64> {
+        match self {
+            ClaimStatus::ClaimableAt(at) => Some(*at),
+            _ => None,
+        }
+    }
+
+    /// No later check can change the answer
+    fn is_final(&self) -> bool {
+        matches!(self, ClaimStatus::Claimed | ClaimStatus::Closed)
+    }
+}
+
+/// Last known on-chain state of one pool
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolClaimStatus {
+    pub pool: PublicKey,
+    /// Funds left in the pool
+    pub balance: u64,
+    /// Funds the pool started with
+    pub original_balance: u64,
+    /// Pool phase (None when the account wasn't found)
+    pub phase: Option<EpochPhase>,
+    pub status: ClaimStatus,
+    /// Unix time of the check
+    pub checked_at: u64,
+}
+
+impl PoolClaimStatus {
+    pub fn new(pool: PublicKey, state: Option<&SubscriptionState>, claimed: bool, now: u64) -> Self {
+        Self {
+            pool,
+            balance: state.map_or(0, |s| s.pool_balance),
+            original_balance: state.map_or(0, |s| s.original_pool_balance),
+            phase: state.map(|s| s.phase(now)),
+            status: ClaimStatus::of(state, claimed, now),
+            checked_at: now,
+        }
+    }
+}
+
+struct Tracked {
+    status: PoolClaimStatus,
+    refreshed: Instant,
+}
+
+/// Claim status per pool, refreshed in small batches
+pub struct PoolClaims {
+    pools: HashMap<PublicKey, Tracked>,
+    /// How long an answer stays fresh
+    interval: Duration,
+    /// Pools checked per refresh
+    batch: usize,
+}
+
+impl PoolClaims {
+    pub fn new(interval: Duration, batch: usize) -> Self {
+        Self { pools: HashMap::new(), interval, batch }
+    }
+
+    /// Pools out of `earned` to check now: never-checked pools first, then
+    /// the stalest. Claimed and closed pools are never checked again.
+    pub fn due<'a>(&self, earned: impl IntoIterator<Item = &'a PublicKey>, now: Instant) -> Vec<PublicKey> {
+        let mut due: Vec<(Option<Instant>, PublicKey)> = earned
+            .into_iter()
+            .filter_map(|pool| match self.pools.get(pool) {
+                None => Some((None, *pool)),
+                Some(t) if t.status.status.is_final() => None,
+                Some(t) if now.saturating_duration_since(t.refreshed) >= self.interval => {
+                    Some((Some(t.refreshed), *pool))
+                }
+                Some(_) => None,
+            })
+            .collect();
+        due.sort();
+        due.into_iter().take(self.batch).map(|(_, pool)| pool).collect()
+    }
+
+    pub fn record(&mut self, status: PoolClaimStatus, now: Instant) {
+        self.pools.insert(status.pool, Tracked { status, refreshed: now });
+    }
+
+    pub fn get(&self, pool: &PublicKey) -> Option<&PoolClaimStatus> {
+        self.pools.get(pool).map(|t| &t.status)
+    }
+
+    /// All known statuses, ordered by pool
+    pub fn snapshot(&self) -> Vec<PoolClaimStatus> {
+        let mut out: Vec<PoolClaimStatus> = self.pools.values().map(|t| t.status.clone()).collect();
+        out.sort_by(|a, b| a.pool.cmp(&b.pool));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use craftnet_core::SubscriptionTier;
+
+    fn state(expires_at: u64, balance: u64, posted: bool) -> SubscriptionState {
+        SubscriptionState {
+            pool_pubkey: [1; 32],
+            tier: SubscriptionTier::Standard,
+            start_date: 0,
+            created_at: 0,
+            expires_at,
+            pool_balance: balance,
+            original_pool_balance: 1_000,
+            total_bytes: 10,
+            distribution_posted: posted,
+            distribution_root: [0; 32],
+            distribution_poster: None,
+        }
+    }
+
+    #[test]
+    fn test_claim_status_of() {
+        let now = 10_000;
+        assert_eq!(ClaimStatus::of(Some(&state(now + 60, 1_000, false)), false, now), ClaimStatus::ClaimableAt(now + 60 + GRACE_PERIOD_SECS));
+        assert_eq!(ClaimStatus::of(Some(&state(now - 100, 1_000, false)), false, now), ClaimStatus::AwaitingDistribution);
+        assert_eq!(ClaimStatus::of(Some(&state(now - 100, 1_000, true)), false, now), ClaimStatus::ClaimableNow);
+        assert_eq!(ClaimStatus::of(Some(&state(now - 100, 0, true)), false, now), ClaimStatus::Closed);
+        assert_eq!(ClaimStatus::of(Some(&state(now - 100, 0, true)), true, now), ClaimStatus::Claimed);
+        assert_eq!(ClaimStatus::of(None, false, now), ClaimStatus::NotFound);
+    }
+
+    #[test]
+    fn test_due_batches_and_skips_final() {
+        let mut claims = PoolClaims::new(Duration::from_secs(60), 2);
+        let start = Instant::now();
+        let pools = [[1u8; 32], [2; 32], [3; 32]];
+
+        assert_eq!(claims.due(&pools, start).len(), 2);
+        claims.record(PoolClaimStatus::new(pools[0], None, true, 0), start);
+        claims.record(PoolClaimStatus::new(pools[1], Some(&state(u64::MAX / 2, 1, false)), false, 0), start);
+
+        // Only the unchecked pool until the interval passes; claimed never again
+        assert_eq!(claims.due(&pools, start), vec![pools[2]]);
+        claims.record(PoolClaimStatus::new(pools[2], None, false, 0), start + Duration::from_secs(10));
+        assert_eq!(claims.due(&pools, start + Duration::from_secs(65)), vec![pools[1]]);
+        assert_eq!(claims.due(&pools, start + Duration::from_secs(75)), vec![pools[1], pools[2]]);
+        assert_eq!(claims.snapshot()[0].status, ClaimStatus::Claimed);
+    }
+}
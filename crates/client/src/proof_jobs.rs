@@ -0,0 +1,469 @@
+//! Distribution proof job queue
+//!
+//! Groth16 distribution proofs take minutes, so the aggregator never proves
+//! inline. `maybe_post_distributions` enqueues a job per expired pool; the
+//! node starts up to `max_concurrent` jobs on blocking threads, records each
+//! state change (Queued → Proving → Verifying → Done / Failed) as a
+//! `ProofJobEvent` for IPC subscribers, and posts Done jobs on-chain.
+//!
+//! The queue is persisted to `{data_dir}/proof-jobs-{peer_id}.json` on every
+//! change. Jobs that were Proving or Verifying when the process died are
+//! reset to Queued on load, and Done jobs keep their proof so a crash
+//! between proving and posting doesn't cost a second proof.
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+
+use craftnet_core::{system_clock, PublicKey, SharedClock};
+use craftnet_network::PoolType;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// Attempts per job before it stays Failed
+pub const MAX_PROOF_ATTEMPTS: u32 = 3;
+
+/// Failed jobs are dropped after this long so the pool is retried
+/// with a freshly built distribution
+pub const FAILED_JOB_RETRY_SECS: u64 = 3600;
+
+/// Default number of proofs generated at once
+pub const DEFAULT_MAX_CONCURRENT_PROOFS: usize = 1;
+
+/// Progress of a distribution proof job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProofJobState {
+    Queued,
+    Proving,
+    Verifying,
+    Done,
+    Failed,
+}
+
+impl ProofJobState {
+    /// Job occupies a proving slot
+    pub fn is_running(&self) -> bool {
+        matches!(self, Self::Proving | Self::Verifying)
+    }
+}
+
+/// One distribution to prove and post
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProofJob {
+    pub id: u64,
+    pub pool_pubkey: PublicKey,
+    pub pool_type: PoolType,
+    pub distribution_root: [u8; 32],
+    pub total_bytes: u64,
+    /// (relay pubkey, cumulative bytes) — prover input
+    pub entries: Vec<([u8; 32], u64)>,
+    pub state: ProofJobState,
+    /// Proving attempts started so far
+    pub attempts: u32,
+    /// Last failure reason
+    pub error: Option<String>,
+    /// Groth16 proof bytes (set when Done)
+    #[serde(default)]
+    pub proof_bytes: Vec<u8>,
+    /// Committed public values (set when Done)
+    #[serde(default)]
+    pub public_values: Vec<u8>,
+    /// Guest cycles the proof took (set when Done, if the backend reports them)
+    #[serde(default)]
+    pub cycles: Option<u64>,
+    /// Unix seconds
+    pub created_at: u64,
+    /// Unix seconds of the last state change
+    pub updated_at: u64,
+}
+
+/// State change broadcast to IPC subscribers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProofJobEvent {
+    pub job_id: u64,
+    /// Hex-encoded pool pubkey
+    pub pool_pubkey: String,
+    pub state: ProofJobState,
+    pub attempts: u32,
+    pub error: Option<String>,
+    /// Guest cycles of the finished proof
+    #[serde(default)]
+    pub cycles: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ProofJobFile {
+    next_id: u64,
+    jobs: Vec<ProofJob>,
+}
+
+/// Persistent queue of distribution proof jobs
+pub struct ProofJobQueue {
+    jobs: Vec<ProofJob>,
+    next_id: u64,
+    max_concurrent: usize,
+    path: Option<PathBuf>,
+    events: VecDeque<ProofJobEvent>,
+    clock: SharedClock,
+}
+
+impl std::fmt::Debug for ProofJobQueue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProofJobQueue")
+            .field("jobs", &self.jobs)
+            .field("next_id", &self.next_id)
+            .field("max_concurrent", &self.max_concurrent)
+            .field("path", &self.path)
+            .field("events", &self.events)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ProofJobQueue {
+    pub fn new(max_concurrent: usize, path: Option<PathBuf>) -> Self {
+        Self {
+            jobs: Vec::new(),
+            next_id: 1,
+            max_concurrent: max_concurrent.max(1),
+            path,
+            events: VecDeque::new(),
+            clock: system_clock(),
+        }
+    }
+
+    /// Stamp and expire jobs by `clock` instead of the system clock
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+    }
+
+    /// Load the queue from `path` (if it exists), restarting interrupted jobs.
+    pub fn load(max_concurrent: usize, path: Option<PathBuf>) -> Self {
+        let mut queue = Self::new(max_concurrent, path);
+        let Some(ref path) = queue.path else { return queue };
+        if !path.exists() {
+            return queue;
+        }
+
+        let file: ProofJobFile = match std::fs::read(path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| serde_json::from_slice(&data).map_err(|e| e.to_string()))
+        {
+            Ok(file) => file,
+            Err(e) => {
+                warn!("Failed to load proof job queue from {}: {}", path.display(), e);
+                return queue;
+            }
+        };
+
+        queue.next_id = file.next_id.max(1);
+        queue.jobs = file.jobs;
+        let now = queue.clock.unix_now();
+        let mut interrupted = Vec::new();
+        for job in &mut queue.jobs {
+            if job.state.is_running() {
+                job.state = ProofJobState::Queued;
+                job.updated_at = now;
+                interrupted.push(job.id);
+            }
+        }
+        for id in interrupted {
+            queue.emit(id);
+        }
+        queue
+    }
+
+    /// Persist the queue (write to tmp, then rename)
+    pub fn save(&self) {
+        let Some(ref path) = self.path else { return };
+        save_to(path, &ProofJobFile { next_id: self.next_id, jobs: self.jobs.clone() });
+    }
+
+    /// Enqueue a distribution. Returns `None` if the pool already has a job.
+    pub fn enqueue(
+        &mut self,
+        pool_pubkey: PublicKey,
+        pool_type: PoolType,
+        distribution_root: [u8; 32],
+        total_bytes: u64,
+        entries: Vec<([u8; 32], u64)>,
+    ) -> Option<u64> {
+        if self.has_job(&pool_pubkey) {
+            return None;
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        let now = self.clock.unix_now();
+        self.jobs.push(ProofJob {
+            id,
+            pool_pubkey,
+            pool_type,
+            distribution_root,
+            total_bytes,
+            entries,
+            state: ProofJobState::Queued,
+            attempts: 0,
+            error: None,
+            proof_bytes: Vec::new(),
+            public_values: Vec::new(),
+            cycles: None,
+            created_at: now,
+            updated_at: now,
+        });
+        self.emit(id);
+        self.save();
+        Some(id)
+    }
+
+    /// Whether a job (in any state) exists for this pool
+    pub fn has_job(&self, pool_pubkey: &PublicKey) -> bool {
+        self.jobs.iter().any(|j| &j.pool_pubkey == pool_pubkey)
+    }
+
+    /// Whether any job is waiting for a proving slot
+    pub fn has_queued(&self) -> bool {
+        self.jobs.iter().any(|j| j.state == ProofJobState::Queued)
+    }
+
+    /// Drop jobs that stayed Failed for `FAILED_JOB_RETRY_SECS`
+    pub fn prune_failed(&mut self) {
+        self.prune_failed_at(self.clock.unix_now());
+    }
+
+    fn prune_failed_at(&mut self, now: u64) {
+        let before = self.jobs.len();
+        self.jobs.retain(|j| {
+            j.state != ProofJobState::Failed || now.saturating_sub(j.updated_at) < FAILED_JOB_RETRY_SECS
+        });
+        if self.jobs.len() != before {
+            self.save();
+        }
+    }
+
+    /// Move queued jobs to Proving while slots are free; returns the started jobs.
+    pub fn start_ready(&mut self) -> Vec<ProofJob> {
+        let running = self.jobs.iter().filter(|j| j.state.is_running()).count();
+        let free = self.max_concurrent.saturating_sub(running);
+        let ids: Vec<u64> = self.jobs.iter()
+            .filter(|j| j.state == ProofJobState::Queued)
+            .take(free)
+            .map(|j| j.id)
+            .collect();
+
+        let mut started = Vec::new();
+        for id in ids {
+            if let Some(job) = self.update(id, ProofJobState::Proving) {
+                job.attempts += 1;
+                started.push(job.clone());
+            }
+        }
+        if !started.is_empty() {
+            for job in &started {
+                self.emit(job.id);
+            }
+            self.save();
+        }
+        started
+    }
+
+    /// Proof generated, now being verified
+    pub fn mark_verifying(&mut self, id: u64) {
+        if self.update(id, ProofJobState::Verifying).is_some() {
+            self.emit(id);
+        }
+    }
+
+    /// Proof generated and verified
+    pub fn mark_done(&mut self, id: u64, proof_bytes: Vec<u8>, public_values: Vec<u8>, cycles: Option<u64>) {
+        if let Some(job) = self.update(id, ProofJobState::Done) {
+            job.proof_bytes = proof_bytes;
+            job.public_values = public_values;
+            job.cycles = cycles;
+            job.error = None;
+            self.emit(id);
+            self.save();
+        }
+    }
+
+    /// Proving failed: requeue until `MAX_PROOF_ATTEMPTS`, then stay Failed
+    pub fn mark_failed(&mut self, id: u64, error: String) {
+        let Some(job) = self.jobs.iter().find(|j| j.id == id) else { return };
+        let next = if job.attempts >= MAX_PROOF_ATTEMPTS {
+            ProofJobState::Failed
+        } else {
+            ProofJobState::Queued
+        };
+        if let Some(job) = self.update(id, next) {
+            job.error = Some(error);
+            self.emit(id);
+            self.save();
+        }
+    }
+
+    /// Jobs with a verified proof, ready to post
+    pub fn done_jobs(&self) -> Vec<ProofJob> {
+        self.jobs.iter()
+            .filter(|j| j.state == ProofJobState::Done)
+            .cloned()
+            .collect()
+    }
+
+    /// Drop a job (posted on-chain, or no longer needed)
+    pub fn remove(&mut self, id: u64) {
+        let before = self.jobs.len();
+        self.jobs.retain(|j| j.id != id);
+        if self.jobs.len() != before {
+            self.save();
+        }
+    }
+
+    /// All jobs, oldest first
+    pub fn jobs(&self) -> &[ProofJob] {
+        &self.jobs
+    }
+
+    /// Drain state-change events since the last call
+    pub fn take_events(&mut self) -> Vec<ProofJobEvent> {
+        self.events.drain(..).collect()
+    }
+
+    fn update(&mut self, id: u64, state: ProofJobState) -> Option<&mut ProofJob> {
+        let now = self.clock.unix_now();
+        let job = self.jobs.iter_mut().find(|j| j.id == id)?;
+        job.state = state;
+        job.updated_at = now;
+        Some(job)
+    }
+
+    fn emit(&mut self, id: u64) {
+        let Some(job) = self.jobs.iter().find(|j| j.id == id) else { return };
+        self.events.push_back(ProofJobEvent {
+            job_id: job.id,
+            pool_pubkey: hex::encode(job.pool_pubkey),
+            state: job.state,
+            attempts: job.attempts,
+            error: job.error.clone(),
+            cycles: job.cycles,
+        });
+    }
+}
+
+fn save_to(path: &Path, file: &ProofJobFile) {
+    let json = match serde_json::to_vec_pretty(file) {
+        Ok(j) => j,
+        Err(e) => {
+            warn!("Failed to serialize proof job queue: {}", e);
+            return;
+        }
+    };
+    let tmp_path = path.with_extension("json.tmp");
+    if let Err(e) = std::fs::write(&tmp_path, &json) {
+        warn!("Failed to write proof job queue: {}", e);
+        return;
+    }
+    if let Err(e) = std::fs::rename(&tmp_path, path) {
+        warn!("Failed to rename proof job queue file: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enqueue(queue: &mut ProofJobQueue, pool: u8) -> u64 {
+        queue.enqueue([pool; 32], PoolType::Subscribed, [9u8; 32], 100, vec![([1u8; 32], 100)]).unwrap()
+    }
+
+    #[test]
+    fn test_enqueue_dedupes_per_pool() {
+        let mut queue = ProofJobQueue::new(1, None);
+        enqueue(&mut queue, 1);
+        assert!(queue.enqueue([1u8; 32], PoolType::Subscribed, [0u8; 32], 0, vec![]).is_none());
+        assert_eq!(queue.jobs().len(), 1);
+    }
+
+    #[test]
+    fn test_concurrency_limit() {
+        let mut queue = ProofJobQueue::new(2, None);
+        let a = enqueue(&mut queue, 1);
+        let b = enqueue(&mut queue, 2);
+        let c = enqueue(&mut queue, 3);
+
+        let started: Vec<u64> = queue.start_ready().iter().map(|j| j.id).collect();
+        assert_eq!(started, vec![a, b]);
+        assert!(queue.start_ready().is_empty());
+
+        queue.mark_verifying(a);
+        queue.mark_done(a, vec![1], vec![2], None);
+        let started: Vec<u64> = queue.start_ready().iter().map(|j| j.id).collect();
+        assert_eq!(started, vec![c]);
+    }
+
+    #[test]
+    fn test_events_follow_state_changes() {
+        let mut queue = ProofJobQueue::new(1, None);
+        let id = enqueue(&mut queue, 1);
+        queue.start_ready();
+        queue.mark_verifying(id);
+        queue.mark_done(id, vec![1], vec![2], Some(1_000));
+
+        let events = queue.take_events();
+        assert_eq!(events.last().unwrap().cycles, Some(1_000));
+        let states: Vec<ProofJobState> = events.iter().map(|e| e.state).collect();
+        assert_eq!(states, vec![
+            ProofJobState::Queued,
+            ProofJobState::Proving,
+            ProofJobState::Verifying,
+            ProofJobState::Done,
+        ]);
+        assert!(queue.take_events().is_empty());
+    }
+
+    #[test]
+    fn test_failed_job_retries_then_stays_failed() {
+        let mut queue = ProofJobQueue::new(1, None);
+        let id = enqueue(&mut queue, 1);
+        for attempt in 1..=MAX_PROOF_ATTEMPTS {
+            assert_eq!(queue.start_ready().len(), 1);
+            queue.mark_failed(id, format!("attempt {}", attempt));
+        }
+        assert_eq!(queue.jobs()[0].state, ProofJobState::Failed);
+        assert!(queue.start_ready().is_empty());
+
+        let failed_at = queue.jobs()[0].updated_at;
+        queue.prune_failed_at(failed_at + 1);
+        assert!(queue.has_job(&[1u8; 32]));
+        queue.prune_failed_at(failed_at + FAILED_JOB_RETRY_SECS);
+        assert!(!queue.has_job(&[1u8; 32]));
+    }
+
+    #[test]
+    fn test_interrupted_job_restarts_after_reload() {
+        let dir = std::env::temp_dir().join(format!("craftnet-test-proof-jobs-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("proof-jobs.json");
+
+        let mut queue = ProofJobQueue::new(1, Some(path.clone()));
+        let running = enqueue(&mut queue, 1);
+        let done = enqueue(&mut queue, 2);
+        queue.start_ready();
+        queue.mark_verifying(running);
+        // Force the second job to Done as if it had finished earlier
+        queue.update(done, ProofJobState::Done).unwrap().proof_bytes = vec![7];
+        queue.save();
+        drop(queue);
+
+        let mut reloaded = ProofJobQueue::load(1, Some(path));
+        let job = reloaded.jobs().iter().find(|j| j.id == running).unwrap();
+        assert_eq!(job.state, ProofJobState::Queued);
+        assert_eq!(job.attempts, 1);
+        assert_eq!(reloaded.done_jobs().len(), 1);
+        assert_eq!(reloaded.done_jobs()[0].proof_bytes, vec![7]);
+
+        // New ids don't collide with persisted ones
+        let next = enqueue(&mut reloaded, 3);
+        assert!(next > done);
+        assert_eq!(reloaded.start_ready()[0].id, running);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,285 @@
+//! Proof publication pacing
+//!
+//! A busy relay compresses receipts into many small batches, and each batch
+//! becomes a [`ProofMessage`] on the proof gossip topic. [`ProofPublishPolicy`]
+//! bounds how often a pool's batches are cut (at most every
+//! `min_interval`, sooner once `batch_bytes` of payload are covered), and
+//! the [`ProofOutbox`] holds compressed proofs until they can be published:
+//! while the node is offline or over its [`GossipBudget`], consecutive
+//! proofs of the same pool are merged into one message covering the whole
+//! chain segment, so a backlog costs one publication per pool rather than
+//! one per batch. Proofs that neither gossip nor a direct submission could
+//! deliver go back to the front of the outbox.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use craftnet_core::BandwidthClass;
+use craftnet_network::{PoolType, ProofMessage};
+
+/// Default shortest time between two published batches of one pool
+pub const DEFAULT_PROOF_MIN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Default payload bytes that make a batch ready before it is full
+pub const DEFAULT_PROOF_BATCH_BYTES: u64 = 256 * 1024 * 1024;
+
+/// When relay batches are cut and how much uplink their publication may use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProofPublishPolicy {
+    /// Shortest time between two batches of one pool (ignored while draining)
+    pub min_interval: Duration,
+    /// Receipt payload bytes that make a pool's batch ready
+    pub batch_bytes: u64,
+    /// Bytes per second proof gossip may use (None = by our advertised
+    /// bandwidth class, see [`default_gossip_budget`]). Measured gossip
+    /// uplink beyond the proofs themselves (mesh fan-out, other topics) is
+    /// charged against it too.
+    pub gossip_budget: Option<u64>,
+}
+
+impl Default for ProofPublishPolicy {
+    fn default() -> Self {
+        Self {
+            min_interval: DEFAULT_PROOF_MIN_INTERVAL,
+            batch_bytes: DEFAULT_PROOF_BATCH_BYTES,
+            gossip_budget: None,
+        }
+    }
+}
+
+impl ProofPublishPolicy {
+    /// Gossip budget in bytes per second for a relay of `class` (None = unlimited)
+    pub fn budget_for(&self, class: BandwidthClass) -> Option<u64> {
+        self.gossip_budget.or_else(|| default_gossip_budget(class))
+    }
+}
+
+/// Proof gossip budget of a relay that didn't configure one: a small slice
+/// of a low-bandwidth uplink, unlimited on fast relays
+pub fn default_gossip_budget(class: BandwidthClass) -> Option<u64> {
+    match class {
+        BandwidthClass::Low => Some(4 * 1024),
+        BandwidthClass::Medium => Some(32 * 1024),
+        BandwidthClass::High => None,
+    }
+}
+
+/// Token bucket limiting the bytes published on the proof topic
+#[derive(Debug, Clone)]
+pub struct GossipBudget {
+    /// Refill rate (bytes/second, None = unlimited)
+    rate: Option<u64>,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl GossipBudget {
+    /// Burst allowance, in seconds of the refill rate
+    const BURST_SECS: u64 = 10;
+
+    pub fn new(rate: Option<u64>) -> Self {
+        Self {
+            rate,
+            tokens: rate.map_or(0.0, |r| (r * Self::BURST_SECS) as f64),
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Change the refill rate, keeping the tokens saved so far (up to the
+    /// new burst)
+    pub fn set_rate(&mut self, rate: Option<u64>) {
+        if rate != self.rate {
+            self.rate = rate;
+            if let Some(r) = rate {
+                self.tokens = self.tokens.min((r * Self::BURST_SECS) as f64);
+            }
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let Some(rate) = self.rate else { return };
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate as f64).min((rate * Self::BURST_SECS) as f64);
+        self.last_refill = now;
+    }
+
+    /// Take `bytes` that were already sent, as far as the tokens go
+    pub fn charge(&mut self, bytes: u64, now: Instant) {
+        if self.rate.is_none() {
+            return;
+        }
+        self.refill(now);
+        self.tokens = (self.tokens - bytes as f64).max(0.0);
+    }
+
+    /// Spend `bytes` if the budget allows it. A message larger than the
+    /// whole burst goes out once the bucket is full, so it can't stall forever.
+    pub fn try_spend(&mut self, bytes: usize, now: Instant) -> bool {
+        let Some(rate) = self.rate else { return true };
+        self.refill(now);
+        let burst = (rate * Self::BURST_SECS) as f64;
+        let cost = (bytes as f64).min(burst);
+        if self.tokens >= cost {
+            self.tokens -= cost;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Compressed proofs waiting to be published, oldest first
+#[derive(Debug, Default)]
+pub struct ProofOutbox {
+    queue: VecDeque<ProofMessage>,
+    /// Proofs folded into an earlier queued one
+    merged: u64,
+}
+
+impl ProofOutbox {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a freshly compressed proof. If the last queued proof of the
+    /// same pool ends where this one starts, the two become one message
+    /// (re-signed with `sign`) covering both batches.
+    pub fn push(&mut self, msg: ProofMessage, sign: impl Fn(&ProofMessage) -> Vec<u8>) {
+        let earlier = self.queue.iter_mut().rev().find(|queued| {
+            queued.pool_pubkey == msg.pool_pubkey && queued.pool_type == msg.pool_type
+        });
+        match earlier {
+            Some(queued) if queued.new_root == msg.prev_root && queued.proof.is_empty() && msg.proof.is_empty() => {
+                queued.batch_bytes += msg.batch_bytes;
+                queued.cumulative_bytes = msg.cumulative_bytes;
+                queued.new_root = msg.new_root;
+                queued.timestamp = msg.timestamp;
+                queued.signature = sign(queued);
+                self.merged += 1;
+            }
+            _ => self.queue.push_back(msg),
+        }
+    }
+
+    /// Put back a proof that couldn't be delivered, ahead of the queue
+    pub fn requeue(&mut self, msg: ProofMessage) {
+        self.queue.push_front(msg);
+    }
+
+    /// Oldest queued proof
+    pub fn front(&self) -> Option<&ProofMessage> {
+        self.queue.front()
+    }
+
+    /// Remove the oldest queued proof (after publishing it)
+    pub fn pop(&mut self) -> Option<ProofMessage> {
+        self.queue.pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Proofs merged into earlier ones so far
+    pub fn merged(&self) -> u64 {
+        self.merged
+    }
+
+    /// Queued proofs of a pool
+    pub fn pending_for(&self, pool_pubkey: &[u8; 32], pool_type: PoolType) -> usize {
+        self.queue
+            .iter()
+            .filter(|m| &m.pool_pubkey == pool_pubkey && m.pool_type == pool_type)
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proof(pool: u8, prev: u8, new: u8, bytes: u64, cumulative: u64) -> ProofMessage {
+        ProofMessage {
+            relay_pubkey: [9; 32],
+            pool_pubkey: [pool; 32],
+            pool_type: PoolType::Free,
+            batch_bytes: bytes,
+            cumulative_bytes: cumulative,
+            prev_root: [prev; 32],
+            new_root: [new; 32],
+            proof: vec![],
+            timestamp: new as u64,
+            signature: vec![],
+        }
+    }
+
+    #[test]
+    fn test_outbox_merges_chained_proofs() {
+        let mut outbox = ProofOutbox::new();
+        let sign = |m: &ProofMessage| m.new_root.to_vec();
+        outbox.push(proof(1, 0, 1, 100, 100), sign);
+        outbox.push(proof(2, 0, 5, 10, 10), sign);
+        outbox.push(proof(1, 1, 2, 50, 150), sign);
+
+        assert_eq!(outbox.len(), 2);
+        assert_eq!(outbox.merged(), 1);
+        let merged = outbox.pop().unwrap();
+        assert_eq!(merged.prev_root, [0; 32]);
+        assert_eq!(merged.new_root, [2; 32]);
+        assert_eq!(merged.batch_bytes, 150);
+        assert_eq!(merged.cumulative_bytes, 150);
+        assert_eq!(merged.signature, vec![2; 32]);
+    }
+
+    #[test]
+    fn test_outbox_keeps_unchained_proofs_apart() {
+        let mut outbox = ProofOutbox::new();
+        outbox.push(proof(1, 0, 1, 100, 100), |_| vec![]);
+        // Doesn't start at the queued proof's new root
+        outbox.push(proof(1, 7, 8, 50, 150), |_| vec![]);
+        assert_eq!(outbox.len(), 2);
+        assert_eq!(outbox.pending_for(&[1; 32], PoolType::Free), 2);
+    }
+
+    #[test]
+    fn test_outbox_requeue_goes_first() {
+        let mut outbox = ProofOutbox::new();
+        outbox.push(proof(1, 1, 2, 50, 150), |_| vec![]);
+        outbox.requeue(proof(1, 0, 1, 100, 100));
+        assert_eq!(outbox.pop().unwrap().new_root, [1; 32]);
+        assert_eq!(outbox.pop().unwrap().new_root, [2; 32]);
+    }
+
+    #[test]
+    fn test_gossip_budget() {
+        let now = Instant::now();
+        let mut unlimited = GossipBudget::new(None);
+        assert!(unlimited.try_spend(usize::MAX, now));
+
+        let mut budget = GossipBudget::new(Some(100));
+        assert!(budget.try_spend(1000, now));
+        assert!(!budget.try_spend(1, now));
+        assert!(budget.try_spend(100, now + Duration::from_secs(1)));
+        // Oversized messages wait for a full bucket instead of never going out
+        assert!(budget.try_spend(1_000_000, now + Duration::from_secs(20)));
+
+        // Traffic sent outside the budget delays what's left
+        let later = now + Duration::from_secs(40);
+        budget.charge(900, later);
+        assert!(!budget.try_spend(200, later));
+        assert!(budget.try_spend(100, later));
+    }
+
+    #[test]
+    fn test_default_budget_by_class() {
+        let policy = ProofPublishPolicy::default();
+        assert_eq!(policy.budget_for(BandwidthClass::High), None);
+        assert!(policy.budget_for(BandwidthClass::Low) < policy.budget_for(BandwidthClass::Medium));
+        let fixed = ProofPublishPolicy { gossip_budget: Some(1), ..Default::default() };
+        assert_eq!(fixed.budget_for(BandwidthClass::High), Some(1));
+    }
+}
@@ -0,0 +1,119 @@
+//! Free-tier quota tokens held by the client (see `craftnet_core::quota`)
+//!
+//! Tokens are kept per issuing exit (its quota key) and spent oldest first.
+//! Tunnel sessions keep a credit balance mirroring the exit's ledger, so a
+//! frame only carries tokens when the session runs short.
+
+use std::collections::{HashMap, VecDeque};
+
+use craftnet_core::{Id, QuotaTerms, QuotaToken};
+
+/// Unspent quota tokens and per-session credit
+#[derive(Debug, Default)]
+pub struct QuotaWallet {
+    /// Unspent tokens per issuer key, oldest epoch first
+    tokens: HashMap<[u8; 32], VecDeque<QuotaToken>>,
+    /// Latest epoch a batch was received for, per issuer key
+    fetched: HashMap<[u8; 32], u64>,
+    /// Credit (bytes) of metered tunnel sessions
+    sessions: HashMap<Id, i64>,
+}
+
+impl QuotaWallet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether `issuer`'s batch for `epoch` is still to be fetched
+    pub fn needs_batch(&self, issuer: &[u8; 32], epoch: u64) -> bool {
+        self.fetched.get(issuer).map_or(true, |&fetched| fetched < epoch)
+    }
+
+    /// Store a batch issued in `epoch`
+    pub fn add(&mut self, issuer: [u8; 32], epoch: u64, tokens: Vec<QuotaToken>) {
+        self.tokens.entry(issuer).or_default().extend(tokens);
+        self.fetched.insert(issuer, epoch);
+    }
+
+    /// Unspent tokens of `issuer`
+    pub fn balance(&self, issuer: &[u8; 32]) -> usize {
+        self.tokens.get(issuer).map_or(0, VecDeque::len)
+    }
+
+    /// Take `count` tokens of `issuer`, or none if fewer are left (a
+    /// partial payment would be spent at the exit and still refused)
+    pub fn take(&mut self, issuer: &[u8; 32], count: usize) -> Vec<QuotaToken> {
+        match self.tokens.get_mut(issuer) {
+            Some(tokens) if tokens.len() >= count => tokens.drain(..count).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Tokens for a tunnel frame of `bytes` in `session`, topping its
+    /// credit up to cover them. Closing frames end the session.
+    pub fn tokens_for_frame(&mut self, terms: &QuotaTerms, session: Id, bytes: usize, is_close: bool) -> Vec<QuotaToken> {
+        let credit = self.sessions.get(&session).copied().unwrap_or(0);
+        let short = bytes as i64 - credit;
+        let tokens = if short > 0 {
+            self.take(&terms.issuer_key, terms.tokens_for(short as u64))
+        } else {
+            Vec::new()
+        };
+        let paid = credit + (tokens.len() as u64 * terms.token_bytes) as i64;
+        if is_close {
+            self.sessions.remove(&session);
+        } else if paid >= bytes as i64 {
+            self.sessions.insert(session, paid - bytes as i64);
+        }
+        tokens
+    }
+
+    /// Charge a tunnel response to its session (ignored for sessions that
+    /// are not metered or already closed)
+    pub fn charge_session(&mut self, session: &Id, bytes: usize) {
+        if let Some(credit) = self.sessions.get_mut(session) {
+            *credit -= bytes as i64;
+        }
+    }
+
+    /// Drop tokens that can no longer be spent in `epoch`
+    pub fn prune(&mut self, epoch: u64) {
+        for tokens in self.tokens.values_mut() {
+            tokens.retain(|t| t.epoch + 1 >= epoch);
+        }
+        self.tokens.retain(|_, tokens| !tokens.is_empty());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(epoch: u64, n: u8) -> QuotaToken {
+        QuotaToken { epoch, nonce: [n; 32], signature: [0; 32] }
+    }
+
+    #[test]
+    fn test_session_credit_mirrors_exit() {
+        let terms = QuotaTerms { issuer_key: [1; 32], token_bytes: 1000, tokens_per_epoch: 10 };
+        let mut wallet = QuotaWallet::new();
+        assert!(wallet.needs_batch(&[1; 32], 5));
+        wallet.add([1; 32], 5, (0..4).map(|n| token(5, n)).collect());
+        assert!(!wallet.needs_batch(&[1; 32], 5));
+
+        // 1500 bytes need two tokens, leaving 500 of credit
+        assert_eq!(wallet.tokens_for_frame(&terms, [9; 32], 1500, false).len(), 2);
+        assert!(wallet.tokens_for_frame(&terms, [9; 32], 500, false).is_empty());
+        // A response pushes the credit below zero; the next frame pays it back
+        wallet.charge_session(&[9; 32], 800);
+        assert_eq!(wallet.tokens_for_frame(&terms, [9; 32], 0, false).len(), 1);
+        assert_eq!(wallet.balance(&[1; 32]), 1);
+
+        // Not enough left: nothing is taken
+        assert!(wallet.tokens_for_frame(&terms, [8; 32], 5000, false).is_empty());
+        assert_eq!(wallet.balance(&[1; 32]), 1);
+
+        wallet.prune(7);
+        assert_eq!(wallet.balance(&[1; 32]), 0);
+    }
+}
@@ -0,0 +1,350 @@
+//! HTTP Range splitting for large GETs
+//!
+//! A large GET is fetched as a series of `Range: bytes=a-b` sub-requests,
+//! each executed by the exit as an ordinary request over its own circuit.
+//! [`RangedDownload`] tracks which byte ranges are done, in flight or
+//! waiting, and reassembles the body in order.
+//!
+//! Resume: when a sub-request fails (its circuit died, the exit timed out)
+//! its range goes back into the queue. When the origin returns fewer bytes
+//! than asked for, the received prefix is kept and only the remainder is
+//! requested again, starting at the first missing offset.
+//!
+//! Pieces are only joined if they come from one version of the resource:
+//! every sub-request carries `If-Range` with the probe's validator (a strong
+//! `ETag`, else `Last-Modified`), and a piece with another validator, or a
+//! full 200 body from a server whose copy changed, aborts the download. A
+//! resource without a validator isn't split.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use crate::{ClientError, Result, TunnelResponse};
+
+/// Suggested bytes per Range sub-request (`NodeConfig::range_chunk_size`;
+/// splitting is off by default)
+pub const DEFAULT_RANGE_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// Default Range sub-requests in flight at once
+pub const DEFAULT_RANGE_PARALLELISM: usize = 4;
+
+/// Attempts per byte range before the whole download fails
+pub const MAX_RANGE_ATTEMPTS: u32 = 3;
+
+/// Default largest resource fetched by Range splitting
+pub const DEFAULT_RANGE_MAX_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// Parsed `Content-Range: bytes start-end/total` header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentRange {
+    /// First byte offset (inclusive)
+    pub start: u64,
+    /// Last byte offset (inclusive)
+    pub end: u64,
+    /// Full resource length, if the server reported it
+    pub total: Option<u64>,
+}
+
+impl ContentRange {
+    /// Parse a `Content-Range` header value (`bytes 0-99/1000`, `bytes 0-99/*`)
+    pub fn parse(value: &str) -> Option<Self> {
+        let spec = value.trim().strip_prefix("bytes")?.trim_start();
+        let (range, total) = spec.split_once('/')?;
+        let (start, end) = range.split_once('-')?;
+        let start: u64 = start.trim().parse().ok()?;
+        let end: u64 = end.trim().parse().ok()?;
+        if end < start {
+            return None;
+        }
+        let total = match total.trim() {
+            "*" => None,
+            t => Some(t.parse().ok()?),
+        };
+        Some(Self { start, end, total })
+    }
+
+    /// Find and parse the Content-Range header of a response
+    pub fn from_response(response: &TunnelResponse) -> Option<Self> {
+        header_value(response, "content-range").and_then(Self::parse)
+    }
+}
+
+/// Case-insensitive header lookup
+pub(crate) fn header_value<'a>(response: &'a TunnelResponse, name: &str) -> Option<&'a str> {
+    response.headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+/// `Range` header value for bytes `start..=end`
+pub fn range_header(start: u64, end: u64) -> String {
+    format!("bytes={}-{}", start, end)
+}
+
+/// Validator identifying the version of a response's resource, usable in
+/// `If-Range`: a strong `ETag`, else `Last-Modified` (weak ETags can't be)
+pub fn range_validator(response: &TunnelResponse) -> Option<String> {
+    header_value(response, "etag")
+        .filter(|etag| !etag.trim_start().starts_with("W/"))
+        .or_else(|| header_value(response, "last-modified"))
+        .map(|v| v.trim().to_string())
+}
+
+/// State of one ranged download
+#[derive(Debug)]
+pub struct RangedDownload {
+    total: u64,
+    chunk_size: u64,
+    /// Probe's validator, every piece must carry the same
+    validator: String,
+    /// A piece came from another version of the resource
+    changed: bool,
+    /// First offset never handed out yet
+    next_offset: u64,
+    /// Ranges to (re)request before moving on: (start, end inclusive)
+    retry: VecDeque<(u64, u64)>,
+    /// Received bytes by start offset
+    done: BTreeMap<u64, Vec<u8>>,
+    /// Attempts per range start
+    attempts: HashMap<u64, u32>,
+}
+
+impl RangedDownload {
+    /// Plan a download of `total` bytes of the resource version identified
+    /// by `validator` (see [`range_validator`]), the first `first.len()` of
+    /// which arrived with the initial probe.
+    pub fn new(total: u64, chunk_size: usize, validator: String, first: Vec<u8>) -> Self {
+        let next_offset = first.len() as u64;
+        let mut done = BTreeMap::new();
+        if !first.is_empty() {
+            done.insert(0, first);
+        }
+        Self {
+            total,
+            chunk_size: chunk_size.max(1) as u64,
+            validator,
+            changed: false,
+            next_offset,
+            retry: VecDeque::new(),
+            done,
+            attempts: HashMap::new(),
+        }
+    }
+
+    /// Full resource length
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// `If-Range` header value for sub-requests
+    pub fn validator(&self) -> &str {
+        &self.validator
+    }
+
+    /// Bytes received so far
+    pub fn received(&self) -> u64 {
+        self.done.values().map(|b| b.len() as u64).sum()
+    }
+
+    /// Next byte range to request, retries first
+    pub fn next_range(&mut self) -> Option<(u64, u64)> {
+        if let Some(range) = self.retry.pop_front() {
+            return Some(range);
+        }
+        if self.next_offset >= self.total {
+            return None;
+        }
+        let start = self.next_offset;
+        let end = (start + self.chunk_size).min(self.total) - 1;
+        self.next_offset = end + 1;
+        Some((start, end))
+    }
+
+    /// Record a sub-response for `start..=end`.
+    ///
+    /// A short body keeps its prefix and requeues the rest (resume from the
+    /// first missing offset). Errors if the response is not the asked range;
+    /// a piece of another version of the resource also aborts the download
+    /// (see [`Self::fail`]).
+    pub fn complete(&mut self, start: u64, end: u64, response: TunnelResponse) -> Result<()> {
+        let same_version = range_validator(&response).as_deref() == Some(self.validator.as_str());
+        // If-Range answers a changed resource with a full 200
+        if response.status == 200 || (response.status == 206 && !same_version) {
+            self.changed = true;
+            return Err(ClientError::RequestFailed(format!(
+                "Range {}-{}: resource changed during the download", start, end,
+            )));
+        }
+        if response.status != 206 {
+            return Err(ClientError::RequestFailed(format!(
+                "Range {}-{} returned status {}", start, end, response.status,
+            )));
+        }
+        if let Some(range) = ContentRange::from_response(&response) {
+            if range.start != start {
+                return Err(ClientError::RequestFailed(format!(
+                    "Range {}-{} answered with offset {}", start, end, range.start,
+                )));
+            }
+            if range.total.is_some_and(|total| total != self.total) {
+                self.changed = true;
+                return Err(ClientError::RequestFailed(format!(
+                    "Range {}-{}: resource length changed during the download", start, end,
+                )));
+            }
+        }
+
+        let mut body = response.body;
+        let wanted = end - start + 1;
+        body.truncate(wanted as usize);
+        let got = body.len() as u64;
+        if got < wanted {
+            // Resume the remainder; this counts as a fresh range
+            self.retry.push_back((start + got, end));
+        }
+        if got > 0 {
+            self.done.insert(start, body);
+        }
+        Ok(())
+    }
+
+    /// Record a failed sub-request. Requeues the range unless it has used up
+    /// its attempts or the resource changed, in which case the download fails.
+    pub fn fail(&mut self, start: u64, end: u64) -> Result<()> {
+        if self.changed {
+            return Err(ClientError::RequestFailed(
+                "Resource changed during a ranged download".to_string(),
+            ));
+        }
+        let attempts = self.attempts.entry(start).or_default();
+        *attempts += 1;
+        if *attempts >= MAX_RANGE_ATTEMPTS {
+            return Err(ClientError::RequestFailed(format!(
+                "Range {}-{} failed {} times", start, end, attempts,
+            )));
+        }
+        self.retry.push_back((start, end));
+        Ok(())
+    }
+
+    /// Whether every byte has been received
+    pub fn is_complete(&self) -> bool {
+        self.retry.is_empty() && self.next_offset >= self.total && self.received() >= self.total
+    }
+
+    /// Body bytes in order (only meaningful once complete)
+    pub fn into_body(self) -> Vec<u8> {
+        let mut body = Vec::with_capacity(self.received() as usize);
+        for (_, bytes) in self.done {
+            body.extend_from_slice(&bytes);
+        }
+        body
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ETAG: &str = "\"v1\"";
+
+    fn dl(total: u64, chunk_size: usize, first: Vec<u8>) -> RangedDownload {
+        RangedDownload::new(total, chunk_size, ETAG.to_string(), first)
+    }
+
+    fn partial(start: u64, body: Vec<u8>, total: u64) -> TunnelResponse {
+        let end = start + body.len() as u64 - 1;
+        let mut headers = std::collections::HashMap::new();
+        headers.insert("content-range".to_string(), format!("bytes {}-{}/{}", start, end, total));
+        headers.insert("ETag".to_string(), ETAG.to_string());
+        TunnelResponse { status: 206, headers, body, hop_timings: Vec::new(), exit_failovers: 0 }
+    }
+
+    #[test]
+    fn test_parse_content_range() {
+        assert_eq!(
+            ContentRange::parse("bytes 0-99/1000"),
+            Some(ContentRange { start: 0, end: 99, total: Some(1000) }),
+        );
+        assert_eq!(ContentRange::parse("bytes 5-9/*").unwrap().total, None);
+        assert!(ContentRange::parse("bytes 9-5/10").is_none());
+        assert!(ContentRange::parse("items 0-1/2").is_none());
+    }
+
+    #[test]
+    fn test_ranges_cover_resource() {
+        let mut dl = dl(25, 10, vec![0u8; 10]);
+        assert_eq!(dl.next_range(), Some((10, 19)));
+        assert_eq!(dl.next_range(), Some((20, 24)));
+        assert_eq!(dl.next_range(), None);
+    }
+
+    #[test]
+    fn test_out_of_order_completion_reassembles_in_order() {
+        let mut dl = dl(30, 10, (0..10).collect());
+        let a = dl.next_range().unwrap();
+        let b = dl.next_range().unwrap();
+        dl.complete(b.0, b.1, partial(20, (20..30).collect(), 30)).unwrap();
+        assert!(!dl.is_complete());
+        dl.complete(a.0, a.1, partial(10, (10..20).collect(), 30)).unwrap();
+        assert!(dl.is_complete());
+        assert_eq!(dl.into_body(), (0..30).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn test_short_response_resumes_from_offset() {
+        let mut dl = dl(20, 10, (0..10).collect());
+        let r = dl.next_range().unwrap();
+        dl.complete(r.0, r.1, partial(10, (10..14).collect(), 20)).unwrap();
+        assert_eq!(dl.next_range(), Some((14, 19)));
+        dl.complete(14, 19, partial(14, (14..20).collect(), 20)).unwrap();
+        assert!(dl.is_complete());
+        assert_eq!(dl.into_body(), (0..20).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn test_failed_range_retried_then_gives_up() {
+        let mut dl = dl(20, 10, vec![0u8; 10]);
+        let r = dl.next_range().unwrap();
+        dl.fail(r.0, r.1).unwrap();
+        assert_eq!(dl.next_range(), Some(r));
+        dl.fail(r.0, r.1).unwrap();
+        assert!(dl.fail(r.0, r.1).is_err());
+    }
+
+    #[test]
+    fn test_wrong_offset_rejected() {
+        let mut dl = dl(20, 10, vec![0u8; 10]);
+        let r = dl.next_range().unwrap();
+        assert!(dl.complete(r.0, r.1, partial(0, vec![0u8; 10], 20)).is_err());
+    }
+
+    #[test]
+    fn test_changed_resource_aborts() {
+        let mut download = dl(30, 10, vec![0u8; 10]);
+        let r = download.next_range().unwrap();
+        let mut other = partial(10, vec![1u8; 10], 30);
+        other.headers.insert("ETag".to_string(), "\"v2\"".to_string());
+        assert!(download.complete(r.0, r.1, other).is_err());
+        // No retry: the pieces can't be joined any more
+        assert!(download.fail(r.0, r.1).is_err());
+
+        // If-Range answered with the whole new body
+        let mut download = dl(30, 10, vec![0u8; 10]);
+        let r = download.next_range().unwrap();
+        let full = TunnelResponse { status: 200, ..partial(0, vec![0u8; 40], 40) };
+        assert!(download.complete(r.0, r.1, full).is_err());
+        assert!(download.fail(r.0, r.1).is_err());
+    }
+
+    #[test]
+    fn test_range_validator() {
+        let mut response = partial(0, vec![0u8; 4], 4);
+        assert_eq!(range_validator(&response).as_deref(), Some(ETAG));
+        // Weak ETags can't be used in If-Range; Last-Modified can
+        response.headers.insert("ETag".to_string(), "W/\"v1\"".to_string());
+        assert_eq!(range_validator(&response), None);
+        response.headers.insert("Last-Modified".to_string(), "Tue, 15 Nov 1994 08:12:31 GMT".to_string());
+        assert_eq!(range_validator(&response).as_deref(), Some("Tue, 15 Nov 1994 08:12:31 GMT"));
+    }
+}
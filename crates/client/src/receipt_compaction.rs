@@ -0,0 +1,372 @@
+//! Relay receipt compaction
+//!
+//! A busy relay used to keep every [`ForwardReceipt`] it was handed until
+//! long after it had been proven. Once a receipt is part of a compressed
+//! batch (its leaf is under a published Merkle root) the distribution
+//! proof only needs the relay's byte count for the pool, so the
+//! [`ReceiptCompactor`] folds proven receipts into one [`HourlyReceipts`]
+//! accumulator per (pool, hour) and the receipts themselves are dropped.
+//!
+//! Receipts that arrive faster than they can be batched are bounded too:
+//! beyond the per-pool in-memory queue limit they go to a [`ReceiptSpill`]
+//! file and are read back as the queue drains, instead of being dropped.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use craftnet_core::{ForwardReceipt, PublicKey};
+use craftnet_network::PoolType;
+
+/// Seconds covered by one accumulator
+pub const COMPACTION_BUCKET_SECS: u64 = 3600;
+
+/// Hours of accumulators kept (a subscription period plus slack)
+pub const DEFAULT_COMPACTION_RETENTION_HOURS: u64 = 31 * 24;
+
+/// Spilled receipts buffered in memory before they are appended to disk
+const SPILL_WRITE_BATCH: usize = 1024;
+
+/// Everything kept of the proven receipts of one pool in one hour
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HourlyReceipts {
+    /// Payload bytes covered (what the distribution proof weights by)
+    pub bytes: u64,
+    /// Receipts folded in
+    pub receipts: u64,
+    /// Roots of the batches these receipts were compressed into
+    pub batch_roots: Vec<[u8; 32]>,
+}
+
+/// Per-(pool, hour) accumulators of proven receipts
+#[derive(Debug, Clone)]
+pub struct ReceiptCompactor {
+    hours: HashMap<(PublicKey, PoolType), BTreeMap<u64, HourlyReceipts>>,
+    retention_hours: u64,
+    /// Receipts folded since startup
+    folded: u64,
+}
+
+impl Default for ReceiptCompactor {
+    fn default() -> Self {
+        Self::new(DEFAULT_COMPACTION_RETENTION_HOURS)
+    }
+}
+
+impl ReceiptCompactor {
+    pub fn new(retention_hours: u64) -> Self {
+        Self { hours: HashMap::new(), retention_hours, folded: 0 }
+    }
+
+    /// Hour bucket of a unix timestamp
+    pub fn hour_of(timestamp: u64) -> u64 {
+        timestamp / COMPACTION_BUCKET_SECS
+    }
+
+    /// Fold a compressed batch of `pool_key` (whose Merkle root is `root`)
+    /// into the accumulators of the hours its receipts fall in
+    pub fn fold(&mut self, pool_key: (PublicKey, PoolType), batch: &[ForwardReceipt], root: [u8; 32]) {
+        let hours = self.hours.entry(pool_key).or_default();
+        for receipt in batch {
+            let hour = hours.entry(Self::hour_of(receipt.timestamp)).or_default();
+            hour.bytes += receipt.payload_size as u64;
+            hour.receipts += 1;
+            if hour.batch_roots.last() != Some(&root) {
+                hour.batch_roots.push(root);
+            }
+        }
+        self.folded += batch.len() as u64;
+    }
+
+    /// Restore a persisted accumulator
+    pub fn insert(&mut self, pool_key: (PublicKey, PoolType), hour: u64, receipts: HourlyReceipts) {
+        self.hours.entry(pool_key).or_default().insert(hour, receipts);
+    }
+
+    /// Drop accumulators older than the retention window
+    pub fn prune(&mut self, now_secs: u64) {
+        let cutoff = Self::hour_of(now_secs).saturating_sub(self.retention_hours);
+        for hours in self.hours.values_mut() {
+            hours.retain(|hour, _| *hour >= cutoff);
+        }
+        self.hours.retain(|_, hours| !hours.is_empty());
+    }
+
+    /// Accumulators of a pool, oldest hour first
+    pub fn hours_for(&self, pool: &PublicKey, pool_type: PoolType) -> Vec<(u64, &HourlyReceipts)> {
+        self.hours
+            .get(&(*pool, pool_type))
+            .map(|hours| hours.iter().map(|(hour, acc)| (*hour, acc)).collect())
+            .unwrap_or_default()
+    }
+
+    /// Proven bytes of a pool since `from_hour` — our distribution entry
+    pub fn pool_bytes(&self, pool: &PublicKey, pool_type: PoolType, from_hour: u64) -> u64 {
+        self.hours_for(pool, pool_type)
+            .into_iter()
+            .filter(|(hour, _)| *hour >= from_hour)
+            .map(|(_, acc)| acc.bytes)
+            .sum()
+    }
+
+    /// All accumulators (for persistence)
+    pub fn iter(&self) -> impl Iterator<Item = ((PublicKey, PoolType), u64, &HourlyReceipts)> {
+        self.hours
+            .iter()
+            .flat_map(|(pool_key, hours)| hours.iter().map(move |(hour, acc)| (*pool_key, *hour, acc)))
+    }
+
+    /// Number of (pool, hour) accumulators
+    pub fn len(&self) -> usize {
+        self.hours.values().map(|hours| hours.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hours.is_empty()
+    }
+
+    /// Receipts folded since startup
+    pub fn folded(&self) -> u64 {
+        self.folded
+    }
+}
+
+/// File name of a pool's spill file: "hex_pubkey-free.jsonl"
+fn spill_file_name(pool: &PublicKey, pool_type: PoolType) -> String {
+    let kind = match pool_type {
+        PoolType::Subscribed => "subscribed",
+        PoolType::Free => "free",
+    };
+    format!("{}-{}.jsonl", hex::encode(pool), kind)
+}
+
+/// Pool key of a spill file name
+fn parse_spill_file_name(name: &str) -> Option<(PublicKey, PoolType)> {
+    let stem = name.strip_suffix(".jsonl")?;
+    let (hex_pool, kind) = stem.split_once('-')?;
+    let pool_type = match kind {
+        "subscribed" => PoolType::Subscribed,
+        "free" => PoolType::Free,
+        _ => return None,
+    };
+    let pool: PublicKey = hex::decode(hex_pool).ok()?.try_into().ok()?;
+    Some((pool, pool_type))
+}
+
+/// On-disk overflow of the proof queue, one JSON-lines file per pool
+#[derive(Debug)]
+pub struct ReceiptSpill {
+    dir: PathBuf,
+    /// Receipts on disk per pool
+    on_disk: HashMap<(PublicKey, PoolType), usize>,
+    /// Receipts spilled but not yet appended to disk
+    unwritten: HashMap<(PublicKey, PoolType), Vec<ForwardReceipt>>,
+}
+
+impl ReceiptSpill {
+    /// Open the spill directory, counting what an earlier run left in it
+    pub fn open(dir: PathBuf) -> Self {
+        let mut on_disk = HashMap::new();
+        if let Ok(entries) = std::fs::read_dir(&dir) {
+            for entry in entries.flatten() {
+                let name = entry.file_name();
+                let Some(pool_key) = name.to_str().and_then(parse_spill_file_name) else { continue };
+                let count = std::fs::File::open(entry.path())
+                    .map(|f| std::io::BufReader::new(f).lines().map_while(|l| l.ok()).filter(|l| !l.is_empty()).count())
+                    .unwrap_or(0);
+                if count > 0 {
+                    on_disk.insert(pool_key, count);
+                }
+            }
+        }
+        Self { dir, on_disk, unwritten: HashMap::new() }
+    }
+
+    fn path(&self, pool_key: &(PublicKey, PoolType)) -> PathBuf {
+        self.dir.join(spill_file_name(&pool_key.0, pool_key.1))
+    }
+
+    /// Set a receipt aside for later. Writes are batched; call
+    /// [`Self::flush`] to force them out.
+    pub fn push(&mut self, pool_key: (PublicKey, PoolType), receipt: ForwardReceipt) -> std::io::Result<()> {
+        let unwritten = self.unwritten.entry(pool_key).or_default();
+        unwritten.push(receipt);
+        if unwritten.len() >= SPILL_WRITE_BATCH {
+            self.flush_pool(&pool_key)?;
+        }
+        Ok(())
+    }
+
+    fn flush_pool(&mut self, pool_key: &(PublicKey, PoolType)) -> std::io::Result<()> {
+        let Some(receipts) = self.unwritten.remove(pool_key) else { return Ok(()) };
+        if receipts.is_empty() {
+            return Ok(());
+        }
+        let path = self.path(pool_key);
+        let written = append_receipts(&path, &receipts);
+        if let Err(e) = written {
+            self.unwritten.insert(*pool_key, receipts);
+            return Err(e);
+        }
+        *self.on_disk.entry(*pool_key).or_default() += receipts.len();
+        Ok(())
+    }
+
+    /// Append every buffered receipt to disk
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        let pools: Vec<_> = self.unwritten.keys().copied().collect();
+        for pool_key in pools {
+            self.flush_pool(&pool_key)?;
+        }
+        Ok(())
+    }
+
+    /// Take up to `max` of a pool's spilled receipts, oldest first
+    pub fn take(&mut self, pool_key: &(PublicKey, PoolType), max: usize) -> std::io::Result<Vec<ForwardReceipt>> {
+        if max == 0 || self.spilled(pool_key) == 0 {
+            return Ok(Vec::new());
+        }
+        self.flush_pool(pool_key)?;
+        let path = self.path(pool_key);
+        let file = std::fs::File::open(&path)?;
+        let mut taken = Vec::new();
+        let mut rest = Vec::new();
+        for line in std::io::BufReader::new(file).lines().map_while(|l| l.ok()) {
+            if taken.len() < max {
+                if let Ok(receipt) = serde_json::from_str::<ForwardReceipt>(&line) {
+                    taken.push(receipt);
+                }
+            } else if !line.is_empty() {
+                rest.push(line);
+            }
+        }
+
+        if rest.is_empty() {
+            std::fs::remove_file(&path)?;
+            self.on_disk.remove(pool_key);
+        } else {
+            let tmp_path = path.with_extension("jsonl.tmp");
+            let mut contents = rest.join("\n");
+            contents.push('\n');
+            std::fs::write(&tmp_path, contents)?;
+            std::fs::rename(&tmp_path, &path)?;
+            self.on_disk.insert(*pool_key, rest.len());
+        }
+        Ok(taken)
+    }
+
+    /// Receipts spilled for a pool
+    pub fn spilled(&self, pool_key: &(PublicKey, PoolType)) -> usize {
+        self.on_disk.get(pool_key).copied().unwrap_or(0)
+            + self.unwritten.get(pool_key).map_or(0, |v| v.len())
+    }
+
+    /// Receipts spilled across all pools
+    pub fn total(&self) -> usize {
+        self.on_disk.values().sum::<usize>() + self.unwritten.values().map(|v| v.len()).sum::<usize>()
+    }
+
+    /// Pools with spilled receipts
+    pub fn pools(&self) -> Vec<(PublicKey, PoolType)> {
+        let mut pools: Vec<_> = self.on_disk.keys().chain(self.unwritten.keys()).copied().collect();
+        pools.sort_by_key(|(pool, pool_type)| (*pool, matches!(pool_type, PoolType::Free)));
+        pools.dedup();
+        pools
+    }
+}
+
+fn append_receipts(path: &Path, receipts: &[ForwardReceipt]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    for receipt in receipts {
+        if let Ok(json) = serde_json::to_string(receipt) {
+            writeln!(file, "{}", json)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn receipt(shard: u8, payload_size: u32, timestamp: u64) -> ForwardReceipt {
+        ForwardReceipt {
+            shard_id: [shard; 32],
+            sender_pubkey: [1; 32],
+            receiver_pubkey: [2; 32],
+            pool_pubkey: [3; 32],
+            payload_size,
+            timestamp,
+            signature: [0; 64],
+        }
+    }
+
+    #[test]
+    fn test_fold_splits_batches_by_hour() {
+        let mut compactor = ReceiptCompactor::default();
+        let pool_key = ([3; 32], PoolType::Subscribed);
+        let base = 1_700_000_000 / COMPACTION_BUCKET_SECS * COMPACTION_BUCKET_SECS;
+        compactor.fold(pool_key, &[receipt(1, 100, base), receipt(2, 50, base + 10)], [7; 32]);
+        compactor.fold(pool_key, &[receipt(3, 25, base + 20), receipt(4, 1000, base + 3600)], [8; 32]);
+
+        let hours = compactor.hours_for(&[3; 32], PoolType::Subscribed);
+        assert_eq!(hours.len(), 2);
+        assert_eq!(hours[0].1.bytes, 175);
+        assert_eq!(hours[0].1.receipts, 3);
+        assert_eq!(hours[0].1.batch_roots, vec![[7; 32], [8; 32]]);
+        assert_eq!(hours[1].1.bytes, 1000);
+        assert_eq!(hours[1].1.batch_roots, vec![[8; 32]]);
+        assert_eq!(compactor.pool_bytes(&[3; 32], PoolType::Subscribed, 0), 1175);
+        assert_eq!(compactor.pool_bytes(&[3; 32], PoolType::Free, 0), 0);
+        assert_eq!(compactor.folded(), 4);
+    }
+
+    #[test]
+    fn test_prune_drops_old_hours() {
+        let mut compactor = ReceiptCompactor::new(2);
+        let pool_key = ([3; 32], PoolType::Free);
+        compactor.fold(pool_key, &[receipt(1, 1, 0)], [0; 32]);
+        compactor.fold(pool_key, &[receipt(2, 1, 10 * COMPACTION_BUCKET_SECS)], [0; 32]);
+        compactor.prune(11 * COMPACTION_BUCKET_SECS);
+        assert_eq!(compactor.len(), 1);
+    }
+
+    #[test]
+    fn test_spill_round_trip() {
+        let dir = std::env::temp_dir().join(format!("craftnet-test-receipt-spill-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let pool_key = ([3; 32], PoolType::Subscribed);
+
+        let mut spill = ReceiptSpill::open(dir.clone());
+        for i in 0..5 {
+            spill.push(pool_key, receipt(i, 10, i as u64)).unwrap();
+        }
+        spill.flush().unwrap();
+        assert_eq!(spill.spilled(&pool_key), 5);
+
+        // A restart finds what was spilled
+        let mut spill = ReceiptSpill::open(dir.clone());
+        assert_eq!(spill.pools(), vec![pool_key]);
+        let first = spill.take(&pool_key, 3).unwrap();
+        assert_eq!(first.iter().map(|r| r.shard_id[0]).collect::<Vec<_>>(), vec![0, 1, 2]);
+        assert_eq!(spill.spilled(&pool_key), 2);
+        let rest = spill.take(&pool_key, 10).unwrap();
+        assert_eq!(rest.len(), 2);
+        assert_eq!(spill.total(), 0);
+        assert!(!dir.join(spill_file_name(&pool_key.0, pool_key.1)).exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_spill_file_name_round_trip() {
+        let name = spill_file_name(&[0xab; 32], PoolType::Free);
+        assert_eq!(parse_spill_file_name(&name), Some(([0xab; 32], PoolType::Free)));
+        assert_eq!(parse_spill_file_name("notes.txt"), None);
+    }
+}
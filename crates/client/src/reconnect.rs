@@ -0,0 +1,208 @@
+//! Always-on reconnect supervision
+//!
+//! A client that was ready (connected, exit selected, gateway stream open)
+//! can lose readiness when its bootstrap peers, gateway or exit disappear.
+//! [`ReconnectSupervisor`] notices the loss, waits a short grace period so
+//! a brief flap doesn't trigger anything, then asks the node to reconnect —
+//! re-bootstrap, re-select the exit, reopen gateway streams — with jittered
+//! exponential backoff until readiness is back. Every step is reported as a
+//! [`ReconnectEvent`] so UIs can show "reconnecting (attempt 3)".
+
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// Backoff parameters of the reconnect loop
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReconnectPolicy {
+    /// How long readiness must be lost before the first attempt
+    pub grace: Duration,
+    /// Delay after the first attempt
+    pub initial_backoff: Duration,
+    /// Upper bound of the delay between attempts
+    pub max_backoff: Duration,
+    /// Growth factor of the delay per attempt
+    pub multiplier: f64,
+    /// Random spread of each delay (0.2 = ±20%)
+    pub jitter: f64,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            grace: Duration::from_secs(3),
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            multiplier: 2.0,
+            jitter: 0.2,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Delay after attempt `attempt` (1-based) before jitter
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exp = self.multiplier.powi(attempt.saturating_sub(1).min(32) as i32);
+        let secs = self.initial_backoff.as_secs_f64() * exp;
+        Duration::from_secs_f64(secs.min(self.max_backoff.as_secs_f64()))
+    }
+
+    /// Backoff spread by `unit` in [0, 1) across ±`jitter`
+    fn jittered(&self, attempt: u32, unit: f64) -> Duration {
+        let factor = 1.0 + self.jitter * (2.0 * unit - 1.0);
+        self.backoff(attempt).mul_f64(factor.max(0.0))
+    }
+}
+
+/// Reconnect progress reported to IPC subscribers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum ReconnectEvent {
+    /// Readiness was lost; reconnecting after the grace period
+    Lost,
+    /// Reconnect attempt `attempt` started; the next one follows in `retry_in_ms`
+    Reconnecting { attempt: u32, retry_in_ms: u64 },
+    /// Ready again after `attempts` attempts and `downtime_ms` without service
+    Reconnected { attempts: u32, downtime_ms: u64 },
+}
+
+/// Tracks readiness and schedules reconnect attempts
+#[derive(Debug, Clone)]
+pub struct ReconnectSupervisor {
+    policy: ReconnectPolicy,
+    /// Only a node that was ready once is supervised
+    was_ready: bool,
+    lost_at: Option<Instant>,
+    attempt: u32,
+    next_attempt: Option<Instant>,
+}
+
+impl ReconnectSupervisor {
+    pub fn new(policy: ReconnectPolicy) -> Self {
+        Self {
+            policy,
+            was_ready: false,
+            lost_at: None,
+            attempt: 0,
+            next_attempt: None,
+        }
+    }
+
+    /// Current attempt number while reconnecting
+    pub fn attempt(&self) -> Option<u32> {
+        self.lost_at.map(|_| self.attempt)
+    }
+
+    /// Forget the readiness history (e.g. after a deliberate stop)
+    pub fn reset(&mut self) {
+        self.was_ready = false;
+        self.lost_at = None;
+        self.attempt = 0;
+        self.next_attempt = None;
+    }
+
+    /// Feed the current readiness. A `Reconnecting` event means the caller
+    /// should run a reconnect attempt now.
+    pub fn update(&mut self, ready: bool, now: Instant) -> Option<ReconnectEvent> {
+        if ready {
+            self.was_ready = true;
+            let lost_at = self.lost_at.take()?;
+            let attempts = std::mem::take(&mut self.attempt);
+            self.next_attempt = None;
+            return Some(ReconnectEvent::Reconnected {
+                attempts,
+                downtime_ms: now.duration_since(lost_at).as_millis() as u64,
+            });
+        }
+        if !self.was_ready {
+            return None;
+        }
+        if self.lost_at.is_none() {
+            self.lost_at = Some(now);
+            self.next_attempt = Some(now + self.policy.grace);
+            return Some(ReconnectEvent::Lost);
+        }
+        if self.next_attempt.is_some_and(|at| now < at) {
+            return None;
+        }
+        self.attempt += 1;
+        let delay = self.policy.jittered(self.attempt, rand::random::<f64>());
+        self.next_attempt = Some(now + delay);
+        Some(ReconnectEvent::Reconnecting {
+            attempt: self.attempt,
+            retry_in_ms: delay.as_millis() as u64,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_grows_and_caps() {
+        let policy = ReconnectPolicy::default();
+        assert_eq!(policy.backoff(1), Duration::from_secs(1));
+        assert_eq!(policy.backoff(2), Duration::from_secs(2));
+        assert_eq!(policy.backoff(4), Duration::from_secs(8));
+        assert_eq!(policy.backoff(20), Duration::from_secs(60));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(60));
+
+        assert_eq!(policy.jittered(3, 0.5), Duration::from_secs(4));
+        let low = policy.jittered(3, 0.0);
+        assert!(low >= Duration::from_millis(3199) && low <= Duration::from_millis(3201));
+    }
+
+    #[test]
+    fn test_not_supervised_before_first_ready() {
+        let mut sup = ReconnectSupervisor::new(ReconnectPolicy::default());
+        assert!(sup.update(false, Instant::now()).is_none());
+        assert_eq!(sup.attempt(), None);
+    }
+
+    #[test]
+    fn test_lost_grace_attempts_and_recovery() {
+        let policy = ReconnectPolicy { jitter: 0.0, ..Default::default() };
+        let mut sup = ReconnectSupervisor::new(policy);
+        let t0 = Instant::now();
+        assert!(sup.update(true, t0).is_none());
+
+        assert_eq!(sup.update(false, t0), Some(ReconnectEvent::Lost));
+        // Within the grace period
+        assert!(sup.update(false, t0 + Duration::from_secs(1)).is_none());
+
+        let t1 = t0 + Duration::from_secs(3);
+        assert_eq!(
+            sup.update(false, t1),
+            Some(ReconnectEvent::Reconnecting { attempt: 1, retry_in_ms: 1000 }),
+        );
+        assert!(sup.update(false, t1 + Duration::from_millis(500)).is_none());
+        assert_eq!(
+            sup.update(false, t1 + Duration::from_secs(1)),
+            Some(ReconnectEvent::Reconnecting { attempt: 2, retry_in_ms: 2000 }),
+        );
+        assert_eq!(sup.attempt(), Some(2));
+
+        assert_eq!(
+            sup.update(true, t0 + Duration::from_secs(5)),
+            Some(ReconnectEvent::Reconnected { attempts: 2, downtime_ms: 5000 }),
+        );
+        assert_eq!(sup.attempt(), None);
+        assert!(sup.update(true, t0 + Duration::from_secs(6)).is_none());
+    }
+
+    #[test]
+    fn test_reset_stops_supervision() {
+        let mut sup = ReconnectSupervisor::new(ReconnectPolicy::default());
+        let now = Instant::now();
+        sup.update(true, now);
+        sup.reset();
+        assert!(sup.update(false, now).is_none());
+    }
+
+    #[test]
+    fn test_event_serialization() {
+        let json = serde_json::to_value(ReconnectEvent::Reconnecting { attempt: 3, retry_in_ms: 4000 }).unwrap();
+        assert_eq!(json, serde_json::json!({"state": "reconnecting", "attempt": 3, "retry_in_ms": 4000}));
+    }
+}
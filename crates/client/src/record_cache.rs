@@ -0,0 +1,297 @@
+//! Persistent exit/relay record cache
+//!
+//! Without a cache every start waits on live DHT queries before an exit can
+//! be picked, which is slow on mobile cold starts. The node keeps the signed
+//! exit/relay records it accepted in `{data_dir}/record-cache-{peer_id}.json`
+//! and replays them on start:
+//!
+//! - **Fresh** records (still within their TTL) are used exactly like
+//!   records fetched from the DHT.
+//! - **Stale** records (past their TTL but younger than
+//!   `RECORD_CACHE_MAX_STALE`) are used right away while a background DHT
+//!   lookup revalidates them; an exit that never re-announces drops out
+//!   after one TTL like any other unseen exit.
+//!
+//! Records stay in their signed envelope, so the signature and peer binding
+//! are checked again on load.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use craftnet_core::{system_clock, SharedClock};
+use craftnet_network::{RegistryKind, SignedDhtRecord};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// How long past its TTL a cached record may still be used (pending revalidation)
+pub const RECORD_CACHE_MAX_STALE: Duration = Duration::from_secs(24 * 3600);
+
+/// Most records kept; the oldest are dropped first
+pub const MAX_CACHED_RECORDS: usize = 4096;
+
+/// Whether a cached record can be used as is or needs revalidating
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CachedRecordState {
+    Fresh,
+    Stale,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedRecord {
+    key: String,
+    record: SignedDhtRecord,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RecordCacheFile {
+    records: Vec<CachedRecord>,
+}
+
+/// Disk-backed cache of signed exit/relay DHT records
+pub struct RecordCache {
+    records: HashMap<String, SignedDhtRecord>,
+    path: Option<PathBuf>,
+    dirty: bool,
+    clock: SharedClock,
+}
+
+impl std::fmt::Debug for RecordCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RecordCache")
+            .field("records", &self.records)
+            .field("path", &self.path)
+            .field("dirty", &self.dirty)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for RecordCache {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+fn record_ttl(key: &str) -> Option<Duration> {
+    RegistryKind::of_record_key(key.as_bytes()).map(RegistryKind::record_ttl)
+}
+
+impl RecordCache {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Self {
+            records: HashMap::new(),
+            path,
+            dirty: false,
+            clock: system_clock(),
+        }
+    }
+
+    /// Age records by `clock` instead of the system clock
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+    }
+
+    /// Load the cache from `path` (if it exists), dropping records too old to use
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let mut cache = Self::new(path);
+        let Some(ref path) = cache.path else { return cache };
+        if !path.exists() {
+            return cache;
+        }
+
+        let file: RecordCacheFile = match std::fs::read(path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| serde_json::from_slice(&data).map_err(|e| e.to_string()))
+        {
+            Ok(file) => file,
+            Err(e) => {
+                warn!("Failed to load record cache from {}: {}", path.display(), e);
+                return cache;
+            }
+        };
+        for entry in file.records {
+            if record_ttl(&entry.key).is_some() {
+                cache.records.insert(entry.key, entry.record);
+            }
+        }
+        cache.prune_at(cache.clock.unix_now());
+        cache.dirty = false;
+        cache
+    }
+
+    /// Keep a validated exit/relay record, replacing an older one.
+    ///
+    /// Returns whether the cache changed.
+    pub fn insert(&mut self, key: &[u8], record: SignedDhtRecord) -> bool {
+        let Ok(key) = std::str::from_utf8(key) else {
+            return false;
+        };
+        if record_ttl(key).is_none() {
+            return false;
+        }
+        if self.records.get(key).is_some_and(|r| r.timestamp >= record.timestamp) {
+            return false;
+        }
+        self.records.insert(key.to_string(), record);
+        self.dirty = true;
+        if self.records.len() > MAX_CACHED_RECORDS {
+            if let Some(oldest) = self.records.iter()
+                .min_by_key(|(_, r)| r.timestamp)
+                .map(|(k, _)| k.clone())
+            {
+                self.records.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    /// Usable records with their freshness at unix time `now`
+    pub fn entries_at(&self, now: u64) -> Vec<(Vec<u8>, SignedDhtRecord, CachedRecordState)> {
+        self.records.iter()
+            .filter_map(|(key, record)| {
+                let state = Self::state(key, record, now)?;
+                Some((key.clone().into_bytes(), record.clone(), state))
+            })
+            .collect()
+    }
+
+    fn state(key: &str, record: &SignedDhtRecord, now: u64) -> Option<CachedRecordState> {
+        let ttl = record_ttl(key)?.as_secs();
+        let age = now.saturating_sub(record.timestamp);
+        if age <= ttl {
+            Some(CachedRecordState::Fresh)
+        } else if age <= ttl + RECORD_CACHE_MAX_STALE.as_secs() {
+            Some(CachedRecordState::Stale)
+        } else {
+            None
+        }
+    }
+
+    /// Drop records too old to use; returns how many were removed
+    pub fn prune_at(&mut self, now: u64) -> usize {
+        let before = self.records.len();
+        self.records.retain(|key, record| Self::state(key, record, now).is_some());
+        let removed = before - self.records.len();
+        if removed > 0 {
+            self.dirty = true;
+        }
+        removed
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Changed since the last save
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Persist the cache (write to tmp, then rename)
+    pub fn save(&mut self) {
+        let Some(ref path) = self.path else { return };
+        self.prune_at(self.clock.unix_now());
+        let file = RecordCacheFile {
+            records: self.records.iter()
+                .map(|(key, record)| CachedRecord { key: key.clone(), record: record.clone() })
+                .collect(),
+        };
+        if save_to(path, &file) {
+            self.dirty = false;
+        }
+    }
+}
+
+fn save_to(path: &Path, file: &RecordCacheFile) -> bool {
+    let json = match serde_json::to_vec(file) {
+        Ok(j) => j,
+        Err(e) => {
+            warn!("Failed to serialize record cache: {}", e);
+            return false;
+        }
+    };
+    let tmp_path = path.with_extension("json.tmp");
+    if let Err(e) = std::fs::write(&tmp_path, &json) {
+        warn!("Failed to write record cache: {}", e);
+        return false;
+    }
+    if let Err(e) = std::fs::rename(&tmp_path, path) {
+        warn!("Failed to rename record cache file: {}", e);
+        return false;
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use craftec_crypto::SigningKeypair;
+    use craftnet_network::{EXIT_RECORD_TTL, RELAY_RECORD_TTL};
+
+    fn record(key: &[u8], timestamp: u64) -> SignedDhtRecord {
+        SignedDhtRecord::sign_at(&SigningKeypair::generate(), key, "{}".to_string(), timestamp)
+    }
+
+    #[test]
+    fn test_insert_keeps_newest_registry_records() {
+        let mut cache = RecordCache::new(None);
+        let key = b"/craftnet/exits/12D3KooWExample";
+
+        assert!(cache.insert(key, record(key, 100)));
+        assert!(!cache.insert(key, record(key, 50)));
+        assert!(cache.insert(key, record(key, 200)));
+        assert!(!cache.insert(b"/craftnet/peers/abcd", record(b"/craftnet/peers/abcd", 300)));
+        assert_eq!(cache.len(), 1);
+        assert!(cache.is_dirty());
+    }
+
+    #[test]
+    fn test_fresh_stale_and_expired() {
+        let now = 1_000_000;
+        let mut cache = RecordCache::new(None);
+        let fresh = b"/craftnet/exits/fresh";
+        let stale = b"/craftnet/relays/stale";
+        let dead = b"/craftnet/exits/dead";
+        cache.insert(fresh, record(fresh, now - 10));
+        cache.insert(stale, record(stale, now - RELAY_RECORD_TTL.as_secs() - 60));
+        cache.insert(dead, record(dead, now - EXIT_RECORD_TTL.as_secs() - RECORD_CACHE_MAX_STALE.as_secs() - 1));
+
+        let mut entries = cache.entries_at(now);
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        let states: Vec<_> = entries.iter().map(|(k, _, s)| (k.clone(), *s)).collect();
+        assert_eq!(states, vec![
+            (fresh.to_vec(), CachedRecordState::Fresh),
+            (stale.to_vec(), CachedRecordState::Stale),
+        ]);
+
+        assert_eq!(cache.prune_at(now), 1);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_persisted_across_restarts() {
+        let path = std::env::temp_dir().join(format!("craftnet-record-cache-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let key = b"/craftnet/exits/persisted";
+        let now = system_clock().unix_now();
+        let signed = record(key, now);
+
+        let mut cache = RecordCache::load(Some(path.clone()));
+        assert!(cache.is_empty());
+        cache.insert(key, signed.clone());
+        cache.save();
+        assert!(!cache.is_dirty());
+
+        let reloaded = RecordCache::load(Some(path.clone()));
+        let entries = reloaded.entries_at(now);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].1, signed);
+        assert_eq!(entries[0].2, CachedRecordState::Fresh);
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
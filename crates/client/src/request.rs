@@ -3,22 +3,46 @@
 //! Builds HTTP request data, then delegates to the shared shard builder
 //! for encrypt → frame → erasure code → onion wrap.
 
+use std::sync::Arc;
+
 use craftnet_core::{
     Shard, Id, PublicKey,
     lease_set::LeaseSet,
+    PayloadCompression, QuotaToken, PAYLOAD_MODE_HTTP, PAYLOAD_MODE_HTTP_STREAM, PAYLOAD_MODE_HTTP_BATCH,
+    BatchRequest, SubRequest, MAX_BATCH_REQUESTS,
+    TAG_FLAG_ACCEPT_ZSTD, TAG_FLAG_ZSTD,
 };
 use craftec_crypto::SigningKeypair;
 
 use crate::path::{OnionPath, PathHop};
-use crate::shard_builder::build_onion_shards;
-use crate::Result;
+use crate::shard_builder::{build_onion_shards_acked, build_onion_shards_traced, ShardAckChain};
+use crate::{ClientError, Result};
 
 /// Builder for creating VPN requests
+#[derive(Clone)]
 pub struct RequestBuilder {
     method: String,
     url: String,
     headers: Vec<(String, String)>,
     body: Option<Vec<u8>>,
+    /// Ask the exit for a segmented (streamed) response
+    stream: bool,
+    /// Serialized [`BatchRequest`] of a coalesced request (see
+    /// [`coalesced`](Self::coalesced))
+    batch: Option<Vec<u8>>,
+    /// Negotiate zstd compression, recording sizes here
+    compression: Option<Arc<PayloadCompression>>,
+    /// The exit decompresses requests (`ExitInfo::zstd_requests`)
+    exit_zstd: bool,
+    /// A coalesced sub-request carried credentials
+    batch_credentials: bool,
+    /// Free-tier quota tokens paying the exit
+    quota_tokens: Vec<QuotaToken>,
+    /// Trace key every hop seals its timing for (None = untraced)
+    trace_pubkey: Option<[u8; 32]>,
+    /// Payload frozen by [`encoded`](Self::encoded): serialized request and,
+    /// if compression is negotiated and allowed, its compressed form
+    encoded: Option<(Arc<Vec<u8>>, Option<Arc<Vec<u8>>>)>,
 }
 
 impl RequestBuilder {
@@ -29,6 +53,14 @@ impl RequestBuilder {
             url: url.to_string(),
             headers: Vec::new(),
             body: None,
+            stream: false,
+            batch: None,
+            compression: None,
+            exit_zstd: false,
+            batch_credentials: false,
+            quota_tokens: Vec::new(),
+            trace_pubkey: None,
+            encoded: None,
         }
     }
 
@@ -44,8 +76,167 @@ impl RequestBuilder {
         self
     }
 
+    /// Ask the exit to stream the response back in segments
+    pub fn streaming(mut self) -> Self {
+        self.stream = true;
+        self
+    }
+
+    /// Coalesce several requests for the same exit into one request.
+    ///
+    /// Sub-request `i` gets `sub_id` `i`; only their method, URL, headers
+    /// and body are carried over. The exit answers with one response whose
+    /// body is a `BatchResponse`.
+    pub fn coalesced(requests: Vec<RequestBuilder>) -> Result<Self> {
+        if requests.is_empty() || requests.len() > MAX_BATCH_REQUESTS {
+            return Err(ClientError::RequestFailed(format!(
+                "can't coalesce {} requests (1 to {} allowed)",
+                requests.len(), MAX_BATCH_REQUESTS,
+            )));
+        }
+        let batch = BatchRequest {
+            requests: requests
+                .iter()
+                .enumerate()
+                .map(|(i, r)| SubRequest { sub_id: i as u16, data: r.serialize() })
+                .collect(),
+        };
+        let data = batch.to_bytes().map_err(|e| ClientError::RequestFailed(e.to_string()))?;
+        let mut builder = Self::new("POST", "");
+        builder.batch = Some(data);
+        builder.batch_credentials = requests.iter().any(|r| r.has_credentials());
+        Ok(builder)
+    }
+
+    /// Let the exit compress the response, and compress the request when
+    /// that makes it smaller and the exit takes it (see
+    /// [`exit_accepts_zstd`](Self::exit_accepts_zstd)); compressed sizes are
+    /// recorded in `stats`. Requests carrying credentials are never
+    /// compressed.
+    pub fn compressed(mut self, stats: Arc<PayloadCompression>) -> Self {
+        self.compression = Some(stats);
+        self
+    }
+
+    /// Whether the exit this request is built for decompresses requests
+    /// (`ExitInfo::zstd_requests`). Off by default: the request goes out
+    /// uncompressed.
+    pub fn exit_accepts_zstd(mut self, accepts: bool) -> Self {
+        self.exit_zstd = accepts;
+        self
+    }
+
+    /// Pay the exit with free-tier quota tokens. They must cover
+    /// [`payload_len`](Self::payload_len); what is left bounds the response.
+    pub fn quota_tokens(mut self, tokens: Vec<QuotaToken>) -> Self {
+        self.quota_tokens = tokens;
+        self
+    }
+
+    /// Ask every hop to seal its timing for `trace_pubkey`. Diagnostic
+    /// requests only: traced shards stand out (see `craftnet_core::trace`).
+    pub fn traced(mut self, trace_pubkey: [u8; 32]) -> Self {
+        self.trace_pubkey = Some(trace_pubkey);
+        self
+    }
+
+    /// Target URL (empty for a coalesced request)
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Serialize (and compress, if negotiated) the payload now, so clones of
+    /// the builder sent to several exits share it instead of encoding it
+    /// again. Each copy still goes out compressed only if its exit accepts
+    /// it. Later changes to the request or its compression don't affect the
+    /// frozen payload.
+    pub fn encoded(mut self) -> Self {
+        if self.encoded.is_none() {
+            let data = self.serialize();
+            let packed = self.compress(&data);
+            self.encoded = Some((Arc::new(data), packed.map(Arc::new)));
+        }
+        self
+    }
+
+    /// Size of the serialized request before compression, as the exit meters it
+    pub fn payload_len(&self) -> usize {
+        match &self.encoded {
+            Some((data, _)) => data.len(),
+            None => self.serialize().len(),
+        }
+    }
+
+    /// Whether the request carries credentials that must not be compressed
+    /// along with attacker-influenced data (CRIME)
+    fn has_credentials(&self) -> bool {
+        self.batch_credentials
+            || ["authorization", "proxy-authorization", "cookie"]
+                .iter()
+                .any(|name| self.header_value(name).is_some())
+    }
+
+    /// Case-insensitive lookup of a request header
+    fn header_value(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Payload mode the exit dispatches on
+    fn mode(&self) -> u8 {
+        if self.batch.is_some() {
+            PAYLOAD_MODE_HTTP_BATCH
+        } else if self.stream {
+            PAYLOAD_MODE_HTTP_STREAM
+        } else {
+            PAYLOAD_MODE_HTTP
+        }
+    }
+
+    /// Compressed form of the serialized request `data`, if compression is
+    /// negotiated, allowed for this request and makes it smaller
+    fn compress(&self, data: &[u8]) -> Option<Vec<u8>> {
+        let stats = self.compression.as_ref()?;
+        let precompressed = craftnet_core::is_precompressed(
+            self.header_value("content-type"),
+            self.header_value("content-encoding"),
+        );
+        if precompressed || self.has_credentials() {
+            return None;
+        }
+        let packed = craftnet_core::compress(data)?;
+        stats.record(data.len(), packed.len());
+        Some(packed)
+    }
+
+    /// Serialized request plus routing tag flags, compressed if negotiated
+    /// and the exit accepts it
+    fn payload(&self) -> (Vec<u8>, u8) {
+        if self.compression.is_none() {
+            return (self.serialize(), 0);
+        }
+        let (data, packed) = match &self.encoded {
+            Some((data, packed)) => (data.as_ref().clone(), packed.as_deref().cloned()),
+            None if self.exit_zstd => {
+                let data = self.serialize();
+                let packed = self.compress(&data);
+                (data, packed)
+            }
+            None => (self.serialize(), None),
+        };
+        match packed {
+            Some(packed) if self.exit_zstd => (packed, TAG_FLAG_ACCEPT_ZSTD | TAG_FLAG_ZSTD),
+            _ => (data, TAG_FLAG_ACCEPT_ZSTD),
+        }
+    }
+
     /// Serialize the request to bytes (HTTP format for exit)
     fn serialize(&self) -> Vec<u8> {
+        if let Some(batch) = &self.batch {
+            return batch.clone();
+        }
         let mut data = Vec::new();
 
         data.extend_from_slice(self.method.as_bytes());
@@ -106,15 +297,49 @@ impl RequestBuilder {
         response_enc_pubkey: [u8; 32],
         pool_pubkey: PublicKey,
     ) -> Result<(Id, Vec<Shard>)> {
-        build_onion_shards(
-            0x00, // HTTP mode
-            self.serialize(),
+        let mode = self.mode();
+        let (data, tag_flags) = self.payload();
+        build_onion_shards_traced(
+            mode,
+            data,
+            response_enc_pubkey,
+            keypair,
+            exit,
+            paths,
+            lease_set,
+            pool_pubkey,
+            tag_flags,
+            self.quota_tokens,
+            self.trace_pubkey,
+        )
+    }
+
+    /// Like [`build_onion_with_enc_key`](Self::build_onion_with_enc_key),
+    /// asking every relay to ack its shards back to us. Also returns each
+    /// shard's expected [`ShardAckChain`] (None for direct shards).
+    pub fn build_onion_acked(
+        self,
+        keypair: &SigningKeypair,
+        exit: &PathHop,
+        paths: &[OnionPath],
+        lease_set: &LeaseSet,
+        response_enc_pubkey: [u8; 32],
+        pool_pubkey: PublicKey,
+    ) -> Result<(Id, Vec<Shard>, Vec<Option<ShardAckChain>>)> {
+        let mode = self.mode();
+        let (data, tag_flags) = self.payload();
+        build_onion_shards_acked(
+            mode,
+            data,
             response_enc_pubkey,
             keypair,
             exit,
             paths,
             lease_set,
             pool_pubkey,
+            tag_flags,
+            self.quota_tokens,
+            self.trace_pubkey,
         )
     }
 }
@@ -182,6 +407,129 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_streaming_sets_stream_mode() {
+        let enc_keypair = craftec_crypto::EncryptionKeypair::generate();
+        let exit = PathHop {
+            peer_id: b"exit_peer".to_vec(),
+            signing_pubkey: [2u8; 32],
+            encryption_pubkey: enc_keypair.public_key_bytes(),
+        };
+        let lease_set = LeaseSet {
+            session_id: [0u8; 32],
+            leases: vec![],
+        };
+
+        let (_, shards) = RequestBuilder::new("GET", "https://example.com")
+            .streaming()
+            .build_onion(&SigningKeypair::generate(), &exit, &[], &lease_set, [0u8; 32])
+            .unwrap();
+
+        // Reassemble the direct-mode shards and check the mode the exit sees
+        let coder = craftnet_erasure::ErasureCoder::new().unwrap();
+        let mut data: Vec<Option<Vec<u8>>> = shards.iter().map(|s| Some(s.payload.to_vec())).collect();
+        let max_len = shards[0].payload.len() * craftnet_erasure::DATA_SHARDS;
+        let framed = coder.decode(&mut data, max_len).unwrap();
+        let len = u32::from_le_bytes(framed[..4].try_into().unwrap()) as usize;
+        let payload = craftnet_core::onion_crypto::decrypt_exit_payload(
+            &enc_keypair.secret_key_bytes(),
+            &framed[4..4 + len],
+        ).unwrap();
+        assert_eq!(payload.mode, PAYLOAD_MODE_HTTP_STREAM);
+    }
+
+    #[test]
+    fn test_coalesced_request() {
+        let builder = RequestBuilder::coalesced(vec![
+            RequestBuilder::new("GET", "https://a.example"),
+            RequestBuilder::new("post", "https://b.example").body(b"{}".to_vec()),
+        ])
+        .unwrap();
+        assert_eq!(builder.mode(), PAYLOAD_MODE_HTTP_BATCH);
+
+        let batch = BatchRequest::from_bytes(&builder.serialize()).unwrap();
+        assert_eq!(batch.requests.len(), 2);
+        assert_eq!(batch.requests[1].sub_id, 1);
+        assert!(batch.requests[0].data.starts_with(b"GET\nhttps://a.example\n"));
+        assert!(batch.requests[1].data.starts_with(b"POST\nhttps://b.example\n"));
+
+        assert!(RequestBuilder::coalesced(Vec::new()).is_err());
+        let too_many = (0..=MAX_BATCH_REQUESTS).map(|_| RequestBuilder::new("GET", "https://a.example")).collect();
+        assert!(RequestBuilder::coalesced(too_many).is_err());
+    }
+
+    #[test]
+    fn test_compressed_payload_and_flags() {
+        let stats = Arc::new(PayloadCompression::default());
+        let body = b"{\"key\": \"value\"}".repeat(100);
+        let builder = RequestBuilder::new("POST", "https://api.example.com")
+            .header("Content-Type", "application/json")
+            .body(body.clone())
+            .compressed(stats.clone());
+
+        // Exits that don't advertise zstd requests get them uncompressed
+        let (data, flags) = builder.payload();
+        assert_eq!(flags, TAG_FLAG_ACCEPT_ZSTD);
+        assert_eq!(data, builder.serialize());
+        assert_eq!(stats.payloads(), 0);
+
+        let builder = builder.exit_accepts_zstd(true);
+        let (data, flags) = builder.payload();
+        assert_eq!(flags, TAG_FLAG_ACCEPT_ZSTD | TAG_FLAG_ZSTD);
+        let plain = craftnet_core::decompress(&data, 1 << 20).unwrap();
+        assert_eq!(plain, builder.serialize());
+        assert_eq!(stats.payloads(), 1);
+        assert!(stats.ratio().unwrap() > 1.0);
+
+        // Already-compressed bodies are only marked as accepting compression
+        let (data, flags) = RequestBuilder::new("POST", "https://api.example.com")
+            .header("content-type", "image/png")
+            .body(body.clone())
+            .compressed(stats.clone())
+            .exit_accepts_zstd(true)
+            .payload();
+        assert_eq!(flags, TAG_FLAG_ACCEPT_ZSTD);
+        assert!(data.starts_with(b"POST\n"));
+        assert_eq!(stats.payloads(), 1);
+
+        // Nor are requests carrying credentials, coalesced or not
+        let with_cookie = RequestBuilder::new("POST", "https://api.example.com")
+            .header("Cookie", "session=secret")
+            .body(body.clone());
+        let (_, flags) = with_cookie.clone().compressed(stats.clone()).exit_accepts_zstd(true).payload();
+        assert_eq!(flags, TAG_FLAG_ACCEPT_ZSTD);
+        let batch = RequestBuilder::coalesced(vec![with_cookie, RequestBuilder::new("POST", "https://b.example").body(body)])
+            .unwrap()
+            .compressed(stats.clone())
+            .exit_accepts_zstd(true);
+        assert_eq!(batch.payload().1, TAG_FLAG_ACCEPT_ZSTD);
+        assert_eq!(stats.payloads(), 1);
+    }
+
+    #[test]
+    fn test_encoded_payload_shared_by_clones() {
+        let stats = Arc::new(PayloadCompression::default());
+        let builder = RequestBuilder::new("PUT", "https://api.example.com")
+            .body(b"payload ".repeat(100))
+            .compressed(stats.clone())
+            .encoded();
+        let raw_len = builder.serialize().len();
+        assert_eq!(stats.payloads(), 1);
+
+        // Each exit's copy reuses the compressed payload
+        let first = builder.clone().exit_accepts_zstd(true).payload();
+        let second = builder.clone().header("X-Late", "ignored").exit_accepts_zstd(true).payload();
+        assert_eq!(first, second);
+        assert_eq!(first.1, TAG_FLAG_ACCEPT_ZSTD | TAG_FLAG_ZSTD);
+        assert_eq!(builder.payload_len(), raw_len);
+        assert_eq!(stats.payloads(), 1);
+
+        // An exit that doesn't take zstd requests gets the same request plain
+        let (data, flags) = builder.clone().payload();
+        assert_eq!(flags, TAG_FLAG_ACCEPT_ZSTD);
+        assert_eq!(data.len(), raw_len);
+    }
+
     #[test]
     fn test_request_method_normalized_to_uppercase() {
         let builder = RequestBuilder::new("get", "https://example.com");
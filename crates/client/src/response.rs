@@ -1,8 +1,20 @@
-//! Tunnel response type
+//! Tunnel response types
 //!
-//! HTTP response returned through the VPN tunnel.
+//! HTTP response returned through the VPN tunnel, either fully buffered
+//! ([`TunnelResponse`]) or streamed as it arrives ([`ResponseStream`]).
 
 use std::collections::HashMap;
+#[cfg(feature = "native")]
+use std::pin::Pin;
+#[cfg(feature = "native")]
+use std::task::{Context, Poll};
+
+#[cfg(feature = "native")]
+use tokio::io::{AsyncRead, ReadBuf};
+#[cfg(feature = "native")]
+use tokio::sync::mpsc;
+
+use craftnet_core::{ErrorCode, ExitErrorFrame, HopTiming, EXIT_ERROR_HEADER};
 
 use crate::{ClientError, Result};
 
@@ -15,6 +27,12 @@ pub struct TunnelResponse {
     pub headers: HashMap<String, String>,
     /// Response body
     pub body: Vec<u8>,
+    /// Per-hop timings of a traced request, in path order (empty unless
+    /// the request set `RequestOptions::trace`)
+    pub hop_timings: Vec<HopTiming>,
+    /// Times the request moved to an alternate exit before this response
+    /// (0 = answered by the exit it was first sent to)
+    pub exit_failovers: u32,
 }
 
 impl TunnelResponse {
@@ -64,6 +82,8 @@ impl TunnelResponse {
             status,
             headers,
             body,
+            hop_timings: Vec::new(),
+            exit_failovers: 0,
         })
     }
 
@@ -71,6 +91,112 @@ impl TunnelResponse {
     pub fn text(&self) -> String {
         String::from_utf8_lossy(&self.body).to_string()
     }
+
+    /// The error reported by an exit's error frame (see
+    /// [`ExitErrorFrame`]); None for an ordinary response
+    pub fn exit_error(&self) -> Option<ClientError> {
+        self.headers.keys().find(|k| k.eq_ignore_ascii_case(EXIT_ERROR_HEADER))?;
+        let Ok(frame) = ExitErrorFrame::from_bytes(&self.body) else {
+            return Some(ClientError::InvalidResponse);
+        };
+        Some(match (frame.code, frame.limit) {
+            (ErrorCode::RequestTooLarge, Some(limit)) => ClientError::RequestTooLarge { limit },
+            (ErrorCode::ResponseTooLarge, Some(limit)) => ClientError::ResponseTooLarge { limit },
+            _ => ClientError::RequestFailed(frame.message),
+        })
+    }
+}
+
+/// HTTP response whose body is delivered incrementally.
+///
+/// Returned by `CraftNetNode::fetch_stream` once the response head arrives.
+/// Body chunks keep flowing only while the node is being polled (`run()`,
+/// `poll_once()` or the daemon event loop). The channel is bounded: while it
+/// is full the node holds decoded segments back instead of delivering them.
+#[cfg(feature = "native")]
+#[derive(Debug)]
+pub struct ResponseStream {
+    /// HTTP status code
+    pub status: u16,
+    /// Response headers
+    pub headers: HashMap<String, String>,
+    chunks: mpsc::Receiver<Result<Vec<u8>>>,
+    /// Partially read chunk (AsyncRead)
+    buffered: Vec<u8>,
+    buffered_pos: usize,
+}
+
+#[cfg(feature = "native")]
+impl ResponseStream {
+    pub(crate) fn new(
+        status: u16,
+        headers: HashMap<String, String>,
+        chunks: mpsc::Receiver<Result<Vec<u8>>>,
+    ) -> Self {
+        Self {
+            status,
+            headers,
+            chunks,
+            buffered: Vec::new(),
+            buffered_pos: 0,
+        }
+    }
+
+    /// Next body chunk, or `None` at the end of the body
+    pub async fn chunk(&mut self) -> Result<Option<Vec<u8>>> {
+        if self.buffered_pos < self.buffered.len() {
+            let rest = self.buffered.split_off(self.buffered_pos);
+            self.buffered.clear();
+            self.buffered_pos = 0;
+            return Ok(Some(rest));
+        }
+        self.chunks.recv().await.transpose()
+    }
+
+    /// Read the rest of the body into a buffered [`TunnelResponse`]
+    pub async fn collect(mut self) -> Result<TunnelResponse> {
+        let mut body = Vec::new();
+        while let Some(chunk) = self.chunk().await? {
+            body.extend_from_slice(&chunk);
+        }
+        Ok(TunnelResponse {
+            status: self.status,
+            headers: self.headers,
+            body,
+            hop_timings: Vec::new(),
+            exit_failovers: 0,
+        })
+    }
+}
+
+#[cfg(feature = "native")]
+impl AsyncRead for ResponseStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = &mut *self;
+        while this.buffered_pos >= this.buffered.len() {
+            match this.chunks.poll_recv(cx) {
+                Poll::Ready(Some(Ok(chunk))) => {
+                    this.buffered = chunk;
+                    this.buffered_pos = 0;
+                }
+                Poll::Ready(Some(Err(e))) => {
+                    return Poll::Ready(Err(std::io::Error::other(e.to_string())));
+                }
+                // End of body
+                Poll::Ready(None) => return Poll::Ready(Ok(())),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        let available = &this.buffered[this.buffered_pos..];
+        let n = available.len().min(buf.remaining());
+        buf.put_slice(&available[..n]);
+        this.buffered_pos += n;
+        Poll::Ready(Ok(()))
+    }
 }
 
 #[cfg(test)]
@@ -96,4 +222,68 @@ mod tests {
         assert!(response.headers.is_empty());
         assert!(response.body.is_empty());
     }
+
+    #[test]
+    fn test_exit_error_frame() {
+        let frame = ExitErrorFrame::request_too_large(4096, "too big");
+        let response = TunnelResponse {
+            status: frame.status(),
+            headers: HashMap::from([(EXIT_ERROR_HEADER.to_string(), "REQUEST_TOO_LARGE".to_string())]),
+            body: frame.to_bytes(),
+            hop_timings: Vec::new(),
+            exit_failovers: 0,
+        };
+        assert!(matches!(response.exit_error(), Some(ClientError::RequestTooLarge { limit: 4096 })));
+
+        // An origin's 413 is an ordinary response
+        let data = b"413\n0\n0\n";
+        assert!(TunnelResponse::from_bytes(data).unwrap().exit_error().is_none());
+    }
+
+    #[cfg(feature = "native")]
+    #[tokio::test]
+    async fn test_response_stream_chunks_and_collect() {
+        let (tx, rx) = mpsc::channel(4);
+        let stream = ResponseStream::new(200, HashMap::new(), rx);
+        tx.send(Ok(b"Hel".to_vec())).await.unwrap();
+        tx.send(Ok(b"lo".to_vec())).await.unwrap();
+        drop(tx);
+
+        let response = stream.collect().await.unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.text(), "Hello");
+    }
+
+    #[cfg(feature = "native")]
+    #[tokio::test]
+    async fn test_response_stream_async_read() {
+        use tokio::io::AsyncReadExt;
+
+        let (tx, rx) = mpsc::channel(4);
+        let mut stream = ResponseStream::new(200, HashMap::new(), rx);
+        tx.send(Ok(vec![1u8; 10])).await.unwrap();
+        tx.send(Ok(vec![2u8; 5])).await.unwrap();
+        drop(tx);
+
+        let mut small = [0u8; 4];
+        stream.read_exact(&mut small).await.unwrap();
+        assert_eq!(small, [1u8; 4]);
+
+        let mut rest = Vec::new();
+        stream.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest.len(), 11);
+        assert_eq!(&rest[6..], &[2u8; 5]);
+    }
+
+    #[cfg(feature = "native")]
+    #[tokio::test]
+    async fn test_response_stream_error_surfaces() {
+        let (tx, rx) = mpsc::channel(4);
+        let mut stream = ResponseStream::new(200, HashMap::new(), rx);
+        tx.send(Ok(b"partial".to_vec())).await.unwrap();
+        tx.send(Err(ClientError::RequestFailed("upstream reset".to_string()))).await.unwrap();
+
+        assert_eq!(stream.chunk().await.unwrap().unwrap(), b"partial");
+        assert!(stream.chunk().await.is_err());
+    }
 }
@@ -0,0 +1,257 @@
+//! Adaptive request timeouts and retry budget
+//!
+//! A fixed timeout is either too short for a slow circuit carrying a large
+//! upload or far too long for a fast one that silently lost a shard.
+//! [`RttEstimator`] keeps a smoothed round-trip time and its variance per
+//! circuit (RFC 6298 style) plus the observed transfer rate, and derives a
+//! timeout from the circuit's latency and the request's payload size.
+//!
+//! [`RetryPolicy`] decides whether a failed request is sent again: at most
+//! `max_retries` more times, each over a different circuit, and only for
+//! idempotent methods unless the caller opts in. When the failures point at
+//! the exit rather than the circuit (the exit is unreachable, or fresh
+//! circuits to it kept failing), a retry may go to an alternate exit
+//! instead, at most `max_exit_failovers` times per request.
+
+use std::time::Duration;
+
+use crate::ClientError;
+
+/// Default retries after the first attempt
+pub const DEFAULT_MAX_RETRIES: u32 = 2;
+
+/// Default alternate exits one request may fail over to
+pub const DEFAULT_MAX_EXIT_FAILOVERS: u32 = 1;
+
+/// Failed attempts (each over a different circuit) after which an exit is
+/// suspected rather than the circuits to it
+pub const EXIT_SUSPECT_FAILURES: u32 = 2;
+
+/// Default lower bound of an adaptive timeout
+pub const DEFAULT_MIN_REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Smoothing factor of the RTT average (1/8)
+const ALPHA: f64 = 0.125;
+/// Smoothing factor of the RTT variance (1/4)
+const BETA: f64 = 0.25;
+/// Variance multiplier in the timeout (as in TCP's RTO)
+const K: f64 = 4.0;
+
+/// Smoothed round-trip time and transfer rate of one circuit
+#[derive(Debug, Clone, Default)]
+pub struct RttEstimator {
+    /// Smoothed RTT (seconds)
+    srtt: Option<f64>,
+    /// RTT variance (seconds)
+    rttvar: f64,
+    /// Smoothed transfer rate (bytes/second)
+    rate: Option<f64>,
+    samples: u32,
+}
+
+impl RttEstimator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a completed request: time from send to full response, and the
+    /// request + response bytes moved over the circuit in that time
+    pub fn observe(&mut self, elapsed: Duration, bytes: usize) {
+        let rtt = elapsed.as_secs_f64();
+        match self.srtt {
+            None => {
+                self.srtt = Some(rtt);
+                self.rttvar = rtt / 2.0;
+            }
+            Some(srtt) => {
+                self.rttvar = (1.0 - BETA) * self.rttvar + BETA * (srtt - rtt).abs();
+                self.srtt = Some((1.0 - ALPHA) * srtt + ALPHA * rtt);
+            }
+        }
+        if rtt > 0.0 && bytes > 0 {
+            let rate = bytes as f64 / rtt;
+            self.rate = Some(match self.rate {
+                None => rate,
+                Some(r) => (1.0 - ALPHA) * r + ALPHA * rate,
+            });
+        }
+        self.samples = self.samples.saturating_add(1);
+    }
+
+    /// Completed requests observed so far
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    /// Smoothed RTT, if anything was observed
+    pub fn srtt(&self) -> Option<Duration> {
+        self.srtt.map(Duration::from_secs_f64)
+    }
+
+    /// Timeout for a request sending `payload_bytes`, clamped to
+    /// `min..=max`. Without samples there is nothing to adapt to and `max`
+    /// is returned.
+    pub fn timeout(&self, payload_bytes: usize, min: Duration, max: Duration) -> Duration {
+        let Some(srtt) = self.srtt else {
+            return max;
+        };
+        let transfer = self.rate.map_or(0.0, |rate| payload_bytes as f64 / rate);
+        let secs = srtt + K * self.rttvar + transfer;
+        Duration::from_secs_f64(secs).clamp(min, max.max(min))
+    }
+}
+
+/// When a failed request may be sent again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt (each over a different circuit)
+    pub max_retries: u32,
+    /// Also retry methods that are not idempotent (POST, PATCH). The exit
+    /// may have executed the first attempt, so only set this when repeating
+    /// the request is harmless.
+    pub retry_non_idempotent: bool,
+    /// Retries that may go to an alternate exit (each counts as a retry)
+    pub max_exit_failovers: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: DEFAULT_MAX_RETRIES,
+            retry_non_idempotent: false,
+            max_exit_failovers: DEFAULT_MAX_EXIT_FAILOVERS,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Never retry
+    pub fn none() -> Self {
+        Self { max_retries: 0, retry_non_idempotent: false, max_exit_failovers: 0 }
+    }
+
+    /// Whether attempt number `attempt` (0 = first) that failed with `error`
+    /// may be retried
+    pub fn should_retry(&self, method: &str, attempt: u32, error: &ClientError) -> bool {
+        attempt < self.max_retries
+            && is_retryable(error)
+            && (self.retry_non_idempotent || is_idempotent(method))
+    }
+
+    /// Whether attempt number `attempt` that failed with `error`, after
+    /// `exit_failures` failed attempts through the same exit (this one
+    /// included) and `failovers` earlier failovers, should be retried
+    /// through an alternate exit
+    pub fn should_fail_over(&self, method: &str, attempt: u32, exit_failures: u32, failovers: u32, error: &ClientError) -> bool {
+        let exit_suspect = is_exit_failure(error) || (is_retryable(error) && exit_failures >= EXIT_SUSPECT_FAILURES);
+        attempt < self.max_retries
+            && failovers < self.max_exit_failovers
+            && exit_suspect
+            && (self.retry_non_idempotent || is_idempotent(method))
+    }
+}
+
+/// Methods that can be repeated without changing the outcome (RFC 9110 §9.2.2)
+pub fn is_idempotent(method: &str) -> bool {
+    ["GET", "HEAD", "OPTIONS", "TRACE", "PUT", "DELETE"]
+        .iter()
+        .any(|m| m.eq_ignore_ascii_case(method))
+}
+
+/// Failures a different circuit may not hit: timeouts and responses that
+/// could not be reconstructed. Errors from the request itself (bad input,
+/// no exit, no credits) would fail the same way again.
+pub fn is_retryable(error: &ClientError) -> bool {
+    matches!(
+        error,
+        ClientError::Timeout | ClientError::InvalidResponse | ClientError::ErasureError(_)
+    )
+}
+
+/// Failures of the exit itself, which no circuit to it would avoid
+pub fn is_exit_failure(error: &ClientError) -> bool {
+    matches!(error, ClientError::ExitUnreachable(_))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIN: Duration = Duration::from_millis(500);
+    const MAX: Duration = Duration::from_secs(30);
+
+    #[test]
+    fn test_timeout_without_samples_is_max() {
+        let est = RttEstimator::new();
+        assert_eq!(est.timeout(1024, MIN, MAX), MAX);
+        assert_eq!(est.srtt(), None);
+    }
+
+    #[test]
+    fn test_timeout_adapts_to_rtt_and_size() {
+        let mut est = RttEstimator::new();
+        for _ in 0..20 {
+            est.observe(Duration::from_millis(200), 100_000); // 500 KB/s
+        }
+        assert_eq!(est.samples(), 20);
+
+        // Stable RTT: variance decays, timeout approaches the RTT
+        let small = est.timeout(0, Duration::ZERO, MAX);
+        assert!(small >= Duration::from_millis(200) && small < Duration::from_millis(400), "{small:?}");
+
+        // A 1 MB upload adds roughly two seconds of transfer time
+        let large = est.timeout(1_000_000, Duration::ZERO, MAX);
+        assert!(large > small + Duration::from_millis(1800), "{large:?}");
+
+        // Clamped to the bounds
+        assert_eq!(est.timeout(0, MIN, MAX), MIN);
+        assert_eq!(est.timeout(usize::MAX, MIN, MAX), MAX);
+    }
+
+    #[test]
+    fn test_jitter_widens_timeout() {
+        let mut steady = RttEstimator::new();
+        let mut jittery = RttEstimator::new();
+        for i in 0..20 {
+            steady.observe(Duration::from_millis(300), 0);
+            jittery.observe(Duration::from_millis(if i % 2 == 0 { 100 } else { 500 }), 0);
+        }
+        assert!(jittery.timeout(0, Duration::ZERO, MAX) > steady.timeout(0, Duration::ZERO, MAX));
+    }
+
+    #[test]
+    fn test_retry_policy() {
+        let policy = RetryPolicy::default();
+        assert!(policy.should_retry("GET", 0, &ClientError::Timeout));
+        assert!(policy.should_retry("get", 1, &ClientError::InvalidResponse));
+        assert!(!policy.should_retry("GET", DEFAULT_MAX_RETRIES, &ClientError::Timeout));
+
+        // Not idempotent unless asked
+        assert!(!policy.should_retry("POST", 0, &ClientError::Timeout));
+        let opt_in = RetryPolicy { retry_non_idempotent: true, ..policy };
+        assert!(opt_in.should_retry("POST", 0, &ClientError::Timeout));
+
+        // Errors another circuit wouldn't fix
+        assert!(!policy.should_retry("GET", 0, &ClientError::NoExitNodes));
+        assert!(!RetryPolicy::none().should_retry("GET", 0, &ClientError::Timeout));
+    }
+
+    #[test]
+    fn test_exit_failover_policy() {
+        let policy = RetryPolicy::default();
+        let unreachable = ClientError::ExitUnreachable("exit peer id unknown".to_string());
+
+        // An unreachable exit is failed over at once, a timeout only once
+        // another circuit to the exit failed too
+        assert!(policy.should_fail_over("GET", 0, 1, 0, &unreachable));
+        assert!(!policy.should_fail_over("GET", 0, 1, 0, &ClientError::Timeout));
+        assert!(policy.should_fail_over("GET", 1, EXIT_SUSPECT_FAILURES, 0, &ClientError::Timeout));
+
+        // Capped, idempotent only, and never for errors no exit would fix
+        assert!(!policy.should_fail_over("GET", 1, 2, DEFAULT_MAX_EXIT_FAILOVERS, &unreachable));
+        assert!(!policy.should_fail_over("GET", DEFAULT_MAX_RETRIES, 1, 0, &unreachable));
+        assert!(!policy.should_fail_over("POST", 0, 1, 0, &unreachable));
+        assert!(!policy.should_fail_over("GET", 1, 2, 0, &ClientError::NoExitNodes));
+        assert!(!RetryPolicy::none().should_fail_over("GET", 0, 1, 0, &unreachable));
+    }
+}
@@ -7,17 +7,34 @@
 use sha2::{Sha256, Digest};
 
 use craftnet_core::{
-    Shard, Id, PublicKey, ExitPayload, ShardType, OnionSettlement,
+    Shard, Id, PublicKey, ExitPayload, ShardType, OnionSettlement, QuotaToken, ChainAckRequest,
     lease_set::LeaseSet,
 };
 use craftec_crypto::{SigningKeypair};
-use craftnet_core::onion_crypto::{build_onion_header, encrypt_exit_payload, encrypt_routing_tag};
+use craftnet_core::onion_crypto::{build_onion_header, encrypt_exit_payload, encrypt_routing_tag_with_flags};
 use craftnet_erasure::TOTAL_SHARDS;
 use craftnet_erasure::chunker::chunk_and_encode;
 
 use crate::path::{OnionPath, PathHop, random_id};
 use crate::{ClientError, Result};
 
+/// Chain ack one relay owes the client for a shard (see `craftnet_core::chain_ack`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpectedAck {
+    pub relay_pubkey: PublicKey,
+    pub shard_id: Id,
+    pub nonce: [u8; 32],
+}
+
+/// Chain acks a shard's relays owe the client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShardAckChain {
+    /// Ref the gateway tags the acks it sends us with
+    pub ack_ref: Id,
+    /// One per relay, first hop first
+    pub hops: Vec<ExpectedAck>,
+}
+
 /// Build onion-routed shards from mode-specific payload data.
 ///
 /// Shared pipeline for both HTTP and tunnel modes:
@@ -28,7 +45,7 @@ use crate::{ClientError, Result};
 /// 5. Wrap each shard in onion header with per-hop settlement
 ///
 /// # Arguments
-/// * `mode` - `PAYLOAD_MODE_HTTP` (0x00) or `PAYLOAD_MODE_TUNNEL` (0x01)
+/// * `mode` - `PAYLOAD_MODE_HTTP` (0x00), `PAYLOAD_MODE_TUNNEL` (0x01) or `PAYLOAD_MODE_HTTP_STREAM` (0x02)
 /// * `payload_data` - Mode-specific data (serialized HTTP request or tunnel metadata+tcp)
 /// * `response_enc_pubkey` - Client's X25519 key for response encryption
 /// * `keypair` - User's signing keypair
@@ -46,6 +63,159 @@ pub fn build_onion_shards(
     lease_set: &LeaseSet,
     pool_pubkey: PublicKey,
 ) -> Result<(Id, Vec<Shard>)> {
+    build_onion_shards_with_flags(
+        mode,
+        payload_data,
+        response_enc_pubkey,
+        keypair,
+        exit,
+        paths,
+        lease_set,
+        pool_pubkey,
+        0,
+    )
+}
+
+/// Like [`build_onion_shards`], with `tag_flags` set on every routing tag
+/// (payload compression, see `craftnet_core::compression`).
+#[allow(clippy::too_many_arguments)]
+pub fn build_onion_shards_with_flags(
+    mode: u8,
+    payload_data: Vec<u8>,
+    response_enc_pubkey: [u8; 32],
+    keypair: &SigningKeypair,
+    exit: &PathHop,
+    paths: &[OnionPath],
+    lease_set: &LeaseSet,
+    pool_pubkey: PublicKey,
+    tag_flags: u8,
+) -> Result<(Id, Vec<Shard>)> {
+    build_onion_shards_with_tokens(
+        mode,
+        payload_data,
+        response_enc_pubkey,
+        keypair,
+        exit,
+        paths,
+        lease_set,
+        pool_pubkey,
+        tag_flags,
+        vec![],
+    )
+}
+
+/// Like [`build_onion_shards_with_flags`], paying the exit with free-tier
+/// `quota_tokens` (see `craftnet_core::quota`).
+#[allow(clippy::too_many_arguments)]
+pub fn build_onion_shards_with_tokens(
+    mode: u8,
+    payload_data: Vec<u8>,
+    response_enc_pubkey: [u8; 32],
+    keypair: &SigningKeypair,
+    exit: &PathHop,
+    paths: &[OnionPath],
+    lease_set: &LeaseSet,
+    pool_pubkey: PublicKey,
+    tag_flags: u8,
+    quota_tokens: Vec<QuotaToken>,
+) -> Result<(Id, Vec<Shard>)> {
+    build_onion_shards_traced(
+        mode,
+        payload_data,
+        response_enc_pubkey,
+        keypair,
+        exit,
+        paths,
+        lease_set,
+        pool_pubkey,
+        tag_flags,
+        quota_tokens,
+        None,
+    )
+}
+
+/// Like [`build_onion_shards_with_tokens`], asking every hop to seal its
+/// timing for `trace_pubkey` (diagnostic requests only, see
+/// `craftnet_core::trace`).
+#[allow(clippy::too_many_arguments)]
+pub fn build_onion_shards_traced(
+    mode: u8,
+    payload_data: Vec<u8>,
+    response_enc_pubkey: [u8; 32],
+    keypair: &SigningKeypair,
+    exit: &PathHop,
+    paths: &[OnionPath],
+    lease_set: &LeaseSet,
+    pool_pubkey: PublicKey,
+    tag_flags: u8,
+    quota_tokens: Vec<QuotaToken>,
+    trace_pubkey: Option<[u8; 32]>,
+) -> Result<(Id, Vec<Shard>)> {
+    build_shards(
+        mode,
+        payload_data,
+        response_enc_pubkey,
+        keypair,
+        exit,
+        paths,
+        lease_set,
+        pool_pubkey,
+        tag_flags,
+        quota_tokens,
+        trace_pubkey,
+        false,
+    )
+    .map(|(request_id, shards, _)| (request_id, shards))
+}
+
+/// Like [`build_onion_shards_traced`], asking every relay to ack its shards
+/// back up the path. Also returns each shard's [`ShardAckChain`], in shard
+/// order (None for shards sent straight to the exit).
+#[allow(clippy::too_many_arguments)]
+pub fn build_onion_shards_acked(
+    mode: u8,
+    payload_data: Vec<u8>,
+    response_enc_pubkey: [u8; 32],
+    keypair: &SigningKeypair,
+    exit: &PathHop,
+    paths: &[OnionPath],
+    lease_set: &LeaseSet,
+    pool_pubkey: PublicKey,
+    tag_flags: u8,
+    quota_tokens: Vec<QuotaToken>,
+    trace_pubkey: Option<[u8; 32]>,
+) -> Result<(Id, Vec<Shard>, Vec<Option<ShardAckChain>>)> {
+    build_shards(
+        mode,
+        payload_data,
+        response_enc_pubkey,
+        keypair,
+        exit,
+        paths,
+        lease_set,
+        pool_pubkey,
+        tag_flags,
+        quota_tokens,
+        trace_pubkey,
+        true,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn build_shards(
+    mode: u8,
+    payload_data: Vec<u8>,
+    response_enc_pubkey: [u8; 32],
+    keypair: &SigningKeypair,
+    exit: &PathHop,
+    paths: &[OnionPath],
+    lease_set: &LeaseSet,
+    pool_pubkey: PublicKey,
+    tag_flags: u8,
+    quota_tokens: Vec<QuotaToken>,
+    trace_pubkey: Option<[u8; 32]>,
+    chain_acks: bool,
+) -> Result<(Id, Vec<Shard>, Vec<Option<ShardAckChain>>)> {
     let request_id = random_id();
     let assembly_id = random_id();
     let user_pubkey = keypair.public_key_bytes();
@@ -60,6 +230,9 @@ pub fn build_onion_shards(
         mode,
         data: payload_data,
         response_enc_pubkey,
+        pool_pubkey,
+        quota_tokens,
+        trace_pubkey,
     };
 
     // Encrypt for exit
@@ -82,6 +255,7 @@ pub fn build_onion_shards(
 
     let total_chunks = chunks.len() as u16;
     let mut shards = Vec::with_capacity(chunks.len() * TOTAL_SHARDS);
+    let mut ack_chains = Vec::new();
 
     for (chunk_index, shard_payloads) in chunks {
         let total_shards_in_chunk = shard_payloads.len() as u8;
@@ -93,15 +267,35 @@ pub fn build_onion_shards(
                 &paths[i % paths.len()]
             };
 
+            // Random link refs between consecutive relays: relay k tags its
+            // acks refs[k] and passes on the ones arriving tagged refs[k + 1]
+            let refs: Vec<Id> = if chain_acks && !path.hops.is_empty() {
+                (0..path.hops.len()).map(|_| random_id()).collect()
+            } else {
+                Vec::new()
+            };
+            let mut expected = Vec::with_capacity(refs.len());
+
             // Build per-hop settlement data with unique shard_id per relay
-            let settlement: Vec<OnionSettlement> = path.hops.iter().map(|hop| {
+            let settlement: Vec<OnionSettlement> = path.hops.iter().enumerate().map(|(k, hop)| {
                 let shard_id = generate_shard_id(&request_id, chunk_index, i as u8, &hop.signing_pubkey);
+                let chain_ack = refs.get(k).map(|&up_ref| {
+                    let nonce = random_id();
+                    expected.push(ExpectedAck { relay_pubkey: hop.signing_pubkey, shard_id, nonce });
+                    ChainAckRequest { up_ref, down_ref: refs.get(k + 1).copied(), nonce }
+                });
                 OnionSettlement {
                     shard_id,
                     payload_size: payload.len() as u32,
                     pool_pubkey,
+                    cover: false,
+                    trace_pubkey,
+                    chain_ack,
                 }
             }).collect();
+            if chain_acks {
+                ack_chains.push(refs.first().map(|&ack_ref| ShardAckChain { ack_ref, hops: expected }));
+            }
 
             // Build onion header
             let hops_for_header: Vec<(&[u8], &[u8; 32])> = path.hops.iter()
@@ -116,7 +310,7 @@ pub fn build_onion_shards(
             ).map_err(|e| ClientError::CryptoError(e.to_string()))?;
 
             // Encrypt routing tag with shard/chunk metadata
-            let routing_tag = encrypt_routing_tag(
+            let routing_tag = encrypt_routing_tag_with_flags(
                 &exit.encryption_pubkey,
                 &assembly_id,
                 i as u8,
@@ -124,6 +318,7 @@ pub fn build_onion_shards(
                 chunk_index,
                 total_chunks,
                 &pool_pubkey,
+                tag_flags,
             ).map_err(|e| ClientError::CryptoError(e.to_string()))?;
 
             let total_hops = path.hops.len() as u8;
@@ -138,7 +333,7 @@ pub fn build_onion_shards(
         }
     }
 
-    Ok((request_id, shards))
+    Ok((request_id, shards, ack_chains))
 }
 
 /// Generate a per-hop unique shard ID: SHA256(request_id || "shard" || chunk_index || shard_index || relay_pubkey)
@@ -263,4 +458,55 @@ mod tests {
             assert_eq!(shard.hops_remaining, 0);
         }
     }
+
+    #[test]
+    fn test_build_onion_shards_acked_links_refs() {
+        let keypair = SigningKeypair::generate();
+        let exit_keys = craftec_crypto::EncryptionKeypair::generate();
+        let relay_keys: Vec<_> = (0..2).map(|_| craftec_crypto::EncryptionKeypair::generate()).collect();
+        let exit = PathHop {
+            peer_id: b"exit_peer".to_vec(),
+            signing_pubkey: [2u8; 32],
+            encryption_pubkey: exit_keys.public_key_bytes(),
+        };
+        let hops: Vec<PathHop> = relay_keys.iter().enumerate().map(|(k, keys)| PathHop {
+            peer_id: format!("relay_{}", k).into_bytes(),
+            signing_pubkey: [10 + k as u8; 32],
+            encryption_pubkey: keys.public_key_bytes(),
+        }).collect();
+        let path = OnionPath { hops, exit: exit.clone() };
+        let lease_set = LeaseSet { session_id: [0u8; 32], leases: vec![] };
+
+        let (_, shards, chains) = build_onion_shards_acked(
+            0x00,
+            b"GET\nhttps://example.com\n0\n0\n".to_vec(),
+            [0u8; 32],
+            &keypair,
+            &exit,
+            &[path],
+            &lease_set,
+            [0u8; 32],
+            0,
+            vec![],
+            None,
+        ).unwrap();
+        assert_eq!(chains.len(), shards.len());
+
+        let shard = &shards[0];
+        let chain = chains[0].as_ref().unwrap();
+        let first = craftnet_core::peel_onion_layer(&relay_keys[0].secret_key_bytes(), &shard.ephemeral_pubkey, &shard.header).unwrap();
+        let second = craftnet_core::peel_onion_layer(&relay_keys[1].secret_key_bytes(), &first.next_ephemeral_pubkey, &first.remaining_header).unwrap();
+        let (a, b) = (first.settlement.chain_ack.unwrap(), second.settlement.chain_ack.unwrap());
+
+        // The gateway's acks reach us tagged with the chain's ref, and the
+        // second relay's acks are tagged with the ref the gateway passes on
+        assert_eq!(a.up_ref, chain.ack_ref);
+        assert_eq!(a.down_ref, Some(b.up_ref));
+        assert_eq!(b.down_ref, None);
+        assert_eq!(chain.hops[1], ExpectedAck { relay_pubkey: [11u8; 32], shard_id: second.settlement.shard_id, nonce: b.nonce });
+
+        // Refs are random, not derived from the shard ID published in receipts
+        assert_ne!(a.up_ref, first.settlement.shard_id);
+        assert_ne!(chains[1].as_ref().unwrap().ack_ref, chain.ack_ref);
+    }
 }
@@ -6,15 +6,23 @@
 //! Each SOCKS5 CONNECT creates a long-lived session. Incoming TCP data is
 //! buffered into bursts (50ms timeout or 18KB full) and sent as tunnel-mode
 //! shards through the VPN.
+//!
+//! With split tunneling rules set (see [`Socks5Server::with_split_tunnel`]),
+//! connections whose process, domain or destination IP is ruled `bypass` are
+//! connected directly instead. Rules are read per connection, so edits take
+//! effect on the next CONNECT. The originating process is looked up only
+//! when a process rule exists (Linux only; elsewhere process rules never
+//! match).
 
 use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
 
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::mpsc;
 use tracing::{debug, error, info, warn};
 
-use craftnet_core::TunnelMetadata;
+use craftnet_core::{SplitTunnelAction, SplitTunnelRules, TunnelMetadata};
 
 use crate::node::TunnelBurst;
 use crate::ClientError;
@@ -25,11 +33,16 @@ const BURST_BUFFER_SIZE: usize = 18 * 1024;
 /// Idle timeout before flushing a partial buffer
 const BURST_FLUSH_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(50);
 
+/// Split tunneling rules shared between the daemon and its proxies
+pub type SharedSplitTunnelRules = Arc<RwLock<SplitTunnelRules>>;
+
 /// SOCKS5 proxy server
 pub struct Socks5Server {
     listen_addr: SocketAddr,
     /// Sender to push tunnel bursts to the node's event loop
     burst_tx: mpsc::Sender<TunnelBurst>,
+    /// Split tunneling rules (None = tunnel everything)
+    split_tunnel: Option<SharedSplitTunnelRules>,
     /// Handle for the listener task
     handle: Option<tokio::task::JoinHandle<()>>,
 }
@@ -43,10 +56,17 @@ impl Socks5Server {
         Self {
             listen_addr,
             burst_tx,
+            split_tunnel: None,
             handle: None,
         }
     }
 
+    /// Apply split tunneling rules to new connections
+    pub fn with_split_tunnel(mut self, rules: SharedSplitTunnelRules) -> Self {
+        self.split_tunnel = Some(rules);
+        self
+    }
+
     /// Start listening for SOCKS5 connections.
     ///
     /// Returns immediately; the server runs in a background task.
@@ -57,6 +77,7 @@ impl Socks5Server {
         self.listen_addr = actual_addr;
 
         let burst_tx = self.burst_tx.clone();
+        let split_tunnel = self.split_tunnel.clone();
 
         let handle = tokio::spawn(async move {
             loop {
@@ -64,8 +85,9 @@ impl Socks5Server {
                     Ok((stream, peer_addr)) => {
                         debug!("SOCKS5 connection from {}", peer_addr);
                         let tx = burst_tx.clone();
+                        let rules = split_tunnel.clone();
                         tokio::spawn(async move {
-                            if let Err(e) = handle_socks5_connection(stream, tx).await {
+                            if let Err(e) = handle_socks5_connection(stream, tx, rules).await {
                                 debug!("SOCKS5 connection from {} ended: {}", peer_addr, e);
                             }
                         });
@@ -105,6 +127,7 @@ impl Drop for Socks5Server {
 async fn handle_socks5_connection(
     mut stream: TcpStream,
     burst_tx: mpsc::Sender<TunnelBurst>,
+    split_tunnel: Option<SharedSplitTunnelRules>,
 ) -> std::result::Result<(), Box<dyn std::error::Error + Send + Sync>> {
     // === SOCKS5 Greeting ===
     // Client sends: VER (1) | NMETHODS (1) | METHODS (1..255)
@@ -185,12 +208,27 @@ async fn handle_socks5_connection(
 
     debug!("SOCKS5 CONNECT to {}:{}", host, port);
 
+    if split_tunnel_action(&stream, split_tunnel.as_ref(), &host) == SplitTunnelAction::Bypass {
+        return bypass_connection(stream, &host, port).await;
+    }
+
     // Reply with success (bound address = 0.0.0.0:0)
     // VER (1) | REP (1) | RSV (1) | ATYP (1) | BND.ADDR (4) | BND.PORT (2)
     stream.write_all(&[0x05, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0]).await?;
 
-    // === Bidirectional relay ===
-    // Generate session_id for this SOCKS5 connection
+    relay_session(&mut stream, &host, port, &burst_tx).await
+}
+
+/// Carry one TCP connection to `host:port` through the tunnel as a
+/// long-lived session, closing the session when either side ends it.
+///
+/// Shared by the SOCKS5 proxy and the full-device TUN interface.
+pub(crate) async fn relay_session<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    host: &str,
+    port: u16,
+    burst_tx: &mpsc::Sender<TunnelBurst>,
+) -> std::result::Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let session_id = {
         let mut id = [0u8; 32];
         rand::Rng::fill(&mut rand::thread_rng(), &mut id);
@@ -198,14 +236,14 @@ async fn handle_socks5_connection(
     };
 
     info!(
-        "SOCKS5 session {} relaying to {}:{}",
+        "Tunnel session {} relaying to {}:{}",
         hex::encode(&session_id[..8]),
         host,
         port
     );
 
-    // Relay loop: read from browser, send through tunnel, write response back
-    let result = relay_loop(&mut stream, &host, port, session_id, &burst_tx).await;
+    // Relay loop: read from the local socket, send through tunnel, write response back
+    let result = relay_loop(stream, host, port, session_id, burst_tx).await;
 
     // Send close signal
     let close_metadata = TunnelMetadata {
@@ -223,16 +261,98 @@ async fn handle_socks5_connection(
     }).await;
 
     debug!(
-        "SOCKS5 session {} ended",
+        "Tunnel session {} ended",
         hex::encode(&session_id[..8])
     );
 
     result
 }
 
-/// Bidirectional relay loop between browser socket and tunnel
-async fn relay_loop(
-    stream: &mut TcpStream,
+/// Route of a new connection under the current split tunneling rules
+fn split_tunnel_action(
+    stream: &TcpStream,
+    rules: Option<&SharedSplitTunnelRules>,
+    host: &str,
+) -> SplitTunnelAction {
+    let Some(rules) = rules else {
+        return SplitTunnelAction::Tunnel;
+    };
+    let rules = rules.read().unwrap_or_else(|e| e.into_inner()).clone();
+    let process = if rules.has_process_rules() {
+        match (stream.peer_addr(), stream.local_addr()) {
+            (Ok(peer), Ok(local)) => local_process_name(peer, local),
+            _ => None,
+        }
+    } else {
+        None
+    };
+    rules.decide(process.as_deref(), host)
+}
+
+/// Connect to the destination directly and splice the two sockets
+async fn bypass_connection(
+    mut stream: TcpStream,
+    host: &str,
+    port: u16,
+) -> std::result::Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut upstream = match TcpStream::connect((host, port)).await {
+        Ok(upstream) => upstream,
+        Err(e) => {
+            // Host unreachable
+            stream.write_all(&[0x05, 0x04, 0x00, 0x01, 0, 0, 0, 0, 0, 0]).await?;
+            return Err(format!("Bypass connect to {}:{} failed: {}", host, port, e).into());
+        }
+    };
+    stream.write_all(&[0x05, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0]).await?;
+    info!("SOCKS5 bypassing tunnel for {}:{}", host, port);
+
+    tokio::io::copy_bidirectional(&mut stream, &mut upstream).await?;
+    Ok(())
+}
+
+/// Name of the local process owning the client end of a proxied connection.
+///
+/// Finds the socket in `/proc/net/tcp{,6}` by its ports and the process
+/// holding that socket inode.
+#[cfg(target_os = "linux")]
+fn local_process_name(peer: SocketAddr, local: SocketAddr) -> Option<String> {
+    let inode = ["/proc/net/tcp", "/proc/net/tcp6"].iter().find_map(|table| {
+        let contents = std::fs::read_to_string(table).ok()?;
+        contents.lines().skip(1).find_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let port_of = |addr: &str| {
+                addr.rsplit_once(':').and_then(|(_, p)| u16::from_str_radix(p, 16).ok())
+            };
+            (port_of(fields.get(1)?)? == peer.port() && port_of(fields.get(2)?)? == local.port())
+                .then(|| fields.get(9).map(|s| s.to_string()))
+                .flatten()
+        })
+    })?;
+    let target = format!("socket:[{}]", inode);
+
+    for proc_entry in std::fs::read_dir("/proc").ok()?.flatten() {
+        let pid_dir = proc_entry.path();
+        let Ok(fds) = std::fs::read_dir(pid_dir.join("fd")) else {
+            continue;
+        };
+        let owns_socket = fds.flatten().any(|fd| {
+            std::fs::read_link(fd.path()).is_ok_and(|link| link.to_string_lossy() == target)
+        });
+        if owns_socket {
+            return std::fs::read_to_string(pid_dir.join("comm")).ok().map(|c| c.trim().to_string());
+        }
+    }
+    None
+}
+
+#[cfg(not(target_os = "linux"))]
+fn local_process_name(_peer: SocketAddr, _local: SocketAddr) -> Option<String> {
+    None
+}
+
+/// Bidirectional relay loop between local socket and tunnel
+async fn relay_loop<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
     host: &str,
     port: u16,
     session_id: [u8; 32],
@@ -318,4 +438,44 @@ mod tests {
         let server = Socks5Server::new(addr, tx);
         assert_eq!(server.listen_addr().port(), 0);
     }
+
+    #[tokio::test]
+    async fn test_socks5_bypass_connects_directly() {
+        use craftnet_core::SplitTunnelMatch;
+
+        // Destination the bypassed connection should reach
+        let echo = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let echo_port = echo.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut s, _) = echo.accept().await.unwrap();
+            let mut buf = [0u8; 4];
+            s.read_exact(&mut buf).await.unwrap();
+            s.write_all(&buf).await.unwrap();
+        });
+
+        let mut rules = SplitTunnelRules::default();
+        rules.add(SplitTunnelMatch::Cidr("127.0.0.0/8".into()), SplitTunnelAction::Bypass).unwrap();
+        let (tx, mut rx) = mpsc::channel(10);
+        let mut server = Socks5Server::new("127.0.0.1:0".parse().unwrap(), tx)
+            .with_split_tunnel(Arc::new(RwLock::new(rules)));
+        server.start().await.unwrap();
+
+        let mut client = TcpStream::connect(server.listen_addr()).await.unwrap();
+        client.write_all(&[0x05, 0x01, 0x00]).await.unwrap();
+        let mut reply = [0u8; 2];
+        client.read_exact(&mut reply).await.unwrap();
+        let mut connect = vec![0x05, 0x01, 0x00, 0x01, 127, 0, 0, 1];
+        connect.extend_from_slice(&echo_port.to_be_bytes());
+        client.write_all(&connect).await.unwrap();
+        let mut reply = [0u8; 10];
+        client.read_exact(&mut reply).await.unwrap();
+        assert_eq!(reply[1], 0x00);
+
+        client.write_all(b"ping").await.unwrap();
+        let mut buf = [0u8; 4];
+        client.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+        // Nothing went through the tunnel
+        assert!(rx.try_recv().is_err());
+    }
 }
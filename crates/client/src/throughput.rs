@@ -0,0 +1,168 @@
+//! Per-second throughput sampling
+//!
+//! Cumulative counters in [`crate::NodeStats`] can't drive live graphs, so
+//! the node also adds every byte it moves to a one-second bucket, split by
+//! the capability that moved it. [`ThroughputSeries`] keeps the last
+//! [`THROUGHPUT_HISTORY_SECS`] buckets; [`ThroughputSeries::window`] returns
+//! one zero-filled sample per second, oldest first.
+
+use std::collections::VecDeque;
+
+use craftnet_core::{system_clock, SharedClock};
+use serde::{Deserialize, Serialize};
+
+/// Seconds of samples kept
+pub const THROUGHPUT_HISTORY_SECS: u64 = 3600;
+
+/// Which capability moved the bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrafficClass {
+    /// Our own requests and responses
+    Client,
+    /// Shards forwarded for others
+    Relay,
+    /// Requests served as an exit
+    Exit,
+}
+
+/// Bytes moved during one second
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThroughputSample {
+    /// Unix second the sample covers
+    pub timestamp: u64,
+    pub client_up: u64,
+    pub client_down: u64,
+    pub relay_up: u64,
+    pub relay_down: u64,
+    pub exit_up: u64,
+    pub exit_down: u64,
+}
+
+impl ThroughputSample {
+    fn empty(timestamp: u64) -> Self {
+        Self { timestamp, ..Default::default() }
+    }
+
+    /// Bytes sent, all capabilities
+    pub fn up(&self) -> u64 {
+        self.client_up + self.relay_up + self.exit_up
+    }
+
+    /// Bytes received, all capabilities
+    pub fn down(&self) -> u64 {
+        self.client_down + self.relay_down + self.exit_down
+    }
+
+    fn add(&mut self, class: TrafficClass, up: u64, down: u64) {
+        let (u, d) = match class {
+            TrafficClass::Client => (&mut self.client_up, &mut self.client_down),
+            TrafficClass::Relay => (&mut self.relay_up, &mut self.relay_down),
+            TrafficClass::Exit => (&mut self.exit_up, &mut self.exit_down),
+        };
+        *u += up;
+        *d += down;
+    }
+}
+
+/// Ring buffer of per-second samples (only seconds with traffic are stored)
+#[derive(Clone)]
+pub struct ThroughputSeries {
+    samples: VecDeque<ThroughputSample>,
+    clock: SharedClock,
+}
+
+impl std::fmt::Debug for ThroughputSeries {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ThroughputSeries").field("samples", &self.samples.len()).finish_non_exhaustive()
+    }
+}
+
+impl Default for ThroughputSeries {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ThroughputSeries {
+    pub fn new() -> Self {
+        Self { samples: VecDeque::new(), clock: system_clock() }
+    }
+
+    /// Read time from `clock` instead of the system clock
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+    }
+
+    /// Add bytes moved now
+    pub fn record(&mut self, class: TrafficClass, up: u64, down: u64) {
+        self.record_at(self.clock.unix_now(), class, up, down);
+    }
+
+    /// Add bytes moved at unix second `now`
+    pub fn record_at(&mut self, now: u64, class: TrafficClass, up: u64, down: u64) {
+        match self.samples.back_mut() {
+            // A clock stepping back lands in the newest bucket
+            Some(last) if last.timestamp >= now => last.add(class, up, down),
+            _ => {
+                let mut sample = ThroughputSample::empty(now);
+                sample.add(class, up, down);
+                self.samples.push_back(sample);
+            }
+        }
+        while self.samples.front().is_some_and(|s| s.timestamp + THROUGHPUT_HISTORY_SECS <= now) {
+            self.samples.pop_front();
+        }
+    }
+
+    /// The last `window_secs` seconds up to now, oldest first
+    pub fn window(&self, window_secs: u64) -> Vec<ThroughputSample> {
+        self.window_at(self.clock.unix_now(), window_secs)
+    }
+
+    /// The `window_secs` seconds ending at unix second `now`, oldest first,
+    /// one sample per second (capped at [`THROUGHPUT_HISTORY_SECS`])
+    pub fn window_at(&self, now: u64, window_secs: u64) -> Vec<ThroughputSample> {
+        let window = window_secs.clamp(1, THROUGHPUT_HISTORY_SECS);
+        let start = now.saturating_sub(window - 1);
+        let mut recorded = self.samples.iter().filter(|s| s.timestamp >= start).peekable();
+        (start..=now)
+            .map(|second| match recorded.peek() {
+                Some(s) if s.timestamp == second => *recorded.next().unwrap(),
+                _ => ThroughputSample::empty(second),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_is_zero_filled_per_second() {
+        let mut series = ThroughputSeries::new();
+        series.record_at(100, TrafficClass::Client, 10, 500);
+        series.record_at(100, TrafficClass::Relay, 64, 64);
+        series.record_at(102, TrafficClass::Exit, 300, 20);
+
+        let window = series.window_at(103, 5);
+        assert_eq!(window.iter().map(|s| s.timestamp).collect::<Vec<_>>(), vec![99, 100, 101, 102, 103]);
+        assert_eq!(window[1].client_down, 500);
+        assert_eq!(window[1].up(), 74);
+        assert_eq!(window[1].down(), 564);
+        assert_eq!(window[2], ThroughputSample::empty(101));
+        assert_eq!(window[3].exit_up, 300);
+    }
+
+    #[test]
+    fn test_old_samples_are_dropped() {
+        let mut series = ThroughputSeries::new();
+        series.record_at(1000, TrafficClass::Client, 1, 1);
+        series.record_at(1000 + THROUGHPUT_HISTORY_SECS, TrafficClass::Client, 2, 2);
+        assert_eq!(series.samples.len(), 1);
+
+        // Clock stepped back: counted in the newest bucket
+        series.record_at(900, TrafficClass::Client, 3, 3);
+        assert_eq!(series.samples.back().unwrap().client_up, 5);
+    }
+}
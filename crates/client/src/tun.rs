@@ -0,0 +1,599 @@
+//! Full-device VPN on desktop (TUN interface)
+//!
+//! The SOCKS5 proxy only carries traffic from applications configured to
+//! use it. [`TunDevice`] instead creates a virtual interface (via `tun`,
+//! backed by `wintun` on Windows), points the system's default routes and
+//! DNS at it, and terminates the captured packets in a userspace TCP/IP
+//! stack. Every TCP connection becomes a tunnel session exactly like a
+//! SOCKS5 CONNECT; UDP DNS queries are forwarded to the same resolver over
+//! DNS-over-TCP through the tunnel. Other UDP is dropped — the tunnel only
+//! carries streams — so applications like browsers fall back from QUIC to
+//! TCP.
+//!
+//! The node's own connections to its peers must not be captured, or the
+//! tunnel would be routed through itself. Routes are installed as two /1
+//! halves (more specific than the physical default route, so nothing is
+//! deleted) plus /32 host routes via the original gateway for every peer
+//! address the node knows ([`TunDevice::sync_bypass`]); private and
+//! link-local peers are already reached over their subnet routes.
+//!
+//! Route and DNS changes go through the platform tools (`ip`/`resolvectl`
+//! on Linux, `route`/`scutil` on macOS, `route`/`netsh` on Windows), need
+//! administrator rights, and are undone when the device stops. Only IPv4 is
+//! captured; IPv6 leaks are left to the kill switch.
+//!
+//! Built with the `tun` feature.
+
+use std::collections::BTreeSet;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use ipstack::stream::IpStackStream;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+use crate::node::TunnelBurst;
+use crate::socks5::relay_session;
+
+/// Largest DNS message carried over the tunnel
+const MAX_DNS_MESSAGE: usize = 4096;
+
+/// Buffer between a UDP DNS flow and its DNS-over-TCP tunnel session
+const DNS_PIPE_CAPACITY: usize = 64 * 1024;
+
+/// Virtual interface settings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TunConfig {
+    /// Interface name (None = platform default, e.g. `utunN` on macOS)
+    pub name: Option<String>,
+    /// Address of the interface
+    pub address: Ipv4Addr,
+    pub netmask: Ipv4Addr,
+    pub mtu: u16,
+    /// Resolvers the system is pointed at while the device is up; their
+    /// queries are routed into the device and answered through the tunnel
+    pub dns_servers: Vec<IpAddr>,
+    /// Install default routes and DNS (false = only create the interface,
+    /// e.g. when routes are managed externally)
+    pub manage_routes: bool,
+}
+
+impl Default for TunConfig {
+    fn default() -> Self {
+        Self {
+            name: None,
+            address: Ipv4Addr::new(10, 88, 0, 2),
+            netmask: Ipv4Addr::new(255, 255, 255, 0),
+            mtu: 1500,
+            dns_servers: vec![IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1))],
+            manage_routes: true,
+        }
+    }
+}
+
+/// Snapshot of the device for IPC
+#[derive(Debug, Clone, Serialize)]
+pub struct TunStatus {
+    pub interface: String,
+    pub address: Ipv4Addr,
+    pub mtu: u16,
+    pub dns_servers: Vec<IpAddr>,
+    /// Peer addresses routed around the device
+    pub bypassed: usize,
+    /// TCP connections carried as tunnel sessions
+    pub tcp_sessions: u64,
+    /// UDP DNS flows answered through the tunnel
+    pub dns_flows: u64,
+    /// Flows dropped because the tunnel doesn't carry them
+    pub dropped_flows: u64,
+}
+
+#[derive(Debug, Default)]
+struct TunCounters {
+    tcp_sessions: AtomicU64,
+    dns_flows: AtomicU64,
+    dropped_flows: AtomicU64,
+}
+
+/// Full-device VPN interface feeding the node's tunnel sessions
+pub struct TunDevice {
+    config: TunConfig,
+    /// Sender to push tunnel bursts to the node's event loop
+    burst_tx: mpsc::Sender<TunnelBurst>,
+    /// Actual interface name (set once started)
+    interface: Option<String>,
+    /// Installed routes/DNS, undone on stop
+    routes: Option<PlatformRoutes>,
+    counters: Arc<TunCounters>,
+    /// Handle for the packet stack task
+    handle: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl TunDevice {
+    /// Create a TUN device.
+    ///
+    /// `burst_tx` feeds into `CraftNetNode`'s event loop via
+    /// `set_tunnel_burst_rx()`, like [`crate::Socks5Server`].
+    pub fn new(config: TunConfig, burst_tx: mpsc::Sender<TunnelBurst>) -> Self {
+        Self {
+            config,
+            burst_tx,
+            interface: None,
+            routes: None,
+            counters: Arc::new(TunCounters::default()),
+            handle: None,
+        }
+    }
+
+    /// Create the interface, install routes/DNS and start carrying traffic.
+    ///
+    /// `bypass` are the node's peer addresses, routed around the device.
+    /// Returns immediately; packets are handled in a background task.
+    pub async fn start(&mut self, bypass: impl IntoIterator<Item = IpAddr>) -> std::io::Result<()> {
+        use ::tun::AbstractDevice;
+
+        let mut tun_config = ::tun::Configuration::default();
+        tun_config
+            .address(self.config.address)
+            .netmask(self.config.netmask)
+            .mtu(self.config.mtu)
+            .up();
+        if let Some(ref name) = self.config.name {
+            tun_config.tun_name(name);
+        }
+        let device = ::tun::create_as_async(&tun_config).map_err(std::io::Error::other)?;
+        let interface = device.tun_name().map_err(std::io::Error::other)?;
+        info!("TUN interface {} up at {}", interface, self.config.address);
+
+        if self.config.manage_routes {
+            let mut routes = PlatformRoutes::install(&interface, &self.config)?;
+            routes.sync_bypass(bypass);
+            self.routes = Some(routes);
+        }
+
+        let mut stack_config = ipstack::IpStackConfig::default();
+        stack_config.mtu(self.config.mtu);
+        let mut stack = ipstack::IpStack::new(stack_config, device);
+
+        let burst_tx = self.burst_tx.clone();
+        let counters = Arc::clone(&self.counters);
+        let handle = tokio::spawn(async move {
+            loop {
+                let stream = match stack.accept().await {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        warn!("TUN stack stopped: {}", e);
+                        break;
+                    }
+                };
+                match stream {
+                    IpStackStream::Tcp(mut tcp) => {
+                        let dest = tcp.peer_addr();
+                        counters.tcp_sessions.fetch_add(1, Ordering::Relaxed);
+                        let tx = burst_tx.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = relay_session(&mut tcp, &dest.ip().to_string(), dest.port(), &tx).await {
+                                debug!("TUN connection to {} ended: {}", dest, e);
+                            }
+                        });
+                    }
+                    IpStackStream::Udp(udp) if udp.peer_addr().port() == 53 => {
+                        let resolver = udp.peer_addr();
+                        counters.dns_flows.fetch_add(1, Ordering::Relaxed);
+                        let tx = burst_tx.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = serve_dns_flow(udp, resolver, tx).await {
+                                debug!("TUN DNS flow to {} ended: {}", resolver, e);
+                            }
+                        });
+                    }
+                    IpStackStream::Udp(udp) => {
+                        debug!("Dropping UDP flow to {} (tunnel carries TCP only)", udp.peer_addr());
+                        counters.dropped_flows.fetch_add(1, Ordering::Relaxed);
+                    }
+                    _ => {
+                        counters.dropped_flows.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            }
+        });
+
+        self.interface = Some(interface);
+        self.handle = Some(handle);
+        Ok(())
+    }
+
+    /// Route the node's current peer addresses around the device (adds new
+    /// ones, removes those no longer known)
+    pub fn sync_bypass(&mut self, bypass: impl IntoIterator<Item = IpAddr>) {
+        if let Some(ref mut routes) = self.routes {
+            routes.sync_bypass(bypass);
+        }
+    }
+
+    /// Stop carrying traffic, undo routes/DNS and remove the interface
+    pub fn stop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            handle.abort();
+        }
+        if let Some(routes) = self.routes.take() {
+            routes.uninstall();
+        }
+        if let Some(interface) = self.interface.take() {
+            info!("TUN interface {} down", interface);
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.handle.is_some()
+    }
+
+    pub fn status(&self) -> TunStatus {
+        TunStatus {
+            interface: self.interface.clone().unwrap_or_default(),
+            address: self.config.address,
+            mtu: self.config.mtu,
+            dns_servers: self.config.dns_servers.clone(),
+            bypassed: self.routes.as_ref().map_or(0, |r| r.bypass.len()),
+            tcp_sessions: self.counters.tcp_sessions.load(Ordering::Relaxed),
+            dns_flows: self.counters.dns_flows.load(Ordering::Relaxed),
+            dropped_flows: self.counters.dropped_flows.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Drop for TunDevice {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Answer the DNS queries of one UDP flow over a DNS-over-TCP tunnel
+/// session to the same resolver (RFC 1035 §4.2.2 length framing).
+///
+/// Each read of `udp` is one datagram.
+async fn serve_dns_flow<S: AsyncRead + AsyncWrite + Unpin>(
+    mut udp: S,
+    resolver: SocketAddr,
+    burst_tx: mpsc::Sender<TunnelBurst>,
+) -> std::result::Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (mut local, mut remote) = tokio::io::duplex(DNS_PIPE_CAPACITY);
+    let session = tokio::spawn(async move {
+        relay_session(&mut remote, &resolver.ip().to_string(), resolver.port(), &burst_tx).await
+    });
+
+    let mut query = vec![0u8; MAX_DNS_MESSAGE];
+    let result = async {
+        loop {
+            let n = udp.read(&mut query).await?;
+            if n == 0 {
+                return Ok(());
+            }
+            local.write_all(&(n as u16).to_be_bytes()).await?;
+            local.write_all(&query[..n]).await?;
+
+            let mut len = [0u8; 2];
+            local.read_exact(&mut len).await?;
+            let mut answer = vec![0u8; u16::from_be_bytes(len) as usize];
+            local.read_exact(&mut answer).await?;
+            udp.write_all(&answer).await?;
+        }
+    }
+    .await;
+
+    // Closing the pipe ends the tunnel session
+    drop(local);
+    let _ = session.await;
+    result
+}
+
+/// Routes and DNS installed for the device
+struct PlatformRoutes {
+    interface: String,
+    /// Physical default gateway and its interface, for bypass routes
+    gateway: Option<(IpAddr, String)>,
+    bypass: BTreeSet<IpAddr>,
+}
+
+impl PlatformRoutes {
+    fn install(interface: &str, config: &TunConfig) -> std::io::Result<Self> {
+        let gateway = default_gateway();
+        if gateway.is_none() {
+            warn!("No default gateway found — peer connections can't bypass {}", interface);
+        }
+        for half in ["0.0.0.0/1", "128.0.0.0/1"] {
+            run(&add_device_route(half, interface))?;
+        }
+        if !config.dns_servers.is_empty() {
+            set_dns(interface, &config.dns_servers)?;
+        }
+        Ok(Self { interface: interface.to_string(), gateway, bypass: BTreeSet::new() })
+    }
+
+    fn sync_bypass(&mut self, bypass: impl IntoIterator<Item = IpAddr>) {
+        let Some((gateway, ref gateway_if)) = self.gateway else { return };
+        // Only IPv4 is captured, so only IPv4 peers need routing around it
+        let wanted: BTreeSet<IpAddr> = bypass.into_iter().filter(IpAddr::is_ipv4).collect();
+        for ip in self.bypass.difference(&wanted) {
+            let _ = run(&delete_host_route(*ip));
+        }
+        for ip in wanted.difference(&self.bypass) {
+            if let Err(e) = run(&add_host_route(*ip, gateway, gateway_if)) {
+                warn!("Failed to route peer {} around {}: {}", ip, self.interface, e);
+            }
+        }
+        self.bypass = wanted;
+    }
+
+    fn uninstall(self) {
+        for ip in &self.bypass {
+            let _ = run(&delete_host_route(*ip));
+        }
+        if let Err(e) = clear_dns(&self.interface) {
+            warn!("Failed to restore DNS settings: {}", e);
+        }
+        // The /1 routes go away with the interface
+    }
+}
+
+/// Run a platform command, failing on a non-zero exit
+fn run(args: &[String]) -> std::io::Result<()> {
+    let (program, rest) = args.split_first().expect("command has a program");
+    let output = Command::new(program).args(rest).output()?;
+    if !output.status.success() {
+        return Err(std::io::Error::other(format!(
+            "`{}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+fn to_args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|s| s.to_string()).collect()
+}
+
+/// Physical default gateway and its interface
+fn default_gateway() -> Option<(IpAddr, String)> {
+    #[cfg(target_os = "linux")]
+    let output = Command::new("ip").args(["-4", "route", "show", "default"]).output().ok()?;
+    #[cfg(target_os = "macos")]
+    let output = Command::new("route").args(["-n", "get", "default"]).output().ok()?;
+    #[cfg(windows)]
+    let output = Command::new("powershell")
+        .args([
+            "-NoProfile",
+            "-Command",
+            "Get-NetRoute -DestinationPrefix 0.0.0.0/0 | Sort-Object RouteMetric | \
+             Select-Object -First 1 | ForEach-Object { \"$($_.NextHop) $($_.InterfaceIndex)\" }",
+        ])
+        .output()
+        .ok()?;
+    #[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+    return None;
+
+    #[cfg(any(target_os = "linux", target_os = "macos", windows))]
+    parse_default_gateway(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(target_os = "linux")]
+fn parse_default_gateway(output: &str) -> Option<(IpAddr, String)> {
+    // default via 192.168.1.1 dev wlan0 proto dhcp metric 600
+    output.lines().find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let gateway = fields.iter().position(|f| *f == "via").and_then(|i| fields.get(i + 1))?;
+        let device = fields.iter().position(|f| *f == "dev").and_then(|i| fields.get(i + 1))?;
+        Some((gateway.parse().ok()?, device.to_string()))
+    })
+}
+
+#[cfg(target_os = "macos")]
+fn parse_default_gateway(output: &str) -> Option<(IpAddr, String)> {
+    //     gateway: 192.168.1.1
+    //   interface: en0
+    let field = |name: &str| {
+        output.lines().find_map(|line| line.trim().strip_prefix(name).map(|v| v.trim().to_string()))
+    };
+    Some((field("gateway:")?.parse().ok()?, field("interface:")?))
+}
+
+#[cfg(windows)]
+fn parse_default_gateway(output: &str) -> Option<(IpAddr, String)> {
+    // "192.168.1.1 12" (next hop, interface index)
+    let mut fields = output.split_whitespace();
+    Some((fields.next()?.parse().ok()?, fields.next()?.to_string()))
+}
+
+#[cfg(target_os = "linux")]
+fn add_device_route(prefix: &str, interface: &str) -> Vec<String> {
+    to_args(&["ip", "route", "add", prefix, "dev", interface])
+}
+
+#[cfg(target_os = "macos")]
+fn add_device_route(prefix: &str, interface: &str) -> Vec<String> {
+    to_args(&["route", "-n", "add", "-net", prefix, "-interface", interface])
+}
+
+#[cfg(windows)]
+fn add_device_route(prefix: &str, interface: &str) -> Vec<String> {
+    let interface = format!("interface={}", interface);
+    to_args(&["netsh", "interface", "ipv4", "add", "route", prefix, &interface, "store=active"])
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn add_device_route(_prefix: &str, _interface: &str) -> Vec<String> {
+    to_args(&["false"])
+}
+
+#[cfg(target_os = "linux")]
+fn add_host_route(ip: IpAddr, gateway: IpAddr, gateway_if: &str) -> Vec<String> {
+    to_args(&["ip", "route", "replace", &format!("{}/32", ip), "via", &gateway.to_string(), "dev", gateway_if])
+}
+
+#[cfg(target_os = "macos")]
+fn add_host_route(ip: IpAddr, gateway: IpAddr, _gateway_if: &str) -> Vec<String> {
+    to_args(&["route", "-n", "add", "-host", &ip.to_string(), &gateway.to_string()])
+}
+
+#[cfg(windows)]
+fn add_host_route(ip: IpAddr, gateway: IpAddr, gateway_if: &str) -> Vec<String> {
+    to_args(&["route", "add", &ip.to_string(), "mask", "255.255.255.255", &gateway.to_string(), "if", gateway_if])
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn add_host_route(_ip: IpAddr, _gateway: IpAddr, _gateway_if: &str) -> Vec<String> {
+    to_args(&["false"])
+}
+
+#[cfg(target_os = "linux")]
+fn delete_host_route(ip: IpAddr) -> Vec<String> {
+    to_args(&["ip", "route", "del", &format!("{}/32", ip)])
+}
+
+#[cfg(target_os = "macos")]
+fn delete_host_route(ip: IpAddr) -> Vec<String> {
+    to_args(&["route", "-n", "delete", "-host", &ip.to_string()])
+}
+
+#[cfg(windows)]
+fn delete_host_route(ip: IpAddr) -> Vec<String> {
+    to_args(&["route", "delete", &ip.to_string()])
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn delete_host_route(_ip: IpAddr) -> Vec<String> {
+    to_args(&["false"])
+}
+
+/// Point the system resolver at `servers` for every domain
+#[cfg(target_os = "linux")]
+fn set_dns(interface: &str, servers: &[IpAddr]) -> std::io::Result<()> {
+    let mut args = to_args(&["resolvectl", "dns", interface]);
+    args.extend(servers.iter().map(IpAddr::to_string));
+    run(&args)?;
+    run(&to_args(&["resolvectl", "domain", interface, "~."]))
+}
+
+#[cfg(target_os = "macos")]
+fn set_dns(_interface: &str, servers: &[IpAddr]) -> std::io::Result<()> {
+    let servers: Vec<String> = servers.iter().map(IpAddr::to_string).collect();
+    scutil(&format!(
+        "d.init\nd.add ServerAddresses * {}\nd.add SupplementalMatchDomains * \"\"\nset {}\n",
+        servers.join(" "),
+        MACOS_DNS_KEY
+    ))
+}
+
+#[cfg(windows)]
+fn set_dns(interface: &str, servers: &[IpAddr]) -> std::io::Result<()> {
+    let name = format!("name={}", interface);
+    for (i, server) in servers.iter().enumerate() {
+        let server = server.to_string();
+        if i == 0 {
+            run(&to_args(&["netsh", "interface", "ipv4", "set", "dnsservers", &name, "static", &server, "primary", "validate=no"]))?;
+        } else {
+            let index = format!("index={}", i + 1);
+            run(&to_args(&["netsh", "interface", "ipv4", "add", "dnsservers", &name, &server, &index, "validate=no"]))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn set_dns(_interface: &str, _servers: &[IpAddr]) -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "DNS configuration not supported on this platform"))
+}
+
+#[cfg(target_os = "linux")]
+fn clear_dns(interface: &str) -> std::io::Result<()> {
+    run(&to_args(&["resolvectl", "revert", interface]))
+}
+
+#[cfg(target_os = "macos")]
+fn clear_dns(_interface: &str) -> std::io::Result<()> {
+    scutil(&format!("remove {}\n", MACOS_DNS_KEY))
+}
+
+#[cfg(windows)]
+fn clear_dns(_interface: &str) -> std::io::Result<()> {
+    // Settings of a wintun adapter are dropped with the adapter
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn clear_dns(_interface: &str) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// Dynamic store key of the resolver configuration set by the device
+#[cfg(target_os = "macos")]
+const MACOS_DNS_KEY: &str = "State:/Network/Service/craftnet-tun/DNS";
+
+#[cfg(target_os = "macos")]
+fn scutil(script: &str) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let mut child = Command::new("scutil").stdin(Stdio::piped()).spawn()?;
+    child.stdin.take().expect("piped stdin").write_all(script.as_bytes())?;
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(std::io::Error::other("scutil failed"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tun_config_defaults_from_partial_json() {
+        let config: TunConfig = serde_json::from_str(r#"{"dns_servers": ["9.9.9.9"]}"#).unwrap();
+        assert_eq!(config.address, TunConfig::default().address);
+        assert_eq!(config.dns_servers, vec!["9.9.9.9".parse::<IpAddr>().unwrap()]);
+        assert!(config.manage_routes);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_parse_default_gateway() {
+        let output = "default via 192.168.1.1 dev wlan0 proto dhcp src 192.168.1.20 metric 600\n";
+        assert_eq!(
+            parse_default_gateway(output),
+            Some(("192.168.1.1".parse().unwrap(), "wlan0".to_string()))
+        );
+        assert_eq!(parse_default_gateway("default dev wg0 scope link\n"), None);
+    }
+
+    #[tokio::test]
+    async fn test_dns_flow_framed_over_tunnel_session() {
+        let (burst_tx, mut burst_rx) = mpsc::channel::<TunnelBurst>(4);
+        let (mut app, udp) = tokio::io::duplex(1024);
+        let resolver: SocketAddr = "1.1.1.1:53".parse().unwrap();
+        let flow = tokio::spawn(serve_dns_flow(udp, resolver, burst_tx));
+
+        app.write_all(b"query").await.unwrap();
+        let burst = burst_rx.recv().await.unwrap();
+        assert_eq!(burst.metadata.host, "1.1.1.1");
+        assert_eq!(burst.metadata.port, 53);
+        assert_eq!(burst.data, b"\x00\x05query");
+
+        burst.response_tx.send(Ok(b"\x00\x06answer".to_vec())).await.unwrap();
+        let mut answer = [0u8; 6];
+        app.read_exact(&mut answer).await.unwrap();
+        assert_eq!(&answer, b"answer");
+
+        // The app going away closes the tunnel session
+        drop(app);
+        let close = burst_rx.recv().await.unwrap();
+        assert!(close.metadata.is_close);
+        assert!(flow.await.unwrap().is_ok());
+    }
+}
@@ -4,14 +4,14 @@
 //! Delegates to the shared shard builder for the encrypt → frame → erasure → onion pipeline.
 
 use craftnet_core::{
-    Shard, Id, PublicKey,
+    Shard, Id, PublicKey, QuotaToken,
     TunnelMetadata, PAYLOAD_MODE_TUNNEL,
     lease_set::LeaseSet,
 };
 use craftec_crypto::SigningKeypair;
 
 use crate::path::{OnionPath, PathHop};
-use crate::shard_builder::build_onion_shards;
+use crate::shard_builder::build_onion_shards_with_tokens;
 use crate::Result;
 
 /// Build tunnel-mode onion-routed shards from raw TCP bytes.
@@ -37,28 +37,61 @@ pub fn build_tunnel_shards(
     response_enc_pubkey: [u8; 32],
     pool_pubkey: PublicKey,
 ) -> Result<(Id, Vec<Shard>)> {
-    // Build payload: [metadata_len: u32 BE] [metadata bincode] [tcp_data]
-    // (mode byte is NOT in data — it's the ExitPayload.mode field)
-    let metadata_bytes = metadata.to_bytes();
-    let metadata_len = metadata_bytes.len() as u32;
-
-    let mut payload_data = Vec::with_capacity(4 + metadata_bytes.len() + tcp_data.len());
-    payload_data.extend_from_slice(&metadata_len.to_be_bytes());
-    payload_data.extend_from_slice(&metadata_bytes);
-    payload_data.extend_from_slice(tcp_data);
+    build_tunnel_shards_with_tokens(
+        metadata,
+        tcp_data,
+        keypair,
+        exit,
+        paths,
+        lease_set,
+        response_enc_pubkey,
+        pool_pubkey,
+        vec![],
+    )
+}
 
-    build_onion_shards(
+/// Like [`build_tunnel_shards`], paying the exit with free-tier
+/// `quota_tokens`. The exit meters `tcp_data`, not the metadata.
+#[allow(clippy::too_many_arguments)]
+pub fn build_tunnel_shards_with_tokens(
+    metadata: &TunnelMetadata,
+    tcp_data: &[u8],
+    keypair: &SigningKeypair,
+    exit: &PathHop,
+    paths: &[OnionPath],
+    lease_set: &LeaseSet,
+    response_enc_pubkey: [u8; 32],
+    pool_pubkey: PublicKey,
+    quota_tokens: Vec<QuotaToken>,
+) -> Result<(Id, Vec<Shard>)> {
+    build_onion_shards_with_tokens(
         PAYLOAD_MODE_TUNNEL,
-        payload_data,
+        tunnel_payload(metadata, tcp_data),
         response_enc_pubkey,
         keypair,
         exit,
         paths,
         lease_set,
         pool_pubkey,
+        0,
+        quota_tokens,
     )
 }
 
+/// Exit payload data of a tunnel burst:
+/// `[metadata_len: u32 BE] [metadata bincode] [tcp_data]`
+/// (the mode byte is NOT in data — it's the ExitPayload.mode field)
+pub(crate) fn tunnel_payload(metadata: &TunnelMetadata, tcp_data: &[u8]) -> Vec<u8> {
+    let metadata_bytes = metadata.to_bytes();
+    let metadata_len = metadata_bytes.len() as u32;
+
+    let mut payload_data = Vec::with_capacity(4 + metadata_bytes.len() + tcp_data.len());
+    payload_data.extend_from_slice(&metadata_len.to_be_bytes());
+    payload_data.extend_from_slice(&metadata_bytes);
+    payload_data.extend_from_slice(tcp_data);
+    payload_data
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,459 @@
+//! Browser client (WebAssembly)
+//!
+//! [`WasmClient`] runs the request path of this crate inside a browser: it
+//! builds the onion-encrypted, erasure-coded request shards locally and
+//! exchanges them with a gateway relay over WebTransport. Only the relay sees
+//! the browser's address; the exit sees the relay, as with a native client.
+//!
+//! The relay must accept WebTransport sessions and speak the shard-stream
+//! framing (`[type:1][len:4 BE][seq_id:8][bincode shard]`, acks and nacks as
+//! in `craftnet_network::protocol`) on a bidirectional stream. It identifies
+//! the browser by [`WasmClient::peer_id`] and registers the tunnel for it
+//! the same way it does for a libp2p peer on connect.
+//!
+//! The browser has no topology view, so the caller supplies the relay and
+//! exit (e.g. from a bootstrap API) and requests take one relay hop:
+//! browser → gateway relay → exit.
+//!
+//! ```text
+//! const client = new WasmClient(url, relayPeerId, relaySigningKey, relayEncKey);
+//! client.setExit(exitPeerId, exitSigningKey, exitEncKey);
+//! const res = await client.fetch("GET", "https://example.com", {}, undefined);
+//! console.log(res.status, new TextDecoder().decode(res.body));
+//! ```
+
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use js_sys::{Array, Object, Promise, Reflect, Uint8Array};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+use web_sys::{
+    ReadableStreamDefaultReader, WebTransport, WebTransportBidirectionalStream,
+    WritableStreamDefaultWriter,
+};
+
+use craftec_crypto::{EncryptionKeypair, SigningKeypair};
+use craftnet_core::{PayloadCompression, PublicKey, Shard};
+use craftnet_erasure::ErasureCoder;
+
+use crate::decoder::ResponseAssembly;
+use crate::path::{build_gateway_paths, ed25519_peer_id, GeoFilter, PathHop, TopologyGraph};
+use crate::{ClientError, RequestBuilder, Result, TunnelResponse};
+
+/// Frame type bytes (must match `craftnet_network::protocol`)
+const FRAME_TYPE_SHARD: u8 = 0x01;
+const FRAME_TYPE_ACK: u8 = 0x02;
+const FRAME_TYPE_NACK: u8 = 0x03;
+
+/// Maximum frame payload size (must match `craftnet_network::protocol`)
+const MAX_FRAME_PAYLOAD: usize = 64 * 1024;
+
+/// Default time to wait for a complete response
+const DEFAULT_TIMEOUT_MS: u32 = 30_000;
+
+/// A frame on the shard stream
+#[derive(Debug)]
+enum Frame {
+    Shard { seq_id: u64, shard: Shard },
+    Ack { seq_id: u64 },
+    Nack { seq_id: u64, reason: String },
+}
+
+/// Encode a shard frame
+fn shard_frame(shard: &Shard, seq_id: u64) -> Result<Vec<u8>> {
+    let shard_bytes = shard.to_bytes()
+        .map_err(|e| ClientError::RequestFailed(format!("Failed to serialize shard: {}", e)))?;
+    let payload_len = 8 + shard_bytes.len();
+    if payload_len > MAX_FRAME_PAYLOAD {
+        return Err(ClientError::RequestFailed(format!(
+            "Shard frame payload too large: {} > {}", payload_len, MAX_FRAME_PAYLOAD,
+        )));
+    }
+    let mut buf = Vec::with_capacity(1 + 4 + payload_len);
+    buf.push(FRAME_TYPE_SHARD);
+    buf.extend_from_slice(&(payload_len as u32).to_be_bytes());
+    buf.extend_from_slice(&seq_id.to_be_bytes());
+    buf.extend_from_slice(&shard_bytes);
+    Ok(buf)
+}
+
+/// Encode an ack frame (browsers never attach receipts)
+fn ack_frame(seq_id: u64) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(1 + 4 + 9);
+    buf.push(FRAME_TYPE_ACK);
+    buf.extend_from_slice(&9u32.to_be_bytes());
+    buf.extend_from_slice(&seq_id.to_be_bytes());
+    buf.push(0);
+    buf
+}
+
+/// Reassembles frames from WebTransport reads, which split the byte stream
+/// at arbitrary points.
+#[derive(Debug, Default)]
+struct FrameBuffer {
+    buf: Vec<u8>,
+}
+
+impl FrameBuffer {
+    fn push(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Next complete frame, or None until more bytes arrive
+    fn next_frame(&mut self) -> Result<Option<Frame>> {
+        if self.buf.len() < 5 {
+            return Ok(None);
+        }
+        let len = u32::from_be_bytes(self.buf[1..5].try_into().unwrap()) as usize;
+        if len > MAX_FRAME_PAYLOAD {
+            return Err(ClientError::RequestFailed(format!(
+                "Frame payload too large: {} > {}", len, MAX_FRAME_PAYLOAD,
+            )));
+        }
+        if self.buf.len() < 5 + len {
+            return Ok(None);
+        }
+        let ty = self.buf[0];
+        let payload: Vec<u8> = self.buf.drain(..5 + len).skip(5).collect();
+        if payload.len() < 8 {
+            return Err(ClientError::RequestFailed("Frame too short for seq_id".to_string()));
+        }
+        let seq_id = u64::from_be_bytes(payload[..8].try_into().unwrap());
+
+        let frame = match ty {
+            FRAME_TYPE_SHARD => {
+                let shard = Shard::from_bytes(&payload[8..])
+                    .map_err(|e| ClientError::RequestFailed(format!("Invalid shard: {}", e)))?;
+                Frame::Shard { seq_id, shard }
+            }
+            FRAME_TYPE_ACK => Frame::Ack { seq_id },
+            FRAME_TYPE_NACK => {
+                let reason = payload.get(10..)
+                    .map(|r| String::from_utf8_lossy(r).to_string())
+                    .unwrap_or_default();
+                Frame::Nack { seq_id, reason }
+            }
+            other => {
+                return Err(ClientError::RequestFailed(format!(
+                    "Unknown frame type: 0x{:02x}", other,
+                )));
+            }
+        };
+        Ok(Some(frame))
+    }
+}
+
+fn to_key(bytes: &[u8], what: &str) -> std::result::Result<[u8; 32], JsError> {
+    bytes.try_into()
+        .map_err(|_| JsError::new(&format!("{} must be 32 bytes", what)))
+}
+
+fn js_err(e: JsValue) -> ClientError {
+    ClientError::ConnectionFailed(format!("{:?}", e))
+}
+
+/// Keys and hops shared with in-flight fetches
+struct Inner {
+    keypair: SigningKeypair,
+    encryption_keypair: EncryptionKeypair,
+    peer_id: Vec<u8>,
+    relay_url: String,
+    gateway: PathHop,
+    exit: Option<PathHop>,
+    timeout_ms: u32,
+}
+
+/// CraftNet client for browsers.
+///
+/// Each `fetch` opens a bidirectional stream on a fresh WebTransport session
+/// to the gateway relay, sends the request shards, and resolves once enough
+/// response shards arrived to decode the response.
+#[wasm_bindgen]
+pub struct WasmClient {
+    inner: Rc<Inner>,
+}
+
+#[wasm_bindgen]
+impl WasmClient {
+    /// Create a client with a fresh identity that enters the network at the
+    /// relay reachable at `relay_url` (an `https://` WebTransport URL).
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        relay_url: String,
+        relay_peer_id: &[u8],
+        relay_signing_pubkey: &[u8],
+        relay_encryption_pubkey: &[u8],
+    ) -> std::result::Result<WasmClient, JsError> {
+        let keypair = SigningKeypair::generate();
+        let peer_id = ed25519_peer_id(&keypair.public_key_bytes());
+        let gateway = PathHop {
+            peer_id: relay_peer_id.to_vec(),
+            signing_pubkey: to_key(relay_signing_pubkey, "relay signing pubkey")?,
+            encryption_pubkey: to_key(relay_encryption_pubkey, "relay encryption pubkey")?,
+        };
+        Ok(Self {
+            inner: Rc::new(Inner {
+                keypair,
+                encryption_keypair: EncryptionKeypair::generate(),
+                peer_id,
+                relay_url,
+                gateway,
+                exit: None,
+                timeout_ms: DEFAULT_TIMEOUT_MS,
+            }),
+        })
+    }
+
+    /// Select the exit node that executes requests
+    #[wasm_bindgen(js_name = setExit)]
+    pub fn set_exit(
+        &mut self,
+        exit_peer_id: &[u8],
+        exit_signing_pubkey: &[u8],
+        exit_encryption_pubkey: &[u8],
+    ) -> std::result::Result<(), JsError> {
+        let exit = PathHop {
+            peer_id: exit_peer_id.to_vec(),
+            signing_pubkey: to_key(exit_signing_pubkey, "exit signing pubkey")?,
+            encryption_pubkey: to_key(exit_encryption_pubkey, "exit encryption pubkey")?,
+        };
+        self.inner_mut()?.exit = Some(exit);
+        Ok(())
+    }
+
+    /// Time to wait for a complete response, in milliseconds
+    #[wasm_bindgen(js_name = setTimeout)]
+    pub fn set_timeout(&mut self, timeout_ms: u32) -> std::result::Result<(), JsError> {
+        self.inner_mut()?.timeout_ms = timeout_ms;
+        Ok(())
+    }
+
+    /// libp2p PeerId bytes the relay knows this client by
+    #[wasm_bindgen(js_name = peerId)]
+    pub fn peer_id(&self) -> Vec<u8> {
+        self.inner.peer_id.clone()
+    }
+
+    /// Ed25519 public key (also the pool key requests are billed to)
+    #[wasm_bindgen(js_name = publicKey)]
+    pub fn public_key(&self) -> Vec<u8> {
+        self.inner.keypair.public_key_bytes().to_vec()
+    }
+
+    /// Make an HTTP request through the network.
+    ///
+    /// `headers` is a plain object of header names to values. Resolves to
+    /// `{ status, headers, body: Uint8Array }`.
+    pub fn fetch(
+        &self,
+        method: String,
+        url: String,
+        headers: JsValue,
+        body: Option<Vec<u8>>,
+    ) -> Promise {
+        let inner = self.inner.clone();
+        future_to_promise(async move {
+            let headers: HashMap<String, String> = if headers.is_undefined() || headers.is_null() {
+                HashMap::new()
+            } else {
+                serde_wasm_bindgen::from_value(headers)?
+            };
+            let response = inner.fetch(&method, &url, headers, body).await
+                .map_err(|e| JsValue::from(JsError::new(&e.to_string())))?;
+            response_to_js(&response)
+        })
+    }
+}
+
+impl WasmClient {
+    /// Settings can only change while no fetch holds the shared state
+    fn inner_mut(&mut self) -> std::result::Result<&mut Inner, JsError> {
+        Rc::get_mut(&mut self.inner)
+            .ok_or_else(|| JsError::new("cannot change settings while a fetch is in flight"))
+    }
+}
+
+impl Inner {
+    async fn fetch(
+        &self,
+        method: &str,
+        url: &str,
+        headers: HashMap<String, String>,
+        body: Option<Vec<u8>>,
+    ) -> Result<TunnelResponse> {
+        let exit = self.exit.as_ref().ok_or(ClientError::NoExitNodes)?;
+        let (paths, lease_set) = build_gateway_paths(
+            &TopologyGraph::new(),
+            &self.peer_id,
+            std::slice::from_ref(&self.gateway),
+            0,
+            exit,
+            &GeoFilter::none(),
+        )?;
+
+        let mut builder = RequestBuilder::new(method, url)
+            .compressed(Arc::new(PayloadCompression::default()));
+        for (key, value) in &headers {
+            builder = builder.header(key, value);
+        }
+        if let Some(body) = body {
+            builder = builder.body(body);
+        }
+        let pool_pubkey: PublicKey = self.keypair.public_key_bytes();
+        let (request_id, shards) = builder.build_onion_with_enc_key(
+            &self.keypair,
+            exit,
+            &paths,
+            &lease_set,
+            self.encryption_keypair.public_key_bytes(),
+            pool_pubkey,
+        )?;
+
+        let transport = WebTransport::new(&self.relay_url).map_err(js_err)?;
+        let result = self.exchange(&transport, &request_id, exit, &shards).await;
+        transport.close();
+        result
+    }
+
+    /// Send the request shards and collect the response assembly
+    async fn exchange(
+        &self,
+        transport: &WebTransport,
+        request_id: &[u8; 32],
+        exit: &PathHop,
+        shards: &[Shard],
+    ) -> Result<TunnelResponse> {
+        JsFuture::from(transport.ready()).await.map_err(js_err)?;
+        let stream: WebTransportBidirectionalStream =
+            JsFuture::from(transport.create_bidirectional_stream())
+                .await
+                .map_err(js_err)?
+                .unchecked_into();
+        let writer = stream.writable().get_writer().map_err(js_err)?;
+        let reader: ReadableStreamDefaultReader = stream.readable().get_reader().unchecked_into();
+
+        for (seq_id, shard) in shards.iter().enumerate() {
+            write_bytes(&writer, &shard_frame(shard, seq_id as u64)?).await?;
+        }
+
+        let timeout = timeout_promise(self.timeout_ms)?;
+        let erasure = ErasureCoder::new().map_err(|e| ClientError::ErasureError(e.to_string()))?;
+        let secret = self.encryption_keypair.secret_key_bytes();
+        let mut frames = FrameBuffer::default();
+        let mut assembly = ResponseAssembly::new();
+
+        loop {
+            while let Some(frame) = frames.next_frame()? {
+                match frame {
+                    Frame::Shard { seq_id, shard } => {
+                        write_bytes(&writer, &ack_frame(seq_id)).await?;
+                        let Ok(tag) = craftnet_core::onion_crypto::decrypt_routing_tag(
+                            &secret,
+                            &shard.routing_tag,
+                        ) else {
+                            continue;
+                        };
+                        if &tag.assembly_id != request_id {
+                            continue;
+                        }
+                        assembly.insert(&tag, shard.payload.into());
+                        if assembly.is_ready() {
+                            let data = assembly.decode(&erasure, &exit.encryption_pubkey, &secret)?;
+                            return TunnelResponse::from_bytes(&data);
+                        }
+                    }
+                    Frame::Ack { .. } => {}
+                    Frame::Nack { seq_id, reason } => {
+                        return Err(ClientError::RequestFailed(format!(
+                            "Relay rejected shard {}: {}", seq_id, reason,
+                        )));
+                    }
+                }
+            }
+
+            let read = Promise::race(&Array::of2(&reader.read(), &timeout));
+            let result = JsFuture::from(read).await.map_err(js_err)?;
+            if result.is_undefined() {
+                return Err(ClientError::Timeout);
+            }
+            let done = Reflect::get(&result, &"done".into()).map_err(js_err)?;
+            if done.as_bool().unwrap_or(false) {
+                return Err(ClientError::ConnectionFailed(format!(
+                    "Relay closed the stream after {} response shards",
+                    assembly.shard_count(),
+                )));
+            }
+            let value = Reflect::get(&result, &"value".into()).map_err(js_err)?;
+            frames.push(&Uint8Array::new(&value).to_vec());
+        }
+    }
+}
+
+/// Write one buffer to the stream and wait for it to be accepted
+async fn write_bytes(writer: &WritableStreamDefaultWriter, bytes: &[u8]) -> Result<()> {
+    let chunk = Uint8Array::from(bytes);
+    JsFuture::from(writer.write_with_chunk(&chunk)).await.map_err(js_err)?;
+    Ok(())
+}
+
+/// A promise that resolves to `undefined` after `ms` milliseconds
+fn timeout_promise(ms: u32) -> Result<Promise> {
+    let set_timeout: js_sys::Function = Reflect::get(&js_sys::global(), &"setTimeout".into())
+        .map_err(js_err)?
+        .unchecked_into();
+    Ok(Promise::new(&mut |resolve, _reject| {
+        let _ = set_timeout.call2(&JsValue::NULL, &resolve, &JsValue::from(ms));
+    }))
+}
+
+/// `{ status, headers, body }` for JS
+fn response_to_js(response: &TunnelResponse) -> std::result::Result<JsValue, JsValue> {
+    let out = Object::new();
+    Reflect::set(&out, &"status".into(), &JsValue::from(response.status))?;
+    let headers = Object::new();
+    for (key, value) in &response.headers {
+        Reflect::set(&headers, &key.into(), &value.into())?;
+    }
+    Reflect::set(&out, &"headers".into(), &headers)?;
+    Reflect::set(&out, &"body".into(), &Uint8Array::from(response.body.as_slice()))?;
+    Ok(out.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames_split_across_reads() {
+        let shard = Shard::new([1u8; 32], vec![2u8; 64], vec![3u8; 100], vec![4u8; 60], 1, 1);
+        let mut bytes = shard_frame(&shard, 7).unwrap();
+        bytes.extend(ack_frame(8));
+
+        let mut frames = FrameBuffer::default();
+        let (a, b) = bytes.split_at(10);
+        frames.push(a);
+        assert!(frames.next_frame().unwrap().is_none());
+        frames.push(b);
+
+        match frames.next_frame().unwrap() {
+            Some(Frame::Shard { seq_id: 7, shard: decoded }) => {
+                assert_eq!(decoded.payload, shard.payload);
+            }
+            other => panic!("unexpected frame {:?}", other),
+        }
+        assert!(matches!(frames.next_frame().unwrap(), Some(Frame::Ack { seq_id: 8 })));
+        assert!(frames.next_frame().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_oversized_frame_rejected() {
+        let mut frames = FrameBuffer::default();
+        let mut bytes = vec![FRAME_TYPE_SHARD];
+        bytes.extend_from_slice(&((MAX_FRAME_PAYLOAD + 1) as u32).to_be_bytes());
+        frames.push(&bytes);
+        assert!(frames.next_frame().is_err());
+    }
+}
@@ -0,0 +1,281 @@
+//! WebSocket sessions through the tunnel
+//!
+//! [`CraftNetNode::open_websocket`](crate::CraftNetNode::open_websocket) asks
+//! the exit to perform the upgrade against the origin and returns a
+//! [`WebSocket`]. Like a SOCKS5 tunnel, the session is then carried by
+//! bursts (`PAYLOAD_MODE_WEBSOCKET`, see `craftnet_core::websocket`): a pump
+//! task sends the messages queued since the last burst, or an empty burst to
+//! poll, and hands back what the origin sent meanwhile.
+//!
+//! Polling backs off while the session is quiet and snaps back once
+//! messages flow. Bursts only travel while the node's event loop runs
+//! (`run` or `poll_once`); the exit closes sessions left unpolled for its
+//! idle timeout.
+
+use std::time::Duration;
+
+use craftnet_core::{Id, WsAction, WsClose, WsMessage, WsReply, WsRequest, WS_CLOSE_ABNORMAL, WS_CLOSE_NORMAL};
+use tokio::sync::mpsc;
+use tracing::debug;
+
+use crate::{ClientError, Result};
+
+/// Poll interval while messages are flowing
+const MIN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Poll interval of a quiet session
+const MAX_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Messages queued in either direction
+const MESSAGE_QUEUE: usize = 256;
+
+/// A WebSocket burst for the node's event loop
+pub(crate) struct WebSocketBurst {
+    pub request: WsRequest,
+    /// Channel to receive the exit's encoded [`WsReply`]
+    pub response_tx: mpsc::Sender<std::result::Result<Vec<u8>, ClientError>>,
+}
+
+/// What the handle asks of the pump
+enum Command {
+    Send(WsMessage),
+    Close(WsClose),
+}
+
+/// What the pump hands to the handle
+enum Event {
+    Message(WsMessage),
+    Closed(WsClose),
+    Failed(ClientError),
+}
+
+/// An open WebSocket session through the tunnel
+pub struct WebSocket {
+    session_id: Id,
+    protocol: Option<String>,
+    commands: mpsc::Sender<Command>,
+    events: mpsc::Receiver<Event>,
+    close: Option<WsClose>,
+}
+
+impl WebSocket {
+    /// Start pumping an opened session through `bursts`, waiting up to
+    /// `timeout` for each reply
+    pub(crate) fn spawn(
+        session_id: Id,
+        protocol: Option<String>,
+        bursts: mpsc::Sender<WebSocketBurst>,
+        timeout: Duration,
+    ) -> Self {
+        let (commands_tx, commands_rx) = mpsc::channel(MESSAGE_QUEUE);
+        let (events_tx, events_rx) = mpsc::channel(MESSAGE_QUEUE);
+        tokio::spawn(pump(session_id, bursts, commands_rx, events_tx, timeout));
+        Self { session_id, protocol, commands: commands_tx, events: events_rx, close: None }
+    }
+
+    /// Session ID the exit knows this session by
+    pub fn session_id(&self) -> Id {
+        self.session_id
+    }
+
+    /// Subprotocol the origin picked
+    pub fn protocol(&self) -> Option<&str> {
+        self.protocol.as_deref()
+    }
+
+    /// Queue a message for the origin
+    pub async fn send(&self, message: WsMessage) -> Result<()> {
+        self.commands
+            .send(Command::Send(message))
+            .await
+            .map_err(|_| ClientError::NotConnected)
+    }
+
+    /// Queue a text message for the origin
+    pub async fn send_text(&self, text: impl Into<String>) -> Result<()> {
+        self.send(WsMessage::Text(text.into())).await
+    }
+
+    /// Queue a binary message for the origin
+    pub async fn send_binary(&self, data: impl Into<Vec<u8>>) -> Result<()> {
+        self.send(WsMessage::Binary(data.into())).await
+    }
+
+    /// Next message from the origin.
+    ///
+    /// Returns None once the session closed (see [`Self::close_reason`]) and
+    /// an error if a burst failed, which also ends the session.
+    pub async fn recv(&mut self) -> Option<Result<WsMessage>> {
+        if self.close.is_some() {
+            return None;
+        }
+        match self.events.recv().await {
+            Some(Event::Message(message)) => Some(Ok(message)),
+            Some(Event::Failed(e)) => {
+                self.close = Some(WsClose { code: WS_CLOSE_ABNORMAL, reason: e.to_string() });
+                Some(Err(e))
+            }
+            Some(Event::Closed(close)) => {
+                self.close = Some(close);
+                None
+            }
+            None => {
+                self.close = Some(WsClose { code: WS_CLOSE_ABNORMAL, reason: String::new() });
+                None
+            }
+        }
+    }
+
+    /// Why the session closed (None while it is open)
+    pub fn close_reason(&self) -> Option<&WsClose> {
+        self.close.as_ref()
+    }
+
+    /// Close the session after the messages queued so far
+    pub async fn close(self, code: Option<u16>, reason: &str) -> Result<()> {
+        let close = WsClose { code: code.unwrap_or(WS_CLOSE_NORMAL), reason: reason.to_string() };
+        self.commands
+            .send(Command::Close(close))
+            .await
+            .map_err(|_| ClientError::NotConnected)
+    }
+}
+
+/// Carry one session until either side closes it or a burst fails
+async fn pump(
+    session_id: Id,
+    bursts: mpsc::Sender<WebSocketBurst>,
+    mut commands: mpsc::Receiver<Command>,
+    events: mpsc::Sender<Event>,
+    timeout: Duration,
+) {
+    let mut interval = MIN_POLL_INTERVAL;
+    loop {
+        // Wait for something to send, or until the next poll is due
+        let mut messages = Vec::new();
+        let mut close = None;
+        match tokio::time::timeout(interval, commands.recv()).await {
+            Ok(Some(Command::Send(message))) => messages.push(message),
+            Ok(Some(Command::Close(c))) => close = Some(c),
+            // Handle dropped: close the session
+            Ok(None) => close = Some(WsClose { code: WS_CLOSE_NORMAL, reason: String::new() }),
+            Err(_) => {}
+        }
+        while close.is_none() {
+            match commands.try_recv() {
+                Ok(Command::Send(message)) => messages.push(message),
+                Ok(Command::Close(c)) => close = Some(c),
+                Err(_) => break,
+            }
+        }
+
+        // Messages queued before a close still go out first
+        if !messages.is_empty() || close.is_none() {
+            let sent = !messages.is_empty();
+            let reply = match exchange(session_id, WsAction::Send(messages), &bursts, timeout).await {
+                Ok(reply) => reply,
+                Err(e) => {
+                    let _ = events.send(Event::Failed(e)).await;
+                    return;
+                }
+            };
+            match reply {
+                WsReply::Messages { messages, close: origin_close } => {
+                    interval = if sent || !messages.is_empty() {
+                        MIN_POLL_INTERVAL
+                    } else {
+                        (interval * 2).min(MAX_POLL_INTERVAL)
+                    };
+                    for message in messages {
+                        if events.send(Event::Message(message)).await.is_err() && close.is_none() {
+                            close = Some(WsClose { code: WS_CLOSE_NORMAL, reason: String::new() });
+                        }
+                    }
+                    if let Some(origin_close) = origin_close {
+                        debug!("WebSocket session {} closed by origin ({})", hex::encode(&session_id[..8]), origin_close.code);
+                        let _ = events.send(Event::Closed(origin_close)).await;
+                        return;
+                    }
+                }
+                WsReply::Error(e) => {
+                    let _ = events.send(Event::Failed(ClientError::RequestFailed(e))).await;
+                    return;
+                }
+                WsReply::Opened { .. } => {
+                    let _ = events.send(Event::Failed(ClientError::InvalidResponse)).await;
+                    return;
+                }
+            }
+        }
+
+        if let Some(close) = close {
+            // Best effort: the exit drops unpolled sessions anyway
+            let _ = exchange(session_id, WsAction::Close(close.clone()), &bursts, timeout).await;
+            let _ = events.send(Event::Closed(close)).await;
+            return;
+        }
+    }
+}
+
+/// Send one burst of `session_id` and wait up to `timeout` for the reply
+pub(crate) async fn exchange(
+    session_id: Id,
+    action: WsAction,
+    bursts: &mpsc::Sender<WebSocketBurst>,
+    timeout: Duration,
+) -> Result<WsReply> {
+    let (response_tx, mut response_rx) = mpsc::channel(1);
+    let burst = WebSocketBurst { request: WsRequest { session_id, action }, response_tx };
+    bursts.send(burst).await.map_err(|_| ClientError::NotConnected)?;
+    let reply = tokio::time::timeout(timeout, response_rx.recv())
+        .await
+        .map_err(|_| ClientError::Timeout)?
+        .ok_or(ClientError::Timeout)??;
+    WsReply::from_bytes(&reply).map_err(|_| ClientError::InvalidResponse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answer bursts like an exit whose origin echoes every message
+    fn echo_exit() -> mpsc::Sender<WebSocketBurst> {
+        let (tx, mut rx) = mpsc::channel::<WebSocketBurst>(16);
+        tokio::spawn(async move {
+            while let Some(burst) = rx.recv().await {
+                let reply = match burst.request.action {
+                    WsAction::Send(messages) => WsReply::Messages { messages, close: None },
+                    WsAction::Close(close) => WsReply::Messages { messages: Vec::new(), close: Some(close) },
+                    WsAction::Open { .. } => WsReply::Opened { protocol: None },
+                };
+                let _ = burst.response_tx.send(Ok(reply.to_bytes().unwrap())).await;
+            }
+        });
+        tx
+    }
+
+    #[tokio::test]
+    async fn test_websocket_echo_and_close() {
+        let mut ws = WebSocket::spawn([1u8; 32], Some("chat".to_string()), echo_exit(), Duration::from_secs(5));
+        assert_eq!(ws.protocol(), Some("chat"));
+
+        ws.send_text("hello").await.unwrap();
+        ws.send_binary(vec![1, 2]).await.unwrap();
+        assert_eq!(ws.recv().await.unwrap().unwrap(), WsMessage::Text("hello".to_string()));
+        assert_eq!(ws.recv().await.unwrap().unwrap(), WsMessage::Binary(vec![1, 2]));
+        assert!(ws.close_reason().is_none());
+
+        ws.close(None, "bye").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_websocket_burst_failure_ends_session() {
+        // No exit answers: the event loop is gone
+        let (tx, rx) = mpsc::channel::<WebSocketBurst>(1);
+        drop(rx);
+        let mut ws = WebSocket::spawn([2u8; 32], None, tx, Duration::from_secs(5));
+        assert!(matches!(ws.recv().await, Some(Err(ClientError::NotConnected))));
+        assert!(ws.recv().await.is_none());
+        assert_eq!(ws.close_reason().map(|c| c.code), Some(WS_CLOSE_ABNORMAL));
+    }
+}
@@ -0,0 +1,40 @@
+//! Embeds the build manifest (see `src/build_manifest.rs`): git commit,
+//! Cargo.lock hash, target triple and compiler version.
+
+use std::path::Path;
+use std::process::Command;
+
+use sha2::{Digest, Sha256};
+
+fn main() {
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let root = Path::new(&manifest_dir).join("../..");
+
+    // Builds without a .git directory (source tarballs, Docker) pass the
+    // commit in explicitly
+    println!("cargo:rerun-if-env-changed=CRAFTNET_GIT_COMMIT");
+    let commit = std::env::var("CRAFTNET_GIT_COMMIT").ok().filter(|c| !c.is_empty())
+        .or_else(|| command_output(Command::new("git").args(["rev-parse", "HEAD"]).current_dir(&root)))
+        .unwrap_or_default();
+    println!("cargo:rerun-if-changed=../../.git/HEAD");
+    println!("cargo:rerun-if-changed=../../.git/refs/heads");
+
+    let lock = root.join("Cargo.lock");
+    println!("cargo:rerun-if-changed={}", lock.display());
+    let lock_hash = std::fs::read(&lock)
+        .map(|bytes| hex::encode(Sha256::digest(bytes)))
+        .unwrap_or_default();
+
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let compiler = command_output(Command::new(rustc).arg("--version")).unwrap_or_default();
+
+    println!("cargo:rustc-env=CRAFTNET_BUILD_COMMIT={}", commit.trim());
+    println!("cargo:rustc-env=CRAFTNET_BUILD_LOCK_SHA256={}", lock_hash);
+    println!("cargo:rustc-env=CRAFTNET_BUILD_TARGET={}", std::env::var("TARGET").unwrap_or_default());
+    println!("cargo:rustc-env=CRAFTNET_BUILD_RUSTC={}", compiler.trim());
+}
+
+fn command_output(command: &mut Command) -> Option<String> {
+    let output = command.output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
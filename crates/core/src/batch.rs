@@ -0,0 +1,116 @@
+//! Coalesced HTTP request types
+//!
+//! When a request's `ExitPayload.mode` is `PAYLOAD_MODE_HTTP_BATCH`, its data
+//! is a [`BatchRequest`]: several small HTTP requests for the same exit,
+//! sharing one assembly (and so one round of erasure coding and onion
+//! wrapping). The exit runs each sub-request and answers with a single
+//! response whose body is a [`BatchResponse`], matched back to the
+//! sub-requests by `sub_id`.
+
+use serde::{Deserialize, Serialize};
+
+/// Payload mode: several HTTP requests coalesced into one assembly
+pub const PAYLOAD_MODE_HTTP_BATCH: u8 = 0x03;
+
+/// Most sub-requests an exit accepts in one batch
+pub const MAX_BATCH_REQUESTS: usize = 32;
+
+/// Header on a batch response carrying its sub-response count
+pub const BATCH_RESPONSE_HEADER: &str = "X-Craftnet-Batch";
+
+/// One HTTP request inside a [`BatchRequest`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubRequest {
+    /// Caller-chosen id echoed in the matching [`SubResponse`]
+    pub sub_id: u16,
+    /// Serialized HTTP request (same format as a `PAYLOAD_MODE_HTTP` payload)
+    pub data: Vec<u8>,
+}
+
+/// Sub-requests coalesced into one exit payload
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchRequest {
+    pub requests: Vec<SubRequest>,
+}
+
+impl BatchRequest {
+    /// Serialize to bytes
+    pub fn to_bytes(&self) -> Result<Vec<u8>, bincode::Error> {
+        bincode::serialize(self)
+    }
+
+    /// Deserialize from bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, bincode::Error> {
+        bincode::deserialize(bytes)
+    }
+}
+
+/// Outcome of one sub-request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SubResult {
+    /// Serialized HTTP response (same format as a plain HTTP response)
+    Response(Vec<u8>),
+    /// The exit could not run the sub-request (blocked, upstream error,
+    /// size limit)
+    Error(String),
+}
+
+/// One answer inside a [`BatchResponse`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubResponse {
+    /// `sub_id` of the [`SubRequest`] this answers
+    pub sub_id: u16,
+    pub result: SubResult,
+}
+
+/// Body of the response to a [`BatchRequest`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchResponse {
+    pub responses: Vec<SubResponse>,
+}
+
+impl BatchResponse {
+    /// Serialize to bytes
+    pub fn to_bytes(&self) -> Result<Vec<u8>, bincode::Error> {
+        bincode::serialize(self)
+    }
+
+    /// Deserialize from bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, bincode::Error> {
+        bincode::deserialize(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_roundtrip() {
+        let request = BatchRequest {
+            requests: vec![
+                SubRequest { sub_id: 0, data: b"GET\nhttps://a.example\n0\n0\n".to_vec() },
+                SubRequest { sub_id: 1, data: b"GET\nhttps://b.example\n0\n0\n".to_vec() },
+            ],
+        };
+        let bytes = request.to_bytes().unwrap();
+        assert_eq!(BatchRequest::from_bytes(&bytes).unwrap(), request);
+
+        let response = BatchResponse {
+            responses: vec![
+                SubResponse { sub_id: 1, result: SubResult::Error("blocked".to_string()) },
+                SubResponse { sub_id: 0, result: SubResult::Response(b"200\n0\n2\nok".to_vec()) },
+            ],
+        };
+        let bytes = response.to_bytes().unwrap();
+        assert_eq!(BatchResponse::from_bytes(&bytes).unwrap(), response);
+        assert!(BatchResponse::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_batch_mode_distinct_from_other_modes() {
+        assert_ne!(PAYLOAD_MODE_HTTP_BATCH, crate::PAYLOAD_MODE_HTTP);
+        assert_ne!(PAYLOAD_MODE_HTTP_BATCH, crate::PAYLOAD_MODE_TUNNEL);
+        assert_ne!(PAYLOAD_MODE_HTTP_BATCH, crate::PAYLOAD_MODE_HTTP_STREAM);
+    }
+}
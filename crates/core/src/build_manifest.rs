@@ -0,0 +1,106 @@
+//! Build manifest for supply-chain attestation
+//!
+//! `build.rs` embeds what went into this binary: the git commit, a SHA-256
+//! of `Cargo.lock`, the target triple and the compiler version. Release
+//! builds are reproducible from those four, so an operator can compare the
+//! manifest of the binary they run ([`BuildManifest::current`]) against the
+//! canonical manifests the maintainers sign into the network parameter
+//! record (`craftnet dev attest`).
+//!
+//! A source build without git metadata or `CRAFTNET_GIT_COMMIT` has an empty
+//! commit and never matches a canonical manifest.
+
+use serde::{Deserialize, Serialize};
+
+/// What a binary was built from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildManifest {
+    /// Crate version (`MAJOR.MINOR.PATCH[-PRE]`)
+    pub version: String,
+    /// Full git commit hash
+    pub git_commit: String,
+    /// Hex SHA-256 of `Cargo.lock`
+    pub cargo_lock_sha256: String,
+    /// Target triple, e.g. `x86_64-unknown-linux-gnu`
+    pub target: String,
+    /// `rustc --version` output
+    pub rustc: String,
+}
+
+impl BuildManifest {
+    /// Manifest of this binary, embedded at build time
+    pub fn current() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_commit: env!("CRAFTNET_BUILD_COMMIT").to_string(),
+            cargo_lock_sha256: env!("CRAFTNET_BUILD_LOCK_SHA256").to_string(),
+            target: env!("CRAFTNET_BUILD_TARGET").to_string(),
+            rustc: env!("CRAFTNET_BUILD_RUSTC").to_string(),
+        }
+    }
+}
+
+/// How a build compares with the canonical manifests
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum BuildAttestation {
+    /// A canonical manifest for this version and target matches exactly
+    Verified,
+    /// The canonical manifest for this version and target differs in
+    /// `fields`
+    Mismatch { fields: Vec<String> },
+    /// No canonical manifest for this version and target
+    Unknown,
+}
+
+/// Compare `build` with the canonical manifest for its version and target
+pub fn attest_build(build: &BuildManifest, canonical: &[BuildManifest]) -> BuildAttestation {
+    let Some(expected) = canonical.iter().find(|m| m.version == build.version && m.target == build.target) else {
+        return BuildAttestation::Unknown;
+    };
+    let mut fields = Vec::new();
+    if build.git_commit.is_empty() || !build.git_commit.eq_ignore_ascii_case(&expected.git_commit) {
+        fields.push("git_commit".to_string());
+    }
+    if !build.cargo_lock_sha256.eq_ignore_ascii_case(&expected.cargo_lock_sha256) {
+        fields.push("cargo_lock_sha256".to_string());
+    }
+    if build.rustc != expected.rustc {
+        fields.push("rustc".to_string());
+    }
+    if fields.is_empty() {
+        BuildAttestation::Verified
+    } else {
+        BuildAttestation::Mismatch { fields }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attest_build() {
+        let build = BuildManifest {
+            version: "0.3.0".to_string(),
+            git_commit: "ab".repeat(20),
+            cargo_lock_sha256: "cd".repeat(32),
+            target: "x86_64-unknown-linux-gnu".to_string(),
+            rustc: "rustc 1.80.0 (051478957 2024-07-21)".to_string(),
+        };
+        let other_target = BuildManifest { target: "aarch64-apple-darwin".to_string(), ..build.clone() };
+        assert_eq!(attest_build(&build, &[other_target.clone()]), BuildAttestation::Unknown);
+        assert_eq!(attest_build(&build, &[other_target, build.clone()]), BuildAttestation::Verified);
+
+        let patched = BuildManifest { cargo_lock_sha256: "ef".repeat(32), rustc: "rustc 1.81.0".to_string(), ..build.clone() };
+        assert_eq!(
+            attest_build(&patched, &[build.clone()]),
+            BuildAttestation::Mismatch { fields: vec!["cargo_lock_sha256".to_string(), "rustc".to_string()] },
+        );
+
+        // A build without a commit can't be checked
+        let uncommitted = BuildManifest { git_commit: String::new(), ..build.clone() };
+        let canonical = BuildManifest { git_commit: String::new(), ..build };
+        assert!(matches!(attest_build(&uncommitted, &[canonical]), BuildAttestation::Mismatch { .. }));
+    }
+}
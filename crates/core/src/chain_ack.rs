@@ -0,0 +1,88 @@
+//! Relay chain acks
+//!
+//! ForwardReceipts only travel one hop back, so a client can't tell whether
+//! the relays past its gateway forwarded its shards. A client that wants to
+//! know puts a [`ChainAckRequest`] in each relay's `OnionSettlement`: a
+//! random nonce plus two random link refs. The relay signs the nonce and its
+//! shard ID and sends the resulting [`ChainAck`] to the peer it got the shard
+//! from, tagged with its `up_ref`. It remembers `down_ref` → (sender,
+//! `up_ref`) so acks coming back from the next relay, tagged `down_ref`, are
+//! passed on re-tagged `up_ref`. The gateway's acks reach the client tagged
+//! with the shard's first ref, one per relay that handled the shard.
+//!
+//! Relays only ever see random refs and signatures over digests they can't
+//! recompute, so an ack passing through doesn't reveal who signed it. Only
+//! the client, which knows every relay's nonce and shard ID, can check them.
+
+use serde::{Deserialize, Serialize};
+use serde_big_array::BigArray;
+use sha2::{Digest, Sha256};
+
+use craftec_crypto::{sign_data, verify_signature, SigningKeypair};
+
+use crate::{Id, PublicKey, Signature};
+
+/// What a relay needs to ack a shard back to the client
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainAckRequest {
+    /// Ref the relay tags its own ack (and forwarded ones) with
+    pub up_ref: Id,
+    /// Ref acks from the next relay arrive with (None for the last relay)
+    pub down_ref: Option<Id>,
+    /// Per-hop random nonce the relay signs
+    pub nonce: [u8; 32],
+}
+
+/// Signed ack travelling back up a shard's path
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainAck {
+    /// Link ref of the hop the ack is currently travelling over
+    pub ack_ref: Id,
+    /// Relay's ed25519 signature over [`chain_ack_digest`]
+    #[serde(with = "BigArray")]
+    pub signature: Signature,
+}
+
+/// Digest a relay signs to ack a shard: domain tag, nonce and shard ID
+pub fn chain_ack_digest(nonce: &[u8; 32], shard_id: &Id) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"craftnet-chain-ack-v1");
+    hasher.update(nonce);
+    hasher.update(shard_id);
+    hasher.finalize().into()
+}
+
+/// Sign the ack for a shard we are forwarding
+pub fn sign_chain_ack(keypair: &SigningKeypair, request: &ChainAckRequest, shard_id: &Id) -> ChainAck {
+    ChainAck {
+        ack_ref: request.up_ref,
+        signature: sign_data(keypair, &chain_ack_digest(&request.nonce, shard_id)),
+    }
+}
+
+/// Check that `relay_pubkey` signed an ack for `shard_id` with `nonce`
+pub fn verify_chain_ack(relay_pubkey: &PublicKey, nonce: &[u8; 32], shard_id: &Id, ack: &ChainAck) -> bool {
+    verify_signature(relay_pubkey, &chain_ack_digest(nonce, shard_id), &ack.signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chain_ack_sign_verify() {
+        let relay = SigningKeypair::generate();
+        let request = ChainAckRequest { up_ref: [1u8; 32], down_ref: Some([2u8; 32]), nonce: [3u8; 32] };
+        let shard_id = [4u8; 32];
+
+        let ack = sign_chain_ack(&relay, &request, &shard_id);
+        assert_eq!(ack.ack_ref, request.up_ref);
+        assert!(verify_chain_ack(&relay.public_key_bytes(), &request.nonce, &shard_id, &ack));
+
+        // Bound to the signer, the nonce and the shard
+        let other = SigningKeypair::generate();
+        assert!(!verify_chain_ack(&other.public_key_bytes(), &request.nonce, &shard_id, &ack));
+        assert!(!verify_chain_ack(&relay.public_key_bytes(), &[9u8; 32], &shard_id, &ack));
+        assert!(!verify_chain_ack(&relay.public_key_bytes(), &request.nonce, &[9u8; 32], &ack));
+    }
+}
@@ -0,0 +1,100 @@
+//! Time source for timers
+//!
+//! Heartbeat thresholds, record TTLs, proof deadlines and compaction read
+//! the time through a [`Clock`] rather than `Instant::now()` or
+//! `SystemTime::now()`. Nodes run on [`SystemClock`]. Simulations and tests
+//! use [`SimClock`], whose time moves only when advanced, so a
+//! timing-sensitive scenario plays out the same way on every run.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Where timers get the current time
+pub trait Clock: Send + Sync {
+    /// Monotonic time, for intervals and deadlines
+    fn now(&self) -> Instant;
+
+    /// Wall-clock time in Unix seconds, for timestamps and TTLs
+    fn unix_now(&self) -> u64;
+}
+
+/// A clock shared between the components of one node
+pub type SharedClock = Arc<dyn Clock>;
+
+/// The real clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn unix_now(&self) -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+    }
+}
+
+/// The real clock, shared
+pub fn system_clock() -> SharedClock {
+    Arc::new(SystemClock)
+}
+
+/// Virtual time that only moves when [`advance`](Self::advance)d.
+///
+/// Share one `Arc<SimClock>` between every component of a simulation;
+/// advancing it moves all their timers at once.
+#[derive(Debug)]
+pub struct SimClock {
+    origin: Instant,
+    origin_unix: u64,
+    /// Virtual time since `origin`, in nanoseconds
+    elapsed: AtomicU64,
+}
+
+impl SimClock {
+    /// A clock reading `unix_start` that stands still until advanced
+    pub fn new(unix_start: u64) -> Self {
+        Self { origin: Instant::now(), origin_unix: unix_start, elapsed: AtomicU64::new(0) }
+    }
+
+    /// Move time forward by `by`
+    pub fn advance(&self, by: Duration) {
+        self.elapsed.fetch_add(by.as_nanos() as u64, Ordering::SeqCst);
+    }
+
+    /// Virtual time passed since the clock was created
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_nanos(self.elapsed.load(Ordering::SeqCst))
+    }
+}
+
+impl Clock for SimClock {
+    fn now(&self) -> Instant {
+        self.origin + self.elapsed()
+    }
+
+    fn unix_now(&self) -> u64 {
+        self.origin_unix + self.elapsed().as_secs()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sim_clock_moves_only_when_advanced() {
+        let clock = Arc::new(SimClock::new(1_700_000_000));
+        let shared: SharedClock = clock.clone();
+        let start = shared.now();
+        assert_eq!(shared.now(), start);
+        assert_eq!(shared.unix_now(), 1_700_000_000);
+
+        clock.advance(Duration::from_millis(1500));
+        assert_eq!(shared.now().duration_since(start), Duration::from_millis(1500));
+        assert_eq!(shared.unix_now(), 1_700_000_001);
+        assert_eq!(clock.elapsed(), Duration::from_millis(1500));
+    }
+}
@@ -0,0 +1,197 @@
+//! Optional zstd compression of tunnel payloads
+//!
+//! Compression is negotiated per request through `RoutingTag::flags`, which
+//! only the two endpoints can read:
+//!
+//! - The client sets `TAG_FLAG_ACCEPT_ZSTD` on its request shards when it can
+//!   decode compressed responses, and `TAG_FLAG_ZSTD` when it compressed the
+//!   `ExitPayload` data itself. It only compresses requests for exits that
+//!   advertise `ExitInfo::zstd_requests` (older exits would read zstd bytes
+//!   as an HTTP request), and never requests carrying credentials
+//!   (`Authorization`, `Cookie`): compressing secrets together with
+//!   attacker-influenced data leaks them through the compressed length.
+//! - The exit compresses the response only if the request accepted it, and
+//!   marks the response shards with `TAG_FLAG_ZSTD`.
+//!
+//! Either side skips compression when the body is already compressed (see
+//! [`is_precompressed`]) or when zstd would not make it smaller.
+
+use std::io::Read;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Routing tag flag: this assembly's payload is zstd compressed
+pub const TAG_FLAG_ZSTD: u8 = 0x01;
+
+/// Routing tag flag: the sender accepts a zstd compressed reply
+pub const TAG_FLAG_ACCEPT_ZSTD: u8 = 0x02;
+
+/// Payloads smaller than this are sent as-is
+pub const COMPRESSION_MIN_SIZE: usize = 256;
+
+/// zstd level used for tunnel payloads (fast, still a good ratio on text)
+pub const COMPRESSION_LEVEL: i32 = 3;
+
+/// Content types whose bodies are already compressed
+const PRECOMPRESSED_TYPES: &[&str] = &[
+    "image/",
+    "video/",
+    "audio/",
+    "font/woff",
+    "application/zip",
+    "application/gzip",
+    "application/x-gzip",
+    "application/zstd",
+    "application/x-bzip2",
+    "application/x-xz",
+    "application/x-7z-compressed",
+    "application/x-rar-compressed",
+    "application/vnd.rar",
+];
+
+/// Whether a body with these headers is already compressed.
+///
+/// Any `Content-Encoding` other than `identity` counts, as do media and
+/// archive content types (images other than SVG are compressed formats).
+pub fn is_precompressed(content_type: Option<&str>, content_encoding: Option<&str>) -> bool {
+    if let Some(encoding) = content_encoding {
+        let encoding = encoding.trim();
+        if !encoding.is_empty() && !encoding.eq_ignore_ascii_case("identity") {
+            return true;
+        }
+    }
+    let Some(content_type) = content_type else {
+        return false;
+    };
+    let content_type = content_type.trim().to_ascii_lowercase();
+    if content_type.starts_with("image/svg") {
+        return false;
+    }
+    PRECOMPRESSED_TYPES.iter().any(|t| content_type.starts_with(t))
+}
+
+/// Compress `data` if that makes it smaller; `None` means send it as-is
+pub fn compress(data: &[u8]) -> Option<Vec<u8>> {
+    if data.len() < COMPRESSION_MIN_SIZE {
+        return None;
+    }
+    let compressed = zstd::bulk::compress(data, COMPRESSION_LEVEL).ok()?;
+    (compressed.len() < data.len()).then_some(compressed)
+}
+
+/// Decompress a zstd payload, refusing output larger than `max_size`
+/// (a small compressed payload must not expand into unbounded memory).
+///
+/// Output over the limit fails with `ErrorKind::InvalidData`; corrupt input
+/// fails with other kinds.
+pub fn decompress(data: &[u8], max_size: usize) -> std::io::Result<Vec<u8>> {
+    let decoder = zstd::stream::read::Decoder::new(data)?;
+    let mut out = Vec::new();
+    decoder.take(max_size as u64 + 1).read_to_end(&mut out)?;
+    if out.len() > max_size {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("decompressed payload exceeds {} bytes", max_size),
+        ));
+    }
+    Ok(out)
+}
+
+/// Running totals of payload bytes before and after compression.
+///
+/// Shared between the client and exit paths of one node, so it uses atomics.
+#[derive(Debug, Default)]
+pub struct PayloadCompression {
+    /// Payload bytes before compression (compressed payloads only)
+    raw_bytes: AtomicU64,
+    /// The same payloads' bytes on the wire
+    compressed_bytes: AtomicU64,
+    /// Payloads sent or received compressed
+    payloads: AtomicU64,
+}
+
+impl PayloadCompression {
+    /// Record one compressed payload
+    pub fn record(&self, raw: usize, compressed: usize) {
+        self.raw_bytes.fetch_add(raw as u64, Ordering::Relaxed);
+        self.compressed_bytes.fetch_add(compressed as u64, Ordering::Relaxed);
+        self.payloads.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Uncompressed bytes of all compressed payloads
+    pub fn raw_bytes(&self) -> u64 {
+        self.raw_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Wire bytes of all compressed payloads
+    pub fn compressed_bytes(&self) -> u64 {
+        self.compressed_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Number of compressed payloads
+    pub fn payloads(&self) -> u64 {
+        self.payloads.load(Ordering::Relaxed)
+    }
+
+    /// Overall raw / compressed ratio, if anything was compressed
+    pub fn ratio(&self) -> Option<f64> {
+        let compressed = self.compressed_bytes();
+        (compressed > 0).then(|| self.raw_bytes() as f64 / compressed as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let data = b"hello tunnel ".repeat(100);
+        let compressed = compress(&data).unwrap();
+        assert!(compressed.len() < data.len());
+        assert_eq!(decompress(&compressed, data.len()).unwrap(), data);
+    }
+
+    #[test]
+    fn test_small_or_incompressible_skipped() {
+        assert!(compress(b"tiny").is_none());
+        // Pseudo-random bytes don't shrink
+        let mut x = 0x2545_f491_4f6c_dd1du64;
+        let noise: Vec<u8> = (0..4096)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                x as u8
+            })
+            .collect();
+        assert!(compress(&noise).is_none());
+    }
+
+    #[test]
+    fn test_decompress_enforces_limit() {
+        let data = vec![0u8; 100_000];
+        let compressed = compress(&data).unwrap();
+        assert!(decompress(&compressed, 1_000).is_err());
+        assert_eq!(decompress(&compressed, 100_000).unwrap().len(), 100_000);
+    }
+
+    #[test]
+    fn test_precompressed_detection() {
+        assert!(is_precompressed(Some("image/png"), None));
+        assert!(is_precompressed(Some("application/zip"), None));
+        assert!(is_precompressed(Some("text/html"), Some("gzip")));
+        assert!(!is_precompressed(Some("image/svg+xml"), None));
+        assert!(!is_precompressed(Some("text/html; charset=utf-8"), Some("identity")));
+        assert!(!is_precompressed(None, None));
+    }
+
+    #[test]
+    fn test_stats_ratio() {
+        let stats = PayloadCompression::default();
+        assert_eq!(stats.ratio(), None);
+        stats.record(1000, 250);
+        stats.record(1000, 250);
+        assert_eq!(stats.payloads(), 2);
+        assert_eq!(stats.ratio(), Some(4.0));
+    }
+}
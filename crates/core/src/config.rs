@@ -2,6 +2,9 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::split_tunnel::SplitTunnelRules;
+use crate::update::UpdateChannel;
+
 /// Main settings structure
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CraftNetConfig {
@@ -16,6 +19,26 @@ pub struct CraftNetConfig {
     /// UI settings
     #[serde(default)]
     pub ui: UiSettings,
+
+    /// Request audit log settings
+    #[serde(default)]
+    pub audit: AuditSettings,
+
+    /// Cookie jar settings
+    #[serde(default)]
+    pub cookies: CookieSettings,
+
+    /// Split tunneling rules (tunnel or bypass per process/domain/CIDR)
+    #[serde(default)]
+    pub split_tunnel: SplitTunnelRules,
+
+    /// In-tunnel DNS resolution (DNS leak prevention)
+    #[serde(default)]
+    pub dns: DnsSettings,
+
+    /// Node binary self-update
+    #[serde(default)]
+    pub update: UpdateSettings,
 }
 
 /// Network settings
@@ -36,6 +59,10 @@ pub struct NetworkSettings {
     /// Auto-connect on startup
     #[serde(default)]
     pub auto_connect: bool,
+
+    /// Block traffic outside the tunnel when it drops unexpectedly
+    #[serde(default)]
+    pub kill_switch: bool,
 }
 
 fn default_hops() -> u8 {
@@ -49,6 +76,7 @@ impl Default for NetworkSettings {
             hop_mode: HopMode::default(),
             bootstrap_peers: Vec::new(),
             auto_connect: false,
+            kill_switch: false,
         }
     }
 }
@@ -157,6 +185,151 @@ pub enum NodeMode {
     Full,
 }
 
+/// Local DNS stub that resolves through the tunnel (client)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DnsSettings {
+    /// Run the stub (off by default; point the system resolver at `listen`)
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// UDP address the stub listens on
+    #[serde(default = "default_dns_listen")]
+    pub listen: String,
+
+    /// DNS-over-HTTPS endpoint queried through the tunnel
+    #[serde(default = "default_doh_url")]
+    pub doh_url: String,
+
+    /// Resolver asked directly for split-tunnel bypass domains
+    #[serde(default = "default_bypass_resolver")]
+    pub bypass_resolver: String,
+}
+
+fn default_dns_listen() -> String {
+    "127.0.0.1:53".to_string()
+}
+
+fn default_doh_url() -> String {
+    "https://cloudflare-dns.com/dns-query".to_string()
+}
+
+fn default_bypass_resolver() -> String {
+    "1.1.1.1:53".to_string()
+}
+
+impl Default for DnsSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen: default_dns_listen(),
+            doh_url: default_doh_url(),
+            bypass_resolver: default_bypass_resolver(),
+        }
+    }
+}
+
+/// Request audit log settings (client)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditSettings {
+    /// Record tunnelled requests to an encrypted local log (off by default)
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Log file path (default: next to the settings file)
+    #[serde(default)]
+    pub path: Option<String>,
+
+    /// Rotate the log once it reaches this size
+    #[serde(default = "default_audit_max_file_bytes")]
+    pub max_file_bytes: u64,
+
+    /// Rotated log files kept
+    #[serde(default = "default_audit_max_files")]
+    pub max_files: u32,
+}
+
+fn default_audit_max_file_bytes() -> u64 {
+    1024 * 1024
+}
+
+fn default_audit_max_files() -> u32 {
+    4
+}
+
+impl Default for AuditSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: None,
+            max_file_bytes: default_audit_max_file_bytes(),
+            max_files: default_audit_max_files(),
+        }
+    }
+}
+
+/// Cookie jar settings (client)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CookieSettings {
+    /// Keep cookies between requests, per identity (off by default)
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Jar file path (default: next to the settings file)
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+/// Node binary self-update settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateSettings {
+    /// Check for, install and restart into new releases (off by default)
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Release channel to follow
+    #[serde(default)]
+    pub channel: UpdateChannel,
+
+    /// Signed manifest URL; `{channel}` is replaced by the channel name.
+    /// Updates stay off while unset.
+    #[serde(default)]
+    pub manifest_url: Option<String>,
+
+    /// Fetch the manifest and binary through the tunnel when the node can
+    /// (falls back to a direct download otherwise)
+    #[serde(default = "default_true")]
+    pub via_tunnel: bool,
+
+    /// Seconds between checks
+    #[serde(default = "default_update_interval")]
+    pub check_interval_secs: u64,
+}
+
+fn default_update_interval() -> u64 {
+    6 * 3600
+}
+
+impl UpdateSettings {
+    /// Manifest URL for the configured channel
+    pub fn manifest_url(&self) -> Option<String> {
+        self.manifest_url
+            .as_ref()
+            .map(|url| url.replace("{channel}", self.channel.as_str()))
+    }
+}
+
+impl Default for UpdateSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            channel: UpdateChannel::default(),
+            manifest_url: None,
+            via_tunnel: true,
+            check_interval_secs: default_update_interval(),
+        }
+    }
+}
+
 /// UI settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UiSettings {
@@ -206,6 +379,8 @@ mod tests {
         assert_eq!(settings.network.default_hops, 2);
         assert_eq!(settings.network.hop_mode, HopMode::Triple);
         assert!(settings.network.bootstrap_peers.is_empty());
+        assert!(!settings.audit.enabled);
+        assert!(!settings.cookies.enabled);
     }
 
     #[test]
@@ -246,4 +421,16 @@ mod tests {
         assert!(!ui.start_minimized);
         assert_eq!(ui.theme, Theme::System);
     }
+
+    #[test]
+    fn test_update_settings_default() {
+        let mut update = UpdateSettings::default();
+        assert!(!update.enabled);
+        assert_eq!(update.channel, UpdateChannel::Stable);
+        assert_eq!(update.manifest_url(), None);
+
+        update.channel = UpdateChannel::Beta;
+        update.manifest_url = Some("https://releases.example.com/{channel}/manifest.json".into());
+        assert_eq!(update.manifest_url().unwrap(), "https://releases.example.com/beta/manifest.json");
+    }
 }
@@ -0,0 +1,435 @@
+//! Credit accounting shared by the client, relay and exit roles
+//!
+//! Every priced unit of work goes through one [`CreditLedger`]: requests a
+//! client sends are debits, shards a relay forwards and requests an exit
+//! serves are credits. [`PricingRules`] turn bytes into credits (per MB by
+//! the pool's tier, plus a per-hop charge), so all roles price traffic the
+//! same way.
+//!
+//! Earned credits are provisional until the pool's distribution is posted
+//! on-chain. [`CreditLedger::reconcile`] then re-prices what the pool
+//! earned to the bytes the distribution pays this node for, and books the
+//! difference as an adjustment.
+
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{system_clock, Id, PublicKey, SharedClock, SubscriptionTier};
+
+/// Bytes in one priced megabyte
+pub const BYTES_PER_MB: u64 = 1024 * 1024;
+
+/// Per-request entries kept for display
+const MAX_RECENT_ENTRIES: usize = 1000;
+
+/// Role a ledger entry was booked in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CreditRole {
+    /// A request this node sent (debit)
+    Client,
+    /// Shards this node forwarded (credit)
+    Relay,
+    /// A request this node served (credit)
+    Exit,
+}
+
+/// How traffic is priced
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PricingRules {
+    /// Credits per MB of free-tier traffic. Default: 0.
+    pub free_per_mb: u64,
+    /// Credits per MB for Basic pools. Default: 10.
+    pub basic_per_mb: u64,
+    /// Credits per MB for Standard pools. Default: 8.
+    pub standard_per_mb: u64,
+    /// Credits per MB for Premium pools. Default: 6.
+    pub premium_per_mb: u64,
+    /// Credits per MB for Ultra pools. Default: 5.
+    pub ultra_per_mb: u64,
+    /// Credits per MB added for every hop the traffic crosses. Default: 1.
+    pub per_hop_per_mb: u64,
+}
+
+impl Default for PricingRules {
+    fn default() -> Self {
+        Self {
+            free_per_mb: 0,
+            basic_per_mb: 10,
+            standard_per_mb: 8,
+            premium_per_mb: 6,
+            ultra_per_mb: 5,
+            per_hop_per_mb: 1,
+        }
+    }
+}
+
+impl PricingRules {
+    /// Per-MB rate of `tier` (None = free)
+    pub fn per_mb(&self, tier: Option<SubscriptionTier>) -> u64 {
+        match tier {
+            None => self.free_per_mb,
+            Some(SubscriptionTier::Basic) => self.basic_per_mb,
+            Some(SubscriptionTier::Standard) => self.standard_per_mb,
+            Some(SubscriptionTier::Premium) => self.premium_per_mb,
+            Some(SubscriptionTier::Ultra) => self.ultra_per_mb,
+        }
+    }
+
+    /// Credits for `bytes` of `tier` traffic over `hops` hops, rounded up
+    pub fn price(&self, tier: Option<SubscriptionTier>, bytes: u64, hops: u8) -> u64 {
+        let rate = self.per_mb(tier).saturating_add(self.per_hop_per_mb.saturating_mul(hops as u64));
+        let credits = (bytes as u128 * rate as u128).div_ceil(BYTES_PER_MB as u128);
+        credits.min(u64::MAX as u128) as u64
+    }
+}
+
+/// One priced request (relayed shards are only totalled per pool)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CreditEntry {
+    pub role: CreditRole,
+    pub request_id: Id,
+    pub pool: PublicKey,
+    pub tier: Option<SubscriptionTier>,
+    pub bytes: u64,
+    pub hops: u8,
+    pub credits: u64,
+    /// Unix time the entry was booked
+    pub timestamp: u64,
+}
+
+/// Credits booked against one pool
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolCredits {
+    /// Bytes forwarded or served for the pool since its last reconciliation
+    pub pending_bytes: u64,
+    /// Provisional credits for `pending_bytes`
+    pub pending_credits: u64,
+    /// Credits confirmed by posted distributions
+    pub settled_credits: u64,
+    /// Bytes this node sent billed to the pool
+    pub spent_bytes: u64,
+    pub spent_credits: u64,
+    /// Root of the last distribution reconciled against
+    pub distribution_root: Option<[u8; 32]>,
+}
+
+/// Ledger totals
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CreditTotals {
+    /// Credits earned (relay and exit, after adjustments)
+    pub earned: u64,
+    /// Credits spent as a client
+    pub spent: u64,
+    /// Credits booked for relayed shards
+    pub relay_earned: u64,
+    /// Credits booked for served requests
+    pub exit_earned: u64,
+    /// Net reconciliation adjustments
+    pub adjustment: i64,
+    /// Pools with earnings not yet reconciled
+    pub unreconciled_pools: usize,
+}
+
+/// Outcome of reconciling a pool against its posted distribution
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reconciliation {
+    pub pool: PublicKey,
+    /// Bytes booked since the previous reconciliation
+    pub booked_bytes: u64,
+    /// Bytes the distribution pays this node for
+    pub distributed_bytes: u64,
+    /// Credits added (negative: removed) to match the distribution
+    pub adjustment: i64,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct LedgerFile {
+    pools: Vec<(PublicKey, PoolCredits)>,
+    relay_earned: u64,
+    exit_earned: u64,
+    spent: u64,
+    adjustment: i64,
+    recent: Vec<CreditEntry>,
+}
+
+/// Debits and credits of one node, optionally persisted as JSON
+pub struct CreditLedger {
+    pricing: PricingRules,
+    pools: HashMap<PublicKey, PoolCredits>,
+    relay_earned: u64,
+    exit_earned: u64,
+    spent: u64,
+    adjustment: i64,
+    recent: VecDeque<CreditEntry>,
+    path: Option<PathBuf>,
+    dirty: bool,
+    clock: SharedClock,
+}
+
+impl std::fmt::Debug for CreditLedger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CreditLedger")
+            .field("pricing", &self.pricing)
+            .field("pools", &self.pools)
+            .field("relay_earned", &self.relay_earned)
+            .field("exit_earned", &self.exit_earned)
+            .field("spent", &self.spent)
+            .field("adjustment", &self.adjustment)
+            .field("recent", &self.recent)
+            .field("path", &self.path)
+            .field("dirty", &self.dirty)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CreditLedger {
+    /// Empty ledger, saved to `path` (None = in memory only)
+    pub fn new(pricing: PricingRules, path: Option<PathBuf>) -> Self {
+        Self {
+            pricing,
+            pools: HashMap::new(),
+            relay_earned: 0,
+            exit_earned: 0,
+            spent: 0,
+            adjustment: 0,
+            recent: VecDeque::new(),
+            path,
+            dirty: false,
+            clock: system_clock(),
+        }
+    }
+
+    /// Timestamp entries by `clock` instead of the system clock
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+    }
+
+    /// Load the ledger saved at `path`; a missing file gives an empty ledger
+    pub fn load(pricing: PricingRules, path: PathBuf) -> io::Result<Self> {
+        let mut ledger = Self::new(pricing, Some(path.clone()));
+        let data = match std::fs::read(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(ledger),
+            Err(e) => return Err(e),
+        };
+        let file: LedgerFile = serde_json::from_slice(&data)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        ledger.pools = file.pools.into_iter().collect();
+        ledger.relay_earned = file.relay_earned;
+        ledger.exit_earned = file.exit_earned;
+        ledger.spent = file.spent;
+        ledger.adjustment = file.adjustment;
+        ledger.recent = file.recent.into();
+        Ok(ledger)
+    }
+
+    /// Persist the ledger (write to tmp, then rename). No-op without a path.
+    pub fn save(&mut self) -> io::Result<()> {
+        let Some(ref path) = self.path else { return Ok(()) };
+        let file = LedgerFile {
+            pools: self.pools.iter().map(|(pool, credits)| (*pool, credits.clone())).collect(),
+            relay_earned: self.relay_earned,
+            exit_earned: self.exit_earned,
+            spent: self.spent,
+            adjustment: self.adjustment,
+            recent: self.recent.iter().cloned().collect(),
+        };
+        write_atomic(path, &serde_json::to_vec(&file).map_err(io::Error::other)?)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Changed since the last save
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn pricing(&self) -> &PricingRules {
+        &self.pricing
+    }
+
+    /// Book a request sent over `hops` hops (relays and exit) as a debit
+    pub fn record_spent(
+        &mut self,
+        request_id: Id,
+        pool: PublicKey,
+        tier: Option<SubscriptionTier>,
+        bytes: u64,
+        hops: u8,
+    ) -> u64 {
+        let credits = self.pricing.price(tier, bytes, hops);
+        let account = self.pools.entry(pool).or_default();
+        account.spent_bytes += bytes;
+        account.spent_credits += credits;
+        self.spent += credits;
+        self.log(CreditRole::Client, request_id, pool, tier, bytes, hops, credits);
+        credits
+    }
+
+    /// Book a request served as exit (one hop) as a credit
+    pub fn record_served(&mut self, request_id: Id, pool: PublicKey, tier: Option<SubscriptionTier>, bytes: u64) -> u64 {
+        let credits = self.earn(pool, tier, bytes);
+        self.exit_earned += credits;
+        self.log(CreditRole::Exit, request_id, pool, tier, bytes, 1, credits);
+        credits
+    }
+
+    /// Book a relayed shard (one hop) as a credit
+    pub fn record_relayed(&mut self, pool: PublicKey, tier: Option<SubscriptionTier>, bytes: u64) -> u64 {
+        let credits = self.earn(pool, tier, bytes);
+        self.relay_earned += credits;
+        credits
+    }
+
+    fn earn(&mut self, pool: PublicKey, tier: Option<SubscriptionTier>, bytes: u64) -> u64 {
+        let credits = self.pricing.price(tier, bytes, 1);
+        let account = self.pools.entry(pool).or_default();
+        account.pending_bytes += bytes;
+        account.pending_credits += credits;
+        self.dirty = true;
+        credits
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn log(
+        &mut self,
+        role: CreditRole,
+        request_id: Id,
+        pool: PublicKey,
+        tier: Option<SubscriptionTier>,
+        bytes: u64,
+        hops: u8,
+        credits: u64,
+    ) {
+        if self.recent.len() >= MAX_RECENT_ENTRIES {
+            self.recent.pop_front();
+        }
+        let timestamp = self.clock.unix_now();
+        self.recent.push_back(CreditEntry { role, request_id, pool, tier, bytes, hops, credits, timestamp });
+        self.dirty = true;
+    }
+
+    /// Settle `pool`'s pending earnings against its posted distribution
+    /// (`root`), which pays this node for `distributed_bytes` at `tier`.
+    ///
+    /// Returns None if the pool was already reconciled against `root`.
+    pub fn reconcile(
+        &mut self,
+        pool: PublicKey,
+        root: [u8; 32],
+        distributed_bytes: u64,
+        tier: Option<SubscriptionTier>,
+    ) -> Option<Reconciliation> {
+        let account = self.pools.entry(pool).or_default();
+        if account.distribution_root == Some(root) {
+            return None;
+        }
+        let settled = self.pricing.price(tier, distributed_bytes, 1);
+        let adjustment = settled as i64 - account.pending_credits as i64;
+        let reconciliation = Reconciliation {
+            pool,
+            booked_bytes: account.pending_bytes,
+            distributed_bytes,
+            adjustment,
+        };
+        account.settled_credits += settled;
+        account.pending_bytes = 0;
+        account.pending_credits = 0;
+        account.distribution_root = Some(root);
+        self.adjustment += adjustment;
+        self.dirty = true;
+        Some(reconciliation)
+    }
+
+    pub fn totals(&self) -> CreditTotals {
+        let booked = (self.relay_earned + self.exit_earned) as i64;
+        CreditTotals {
+            earned: (booked + self.adjustment).max(0) as u64,
+            spent: self.spent,
+            relay_earned: self.relay_earned,
+            exit_earned: self.exit_earned,
+            adjustment: self.adjustment,
+            unreconciled_pools: self.pools.values().filter(|p| p.pending_bytes > 0).count(),
+        }
+    }
+
+    /// Credits booked against `pool`
+    pub fn pool(&self, pool: &PublicKey) -> Option<&PoolCredits> {
+        self.pools.get(pool)
+    }
+
+    /// All pools with booked credits
+    pub fn pools(&self) -> impl Iterator<Item = (&PublicKey, &PoolCredits)> {
+        self.pools.iter()
+    }
+
+    /// The latest `limit` per-request entries, newest first
+    pub fn recent(&self, limit: usize) -> Vec<CreditEntry> {
+        self.recent.iter().rev().take(limit).cloned().collect()
+    }
+}
+
+fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+    let tmp_path = path.with_extension("json.tmp");
+    std::fs::write(&tmp_path, data)?;
+    std::fs::rename(&tmp_path, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pricing() {
+        let pricing = PricingRules::default();
+        assert_eq!(pricing.price(Some(SubscriptionTier::Basic), BYTES_PER_MB, 0), 10);
+        // Two hops add the per-hop rate twice
+        assert_eq!(pricing.price(Some(SubscriptionTier::Basic), 2 * BYTES_PER_MB, 2), 24);
+        // Partial megabytes round up
+        assert_eq!(pricing.price(None, 1, 1), 1);
+        assert_eq!(pricing.price(None, 0, 3), 0);
+    }
+
+    #[test]
+    fn test_reconcile_adjusts_earnings() {
+        let mut ledger = CreditLedger::new(PricingRules::default(), None);
+        let tier = Some(SubscriptionTier::Standard);
+        ledger.record_relayed([1; 32], tier, 10 * BYTES_PER_MB);
+        ledger.record_served([2; 32], [1; 32], tier, 2 * BYTES_PER_MB);
+        ledger.record_spent([3; 32], [9; 32], None, BYTES_PER_MB, 3);
+        assert_eq!(ledger.totals().earned, 12 * 9);
+        assert_eq!(ledger.totals().spent, 3);
+        assert_eq!(ledger.totals().unreconciled_pools, 1);
+
+        // The distribution only pays for 10 of the 12 MB
+        let rec = ledger.reconcile([1; 32], [7; 32], 10 * BYTES_PER_MB, tier).unwrap();
+        assert_eq!(rec.booked_bytes, 12 * BYTES_PER_MB);
+        assert_eq!(rec.adjustment, -18);
+        assert!(ledger.reconcile([1; 32], [7; 32], 10 * BYTES_PER_MB, tier).is_none());
+
+        let totals = ledger.totals();
+        assert_eq!(totals.earned, 90);
+        assert_eq!(totals.unreconciled_pools, 0);
+        assert_eq!(ledger.pool(&[1; 32]).unwrap().settled_credits, 90);
+        assert_eq!(ledger.recent(10).len(), 2);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!("credit-ledger-{}.json", hex::encode(rand::random::<[u8; 8]>())));
+        let mut ledger = CreditLedger::new(PricingRules::default(), Some(path.clone()));
+        ledger.record_served([2; 32], [1; 32], Some(SubscriptionTier::Basic), BYTES_PER_MB);
+        assert!(ledger.is_dirty());
+        ledger.save().unwrap();
+
+        let loaded = CreditLedger::load(PricingRules::default(), path.clone()).unwrap();
+        assert_eq!(loaded.totals(), ledger.totals());
+        assert_eq!(loaded.recent(1), ledger.recent(1));
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(CreditLedger::load(PricingRules::default(), path).unwrap().totals(), CreditTotals::default());
+    }
+}
@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -58,10 +59,187 @@ pub enum CraftNetError {
 
     #[error("Timeout")]
     Timeout,
+
+    #[error("GeoIP database error: {0}")]
+    GeoIpDatabase(String),
+
+    #[error("Invalid quota token: {0}")]
+    InvalidQuotaToken(String),
+
+    #[error("Release rejected: {0}")]
+    InvalidRelease(String),
 }
 
 pub type Result<T> = std::result::Result<T, CraftNetError>;
 
+/// Machine-readable error code shared by every CraftNet error type.
+///
+/// Frontends get these over IPC (`error.data.code`) and FFI so they can
+/// branch on the kind of failure instead of parsing messages. Serialized in
+/// SCREAMING_SNAKE_CASE (`INSUFFICIENT_CREDITS`); new codes may be added, so
+/// clients should treat unknown codes like `INTERNAL`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    /// Bug or unexpected state; the message has details
+    Internal,
+    /// Malformed or unacceptable request parameters
+    InvalidRequest,
+    /// The node is not connected to the network
+    NotConnected,
+    /// The node (or daemon service) is not running
+    NotRunning,
+    /// The node is already running
+    AlreadyRunning,
+    /// Could not join the network (no bootstrap peer reachable)
+    ConnectionFailed,
+    /// No bootstrap or known peers to connect to
+    NoPeers,
+    /// A specific peer could not be reached
+    PeerUnreachable,
+    /// Transport-level failure
+    NetworkError,
+    /// No response within the timeout
+    Timeout,
+    /// The request could not be sent or was rejected along the way
+    RequestFailed,
+    /// No exit nodes known (or none in the requested region)
+    NoExitNodes,
+    /// The selected exit cannot be reached
+    ExitUnreachable,
+    /// The exit refused the request for load or quota reasons
+    RateLimited,
+    /// The exit refuses to connect to this destination
+    BlockedDestination,
+    /// The destination server failed or could not be reached from the exit
+    UpstreamError,
+    /// The response exceeds the exit's size limit
+    ResponseTooLarge,
+    /// The request exceeds the exit's size limit
+    RequestTooLarge,
+    /// The response could not be reconstructed or decoded
+    InvalidResponse,
+    /// The exit's signature over the response did not verify
+    ResponseTampered,
+    /// Signing, encryption or decryption failed
+    CryptoError,
+    /// Not enough credits for the request
+    InsufficientCredits,
+    /// The settlement RPC endpoint is unreachable or failing
+    SettlementUnavailable,
+    /// A settlement transaction or verification failed
+    SettlementError,
+    /// No subscription for this user
+    SubscriptionNotFound,
+    /// Not allowed to perform this operation
+    NotAuthorized,
+    /// Local I/O failure (files, sockets)
+    IoError,
+}
+
+impl ErrorCode {
+    /// All codes, in declaration order
+    pub const ALL: [ErrorCode; 27] = [
+        Self::Internal,
+        Self::InvalidRequest,
+        Self::NotConnected,
+        Self::NotRunning,
+        Self::AlreadyRunning,
+        Self::ConnectionFailed,
+        Self::NoPeers,
+        Self::PeerUnreachable,
+        Self::NetworkError,
+        Self::Timeout,
+        Self::RequestFailed,
+        Self::NoExitNodes,
+        Self::ExitUnreachable,
+        Self::RateLimited,
+        Self::BlockedDestination,
+        Self::UpstreamError,
+        Self::ResponseTooLarge,
+        Self::RequestTooLarge,
+        Self::InvalidResponse,
+        Self::ResponseTampered,
+        Self::CryptoError,
+        Self::InsufficientCredits,
+        Self::SettlementUnavailable,
+        Self::SettlementError,
+        Self::SubscriptionNotFound,
+        Self::NotAuthorized,
+        Self::IoError,
+    ];
+
+    /// Wire name of the code (`"EXIT_UNREACHABLE"`)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Internal => "INTERNAL",
+            Self::InvalidRequest => "INVALID_REQUEST",
+            Self::NotConnected => "NOT_CONNECTED",
+            Self::NotRunning => "NOT_RUNNING",
+            Self::AlreadyRunning => "ALREADY_RUNNING",
+            Self::ConnectionFailed => "CONNECTION_FAILED",
+            Self::NoPeers => "NO_PEERS",
+            Self::PeerUnreachable => "PEER_UNREACHABLE",
+            Self::NetworkError => "NETWORK_ERROR",
+            Self::Timeout => "TIMEOUT",
+            Self::RequestFailed => "REQUEST_FAILED",
+            Self::NoExitNodes => "NO_EXIT_NODES",
+            Self::ExitUnreachable => "EXIT_UNREACHABLE",
+            Self::RateLimited => "RATE_LIMITED",
+            Self::BlockedDestination => "BLOCKED_DESTINATION",
+            Self::UpstreamError => "UPSTREAM_ERROR",
+            Self::ResponseTooLarge => "RESPONSE_TOO_LARGE",
+            Self::RequestTooLarge => "REQUEST_TOO_LARGE",
+            Self::InvalidResponse => "INVALID_RESPONSE",
+            Self::ResponseTampered => "RESPONSE_TAMPERED",
+            Self::CryptoError => "CRYPTO_ERROR",
+            Self::InsufficientCredits => "INSUFFICIENT_CREDITS",
+            Self::SettlementUnavailable => "SETTLEMENT_UNAVAILABLE",
+            Self::SettlementError => "SETTLEMENT_ERROR",
+            Self::SubscriptionNotFound => "SUBSCRIPTION_NOT_FOUND",
+            Self::NotAuthorized => "NOT_AUTHORIZED",
+            Self::IoError => "IO_ERROR",
+        }
+    }
+
+    /// Parse a wire name; `None` for unknown codes
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|code| code.as_str() == name)
+    }
+}
+
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl CraftNetError {
+    /// Machine-readable code of this error
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::DestinationMismatch
+            | Self::InvalidChainSignature(_)
+            | Self::ChainVerificationFailed(_)
+            | Self::InvalidPublicKey
+            | Self::InvalidSignature
+            | Self::EncryptionFailed(_)
+            | Self::DecryptionFailed(_) => ErrorCode::CryptoError,
+            Self::InsufficientShards { .. } | Self::ShardReconstructionFailed(_) => ErrorCode::InvalidResponse,
+            Self::InvalidCreditSecret | Self::CreditExpired => ErrorCode::InsufficientCredits,
+            Self::RequestNotFound(_) | Self::RequestAlreadySettled | Self::RequestNotPending => ErrorCode::InvalidRequest,
+            Self::NetworkError(_) => ErrorCode::NetworkError,
+            Self::PeerNotFound(_) => ErrorCode::PeerUnreachable,
+            Self::SettlementError(_) => ErrorCode::SettlementError,
+            Self::SerializationError(_) => ErrorCode::Internal,
+            Self::Timeout => ErrorCode::Timeout,
+            Self::GeoIpDatabase(_) => ErrorCode::IoError,
+            Self::InvalidQuotaToken(_) => ErrorCode::CryptoError,
+            Self::InvalidRelease(_) => ErrorCode::InvalidResponse,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -201,6 +379,25 @@ mod tests {
         assert_eq!(val, 42);
     }
 
+    #[test]
+    fn test_error_code_names() {
+        for code in ErrorCode::ALL {
+            let json = serde_json::to_string(&code).unwrap();
+            assert_eq!(json, format!("\"{}\"", code.as_str()));
+            assert_eq!(serde_json::from_str::<ErrorCode>(&json).unwrap(), code);
+            assert_eq!(ErrorCode::from_name(code.as_str()), Some(code));
+        }
+        assert_eq!(ErrorCode::InsufficientCredits.to_string(), "INSUFFICIENT_CREDITS");
+        assert_eq!(ErrorCode::from_name("NOT_A_CODE"), None);
+    }
+
+    #[test]
+    fn test_error_code_mapping() {
+        assert_eq!(CraftNetError::Timeout.code(), ErrorCode::Timeout);
+        assert_eq!(CraftNetError::CreditExpired.code(), ErrorCode::InsufficientCredits);
+        assert_eq!(CraftNetError::PeerNotFound("p".to_string()).code(), ErrorCode::PeerUnreachable);
+    }
+
     #[test]
     fn test_result_type_err() {
         let result: Result<i32> = Err(CraftNetError::Timeout);
@@ -0,0 +1,77 @@
+//! Structured exit errors
+//!
+//! An exit that refuses a request for a reason the client can act on (so
+//! far: a request or response over its size limits) answers with an error
+//! frame instead of dropping the request. The frame is an ordinary signed
+//! response carrying [`EXIT_ERROR_HEADER`], whose body is an
+//! [`ExitErrorFrame`] with the limit that was hit, so the client can adapt
+//! (send a smaller body, fetch in ranges) rather than wait for a timeout.
+
+use serde::{Deserialize, Serialize};
+
+use crate::ErrorCode;
+
+/// Response header marking an error frame; its value is the error code name
+pub const EXIT_ERROR_HEADER: &str = "x-craftnet-exit-error";
+
+/// Body of an exit's error frame (JSON)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExitErrorFrame {
+    pub code: ErrorCode,
+    pub message: String,
+    /// The size limit that was exceeded (bytes), for size errors
+    #[serde(default)]
+    pub limit: Option<u64>,
+}
+
+impl ExitErrorFrame {
+    /// Request body over the exit's limit of `limit` bytes
+    pub fn request_too_large(limit: u64, message: impl Into<String>) -> Self {
+        Self { code: ErrorCode::RequestTooLarge, message: message.into(), limit: Some(limit) }
+    }
+
+    /// Response body over the exit's limit of `limit` bytes
+    pub fn response_too_large(limit: u64, message: impl Into<String>) -> Self {
+        Self { code: ErrorCode::ResponseTooLarge, message: message.into(), limit: Some(limit) }
+    }
+
+    /// HTTP status the frame is sent with
+    pub fn status(&self) -> u16 {
+        match self.code {
+            ErrorCode::RequestTooLarge => 413,
+            ErrorCode::RateLimited => 429,
+            ErrorCode::BlockedDestination => 403,
+            _ => 502,
+        }
+    }
+
+    /// Serialize to bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap_or_default()
+    }
+
+    /// Deserialize from bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, serde_json::Error> {
+        serde_json::from_slice(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_frame_roundtrip() {
+        let frame = ExitErrorFrame::request_too_large(1024, "request of 2048 bytes exceeds 1024 byte limit");
+        assert_eq!(frame.status(), 413);
+        let bytes = frame.to_bytes();
+        assert!(String::from_utf8_lossy(&bytes).contains("\"REQUEST_TOO_LARGE\""));
+        assert_eq!(ExitErrorFrame::from_bytes(&bytes).unwrap(), frame);
+
+        // Frames without a limit parse too
+        let frame = ExitErrorFrame::from_bytes(br#"{"code":"RESPONSE_TOO_LARGE","message":"x"}"#).unwrap();
+        assert_eq!(frame.code, ErrorCode::ResponseTooLarge);
+        assert_eq!(frame.limit, None);
+        assert_eq!(frame.status(), 502);
+    }
+}
@@ -1,8 +1,18 @@
 //! Geo-location detection for nodes
 //!
-//! Provides auto-detection of node location for announcement to the network.
+//! Provides auto-detection of node location for announcement to the network,
+//! and optional offline lookups of the country and Autonomous System of an
+//! IP address from a GeoIP database ([`GeoIpDatabase`]): an ipinfo CSV
+//! export, or a MaxMind `.mmdb` file with the `maxmind` feature. Nodes
+//! without a database simply report no ASN.
+
+use std::io::BufRead;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 use serde::{Deserialize, Serialize};
+use crate::error::{CraftNetError, Result};
 use crate::types::ExitRegion;
 
 /// Detected location information
@@ -96,6 +106,21 @@ pub fn country_to_region(country_code: &str) -> ExitRegion {
     }
 }
 
+/// Canonical form of an Autonomous System string, for comparing hops.
+///
+/// ip-api reports `"AS15169 Google LLC"`; nodes may also announce a bare
+/// `"15169"`. Both normalize to `"AS15169"`. Returns None when no AS
+/// number can be read.
+pub fn normalize_asn(as_info: &str) -> Option<String> {
+    let token = as_info.split_whitespace().next()?;
+    let digits = token
+        .strip_prefix("AS")
+        .or_else(|| token.strip_prefix("as"))
+        .unwrap_or(token);
+    let number: u32 = digits.parse().ok()?;
+    Some(format!("AS{}", number))
+}
+
 /// Response from ip-api.com (free tier)
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -176,6 +201,227 @@ impl Default for GeoDetector {
     }
 }
 
+/// Country and Autonomous System of an IP address
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GeoIpRecord {
+    /// Country code (ISO 3166-1 alpha-2)
+    pub country_code: Option<String>,
+    /// Normalized AS number (`"AS15169"`)
+    pub as_number: Option<String>,
+    /// AS organization name
+    pub as_name: Option<String>,
+}
+
+/// An IP address as a u128 (IPv4 as IPv4-mapped IPv6)
+fn ip_key(ip: IpAddr) -> u128 {
+    match ip {
+        IpAddr::V4(v4) => u128::from(v4.to_ipv6_mapped()),
+        IpAddr::V6(v6) => u128::from(v6),
+    }
+}
+
+/// First and last address of a CIDR block (`1.2.3.0/24`)
+fn cidr_range(cidr: &str) -> Option<(u128, u128)> {
+    let (ip, prefix) = cidr.split_once('/')?;
+    let ip: IpAddr = ip.parse().ok()?;
+    let prefix: u32 = prefix.parse().ok()?;
+    let bits = match ip {
+        IpAddr::V4(_) if prefix <= 32 => prefix + 96,
+        IpAddr::V6(_) if prefix <= 128 => prefix,
+        _ => return None,
+    };
+    let mask = if bits == 0 { 0 } else { u128::MAX << (128 - bits) };
+    let start = ip_key(ip) & mask;
+    Some((start, start | !mask))
+}
+
+/// Split one CSV line, honouring double-quoted fields
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+enum GeoIpBackend {
+    /// Sorted, non-overlapping `(first, last, record)` ranges
+    Ranges(Vec<(u128, u128, GeoIpRecord)>),
+    #[cfg(feature = "maxmind")]
+    MaxMind(maxminddb::Reader<Vec<u8>>),
+}
+
+/// Offline IP → country/ASN lookups
+pub struct GeoIpDatabase {
+    backend: GeoIpBackend,
+}
+
+impl std::fmt::Debug for GeoIpDatabase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.backend {
+            GeoIpBackend::Ranges(ranges) => write!(f, "GeoIpDatabase(ipinfo, {} ranges)", ranges.len()),
+            #[cfg(feature = "maxmind")]
+            GeoIpBackend::MaxMind(reader) => write!(f, "GeoIpDatabase({})", reader.metadata.database_type),
+        }
+    }
+}
+
+impl GeoIpDatabase {
+    /// Load a database file: MaxMind for `.mmdb` (needs the `maxmind`
+    /// feature), an ipinfo CSV export otherwise
+    pub fn open(path: &Path) -> Result<Self> {
+        let geoip_err = |e: &dyn std::fmt::Display| CraftNetError::GeoIpDatabase(format!("{}: {}", path.display(), e));
+        if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("mmdb")) {
+            #[cfg(feature = "maxmind")]
+            {
+                let reader = maxminddb::Reader::open_readfile(path).map_err(|e| geoip_err(&e))?;
+                return Ok(Self { backend: GeoIpBackend::MaxMind(reader) });
+            }
+            #[cfg(not(feature = "maxmind"))]
+            return Err(geoip_err(&"MaxMind databases need the `maxmind` feature"));
+        }
+        let file = std::fs::File::open(path).map_err(|e| geoip_err(&e))?;
+        Self::from_ipinfo_csv(std::io::BufReader::new(file)).map_err(|e| geoip_err(&e))
+    }
+
+    /// Parse an ipinfo CSV export (`country_asn.csv`, `asn.csv` or
+    /// `country.csv`). Ranges are given by `start_ip`/`end_ip` or a
+    /// `network` CIDR; country and AS columns are optional.
+    pub fn from_ipinfo_csv<R: BufRead>(reader: R) -> Result<Self> {
+        let malformed = |line: usize, what: &str| CraftNetError::GeoIpDatabase(format!("line {}: {}", line, what));
+        let mut lines = reader.lines();
+        let header = match lines.next() {
+            Some(line) => csv_fields(&line.map_err(|e| CraftNetError::GeoIpDatabase(e.to_string()))?),
+            None => return Err(malformed(1, "missing header")),
+        };
+        let column = |name: &str| header.iter().position(|h| h.trim() == name);
+        let (start, end, network) = (column("start_ip"), column("end_ip"), column("network"));
+        let country = column("country");
+        let asn = column("asn");
+        let as_name = column("as_name").or_else(|| column("name"));
+        if network.is_none() && (start.is_none() || end.is_none()) {
+            return Err(malformed(1, "no start_ip/end_ip or network column"));
+        }
+
+        let mut ranges = Vec::new();
+        for (i, line) in lines.enumerate() {
+            let line = line.map_err(|e| CraftNetError::GeoIpDatabase(e.to_string()))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let fields = csv_fields(&line);
+            let field = |col: Option<usize>| {
+                col.and_then(|c| fields.get(c)).map(|f| f.trim()).filter(|f| !f.is_empty())
+            };
+            let range = match network {
+                Some(_) => field(network).and_then(cidr_range),
+                None => field(start)
+                    .zip(field(end))
+                    .and_then(|(s, e)| Some((ip_key(s.parse().ok()?), ip_key(e.parse().ok()?)))),
+            };
+            let Some((first, last)) = range.filter(|(first, last)| first <= last) else {
+                return Err(malformed(i + 2, "invalid IP range"));
+            };
+            let record = GeoIpRecord {
+                country_code: field(country).map(str::to_ascii_uppercase),
+                as_number: field(asn).and_then(normalize_asn),
+                as_name: field(as_name).map(str::to_string),
+            };
+            ranges.push((first, last, record));
+        }
+        ranges.sort_by_key(|(first, _, _)| *first);
+        Ok(Self { backend: GeoIpBackend::Ranges(ranges) })
+    }
+
+    /// Country and AS of `ip`, if the database covers it
+    pub fn lookup(&self, ip: IpAddr) -> Option<GeoIpRecord> {
+        match &self.backend {
+            GeoIpBackend::Ranges(ranges) => {
+                let key = ip_key(ip);
+                let idx = ranges.partition_point(|(first, _, _)| *first <= key).checked_sub(1)?;
+                let (_, last, record) = &ranges[idx];
+                (key <= *last).then(|| record.clone())
+            }
+            #[cfg(feature = "maxmind")]
+            GeoIpBackend::MaxMind(reader) => {
+                use maxminddb::geoip2;
+                // ASN and country databases are separate; take what this one has
+                let asn = reader.lookup::<geoip2::Asn>(ip).ok();
+                let country = reader
+                    .lookup::<geoip2::Country>(ip)
+                    .ok()
+                    .and_then(|c| c.country)
+                    .and_then(|c| c.iso_code)
+                    .map(str::to_string);
+                let record = GeoIpRecord {
+                    country_code: country,
+                    as_number: asn.as_ref().and_then(|a| a.autonomous_system_number).map(|n| format!("AS{}", n)),
+                    as_name: asn.and_then(|a| a.autonomous_system_organization).map(str::to_string),
+                };
+                (record != GeoIpRecord::default()).then_some(record)
+            }
+        }
+    }
+}
+
+/// A GeoIP database file, reloaded when it changes on disk.
+///
+/// Nodes are built and run without one: until a file loads, lookups
+/// return None.
+#[derive(Debug)]
+pub struct GeoIpResolver {
+    path: PathBuf,
+    database: Option<GeoIpDatabase>,
+    /// Modification time of the loaded file
+    loaded_modified: Option<SystemTime>,
+}
+
+impl GeoIpResolver {
+    /// A resolver for `path`; nothing is loaded until [`Self::refresh`]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into(), database: None, loaded_modified: None }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.database.is_some()
+    }
+
+    /// (Re)load the file if it changed since the last load. Returns true
+    /// if a new database was loaded; on error the previous one stays.
+    pub fn refresh(&mut self) -> Result<bool> {
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|m| m.modified())
+            .map_err(|e| CraftNetError::GeoIpDatabase(format!("{}: {}", self.path.display(), e)))?;
+        if self.database.is_some() && self.loaded_modified == Some(modified) {
+            return Ok(false);
+        }
+        self.database = Some(GeoIpDatabase::open(&self.path)?);
+        self.loaded_modified = Some(modified);
+        Ok(true)
+    }
+
+    /// Country and AS of `ip` (None without a loaded database)
+    pub fn lookup(&self, ip: IpAddr) -> Option<GeoIpRecord> {
+        self.database.as_ref()?.lookup(ip)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -219,6 +465,15 @@ mod tests {
         assert_eq!(country_to_region("Us"), ExitRegion::NorthAmerica);
     }
 
+    #[test]
+    fn test_normalize_asn() {
+        assert_eq!(normalize_asn("AS15169 Google LLC"), Some("AS15169".to_string()));
+        assert_eq!(normalize_asn("15169"), Some("AS15169".to_string()));
+        assert_eq!(normalize_asn("as3320"), Some("AS3320".to_string()));
+        assert_eq!(normalize_asn("Google LLC"), None);
+        assert_eq!(normalize_asn(""), None);
+    }
+
     #[test]
     fn test_geo_location_new() {
         let loc = GeoLocation::new(
@@ -275,6 +530,44 @@ mod tests {
         assert_eq!(loc.city, Some("Frankfurt am Main".to_string()));
     }
 
+    const IPINFO_CSV: &str = "start_ip,end_ip,country,country_name,continent,continent_name,asn,as_name,as_domain
+1.0.0.0,1.0.0.255,AU,Australia,OC,Oceania,AS13335,\"Cloudflare, Inc.\",cloudflare.com
+8.8.8.0,8.8.8.255,US,United States,NA,North America,AS15169,Google LLC,google.com
+2001:4860::,2001:4860:ffff:ffff:ffff:ffff:ffff:ffff,US,United States,NA,North America,AS15169,Google LLC,google.com
+";
+
+    #[test]
+    fn test_geoip_ipinfo_csv() {
+        let db = GeoIpDatabase::from_ipinfo_csv(IPINFO_CSV.as_bytes()).unwrap();
+        let google = db.lookup("8.8.8.8".parse().unwrap()).unwrap();
+        assert_eq!(google.country_code.as_deref(), Some("US"));
+        assert_eq!(google.as_number.as_deref(), Some("AS15169"));
+        let cloudflare = db.lookup("1.0.0.1".parse().unwrap()).unwrap();
+        assert_eq!(cloudflare.as_name.as_deref(), Some("Cloudflare, Inc."));
+        assert_eq!(db.lookup("2001:4860::8888".parse().unwrap()).unwrap().as_number.as_deref(), Some("AS15169"));
+        assert!(db.lookup("8.8.9.1".parse().unwrap()).is_none());
+        assert!(db.lookup("0.0.0.1".parse().unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_geoip_network_column() {
+        let csv = "network,asn,name\n10.1.0.0/16,64512,Example\n";
+        let db = GeoIpDatabase::from_ipinfo_csv(csv.as_bytes()).unwrap();
+        assert_eq!(db.lookup("10.1.200.3".parse().unwrap()).unwrap().as_number.as_deref(), Some("AS64512"));
+        assert!(db.lookup("10.2.0.1".parse().unwrap()).is_none());
+
+        assert!(GeoIpDatabase::from_ipinfo_csv("country,asn\n".as_bytes()).is_err());
+        assert!(GeoIpDatabase::from_ipinfo_csv("network,asn\nnot-a-cidr,1\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_geoip_resolver_without_database() {
+        let mut resolver = GeoIpResolver::new("/nonexistent/geoip.csv");
+        assert!(resolver.refresh().is_err());
+        assert!(!resolver.is_loaded());
+        assert!(resolver.lookup("8.8.8.8".parse().unwrap()).is_none());
+    }
+
     #[test]
     fn test_parse_ip_api_response_failure() {
         let mut detector = GeoDetector::new();
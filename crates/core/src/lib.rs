@@ -2,24 +2,54 @@
 //!
 //! This crate defines the fundamental data structures used throughout CraftNet.
 
+mod batch;
+mod build_manifest;
+mod chain_ack;
+mod clock;
+mod compression;
+mod credits;
 mod error;
+mod exit_error;
 mod geo;
 pub mod lease_set;
 mod onion;
+mod onion_keys;
+mod quota;
 mod shard;
+mod split_tunnel;
+mod stream;
+mod trace;
 mod tunnel;
+mod update;
+mod websocket;
 pub mod config;
 mod types;
 pub mod receipt_crypto;
 pub mod onion_crypto;
+pub mod peer_binding;
 
+pub use batch::*;
+pub use build_manifest::*;
+pub use chain_ack::*;
+pub use clock::*;
+pub use compression::*;
+pub use credits::*;
 pub use error::*;
+pub use exit_error::*;
 pub use geo::*;
 pub use lease_set::{LeaseSet, Lease};
 pub use onion::*;
+pub use onion_keys::*;
+pub use quota::*;
 pub use shard::*;
+pub use split_tunnel::*;
+pub use stream::*;
+pub use trace::*;
 pub use tunnel::*;
+pub use update::*;
+pub use websocket::*;
 pub use types::*;
 
 pub use receipt_crypto::*;
 pub use onion_crypto::*;
+pub use peer_binding::*;
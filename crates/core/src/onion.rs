@@ -6,6 +6,7 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::chain_ack::ChainAckRequest;
 use crate::types::{Id, PublicKey};
 
 /// Decrypted onion layer revealed when a relay peels one encryption layer
@@ -35,6 +36,18 @@ pub struct OnionSettlement {
     /// Ephemeral subscription pubkey identifying the user's pool PDA.
     /// [0u8; 32] for free-tier (no subscription).
     pub pool_pubkey: PublicKey,
+    /// Cover traffic: the relay acks and drops the shard without signing a
+    /// ForwardReceipt, so dummy shards never earn credits.
+    #[serde(default)]
+    pub cover: bool,
+    /// Client's trace key for a diagnostic request: the relay appends its
+    /// sealed timing to the shard (see `trace`). None for normal traffic.
+    #[serde(default)]
+    pub trace_pubkey: Option<[u8; 32]>,
+    /// Client's request to ack the shard back up the path (see
+    /// `chain_ack`). None when the client doesn't verify the chain.
+    #[serde(default)]
+    pub chain_ack: Option<ChainAckRequest>,
 }
 
 /// Shard type indicator (moved here from shard.rs — only visible inside encrypted payload)
@@ -60,7 +73,9 @@ pub struct ExitPayload {
     pub total_hops: u8,
     /// Request or Response
     pub shard_type: ShardType,
-    /// 0x00 HTTP, 0x01 tunnel
+    /// 0x00 HTTP, 0x01 tunnel, 0x02 HTTP with streamed response,
+    /// 0x03 coalesced HTTP requests (see `batch`), 0x04 WebSocket session
+    /// burst (see `websocket`)
     pub mode: u8,
     /// HTTP request bytes or tunnel metadata + TCP bytes
     pub data: Vec<u8>,
@@ -69,6 +84,19 @@ pub struct ExitPayload {
     /// because ECDH requires X25519 keys while user_pubkey is Ed25519.
     #[serde(default)]
     pub response_enc_pubkey: PublicKey,
+    /// Pool the request is billed to (same as the routing tag's
+    /// `pool_pubkey`). Exits with a settlement client check its
+    /// subscription before serving the request.
+    #[serde(default)]
+    pub pool_pubkey: PublicKey,
+    /// Quota tokens paying for the request at an exit that meters the
+    /// free tier (see `quota`)
+    #[serde(default)]
+    pub quota_tokens: Vec<crate::QuotaToken>,
+    /// Client's trace key for a diagnostic request: the exit returns the
+    /// request's hop timings plus its own with the response (see `trace`)
+    #[serde(default)]
+    pub trace_pubkey: Option<[u8; 32]>,
 }
 
 /// Routing tag data (encrypted for exit, per-shard)
@@ -94,6 +122,10 @@ pub struct RoutingTag {
     /// This is encrypted inside the routing tag — only the exit can see it.
     #[serde(default)]
     pub pool_pubkey: PublicKey,
+    /// Payload flags (`TAG_FLAG_ZSTD`, `TAG_FLAG_ACCEPT_ZSTD`), same on
+    /// every shard of an assembly
+    #[serde(default)]
+    pub flags: u8,
 }
 
 impl OnionLayer {
@@ -158,6 +190,9 @@ mod tests {
                 shard_id: [7u8; 32],
                 payload_size: 1024,
                 pool_pubkey: [0u8; 32],
+                cover: false,
+                trace_pubkey: None,
+                chain_ack: None,
             },
             remaining_header: vec![8, 9, 10],
             is_terminal: false,
@@ -182,6 +217,9 @@ mod tests {
                 shard_id: [0u8; 32],
                 payload_size: 0,
                 pool_pubkey: [0u8; 32],
+                cover: false,
+                trace_pubkey: None,
+                chain_ack: None,
             },
             remaining_header: vec![],
             is_terminal: true,
@@ -208,6 +246,9 @@ mod tests {
             mode: 0x01,
             data: vec![5, 6, 7],
             response_enc_pubkey: [0u8; 32],
+            pool_pubkey: [6u8; 32],
+            quota_tokens: vec![],
+            trace_pubkey: None,
         };
 
         let bytes = payload.to_bytes().unwrap();
@@ -217,6 +258,7 @@ mod tests {
         assert_eq!(restored.total_hops, 2);
         assert_eq!(restored.shard_type, ShardType::Request);
         assert_eq!(restored.mode, 0x01);
+        assert_eq!(restored.pool_pubkey, [6u8; 32]);
         assert_eq!(restored.data, vec![5, 6, 7]);
     }
 
@@ -236,6 +278,7 @@ mod tests {
             chunk_index: 1,
             total_chunks: 3,
             pool_pubkey: [99u8; 32],
+            flags: crate::TAG_FLAG_ACCEPT_ZSTD,
         };
         let bytes = tag.to_bytes().unwrap();
         let restored = RoutingTag::from_bytes(&bytes).unwrap();
@@ -245,5 +288,6 @@ mod tests {
         assert_eq!(restored.chunk_index, 1);
         assert_eq!(restored.total_chunks, 3);
         assert_eq!(restored.pool_pubkey, [99u8; 32]);
+        assert_eq!(restored.flags, crate::TAG_FLAG_ACCEPT_ZSTD);
     }
 }
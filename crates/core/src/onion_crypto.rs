@@ -173,6 +173,30 @@ pub fn encrypt_routing_tag(
     chunk_index: u16,
     total_chunks: u16,
     pool_pubkey: &PublicKey,
+) -> Result<Vec<u8>, EncryptError> {
+    encrypt_routing_tag_with_flags(
+        exit_encryption_pubkey,
+        assembly_id,
+        shard_index,
+        total_shards,
+        chunk_index,
+        total_chunks,
+        pool_pubkey,
+        0,
+    )
+}
+
+/// Encrypt a routing tag carrying payload flags (see `compression`).
+#[allow(clippy::too_many_arguments)]
+pub fn encrypt_routing_tag_with_flags(
+    recipient_encryption_pubkey: &[u8; 32],
+    assembly_id: &Id,
+    shard_index: u8,
+    total_shards: u8,
+    chunk_index: u16,
+    total_chunks: u16,
+    pool_pubkey: &PublicKey,
+    flags: u8,
 ) -> Result<Vec<u8>, EncryptError> {
     let tag = RoutingTag {
         assembly_id: *assembly_id,
@@ -181,13 +205,14 @@ pub fn encrypt_routing_tag(
         chunk_index,
         total_chunks,
         pool_pubkey: *pool_pubkey,
+        flags,
     };
     let tag_bytes = tag.to_bytes()
         .map_err(|_| EncryptError::EncryptionFailed)?;
 
     let ephemeral = EncryptionKeypair::generate();
     let ciphertext = encrypt_for_recipient(
-        exit_encryption_pubkey,
+        recipient_encryption_pubkey,
         &ephemeral.secret_key_bytes(),
         &tag_bytes,
     )?;
@@ -233,6 +258,9 @@ mod tests {
             shard_id: [idx + 100; 32],
             payload_size: 1024,
             pool_pubkey: [0u8; 32],
+            cover: false,
+            trace_pubkey: None,
+            chain_ack: None,
         }
     }
 
@@ -260,6 +288,23 @@ mod tests {
         assert_eq!(tag.chunk_index, 1);
         assert_eq!(tag.total_chunks, 3);
         assert_eq!(tag.pool_pubkey, pool_pubkey);
+        assert_eq!(tag.flags, 0);
+    }
+
+    #[test]
+    fn test_routing_tag_flags_roundtrip() {
+        let keys = EncryptionKeypair::generate();
+        let flags = crate::TAG_FLAG_ZSTD | crate::TAG_FLAG_ACCEPT_ZSTD;
+        let encrypted = encrypt_routing_tag_with_flags(
+            &keys.public_key_bytes(),
+            &[1u8; 32],
+            0, 5, 0, 1,
+            &[0u8; 32],
+            flags,
+        ).unwrap();
+
+        let tag = decrypt_routing_tag(&keys.secret_key_bytes(), &encrypted).unwrap();
+        assert_eq!(tag.flags, flags);
     }
 
     #[test]
@@ -294,6 +339,9 @@ mod tests {
             mode: 0x01,
             data: vec![5, 6, 7, 8, 9],
             response_enc_pubkey: [0u8; 32],
+            pool_pubkey: [0u8; 32],
+            quota_tokens: vec![],
+            trace_pubkey: None,
         };
 
         let encrypted = encrypt_exit_payload(
@@ -495,6 +543,9 @@ mod tests {
             mode: 0x00,
             data: vec![],
             response_enc_pubkey: [0u8; 32],
+            pool_pubkey: [0u8; 32],
+            quota_tokens: vec![],
+            trace_pubkey: None,
         };
 
         let encrypted = encrypt_exit_payload(
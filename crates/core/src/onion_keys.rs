@@ -0,0 +1,130 @@
+//! Rotating onion keys
+//!
+//! Every onion layer is sealed to the relay's X25519 key with a fresh
+//! per-shard ephemeral key, so a relay key that leaks later would open every
+//! layer ever recorded for it. An [`OnionKeyRing`] keeps that window short:
+//! the relay generates a new onion keypair every rotation interval, keeps the
+//! previous one for a grace period so shards built against it still peel,
+//! then drops it. Past traffic can't be opened once both keys are gone.
+//!
+//! Relays announce the current key in a signed offer on their heartbeat
+//! (`craftnet_network::OnionKeyOffer`); clients build paths with it.
+
+use std::time::{Duration, Instant};
+
+use craftec_crypto::EncryptionKeypair;
+
+/// How often a relay generates a new onion key
+pub const DEFAULT_ONION_KEY_ROTATION: Duration = Duration::from_secs(60 * 60);
+
+/// How long a rotated-out key still peels shards built against it
+pub const DEFAULT_ONION_KEY_GRACE: Duration = Duration::from_secs(10 * 60);
+
+struct OnionKey {
+    epoch: u64,
+    keypair: EncryptionKeypair,
+    created_at: Instant,
+}
+
+/// Current onion key, as announced to clients
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OnionKeyInfo {
+    /// Rotation counter, starting at 0 when the relay starts
+    pub epoch: u64,
+    /// X25519 pubkey clients seal layers to
+    pub public_key: [u8; 32],
+    /// How much longer the key peels shards (rest of its epoch plus grace)
+    pub valid_for: Duration,
+}
+
+/// A relay's current onion key and the one it replaced
+pub struct OnionKeyRing {
+    current: OnionKey,
+    /// Previous key and when it was rotated out
+    previous: Option<(OnionKey, Instant)>,
+    rotation: Duration,
+    grace: Duration,
+}
+
+impl OnionKeyRing {
+    pub fn new(rotation: Duration, grace: Duration, now: Instant) -> Self {
+        Self {
+            current: OnionKey { epoch: 0, keypair: EncryptionKeypair::generate(), created_at: now },
+            previous: None,
+            rotation,
+            grace,
+        }
+    }
+
+    pub fn current(&self, now: Instant) -> OnionKeyInfo {
+        let age = now.saturating_duration_since(self.current.created_at);
+        OnionKeyInfo {
+            epoch: self.current.epoch,
+            public_key: self.current.keypair.public_key_bytes(),
+            valid_for: self.rotation.saturating_sub(age) + self.grace,
+        }
+    }
+
+    /// Rotate if the current key's epoch is over and drop a previous key
+    /// past its grace period. Returns whether a new key was generated.
+    pub fn maybe_rotate(&mut self, now: Instant) -> bool {
+        if self.previous.as_ref().is_some_and(|(_, retired_at)| now.saturating_duration_since(*retired_at) >= self.grace) {
+            self.previous = None;
+        }
+        if now.saturating_duration_since(self.current.created_at) < self.rotation {
+            return false;
+        }
+        self.rotate(now);
+        true
+    }
+
+    /// Start a new epoch now, keeping the current key for the grace period
+    pub fn rotate(&mut self, now: Instant) {
+        let next = OnionKey {
+            epoch: self.current.epoch + 1,
+            keypair: EncryptionKeypair::generate(),
+            created_at: now,
+        };
+        let retired = std::mem::replace(&mut self.current, next);
+        self.previous = Some((retired, now));
+    }
+
+    /// Secret keys that still peel shards, current first
+    pub fn secrets(&self) -> Vec<[u8; 32]> {
+        let mut secrets = vec![self.current.keypair.secret_key_bytes()];
+        if let Some((previous, _)) = &self.previous {
+            secrets.push(previous.keypair.secret_key_bytes());
+        }
+        secrets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotation_keeps_previous_key_for_grace() {
+        let start = Instant::now();
+        let rotation = Duration::from_secs(3600);
+        let grace = Duration::from_secs(600);
+        let mut ring = OnionKeyRing::new(rotation, grace, start);
+        let first = ring.current(start);
+        assert_eq!(first.epoch, 0);
+        assert_eq!(first.valid_for, rotation + grace);
+
+        assert!(!ring.maybe_rotate(start + rotation / 2));
+        assert_eq!(ring.current(start + rotation / 2).valid_for, rotation / 2 + grace);
+
+        let rotated_at = start + rotation;
+        assert!(ring.maybe_rotate(rotated_at));
+        let second = ring.current(rotated_at);
+        assert_eq!(second.epoch, 1);
+        assert_ne!(second.public_key, first.public_key);
+        assert_eq!(ring.secrets().len(), 2);
+
+        // The old key is gone once its grace period is over
+        assert!(!ring.maybe_rotate(rotated_at + grace));
+        assert_eq!(ring.secrets().len(), 1);
+    }
+}
@@ -0,0 +1,128 @@
+//! Peer identity ↔ settlement pubkey binding
+//!
+//! A node has two identities: the libp2p PeerId used on the wire and the
+//! ed25519 signing key used for receipts, proofs and settlement. A
+//! `PeerBinding` ties them together with a signature from each key over the
+//! same payload, so neither side can be claimed by someone holding only the
+//! other key.
+//!
+//! This crate has no libp2p dependency, so only the settlement half is
+//! checked here. The PeerId half (decoding `peer_public_key` and checking
+//! `peer_signature`) lives in `craftnet-network`.
+
+use craftec_crypto::{sign_data, verify_signature, SigningKeypair};
+use serde::{Deserialize, Serialize};
+use serde_big_array::BigArray;
+
+use crate::{PublicKey, Signature};
+
+/// Domain separator for binding signatures
+const PEER_BINDING_DOMAIN: &[u8] = b"craftnet-peer-binding-v1";
+
+/// Signed binding between a libp2p PeerId and a settlement pubkey
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerBinding {
+    /// libp2p PeerId (multihash bytes)
+    pub peer_id: Vec<u8>,
+    /// Settlement (ed25519 signing) pubkey
+    pub settlement_pubkey: PublicKey,
+    /// Unix timestamp (seconds) when the binding was signed
+    pub timestamp: u64,
+    /// Settlement key's signature over `signable_data()`
+    #[serde(with = "BigArray")]
+    pub settlement_signature: Signature,
+    /// Protobuf-encoded libp2p public key (PeerId is derived from it)
+    pub peer_public_key: Vec<u8>,
+    /// libp2p key's signature over `signable_data()`
+    pub peer_signature: Vec<u8>,
+}
+
+impl PeerBinding {
+    /// Data signed by both keys
+    pub fn signable_data(peer_id: &[u8], settlement_pubkey: &PublicKey, timestamp: u64) -> Vec<u8> {
+        let mut data = Vec::with_capacity(PEER_BINDING_DOMAIN.len() + peer_id.len() + 32 + 8);
+        data.extend_from_slice(PEER_BINDING_DOMAIN);
+        data.extend_from_slice(peer_id);
+        data.extend_from_slice(settlement_pubkey);
+        data.extend_from_slice(&timestamp.to_le_bytes());
+        data
+    }
+
+    /// Signable data for this binding
+    pub fn signable(&self) -> Vec<u8> {
+        Self::signable_data(&self.peer_id, &self.settlement_pubkey, self.timestamp)
+    }
+
+    /// Verify the settlement key's signature (PeerId side is checked by the network crate)
+    pub fn verify_settlement_signature(&self) -> bool {
+        verify_signature(&self.settlement_pubkey, &self.signable(), &self.settlement_signature)
+    }
+
+    /// Serialize to bytes (JSON, same as DHT info records)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap_or_default()
+    }
+
+    /// Deserialize from bytes
+    pub fn from_bytes(data: &[u8]) -> Result<Self, serde_json::Error> {
+        serde_json::from_slice(data)
+    }
+}
+
+/// Settlement signature over a binding payload.
+///
+/// The caller fills in the PeerId half (`peer_public_key` / `peer_signature`).
+pub fn sign_peer_binding_settlement(
+    keypair: &SigningKeypair,
+    peer_id: &[u8],
+    timestamp: u64,
+) -> Signature {
+    let data = PeerBinding::signable_data(peer_id, &keypair.public_key_bytes(), timestamp);
+    sign_data(keypair, &data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binding(keypair: &SigningKeypair) -> PeerBinding {
+        let peer_id = vec![0x00, 0x24, 7, 7, 7];
+        let settlement_signature = sign_peer_binding_settlement(keypair, &peer_id, 1_700_000_000);
+        PeerBinding {
+            peer_id,
+            settlement_pubkey: keypair.public_key_bytes(),
+            timestamp: 1_700_000_000,
+            settlement_signature,
+            peer_public_key: Vec::new(),
+            peer_signature: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_settlement_signature_roundtrip() {
+        let keypair = SigningKeypair::generate();
+        let b = binding(&keypair);
+        assert!(b.verify_settlement_signature());
+
+        let decoded = PeerBinding::from_bytes(&b.to_bytes()).unwrap();
+        assert_eq!(decoded, b);
+        assert!(decoded.verify_settlement_signature());
+    }
+
+    #[test]
+    fn test_tampered_binding_rejected() {
+        let keypair = SigningKeypair::generate();
+
+        let mut b = binding(&keypair);
+        b.peer_id[4] ^= 1;
+        assert!(!b.verify_settlement_signature());
+
+        let mut b = binding(&keypair);
+        b.timestamp += 1;
+        assert!(!b.verify_settlement_signature());
+
+        let mut b = binding(&keypair);
+        b.settlement_pubkey = SigningKeypair::generate().public_key_bytes();
+        assert!(!b.verify_settlement_signature());
+    }
+}
@@ -0,0 +1,330 @@
+//! Anonymous free-tier quota tokens
+//!
+//! Free-tier clients pay exits with tokens instead of being metered under
+//! their pool key. Tokens are a VOPRF over ristretto255, as in Privacy
+//! Pass:
+//!
+//! 1. The client picks random nonces, hashes each to a point
+//!    `T = H(epoch, nonce)` and blinds it with a random scalar: `B = r·T`.
+//! 2. The exit multiplies every blinded point by its quota key `k` and
+//!    proves with one batched DLEQ proof that it used the key behind its
+//!    advertised `k·G` (so it cannot tag a client with a per-client key).
+//! 3. The client unblinds: `r⁻¹·(k·B) = k·T`. The token is
+//!    `(epoch, nonce, k·T)`.
+//!
+//! Only the exit that issued a token can check it (it needs `k`), and it
+//! cannot link the token to the batch it came from. A token is valid in
+//! its issuing epoch (a UTC day) and the next one; exits keep the spent
+//! nonces of those epochs to stop double spending.
+//!
+//! Batches are requested with an HTTP-shaped request to
+//! [`QUOTA_ISSUE_URL`], answered by the exit itself.
+
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+
+use crate::{CraftNetError, Result};
+
+/// Length of a quota epoch (tokens of one epoch are spendable in the next)
+pub const QUOTA_EPOCH_SECS: u64 = 24 * 3600;
+
+/// Pseudo-URL of a batch request; the exit answers it without going upstream
+pub const QUOTA_ISSUE_URL: &str = "craftnet://quota/issue";
+
+const TOKEN_DOMAIN: &[u8] = b"craftnet-quota-token-v1";
+const DLEQ_DOMAIN: &[u8] = b"craftnet-quota-dleq-v1";
+const BATCH_DOMAIN: &[u8] = b"craftnet-quota-batch-v1";
+
+/// Quota epoch of a unix time
+pub fn quota_epoch(unix_secs: u64) -> u64 {
+    unix_secs / QUOTA_EPOCH_SECS
+}
+
+/// What an exit charges free-tier clients, advertised in its `ExitInfo`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaTerms {
+    /// The exit's [`QuotaIssuer::public_key`]
+    pub issuer_key: [u8; 32],
+    /// Bytes of traffic one token pays for
+    pub token_bytes: u64,
+    /// Most tokens the exit issues to one pool per epoch
+    pub tokens_per_epoch: u32,
+}
+
+impl QuotaTerms {
+    /// Tokens that pay for `bytes` of traffic
+    pub fn tokens_for(&self, bytes: u64) -> usize {
+        bytes.div_ceil(self.token_bytes.max(1)) as usize
+    }
+}
+
+/// An unblinded token, spent by attaching it to an `ExitPayload`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaToken {
+    pub epoch: u64,
+    pub nonce: [u8; 32],
+    /// `k·H(epoch, nonce)`, compressed
+    pub signature: [u8; 32],
+}
+
+/// Blinded points of a batch, sent to the issuing exit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaIssueRequest {
+    pub epoch: u64,
+    pub blinded: Vec<[u8; 32]>,
+}
+
+/// Proof that `log_G(X) = log_M(Z)` for the batch's combined points
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DleqProof {
+    pub c: [u8; 32],
+    pub s: [u8; 32],
+}
+
+/// The issuer's answer to a [`QuotaIssueRequest`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaIssueResponse {
+    pub epoch: u64,
+    /// `k·B` for every blinded point, in request order
+    pub signed: Vec<[u8; 32]>,
+    pub proof: DleqProof,
+}
+
+impl QuotaIssueRequest {
+    pub fn to_bytes(&self) -> std::result::Result<Vec<u8>, bincode::Error> {
+        bincode::serialize(self)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> std::result::Result<Self, bincode::Error> {
+        bincode::deserialize(bytes)
+    }
+}
+
+impl QuotaIssueResponse {
+    pub fn to_bytes(&self) -> std::result::Result<Vec<u8>, bincode::Error> {
+        bincode::serialize(self)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> std::result::Result<Self, bincode::Error> {
+        bincode::deserialize(bytes)
+    }
+}
+
+fn invalid(msg: &str) -> CraftNetError {
+    CraftNetError::InvalidQuotaToken(msg.to_string())
+}
+
+fn decompress(bytes: &[u8; 32]) -> Result<RistrettoPoint> {
+    CompressedRistretto(*bytes).decompress().ok_or_else(|| invalid("not a ristretto point"))
+}
+
+fn hash_to_scalar(parts: &[&[u8]]) -> Scalar {
+    let mut hasher = Sha512::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    Scalar::from_hash(hasher)
+}
+
+fn random_scalar() -> Scalar {
+    let mut wide = [0u8; 64];
+    OsRng.fill_bytes(&mut wide);
+    Scalar::from_bytes_mod_order_wide(&wide)
+}
+
+/// `H(epoch, nonce)`
+fn token_point(epoch: u64, nonce: &[u8; 32]) -> RistrettoPoint {
+    let mut input = Vec::with_capacity(TOKEN_DOMAIN.len() + 40);
+    input.extend_from_slice(TOKEN_DOMAIN);
+    input.extend_from_slice(&epoch.to_le_bytes());
+    input.extend_from_slice(nonce);
+    RistrettoPoint::hash_from_bytes::<Sha512>(&input)
+}
+
+/// Random linear combination of the blinded and signed points, so one
+/// DLEQ proof covers the whole batch
+fn combine(public: &RistrettoPoint, blinded: &[RistrettoPoint], signed: &[RistrettoPoint]) -> (RistrettoPoint, RistrettoPoint) {
+    let mut hasher = Sha512::new();
+    hasher.update(BATCH_DOMAIN);
+    hasher.update(public.compress().as_bytes());
+    for p in blinded.iter().chain(signed) {
+        hasher.update(p.compress().as_bytes());
+    }
+    let seed = hasher.finalize();
+
+    let mut m = RistrettoPoint::default();
+    let mut z = RistrettoPoint::default();
+    for (i, (b, s)) in blinded.iter().zip(signed).enumerate() {
+        let weight = hash_to_scalar(&[seed.as_slice(), &(i as u64).to_le_bytes()]);
+        m += weight * b;
+        z += weight * s;
+    }
+    (m, z)
+}
+
+fn dleq_challenge(public: &RistrettoPoint, m: &RistrettoPoint, z: &RistrettoPoint, a: &RistrettoPoint, b: &RistrettoPoint) -> Scalar {
+    hash_to_scalar(&[
+        DLEQ_DOMAIN,
+        public.compress().as_bytes(),
+        m.compress().as_bytes(),
+        z.compress().as_bytes(),
+        a.compress().as_bytes(),
+        b.compress().as_bytes(),
+    ])
+}
+
+/// An exit's quota key
+pub struct QuotaIssuer {
+    key: Scalar,
+    public: RistrettoPoint,
+}
+
+impl QuotaIssuer {
+    /// Derive the key from secret seed material (e.g. the exit's signing secret)
+    pub fn from_seed(seed: &[u8]) -> Self {
+        let key = hash_to_scalar(&[TOKEN_DOMAIN, seed]);
+        Self { key, public: key * RISTRETTO_BASEPOINT_POINT }
+    }
+
+    /// `k·G`, advertised for clients to check batch proofs against
+    pub fn public_key(&self) -> [u8; 32] {
+        self.public.compress().to_bytes()
+    }
+
+    /// Sign a batch of blinded points
+    pub fn issue(&self, request: &QuotaIssueRequest) -> Result<QuotaIssueResponse> {
+        let blinded = request.blinded.iter().map(decompress).collect::<Result<Vec<_>>>()?;
+        let signed: Vec<RistrettoPoint> = blinded.iter().map(|b| self.key * b).collect();
+
+        let (m, z) = combine(&self.public, &blinded, &signed);
+        let r = random_scalar();
+        let (a, b) = (r * RISTRETTO_BASEPOINT_POINT, r * m);
+        let c = dleq_challenge(&self.public, &m, &z, &a, &b);
+        let s = r - c * self.key;
+
+        Ok(QuotaIssueResponse {
+            epoch: request.epoch,
+            signed: signed.iter().map(|p| p.compress().to_bytes()).collect(),
+            proof: DleqProof { c: c.to_bytes(), s: s.to_bytes() },
+        })
+    }
+
+    /// Whether `token` was issued with this key (spent or not)
+    pub fn verify(&self, token: &QuotaToken) -> bool {
+        let expected = self.key * token_point(token.epoch, &token.nonce);
+        expected.compress().to_bytes() == token.signature
+    }
+}
+
+/// A batch request awaiting the issuer's answer
+pub struct PendingQuotaBatch {
+    epoch: u64,
+    nonces: Vec<[u8; 32]>,
+    blinds: Vec<Scalar>,
+    blinded: Vec<RistrettoPoint>,
+}
+
+impl PendingQuotaBatch {
+    /// Blind `count` fresh tokens for `epoch`
+    pub fn new(epoch: u64, count: usize) -> (Self, QuotaIssueRequest) {
+        let mut nonces = Vec::with_capacity(count);
+        let mut blinds = Vec::with_capacity(count);
+        let mut blinded = Vec::with_capacity(count);
+        for _ in 0..count {
+            let mut nonce = [0u8; 32];
+            OsRng.fill_bytes(&mut nonce);
+            let r = random_scalar();
+            blinded.push(r * token_point(epoch, &nonce));
+            nonces.push(nonce);
+            blinds.push(r);
+        }
+        let request = QuotaIssueRequest {
+            epoch,
+            blinded: blinded.iter().map(|p| p.compress().to_bytes()).collect(),
+        };
+        (Self { epoch, nonces, blinds, blinded }, request)
+    }
+
+    /// Check the issuer's proof against its advertised `issuer_key` and
+    /// unblind the tokens
+    pub fn finalize(self, issuer_key: &[u8; 32], response: &QuotaIssueResponse) -> Result<Vec<QuotaToken>> {
+        if response.epoch != self.epoch || response.signed.len() != self.blinded.len() {
+            return Err(invalid("batch response does not match request"));
+        }
+        let public = decompress(issuer_key)?;
+        let signed = response.signed.iter().map(decompress).collect::<Result<Vec<_>>>()?;
+
+        let c = Option::<Scalar>::from(Scalar::from_canonical_bytes(response.proof.c));
+        let s = Option::<Scalar>::from(Scalar::from_canonical_bytes(response.proof.s));
+        let (Some(c), Some(s)) = (c, s) else {
+            return Err(invalid("malformed batch proof"));
+        };
+        let (m, z) = combine(&public, &self.blinded, &signed);
+        let a = s * RISTRETTO_BASEPOINT_POINT + c * public;
+        let b = s * m + c * z;
+        if dleq_challenge(&public, &m, &z, &a, &b) != c {
+            return Err(invalid("batch proof does not verify against the issuer key"));
+        }
+
+        Ok(self
+            .nonces
+            .into_iter()
+            .zip(self.blinds)
+            .zip(signed)
+            .map(|((nonce, r), point)| QuotaToken {
+                epoch: self.epoch,
+                nonce,
+                signature: (r.invert() * point).compress().to_bytes(),
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_issue_and_verify() {
+        let issuer = QuotaIssuer::from_seed(b"exit secret");
+        let (pending, request) = PendingQuotaBatch::new(20_000, 4);
+        let response = issuer.issue(&request).unwrap();
+        let response = QuotaIssueResponse::from_bytes(&response.to_bytes().unwrap()).unwrap();
+        let tokens = pending.finalize(&issuer.public_key(), &response).unwrap();
+        assert_eq!(tokens.len(), 4);
+        assert!(tokens.iter().all(|t| issuer.verify(t)));
+
+        let mut forged = tokens[0].clone();
+        forged.nonce[0] ^= 1;
+        assert!(!issuer.verify(&forged));
+        assert!(!QuotaIssuer::from_seed(b"another exit").verify(&tokens[0]));
+    }
+
+    #[test]
+    fn test_proof_binds_issuer_key() {
+        let issuer = QuotaIssuer::from_seed(b"exit secret");
+        let tagging = QuotaIssuer::from_seed(b"per-client key");
+        let (pending, request) = PendingQuotaBatch::new(1, 2);
+
+        // Signed with a key other than the advertised one
+        let response = tagging.issue(&request).unwrap();
+        assert!(pending.finalize(&issuer.public_key(), &response).is_err());
+
+        let (pending, request) = PendingQuotaBatch::new(1, 2);
+        let mut response = issuer.issue(&request).unwrap();
+        response.signed.swap(0, 1);
+        assert!(pending.finalize(&issuer.public_key(), &response).is_err());
+    }
+
+    #[test]
+    fn test_quota_epoch() {
+        assert_eq!(quota_epoch(0), 0);
+        assert_eq!(quota_epoch(QUOTA_EPOCH_SECS - 1), 0);
+        assert_eq!(quota_epoch(QUOTA_EPOCH_SECS), 1);
+    }
+}
@@ -1,9 +1,15 @@
 //! Cryptographic helpers for Craftnet structures
 
 use craftec_crypto::{sign_data, verify_signature, SigningKeypair};
-use crate::ForwardReceipt;
+use crate::{ForwardReceipt, Id, PublicKey, Signature};
+use sha2::{Digest, Sha256};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// Response header carrying the exit's signature over the response.
+///
+/// It travels inside the encrypted response, so only the client sees it.
+pub const EXIT_SIGNATURE_HEADER: &str = "x-craftnet-exit-signature";
+
 /// Sign a forward receipt proving we received a shard.
 pub fn sign_forward_receipt(
     keypair: &SigningKeypair,
@@ -49,3 +55,50 @@ pub fn verify_forward_receipt(receipt: &ForwardReceipt) -> bool {
     );
     verify_signature(&receipt.receiver_pubkey, &data, &receipt.signature)
 }
+
+/// Digest an exit signs for an HTTP response: request id, status and body hash
+pub fn exit_response_digest(request_id: &Id, status: u16, body: &[u8]) -> [u8; 32] {
+    let body_hash = Sha256::digest(body);
+    let mut hasher = Sha256::new();
+    hasher.update(b"craftnet-exit-response-v1");
+    hasher.update(request_id);
+    hasher.update(status.to_be_bytes());
+    hasher.update(body_hash);
+    hasher.finalize().into()
+}
+
+/// Sign an HTTP response as the exit that fetched it
+pub fn sign_exit_response(keypair: &SigningKeypair, request_id: &Id, status: u16, body: &[u8]) -> Signature {
+    sign_data(keypair, &exit_response_digest(request_id, status, body))
+}
+
+/// Verify an exit's signature over an HTTP response
+pub fn verify_exit_response(
+    exit_pubkey: &PublicKey,
+    request_id: &Id,
+    status: u16,
+    body: &[u8],
+    signature: &Signature,
+) -> bool {
+    verify_signature(exit_pubkey, &exit_response_digest(request_id, status, body), signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_response_signature() {
+        let keypair = SigningKeypair::generate();
+        let exit = keypair.public_key_bytes();
+        let request_id = [3u8; 32];
+        let sig = sign_exit_response(&keypair, &request_id, 200, b"hello");
+
+        assert!(verify_exit_response(&exit, &request_id, 200, b"hello", &sig));
+        assert!(!verify_exit_response(&exit, &request_id, 200, b"hellO", &sig));
+        assert!(!verify_exit_response(&exit, &request_id, 404, b"hello", &sig));
+        assert!(!verify_exit_response(&exit, &[4u8; 32], 200, b"hello", &sig));
+        let other = SigningKeypair::generate().public_key_bytes();
+        assert!(!verify_exit_response(&other, &request_id, 200, b"hello", &sig));
+    }
+}
@@ -3,7 +3,13 @@
 //! A shard is a fragment of an erasure-coded, onion-encrypted request or response.
 //! No plaintext routing metadata is visible — each relay peels one onion layer
 //! from the header to learn the next hop.
+//!
+//! The header and payload are reference-counted [`Bytes`]: a relay moves the
+//! payload through untouched, and cloning a shard (retries, fan-out) shares
+//! both buffers instead of copying them. `Bytes` encodes like `Vec<u8>`, so
+//! the wire format is unchanged.
 
+use bytes::Bytes;
 use serde::{Deserialize, Serialize};
 
 /// A shard carrying an onion-encrypted payload fragment
@@ -20,9 +26,9 @@ pub struct Shard {
     /// Ephemeral X25519 pubkey for ECDH with the current hop
     pub ephemeral_pubkey: [u8; 32],
     /// Encrypted onion routing layers (each relay peels one)
-    pub header: Vec<u8>,
+    pub header: Bytes,
     /// Erasure-coded piece of exit-encrypted data
-    pub payload: Vec<u8>,
+    pub payload: Bytes,
     /// Exit-encrypted routing tag containing assembly_id + shard/chunk metadata
     /// Format: [ephemeral_pubkey: 32][nonce: 12][encrypted(RoutingTag)]
     pub routing_tag: Vec<u8>,
@@ -34,6 +40,14 @@ pub struct Shard {
     /// When 0, no honest relay will process the shard further.
     #[serde(default)]
     pub hops_remaining: u8,
+    /// Zero filler so the encoded shard lands on a size bucket
+    /// (see `pad_to_bucket`). Ignored by every receiver.
+    #[serde(default)]
+    pub padding: Bytes,
+    /// Sealed per-hop timings of a traced request (see `trace`), empty for
+    /// normal traffic
+    #[serde(default)]
+    pub trace: Vec<Vec<u8>>,
 }
 
 impl Shard {
@@ -48,11 +62,37 @@ impl Shard {
     ) -> Self {
         Self {
             ephemeral_pubkey,
-            header,
-            payload,
+            header: header.into(),
+            payload: payload.into(),
             routing_tag,
             total_hops,
             hops_remaining,
+            padding: Bytes::new(),
+            trace: Vec::new(),
+        }
+    }
+
+    /// Encoded size of this shard (bincode, as written to the wire)
+    pub fn encoded_len(&self) -> usize {
+        bincode::serialized_size(self).map(|n| n as usize).unwrap_or(usize::MAX)
+    }
+
+    /// Whether this shard carries padding
+    pub fn is_padded(&self) -> bool {
+        !self.padding.is_empty()
+    }
+
+    /// Pad the shard so its encoded size is exactly the smallest bucket in
+    /// `SHARD_SIZE_BUCKETS` that fits it.
+    ///
+    /// Deterministic: the result depends only on the unpadded size, so relays
+    /// can re-pad after peeling a layer (the header shrinks every hop).
+    /// Shards larger than the biggest bucket are left unpadded.
+    pub fn pad_to_bucket(&mut self) {
+        self.padding.clear();
+        let unpadded = self.encoded_len();
+        if let Some(bucket) = shard_size_bucket(unpadded) {
+            self.padding = Bytes::from_static(&ZERO_PADDING[..bucket - unpadded]);
         }
     }
 
@@ -67,6 +107,21 @@ impl Shard {
     }
 }
 
+/// Encoded shard size buckets for padding (bytes).
+///
+/// A 3-hop shard carrying a full erasure piece of an 18 KiB chunk fits the
+/// 8 KiB bucket; all buckets stay below the 64 KiB stream frame limit.
+pub const SHARD_SIZE_BUCKETS: [usize; 6] = [1024, 2048, 4096, 8192, 16384, 32768];
+
+/// Shared filler for `pad_to_bucket`, so re-padding at every hop doesn't allocate
+static ZERO_PADDING: [u8; SHARD_SIZE_BUCKETS[SHARD_SIZE_BUCKETS.len() - 1]] =
+    [0; SHARD_SIZE_BUCKETS[SHARD_SIZE_BUCKETS.len() - 1]];
+
+/// Smallest bucket that fits an encoded shard of `len` bytes
+pub fn shard_size_bucket(len: usize) -> Option<usize> {
+    SHARD_SIZE_BUCKETS.iter().copied().find(|&b| b >= len)
+}
+
 /// Wire format header magic bytes (new format: "TCON" = CraftNet ONion)
 pub const SHARD_MAGIC: [u8; 4] = [0x54, 0x43, 0x4F, 0x4E]; // "TCON"
 
@@ -133,6 +188,48 @@ mod tests {
         assert_eq!(restored.routing_tag, shard.routing_tag);
     }
 
+    #[test]
+    fn test_pad_to_bucket_hits_bucket_exactly() {
+        for payload_len in [0, 100, 1000, 3000, 6144, 20_000] {
+            let mut shard = Shard::new(
+                [1u8; 32],
+                vec![2; 540],
+                vec![3; payload_len],
+                vec![9; 98],
+                3, 3,
+            );
+            let unpadded = shard.encoded_len();
+            shard.pad_to_bucket();
+
+            let expected = shard_size_bucket(unpadded).unwrap();
+            assert_eq!(shard.to_bytes().unwrap().len(), expected);
+            assert!(shard.padding.iter().all(|&b| b == 0));
+
+            let restored = Shard::from_bytes(&shard.to_bytes().unwrap()).unwrap();
+            assert_eq!(restored.payload, vec![3; payload_len]);
+        }
+    }
+
+    #[test]
+    fn test_repad_after_header_shrinks() {
+        let mut shard = Shard::new([0u8; 32], vec![1; 540], vec![2; 3000], vec![0; 98], 3, 3);
+        shard.pad_to_bucket();
+        let first = shard.encoded_len();
+
+        // A relay peels a layer and re-pads: same bucket, same size
+        shard.header.truncate(360);
+        shard.pad_to_bucket();
+        assert_eq!(shard.encoded_len(), first);
+    }
+
+    #[test]
+    fn test_oversized_shard_left_unpadded() {
+        let mut shard = Shard::new([0u8; 32], vec![], vec![0; 40_000], vec![], 0, 0);
+        shard.pad_to_bucket();
+        assert!(!shard.is_padded());
+        assert_eq!(shard_size_bucket(40_000), None);
+    }
+
     #[test]
     fn test_deserialization_invalid_data() {
         let result = Shard::from_bytes(&[0xFF, 0xFE, 0xFD]);
@@ -0,0 +1,215 @@
+//! Split tunneling rules
+//!
+//! Rules decide per connection whether traffic goes through the tunnel or
+//! bypasses it on the raw network. A rule matches on the originating process
+//! name, the destination domain (the domain and all its subdomains) or a
+//! destination CIDR; the first matching rule wins and unmatched traffic
+//! takes the default action (tunnel).
+
+use std::net::IpAddr;
+
+use serde::{Deserialize, Serialize};
+
+/// Where matching traffic goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SplitTunnelAction {
+    /// Route through CraftNet
+    #[default]
+    Tunnel,
+    /// Connect directly, outside the tunnel
+    Bypass,
+}
+
+/// What a rule matches on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SplitTunnelMatch {
+    /// Process name (case-insensitive, e.g. `firefox`)
+    Process(String),
+    /// Destination domain and its subdomains (`example.com`, `*.example.com`)
+    Domain(String),
+    /// Destination IP range (`10.0.0.0/8`, `fd00::/8`)
+    Cidr(String),
+}
+
+impl SplitTunnelMatch {
+    /// Check that the pattern is well formed
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Self::Process(name) | Self::Domain(name) if name.trim().is_empty() => {
+                Err("empty split tunnel pattern".to_string())
+            }
+            Self::Cidr(cidr) => parse_cidr(cidr).map(|_| ()),
+            _ => Ok(()),
+        }
+    }
+
+    fn matches(&self, process: Option<&str>, host: &str) -> bool {
+        match self {
+            Self::Process(name) => process.is_some_and(|p| p.eq_ignore_ascii_case(name.trim())),
+            Self::Domain(domain) => {
+                let domain = domain.trim().trim_start_matches("*.").trim_end_matches('.');
+                let host = host.trim_end_matches('.');
+                host.eq_ignore_ascii_case(domain)
+                    || (host.len() > domain.len()
+                        && host.as_bytes()[host.len() - domain.len() - 1] == b'.'
+                        && host[host.len() - domain.len()..].eq_ignore_ascii_case(domain))
+            }
+            Self::Cidr(cidr) => {
+                let Ok(ip) = host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() else {
+                    return false;
+                };
+                parse_cidr(cidr).is_ok_and(|(net, len)| cidr_contains(net, len, ip))
+            }
+        }
+    }
+}
+
+/// One split tunneling rule
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SplitTunnelRule {
+    /// Stable id for removal over IPC
+    pub id: u64,
+    #[serde(rename = "match")]
+    pub matcher: SplitTunnelMatch,
+    pub action: SplitTunnelAction,
+}
+
+/// Ordered split tunneling rules (persisted in settings)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SplitTunnelRules {
+    #[serde(default)]
+    pub rules: Vec<SplitTunnelRule>,
+    /// Action for traffic no rule matches
+    #[serde(default)]
+    pub default_action: SplitTunnelAction,
+}
+
+impl SplitTunnelRules {
+    /// Route for a connection from `process` (if known) to `host` (domain or IP)
+    pub fn decide(&self, process: Option<&str>, host: &str) -> SplitTunnelAction {
+        self.rules
+            .iter()
+            .find(|r| r.matcher.matches(process, host))
+            .map_or(self.default_action, |r| r.action)
+    }
+
+    /// Whether any rule needs the originating process to be known
+    pub fn has_process_rules(&self) -> bool {
+        self.rules.iter().any(|r| matches!(r.matcher, SplitTunnelMatch::Process(_)))
+    }
+
+    /// Append a rule, returning its id
+    pub fn add(&mut self, matcher: SplitTunnelMatch, action: SplitTunnelAction) -> Result<u64, String> {
+        matcher.validate()?;
+        let id = self.rules.iter().map(|r| r.id).max().map_or(1, |max| max + 1);
+        self.rules.push(SplitTunnelRule { id, matcher, action });
+        Ok(id)
+    }
+
+    /// Remove a rule by id; returns whether it existed
+    pub fn remove(&mut self, id: u64) -> bool {
+        let before = self.rules.len();
+        self.rules.retain(|r| r.id != id);
+        self.rules.len() != before
+    }
+}
+
+fn parse_cidr(cidr: &str) -> Result<(IpAddr, u8), String> {
+    let (addr, len) = match cidr.trim().split_once('/') {
+        Some((addr, len)) => (addr, Some(len)),
+        None => (cidr.trim(), None),
+    };
+    let net: IpAddr = addr.parse().map_err(|_| format!("invalid CIDR address: {}", cidr))?;
+    let max = if net.is_ipv4() { 32 } else { 128 };
+    let len = match len {
+        Some(len) => len.parse::<u8>().map_err(|_| format!("invalid CIDR prefix: {}", cidr))?,
+        None => max,
+    };
+    if len > max {
+        return Err(format!("invalid CIDR prefix: {}", cidr));
+    }
+    Ok((net, len))
+}
+
+fn cidr_contains(net: IpAddr, len: u8, ip: IpAddr) -> bool {
+    match (net, ip) {
+        (IpAddr::V4(net), IpAddr::V4(ip)) => {
+            let mask = u32::MAX.checked_shl(32 - len as u32).unwrap_or(0);
+            u32::from(net) & mask == u32::from(ip) & mask
+        }
+        (IpAddr::V6(net), IpAddr::V6(ip)) => {
+            let mask = u128::MAX.checked_shl(128 - len as u32).unwrap_or(0);
+            u128::from(net) & mask == u128::from(ip) & mask
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(entries: &[(SplitTunnelMatch, SplitTunnelAction)]) -> SplitTunnelRules {
+        let mut rules = SplitTunnelRules::default();
+        for (m, a) in entries {
+            rules.add(m.clone(), *a).unwrap();
+        }
+        rules
+    }
+
+    #[test]
+    fn test_domain_matches_subdomains() {
+        let r = rules(&[(SplitTunnelMatch::Domain("*.bank.example".into()), SplitTunnelAction::Bypass)]);
+        assert_eq!(r.decide(None, "bank.example"), SplitTunnelAction::Bypass);
+        assert_eq!(r.decide(None, "www.Bank.example"), SplitTunnelAction::Bypass);
+        assert_eq!(r.decide(None, "notbank.example"), SplitTunnelAction::Tunnel);
+    }
+
+    #[test]
+    fn test_cidr_matches() {
+        let r = rules(&[
+            (SplitTunnelMatch::Cidr("192.168.0.0/16".into()), SplitTunnelAction::Bypass),
+            (SplitTunnelMatch::Cidr("fd00::/8".into()), SplitTunnelAction::Bypass),
+        ]);
+        assert_eq!(r.decide(None, "192.168.1.20"), SplitTunnelAction::Bypass);
+        assert_eq!(r.decide(None, "192.169.1.20"), SplitTunnelAction::Tunnel);
+        assert_eq!(r.decide(None, "fd12::1"), SplitTunnelAction::Bypass);
+        assert_eq!(r.decide(None, "example.com"), SplitTunnelAction::Tunnel);
+    }
+
+    #[test]
+    fn test_process_and_first_match_wins() {
+        let r = rules(&[
+            (SplitTunnelMatch::Process("Steam".into()), SplitTunnelAction::Bypass),
+            (SplitTunnelMatch::Domain("example.com".into()), SplitTunnelAction::Tunnel),
+            (SplitTunnelMatch::Domain("example.com".into()), SplitTunnelAction::Bypass),
+        ]);
+        assert!(r.has_process_rules());
+        assert_eq!(r.decide(Some("steam"), "example.com"), SplitTunnelAction::Bypass);
+        assert_eq!(r.decide(Some("firefox"), "example.com"), SplitTunnelAction::Tunnel);
+        assert_eq!(r.decide(None, "other.org"), SplitTunnelAction::Tunnel);
+    }
+
+    #[test]
+    fn test_add_remove_and_validate() {
+        let mut r = SplitTunnelRules::default();
+        assert!(r.add(SplitTunnelMatch::Cidr("10.0.0.0/33".into()), SplitTunnelAction::Bypass).is_err());
+        assert!(r.add(SplitTunnelMatch::Domain(" ".into()), SplitTunnelAction::Bypass).is_err());
+
+        let a = r.add(SplitTunnelMatch::Cidr("10.0.0.0/8".into()), SplitTunnelAction::Bypass).unwrap();
+        let b = r.add(SplitTunnelMatch::Domain("lan".into()), SplitTunnelAction::Bypass).unwrap();
+        assert_ne!(a, b);
+        assert!(r.remove(a));
+        assert!(!r.remove(a));
+        assert_eq!(r.rules.len(), 1);
+    }
+
+    #[test]
+    fn test_rule_serialization() {
+        let r = rules(&[(SplitTunnelMatch::Process("steam".into()), SplitTunnelAction::Bypass)]);
+        let json = serde_json::to_value(&r.rules[0]).unwrap();
+        assert_eq!(json, serde_json::json!({"id": 1, "match": {"process": "steam"}, "action": "bypass"}));
+    }
+}
@@ -0,0 +1,108 @@
+//! Streamed HTTP response types
+//!
+//! When a request's `ExitPayload.mode` is `PAYLOAD_MODE_HTTP_STREAM`, the exit
+//! does not buffer the whole response. It sends it back as a sequence of
+//! independently encrypted segments, each erasure coded as its own response
+//! assembly. Segment `n` uses `segment_assembly_id(request_id, n)` as its
+//! assembly_id, so the client can decode segments as they arrive and hand the
+//! body to the caller incrementally.
+
+use serde::{Deserialize, Serialize};
+
+use crate::Id;
+
+/// Payload mode: HTTP request with a streamed (segmented) response
+pub const PAYLOAD_MODE_HTTP_STREAM: u8 = 0x02;
+
+/// Target body bytes per streamed segment
+pub const STREAM_SEGMENT_SIZE: usize = 64 * 1024;
+
+/// One segment of a streamed response
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ResponseSegment {
+    /// First segment: status line and headers
+    Head {
+        status: u16,
+        headers: Vec<(String, String)>,
+    },
+    /// A slice of the response body
+    Body(Vec<u8>),
+    /// Last segment of a complete response
+    End {
+        /// Total body bytes sent, for the client to cross-check
+        total_bytes: u64,
+    },
+    /// The exit aborted the response (upstream error, size limit)
+    Error(String),
+}
+
+impl ResponseSegment {
+    /// Whether this segment ends the stream
+    pub fn is_final(&self) -> bool {
+        matches!(self, Self::End { .. } | Self::Error(_))
+    }
+
+    /// Serialize to bytes
+    pub fn to_bytes(&self) -> Result<Vec<u8>, bincode::Error> {
+        bincode::serialize(self)
+    }
+
+    /// Deserialize from bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, bincode::Error> {
+        bincode::deserialize(bytes)
+    }
+}
+
+/// Assembly ID of segment `seq` of a streamed response.
+///
+/// XORs `seq + 1` into the last four bytes of the request_id, so every
+/// segment (including the head) gets a distinct id that neither side has to
+/// transmit and that never equals the request_id itself.
+pub fn segment_assembly_id(request_id: &Id, seq: u32) -> Id {
+    let mut id = *request_id;
+    let tag = (seq.wrapping_add(1)).to_be_bytes();
+    for (byte, t) in id[28..].iter_mut().zip(tag) {
+        *byte ^= t;
+    }
+    id
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segment_roundtrip() {
+        let head = ResponseSegment::Head {
+            status: 200,
+            headers: vec![("Content-Type".to_string(), "text/plain".to_string())],
+        };
+        let bytes = head.to_bytes().unwrap();
+        assert_eq!(ResponseSegment::from_bytes(&bytes).unwrap(), head);
+
+        let body = ResponseSegment::Body(vec![7u8; 1000]);
+        let bytes = body.to_bytes().unwrap();
+        assert_eq!(ResponseSegment::from_bytes(&bytes).unwrap(), body);
+        assert!(!body.is_final());
+        assert!(ResponseSegment::End { total_bytes: 1000 }.is_final());
+        assert!(ResponseSegment::Error("boom".to_string()).is_final());
+    }
+
+    #[test]
+    fn test_segment_assembly_ids_distinct() {
+        let request_id = [9u8; 32];
+        let ids: std::collections::HashSet<Id> = (0..1000)
+            .map(|seq| segment_assembly_id(&request_id, seq))
+            .collect();
+        assert_eq!(ids.len(), 1000);
+        assert!(!ids.contains(&request_id));
+        // Only the trailing bytes differ
+        assert_eq!(segment_assembly_id(&request_id, 5)[..28], request_id[..28]);
+    }
+
+    #[test]
+    fn test_stream_mode_distinct_from_other_modes() {
+        assert_ne!(PAYLOAD_MODE_HTTP_STREAM, crate::PAYLOAD_MODE_HTTP);
+        assert_ne!(PAYLOAD_MODE_HTTP_STREAM, crate::PAYLOAD_MODE_TUNNEL);
+    }
+}
@@ -0,0 +1,97 @@
+//! Per-hop request tracing
+//!
+//! A diagnostic request can ask every hop to record when it handled the
+//! request's shards. The client generates a one-off X25519 trace key and puts
+//! its pubkey in each relay's `OnionSettlement` and in the `ExitPayload`.
+//! Hops that find a trace key seal a [`HopTiming`] to it and append the blob
+//! to the shard's `trace` list; the exit copies the request blobs (plus its
+//! own) onto the response shards, so only the client can read the timings.
+//!
+//! Traced shards are larger than normal ones and every hop of a request sees
+//! the same trace key, so tracing is off by default and only ever enabled
+//! for explicit test requests.
+
+use serde::{Deserialize, Serialize};
+
+use craftec_crypto::{decrypt_from_sender, encrypt_for_recipient, EncryptError, EncryptionKeypair};
+
+/// Most trace blobs a single shard carries; hops stop appending past this
+pub const MAX_SHARD_TRACE_ENTRIES: usize = 32;
+
+/// Most request trace blobs an exit copies onto a response, leaving room
+/// for its own timing and the response path's
+pub const MAX_RESPONSE_TRACE_ENTRIES: usize = 16;
+
+/// Which kind of hop recorded a timing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HopRole {
+    Relay,
+    Exit,
+}
+
+/// When one hop handled a traced shard (unix milliseconds, hop's own clock)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HopTiming {
+    pub role: HopRole,
+    /// First shard of the request arrived
+    pub received_at_ms: u64,
+    /// Shard (or the response, at the exit) left the hop
+    pub sent_at_ms: u64,
+}
+
+impl HopTiming {
+    /// Time the hop held the request
+    pub fn processing_ms(&self) -> u64 {
+        self.sent_at_ms.saturating_sub(self.received_at_ms)
+    }
+}
+
+/// Seal a timing for the client's trace key.
+///
+/// Returns `[ephemeral_pubkey: 32][nonce: 12][ciphertext]`.
+pub fn seal_hop_timing(trace_pubkey: &[u8; 32], timing: &HopTiming) -> Result<Vec<u8>, EncryptError> {
+    let bytes = bincode::serialize(timing).map_err(|_| EncryptError::EncryptionFailed)?;
+    let ephemeral = EncryptionKeypair::generate();
+    let ciphertext = encrypt_for_recipient(trace_pubkey, &ephemeral.secret_key_bytes(), &bytes)?;
+
+    let mut blob = Vec::with_capacity(32 + ciphertext.len());
+    blob.extend_from_slice(&ephemeral.public_key_bytes());
+    blob.extend_from_slice(&ciphertext);
+    Ok(blob)
+}
+
+/// Open a blob sealed by [`seal_hop_timing`] (None if it isn't for this key)
+pub fn open_hop_timing(trace_secret: &[u8; 32], blob: &[u8]) -> Option<HopTiming> {
+    let ephemeral: [u8; 32] = blob.get(..32)?.try_into().ok()?;
+    let bytes = decrypt_from_sender(&ephemeral, trace_secret, &blob[32..]).ok()?;
+    bincode::deserialize(&bytes).ok()
+}
+
+/// Unix milliseconds now
+pub fn trace_now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hop_timing_roundtrip() {
+        let trace_keys = EncryptionKeypair::generate();
+        let timing = HopTiming { role: HopRole::Relay, received_at_ms: 1_000, sent_at_ms: 1_004 };
+
+        let blob = seal_hop_timing(&trace_keys.public_key_bytes(), &timing).unwrap();
+        assert_eq!(open_hop_timing(&trace_keys.secret_key_bytes(), &blob), Some(timing));
+        assert_eq!(timing.processing_ms(), 4);
+
+        // Only the trace key opens it
+        let other = EncryptionKeypair::generate();
+        assert_eq!(open_hop_timing(&other.secret_key_bytes(), &blob), None);
+        assert_eq!(open_hop_timing(&trace_keys.secret_key_bytes(), &blob[..20]), None);
+    }
+}
@@ -290,6 +290,103 @@ pub struct ExitInfo {
     /// libp2p PeerId string (learned from gossipsub or DHT)
     #[serde(default)]
     pub peer_id: Option<String>,
+    /// Signed PeerId ↔ settlement pubkey binding
+    #[serde(default)]
+    pub peer_binding: Option<crate::PeerBinding>,
+    /// Autonomous System of the exit (see [`normalize_asn`](crate::normalize_asn))
+    #[serde(default)]
+    pub as_number: Option<String>,
+    /// Free-tier quota token terms (None = free tier not metered)
+    #[serde(default)]
+    pub quota: Option<crate::QuotaTerms>,
+    /// What the exit's startup self-test found working (None from exits
+    /// that predate the self-test)
+    #[serde(default)]
+    pub capabilities: Option<ExitCapabilities>,
+    /// Largest request payload the exit accepts (bytes; None from exits
+    /// that don't advertise it). Pool tiers may lower it.
+    #[serde(default)]
+    pub max_request_bytes: Option<u64>,
+    /// Largest response body the exit returns (bytes; None = not advertised)
+    #[serde(default)]
+    pub max_response_bytes: Option<u64>,
+    /// Exit decompresses zstd request payloads (`TAG_FLAG_ZSTD`); false from
+    /// exits that predate it, which must get requests uncompressed
+    #[serde(default)]
+    pub zstd_requests: bool,
+}
+
+/// Pseudo-URL of a client health probe; the exit answers it with an empty
+/// 204 without going upstream
+pub const EXIT_PROBE_URL: &str = "craftnet://exit/probe";
+
+/// Upstream capabilities an exit verified with its startup self-test
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExitCapabilities {
+    /// HTTP fetches to IPv4 destinations work
+    pub ipv4: bool,
+    /// HTTP fetches to IPv6 destinations work
+    pub ipv6: bool,
+    /// Upstream DNS resolution works
+    pub dns: bool,
+    /// Large response bodies arrive intact
+    pub large_body: bool,
+    /// TCP tunnels carry data both ways
+    pub tcp_tunnel: bool,
+    /// Measured upstream throughput (KB/s, 0 = not measured)
+    pub upstream_kbps: u32,
+}
+
+impl ExitCapabilities {
+    /// Whether the exit can serve HTTP requests at all: some address
+    /// family reachable and names resolvable
+    pub fn can_serve_http(&self) -> bool {
+        (self.ipv4 || self.ipv6) && self.dns
+    }
+}
+
+/// Self-reported bandwidth class of a relay
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BandwidthClass {
+    /// Under 1 MB/s
+    Low,
+    /// 1 MB/s to 12 MB/s
+    Medium,
+    /// 12 MB/s (~100 Mbit/s) and above
+    High,
+}
+
+impl BandwidthClass {
+    /// Class of a measured throughput (KB/s)
+    pub fn from_kbps(kbps: u32) -> Self {
+        if kbps >= Self::High.min_kbps() {
+            Self::High
+        } else if kbps >= Self::Medium.min_kbps() {
+            Self::Medium
+        } else {
+            Self::Low
+        }
+    }
+
+    /// Lowest throughput (KB/s) of the class
+    pub fn min_kbps(self) -> u32 {
+        match self {
+            Self::Low => 0,
+            Self::Medium => 1024,
+            Self::High => 12 * 1024,
+        }
+    }
+}
+
+/// Capacity a relay advertises in its DHT record and status heartbeats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayCapacity {
+    pub bandwidth_class: BandwidthClass,
+    /// Most peer shard streams it serves at once
+    pub max_concurrent_streams: u32,
+    /// Largest shard it forwards (bytes)
+    pub max_shard_size: u32,
 }
 
 /// Information about a relay node (stored in DHT)
@@ -302,6 +399,18 @@ pub struct RelayInfo {
     /// X25519 encryption pubkey (for onion routing)
     #[serde(default)]
     pub encryption_pubkey: Option<[u8; 32]>,
+    /// Signed PeerId ↔ settlement pubkey binding
+    #[serde(default)]
+    pub peer_binding: Option<crate::PeerBinding>,
+    /// Self-reported capacity (None from relays that predate it)
+    #[serde(default)]
+    pub capacity: Option<RelayCapacity>,
+    /// Country code of the relay (ISO 3166-1 alpha-2)
+    #[serde(default)]
+    pub country_code: Option<String>,
+    /// Autonomous System of the relay (see [`normalize_asn`](crate::normalize_asn))
+    #[serde(default)]
+    pub as_number: Option<String>,
 }
 
 /// Information about a peer node
@@ -583,6 +692,27 @@ mod tests {
         assert_ne!(data1, data2, "Different senders should produce different signable data");
     }
 
+    #[test]
+    fn test_forward_receipt_signable_data_golden() {
+        // Shared with the prover and the aggregator (test-vectors/hashing.json)
+        let vectors: serde_json::Value =
+            serde_json::from_str(include_str!("../../../test-vectors/hashing.json")).unwrap();
+        let key = |v: &serde_json::Value| -> [u8; 32] {
+            hex::decode(v.as_str().unwrap()).unwrap().try_into().unwrap()
+        };
+        for r in vectors["receipts"].as_array().unwrap() {
+            let data = ForwardReceipt::signable_data(
+                &key(&r["shard_id"]),
+                &key(&r["sender_pubkey"]),
+                &key(&r["receiver_pubkey"]),
+                &key(&r["pool_pubkey"]),
+                r["payload_size"].as_u64().unwrap() as u32,
+                r["timestamp"].as_u64().unwrap(),
+            );
+            assert_eq!(hex::encode(data), r["signable"].as_str().unwrap());
+        }
+    }
+
     // ==================== ExitInfo Tests ====================
 
     #[test]
@@ -597,6 +727,13 @@ mod tests {
             latency_ms: 50,
             encryption_pubkey: None,
             peer_id: None,
+            peer_binding: None,
+            as_number: None,
+            quota: None,
+            capabilities: None,
+            max_request_bytes: None,
+            max_response_bytes: None,
+            zstd_requests: false,
         };
 
         assert_eq!(exit.pubkey, [1u8; 32]);
@@ -620,6 +757,13 @@ mod tests {
             latency_ms: 0,
             encryption_pubkey: None,
             peer_id: None,
+            peer_binding: None,
+            as_number: None,
+            quota: None,
+            capabilities: None,
+            max_request_bytes: None,
+            max_response_bytes: None,
+            zstd_requests: false,
         };
 
         assert!(exit.address.is_empty());
@@ -684,6 +828,13 @@ mod tests {
             latency_ms: 100,
             encryption_pubkey: None,
             peer_id: None,
+            peer_binding: None,
+            as_number: None,
+            quota: None,
+            capabilities: None,
+            max_request_bytes: None,
+            max_response_bytes: None,
+            zstd_requests: false,
         };
 
         let json = serde_json::to_string(&exit).unwrap();
@@ -749,6 +900,15 @@ mod tests {
         assert!(all.is_service_node());
     }
 
+    #[test]
+    fn test_bandwidth_class_from_kbps() {
+        assert_eq!(BandwidthClass::from_kbps(0), BandwidthClass::Low);
+        assert_eq!(BandwidthClass::from_kbps(1023), BandwidthClass::Low);
+        assert_eq!(BandwidthClass::from_kbps(1024), BandwidthClass::Medium);
+        assert_eq!(BandwidthClass::from_kbps(50_000), BandwidthClass::High);
+        assert!(BandwidthClass::Low < BandwidthClass::High);
+    }
+
     #[test]
     fn test_capabilities_empty() {
         let empty = Capabilities::empty();
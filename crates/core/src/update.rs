@@ -0,0 +1,290 @@
+//! Signed release manifests for node self-update
+//!
+//! Each release channel publishes a [`SignedManifest`]: the JSON text of a
+//! [`ReleaseManifest`] plus an ed25519 signature over
+//! `"craftnet-release-manifest-v1" || manifest`. Nodes only accept a
+//! manifest signed by one of their embedded [`release_keys`], for the
+//! channel they follow, with a version newer than their own, and only
+//! install an artifact whose SHA-256 matches the manifest.
+//!
+//! Release keys are embedded at build time from `CRAFTNET_RELEASE_KEYS`
+//! (comma-separated hex ed25519 public keys). A build without them cannot
+//! verify any manifest, so it never updates itself.
+
+use std::cmp::Ordering;
+
+use craftec_crypto::{sign_data, verify_signature, SigningKeypair};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{CraftNetError, PublicKey, Result};
+
+const MANIFEST_DOMAIN: &[u8] = b"craftnet-release-manifest-v1";
+
+/// Release channel a node follows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateChannel {
+    /// Tested releases
+    #[default]
+    Stable,
+    /// Release candidates, ahead of stable
+    Beta,
+}
+
+impl UpdateChannel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Stable => "stable",
+            Self::Beta => "beta",
+        }
+    }
+}
+
+impl std::str::FromStr for UpdateChannel {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "stable" => Ok(Self::Stable),
+            "beta" => Ok(Self::Beta),
+            other => Err(format!("unknown update channel '{}' (stable, beta)", other)),
+        }
+    }
+}
+
+/// One downloadable binary of a release
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReleaseArtifact {
+    /// `<arch>-<os>`, e.g. `x86_64-linux` (see [`current_target`])
+    pub target: String,
+    /// Download URL
+    pub url: String,
+    /// Hex SHA-256 of the binary
+    pub sha256: String,
+    /// Size in bytes
+    pub size: u64,
+}
+
+/// A release on one channel
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReleaseManifest {
+    pub channel: UpdateChannel,
+    /// Release version (`MAJOR.MINOR.PATCH[-PRE]`)
+    pub version: String,
+    /// Unix seconds
+    pub published_at: u64,
+    #[serde(default)]
+    pub notes: Option<String>,
+    pub artifacts: Vec<ReleaseArtifact>,
+}
+
+impl ReleaseManifest {
+    /// Artifact for a build target
+    pub fn artifact(&self, target: &str) -> Option<&ReleaseArtifact> {
+        self.artifacts.iter().find(|a| a.target == target)
+    }
+
+    /// Whether this release is newer than `current`
+    pub fn is_newer_than(&self, current: &str) -> bool {
+        compare_versions(&self.version, current) == Some(Ordering::Greater)
+    }
+}
+
+/// A manifest as published: its exact JSON text and the signature over it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedManifest {
+    /// JSON text of a [`ReleaseManifest`]
+    pub manifest: String,
+    /// Hex ed25519 public key that signed it
+    pub key: String,
+    /// Hex ed25519 signature
+    pub signature: String,
+}
+
+impl SignedManifest {
+    /// Sign a manifest (release tooling)
+    pub fn sign(keypair: &SigningKeypair, manifest: &ReleaseManifest) -> Result<Self> {
+        let manifest = serde_json::to_string(manifest)
+            .map_err(|e| CraftNetError::SerializationError(e.to_string()))?;
+        let signature = sign_data(keypair, &signable(&manifest));
+        Ok(Self {
+            manifest,
+            key: hex::encode(keypair.public_key_bytes()),
+            signature: hex::encode(signature),
+        })
+    }
+
+    /// Check the signature against `trusted_keys` and the channel, and
+    /// return the manifest
+    pub fn verify(&self, trusted_keys: &[PublicKey], channel: UpdateChannel) -> Result<ReleaseManifest> {
+        let key: PublicKey = hex::decode(&self.key)
+            .ok()
+            .and_then(|b| b.try_into().ok())
+            .ok_or(CraftNetError::InvalidPublicKey)?;
+        if !trusted_keys.contains(&key) {
+            return Err(CraftNetError::InvalidRelease(format!("manifest signed by untrusted key {}", self.key)));
+        }
+        let signature: [u8; 64] = hex::decode(&self.signature)
+            .ok()
+            .and_then(|b| b.try_into().ok())
+            .ok_or(CraftNetError::InvalidSignature)?;
+        if !verify_signature(&key, &signable(&self.manifest), &signature) {
+            return Err(CraftNetError::InvalidSignature);
+        }
+        let manifest: ReleaseManifest = serde_json::from_str(&self.manifest)
+            .map_err(|e| CraftNetError::InvalidRelease(format!("bad manifest: {}", e)))?;
+        if manifest.channel != channel {
+            return Err(CraftNetError::InvalidRelease(format!(
+                "manifest is for channel {}, expected {}",
+                manifest.channel.as_str(),
+                channel.as_str()
+            )));
+        }
+        Ok(manifest)
+    }
+}
+
+fn signable(manifest: &str) -> Vec<u8> {
+    let mut data = MANIFEST_DOMAIN.to_vec();
+    data.extend_from_slice(manifest.as_bytes());
+    data
+}
+
+/// Check a downloaded binary against its manifest entry
+pub fn verify_artifact(artifact: &ReleaseArtifact, bytes: &[u8]) -> Result<()> {
+    if bytes.len() as u64 != artifact.size {
+        return Err(CraftNetError::InvalidRelease(format!(
+            "{} is {} bytes, manifest says {}",
+            artifact.url,
+            bytes.len(),
+            artifact.size
+        )));
+    }
+    let digest = hex::encode(Sha256::digest(bytes));
+    if !digest.eq_ignore_ascii_case(&artifact.sha256) {
+        return Err(CraftNetError::InvalidRelease(format!("{} SHA-256 mismatch", artifact.url)));
+    }
+    Ok(())
+}
+
+/// Release signing keys embedded at build time (`CRAFTNET_RELEASE_KEYS`)
+pub fn release_keys() -> Vec<PublicKey> {
+    option_env!("CRAFTNET_RELEASE_KEYS")
+        .unwrap_or("")
+        .split(',')
+        .filter_map(|k| hex::decode(k.trim()).ok()?.try_into().ok())
+        .collect()
+}
+
+/// Artifact target of this build (`x86_64-linux`, `aarch64-macos`, ...)
+pub fn current_target() -> String {
+    format!("{}-{}", std::env::consts::ARCH, std::env::consts::OS)
+}
+
+/// Compare `MAJOR.MINOR.PATCH[-PRE]` versions; a pre-release sorts before
+/// its release. None if either does not parse.
+pub fn compare_versions(a: &str, b: &str) -> Option<Ordering> {
+    let (a_core, a_pre) = parse_version(a)?;
+    let (b_core, b_pre) = parse_version(b)?;
+    Some(a_core.cmp(&b_core).then_with(|| match (a_pre, b_pre) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a), Some(b)) => compare_pre_release(a, b),
+    }))
+}
+
+fn parse_version(v: &str) -> Option<([u64; 3], Option<&str>)> {
+    let v = v.trim().trim_start_matches('v');
+    let (core, pre) = match v.split_once('-') {
+        Some((core, pre)) => (core, Some(pre)),
+        None => (v, None),
+    };
+    let mut parts = core.split('.').map(|p| p.parse::<u64>().ok());
+    let version = [parts.next()??, parts.next()??, parts.next()??];
+    if parts.next().is_some() {
+        return None;
+    }
+    Some((version, pre))
+}
+
+/// Dot-separated identifiers; numeric ones compare numerically
+fn compare_pre_release(a: &str, b: &str) -> Ordering {
+    let mut a_ids = a.split('.');
+    let mut b_ids = b.split('.');
+    loop {
+        match (a_ids.next(), b_ids.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => {
+                let ord = match (x.parse::<u64>(), y.parse::<u64>()) {
+                    (Ok(x), Ok(y)) => x.cmp(&y),
+                    _ => x.cmp(y),
+                };
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(channel: UpdateChannel, version: &str, binary: &[u8]) -> ReleaseManifest {
+        ReleaseManifest {
+            channel,
+            version: version.to_string(),
+            published_at: 1_700_000_000,
+            notes: None,
+            artifacts: vec![ReleaseArtifact {
+                target: "x86_64-linux".to_string(),
+                url: "https://example.com/craftnet".to_string(),
+                sha256: hex::encode(Sha256::digest(binary)),
+                size: binary.len() as u64,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_signed_manifest_roundtrip() {
+        let keypair = SigningKeypair::generate();
+        let key = keypair.public_key_bytes();
+        let signed = SignedManifest::sign(&keypair, &manifest(UpdateChannel::Beta, "0.3.0-rc.1", b"bin")).unwrap();
+
+        let verified = signed.verify(&[key], UpdateChannel::Beta).unwrap();
+        assert_eq!(verified.version, "0.3.0-rc.1");
+
+        assert!(signed.verify(&[[7u8; 32]], UpdateChannel::Beta).is_err());
+        assert!(signed.verify(&[key], UpdateChannel::Stable).is_err());
+
+        let mut tampered = signed.clone();
+        tampered.manifest = tampered.manifest.replace("0.3.0-rc.1", "9.9.9");
+        assert!(matches!(tampered.verify(&[key], UpdateChannel::Beta), Err(CraftNetError::InvalidSignature)));
+    }
+
+    #[test]
+    fn test_verify_artifact() {
+        let m = manifest(UpdateChannel::Stable, "1.0.0", b"binary");
+        let artifact = m.artifact("x86_64-linux").unwrap();
+        assert!(verify_artifact(artifact, b"binary").is_ok());
+        assert!(verify_artifact(artifact, b"binarz").is_err());
+        assert!(verify_artifact(artifact, b"bin").is_err());
+        assert!(m.artifact("aarch64-macos").is_none());
+    }
+
+    #[test]
+    fn test_version_ordering() {
+        assert_eq!(compare_versions("1.2.3", "1.2.3"), Some(Ordering::Equal));
+        assert_eq!(compare_versions("1.10.0", "1.9.9"), Some(Ordering::Greater));
+        assert_eq!(compare_versions("v2.0.0", "1.99.0"), Some(Ordering::Greater));
+        assert_eq!(compare_versions("1.0.0-beta.2", "1.0.0"), Some(Ordering::Less));
+        assert_eq!(compare_versions("1.0.0-beta.10", "1.0.0-beta.2"), Some(Ordering::Greater));
+        assert_eq!(compare_versions("1.0", "1.0.0"), None);
+        assert!(!manifest(UpdateChannel::Stable, "garbage", b"").is_newer_than("1.0.0"));
+    }
+}
@@ -0,0 +1,158 @@
+//! WebSocket session types
+//!
+//! When a request's `ExitPayload.mode` is `PAYLOAD_MODE_WEBSOCKET`, its data
+//! is a [`WsRequest`] for one WebSocket session. Like a TCP tunnel, the
+//! session lives at the exit under a client-chosen `session_id` and is
+//! carried by a series of bursts: the client opens it, then sends the
+//! messages queued since its last burst (or none, to poll) and gets back the
+//! messages the origin sent meanwhile as a [`WsReply`].
+//!
+//! Ping/pong frames never cross the tunnel. The exit answers the origin's
+//! pings and pings the origin itself; the client's bursts are its keepalive,
+//! and a session it stops polling is closed after the exit's idle timeout.
+
+use serde::{Deserialize, Serialize};
+
+use crate::Id;
+
+/// Payload mode: WebSocket session burst
+pub const PAYLOAD_MODE_WEBSOCKET: u8 = 0x04;
+
+/// Close code sent when the client closes without giving one (normal closure)
+pub const WS_CLOSE_NORMAL: u16 = 1000;
+
+/// Close code reported when a session ends without a close frame
+pub const WS_CLOSE_ABNORMAL: u16 = 1006;
+
+/// A WebSocket data message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WsMessage {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+impl WsMessage {
+    /// Payload length in bytes
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Text(text) => text.len(),
+            Self::Binary(data) => data.len(),
+        }
+    }
+
+    /// Whether the payload is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Why a session closed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WsClose {
+    pub code: u16,
+    pub reason: String,
+}
+
+/// What a client burst asks of its session
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WsAction {
+    /// Perform the upgrade against the origin
+    Open {
+        /// `ws://` or `wss://` URL
+        url: String,
+        /// Extra handshake headers
+        headers: Vec<(String, String)>,
+        /// Offered subprotocols (`Sec-WebSocket-Protocol`)
+        protocols: Vec<String>,
+    },
+    /// Forward these messages (none: just poll)
+    Send(Vec<WsMessage>),
+    /// Close the session
+    Close(WsClose),
+}
+
+/// One client burst of a WebSocket session
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WsRequest {
+    pub session_id: Id,
+    pub action: WsAction,
+}
+
+impl WsRequest {
+    /// Serialize to bytes
+    pub fn to_bytes(&self) -> Result<Vec<u8>, bincode::Error> {
+        bincode::serialize(self)
+    }
+
+    /// Deserialize from bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, bincode::Error> {
+        bincode::deserialize(bytes)
+    }
+}
+
+/// The exit's answer to a [`WsRequest`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WsReply {
+    /// The upgrade succeeded
+    Opened {
+        /// Subprotocol the origin picked
+        protocol: Option<String>,
+    },
+    /// Messages from the origin since the last burst, and the close once
+    /// the session ended (no further bursts are accepted after it)
+    Messages {
+        messages: Vec<WsMessage>,
+        close: Option<WsClose>,
+    },
+    /// The exit could not open or find the session
+    Error(String),
+}
+
+impl WsReply {
+    /// Serialize to bytes
+    pub fn to_bytes(&self) -> Result<Vec<u8>, bincode::Error> {
+        bincode::serialize(self)
+    }
+
+    /// Deserialize from bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, bincode::Error> {
+        bincode::deserialize(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ws_roundtrip() {
+        let request = WsRequest {
+            session_id: [5u8; 32],
+            action: WsAction::Open {
+                url: "wss://echo.example/socket".to_string(),
+                headers: vec![("Origin".to_string(), "https://app.example".to_string())],
+                protocols: vec!["chat".to_string()],
+            },
+        };
+        assert_eq!(WsRequest::from_bytes(&request.to_bytes().unwrap()).unwrap(), request);
+
+        let reply = WsReply::Messages {
+            messages: vec![WsMessage::Text("hi".to_string()), WsMessage::Binary(vec![1, 2, 3])],
+            close: Some(WsClose { code: WS_CLOSE_NORMAL, reason: "bye".to_string() }),
+        };
+        assert_eq!(WsReply::from_bytes(&reply.to_bytes().unwrap()).unwrap(), reply);
+        assert_eq!(WsMessage::Binary(vec![1, 2, 3]).len(), 3);
+    }
+
+    #[test]
+    fn test_websocket_mode_distinct_from_other_modes() {
+        for mode in [
+            crate::PAYLOAD_MODE_HTTP,
+            crate::PAYLOAD_MODE_TUNNEL,
+            crate::PAYLOAD_MODE_HTTP_STREAM,
+            crate::PAYLOAD_MODE_HTTP_BATCH,
+        ] {
+            assert_ne!(PAYLOAD_MODE_WEBSOCKET, mode);
+        }
+    }
+}
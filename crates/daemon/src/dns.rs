@@ -0,0 +1,397 @@
+//! In-tunnel DNS stub resolver
+//!
+//! Traffic carried by the tunnel is useless for privacy if the system
+//! resolver still asks the local network's DNS server where it is going.
+//! With the stub enabled (`dns.enabled` in the settings, or the
+//! `set_dns_stub` IPC method) the daemon listens for UDP queries on
+//! `dns.listen`; point the system resolver there.
+//!
+//! - Queries are POSTed to the `dns.doh_url` DNS-over-HTTPS endpoint as a
+//!   tunnelled request, so the exit resolves them and nothing is sent in
+//!   the clear.
+//! - Names whose split tunneling rule is `bypass` are resolved directly
+//!   with `dns.bypass_resolver`; the stub answers with a synthesized
+//!   response holding only the A/AAAA records and a short TTL, so rule
+//!   edits take effect quickly.
+//! - While the kill switch is engaged every query is refused, and when the
+//!   tunnel is down tunnelled names fail with SERVFAIL. The stub never
+//!   falls back to the system resolver.
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::net::UdpSocket;
+use tracing::{debug, info, warn};
+
+/// Largest DNS message handled (EDNS0 payload size most resolvers use)
+const MAX_DNS_MESSAGE: usize = 4096;
+
+/// TTL of synthesized answers for bypass domains
+const BYPASS_TTL_SECS: u32 = 60;
+
+/// How long the direct resolver gets to answer a bypass query
+const BYPASS_TIMEOUT: Duration = Duration::from_secs(3);
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+
+const RCODE_FORMERR: u8 = 1;
+const RCODE_SERVFAIL: u8 = 2;
+const RCODE_REFUSED: u8 = 5;
+
+/// What the stub needs from the daemon
+pub(crate) trait DnsBackend: Send + Sync + 'static {
+    /// Whether the kill switch is blocking traffic outside the tunnel
+    fn kill_switch_engaged(&self) -> impl std::future::Future<Output = bool> + Send;
+
+    /// Whether split tunneling routes `domain` around the tunnel
+    fn bypass(&self, domain: &str) -> bool;
+
+    /// Resolve a DNS message through the tunnel via DNS-over-HTTPS
+    fn resolve_doh(&self, query: Vec<u8>) -> impl std::future::Future<Output = Result<Vec<u8>, String>> + Send;
+}
+
+/// Stub status for IPC
+#[derive(Debug, Clone, Serialize)]
+pub struct DnsStubStatus {
+    pub enabled: bool,
+    /// Address the stub is listening on (None = not running)
+    pub listening: Option<String>,
+    pub doh_url: String,
+    pub bypass_resolver: String,
+}
+
+/// Running stub listener
+pub(crate) struct DnsStub {
+    listen_addr: SocketAddr,
+    handle: tokio::task::JoinHandle<()>,
+}
+
+impl DnsStub {
+    /// Bind `listen` and answer queries in a background task
+    pub(crate) async fn start<B: DnsBackend>(
+        listen: SocketAddr,
+        bypass_resolver: SocketAddr,
+        backend: Arc<B>,
+    ) -> std::io::Result<Self> {
+        let socket = Arc::new(UdpSocket::bind(listen).await?);
+        let listen_addr = socket.local_addr()?;
+        info!("DNS stub listening on {}", listen_addr);
+
+        let handle = tokio::spawn(async move {
+            let mut buf = vec![0u8; MAX_DNS_MESSAGE];
+            loop {
+                let (n, from) = match socket.recv_from(&mut buf).await {
+                    Ok(received) => received,
+                    Err(e) => {
+                        debug!("DNS stub receive error: {}", e);
+                        continue;
+                    }
+                };
+                let query = buf[..n].to_vec();
+                let socket = Arc::clone(&socket);
+                let backend = Arc::clone(&backend);
+                tokio::spawn(async move {
+                    if let Some(answer) = answer_query(backend.as_ref(), bypass_resolver, &query).await {
+                        let _ = socket.send_to(&answer, from).await;
+                    }
+                });
+            }
+        });
+
+        Ok(Self { listen_addr, handle })
+    }
+
+    pub(crate) fn listen_addr(&self) -> SocketAddr {
+        self.listen_addr
+    }
+}
+
+impl Drop for DnsStub {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// Answer one query (None = not a DNS query, drop it)
+async fn answer_query<B: DnsBackend>(backend: &B, bypass_resolver: SocketAddr, query: &[u8]) -> Option<Vec<u8>> {
+    let Some(question) = Question::parse(query) else {
+        return (query.len() >= 12).then(|| error_response(query, RCODE_FORMERR));
+    };
+
+    if backend.kill_switch_engaged().await {
+        debug!("DNS stub refusing {} while the kill switch is engaged", question.name);
+        return Some(error_response(query, RCODE_REFUSED));
+    }
+
+    if backend.bypass(&question.name) {
+        return Some(match resolve_direct(bypass_resolver, &question).await {
+            Ok(ips) => synthesize(query, &question, &ips),
+            Err(e) => {
+                warn!("Direct lookup of bypass domain {} failed: {}", question.name, e);
+                error_response(query, RCODE_SERVFAIL)
+            }
+        });
+    }
+
+    match backend.resolve_doh(query.to_vec()).await {
+        Ok(mut answer) if answer.len() >= 12 => {
+            // DoH clients may send id 0; restore the asker's
+            answer[..2].copy_from_slice(&query[..2]);
+            Some(answer)
+        }
+        Ok(_) => Some(error_response(query, RCODE_SERVFAIL)),
+        Err(e) => {
+            debug!("DoH lookup of {} failed: {}", question.name, e);
+            Some(error_response(query, RCODE_SERVFAIL))
+        }
+    }
+}
+
+/// The single question of a query
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Question {
+    /// Lowercase name without the trailing dot
+    name: String,
+    qtype: u16,
+    /// Offset just past the question section
+    end: usize,
+}
+
+impl Question {
+    fn parse(msg: &[u8]) -> Option<Self> {
+        if msg.len() < 12 || u16::from_be_bytes([msg[4], msg[5]]) != 1 {
+            return None;
+        }
+        let mut labels = Vec::new();
+        let mut pos = 12;
+        loop {
+            let len = *msg.get(pos)? as usize;
+            pos += 1;
+            if len == 0 {
+                break;
+            }
+            // Compression pointers have no place in a query's question
+            if len & 0xC0 != 0 {
+                return None;
+            }
+            labels.push(String::from_utf8_lossy(msg.get(pos..pos + len)?).to_ascii_lowercase());
+            pos += len;
+        }
+        let qtype = u16::from_be_bytes([*msg.get(pos)?, *msg.get(pos + 1)?]);
+        msg.get(pos + 3)?;
+        Some(Self { name: labels.join("."), qtype, end: pos + 4 })
+    }
+}
+
+/// Header of a response to `query` (id, opcode and RD copied) with `rcode`
+fn response_header(query: &[u8], rcode: u8, qdcount: u16, ancount: u16) -> Vec<u8> {
+    let mut msg = Vec::with_capacity(512);
+    msg.extend_from_slice(&query[..2]);
+    // QR, opcode and RD from the query; RA set
+    msg.push(0x80 | (query[2] & 0x79));
+    msg.push(0x80 | (rcode & 0x0F));
+    msg.extend_from_slice(&qdcount.to_be_bytes());
+    msg.extend_from_slice(&ancount.to_be_bytes());
+    msg.extend_from_slice(&[0, 0, 0, 0]);
+    msg
+}
+
+/// Header-only (plus question, when parseable) error response
+fn error_response(query: &[u8], rcode: u8) -> Vec<u8> {
+    match Question::parse(query) {
+        Some(question) => {
+            let mut msg = response_header(query, rcode, 1, 0);
+            msg.extend_from_slice(&query[12..question.end]);
+            msg
+        }
+        None => response_header(query, rcode, 0, 0),
+    }
+}
+
+/// Response answering `question` with the addresses of its type
+fn synthesize(query: &[u8], question: &Question, ips: &[IpAddr]) -> Vec<u8> {
+    let rdata: Vec<Vec<u8>> = ips
+        .iter()
+        .filter_map(|ip| match (ip, question.qtype) {
+            (IpAddr::V4(v4), TYPE_A) => Some(v4.octets().to_vec()),
+            (IpAddr::V6(v6), TYPE_AAAA) => Some(v6.octets().to_vec()),
+            _ => None,
+        })
+        .collect();
+    let mut msg = response_header(query, 0, 1, rdata.len() as u16);
+    msg.extend_from_slice(&query[12..question.end]);
+    for data in rdata {
+        // Name: pointer to the question
+        msg.extend_from_slice(&[0xC0, 0x0C]);
+        msg.extend_from_slice(&question.qtype.to_be_bytes());
+        msg.extend_from_slice(&CLASS_IN.to_be_bytes());
+        msg.extend_from_slice(&BYPASS_TTL_SECS.to_be_bytes());
+        msg.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        msg.extend_from_slice(&data);
+    }
+    msg
+}
+
+/// Ask `resolver` directly for the addresses of `question`
+async fn resolve_direct(resolver: SocketAddr, question: &Question) -> std::io::Result<Vec<IpAddr>> {
+    if question.qtype != TYPE_A && question.qtype != TYPE_AAAA {
+        return Ok(Vec::new());
+    }
+    let mut query = vec![0u8; 12];
+    let id: u16 = rand::random();
+    query[..2].copy_from_slice(&id.to_be_bytes());
+    // RD, one question
+    query[2] = 0x01;
+    query[5] = 1;
+    for label in question.name.split('.').filter(|l| !l.is_empty()) {
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&question.qtype.to_be_bytes());
+    query.extend_from_slice(&CLASS_IN.to_be_bytes());
+
+    let bind: SocketAddr = if resolver.is_ipv4() { ([0, 0, 0, 0], 0).into() } else { (std::net::Ipv6Addr::UNSPECIFIED, 0).into() };
+    let socket = UdpSocket::bind(bind).await?;
+    socket.connect(resolver).await?;
+    socket.send(&query).await?;
+    let mut buf = vec![0u8; MAX_DNS_MESSAGE];
+    let n = tokio::time::timeout(BYPASS_TIMEOUT, socket.recv(&mut buf))
+        .await
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "resolver did not answer"))??;
+    if n < 12 || buf[..2] != id.to_be_bytes() {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "unexpected answer"));
+    }
+    Ok(answer_addresses(&buf[..n]))
+}
+
+/// A/AAAA addresses in the answer section of a response
+fn answer_addresses(msg: &[u8]) -> Vec<IpAddr> {
+    let qdcount = u16::from_be_bytes([msg[4], msg[5]]);
+    let ancount = u16::from_be_bytes([msg[6], msg[7]]);
+    let mut pos = 12;
+    for _ in 0..qdcount {
+        let Some(end) = skip_name(msg, pos) else { return Vec::new() };
+        pos = end + 4;
+    }
+    let mut ips = Vec::new();
+    for _ in 0..ancount {
+        let Some(end) = skip_name(msg, pos) else { break };
+        let Some(fixed) = msg.get(end..end + 10) else { break };
+        let rtype = u16::from_be_bytes([fixed[0], fixed[1]]);
+        let rdlen = u16::from_be_bytes([fixed[8], fixed[9]]) as usize;
+        let Some(rdata) = msg.get(end + 10..end + 10 + rdlen) else { break };
+        match (rtype, rdata.len()) {
+            (TYPE_A, 4) => ips.push(IpAddr::from(<[u8; 4]>::try_from(rdata).unwrap())),
+            (TYPE_AAAA, 16) => ips.push(IpAddr::from(<[u8; 16]>::try_from(rdata).unwrap())),
+            _ => {}
+        }
+        pos = end + 10 + rdlen;
+    }
+    ips
+}
+
+/// Offset just past the (possibly compressed) name at `pos`
+fn skip_name(msg: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *msg.get(pos)?;
+        if len == 0 {
+            return Some(pos + 1);
+        }
+        if len & 0xC0 == 0xC0 {
+            msg.get(pos + 1)?;
+            return Some(pos + 2);
+        }
+        pos += 1 + len as usize;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    struct FakeBackend {
+        engaged: AtomicBool,
+        bypass_domain: &'static str,
+    }
+
+    impl DnsBackend for FakeBackend {
+        async fn kill_switch_engaged(&self) -> bool {
+            self.engaged.load(Ordering::Relaxed)
+        }
+
+        fn bypass(&self, domain: &str) -> bool {
+            domain == self.bypass_domain
+        }
+
+        async fn resolve_doh(&self, query: Vec<u8>) -> Result<Vec<u8>, String> {
+            let question = Question::parse(&query).ok_or("bad query")?;
+            let mut answer = synthesize(&query, &question, &["93.184.216.34".parse().unwrap()]);
+            // DoH answers come back with id 0
+            answer[..2].copy_from_slice(&[0, 0]);
+            Ok(answer)
+        }
+    }
+
+    fn query(id: u16, name: &str, qtype: u16) -> Vec<u8> {
+        let mut msg = vec![0u8; 12];
+        msg[..2].copy_from_slice(&id.to_be_bytes());
+        msg[2] = 0x01;
+        msg[5] = 1;
+        for label in name.split('.') {
+            msg.push(label.len() as u8);
+            msg.extend_from_slice(label.as_bytes());
+        }
+        msg.push(0);
+        msg.extend_from_slice(&qtype.to_be_bytes());
+        msg.extend_from_slice(&CLASS_IN.to_be_bytes());
+        msg
+    }
+
+    fn rcode(msg: &[u8]) -> u8 {
+        msg[3] & 0x0F
+    }
+
+    #[test]
+    fn test_question_and_synthesized_answer() {
+        let q = query(7, "Example.COM", TYPE_A);
+        let question = Question::parse(&q).unwrap();
+        assert_eq!(question.name, "example.com");
+        assert_eq!(question.qtype, TYPE_A);
+
+        let ips: Vec<IpAddr> = vec!["10.0.0.1".parse().unwrap(), "::1".parse().unwrap()];
+        let answer = synthesize(&q, &question, &ips);
+        assert_eq!(&answer[..2], &7u16.to_be_bytes());
+        assert_eq!(rcode(&answer), 0);
+        // Only the A record matches the question
+        assert_eq!(answer_addresses(&answer), vec!["10.0.0.1".parse::<IpAddr>().unwrap()]);
+    }
+
+    #[tokio::test]
+    async fn test_answers_follow_kill_switch_and_split_tunnel() {
+        let backend = FakeBackend { engaged: AtomicBool::new(false), bypass_domain: "bank.example" };
+        // Nothing listens here, so a direct lookup fails fast or times out
+        let resolver: SocketAddr = "127.0.0.1:9".parse().unwrap();
+
+        let answer = answer_query(&backend, resolver, &query(42, "example.com", TYPE_A)).await.unwrap();
+        assert_eq!(&answer[..2], &42u16.to_be_bytes());
+        assert_eq!(answer_addresses(&answer), vec!["93.184.216.34".parse::<IpAddr>().unwrap()]);
+
+        // Bypass domains never go to DoH
+        let answer = answer_query(&backend, resolver, &query(43, "bank.example", TYPE_A)).await.unwrap();
+        assert_eq!(rcode(&answer), RCODE_SERVFAIL);
+
+        backend.engaged.store(true, Ordering::Relaxed);
+        let answer = answer_query(&backend, resolver, &query(44, "example.com", TYPE_A)).await.unwrap();
+        assert_eq!(rcode(&answer), RCODE_REFUSED);
+
+        // Garbage is answered with FORMERR or dropped
+        assert_eq!(rcode(&answer_query(&backend, resolver, &[0u8; 12]).await.unwrap()), RCODE_FORMERR);
+        assert!(answer_query(&backend, resolver, &[1, 2, 3]).await.is_none());
+    }
+}
@@ -0,0 +1,471 @@
+//! Health endpoint and service-manager readiness
+//!
+//! A small HTTP/1.1 listener for orchestrators and load balancers:
+//!
+//! - `GET /healthz` - liveness: 200 while the node task answers (or has not
+//!   been started yet), 503 once it stops responding
+//! - `GET /readyz` - readiness: 200 once the swarm has peers, the node has
+//!   reached a bootstrap peer and the settlement RPC is reachable
+//! - `GET /metrics` - Prometheus text exposition: peer count, readiness and
+//!   the node's traffic accounting (bytes by protocol and direction)
+//!
+//! The first two return the full [`HealthReport`] as JSON. The listener is
+//! enabled by setting `CRAFTNET_HEALTH_ADDR` (e.g. `127.0.0.1:9464`).
+//!
+//! On Linux the daemon also speaks the systemd notify protocol when
+//! `NOTIFY_SOCKET` is set: `READY=1` once startup has finished (the node is
+//! ready, or idle waiting for a `start` call over IPC), `STATUS=` lines as
+//! readiness changes, and `WATCHDOG=1` pings while live if `WatchdogSec=` is
+//! configured.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info, warn};
+
+use craftnet_client::{TrafficCounters, TrafficProtocol};
+
+use crate::service::{DaemonState, HealthProbe};
+use crate::{DaemonError, Result};
+
+/// Environment variable holding the health listener address
+pub const HEALTH_ADDR_ENV: &str = "CRAFTNET_HEALTH_ADDR";
+
+/// Largest request head accepted by the health listener
+const MAX_REQUEST_HEAD: usize = 4096;
+
+/// How long a client gets to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Content type of `/metrics` (Prometheus text format)
+const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// How often readiness is re-checked for systemd status updates
+const NOTIFY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Snapshot of daemon health
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    pub state: DaemonState,
+    /// Whether the node task answered (None = node not started)
+    pub node_responsive: Option<bool>,
+    /// Swarm is running and has at least one connected peer
+    pub swarm_ready: bool,
+    pub peer_id: Option<String>,
+    pub peer_count: usize,
+    /// Connected to a bootstrap peer
+    pub bootstrapped: bool,
+    pub settlement_reachable: bool,
+    pub settlement_error: Option<String>,
+    /// Bytes exchanged with peers per protocol (served on `/metrics` only)
+    #[serde(skip)]
+    pub traffic: Vec<(TrafficProtocol, TrafficCounters)>,
+    /// Peers with a traffic accounting row
+    #[serde(skip)]
+    pub traffic_peers: usize,
+    /// Tracked peers the relay treats as freeloaders
+    #[serde(skip)]
+    pub freeloaders: usize,
+}
+
+impl HealthReport {
+    pub(crate) fn new(state: DaemonState) -> Self {
+        Self {
+            state,
+            node_responsive: None,
+            swarm_ready: false,
+            peer_id: None,
+            peer_count: 0,
+            bootstrapped: false,
+            settlement_reachable: false,
+            settlement_error: None,
+            traffic: Vec::new(),
+            traffic_peers: 0,
+            freeloaders: 0,
+        }
+    }
+
+    /// Process is serving and the node task (if started) is responsive
+    pub fn is_live(&self) -> bool {
+        self.node_responsive != Some(false)
+    }
+
+    /// Node is joined to the network and settlement is reachable
+    /// (never while draining for shutdown)
+    pub fn is_ready(&self) -> bool {
+        self.state != DaemonState::Stopping
+            && self.node_responsive == Some(true)
+            && self.swarm_ready
+            && self.bootstrapped
+            && self.settlement_reachable
+    }
+
+    /// Startup finished from a service manager's point of view
+    fn is_started(&self) -> bool {
+        self.node_responsive.is_none() || self.is_ready()
+    }
+
+    /// Prometheus text exposition of the report
+    fn prometheus(&self) -> String {
+        use std::fmt::Write;
+
+        let mut out = String::new();
+        let mut gauge = |name: &str, help: &str, value: u64| {
+            let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} gauge\n{name} {value}");
+        };
+        gauge("craftnet_up", "Whether the node task is responsive", u64::from(self.is_live()));
+        gauge("craftnet_ready", "Whether the node is ready", u64::from(self.is_ready()));
+        gauge("craftnet_connected_peers", "Connected peers", self.peer_count as u64);
+        gauge("craftnet_traffic_tracked_peers", "Peers with a traffic accounting row", self.traffic_peers as u64);
+        gauge("craftnet_freeloader_peers", "Tracked peers treated as freeloaders", self.freeloaders as u64);
+
+        let name = "craftnet_peer_traffic_bytes_total";
+        let _ = writeln!(out, "# HELP {name} Bytes exchanged with peers\n# TYPE {name} counter");
+        for (protocol, counters) in &self.traffic {
+            for (direction, bytes) in [("in", counters.bytes_in), ("out", counters.bytes_out)] {
+                let _ = writeln!(out, "{name}{{protocol=\"{}\",direction=\"{direction}\"}} {bytes}", protocol.name());
+            }
+        }
+        out
+    }
+
+    /// One-line summary for systemd `STATUS=`
+    fn summary(&self) -> String {
+        if self.state == DaemonState::Stopping {
+            return "Draining".to_string();
+        }
+        match self.node_responsive {
+            None => "Idle, node not started".to_string(),
+            Some(false) => "Node task not responding".to_string(),
+            Some(true) if self.is_ready() => format!("Ready, {} peers", self.peer_count),
+            Some(true) if !self.bootstrapped => format!("Bootstrapping, {} peers", self.peer_count),
+            Some(true) if !self.settlement_reachable => "Settlement RPC unreachable".to_string(),
+            Some(true) => "Waiting for peers".to_string(),
+        }
+    }
+}
+
+/// Health listener address from `CRAFTNET_HEALTH_ADDR` (None = disabled)
+pub fn health_addr_from_env() -> Option<SocketAddr> {
+    let value = std::env::var(HEALTH_ADDR_ENV).ok()?;
+    match value.parse() {
+        Ok(addr) => Some(addr),
+        Err(e) => {
+            warn!("Invalid {} '{}': {}", HEALTH_ADDR_ENV, value, e);
+            None
+        }
+    }
+}
+
+/// Serve `/healthz` and `/readyz` on `listener` until the task is dropped
+pub async fn serve_health(listener: TcpListener, probe: HealthProbe) -> Result<()> {
+    if let Ok(addr) = listener.local_addr() {
+        info!("Health endpoint listening on http://{}", addr);
+    }
+    loop {
+        let (stream, peer) = listener.accept().await
+            .map_err(|e| DaemonError::IpcError(format!("Health accept failed: {}", e)))?;
+        let probe = probe.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, probe).await {
+                debug!("Health request from {} failed: {}", peer, e);
+            }
+        });
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, probe: HealthProbe) -> std::io::Result<()> {
+    let head = match tokio::time::timeout(REQUEST_TIMEOUT, read_request_head(&mut stream)).await {
+        Ok(head) => head?,
+        Err(_) => return Ok(()),
+    };
+
+    let mut content_type = "application/json";
+    let (status, body) = match parse_request_line(&head) {
+        Some(("GET" | "HEAD", path)) if is_health_path(path) => {
+            if path == "/metrics" {
+                content_type = METRICS_CONTENT_TYPE;
+            }
+            let report = probe.check().await;
+            route(path, &report)
+        }
+        Some(("GET" | "HEAD", _)) => (404, r#"{"error":"not found"}"#.to_string()),
+        Some(_) => (405, r#"{"error":"method not allowed"}"#.to_string()),
+        None => (400, r#"{"error":"bad request"}"#.to_string()),
+    };
+    let head_only = head.starts_with(b"HEAD ");
+
+    let mut response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        reason_phrase(status),
+        content_type,
+        body.len(),
+    );
+    if !head_only {
+        response.push_str(&body);
+    }
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Read until the end of the request head (bodies are ignored)
+async fn read_request_head(stream: &mut TcpStream) -> std::io::Result<Vec<u8>> {
+    let mut head = Vec::with_capacity(512);
+    let mut buf = [0u8; 512];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") && head.len() < MAX_REQUEST_HEAD {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+    Ok(head)
+}
+
+/// Method and path (query stripped) of an HTTP/1.x request line
+fn parse_request_line(head: &[u8]) -> Option<(&str, &str)> {
+    let line_end = head.windows(2).position(|w| w == b"\r\n")?;
+    let line = std::str::from_utf8(&head[..line_end]).ok()?;
+    let mut parts = line.split(' ');
+    let method = parts.next()?;
+    let target = parts.next()?;
+    let version = parts.next()?;
+    if parts.next().is_some() || !version.starts_with("HTTP/1.") {
+        return None;
+    }
+    let path = target.split('?').next().unwrap_or(target);
+    Some((method, path))
+}
+
+fn is_health_path(path: &str) -> bool {
+    matches!(path, "/healthz" | "/readyz" | "/metrics")
+}
+
+/// Status code and body for a health path (JSON, or Prometheus text for
+/// `/metrics`)
+fn route(path: &str, report: &HealthReport) -> (u16, String) {
+    let ok = match path {
+        "/metrics" => return (200, report.prometheus()),
+        "/healthz" => report.is_live(),
+        "/readyz" => report.is_ready(),
+        _ => return (404, r#"{"error":"not found"}"#.to_string()),
+    };
+    let body = serde_json::to_string(report).unwrap_or_else(|_| "{}".to_string());
+    (if ok { 200 } else { 503 }, body)
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        503 => "Service Unavailable",
+        _ => "",
+    }
+}
+
+/// Report readiness and watchdog pings to systemd while the daemon runs.
+///
+/// Returns immediately when not started under a notify-type unit.
+pub async fn run_systemd_notifier(probe: HealthProbe) {
+    if !sd_notify::enabled() {
+        return;
+    }
+    let watchdog = sd_notify::watchdog_interval();
+    let interval = watchdog.map(|w| (w / 2).min(NOTIFY_INTERVAL)).unwrap_or(NOTIFY_INTERVAL);
+    if let Some(w) = watchdog {
+        info!("systemd watchdog enabled ({:?}), pinging every {:?}", w, interval);
+    }
+
+    let mut started = false;
+    let mut last_status = String::new();
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        let report = probe.check().await;
+
+        let status = report.summary();
+        let mut msg = String::new();
+        if !started && report.is_started() {
+            started = true;
+            msg.push_str("READY=1\n");
+        }
+        if status != last_status {
+            msg.push_str(&format!("STATUS={}\n", status));
+            last_status = status;
+        }
+        if watchdog.is_some() && report.is_live() {
+            msg.push_str("WATCHDOG=1\n");
+        }
+        if !msg.is_empty() {
+            sd_notify::notify(&msg);
+        }
+    }
+}
+
+/// Tell systemd the daemon is shutting down
+pub fn notify_stopping() {
+    if sd_notify::enabled() {
+        sd_notify::notify("STOPPING=1\n");
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sd_notify {
+    use std::os::unix::net::UnixDatagram;
+    use std::time::Duration;
+
+    use tracing::debug;
+
+    pub fn enabled() -> bool {
+        std::env::var_os("NOTIFY_SOCKET").is_some()
+    }
+
+    /// `WATCHDOG_USEC` if it is meant for this process
+    pub fn watchdog_interval() -> Option<Duration> {
+        if let Ok(pid) = std::env::var("WATCHDOG_PID") {
+            if pid.parse::<u32>().ok() != Some(std::process::id()) {
+                return None;
+            }
+        }
+        let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+        (usec > 0).then(|| Duration::from_micros(usec))
+    }
+
+    pub fn notify(msg: &str) {
+        let Some(path) = std::env::var_os("NOTIFY_SOCKET") else { return };
+        if let Err(e) = notify_to(&path.to_string_lossy(), msg) {
+            debug!("sd_notify failed: {}", e);
+        }
+    }
+
+    /// Send one datagram to a notify socket path (`@` = abstract namespace)
+    pub(super) fn notify_to(path: &str, msg: &str) -> std::io::Result<()> {
+        let socket = UnixDatagram::unbound()?;
+        if let Some(name) = path.strip_prefix('@') {
+            use std::os::linux::net::SocketAddrExt;
+            let addr = std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes())?;
+            socket.send_to_addr(msg.as_bytes(), &addr)?;
+        } else {
+            socket.send_to(msg.as_bytes(), path)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sd_notify {
+    use std::time::Duration;
+
+    pub fn enabled() -> bool {
+        false
+    }
+
+    pub fn watchdog_interval() -> Option<Duration> {
+        None
+    }
+
+    pub fn notify(_msg: &str) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ready_report() -> HealthReport {
+        HealthReport {
+            state: DaemonState::Ready,
+            node_responsive: Some(true),
+            swarm_ready: true,
+            peer_id: Some("12D3KooWExample".to_string()),
+            peer_count: 4,
+            bootstrapped: true,
+            settlement_reachable: true,
+            settlement_error: None,
+            traffic: Vec::new(),
+            traffic_peers: 0,
+            freeloaders: 0,
+        }
+    }
+
+    #[test]
+    fn test_route_status_codes() {
+        let report = ready_report();
+        assert_eq!(route("/healthz", &report).0, 200);
+        assert_eq!(route("/readyz", &report).0, 200);
+        assert_eq!(route("/status", &report).0, 404);
+
+        let mut bootstrapping = ready_report();
+        bootstrapping.bootstrapped = false;
+        assert_eq!(route("/healthz", &bootstrapping).0, 200);
+        assert_eq!(route("/readyz", &bootstrapping).0, 503);
+
+        let mut no_settlement = ready_report();
+        no_settlement.settlement_reachable = false;
+        no_settlement.settlement_error = Some("RPC error: get_health".to_string());
+        let (status, body) = route("/readyz", &no_settlement);
+        assert_eq!(status, 503);
+        assert!(body.contains("get_health"));
+
+        let mut hung = ready_report();
+        hung.node_responsive = Some(false);
+        assert_eq!(route("/healthz", &hung).0, 503);
+
+        // Not started yet: alive, not ready, but startup is complete
+        let idle = HealthReport::new(DaemonState::Ready);
+        assert_eq!(route("/healthz", &idle).0, 200);
+        assert_eq!(route("/readyz", &idle).0, 503);
+        assert!(idle.is_started());
+    }
+
+    #[test]
+    fn test_metrics() {
+        let mut report = ready_report();
+        report.traffic = vec![
+            (TrafficProtocol::ShardStream, TrafficCounters { bytes_in: 4000, bytes_out: 1000 }),
+            (TrafficProtocol::Gossip, TrafficCounters { bytes_in: 20, bytes_out: 60 }),
+        ];
+        report.freeloaders = 1;
+        let (status, body) = route("/metrics", &report);
+        assert_eq!(status, 200);
+        assert!(body.contains("craftnet_connected_peers 4\n"));
+        assert!(body.contains("craftnet_freeloader_peers 1\n"));
+        assert!(body.contains("craftnet_peer_traffic_bytes_total{protocol=\"shard_stream\",direction=\"in\"} 4000\n"));
+        assert!(body.contains("craftnet_peer_traffic_bytes_total{protocol=\"gossip\",direction=\"out\"} 60\n"));
+
+        // Served while not ready too
+        assert_eq!(route("/metrics", &HealthReport::new(DaemonState::Ready)).0, 200);
+    }
+
+    #[test]
+    fn test_parse_request_line() {
+        assert_eq!(
+            parse_request_line(b"GET /readyz?verbose=1 HTTP/1.1\r\nHost: x\r\n\r\n"),
+            Some(("GET", "/readyz"))
+        );
+        assert_eq!(parse_request_line(b"GET /healthz HTTP/1.0\r\n\r\n"), Some(("GET", "/healthz")));
+        assert_eq!(parse_request_line(b"GET /healthz\r\n\r\n"), None);
+        assert_eq!(parse_request_line(b"GET /healthz SPDY/3\r\n\r\n"), None);
+        assert_eq!(parse_request_line(b"GET /healthz HTTP/1.1"), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_notify_to_socket() {
+        let path = std::env::temp_dir().join(format!("craftnet-notify-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let server = std::os::unix::net::UnixDatagram::bind(&path).unwrap();
+
+        sd_notify::notify_to(path.to_str().unwrap(), "READY=1\nSTATUS=Ready\n").unwrap();
+        let mut buf = [0u8; 64];
+        let n = server.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"READY=1\nSTATUS=Ready\n");
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,183 @@
+//! Persistent connection history
+//!
+//! Every tunnel session (and every failed connect) is appended as one JSON
+//! line to `craftnet_history.jsonl` next to the settings file, so past
+//! sessions survive daemon restarts. When the file would grow past
+//! [`MAX_HISTORY_FILE_BYTES`] it is rotated to `{path}.1`, replacing the
+//! previous rotation. The most recent [`MAX_HISTORY_ENTRIES`] entries are
+//! loaded on start and served by the `get_connection_history` IPC method.
+
+use std::collections::VecDeque;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// Size at which the history file is rotated (256 KiB)
+pub const MAX_HISTORY_FILE_BYTES: u64 = 256 * 1024;
+
+/// Entries kept in memory and returned by queries
+pub const MAX_HISTORY_ENTRIES: usize = 1000;
+
+/// Connection history entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConnectionHistoryEntry {
+    pub id: u64,
+    pub connected_at: u64,
+    pub disconnected_at: Option<u64>,
+    pub duration_secs: Option<u64>,
+    /// Exit signing pubkey (hex) the session used
+    #[serde(default)]
+    pub exit: Option<String>,
+    pub exit_region: Option<String>,
+    /// Privacy level of the session (`direct` ... `quad`)
+    #[serde(default)]
+    pub hop_mode: Option<String>,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// Why the connect failed (None = the session connected)
+    #[serde(default)]
+    pub failure_reason: Option<String>,
+}
+
+/// Connection history backed by a rotated JSONL file
+#[derive(Debug)]
+pub(crate) struct ConnectionHistory {
+    path: PathBuf,
+    entries: VecDeque<ConnectionHistoryEntry>,
+    next_id: u64,
+}
+
+impl ConnectionHistory {
+    /// Load the entries recorded at `path` (and its rotation)
+    pub(crate) fn load(path: PathBuf) -> Self {
+        let mut entries = VecDeque::new();
+        for file in [rotated_path(&path), path.clone()] {
+            let Ok(f) = fs::File::open(&file) else { continue };
+            for line in BufReader::new(f).lines().map_while(|l| l.ok()) {
+                match serde_json::from_str::<ConnectionHistoryEntry>(&line) {
+                    Ok(entry) => {
+                        if entries.len() >= MAX_HISTORY_ENTRIES {
+                            entries.pop_front();
+                        }
+                        entries.push_back(entry);
+                    }
+                    Err(e) => warn!("Skipping unreadable history line in {}: {}", file.display(), e),
+                }
+            }
+        }
+        let next_id = entries.iter().map(|e| e.id).max().unwrap_or(0) + 1;
+        Self { path, entries, next_id }
+    }
+
+    /// Assign the entry an id and append it
+    pub(crate) fn record(&mut self, mut entry: ConnectionHistoryEntry) -> std::io::Result<()> {
+        entry.id = self.next_id;
+        self.next_id += 1;
+        let line = serde_json::to_string(&entry).map_err(std::io::Error::other)?;
+        if self.entries.len() >= MAX_HISTORY_ENTRIES {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+
+        let size = fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0);
+        if size > 0 && size + line.len() as u64 + 1 > MAX_HISTORY_FILE_BYTES {
+            fs::rename(&self.path, rotated_path(&self.path))?;
+        }
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(file, "{}", line)
+    }
+
+    /// Newest first, at most `limit`
+    pub(crate) fn entries(&self, limit: Option<usize>) -> Vec<ConnectionHistoryEntry> {
+        self.entries.iter().rev().take(limit.unwrap_or(usize::MAX)).cloned().collect()
+    }
+
+    /// Forget every entry and delete the files
+    pub(crate) fn clear(&mut self) -> std::io::Result<usize> {
+        let cleared = self.entries.len();
+        self.entries.clear();
+        for file in [self.path.clone(), rotated_path(&self.path)] {
+            match fs::remove_file(&file) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        Ok(cleared)
+    }
+}
+
+fn rotated_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".1");
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(connected_at: u64) -> ConnectionHistoryEntry {
+        ConnectionHistoryEntry {
+            id: 0,
+            connected_at,
+            disconnected_at: Some(connected_at + 60),
+            duration_secs: Some(60),
+            exit: Some("ab".repeat(32)),
+            exit_region: None,
+            hop_mode: Some("double".to_string()),
+            bytes_sent: 1000,
+            bytes_received: 5000,
+            failure_reason: None,
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("craftnet-history-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join("craftnet_history.jsonl")
+    }
+
+    #[test]
+    fn test_history_survives_reload() {
+        let path = temp_path("reload");
+        let mut history = ConnectionHistory::load(path.clone());
+        history.record(entry(100)).unwrap();
+        history.record(ConnectionHistoryEntry { failure_reason: Some("no exits".to_string()), ..entry(200) }).unwrap();
+
+        let mut reloaded = ConnectionHistory::load(path.clone());
+        let entries = reloaded.entries(None);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].id, 2);
+        assert_eq!(entries[0].failure_reason.as_deref(), Some("no exits"));
+        assert_eq!(reloaded.entries(Some(1)).len(), 1);
+
+        // Ids keep counting after a reload
+        reloaded.record(entry(300)).unwrap();
+        assert_eq!(reloaded.entries(None)[0].id, 3);
+
+        assert_eq!(reloaded.clear().unwrap(), 3);
+        assert!(ConnectionHistory::load(path.clone()).entries(None).is_empty());
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_history_rotates() {
+        let path = temp_path("rotate");
+        let mut history = ConnectionHistory::load(path.clone());
+        let line_len = serde_json::to_string(&entry(0)).unwrap().len() as u64 + 1;
+        let per_file = MAX_HISTORY_FILE_BYTES / line_len;
+        for i in 0..per_file + 2 {
+            history.record(entry(i)).unwrap();
+        }
+        assert!(rotated_path(&path).exists());
+        assert!(fs::metadata(&path).unwrap().len() < MAX_HISTORY_FILE_BYTES);
+        assert_eq!(ConnectionHistory::load(path.clone()).entries(None).len(), (per_file + 2).min(MAX_HISTORY_ENTRIES as u64) as usize);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -1,15 +1,26 @@
 //! IPC server for JSON-RPC communication
 //!
 //! Uses `craftec-ipc` for the shared IpcHandler trait and protocol types.
-//! Keeps CraftNet-specific IpcConfig and IpcServer (event streaming, shutdown).
+//! Keeps CraftNet-specific IpcConfig and IpcServer (event streaming, shutdown,
+//! per-connection sessions; see `session`).
 
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::path::PathBuf;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::net::{UnixListener, UnixStream};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+#[cfg(unix)]
+use tokio::net::UnixListener;
 use tokio::sync::{broadcast, mpsc};
 use tracing::{debug, error, info, warn};
 
+use craftnet_core::ErrorCode;
+
+use crate::session::{AccessPolicy, IpcSession};
+#[cfg(unix)]
+use crate::session::{current_uid, default_socket_path, PeerCredentials};
+#[cfg(unix)]
 use crate::{DaemonError, Result};
 
 // Re-export the shared IpcHandler trait from craftec-ipc
@@ -21,36 +32,271 @@ pub use craftec_ipc::protocol::RpcResponse as JsonRpcResponse;
 #[allow(unused_imports)]
 pub use craftec_ipc::protocol::RpcError as JsonRpcError;
 
+/// Error message of an [`IpcHandler`] call carrying a machine-readable code.
+///
+/// `IpcHandler::handle` can only fail with a string, so the code travels as a
+/// `CODE: ` prefix; the IPC servers move it into the JSON-RPC `error.data`
+/// (see [`error_response`]).
+pub fn coded_error(code: ErrorCode, message: impl std::fmt::Display) -> String {
+    format!("{}: {}", code.as_str(), message)
+}
+
+/// Split a handler error into its code and message. Messages without a code
+/// prefix are `INTERNAL`.
+pub fn error_code_of(error: &str) -> (ErrorCode, &str) {
+    error
+        .split_once(": ")
+        .and_then(|(code, message)| Some((ErrorCode::from_name(code)?, message)))
+        .unwrap_or((ErrorCode::Internal, error))
+}
+
+/// JSON-RPC error response with the error code in `error.data.code`
+pub(crate) fn error_response(
+    id: serde_json::Value,
+    rpc_code: i32,
+    code: ErrorCode,
+    message: String,
+) -> JsonRpcResponse {
+    let mut response = JsonRpcResponse::error(id, rpc_code, message);
+    if let Some(error) = response.error.as_mut() {
+        error.data = Some(serde_json::json!({ "code": code }));
+    }
+    response
+}
+
+/// JSON-RPC error response for a failed [`IpcHandler`] call
+pub(crate) fn handler_error_response(id: serde_json::Value, error: String) -> JsonRpcResponse {
+    let (code, message) = error_code_of(&error);
+    error_response(id, -32000, code, message.to_string())
+}
+
+/// [`IpcHandler`] that knows which session each call comes from.
+///
+/// The defaults refuse admin-only methods to non-admin sessions and
+/// otherwise defer to `handle`, so a plain handler only needs an empty impl.
+pub trait SessionHandler: IpcHandler {
+    /// A client connected
+    fn open_session(&self, _session: &IpcSession) {}
+
+    /// A client disconnected
+    fn close_session(&self, _session: &IpcSession) {}
+
+    /// Handle a call made by `session`
+    fn handle_session<'a>(
+        &'a self,
+        session: &'a IpcSession,
+        method: &'a str,
+        params: Option<serde_json::Value>,
+    ) -> Pin<Box<dyn Future<Output = std::result::Result<serde_json::Value, String>> + Send + 'a>> {
+        if let Err(e) = session.authorize(method) {
+            return Box::pin(async move { Err(e) });
+        }
+        self.handle(method, params)
+    }
+
+    /// Whether a broadcast event is forwarded to `session`
+    fn event_visible(&self, _session: &IpcSession, _event: &str) -> bool {
+        true
+    }
+
+    /// Events addressed to `session` alone, taken once after `open_session`.
+    ///
+    /// Unlike broadcast events these are never dropped: the channel is
+    /// bounded, so a slow client holds back whoever sends on it.
+    fn session_events(&self, _session: &IpcSession) -> Option<mpsc::Receiver<String>> {
+        None
+    }
+}
+
 /// IPC server configuration (CraftNet-specific defaults)
 #[derive(Debug, Clone)]
 pub struct IpcConfig {
     /// Socket path (Unix) or pipe name (Windows)
     pub socket_path: PathBuf,
+    /// Who may connect, and who is an admin
+    pub access: AccessPolicy,
 }
 
 impl Default for IpcConfig {
     fn default() -> Self {
-        let path = if cfg!(target_os = "macos") {
-            PathBuf::from("/tmp/craftnet.sock")
-        } else if cfg!(target_os = "linux") {
-            let xdg_runtime = std::env::var("XDG_RUNTIME_DIR")
-                .unwrap_or_else(|_| "/tmp".to_string());
-            PathBuf::from(format!("{}/craftnet.sock", xdg_runtime))
-        } else {
-            PathBuf::from("\\\\.\\pipe\\craftnet")
-        };
+        #[cfg(unix)]
+        let path = default_socket_path();
+        #[cfg(not(unix))]
+        let path = PathBuf::from("\\\\.\\pipe\\craftnet");
 
-        Self { socket_path: path }
+        Self { socket_path: path, access: AccessPolicy::default() }
     }
 }
 
-/// IPC server with event streaming and graceful shutdown.
+impl IpcConfig {
+    /// Default socket path with the access policy from the environment
+    /// (see `session`)
+    pub fn from_env() -> Self {
+        Self { access: AccessPolicy::from_env(), ..Self::default() }
+    }
+}
+
+/// Serve one client connection until it disconnects (shared by the Unix
+/// socket and Windows named pipe transports).
+///
+/// Requests are answered in order while broadcast events visible to the
+/// session, and the session's own events, are written between responses as
+/// they arrive; each frame is one line of JSON.
+pub(crate) async fn serve_connection<R, W, H>(
+    reader: R,
+    writer: W,
+    session: IpcSession,
+    handler: Arc<H>,
+    event_rx: Option<broadcast::Receiver<String>>,
+) where
+    R: AsyncRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
+    H: SessionHandler + 'static,
+{
+    handler.open_session(&session);
+    let session_rx = handler.session_events(&session);
+    let reader = BufReader::new(reader);
+    let writer = Arc::new(tokio::sync::Mutex::new(writer));
+
+    let request_writer = writer.clone();
+    let request_handler = handler.clone();
+    let request_session = session.clone();
+
+    // Task 1: Read JSON-RPC requests and write responses
+    let request_task = tokio::spawn(async move {
+        let mut reader = reader;
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+            let bytes_read = match reader.read_line(&mut line).await {
+                Ok(n) => n,
+                Err(e) => {
+                    debug!("Read error: {}", e);
+                    break;
+                }
+            };
+
+            if bytes_read == 0 {
+                break;
+            }
+
+            debug!("Received: {}", line.trim());
+
+            let response = match serde_json::from_str::<JsonRpcRequest>(&line) {
+                Ok(request) => {
+                    if request.jsonrpc != "2.0" {
+                        error_response(
+                            request.id,
+                            -32600,
+                            ErrorCode::InvalidRequest,
+                            "Invalid Request: jsonrpc must be '2.0'".to_string(),
+                        )
+                    } else {
+                        match request_handler.handle_session(&request_session, &request.method, request.params).await {
+                            Ok(result) => JsonRpcResponse::success(request.id, result),
+                            Err(msg) => handler_error_response(request.id, msg),
+                        }
+                    }
+                }
+                Err(e) => {
+                    error_response(
+                        serde_json::Value::Null,
+                        -32700,
+                        ErrorCode::InvalidRequest,
+                        format!("Parse error: {}", e),
+                    )
+                }
+            };
+
+            let response_str = match serde_json::to_string(&response) {
+                Ok(s) => s,
+                Err(e) => {
+                    error!("Serialize error: {}", e);
+                    break;
+                }
+            };
+
+            debug!("Sending: {}", response_str);
+            if write_frame(&request_writer, &response_str).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    // Task 2: Forward broadcast and session events to the client
+    let event_task = if event_rx.is_some() || session_rx.is_some() {
+        let event_writer = writer.clone();
+        let event_handler = handler.clone();
+        let event_session = session.clone();
+        let (mut event_rx, mut session_rx) = (event_rx, session_rx);
+        Some(tokio::spawn(async move {
+            loop {
+                let event = tokio::select! {
+                    received = async { event_rx.as_mut().unwrap().recv().await }, if event_rx.is_some() => {
+                        match received {
+                            Ok(event) => event,
+                            Err(broadcast::error::RecvError::Lagged(n)) => {
+                                warn!("Event stream lagged, missed {} events", n);
+                                continue;
+                            }
+                            Err(broadcast::error::RecvError::Closed) => {
+                                event_rx = None;
+                                continue;
+                            }
+                        }
+                    }
+                    received = async { session_rx.as_mut().unwrap().recv().await }, if session_rx.is_some() => {
+                        match received {
+                            Some(event) => event,
+                            None => {
+                                session_rx = None;
+                                continue;
+                            }
+                        }
+                    }
+                    else => break,
+                };
+                if !event_handler.event_visible(&event_session, &event) {
+                    continue;
+                }
+                if write_frame(&event_writer, &event).await.is_err() {
+                    break;
+                }
+            }
+        }))
+    } else {
+        None
+    };
+
+    // Wait for the request task to finish (client disconnected)
+    let _ = request_task.await;
+
+    // Cancel the event task
+    if let Some(task) = event_task {
+        task.abort();
+    }
+    handler.close_session(&session);
+    debug!("IPC session {} closed", session.id);
+}
+
+/// Write one newline-terminated frame
+async fn write_frame<W: AsyncWrite + Unpin>(writer: &tokio::sync::Mutex<W>, frame: &str) -> std::io::Result<()> {
+    let mut w = writer.lock().await;
+    w.write_all(frame.as_bytes()).await?;
+    w.write_all(b"\n").await?;
+    w.flush().await
+}
+
+/// Unix socket IPC server with event streaming and graceful shutdown.
+#[cfg(unix)]
 pub struct IpcServer {
     config: IpcConfig,
     shutdown_tx: Option<mpsc::Sender<()>>,
     event_tx: Option<broadcast::Sender<String>>,
 }
 
+#[cfg(unix)]
 impl IpcServer {
     /// Create a new IPC server
     pub fn new(config: IpcConfig) -> Self {
@@ -67,7 +313,7 @@ impl IpcServer {
     }
 
     /// Start the IPC server
-    pub async fn start<H: IpcHandler + 'static>(&mut self, handler: H) -> Result<()> {
+    pub async fn start<H: SessionHandler + 'static>(&mut self, handler: H) -> Result<()> {
         // Remove existing socket file
         if self.config.socket_path.exists() {
             std::fs::remove_file(&self.config.socket_path)?;
@@ -76,26 +322,48 @@ impl IpcServer {
         let listener = UnixListener::bind(&self.config.socket_path)
             .map_err(|e| DaemonError::IpcError(format!("Failed to bind: {}", e)))?;
 
-        info!("IPC server listening on {:?}", self.config.socket_path);
+        // Private sockets are owner-only; shared ones rely on peer credentials
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = if self.config.access.allow_other_users { 0o666 } else { 0o600 };
+            std::fs::set_permissions(&self.config.socket_path, std::fs::Permissions::from_mode(mode))?;
+        }
+
+        info!(
+            "IPC server listening on {:?} (other users {})",
+            self.config.socket_path,
+            if self.config.access.allow_other_users { "allowed" } else { "refused" },
+        );
 
         let (shutdown_tx, mut shutdown_rx) = mpsc::channel::<()>(1);
         self.shutdown_tx = Some(shutdown_tx);
 
         let handler = Arc::new(handler);
         let event_tx = self.event_tx.clone();
+        let daemon_uid = current_uid();
+        let next_session = AtomicU64::new(1);
 
         loop {
             tokio::select! {
                 result = listener.accept() => {
                     match result {
                         Ok((stream, _addr)) => {
-                            let handler = handler.clone();
-                            let event_rx = event_tx.as_ref().map(|tx| tx.subscribe());
-                            tokio::spawn(async move {
-                                if let Err(e) = Self::handle_connection(stream, handler, event_rx).await {
-                                    warn!("Connection error: {}", e);
+                            let peer = match stream.peer_cred() {
+                                Ok(cred) => PeerCredentials { uid: cred.uid(), gid: cred.gid(), pid: cred.pid() },
+                                Err(e) => {
+                                    warn!("Refusing IPC connection without peer credentials: {}", e);
+                                    continue;
                                 }
-                            });
+                            };
+                            let id = next_session.fetch_add(1, Ordering::Relaxed);
+                            let Some(session) = self.config.access.session(id, peer, daemon_uid) else {
+                                warn!("Refusing IPC connection from uid {} (socket is private)", peer.uid);
+                                continue;
+                            };
+                            debug!("IPC session {} opened: uid={} admin={}", session.id, peer.uid, session.admin);
+                            let (reader, writer) = stream.into_split();
+                            let event_rx = event_tx.as_ref().map(|tx| tx.subscribe());
+                            tokio::spawn(serve_connection(reader, writer, session, handler.clone(), event_rx));
                         }
                         Err(e) => {
                             error!("Accept error: {}", e);
@@ -115,122 +383,6 @@ impl IpcServer {
         Ok(())
     }
 
-    /// Handle a single connection with concurrent request handling and event streaming
-    async fn handle_connection<H: IpcHandler + 'static>(
-        stream: UnixStream,
-        handler: Arc<H>,
-        event_rx: Option<broadcast::Receiver<String>>,
-    ) -> Result<()> {
-        let (reader, writer) = stream.into_split();
-        let reader = BufReader::new(reader);
-        let writer = Arc::new(tokio::sync::Mutex::new(writer));
-
-        let request_writer = writer.clone();
-        let request_handler = handler.clone();
-
-        // Task 1: Read JSON-RPC requests and write responses
-        let request_task = tokio::spawn(async move {
-            let mut reader = reader;
-            let mut line = String::new();
-
-            loop {
-                line.clear();
-                let bytes_read = match reader.read_line(&mut line).await {
-                    Ok(n) => n,
-                    Err(e) => {
-                        debug!("Read error: {}", e);
-                        break;
-                    }
-                };
-
-                if bytes_read == 0 {
-                    break;
-                }
-
-                debug!("Received: {}", line.trim());
-
-                let response = match serde_json::from_str::<JsonRpcRequest>(&line) {
-                    Ok(request) => {
-                        if request.jsonrpc != "2.0" {
-                            JsonRpcResponse::error(
-                                request.id,
-                                -32600,
-                                "Invalid Request: jsonrpc must be '2.0'".to_string(),
-                            )
-                        } else {
-                            match request_handler.handle(&request.method, request.params).await {
-                                Ok(result) => JsonRpcResponse::success(request.id, result),
-                                Err(msg) => JsonRpcResponse::error(request.id, -32000, msg),
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        JsonRpcResponse::error(
-                            serde_json::Value::Null,
-                            -32700,
-                            format!("Parse error: {}", e),
-                        )
-                    }
-                };
-
-                let response_str = match serde_json::to_string(&response) {
-                    Ok(s) => s,
-                    Err(e) => {
-                        error!("Serialize error: {}", e);
-                        break;
-                    }
-                };
-
-                debug!("Sending: {}", response_str);
-                let mut w = request_writer.lock().await;
-                if w.write_all(response_str.as_bytes()).await.is_err()
-                    || w.write_all(b"\n").await.is_err()
-                    || w.flush().await.is_err()
-                {
-                    break;
-                }
-            }
-        });
-
-        // Task 2: Forward broadcast events to the client
-        let event_task = if let Some(mut rx) = event_rx {
-            let event_writer = writer.clone();
-            Some(tokio::spawn(async move {
-                loop {
-                    match rx.recv().await {
-                        Ok(event) => {
-                            let mut w = event_writer.lock().await;
-                            if w.write_all(event.as_bytes()).await.is_err()
-                                || w.write_all(b"\n").await.is_err()
-                                || w.flush().await.is_err()
-                            {
-                                break;
-                            }
-                        }
-                        Err(broadcast::error::RecvError::Lagged(n)) => {
-                            warn!("Event stream lagged, missed {} events", n);
-                        }
-                        Err(broadcast::error::RecvError::Closed) => {
-                            break;
-                        }
-                    }
-                }
-            }))
-        } else {
-            None
-        };
-
-        // Wait for the request task to finish (client disconnected)
-        let _ = request_task.await;
-
-        // Cancel the event task
-        if let Some(task) = event_task {
-            task.abort();
-        }
-
-        Ok(())
-    }
-
     /// Stop the IPC server
     pub async fn stop(&mut self) {
         if let Some(tx) = self.shutdown_tx.take() {
@@ -258,6 +410,7 @@ mod tests {
     fn test_custom_socket_path() {
         let config = IpcConfig {
             socket_path: PathBuf::from("/custom/path/to/socket.sock"),
+            ..Default::default()
         };
         assert_eq!(
             config.socket_path.to_str().unwrap(),
@@ -289,6 +442,21 @@ mod tests {
         assert_eq!(response.error.unwrap().code, -32600);
     }
 
+    #[test]
+    fn test_coded_handler_error() {
+        let error = coded_error(ErrorCode::InsufficientCredits, "Connect error: Insufficient credits");
+        assert_eq!(error_code_of(&error), (ErrorCode::InsufficientCredits, "Connect error: Insufficient credits"));
+
+        let response = handler_error_response(serde_json::json!(7), error);
+        let json = serde_json::to_value(&response).unwrap();
+        assert_eq!(json["error"]["code"], -32000);
+        assert_eq!(json["error"]["message"], "Connect error: Insufficient credits");
+        assert_eq!(json["error"]["data"]["code"], "INSUFFICIENT_CREDITS");
+
+        // Uncoded messages (including ones that merely contain a colon)
+        assert_eq!(error_code_of("Request error: boom"), (ErrorCode::Internal, "Request error: boom"));
+    }
+
     #[test]
     fn test_parse_request() {
         let json = r#"{"jsonrpc":"2.0","method":"status","id":1}"#;
@@ -308,23 +476,180 @@ mod tests {
         assert_eq!(params["hops"], 2);
     }
 
+    #[cfg(unix)]
     #[test]
     fn test_ipc_server_creation() {
         let config = IpcConfig {
             socket_path: PathBuf::from("/tmp/test.sock"),
+            ..Default::default()
         };
         let server = IpcServer::new(config.clone());
         assert_eq!(server.socket_path(), &config.socket_path);
     }
 
+    #[cfg(unix)]
     #[test]
     fn test_ipc_server_with_event_sender() {
         let config = IpcConfig {
             socket_path: PathBuf::from("/tmp/test_events.sock"),
+            ..Default::default()
         };
         let mut server = IpcServer::new(config);
         let (tx, _rx) = broadcast::channel::<String>(16);
         server.set_event_sender(tx);
         assert!(server.event_tx.is_some());
     }
+
+    /// Echoes params back and counts open sessions
+    #[derive(Default)]
+    struct EchoHandler {
+        open: std::sync::atomic::AtomicUsize,
+        session_rx: std::sync::Mutex<Option<mpsc::Receiver<String>>>,
+    }
+
+    impl IpcHandler for EchoHandler {
+        fn handle(
+            &self,
+            method: &str,
+            params: Option<serde_json::Value>,
+        ) -> Pin<Box<dyn Future<Output = std::result::Result<serde_json::Value, String>> + Send + '_>> {
+            let method = method.to_string();
+            Box::pin(async move {
+                match method.as_str() {
+                    "echo" => Ok(params.unwrap_or_default()),
+                    _ => Err(coded_error(ErrorCode::InvalidRequest, format!("Unknown method: {}", method))),
+                }
+            })
+        }
+    }
+
+    impl SessionHandler for EchoHandler {
+        fn open_session(&self, _session: &IpcSession) {
+            self.open.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn close_session(&self, _session: &IpcSession) {
+            self.open.fetch_sub(1, Ordering::SeqCst);
+        }
+
+        fn event_visible(&self, _session: &IpcSession, event: &str) -> bool {
+            !event.contains("hidden")
+        }
+
+        fn session_events(&self, _session: &IpcSession) -> Option<mpsc::Receiver<String>> {
+            self.session_rx.lock().unwrap().take()
+        }
+    }
+
+    /// Connect a client over an in-memory pipe; returns its line reader and writer
+    fn connect(
+        handler: &Arc<EchoHandler>,
+        events: &broadcast::Sender<String>,
+        id: u64,
+    ) -> (tokio::io::Lines<BufReader<tokio::io::ReadHalf<tokio::io::DuplexStream>>>, tokio::io::WriteHalf<tokio::io::DuplexStream>) {
+        let (client, server) = tokio::io::duplex(4096);
+        let (server_reader, server_writer) = tokio::io::split(server);
+        tokio::spawn(serve_connection(
+            server_reader,
+            server_writer,
+            IpcSession::local(id),
+            handler.clone(),
+            Some(events.subscribe()),
+        ));
+        let (client_reader, client_writer) = tokio::io::split(client);
+        (BufReader::new(client_reader).lines(), client_writer)
+    }
+
+    async fn next_frame(
+        lines: &mut tokio::io::Lines<BufReader<tokio::io::ReadHalf<tokio::io::DuplexStream>>>,
+    ) -> serde_json::Value {
+        let line = tokio::time::timeout(std::time::Duration::from_secs(5), lines.next_line())
+            .await
+            .expect("frame within timeout")
+            .unwrap()
+            .expect("connection open");
+        serde_json::from_str(&line).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_serve_connection_requests_and_events() {
+        let handler = Arc::new(EchoHandler::default());
+        let (events, _) = broadcast::channel(16);
+        let (mut lines, mut writer) = connect(&handler, &events, 1);
+
+        writer.write_all(b"{\"jsonrpc\":\"2.0\",\"method\":\"echo\",\"params\":{\"n\":1},\"id\":1}\n").await.unwrap();
+        let response = next_frame(&mut lines).await;
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["result"]["n"], 1);
+        assert_eq!(handler.open.load(Ordering::SeqCst), 1);
+
+        // Events are pushed without a request; hidden ones are filtered per session
+        events.send(r#"{"event":"hidden"}"#.to_string()).unwrap();
+        events.send(r#"{"event":"state_change"}"#.to_string()).unwrap();
+        assert_eq!(next_frame(&mut lines).await["event"], "state_change");
+
+        writer.write_all(b"{\"jsonrpc\":\"2.0\",\"method\":\"nope\",\"id\":2}\n").await.unwrap();
+        let response = next_frame(&mut lines).await;
+        assert_eq!(response["error"]["data"]["code"], "INVALID_REQUEST");
+    }
+
+    #[tokio::test]
+    async fn test_serve_connection_reconnect() {
+        let handler = Arc::new(EchoHandler::default());
+        let (events, _) = broadcast::channel(16);
+
+        // Two clients at once both get every event
+        let (mut first, mut first_writer) = connect(&handler, &events, 1);
+        let (mut second, mut second_writer) = connect(&handler, &events, 2);
+        events.send(r#"{"event":"one"}"#.to_string()).unwrap();
+        assert_eq!(next_frame(&mut first).await["event"], "one");
+        assert_eq!(next_frame(&mut second).await["event"], "one");
+
+        // A client hanging up closes its session; the other keeps working
+        first_writer.shutdown().await.unwrap();
+        assert!(first.next_line().await.unwrap().is_none());
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while handler.open.load(Ordering::SeqCst) != 1 {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+
+        // A reconnecting client only sees events sent after it connected
+        events.send(r#"{"event":"missed"}"#.to_string()).unwrap();
+        let (mut again, mut again_writer) = connect(&handler, &events, 3);
+        events.send(r#"{"event":"two"}"#.to_string()).unwrap();
+        assert_eq!(next_frame(&mut again).await["event"], "two");
+        assert_eq!(next_frame(&mut second).await["event"], "missed");
+
+        again_writer.write_all(b"{\"jsonrpc\":\"2.0\",\"method\":\"echo\",\"id\":9}\n").await.unwrap();
+        assert_eq!(next_frame(&mut again).await["id"], 9);
+        second_writer.write_all(b"{\"jsonrpc\":\"2.0\",\"method\":\"echo\",\"id\":10}\n").await.unwrap();
+        assert_eq!(next_frame(&mut second).await["event"], "two");
+        assert_eq!(next_frame(&mut second).await["id"], 10);
+    }
+
+    #[tokio::test]
+    async fn test_serve_connection_session_events_wait_for_client() {
+        let (session_tx, session_rx) = mpsc::channel(4);
+        let handler = Arc::new(EchoHandler { session_rx: std::sync::Mutex::new(Some(session_rx)), ..Default::default() });
+        let (events, _) = broadcast::channel(16);
+        let (mut lines, _writer) = connect(&handler, &events, 1);
+
+        // Far more than either channel holds: the sender waits, nothing is lost
+        let sender = tokio::spawn(async move {
+            for seq in 0..100 {
+                session_tx.send(format!("{{\"event\":\"chunk\",\"seq\":{}}}", seq)).await.unwrap();
+            }
+        });
+        for seq in 0..100 {
+            assert_eq!(next_frame(&mut lines).await["seq"], seq);
+        }
+        sender.await.unwrap();
+
+        // Broadcast events still arrive alongside
+        events.send(r#"{"event":"state_change"}"#.to_string()).unwrap();
+        assert_eq!(next_frame(&mut lines).await["event"], "state_change");
+    }
 }
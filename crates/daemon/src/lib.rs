@@ -6,29 +6,87 @@
 //!
 //! - **DaemonService**: VPN client wrapper with IPC interface (uses CraftNetNode)
 //! - **IpcServer**: JSON-RPC 2.0 over Unix sockets (macOS/Linux) or Named Pipes (Windows)
+//! - **Health endpoint**: `/healthz` and `/readyz` over HTTP plus systemd
+//!   `sd_notify` readiness (see `health`)
 //!
 //! ## IPC Methods
 //!
-//! - `connect` - Connect to VPN with optional hop count
+//! - `connect` - Connect to VPN with optional hop count; if the tunnel later drops,
+//!   the node reconnects with backoff and broadcasts `reconnect` events
 //! - `disconnect` - Disconnect from VPN
 //! - `status` - Get current connection status
 //! - `purchase_credits` - Purchase credits on-chain
 //! - `get_credits` - Get current credit balance
+//! - `get_topology` - Get the live network topology graph
+//! - `list_peers` - Connected peers with address, direction, transport, identify
+//!   protocols, connection age and shard bytes exchanged
+//! - `get_dht` / `dht_add_address` / `dht_bootstrap` / `dht_get_record` - Kademlia
+//!   bucket occupancy and registry providers, and manual DHT actions (`table`:
+//!   `main` or `registry`, default `registry`)
+//! - `get_recent_logs` - Last log lines of the daemon (`since` a line's `seq`, `limit`,
+//!   default 100), for `craftnet node dashboard`
+//! - `get_build_attestation` - This binary's build manifest (git commit, Cargo.lock hash,
+//!   target, compiler) checked against the canonical manifests in the network parameters
+//! - `drain` - Finish in-flight relay/exit work and stop the node
+//! - `get_throughput_series` - Per-second client/relay/exit bytes up and down for live
+//!   bandwidth graphs (`window_secs`, default 60, at most an hour)
+//! - `get_kill_switch` / `set_kill_switch` / `engage_kill_switch` / `release_kill_switch` -
+//!   Kill switch status, auto-engage toggle and manual engage/override; transitions are
+//!   broadcast as `kill_switch` events for platform firewall integrations
+//! - `get_dns_stub` / `set_dns_stub` - Local DNS stub that resolves through the tunnel
+//!   via the exit's DoH, answers bypass domains directly and refuses queries while the
+//!   kill switch is engaged (see `dns`)
+//! - `get_split_tunnel_rules` / `add_split_tunnel_rule` / `remove_split_tunnel_rule` -
+//!   Manage per-process/domain/CIDR tunnel-or-bypass rules (applied to new proxy connections)
+//! - `set_audit_log` - Turn the encrypted local request audit log on/off (off by default)
+//! - `get_audit_log` / `export_audit_log` - Query the audit log, or export it as JSONL/CSV
+//! - `get_connection_history` / `clear_connection_history` - Past tunnel sessions (exit, hop
+//!   mode, bytes, failure reason), persisted with rotation next to the settings file
+//! - `whoami` / `list_sessions` - The caller's IPC session, or every connected session (admin)
+//!
+//! Failed calls carry a machine-readable [`ErrorCode`] in `error.data.code`
+//! (e.g. `{"code": "INSUFFICIENT_CREDITS"}`) next to the human-readable
+//! `error.message`.
 //!
 //! ## Platform-Specific IPC
 //!
-//! - **macOS/Linux**: Unix domain sockets (`/tmp/craftnet.sock`)
+//! - **macOS/Linux**: Unix domain sockets, one per user (`$XDG_RUNTIME_DIR/craftnet.sock`
+//!   or `/tmp/craftnet-<uid>.sock`)
 //! - **Windows**: Named pipes (`\\.\pipe\craftnet`)
+//!
+//! ## Multi-User
+//!
+//! Each connection is a session authenticated by its peer credentials. A
+//! system daemon started with `CRAFTNET_IPC_SHARED=1` accepts other users;
+//! their streamed responses stay private to their session, and methods that
+//! reconfigure the shared node are limited to admins (see `session`).
 
+mod dns;
+mod health;
+mod history;
 mod ipc;
+mod logs;
 mod service;
+mod session;
+mod topology;
 mod windows_pipe;
 
-pub use ipc::{IpcServer, IpcConfig, IpcHandler};
-pub use service::{DaemonService, DaemonState, ConnectParams, StatusResponse, ConnectionHistoryEntry, AvailableExitResponse, PeerSummary, ProxyStatusInfo};
+pub use dns::DnsStubStatus;
+pub use health::{HealthReport, HEALTH_ADDR_ENV, health_addr_from_env, serve_health, run_systemd_notifier, notify_stopping};
+pub use ipc::{IpcConfig, IpcHandler, SessionHandler, coded_error, error_code_of};
+#[cfg(unix)]
+pub use ipc::IpcServer;
+pub use session::{AccessPolicy, IpcSession, PeerCredentials, ADMIN_METHODS, IPC_ADMIN_GIDS_ENV, IPC_SHARED_ENV, is_admin_method};
+#[cfg(unix)]
+pub use session::current_uid;
+pub use history::{ConnectionHistoryEntry, MAX_HISTORY_ENTRIES};
+pub use logs::{recent_logs, LogBuffer, LogBufferLayer, LogLine, MAX_LOG_LINES};
+pub use service::{DaemonService, DaemonState, ConnectParams, StatusResponse, AvailableExitResponse, PeerConnectionEntry, PeerSummary, ProxyStatusInfo, HealthProbe, ShutdownHandle, DrainResponse, AuditLogResponse, SessionInfo};
+pub use topology::{TopologyCollector, TopologyNode, TopologyEdge, TopologyResponse};
 pub use windows_pipe::{WindowsPipeServer, WindowsPipeConfig};
 pub use craftnet_client::SwarmHandles;
 
+use craftnet_core::ErrorCode;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -55,4 +113,18 @@ pub enum DaemonError {
     IoError(#[from] std::io::Error),
 }
 
+impl DaemonError {
+    /// Machine-readable code of this error
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::IpcError(_) | Self::SdkError(_) => ErrorCode::Internal,
+            Self::ClientError(e) => e.code(),
+            Self::AlreadyRunning => ErrorCode::AlreadyRunning,
+            Self::NotRunning => ErrorCode::NotRunning,
+            Self::InvalidRequest(_) => ErrorCode::InvalidRequest,
+            Self::IoError(_) => ErrorCode::IoError,
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, DaemonError>;
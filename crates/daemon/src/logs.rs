@@ -0,0 +1,155 @@
+//! Recent log lines for the operator dashboard
+//!
+//! [`LogBufferLayer`] is a `tracing` layer that keeps the last
+//! [`MAX_LOG_LINES`] events of the process in [`recent_logs`]. The daemon
+//! binary installs it next to its stdout formatter; the `get_recent_logs` IPC
+//! method serves the lines so `craftnet node dashboard` can tail them without
+//! access to the daemon's stdout or journal. Processes that don't install the
+//! layer (e.g. a daemon embedded in the CLI) serve no lines.
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::sync::{LazyLock, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// Lines kept in memory
+pub const MAX_LOG_LINES: usize = 1000;
+
+/// One log event
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LogLine {
+    /// Increases by one per line; pass the last seen one as `since`
+    pub seq: u64,
+    /// Unix milliseconds
+    pub timestamp_ms: u64,
+    /// `ERROR`, `WARN`, `INFO`, `DEBUG` or `TRACE`
+    pub level: String,
+    /// Module the event came from
+    pub target: String,
+    /// Message followed by any other fields (`key=value`)
+    pub message: String,
+}
+
+/// Ring buffer of the most recent log lines
+#[derive(Debug)]
+pub struct LogBuffer {
+    capacity: usize,
+    inner: Mutex<LogBufferInner>,
+}
+
+#[derive(Debug, Default)]
+struct LogBufferInner {
+    lines: VecDeque<LogLine>,
+    next_seq: u64,
+}
+
+impl LogBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, inner: Mutex::new(LogBufferInner { lines: VecDeque::new(), next_seq: 1 }) }
+    }
+
+    pub fn push(&self, timestamp_ms: u64, level: &str, target: &str, message: String) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let seq = inner.next_seq;
+        inner.next_seq += 1;
+        if inner.lines.len() >= self.capacity {
+            inner.lines.pop_front();
+        }
+        inner.lines.push_back(LogLine {
+            seq,
+            timestamp_ms,
+            level: level.to_string(),
+            target: target.to_string(),
+            message,
+        });
+    }
+
+    /// Up to `limit` of the newest lines after `since`, oldest first
+    pub fn recent(&self, since: Option<u64>, limit: usize) -> Vec<LogLine> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let newer: Vec<&LogLine> = inner.lines.iter().filter(|l| since.is_none_or(|s| l.seq > s)).collect();
+        newer[newer.len().saturating_sub(limit)..].iter().map(|l| (*l).clone()).collect()
+    }
+}
+
+static RECENT_LOGS: LazyLock<LogBuffer> = LazyLock::new(|| LogBuffer::new(MAX_LOG_LINES));
+
+/// Lines recorded by [`LogBufferLayer`] in this process
+pub fn recent_logs() -> &'static LogBuffer {
+    &RECENT_LOGS
+}
+
+/// `tracing` layer recording events into [`recent_logs`]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LogBufferLayer;
+
+impl<S: Subscriber> Layer<S> for LogBufferLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let timestamp_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+        let metadata = event.metadata();
+        recent_logs().push(timestamp_ms, metadata.level().as_str(), metadata.target(), visitor.finish());
+    }
+}
+
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl MessageVisitor {
+    fn finish(self) -> String {
+        if self.fields.is_empty() {
+            self.message
+        } else if self.message.is_empty() {
+            self.fields.trim_start().to_string()
+        } else {
+            format!("{}{}", self.message, self.fields)
+        }
+    }
+}
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            let _ = write!(self.fields, " {}={}", field.name(), value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_buffer_keeps_newest_lines() {
+        let buffer = LogBuffer::new(3);
+        for i in 0..5u64 {
+            buffer.push(i, "INFO", "craftnet", format!("line {}", i));
+        }
+
+        let all = buffer.recent(None, 10);
+        assert_eq!(all.iter().map(|l| l.seq).collect::<Vec<_>>(), vec![3, 4, 5]);
+        assert_eq!(all[2].message, "line 4");
+        assert_eq!(buffer.recent(None, 1)[0].seq, 5);
+        assert_eq!(buffer.recent(Some(4), 10).len(), 1);
+        assert!(buffer.recent(Some(5), 10).is_empty());
+    }
+}
@@ -1,8 +1,16 @@
 //! CraftNet Daemon Binary
 //!
 //! Runs the IPC server for desktop/mobile frontends.
+//!
+//! SIGTERM (or Ctrl-C) drains a running relay/exit before exiting, so
+//! in-flight shards are not dropped.
 
-use craftnet_daemon::{DaemonService, IpcServer, IpcConfig, DaemonError};
+use craftnet_daemon::{DaemonService, DaemonError};
+#[cfg(unix)]
+use craftnet_daemon::{IpcServer, IpcConfig};
+#[cfg(windows)]
+use craftnet_daemon::{WindowsPipeServer, WindowsPipeConfig};
+use craftnet_daemon::{health_addr_from_env, serve_health, run_systemd_notifier, notify_stopping, LogBufferLayer};
 use tracing_subscriber::{EnvFilter, fmt, prelude::*};
 
 fn init_logging() {
@@ -11,10 +19,34 @@ fn init_logging() {
     
     tracing_subscriber::registry()
         .with(fmt::layer())
+        .with(LogBufferLayer)
         .with(filter)
         .init();
 }
 
+/// Wait for Ctrl-C, or SIGTERM on Unix
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut sigterm = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(s) => s,
+            Err(e) => {
+                tracing::warn!("Cannot listen for SIGTERM: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+                return;
+            }
+        };
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = sigterm.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), DaemonError> {
     init_logging();
@@ -24,15 +56,36 @@ async fn main() -> Result<(), DaemonError> {
     // Create the daemon service (implements IpcHandler)
     let daemon = DaemonService::new()?;
     
-    // Configure IPC server
-    let config = IpcConfig::default();
-    
-    tracing::info!("Daemon starting, will listen on {:?}", config.socket_path);
-    
-    // Create IPC server with event streaming
-    let mut ipc = IpcServer::new(config);
+    // Create IPC server with event streaming (Unix socket or named pipe)
+    #[cfg(unix)]
+    let mut ipc = {
+        let config = IpcConfig::from_env();
+        tracing::info!("Daemon starting, will listen on {:?}", config.socket_path);
+        IpcServer::new(config)
+    };
+    #[cfg(windows)]
+    let mut ipc = {
+        let config = WindowsPipeConfig::default();
+        tracing::info!("Daemon starting, will listen on {}", config.pipe_name);
+        WindowsPipeServer::new(config)
+    };
     ipc.set_event_sender(daemon.event_sender());
 
+    // Health endpoint and systemd readiness (both optional)
+    let probe = daemon.health_probe();
+    if let Some(addr) = health_addr_from_env() {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        let probe = probe.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve_health(listener, probe).await {
+                tracing::error!("Health endpoint error: {}", e);
+            }
+        });
+    }
+    tokio::spawn(run_systemd_notifier(probe));
+
+    let shutdown = daemon.shutdown_handle();
+
     // Run until interrupted
     tokio::select! {
         result = ipc.start(daemon) => {
@@ -41,8 +94,12 @@ async fn main() -> Result<(), DaemonError> {
                 return Err(e);
             }
         }
-        _ = tokio::signal::ctrl_c() => {
-            tracing::info!("Received shutdown signal");
+        _ = shutdown_signal() => {
+            tracing::info!("Received shutdown signal, draining");
+            notify_stopping();
+            if let Some(report) = shutdown.drain(None).await {
+                tracing::info!("Drain finished: {:?}", report);
+            }
             ipc.stop().await;
         }
     }
@@ -1,5 +1,6 @@
 //! Daemon service implementation
 
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use serde::{Deserialize, Serialize};
@@ -7,14 +8,21 @@ use tokio::sync::{broadcast, mpsc, oneshot, RwLock};
 use tracing::{debug, info, warn, error};
 use ed25519_dalek;
 
-use craftnet_client::{Capabilities, NodeConfig, NodeStats as ClientNodeStats, CraftNetNode, TunnelResponse, Socks5Server};
-use craftnet_core::{ExitRegion, HopMode};
-use craftnet_settlement::{SettlementClient, SettlementConfig, Subscribe};
-use craftnet_core::SubscriptionTier;
+use craftnet_client::{AbuseReport, AggregatorPools, BlockTarget, DistributionBatchProof, PoolType, KillSwitch, KillSwitchStatus, SharedSplitTunnelRules, AuditEntry, AuditExportFormat, AuditLog, AuditLogConfig, Capabilities, CookieJar, CookieJarConfig, DrainStatus, NodeConfig, NodeStats as ClientNodeStats, CraftNetNode, DhtHandle, DhtTable, PeerConnectionInfo, RequestOptions, ResponseStream, ThroughputSample, TunnelBurst, TunnelResponse, Socks5Server};
+use craftnet_core::{BuildAttestation, BuildManifest, ErrorCode, ExitRegion, HopMode, SplitTunnelAction, SplitTunnelMatch, SplitTunnelRule};
+use craftnet_settlement::{EpochPhase, SettlementClient, SettlementConfig, Subscribe};
+use craftnet_core::{CreditLedger, CreditRole, CreditTotals, SubscriptionTier};
 use craftec_settings::Settings;
 use craftnet_core::config::{CraftNetConfig, NodeMode, HopMode as ConfigHopMode};
 
 use craftec_ipc::server::IpcHandler;
+use crate::dns::{DnsBackend, DnsStub, DnsStubStatus};
+use crate::health::HealthReport;
+use crate::history::{ConnectionHistory, ConnectionHistoryEntry};
+use crate::logs::{recent_logs, MAX_LOG_LINES};
+use crate::ipc::{coded_error, SessionHandler};
+use crate::session::IpcSession;
+use crate::topology::{TopologyCollector, TopologyResponse, TOPOLOGY_REFRESH_INTERVAL};
 use crate::Result;
 
 /// Daemon state
@@ -67,6 +75,8 @@ pub struct AvailableExitResponse {
     pub score: u8,
     pub load: u8,
     pub latency_ms: Option<u64>,
+    /// PeerId bound to the exit's pubkey by a verified binding record
+    pub peer_id: Option<String>,
 }
 
 /// Node stats response for get_node_stats IPC method
@@ -80,12 +90,275 @@ pub struct NodeStatsResponse {
     pub bytes_sent: u64,
     pub bytes_received: u64,
     pub bytes_relayed: u64,
+    pub cover_shards_sent: u64,
+    pub cover_shards_dropped: u64,
+    pub replays_rejected: u64,
+    pub chain_hops_verified: u64,
+    pub chain_hops_missing: u64,
+    /// Forward receipts waiting to be proven
+    pub proof_queue_depth: usize,
+    /// Bandwidth cap set with `set_bandwidth_limit` (None: unlimited)
+    pub bandwidth_limit_kbps: Option<u64>,
+    pub exit_queues: Vec<PoolQueueResponse>,
+    pub exit_fetch_pool: FetchPoolResponse,
+    pub credit_ledger: CreditTotals,
+    /// Relay forwarding per tier, highest first
+    pub relay_qos: Vec<QosClassResponse>,
+    /// On-chain balance and claim status of pools we earned from
+    pub pool_claims: Vec<PoolClaimResponse>,
+}
+
+/// Credit ledger response for get_credit_ledger IPC method
+#[derive(Debug, Serialize)]
+pub struct CreditLedgerResponse {
+    pub totals: CreditTotals,
+    pub pools: Vec<PoolCreditsResponse>,
+    /// Latest per-request entries, newest first
+    pub recent: Vec<CreditEntryResponse>,
+}
+
+/// Credits booked against one pool
+#[derive(Debug, Serialize)]
+pub struct PoolCreditsResponse {
+    /// Pool pubkey (hex)
+    pub pool: String,
+    pub pending_bytes: u64,
+    pub pending_credits: u64,
+    pub settled_credits: u64,
+    pub spent_bytes: u64,
+    pub spent_credits: u64,
+    /// Root of the last distribution reconciled against (hex)
+    pub distribution_root: Option<String>,
+}
+
+/// One priced request
+#[derive(Debug, Serialize)]
+pub struct CreditEntryResponse {
+    /// "client", "relay" or "exit"
+    pub role: String,
+    /// Request ID (hex)
+    pub request_id: String,
+    /// Pool pubkey (hex)
+    pub pool: String,
+    pub tier: Option<SubscriptionTier>,
+    pub bytes: u64,
+    pub hops: u8,
+    pub credits: u64,
+    pub timestamp: u64,
+}
+
+impl CreditLedgerResponse {
+    fn new(ledger: &CreditLedger, limit: usize) -> Self {
+        let mut pools: Vec<PoolCreditsResponse> = ledger.pools()
+            .map(|(pool, c)| PoolCreditsResponse {
+                pool: hex::encode(pool),
+                pending_bytes: c.pending_bytes,
+                pending_credits: c.pending_credits,
+                settled_credits: c.settled_credits,
+                spent_bytes: c.spent_bytes,
+                spent_credits: c.spent_credits,
+                distribution_root: c.distribution_root.map(hex::encode),
+            })
+            .collect();
+        pools.sort_by(|a, b| a.pool.cmp(&b.pool));
+        Self {
+            totals: ledger.totals(),
+            pools,
+            recent: ledger.recent(limit).into_iter()
+                .map(|e| CreditEntryResponse {
+                    role: match e.role {
+                        CreditRole::Client => "client",
+                        CreditRole::Relay => "relay",
+                        CreditRole::Exit => "exit",
+                    }.to_string(),
+                    request_id: hex::encode(e.request_id),
+                    pool: hex::encode(e.pool),
+                    tier: e.tier,
+                    bytes: e.bytes,
+                    hops: e.hops,
+                    credits: e.credits,
+                    timestamp: e.timestamp,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Exit request log response for get_exit_abuse IPC method
+#[derive(Debug, Serialize)]
+pub struct ExitAbuseResponse {
+    /// Busiest destinations first
+    pub destinations: Vec<DestinationVolumeResponse>,
+    /// Destinations in the log
+    pub tracked_destinations: usize,
+    pub throttled: Vec<ThrottledPoolResponse>,
+    /// Blocked destination hashes (hex)
+    pub blocked_destinations: Vec<String>,
+    /// Blocked pool pubkeys (hex)
+    pub blocked_pools: Vec<String>,
+}
+
+/// Request volume of one destination
+#[derive(Debug, Serialize)]
+pub struct DestinationVolumeResponse {
+    /// SHA-256 of the hostname (hex)
+    pub destination: String,
+    pub requests: u64,
+    pub refused: u64,
+    pub age_secs: u64,
+    pub idle_secs: u64,
+}
+
+/// A pool throttled for one destination
+#[derive(Debug, Serialize)]
+pub struct ThrottledPoolResponse {
+    /// Pool pubkey (hex)
+    pub pool: String,
+    /// SHA-256 of the hostname (hex)
+    pub destination: String,
+    pub remaining_secs: u64,
+}
+
+/// Distribution multiproof response for get_distribution_batch_proof IPC method
+#[derive(Debug, Serialize)]
+pub struct DistributionBatchProofResponse {
+    /// Distribution Merkle root (hex)
+    pub root: String,
+    /// Proven claims, in `leaf_indices` order
+    pub entries: Vec<DistributionEntryResponse>,
+    /// Leaf index of each entry
+    pub leaf_indices: Vec<usize>,
+    /// Leaves in the tree (including padding)
+    pub leaf_count: usize,
+    /// Sibling hashes shared by all entries (hex)
+    pub hashes: Vec<String>,
+}
+
+/// One relay's claim in a distribution
+#[derive(Debug, Serialize)]
+pub struct DistributionEntryResponse {
+    /// Relay pubkey (hex)
+    pub relay: String,
+    pub cumulative_bytes: u64,
+}
+
+impl From<DistributionBatchProof> for DistributionBatchProofResponse {
+    fn from(batch: DistributionBatchProof) -> Self {
+        Self {
+            root: hex::encode(batch.root),
+            entries: batch.entries.into_iter()
+                .map(|(relay, cumulative_bytes)| DistributionEntryResponse {
+                    relay: hex::encode(relay),
+                    cumulative_bytes,
+                })
+                .collect(),
+            leaf_indices: batch.proof.leaf_indices,
+            leaf_count: batch.proof.leaf_count,
+            hashes: batch.proof.hashes.into_iter().map(hex::encode).collect(),
+        }
+    }
+}
+
+impl From<AbuseReport> for ExitAbuseResponse {
+    fn from(report: AbuseReport) -> Self {
+        Self {
+            destinations: report.destinations.into_iter()
+                .map(|d| DestinationVolumeResponse {
+                    destination: hex::encode(d.destination),
+                    requests: d.requests,
+                    refused: d.refused,
+                    age_secs: d.age.as_secs(),
+                    idle_secs: d.idle.as_secs(),
+                })
+                .collect(),
+            tracked_destinations: report.tracked_destinations,
+            throttled: report.throttled.into_iter()
+                .map(|t| ThrottledPoolResponse {
+                    pool: hex::encode(t.pool),
+                    destination: hex::encode(t.destination),
+                    remaining_secs: t.remaining.as_secs(),
+                })
+                .collect(),
+            blocked_destinations: report.blocked_destinations.into_iter().map(hex::encode).collect(),
+            blocked_pools: report.blocked_pools.into_iter().map(hex::encode).collect(),
+        }
+    }
+}
+
+/// Relay forwarding counters of one tier
+#[derive(Debug, Serialize)]
+pub struct QosClassResponse {
+    /// "ultra", "premium", "standard", "basic" or "free"
+    pub class: String,
+    pub queued: usize,
+    pub forwarded: u64,
+    pub forwarded_bytes: u64,
+    pub dropped: u64,
+    pub throttled: u64,
+}
+
+/// On-chain claim status of one pool we earned from
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolClaimResponse {
+    /// Pool pubkey (hex)
+    pub pool: String,
+    pub balance: u64,
+    pub original_balance: u64,
+    /// "active", "grace", "claimable" or "closed" (None: no account found)
+    pub phase: Option<String>,
+    /// "claimable_at", "awaiting_distribution", "claimable_now", "claimed",
+    /// "closed" or "not_found"
+    pub status: String,
+    /// Unix time claims open (status "claimable_at")
+    pub claimable_at: Option<u64>,
+    /// Unix time of the on-chain check
+    pub checked_at: u64,
+}
+
+impl From<craftnet_client::PoolClaimStatus> for PoolClaimResponse {
+    fn from(c: craftnet_client::PoolClaimStatus) -> Self {
+        Self {
+            pool: hex::encode(c.pool),
+            balance: c.balance,
+            original_balance: c.original_balance,
+            phase: c.phase.map(|p| match p {
+                EpochPhase::Active => "active",
+                EpochPhase::Grace => "grace",
+                EpochPhase::Claimable => "claimable",
+                EpochPhase::Closed => "closed",
+            }.to_string()),
+            status: c.status.name().to_string(),
+            claimable_at: c.status.claimable_at(),
+            checked_at: c.checked_at,
+        }
+    }
+}
+
+/// Upstream connection reuse at the exit
+#[derive(Debug, Serialize)]
+pub struct FetchPoolResponse {
+    pub requests: u64,
+    pub pool_hits: u64,
+    pub pool_misses: u64,
+    pub dns_hits: u64,
+    pub dns_misses: u64,
+}
+
+/// Exit request queue depth of one pool
+#[derive(Debug, Serialize)]
+pub struct PoolQueueResponse {
+    /// Pool pubkey (hex)
+    pub pool: String,
+    pub weight: u32,
+    pub queued: usize,
+    pub in_flight: usize,
 }
 
 /// Serialisable snapshot of a CraftNet network peer for the UI.
 #[derive(Debug, Clone, Serialize)]
 pub struct PeerSummary {
     pub peer_id: String,
+    pub binding_verified: bool,
     pub role: String,
     pub online: bool,
     pub score: u8,
@@ -109,22 +382,44 @@ impl From<ClientNodeStats> for NodeStatsResponse {
             bytes_sent: s.bytes_sent,
             bytes_received: s.bytes_received,
             bytes_relayed: s.bytes_relayed,
+            cover_shards_sent: s.cover_shards_sent,
+            cover_shards_dropped: s.cover_shards_dropped,
+            replays_rejected: s.replays_rejected,
+            chain_hops_verified: s.chain_hops_verified,
+            chain_hops_missing: s.chain_hops_missing,
+            proof_queue_depth: s.proof_queue_depth,
+            bandwidth_limit_kbps: None,
+            exit_queues: s.exit_queues.into_iter()
+                .map(|q| PoolQueueResponse {
+                    pool: hex::encode(q.pool),
+                    weight: q.weight,
+                    queued: q.queued,
+                    in_flight: q.in_flight,
+                })
+                .collect(),
+            exit_fetch_pool: FetchPoolResponse {
+                requests: s.exit_fetch_pool.requests,
+                pool_hits: s.exit_fetch_pool.pool_hits,
+                pool_misses: s.exit_fetch_pool.pool_misses,
+                dns_hits: s.exit_fetch_pool.dns_hits,
+                dns_misses: s.exit_fetch_pool.dns_misses,
+            },
+            credit_ledger: s.credit_ledger,
+            relay_qos: s.relay_qos.into_iter()
+                .map(|q| QosClassResponse {
+                    class: q.class.name().to_string(),
+                    queued: q.queued,
+                    forwarded: q.forwarded,
+                    forwarded_bytes: q.forwarded_bytes,
+                    dropped: q.dropped,
+                    throttled: q.throttled,
+                })
+                .collect(),
+            pool_claims: s.pool_claims.into_iter().map(PoolClaimResponse::from).collect(),
         }
     }
 }
 
-/// Connection history entry
-#[derive(Debug, Clone, Serialize)]
-pub struct ConnectionHistoryEntry {
-    pub id: u64,
-    pub connected_at: u64,
-    pub disconnected_at: Option<u64>,
-    pub duration_secs: Option<u64>,
-    pub exit_region: Option<String>,
-    pub bytes_sent: u64,
-    pub bytes_received: u64,
-}
-
 /// Earnings history entry
 #[derive(Debug, Clone, Serialize)]
 pub struct EarningsEntry {
@@ -135,6 +430,50 @@ pub struct EarningsEntry {
     pub shards_count: u64,
 }
 
+/// A connected peer (`list_peers`)
+#[derive(Debug, Clone, Serialize)]
+pub struct PeerConnectionEntry {
+    pub peer_id: String,
+    pub address: Option<String>,
+    /// `inbound` / `outbound` (None on a shared swarm)
+    pub direction: Option<craftnet_client::ConnectionDirection>,
+    /// `tcp`, `quic`, `relay`, `webrtc` or `unknown`
+    pub transport: craftnet_client::ConnectionTransport,
+    pub protocols: Vec<String>,
+    pub connections: u32,
+    pub connected_at: u64,
+    pub age_secs: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+impl PeerConnectionEntry {
+    fn new(info: PeerConnectionInfo, now: u64) -> Self {
+        Self {
+            peer_id: info.peer_id.to_string(),
+            address: info.address.as_ref().map(|a| a.to_string()),
+            direction: info.direction,
+            transport: info.transport,
+            age_secs: info.age_secs(now),
+            protocols: info.protocols,
+            connections: info.connections,
+            connected_at: info.connected_at,
+            bytes_sent: info.bytes_sent,
+            bytes_received: info.bytes_received,
+        }
+    }
+}
+
+/// Build manifest and its attestation (`get_build_attestation`)
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildAttestationResponse {
+    pub build: BuildManifest,
+    /// Sequence of the network parameters checked against (None when the
+    /// node isn't running or has no parameters yet)
+    pub params_sequence: Option<u64>,
+    pub attestation: Option<BuildAttestation>,
+}
+
 /// Speed test result
 #[derive(Debug, Clone, Serialize)]
 pub struct SpeedTestResultData {
@@ -149,6 +488,44 @@ pub struct SpeedTestResultData {
 #[derive(Debug, Clone, Deserialize, Default)]
 pub struct ConnectParams {
     pub hops: Option<u8>,
+    /// Also bring up the TUN interface so all device traffic is tunneled
+    #[serde(default)]
+    pub full_device: bool,
+}
+
+/// Head of a streamed response (body follows as IPC events)
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamHead {
+    pub stream_id: String,
+    pub status: u16,
+    pub headers: std::collections::HashMap<String, String>,
+}
+
+/// Outcome of a drain (work still in flight when the node stopped)
+#[derive(Debug, Clone, Serialize)]
+pub struct DrainResponse {
+    /// Everything in flight finished before the deadline
+    pub completed: bool,
+    pub pending_assemblies: usize,
+    pub exit_tasks: usize,
+    pub queued_receipts: usize,
+    pub unpublished_proofs: usize,
+    pub unflushed_receipts: usize,
+    pub outbound_queued: usize,
+}
+
+impl From<DrainStatus> for DrainResponse {
+    fn from(s: DrainStatus) -> Self {
+        Self {
+            completed: s.is_idle(),
+            pending_assemblies: s.pending_assemblies,
+            exit_tasks: s.exit_tasks,
+            queued_receipts: s.queued_receipts,
+            unpublished_proofs: s.unpublished_proofs,
+            unflushed_receipts: s.unflushed_receipts,
+            outbound_queued: s.outbound_queued,
+        }
+    }
 }
 
 /// Commands sent to the node task
@@ -160,8 +537,21 @@ enum NodeCommand {
         url: String,
         body: Option<Vec<u8>>,
         headers: Option<std::collections::HashMap<String, String>>,
+        options: RequestOptions,
         reply: oneshot::Sender<std::result::Result<TunnelResponse, String>>,
     },
+    /// Streamed request: reply carries the head, body arrives as
+    /// `response_chunk` events tagged with `stream_id` (on `events` when
+    /// set, otherwise broadcast)
+    RequestStream {
+        stream_id: String,
+        events: Option<mpsc::Sender<String>>,
+        method: String,
+        url: String,
+        body: Option<Vec<u8>>,
+        headers: Option<std::collections::HashMap<String, String>>,
+        reply: oneshot::Sender<std::result::Result<StreamHead, String>>,
+    },
     GetStatus(oneshot::Sender<NodeStatusInfo>),
     GetStats(oneshot::Sender<ClientNodeStats>),
     GetPeerId(oneshot::Sender<Option<String>>),
@@ -173,17 +563,222 @@ enum NodeCommand {
         reply: oneshot::Sender<std::result::Result<(), String>>,
     },
     SetLocalDiscovery(bool, oneshot::Sender<std::result::Result<(), String>>),
+    /// Replies with the number of chains re-synced
+    SetAggregatorPools(AggregatorPools, oneshot::Sender<usize>),
+    /// Multiproof for `relays`' claims in a pool's current distribution
+    /// (None without an aggregator or a claim)
+    GetDistributionBatchProof {
+        pool_key: ([u8; 32], PoolType),
+        relays: Vec<[u8; 32]>,
+        reply: oneshot::Sender<Option<DistributionBatchProofResponse>>,
+    },
     GetAvailableExits(oneshot::Sender<Vec<AvailableExitResponse>>),
     RunSpeedTest(oneshot::Sender<SpeedTestResultData>),
     SetBandwidthLimit(Option<u64>, oneshot::Sender<std::result::Result<(), String>>),
     SetCredits(u64),
+    /// Fetch the day's quota token batch if due; replies with tokens held
+    RefreshQuotaTokens(oneshot::Sender<std::result::Result<usize, String>>),
+    /// Credit ledger totals, pools and the latest `limit` entries
+    GetCreditLedger {
+        limit: usize,
+        reply: oneshot::Sender<CreditLedgerResponse>,
+    },
+    /// Exit request log with its `limit` busiest destinations
+    GetExitAbuse {
+        limit: usize,
+        reply: oneshot::Sender<ExitAbuseResponse>,
+    },
+    /// Block (or unblock) a destination or pool at the exit; replies
+    /// whether that changed anything
+    SetExitBlock {
+        target: BlockTarget,
+        block: bool,
+        reply: oneshot::Sender<bool>,
+    },
+    /// Per-second throughput samples of the last `window_secs` seconds
+    GetThroughput {
+        window_secs: u64,
+        reply: oneshot::Sender<Vec<ThroughputSample>>,
+    },
+    /// Connected peers from the swarm's connection table
+    ListPeers(oneshot::Sender<Vec<PeerConnectionInfo>>),
+    /// Kademlia inspection handle (None on a shared swarm)
+    GetDhtHandle(oneshot::Sender<Option<DhtHandle>>),
+    /// Build attestation against the network parameters (None before any arrived)
+    GetBuildAttestation(oneshot::Sender<Option<(u64, BuildAttestation)>>),
     StartProxy {
         port: u16,
         reply: oneshot::Sender<std::result::Result<(), String>>,
     },
     StopProxy(oneshot::Sender<std::result::Result<(), String>>),
+    #[cfg(feature = "tun")]
+    StartTun {
+        config: craftnet_client::TunConfig,
+        reply: oneshot::Sender<std::result::Result<(), String>>,
+    },
+    #[cfg(feature = "tun")]
+    StopTun(oneshot::Sender<std::result::Result<(), String>>),
+    #[cfg(feature = "tun")]
+    GetTunStatus(oneshot::Sender<Option<craftnet_client::TunStatus>>),
     GetPeers(oneshot::Sender<Vec<PeerSummary>>),
     GetProxyStatus(oneshot::Sender<Option<ProxyStatusInfo>>),
+    GetTopology(oneshot::Sender<craftnet_client::TopologySnapshot>),
+    GetHealth(oneshot::Sender<NodeHealth>),
+    SetAuditLog(Option<AuditLogConfig>, oneshot::Sender<std::result::Result<(), String>>),
+    SetCookieJar(Option<CookieJarConfig>, oneshot::Sender<std::result::Result<(), String>>),
+    ClearSessionData(Option<String>, oneshot::Sender<std::result::Result<(), String>>),
+    /// Stop advertising, finish in-flight work, then stop the node task
+    Drain {
+        timeout: Option<std::time::Duration>,
+        reply: oneshot::Sender<DrainStatus>,
+    },
+}
+
+/// Swarm readiness as seen by the node task
+#[derive(Debug, Clone, Default)]
+struct NodeHealth {
+    peer_id: Option<String>,
+    peer_count: usize,
+    bootstrapped: bool,
+    traffic: Vec<(craftnet_client::TrafficProtocol, craftnet_client::TrafficCounters)>,
+    traffic_peers: usize,
+    freeloaders: usize,
+}
+
+/// How often the node task checks whether the tunnel dropped (kill switch)
+/// Error of the TUN methods when the daemon is built without the `tun` feature
+#[cfg(not(feature = "tun"))]
+const TUN_UNSUPPORTED: &str = "full-device mode needs a daemon built with the `tun` feature";
+
+const KILL_SWITCH_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// How often the node task checks whether a quota token batch is due
+const QUOTA_REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(600);
+
+/// How often the selected exit's health is checked (the node only probes
+/// it after `ExitHealthPolicy::probe_interval` without traffic)
+const EXIT_PROBE_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// How long a health probe waits for the node task to answer
+const HEALTH_NODE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// How long a settlement RPC health result is reused before probing again
+const HEALTH_SETTLEMENT_TTL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Events queued for one IPC client before its streams wait for it to read
+const SESSION_EVENT_CAPACITY: usize = 64;
+
+/// Read-only health view of a running daemon.
+///
+/// Cheap to clone; shares state with the `DaemonService` it came from so it
+/// can keep answering probes after the service is moved into the IPC server.
+#[derive(Clone)]
+pub struct HealthProbe {
+    state: Arc<RwLock<DaemonState>>,
+    cmd_tx: Arc<RwLock<Option<mpsc::Sender<NodeCommand>>>>,
+    settlement_client: Arc<SettlementClient>,
+    settlement_status: Arc<RwLock<Option<(std::time::Instant, std::result::Result<(), String>)>>>,
+}
+
+impl HealthProbe {
+    /// Gather a health report (node task, swarm, settlement RPC)
+    pub async fn check(&self) -> HealthReport {
+        let state = *self.state.read().await;
+        let mut report = HealthReport::new(state);
+
+        let cmd_tx = self.cmd_tx.read().await.clone();
+        if let Some(tx) = cmd_tx {
+            let (reply_tx, reply_rx) = oneshot::channel();
+            let node = async {
+                tx.send(NodeCommand::GetHealth(reply_tx)).await.ok()?;
+                reply_rx.await.ok()
+            };
+            match tokio::time::timeout(HEALTH_NODE_TIMEOUT, node).await {
+                Ok(Some(health)) => {
+                    report.node_responsive = Some(true);
+                    report.swarm_ready = health.peer_id.is_some() && health.peer_count > 0;
+                    report.peer_id = health.peer_id;
+                    report.peer_count = health.peer_count;
+                    report.bootstrapped = health.bootstrapped;
+                    report.traffic = health.traffic;
+                    report.traffic_peers = health.traffic_peers;
+                    report.freeloaders = health.freeloaders;
+                }
+                _ => report.node_responsive = Some(false),
+            }
+        }
+
+        match self.settlement_status().await {
+            Ok(()) => report.settlement_reachable = true,
+            Err(e) => report.settlement_error = Some(e),
+        }
+
+        report
+    }
+
+    /// Settlement RPC reachability, cached for `HEALTH_SETTLEMENT_TTL`
+    async fn settlement_status(&self) -> std::result::Result<(), String> {
+        if let Some((checked_at, ref result)) = *self.settlement_status.read().await {
+            if checked_at.elapsed() < HEALTH_SETTLEMENT_TTL {
+                return result.clone();
+            }
+        }
+
+        let result = match tokio::time::timeout(HEALTH_NODE_TIMEOUT, self.settlement_client.health_check()).await {
+            Ok(result) => result.map_err(|e| e.to_string()),
+            Err(_) => Err("settlement RPC health check timed out".to_string()),
+        };
+        if let Err(ref e) = result {
+            debug!("Settlement RPC health check failed: {}", e);
+        }
+        *self.settlement_status.write().await = Some((std::time::Instant::now(), result.clone()));
+        result
+    }
+}
+
+/// Drains the node on shutdown.
+///
+/// Cheap to clone; like [`HealthProbe`] it outlives the move of the
+/// `DaemonService` into the IPC server, so signal handlers can use it.
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    state: Arc<RwLock<DaemonState>>,
+    cmd_tx: Arc<RwLock<Option<mpsc::Sender<NodeCommand>>>>,
+    event_tx: broadcast::Sender<String>,
+}
+
+impl ShutdownHandle {
+    /// Drain the node: stop advertising, nack new streams with `Draining`,
+    /// wait for in-flight assemblies/acks and flush proofs (up to `timeout`,
+    /// default `NodeConfig::drain_timeout`), then stop the node task.
+    ///
+    /// Returns `None` when the node was not running.
+    pub async fn drain(&self, timeout: Option<std::time::Duration>) -> Option<DrainResponse> {
+        let tx = self.cmd_tx.read().await.clone()?;
+        self.set_state(DaemonState::Stopping).await;
+
+        let (reply_tx, reply_rx) = oneshot::channel();
+        let result = match tx.send(NodeCommand::Drain { timeout, reply: reply_tx }).await {
+            Ok(()) => reply_rx.await.ok().map(DrainResponse::from),
+            Err(_) => None,
+        };
+
+        *self.cmd_tx.write().await = None;
+        self.set_state(DaemonState::Ready).await;
+        match result {
+            Some(ref r) if r.completed => info!("Node drained"),
+            Some(ref r) => warn!("Drain deadline passed with work in flight: {:?}", r),
+            None => warn!("Node task exited before the drain finished"),
+        }
+        result
+    }
+
+    async fn set_state(&self, new_state: DaemonState) {
+        *self.state.write().await = new_state;
+        let state_str = serde_json::to_value(new_state).unwrap_or_default();
+        let msg = serde_json::json!({"event": "state_change", "data": {"state": state_str}});
+        let _ = self.event_tx.send(msg.to_string());
+    }
 }
 
 /// Proxy status information
@@ -206,6 +801,19 @@ struct NodeStatusInfo {
     relay_announced_secs_ago: Option<u64>,
     /// Seconds since exit capability was last announced (None = never)
     exit_announced_secs_ago: Option<u64>,
+    /// Selected exit pubkey (hex) and region
+    exit: Option<String>,
+    exit_region: Option<String>,
+}
+
+/// The tunnel session in progress, for its history entry
+#[derive(Debug, Clone)]
+struct ActiveConnection {
+    connected_at: u64,
+    hop_mode: HopMode,
+    /// Client traffic counters when the session started
+    bytes_sent: u64,
+    bytes_received: u64,
 }
 
 /// Daemon service
@@ -231,12 +839,10 @@ pub struct DaemonService {
     node_pubkey: [u8; 32],
     /// Persisted settings
     settings: Arc<RwLock<Settings<CraftNetConfig>>>,
-    /// Connection history (capped at 100 entries)
-    connection_history: Arc<RwLock<Vec<ConnectionHistoryEntry>>>,
-    /// Current connection start time (for computing duration on disconnect)
-    connection_start: Arc<RwLock<Option<u64>>>,
-    /// Connection ID counter
-    connection_id_counter: Arc<RwLock<u64>>,
+    /// Connection history (persisted next to the settings)
+    connection_history: Arc<RwLock<ConnectionHistory>>,
+    /// Current tunnel session (for its history entry on disconnect)
+    connection_start: Arc<RwLock<Option<ActiveConnection>>>,
     /// Earnings history (capped at 100 entries)
     earnings_history: Arc<RwLock<Vec<EarningsEntry>>>,
     /// Earnings ID counter
@@ -246,6 +852,169 @@ pub struct DaemonService {
     /// Current bandwidth limit in kbps (None = unlimited)
     bandwidth_limit_kbps: Arc<RwLock<Option<u64>>>,
     swarm_handles: Arc<RwLock<Option<craftnet_client::SwarmHandles>>>,
+    /// Live topology graph (refreshed by the node task, served via IPC)
+    topology: Arc<RwLock<TopologyCollector>>,
+    /// Key the request audit log is sealed with (derived from the node secret)
+    audit_key: [u8; 32],
+    /// Audit log location when the settings don't name one
+    default_audit_path: std::path::PathBuf,
+    /// Key the cookie jar is sealed with (derived from the audit key)
+    cookie_key: [u8; 32],
+    /// Cookie jar location when the settings don't name one
+    default_cookie_path: std::path::PathBuf,
+    /// Split tunneling rules, shared with the running proxy
+    split_tunnel: SharedSplitTunnelRules,
+    /// Kill switch (engaged by the node task when the tunnel drops)
+    kill_switch: Arc<RwLock<KillSwitch>>,
+    /// In-tunnel DNS stub listener (None = not running)
+    dns_stub: Arc<RwLock<Option<DnsStub>>>,
+    /// Connected IPC clients, by session id
+    sessions: Arc<std::sync::Mutex<HashMap<u64, ClientSession>>>,
+}
+
+/// State kept per IPC client
+struct ClientSession {
+    session: IpcSession,
+    opened_at: u64,
+    requests: u64,
+    /// Streamed responses started by this client (their events go only to it)
+    streams: HashSet<String>,
+    /// Events for this client alone (streamed response bodies)
+    events: mpsc::Sender<String>,
+    /// Receiving end of `events`, until the IPC server takes it
+    events_rx: Option<mpsc::Receiver<String>>,
+}
+
+/// IPC client session, for the whoami and list_sessions IPC methods
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionInfo {
+    pub id: u64,
+    pub uid: Option<u32>,
+    pub pid: Option<i32>,
+    pub admin: bool,
+    /// Unix time the client connected
+    pub opened_at: u64,
+    pub requests: u64,
+    pub active_streams: usize,
+}
+
+impl ClientSession {
+    fn info(&self) -> SessionInfo {
+        SessionInfo {
+            id: self.session.id,
+            uid: self.session.peer.map(|p| p.uid),
+            pid: self.session.peer.and_then(|p| p.pid),
+            admin: self.session.admin,
+            opened_at: self.opened_at,
+            requests: self.requests,
+            active_streams: self.streams.len(),
+        }
+    }
+}
+
+/// Privacy level name of a hop mode
+fn privacy_level_name(mode: HopMode) -> &'static str {
+    match mode {
+        HopMode::Direct => "direct",
+        HopMode::Single => "single",
+        HopMode::Double => "double",
+        HopMode::Triple => "triple",
+        HopMode::Quad => "quad",
+    }
+}
+
+/// Hop mode of a privacy level name (`direct` ... `quad`)
+fn parse_privacy_level(level: &str) -> Result<HopMode> {
+    match level {
+        "direct" => Ok(HopMode::Direct),
+        "single" => Ok(HopMode::Single),
+        "double" => Ok(HopMode::Double),
+        "triple" => Ok(HopMode::Triple),
+        "quad" => Ok(HopMode::Quad),
+        _ => Err(crate::DaemonError::InvalidRequest(
+            format!("Unknown privacy level: {}. Use direct, single, double, triple, or quad", level)
+        )),
+    }
+}
+
+/// Broadcast a kill switch transition for platform firewall integrations
+fn send_kill_switch_event(event_tx: &broadcast::Sender<String>, status: &KillSwitchStatus) {
+    let data = serde_json::to_value(status).unwrap_or_default();
+    let msg = serde_json::json!({"event": "kill_switch", "data": data});
+    let _ = event_tx.send(msg.to_string());
+}
+
+/// What the DNS stub asks of the daemon: kill switch state, split
+/// tunneling decisions and DoH lookups through the node
+struct DaemonDnsBackend {
+    cmd_tx: Arc<RwLock<Option<mpsc::Sender<NodeCommand>>>>,
+    kill_switch: Arc<RwLock<KillSwitch>>,
+    split_tunnel: SharedSplitTunnelRules,
+    doh_url: String,
+}
+
+impl DnsBackend for DaemonDnsBackend {
+    async fn kill_switch_engaged(&self) -> bool {
+        self.kill_switch.read().await.is_engaged()
+    }
+
+    fn bypass(&self, domain: &str) -> bool {
+        self.split_tunnel.read().unwrap_or_else(|e| e.into_inner()).decide(None, domain) == SplitTunnelAction::Bypass
+    }
+
+    async fn resolve_doh(&self, query: Vec<u8>) -> std::result::Result<Vec<u8>, String> {
+        let headers: HashMap<String, String> = [
+            ("content-type".to_string(), "application/dns-message".to_string()),
+            ("accept".to_string(), "application/dns-message".to_string()),
+        ]
+        .into();
+        let (reply_tx, reply_rx) = oneshot::channel();
+        {
+            let cmd_tx = self.cmd_tx.read().await;
+            let tx = cmd_tx.as_ref().ok_or("Node not initialized")?;
+            tx.send(NodeCommand::Request {
+                method: "POST".to_string(),
+                url: self.doh_url.clone(),
+                body: Some(query),
+                headers: Some(headers),
+                options: RequestOptions::default(),
+                reply: reply_tx,
+            })
+            .await
+            .map_err(|_| "Node channel closed".to_string())?;
+        }
+        let response = reply_rx.await.map_err(|_| "Node reply channel closed".to_string())??;
+        if response.status != 200 {
+            return Err(format!("DoH server answered HTTP {}", response.status));
+        }
+        Ok(response.body)
+    }
+}
+
+/// Audit log response for the get_audit_log IPC method
+#[derive(Debug, Serialize)]
+pub struct AuditLogResponse {
+    pub enabled: bool,
+    pub entries: Vec<AuditEntry>,
+}
+
+/// Derive the audit log key from the node's ed25519 secret
+fn derive_audit_key(secret: &[u8; 32]) -> [u8; 32] {
+    use sha2::{Digest, Sha256};
+    let mut hasher = Sha256::new();
+    hasher.update(b"craftnet-audit-log-v1");
+    hasher.update(secret);
+    hasher.finalize().into()
+}
+
+/// Derive the cookie jar key from the audit log key, so one secret-derived
+/// key doesn't seal two different files
+fn derive_cookie_key(audit_key: &[u8; 32]) -> [u8; 32] {
+    use sha2::{Digest, Sha256};
+    let mut hasher = Sha256::new();
+    hasher.update(b"craftnet-cookie-jar-v1");
+    hasher.update(audit_key);
+    hasher.finalize().into()
 }
 
 impl DaemonService {
@@ -267,7 +1036,7 @@ impl DaemonService {
 
         let settlement_client = Arc::new(SettlementClient::with_secret_key(settlement_config, &secret));
 
-        Self::new_inner(settlement_client, node_pubkey, None)
+        Self::new_inner(settlement_client, node_pubkey, derive_audit_key(&secret), None)
     }
 
     /// Create a daemon service with a specific keypair.
@@ -294,7 +1063,7 @@ impl DaemonService {
         #[cfg(not(test))]
         let settings_path = None;
 
-        Self::new_inner(settlement_client, node_pubkey, settings_path)
+        Self::new_inner(settlement_client, node_pubkey, derive_audit_key(secret), settings_path)
     }
 
     /// Create a daemon service with a specific keypair and a custom data directory.
@@ -313,7 +1082,7 @@ impl DaemonService {
         // This prevents cross-instance pollution when multiple daemons run on the same machine.
         let settings_path = data_dir.join("craftnet_settings.json");
 
-        Self::new_inner(settlement_client, node_pubkey, Some(settings_path))
+        Self::new_inner(settlement_client, node_pubkey, derive_audit_key(secret), Some(settings_path))
     }
 
     /// Build settlement config from environment variables.
@@ -356,12 +1125,13 @@ impl DaemonService {
         let settlement_client = Arc::new(SettlementClient::new(settlement_config, [0u8; 32]));
         let mut path = std::env::temp_dir();
         path.push(format!("craftnet_daemon_settings_{}.toml", rand::random::<u64>()));
-        Self::new_inner(settlement_client, [0u8; 32], Some(path))
+        Self::new_inner(settlement_client, [0u8; 32], [0u8; 32], Some(path))
     }
 
     fn new_inner(
         settlement_client: Arc<SettlementClient>,
         node_pubkey: [u8; 32],
+        audit_key: [u8; 32],
         settings_path: Option<std::path::PathBuf>,
     ) -> Result<Self> {
         let (event_tx, _) = broadcast::channel(64);
@@ -395,6 +1165,22 @@ impl DaemonService {
             ConfigHopMode::Triple => HopMode::Triple,
             ConfigHopMode::Quad => HopMode::Quad,
         };
+        let default_audit_path = settings_path
+            .clone()
+            .unwrap_or_else(|| craftec_settings::default_settings_path("craftnet"))
+            .with_file_name("craftnet_audit.log");
+        let default_cookie_path = settings_path
+            .clone()
+            .unwrap_or_else(|| craftec_settings::default_settings_path("craftnet"))
+            .with_file_name("craftnet_cookies.jar");
+        let connection_history = ConnectionHistory::load(
+            settings_path
+                .clone()
+                .unwrap_or_else(|| craftec_settings::default_settings_path("craftnet"))
+                .with_file_name("craftnet_history.jsonl"),
+        );
+        let split_tunnel = Arc::new(std::sync::RwLock::new(settings.config.split_tunnel.clone()));
+        let kill_switch = Arc::new(RwLock::new(KillSwitch::new(settings.config.network.kill_switch)));
         let node_caps = match settings.config.node.mode {
             NodeMode::Disabled => Capabilities::CLIENT,
             NodeMode::Relay    => Capabilities::CLIENT | Capabilities::RELAY,
@@ -419,14 +1205,22 @@ impl DaemonService {
             settlement_client,
             node_pubkey,
             settings: Arc::new(RwLock::new(settings)),
-            connection_history: Arc::new(RwLock::new(Vec::new())),
+            connection_history: Arc::new(RwLock::new(connection_history)),
             connection_start: Arc::new(RwLock::new(None)),
-            connection_id_counter: Arc::new(RwLock::new(0)),
             earnings_history: Arc::new(RwLock::new(Vec::new())),
             earnings_id_counter: Arc::new(RwLock::new(0)),
             speed_test_results: Arc::new(RwLock::new(Vec::new())),
             bandwidth_limit_kbps: Arc::new(RwLock::new(None)),
             swarm_handles: Arc::new(RwLock::new(None)),
+            topology: Arc::new(RwLock::new(TopologyCollector::default())),
+            audit_key,
+            default_audit_path,
+            cookie_key: derive_cookie_key(&audit_key),
+            default_cookie_path,
+            split_tunnel,
+            kill_switch,
+            dns_stub: Arc::new(RwLock::new(None)),
+            sessions: Arc::new(std::sync::Mutex::new(HashMap::new())),
         })
     }
 
@@ -435,6 +1229,25 @@ impl DaemonService {
         self.event_tx.clone()
     }
 
+    /// Health probe sharing this service's state (for the health endpoint)
+    pub fn health_probe(&self) -> HealthProbe {
+        HealthProbe {
+            state: self.state.clone(),
+            cmd_tx: self.cmd_tx.clone(),
+            settlement_client: self.settlement_client.clone(),
+            settlement_status: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Shutdown handle sharing this service's state (for signal handlers)
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            state: self.state.clone(),
+            cmd_tx: self.cmd_tx.clone(),
+            event_tx: self.event_tx.clone(),
+        }
+    }
+
     /// Set external swarm handles (e.g. from DaemonManager for single-swarm integration)
     pub async fn set_swarm_handles(&self, handles: craftnet_client::SwarmHandles) {
         *self.swarm_handles.write().await = Some(handles);
@@ -462,9 +1275,13 @@ impl DaemonService {
         let privacy_level = *self.privacy_level.read().await;
         let capabilities = *self.node_capabilities.read().await;
         info!("[init] starting node with capabilities={:?}", capabilities);
+        let audit = self.settings.read().await.config.audit.clone();
+        let cookies = self.settings.read().await.config.cookies.clone();
         let config = NodeConfig {
             capabilities,
             hop_mode: privacy_level,
+            audit_log: audit.enabled.then(|| self.audit_log_config(&audit)),
+            cookie_jar: cookies.enabled.then(|| self.cookie_jar_config(&cookies)),
             ..Default::default()
         };
 
@@ -476,18 +1293,29 @@ impl DaemonService {
     pub async fn init_with_node_config(&self, config: NodeConfig) -> Result<()> {
         let (cmd_tx, cmd_rx) = mpsc::channel::<NodeCommand>(32);
         let node_status = self.node_status.clone();
+        let topology = self.topology.clone();
+        let event_tx = self.event_tx.clone();
+        let split_tunnel = self.split_tunnel.clone();
+        let kill_switch = self.kill_switch.clone();
 
         let handles: Option<craftnet_client::SwarmHandles> = self.swarm_handles.write().await.take();
 
         // Spawn node task
         tokio::spawn(async move {
-            if let Err(e) = run_node_task(config, cmd_rx, node_status, handles).await {
+            if let Err(e) = run_node_task(config, cmd_rx, node_status, topology, event_tx, split_tunnel, kill_switch, handles).await {
                 error!("Node task error: {}", e);
             }
         });
 
         *self.cmd_tx.write().await = Some(cmd_tx);
         info!("Node task started");
+
+        let dns = self.settings.read().await.config.dns.clone();
+        if dns.enabled {
+            if let Err(e) = self.start_dns_stub(&dns).await {
+                warn!("DNS stub not started: {}", e);
+            }
+        }
         Ok(())
     }
 
@@ -539,6 +1367,12 @@ impl DaemonService {
         Ok(())
     }
 
+    /// Gracefully drain and stop the tunnel daemon (see [`ShutdownHandle::drain`]).
+    pub async fn drain(&self, timeout: Option<std::time::Duration>) -> Result<DrainResponse> {
+        self.shutdown_handle().drain(timeout).await
+            .ok_or(crate::DaemonError::NotRunning)
+    }
+
     /// Get current state
     pub async fn state(&self) -> DaemonState {
         *self.state.read().await
@@ -561,13 +1395,7 @@ impl DaemonService {
         } else {
             "client"
         }.to_string();
-        let privacy = match *self.privacy_level.read().await {
-            HopMode::Direct => "direct",
-            HopMode::Single => "single",
-            HopMode::Double => "double",
-            HopMode::Triple => "triple",
-            HopMode::Quad => "quad",
-        }.to_string();
+        let privacy = privacy_level_name(*self.privacy_level.read().await).to_string();
 
         let relay_caps_enabled_secs_ago = self.relay_caps_enabled_at.read().await
             .map(|t| t.elapsed().as_secs());
@@ -629,59 +1457,196 @@ impl DaemonService {
             if tx.send(NodeCommand::GetStats(reply_tx)).await.is_ok() {
                 drop(cmd_tx);
                 if let Ok(stats) = reply_rx.await {
-                    return Some(NodeStatsResponse::from(stats));
+                    let mut response = NodeStatsResponse::from(stats);
+                    response.bandwidth_limit_kbps = *self.bandwidth_limit_kbps.read().await;
+                    return Some(response);
                 }
             }
         }
         None
     }
 
-    /// Get the local PeerId string of the running node task, or None if not yet started.
-    pub async fn local_peer_id_str(&self) -> Option<String> {
+    /// Get the credit ledger with its latest `limit` entries
+    pub async fn get_credit_ledger(&self, limit: usize) -> Option<CreditLedgerResponse> {
         let cmd_tx = self.cmd_tx.read().await;
         if let Some(ref tx) = *cmd_tx {
             let (reply_tx, reply_rx) = oneshot::channel();
-            if tx.send(NodeCommand::GetPeerId(reply_tx)).await.is_ok() {
+            if tx.send(NodeCommand::GetCreditLedger { limit, reply: reply_tx }).await.is_ok() {
                 drop(cmd_tx);
-                if let Ok(pid) = reply_rx.await {
-                    return pid;
-                }
+                return reply_rx.await.ok();
             }
         }
         None
     }
 
-    /// Get list of all known CraftNet peers (relay + exit nodes)
-    pub async fn get_peers(&self) -> Vec<PeerSummary> {
+    /// Get the exit request log with its `limit` busiest destinations
+    pub async fn get_exit_abuse(&self, limit: usize) -> Option<ExitAbuseResponse> {
         let cmd_tx = self.cmd_tx.read().await;
         if let Some(ref tx) = *cmd_tx {
             let (reply_tx, reply_rx) = oneshot::channel();
-            if tx.send(NodeCommand::GetPeers(reply_tx)).await.is_ok() {
+            if tx.send(NodeCommand::GetExitAbuse { limit, reply: reply_tx }).await.is_ok() {
                 drop(cmd_tx);
-                if let Ok(peers) = reply_rx.await {
-                    return peers;
-                }
+                return reply_rx.await.ok();
             }
         }
-        vec![]
+        None
     }
 
-    /// Connect to VPN
-    pub async fn connect(&self, params: ConnectParams) -> Result<()> {
-        info!("Connecting to VPN with hops: {:?}", params.hops);
-
-        // Apply hops param to privacy level if provided
-        if let Some(hops) = params.hops {
-            let hop_mode = HopMode::from_count(hops);
-            *self.privacy_level.write().await = hop_mode;
-        }
+    /// Block (or unblock) a destination or pool at the exit, returning
+    /// whether that changed anything
+    pub async fn set_exit_block(&self, target: BlockTarget, block: bool) -> Result<bool> {
+        let cmd_tx = self.cmd_tx.read().await;
+        let Some(ref tx) = *cmd_tx else {
+            return Err(crate::DaemonError::NotRunning);
+        };
+        let (reply_tx, reply_rx) = oneshot::channel();
+        tx.send(NodeCommand::SetExitBlock { target, block, reply: reply_tx })
+            .await
+            .map_err(|_| crate::DaemonError::SdkError("Node not running".to_string()))?;
+        drop(cmd_tx);
+        reply_rx.await
+            .map_err(|_| crate::DaemonError::SdkError("Node task died".to_string()))
+    }
 
-        // Initialize node if not already done
-        {
-            let cmd_tx = self.cmd_tx.read().await;
-            if cmd_tx.is_none() {
+    /// Bytes moved per second over the last `window_secs` seconds (oldest
+    /// first), split into client, relay and exit traffic
+    pub async fn get_throughput_series(&self, window_secs: u64) -> Vec<ThroughputSample> {
+        let cmd_tx = self.cmd_tx.read().await;
+        if let Some(ref tx) = *cmd_tx {
+            let (reply_tx, reply_rx) = oneshot::channel();
+            if tx.send(NodeCommand::GetThroughput { window_secs, reply: reply_tx }).await.is_ok() {
                 drop(cmd_tx);
-                self.init().await?;
+                return reply_rx.await.unwrap_or_default();
+            }
+        }
+        Vec::new()
+    }
+
+    /// Connected peers, longest connected first (empty when the node isn't running)
+    pub async fn list_peers(&self) -> Vec<PeerConnectionEntry> {
+        let cmd_tx = self.cmd_tx.read().await;
+        if let Some(ref tx) = *cmd_tx {
+            let (reply_tx, reply_rx) = oneshot::channel();
+            if tx.send(NodeCommand::ListPeers(reply_tx)).await.is_ok() {
+                drop(cmd_tx);
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+                return reply_rx.await
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|info| PeerConnectionEntry::new(info, now))
+                    .collect();
+            }
+        }
+        Vec::new()
+    }
+
+    /// This binary's build manifest, checked against the canonical manifests
+    /// in the network parameters when the node is running and has them
+    pub async fn build_attestation(&self) -> BuildAttestationResponse {
+        let mut response = BuildAttestationResponse {
+            build: BuildManifest::current(),
+            params_sequence: None,
+            attestation: None,
+        };
+        let cmd_tx = self.cmd_tx.read().await;
+        if let Some(ref tx) = *cmd_tx {
+            let (reply_tx, reply_rx) = oneshot::channel();
+            if tx.send(NodeCommand::GetBuildAttestation(reply_tx)).await.is_ok() {
+                drop(cmd_tx);
+                if let Some((sequence, attestation)) = reply_rx.await.ok().flatten() {
+                    response.params_sequence = Some(sequence);
+                    response.attestation = Some(attestation);
+                }
+            }
+        }
+        response
+    }
+
+    /// Kademlia inspection handle of the running node. DHT queries go
+    /// straight to the swarm so slow lookups don't hold up the node task.
+    pub async fn dht_handle(&self) -> Result<DhtHandle> {
+        let cmd_tx = self.cmd_tx.read().await;
+        let Some(ref tx) = *cmd_tx else {
+            return Err(crate::DaemonError::NotRunning);
+        };
+        let (reply_tx, reply_rx) = oneshot::channel();
+        tx.send(NodeCommand::GetDhtHandle(reply_tx)).await
+            .map_err(|_| crate::DaemonError::NotRunning)?;
+        drop(cmd_tx);
+        reply_rx.await
+            .map_err(|_| crate::DaemonError::NotRunning)?
+            .ok_or_else(|| crate::DaemonError::InvalidRequest("DHT inspection needs a standalone swarm".to_string()))
+    }
+
+    /// Get the local PeerId string of the running node task, or None if not yet started.
+    pub async fn local_peer_id_str(&self) -> Option<String> {
+        let cmd_tx = self.cmd_tx.read().await;
+        if let Some(ref tx) = *cmd_tx {
+            let (reply_tx, reply_rx) = oneshot::channel();
+            if tx.send(NodeCommand::GetPeerId(reply_tx)).await.is_ok() {
+                drop(cmd_tx);
+                if let Ok(pid) = reply_rx.await {
+                    return pid;
+                }
+            }
+        }
+        None
+    }
+
+    /// Get list of all known CraftNet peers (relay + exit nodes)
+    pub async fn get_peers(&self) -> Vec<PeerSummary> {
+        let cmd_tx = self.cmd_tx.read().await;
+        if let Some(ref tx) = *cmd_tx {
+            let (reply_tx, reply_rx) = oneshot::channel();
+            if tx.send(NodeCommand::GetPeers(reply_tx)).await.is_ok() {
+                drop(cmd_tx);
+                if let Ok(peers) = reply_rx.await {
+                    return peers;
+                }
+            }
+        }
+        vec![]
+    }
+
+    /// Get the live network topology (nodes, roles, links, last-seen)
+    pub async fn get_topology(&self) -> TopologyResponse {
+        let cmd_tx = self.cmd_tx.read().await;
+        if let Some(ref tx) = *cmd_tx {
+            let (reply_tx, reply_rx) = oneshot::channel();
+            if tx.send(NodeCommand::GetTopology(reply_tx)).await.is_ok() {
+                drop(cmd_tx);
+                if let Ok(snapshot) = reply_rx.await {
+                    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+                    self.topology.write().await.ingest(&snapshot, now);
+                }
+            }
+        }
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        self.topology.read().await.view(now)
+    }
+
+    /// Connect to VPN
+    pub async fn connect(&self, params: ConnectParams) -> Result<()> {
+        info!("Connecting to VPN with hops: {:?}", params.hops);
+
+        #[cfg(not(feature = "tun"))]
+        if params.full_device {
+            return Err(crate::DaemonError::InvalidRequest(TUN_UNSUPPORTED.to_string()));
+        }
+
+        // Apply hops param to privacy level if provided
+        if let Some(hops) = params.hops {
+            let hop_mode = HopMode::from_count(hops);
+            *self.privacy_level.write().await = hop_mode;
+        }
+
+        // Initialize node if not already done
+        {
+            let cmd_tx = self.cmd_tx.read().await;
+            if cmd_tx.is_none() {
+                drop(cmd_tx);
+                self.init().await?;
             }
         }
 
@@ -695,19 +1660,46 @@ impl DaemonService {
 
             drop(cmd_tx);
 
-            reply_rx.await
-                .map_err(|_| crate::DaemonError::SdkError("Node reply channel closed".to_string()))?
-                .map_err(crate::DaemonError::SdkError)?;
+            let hop_mode = *self.privacy_level.read().await;
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+            let connected = reply_rx.await
+                .map_err(|_| "Node reply channel closed".to_string())
+                .and_then(|r| r);
+            if let Err(reason) = connected {
+                self.record_connection(ConnectionHistoryEntry {
+                    id: 0,
+                    connected_at: now,
+                    disconnected_at: Some(now),
+                    duration_secs: Some(0),
+                    exit: None,
+                    exit_region: None,
+                    hop_mode: Some(privacy_level_name(hop_mode).to_string()),
+                    bytes_sent: 0,
+                    bytes_received: 0,
+                    failure_reason: Some(reason.clone()),
+                }).await;
+                return Err(crate::DaemonError::SdkError(reason));
+            }
 
             self.set_state(DaemonState::Connected).await;
 
-            // Record connection start time
-            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-            *self.connection_start.write().await = Some(now);
+            // Record the session start for its history entry
+            let stats = self.get_node_stats().await;
+            *self.connection_start.write().await = Some(ActiveConnection {
+                connected_at: now,
+                hop_mode,
+                bytes_sent: stats.as_ref().map_or(0, |s| s.bytes_sent),
+                bytes_received: stats.as_ref().map_or(0, |s| s.bytes_received),
+            });
 
             info!("Connected to VPN");
         }
 
+        #[cfg(feature = "tun")]
+        if params.full_device {
+            self.start_tun(craftnet_client::TunConfig::default()).await?;
+        }
+
         Ok(())
     }
 
@@ -715,6 +1707,10 @@ impl DaemonService {
     pub async fn disconnect(&self) -> Result<()> {
         info!("Disconnecting from VPN");
 
+        // Counters and exit of the session, read while the node is still up
+        let stats = self.get_node_stats().await;
+        self.status().await;
+
         self.set_state(DaemonState::Disconnecting).await;
 
         let cmd_tx = self.cmd_tx.read().await;
@@ -735,25 +1731,23 @@ impl DaemonService {
         // Record connection history entry
         let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
         let start = self.connection_start.write().await.take();
-        if let Some(connected_at) = start {
-            let duration = now.saturating_sub(connected_at);
-            let ns = self.node_status.read().await;
-            let mut counter = self.connection_id_counter.write().await;
-            *counter += 1;
-            let entry = ConnectionHistoryEntry {
-                id: *counter,
-                connected_at,
-                disconnected_at: Some(now),
-                duration_secs: Some(duration),
-                exit_region: None,
-                bytes_sent: ns.shards_relayed * 1024, // estimate
-                bytes_received: ns.requests_exited * 1024, // estimate
+        if let Some(session) = start {
+            let (exit, exit_region) = {
+                let ns = self.node_status.read().await;
+                (ns.exit.clone(), ns.exit_region.clone())
             };
-            let mut history = self.connection_history.write().await;
-            history.push(entry);
-            if history.len() > 100 {
-                history.remove(0);
-            }
+            self.record_connection(ConnectionHistoryEntry {
+                id: 0,
+                connected_at: session.connected_at,
+                disconnected_at: Some(now),
+                duration_secs: Some(now.saturating_sub(session.connected_at)),
+                exit,
+                exit_region,
+                hop_mode: Some(privacy_level_name(session.hop_mode).to_string()),
+                bytes_sent: stats.as_ref().map_or(0, |s| s.bytes_sent.saturating_sub(session.bytes_sent)),
+                bytes_received: stats.as_ref().map_or(0, |s| s.bytes_received.saturating_sub(session.bytes_received)),
+                failure_reason: None,
+            }).await;
         }
 
         info!("Disconnected from VPN");
@@ -899,16 +1893,7 @@ impl DaemonService {
 
     /// Set privacy level for the next connection
     pub async fn set_privacy_level(&self, level: &str) -> Result<()> {
-        let hop_mode = match level {
-            "direct" => HopMode::Direct,
-            "single" => HopMode::Single,
-            "double" => HopMode::Double,
-            "triple" => HopMode::Triple,
-            "quad" => HopMode::Quad,
-            _ => return Err(crate::DaemonError::InvalidRequest(
-                format!("Unknown privacy level: {}. Use direct, single, double, triple, or quad", level)
-            )),
-        };
+        let hop_mode = parse_privacy_level(level)?;
 
         *self.privacy_level.write().await = hop_mode;
 
@@ -931,12 +1916,53 @@ impl DaemonService {
         Ok(())
     }
 
-    /// Make an HTTP request through the tunnel
-    pub async fn request(&self, method: &str, url: &str, body: Option<Vec<u8>>, headers: Option<std::collections::HashMap<String, String>>) -> Result<TunnelResponse> {
+    /// Make an HTTP request through the tunnel; `options` override the hop
+    /// mode, exit and timeout for this request only
+    pub async fn request(&self, method: &str, url: &str, body: Option<Vec<u8>>, headers: Option<std::collections::HashMap<String, String>>, options: RequestOptions) -> Result<TunnelResponse> {
         let cmd_tx = self.cmd_tx.read().await;
         if let Some(ref tx) = *cmd_tx {
             let (reply_tx, reply_rx) = oneshot::channel();
             tx.send(NodeCommand::Request {
+                method: method.to_string(),
+                url: url.to_string(),
+                body,
+                headers,
+                options,
+                reply: reply_tx,
+            }).await
+                .map_err(|_| crate::DaemonError::SdkError("Node channel closed".to_string()))?;
+
+            drop(cmd_tx);
+
+            reply_rx.await
+                .map_err(|_| crate::DaemonError::SdkError("Node reply channel closed".to_string()))?
+                .map_err(crate::DaemonError::SdkError)
+        } else {
+            Err(crate::DaemonError::SdkError("Node not initialized".to_string()))
+        }
+    }
+
+    /// Make an HTTP request through the tunnel, streaming the body as IPC events.
+    ///
+    /// Returns the response head; body chunks follow as `response_chunk`
+    /// events and the stream ends with `response_end` or `response_error`,
+    /// all tagged with `stream_id`. They go to `events` (a client's session
+    /// channel) when given, otherwise to every client.
+    pub async fn request_stream(
+        &self,
+        stream_id: String,
+        events: Option<mpsc::Sender<String>>,
+        method: &str,
+        url: &str,
+        body: Option<Vec<u8>>,
+        headers: Option<std::collections::HashMap<String, String>>,
+    ) -> Result<StreamHead> {
+        let cmd_tx = self.cmd_tx.read().await;
+        if let Some(ref tx) = *cmd_tx {
+            let (reply_tx, reply_rx) = oneshot::channel();
+            tx.send(NodeCommand::RequestStream {
+                stream_id,
+                events,
                 method: method.to_string(),
                 url: url.to_string(),
                 body,
@@ -979,6 +2005,33 @@ impl DaemonService {
         Ok(())
     }
 
+    /// Choose the pools the node's aggregator follows. Returns the number
+    /// of chains re-synced because the selection widened.
+    pub async fn set_aggregator_pools(&self, pools: AggregatorPools) -> Result<usize> {
+        let tx = self.cmd_tx.read().await.clone().ok_or(crate::DaemonError::NotRunning)?;
+        let (reply_tx, reply_rx) = oneshot::channel();
+        tx.send(NodeCommand::SetAggregatorPools(pools, reply_tx)).await
+            .map_err(|_| crate::DaemonError::SdkError("Node channel closed".to_string()))?;
+        reply_rx.await
+            .map_err(|_| crate::DaemonError::SdkError("Node reply channel closed".to_string()))
+    }
+
+    /// Get one Merkle multiproof for several relays' claims in a pool's
+    /// current distribution (None when the node runs no aggregator or none
+    /// of the relays has a claim)
+    pub async fn get_distribution_batch_proof(
+        &self,
+        pool_key: ([u8; 32], PoolType),
+        relays: Vec<[u8; 32]>,
+    ) -> Result<Option<DistributionBatchProofResponse>> {
+        let tx = self.cmd_tx.read().await.clone().ok_or(crate::DaemonError::NotRunning)?;
+        let (reply_tx, reply_rx) = oneshot::channel();
+        tx.send(NodeCommand::GetDistributionBatchProof { pool_key, relays, reply: reply_tx }).await
+            .map_err(|_| crate::DaemonError::SdkError("Node channel closed".to_string()))?;
+        reply_rx.await
+            .map_err(|_| crate::DaemonError::SdkError("Node reply channel closed".to_string()))
+    }
+
     /// Set local discovery preference
     pub async fn set_local_discovery(&self, enabled: bool) -> Result<()> {
         *self.local_discovery.write().await = enabled;
@@ -1000,9 +2053,287 @@ impl DaemonService {
         Ok(())
     }
 
+    /// Audit log location, key and rotation from the persisted settings
+    fn audit_log_config(&self, audit: &craftnet_core::config::AuditSettings) -> AuditLogConfig {
+        let path = audit.path.as_ref()
+            .map(std::path::PathBuf::from)
+            .unwrap_or_else(|| self.default_audit_path.clone());
+        AuditLogConfig {
+            max_file_bytes: audit.max_file_bytes,
+            max_files: audit.max_files,
+            ..AuditLogConfig::new(path, self.audit_key)
+        }
+    }
+
+    /// Turn the request audit log on or off (persisted; off by default)
+    pub async fn set_audit_log(&self, enabled: bool) -> Result<()> {
+        let audit = {
+            let mut settings = self.settings.write().await;
+            settings.config.audit.enabled = enabled;
+            if let Err(e) = settings.save() {
+                debug!("Failed to save settings: {}", e);
+            }
+            settings.config.audit.clone()
+        };
+        let config = enabled.then(|| self.audit_log_config(&audit));
+
+        let cmd_tx = self.cmd_tx.read().await;
+        if let Some(ref tx) = *cmd_tx {
+            let (reply_tx, reply_rx) = oneshot::channel();
+            tx.send(NodeCommand::SetAuditLog(config, reply_tx)).await
+                .map_err(|_| crate::DaemonError::SdkError("Node channel closed".to_string()))?;
+
+            drop(cmd_tx);
+
+            reply_rx.await
+                .map_err(|_| crate::DaemonError::SdkError("Node reply channel closed".to_string()))?
+                .map_err(crate::DaemonError::SdkError)?;
+        }
+
+        info!("Request audit log {}", if enabled { "enabled" } else { "disabled" });
+        Ok(())
+    }
+
+    /// Audit log entries completed at or after `since` (newest `limit` ones).
+    ///
+    /// Entries already on disk stay readable after the log is disabled.
+    pub async fn get_audit_log(&self, since: Option<u64>, limit: Option<usize>) -> AuditLogResponse {
+        let audit = self.settings.read().await.config.audit.clone();
+        let log = AuditLog::new(self.audit_log_config(&audit));
+        AuditLogResponse {
+            enabled: audit.enabled,
+            entries: log.query(since, limit),
+        }
+    }
+
+    /// Export the decrypted audit log as JSONL or CSV text
+    pub async fn export_audit_log(&self, format: AuditExportFormat, since: Option<u64>) -> String {
+        let audit = self.settings.read().await.config.audit.clone();
+        AuditLog::new(self.audit_log_config(&audit)).export(format, since)
+    }
+
+    /// Cookie jar location and key from the persisted settings
+    fn cookie_jar_config(&self, cookies: &craftnet_core::config::CookieSettings) -> CookieJarConfig {
+        let path = cookies.path.as_ref()
+            .map(std::path::PathBuf::from)
+            .unwrap_or_else(|| self.default_cookie_path.clone());
+        CookieJarConfig::persistent(path, self.cookie_key)
+    }
+
+    /// Turn the cookie jar on or off (persisted; off by default)
+    pub async fn set_cookie_jar(&self, enabled: bool) -> Result<()> {
+        let cookies = {
+            let mut settings = self.settings.write().await;
+            settings.config.cookies.enabled = enabled;
+            if let Err(e) = settings.save() {
+                debug!("Failed to save settings: {}", e);
+            }
+            settings.config.cookies.clone()
+        };
+        let config = enabled.then(|| self.cookie_jar_config(&cookies));
+
+        let cmd_tx = self.cmd_tx.read().await;
+        if let Some(ref tx) = *cmd_tx {
+            let (reply_tx, reply_rx) = oneshot::channel();
+            tx.send(NodeCommand::SetCookieJar(config, reply_tx)).await
+                .map_err(|_| crate::DaemonError::SdkError("Node channel closed".to_string()))?;
+
+            drop(cmd_tx);
+
+            reply_rx.await
+                .map_err(|_| crate::DaemonError::SdkError("Node reply channel closed".to_string()))?
+                .map_err(crate::DaemonError::SdkError)?;
+        }
+
+        info!("Cookie jar {}", if enabled { "enabled" } else { "disabled" });
+        Ok(())
+    }
+
+    /// Forget stored cookies of `identity` (None = of every identity), in
+    /// the running node and on disk
+    pub async fn clear_session_data(&self, identity: Option<String>) -> Result<()> {
+        let cookies = self.settings.read().await.config.cookies.clone();
+        let cmd_tx = self.cmd_tx.read().await;
+        match *cmd_tx {
+            Some(ref tx) if cookies.enabled => {
+                let (reply_tx, reply_rx) = oneshot::channel();
+                tx.send(NodeCommand::ClearSessionData(identity, reply_tx)).await
+                    .map_err(|_| crate::DaemonError::SdkError("Node channel closed".to_string()))?;
+
+                drop(cmd_tx);
+
+                reply_rx.await
+                    .map_err(|_| crate::DaemonError::SdkError("Node reply channel closed".to_string()))?
+                    .map_err(crate::DaemonError::SdkError)?;
+            }
+            // No node using the jar: clear the file directly
+            _ => {
+                let config = self.cookie_jar_config(&cookies);
+                if config.path.as_ref().is_some_and(|p| p.exists()) {
+                    CookieJar::load(config).clear(identity.as_deref());
+                }
+            }
+        }
+
+        info!("Session data cleared");
+        Ok(())
+    }
+
+    /// Kill switch status
+    pub async fn kill_switch_status(&self) -> KillSwitchStatus {
+        self.kill_switch.read().await.status()
+    }
+
+    /// Enable or disable automatic kill switch engagement (persisted)
+    pub async fn set_kill_switch_enabled(&self, enabled: bool) -> KillSwitchStatus {
+        let status = {
+            let mut ks = self.kill_switch.write().await;
+            ks.set_enabled(enabled);
+            ks.status()
+        };
+        {
+            let mut settings = self.settings.write().await;
+            settings.config.network.kill_switch = enabled;
+            if let Err(e) = settings.save() {
+                debug!("Failed to save settings: {}", e);
+            }
+        }
+        info!("Kill switch {}", if enabled { "enabled" } else { "disabled" });
+        status
+    }
+
+    /// DNS stub status
+    pub async fn dns_stub_status(&self) -> DnsStubStatus {
+        let dns = self.settings.read().await.config.dns.clone();
+        DnsStubStatus {
+            enabled: dns.enabled,
+            listening: self.dns_stub.read().await.as_ref().map(|stub| stub.listen_addr().to_string()),
+            doh_url: dns.doh_url,
+            bypass_resolver: dns.bypass_resolver,
+        }
+    }
+
+    /// Turn the in-tunnel DNS stub on or off, optionally moving its listen
+    /// address or DoH server (persisted)
+    pub async fn set_dns_stub(&self, enabled: bool, listen: Option<String>, doh_url: Option<String>) -> Result<DnsStubStatus> {
+        let mut dns = self.settings.read().await.config.dns.clone();
+        dns.enabled = enabled;
+        if let Some(listen) = listen {
+            dns.listen = listen;
+        }
+        if let Some(doh_url) = doh_url {
+            if !doh_url.starts_with("https://") {
+                return Err(crate::DaemonError::InvalidRequest("doh_url must be an https:// URL".to_string()));
+            }
+            dns.doh_url = doh_url;
+        }
+
+        self.dns_stub.write().await.take();
+        if enabled {
+            self.start_dns_stub(&dns).await?;
+        }
+        {
+            let mut settings = self.settings.write().await;
+            settings.config.dns = dns;
+            if let Err(e) = settings.save() {
+                debug!("Failed to save settings: {}", e);
+            }
+        }
+        info!("DNS stub {}", if enabled { "enabled" } else { "disabled" });
+        Ok(self.dns_stub_status().await)
+    }
+
+    async fn start_dns_stub(&self, dns: &craftnet_core::config::DnsSettings) -> Result<()> {
+        let listen: std::net::SocketAddr = dns.listen.parse()
+            .map_err(|_| crate::DaemonError::InvalidRequest(format!("Invalid DNS listen address: {}", dns.listen)))?;
+        let bypass_resolver: std::net::SocketAddr = dns.bypass_resolver.parse()
+            .map_err(|_| crate::DaemonError::InvalidRequest(format!("Invalid bypass resolver: {}", dns.bypass_resolver)))?;
+        let backend = Arc::new(DaemonDnsBackend {
+            cmd_tx: self.cmd_tx.clone(),
+            kill_switch: self.kill_switch.clone(),
+            split_tunnel: self.split_tunnel.clone(),
+            doh_url: dns.doh_url.clone(),
+        });
+        let stub = DnsStub::start(listen, bypass_resolver, backend).await?;
+        *self.dns_stub.write().await = Some(stub);
+        Ok(())
+    }
+
+    /// Block traffic outside the tunnel now
+    pub async fn engage_kill_switch(&self, reason: &str) -> KillSwitchStatus {
+        let mut ks = self.kill_switch.write().await;
+        if let Some(status) = ks.engage(reason) {
+            send_kill_switch_event(&self.event_tx, &status);
+        }
+        ks.status()
+    }
+
+    /// User override: stop blocking until the tunnel is back
+    pub async fn release_kill_switch(&self) -> KillSwitchStatus {
+        let mut ks = self.kill_switch.write().await;
+        if let Some(status) = ks.release() {
+            send_kill_switch_event(&self.event_tx, &status);
+        }
+        ks.status()
+    }
+
+    /// Current split tunneling rules, in match order
+    pub fn split_tunnel_rules(&self) -> Vec<SplitTunnelRule> {
+        self.split_tunnel.read().unwrap_or_else(|e| e.into_inner()).rules.clone()
+    }
+
+    /// Append a split tunneling rule; applies to new proxy connections at once
+    pub async fn add_split_tunnel_rule(&self, matcher: SplitTunnelMatch, action: SplitTunnelAction) -> Result<u64> {
+        let rules = {
+            let mut rules = self.split_tunnel.write().unwrap_or_else(|e| e.into_inner());
+            rules.add(matcher, action).map_err(crate::DaemonError::InvalidRequest)?;
+            rules.clone()
+        };
+        let id = rules.rules.last().map_or(0, |r| r.id);
+        self.save_split_tunnel(rules).await;
+        info!("Split tunnel rule {} added", id);
+        Ok(id)
+    }
+
+    /// Remove a split tunneling rule by id
+    pub async fn remove_split_tunnel_rule(&self, id: u64) -> Result<()> {
+        let rules = {
+            let mut rules = self.split_tunnel.write().unwrap_or_else(|e| e.into_inner());
+            if !rules.remove(id) {
+                return Err(crate::DaemonError::InvalidRequest(format!("Unknown split tunnel rule: {}", id)));
+            }
+            rules.clone()
+        };
+        self.save_split_tunnel(rules).await;
+        info!("Split tunnel rule {} removed", id);
+        Ok(())
+    }
+
+    async fn save_split_tunnel(&self, rules: craftnet_core::SplitTunnelRules) {
+        let mut settings = self.settings.write().await;
+        settings.config.split_tunnel = rules;
+        if let Err(e) = settings.save() {
+            debug!("Failed to save settings: {}", e);
+        }
+    }
+
     /// Get connection history
-    pub async fn get_connection_history(&self) -> Vec<ConnectionHistoryEntry> {
-        self.connection_history.read().await.clone()
+    pub async fn get_connection_history(&self, limit: Option<usize>) -> Vec<ConnectionHistoryEntry> {
+        self.connection_history.read().await.entries(limit)
+    }
+
+    /// Forget all connection history (memory and disk); returns the number
+    /// of entries removed
+    pub async fn clear_connection_history(&self) -> Result<usize> {
+        let cleared = self.connection_history.write().await.clear()?;
+        info!("Cleared {} connection history entries", cleared);
+        Ok(cleared)
+    }
+
+    async fn record_connection(&self, entry: ConnectionHistoryEntry) {
+        if let Err(e) = self.connection_history.write().await.record(entry) {
+            warn!("Failed to write connection history: {}", e);
+        }
     }
 
     /// Get earnings history
@@ -1077,8 +2408,25 @@ impl DaemonService {
         Ok(())
     }
 
-    /// Start the SOCKS5 proxy server
-    pub async fn start_proxy(&self, port: u16) -> Result<()> {
+    /// Fetch the day's free-tier quota tokens from the selected exit if
+    /// due, returning the tokens held for it
+    pub async fn refresh_quota_tokens(&self) -> Result<usize> {
+        let cmd_tx = self.cmd_tx.read().await;
+        let Some(ref tx) = *cmd_tx else {
+            return Err(crate::DaemonError::NotRunning);
+        };
+        let (reply_tx, reply_rx) = oneshot::channel();
+        tx.send(NodeCommand::RefreshQuotaTokens(reply_tx))
+            .await
+            .map_err(|_| crate::DaemonError::SdkError("Node not running".to_string()))?;
+        drop(cmd_tx);
+        reply_rx.await
+            .map_err(|_| crate::DaemonError::SdkError("Node task died".to_string()))?
+            .map_err(crate::DaemonError::SdkError)
+    }
+
+    /// Start the SOCKS5 proxy server
+    pub async fn start_proxy(&self, port: u16) -> Result<()> {
         let cmd_tx = self.cmd_tx.read().await;
         if let Some(ref tx) = *cmd_tx {
             let (reply_tx, reply_rx) = oneshot::channel();
@@ -1128,6 +2476,60 @@ impl DaemonService {
         None
     }
 
+    /// Bring up the full-device TUN interface
+    #[cfg(feature = "tun")]
+    pub async fn start_tun(&self, config: craftnet_client::TunConfig) -> Result<()> {
+        let cmd_tx = self.cmd_tx.read().await;
+        if let Some(ref tx) = *cmd_tx {
+            let (reply_tx, reply_rx) = oneshot::channel();
+            tx.send(NodeCommand::StartTun { config, reply: reply_tx })
+                .await
+                .map_err(|_| crate::DaemonError::SdkError("Node not running".to_string()))?;
+            drop(cmd_tx);
+            reply_rx.await
+                .map_err(|_| crate::DaemonError::SdkError("Node task died".to_string()))?
+                .map_err(crate::DaemonError::SdkError)?;
+        } else {
+            return Err(crate::DaemonError::NotRunning);
+        }
+        Ok(())
+    }
+
+    /// Take down the TUN interface and restore routes/DNS
+    #[cfg(feature = "tun")]
+    pub async fn stop_tun(&self) -> Result<()> {
+        let cmd_tx = self.cmd_tx.read().await;
+        if let Some(ref tx) = *cmd_tx {
+            let (reply_tx, reply_rx) = oneshot::channel();
+            tx.send(NodeCommand::StopTun(reply_tx))
+                .await
+                .map_err(|_| crate::DaemonError::SdkError("Node not running".to_string()))?;
+            drop(cmd_tx);
+            reply_rx.await
+                .map_err(|_| crate::DaemonError::SdkError("Node task died".to_string()))?
+                .map_err(crate::DaemonError::SdkError)?;
+        } else {
+            return Err(crate::DaemonError::NotRunning);
+        }
+        Ok(())
+    }
+
+    /// Get TUN interface status (None = not running)
+    #[cfg(feature = "tun")]
+    pub async fn tun_status(&self) -> Option<craftnet_client::TunStatus> {
+        let cmd_tx = self.cmd_tx.read().await;
+        if let Some(ref tx) = *cmd_tx {
+            let (reply_tx, reply_rx) = oneshot::channel();
+            if tx.send(NodeCommand::GetTunStatus(reply_tx)).await.is_ok() {
+                drop(cmd_tx);
+                if let Ok(status) = reply_rx.await {
+                    return status;
+                }
+            }
+        }
+        None
+    }
+
     /// Export private key (encrypted with Argon2id-derived key + ChaCha20-Poly1305)
     ///
     /// File format: salt (16 bytes) || nonce (12 bytes) || ciphertext (48 bytes)
@@ -1253,10 +2655,92 @@ async fn announce_and_update_status(
     ns.exit_announced_secs_ago = exit_secs;
 }
 
+/// Send a streamed response body as IPC events until it ends.
+///
+/// Each chunk becomes a `response_chunk` event (hex-encoded `data`, running
+/// `seq`), followed by one `response_end` or `response_error`. With a
+/// session channel every send waits for room, so a slow client slows the
+/// stream down instead of losing chunks; the stream is dropped once that
+/// client goes away. Without one the events are broadcast.
+async fn forward_response_stream(
+    stream_id: String,
+    mut stream: ResponseStream,
+    events: Option<mpsc::Sender<String>>,
+    event_tx: broadcast::Sender<String>,
+) {
+    let send = |event: &str, data: serde_json::Value| {
+        let msg = serde_json::json!({"event": event, "data": data}).to_string();
+        let events = events.clone();
+        let event_tx = event_tx.clone();
+        async move {
+            match events {
+                Some(tx) => tx.send(msg).await.is_ok(),
+                None => {
+                    let _ = event_tx.send(msg);
+                    true
+                }
+            }
+        }
+    };
+
+    let mut seq = 0u64;
+    let mut total_bytes = 0u64;
+    loop {
+        match stream.chunk().await {
+            Ok(Some(chunk)) => {
+                total_bytes += chunk.len() as u64;
+                let sent = send("response_chunk", serde_json::json!({
+                    "stream_id": stream_id,
+                    "seq": seq,
+                    "data": hex::encode(&chunk),
+                })).await;
+                if !sent {
+                    debug!("Client of stream {} went away, dropping it", stream_id);
+                    break;
+                }
+                seq += 1;
+            }
+            Ok(None) => {
+                send("response_end", serde_json::json!({
+                    "stream_id": stream_id,
+                    "total_bytes": total_bytes,
+                })).await;
+                break;
+            }
+            Err(e) => {
+                send("response_error", serde_json::json!({
+                    "stream_id": stream_id,
+                    "error": e.to_string(),
+                })).await;
+                break;
+            }
+        }
+    }
+}
+
+/// Sender feeding the node's tunnel sessions, shared by the SOCKS5 proxy
+/// and the TUN interface (a new channel once the node dropped the old one)
+fn tunnel_burst_sender(
+    node: &mut CraftNetNode,
+    burst_tx: &mut Option<mpsc::Sender<TunnelBurst>>,
+) -> mpsc::Sender<TunnelBurst> {
+    if let Some(tx) = burst_tx.as_ref().filter(|tx| !tx.is_closed()) {
+        return tx.clone();
+    }
+    let (tx, rx) = mpsc::channel(256);
+    node.set_tunnel_burst_rx(rx);
+    *burst_tx = Some(tx.clone());
+    tx
+}
+
 async fn run_node_task(
     config: NodeConfig,
     mut cmd_rx: mpsc::Receiver<NodeCommand>,
     status: Arc<RwLock<NodeStatusInfo>>,
+    topology: Arc<RwLock<TopologyCollector>>,
+    event_tx: broadcast::Sender<String>,
+    split_tunnel: SharedSplitTunnelRules,
+    kill_switch: Arc<RwLock<KillSwitch>>,
     mut swarm_handles: Option<craftnet_client::SwarmHandles>,
 ) -> std::result::Result<(), String> {
     let mut node = CraftNetNode::new(config)
@@ -1264,6 +2748,11 @@ async fn run_node_task(
 
     // SOCKS5 proxy state (created on StartProxy, dropped on StopProxy)
     let mut socks5_server: Option<Socks5Server> = None;
+    // Full-device interface (created on StartTun, dropped on StopTun/Disconnect)
+    #[cfg(feature = "tun")]
+    let mut tun_device: Option<craftnet_client::TunDevice> = None;
+    // Feeds the node's tunnel sessions; shared by the proxy and the TUN interface
+    let mut burst_tx: Option<mpsc::Sender<TunnelBurst>> = None;
 
     // Join the network immediately — don't wait for NodeCommand::Connect.
     // This means relay/exit nodes announce themselves in Tunnel Ready state,
@@ -1282,10 +2771,109 @@ async fn run_node_task(
 
     info!("CraftNetNode event loop running");
 
+    let mut topology_tick = tokio::time::interval(TOPOLOGY_REFRESH_INTERVAL);
+    let mut kill_switch_tick = tokio::time::interval(KILL_SWITCH_CHECK_INTERVAL);
+    let mut quota_tick = tokio::time::interval(QUOTA_REFRESH_INTERVAL);
+    let mut exit_probe_tick = tokio::time::interval(EXIT_PROBE_CHECK_INTERVAL);
+
+    // Whether the user asked for the tunnel to be up (Connect without a later Disconnect)
+    let mut tunnel_wanted = false;
+
+    // Set while draining: answered (and the task exits) once the node is drained
+    let mut drain_reply: Option<oneshot::Sender<DrainStatus>> = None;
+
     loop {
         tokio::select! {
             // Drive the swarm event loop continuously (peer discovery, DHT, gossipsub)
-            _ = node.poll_once() => {}
+            _ = node.poll_once() => {
+                // Forward distribution proof job progress to IPC subscribers
+                for event in node.take_proof_job_events() {
+                    let data = serde_json::to_value(&event).unwrap_or_default();
+                    let msg = serde_json::json!({"event": "proof_job", "data": data});
+                    let _ = event_tx.send(msg.to_string());
+                }
+                // Forward distribution challenges and their outcomes
+                for event in node.take_dispute_events() {
+                    let data = serde_json::to_value(&event).unwrap_or_default();
+                    let msg = serde_json::json!({"event": "dispute", "data": data});
+                    let _ = event_tx.send(msg.to_string());
+                }
+                // Forward exits caught sending badly signed responses
+                for event in node.take_exit_tamper_events() {
+                    let data = serde_json::to_value(&event).unwrap_or_default();
+                    let msg = serde_json::json!({"event": "exit_tampered", "data": data});
+                    let _ = event_tx.send(msg.to_string());
+                }
+                // Forward exit switches caused by failing health checks
+                for event in node.take_exit_failover_events() {
+                    let data = serde_json::to_value(&event).unwrap_or_default();
+                    let msg = serde_json::json!({"event": "exit_failover", "data": data});
+                    let _ = event_tx.send(msg.to_string());
+                }
+                // Forward reconnect progress (lost / reconnecting / reconnected)
+                for event in node.take_reconnect_events() {
+                    let data = serde_json::to_value(&event).unwrap_or_default();
+                    let msg = serde_json::json!({"event": "reconnect", "data": data});
+                    let _ = event_tx.send(msg.to_string());
+                }
+
+                if drain_reply.is_some() && node.is_drained() {
+                    let drain_status = node.drain_status();
+                    if let Some(mut server) = socks5_server.take() {
+                        server.stop();
+                    }
+                    #[cfg(feature = "tun")]
+                    if let Some(mut tun) = tun_device.take() {
+                        tun.stop();
+                    }
+                    node.stop().await;
+                    let mut ns = status.write().await;
+                    ns.connected = false;
+                    ns.peer_count = 0;
+                    drop(ns);
+                    if let Some(reply) = drain_reply.take() {
+                        let _ = reply.send(drain_status);
+                    }
+                    info!("Node drained, shutting down node task");
+                    break;
+                }
+            }
+
+            // Keep the topology graph live between IPC queries
+            _ = topology_tick.tick() => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+                topology.write().await.ingest(&node.topology_snapshot(), now);
+                // Keep newly learned peers routed around the TUN interface
+                #[cfg(feature = "tun")]
+                if let Some(ref mut tun) = tun_device {
+                    tun.sync_bypass(node.peer_ip_addrs());
+                }
+            }
+
+            // Engage the kill switch if the tunnel drops while it should be up
+            _ = kill_switch_tick.tick(), if tunnel_wanted => {
+                let transition = if node.is_connected() {
+                    kill_switch.write().await.on_connected()
+                } else {
+                    kill_switch.write().await.on_unexpected_disconnect("lost all peers")
+                };
+                if let Some(ks) = transition {
+                    warn!("Kill switch {:?}", ks.state);
+                    send_kill_switch_event(&event_tx, &ks);
+                }
+            }
+
+            // Keep a batch of free-tier quota tokens while the tunnel is up
+            _ = quota_tick.tick(), if tunnel_wanted => {
+                if let Err(e) = node.refresh_quota_tokens().await {
+                    debug!("Quota token refresh failed: {}", e);
+                }
+            }
+
+            // Probe the selected exit when it has been idle
+            _ = exit_probe_tick.tick(), if tunnel_wanted => {
+                node.probe_selected_exit().await;
+            }
 
             // Handle commands from the daemon service
             cmd = cmd_rx.recv() => {
@@ -1313,28 +2901,61 @@ async fn run_node_task(
                         ns.peer_count = node_status.peer_count;
                         ns.shards_relayed = node_status.stats.shards_relayed;
                         ns.requests_exited = node_status.stats.requests_exited;
+                        drop(ns);
+                        tunnel_wanted = true;
+                        if let Some(ks) = kill_switch.write().await.on_connected() {
+                            send_kill_switch_event(&event_tx, &ks);
+                        }
                         let _ = reply.send(Ok(()));
                     }
                     Some(NodeCommand::Disconnect(reply)) => {
+                        tunnel_wanted = false;
+                        if let Some(ks) = kill_switch.write().await.on_user_disconnect() {
+                            send_kill_switch_event(&event_tx, &ks);
+                        }
+                        #[cfg(feature = "tun")]
+                        if let Some(mut tun) = tun_device.take() {
+                            tun.stop();
+                        }
                         node.stop().await;
                         let mut ns = status.write().await;
                         ns.connected = false;
                         ns.peer_count = 0;
                         let _ = reply.send(Ok(()));
                     }
-                    Some(NodeCommand::Request { method, url, body, headers, reply }) => {
-                        // Convert HashMap headers to Vec<(String, String)> for node.fetch()
+                    Some(NodeCommand::Request { method, url, body, headers, options, reply }) => {
+                        // Convert HashMap headers to Vec<(String, String)> for node.fetch_with()
                         let header_vec = headers.map(|h| {
                             h.into_iter().collect::<Vec<(String, String)>>()
                         });
-                        let result = node.fetch(
+                        let result = node.fetch_with(
                             &method.to_uppercase(),
                             &url,
                             body,
                             header_vec,
+                            options,
                         ).await;
                         let _ = reply.send(result.map_err(|e| e.to_string()));
                     }
+                    Some(NodeCommand::RequestStream { stream_id, events, method, url, body, headers, reply }) => {
+                        let header_vec = headers.map(|h| {
+                            h.into_iter().collect::<Vec<(String, String)>>()
+                        });
+                        match node.fetch_stream(&method.to_uppercase(), &url, body, header_vec).await {
+                            Ok(stream) => {
+                                let head = StreamHead {
+                                    stream_id: stream_id.clone(),
+                                    status: stream.status,
+                                    headers: stream.headers.clone(),
+                                };
+                                tokio::spawn(forward_response_stream(stream_id, stream, events, event_tx.clone()));
+                                let _ = reply.send(Ok(head));
+                            }
+                            Err(e) => {
+                                let _ = reply.send(Err(e.to_string()));
+                            }
+                        }
+                    }
                     Some(NodeCommand::GetStatus(reply)) => {
                         let node_status = node.status();
                         let (relay_secs, exit_secs) = node.announce_timing();
@@ -1347,6 +2968,8 @@ async fn run_node_task(
                             requests_exited: node_status.stats.requests_exited,
                             relay_announced_secs_ago: relay_secs,
                             exit_announced_secs_ago: exit_secs,
+                            exit: node.selected_exit().map(|e| hex::encode(e.pubkey)),
+                            exit_region: node.selected_exit().map(|e| e.region.code().to_string()),
                         });
                     }
                     Some(NodeCommand::GetStats(reply)) => {
@@ -1372,6 +2995,12 @@ async fn run_node_task(
                         node.set_local_discovery(enabled);
                         let _ = reply.send(Ok(()));
                     }
+                    Some(NodeCommand::SetAggregatorPools(pools, reply)) => {
+                        let _ = reply.send(node.set_aggregator_pools(pools));
+                    }
+                    Some(NodeCommand::GetDistributionBatchProof { pool_key, relays, reply }) => {
+                        let _ = reply.send(node.aggregator_batch_proof(&pool_key, &relays).map(Into::into));
+                    }
                     Some(NodeCommand::GetAvailableExits(reply)) => {
                         // Trigger a fresh DHT discovery on every poll (throttled internally).
                         // This means the UI polling at ~5s intervals continuously refreshes exits.
@@ -1391,6 +3020,7 @@ async fn run_node_task(
                                     score: node.exit_score(&e.pubkey).unwrap_or(50),
                                     load: node.exit_load(&e.pubkey).unwrap_or(0),
                                     latency_ms,
+                                    peer_id: node.bound_peer_id(&e.pubkey).map(|p| p.to_string()),
                                 }
                             })
                             .collect();
@@ -1401,6 +3031,7 @@ async fn run_node_task(
                             .into_iter()
                             .map(|p| PeerSummary {
                                 peer_id: p.peer_id,
+                                binding_verified: p.binding_verified,
                                 role: p.role,
                                 online: p.online,
                                 score: p.score,
@@ -1443,6 +3074,33 @@ async fn run_node_task(
                         node.set_bandwidth_limit(limit);
                         let _ = reply.send(Ok(()));
                     }
+                    Some(NodeCommand::RefreshQuotaTokens(reply)) => {
+                        let result = node.refresh_quota_tokens().await;
+                        let _ = reply.send(result.map_err(|e| e.to_string()));
+                    }
+                    Some(NodeCommand::GetCreditLedger { limit, reply }) => {
+                        let _ = reply.send(CreditLedgerResponse::new(node.credit_ledger(), limit));
+                    }
+                    Some(NodeCommand::GetExitAbuse { limit, reply }) => {
+                        let _ = reply.send(node.exit_abuse().report(limit).into());
+                    }
+                    Some(NodeCommand::SetExitBlock { target, block, reply }) => {
+                        let abuse = node.exit_abuse();
+                        let changed = if block { abuse.block(target) } else { abuse.unblock(target) };
+                        let _ = reply.send(changed);
+                    }
+                    Some(NodeCommand::GetThroughput { window_secs, reply }) => {
+                        let _ = reply.send(node.throughput_series(window_secs));
+                    }
+                    Some(NodeCommand::ListPeers(reply)) => {
+                        let _ = reply.send(node.peer_connections());
+                    }
+                    Some(NodeCommand::GetDhtHandle(reply)) => {
+                        let _ = reply.send(node.dht_handle());
+                    }
+                    Some(NodeCommand::GetBuildAttestation(reply)) => {
+                        let _ = reply.send(node.build_attestation());
+                    }
                     Some(NodeCommand::SetCredits(credits)) => {
                         node.set_credits(credits);
                         status.write().await.credits = credits;
@@ -1455,10 +3113,10 @@ async fn run_node_task(
                         }
 
                         let addr: std::net::SocketAddr = ([127, 0, 0, 1], port).into();
-                        let (burst_tx, burst_rx) = tokio::sync::mpsc::channel(256);
-                        node.set_tunnel_burst_rx(burst_rx);
+                        let tx = tunnel_burst_sender(&mut node, &mut burst_tx);
 
-                        let mut server = Socks5Server::new(addr, burst_tx);
+                        let mut server = Socks5Server::new(addr, tx)
+                            .with_split_tunnel(split_tunnel.clone());
                         match server.start().await {
                             Ok(()) => {
                                 info!("SOCKS5 proxy started on port {}", server.listen_addr().port());
@@ -1479,6 +3137,38 @@ async fn run_node_task(
                             let _ = reply.send(Err("Proxy not running".to_string()));
                         }
                     }
+                    #[cfg(feature = "tun")]
+                    Some(NodeCommand::StartTun { config, reply }) => {
+                        if let Some(mut existing) = tun_device.take() {
+                            existing.stop();
+                        }
+
+                        let tx = tunnel_burst_sender(&mut node, &mut burst_tx);
+                        let mut tun = craftnet_client::TunDevice::new(config, tx);
+                        match tun.start(node.peer_ip_addrs()).await {
+                            Ok(()) => {
+                                info!("TUN interface {} carrying device traffic", tun.status().interface);
+                                tun_device = Some(tun);
+                                let _ = reply.send(Ok(()));
+                            }
+                            Err(e) => {
+                                let _ = reply.send(Err(format!("Failed to start TUN interface: {}", e)));
+                            }
+                        }
+                    }
+                    #[cfg(feature = "tun")]
+                    Some(NodeCommand::StopTun(reply)) => {
+                        if let Some(mut tun) = tun_device.take() {
+                            tun.stop();
+                            let _ = reply.send(Ok(()));
+                        } else {
+                            let _ = reply.send(Err("TUN interface not running".to_string()));
+                        }
+                    }
+                    #[cfg(feature = "tun")]
+                    Some(NodeCommand::GetTunStatus(reply)) => {
+                        let _ = reply.send(tun_device.as_ref().map(|t| t.status()));
+                    }
                     Some(NodeCommand::GetProxyStatus(reply)) => {
                         let status_info = socks5_server.as_ref().map(|s| ProxyStatusInfo {
                             listening: true,
@@ -1486,6 +3176,38 @@ async fn run_node_task(
                         });
                         let _ = reply.send(status_info);
                     }
+                    Some(NodeCommand::GetTopology(reply)) => {
+                        let _ = reply.send(node.topology_snapshot());
+                    }
+                    Some(NodeCommand::Drain { timeout, reply }) => {
+                        node.begin_drain(timeout);
+                        let data = serde_json::to_value(DrainResponse::from(node.drain_status())).unwrap_or_default();
+                        let msg = serde_json::json!({"event": "draining", "data": data});
+                        let _ = event_tx.send(msg.to_string());
+                        drain_reply = Some(reply);
+                    }
+                    Some(NodeCommand::SetAuditLog(config, reply)) => {
+                        node.set_audit_log(config);
+                        let _ = reply.send(Ok(()));
+                    }
+                    Some(NodeCommand::SetCookieJar(config, reply)) => {
+                        node.set_cookie_jar(config);
+                        let _ = reply.send(Ok(()));
+                    }
+                    Some(NodeCommand::ClearSessionData(identity, reply)) => {
+                        node.clear_session_data(identity.as_deref());
+                        let _ = reply.send(Ok(()));
+                    }
+                    Some(NodeCommand::GetHealth(reply)) => {
+                        let _ = reply.send(NodeHealth {
+                            peer_id: node.local_peer_id().map(|p| p.to_string()),
+                            peer_count: node.status().peer_count,
+                            bootstrapped: node.is_bootstrapped(),
+                            traffic: node.traffic_totals(),
+                            traffic_peers: node.peer_traffic().len(),
+                            freeloaders: node.freeloaders().len(),
+                        });
+                    }
                     None => {
                         info!("Command channel closed, shutting down node task");
                         break;
@@ -1534,7 +3256,7 @@ impl IpcHandler for DaemonService {
                 "status" => {
                     let status = self.status().await;
                     serde_json::to_value(status)
-                        .map_err(|e| format!("Serialize error: {}", e))
+                        .map_err(|e| coded_error(ErrorCode::Internal, format!("Serialize error: {}", e)))
                 }
 
                 "connect" => {
@@ -1543,17 +3265,18 @@ impl IpcHandler for DaemonService {
                         .unwrap_or_default();
 
                     self.connect(params.clone()).await
-                        .map_err(|e| format!("Connect error: {}", e))?;
+                        .map_err(|e| coded_error(e.code(), format!("Connect error: {}", e)))?;
 
                     Ok(serde_json::json!({
                         "connected": true,
-                        "hops": params.hops
+                        "hops": params.hops,
+                        "full_device": params.full_device
                     }))
                 }
 
                 "disconnect" => {
                     self.disconnect().await
-                        .map_err(|e| format!("Disconnect error: {}", e))?;
+                        .map_err(|e| coded_error(e.code(), format!("Disconnect error: {}", e)))?;
 
                     Ok(serde_json::json!({"success": true}))
                 }
@@ -1575,225 +3298,901 @@ impl IpcHandler for DaemonService {
 
                     let amount = params.amount.unwrap_or(100);
                     let balance = self.purchase_credits(amount).await
-                        .map_err(|e| format!("Purchase error: {}", e))?;
+                        .map_err(|e| coded_error(e.code(), format!("Purchase error: {}", e)))?;
+
+                    Ok(serde_json::json!({"success": true, "balance": balance}))
+                }
+
+                "set_privacy_level" => {
+                    #[derive(Deserialize)]
+                    struct PrivacyParams {
+                        level: String,
+                    }
+
+                    let params: PrivacyParams = params
+                        .ok_or_else(|| coded_error(ErrorCode::InvalidRequest, "Missing params"))
+                        .and_then(|p| serde_json::from_value(p)
+                            .map_err(|e| coded_error(ErrorCode::InvalidRequest, format!("Invalid params: {}", e))))?;
+
+                    self.set_privacy_level(&params.level).await
+                        .map_err(|e| coded_error(e.code(), format!("Set privacy level error: {}", e)))?;
+
+                    Ok(serde_json::json!({"success": true, "level": params.level}))
+                }
+
+                "set_mode" => {
+                    #[derive(Deserialize)]
+                    struct ModeParams {
+                        mode: String,
+                    }
+
+                    let params: ModeParams = params
+                        .ok_or_else(|| coded_error(ErrorCode::InvalidRequest, "Missing params"))
+                        .and_then(|p| serde_json::from_value(p)
+                            .map_err(|e| coded_error(ErrorCode::InvalidRequest, format!("Invalid params: {}", e))))?;
+
+                    self.set_mode(&params.mode).await
+                        .map_err(|e| coded_error(e.code(), format!("Set mode error: {}", e)))?;
+
+                    Ok(serde_json::json!({"success": true, "mode": params.mode}))
+                }
+
+                "get_node_stats" => {
+                    match self.get_node_stats().await {
+                        Some(stats) => serde_json::to_value(stats)
+                            .map_err(|e| coded_error(ErrorCode::Internal, format!("Serialize error: {}", e))),
+                        None => Ok(serde_json::json!({})),
+                    }
+                }
+
+                "get_credit_ledger" => {
+                    #[derive(Deserialize)]
+                    struct LedgerParams {
+                        limit: Option<usize>,
+                    }
+
+                    let limit = params
+                        .and_then(|p| serde_json::from_value::<LedgerParams>(p).ok())
+                        .and_then(|p| p.limit)
+                        .unwrap_or(100);
+                    match self.get_credit_ledger(limit).await {
+                        Some(ledger) => serde_json::to_value(ledger)
+                            .map_err(|e| coded_error(ErrorCode::Internal, format!("Serialize error: {}", e))),
+                        None => Ok(serde_json::json!({})),
+                    }
+                }
+
+                "get_exit_abuse" => {
+                    #[derive(Deserialize)]
+                    struct AbuseParams {
+                        limit: Option<usize>,
+                    }
+
+                    let limit = params
+                        .and_then(|p| serde_json::from_value::<AbuseParams>(p).ok())
+                        .and_then(|p| p.limit)
+                        .unwrap_or(50);
+                    match self.get_exit_abuse(limit).await {
+                        Some(report) => serde_json::to_value(report)
+                            .map_err(|e| coded_error(ErrorCode::Internal, format!("Serialize error: {}", e))),
+                        None => Ok(serde_json::json!({})),
+                    }
+                }
+
+                "exit_block" | "exit_unblock" => {
+                    #[derive(Deserialize)]
+                    struct BlockParams {
+                        /// Hostname or destination hash (hex)
+                        destination: Option<String>,
+                        /// Pool pubkey (hex)
+                        pool: Option<String>,
+                    }
+
+                    let params: BlockParams = params
+                        .ok_or_else(|| coded_error(ErrorCode::InvalidRequest, "Missing params"))
+                        .and_then(|p| serde_json::from_value(p)
+                            .map_err(|e| coded_error(ErrorCode::InvalidRequest, format!("Invalid params: {}", e))))?;
+                    let target = match (params.destination, params.pool) {
+                        (Some(destination), None) => BlockTarget::Destination(craftnet_client::parse_destination(&destination)),
+                        (None, Some(pool)) => hex::decode(&pool).ok()
+                            .and_then(|b| <[u8; 32]>::try_from(b).ok())
+                            .map(BlockTarget::Pool)
+                            .ok_or_else(|| coded_error(ErrorCode::InvalidRequest, "pool must be a 32-byte hex pubkey"))?,
+                        _ => return Err(coded_error(ErrorCode::InvalidRequest, "Give either destination or pool")),
+                    };
+                    let block = method == "exit_block";
+                    let changed = self.set_exit_block(target, block).await
+                        .map_err(|e| coded_error(e.code(), format!("Exit block error: {}", e)))?;
+                    Ok(serde_json::json!({"changed": changed}))
+                }
+
+                "get_throughput_series" => {
+                    #[derive(Deserialize)]
+                    struct ThroughputParams {
+                        window_secs: Option<u64>,
+                    }
+
+                    let window_secs = params
+                        .and_then(|p| serde_json::from_value::<ThroughputParams>(p).ok())
+                        .and_then(|p| p.window_secs)
+                        .unwrap_or(60);
+                    let samples = self.get_throughput_series(window_secs).await;
+                    Ok(serde_json::json!({"window_secs": window_secs, "samples": samples}))
+                }
+
+                "list_peers" => {
+                    let peers = self.list_peers().await;
+                    Ok(serde_json::json!({"peers": peers}))
+                }
+
+                "get_recent_logs" => {
+                    #[derive(Deserialize)]
+                    struct LogParams {
+                        since: Option<u64>,
+                        limit: Option<usize>,
+                    }
+
+                    let params = params.and_then(|p| serde_json::from_value::<LogParams>(p).ok());
+                    let since = params.as_ref().and_then(|p| p.since);
+                    let limit = params.and_then(|p| p.limit).unwrap_or(100).min(MAX_LOG_LINES);
+                    Ok(serde_json::json!({"lines": recent_logs().recent(since, limit)}))
+                }
+
+                "get_build_attestation" => {
+                    let response = self.build_attestation().await;
+                    serde_json::to_value(response).map_err(|e| coded_error(ErrorCode::Internal, format!("Serialize error: {}", e)))
+                }
+
+                "request" => {
+                    #[derive(Deserialize)]
+                    struct RequestParams {
+                        method: String,
+                        url: String,
+                        body: Option<String>,
+                        #[serde(default)]
+                        headers: Option<std::collections::HashMap<String, String>>,
+                        /// Privacy level for this request only (`direct` ... `quad`)
+                        hop_mode: Option<String>,
+                        /// Exit signing pubkey (hex) for this request only
+                        exit: Option<String>,
+                        /// Idle timeout for this request only
+                        timeout_ms: Option<u64>,
+                        /// Return per-hop timings (diagnostic requests only)
+                        #[serde(default)]
+                        trace: bool,
+                    }
+
+                    let params: RequestParams = params
+                        .ok_or_else(|| coded_error(ErrorCode::InvalidRequest, "Missing params"))
+                        .and_then(|p| serde_json::from_value(p).map_err(|e| coded_error(ErrorCode::InvalidRequest, format!("Invalid params: {}", e))))?;
+
+                    let hop_mode = params.hop_mode.as_deref()
+                        .map(parse_privacy_level)
+                        .transpose()
+                        .map_err(|e| coded_error(e.code(), e.to_string()))?;
+                    let exit = params.exit.as_deref()
+                        .map(|hex_key| hex::decode(hex_key).ok().and_then(|b| <[u8; 32]>::try_from(b).ok())
+                            .ok_or_else(|| coded_error(ErrorCode::InvalidRequest, "exit must be a 32-byte hex pubkey")))
+                        .transpose()?;
+                    let options = RequestOptions {
+                        hop_mode,
+                        exit,
+                        timeout: params.timeout_ms.map(std::time::Duration::from_millis),
+                        trace: params.trace,
+                    };
+                    let body_bytes = params.body.map(|b| b.into_bytes());
+
+                    let response = self.request(&params.method, &params.url, body_bytes, params.headers, options).await
+                        .map_err(|e| coded_error(e.code(), format!("Request error: {}", e)))?;
+
+                    let mut result = serde_json::json!({
+                        "status": response.status,
+                        "headers": response.headers,
+                        "body": String::from_utf8_lossy(&response.body),
+                        "exit_failovers": response.exit_failovers,
+                    });
+                    if params.trace {
+                        result["hop_timings"] = serde_json::json!(response.hop_timings);
+                    }
+                    Ok(result)
+                }
+
+                "request_stream" => {
+                    #[derive(Deserialize)]
+                    struct RequestStreamParams {
+                        method: String,
+                        url: String,
+                        body: Option<String>,
+                        #[serde(default)]
+                        headers: Option<std::collections::HashMap<String, String>>,
+                        /// Caller-chosen id, so events that race the reply can be matched
+                        stream_id: Option<String>,
+                    }
+
+                    let params: RequestStreamParams = params
+                        .ok_or_else(|| coded_error(ErrorCode::InvalidRequest, "Missing params"))
+                        .and_then(|p| serde_json::from_value(p).map_err(|e| coded_error(ErrorCode::InvalidRequest, format!("Invalid params: {}", e))))?;
+
+                    let stream_id = params.stream_id
+                        .unwrap_or_else(|| hex::encode(rand::random::<[u8; 8]>()));
+                    let body_bytes = params.body.map(|b| b.into_bytes());
+
+                    let events = self.stream_events(&stream_id);
+                    let head = self.request_stream(stream_id, events, &params.method, &params.url, body_bytes, params.headers).await
+                        .map_err(|e| coded_error(e.code(), format!("Request error: {}", e)))?;
+
+                    serde_json::to_value(head).map_err(|e| coded_error(ErrorCode::Internal, format!("Serialize error: {}", e)))
+                }
+
+                "set_exit_node" => {
+                    #[derive(Deserialize)]
+                    struct ExitNodeParams {
+                        region: String,
+                        country_code: Option<String>,
+                        city: Option<String>,
+                    }
+
+                    let params: ExitNodeParams = params
+                        .ok_or_else(|| coded_error(ErrorCode::InvalidRequest, "Missing params"))
+                        .and_then(|p| serde_json::from_value(p)
+                            .map_err(|e| coded_error(ErrorCode::InvalidRequest, format!("Invalid params: {}", e))))?;
+
+                    self.set_exit_node(&params.region, params.country_code, params.city).await
+                        .map_err(|e| coded_error(e.code(), format!("Set exit node error: {}", e)))?;
+
+                    Ok(serde_json::json!({"success": true, "region": params.region}))
+                }
+
+                "get_available_exits" => {
+                    let exits = self.get_available_exits().await;
+                    Ok(serde_json::json!({"exits": exits}))
+                }
+
+                "set_local_discovery" => {
+                    #[derive(Deserialize)]
+                    struct LocalDiscoveryParams {
+                        enabled: bool,
+                    }
+
+                    let params: LocalDiscoveryParams = params
+                        .ok_or_else(|| coded_error(ErrorCode::InvalidRequest, "Missing params"))
+                        .and_then(|p| serde_json::from_value(p)
+                            .map_err(|e| coded_error(ErrorCode::InvalidRequest, format!("Invalid params: {}", e))))?;
+
+                    self.set_local_discovery(params.enabled).await
+                        .map_err(|e| coded_error(e.code(), format!("Set local discovery error: {}", e)))?;
+
+                    Ok(serde_json::json!({"success": true, "enabled": params.enabled}))
+                }
+
+                "set_aggregator_pools" => {
+                    #[derive(Deserialize)]
+                    struct AggregatorPoolsParams {
+                        /// "all", "own" or "only"
+                        mode: String,
+                        #[serde(default)]
+                        pools: Vec<String>,
+                    }
+
+                    let params: AggregatorPoolsParams = params
+                        .ok_or_else(|| coded_error(ErrorCode::InvalidRequest, "Missing params"))
+                        .and_then(|p| serde_json::from_value(p)
+                            .map_err(|e| coded_error(ErrorCode::InvalidRequest, format!("Invalid params: {}", e))))?;
+
+                    let pools = match params.mode.as_str() {
+                        "all" => AggregatorPools::All,
+                        "own" => AggregatorPools::Own,
+                        "only" => AggregatorPools::Only(params.pools.iter()
+                            .map(|hex_key| hex::decode(hex_key).ok().and_then(|b| <[u8; 32]>::try_from(b).ok())
+                                .ok_or_else(|| coded_error(ErrorCode::InvalidRequest, "pools must be 32-byte hex pubkeys")))
+                            .collect::<std::result::Result<_, _>>()?),
+                        other => return Err(coded_error(ErrorCode::InvalidRequest, format!("Unknown pool mode: {}", other))),
+                    };
+                    let resynced = self.set_aggregator_pools(pools).await
+                        .map_err(|e| coded_error(e.code(), format!("Set aggregator pools error: {}", e)))?;
+
+                    Ok(serde_json::json!({"success": true, "mode": params.mode, "resynced": resynced}))
+                }
+
+                "get_distribution_batch_proof" => {
+                    #[derive(Deserialize)]
+                    struct BatchProofParams {
+                        /// Pool pubkey (hex)
+                        pool: String,
+                        /// "subscribed" or "free"
+                        #[serde(default = "default_pool_type")]
+                        pool_type: String,
+                        /// Relay pubkeys (hex)
+                        relays: Vec<String>,
+                    }
+                    fn default_pool_type() -> String {
+                        "subscribed".to_string()
+                    }
+
+                    let params: BatchProofParams = params
+                        .ok_or_else(|| coded_error(ErrorCode::InvalidRequest, "Missing params"))
+                        .and_then(|p| serde_json::from_value(p)
+                            .map_err(|e| coded_error(ErrorCode::InvalidRequest, format!("Invalid params: {}", e))))?;
+
+                    let pubkey = |hex_key: &str| hex::decode(hex_key).ok().and_then(|b| <[u8; 32]>::try_from(b).ok());
+                    let pool = pubkey(&params.pool)
+                        .ok_or_else(|| coded_error(ErrorCode::InvalidRequest, "pool must be a 32-byte hex pubkey"))?;
+                    let pool_type = match params.pool_type.as_str() {
+                        "subscribed" => PoolType::Subscribed,
+                        "free" => PoolType::Free,
+                        other => return Err(coded_error(ErrorCode::InvalidRequest, format!("Unknown pool type: {}", other))),
+                    };
+                    let relays = params.relays.iter()
+                        .map(|relay| pubkey(relay)
+                            .ok_or_else(|| coded_error(ErrorCode::InvalidRequest, "relays must be 32-byte hex pubkeys")))
+                        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+                    let proof = self.get_distribution_batch_proof((pool, pool_type), relays).await
+                        .map_err(|e| coded_error(e.code(), format!("Batch proof error: {}", e)))?;
+                    Ok(serde_json::json!({"proof": proof}))
+                }
+
+                "get_connection_history" => {
+                    #[derive(Deserialize, Default)]
+                    struct HistoryParams {
+                        limit: Option<usize>,
+                    }
+
+                    let params: HistoryParams = params
+                        .map(|p| serde_json::from_value(p).unwrap_or_default())
+                        .unwrap_or_default();
+
+                    let entries = self.get_connection_history(params.limit).await;
+                    Ok(serde_json::json!({"entries": entries}))
+                }
+
+                "clear_connection_history" => {
+                    let cleared = self.clear_connection_history().await
+                        .map_err(|e| coded_error(e.code(), format!("Clear history error: {}", e)))?;
+                    Ok(serde_json::json!({"success": true, "cleared": cleared}))
+                }
+
+                "get_earnings_history" => {
+                    let entries = self.get_earnings_history().await;
+                    let pools = self.get_node_stats().await
+                        .map(|stats| stats.pool_claims)
+                        .unwrap_or_default();
+                    Ok(serde_json::json!({"entries": entries, "pools": pools}))
+                }
+
+                "run_speed_test" => {
+                    let result = self.run_speed_test().await;
+                    Ok(serde_json::json!({"result": result}))
+                }
+
+                "set_bandwidth_limit" => {
+                    #[derive(Deserialize)]
+                    struct BandwidthParams {
+                        limit_kbps: Option<u64>,
+                    }
+
+                    let params: BandwidthParams = params
+                        .ok_or_else(|| coded_error(ErrorCode::InvalidRequest, "Missing params"))
+                        .and_then(|p| serde_json::from_value(p)
+                            .map_err(|e| coded_error(ErrorCode::InvalidRequest, format!("Invalid params: {}", e))))?;
+
+                    self.set_bandwidth_limit(params.limit_kbps).await
+                        .map_err(|e| coded_error(e.code(), format!("Set bandwidth limit error: {}", e)))?;
+
+                    Ok(serde_json::json!({"success": true, "limit_kbps": params.limit_kbps}))
+                }
+
+                "export_key" => {
+                    #[derive(Deserialize)]
+                    struct ExportParams {
+                        path: String,
+                        password: String,
+                    }
+
+                    let params: ExportParams = params
+                        .ok_or_else(|| coded_error(ErrorCode::InvalidRequest, "Missing params"))
+                        .and_then(|p| serde_json::from_value(p)
+                            .map_err(|e| coded_error(ErrorCode::InvalidRequest, format!("Invalid params: {}", e))))?;
+
+                    let (path, public_key) = self.export_key(&params.path, &params.password).await
+                        .map_err(|e| coded_error(e.code(), format!("Export key error: {}", e)))?;
+
+                    Ok(serde_json::json!({"path": path, "public_key": public_key}))
+                }
+
+                "import_key" => {
+                    #[derive(Deserialize)]
+                    struct ImportParams {
+                        path: String,
+                        password: String,
+                    }
+
+                    let params: ImportParams = params
+                        .ok_or_else(|| coded_error(ErrorCode::InvalidRequest, "Missing params"))
+                        .and_then(|p| serde_json::from_value(p)
+                            .map_err(|e| coded_error(ErrorCode::InvalidRequest, format!("Invalid params: {}", e))))?;
+
+                    let public_key = self.import_key(&params.path, &params.password).await
+                        .map_err(|e| coded_error(e.code(), format!("Import key error: {}", e)))?;
+
+                    Ok(serde_json::json!({"public_key": public_key}))
+                }
+
+                "refresh_quota_tokens" => {
+                    let tokens = self.refresh_quota_tokens().await
+                        .map_err(|e| coded_error(e.code(), format!("Quota refresh error: {}", e)))?;
+                    Ok(serde_json::json!({"tokens": tokens}))
+                }
+
+                "start_proxy" => {
+                    #[derive(Deserialize)]
+                    struct ProxyParams {
+                        port: Option<u16>,
+                    }
+
+                    let params: ProxyParams = params
+                        .map(|p| serde_json::from_value(p).unwrap_or(ProxyParams { port: None }))
+                        .unwrap_or(ProxyParams { port: None });
+
+                    let port = params.port.unwrap_or(1080);
+                    self.start_proxy(port).await
+                        .map_err(|e| coded_error(e.code(), format!("Start proxy error: {}", e)))?;
+
+                    Ok(serde_json::json!({"success": true, "port": port}))
+                }
+
+                "stop_proxy" => {
+                    self.stop_proxy().await
+                        .map_err(|e| coded_error(e.code(), format!("Stop proxy error: {}", e)))?;
+
+                    Ok(serde_json::json!({"success": true}))
+                }
+
+                #[cfg(feature = "tun")]
+                "start_tun" => {
+                    let config: craftnet_client::TunConfig = params
+                        .map(serde_json::from_value)
+                        .transpose()
+                        .map_err(|e| coded_error(ErrorCode::InvalidRequest, format!("Invalid params: {}", e)))?
+                        .unwrap_or_default();
+                    self.start_tun(config).await
+                        .map_err(|e| coded_error(e.code(), format!("Start TUN error: {}", e)))?;
+
+                    Ok(serde_json::json!({"success": true}))
+                }
+
+                #[cfg(feature = "tun")]
+                "stop_tun" => {
+                    self.stop_tun().await
+                        .map_err(|e| coded_error(e.code(), format!("Stop TUN error: {}", e)))?;
+
+                    Ok(serde_json::json!({"success": true}))
+                }
+
+                #[cfg(feature = "tun")]
+                "tun_status" => {
+                    match self.tun_status().await {
+                        Some(s) => serde_json::to_value(s)
+                            .map_err(|e| coded_error(ErrorCode::Internal, format!("Serialize error: {}", e))),
+                        None => Ok(serde_json::json!({"running": false})),
+                    }
+                }
+
+                #[cfg(not(feature = "tun"))]
+                "start_tun" | "stop_tun" => Err(coded_error(ErrorCode::InvalidRequest, TUN_UNSUPPORTED)),
+
+                #[cfg(not(feature = "tun"))]
+                "tun_status" => Ok(serde_json::json!({"running": false})),
+
+                "proxy_status" => {
+                    let status = self.proxy_status().await;
+                    match status {
+                        Some(s) => serde_json::to_value(s)
+                            .map_err(|e| coded_error(ErrorCode::Internal, format!("Serialize error: {}", e))),
+                        None => Ok(serde_json::json!({"listening": false})),
+                    }
+                }
+
+                "get_dht" => {
+                    let dht = self.dht_handle().await
+                        .map_err(|e| coded_error(e.code(), e.to_string()))?;
+                    let snapshot = dht.snapshot().await
+                        .map_err(|e| coded_error(e.code(), format!("DHT error: {}", e)))?;
+                    serde_json::to_value(snapshot)
+                        .map_err(|e| coded_error(ErrorCode::Internal, format!("Serialize error: {}", e)))
+                }
+
+                "dht_add_address" => {
+                    #[derive(Deserialize)]
+                    struct AddAddressParams {
+                        /// Multiaddr ending in `/p2p/<peer_id>`
+                        addr: String,
+                    }
+
+                    let params: AddAddressParams = params
+                        .and_then(|p| serde_json::from_value(p).ok())
+                        .ok_or_else(|| coded_error(ErrorCode::InvalidRequest, "Missing addr"))?;
+                    let (peer, addr) = craftnet_client::parse_bootstrap_addr(&params.addr)
+                        .ok_or_else(|| coded_error(ErrorCode::InvalidRequest, format!("Expected a multiaddr with /p2p/<peer_id>: {}", params.addr)))?;
+                    let dht = self.dht_handle().await
+                        .map_err(|e| coded_error(e.code(), e.to_string()))?;
+                    dht.add_address(peer, addr).await
+                        .map_err(|e| coded_error(e.code(), format!("DHT error: {}", e)))?;
+                    Ok(serde_json::json!({"success": true}))
+                }
+
+                "dht_bootstrap" | "dht_get_record" => {
+                    #[derive(Deserialize)]
+                    struct DhtParams {
+                        key: Option<String>,
+                        table: Option<String>,
+                    }
+
+                    let params: DhtParams = params
+                        .and_then(|p| serde_json::from_value(p).ok())
+                        .unwrap_or(DhtParams { key: None, table: None });
+                    let table = params.table.as_deref().unwrap_or("registry").parse::<DhtTable>()
+                        .map_err(|e| coded_error(ErrorCode::InvalidRequest, e))?;
+                    let dht = self.dht_handle().await
+                        .map_err(|e| coded_error(e.code(), e.to_string()))?;
+                    if method == "dht_bootstrap" {
+                        dht.bootstrap(table).await
+                            .map_err(|e| coded_error(e.code(), format!("DHT error: {}", e)))?;
+                        return Ok(serde_json::json!({"success": true}));
+                    }
+                    let key = params.key
+                        .ok_or_else(|| coded_error(ErrorCode::InvalidRequest, "Missing key"))?;
+                    let lookup = dht.get_record(table, key.into_bytes()).await
+                        .map_err(|e| coded_error(e.code(), format!("DHT error: {}", e)))?;
+                    serde_json::to_value(lookup)
+                        .map_err(|e| coded_error(ErrorCode::Internal, format!("Serialize error: {}", e)))
+                }
+
+                "get_topology" => {
+                    let topology = self.get_topology().await;
+                    serde_json::to_value(topology)
+                        .map_err(|e| coded_error(ErrorCode::Internal, format!("Serialize error: {}", e)))
+                }
+
+                "drain" => {
+                    #[derive(Deserialize)]
+                    struct DrainParams {
+                        timeout_secs: Option<u64>,
+                    }
+
+                    let params: DrainParams = params
+                        .map(|p| serde_json::from_value(p).unwrap_or(DrainParams { timeout_secs: None }))
+                        .unwrap_or(DrainParams { timeout_secs: None });
+
+                    let timeout = params.timeout_secs.map(std::time::Duration::from_secs);
+                    let report = self.drain(timeout).await
+                        .map_err(|e| coded_error(e.code(), format!("Drain error: {}", e)))?;
+                    serde_json::to_value(report)
+                        .map_err(|e| coded_error(ErrorCode::Internal, format!("Serialize error: {}", e)))
+                }
+
+                "get_kill_switch" => {
+                    let status = self.kill_switch_status().await;
+                    serde_json::to_value(status)
+                        .map_err(|e| coded_error(ErrorCode::Internal, format!("Serialize error: {}", e)))
+                }
+
+                "set_kill_switch" => {
+                    #[derive(Deserialize)]
+                    struct KillSwitchParams {
+                        enabled: bool,
+                    }
+
+                    let params: KillSwitchParams = params
+                        .ok_or_else(|| coded_error(ErrorCode::InvalidRequest, "Missing params"))
+                        .and_then(|p| serde_json::from_value(p)
+                            .map_err(|e| coded_error(ErrorCode::InvalidRequest, format!("Invalid params: {}", e))))?;
+
+                    let status = self.set_kill_switch_enabled(params.enabled).await;
+                    serde_json::to_value(status)
+                        .map_err(|e| coded_error(ErrorCode::Internal, format!("Serialize error: {}", e)))
+                }
+
+                "engage_kill_switch" => {
+                    #[derive(Deserialize, Default)]
+                    struct EngageParams {
+                        reason: Option<String>,
+                    }
+
+                    let params: EngageParams = params
+                        .map(|p| serde_json::from_value(p).unwrap_or_default())
+                        .unwrap_or_default();
+
+                    let reason = params.reason.unwrap_or_else(|| "engaged by user".to_string());
+                    let status = self.engage_kill_switch(&reason).await;
+                    serde_json::to_value(status)
+                        .map_err(|e| coded_error(ErrorCode::Internal, format!("Serialize error: {}", e)))
+                }
+
+                "release_kill_switch" => {
+                    let status = self.release_kill_switch().await;
+                    serde_json::to_value(status)
+                        .map_err(|e| coded_error(ErrorCode::Internal, format!("Serialize error: {}", e)))
+                }
 
-                    Ok(serde_json::json!({"success": true, "balance": balance}))
+                "get_dns_stub" => {
+                    let status = self.dns_stub_status().await;
+                    serde_json::to_value(status)
+                        .map_err(|e| coded_error(ErrorCode::Internal, format!("Serialize error: {}", e)))
                 }
 
-                "set_privacy_level" => {
+                "set_dns_stub" => {
                     #[derive(Deserialize)]
-                    struct PrivacyParams {
-                        level: String,
+                    struct DnsStubParams {
+                        enabled: bool,
+                        listen: Option<String>,
+                        doh_url: Option<String>,
                     }
 
-                    let params: PrivacyParams = params
-                        .ok_or_else(|| "Missing params".to_string())
+                    let params: DnsStubParams = params
+                        .ok_or_else(|| coded_error(ErrorCode::InvalidRequest, "Missing params"))
                         .and_then(|p| serde_json::from_value(p)
-                            .map_err(|e| format!("Invalid params: {}", e)))?;
+                            .map_err(|e| coded_error(ErrorCode::InvalidRequest, format!("Invalid params: {}", e))))?;
 
-                    self.set_privacy_level(&params.level).await
-                        .map_err(|e| format!("Set privacy level error: {}", e))?;
+                    let status = self.set_dns_stub(params.enabled, params.listen, params.doh_url).await
+                        .map_err(|e| coded_error(e.code(), format!("DNS stub error: {}", e)))?;
+                    serde_json::to_value(status)
+                        .map_err(|e| coded_error(ErrorCode::Internal, format!("Serialize error: {}", e)))
+                }
 
-                    Ok(serde_json::json!({"success": true, "level": params.level}))
+                "get_split_tunnel_rules" => {
+                    let rules = self.split_tunnel_rules();
+                    Ok(serde_json::json!({"rules": rules}))
                 }
 
-                "set_mode" => {
+                "add_split_tunnel_rule" => {
                     #[derive(Deserialize)]
-                    struct ModeParams {
-                        mode: String,
+                    struct AddRuleParams {
+                        #[serde(rename = "match")]
+                        matcher: SplitTunnelMatch,
+                        action: SplitTunnelAction,
                     }
 
-                    let params: ModeParams = params
-                        .ok_or_else(|| "Missing params".to_string())
+                    let params: AddRuleParams = params
+                        .ok_or_else(|| coded_error(ErrorCode::InvalidRequest, "Missing params"))
                         .and_then(|p| serde_json::from_value(p)
-                            .map_err(|e| format!("Invalid params: {}", e)))?;
+                            .map_err(|e| coded_error(ErrorCode::InvalidRequest, format!("Invalid params: {}", e))))?;
 
-                    self.set_mode(&params.mode).await
-                        .map_err(|e| format!("Set mode error: {}", e))?;
-
-                    Ok(serde_json::json!({"success": true, "mode": params.mode}))
-                }
+                    let id = self.add_split_tunnel_rule(params.matcher, params.action).await
+                        .map_err(|e| coded_error(e.code(), format!("Add split tunnel rule error: {}", e)))?;
 
-                "get_node_stats" => {
-                    match self.get_node_stats().await {
-                        Some(stats) => serde_json::to_value(stats)
-                            .map_err(|e| format!("Serialize error: {}", e)),
-                        None => Ok(serde_json::json!({})),
-                    }
+                    Ok(serde_json::json!({"success": true, "id": id}))
                 }
 
-                "request" => {
+                "remove_split_tunnel_rule" => {
                     #[derive(Deserialize)]
-                    struct RequestParams {
-                        method: String,
-                        url: String,
-                        body: Option<String>,
-                        #[serde(default)]
-                        headers: Option<std::collections::HashMap<String, String>>,
+                    struct RemoveRuleParams {
+                        id: u64,
                     }
 
-                    let params: RequestParams = params
-                        .ok_or_else(|| "Missing params".to_string())
-                        .and_then(|p| serde_json::from_value(p).map_err(|e| format!("Invalid params: {}", e)))?;
-
-                    let body_bytes = params.body.map(|b| b.into_bytes());
+                    let params: RemoveRuleParams = params
+                        .ok_or_else(|| coded_error(ErrorCode::InvalidRequest, "Missing params"))
+                        .and_then(|p| serde_json::from_value(p)
+                            .map_err(|e| coded_error(ErrorCode::InvalidRequest, format!("Invalid params: {}", e))))?;
 
-                    let response = self.request(&params.method, &params.url, body_bytes, params.headers).await
-                        .map_err(|e| format!("Request error: {}", e))?;
+                    self.remove_split_tunnel_rule(params.id).await
+                        .map_err(|e| coded_error(e.code(), format!("Remove split tunnel rule error: {}", e)))?;
 
-                    Ok(serde_json::json!({
-                        "status": response.status,
-                        "headers": response.headers,
-                        "body": String::from_utf8_lossy(&response.body)
-                    }))
+                    Ok(serde_json::json!({"success": true}))
                 }
 
-                "set_exit_node" => {
+                "set_audit_log" => {
                     #[derive(Deserialize)]
-                    struct ExitNodeParams {
-                        region: String,
-                        country_code: Option<String>,
-                        city: Option<String>,
+                    struct AuditParams {
+                        enabled: bool,
                     }
 
-                    let params: ExitNodeParams = params
-                        .ok_or_else(|| "Missing params".to_string())
+                    let params: AuditParams = params
+                        .ok_or_else(|| coded_error(ErrorCode::InvalidRequest, "Missing params"))
                         .and_then(|p| serde_json::from_value(p)
-                            .map_err(|e| format!("Invalid params: {}", e)))?;
+                            .map_err(|e| coded_error(ErrorCode::InvalidRequest, format!("Invalid params: {}", e))))?;
 
-                    self.set_exit_node(&params.region, params.country_code, params.city).await
-                        .map_err(|e| format!("Set exit node error: {}", e))?;
-
-                    Ok(serde_json::json!({"success": true, "region": params.region}))
-                }
+                    self.set_audit_log(params.enabled).await
+                        .map_err(|e| coded_error(e.code(), format!("Set audit log error: {}", e)))?;
 
-                "get_available_exits" => {
-                    let exits = self.get_available_exits().await;
-                    Ok(serde_json::json!({"exits": exits}))
+                    Ok(serde_json::json!({"success": true, "enabled": params.enabled}))
                 }
 
-                "set_local_discovery" => {
+                "set_cookie_jar" => {
                     #[derive(Deserialize)]
-                    struct LocalDiscoveryParams {
+                    struct CookieJarParams {
                         enabled: bool,
                     }
 
-                    let params: LocalDiscoveryParams = params
-                        .ok_or_else(|| "Missing params".to_string())
+                    let params: CookieJarParams = params
+                        .ok_or_else(|| coded_error(ErrorCode::InvalidRequest, "Missing params"))
                         .and_then(|p| serde_json::from_value(p)
-                            .map_err(|e| format!("Invalid params: {}", e)))?;
+                            .map_err(|e| coded_error(ErrorCode::InvalidRequest, format!("Invalid params: {}", e))))?;
 
-                    self.set_local_discovery(params.enabled).await
-                        .map_err(|e| format!("Set local discovery error: {}", e))?;
+                    self.set_cookie_jar(params.enabled).await
+                        .map_err(|e| coded_error(e.code(), format!("Set cookie jar error: {}", e)))?;
 
                     Ok(serde_json::json!({"success": true, "enabled": params.enabled}))
                 }
 
-                "get_connection_history" => {
-                    let entries = self.get_connection_history().await;
-                    Ok(serde_json::json!({"entries": entries}))
-                }
+                "clear_session_data" => {
+                    #[derive(Deserialize, Default)]
+                    struct ClearSessionParams {
+                        identity: Option<String>,
+                    }
 
-                "get_earnings_history" => {
-                    let entries = self.get_earnings_history().await;
-                    Ok(serde_json::json!({"entries": entries}))
-                }
+                    let params: ClearSessionParams = params
+                        .map(|p| serde_json::from_value(p).unwrap_or_default())
+                        .unwrap_or_default();
 
-                "run_speed_test" => {
-                    let result = self.run_speed_test().await;
-                    Ok(serde_json::json!({"result": result}))
+                    self.clear_session_data(params.identity).await
+                        .map_err(|e| coded_error(e.code(), format!("Clear session data error: {}", e)))?;
+
+                    Ok(serde_json::json!({"success": true}))
                 }
 
-                "set_bandwidth_limit" => {
-                    #[derive(Deserialize)]
-                    struct BandwidthParams {
-                        limit_kbps: Option<u64>,
+                "get_audit_log" => {
+                    #[derive(Deserialize, Default)]
+                    struct AuditQueryParams {
+                        since: Option<u64>,
+                        limit: Option<usize>,
                     }
 
-                    let params: BandwidthParams = params
-                        .ok_or_else(|| "Missing params".to_string())
-                        .and_then(|p| serde_json::from_value(p)
-                            .map_err(|e| format!("Invalid params: {}", e)))?;
-
-                    self.set_bandwidth_limit(params.limit_kbps).await
-                        .map_err(|e| format!("Set bandwidth limit error: {}", e))?;
+                    let params: AuditQueryParams = params
+                        .map(|p| serde_json::from_value(p).unwrap_or_default())
+                        .unwrap_or_default();
 
-                    Ok(serde_json::json!({"success": true, "limit_kbps": params.limit_kbps}))
+                    let log = self.get_audit_log(params.since, params.limit).await;
+                    serde_json::to_value(log)
+                        .map_err(|e| coded_error(ErrorCode::Internal, format!("Serialize error: {}", e)))
                 }
 
-                "export_key" => {
-                    #[derive(Deserialize)]
-                    struct ExportParams {
-                        path: String,
-                        password: String,
+                "export_audit_log" => {
+                    #[derive(Deserialize, Default)]
+                    struct AuditExportParams {
+                        format: Option<String>,
+                        since: Option<u64>,
                     }
 
-                    let params: ExportParams = params
-                        .ok_or_else(|| "Missing params".to_string())
-                        .and_then(|p| serde_json::from_value(p)
-                            .map_err(|e| format!("Invalid params: {}", e)))?;
+                    let params: AuditExportParams = params
+                        .map(|p| serde_json::from_value(p).unwrap_or_default())
+                        .unwrap_or_default();
 
-                    let (path, public_key) = self.export_key(&params.path, &params.password).await
-                        .map_err(|e| format!("Export key error: {}", e))?;
+                    let format: AuditExportFormat = params.format.as_deref()
+                        .unwrap_or("jsonl")
+                        .parse()
+                        .map_err(|e: String| coded_error(ErrorCode::InvalidRequest, e))?;
+                    let data = self.export_audit_log(format, params.since).await;
 
-                    Ok(serde_json::json!({"path": path, "public_key": public_key}))
+                    Ok(serde_json::json!({"format": format, "data": data}))
                 }
 
-                "import_key" => {
-                    #[derive(Deserialize)]
-                    struct ImportParams {
-                        path: String,
-                        password: String,
-                    }
+                _ => {
+                    Err(coded_error(ErrorCode::InvalidRequest, format!("Unknown method: {}", method)))
+                }
+            }
+        })
+    }
+}
 
-                    let params: ImportParams = params
-                        .ok_or_else(|| "Missing params".to_string())
-                        .and_then(|p| serde_json::from_value(p)
-                            .map_err(|e| format!("Invalid params: {}", e)))?;
+impl DaemonService {
+    fn with_sessions<T>(&self, f: impl FnOnce(&mut HashMap<u64, ClientSession>) -> T) -> T {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut sessions)
+    }
 
-                    let public_key = self.import_key(&params.path, &params.password).await
-                        .map_err(|e| format!("Import key error: {}", e))?;
+    /// Claim `stream_id` for `session`; ids held by another client are refused
+    fn claim_stream(&self, session: &IpcSession, stream_id: &str) -> std::result::Result<(), String> {
+        self.with_sessions(|sessions| {
+            if sessions.iter().any(|(id, s)| *id != session.id && s.streams.contains(stream_id)) {
+                return Err(coded_error(ErrorCode::InvalidRequest, format!("stream_id '{}' is in use", stream_id)));
+            }
+            if let Some(client) = sessions.get_mut(&session.id) {
+                client.streams.insert(stream_id.to_string());
+            }
+            Ok(())
+        })
+    }
 
-                    Ok(serde_json::json!({"public_key": public_key}))
-                }
+    /// Event channel of the client that claimed `stream_id`, if any
+    fn stream_events(&self, stream_id: &str) -> Option<mpsc::Sender<String>> {
+        self.with_sessions(|sessions| {
+            sessions.values().find(|s| s.streams.contains(stream_id)).map(|s| s.events.clone())
+        })
+    }
+}
 
-                "start_proxy" => {
-                    #[derive(Deserialize)]
-                    struct ProxyParams {
-                        port: Option<u16>,
-                    }
+impl SessionHandler for DaemonService {
+    fn open_session(&self, session: &IpcSession) {
+        let opened_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let (events, events_rx) = mpsc::channel(SESSION_EVENT_CAPACITY);
+        self.with_sessions(|sessions| {
+            sessions.insert(session.id, ClientSession {
+                session: session.clone(),
+                opened_at,
+                requests: 0,
+                streams: HashSet::new(),
+                events,
+                events_rx: Some(events_rx),
+            });
+        });
+    }
 
-                    let params: ProxyParams = params
-                        .map(|p| serde_json::from_value(p).unwrap_or(ProxyParams { port: None }))
-                        .unwrap_or(ProxyParams { port: None });
+    fn session_events(&self, session: &IpcSession) -> Option<mpsc::Receiver<String>> {
+        self.with_sessions(|sessions| sessions.get_mut(&session.id)?.events_rx.take())
+    }
 
-                    let port = params.port.unwrap_or(1080);
-                    self.start_proxy(port).await
-                        .map_err(|e| format!("Start proxy error: {}", e))?;
+    fn close_session(&self, session: &IpcSession) {
+        self.with_sessions(|sessions| sessions.remove(&session.id));
+    }
 
-                    Ok(serde_json::json!({"success": true, "port": port}))
+    fn handle_session<'a>(
+        &'a self,
+        session: &'a IpcSession,
+        method: &'a str,
+        params: Option<serde_json::Value>,
+    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = std::result::Result<serde_json::Value, String>> + Send + 'a>> {
+        Box::pin(async move {
+            session.authorize(method)?;
+            self.with_sessions(|sessions| {
+                if let Some(client) = sessions.get_mut(&session.id) {
+                    client.requests += 1;
                 }
+            });
 
-                "stop_proxy" => {
-                    self.stop_proxy().await
-                        .map_err(|e| format!("Stop proxy error: {}", e))?;
-
-                    Ok(serde_json::json!({"success": true}))
+            match method {
+                "whoami" => {
+                    let info = self.with_sessions(|sessions| sessions.get(&session.id).map(ClientSession::info));
+                    serde_json::to_value(info)
+                        .map_err(|e| coded_error(ErrorCode::Internal, format!("Serialize error: {}", e)))
                 }
 
-                "proxy_status" => {
-                    let status = self.proxy_status().await;
-                    match status {
-                        Some(s) => serde_json::to_value(s)
-                            .map_err(|e| format!("Serialize error: {}", e)),
-                        None => Ok(serde_json::json!({"listening": false})),
-                    }
+                "list_sessions" => {
+                    let mut infos: Vec<SessionInfo> = self.with_sessions(|sessions| {
+                        sessions.values().map(ClientSession::info).collect()
+                    });
+                    infos.sort_by_key(|info| info.id);
+                    Ok(serde_json::json!({"sessions": infos}))
                 }
 
-                _ => {
-                    Err(format!("Unknown method: {}", method))
+                // Name the stream up front so its events can be routed to this client only
+                "request_stream" => {
+                    let mut params = params
+                        .filter(|p| p.is_object())
+                        .ok_or_else(|| coded_error(ErrorCode::InvalidRequest, "Missing params"))?;
+                    let stream_id = match params.get("stream_id").and_then(|v| v.as_str()) {
+                        Some(id) => id.to_string(),
+                        None => hex::encode(rand::random::<[u8; 8]>()),
+                    };
+                    self.claim_stream(session, &stream_id)?;
+                    params["stream_id"] = serde_json::Value::String(stream_id);
+                    self.handle(method, Some(params)).await
                 }
+
+                _ => self.handle(method, params).await,
+            }
+        })
+    }
+
+    /// Streamed response events only go to the client that started the stream
+    fn event_visible(&self, session: &IpcSession, event: &str) -> bool {
+        if !event.contains("\"response_") {
+            return true;
+        }
+        let Ok(msg) = serde_json::from_str::<serde_json::Value>(event) else { return true };
+        let name = msg["event"].as_str().unwrap_or_default();
+        let Some(stream_id) = msg["data"]["stream_id"].as_str().filter(|_| name.starts_with("response_")) else {
+            return true;
+        };
+        self.with_sessions(|sessions| {
+            let Some(client) = sessions.get_mut(&session.id) else { return false };
+            if !client.streams.contains(stream_id) {
+                return false;
+            }
+            if name != "response_chunk" {
+                client.streams.remove(stream_id);
             }
+            true
         })
     }
 }
@@ -1880,6 +4279,88 @@ mod tests {
         assert!(value.get("state").is_some());
     }
 
+    #[tokio::test]
+    async fn test_ipc_handler_get_topology() {
+        let service = mock_service();
+
+        let result = service.handle("get_topology", None).await;
+        assert!(result.is_ok());
+
+        let value = result.unwrap();
+        assert!(value["nodes"].is_array());
+        assert!(value["edges"].is_array());
+    }
+
+    #[tokio::test]
+    async fn test_ipc_handler_list_peers_not_running() {
+        let service = mock_service();
+
+        let value = service.handle("list_peers", None).await.unwrap();
+        assert_eq!(value["peers"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_ipc_handler_recent_logs() {
+        let service = mock_service();
+        recent_logs().push(0, "INFO", "craftnet", "dashboard test line".to_string());
+
+        let value = service.handle("get_recent_logs", Some(serde_json::json!({"limit": 1}))).await.unwrap();
+        let lines = value["lines"].as_array().unwrap();
+        assert_eq!(lines.len(), 1);
+        let seq = lines[0]["seq"].as_u64().unwrap();
+        let value = service.handle("get_recent_logs", Some(serde_json::json!({"since": seq}))).await.unwrap();
+        assert!(value["lines"].as_array().unwrap().iter().all(|l| l["seq"].as_u64().unwrap() > seq));
+    }
+
+    #[tokio::test]
+    async fn test_ipc_handler_build_attestation_not_running() {
+        let service = mock_service();
+
+        let value = service.handle("get_build_attestation", None).await.unwrap();
+        assert_eq!(value["build"]["version"], env!("CARGO_PKG_VERSION"));
+        assert!(value["attestation"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_ipc_handler_dht_not_running() {
+        let service = mock_service();
+
+        assert!(matches!(service.dht_handle().await, Err(crate::DaemonError::NotRunning)));
+        assert!(service.handle("get_dht", None).await.is_err());
+        let bad_table = service.handle("dht_bootstrap", Some(serde_json::json!({"table": "secondary"}))).await;
+        assert!(bad_table.unwrap_err().contains("Unknown DHT table"));
+    }
+
+    #[tokio::test]
+    async fn test_ipc_handler_batch_proof_params() {
+        let service = mock_service();
+        let pool = hex::encode([1u8; 32]);
+
+        let bad_relay = service.handle("get_distribution_batch_proof", Some(serde_json::json!({
+            "pool": pool, "relays": ["zz"],
+        }))).await;
+        assert!(bad_relay.unwrap_err().contains("32-byte hex"));
+        let bad_type = service.handle("get_distribution_batch_proof", Some(serde_json::json!({
+            "pool": pool, "pool_type": "paid", "relays": [],
+        }))).await;
+        assert!(bad_type.unwrap_err().contains("Unknown pool type"));
+        // Well-formed, but no node to ask
+        let result = service.handle("get_distribution_batch_proof", Some(serde_json::json!({
+            "pool": pool, "relays": [hex::encode([2u8; 32])],
+        }))).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_drain_when_not_running() {
+        let service = mock_service();
+
+        assert!(matches!(service.drain(None).await, Err(crate::DaemonError::NotRunning)));
+        let result = service.handle("drain", Some(serde_json::json!({"timeout_secs": 5}))).await;
+        assert!(result.is_err());
+        assert_eq!(service.state().await, DaemonState::Ready);
+    }
+
     #[tokio::test]
     async fn test_ipc_handler_unknown_method() {
         let service = mock_service();
@@ -1944,6 +4425,119 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_ipc_handler_audit_log_off_by_default() {
+        let service = mock_service();
+
+        let result = service.handle("get_audit_log", None).await.unwrap();
+        assert_eq!(result["enabled"], false);
+        assert_eq!(result["entries"].as_array().unwrap().len(), 0);
+
+        let result = service.handle(
+            "export_audit_log",
+            Some(serde_json::json!({"format": "csv"})),
+        ).await.unwrap();
+        assert_eq!(result["format"], "csv");
+        assert!(result["data"].as_str().unwrap().starts_with("timestamp,host"));
+
+        let result = service.handle(
+            "export_audit_log",
+            Some(serde_json::json!({"format": "xml"})),
+        ).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ipc_handler_set_audit_log() {
+        let service = mock_service();
+
+        let result = service.handle(
+            "set_audit_log",
+            Some(serde_json::json!({"enabled": true})),
+        ).await.unwrap();
+        assert_eq!(result["enabled"], true);
+        assert!(service.settings.read().await.config.audit.enabled);
+
+        service.handle("set_audit_log", Some(serde_json::json!({"enabled": false}))).await.unwrap();
+        assert!(!service.settings.read().await.config.audit.enabled);
+    }
+
+    #[tokio::test]
+    async fn test_ipc_handler_cookie_jar() {
+        let service = mock_service();
+
+        let result = service.handle(
+            "set_cookie_jar",
+            Some(serde_json::json!({"enabled": true})),
+        ).await.unwrap();
+        assert_eq!(result["enabled"], true);
+        assert!(service.settings.read().await.config.cookies.enabled);
+
+        // Cookies a previous run persisted are cleared from disk
+        let cookies = service.settings.read().await.config.cookies.clone();
+        let config = service.cookie_jar_config(&cookies);
+        let mut jar = CookieJar::load(config.clone());
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        jar.store(None, "https://example.com/", &["sid=1; Max-Age=3600"], now);
+        assert_eq!(CookieJar::load(config.clone()).cookies(None).len(), 1);
+
+        service.handle("clear_session_data", None).await.unwrap();
+        assert!(CookieJar::load(config.clone()).cookies(None).is_empty());
+        let _ = std::fs::remove_file(config.path.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_ipc_handler_kill_switch() {
+        let service = mock_service();
+        let mut rx = service.event_sender().subscribe();
+
+        let result = service.handle("get_kill_switch", None).await.unwrap();
+        assert_eq!(result["enabled"], false);
+        assert_eq!(result["state"], "armed");
+
+        let result = service.handle("set_kill_switch", Some(serde_json::json!({"enabled": true}))).await.unwrap();
+        assert_eq!(result["enabled"], true);
+        assert!(service.settings.read().await.config.network.kill_switch);
+
+        let result = service.handle("engage_kill_switch", None).await.unwrap();
+        assert_eq!(result["state"], "engaged");
+        let msg = rx.try_recv().unwrap();
+        assert!(msg.contains("kill_switch"));
+        assert!(msg.contains("engaged"));
+
+        let result = service.handle("release_kill_switch", None).await.unwrap();
+        assert_eq!(result["state"], "released");
+    }
+
+    #[tokio::test]
+    async fn test_ipc_handler_split_tunnel_rules() {
+        let service = mock_service();
+
+        let result = service.handle(
+            "add_split_tunnel_rule",
+            Some(serde_json::json!({"match": {"domain": "bank.example"}, "action": "bypass"})),
+        ).await.unwrap();
+        let id = result["id"].as_u64().unwrap();
+
+        let result = service.handle("get_split_tunnel_rules", None).await.unwrap();
+        assert_eq!(result["rules"][0]["match"]["domain"], "bank.example");
+        assert_eq!(result["rules"][0]["action"], "bypass");
+        assert_eq!(
+            service.split_tunnel.read().unwrap().decide(None, "www.bank.example"),
+            SplitTunnelAction::Bypass,
+        );
+
+        let result = service.handle(
+            "add_split_tunnel_rule",
+            Some(serde_json::json!({"match": {"cidr": "10.0.0.0/99"}, "action": "bypass"})),
+        ).await;
+        assert!(result.is_err());
+
+        service.handle("remove_split_tunnel_rule", Some(serde_json::json!({"id": id}))).await.unwrap();
+        assert!(service.split_tunnel_rules().is_empty());
+        assert!(service.handle("remove_split_tunnel_rule", Some(serde_json::json!({"id": id}))).await.is_err());
+    }
+
     #[tokio::test]
     async fn test_ipc_handler_get_node_stats() {
         let service = mock_service();
@@ -2161,6 +4755,23 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_ipc_handler_exit_block_params() {
+        let service = mock_service();
+
+        // Exactly one of destination and pool
+        assert!(service.handle("exit_block", Some(serde_json::json!({}))).await.is_err());
+        let both = serde_json::json!({"destination": "example.com", "pool": hex::encode([1u8; 32])});
+        assert!(service.handle("exit_block", Some(both)).await.is_err());
+        let short_pool = serde_json::json!({"pool": "abcd"});
+        assert!(service.handle("exit_unblock", Some(short_pool)).await.is_err());
+
+        // Valid, but no node to apply it to
+        let destination = serde_json::json!({"destination": "example.com"});
+        assert!(service.handle("exit_block", Some(destination)).await.is_err());
+        assert_eq!(service.handle("get_exit_abuse", None).await.unwrap(), serde_json::json!({}));
+    }
+
     #[tokio::test]
     async fn test_set_mode_with_running_node() {
         let service = mock_service();
@@ -2197,4 +4808,42 @@ mod tests {
         assert!(msg.contains("state_change"));
         assert!(msg.contains("connecting"));
     }
+
+    #[tokio::test]
+    async fn test_sessions_are_isolated() {
+        use crate::session::PeerCredentials;
+
+        let service = mock_service();
+        let admin = IpcSession::local(1);
+        let user = IpcSession { id: 2, peer: Some(PeerCredentials { uid: 1001, gid: 1001, pid: None }), admin: false };
+        service.open_session(&admin);
+        service.open_session(&user);
+
+        // Admin-only methods are refused to other users
+        let error = service.handle_session(&user, "drain", None).await.unwrap_err();
+        assert!(error.starts_with("NOT_AUTHORIZED: "));
+        let whoami = service.handle_session(&user, "whoami", None).await.unwrap();
+        assert_eq!(whoami["uid"], 1001);
+        assert_eq!(whoami["requests"], 2);
+
+        // A stream's events only reach the session that started it
+        service.claim_stream(&user, "abc").unwrap();
+        assert!(service.claim_stream(&admin, "abc").is_err());
+        let chunk = r#"{"event":"response_chunk","data":{"stream_id":"abc","seq":0,"data":""}}"#;
+        assert!(service.event_visible(&user, chunk));
+        assert!(!service.event_visible(&admin, chunk));
+        assert!(service.event_visible(&admin, r#"{"event":"state_change","data":{"state":"ready"}}"#));
+
+        // ...through that client's own channel, which is taken once
+        let mut user_events = service.session_events(&user).unwrap();
+        assert!(service.session_events(&user).is_none());
+        service.stream_events("abc").unwrap().send(chunk.to_string()).await.unwrap();
+        assert_eq!(user_events.recv().await.as_deref(), Some(chunk));
+        assert!(service.stream_events("nope").is_none());
+
+        let sessions = service.handle_session(&admin, "list_sessions", None).await.unwrap();
+        assert_eq!(sessions["sessions"].as_array().unwrap().len(), 2);
+        service.close_session(&user);
+        assert!(service.claim_stream(&admin, "abc").is_ok());
+    }
 }
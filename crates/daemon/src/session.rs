@@ -0,0 +1,228 @@
+//! Per-connection IPC sessions and who may call what
+//!
+//! Every IPC connection becomes an [`IpcSession`] carrying the peer's
+//! credentials (`SO_PEERCRED` / `getpeereid` on the Unix socket). By default
+//! the socket is private to the daemon's user (mode 0600, one socket path
+//! per user) and every other uid is refused. A system daemon serving several
+//! users sets [`AccessPolicy::allow_other_users`]: the socket becomes
+//! world-connectable and methods that change the shared node or read other
+//! users' data ([`ADMIN_METHODS`]) are limited to admins.
+//!
+//! Admins are root, the daemon's own user, `admin_uids`, and members (primary
+//! or listed in `/etc/group`) of `admin_gids`.
+//!
+//! Windows named pipes do not report the client SID here yet; the pipe
+//! server keeps treating every client as the daemon's user.
+
+use serde::Serialize;
+use tracing::warn;
+
+use craftnet_core::ErrorCode;
+
+use crate::ipc::coded_error;
+
+/// Set to `1`/`true` to let other users connect (system daemon)
+pub const IPC_SHARED_ENV: &str = "CRAFTNET_IPC_SHARED";
+
+/// Comma-separated gids whose members are admins
+pub const IPC_ADMIN_GIDS_ENV: &str = "CRAFTNET_IPC_ADMIN_GIDS";
+
+/// Methods that change the shared node or expose other users' data
+pub const ADMIN_METHODS: &[&str] = &[
+    "set_mode",
+    "set_local_discovery",
+    "set_bandwidth_limit",
+    "purchase_credits",
+    "export_key",
+    "import_key",
+    "start_proxy",
+    "stop_proxy",
+    "start_tun",
+    "stop_tun",
+    "drain",
+    "set_kill_switch",
+    "set_dns_stub",
+    "clear_connection_history",
+    "dht_add_address",
+    "dht_bootstrap",
+    "engage_kill_switch",
+    "release_kill_switch",
+    "add_split_tunnel_rule",
+    "remove_split_tunnel_rule",
+    "set_audit_log",
+    "get_audit_log",
+    "export_audit_log",
+    "get_recent_logs",
+    "list_sessions",
+    "get_exit_abuse",
+    "exit_block",
+    "exit_unblock",
+];
+
+/// Whether `method` is admin-only
+pub fn is_admin_method(method: &str) -> bool {
+    ADMIN_METHODS.contains(&method)
+}
+
+/// Credentials of the process on the other end of the socket
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct PeerCredentials {
+    pub uid: u32,
+    pub gid: u32,
+    pub pid: Option<i32>,
+}
+
+/// One IPC connection
+#[derive(Debug, Clone, Serialize)]
+pub struct IpcSession {
+    /// Daemon-unique session id
+    pub id: u64,
+    /// Peer credentials (None for in-process or Windows pipe clients)
+    pub peer: Option<PeerCredentials>,
+    /// Whether admin-only methods are allowed
+    pub admin: bool,
+}
+
+impl IpcSession {
+    /// Session of a trusted local caller (the daemon's own user)
+    pub fn local(id: u64) -> Self {
+        Self { id, peer: None, admin: true }
+    }
+
+    /// Refuse admin-only methods to non-admins (coded error message)
+    pub fn authorize(&self, method: &str) -> std::result::Result<(), String> {
+        if self.admin || !is_admin_method(method) {
+            return Ok(());
+        }
+        Err(coded_error(
+            ErrorCode::NotAuthorized,
+            format!("'{}' is limited to daemon admins", method),
+        ))
+    }
+}
+
+/// Which users may connect and which of them are admins
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccessPolicy {
+    /// Let users other than the daemon's connect (system daemon). Default: false.
+    pub allow_other_users: bool,
+    /// Extra admin uids. Default: none.
+    pub admin_uids: Vec<u32>,
+    /// Groups whose members are admins. Default: none.
+    pub admin_gids: Vec<u32>,
+}
+
+impl AccessPolicy {
+    /// Policy from `CRAFTNET_IPC_SHARED` and `CRAFTNET_IPC_ADMIN_GIDS`
+    pub fn from_env() -> Self {
+        let allow_other_users = std::env::var(IPC_SHARED_ENV)
+            .is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"));
+        let admin_gids = std::env::var(IPC_ADMIN_GIDS_ENV)
+            .map(|v| {
+                v.split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .filter_map(|s| match s.parse() {
+                        Ok(gid) => Some(gid),
+                        Err(_) => {
+                            warn!("Ignoring invalid gid '{}' in {}", s, IPC_ADMIN_GIDS_ENV);
+                            None
+                        }
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self { allow_other_users, admin_uids: Vec::new(), admin_gids }
+    }
+
+    /// Session for a connecting peer owned by a daemon running as
+    /// `daemon_uid`, or None if the peer may not connect
+    pub fn session(&self, id: u64, peer: PeerCredentials, daemon_uid: u32) -> Option<IpcSession> {
+        let own = peer.uid == 0 || peer.uid == daemon_uid;
+        if !own && !self.allow_other_users {
+            return None;
+        }
+        let admin = own || self.is_admin(&peer);
+        Some(IpcSession { id, peer: Some(peer), admin })
+    }
+
+    fn is_admin(&self, peer: &PeerCredentials) -> bool {
+        if self.admin_uids.contains(&peer.uid) || self.admin_gids.contains(&peer.gid) {
+            return true;
+        }
+        if self.admin_gids.is_empty() {
+            return false;
+        }
+        let (Ok(passwd), Ok(group)) = (std::fs::read_to_string("/etc/passwd"), std::fs::read_to_string("/etc/group")) else {
+            return false;
+        };
+        let Some(name) = user_name(&passwd, peer.uid) else { return false };
+        self.admin_gids.iter().any(|gid| group_members(&group, *gid).contains(&name))
+    }
+}
+
+/// Name of `uid` in a passwd file
+fn user_name(passwd: &str, uid: u32) -> Option<&str> {
+    passwd.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        let name = fields.next()?;
+        (fields.nth(1)?.parse::<u32>().ok()? == uid).then_some(name)
+    })
+}
+
+/// Listed (supplementary) members of `gid` in a group file
+fn group_members(group: &str, gid: u32) -> Vec<&str> {
+    group
+        .lines()
+        .find_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            (fields.len() == 4 && fields[2].parse::<u32>().ok()? == gid).then_some(fields[3])
+        })
+        .map(|members| members.split(',').filter(|m| !m.is_empty()).collect())
+        .unwrap_or_default()
+}
+
+// Effective uid and the per-user default socket path are shared with the
+// client so both ends agree on where the socket lives
+#[cfg(unix)]
+pub use craftnet_ipc_client::{current_uid, default_socket_path};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer(uid: u32, gid: u32) -> PeerCredentials {
+        PeerCredentials { uid, gid, pid: None }
+    }
+
+    #[test]
+    fn test_private_socket_refuses_other_users() {
+        let policy = AccessPolicy::default();
+        assert!(policy.session(1, peer(1000, 1000), 1000).unwrap().admin);
+        assert!(policy.session(2, peer(0, 0), 1000).unwrap().admin);
+        assert!(policy.session(3, peer(1001, 1001), 1000).is_none());
+    }
+
+    #[test]
+    fn test_shared_socket_limits_admin_methods() {
+        let policy = AccessPolicy { allow_other_users: true, admin_uids: vec![1002], admin_gids: vec![27] };
+        let user = policy.session(1, peer(1001, 1001), 999).unwrap();
+        assert!(!user.admin);
+        assert!(user.authorize("request").is_ok());
+        let error = user.authorize("drain").unwrap_err();
+        assert!(error.starts_with("NOT_AUTHORIZED: "));
+
+        assert!(policy.session(2, peer(1002, 1002), 999).unwrap().admin);
+        assert!(policy.session(3, peer(1003, 27), 999).unwrap().admin);
+    }
+
+    #[test]
+    fn test_group_file_parsing() {
+        let passwd = "root:x:0:0:root:/root:/bin/bash\nalice:x:1001:1001::/home/alice:/bin/sh\n";
+        let group = "root:x:0:\nsudo:x:27:alice,bob\nalice:x:1001:\n";
+        assert_eq!(user_name(passwd, 1001), Some("alice"));
+        assert_eq!(user_name(passwd, 5), None);
+        assert_eq!(group_members(group, 27), vec!["alice", "bob"]);
+        assert!(group_members(group, 0).is_empty());
+    }
+}
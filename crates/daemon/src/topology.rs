@@ -0,0 +1,185 @@
+//! Live network topology collector
+//!
+//! Maintains a graph of peers, roles, and links built from the node's
+//! topology snapshots (relay heartbeats carry `connected_peers`, which is
+//! the topology feed). Snapshots are merged so peers that briefly drop out
+//! of a snapshot keep their last-seen time until the retention window expires.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use craftnet_client::TopologySnapshot;
+
+/// How often the node task feeds a fresh snapshot into the collector
+pub const TOPOLOGY_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Default retention for nodes/edges not refreshed by a snapshot (10 minutes)
+pub const TOPOLOGY_RETENTION: Duration = Duration::from_secs(600);
+
+/// A peer in the topology graph
+#[derive(Debug, Clone, Serialize)]
+pub struct TopologyNode {
+    pub peer_id: String,
+    pub roles: Vec<String>,
+    /// Unix timestamp (seconds) when the peer was last seen
+    pub last_seen: u64,
+}
+
+/// An undirected link between two peers
+#[derive(Debug, Clone, Serialize)]
+pub struct TopologyEdge {
+    pub source: String,
+    pub target: String,
+    /// Unix timestamp (seconds) when the link was last reported
+    pub last_seen: u64,
+}
+
+/// Topology view returned by the `get_topology` IPC method
+#[derive(Debug, Clone, Serialize)]
+pub struct TopologyResponse {
+    pub local_peer_id: Option<String>,
+    pub nodes: Vec<TopologyNode>,
+    pub edges: Vec<TopologyEdge>,
+    /// Unix timestamp (seconds) when this view was generated
+    pub generated_at: u64,
+}
+
+/// Collects topology snapshots into a live graph
+pub struct TopologyCollector {
+    local_peer_id: Option<String>,
+    nodes: HashMap<String, TopologyNode>,
+    edges: HashMap<(String, String), u64>,
+    retention: Duration,
+}
+
+impl TopologyCollector {
+    pub fn new(retention: Duration) -> Self {
+        Self {
+            local_peer_id: None,
+            nodes: HashMap::new(),
+            edges: HashMap::new(),
+            retention,
+        }
+    }
+
+    /// Merge a node snapshot taken at unix time `now`
+    pub fn ingest(&mut self, snapshot: &TopologySnapshot, now: u64) {
+        if snapshot.local_peer_id.is_some() {
+            self.local_peer_id = snapshot.local_peer_id.clone();
+        }
+
+        for info in &snapshot.nodes {
+            let last_seen = now.saturating_sub(info.last_seen_secs);
+            let node = self.nodes.entry(info.peer_id.clone()).or_insert_with(|| TopologyNode {
+                peer_id: info.peer_id.clone(),
+                roles: Vec::new(),
+                last_seen,
+            });
+            node.last_seen = node.last_seen.max(last_seen);
+            for role in &info.roles {
+                if !node.roles.contains(role) {
+                    node.roles.push(role.clone());
+                }
+            }
+        }
+
+        for (a, b) in &snapshot.edges {
+            self.edges.insert((a.clone(), b.clone()), now);
+        }
+
+        self.prune(now);
+    }
+
+    /// Drop nodes and edges not seen within the retention window
+    pub fn prune(&mut self, now: u64) {
+        let cutoff = now.saturating_sub(self.retention.as_secs());
+        self.nodes.retain(|_, n| n.last_seen >= cutoff);
+        self.edges.retain(|_, last_seen| *last_seen >= cutoff);
+    }
+
+    /// Current graph as a serializable view
+    pub fn view(&self, now: u64) -> TopologyResponse {
+        let mut nodes: Vec<TopologyNode> = self.nodes.values().cloned().collect();
+        nodes.sort_by(|a, b| a.peer_id.cmp(&b.peer_id));
+        let mut edges: Vec<TopologyEdge> = self.edges.iter()
+            .map(|((source, target), last_seen)| TopologyEdge {
+                source: source.clone(),
+                target: target.clone(),
+                last_seen: *last_seen,
+            })
+            .collect();
+        edges.sort_by(|a, b| (&a.source, &a.target).cmp(&(&b.source, &b.target)));
+
+        TopologyResponse {
+            local_peer_id: self.local_peer_id.clone(),
+            nodes,
+            edges,
+            generated_at: now,
+        }
+    }
+}
+
+impl Default for TopologyCollector {
+    fn default() -> Self {
+        Self::new(TOPOLOGY_RETENTION)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use craftnet_client::TopologyNodeInfo;
+
+    fn snapshot(nodes: &[(&str, &[&str], u64)], edges: &[(&str, &str)]) -> TopologySnapshot {
+        TopologySnapshot {
+            local_peer_id: Some("local".to_string()),
+            nodes: nodes.iter().map(|(id, roles, secs)| TopologyNodeInfo {
+                peer_id: id.to_string(),
+                roles: roles.iter().map(|r| r.to_string()).collect(),
+                last_seen_secs: *secs,
+            }).collect(),
+            edges: edges.iter().map(|(a, b)| (a.to_string(), b.to_string())).collect(),
+        }
+    }
+
+    #[test]
+    fn test_ingest_builds_graph() {
+        let mut collector = TopologyCollector::default();
+        collector.ingest(&snapshot(
+            &[("local", &["client"], 0), ("relay1", &["relay"], 5)],
+            &[("local", "relay1")],
+        ), 1000);
+
+        let view = collector.view(1000);
+        assert_eq!(view.local_peer_id.as_deref(), Some("local"));
+        assert_eq!(view.nodes.len(), 2);
+        assert_eq!(view.nodes[1].last_seen, 995);
+        assert_eq!(view.edges.len(), 1);
+        assert_eq!(view.edges[0].source, "local");
+    }
+
+    #[test]
+    fn test_roles_merge_across_snapshots() {
+        let mut collector = TopologyCollector::default();
+        collector.ingest(&snapshot(&[("n1", &["relay"], 0)], &[]), 100);
+        collector.ingest(&snapshot(&[("n1", &["exit"], 0)], &[]), 110);
+
+        let view = collector.view(110);
+        assert_eq!(view.nodes[0].roles, vec!["relay".to_string(), "exit".to_string()]);
+        assert_eq!(view.nodes[0].last_seen, 110);
+    }
+
+    #[test]
+    fn test_prune_drops_stale_entries() {
+        let mut collector = TopologyCollector::new(Duration::from_secs(60));
+        collector.ingest(&snapshot(&[("old", &["relay"], 0)], &[("local", "old")]), 100);
+        collector.ingest(&snapshot(&[("new", &["relay"], 0)], &[]), 200);
+
+        let view = collector.view(200);
+        assert_eq!(view.nodes.len(), 1);
+        assert_eq!(view.nodes[0].peer_id, "new");
+        assert!(view.edges.is_empty());
+    }
+}
@@ -1,25 +1,31 @@
 //! Windows Named Pipe IPC Server
 //!
 //! Implements JSON-RPC 2.0 over Windows named pipes for the CraftNet daemon.
+//! Connections are served by the same code as the Unix socket server
+//! (`ipc::serve_connection`): several clients at once, each with its own
+//! session and the broadcast event stream.
+//!
+//! The pipe does not report the client's SID yet, so every client gets a
+//! local (admin) session.
 
+#[cfg(windows)]
+use std::sync::atomic::{AtomicU64, Ordering};
 #[cfg(windows)]
 use std::sync::Arc;
 
 #[cfg(windows)]
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::windows::named_pipe::{PipeMode, ServerOptions};
 #[cfg(windows)]
-use tokio::net::windows::named_pipe::{
-    NamedPipeServer, ServerOptions, PipeMode,
-};
+use tokio::sync::{broadcast, mpsc};
 #[cfg(windows)]
-use tokio::sync::mpsc;
-#[cfg(windows)]
-use tracing::{debug, error, info, warn};
+use tracing::{error, info};
 
 #[cfg(windows)]
 use crate::{DaemonError, Result};
 #[cfg(windows)]
-use crate::ipc::{IpcHandler, JsonRpcRequest, JsonRpcResponse};
+use crate::ipc::{serve_connection, SessionHandler};
+#[cfg(windows)]
+use crate::session::IpcSession;
 
 /// Windows Named Pipe configuration
 #[cfg(windows)]
@@ -46,6 +52,7 @@ impl Default for WindowsPipeConfig {
 pub struct WindowsPipeServer {
     config: WindowsPipeConfig,
     shutdown_tx: Option<mpsc::Sender<()>>,
+    event_tx: Option<broadcast::Sender<String>>,
 }
 
 #[cfg(windows)]
@@ -55,25 +62,37 @@ impl WindowsPipeServer {
         Self {
             config,
             shutdown_tx: None,
+            event_tx: None,
         }
     }
 
+    /// Set the event broadcast sender for streaming events to clients
+    pub fn set_event_sender(&mut self, tx: broadcast::Sender<String>) {
+        self.event_tx = Some(tx);
+    }
+
+    fn create_instance(&self, first: bool) -> Result<tokio::net::windows::named_pipe::NamedPipeServer> {
+        ServerOptions::new()
+            .first_pipe_instance(first)
+            .pipe_mode(PipeMode::Byte)
+            .max_instances(self.config.max_connections as usize)
+            .create(&self.config.pipe_name)
+            .map_err(|e| DaemonError::IpcError(format!("Failed to create pipe: {}", e)))
+    }
+
     /// Start the named pipe server
-    pub async fn start<H: IpcHandler + 'static>(&mut self, handler: H) -> Result<()> {
+    pub async fn start<H: SessionHandler + 'static>(&mut self, handler: H) -> Result<()> {
         info!("Starting Windows named pipe server on {}", self.config.pipe_name);
 
         let (shutdown_tx, mut shutdown_rx) = mpsc::channel::<()>(1);
         self.shutdown_tx = Some(shutdown_tx);
 
         let handler = Arc::new(handler);
-        let pipe_name = self.config.pipe_name.clone();
-        
+        let event_tx = self.event_tx.clone();
+        let next_session = AtomicU64::new(1);
+
         // Create the first pipe server instance
-        let mut server = ServerOptions::new()
-            .first_pipe_instance(true)
-            .pipe_mode(PipeMode::Message)
-            .create(&pipe_name)
-            .map_err(|e| DaemonError::IpcError(format!("Failed to create pipe: {}", e)))?;
+        let mut server = self.create_instance(true)?;
 
         loop {
             tokio::select! {
@@ -81,28 +100,20 @@ impl WindowsPipeServer {
                 result = server.connect() => {
                     match result {
                         Ok(()) => {
-                            let handler_clone = handler.clone();
-                            let connected_pipe = server;
-                            
-                            // Create a new server for the next connection
-                            server = ServerOptions::new()
-                                .pipe_mode(PipeMode::Message)
-                                .create(&pipe_name)
-                                .map_err(|e| DaemonError::IpcError(format!("Failed to create pipe: {}", e)))?;
-                            
-                            // Handle this connection in a separate task
-                            tokio::spawn(async move {
-                                if let Err(e) = Self::handle_connection(connected_pipe, handler_clone).await {
-                                    warn!("Pipe connection error: {}", e);
-                                }
-                            });
+                            // Open the next instance before serving this one, so
+                            // clients never find the pipe missing
+                            let connected_pipe = std::mem::replace(&mut server, self.create_instance(false)?);
+                            let session = IpcSession::local(next_session.fetch_add(1, Ordering::Relaxed));
+                            let (reader, writer) = tokio::io::split(connected_pipe);
+                            let event_rx = event_tx.as_ref().map(|tx| tx.subscribe());
+                            tokio::spawn(serve_connection(reader, writer, session, handler.clone(), event_rx));
                         }
                         Err(e) => {
                             error!("Failed to accept pipe connection: {}", e);
                         }
                     }
                 }
-                
+
                 // Check for shutdown signal
                 _ = shutdown_rx.recv() => {
                     info!("Named pipe server shutting down");
@@ -114,68 +125,6 @@ impl WindowsPipeServer {
         Ok(())
     }
 
-    /// Handle a single pipe connection
-    async fn handle_connection<H: IpcHandler>(
-        pipe: NamedPipeServer,
-        handler: Arc<H>,
-    ) -> Result<()> {
-        let (reader, mut writer) = tokio::io::split(pipe);
-        let mut reader = BufReader::new(reader);
-        let mut line = String::new();
-
-        loop {
-            line.clear();
-            let bytes_read = reader.read_line(&mut line).await
-                .map_err(|e| DaemonError::IpcError(format!("Read error: {}", e)))?;
-
-            if bytes_read == 0 {
-                // Connection closed
-                break;
-            }
-
-            debug!("Received: {}", line.trim());
-
-            // Parse request
-            let response = match serde_json::from_str::<JsonRpcRequest>(&line) {
-                Ok(request) => {
-                    if request.jsonrpc != "2.0" {
-                        JsonRpcResponse::error(
-                            request.id,
-                            -32600,
-                            "Invalid Request: jsonrpc must be '2.0'".to_string(),
-                        )
-                    } else {
-                        match handler.handle(&request.method, request.params).await {
-                            Ok(result) => JsonRpcResponse::success(request.id, result),
-                            Err(msg) => JsonRpcResponse::error(request.id, -32000, msg),
-                        }
-                    }
-                }
-                Err(e) => {
-                    JsonRpcResponse::error(
-                        serde_json::Value::Null,
-                        -32700,
-                        format!("Parse error: {}", e),
-                    )
-                }
-            };
-
-            // Send response
-            let response_str = serde_json::to_string(&response)
-                .map_err(|e| DaemonError::IpcError(format!("Serialize error: {}", e)))?;
-
-            debug!("Sending: {}", response_str);
-            writer.write_all(response_str.as_bytes()).await
-                .map_err(|e| DaemonError::IpcError(format!("Write error: {}", e)))?;
-            writer.write_all(b"\n").await
-                .map_err(|e| DaemonError::IpcError(format!("Write error: {}", e)))?;
-            writer.flush().await
-                .map_err(|e| DaemonError::IpcError(format!("Flush error: {}", e)))?;
-        }
-
-        Ok(())
-    }
-
     /// Stop the named pipe server
     pub async fn stop(&mut self) {
         if let Some(tx) = self.shutdown_tx.take() {
@@ -217,7 +166,9 @@ impl WindowsPipeServer {
         Self { config }
     }
 
-    pub async fn start<H: crate::ipc::IpcHandler + 'static>(&mut self, _handler: H) -> crate::Result<()> {
+    pub fn set_event_sender(&mut self, _tx: tokio::sync::broadcast::Sender<String>) {}
+
+    pub async fn start<H: crate::ipc::SessionHandler + 'static>(&mut self, _handler: H) -> crate::Result<()> {
         Err(crate::DaemonError::IpcError(
             "Windows named pipes are only available on Windows".to_string()
         ))
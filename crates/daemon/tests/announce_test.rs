@@ -67,7 +67,7 @@ async fn test_relay_announce_when_enabled_before_connect() {
 
     // Connect — node.start() builds a standalone swarm, sets peer_id, then announces
     eprintln!("[test] connecting...");
-    svc.connect(ConnectParams { hops: None }).await.expect("connect");
+    svc.connect(ConnectParams { hops: None, ..Default::default() }).await.expect("connect");
     eprintln!("[test] connected, polling for announce...");
 
     let result = wait_for_relay_announce(&svc, Duration::from_secs(10)).await;
@@ -87,7 +87,7 @@ async fn test_relay_announce_when_enabled_after_connect() {
     let svc = DaemonService::new_with_keypair(&secret).expect("DaemonService::new_with_keypair");
 
     // Connect as client first
-    svc.connect(ConnectParams { hops: None }).await.expect("connect");
+    svc.connect(ConnectParams { hops: None, ..Default::default() }).await.expect("connect");
 
     let before = svc.status().await;
     eprintln!("[test] connected (client only): relay_secs={:?}", before.relay_announced_secs_ago);
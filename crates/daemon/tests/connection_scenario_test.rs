@@ -63,6 +63,8 @@ async fn test_02_two_node_exit_discovery() {
         capabilities: Capabilities::CLIENT | Capabilities::EXIT,
         listen_addr: "/ip4/127.0.0.1/tcp/44210".parse().unwrap(),
         bootstrap_peers: vec![],
+        // No upstream internet in CI; announce without capabilities
+        exit_self_test: false,
         ..Default::default()
     }).await.unwrap();
 
@@ -100,7 +102,7 @@ async fn test_02_two_node_exit_discovery() {
     tokio::time::sleep(Duration::from_secs(2)).await;
 
     // Connect B (triggers wait_for_exit + discover_exits)
-    let connect_result = svc_b.connect(ConnectParams { hops: None }).await;
+    let connect_result = svc_b.connect(ConnectParams { hops: None, ..Default::default() }).await;
     eprintln!("[t02] B connect result: {:?}", connect_result.is_ok());
 
     let exits = timeout(Duration::from_secs(30), async {
@@ -153,7 +155,7 @@ async fn test_03_two_node_relay_discovery() {
     }).await.unwrap();
 
     tokio::time::sleep(Duration::from_secs(2)).await;
-    let _ = svc_client.connect(ConnectParams { hops: None }).await;
+    let _ = svc_client.connect(ConnectParams { hops: None, ..Default::default() }).await;
 
     let peers = timeout(Duration::from_secs(20), async {
         loop {
@@ -0,0 +1,421 @@
+//! Abuse reporting and automatic throttling
+//!
+//! The exit keeps a log of request volume per destination, keyed by the
+//! SHA-256 of the hostname so the log never holds a plaintext destination.
+//! Entries idle for longer than `retention` are pruned, and the log holds
+//! at most `max_destinations` entries (the least recently seen go first).
+//!
+//! A pool sending more than `throttle_threshold` requests to one
+//! destination within `throttle_window` is throttled: its requests to that
+//! destination are refused for `throttle_duration`. Other pools and other
+//! destinations are unaffected. Per-pool counters only live for one window.
+//!
+//! Operators block a destination (by hostname or hash) or a pool outright
+//! with [`AbuseMonitor::block`]. Blocks are kept in memory only.
+//!
+//! The monitor is shared (`Arc`) between the handler and its owner, so it
+//! can be read and changed while the handler is busy with a request.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use craftnet_core::PublicKey;
+
+use crate::{ExitError, Result};
+
+/// SHA-256 of a destination hostname
+pub type DestinationHash = [u8; 32];
+
+/// Hash of `host` as the abuse log keys it (case and a trailing dot
+/// don't matter)
+pub fn hash_destination(host: &str) -> DestinationHash {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    Sha256::digest(host.as_bytes()).into()
+}
+
+/// Destination named by an operator: a 64-character hex hash as shown in
+/// the [`AbuseReport`], or a hostname
+pub fn parse_destination(destination: &str) -> DestinationHash {
+    let mut hash = [0u8; 32];
+    match hex::decode_to_slice(destination, &mut hash) {
+        Ok(()) => hash,
+        Err(_) => hash_destination(destination),
+    }
+}
+
+/// Thresholds and retention of the abuse log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AbuseConfig {
+    /// Requests one pool may send to one destination per window before it
+    /// is throttled (0 = never throttle)
+    pub throttle_threshold: u32,
+    /// Window the threshold applies to
+    pub throttle_window: Duration,
+    /// How long a throttled pool's requests to the destination are refused
+    pub throttle_duration: Duration,
+    /// Destinations not seen for this long are dropped from the log
+    pub retention: Duration,
+    /// Destinations the log holds at most
+    pub max_destinations: usize,
+}
+
+impl Default for AbuseConfig {
+    fn default() -> Self {
+        Self {
+            throttle_threshold: 300,
+            throttle_window: Duration::from_secs(60),
+            throttle_duration: Duration::from_secs(600),
+            retention: Duration::from_secs(24 * 3600),
+            max_destinations: 10_000,
+        }
+    }
+}
+
+/// What an operator blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlockTarget {
+    Destination(DestinationHash),
+    Pool(PublicKey),
+}
+
+/// Logged request volume of one destination
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DestinationVolume {
+    pub destination: DestinationHash,
+    /// Requests seen, refused ones included
+    pub requests: u64,
+    /// Requests refused by a block or throttle
+    pub refused: u64,
+    /// Time since the first request in the log
+    pub age: Duration,
+    /// Time since the latest request
+    pub idle: Duration,
+}
+
+/// A pool throttled for one destination
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThrottledPool {
+    pub pool: PublicKey,
+    pub destination: DestinationHash,
+    /// Time until the throttle lifts
+    pub remaining: Duration,
+}
+
+/// Snapshot of the abuse log, throttles and blocks
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AbuseReport {
+    /// Busiest destinations first
+    pub destinations: Vec<DestinationVolume>,
+    /// Destinations in the log (`destinations` may be cut short)
+    pub tracked_destinations: usize,
+    pub throttled: Vec<ThrottledPool>,
+    pub blocked_destinations: Vec<DestinationHash>,
+    pub blocked_pools: Vec<PublicKey>,
+}
+
+struct Volume {
+    requests: u64,
+    refused: u64,
+    first_seen: Instant,
+    last_seen: Instant,
+}
+
+struct Window {
+    started: Instant,
+    requests: u32,
+}
+
+#[derive(Default)]
+struct AbuseState {
+    volumes: HashMap<DestinationHash, Volume>,
+    windows: HashMap<(PublicKey, DestinationHash), Window>,
+    /// Throttled pool/destination pairs and when each throttle lifts
+    throttled: HashMap<(PublicKey, DestinationHash), Instant>,
+    blocked: HashSet<BlockTarget>,
+}
+
+/// Per-destination request log, throttles and operator blocks
+pub struct AbuseMonitor {
+    config: AbuseConfig,
+    state: Mutex<AbuseState>,
+}
+
+impl Default for AbuseMonitor {
+    fn default() -> Self {
+        Self::new(AbuseConfig::default())
+    }
+}
+
+impl AbuseMonitor {
+    pub fn new(config: AbuseConfig) -> Self {
+        Self { config, state: Mutex::default() }
+    }
+
+    /// Log a request from `pool` to `host` and refuse it if the pool or
+    /// destination is blocked, or the pool is (or now gets) throttled
+    pub(crate) fn admit(&self, pool: &PublicKey, host: &str) -> Result<()> {
+        self.admit_at(pool, host, Instant::now())
+    }
+
+    fn admit_at(&self, pool: &PublicKey, host: &str, now: Instant) -> Result<()> {
+        let destination = hash_destination(host);
+        let mut state = self.state.lock().unwrap();
+        let refusal = Self::refusal(&self.config, &mut state, pool, destination, host, now);
+
+        let volume = state.volumes.entry(destination).or_insert(Volume {
+            requests: 0,
+            refused: 0,
+            first_seen: now,
+            last_seen: now,
+        });
+        volume.requests += 1;
+        volume.last_seen = now;
+        if refusal.is_some() {
+            volume.refused += 1;
+        }
+        if state.volumes.len() > self.config.max_destinations {
+            let oldest = state.volumes.iter()
+                .min_by_key(|(_, v)| v.last_seen)
+                .map(|(d, _)| *d);
+            if let Some(oldest) = oldest {
+                state.volumes.remove(&oldest);
+            }
+        }
+
+        match refusal {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Why a request from `pool` to `destination` is refused, counting it
+    /// against the pool's window (None = admitted)
+    fn refusal(
+        config: &AbuseConfig,
+        state: &mut AbuseState,
+        pool: &PublicKey,
+        destination: DestinationHash,
+        host: &str,
+        now: Instant,
+    ) -> Option<ExitError> {
+        if state.blocked.contains(&BlockTarget::Pool(*pool)) {
+            return Some(ExitError::RateLimited("pool blocked by the exit operator".to_string()));
+        }
+        if state.blocked.contains(&BlockTarget::Destination(destination)) {
+            return Some(ExitError::BlockedDestination(format!("{} (blocked by the exit operator)", host)));
+        }
+        let key = (*pool, destination);
+        if let Some(&until) = state.throttled.get(&key) {
+            if until > now {
+                return Some(throttled(host, until - now));
+            }
+            state.throttled.remove(&key);
+        }
+        if config.throttle_threshold == 0 {
+            return None;
+        }
+
+        let window = state.windows.entry(key).or_insert(Window { started: now, requests: 0 });
+        if now.duration_since(window.started) >= config.throttle_window {
+            *window = Window { started: now, requests: 0 };
+        }
+        window.requests += 1;
+        if window.requests <= config.throttle_threshold {
+            return None;
+        }
+        state.windows.remove(&key);
+        state.throttled.insert(key, now + config.throttle_duration);
+        warn!(
+            "Throttling pool {} for destination {}: over {} requests in {:?}",
+            hex::encode(&pool[..8]),
+            hex::encode(&destination[..8]),
+            config.throttle_threshold,
+            config.throttle_window,
+        );
+        Some(throttled(host, config.throttle_duration))
+    }
+
+    /// Refuse every request to a destination, or from a pool. Returns false
+    /// if it was already blocked.
+    pub fn block(&self, target: BlockTarget) -> bool {
+        info!("Exit operator blocked {}", describe(&target));
+        self.state.lock().unwrap().blocked.insert(target)
+    }
+
+    /// Lift a block. Returns false if it wasn't blocked.
+    pub fn unblock(&self, target: BlockTarget) -> bool {
+        info!("Exit operator unblocked {}", describe(&target));
+        self.state.lock().unwrap().blocked.remove(&target)
+    }
+
+    /// The `limit` busiest destinations, current throttles and blocks
+    pub fn report(&self, limit: usize) -> AbuseReport {
+        self.report_at(limit, Instant::now())
+    }
+
+    fn report_at(&self, limit: usize, now: Instant) -> AbuseReport {
+        let state = self.state.lock().unwrap();
+        let mut destinations: Vec<DestinationVolume> = state.volumes.iter()
+            .map(|(destination, v)| DestinationVolume {
+                destination: *destination,
+                requests: v.requests,
+                refused: v.refused,
+                age: now.saturating_duration_since(v.first_seen),
+                idle: now.saturating_duration_since(v.last_seen),
+            })
+            .collect();
+        destinations.sort_by(|a, b| b.requests.cmp(&a.requests).then(a.destination.cmp(&b.destination)));
+        destinations.truncate(limit);
+
+        let mut throttled: Vec<ThrottledPool> = state.throttled.iter()
+            .filter(|(_, until)| **until > now)
+            .map(|((pool, destination), until)| ThrottledPool {
+                pool: *pool,
+                destination: *destination,
+                remaining: *until - now,
+            })
+            .collect();
+        throttled.sort_by(|a, b| b.remaining.cmp(&a.remaining));
+
+        let mut blocked_destinations = Vec::new();
+        let mut blocked_pools = Vec::new();
+        for target in &state.blocked {
+            match target {
+                BlockTarget::Destination(d) => blocked_destinations.push(*d),
+                BlockTarget::Pool(p) => blocked_pools.push(*p),
+            }
+        }
+        blocked_destinations.sort();
+        blocked_pools.sort();
+
+        AbuseReport {
+            destinations,
+            tracked_destinations: state.volumes.len(),
+            throttled,
+            blocked_destinations,
+            blocked_pools,
+        }
+    }
+
+    /// Drop destinations past retention, finished windows and lifted throttles
+    pub fn prune(&self) {
+        self.prune_at(Instant::now());
+    }
+
+    fn prune_at(&self, now: Instant) {
+        let config = &self.config;
+        let mut state = self.state.lock().unwrap();
+        state.volumes.retain(|_, v| now.saturating_duration_since(v.last_seen) < config.retention);
+        state.windows.retain(|_, w| now.saturating_duration_since(w.started) < config.throttle_window);
+        state.throttled.retain(|_, until| *until > now);
+    }
+}
+
+fn throttled(host: &str, remaining: Duration) -> ExitError {
+    ExitError::RateLimited(format!(
+        "too many requests to {}, throttled for {}s",
+        host,
+        remaining.as_secs().max(1),
+    ))
+}
+
+fn describe(target: &BlockTarget) -> String {
+    match target {
+        BlockTarget::Destination(d) => format!("destination {}", hex::encode(&d[..8])),
+        BlockTarget::Pool(p) => format!("pool {}", hex::encode(&p[..8])),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor(threshold: u32) -> AbuseMonitor {
+        AbuseMonitor::new(AbuseConfig {
+            throttle_threshold: threshold,
+            throttle_window: Duration::from_secs(60),
+            throttle_duration: Duration::from_secs(300),
+            retention: Duration::from_secs(3600),
+            max_destinations: 2,
+        })
+    }
+
+    #[test]
+    fn test_pool_hammering_one_destination_is_throttled() {
+        let monitor = monitor(3);
+        let t0 = Instant::now();
+        for _ in 0..3 {
+            monitor.admit_at(&[1; 32], "api.example.com", t0).unwrap();
+        }
+        assert!(matches!(monitor.admit_at(&[1; 32], "api.example.com", t0), Err(ExitError::RateLimited(_))));
+        // Other pools and other destinations still get through
+        monitor.admit_at(&[2; 32], "api.example.com", t0).unwrap();
+        monitor.admit_at(&[1; 32], "other.example.com", t0).unwrap();
+
+        let report = monitor.report_at(10, t0);
+        assert_eq!(report.throttled.len(), 1);
+        assert_eq!(report.throttled[0].pool, [1; 32]);
+        assert_eq!(report.throttled[0].destination, hash_destination("API.example.com."));
+        assert_eq!(report.destinations[0].requests, 5);
+        assert_eq!(report.destinations[0].refused, 1);
+
+        // The throttle lifts after its duration; the window starts afresh
+        let later = t0 + Duration::from_secs(301);
+        monitor.admit_at(&[1; 32], "api.example.com", later).unwrap();
+        assert!(monitor.report_at(10, later).throttled.is_empty());
+    }
+
+    #[test]
+    fn test_requests_spread_over_windows_are_not_throttled() {
+        let monitor = monitor(2);
+        let t0 = Instant::now();
+        for i in 0..6 {
+            let now = t0 + Duration::from_secs(30 * i);
+            monitor.admit_at(&[1; 32], "example.com", now).unwrap();
+        }
+        assert!(monitor.report(10).throttled.is_empty());
+    }
+
+    #[test]
+    fn test_operator_blocks() {
+        let monitor = monitor(0);
+        assert!(monitor.block(BlockTarget::Destination(parse_destination("bad.example"))));
+        assert!(!monitor.block(BlockTarget::Destination(parse_destination(&hex::encode(hash_destination("bad.example"))))));
+        assert!(monitor.block(BlockTarget::Pool([9; 32])));
+
+        assert!(matches!(monitor.admit(&[1; 32], "bad.example"), Err(ExitError::BlockedDestination(_))));
+        assert!(matches!(monitor.admit(&[9; 32], "good.example"), Err(ExitError::RateLimited(_))));
+        monitor.admit(&[1; 32], "good.example").unwrap();
+
+        let report = monitor.report(10);
+        assert_eq!(report.blocked_destinations, vec![hash_destination("bad.example")]);
+        assert_eq!(report.blocked_pools, vec![[9; 32]]);
+
+        assert!(monitor.unblock(BlockTarget::Pool([9; 32])));
+        assert!(!monitor.unblock(BlockTarget::Pool([9; 32])));
+        monitor.admit(&[9; 32], "good.example").unwrap();
+    }
+
+    #[test]
+    fn test_log_is_bounded_and_pruned() {
+        let monitor = monitor(0);
+        let t0 = Instant::now();
+        monitor.admit_at(&[1; 32], "a.example", t0).unwrap();
+        monitor.admit_at(&[1; 32], "b.example", t0 + Duration::from_secs(1)).unwrap();
+        monitor.admit_at(&[1; 32], "c.example", t0 + Duration::from_secs(2)).unwrap();
+
+        // Over `max_destinations`, the least recently seen is evicted
+        let report = monitor.report_at(10, t0 + Duration::from_secs(2));
+        assert_eq!(report.tracked_destinations, 2);
+        assert!(report.destinations.iter().all(|d| d.destination != hash_destination("a.example")));
+
+        monitor.prune_at(t0 + Duration::from_secs(3601));
+        assert_eq!(monitor.report_at(10, t0 + Duration::from_secs(3601)).tracked_destinations, 1);
+        monitor.prune_at(t0 + Duration::from_secs(3603));
+        assert_eq!(monitor.report(10).tracked_destinations, 0);
+    }
+}
@@ -0,0 +1,175 @@
+//! Subscription-based access control
+//!
+//! With a settlement client, the exit looks up the subscription of the pool
+//! each request is billed to (`SettlementClient::get_subscription`) and
+//! applies the [`TierLimits`] of its tier. Pools without an active
+//! subscription get the free-tier limits, or are refused when
+//! `require_subscription` is set.
+//!
+//! Lookups are cached per pool for `subscription_cache_ttl`. The cache is
+//! read synchronously while shards are collected (to reject oversized or
+//! excess assemblies before buffering them) and filled when a completed
+//! request is verified, so a pool's first request is only checked against
+//! the global limits until its assembly completes.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use craftnet_core::{Clock, PublicKey, SubscriptionTier, SystemClock};
+use craftnet_settlement::SettlementClient;
+
+/// Per-pool limits of one subscription tier
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TierLimits {
+    /// Largest request body (bytes)
+    pub max_request_size: usize,
+    /// Requests a pool may have collecting or queued at once
+    pub max_concurrent_requests: usize,
+    /// Tunnels a pool may have open at once
+    pub max_tunnels: usize,
+}
+
+/// Limits for every tier, free (no active subscription) included
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessLimits {
+    pub free: TierLimits,
+    pub basic: TierLimits,
+    pub standard: TierLimits,
+    pub premium: TierLimits,
+    pub ultra: TierLimits,
+}
+
+impl Default for AccessLimits {
+    fn default() -> Self {
+        Self {
+            free: TierLimits { max_request_size: 1024 * 1024, max_concurrent_requests: 10, max_tunnels: 5 },
+            basic: TierLimits { max_request_size: 4 * 1024 * 1024, max_concurrent_requests: 25, max_tunnels: 10 },
+            standard: TierLimits { max_request_size: 10 * 1024 * 1024, max_concurrent_requests: 50, max_tunnels: 25 },
+            premium: TierLimits { max_request_size: 10 * 1024 * 1024, max_concurrent_requests: 100, max_tunnels: 50 },
+            ultra: TierLimits { max_request_size: 10 * 1024 * 1024, max_concurrent_requests: 100, max_tunnels: 50 },
+        }
+    }
+}
+
+impl TierLimits {
+    /// Field-wise minimum of both limits
+    pub(crate) fn capped_by(self, other: TierLimits) -> TierLimits {
+        TierLimits {
+            max_request_size: self.max_request_size.min(other.max_request_size),
+            max_concurrent_requests: self.max_concurrent_requests.min(other.max_concurrent_requests),
+            max_tunnels: self.max_tunnels.min(other.max_tunnels),
+        }
+    }
+}
+
+impl AccessLimits {
+    /// Limits of `tier` (None = free)
+    pub fn for_tier(&self, tier: Option<SubscriptionTier>) -> TierLimits {
+        match tier {
+            None => self.free,
+            Some(SubscriptionTier::Basic) => self.basic,
+            Some(SubscriptionTier::Standard) => self.standard,
+            Some(SubscriptionTier::Premium) => self.premium,
+            Some(SubscriptionTier::Ultra) => self.ultra,
+        }
+    }
+}
+
+struct CachedSubscription {
+    /// Tier and expiry (unix seconds) of the pool's subscription, if any
+    subscription: Option<(SubscriptionTier, u64)>,
+    fetched_at: Instant,
+}
+
+/// Subscription lookups per pool, valid for a TTL
+pub(crate) struct SubscriptionCache {
+    ttl: Duration,
+    entries: HashMap<PublicKey, CachedSubscription>,
+}
+
+impl SubscriptionCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self { ttl, entries: HashMap::new() }
+    }
+
+    /// Cached tier of `pool`: `Some(None)` for a pool known to have no
+    /// active subscription, `None` if not cached (or stale)
+    pub fn get(&self, pool: &PublicKey) -> Option<Option<SubscriptionTier>> {
+        let entry = self.entries.get(pool)?;
+        if entry.fetched_at.elapsed() >= self.ttl {
+            return None;
+        }
+        Some(entry.subscription.and_then(|(tier, expires_at)| (expires_at > SystemClock.unix_now()).then_some(tier)))
+    }
+
+    /// Record a lookup result (`(tier, expires_at)`, None = no subscription)
+    pub fn insert(&mut self, pool: PublicKey, subscription: Option<(SubscriptionTier, u64)>) {
+        self.entries.insert(pool, CachedSubscription { subscription, fetched_at: Instant::now() });
+    }
+
+    /// Active tier of `pool`, from the cache or the settlement layer.
+    /// Failed lookups are not cached.
+    pub async fn lookup(
+        &mut self,
+        client: &SettlementClient,
+        pool: PublicKey,
+    ) -> craftnet_settlement::Result<Option<SubscriptionTier>> {
+        if let Some(tier) = self.get(&pool) {
+            return Ok(tier);
+        }
+        let subscription = client
+            .get_subscription(pool)
+            .await?
+            .map(|(tier, _start, expires_at)| (tier, expires_at));
+        self.insert(pool, subscription);
+        Ok(self.get(&pool).flatten())
+    }
+
+    /// Drop stale entries
+    pub fn prune(&mut self) {
+        let ttl = self.ttl;
+        self.entries.retain(|_, e| e.fetched_at.elapsed() < ttl);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use craftnet_settlement::SettlementConfig;
+
+    #[tokio::test]
+    async fn test_lookup_caches_tier() {
+        let client = SettlementClient::new(SettlementConfig::mock(), [0; 32]);
+        client.add_mock_subscription([1; 32], SubscriptionTier::Premium, 1_000).unwrap();
+        let expired = SystemClock.unix_now() - 10;
+        client.add_mock_subscription_with_expiry([2; 32], SubscriptionTier::Basic, 1_000, expired - 100, expired).unwrap();
+
+        let mut cache = SubscriptionCache::new(Duration::from_secs(60));
+        assert_eq!(cache.get(&[1; 32]), None);
+        assert_eq!(cache.lookup(&client, [1; 32]).await.unwrap(), Some(SubscriptionTier::Premium));
+        assert_eq!(cache.lookup(&client, [2; 32]).await.unwrap(), None);
+        assert_eq!(cache.lookup(&client, [3; 32]).await.unwrap(), None);
+
+        // Served from the cache within the TTL
+        assert_eq!(cache.get(&[1; 32]), Some(Some(SubscriptionTier::Premium)));
+        assert_eq!(cache.get(&[3; 32]), Some(None));
+        assert_eq!(cache.entries.len(), 3);
+
+        let mut stale = SubscriptionCache::new(Duration::ZERO);
+        stale.insert([1; 32], Some((SubscriptionTier::Basic, u64::MAX)));
+        assert_eq!(stale.get(&[1; 32]), None);
+        stale.prune();
+        assert_eq!(stale.entries.len(), 0);
+    }
+
+    #[test]
+    fn test_tier_limits() {
+        let limits = AccessLimits::default();
+        assert_eq!(limits.for_tier(None), limits.free);
+        assert!(limits.for_tier(Some(SubscriptionTier::Basic)).max_request_size > limits.free.max_request_size);
+        assert!(
+            limits.for_tier(Some(SubscriptionTier::Premium)).max_concurrent_requests
+                > limits.for_tier(Some(SubscriptionTier::Basic)).max_concurrent_requests
+        );
+    }
+}
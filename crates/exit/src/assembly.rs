@@ -0,0 +1,327 @@
+//! Streaming request reassembly
+//!
+//! Shards arrive stripe by stripe (one stripe = one erasure-coded chunk).
+//! Instead of holding every shard until the whole assembly is present, a
+//! `StreamingAssembly` decodes each stripe as soon as `DATA_SHARDS` of its
+//! shards are in and drops the shard payloads. Decoded stripes stay in
+//! memory until the assembly holds more than the spill threshold, after
+//! which they are written to a temp file at their final offset.
+//!
+//! Execution still starts only once every stripe is decoded: the
+//! ExitPayload is a single authenticated ciphertext, so no byte of the HTTP
+//! body or tunnel data can be trusted before the whole payload decrypts.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use craftnet_erasure::chunker::CHUNK_SIZE;
+use craftnet_erasure::{ErasureCoder, DATA_SHARDS, TOTAL_SHARDS};
+use rand::RngCore;
+use tracing::debug;
+
+use crate::{ExitError, Result};
+
+/// Where decoded stripes are kept
+enum ChunkStore {
+    Memory(BTreeMap<u16, Vec<u8>>),
+    Disk { file: File, path: PathBuf },
+}
+
+/// Incrementally decoded assembly
+pub(crate) struct StreamingAssembly {
+    total_chunks: u16,
+    /// Shard slots for stripes not yet decoded
+    stripes: HashMap<u16, Vec<Option<Vec<u8>>>>,
+    /// Stripes already decoded (late shards for them are dropped)
+    decoded: HashSet<u16>,
+    /// Length of the last stripe once decoded (it may be short)
+    last_len: Option<usize>,
+    store: ChunkStore,
+    /// Bytes held in memory (pending shards + in-memory stripes)
+    buffered: usize,
+    spill_threshold: usize,
+    spill_dir: PathBuf,
+}
+
+impl StreamingAssembly {
+    pub(crate) fn new(total_chunks: u16, spill_threshold: usize, spill_dir: PathBuf) -> Self {
+        Self {
+            total_chunks,
+            stripes: HashMap::new(),
+            decoded: HashSet::new(),
+            last_len: None,
+            store: ChunkStore::Memory(BTreeMap::new()),
+            buffered: 0,
+            spill_threshold,
+            spill_dir,
+        }
+    }
+
+    /// Add one shard, decoding its stripe once enough shards are present.
+    pub(crate) fn add_shard(
+        &mut self,
+        coder: &ErasureCoder,
+        chunk_index: u16,
+        shard_index: u8,
+        payload: Vec<u8>,
+    ) -> Result<()> {
+        if chunk_index >= self.total_chunks || shard_index as usize >= TOTAL_SHARDS {
+            return Err(ExitError::InvalidRequest(format!(
+                "shard ({}, {}) out of range for {} chunks",
+                chunk_index, shard_index, self.total_chunks,
+            )));
+        }
+        if self.decoded.contains(&chunk_index) {
+            return Ok(());
+        }
+
+        let slots = self.stripes.entry(chunk_index).or_insert_with(|| vec![None; TOTAL_SHARDS]);
+        let slot = &mut slots[shard_index as usize];
+        if let Some(old) = slot.take() {
+            self.buffered -= old.len();
+        }
+        self.buffered += payload.len();
+        *slot = Some(payload);
+
+        if slots.iter().filter(|s| s.is_some()).count() < DATA_SHARDS {
+            return Ok(());
+        }
+
+        // Stripe decodable — reconstruct it and free the shard payloads
+        let mut slots = self.stripes.remove(&chunk_index).unwrap();
+        let shard_bytes: usize = slots.iter().flatten().map(|s| s.len()).sum();
+        self.buffered -= shard_bytes;
+        let shard_size = slots.iter().flatten().next().map_or(0, |s| s.len());
+        let chunk = coder
+            .decode(&mut slots, shard_size * DATA_SHARDS)
+            .map_err(|e| ExitError::ErasureDecodeError(e.to_string()))?;
+
+        self.store_chunk(chunk_index, chunk)?;
+        self.decoded.insert(chunk_index);
+        Ok(())
+    }
+
+    /// Every stripe decoded
+    pub(crate) fn is_complete(&self) -> bool {
+        self.decoded.len() == self.total_chunks as usize
+    }
+
+    /// Shards received so far (decoded stripes count as `DATA_SHARDS`)
+    pub(crate) fn shard_count(&self) -> usize {
+        let pending: usize = self.stripes.values()
+            .map(|slots| slots.iter().filter(|s| s.is_some()).count())
+            .sum();
+        pending + self.decoded.len() * DATA_SHARDS
+    }
+
+    /// Bytes currently held in memory
+    pub(crate) fn buffered_bytes(&self) -> usize {
+        self.buffered
+    }
+
+    /// Whether decoded stripes have been spilled to disk
+    pub(crate) fn is_spilled(&self) -> bool {
+        matches!(self.store, ChunkStore::Disk { .. })
+    }
+
+    /// Concatenate all decoded stripes (framed payload, padding included)
+    pub(crate) fn into_data(mut self) -> Result<Vec<u8>> {
+        if !self.is_complete() {
+            return Err(ExitError::InsufficientShards {
+                have: self.decoded.len(),
+                need: self.total_chunks as usize,
+            });
+        }
+        let total_len = (self.total_chunks as usize - 1) * CHUNK_SIZE + self.last_len.unwrap_or(0);
+
+        match self.store {
+            ChunkStore::Memory(ref mut chunks) => {
+                let mut data = Vec::with_capacity(total_len);
+                for chunk in std::mem::take(chunks).into_values() {
+                    data.extend_from_slice(&chunk);
+                }
+                Ok(data)
+            }
+            ChunkStore::Disk { ref mut file, .. } => {
+                let mut data = Vec::with_capacity(total_len);
+                file.seek(SeekFrom::Start(0)).map_err(spill_err)?;
+                file.by_ref().take(total_len as u64).read_to_end(&mut data).map_err(spill_err)?;
+                if data.len() != total_len {
+                    return Err(ExitError::ErasureDecodeError(format!(
+                        "spill file truncated: {} < {}", data.len(), total_len,
+                    )));
+                }
+                Ok(data)
+            }
+        }
+    }
+
+    fn store_chunk(&mut self, chunk_index: u16, chunk: Vec<u8>) -> Result<()> {
+        // Every stripe but the last is exactly CHUNK_SIZE, so stripes can be
+        // placed at fixed offsets in the spill file
+        if chunk_index + 1 == self.total_chunks {
+            self.last_len = Some(chunk.len());
+        } else if chunk.len() != CHUNK_SIZE {
+            return Err(ExitError::ErasureDecodeError(format!(
+                "stripe {} decoded to {} bytes, expected {}", chunk_index, chunk.len(), CHUNK_SIZE,
+            )));
+        }
+
+        let over_threshold = self.buffered + chunk.len() > self.spill_threshold;
+        if over_threshold && matches!(self.store, ChunkStore::Memory(_)) {
+            let empty = ChunkStore::Memory(BTreeMap::new());
+            if let ChunkStore::Memory(chunks) = std::mem::replace(&mut self.store, empty) {
+                self.spill(chunks)?;
+            }
+        }
+
+        match self.store {
+            ChunkStore::Memory(ref mut chunks) => {
+                self.buffered += chunk.len();
+                chunks.insert(chunk_index, chunk);
+            }
+            ChunkStore::Disk { ref mut file, .. } => {
+                write_at(file, chunk_index, &chunk)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Move in-memory stripes to a temp file; later stripes go straight to disk.
+    fn spill(&mut self, chunks: BTreeMap<u16, Vec<u8>>) -> Result<()> {
+        let mut name = [0u8; 8];
+        rand::thread_rng().fill_bytes(&mut name);
+        let path = self.spill_dir.join(format!("craftnet-exit-assembly-{}.part", hex::encode(name)));
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .map_err(spill_err)?;
+
+        for (index, chunk) in &chunks {
+            if let Err(e) = write_at(&mut file, *index, chunk) {
+                let _ = std::fs::remove_file(&path);
+                return Err(e);
+            }
+            self.buffered -= chunk.len();
+        }
+        debug!("Assembly spilled {} stripes to {}", chunks.len(), path.display());
+        self.store = ChunkStore::Disk { file, path };
+        Ok(())
+    }
+}
+
+impl Drop for StreamingAssembly {
+    fn drop(&mut self) {
+        if let ChunkStore::Disk { ref path, .. } = self.store {
+            remove_spill_file(path);
+        }
+    }
+}
+
+fn write_at(file: &mut File, chunk_index: u16, chunk: &[u8]) -> Result<()> {
+    file.seek(SeekFrom::Start(chunk_index as u64 * CHUNK_SIZE as u64)).map_err(spill_err)?;
+    file.write_all(chunk).map_err(spill_err)
+}
+
+fn remove_spill_file(path: &Path) {
+    if let Err(e) = std::fs::remove_file(path) {
+        debug!("Failed to remove spill file {}: {}", path.display(), e);
+    }
+}
+
+fn spill_err(e: std::io::Error) -> ExitError {
+    ExitError::ErasureDecodeError(format!("assembly spill I/O: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use craftnet_erasure::chunker::chunk_and_encode;
+
+    fn feed(assembly: &mut StreamingAssembly, data: &[u8], skip_shard: usize) {
+        let coder = ErasureCoder::new().unwrap();
+        for (chunk_index, shards) in chunk_and_encode(data).unwrap() {
+            for (shard_index, payload) in shards.into_iter().enumerate() {
+                if shard_index == skip_shard {
+                    continue;
+                }
+                assembly.add_shard(&coder, chunk_index, shard_index as u8, payload).unwrap();
+            }
+        }
+    }
+
+    fn test_data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_stripes_decode_as_they_complete() {
+        let data = test_data(CHUNK_SIZE * 2 + 100);
+        let chunks = chunk_and_encode(&data).unwrap();
+        let coder = ErasureCoder::new().unwrap();
+        let mut assembly = StreamingAssembly::new(chunks.len() as u16, usize::MAX, std::env::temp_dir());
+
+        // Complete stripe 1 first: its shards are freed immediately
+        for (shard_index, payload) in chunks[1].1.iter().take(DATA_SHARDS).enumerate() {
+            assembly.add_shard(&coder, 1, shard_index as u8, payload.clone()).unwrap();
+        }
+        assert_eq!(assembly.buffered_bytes(), CHUNK_SIZE);
+        assert!(!assembly.is_complete());
+
+        for (chunk_index, shards) in [&chunks[0], &chunks[2]].into_iter().map(|(i, s)| (*i, s)) {
+            for (shard_index, payload) in shards.iter().enumerate().skip(2) {
+                assembly.add_shard(&coder, chunk_index, shard_index as u8, payload.clone()).unwrap();
+            }
+        }
+        assert!(assembly.is_complete());
+        assert!(!assembly.is_spilled());
+
+        let out = assembly.into_data().unwrap();
+        assert_eq!(&out[..data.len()], &data[..]);
+    }
+
+    #[test]
+    fn test_spill_to_disk_over_threshold() {
+        let data = test_data(CHUNK_SIZE * 4 + 17);
+        let dir = std::env::temp_dir().join(format!("craftnet-test-spill-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let total_chunks = data.len().div_ceil(CHUNK_SIZE) as u16;
+        let mut assembly = StreamingAssembly::new(total_chunks, CHUNK_SIZE * 2, dir.clone());
+        feed(&mut assembly, &data, 0);
+
+        assert!(assembly.is_spilled());
+        assert!(assembly.buffered_bytes() <= CHUNK_SIZE * 2);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        let out = assembly.into_data().unwrap();
+        assert_eq!(&out[..data.len()], &data[..]);
+        // Spill file removed when the assembly is dropped
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        let _ = std::fs::remove_dir(&dir);
+    }
+
+    #[test]
+    fn test_out_of_range_shard_rejected() {
+        let coder = ErasureCoder::new().unwrap();
+        let mut assembly = StreamingAssembly::new(1, usize::MAX, std::env::temp_dir());
+        assert!(assembly.add_shard(&coder, 1, 0, vec![0u8; 8]).is_err());
+        assert!(assembly.add_shard(&coder, 0, TOTAL_SHARDS as u8, vec![0u8; 8]).is_err());
+    }
+
+    #[test]
+    fn test_incomplete_assembly_has_no_data() {
+        let data = test_data(100);
+        let coder = ErasureCoder::new().unwrap();
+        let (_, shards) = chunk_and_encode(&data).unwrap().remove(0);
+        let mut assembly = StreamingAssembly::new(1, usize::MAX, std::env::temp_dir());
+        assembly.add_shard(&coder, 0, 0, shards[0].clone()).unwrap();
+
+        assert_eq!(assembly.shard_count(), 1);
+        assert!(assembly.into_data().is_err());
+    }
+}
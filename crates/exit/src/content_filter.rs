@@ -0,0 +1,190 @@
+//! Pluggable content filtering
+//!
+//! Operators who must filter (e.g. against a hash list of known illegal
+//! material) install a [`ContentFilter`] with
+//! [`ExitHandler::set_content_filter`](crate::ExitHandler::set_content_filter).
+//! The default is [`NoopFilter`], which allows everything.
+//!
+//! Request URLs (and tunnel hosts) are checked before anything goes
+//! upstream. Response bodies are checked by a per-response [`BodyScanner`]
+//! that sees the body chunk by chunk as it is read, so a filter never forces
+//! the exit to hold a whole body in memory:
+//!
+//! - Buffered requests scan each chunk as it arrives and refuse the request
+//!   as soon as a chunk is blocked; `finish` runs before the response is
+//!   signed. Cached responses are rescanned on every hit.
+//! - Streamed requests scan each chunk before the segment holding it is
+//!   sent, and `finish` runs before the last segment goes out. A block ends
+//!   the stream with an Error segment, so a client never gets an End for
+//!   filtered content, but segments sent before the block have been
+//!   delivered: a filter that only decides on the whole body withholds just
+//!   the final segment.
+//!
+//! Tunnel and WebSocket payloads are not scanned; only their destination is.
+
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+
+use crate::{ExitError, Result};
+
+/// Outcome of a filter check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterVerdict {
+    Allow,
+    /// Refuse, with the reason given to the client
+    Block(String),
+}
+
+impl FilterVerdict {
+    /// `Err(ContentFiltered)` for a block
+    pub(crate) fn into_result(self) -> Result<()> {
+        match self {
+            FilterVerdict::Allow => Ok(()),
+            FilterVerdict::Block(reason) => Err(ExitError::ContentFiltered(reason)),
+        }
+    }
+}
+
+/// Checks request URLs and response bodies at the exit
+#[async_trait]
+pub trait ContentFilter: Send + Sync {
+    /// Check a request URL (or `host:port` of a tunnel) before it goes
+    /// upstream
+    async fn check_request(&self, url: &str) -> FilterVerdict;
+
+    /// Scanner for the body of the response to `url` (None = don't scan it)
+    fn body_scanner(&self, url: &str) -> Option<Box<dyn BodyScanner>>;
+}
+
+/// Checks one response body, chunk by chunk
+#[async_trait]
+pub trait BodyScanner: Send {
+    /// Check the next chunk of the body
+    async fn scan(&mut self, chunk: &[u8]) -> FilterVerdict;
+
+    /// Check the body as a whole, after its last chunk
+    async fn finish(&mut self) -> FilterVerdict;
+}
+
+/// Scan a body held in memory (e.g. from the response cache) in one go
+pub(crate) async fn scan_body(filter: &dyn ContentFilter, url: &str, body: &[u8]) -> Result<()> {
+    let Some(mut scanner) = filter.body_scanner(url) else {
+        return Ok(());
+    };
+    scanner.scan(body).await.into_result()?;
+    scanner.finish().await.into_result()
+}
+
+/// Allows everything
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopFilter;
+
+#[async_trait]
+impl ContentFilter for NoopFilter {
+    async fn check_request(&self, _url: &str) -> FilterVerdict {
+        FilterVerdict::Allow
+    }
+
+    fn body_scanner(&self, _url: &str) -> Option<Box<dyn BodyScanner>> {
+        None
+    }
+}
+
+/// Example filter: blocks URLs containing a listed pattern and bodies whose
+/// SHA-256 is on a hash list.
+///
+/// Bodies are hashed incrementally, so only the digest state is kept per
+/// response.
+#[derive(Debug, Clone, Default)]
+pub struct BlocklistFilter {
+    url_patterns: Vec<String>,
+    body_hashes: HashSet<[u8; 32]>,
+}
+
+impl BlocklistFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Block URLs containing `pattern` (case-insensitive)
+    pub fn block_url_pattern(mut self, pattern: &str) -> Self {
+        self.url_patterns.push(pattern.to_ascii_lowercase());
+        self
+    }
+
+    /// Block bodies with this SHA-256
+    pub fn block_body_hash(mut self, hash: [u8; 32]) -> Self {
+        self.body_hashes.insert(hash);
+        self
+    }
+}
+
+#[async_trait]
+impl ContentFilter for BlocklistFilter {
+    async fn check_request(&self, url: &str) -> FilterVerdict {
+        let url = url.to_ascii_lowercase();
+        match self.url_patterns.iter().find(|p| url.contains(p.as_str())) {
+            Some(_) => FilterVerdict::Block("destination is on the exit's blocklist".to_string()),
+            None => FilterVerdict::Allow,
+        }
+    }
+
+    fn body_scanner(&self, _url: &str) -> Option<Box<dyn BodyScanner>> {
+        if self.body_hashes.is_empty() {
+            return None;
+        }
+        Some(Box::new(HashScanner { hasher: Sha256::new(), blocked: self.body_hashes.clone() }))
+    }
+}
+
+struct HashScanner {
+    hasher: Sha256,
+    blocked: HashSet<[u8; 32]>,
+}
+
+#[async_trait]
+impl BodyScanner for HashScanner {
+    async fn scan(&mut self, chunk: &[u8]) -> FilterVerdict {
+        self.hasher.update(chunk);
+        FilterVerdict::Allow
+    }
+
+    async fn finish(&mut self) -> FilterVerdict {
+        let hash: [u8; 32] = std::mem::take(&mut self.hasher).finalize().into();
+        if self.blocked.contains(&hash) {
+            FilterVerdict::Block("content is on the exit's blocklist".to_string())
+        } else {
+            FilterVerdict::Allow
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_blocklist_filter() {
+        let filter = BlocklistFilter::new()
+            .block_url_pattern("Bad.Example")
+            .block_body_hash(Sha256::digest(b"forbidden body").into());
+
+        assert_eq!(filter.check_request("https://good.example/x").await, FilterVerdict::Allow);
+        assert!(matches!(filter.check_request("https://www.bad.example/").await, FilterVerdict::Block(_)));
+
+        // The hash is taken over the whole body, however it is chunked
+        let mut scanner = filter.body_scanner("https://good.example/").unwrap();
+        assert_eq!(scanner.scan(b"forbidden ").await, FilterVerdict::Allow);
+        assert_eq!(scanner.scan(b"body").await, FilterVerdict::Allow);
+        assert!(matches!(scanner.finish().await, FilterVerdict::Block(_)));
+
+        let mut scanner = filter.body_scanner("https://good.example/").unwrap();
+        scanner.scan(b"fine body").await;
+        assert_eq!(scanner.finish().await, FilterVerdict::Allow);
+
+        assert!(BlocklistFilter::new().body_scanner("https://good.example/").is_none());
+        assert!(NoopFilter.body_scanner("https://good.example/").is_none());
+    }
+}
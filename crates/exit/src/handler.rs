@@ -2,31 +2,53 @@
 //!
 //! Manages the complete request/response lifecycle:
 //! 1. Decrypt routing_tag to get assembly_id
-//! 2. Group shards by assembly_id
-//! 3. Reconstruct and decrypt ExitPayload
-//! 4. Execute HTTP request or tunnel connection
-//! 5. Create response shards with onion routing via LeaseSet
-
-use std::collections::{BTreeMap, HashMap};
+//! 2. Group shards by assembly_id, decoding each erasure stripe as soon as
+//!    enough of its shards arrive (see `assembly`)
+//! 3. Reconstruct and decrypt ExitPayload, then verify the pool's
+//!    subscription and apply its tier's limits (see `access`)
+//! 4. Execute HTTP request or tunnel connection; a coalesced request runs
+//!    each of its sub-requests and answers with one combined response
+//! 5. Create response shards with onion routing via LeaseSet; streamed
+//!    requests get one assembly per body segment (see `stream`)
+
+use std::collections::HashMap;
 use std::net::IpAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use sha2::{Sha256, Digest};
 use tracing::{debug, info, warn};
 
 use craftnet_core::{
-    Shard, Id, PublicKey, ExitPayload,
-    TunnelMetadata, PAYLOAD_MODE_TUNNEL,
+    Shard, Id, PublicKey, ExitPayload, PayloadCompression, ExitCapabilities,
+    TunnelMetadata, PAYLOAD_MODE_HTTP, PAYLOAD_MODE_TUNNEL, PAYLOAD_MODE_HTTP_STREAM, PAYLOAD_MODE_HTTP_BATCH,
+    PAYLOAD_MODE_WEBSOCKET, WsAction, WsReply, WsRequest,
+    BatchRequest, BatchResponse, SubResponse, SubResult, BATCH_RESPONSE_HEADER, MAX_BATCH_REQUESTS,
+    TAG_FLAG_ACCEPT_ZSTD, TAG_FLAG_ZSTD, EXIT_SIGNATURE_HEADER, EXIT_ERROR_HEADER, sign_exit_response,
+    SubscriptionTier, QuotaIssuer, QuotaIssueRequest, QuotaTerms, QuotaToken, QUOTA_ISSUE_URL,
+    EXIT_PROBE_URL, HopRole, HopTiming, seal_hop_timing, trace_now_ms, MAX_RESPONSE_TRACE_ENTRIES,
 };
 use craftec_crypto::{SigningKeypair, EncryptionKeypair};
-use craftnet_core::onion_crypto::{decrypt_routing_tag, decrypt_exit_payload, build_onion_header, encrypt_routing_tag};
+use craftnet_core::onion_crypto::{decrypt_routing_tag, decrypt_exit_payload, build_onion_header, encrypt_routing_tag_with_flags};
 use craftnet_core::OnionSettlement;
 use craftnet_erasure::ErasureCoder;
-use craftnet_erasure::chunker::{chunk_and_encode, reassemble};
+use craftnet_erasure::chunker::{chunk_and_encode, CHUNK_SIZE};
 use craftnet_settlement::SettlementClient;
-
-use crate::{ExitError, Result, HttpRequest, HttpResponse};
-use crate::tunnel_handler::TunnelHandler;
+use tokio::sync::mpsc;
+
+use crate::{ExitError, Result, HeaderPolicy, HttpRequest, HttpResponse};
+use crate::abuse::{AbuseConfig, AbuseMonitor};
+use crate::content_filter::{scan_body, ContentFilter, NoopFilter};
+use crate::access::{AccessLimits, SubscriptionCache, TierLimits};
+use crate::assembly::StreamingAssembly;
+use crate::pool::{FetchPool, FetchPoolStats};
+use crate::quota::{current_epoch, QuotaLedger};
+use crate::response_cache::{ResponseCache, ResponseCacheStats};
+use crate::scheduler::{FairQueue, PoolQueueStats};
+use crate::self_test::SelfTestTargets;
+use crate::stream::{ResponseStreamer, ShardPairs};
+use crate::tunnel_handler::{SniAuditRecord, SniPolicy, TunnelHandler};
+use crate::websocket::WebSocketHandler;
 
 /// Exit node configuration
 #[derive(Debug, Clone)]
@@ -47,6 +69,115 @@ pub struct ExitConfig {
     pub max_pending_per_user: usize,
     /// Global cap on pending assemblies (prevents memory exhaustion from orphan entries)
     pub max_pending_assemblies: usize,
+    /// Decoded bytes one assembly may hold in memory before spilling to disk
+    pub assembly_spill_threshold: usize,
+    /// Directory for assembly spill files (None = system temp dir)
+    pub spill_dir: Option<PathBuf>,
+    /// Requests processed at once across all pools (upstream capacity)
+    pub max_concurrent_requests: usize,
+    /// Requests one pool may have in flight at once
+    pub max_concurrent_per_pool: usize,
+    /// Completed requests one pool may have waiting for a slot
+    pub max_queued_per_pool: usize,
+    /// Fair-queue weight per pool pubkey (pools not listed get weight 1)
+    pub pool_weights: HashMap<PublicKey, u32>,
+    /// Idle upstream connections kept per host
+    pub pool_max_idle_per_host: usize,
+    /// How long an idle upstream connection is kept for reuse
+    pub pool_idle_timeout: Duration,
+    /// How long upstream DNS answers are cached (zero = no cache)
+    pub dns_cache_ttl: Duration,
+    /// Cache shareable GET responses (see `response_cache`)
+    pub response_cache: bool,
+    /// Bytes the response cache may hold
+    pub response_cache_max_bytes: usize,
+    /// Largest single response the cache stores
+    pub response_cache_max_entry_bytes: usize,
+    /// Which origin response headers reach the client (see `response`)
+    pub response_headers: HeaderPolicy,
+    /// Sniff the ClientHello of tunnels to port 443 and apply
+    /// `blocked_domains` to the SNI hostname
+    pub tunnel_sni_inspection: bool,
+    /// Refuse TLS tunnels without an SNI hostname (needs `tunnel_sni_inspection`)
+    pub require_sni: bool,
+    /// How often idle WebSocket origins are pinged (a missed pong closes
+    /// the session)
+    pub websocket_ping_interval: Duration,
+    /// WebSocket sessions the client stops polling for this long are closed
+    pub websocket_idle_timeout: Duration,
+    /// Refuse requests from pools without an active subscription instead of
+    /// serving them at free-tier limits (without a settlement client every
+    /// request is refused)
+    pub require_subscription: bool,
+    /// How long a pool's subscription lookup is trusted
+    pub subscription_cache_ttl: Duration,
+    /// Per-tier limits, applied on top of the global ones when a settlement
+    /// client is set
+    pub access_limits: AccessLimits,
+    /// Meter pools without a subscription with quota tokens (see `quota`)
+    /// instead of serving them for free
+    pub quota_tokens: bool,
+    /// Traffic one quota token pays for (bytes)
+    pub quota_token_bytes: u64,
+    /// Quota tokens issued to one pool per day
+    pub quota_tokens_per_epoch: u32,
+    /// File spent quota token nonces are kept in, so a restart can't
+    /// spend them again (None = memory only)
+    pub quota_spent_file: Option<PathBuf>,
+    /// Targets of the startup self-test (None = skip it and advertise no
+    /// capabilities)
+    pub self_test: Option<SelfTestTargets>,
+    /// Per-destination request log and throttling thresholds (see `abuse`)
+    pub abuse: AbuseConfig,
+}
+
+impl ExitConfig {
+    /// Response cache for the handler (None = disabled)
+    fn response_cache(&self) -> Option<ResponseCache> {
+        self.response_cache.then(|| {
+            ResponseCache::new(self.response_cache_max_bytes, self.response_cache_max_entry_bytes)
+        })
+    }
+
+    /// SNI policy for the tunnel handler (None = inspection off)
+    fn sni_policy(&self) -> Option<SniPolicy> {
+        self.tunnel_sni_inspection.then(|| SniPolicy {
+            blocked_domains: self.blocked_domains.clone(),
+            require_sni: self.require_sni,
+        })
+    }
+
+    /// WebSocket handler for the handler, upgrading within the request timeout
+    fn websocket_handler(&self) -> WebSocketHandler {
+        WebSocketHandler::new(self.timeout, self.websocket_ping_interval, self.websocket_idle_timeout)
+    }
+
+    /// Limits that apply to every pool, whatever its tier
+    fn global_limits(&self) -> TierLimits {
+        TierLimits {
+            max_request_size: self.max_request_size,
+            max_concurrent_requests: self.max_pending_per_user,
+            max_tunnels: self.max_tunnels_per_user,
+        }
+    }
+
+    /// Quota ledger for the handler, keyed off its signing secret (None = disabled)
+    fn quota_ledger(&self, keypair: &SigningKeypair) -> Option<QuotaLedger> {
+        self.quota_tokens.then(|| {
+            let mut ledger = QuotaLedger::new(
+                QuotaIssuer::from_seed(&keypair.secret_key_bytes()),
+                self.quota_token_bytes,
+                self.quota_tokens_per_epoch,
+            );
+            if let Some(ref path) = self.quota_spent_file {
+                match ledger.load_spent(path.clone(), current_epoch()) {
+                    Ok(loaded) => debug!("Loaded {} spent quota tokens from {}", loaded, path.display()),
+                    Err(e) => warn!("Failed to load spent quota tokens from {}: {}", path.display(), e),
+                }
+            }
+            ledger
+        })
+    }
 }
 
 impl Default for ExitConfig {
@@ -64,6 +195,32 @@ impl Default for ExitConfig {
             max_tunnels_per_user: 50,
             max_pending_per_user: 100,
             max_pending_assemblies: 10_000,
+            assembly_spill_threshold: 4 * 1024 * 1024, // 4 MB
+            spill_dir: None,
+            max_concurrent_requests: 32,
+            max_concurrent_per_pool: 4,
+            max_queued_per_pool: 64,
+            pool_weights: HashMap::new(),
+            pool_max_idle_per_host: 32,
+            pool_idle_timeout: Duration::from_secs(90),
+            dns_cache_ttl: Duration::from_secs(60),
+            response_cache: false,
+            response_cache_max_bytes: 64 * 1024 * 1024,      // 64 MB
+            response_cache_max_entry_bytes: 4 * 1024 * 1024, // 4 MB
+            response_headers: HeaderPolicy::default(),
+            tunnel_sni_inspection: true,
+            require_sni: false,
+            websocket_ping_interval: Duration::from_secs(30),
+            websocket_idle_timeout: Duration::from_secs(120),
+            require_subscription: false,
+            subscription_cache_ttl: Duration::from_secs(300),
+            access_limits: AccessLimits::default(),
+            quota_tokens: false,
+            quota_token_bytes: 5 * 1024 * 1024, // 5 MB
+            quota_tokens_per_epoch: 100,
+            quota_spent_file: None,
+            self_test: Some(SelfTestTargets::default()),
+            abuse: AbuseConfig::default(),
         }
     }
 }
@@ -102,6 +259,8 @@ fn extract_host(url_or_host: &str) -> &str {
     // Try to parse as URL with scheme
     if let Some(after_scheme) = url_or_host.strip_prefix("http://")
         .or_else(|| url_or_host.strip_prefix("https://"))
+        .or_else(|| url_or_host.strip_prefix("ws://"))
+        .or_else(|| url_or_host.strip_prefix("wss://"))
     {
         let host_port = after_scheme.split('/').next().unwrap_or(after_scheme);
         // Strip port
@@ -132,23 +291,29 @@ fn extract_host(url_or_host: &str) -> &str {
 
 /// Pending assembly awaiting more shards (grouped by assembly_id)
 struct PendingAssembly {
-    /// Collected shard payloads indexed by (chunk_index, shard_index)
-    shards: HashMap<(u16, u8), Vec<u8>>,
+    /// Stripes decoded so far plus shards of stripes still incomplete
+    assembly: StreamingAssembly,
     /// Total chunks expected
     total_chunks: u16,
-    /// Total shards per chunk
-    #[allow(dead_code)]
-    total_shards: u8,
     /// When this pending assembly was created
     created_at: Instant,
     /// Pool pubkey of the user who owns this assembly (for per-user tracking)
     pool_pubkey: PublicKey,
+    /// Routing tag flags of the first shard (payload compression)
+    flags: u8,
+    /// Distinct hop timings carried by the shards of a traced request
+    trace: Vec<Vec<u8>>,
+    /// Unix ms the first shard arrived (the exit's own trace timing)
+    first_shard_at_ms: u64,
 }
 
 /// Exit node handler (onion-routed)
 pub struct ExitHandler {
     config: ExitConfig,
-    http_client: reqwest::Client,
+    /// Shared upstream client (pooled connections, DNS cache)
+    fetch_pool: FetchPool,
+    /// Cache of shareable GET responses (None = disabled)
+    response_cache: Option<ResponseCache>,
     erasure: ErasureCoder,
     /// Pending assemblies: assembly_id → shard payloads
     pending: HashMap<Id, PendingAssembly>,
@@ -159,57 +324,105 @@ pub struct ExitHandler {
     encryption_keypair: EncryptionKeypair,
     /// Settlement client (optional)
     settlement_client: Option<Arc<SettlementClient>>,
+    /// Subscription tiers of recent pools (see `access`)
+    subscriptions: SubscriptionCache,
+    /// Free-tier quota tokens (None = free tier unmetered)
+    quota: Option<QuotaLedger>,
     /// TCP tunnel handler for SOCKS5 proxy mode
     tunnel_handler: TunnelHandler,
+    /// WebSocket sessions bridged to origins
+    websocket_handler: WebSocketHandler,
     /// Per-user resource tracking
     user_tracking: HashMap<PublicKey, UserTracker>,
+    /// Completed assemblies waiting for upstream capacity, fair across pools
+    scheduler: FairQueue,
+    /// Where streamed response segments go (None = return them all at once)
+    stream_sink: Option<mpsc::Sender<ShardPairs>>,
+    /// Payload compression totals, shared with the owning node
+    compression: Arc<PayloadCompression>,
+    /// Result of the last self-test (None = not run)
+    capabilities: Option<ExitCapabilities>,
+    /// Per-destination request log, throttles and operator blocks
+    abuse: Arc<AbuseMonitor>,
+    /// Operator's checks on request URLs and response bodies
+    content_filter: Arc<dyn ContentFilter>,
 }
 
 impl ExitHandler {
     /// Create a new exit handler with signing and encryption keypairs
     pub fn new(config: ExitConfig, _our_pubkey: PublicKey, our_secret: [u8; 32]) -> Result<Self> {
-        let http_client = reqwest::Client::builder()
-            .timeout(config.timeout)
-            .user_agent("CraftNet/0.1")
-            .build()?;
+        let fetch_pool = FetchPool::new(&config)?;
+        let response_cache = config.response_cache();
 
         let keypair = SigningKeypair::from_secret_bytes(&our_secret);
         let encryption_keypair = EncryptionKeypair::generate();
-        let tunnel_handler = TunnelHandler::new(SigningKeypair::from_secret_bytes(&our_secret));
+        let tunnel_handler = TunnelHandler::new(SigningKeypair::from_secret_bytes(&our_secret))
+            .with_sni_policy(config.sni_policy());
+        let websocket_handler = config.websocket_handler();
+
+        let scheduler = FairQueue::for_config(&config);
+        let subscriptions = SubscriptionCache::new(config.subscription_cache_ttl);
+        let quota = config.quota_ledger(&keypair);
+        let abuse = Arc::new(AbuseMonitor::new(config.abuse.clone()));
 
         Ok(Self {
             config,
-            http_client,
+            fetch_pool,
+            response_cache,
             erasure: ErasureCoder::new()?,
             pending: HashMap::new(),
             keypair,
             encryption_keypair,
             settlement_client: None,
+            subscriptions,
+            quota,
             tunnel_handler,
+            websocket_handler,
             user_tracking: HashMap::new(),
+            scheduler,
+            stream_sink: None,
+            compression: Arc::default(),
+            capabilities: None,
+            abuse,
+            content_filter: Arc::new(NoopFilter),
         })
     }
 
     /// Create a new exit handler with a SigningKeypair directly
     pub fn with_keypair(config: ExitConfig, keypair: SigningKeypair) -> Result<Self> {
-        let http_client = reqwest::Client::builder()
-            .timeout(config.timeout)
-            .user_agent("CraftNet/0.1")
-            .build()?;
+        let fetch_pool = FetchPool::new(&config)?;
+        let response_cache = config.response_cache();
 
         let encryption_keypair = EncryptionKeypair::generate();
-        let tunnel_handler = TunnelHandler::new(keypair.clone());
+        let tunnel_handler = TunnelHandler::new(keypair.clone())
+            .with_sni_policy(config.sni_policy());
+        let websocket_handler = config.websocket_handler();
+
+        let scheduler = FairQueue::for_config(&config);
+        let subscriptions = SubscriptionCache::new(config.subscription_cache_ttl);
+        let quota = config.quota_ledger(&keypair);
+        let abuse = Arc::new(AbuseMonitor::new(config.abuse.clone()));
 
         Ok(Self {
             config,
-            http_client,
+            fetch_pool,
+            response_cache,
             erasure: ErasureCoder::new()?,
             pending: HashMap::new(),
             keypair,
             encryption_keypair,
             settlement_client: None,
+            subscriptions,
+            quota,
             tunnel_handler,
+            websocket_handler,
             user_tracking: HashMap::new(),
+            scheduler,
+            stream_sink: None,
+            compression: Arc::default(),
+            capabilities: None,
+            abuse,
+            content_filter: Arc::new(NoopFilter),
         })
     }
 
@@ -219,23 +432,38 @@ impl ExitHandler {
         keypair: SigningKeypair,
         encryption_keypair: EncryptionKeypair,
     ) -> Result<Self> {
-        let http_client = reqwest::Client::builder()
-            .timeout(config.timeout)
-            .user_agent("CraftNet/0.1")
-            .build()?;
+        let fetch_pool = FetchPool::new(&config)?;
+        let response_cache = config.response_cache();
 
-        let tunnel_handler = TunnelHandler::new(keypair.clone());
+        let tunnel_handler = TunnelHandler::new(keypair.clone())
+            .with_sni_policy(config.sni_policy());
+        let websocket_handler = config.websocket_handler();
+
+        let scheduler = FairQueue::for_config(&config);
+        let subscriptions = SubscriptionCache::new(config.subscription_cache_ttl);
+        let quota = config.quota_ledger(&keypair);
+        let abuse = Arc::new(AbuseMonitor::new(config.abuse.clone()));
 
         Ok(Self {
             config,
-            http_client,
+            fetch_pool,
+            response_cache,
             erasure: ErasureCoder::new()?,
             pending: HashMap::new(),
             keypair,
             encryption_keypair,
             settlement_client: None,
+            subscriptions,
+            quota,
             tunnel_handler,
+            websocket_handler,
             user_tracking: HashMap::new(),
+            scheduler,
+            stream_sink: None,
+            compression: Arc::default(),
+            capabilities: None,
+            abuse,
+            content_filter: Arc::new(NoopFilter),
         })
     }
 
@@ -246,25 +474,40 @@ impl ExitHandler {
         our_secret: [u8; 32],
         settlement_client: Arc<SettlementClient>,
     ) -> Result<Self> {
-        let http_client = reqwest::Client::builder()
-            .timeout(config.timeout)
-            .user_agent("CraftNet/0.1")
-            .build()?;
+        let fetch_pool = FetchPool::new(&config)?;
+        let response_cache = config.response_cache();
 
         let keypair = SigningKeypair::from_secret_bytes(&our_secret);
         let encryption_keypair = EncryptionKeypair::generate();
-        let tunnel_handler = TunnelHandler::new(SigningKeypair::from_secret_bytes(&our_secret));
+        let tunnel_handler = TunnelHandler::new(SigningKeypair::from_secret_bytes(&our_secret))
+            .with_sni_policy(config.sni_policy());
+        let websocket_handler = config.websocket_handler();
+
+        let scheduler = FairQueue::for_config(&config);
+        let subscriptions = SubscriptionCache::new(config.subscription_cache_ttl);
+        let quota = config.quota_ledger(&keypair);
+        let abuse = Arc::new(AbuseMonitor::new(config.abuse.clone()));
 
         Ok(Self {
             config,
-            http_client,
+            fetch_pool,
+            response_cache,
             erasure: ErasureCoder::new()?,
             pending: HashMap::new(),
             keypair,
             encryption_keypair,
             settlement_client: Some(settlement_client),
+            subscriptions,
+            quota,
             tunnel_handler,
+            websocket_handler,
             user_tracking: HashMap::new(),
+            scheduler,
+            stream_sink: None,
+            compression: Arc::default(),
+            capabilities: None,
+            abuse,
+            content_filter: Arc::new(NoopFilter),
         })
     }
 
@@ -274,32 +517,124 @@ impl ExitHandler {
         keypair: SigningKeypair,
         settlement_client: Arc<SettlementClient>,
     ) -> Result<Self> {
-        let http_client = reqwest::Client::builder()
-            .timeout(config.timeout)
-            .user_agent("CraftNet/0.1")
-            .build()?;
+        let fetch_pool = FetchPool::new(&config)?;
+        let response_cache = config.response_cache();
 
         let encryption_keypair = EncryptionKeypair::generate();
-        let tunnel_handler = TunnelHandler::new(keypair.clone());
+        let tunnel_handler = TunnelHandler::new(keypair.clone())
+            .with_sni_policy(config.sni_policy());
+        let websocket_handler = config.websocket_handler();
+
+        let scheduler = FairQueue::for_config(&config);
+        let subscriptions = SubscriptionCache::new(config.subscription_cache_ttl);
+        let quota = config.quota_ledger(&keypair);
+        let abuse = Arc::new(AbuseMonitor::new(config.abuse.clone()));
 
         Ok(Self {
             config,
-            http_client,
+            fetch_pool,
+            response_cache,
             erasure: ErasureCoder::new()?,
             pending: HashMap::new(),
             keypair,
             encryption_keypair,
             settlement_client: Some(settlement_client),
+            subscriptions,
+            quota,
             tunnel_handler,
+            websocket_handler,
             user_tracking: HashMap::new(),
+            scheduler,
+            stream_sink: None,
+            compression: Arc::default(),
+            capabilities: None,
+            abuse,
+            content_filter: Arc::new(NoopFilter),
         })
     }
 
+    /// Connection reuse and DNS cache counters of the upstream client
+    pub fn fetch_pool_stats(&self) -> FetchPoolStats {
+        self.fetch_pool.stats()
+    }
+
+    /// Hit/miss counters of the response cache (None = disabled)
+    pub fn response_cache_stats(&self) -> Option<ResponseCacheStats> {
+        self.response_cache.as_ref().map(ResponseCache::stats)
+    }
+
+    /// Drain the SNI policy decisions on TLS tunnels since the last call
+    pub fn take_sni_audit(&mut self) -> Vec<SniAuditRecord> {
+        self.tunnel_handler.take_sni_audit()
+    }
+
+    /// Per-destination request log, throttles and operator blocks
+    pub fn abuse_monitor(&self) -> &Arc<AbuseMonitor> {
+        &self.abuse
+    }
+
+    /// Keep the abuse log in `monitor` instead of a private one, so blocks
+    /// and the log outlive the handler
+    pub fn set_abuse_monitor(&mut self, monitor: Arc<AbuseMonitor>) {
+        self.abuse = monitor;
+    }
+
+    /// Check request URLs and response bodies with `filter` (see
+    /// `content_filter`)
+    pub fn set_content_filter(&mut self, filter: Arc<dyn ContentFilter>) {
+        self.content_filter = filter;
+    }
+
+    /// Quota terms to advertise in the exit record (None = not metering)
+    pub fn quota_terms(&self) -> Option<QuotaTerms> {
+        self.quota.as_ref().map(QuotaLedger::terms)
+    }
+
+    /// Probe upstream connectivity (see `self_test`) and remember what
+    /// works. Returns None when the self-test is disabled in the config.
+    pub async fn self_test(&mut self) -> Option<ExitCapabilities> {
+        let targets = self.config.self_test.as_ref()?;
+        let capabilities = crate::self_test::run(self.fetch_pool.client(), targets).await;
+        self.capabilities = Some(capabilities);
+        Some(capabilities)
+    }
+
+    /// Capabilities found by the last self-test, to advertise in the exit
+    /// record (None = not run)
+    pub fn capabilities(&self) -> Option<ExitCapabilities> {
+        self.capabilities
+    }
+
+    /// Largest request payload and response body (bytes), to advertise in
+    /// the exit record. Pool tiers may lower the request limit.
+    pub fn size_limits(&self) -> (usize, usize) {
+        (self.config.max_request_size, self.config.max_response_size)
+    }
+
     /// Set the settlement client
     pub fn set_settlement_client(&mut self, client: Arc<SettlementClient>) {
         self.settlement_client = Some(client);
     }
 
+    /// Send streamed response segments to `sink` as they are produced.
+    ///
+    /// Without a sink, streamed requests are read to the end and all their
+    /// segments are returned from [`process_complete_assembly`] together.
+    /// The sink should be bounded: a full sink pauses the upstream read.
+    pub fn set_stream_sink(&mut self, sink: mpsc::Sender<Vec<(Shard, Option<Vec<u8>>)>>) {
+        self.stream_sink = Some(sink);
+    }
+
+    /// Record payload compression in `stats` instead of a private counter
+    pub fn set_compression_stats(&mut self, stats: Arc<PayloadCompression>) {
+        self.compression = stats;
+    }
+
+    /// Payload compression totals
+    pub fn compression_stats(&self) -> &PayloadCompression {
+        &self.compression
+    }
+
     /// Get our encryption public key (for topology advertisements)
     pub fn encryption_pubkey(&self) -> [u8; 32] {
         self.encryption_keypair.public_key_bytes()
@@ -317,9 +652,10 @@ impl ExitHandler {
     /// If no LeaseSet gateway, gateway is None (direct mode — caller should use source_peer).
     /// Collect a shard into its pending assembly (fast, no I/O).
     ///
-    /// Returns `Ok(Some(assembly_id))` when the assembly is complete and ready
-    /// for processing via [`process_complete_assembly`]. Returns `Ok(None)` if
-    /// still collecting shards.
+    /// Returns `Ok(Some(assembly_id))` when the assembly is complete. It is then
+    /// queued in its pool's fair queue; take requests to run with
+    /// [`next_ready`] and process them via [`process_complete_assembly`].
+    /// Returns `Ok(None)` if still collecting shards.
     pub fn collect_shard(&mut self, shard: Shard) -> Result<Option<Id>> {
         // Decrypt routing_tag to get assembly_id + shard/chunk metadata + pool_pubkey
         let tag = decrypt_routing_tag(
@@ -332,11 +668,23 @@ impl ExitHandler {
         let shard_index = tag.shard_index;
         let total_chunks = tag.total_chunks;
         let pool_pubkey = tag.pool_pubkey;
+        let flags = tag.flags;
 
         // Check if this is a new assembly (not already in pending map)
         let is_new_assembly = !self.pending.contains_key(&assembly_id);
 
         if is_new_assembly {
+            let limits = self.cached_limits(&pool_pubkey);
+
+            // Reject assemblies that can't fit the request size limit before
+            // buffering anything (one chunk of slack for framing + AEAD overhead)
+            if total_chunks as usize * CHUNK_SIZE > limits.max_request_size + CHUNK_SIZE {
+                return Err(ExitError::InvalidRequest(format!(
+                    "assembly of {} chunks exceeds {} byte request limit",
+                    total_chunks, limits.max_request_size,
+                )));
+            }
+
             // Global cap: prevent memory exhaustion from sybil/orphan assemblies
             if self.pending.len() >= self.config.max_pending_assemblies {
                 return Err(ExitError::RateLimited(
@@ -354,7 +702,7 @@ impl ExitHandler {
             });
             tracker.last_activity = Instant::now();
 
-            if tracker.pending_assemblies >= self.config.max_pending_per_user {
+            if tracker.pending_assemblies >= limits.max_concurrent_requests {
                 return Err(ExitError::RateLimited(
                     "per-user pending assembly limit reached".to_string(),
                 ));
@@ -363,50 +711,111 @@ impl ExitHandler {
             tracker.pending_assemblies += 1;
         }
 
-        // Add shard payload to pending assembly
-        {
-            let pending = self.pending.entry(assembly_id).or_insert_with(|| {
-                PendingAssembly {
-                    shards: HashMap::new(),
-                    total_chunks,
-                    total_shards: tag.total_shards,
-                    created_at: Instant::now(),
-                    pool_pubkey,
-                }
-            });
-            pending.shards.insert((chunk_index, shard_index), shard.payload);
+        // Add shard to the pending assembly; its stripe is decoded as soon as
+        // DATA_SHARDS shards of it are present
+        let spill_threshold = self.config.assembly_spill_threshold;
+        let spill_dir = self.config.spill_dir.clone().unwrap_or_else(std::env::temp_dir);
+        let pending = self.pending.entry(assembly_id).or_insert_with(|| {
+            PendingAssembly {
+                assembly: StreamingAssembly::new(total_chunks, spill_threshold, spill_dir),
+                total_chunks,
+                created_at: Instant::now(),
+                pool_pubkey,
+                flags,
+                trace: Vec::new(),
+                first_shard_at_ms: trace_now_ms(),
+            }
+        });
+        if pending.total_chunks != total_chunks {
+            return Err(ExitError::InvalidRequest(format!(
+                "shard claims {} chunks, assembly has {}",
+                total_chunks, pending.total_chunks,
+            )));
         }
-
-        // Check if we have enough shards for every chunk
-        if !self.all_chunks_ready(&assembly_id) {
-            if let Some(pending) = self.pending.get(&assembly_id) {
-                let shard_count = pending.shards.len();
-                let needed = total_chunks as usize * craftnet_erasure::DATA_SHARDS;
-                info!(
-                    "[SHARD-FLOW] EXIT assembly={} shard received: chunk={} shard={} ({}/{} shards collected)",
-                    hex::encode(&assembly_id[..8]),
-                    chunk_index, shard_index,
-                    shard_count, needed,
-                );
+        // Timings are sealed for the client, so we can't tell whether the
+        // request is traced until its payload is decrypted; keep them all
+        for blob in shard.trace {
+            if pending.trace.len() < MAX_RESPONSE_TRACE_ENTRIES && !pending.trace.contains(&blob) {
+                pending.trace.push(blob);
             }
+        }
+        if let Err(e) = pending.assembly.add_shard(&self.erasure, chunk_index, shard_index, shard.payload.into()) {
+            self.drop_pending(&assembly_id);
+            return Err(e);
+        }
+
+        if !pending.assembly.is_complete() {
+            let needed = total_chunks as usize * craftnet_erasure::DATA_SHARDS;
+            info!(
+                "[SHARD-FLOW] EXIT assembly={} shard received: chunk={} shard={} ({}/{} shards collected, {} bytes buffered{})",
+                hex::encode(&assembly_id[..8]),
+                chunk_index, shard_index,
+                pending.assembly.shard_count(), needed,
+                pending.assembly.buffered_bytes(),
+                if pending.assembly.is_spilled() { ", spilled" } else { "" },
+            );
             return Ok(None);
         }
 
+        let weight = self.config.pool_weights.get(&pool_pubkey).copied().unwrap_or(1);
+        if let Err(e) = self.scheduler.push(pool_pubkey, assembly_id, weight) {
+            warn!(
+                "Dropping assembly {}: pool {} request queue full",
+                hex::encode(&assembly_id[..8]),
+                hex::encode(&pool_pubkey[..8]),
+            );
+            self.drop_pending(&assembly_id);
+            return Err(e);
+        }
+
         info!(
-            "[SHARD-FLOW] EXIT assembly={} COMPLETE — all shards collected, ready for processing",
+            "[SHARD-FLOW] EXIT assembly={} COMPLETE — all shards collected, queued for processing",
             hex::encode(&assembly_id[..8]),
         );
 
         Ok(Some(assembly_id))
     }
 
+    /// Take the next queued request to process, in weighted-fair order across
+    /// pools. Returns `None` if nothing is queued or the concurrency limits
+    /// are reached; the request holds its slot until [`process_complete_assembly`]
+    /// returns.
+    pub fn next_ready(&mut self) -> Option<Id> {
+        self.scheduler.next()
+    }
+
+    /// Whether [`next_ready`] would return a request
+    pub fn has_ready(&self) -> bool {
+        self.scheduler.has_ready()
+    }
+
+    /// Per-pool request queue depths, busiest pool first
+    pub fn pool_queue_stats(&self) -> Vec<PoolQueueStats> {
+        self.scheduler.stats()
+    }
+
+    /// Set a pool's fair-queue weight (applies to requests queued from now on)
+    pub fn set_pool_weight(&mut self, pool: PublicKey, weight: u32) {
+        self.config.pool_weights.insert(pool, weight.max(1));
+    }
+
     /// Process a complete assembly: reconstruct, HTTP fetch, create response shards.
     ///
     /// This is the slow path — it performs network I/O (HTTP request). Call this
-    /// only after [`collect_shard`] returns `Some(assembly_id)`.
+    /// only after [`collect_shard`] returns `Some(assembly_id)`, normally for
+    /// the id returned by [`next_ready`]. Releases the request's scheduler slot.
     pub async fn process_complete_assembly(
         &mut self,
         assembly_id: Id,
+    ) -> Result<Option<Vec<(Shard, Option<Vec<u8>>)>>> {
+        let result = self.run_assembly(assembly_id).await;
+        self.scheduler.finish(&assembly_id);
+        result
+    }
+
+    async fn run_assembly(
+        &mut self,
+        assembly_id: Id,
     ) -> Result<Option<Vec<(Shard, Option<Vec<u8>>)>>> {
         // Extract and reconstruct
         let Some(pending) = self.pending.remove(&assembly_id) else {
@@ -415,13 +824,16 @@ impl ExitHandler {
         };
 
         let pool_pubkey = pending.pool_pubkey;
+        let flags = pending.flags;
+        let request_trace = pending.trace;
+        let first_shard_at_ms = pending.first_shard_at_ms;
 
         // Decrement per-user pending assembly count
         if let Some(tracker) = self.user_tracking.get_mut(&pool_pubkey) {
             tracker.pending_assemblies = tracker.pending_assemblies.saturating_sub(1);
         }
 
-        let framed_data = self.reconstruct_data(&pending)?;
+        let framed_data = pending.assembly.into_data()?;
 
         // Strip length-prefixed framing (4-byte LE u32 original length)
         if framed_data.len() < 4 {
@@ -439,11 +851,34 @@ impl ExitHandler {
         let encrypted_data = &framed_data[4..4 + original_len];
 
         // Decrypt exit payload
-        let exit_payload = decrypt_exit_payload(
+        let mut exit_payload = decrypt_exit_payload(
             &self.encryption_keypair.secret_key_bytes(),
             encrypted_data,
         ).map_err(|e| ExitError::InvalidRequest(format!("ExitPayload decrypt failed: {}", e)))?;
 
+        let (tier, limits) = self.verify_access(pool_pubkey, &exit_payload).await?;
+        // Free-tier traffic pays with quota tokens when the exit meters it
+        let metered = tier.is_none() && self.quota.is_some();
+
+        // Past this point the client can be answered, so a request over its
+        // tier's size limit gets an error frame with the limit
+        let too_large = ExitError::RequestTooLarge(limits.max_request_size);
+        if flags & TAG_FLAG_ZSTD != 0 {
+            match craftnet_core::decompress(&exit_payload.data, limits.max_request_size) {
+                Ok(data) => {
+                    self.compression.record(data.len(), exit_payload.data.len());
+                    exit_payload.data = data;
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+                    return self.refuse(&exit_payload, too_large, flags, request_trace, first_shard_at_ms).map(Some);
+                }
+                Err(e) => return Err(ExitError::InvalidRequest(format!("Payload decompression failed: {}", e))),
+            }
+        }
+        if exit_payload.data.len() > limits.max_request_size {
+            return self.refuse(&exit_payload, too_large, flags, request_trace, first_shard_at_ms).map(Some);
+        }
+
         debug!(
             "Reconstructed exit payload: request={} type={:?} mode={}",
             hex::encode(&exit_payload.request_id[..8]),
@@ -459,30 +894,48 @@ impl ExitHandler {
             exit_payload.total_hops,
         );
 
-        // Belt-and-suspenders tier enforcement at exit:
-        // Verify that total_hops doesn't exceed what the pool's tier allows.
-        // Primary enforcement is at every relay via the public Shard fields,
-        // but exit validates too after decrypting the ExitPayload.
-        if let Some(tracker) = self.user_tracking.get(&pool_pubkey) {
-            // tracker exists → we've seen this user. Check tier from recent context.
-            let _ = tracker; // tier info would come from subscription cache if we had one at exit
-        }
-        // Note: full tier validation requires subscription cache at exit level.
-        // Currently the relay-side enforcement (decrementing hops_remaining) is the
-        // primary mechanism. Exit-side validation is a future enhancement when
-        // subscription gossip reaches the exit.
-
         // Process based on mode
         if exit_payload.mode == PAYLOAD_MODE_TUNNEL {
-            return self.process_tunnel_payload(&exit_payload, pool_pubkey).await;
+            return self.process_tunnel_payload(&exit_payload, pool_pubkey, limits.max_tunnels, metered).await;
+        }
+        if exit_payload.mode == PAYLOAD_MODE_WEBSOCKET {
+            return self.process_websocket_payload(&exit_payload, pool_pubkey, limits.max_tunnels, metered).await;
+        }
+        if exit_payload.mode == PAYLOAD_MODE_HTTP_BATCH {
+            let response = self.process_batch_request(&exit_payload, pool_pubkey, metered).await?;
+            return self.respond(&exit_payload, response, flags, request_trace, first_shard_at_ms).map(Some);
         }
 
         // HTTP mode
         let http_request = HttpRequest::from_bytes(&exit_payload.data)
             .map_err(|e| ExitError::InvalidRequest(e.to_string()))?;
 
+        if http_request.url == QUOTA_ISSUE_URL {
+            return self.issue_quota_batch(&exit_payload, pool_pubkey, &http_request, flags).map(Some);
+        }
+        if http_request.url == EXIT_PROBE_URL {
+            return self.answer_probe(&exit_payload, flags).map(Some);
+        }
+
+        self.abuse.admit(&pool_pubkey, extract_host(&http_request.url))?;
         self.check_blocked(&http_request.url).await?;
 
+        // A metered request's tokens pay for the request, the rest of their
+        // credit bounds the response
+        let mut max_response = self.config.max_response_size;
+        if metered {
+            let account = exit_payload.request_id;
+            let credit = self.spend_quota(account, &exit_payload.quota_tokens, exit_payload.data.len());
+            if let Some(quota) = self.quota.as_mut() {
+                quota.close(&account);
+            }
+            max_response = max_response.min(credit? as usize);
+        }
+
+        if exit_payload.mode == PAYLOAD_MODE_HTTP_STREAM {
+            return self.process_stream_request(exit_payload, &http_request, max_response).await.map(Some);
+        }
+
         info!(
             "HTTP request starting: {} {} (request={})",
             http_request.method,
@@ -490,26 +943,67 @@ impl ExitHandler {
             hex::encode(&exit_payload.request_id[..8])
         );
 
-        let response = match self.execute_request(&http_request).await {
-            Ok(r) => r,
-            Err(e) => {
-                warn!("HTTP request failed: {} (request={})", e, hex::encode(&exit_payload.request_id[..8]));
-                return Err(e);
+        let response = match self.fetch_cached(&exit_payload.request_id, &http_request, max_response).await {
+            Err(e @ ExitError::ResponseTooLarge(_)) => {
+                return self.refuse(&exit_payload, e, flags, request_trace, first_shard_at_ms).map(Some);
             }
+            result => result?,
         };
-        let response_data = response.to_bytes();
+        self.respond(&exit_payload, response, flags, request_trace, first_shard_at_ms).map(Some)
+    }
+
+    /// Answer a request refused with `err` with its error frame (see
+    /// `craftnet_core::ExitErrorFrame`), or fail with `err` if it has none.
+    ///
+    /// Only buffered HTTP requests (plain or batched) are answered: the
+    /// client of a stream, tunnel or WebSocket waits for another response
+    /// format and would drop the frame.
+    fn refuse(
+        &self,
+        exit_payload: &ExitPayload,
+        err: ExitError,
+        flags: u8,
+        request_trace: Vec<Vec<u8>>,
+        first_shard_at_ms: u64,
+    ) -> Result<Vec<(Shard, Option<Vec<u8>>)>> {
+        let buffered = matches!(exit_payload.mode, PAYLOAD_MODE_HTTP | PAYLOAD_MODE_HTTP_BATCH);
+        let Some(frame) = err.frame().filter(|_| buffered) else {
+            return Err(err);
+        };
+        warn!("Refusing request={}: {}", hex::encode(&exit_payload.request_id[..8]), err);
+        let headers = HashMap::from([(EXIT_ERROR_HEADER.to_string(), frame.code.as_str().to_string())]);
+        let response = HttpResponse::new(frame.status(), headers, frame.to_bytes());
+        self.respond(exit_payload, response, flags, request_trace, first_shard_at_ms)
+    }
+
+    /// Sign, encode and shard the response to a request
+    fn respond(
+        &self,
+        exit_payload: &ExitPayload,
+        mut response: HttpResponse,
+        flags: u8,
+        request_trace: Vec<Vec<u8>>,
+        first_shard_at_ms: u64,
+    ) -> Result<Vec<(Shard, Option<Vec<u8>>)>> {
+        self.sign_response(&exit_payload.request_id, &mut response);
+        let (response_data, response_flags) = self.encode_response(&response, flags);
 
         info!(
-            "HTTP request completed: request={} status={} response_bytes={}",
+            "HTTP request completed: request={} status={} response_bytes={}{}",
             hex::encode(&exit_payload.request_id[..8]),
             response.status,
             response_data.len(),
+            if response_flags & TAG_FLAG_ZSTD != 0 { " (zstd)" } else { "" },
         );
 
-        let shard_pairs = self.create_response_shards(
-            &exit_payload,
+        let mut shard_pairs = self.create_response_shards(
+            exit_payload,
             &response_data,
+            response_flags,
         )?;
+        if let Some(trace_pubkey) = exit_payload.trace_pubkey {
+            attach_trace(&mut shard_pairs, &trace_pubkey, request_trace, first_shard_at_ms);
+        }
 
         debug!(
             "Created {} response shards for request={} (leases={})",
@@ -518,12 +1012,108 @@ impl ExitHandler {
             exit_payload.lease_set.leases.len(),
         );
 
-        Ok(Some(shard_pairs))
+        Ok(shard_pairs)
+    }
+
+    /// Answer an HTTP request from the response cache or upstream
+    async fn fetch_cached(&mut self, request_id: &Id, http_request: &HttpRequest, max_response: usize) -> Result<HttpResponse> {
+        let cached = self.response_cache.as_mut()
+            .and_then(|cache| cache.get(http_request))
+            .filter(|r| r.body.len() <= max_response);
+        if let Some(r) = cached {
+            debug!("Response cache hit: {} (request={})", http_request.url, hex::encode(&request_id[..8]));
+            scan_body(self.content_filter.as_ref(), &http_request.url, &r.body).await?;
+            return Ok(r);
+        }
+        match self.execute_request(http_request, max_response).await {
+            Ok(r) => {
+                if let Some(cache) = self.response_cache.as_mut() {
+                    cache.insert(http_request, &r);
+                }
+                Ok(r)
+            }
+            Err(e) => {
+                warn!("HTTP request failed: {} (request={})", e, hex::encode(&request_id[..8]));
+                Err(e)
+            }
+        }
+    }
+
+    /// Run the sub-requests of a coalesced request one after another.
+    ///
+    /// Returns one response whose body is the [`BatchResponse`]; it is signed
+    /// and sharded like any other, so one signature covers every sub-response.
+    /// A sub-request that fails only fails its own entry. A metered batch
+    /// pays once for the whole payload and the sub-responses share the credit
+    /// that is left.
+    async fn process_batch_request(&mut self, exit_payload: &ExitPayload, pool_pubkey: PublicKey, metered: bool) -> Result<HttpResponse> {
+        let batch = BatchRequest::from_bytes(&exit_payload.data)
+            .map_err(|e| ExitError::InvalidRequest(format!("Invalid batch request: {}", e)))?;
+        if batch.requests.is_empty() || batch.requests.len() > MAX_BATCH_REQUESTS {
+            return Err(ExitError::InvalidRequest(format!(
+                "batch of {} requests (1 to {} allowed)",
+                batch.requests.len(), MAX_BATCH_REQUESTS,
+            )));
+        }
+
+        let mut remaining = self.config.max_response_size;
+        if metered {
+            let account = exit_payload.request_id;
+            let credit = self.spend_quota(account, &exit_payload.quota_tokens, exit_payload.data.len());
+            if let Some(quota) = self.quota.as_mut() {
+                quota.close(&account);
+            }
+            remaining = remaining.min(credit? as usize);
+        }
+
+        info!(
+            "HTTP batch starting: {} requests (request={})",
+            batch.requests.len(),
+            hex::encode(&exit_payload.request_id[..8]),
+        );
+
+        let mut responses = Vec::with_capacity(batch.requests.len());
+        for sub in batch.requests {
+            let result = match self.run_sub_request(&exit_payload.request_id, pool_pubkey, &sub.data, remaining).await {
+                Ok(response) => {
+                    remaining = remaining.saturating_sub(response.body.len());
+                    SubResult::Response(response.to_bytes())
+                }
+                Err(e) => SubResult::Error(e.to_string()),
+            };
+            responses.push(SubResponse { sub_id: sub.sub_id, result });
+        }
+
+        let count = responses.len();
+        let body = BatchResponse { responses }
+            .to_bytes()
+            .map_err(|e| ExitError::InvalidRequest(format!("Batch response encoding failed: {}", e)))?;
+        let headers = HashMap::from([(BATCH_RESPONSE_HEADER.to_string(), count.to_string())]);
+        Ok(HttpResponse::new(200, headers, body))
+    }
+
+    /// Run one sub-request of a batch. Control requests (quota issuing,
+    /// probes) are never batched.
+    async fn run_sub_request(&mut self, request_id: &Id, pool_pubkey: PublicKey, data: &[u8], max_response: usize) -> Result<HttpResponse> {
+        let http_request = HttpRequest::from_bytes(data)
+            .map_err(|e| ExitError::InvalidRequest(e.to_string()))?;
+        if http_request.url == QUOTA_ISSUE_URL || http_request.url == EXIT_PROBE_URL {
+            return Err(ExitError::InvalidRequest(format!("{} can't be batched", http_request.url)));
+        }
+        self.abuse.admit(&pool_pubkey, extract_host(&http_request.url))?;
+        self.check_blocked(&http_request.url).await?;
+        self.fetch_cached(request_id, &http_request, max_response).await
     }
 
     /// Combined collect + process (convenience method, blocks during I/O).
+    ///
+    /// Processes the next request in fair-queue order, which is not
+    /// necessarily the one this shard completed.
     pub async fn process_shard(&mut self, shard: Shard) -> Result<Option<Vec<(Shard, Option<Vec<u8>>)>>> {
-        match self.collect_shard(shard)? {
+        if self.collect_shard(shard)?.is_none() {
+            return Ok(None);
+        }
+        match self.next_ready() {
             Some(assembly_id) => self.process_complete_assembly(assembly_id).await,
             None => Ok(None),
         }
@@ -534,6 +1124,8 @@ impl ExitHandler {
         &mut self,
         exit_payload: &ExitPayload,
         pool_pubkey: PublicKey,
+        max_tunnels: usize,
+        metered: bool,
     ) -> Result<Option<Vec<(Shard, Option<Vec<u8>>)>>> {
         let request_data = &exit_payload.data;
         if request_data.len() < 4 {
@@ -551,6 +1143,7 @@ impl ExitHandler {
             .map_err(|e| ExitError::InvalidRequest(format!("Invalid tunnel metadata: {}", e)))?;
         let tcp_data = request_data[4 + metadata_len..].to_vec();
 
+        self.abuse.admit(&pool_pubkey, extract_host(&metadata.host))?;
         self.check_blocked(&metadata.host).await?;
 
         // Per-user tunnel limit check (keyed by pool_pubkey for consistency)
@@ -562,14 +1155,19 @@ impl ExitHandler {
             });
             tracker.last_activity = Instant::now();
 
-            if !metadata.is_close && tracker.concurrent_tunnels >= self.config.max_tunnels_per_user {
+            if !metadata.is_close && tracker.concurrent_tunnels >= max_tunnels {
                 return Err(ExitError::RateLimited(format!(
                     "User exceeds max concurrent tunnels ({})",
-                    self.config.max_tunnels_per_user,
+                    max_tunnels,
                 )));
             }
         }
 
+        // Response bytes are charged after the fact (see `quota`)
+        if metered {
+            self.spend_quota(metadata.session_id, &exit_payload.quota_tokens, tcp_data.len())?;
+        }
+
         info!(
             "Tunnel request to {}:{} for request {} (session {})",
             metadata.host,
@@ -599,6 +1197,13 @@ impl ExitHandler {
                 tracker.concurrent_tunnels = tracker.concurrent_tunnels.saturating_sub(1);
             }
         }
+        if let Some(quota) = self.quota.as_mut().filter(|_| metered) {
+            if metadata.is_close || zombie {
+                quota.close(&metadata.session_id);
+            } else {
+                quota.charge(metadata.session_id, response_bytes.len());
+            }
+        }
 
         if response_bytes.is_empty() {
             return Ok(Some(vec![]));
@@ -607,71 +1212,256 @@ impl ExitHandler {
         let shard_pairs = self.create_response_shards(
             exit_payload,
             &response_bytes,
+            0,
         )?;
 
         Ok(Some(shard_pairs))
     }
 
-    /// Check if all chunks for an assembly have enough shards
-    fn all_chunks_ready(&self, assembly_id: &Id) -> bool {
-        let Some(pending) = self.pending.get(assembly_id) else {
-            return false;
+    /// Process a WebSocket-mode payload.
+    ///
+    /// Sessions count against the pool's tunnel limit. A session that can't
+    /// be opened or found is answered with `WsReply::Error` rather than
+    /// dropped, so the client doesn't wait out its timeout.
+    async fn process_websocket_payload(
+        &mut self,
+        exit_payload: &ExitPayload,
+        pool_pubkey: PublicKey,
+        max_tunnels: usize,
+        metered: bool,
+    ) -> Result<Option<Vec<(Shard, Option<Vec<u8>>)>>> {
+        let request = WsRequest::from_bytes(&exit_payload.data)
+            .map_err(|e| ExitError::InvalidRequest(format!("Invalid WebSocket request: {}", e)))?;
+        let session_id = request.session_id;
+
+        let tracker = self.user_tracking.entry(pool_pubkey).or_insert(UserTracker {
+            concurrent_tunnels: 0,
+            pending_assemblies: 0,
+            last_activity: Instant::now(),
+        });
+        tracker.last_activity = Instant::now();
+
+        let reply = match request.action {
+            WsAction::Open { url, headers, protocols } => {
+                if tracker.concurrent_tunnels >= max_tunnels {
+                    return Err(ExitError::RateLimited(format!(
+                        "User exceeds max concurrent tunnels ({})",
+                        max_tunnels,
+                    )));
+                }
+                self.abuse.admit(&pool_pubkey, extract_host(&url))?;
+                self.check_blocked(&url).await?;
+                if metered {
+                    self.spend_quota(session_id, &exit_payload.quota_tokens, exit_payload.data.len())?;
+                }
+
+                info!(
+                    "WebSocket request to {} for request {} (session {})",
+                    url,
+                    hex::encode(&exit_payload.request_id[..8]),
+                    hex::encode(&session_id[..8]),
+                );
+                match self.websocket_handler.open(session_id, &url, &headers, &protocols, pool_pubkey).await {
+                    Ok(protocol) => {
+                        if let Some(tracker) = self.user_tracking.get_mut(&pool_pubkey) {
+                            tracker.concurrent_tunnels += 1;
+                        }
+                        WsReply::Opened { protocol }
+                    }
+                    Err(e) => {
+                        self.close_quota_account(&session_id, metered);
+                        WsReply::Error(e.to_string())
+                    }
+                }
+            }
+            WsAction::Send(messages) => {
+                // Response bytes are charged after the fact (see `quota`)
+                if metered {
+                    let bytes = messages.iter().map(|m| m.len()).sum();
+                    self.spend_quota(session_id, &exit_payload.quota_tokens, bytes)?;
+                }
+                match self.websocket_handler.exchange(&session_id, &pool_pubkey, messages).await {
+                    Ok((messages, close)) => {
+                        if close.is_some() {
+                            self.release_tunnel(&pool_pubkey);
+                            self.close_quota_account(&session_id, metered);
+                        } else if let Some(quota) = self.quota.as_mut().filter(|_| metered) {
+                            quota.charge(session_id, messages.iter().map(|m| m.len()).sum());
+                        }
+                        WsReply::Messages { messages, close }
+                    }
+                    Err(e) => WsReply::Error(e.to_string()),
+                }
+            }
+            WsAction::Close(close) => {
+                if self.websocket_handler.close(&session_id, &pool_pubkey, &close).await {
+                    self.release_tunnel(&pool_pubkey);
+                }
+                self.close_quota_account(&session_id, metered);
+                WsReply::Messages { messages: Vec::new(), close: Some(close) }
+            }
         };
 
-        let mut chunk_counts: HashMap<u16, usize> = HashMap::new();
-        for &(chunk_idx, _) in pending.shards.keys() {
-            *chunk_counts.entry(chunk_idx).or_default() += 1;
+        let reply_bytes = reply.to_bytes()
+            .map_err(|e| ExitError::InvalidRequest(format!("Failed to encode WebSocket reply: {}", e)))?;
+        let shard_pairs = self.create_response_shards(exit_payload, &reply_bytes, 0)?;
+        Ok(Some(shard_pairs))
+    }
+
+    /// Count a closed tunnel or WebSocket session off its pool's limit
+    fn release_tunnel(&mut self, pool_pubkey: &PublicKey) {
+        if let Some(tracker) = self.user_tracking.get_mut(pool_pubkey) {
+            tracker.concurrent_tunnels = tracker.concurrent_tunnels.saturating_sub(1);
         }
+    }
 
-        if chunk_counts.len() < pending.total_chunks as usize {
-            return false;
+    /// Drop a metered session's quota account once it has ended
+    fn close_quota_account(&mut self, account: &Id, metered: bool) {
+        if let Some(quota) = self.quota.as_mut().filter(|_| metered) {
+            quota.close(account);
         }
-        chunk_counts.values().all(|&count| count >= craftnet_erasure::DATA_SHARDS)
     }
 
-    /// Reconstruct data from shard payloads (multi-chunk aware)
-    fn reconstruct_data(&self, pending: &PendingAssembly) -> Result<Vec<u8>> {
-        let mut chunks_by_index: HashMap<u16, Vec<(u8, &Vec<u8>)>> = HashMap::new();
-        for (&(chunk_idx, shard_idx), payload) in &pending.shards {
-            chunks_by_index
-                .entry(chunk_idx)
-                .or_default()
-                .push((shard_idx, payload));
+    /// Limits of `pool` known without a settlement lookup: its cached
+    /// tier's, or the global limits until its subscription has been looked up
+    fn cached_limits(&self, pool: &PublicKey) -> TierLimits {
+        let global = self.config.global_limits();
+        if self.settlement_client.is_none() {
+            return global;
         }
+        match self.subscriptions.get(pool) {
+            Some(tier) => global.capped_by(self.config.access_limits.for_tier(tier)),
+            None => global,
+        }
+    }
 
-        let mut reconstructed_chunks: BTreeMap<u16, Vec<u8>> = BTreeMap::new();
+    /// Check the pool a request is billed to against the settlement layer
+    /// and return its tier (None = free) and the limits that apply to it.
+    ///
+    /// Without a settlement client every pool gets the global limits (or is
+    /// refused, with `require_subscription`). A failed lookup serves the
+    /// request at free-tier limits unless a subscription is required.
+    async fn verify_access(
+        &mut self,
+        pool_pubkey: PublicKey,
+        exit_payload: &ExitPayload,
+    ) -> Result<(Option<SubscriptionTier>, TierLimits)> {
+        if exit_payload.pool_pubkey != pool_pubkey {
+            return Err(ExitError::InvalidRequest(
+                "payload pool pubkey does not match routing tag".to_string(),
+            ));
+        }
 
-        for chunk_idx in 0..pending.total_chunks {
-            let chunk_shards = chunks_by_index.get(&chunk_idx);
-            let mut shard_data: Vec<Option<Vec<u8>>> =
-                vec![None; craftnet_erasure::TOTAL_SHARDS];
-            let mut shard_size = 0usize;
+        let global = self.config.global_limits();
+        let Some(client) = self.settlement_client.clone() else {
+            if self.config.require_subscription {
+                return Err(ExitError::SubscriptionRequired(
+                    "exit has no settlement client to verify subscriptions".to_string(),
+                ));
+            }
+            return Ok((None, global));
+        };
+        if pool_pubkey == [0u8; 32] {
+            return Err(ExitError::InvalidRequest("request carries no pool pubkey".to_string()));
+        }
 
-            if let Some(shards) = chunk_shards {
-                for &(shard_idx, payload) in shards {
-                    let idx = shard_idx as usize;
-                    if idx < craftnet_erasure::TOTAL_SHARDS {
-                        shard_size = payload.len();
-                        shard_data[idx] = Some(payload.clone());
-                    }
-                }
+        let tier = match self.subscriptions.lookup(&client, pool_pubkey).await {
+            Ok(tier) => tier,
+            Err(e) if self.config.require_subscription => {
+                return Err(ExitError::SettlementError(e.to_string()));
+            }
+            Err(e) => {
+                warn!(
+                    "Subscription lookup for pool {} failed, serving at free-tier limits: {}",
+                    hex::encode(&pool_pubkey[..8]), e,
+                );
+                None
             }
+        };
+        if tier.is_none() && self.config.require_subscription {
+            return Err(ExitError::SubscriptionRequired(format!(
+                "pool {} has no active subscription",
+                hex::encode(&pool_pubkey[..8]),
+            )));
+        }
+        debug!("Pool {} verified at tier {:?}", hex::encode(&pool_pubkey[..8]), tier);
+        Ok((tier, global.capped_by(self.config.access_limits.for_tier(tier))))
+    }
 
-            let max_len = shard_size * craftnet_erasure::DATA_SHARDS;
-            let chunk_data = self
-                .erasure
-                .decode(&mut shard_data, max_len)
-                .map_err(|e| ExitError::ErasureDecodeError(e.to_string()))?;
+    /// Spend `tokens` into a quota account and charge `bytes` to it,
+    /// returning the credit left. Refused (charging nothing) when the
+    /// credit does not cover `bytes`.
+    fn spend_quota(&mut self, account: Id, tokens: &[QuotaToken], bytes: usize) -> Result<i64> {
+        let Some(quota) = self.quota.as_mut() else {
+            return Ok(i64::MAX);
+        };
+        let credit = quota.redeem(account, tokens, current_epoch())?;
+        if credit < bytes as i64 {
+            return Err(ExitError::RateLimited(format!(
+                "free-tier quota exhausted: {} bytes of credit for {} bytes",
+                credit.max(0), bytes,
+            )));
+        }
+        Ok(quota.charge(account, bytes))
+    }
 
-            reconstructed_chunks.insert(chunk_idx, chunk_data);
+    /// Answer a quota batch request (`QUOTA_ISSUE_URL`) with the signed batch
+    fn issue_quota_batch(
+        &mut self,
+        exit_payload: &ExitPayload,
+        pool_pubkey: PublicKey,
+        request: &HttpRequest,
+        request_flags: u8,
+    ) -> Result<Vec<(Shard, Option<Vec<u8>>)>> {
+        let Some(quota) = self.quota.as_mut() else {
+            return Err(ExitError::InvalidRequest("exit does not issue quota tokens".to_string()));
+        };
+        if pool_pubkey == [0u8; 32] {
+            return Err(ExitError::InvalidRequest("quota batch request carries no pool pubkey".to_string()));
         }
+        let batch = QuotaIssueRequest::from_bytes(request.body.as_deref().unwrap_or_default())
+            .map_err(|e| ExitError::InvalidRequest(format!("Invalid quota batch request: {}", e)))?;
+        let issued = quota.issue(pool_pubkey, &batch, current_epoch())?;
+        info!(
+            "Issued {} quota tokens to pool {} (request={})",
+            batch.blinded.len(),
+            hex::encode(&pool_pubkey[..8]),
+            hex::encode(&exit_payload.request_id[..8]),
+        );
+
+        let body = issued.to_bytes()
+            .map_err(|e| ExitError::InvalidRequest(format!("Quota batch encoding failed: {}", e)))?;
+        let mut response = HttpResponse::new(200, HashMap::new(), body);
+        self.sign_response(&exit_payload.request_id, &mut response);
+        let (response_data, response_flags) = self.encode_response(&response, request_flags);
+        self.create_response_shards(exit_payload, &response_data, response_flags)
+    }
 
-        let total_possible = reconstructed_chunks.values().map(|c| c.len()).sum();
-        reassemble(&reconstructed_chunks, pending.total_chunks, total_possible)
-            .map_err(|e| ExitError::ErasureDecodeError(e.to_string()))
+    /// Answer a client health probe (`EXIT_PROBE_URL`) with an empty 204
+    fn answer_probe(
+        &mut self,
+        exit_payload: &ExitPayload,
+        request_flags: u8,
+    ) -> Result<Vec<(Shard, Option<Vec<u8>>)>> {
+        let mut response = HttpResponse::new(204, HashMap::new(), Vec::new());
+        self.sign_response(&exit_payload.request_id, &mut response);
+        let (response_data, response_flags) = self.encode_response(&response, request_flags);
+        self.create_response_shards(exit_payload, &response_data, response_flags)
     }
 
-    /// Check if URL/host is blocked (domain blocklist + private IP SSRF protection)
+    /// Drop a pending assembly (malformed shard), releasing its per-user slot
+    /// and its place in the fair queue
+    fn drop_pending(&mut self, assembly_id: &Id) {
+        if let Some(asm) = self.pending.remove(assembly_id) {
+            if let Some(tracker) = self.user_tracking.get_mut(&asm.pool_pubkey) {
+                tracker.pending_assemblies = tracker.pending_assemblies.saturating_sub(1);
+            }
+            self.scheduler.finish(assembly_id);
+        }
+    }
+
+    /// Check if URL/host is blocked (domain blocklist, private IP SSRF
+    /// protection, then the content filter)
     async fn check_blocked(&self, url: &str) -> Result<()> {
         let host = extract_host(url);
         for domain in &self.config.blocked_domains {
@@ -708,19 +1498,57 @@ impl ExitHandler {
             }
         }
 
-        Ok(())
+        self.content_filter.check_request(url).await.into_result()
+    }
+
+    /// Execute an HTTP request and stream the response back in segments.
+    ///
+    /// Returns the head segment's shards as soon as the upstream headers
+    /// arrive; body segments follow through the stream sink (see `stream`).
+    async fn process_stream_request(
+        &self,
+        exit_payload: ExitPayload,
+        request: &HttpRequest,
+        max_response: usize,
+    ) -> Result<Vec<(Shard, Option<Vec<u8>>)>> {
+        info!(
+            "HTTP stream request starting: {} {} (request={})",
+            request.method,
+            request.url,
+            hex::encode(&exit_payload.request_id[..8])
+        );
+
+        let response = self.build_request(request)?.send().await?;
+        let streamer = ResponseStreamer::new(
+            response,
+            exit_payload,
+            self.encryption_keypair.secret_key_bytes(),
+            max_response,
+            self.config.response_headers.clone(),
+            self.content_filter.body_scanner(&request.url),
+        );
+        let mut shard_pairs = streamer.head_shards()?;
+
+        match &self.stream_sink {
+            Some(sink) => {
+                tokio::spawn(streamer.run(sink.clone()));
+            }
+            None => shard_pairs.extend(streamer.collect().await?),
+        }
+
+        Ok(shard_pairs)
     }
 
-    /// Execute an HTTP request
-    async fn execute_request(&self, request: &HttpRequest) -> Result<HttpResponse> {
+    /// Build the upstream request for an HTTP request
+    fn build_request(&self, request: &HttpRequest) -> Result<reqwest::RequestBuilder> {
         let method = request.method.to_uppercase();
         let mut req = match method.as_str() {
-            "GET" => self.http_client.get(&request.url),
-            "POST" => self.http_client.post(&request.url),
-            "PUT" => self.http_client.put(&request.url),
-            "DELETE" => self.http_client.delete(&request.url),
-            "PATCH" => self.http_client.patch(&request.url),
-            "HEAD" => self.http_client.head(&request.url),
+            "GET" => self.fetch_pool.client().get(&request.url),
+            "POST" => self.fetch_pool.client().post(&request.url),
+            "PUT" => self.fetch_pool.client().put(&request.url),
+            "DELETE" => self.fetch_pool.client().delete(&request.url),
+            "PATCH" => self.fetch_pool.client().patch(&request.url),
+            "HEAD" => self.fetch_pool.client().head(&request.url),
             _ => return Err(ExitError::InvalidRequest(format!("Unsupported method: {}", method))),
         };
 
@@ -732,168 +1560,102 @@ impl ExitHandler {
             req = req.body(body.clone());
         }
 
-        let mut response = req.send().await?;
+        Ok(req)
+    }
+
+    /// Execute an HTTP request, reading at most `max` bytes of body
+    async fn execute_request(&self, request: &HttpRequest, max: usize) -> Result<HttpResponse> {
+        let mut response = self.build_request(request)?.send().await?;
         let status = response.status().as_u16();
 
-        let mut headers = HashMap::new();
-        for (key, value) in response.headers() {
-            if let Ok(v) = value.to_str() {
-                headers.insert(key.to_string(), v.to_string());
-            }
+        let headers = response.headers()
+            .iter()
+            .filter_map(|(k, v)| v.to_str().ok().map(|v| (k.to_string(), v.to_string())))
+            .collect();
+        // Repeated headers (e.g. several Set-Cookie) are combined, not dropped
+        let mut combined: HashMap<String, String> = HashMap::new();
+        for (key, value) in self.config.response_headers.apply(headers) {
+            combined.entry(key)
+                .and_modify(|v| {
+                    v.push_str(", ");
+                    v.push_str(&value);
+                })
+                .or_insert(value);
         }
 
-        // Stream response body with size enforcement
-        let max = self.config.max_response_size;
+        // Stream response body with size enforcement, filtering each chunk
+        let mut scanner = self.content_filter.body_scanner(&request.url);
         let mut body = Vec::new();
         while let Some(chunk) = response.chunk().await? {
             if body.len() + chunk.len() > max {
                 return Err(ExitError::ResponseTooLarge(max));
             }
+            if let Some(scanner) = scanner.as_mut() {
+                scanner.scan(&chunk).await.into_result()?;
+            }
             body.extend_from_slice(&chunk);
         }
+        if let Some(scanner) = scanner.as_mut() {
+            scanner.finish().await.into_result()?;
+        }
+
+        Ok(HttpResponse::new(status, combined, body))
+    }
+
+    /// Sign the response so the client can prove it came unmodified from us.
+    ///
+    /// Any upstream header of the same name is replaced.
+    fn sign_response(&self, request_id: &Id, response: &mut HttpResponse) {
+        response.headers.retain(|k, _| !k.eq_ignore_ascii_case(EXIT_SIGNATURE_HEADER));
+        let signature = sign_exit_response(&self.keypair, request_id, response.status, &response.body);
+        response.headers.insert(EXIT_SIGNATURE_HEADER.to_string(), hex::encode(signature));
+    }
 
-        Ok(HttpResponse::new(status, headers, body))
+    /// Serialize an HTTP response for the client.
+    ///
+    /// Compressed with zstd when the request carried `TAG_FLAG_ACCEPT_ZSTD`,
+    /// the body isn't already compressed and compression actually helps.
+    /// Returns the bytes and the routing tag flags for the response shards.
+    fn encode_response(&self, response: &HttpResponse, request_flags: u8) -> (Vec<u8>, u8) {
+        let data = response.to_bytes();
+        if request_flags & TAG_FLAG_ACCEPT_ZSTD == 0 {
+            return (data, 0);
+        }
+        let header = |name: &str| {
+            response.headers
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v.as_str())
+        };
+        if craftnet_core::is_precompressed(header("content-type"), header("content-encoding")) {
+            return (data, 0);
+        }
+        match craftnet_core::compress(&data) {
+            Some(packed) => {
+                self.compression.record(data.len(), packed.len());
+                (packed, TAG_FLAG_ZSTD)
+            }
+            None => (data, 0),
+        }
     }
 
     /// Create response shards with onion routing via LeaseSet.
     ///
-    /// Round-robins each shard across gateways in the LeaseSet. Each shard's
-    /// onion header targets its assigned gateway, and the returned pairs tell
-    /// the caller which gateway to send each shard to.
+    /// Uses the request_id as assembly_id so the client can match response
+    /// shards to its pending request map.
     fn create_response_shards(
         &self,
         exit_payload: &ExitPayload,
         response_data: &[u8],
+        tag_flags: u8,
     ) -> Result<Vec<(Shard, Option<Vec<u8>>)>> {
-        // Encrypt response for the client using their X25519 encryption pubkey.
-        // Falls back to user_pubkey for pre-response_enc_pubkey payloads.
-        let recipient_pubkey = if exit_payload.response_enc_pubkey != [0u8; 32] {
-            &exit_payload.response_enc_pubkey
-        } else {
-            &exit_payload.user_pubkey
-        };
-        warn!(
-            "[TRACE] EXIT_RESPONSE_KEYS request={} recipient_enc_key={} is_response_enc={} leases={}",
-            hex::encode(&exit_payload.request_id[..8]),
-            hex::encode(&recipient_pubkey[..8]),
-            exit_payload.response_enc_pubkey != [0u8; 32],
-            exit_payload.lease_set.leases.len(),
-        );
-        let encrypted_response = craftec_crypto::encrypt_for_recipient(
-            recipient_pubkey,
+        build_response_shards(
             &self.encryption_keypair.secret_key_bytes(),
+            exit_payload,
+            exit_payload.request_id,
             response_data,
-        ).map_err(|e| ExitError::InvalidRequest(format!("Response encryption failed: {}", e)))?;
-
-        // Prepend original length (4-byte LE u32) so client can strip erasure padding
-        let original_len = encrypted_response.len() as u32;
-        let mut framed = Vec::with_capacity(4 + encrypted_response.len());
-        framed.extend_from_slice(&original_len.to_le_bytes());
-        framed.extend_from_slice(&encrypted_response);
-
-        // Chunk and erasure code
-        let chunks = chunk_and_encode(&framed)
-            .map_err(|e| ExitError::ErasureDecodeError(e.to_string()))?;
-
-        let total_chunks = chunks.len() as u16;
-        // Use request_id as assembly_id so the client can match
-        // response shards to its pending request map.
-        let assembly_id = exit_payload.request_id;
-
-        let leases = &exit_payload.lease_set.leases;
-        let mut shard_pairs = Vec::with_capacity(chunks.len() * craftnet_erasure::TOTAL_SHARDS);
-        let mut shard_counter: usize = 0;
-
-        for (chunk_index, shard_payloads) in chunks {
-            let total_shards_in_chunk = shard_payloads.len() as u8;
-
-            for (i, payload) in shard_payloads.into_iter().enumerate() {
-                // For each shard, build a routing tag encrypted for the client
-                // Response routing tags don't need pool_pubkey (client doesn't enforce limits)
-                let routing_tag = encrypt_routing_tag(
-                    recipient_pubkey,
-                    &assembly_id,
-                    i as u8,
-                    total_shards_in_chunk,
-                    chunk_index,
-                    total_chunks,
-                    &[0u8; 32],
-                ).map_err(|e| ExitError::InvalidRequest(
-                    format!("routing_tag encrypt failed: {}", e),
-                ))?;
-
-                // Round-robin this shard's gateway across all leases
-                let lease = if !leases.is_empty() {
-                    Some(&leases[shard_counter % leases.len()])
-                } else {
-                    None
-                };
-                shard_counter += 1;
-
-                let (header, ephemeral, gateway) = if let Some(lease) = lease {
-                    // Direct mode: tunnel_id is all zeros — skip onion header,
-                    // send response shards directly to client's peer_id.
-                    if lease.tunnel_id == [0u8; 32] {
-                        (vec![], [0u8; 32], Some(lease.gateway_peer_id.clone()))
-                    } else {
-                        let gateway_pubkey = lease.gateway_encryption_pubkey;
-                        let shard_id = {
-                            let mut hasher = Sha256::new();
-                            hasher.update(exit_payload.request_id);
-                            hasher.update(b"response");
-                            hasher.update(chunk_index.to_be_bytes());
-                            hasher.update([i as u8]);
-                            hasher.update(gateway_pubkey);
-                            let hash = hasher.finalize();
-                            let mut id: Id = [0u8; 32];
-                            id.copy_from_slice(&hash);
-                            id
-                        };
-
-                        let settlement = vec![OnionSettlement {
-                            shard_id,
-                            payload_size: payload.len() as u32,
-                            pool_pubkey: exit_payload.user_pubkey,
-                        }];
-
-                        // Single-hop onion to this shard's gateway with tunnel_id
-                        let (h, e) = build_onion_header(
-                            &[(&lease.gateway_peer_id, &lease.gateway_encryption_pubkey)],
-                            (&lease.gateway_peer_id, &lease.gateway_encryption_pubkey),
-                            &settlement,
-                            Some(&lease.tunnel_id),
-                        ).map_err(|e| ExitError::InvalidRequest(
-                            format!("Onion header build failed: {}", e),
-                        ))?;
-                        (h, e, Some(lease.gateway_peer_id.clone()))
-                    }
-                } else {
-                    // No lease set — fallback (empty header, no gateway)
-                    (vec![], [0u8; 32], None)
-                };
-
-                // Response shards routed through a gateway relay need
-                // total_hops/hops_remaining set so the gateway's tier
-                // enforcement passes (gateway peels one layer and checks
-                // hops_remaining >= 1).  Direct mode responses (empty
-                // header) keep 0/0.
-                let resp_hops: u8 = if header.is_empty() { 0 } else { 1 };
-                shard_pairs.push((
-                    Shard::new(ephemeral, header, payload, routing_tag, resp_hops, resp_hops),
-                    gateway,
-                ));
-            }
-        }
-
-        debug!(
-            "Created {} response shards ({} chunks) for request {} across {} gateways",
-            shard_pairs.len(),
-            total_chunks,
-            hex::encode(&exit_payload.request_id[..8]),
-            leases.len(),
-        );
-
-        Ok(shard_pairs)
+            tag_flags,
+        )
     }
 
     /// Get the number of pending assemblies
@@ -901,7 +1663,18 @@ impl ExitHandler {
         self.pending.len()
     }
 
-    /// Clear stale pending assemblies, tunnel sessions, and inactive user trackers
+    /// Whether shards for this assembly are already being collected
+    pub fn has_pending(&self, assembly_id: &Id) -> bool {
+        self.pending.contains_key(assembly_id)
+    }
+
+    /// Pool the pending assembly is billed to
+    pub fn pending_pool(&self, assembly_id: &Id) -> Option<PublicKey> {
+        self.pending.get(assembly_id).map(|p| p.pool_pubkey)
+    }
+
+    /// Clear stale pending assemblies, tunnel and WebSocket sessions, and
+    /// inactive user trackers
     pub fn clear_stale(&mut self, max_age: Duration) {
         let now = Instant::now();
 
@@ -913,11 +1686,7 @@ impl ExitHandler {
             .collect();
 
         for key in &stale_keys {
-            if let Some(asm) = self.pending.remove(key) {
-                if let Some(tracker) = self.user_tracking.get_mut(&asm.pool_pubkey) {
-                    tracker.pending_assemblies = tracker.pending_assemblies.saturating_sub(1);
-                }
-            }
+            self.drop_pending(key);
         }
 
         let removed = before - self.pending.len();
@@ -928,9 +1697,12 @@ impl ExitHandler {
         // Evict stale tunnel sessions, decrementing per-user concurrent_tunnels
         let evicted_tunnel_owners = self.tunnel_handler.clear_stale(max_age);
         for owner in evicted_tunnel_owners {
-            if let Some(tracker) = self.user_tracking.get_mut(&owner) {
-                tracker.concurrent_tunnels = tracker.concurrent_tunnels.saturating_sub(1);
-            }
+            self.release_tunnel(&owner);
+        }
+
+        // WebSocket sessions go after their own idle timeout
+        for owner in self.websocket_handler.clear_stale() {
+            self.release_tunnel(&owner);
         }
 
         // Clean up stale user trackers (no activity for 5 minutes)
@@ -938,6 +1710,12 @@ impl ExitHandler {
         self.user_tracking.retain(|_, tracker| {
             now.duration_since(tracker.last_activity) < tracker_timeout
         });
+
+        self.subscriptions.prune();
+        self.abuse.prune();
+        if let Some(quota) = self.quota.as_mut() {
+            quota.prune(current_epoch(), tracker_timeout);
+        }
     }
 
     /// Get the number of active tunnel sessions
@@ -946,6 +1724,168 @@ impl ExitHandler {
     }
 }
 
+/// Copy a traced request's hop timings, plus the exit's own, onto every
+/// response shard so any shards the client reconstructs from carry them.
+fn attach_trace(
+    shard_pairs: &mut [(Shard, Option<Vec<u8>>)],
+    trace_pubkey: &[u8; 32],
+    mut blobs: Vec<Vec<u8>>,
+    received_at_ms: u64,
+) {
+    let timing = HopTiming { role: HopRole::Exit, received_at_ms, sent_at_ms: trace_now_ms() };
+    match seal_hop_timing(trace_pubkey, &timing) {
+        Ok(blob) => blobs.push(blob),
+        Err(e) => debug!("Failed to seal exit timing: {}", e),
+    }
+    for (shard, _) in shard_pairs.iter_mut() {
+        shard.trace = blobs.clone();
+    }
+}
+
+/// Encrypt, erasure-code and onion-wrap response data as one assembly.
+///
+/// Round-robins each shard across gateways in the LeaseSet. Each shard's
+/// onion header targets its assigned gateway, and the returned pairs tell
+/// the caller which gateway to send each shard to.
+pub(crate) fn build_response_shards(
+    exit_secret: &[u8; 32],
+    exit_payload: &ExitPayload,
+    assembly_id: Id,
+    response_data: &[u8],
+    tag_flags: u8,
+) -> Result<Vec<(Shard, Option<Vec<u8>>)>> {
+    // Encrypt response for the client using their X25519 encryption pubkey.
+    // Falls back to user_pubkey for pre-response_enc_pubkey payloads.
+    let recipient_pubkey = if exit_payload.response_enc_pubkey != [0u8; 32] {
+        &exit_payload.response_enc_pubkey
+    } else {
+        &exit_payload.user_pubkey
+    };
+    warn!(
+        "[TRACE] EXIT_RESPONSE_KEYS request={} recipient_enc_key={} is_response_enc={} leases={}",
+        hex::encode(&exit_payload.request_id[..8]),
+        hex::encode(&recipient_pubkey[..8]),
+        exit_payload.response_enc_pubkey != [0u8; 32],
+        exit_payload.lease_set.leases.len(),
+    );
+    let encrypted_response = craftec_crypto::encrypt_for_recipient(
+        recipient_pubkey,
+        exit_secret,
+        response_data,
+    ).map_err(|e| ExitError::InvalidRequest(format!("Response encryption failed: {}", e)))?;
+
+    // Prepend original length (4-byte LE u32) so client can strip erasure padding
+    let original_len = encrypted_response.len() as u32;
+    let mut framed = Vec::with_capacity(4 + encrypted_response.len());
+    framed.extend_from_slice(&original_len.to_le_bytes());
+    framed.extend_from_slice(&encrypted_response);
+
+    // Chunk and erasure code
+    let chunks = chunk_and_encode(&framed)
+        .map_err(|e| ExitError::ErasureDecodeError(e.to_string()))?;
+
+    let total_chunks = chunks.len() as u16;
+
+    let leases = &exit_payload.lease_set.leases;
+    let mut shard_pairs = Vec::with_capacity(chunks.len() * craftnet_erasure::TOTAL_SHARDS);
+    let mut shard_counter: usize = 0;
+
+    for (chunk_index, shard_payloads) in chunks {
+        let total_shards_in_chunk = shard_payloads.len() as u8;
+
+        for (i, payload) in shard_payloads.into_iter().enumerate() {
+            // For each shard, build a routing tag encrypted for the client
+            // Response routing tags don't need pool_pubkey (client doesn't enforce limits)
+            let routing_tag = encrypt_routing_tag_with_flags(
+                recipient_pubkey,
+                &assembly_id,
+                i as u8,
+                total_shards_in_chunk,
+                chunk_index,
+                total_chunks,
+                &[0u8; 32],
+                tag_flags,
+            ).map_err(|e| ExitError::InvalidRequest(
+                format!("routing_tag encrypt failed: {}", e),
+            ))?;
+
+            // Round-robin this shard's gateway across all leases
+            let lease = if !leases.is_empty() {
+                Some(&leases[shard_counter % leases.len()])
+            } else {
+                None
+            };
+            shard_counter += 1;
+
+            let (header, ephemeral, gateway) = if let Some(lease) = lease {
+                // Direct mode: tunnel_id is all zeros — skip onion header,
+                // send response shards directly to client's peer_id.
+                if lease.tunnel_id == [0u8; 32] {
+                    (vec![], [0u8; 32], Some(lease.gateway_peer_id.clone()))
+                } else {
+                    let gateway_pubkey = lease.gateway_encryption_pubkey;
+                    let shard_id = {
+                        let mut hasher = Sha256::new();
+                        hasher.update(assembly_id);
+                        hasher.update(b"response");
+                        hasher.update(chunk_index.to_be_bytes());
+                        hasher.update([i as u8]);
+                        hasher.update(gateway_pubkey);
+                        let hash = hasher.finalize();
+                        let mut id: Id = [0u8; 32];
+                        id.copy_from_slice(&hash);
+                        id
+                    };
+
+                    let settlement = vec![OnionSettlement {
+                        shard_id,
+                        payload_size: payload.len() as u32,
+                        pool_pubkey: exit_payload.user_pubkey,
+                        cover: false,
+                        trace_pubkey: exit_payload.trace_pubkey,
+                        chain_ack: None,
+                    }];
+
+                    // Single-hop onion to this shard's gateway with tunnel_id
+                    let (h, e) = build_onion_header(
+                        &[(&lease.gateway_peer_id, &lease.gateway_encryption_pubkey)],
+                        (&lease.gateway_peer_id, &lease.gateway_encryption_pubkey),
+                        &settlement,
+                        Some(&lease.tunnel_id),
+                    ).map_err(|e| ExitError::InvalidRequest(
+                        format!("Onion header build failed: {}", e),
+                    ))?;
+                    (h, e, Some(lease.gateway_peer_id.clone()))
+                }
+            } else {
+                // No lease set — fallback (empty header, no gateway)
+                (vec![], [0u8; 32], None)
+            };
+
+            // Response shards routed through a gateway relay need
+            // total_hops/hops_remaining set so the gateway's tier
+            // enforcement passes (gateway peels one layer and checks
+            // hops_remaining >= 1).  Direct mode responses (empty
+            // header) keep 0/0.
+            let resp_hops: u8 = if header.is_empty() { 0 } else { 1 };
+            shard_pairs.push((
+                Shard::new(ephemeral, header, payload, routing_tag, resp_hops, resp_hops),
+                gateway,
+            ));
+        }
+    }
+
+    debug!(
+        "Created {} response shards ({} chunks) for request {} across {} gateways",
+        shard_pairs.len(),
+        total_chunks,
+        hex::encode(&exit_payload.request_id[..8]),
+        leases.len(),
+    );
+
+    Ok(shard_pairs)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1012,6 +1952,51 @@ mod tests {
         assert_eq!(handler.pending_count(), 0);
     }
 
+    #[test]
+    fn test_encode_response_compression() {
+        let mut handler = ExitHandler::new(ExitConfig::default(), [0u8; 32], [0u8; 32]).unwrap();
+        let stats = Arc::new(PayloadCompression::default());
+        handler.set_compression_stats(stats.clone());
+
+        let mut headers = HashMap::new();
+        headers.insert("content-type".to_string(), "text/html".to_string());
+        let response = HttpResponse::new(200, headers, b"<p>hello</p>".repeat(200));
+
+        // Client didn't ask for compression
+        let (data, flags) = handler.encode_response(&response, 0);
+        assert_eq!(flags, 0);
+        assert_eq!(data, response.to_bytes());
+
+        let (data, flags) = handler.encode_response(&response, TAG_FLAG_ACCEPT_ZSTD);
+        assert_eq!(flags, TAG_FLAG_ZSTD);
+        assert_eq!(craftnet_core::decompress(&data, 1 << 20).unwrap(), response.to_bytes());
+        assert_eq!(stats.payloads(), 1);
+
+        // Already-compressed bodies go out as-is
+        let mut headers = HashMap::new();
+        headers.insert("content-encoding".to_string(), "gzip".to_string());
+        let gzipped = HttpResponse::new(200, headers, vec![0u8; 4096]);
+        let (_, flags) = handler.encode_response(&gzipped, TAG_FLAG_ACCEPT_ZSTD);
+        assert_eq!(flags, 0);
+        assert_eq!(handler.compression_stats().payloads(), 1);
+    }
+
+    #[test]
+    fn test_sign_response_replaces_upstream_header() {
+        let keypair = SigningKeypair::generate();
+        let exit = keypair.public_key_bytes();
+        let handler = ExitHandler::with_keypair(ExitConfig::default(), keypair).unwrap();
+
+        let mut headers = HashMap::new();
+        headers.insert("X-Craftnet-Exit-Signature".to_string(), "forged".to_string());
+        let mut response = HttpResponse::new(200, headers, b"body".to_vec());
+        handler.sign_response(&[5u8; 32], &mut response);
+
+        assert_eq!(response.headers.len(), 1);
+        let sig: [u8; 64] = hex::decode(&response.headers[EXIT_SIGNATURE_HEADER]).unwrap().try_into().unwrap();
+        assert!(craftnet_core::verify_exit_response(&exit, &[5u8; 32], 200, b"body", &sig));
+    }
+
     #[test]
     fn test_encryption_pubkey() {
         let handler = ExitHandler::new(ExitConfig::default(), [0u8; 32], [0u8; 32]).unwrap();
@@ -1026,18 +2011,22 @@ mod tests {
         let mut handler = ExitHandler::with_keypair(ExitConfig::default(), keypair).unwrap();
 
         handler.pending.insert([1u8; 32], PendingAssembly {
-            shards: HashMap::new(),
+            assembly: StreamingAssembly::new(1, usize::MAX, std::env::temp_dir()),
             total_chunks: 1,
-            total_shards: 5,
             created_at: Instant::now() - Duration::from_secs(120),
             pool_pubkey: [0u8; 32],
+            flags: 0,
+            trace: Vec::new(),
+            first_shard_at_ms: 0,
         });
         handler.pending.insert([2u8; 32], PendingAssembly {
-            shards: HashMap::new(),
+            assembly: StreamingAssembly::new(1, usize::MAX, std::env::temp_dir()),
             total_chunks: 1,
-            total_shards: 5,
             created_at: Instant::now(),
             pool_pubkey: [0u8; 32],
+            flags: 0,
+            trace: Vec::new(),
+            first_shard_at_ms: 0,
         });
 
         assert_eq!(handler.pending_count(), 2);
@@ -1046,6 +2035,248 @@ mod tests {
         assert!(handler.pending.contains_key(&[2u8; 32]));
     }
 
+    #[test]
+    fn test_stale_queued_assembly_leaves_fair_queue() {
+        let config = ExitConfig {
+            max_concurrent_per_pool: 1,
+            ..Default::default()
+        };
+        let mut handler = ExitHandler::with_keypair(config, SigningKeypair::generate()).unwrap();
+        let (pool_a, pool_b) = ([0xAA; 32], [0xBB; 32]);
+
+        for (id, pool, age) in [([1u8; 32], pool_a, 0), ([2u8; 32], pool_a, 120), ([3u8; 32], pool_b, 0)] {
+            handler.pending.insert(id, PendingAssembly {
+                assembly: StreamingAssembly::new(1, usize::MAX, std::env::temp_dir()),
+                total_chunks: 1,
+                created_at: Instant::now() - Duration::from_secs(age),
+                pool_pubkey: pool,
+                flags: 0,
+                trace: Vec::new(),
+                first_shard_at_ms: 0,
+            });
+            handler.scheduler.push(pool, id, 1).unwrap();
+        }
+
+        // Pool b is served before pool a's second request
+        assert_eq!(handler.next_ready(), Some([1u8; 32]));
+        assert_eq!(handler.next_ready(), Some([3u8; 32]));
+        assert!(!handler.has_ready());
+
+        // The stale queued request is dropped from its pool's queue
+        handler.clear_stale(Duration::from_secs(60));
+        let stats = handler.pool_queue_stats();
+        assert_eq!(stats.iter().map(|s| s.queued).sum::<usize>(), 0);
+        assert_eq!(stats.iter().map(|s| s.in_flight).sum::<usize>(), 2);
+    }
+
+    fn payload_for_pool(pool_pubkey: PublicKey) -> ExitPayload {
+        ExitPayload {
+            request_id: [3u8; 32],
+            user_pubkey: pool_pubkey,
+            lease_set: craftnet_core::LeaseSet { session_id: [0u8; 32], leases: vec![] },
+            total_hops: 0,
+            shard_type: craftnet_core::ShardType::Request,
+            mode: 0,
+            data: vec![],
+            response_enc_pubkey: [0u8; 32],
+            pool_pubkey,
+            quota_tokens: vec![],
+            trace_pubkey: None,
+        }
+    }
+
+    #[test]
+    fn test_attach_trace_adds_exit_timing() {
+        let trace_keys = EncryptionKeypair::generate();
+        let mut payload = payload_for_pool([0u8; 32]);
+        payload.trace_pubkey = Some(trace_keys.public_key_bytes());
+        let mut shard_pairs = build_response_shards(&[7u8; 32], &payload, [3u8; 32], b"response", 0).unwrap();
+
+        let relay_blob = vec![1u8; 80];
+        attach_trace(&mut shard_pairs, &trace_keys.public_key_bytes(), vec![relay_blob.clone()], 1_000);
+        for (shard, _) in &shard_pairs {
+            assert_eq!(shard.trace.len(), 2);
+            assert_eq!(shard.trace[0], relay_blob);
+            let exit = craftnet_core::open_hop_timing(&trace_keys.secret_key_bytes(), &shard.trace[1]).unwrap();
+            assert_eq!((exit.role, exit.received_at_ms), (HopRole::Exit, 1_000));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_request_fails_per_entry() {
+        use craftnet_core::SubRequest;
+
+        let mut handler = ExitHandler::new(ExitConfig::default(), [0u8; 32], [0u8; 32]).unwrap();
+        let sub = |sub_id: u16, url: &str| SubRequest {
+            sub_id,
+            data: HttpRequest { method: "GET".to_string(), url: url.to_string(), headers: HashMap::new(), body: None }.to_bytes(),
+        };
+        let mut payload = payload_for_pool([0u8; 32]);
+        payload.mode = PAYLOAD_MODE_HTTP_BATCH;
+        payload.data = BatchRequest {
+            requests: vec![
+                sub(4, "http://localhost/admin"),
+                sub(9, EXIT_PROBE_URL),
+                SubRequest { sub_id: 2, data: b"GET".to_vec() },
+            ],
+        }
+        .to_bytes()
+        .unwrap();
+
+        let response = handler.process_batch_request(&payload, [0u8; 32], false).await.unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.headers.get(BATCH_RESPONSE_HEADER).map(String::as_str), Some("3"));
+        let batch = BatchResponse::from_bytes(&response.body).unwrap();
+        let ids: Vec<u16> = batch.responses.iter().map(|r| r.sub_id).collect();
+        assert_eq!(ids, vec![4, 9, 2]);
+        assert!(batch.responses.iter().all(|r| matches!(r.result, SubResult::Error(_))));
+
+        // Empty batches are refused outright
+        payload.data = BatchRequest::default().to_bytes().unwrap();
+        assert!(handler.process_batch_request(&payload, [0u8; 32], false).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_shared_abuse_monitor_blocks_pool() {
+        use craftnet_core::SubRequest;
+        use crate::abuse::{hash_destination, BlockTarget};
+
+        let mut handler = ExitHandler::new(ExitConfig::default(), [0u8; 32], [0u8; 32]).unwrap();
+        let monitor = Arc::new(AbuseMonitor::default());
+        handler.set_abuse_monitor(monitor.clone());
+        monitor.block(BlockTarget::Pool([5u8; 32]));
+
+        let mut payload = payload_for_pool([5u8; 32]);
+        payload.mode = PAYLOAD_MODE_HTTP_BATCH;
+        payload.data = BatchRequest {
+            requests: vec![SubRequest {
+                sub_id: 1,
+                data: HttpRequest { method: "GET".to_string(), url: "https://safe.org/".to_string(), headers: HashMap::new(), body: None }.to_bytes(),
+            }],
+        }
+        .to_bytes()
+        .unwrap();
+
+        let response = handler.process_batch_request(&payload, [5u8; 32], false).await.unwrap();
+        let batch = BatchResponse::from_bytes(&response.body).unwrap();
+        assert!(matches!(batch.responses[0].result, SubResult::Error(_)));
+
+        let report = monitor.report(10);
+        assert_eq!(report.destinations[0].destination, hash_destination("safe.org"));
+        assert_eq!(report.destinations[0].refused, 1);
+    }
+
+    #[test]
+    fn test_size_errors_are_answered_with_a_frame() {
+        let handler = ExitHandler::new(ExitConfig::default(), [0u8; 32], [0u8; 32]).unwrap();
+        assert_eq!(handler.size_limits(), (10 * 1024 * 1024, 50 * 1024 * 1024));
+        let payload = payload_for_pool([0u8; 32]);
+
+        let frame = ExitError::RequestTooLarge(1024).frame().unwrap();
+        assert_eq!((frame.code, frame.limit, frame.status()), (craftnet_core::ErrorCode::RequestTooLarge, Some(1024), 413));
+        let shards = handler.refuse(&payload, ExitError::RequestTooLarge(1024), 0, Vec::new(), 0).unwrap();
+        assert!(!shards.is_empty());
+
+        // Errors without a frame still drop the request, as do streams
+        let err = handler.refuse(&payload, ExitError::BlockedDestination("x".to_string()), 0, Vec::new(), 0);
+        assert!(matches!(err, Err(ExitError::BlockedDestination(_))));
+        let mut stream = payload_for_pool([0u8; 32]);
+        stream.mode = PAYLOAD_MODE_HTTP_STREAM;
+        let err = handler.refuse(&stream, ExitError::RequestTooLarge(1024), 0, Vec::new(), 0);
+        assert!(matches!(err, Err(ExitError::RequestTooLarge(1024))));
+    }
+
+    #[tokio::test]
+    async fn test_websocket_open_checks_policy_and_limits() {
+        use craftnet_core::WsAction;
+
+        let mut handler = ExitHandler::new(ExitConfig::default(), [0u8; 32], [0u8; 32]).unwrap();
+        let mut payload = payload_for_pool([0u8; 32]);
+        payload.mode = PAYLOAD_MODE_WEBSOCKET;
+        payload.data = WsRequest {
+            session_id: [3u8; 32],
+            action: WsAction::Open { url: "ws://localhost:9000/".to_string(), headers: Vec::new(), protocols: Vec::new() },
+        }
+        .to_bytes()
+        .unwrap();
+
+        let result = handler.process_websocket_payload(&payload, [0u8; 32], 4, false).await;
+        assert!(matches!(result, Err(ExitError::BlockedDestination(_))));
+        let result = handler.process_websocket_payload(&payload, [0u8; 32], 0, false).await;
+        assert!(matches!(result, Err(ExitError::RateLimited(_))));
+    }
+
+    #[tokio::test]
+    async fn test_verify_access_applies_tier_limits() {
+        use craftnet_core::SubscriptionTier;
+        use craftnet_settlement::SettlementConfig;
+
+        let settlement = Arc::new(SettlementClient::new(SettlementConfig::mock(), [0u8; 32]));
+        settlement.add_mock_subscription([1u8; 32], SubscriptionTier::Premium, 1_000).unwrap();
+        settlement.add_mock_subscription([2u8; 32], SubscriptionTier::Basic, 1_000).unwrap();
+
+        let keypair = SigningKeypair::generate();
+        let mut handler = ExitHandler::with_keypair_and_settlement(
+            ExitConfig::default(), keypair.clone(), settlement.clone(),
+        ).unwrap();
+        let limits = AccessLimits::default();
+
+        // Unknown pools are only held to the global limits until looked up
+        assert_eq!(handler.cached_limits(&[2u8; 32]), ExitConfig::default().global_limits());
+
+        let premium = handler.verify_access([1u8; 32], &payload_for_pool([1u8; 32])).await.unwrap();
+        assert_eq!(premium, (Some(SubscriptionTier::Premium), limits.premium));
+        let basic = handler.verify_access([2u8; 32], &payload_for_pool([2u8; 32])).await.unwrap();
+        assert_eq!(basic, (Some(SubscriptionTier::Basic), limits.basic));
+        assert_eq!(handler.cached_limits(&[2u8; 32]), limits.basic);
+        let free = handler.verify_access([3u8; 32], &payload_for_pool([3u8; 32])).await.unwrap();
+        assert_eq!(free, (None, limits.free));
+
+        // The payload must name the pool the routing tag bills
+        assert!(matches!(
+            handler.verify_access([1u8; 32], &payload_for_pool([2u8; 32])).await,
+            Err(ExitError::InvalidRequest(_)),
+        ));
+
+        let config = ExitConfig { require_subscription: true, ..Default::default() };
+        let mut strict = ExitHandler::with_keypair_and_settlement(config.clone(), keypair.clone(), settlement).unwrap();
+        assert!(strict.verify_access([1u8; 32], &payload_for_pool([1u8; 32])).await.is_ok());
+        assert!(matches!(
+            strict.verify_access([3u8; 32], &payload_for_pool([3u8; 32])).await,
+            Err(ExitError::SubscriptionRequired(_)),
+        ));
+
+        // Nothing to verify against: refuse everyone
+        let mut unverified = ExitHandler::with_keypair(config, keypair).unwrap();
+        assert!(matches!(
+            unverified.verify_access([1u8; 32], &payload_for_pool([1u8; 32])).await,
+            Err(ExitError::SubscriptionRequired(_)),
+        ));
+    }
+
+    #[test]
+    fn test_spend_quota() {
+        use craftnet_core::PendingQuotaBatch;
+
+        let config = ExitConfig { quota_tokens: true, quota_token_bytes: 1000, ..Default::default() };
+        let mut handler = ExitHandler::with_keypair(config, SigningKeypair::generate()).unwrap();
+        let terms = handler.quota_terms().unwrap();
+        let epoch = current_epoch();
+        let (pending, request) = PendingQuotaBatch::new(epoch, 2);
+        let response = handler.quota.as_mut().unwrap().issue([1u8; 32], &request, epoch).unwrap();
+        let tokens = pending.finalize(&terms.issuer_key, &response).unwrap();
+
+        assert_eq!(handler.spend_quota([9u8; 32], &tokens[..1], 400).unwrap(), 600);
+        // Not covered by the credit: refused without charging
+        assert!(matches!(handler.spend_quota([9u8; 32], &[], 700), Err(ExitError::RateLimited(_))));
+        assert_eq!(handler.spend_quota([9u8; 32], &tokens[1..], 700).unwrap(), 900);
+        // Spent tokens are rejected for any account
+        assert!(handler.spend_quota([8u8; 32], &tokens[..1], 0).is_err());
+
+        let unmetered = ExitHandler::with_keypair(ExitConfig::default(), SigningKeypair::generate()).unwrap();
+        assert!(unmetered.quota_terms().is_none());
+    }
+
     #[tokio::test]
     async fn test_empty_blocked_list() {
         let config = ExitConfig {
@@ -1097,6 +2328,7 @@ mod tests {
         assert_eq!(extract_host("https://example.com:443/path"), "example.com");
         assert_eq!(extract_host("http://127.0.0.1:8080/api"), "127.0.0.1");
         assert_eq!(extract_host("example.com:443"), "example.com");
+        assert_eq!(extract_host("wss://example.com:8443/socket"), "example.com");
         assert_eq!(extract_host("example.com"), "example.com");
     }
 
@@ -1129,4 +2361,63 @@ mod tests {
         assert!(handler.check_blocked("http://127.0.0.1/api").await.is_ok());
         assert!(handler.check_blocked("http://10.0.0.1/api").await.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_fetched_headers_sanitized() {
+        use wiremock::matchers::method;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("Set-Cookie", "id=1")
+                    .insert_header("Keep-Alive", "timeout=5")
+                    .insert_header("X-Kept", "yes"),
+            )
+            .mount(&server)
+            .await;
+
+        let config = ExitConfig {
+            response_headers: HeaderPolicy { strip_set_cookie: true, ..Default::default() },
+            self_test: None,
+            ..Default::default()
+        };
+        let handler = ExitHandler::new(config, [0u8; 32], [0u8; 32]).unwrap();
+        let request = HttpRequest { method: "GET".to_string(), url: server.uri(), headers: HashMap::new(), body: None };
+        let response = handler.execute_request(&request, 1024).await.unwrap();
+        assert_eq!(response.headers.get("x-kept").map(String::as_str), Some("yes"));
+        assert!(!response.headers.contains_key("set-cookie"));
+        assert!(!response.headers.contains_key("keep-alive"));
+    }
+
+    #[tokio::test]
+    async fn test_content_filter_checks_urls_and_bodies() {
+        use crate::content_filter::BlocklistFilter;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        for (route, body) in [("/ok", "fine"), ("/bad", "forbidden body")] {
+            Mock::given(method("GET"))
+                .and(path(route))
+                .respond_with(ResponseTemplate::new(200).set_body_string(body))
+                .mount(&server)
+                .await;
+        }
+
+        let config = ExitConfig { allow_private_ips: true, blocked_domains: vec![], self_test: None, ..Default::default() };
+        let mut handler = ExitHandler::new(config, [0u8; 32], [0u8; 32]).unwrap();
+        let filter = BlocklistFilter::new()
+            .block_url_pattern("filtered.example")
+            .block_body_hash(Sha256::digest(b"forbidden body").into());
+        handler.set_content_filter(Arc::new(filter));
+
+        assert!(matches!(handler.check_blocked("https://filtered.example/a").await, Err(ExitError::ContentFiltered(_))));
+        assert!(handler.check_blocked(&server.uri()).await.is_ok());
+
+        let request = |route: &str| HttpRequest { method: "GET".to_string(), url: format!("{}{}", server.uri(), route), headers: HashMap::new(), body: None };
+        assert_eq!(handler.execute_request(&request("/ok"), 1024).await.unwrap().body, b"fine");
+        assert!(matches!(handler.execute_request(&request("/bad"), 1024).await, Err(ExitError::ContentFiltered(_))));
+    }
 }
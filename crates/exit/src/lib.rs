@@ -6,21 +6,58 @@
 //!
 //! 1. Decrypt routing_tag to get assembly_id
 //! 2. Collect and group shards by assembly_id
-//! 3. Reconstruct via erasure coding and decrypt ExitPayload
-//! 4. Execute HTTP request or open TCP tunnel
-//! 5. Create onion-routed response shards via LeaseSet
+//! 3. Reconstruct via erasure coding (stripe by stripe, spilling large
+//!    assemblies to disk) and decrypt ExitPayload
+//! 4. Queue completed requests per pool and run them in weighted-fair order
+//!    under global and per-pool concurrency limits, verifying each pool's
+//!    subscription tier with the settlement layer and charging free-tier
+//!    traffic to quota tokens when metering is on; requests are logged per
+//!    (hashed) destination and refused for blocked or throttled pools
+//! 5. Execute HTTP request (over a shared, pooled upstream client), open a
+//!    TCP tunnel, or bridge a WebSocket session, passing URLs and response
+//!    bodies through the operator's content filter
+//! 6. Create onion-routed response shards via LeaseSet (all at once, or
+//!    segment by segment for streamed requests); a request or response over
+//!    the size limits is answered with an error frame carrying the limit
+//!
+//! On startup the handler self-tests its upstream (IPv4/IPv6, DNS, large
+//! bodies, TCP tunnels) so the exit only advertises what actually works.
 
+mod abuse;
+mod access;
+mod assembly;
+mod content_filter;
 mod handler;
+mod pool;
+mod quota;
 mod request;
 mod response;
+mod response_cache;
+mod scheduler;
+mod self_test;
+mod sni;
+mod stream;
 mod tunnel_handler;
-
+mod websocket;
+
+pub use abuse::{
+    hash_destination, parse_destination, AbuseConfig, AbuseMonitor, AbuseReport, BlockTarget, DestinationHash,
+    DestinationVolume, ThrottledPool,
+};
+pub use access::{AccessLimits, TierLimits};
+pub use content_filter::{BlocklistFilter, BodyScanner, ContentFilter, FilterVerdict, NoopFilter};
 pub use handler::{ExitHandler, ExitConfig};
 pub use request::HttpRequest;
-pub use response::HttpResponse;
-pub use tunnel_handler::TunnelHandler;
+pub use response::{HeaderPolicy, HttpResponse, HOP_BY_HOP_HEADERS};
+pub use response_cache::ResponseCacheStats;
+pub use pool::FetchPoolStats;
+pub use scheduler::PoolQueueStats;
+pub use self_test::SelfTestTargets;
+pub use tunnel_handler::{SniAuditRecord, SniPolicy, TunnelHandler};
+pub use websocket::WebSocketHandler;
 
 use thiserror::Error;
+use craftnet_core::{ErrorCode, ExitErrorFrame};
 use craftnet_erasure::ErasureError;
 
 #[derive(Error, Debug)]
@@ -58,8 +95,48 @@ pub enum ExitError {
     #[error("Response too large: exceeds {0} byte limit")]
     ResponseTooLarge(usize),
 
+    #[error("Request too large: exceeds {0} byte limit")]
+    RequestTooLarge(usize),
+
     #[error("Rate limited: {0}")]
     RateLimited(String),
+
+    #[error("Subscription required: {0}")]
+    SubscriptionRequired(String),
+
+    #[error("Content filtered: {0}")]
+    ContentFiltered(String),
+}
+
+impl ExitError {
+    /// Machine-readable code of this error
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::InsufficientShards { .. }
+            | Self::ErasureDecodeError(_)
+            | Self::Erasure(_)
+            | Self::InvalidRequest(_) => ErrorCode::InvalidRequest,
+            Self::HttpError(e) if e.is_timeout() => ErrorCode::Timeout,
+            Self::HttpError(_) | Self::TunnelConnectFailed(_) | Self::TunnelIoError(_) => ErrorCode::UpstreamError,
+            Self::SettlementError(_) => ErrorCode::SettlementError,
+            Self::Timeout => ErrorCode::Timeout,
+            Self::BlockedDestination(_) | Self::ContentFiltered(_) => ErrorCode::BlockedDestination,
+            Self::ResponseTooLarge(_) => ErrorCode::ResponseTooLarge,
+            Self::RequestTooLarge(_) => ErrorCode::RequestTooLarge,
+            Self::RateLimited(_) => ErrorCode::RateLimited,
+            Self::SubscriptionRequired(_) => ErrorCode::SubscriptionNotFound,
+        }
+    }
+
+    /// Error frame to send the client instead of dropping the request
+    /// (None = the client gets no answer)
+    pub fn frame(&self) -> Option<ExitErrorFrame> {
+        match self {
+            Self::RequestTooLarge(limit) => Some(ExitErrorFrame::request_too_large(*limit as u64, self.to_string())),
+            Self::ResponseTooLarge(limit) => Some(ExitErrorFrame::response_too_large(*limit as u64, self.to_string())),
+            _ => None,
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, ExitError>;
@@ -0,0 +1,193 @@
+//! Shared upstream connection pool
+//!
+//! Every HTTP request the exit executes goes through one `reqwest::Client`,
+//! tuned so repeated requests to popular origins reuse warm connections:
+//! idle connections are kept per host, HTTP/2 is negotiated over TLS and
+//! multiplexes concurrent requests on one connection, and DNS answers are
+//! cached so a new connection doesn't wait on a lookup.
+//!
+//! The client only resolves a name when it needs a new connection, so the
+//! resolver doubles as a connection counter: requests that didn't trigger a
+//! resolve were served from the pool. Requests to IP literals skip the
+//! resolver and are always counted as pool hits.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+
+use crate::{ExitConfig, Result};
+
+/// Most names kept in the DNS cache
+const MAX_DNS_ENTRIES: usize = 4096;
+
+/// Connection reuse and DNS cache counters of the fetch pool
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FetchPoolStats {
+    /// Requests sent upstream
+    pub requests: u64,
+    /// Requests served on a pooled (idle or multiplexed) connection
+    pub pool_hits: u64,
+    /// Requests that had to open a new connection
+    pub pool_misses: u64,
+    /// New connections whose address came from the DNS cache
+    pub dns_hits: u64,
+    /// New connections that needed a DNS lookup
+    pub dns_misses: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    requests: AtomicU64,
+    connects: AtomicU64,
+    dns_hits: AtomicU64,
+    dns_misses: AtomicU64,
+}
+
+type DnsCache = Arc<Mutex<HashMap<String, (Vec<SocketAddr>, Instant)>>>;
+
+/// Resolver caching answers for `ttl` and counting new connections
+struct CachingResolver {
+    ttl: Duration,
+    cache: DnsCache,
+    counters: Arc<Counters>,
+}
+
+impl CachingResolver {
+    fn cached(&self, name: &str) -> Option<Vec<SocketAddr>> {
+        let cache = self.cache.lock().unwrap();
+        cache
+            .get(name)
+            .filter(|(_, at)| at.elapsed() < self.ttl)
+            .map(|(addrs, _)| addrs.clone())
+    }
+}
+
+impl Resolve for CachingResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let name = name.as_str().to_ascii_lowercase();
+        self.counters.connects.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(addrs) = self.cached(&name) {
+            self.counters.dns_hits.fetch_add(1, Ordering::Relaxed);
+            return Box::pin(async move { Ok(Box::new(addrs.into_iter()) as Addrs) });
+        }
+        self.counters.dns_misses.fetch_add(1, Ordering::Relaxed);
+
+        let ttl = self.ttl;
+        let cache = self.cache.clone();
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+            if !addrs.is_empty() && !ttl.is_zero() {
+                let mut cache = cache.lock().unwrap();
+                if cache.len() >= MAX_DNS_ENTRIES {
+                    cache.retain(|_, (_, at)| at.elapsed() < ttl);
+                }
+                if cache.len() < MAX_DNS_ENTRIES {
+                    cache.insert(name, (addrs.clone(), Instant::now()));
+                }
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// HTTP client with a tuned connection pool and DNS cache
+pub(crate) struct FetchPool {
+    client: reqwest::Client,
+    counters: Arc<Counters>,
+}
+
+impl FetchPool {
+    pub(crate) fn new(config: &ExitConfig) -> Result<Self> {
+        let counters = Arc::new(Counters::default());
+        let resolver = CachingResolver {
+            ttl: config.dns_cache_ttl,
+            cache: DnsCache::default(),
+            counters: counters.clone(),
+        };
+
+        let client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .user_agent("CraftNet/0.1")
+            .pool_max_idle_per_host(config.pool_max_idle_per_host)
+            .pool_idle_timeout(config.pool_idle_timeout)
+            .tcp_nodelay(true)
+            .tcp_keepalive(config.pool_idle_timeout)
+            .http2_adaptive_window(true)
+            .http2_keep_alive_interval(config.pool_idle_timeout / 2)
+            .http2_keep_alive_while_idle(true)
+            .dns_resolver(Arc::new(resolver))
+            .build()?;
+
+        Ok(Self { client, counters })
+    }
+
+    /// The shared client; every request built from it is counted
+    pub(crate) fn client(&self) -> &reqwest::Client {
+        self.counters.requests.fetch_add(1, Ordering::Relaxed);
+        &self.client
+    }
+
+    pub(crate) fn stats(&self) -> FetchPoolStats {
+        let requests = self.counters.requests.load(Ordering::Relaxed);
+        let connects = self.counters.connects.load(Ordering::Relaxed);
+        FetchPoolStats {
+            requests,
+            pool_hits: requests.saturating_sub(connects),
+            pool_misses: connects.min(requests),
+            dns_hits: self.counters.dns_hits.load(Ordering::Relaxed),
+            dns_misses: self.counters.dns_misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_repeated_requests_reuse_connection() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("ok"))
+            .mount(&server)
+            .await;
+        // Go through the resolver rather than the IP literal
+        let url = format!("http://localhost:{}/", server.address().port());
+
+        let pool = FetchPool::new(&ExitConfig::default()).unwrap();
+        for _ in 0..3 {
+            let response = pool.client().get(&url).send().await.unwrap();
+            assert_eq!(response.text().await.unwrap(), "ok");
+        }
+
+        let stats = pool.stats();
+        assert_eq!(stats.requests, 3);
+        assert_eq!(stats.pool_misses, 1);
+        assert_eq!(stats.pool_hits, 2);
+        assert_eq!(stats.dns_misses, 1);
+    }
+
+    #[tokio::test]
+    async fn test_dns_cache_serves_new_connections() {
+        let counters = Arc::new(Counters::default());
+        let resolver = CachingResolver {
+            ttl: Duration::from_secs(60),
+            cache: DnsCache::default(),
+            counters: counters.clone(),
+        };
+        for _ in 0..2 {
+            let addrs: Vec<SocketAddr> = resolver.resolve("localhost".parse().unwrap()).await.unwrap().collect();
+            assert!(!addrs.is_empty());
+        }
+        assert_eq!(counters.dns_misses.load(Ordering::Relaxed), 1);
+        assert_eq!(counters.dns_hits.load(Ordering::Relaxed), 1);
+        assert_eq!(counters.connects.load(Ordering::Relaxed), 2);
+    }
+}
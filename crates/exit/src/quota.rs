@@ -0,0 +1,277 @@
+//! Free-tier metering with quota tokens (see `craftnet_core::quota`)
+//!
+//! Requests from pools without a subscription pay with tokens attached to
+//! their `ExitPayload`, each worth `token_bytes` of traffic. Credit is kept
+//! per account: the session for tunnel payloads, the request for HTTP.
+//!
+//! - An HTTP request's tokens must cover its payload, and the response may
+//!   use what is left.
+//! - A tunnel frame needs the session's credit to cover its payload.
+//!   Response bytes are charged afterwards, so a response can push the
+//!   credit below zero and the next frame then has to bring more tokens.
+//!
+//! Tokens are checked with this exit's own quota key. Spent nonces are
+//! kept for as long as their epoch is spendable. With a spent file
+//! ([`QuotaLedger::load_spent`]) each redemption is appended to it before
+//! the credit is granted, so a restarted exit still refuses tokens spent
+//! before it went down.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use tracing::warn;
+
+use craftnet_core::{
+    quota_epoch, Id, PublicKey, QuotaIssueRequest, QuotaIssueResponse, QuotaIssuer, QuotaTerms, QuotaToken,
+};
+
+use crate::{ExitError, Result};
+
+/// Spent file record: epoch (u64 LE) + nonce
+const SPENT_RECORD_LEN: usize = 8 + 32;
+
+/// Quota epoch of the current time
+pub(crate) fn current_epoch() -> u64 {
+    quota_epoch(SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0))
+}
+
+/// Token issuance and spending at one exit
+pub(crate) struct QuotaLedger {
+    issuer: QuotaIssuer,
+    token_bytes: u64,
+    tokens_per_epoch: u32,
+    /// Spent token nonces per epoch
+    spent: HashMap<u64, HashSet<[u8; 32]>>,
+    /// File spent nonces are appended to (None = memory only)
+    spent_path: Option<PathBuf>,
+    /// Pools that got their batch, per epoch
+    issued: HashMap<u64, HashSet<PublicKey>>,
+    /// Remaining credit (bytes) and last use per account
+    credit: HashMap<Id, (i64, Instant)>,
+}
+
+impl QuotaLedger {
+    pub fn new(issuer: QuotaIssuer, token_bytes: u64, tokens_per_epoch: u32) -> Self {
+        Self {
+            issuer,
+            token_bytes: token_bytes.max(1),
+            tokens_per_epoch,
+            spent: HashMap::new(),
+            spent_path: None,
+            issued: HashMap::new(),
+            credit: HashMap::new(),
+        }
+    }
+
+    /// Load the nonces spent in `path` that are still spendable in `epoch`,
+    /// and append later redemptions to it. A missing file loads nothing.
+    /// Returns the number of nonces loaded.
+    pub fn load_spent(&mut self, path: PathBuf, epoch: u64) -> io::Result<usize> {
+        let data = match std::fs::read(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        let mut loaded = 0;
+        for record in data.chunks_exact(SPENT_RECORD_LEN) {
+            let token_epoch = u64::from_le_bytes(record[..8].try_into().unwrap());
+            let nonce: [u8; 32] = record[8..].try_into().unwrap();
+            if token_epoch + 1 >= epoch && self.spent.entry(token_epoch).or_default().insert(nonce) {
+                loaded += 1;
+            }
+        }
+        self.spent_path = Some(path);
+        // Drop expired epochs (and a torn trailing record) from the file
+        self.save_spent()?;
+        Ok(loaded)
+    }
+
+    /// Rewrite the spent file with the nonces held in memory
+    fn save_spent(&self) -> io::Result<()> {
+        let Some(ref path) = self.spent_path else { return Ok(()) };
+        let records = self.spent.iter().flat_map(|(epoch, nonces)| nonces.iter().map(move |n| (*epoch, *n)));
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, encode_spent(records))?;
+        std::fs::rename(&tmp, path)
+    }
+
+    /// Terms to advertise in the exit record
+    pub fn terms(&self) -> QuotaTerms {
+        QuotaTerms {
+            issuer_key: self.issuer.public_key(),
+            token_bytes: self.token_bytes,
+            tokens_per_epoch: self.tokens_per_epoch,
+        }
+    }
+
+    /// Sign `pool`'s batch for the current epoch (one batch per pool per epoch)
+    pub fn issue(&mut self, pool: PublicKey, request: &QuotaIssueRequest, epoch: u64) -> Result<QuotaIssueResponse> {
+        if request.epoch != epoch {
+            return Err(ExitError::InvalidRequest(format!(
+                "quota batch for epoch {}, current epoch is {}",
+                request.epoch, epoch,
+            )));
+        }
+        if request.blinded.len() > self.tokens_per_epoch as usize {
+            return Err(ExitError::InvalidRequest(format!(
+                "quota batch of {} tokens exceeds {} per epoch",
+                request.blinded.len(), self.tokens_per_epoch,
+            )));
+        }
+        if self.issued.get(&epoch).is_some_and(|pools| pools.contains(&pool)) {
+            return Err(ExitError::RateLimited("quota batch already issued this epoch".to_string()));
+        }
+        let response = self.issuer
+            .issue(request)
+            .map_err(|e| ExitError::InvalidRequest(e.to_string()))?;
+        self.issued.entry(epoch).or_default().insert(pool);
+        Ok(response)
+    }
+
+    /// Spend `tokens` into `account`'s credit and return the new balance.
+    ///
+    /// Either every token is valid, unspent and spendable in `epoch`, or
+    /// none is spent.
+    pub fn redeem(&mut self, account: Id, tokens: &[QuotaToken], epoch: u64) -> Result<i64> {
+        let mut nonces = HashSet::new();
+        for token in tokens {
+            if token.epoch != epoch && token.epoch + 1 != epoch {
+                return Err(ExitError::InvalidRequest(format!("quota token of expired epoch {}", token.epoch)));
+            }
+            if !self.issuer.verify(token) {
+                return Err(ExitError::InvalidRequest("invalid quota token".to_string()));
+            }
+            let spent = self.spent.get(&token.epoch).is_some_and(|s| s.contains(&token.nonce));
+            if spent || !nonces.insert((token.epoch, token.nonce)) {
+                return Err(ExitError::InvalidRequest("quota token already spent".to_string()));
+            }
+        }
+        if let Some(ref path) = self.spent_path {
+            append_spent(path, &encode_spent(nonces.iter().copied()))
+                .map_err(|e| ExitError::SettlementError(format!("failed to record spent quota tokens: {}", e)))?;
+        }
+        for (token_epoch, nonce) in nonces {
+            self.spent.entry(token_epoch).or_default().insert(nonce);
+        }
+
+        let entry = self.credit.entry(account).or_insert((0, Instant::now()));
+        entry.0 += tokens.len() as i64 * self.token_bytes as i64;
+        entry.1 = Instant::now();
+        Ok(entry.0)
+    }
+
+    /// Charge `bytes` to `account` and return the new balance (may be negative)
+    pub fn charge(&mut self, account: Id, bytes: usize) -> i64 {
+        let entry = self.credit.entry(account).or_insert((0, Instant::now()));
+        entry.0 -= bytes as i64;
+        entry.1 = Instant::now();
+        entry.0
+    }
+
+    /// Forget an account (finished HTTP request, closed session)
+    pub fn close(&mut self, account: &Id) {
+        self.credit.remove(account);
+    }
+
+    /// Drop epochs that can no longer be spent and accounts idle for `max_idle`
+    pub fn prune(&mut self, epoch: u64, max_idle: Duration) {
+        let before = self.spent.len();
+        self.spent.retain(|e, _| e + 1 >= epoch);
+        if self.spent.len() != before {
+            if let Err(e) = self.save_spent() {
+                warn!("Failed to rewrite spent quota tokens: {}", e);
+            }
+        }
+        self.issued.retain(|e, _| *e >= epoch);
+        self.credit.retain(|_, (_, last)| last.elapsed() < max_idle);
+    }
+}
+
+fn encode_spent(records: impl Iterator<Item = (u64, [u8; 32])>) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for (epoch, nonce) in records {
+        buf.extend_from_slice(&epoch.to_le_bytes());
+        buf.extend_from_slice(&nonce);
+    }
+    buf
+}
+
+fn append_spent(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(data)?;
+    file.sync_data()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use craftnet_core::PendingQuotaBatch;
+
+    fn ledger() -> QuotaLedger {
+        QuotaLedger::new(QuotaIssuer::from_seed(b"exit"), 1000, 4)
+    }
+
+    fn tokens(ledger: &mut QuotaLedger, pool: PublicKey, epoch: u64, count: usize) -> Vec<QuotaToken> {
+        let (pending, request) = PendingQuotaBatch::new(epoch, count);
+        let response = ledger.issue(pool, &request, epoch).unwrap();
+        pending.finalize(&ledger.terms().issuer_key, &response).unwrap()
+    }
+
+    #[test]
+    fn test_one_batch_per_pool_per_epoch() {
+        let mut ledger = ledger();
+        tokens(&mut ledger, [1; 32], 10, 4);
+
+        let (_, request) = PendingQuotaBatch::new(10, 1);
+        assert!(matches!(ledger.issue([1; 32], &request, 10), Err(ExitError::RateLimited(_))));
+        assert!(ledger.issue([2; 32], &request, 10).is_ok());
+        assert!(ledger.issue([3; 32], &request, 11).is_err());
+
+        let (_, too_many) = PendingQuotaBatch::new(10, 5);
+        assert!(ledger.issue([4; 32], &too_many, 10).is_err());
+    }
+
+    #[test]
+    fn test_redeem_and_double_spend() {
+        let mut ledger = ledger();
+        let batch = tokens(&mut ledger, [1; 32], 10, 3);
+
+        assert_eq!(ledger.redeem([7; 32], &batch[..2], 10).unwrap(), 2000);
+        assert_eq!(ledger.charge([7; 32], 1500), 500);
+        assert!(ledger.redeem([8; 32], &batch[1..], 10).is_err());
+        // The failed redemption spent nothing
+        assert_eq!(ledger.redeem([8; 32], &batch[2..], 11).unwrap(), 1000);
+
+        // Tokens expire after the epoch following their issuance
+        let old = tokens(&mut ledger, [2; 32], 10, 1);
+        assert!(ledger.redeem([9; 32], &old, 12).is_err());
+
+        ledger.prune(12, Duration::from_secs(60));
+        assert!(!ledger.spent.contains_key(&10));
+    }
+
+    #[test]
+    fn test_spent_nonces_survive_restart() {
+        let path = std::env::temp_dir().join(format!("craftnet-quota-spent-{}.bin", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut ledger = ledger();
+        assert_eq!(ledger.load_spent(path.clone(), 10).unwrap(), 0);
+        let batch = tokens(&mut ledger, [1; 32], 10, 2);
+        ledger.redeem([7; 32], &batch[..1], 10).unwrap();
+
+        // A restarted exit refuses the spent token but takes the other
+        let mut restarted = ledger();
+        assert_eq!(restarted.load_spent(path.clone(), 11).unwrap(), 1);
+        assert!(restarted.redeem([8; 32], &batch[..1], 11).is_err());
+        assert_eq!(restarted.redeem([8; 32], &batch[1..], 11).unwrap(), 1000);
+
+        // Expired epochs are dropped from the file
+        restarted.prune(12, Duration::from_secs(60));
+        assert_eq!(ledger().load_spent(path.clone(), 12).unwrap(), 0);
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
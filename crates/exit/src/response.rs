@@ -1,7 +1,111 @@
 //! HTTP response representation for exit node
+//!
+//! Origin headers pass through a [`HeaderPolicy`] before they reach the
+//! client: hop-by-hop headers describe the exit's connection to the origin,
+//! not the client's, and an unbounded header block would let an origin make
+//! every response arbitrarily expensive to carry. Headers only the exit may
+//! set (its error frame marker) are always dropped, so an origin can't forge
+//! them.
 
 use std::collections::HashMap;
 
+use craftnet_core::EXIT_ERROR_HEADER;
+
+/// Hop-by-hop headers (RFC 9110 §7.6.1), dropped by [`HeaderPolicy`]
+pub const HOP_BY_HOP_HEADERS: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// Default most headers kept per response
+pub const DEFAULT_MAX_RESPONSE_HEADERS: usize = 100;
+
+/// Default largest single header (`name: value`) kept
+pub const DEFAULT_MAX_RESPONSE_HEADER_BYTES: usize = 8 * 1024;
+
+/// Default header bytes kept per response
+pub const DEFAULT_MAX_RESPONSE_HEADERS_BYTES: usize = 64 * 1024;
+
+/// Which origin response headers an exit passes on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderPolicy {
+    /// Drop hop-by-hop headers and any header the origin's `Connection`
+    /// header names
+    pub strip_hop_by_hop: bool,
+    /// Most headers kept; later ones are dropped
+    pub max_headers: usize,
+    /// Headers whose `name: value` is longer than this are dropped
+    pub max_header_bytes: usize,
+    /// Header bytes kept in total; later headers are dropped
+    pub max_total_bytes: usize,
+    /// Drop `Set-Cookie` so origins can't track users across requests
+    /// (privacy mode)
+    pub strip_set_cookie: bool,
+}
+
+impl Default for HeaderPolicy {
+    fn default() -> Self {
+        Self {
+            strip_hop_by_hop: true,
+            max_headers: DEFAULT_MAX_RESPONSE_HEADERS,
+            max_header_bytes: DEFAULT_MAX_RESPONSE_HEADER_BYTES,
+            max_total_bytes: DEFAULT_MAX_RESPONSE_HEADERS_BYTES,
+            strip_set_cookie: false,
+        }
+    }
+}
+
+impl HeaderPolicy {
+    /// Filter `headers` (in the order the origin sent them), keeping the
+    /// ones the policy allows
+    pub fn apply(&self, headers: Vec<(String, String)>) -> Vec<(String, String)> {
+        // Connection: close, X-Foo marks X-Foo as hop-by-hop too
+        let connection_named: Vec<String> = if self.strip_hop_by_hop {
+            headers
+                .iter()
+                .filter(|(k, _)| k.eq_ignore_ascii_case("connection"))
+                .flat_map(|(_, v)| v.split(',').map(|t| t.trim().to_ascii_lowercase()))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let mut kept = Vec::new();
+        let mut total = 0;
+        for (key, value) in headers {
+            let name = key.to_ascii_lowercase();
+            if name == EXIT_ERROR_HEADER {
+                continue;
+            }
+            if self.strip_hop_by_hop
+                && (HOP_BY_HOP_HEADERS.contains(&name.as_str()) || connection_named.contains(&name))
+            {
+                continue;
+            }
+            if self.strip_set_cookie && name == "set-cookie" {
+                continue;
+            }
+            let size = key.len() + value.len() + 2;
+            if size > self.max_header_bytes {
+                continue;
+            }
+            if kept.len() >= self.max_headers || total + size > self.max_total_bytes {
+                break;
+            }
+            total += size;
+            kept.push((key, value));
+        }
+        kept
+    }
+}
+
 /// HTTP response to be fragmented into shards
 #[derive(Debug, Clone)]
 pub struct HttpResponse {
@@ -92,6 +196,56 @@ impl HttpResponse {
 mod tests {
     use super::*;
 
+    fn headers(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_default_header_policy() {
+        let policy = HeaderPolicy::default();
+        assert!(policy.strip_hop_by_hop);
+        assert!(!policy.strip_set_cookie);
+        assert_eq!(policy.max_headers, 100);
+        assert_eq!(policy.max_header_bytes, 8 * 1024);
+        assert_eq!(policy.max_total_bytes, 64 * 1024);
+
+        let kept = policy.apply(headers(&[
+            ("Content-Type", "text/html"),
+            ("Connection", "keep-alive, X-Origin-Hop"),
+            ("Keep-Alive", "timeout=5"),
+            ("Transfer-Encoding", "chunked"),
+            ("X-Origin-Hop", "1"),
+            ("Set-Cookie", "id=1"),
+            ("X-Craftnet-Exit-Error", "REQUEST_TOO_LARGE"),
+        ]));
+        assert_eq!(kept, headers(&[("Content-Type", "text/html"), ("Set-Cookie", "id=1")]));
+    }
+
+    #[test]
+    fn test_header_policy_limits() {
+        let policy = HeaderPolicy {
+            strip_hop_by_hop: false,
+            max_headers: 3,
+            max_header_bytes: 20,
+            max_total_bytes: 1024,
+            strip_set_cookie: true,
+        };
+        let kept = policy.apply(headers(&[
+            ("Connection", "close"),
+            ("set-cookie", "id=1"),
+            ("X-Huge", &"a".repeat(64)),
+            ("A", "1"),
+            ("B", "2"),
+            ("C", "3"),
+        ]));
+        assert_eq!(kept, headers(&[("Connection", "close"), ("A", "1"), ("B", "2")]));
+
+        // The total budget cuts the block short
+        let policy = HeaderPolicy { max_total_bytes: 12, ..HeaderPolicy::default() };
+        let kept = policy.apply(headers(&[("A", "12345"), ("B", "12345")]));
+        assert_eq!(kept, headers(&[("A", "12345")]));
+    }
+
     #[test]
     fn test_response_roundtrip() {
         let mut headers = HashMap::new();
@@ -0,0 +1,399 @@
+//! Exit-side response cache for idempotent GETs
+//!
+//! Popular content is fetched over and over by different clients through
+//! the same exit. `ResponseCache` keeps responses upstream marked as
+//! shareable, keyed by the normalized URL plus the request headers the
+//! response `Vary`s on, and serves them until their `max-age` runs out.
+//!
+//! The exit is a shared cache for many users, so it is conservative:
+//! only explicit `s-maxage`/`max-age` freshness is honoured, and requests
+//! with credentials or responses that set cookies, are `private`,
+//! `no-store` or `no-cache` are never stored. A cache hit still produces
+//! the usual response shards, so relays' receipts count it like any
+//! other response.
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+use crate::{HttpRequest, HttpResponse};
+
+/// Status codes cacheable by default (RFC 9111 §4.2.2)
+const CACHEABLE_STATUS: [u16; 7] = [200, 203, 204, 300, 301, 404, 410];
+
+/// Hit/miss counters and occupancy of the response cache
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResponseCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Responses currently cached
+    pub entries: usize,
+    /// Body and header bytes currently cached
+    pub bytes: usize,
+}
+
+struct Entry {
+    /// Lowercased names of the request headers the response varies on
+    vary: Vec<String>,
+    /// Values of those headers in the request that produced the response
+    vary_values: Vec<Option<String>>,
+    response: HttpResponse,
+    size: usize,
+    stored_at: Instant,
+    /// Age the response already had upstream
+    initial_age: Duration,
+    max_age: Duration,
+    /// LRU position (key into `ResponseCache::lru`)
+    tick: u64,
+}
+
+impl Entry {
+    fn age(&self, now: Instant) -> Duration {
+        self.initial_age + now.saturating_duration_since(self.stored_at)
+    }
+}
+
+/// Bounded LRU cache of upstream GET responses
+pub struct ResponseCache {
+    /// Normalized URL → variants of the response
+    entries: HashMap<String, Vec<Entry>>,
+    /// Recency order: tick → URL of the variant using it
+    lru: BTreeMap<u64, String>,
+    next_tick: u64,
+    max_bytes: usize,
+    max_entry_bytes: usize,
+    bytes: usize,
+    hits: u64,
+    misses: u64,
+}
+
+impl ResponseCache {
+    /// Cache holding up to `max_bytes`, none of its responses larger than
+    /// `max_entry_bytes`
+    pub fn new(max_bytes: usize, max_entry_bytes: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            lru: BTreeMap::new(),
+            next_tick: 0,
+            max_bytes,
+            max_entry_bytes: max_entry_bytes.min(max_bytes),
+            bytes: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// A fresh cached response for `request`, if any
+    pub fn get(&mut self, request: &HttpRequest) -> Option<HttpResponse> {
+        self.get_at(request, Instant::now())
+    }
+
+    fn get_at(&mut self, request: &HttpRequest, now: Instant) -> Option<HttpResponse> {
+        let key = cache_key(request)?;
+        let directives = cache_control(header(&request.headers, "cache-control"));
+        if directives.contains_key("no-store") {
+            return None;
+        }
+        // The client insists on a revalidated response
+        if directives.contains_key("no-cache") || directives.get("max-age") == Some(&Some(0)) {
+            self.misses += 1;
+            return None;
+        }
+
+        let tick = self.next_tick;
+        let Some(variants) = self.entries.get_mut(&key) else {
+            self.misses += 1;
+            return None;
+        };
+        let Some(index) = variants.iter().position(|e| e.vary_values == vary_values(&e.vary, request)) else {
+            self.misses += 1;
+            return None;
+        };
+
+        let entry = &mut variants[index];
+        let age = entry.age(now);
+        if age >= entry.max_age {
+            let entry = variants.swap_remove(index);
+            if variants.is_empty() {
+                self.entries.remove(&key);
+            }
+            self.lru.remove(&entry.tick);
+            self.bytes -= entry.size;
+            self.misses += 1;
+            return None;
+        }
+
+        self.lru.remove(&entry.tick);
+        entry.tick = tick;
+        self.lru.insert(tick, key);
+        self.next_tick += 1;
+        self.hits += 1;
+
+        let mut response = entry.response.clone();
+        response.headers.insert("age".to_string(), age.as_secs().to_string());
+        Some(response)
+    }
+
+    /// Store `response` to `request` if both allow shared caching
+    pub fn insert(&mut self, request: &HttpRequest, response: &HttpResponse) {
+        self.insert_at(request, response, Instant::now())
+    }
+
+    fn insert_at(&mut self, request: &HttpRequest, response: &HttpResponse, now: Instant) {
+        let Some(key) = cache_key(request) else {
+            return;
+        };
+        let Some(max_age) = shared_max_age(request, response) else {
+            return;
+        };
+        let vary = match header(&response.headers, "vary") {
+            Some(v) if v.trim() == "*" => return,
+            Some(v) => v
+                .split(',')
+                .map(|h| h.trim().to_ascii_lowercase())
+                .filter(|h| !h.is_empty())
+                .collect(),
+            None => Vec::new(),
+        };
+        let initial_age = header(&response.headers, "age")
+            .and_then(|a| a.trim().parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or_default();
+        if initial_age >= max_age {
+            return;
+        }
+
+        let size = response.body.len()
+            + response.headers.iter().map(|(k, v)| k.len() + v.len()).sum::<usize>();
+        if size > self.max_entry_bytes {
+            return;
+        }
+
+        let vary_values = vary_values(&vary, request);
+        self.remove_variant(&key, &vary_values);
+        while self.bytes + size > self.max_bytes {
+            if !self.evict_oldest() {
+                return;
+            }
+        }
+
+        let tick = self.next_tick;
+        self.next_tick += 1;
+        let mut response = response.clone();
+        response.headers.retain(|k, _| !k.eq_ignore_ascii_case("age"));
+        self.entries.entry(key.clone()).or_default().push(Entry {
+            vary,
+            vary_values,
+            response,
+            size,
+            stored_at: now,
+            initial_age,
+            max_age,
+            tick,
+        });
+        self.lru.insert(tick, key);
+        self.bytes += size;
+    }
+
+    fn remove_variant(&mut self, key: &str, vary_values: &[Option<String>]) {
+        let Some(variants) = self.entries.get_mut(key) else {
+            return;
+        };
+        if let Some(index) = variants.iter().position(|e| e.vary_values == vary_values) {
+            let entry = variants.swap_remove(index);
+            self.lru.remove(&entry.tick);
+            self.bytes -= entry.size;
+        }
+        if variants.is_empty() {
+            self.entries.remove(key);
+        }
+    }
+
+    /// Drop the least recently used response; false if the cache is empty
+    fn evict_oldest(&mut self) -> bool {
+        let Some((tick, key)) = self.lru.pop_first() else {
+            return false;
+        };
+        if let Some(variants) = self.entries.get_mut(&key) {
+            if let Some(index) = variants.iter().position(|e| e.tick == tick) {
+                self.bytes -= variants.swap_remove(index).size;
+            }
+            if variants.is_empty() {
+                self.entries.remove(&key);
+            }
+        }
+        true
+    }
+
+    pub fn stats(&self) -> ResponseCacheStats {
+        ResponseCacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            entries: self.lru.len(),
+            bytes: self.bytes,
+        }
+    }
+}
+
+/// Normalized URL of a cacheable request (None = not cacheable)
+fn cache_key(request: &HttpRequest) -> Option<String> {
+    if !request.method.eq_ignore_ascii_case("GET")
+        || request.body.as_ref().is_some_and(|b| !b.is_empty())
+        || header(&request.headers, "authorization").is_some()
+        || header(&request.headers, "cookie").is_some()
+    {
+        return None;
+    }
+    // Parsing lowercases scheme and host and drops default ports
+    let mut url = reqwest::Url::parse(&request.url).ok()?;
+    url.set_fragment(None);
+    Some(url.into())
+}
+
+/// How long `response` may be served from a shared cache (None = not at all)
+fn shared_max_age(request: &HttpRequest, response: &HttpResponse) -> Option<Duration> {
+    if !CACHEABLE_STATUS.contains(&response.status)
+        || header(&response.headers, "set-cookie").is_some()
+        || cache_control(header(&request.headers, "cache-control")).contains_key("no-store")
+    {
+        return None;
+    }
+    let directives = cache_control(header(&response.headers, "cache-control"));
+    if ["no-store", "no-cache", "private"].iter().any(|d| directives.contains_key(*d)) {
+        return None;
+    }
+    let secs = directives
+        .get("s-maxage")
+        .or_else(|| directives.get("max-age"))
+        .copied()
+        .flatten()?;
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+/// Cache-Control directives with their numeric argument, if any
+fn cache_control(value: Option<&str>) -> HashMap<String, Option<u64>> {
+    value
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|d| {
+            let (name, arg) = match d.split_once('=') {
+                Some((name, arg)) => (name, arg.trim().trim_matches('"').parse().ok()),
+                None => (d, None),
+            };
+            let name = name.trim().to_ascii_lowercase();
+            (!name.is_empty()).then_some((name, arg))
+        })
+        .collect()
+}
+
+fn vary_values(vary: &[String], request: &HttpRequest) -> Vec<Option<String>> {
+    vary.iter()
+        .map(|name| header(&request.headers, name).map(|v| v.trim().to_string()))
+        .collect()
+}
+
+/// Case-insensitive header lookup
+fn header<'a>(headers: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(url: &str, headers: &[(&str, &str)]) -> HttpRequest {
+        HttpRequest {
+            method: "GET".to_string(),
+            url: url.to_string(),
+            headers: headers.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            body: None,
+        }
+    }
+
+    fn response(headers: &[(&str, &str)], body: &[u8]) -> HttpResponse {
+        HttpResponse::new(
+            200,
+            headers.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            body.to_vec(),
+        )
+    }
+
+    #[test]
+    fn test_hit_until_max_age() {
+        let mut cache = ResponseCache::new(1024 * 1024, 1024 * 1024);
+        let now = Instant::now();
+        cache.insert_at(
+            &get("HTTPS://Example.com:443/a#frag", &[]),
+            &response(&[("cache-control", "public, max-age=60")], b"hello"),
+            now,
+        );
+
+        let request = get("https://example.com/a", &[]);
+        let hit = cache.get_at(&request, now + Duration::from_secs(10)).unwrap();
+        assert_eq!(hit.body, b"hello");
+        assert_eq!(hit.headers.get("age").map(String::as_str), Some("10"));
+
+        assert!(cache.get_at(&request, now + Duration::from_secs(60)).is_none());
+        assert_eq!(cache.stats(), ResponseCacheStats { hits: 1, misses: 1, entries: 0, bytes: 0 });
+    }
+
+    #[test]
+    fn test_uncacheable_requests_and_responses() {
+        let mut cache = ResponseCache::new(1024 * 1024, 1024 * 1024);
+        let fresh = response(&[("cache-control", "max-age=60")], b"x");
+        let url = "https://example.com/";
+
+        cache.insert(&get(url, &[("Authorization", "Bearer t")]), &fresh);
+        cache.insert(&get(url, &[]), &response(&[("cache-control", "private, max-age=60")], b"x"));
+        cache.insert(&get(url, &[]), &response(&[("cache-control", "no-store")], b"x"));
+        cache.insert(&get(url, &[]), &response(&[("cache-control", "max-age=60"), ("set-cookie", "a=b")], b"x"));
+        cache.insert(&get(url, &[]), &response(&[], b"x"));
+        cache.insert(&get(url, &[]), &response(&[("cache-control", "max-age=60"), ("vary", "*")], b"x"));
+        let mut post = get(url, &[]);
+        post.method = "POST".to_string();
+        cache.insert(&post, &fresh);
+        assert_eq!(cache.stats().entries, 0);
+
+        cache.insert(&get(url, &[]), &fresh);
+        assert!(cache.get(&get(url, &[("Cache-Control", "no-cache")])).is_none());
+        assert!(cache.get(&get(url, &[])).is_some());
+    }
+
+    #[test]
+    fn test_vary_selects_variant() {
+        let mut cache = ResponseCache::new(1024 * 1024, 1024 * 1024);
+        let url = "https://example.com/page";
+        let vary = [("cache-control", "max-age=60"), ("vary", "Accept-Language")];
+        cache.insert(&get(url, &[("Accept-Language", "en")]), &response(&vary, b"hello"));
+        cache.insert(&get(url, &[("accept-language", "fr")]), &response(&vary, b"bonjour"));
+
+        assert_eq!(cache.get(&get(url, &[("Accept-Language", "fr")])).unwrap().body, b"bonjour");
+        assert_eq!(cache.get(&get(url, &[("Accept-Language", "en")])).unwrap().body, b"hello");
+        assert!(cache.get(&get(url, &[])).is_none());
+        assert_eq!(cache.stats().entries, 2);
+    }
+
+    #[test]
+    fn test_lru_eviction_within_byte_bound() {
+        let mut cache = ResponseCache::new(100, 60);
+        let fresh = |body: &[u8]| response(&[("cache-control", "max-age=60")], body);
+        let a = get("https://example.com/a", &[]);
+        let b = get("https://example.com/b", &[]);
+        let c = get("https://example.com/c", &[]);
+
+        cache.insert(&a, &fresh(&[0; 20]));
+        cache.insert(&b, &fresh(&[0; 20]));
+        // Touch a so b is the least recently used
+        assert!(cache.get(&a).is_some());
+        cache.insert(&c, &fresh(&[0; 20]));
+        assert!(cache.get(&b).is_none());
+        assert!(cache.get(&a).is_some() && cache.get(&c).is_some());
+        assert!(cache.stats().bytes <= 100);
+
+        // Larger than one entry may be
+        cache.insert(&get("https://example.com/big", &[]), &fresh(&[0; 80]));
+        assert!(cache.get(&get("https://example.com/big", &[])).is_none());
+    }
+}
@@ -0,0 +1,280 @@
+//! Fair scheduling of completed requests across pools
+//!
+//! Completed assemblies wait here until the exit has upstream capacity for
+//! them. Each pool (the pool pubkey from the routing tag) gets its own FIFO
+//! queue, and queues are served by deficit round robin: a pool with weight
+//! `w` may start up to `w` requests per round before the next pool's turn.
+//! A pool that floods the exit only grows its own queue (bounded by
+//! `max_queued_per_pool`) instead of delaying everyone else.
+//!
+//! Requests are "in flight" from `next()` until `finish()`. Both a global
+//! and a per-pool in-flight cap apply.
+
+use std::collections::{HashMap, VecDeque};
+
+use craftnet_core::{Id, PublicKey};
+
+use crate::{ExitConfig, ExitError, Result};
+
+/// Queue depth and load of one pool
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolQueueStats {
+    pub pool: PublicKey,
+    pub weight: u32,
+    /// Completed requests waiting for a slot
+    pub queued: usize,
+    /// Requests being processed
+    pub in_flight: usize,
+}
+
+#[derive(Debug)]
+struct PoolQueue {
+    queue: VecDeque<Id>,
+    in_flight: usize,
+    weight: u32,
+    /// Requests this pool may still start in the current round
+    deficit: u32,
+}
+
+/// Weighted-fair queue of ready requests
+#[derive(Debug)]
+pub(crate) struct FairQueue {
+    pools: HashMap<PublicKey, PoolQueue>,
+    /// Pools with queued requests, in round-robin order
+    active: VecDeque<PublicKey>,
+    /// In-flight request → owning pool
+    in_flight: HashMap<Id, PublicKey>,
+    max_concurrent: usize,
+    max_concurrent_per_pool: usize,
+    max_queued_per_pool: usize,
+}
+
+impl FairQueue {
+    pub fn new(max_concurrent: usize, max_concurrent_per_pool: usize, max_queued_per_pool: usize) -> Self {
+        Self {
+            pools: HashMap::new(),
+            active: VecDeque::new(),
+            in_flight: HashMap::new(),
+            max_concurrent: max_concurrent.max(1),
+            max_concurrent_per_pool: max_concurrent_per_pool.max(1),
+            max_queued_per_pool,
+        }
+    }
+
+    pub fn for_config(config: &ExitConfig) -> Self {
+        Self::new(
+            config.max_concurrent_requests,
+            config.max_concurrent_per_pool,
+            config.max_queued_per_pool,
+        )
+    }
+
+    /// Queue a ready request for `pool` (no-op if already queued or running)
+    pub fn push(&mut self, pool: PublicKey, id: Id, weight: u32) -> Result<()> {
+        if self.contains(&id) {
+            return Ok(());
+        }
+        let entry = self.pools.entry(pool).or_insert_with(|| PoolQueue {
+            queue: VecDeque::new(),
+            in_flight: 0,
+            weight: weight.max(1),
+            deficit: 0,
+        });
+        entry.weight = weight.max(1);
+        if entry.queue.len() >= self.max_queued_per_pool {
+            return Err(ExitError::RateLimited(
+                "per-pool request queue full".to_string(),
+            ));
+        }
+        if entry.queue.is_empty() {
+            self.active.push_back(pool);
+        }
+        entry.queue.push_back(id);
+        Ok(())
+    }
+
+    /// Take the next request to process, if a slot is free
+    pub fn next(&mut self) -> Option<Id> {
+        if self.in_flight.len() >= self.max_concurrent {
+            return None;
+        }
+
+        // Every pool gets at most one look per call; a pool at its
+        // in-flight cap keeps its place (and deficit) for a later call
+        let mut skipped = 0;
+        while skipped < self.active.len() {
+            let pool = *self.active.front()?;
+            let q = self.pools.get_mut(&pool)?;
+
+            if q.in_flight >= self.max_concurrent_per_pool {
+                self.active.rotate_left(1);
+                skipped += 1;
+                continue;
+            }
+
+            if q.deficit == 0 {
+                q.deficit = q.weight;
+            }
+            let id = q.queue.pop_front()?;
+            q.deficit -= 1;
+            q.in_flight += 1;
+
+            if q.queue.is_empty() {
+                q.deficit = 0;
+                self.active.pop_front();
+            } else if q.deficit == 0 {
+                self.active.rotate_left(1);
+            }
+            self.in_flight.insert(id, pool);
+            return Some(id);
+        }
+        None
+    }
+
+    /// Whether a request is queued or in flight
+    pub fn contains(&self, id: &Id) -> bool {
+        self.in_flight.contains_key(id) || self.pools.values().any(|q| q.queue.contains(id))
+    }
+
+    /// Whether a request could be started now
+    pub fn has_ready(&self) -> bool {
+        self.in_flight.len() < self.max_concurrent
+            && self.active.iter().any(|pool| {
+                self.pools.get(pool).is_some_and(|q| q.in_flight < self.max_concurrent_per_pool)
+            })
+    }
+
+    /// Release a request's slot, or drop it from its queue if not started
+    pub fn finish(&mut self, id: &Id) {
+        if let Some(pool) = self.in_flight.remove(id) {
+            if let Some(q) = self.pools.get_mut(&pool) {
+                q.in_flight = q.in_flight.saturating_sub(1);
+            }
+            self.remove_idle(&pool);
+            return;
+        }
+
+        let Some((pool, q)) = self.pools.iter_mut().find(|(_, q)| q.queue.contains(id)) else {
+            return;
+        };
+        let pool = *pool;
+        q.queue.retain(|queued| queued != id);
+        if q.queue.is_empty() {
+            q.deficit = 0;
+            self.active.retain(|p| *p != pool);
+        }
+        self.remove_idle(&pool);
+    }
+
+    fn remove_idle(&mut self, pool: &PublicKey) {
+        if self.pools.get(pool).is_some_and(|q| q.queue.is_empty() && q.in_flight == 0) {
+            self.pools.remove(pool);
+        }
+    }
+
+    /// Requests waiting for a slot
+    pub fn queued(&self) -> usize {
+        self.pools.values().map(|q| q.queue.len()).sum()
+    }
+
+    /// Requests being processed
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Per-pool queue depths, busiest first
+    pub fn stats(&self) -> Vec<PoolQueueStats> {
+        let mut stats: Vec<PoolQueueStats> = self.pools.iter()
+            .map(|(pool, q)| PoolQueueStats {
+                pool: *pool,
+                weight: q.weight,
+                queued: q.queue.len(),
+                in_flight: q.in_flight,
+            })
+            .collect();
+        stats.sort_by(|a, b| (b.queued + b.in_flight).cmp(&(a.queued + a.in_flight)).then(a.pool.cmp(&b.pool)));
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(pool: u8, n: u8) -> Id {
+        let mut id = [0u8; 32];
+        id[0] = pool;
+        id[1] = n;
+        id
+    }
+
+    #[test]
+    fn test_round_robin_across_pools() {
+        let mut q = FairQueue::new(100, 100, 100);
+        let (a, b) = ([1u8; 32], [2u8; 32]);
+        for n in 0..4 {
+            q.push(a, id(1, n), 1).unwrap();
+        }
+        q.push(b, id(2, 0), 1).unwrap();
+        q.push(b, id(2, 1), 1).unwrap();
+
+        let order: Vec<u8> = std::iter::from_fn(|| q.next()).map(|id| id[0]).collect();
+        assert_eq!(order, vec![1, 2, 1, 2, 1, 1]);
+    }
+
+    #[test]
+    fn test_weights() {
+        let mut q = FairQueue::new(100, 100, 100);
+        let (a, b) = ([1u8; 32], [2u8; 32]);
+        for n in 0..6 {
+            q.push(a, id(1, n), 2).unwrap();
+            q.push(b, id(2, n), 1).unwrap();
+        }
+
+        let order: Vec<u8> = std::iter::from_fn(|| q.next()).take(6).map(|id| id[0]).collect();
+        assert_eq!(order, vec![1, 1, 2, 1, 1, 2]);
+    }
+
+    #[test]
+    fn test_concurrency_limits() {
+        let mut q = FairQueue::new(3, 2, 100);
+        let (a, b) = ([1u8; 32], [2u8; 32]);
+        for n in 0..4 {
+            q.push(a, id(1, n), 1).unwrap();
+        }
+
+        // Per-pool cap: only two of pool a's requests start
+        let first = q.next().unwrap();
+        assert!(q.next().is_some());
+        assert!(q.next().is_none());
+        assert!(!q.has_ready());
+
+        // Another pool still gets the remaining global slot
+        q.push(b, id(2, 0), 1).unwrap();
+        assert_eq!(q.next().unwrap()[0], 2);
+        assert_eq!(q.in_flight(), 3);
+
+        // Global cap reached until something finishes
+        assert!(q.next().is_none());
+        q.finish(&first);
+        assert_eq!(q.next().unwrap()[0], 1);
+    }
+
+    #[test]
+    fn test_queue_depth_and_stats() {
+        let mut q = FairQueue::new(1, 1, 2);
+        let a = [1u8; 32];
+        q.push(a, id(1, 0), 1).unwrap();
+        q.push(a, id(1, 1), 1).unwrap();
+        assert!(matches!(q.push(a, id(1, 2), 1), Err(ExitError::RateLimited(_))));
+
+        let started = q.next().unwrap();
+        assert_eq!(q.stats(), vec![PoolQueueStats { pool: a, weight: 1, queued: 1, in_flight: 1 }]);
+
+        // Dropping a queued request and finishing the running one empties the pool
+        q.finish(&id(1, 1));
+        q.finish(&started);
+        assert!(q.stats().is_empty());
+        assert_eq!(q.queued(), 0);
+    }
+}
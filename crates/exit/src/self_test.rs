@@ -0,0 +1,214 @@
+//! Startup self-test
+//!
+//! A misconfigured exit (no IPv6 route, broken resolver, firewalled
+//! outbound ports) would otherwise register in the DHT and then fail every
+//! request sent its way. Before the exit record is published, the handler
+//! fetches a few known targets over its real upstream client and opens a
+//! TCP tunnel to an echo server; the resulting [`ExitCapabilities`] go into
+//! the record so clients only route what actually works.
+
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tracing::{debug, info};
+
+use craftnet_core::ExitCapabilities;
+
+/// Bytes written to (and expected back from) the echo server
+const ECHO_PROBE: &[u8] = b"craftnet-exit-self-test\n";
+
+/// Targets probed by the self-test
+#[derive(Debug, Clone)]
+pub struct SelfTestTargets {
+    /// URL on an IPv4 literal (no DNS involved)
+    pub ipv4_url: String,
+    /// URL on an IPv6 literal (no DNS involved)
+    pub ipv6_url: String,
+    /// Hostname that must resolve upstream
+    pub dns_host: String,
+    /// URL serving at least `large_body_bytes`; also times upstream throughput
+    pub large_body_url: String,
+    /// Smallest body that counts as a successful large fetch
+    pub large_body_bytes: usize,
+    /// `host:port` of a TCP echo server
+    pub tcp_echo: String,
+    /// Time allowed for each probe
+    pub timeout: Duration,
+}
+
+impl Default for SelfTestTargets {
+    fn default() -> Self {
+        Self {
+            ipv4_url: "http://1.1.1.1/cdn-cgi/trace".to_string(),
+            ipv6_url: "http://[2606:4700:4700::1111]/cdn-cgi/trace".to_string(),
+            dns_host: "one.one.one.one".to_string(),
+            large_body_url: "https://speed.cloudflare.com/__down?bytes=5000000".to_string(),
+            large_body_bytes: 5_000_000,
+            tcp_echo: "tcpbin.com:4242".to_string(),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Whether a GET of `url` succeeds; returns the body size and fetch time
+async fn fetch(client: &reqwest::Client, url: &str, timeout: Duration) -> Option<(usize, Duration)> {
+    let started = Instant::now();
+    let result = async {
+        let response = client.get(url).timeout(timeout).send().await?.error_for_status()?;
+        response.bytes().await
+    }
+    .await;
+    match result {
+        Ok(body) => Some((body.len(), started.elapsed())),
+        Err(e) => {
+            debug!("Self-test fetch of {} failed: {}", url, e);
+            None
+        }
+    }
+}
+
+/// Whether `host` resolves to at least one address
+async fn resolves(host: &str, timeout: Duration) -> bool {
+    match tokio::time::timeout(timeout, tokio::net::lookup_host((host, 0))).await {
+        Ok(Ok(mut addrs)) => addrs.next().is_some(),
+        Ok(Err(e)) => {
+            debug!("Self-test lookup of {} failed: {}", host, e);
+            false
+        }
+        Err(_) => false,
+    }
+}
+
+/// Whether a TCP connection to the echo server carries data both ways
+async fn tunnel_echoes(addr: &str, timeout: Duration) -> bool {
+    let probe = async {
+        let mut stream = TcpStream::connect(addr).await?;
+        stream.write_all(ECHO_PROBE).await?;
+        let mut echoed = vec![0u8; ECHO_PROBE.len()];
+        stream.read_exact(&mut echoed).await?;
+        Ok::<_, std::io::Error>(echoed == ECHO_PROBE)
+    };
+    match tokio::time::timeout(timeout, probe).await {
+        Ok(Ok(echoed)) => echoed,
+        Ok(Err(e)) => {
+            debug!("Self-test tunnel to {} failed: {}", addr, e);
+            false
+        }
+        Err(_) => false,
+    }
+}
+
+/// Throughput of a transfer in KB/s
+fn throughput_kbps(bytes: usize, elapsed: Duration) -> u32 {
+    let secs = elapsed.as_secs_f64().max(0.001);
+    ((bytes as f64 / 1024.0) / secs).min(u32::MAX as f64) as u32
+}
+
+/// Probe every target concurrently over `client`
+pub(crate) async fn run(client: &reqwest::Client, targets: &SelfTestTargets) -> ExitCapabilities {
+    let timeout = targets.timeout;
+    let (ipv4, ipv6, dns, large, tcp_tunnel) = tokio::join!(
+        fetch(client, &targets.ipv4_url, timeout),
+        fetch(client, &targets.ipv6_url, timeout),
+        resolves(&targets.dns_host, timeout),
+        fetch(client, &targets.large_body_url, timeout),
+        tunnel_echoes(&targets.tcp_echo, timeout),
+    );
+
+    let large = large.filter(|(bytes, _)| *bytes >= targets.large_body_bytes);
+    let capabilities = ExitCapabilities {
+        ipv4: ipv4.is_some(),
+        ipv6: ipv6.is_some(),
+        dns,
+        large_body: large.is_some(),
+        tcp_tunnel,
+        upstream_kbps: large.map(|(bytes, elapsed)| throughput_kbps(bytes, elapsed)).unwrap_or(0),
+    };
+    info!(
+        "Exit self-test: ipv4={} ipv6={} dns={} large_body={} tcp_tunnel={} upstream={} KB/s",
+        capabilities.ipv4, capabilities.ipv6, capabilities.dns,
+        capabilities.large_body, capabilities.tcp_tunnel, capabilities.upstream_kbps,
+    );
+    capabilities
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn echo_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let (mut reader, mut writer) = stream.split();
+                    let _ = tokio::io::copy(&mut reader, &mut writer).await;
+                });
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_self_test_reports_working_capabilities() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/trace"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("ok"))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/large"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(vec![0u8; 64 * 1024]))
+            .mount(&server)
+            .await;
+
+        let targets = SelfTestTargets {
+            ipv4_url: format!("{}/trace", server.uri()),
+            // Nothing listens on the discard port
+            ipv6_url: "http://[::1]:9/trace".to_string(),
+            dns_host: "localhost".to_string(),
+            large_body_url: format!("{}/large", server.uri()),
+            large_body_bytes: 64 * 1024,
+            tcp_echo: echo_server().await,
+            timeout: Duration::from_secs(2),
+        };
+        let capabilities = run(&reqwest::Client::new(), &targets).await;
+
+        assert!(capabilities.ipv4);
+        assert!(!capabilities.ipv6);
+        assert!(capabilities.dns);
+        assert!(capabilities.large_body);
+        assert!(capabilities.tcp_tunnel);
+        assert!(capabilities.can_serve_http());
+    }
+
+    #[tokio::test]
+    async fn test_truncated_large_body_fails() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(vec![0u8; 1024]))
+            .mount(&server)
+            .await;
+
+        let targets = SelfTestTargets {
+            ipv4_url: server.uri(),
+            ipv6_url: server.uri(),
+            dns_host: "localhost".to_string(),
+            large_body_url: server.uri(),
+            large_body_bytes: 64 * 1024,
+            tcp_echo: "127.0.0.1:9".to_string(),
+            timeout: Duration::from_secs(2),
+        };
+        let capabilities = run(&reqwest::Client::new(), &targets).await;
+
+        assert!(!capabilities.large_body);
+        assert_eq!(capabilities.upstream_kbps, 0);
+        assert!(!capabilities.tcp_tunnel);
+    }
+}
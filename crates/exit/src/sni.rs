@@ -0,0 +1,227 @@
+//! TLS ClientHello sniffing
+//!
+//! Tunnels to port 443 carry TLS, and the first thing the client sends is a
+//! ClientHello naming the server it wants in the SNI extension. Reading that
+//! name lets the exit apply its policy to the hostname the client actually
+//! talks to, not just the address the tunnel was opened to. Nothing is
+//! decrypted or modified: the hello is forwarded unchanged.
+
+/// Largest ClientHello buffered while waiting for it to complete
+pub const MAX_HELLO_BYTES: usize = 64 * 1024;
+
+const RECORD_HANDSHAKE: u8 = 0x16;
+const HANDSHAKE_CLIENT_HELLO: u8 = 0x01;
+const EXTENSION_SERVER_NAME: u16 = 0x0000;
+const NAME_TYPE_HOST_NAME: u8 = 0x00;
+
+/// What the first bytes of a tunnel turned out to be
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sniffed {
+    /// A ClientHello has started but more bytes are needed
+    Incomplete,
+    /// Not a (well-formed) TLS ClientHello
+    NotTls,
+    /// A ClientHello without a server name
+    NoSni,
+    /// A ClientHello for this hostname (lowercased)
+    Sni(String),
+}
+
+/// Sniff the SNI hostname from the start of a TLS stream.
+///
+/// The ClientHello may span several handshake records; they are reassembled
+/// up to `MAX_HELLO_BYTES`.
+pub fn sniff(data: &[u8]) -> Sniffed {
+    match sniff_records(data) {
+        // Never buffer without bound for a hello that doesn't finish
+        Sniffed::Incomplete if data.len() >= MAX_HELLO_BYTES => Sniffed::NotTls,
+        sniffed => sniffed,
+    }
+}
+
+fn sniff_records(data: &[u8]) -> Sniffed {
+    let mut handshake = Vec::new();
+    let mut pos = 0;
+    loop {
+        let Some(header) = data.get(pos..pos + 5) else {
+            return if data.get(pos).is_some_and(|t| *t != RECORD_HANDSHAKE) {
+                Sniffed::NotTls
+            } else {
+                Sniffed::Incomplete
+            };
+        };
+        if header[0] != RECORD_HANDSHAKE {
+            return Sniffed::NotTls;
+        }
+        let len = u16::from_be_bytes([header[3], header[4]]) as usize;
+        let Some(fragment) = data.get(pos + 5..pos + 5 + len) else {
+            return Sniffed::Incomplete;
+        };
+        handshake.extend_from_slice(fragment);
+        pos += 5 + len;
+
+        if handshake.len() < 4 {
+            continue;
+        }
+        if handshake[0] != HANDSHAKE_CLIENT_HELLO {
+            return Sniffed::NotTls;
+        }
+        let hello_len = u32::from_be_bytes([0, handshake[1], handshake[2], handshake[3]]) as usize;
+        if hello_len + 4 > MAX_HELLO_BYTES {
+            return Sniffed::NotTls;
+        }
+        if handshake.len() >= hello_len + 4 {
+            return match server_name(&handshake[4..4 + hello_len]) {
+                Some(Some(host)) => Sniffed::Sni(host),
+                Some(None) => Sniffed::NoSni,
+                None => Sniffed::NotTls,
+            };
+        }
+    }
+}
+
+/// Minimal big-endian reader over a ClientHello body
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.data.len() < n {
+            return None;
+        }
+        let (head, rest) = self.data.split_at(n);
+        self.data = rest;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    /// Length-prefixed vector with a `len_bytes`-byte length
+    fn vec(&mut self, len_bytes: usize) -> Option<&'a [u8]> {
+        let len = match len_bytes {
+            1 => self.u8()? as usize,
+            _ => self.u16()? as usize,
+        };
+        self.take(len)
+    }
+}
+
+/// Server name of a ClientHello body: `None` if malformed, `Some(None)` if
+/// it has no host_name entry
+fn server_name(hello: &[u8]) -> Option<Option<String>> {
+    let mut r = Reader { data: hello };
+    r.take(2)?; // legacy_version
+    r.take(32)?; // random
+    r.vec(1)?; // legacy_session_id
+    r.vec(2)?; // cipher_suites
+    r.vec(1)?; // legacy_compression_methods
+    if r.data.is_empty() {
+        // No extensions at all
+        return Some(None);
+    }
+
+    let mut extensions = Reader { data: r.vec(2)? };
+    while !extensions.data.is_empty() {
+        let ext_type = extensions.u16()?;
+        let body = extensions.vec(2)?;
+        if ext_type != EXTENSION_SERVER_NAME {
+            continue;
+        }
+        let mut list = Reader { data: Reader { data: body }.vec(2)? };
+        while !list.data.is_empty() {
+            let name_type = list.u8()?;
+            let name = list.vec(2)?;
+            if name_type != NAME_TYPE_HOST_NAME {
+                continue;
+            }
+            let host = std::str::from_utf8(name).ok()?;
+            if host.is_empty() || !host.bytes().all(|b| b.is_ascii_graphic()) {
+                return None;
+            }
+            return Some(Some(host.trim_end_matches('.').to_ascii_lowercase()));
+        }
+        return Some(None);
+    }
+    Some(None)
+}
+
+/// Build a ClientHello record (for tests)
+#[cfg(test)]
+pub(crate) fn client_hello(sni: Option<&str>) -> Vec<u8> {
+    let mut extensions = Vec::new();
+    if let Some(host) = sni {
+        let mut entry = vec![NAME_TYPE_HOST_NAME];
+        entry.extend_from_slice(&(host.len() as u16).to_be_bytes());
+        entry.extend_from_slice(host.as_bytes());
+        let mut list = (entry.len() as u16).to_be_bytes().to_vec();
+        list.extend_from_slice(&entry);
+        extensions.extend_from_slice(&EXTENSION_SERVER_NAME.to_be_bytes());
+        extensions.extend_from_slice(&(list.len() as u16).to_be_bytes());
+        extensions.extend_from_slice(&list);
+    }
+    // supported_versions: TLS 1.3
+    extensions.extend_from_slice(&[0x00, 0x2b, 0x00, 0x03, 0x02, 0x03, 0x04]);
+
+    let mut hello = vec![0x03, 0x03];
+    hello.extend_from_slice(&[7u8; 32]);
+    hello.push(0); // session id
+    hello.extend_from_slice(&[0x00, 0x02, 0x13, 0x01]); // TLS_AES_128_GCM_SHA256
+    hello.extend_from_slice(&[0x01, 0x00]); // null compression
+    hello.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+    hello.extend_from_slice(&extensions);
+
+    let mut handshake = vec![HANDSHAKE_CLIENT_HELLO];
+    handshake.extend_from_slice(&(hello.len() as u32).to_be_bytes()[1..]);
+    handshake.extend_from_slice(&hello);
+
+    let mut record = vec![RECORD_HANDSHAKE, 0x03, 0x01];
+    record.extend_from_slice(&(handshake.len() as u16).to_be_bytes());
+    record.extend_from_slice(&handshake);
+    record
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_hostname() {
+        let hello = client_hello(Some("WWW.Example.com"));
+        assert_eq!(sniff(&hello), Sniffed::Sni("www.example.com".to_string()));
+    }
+
+    #[test]
+    fn test_sniff_without_sni() {
+        assert_eq!(sniff(&client_hello(None)), Sniffed::NoSni);
+    }
+
+    #[test]
+    fn test_sniff_incomplete_and_not_tls() {
+        let hello = client_hello(Some("example.com"));
+        assert_eq!(sniff(&[]), Sniffed::Incomplete);
+        assert_eq!(sniff(&hello[..3]), Sniffed::Incomplete);
+        assert_eq!(sniff(&hello[..hello.len() - 1]), Sniffed::Incomplete);
+        assert_eq!(sniff(b"GET / HTTP/1.1\r\n\r\n"), Sniffed::NotTls);
+    }
+
+    #[test]
+    fn test_sniff_hello_split_across_records() {
+        let hello = client_hello(Some("split.example"));
+        let handshake = &hello[5..];
+        let (a, b) = handshake.split_at(20);
+        let mut records = Vec::new();
+        for fragment in [a, b] {
+            records.extend_from_slice(&[RECORD_HANDSHAKE, 0x03, 0x01]);
+            records.extend_from_slice(&(fragment.len() as u16).to_be_bytes());
+            records.extend_from_slice(fragment);
+        }
+        assert_eq!(sniff(&records), Sniffed::Sni("split.example".to_string()));
+    }
+}
@@ -0,0 +1,300 @@
+//! Streamed HTTP responses
+//!
+//! For `PAYLOAD_MODE_HTTP_STREAM` requests the exit does not wait for the full
+//! body. Once the upstream headers arrive it returns a head segment, then reads
+//! the body in `STREAM_SEGMENT_SIZE` slices and turns each slice into its own
+//! encrypted, erasure-coded response assembly (see `craftnet_core::stream`).
+//!
+//! Backpressure: segments go to the node through a bounded channel. When the
+//! node's outbound queue is full it stops draining that channel, `run` blocks
+//! on `send`, and the upstream body is no longer read — so a slow relay path
+//! throttles the origin instead of piling segments up in exit memory.
+//!
+//! The content filter scans each chunk as it is read, before the segment
+//! holding it is produced (see `content_filter`).
+
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+use craftnet_core::{
+    segment_assembly_id, ExitPayload, ResponseSegment, Shard, STREAM_SEGMENT_SIZE,
+};
+
+use crate::content_filter::{BodyScanner, FilterVerdict};
+use crate::handler::build_response_shards;
+use crate::{ExitError, HeaderPolicy, Result};
+
+/// Response shards paired with the gateway each one should be sent to
+pub(crate) type ShardPairs = Vec<(Shard, Option<Vec<u8>>)>;
+
+/// Reads an upstream response and cuts it into response segments
+pub(crate) struct ResponseStreamer {
+    response: reqwest::Response,
+    exit_payload: ExitPayload,
+    exit_secret: [u8; 32],
+    max_response_size: usize,
+    /// Which origin headers go into the head segment
+    header_policy: HeaderPolicy,
+    /// Content filter scanning the body as it is read (None = not scanned)
+    scanner: Option<Box<dyn BodyScanner>>,
+    /// Body bytes read but not yet sent
+    buf: Vec<u8>,
+    /// Body bytes read so far
+    total: usize,
+    /// Upstream body fully read
+    eof: bool,
+    /// Final segment (End or Error) already produced
+    finished: bool,
+}
+
+impl ResponseStreamer {
+    pub(crate) fn new(
+        response: reqwest::Response,
+        exit_payload: ExitPayload,
+        exit_secret: [u8; 32],
+        max_response_size: usize,
+        header_policy: HeaderPolicy,
+        scanner: Option<Box<dyn BodyScanner>>,
+    ) -> Self {
+        Self {
+            response,
+            exit_payload,
+            exit_secret,
+            max_response_size,
+            header_policy,
+            scanner,
+            buf: Vec::new(),
+            total: 0,
+            eof: false,
+            finished: false,
+        }
+    }
+
+    /// Head segment (status + headers) from the upstream response
+    pub(crate) fn head(&self) -> ResponseSegment {
+        let headers = self.response
+            .headers()
+            .iter()
+            .filter_map(|(k, v)| v.to_str().ok().map(|v| (k.to_string(), v.to_string())))
+            .collect();
+        ResponseSegment::Head {
+            status: self.response.status().as_u16(),
+            headers: self.header_policy.apply(headers),
+        }
+    }
+
+    /// Shards for segment 0 (the head)
+    pub(crate) fn head_shards(&self) -> Result<ShardPairs> {
+        self.segment_shards(0, &self.head())
+    }
+
+    /// Encrypt and erasure-code one segment as its own response assembly
+    fn segment_shards(&self, seq: u32, segment: &ResponseSegment) -> Result<ShardPairs> {
+        let data = segment.to_bytes()
+            .map_err(|e| ExitError::InvalidRequest(format!("Segment encode failed: {}", e)))?;
+        build_response_shards(
+            &self.exit_secret,
+            &self.exit_payload,
+            segment_assembly_id(&self.exit_payload.request_id, seq),
+            &data,
+            0,
+        )
+    }
+
+    /// Next body segment, then End (or Error); None once the stream is over
+    pub(crate) async fn next_segment(&mut self) -> Option<ResponseSegment> {
+        if self.finished {
+            return None;
+        }
+        while !self.eof && self.buf.len() < STREAM_SEGMENT_SIZE {
+            match self.response.chunk().await {
+                Ok(Some(chunk)) => {
+                    self.total += chunk.len();
+                    if self.total > self.max_response_size {
+                        self.finished = true;
+                        let err = ExitError::ResponseTooLarge(self.max_response_size);
+                        return Some(ResponseSegment::Error(err.to_string()));
+                    }
+                    if let Some(scanner) = self.scanner.as_mut() {
+                        if let FilterVerdict::Block(reason) = scanner.scan(&chunk).await {
+                            self.finished = true;
+                            return Some(ResponseSegment::Error(ExitError::ContentFiltered(reason).to_string()));
+                        }
+                    }
+                    self.buf.extend_from_slice(&chunk);
+                }
+                Ok(None) => {
+                    self.eof = true;
+                    // The whole-body verdict comes before the last segment
+                    if let Some(scanner) = self.scanner.as_mut() {
+                        if let FilterVerdict::Block(reason) = scanner.finish().await {
+                            self.finished = true;
+                            return Some(ResponseSegment::Error(ExitError::ContentFiltered(reason).to_string()));
+                        }
+                    }
+                }
+                Err(e) => {
+                    self.finished = true;
+                    return Some(ResponseSegment::Error(e.to_string()));
+                }
+            }
+        }
+        if !self.buf.is_empty() {
+            let take = self.buf.len().min(STREAM_SEGMENT_SIZE);
+            return Some(ResponseSegment::Body(self.buf.drain(..take).collect()));
+        }
+        self.finished = true;
+        Some(ResponseSegment::End { total_bytes: self.total as u64 })
+    }
+
+    /// Send every remaining segment (1..) to `sink`, waiting for capacity.
+    ///
+    /// Stops early if the sink is closed (node shutting down).
+    pub(crate) async fn run(mut self, sink: mpsc::Sender<ShardPairs>) {
+        let request = hex::encode(&self.exit_payload.request_id[..8]);
+        let mut seq = 1u32;
+        while let Some(segment) = self.next_segment().await {
+            let pairs = match self.segment_shards(seq, &segment) {
+                Ok(pairs) => pairs,
+                Err(e) => {
+                    warn!("Stream segment {} failed for request={}: {}", seq, request, e);
+                    return;
+                }
+            };
+            if sink.send(pairs).await.is_err() {
+                debug!("Stream sink closed, dropping request={} at segment {}", request, seq);
+                return;
+            }
+            seq += 1;
+        }
+        debug!("Streamed request={} in {} segments ({} bytes)", request, seq, self.total);
+    }
+
+    /// Shards for every remaining segment (1..), for callers without a sink
+    pub(crate) async fn collect(mut self) -> Result<ShardPairs> {
+        let mut all = Vec::new();
+        let mut seq = 1u32;
+        while let Some(segment) = self.next_segment().await {
+            all.extend(self.segment_shards(seq, &segment)?);
+            seq += 1;
+        }
+        Ok(all)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use craftnet_core::{LeaseSet, ShardType, PAYLOAD_MODE_HTTP_STREAM};
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn payload() -> ExitPayload {
+        ExitPayload {
+            request_id: [3u8; 32],
+            user_pubkey: [4u8; 32],
+            lease_set: LeaseSet {
+                session_id: [0u8; 32],
+                leases: vec![],
+            },
+            total_hops: 0,
+            shard_type: ShardType::Request,
+            mode: PAYLOAD_MODE_HTTP_STREAM,
+            data: vec![],
+            response_enc_pubkey: [5u8; 32],
+            pool_pubkey: [4u8; 32],
+            quota_tokens: vec![],
+            trace_pubkey: None,
+        }
+    }
+
+    async fn streamer_for(body: Vec<u8>, max: usize) -> (MockServer, ResponseStreamer) {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(body))
+            .mount(&server)
+            .await;
+        let response = reqwest::get(server.uri()).await.unwrap();
+        (server, ResponseStreamer::new(response, payload(), [6u8; 32], max, HeaderPolicy::default(), None))
+    }
+
+    #[tokio::test]
+    async fn test_body_split_into_segments() {
+        let body: Vec<u8> = (0..STREAM_SEGMENT_SIZE * 2 + 100).map(|i| i as u8).collect();
+        let (_server, mut streamer) = streamer_for(body.clone(), usize::MAX).await;
+
+        assert!(matches!(streamer.head(), ResponseSegment::Head { status: 200, .. }));
+
+        let mut received = Vec::new();
+        let mut segments = 0;
+        loop {
+            match streamer.next_segment().await.unwrap() {
+                ResponseSegment::Body(data) => {
+                    assert!(data.len() <= STREAM_SEGMENT_SIZE);
+                    received.extend(data);
+                    segments += 1;
+                }
+                ResponseSegment::End { total_bytes } => {
+                    assert_eq!(total_bytes, body.len() as u64);
+                    break;
+                }
+                other => panic!("unexpected segment {:?}", other),
+            }
+        }
+        assert_eq!(segments, 3);
+        assert_eq!(received, body);
+        assert!(streamer.next_segment().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_oversized_body_ends_with_error() {
+        let (_server, mut streamer) = streamer_for(vec![1u8; 10_000], 1_000).await;
+
+        let last = loop {
+            let segment = streamer.next_segment().await.unwrap();
+            if segment.is_final() {
+                break segment;
+            }
+        };
+        assert!(matches!(last, ResponseSegment::Error(_)));
+        assert!(streamer.next_segment().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_filtered_body_ends_with_error_before_last_segment() {
+        use crate::content_filter::{BlocklistFilter, ContentFilter};
+        use sha2::{Digest, Sha256};
+
+        let body = vec![7u8; STREAM_SEGMENT_SIZE + 10];
+        let filter = BlocklistFilter::new().block_body_hash(Sha256::digest(&body).into());
+        let (_server, streamer) = streamer_for(body, usize::MAX).await;
+        let mut streamer = ResponseStreamer { scanner: filter.body_scanner("http://origin/"), ..streamer };
+
+        let mut segments = Vec::new();
+        while let Some(segment) = streamer.next_segment().await {
+            segments.push(segment);
+        }
+        // The first full segment went out; the tail is withheld for the verdict
+        assert_eq!(segments.len(), 2);
+        assert!(matches!(&segments[0], ResponseSegment::Body(data) if data.len() == STREAM_SEGMENT_SIZE));
+        assert!(matches!(&segments[1], ResponseSegment::Error(e) if e.contains("blocklist")));
+    }
+
+    #[tokio::test]
+    async fn test_run_sends_one_batch_per_segment() {
+        let body = vec![9u8; STREAM_SEGMENT_SIZE + 1];
+        let (_server, streamer) = streamer_for(body, usize::MAX).await;
+        assert!(!streamer.head_shards().unwrap().is_empty());
+
+        let (tx, mut rx) = mpsc::channel(1);
+        tokio::spawn(streamer.run(tx));
+
+        let mut batches = 0;
+        while let Some(pairs) = rx.recv().await {
+            assert!(!pairs.is_empty());
+            batches += 1;
+        }
+        // Two body segments + End
+        assert_eq!(batches, 3);
+    }
+}
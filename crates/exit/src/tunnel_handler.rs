@@ -4,16 +4,24 @@
 //! Each session maps a `session_id` to a live TCP connection to the
 //! destination host. Request bytes are piped to the destination and
 //! response bytes are read back and returned.
+//!
+//! With an [`SniPolicy`], tunnels to port 443 are held until the client's
+//! TLS ClientHello is complete; the exit policy is applied to its SNI
+//! hostname before the destination is dialed, and a hash of the hostname is
+//! kept as an [`SniAuditRecord`]. The hello itself is passed through as is.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::time::{Duration, Instant};
 
+use sha2::{Digest, Sha256};
+
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
 use tracing::{debug, info, warn};
 
 use craftnet_core::{Id, PublicKey, TunnelMetadata};
 
+use crate::sni::{self, Sniffed};
 use crate::{ExitError, Result};
 
 /// Maximum bytes to read from a TCP destination per burst
@@ -22,6 +30,60 @@ const MAX_RESPONSE_BYTES: usize = 256 * 1024; // 256 KB
 /// Idle timeout for reading response bytes from destination
 const READ_IDLE_TIMEOUT: Duration = Duration::from_millis(100);
 
+/// Port whose tunnels carry TLS and get their ClientHello sniffed
+const TLS_PORT: u16 = 443;
+
+/// Audit records kept until drained (oldest dropped first)
+const MAX_AUDIT_RECORDS: usize = 10_000;
+
+/// Exit policy applied to the SNI hostname of TLS tunnels
+#[derive(Debug, Clone, Default)]
+pub struct SniPolicy {
+    /// Hostnames containing any of these are refused (as `ExitConfig::blocked_domains`)
+    pub blocked_domains: Vec<String>,
+    /// Refuse TLS tunnels whose ClientHello names no server (or isn't TLS)
+    pub require_sni: bool,
+}
+
+impl SniPolicy {
+    /// Check a sniffed hostname (`None` = no SNI) against the policy
+    pub fn check(&self, hostname: Option<&str>) -> Result<()> {
+        match hostname {
+            Some(host) => {
+                for domain in &self.blocked_domains {
+                    if host.contains(domain.as_str()) {
+                        return Err(ExitError::BlockedDestination(domain.clone()));
+                    }
+                }
+                Ok(())
+            }
+            None if self.require_sni => Err(ExitError::BlockedDestination(
+                "TLS ClientHello without SNI".to_string(),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Policy decision on one TLS tunnel, for audit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SniAuditRecord {
+    pub session_id: Id,
+    pub pool_pubkey: PublicKey,
+    /// SHA-256 of the lowercased SNI hostname (None = no SNI)
+    pub hostname_hash: Option<[u8; 32]>,
+    /// Whether the tunnel was allowed
+    pub allowed: bool,
+    /// Unix timestamp (seconds) of the decision
+    pub timestamp: u64,
+}
+
+/// Start of a TLS tunnel buffered until its ClientHello is complete
+struct PendingHello {
+    buf: Vec<u8>,
+    last_activity: Instant,
+}
+
 /// Active TCP session to a destination
 struct TcpSession {
     stream: TcpStream,
@@ -33,6 +95,12 @@ struct TcpSession {
 /// TCP tunnel handler managing session pool
 pub struct TunnelHandler {
     sessions: HashMap<Id, TcpSession>,
+    /// SNI policy for TLS tunnels (None = connect without sniffing)
+    sni_policy: Option<SniPolicy>,
+    /// TLS tunnels waiting for the rest of their ClientHello
+    pending_hellos: HashMap<Id, PendingHello>,
+    /// SNI decisions not yet drained
+    sni_audit: VecDeque<SniAuditRecord>,
 }
 
 impl TunnelHandler {
@@ -40,9 +108,18 @@ impl TunnelHandler {
     pub fn new(_keypair: craftec_crypto::SigningKeypair) -> Self {
         Self {
             sessions: HashMap::new(),
+            sni_policy: None,
+            pending_hellos: HashMap::new(),
+            sni_audit: VecDeque::new(),
         }
     }
 
+    /// Sniff TLS tunnels and enforce `policy` on their SNI hostname
+    pub fn with_sni_policy(mut self, policy: Option<SniPolicy>) -> Self {
+        self.sni_policy = policy;
+        self
+    }
+
     /// Process tunnel data: connect, write, read, return raw response bytes.
     ///
     /// The caller (ExitHandler) is responsible for creating response shards.
@@ -55,9 +132,11 @@ impl TunnelHandler {
         pool_pubkey: PublicKey,
     ) -> Result<(Vec<u8>, bool)> {
         let session_id = metadata.session_id;
+        let mut data = data;
 
         // Handle close signal
         if metadata.is_close {
+            self.pending_hellos.remove(&session_id);
             if self.sessions.remove(&session_id).is_some() {
                 debug!(
                     "Tunnel session {} closed by client",
@@ -67,6 +146,14 @@ impl TunnelHandler {
             return Ok((Vec::new(), false));
         }
 
+        // Hold TLS tunnels until the SNI hostname passes the policy
+        if !self.sessions.contains_key(&session_id) && metadata.port == TLS_PORT {
+            match self.sniff_hello(session_id, data, pool_pubkey)? {
+                Some(hello) => data = hello,
+                None => return Ok((Vec::new(), false)),
+            }
+        }
+
         // Get or create session
         #[allow(clippy::map_entry)]
         if !self.sessions.contains_key(&session_id) {
@@ -142,12 +229,76 @@ impl TunnelHandler {
         Ok((response_buf, eof))
     }
 
+    /// Buffer the start of a TLS tunnel until its ClientHello is complete,
+    /// then check the SNI hostname against the policy.
+    ///
+    /// Returns the bytes to send once the tunnel may connect, or `None` while
+    /// more of the hello is needed.
+    fn sniff_hello(
+        &mut self,
+        session_id: Id,
+        data: Vec<u8>,
+        pool_pubkey: PublicKey,
+    ) -> Result<Option<Vec<u8>>> {
+        let Some(policy) = &self.sni_policy else {
+            return Ok(Some(data));
+        };
+
+        let buf = match self.pending_hellos.remove(&session_id) {
+            Some(mut pending) => {
+                pending.buf.extend_from_slice(&data);
+                pending.buf
+            }
+            None => data,
+        };
+        let hostname = match sni::sniff(&buf) {
+            Sniffed::Incomplete => {
+                self.pending_hellos.insert(session_id, PendingHello {
+                    buf,
+                    last_activity: Instant::now(),
+                });
+                return Ok(None);
+            }
+            Sniffed::Sni(host) => Some(host),
+            Sniffed::NoSni | Sniffed::NotTls => None,
+        };
+
+        let verdict = policy.check(hostname.as_deref());
+        if let Err(e) = &verdict {
+            info!("Refused TLS tunnel {}: {}", hex::encode(&session_id[..8]), e);
+        }
+
+        if self.sni_audit.len() >= MAX_AUDIT_RECORDS {
+            self.sni_audit.pop_front();
+        }
+        self.sni_audit.push_back(SniAuditRecord {
+            session_id,
+            pool_pubkey,
+            hostname_hash: hostname.map(|host| Sha256::digest(host.as_bytes()).into()),
+            allowed: verdict.is_ok(),
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        });
+
+        verdict.map(|()| Some(buf))
+    }
+
+    /// Drain the SNI policy decisions recorded since the last call
+    pub fn take_sni_audit(&mut self) -> Vec<SniAuditRecord> {
+        self.sni_audit.drain(..).collect()
+    }
+
     /// Remove sessions idle longer than `max_age`.
     ///
     /// Returns pool_pubkeys of evicted sessions so the caller can decrement
     /// per-user concurrent_tunnels counters.
     pub fn clear_stale(&mut self, max_age: Duration) -> Vec<PublicKey> {
         let now = Instant::now();
+        // Never connected, so not counted against the owner's tunnels
+        self.pending_hellos.retain(|_, p| now.duration_since(p.last_activity) < max_age);
+
         let stale_ids: Vec<Id> = self.sessions.iter()
             .filter(|(_, session)| now.duration_since(session.last_activity) >= max_age)
             .map(|(id, _)| *id)
@@ -177,3 +328,75 @@ impl TunnelHandler {
         self.sessions.len()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sni::client_hello;
+
+    fn handler(require_sni: bool) -> TunnelHandler {
+        TunnelHandler::new(craftec_crypto::SigningKeypair::generate()).with_sni_policy(Some(SniPolicy {
+            blocked_domains: vec!["blocked.example".to_string()],
+            require_sni,
+        }))
+    }
+
+    fn metadata(session: u8) -> TunnelMetadata {
+        TunnelMetadata {
+            host: "127.0.0.1".to_string(),
+            port: TLS_PORT,
+            session_id: [session; 32],
+            is_close: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blocked_sni_refused_before_connect() {
+        let mut handler = handler(false);
+        let hello = client_hello(Some("www.Blocked.example"));
+        let result = handler.process_tunnel_bytes(&metadata(1), hello, [9u8; 32]).await;
+        assert!(matches!(result, Err(ExitError::BlockedDestination(_))));
+        assert!(!handler.has_session(&[1u8; 32]));
+
+        let audit = handler.take_sni_audit();
+        assert_eq!(audit.len(), 1);
+        assert!(!audit[0].allowed);
+        assert_eq!(audit[0].pool_pubkey, [9u8; 32]);
+        let expected: [u8; 32] = Sha256::digest(b"www.blocked.example").into();
+        assert_eq!(audit[0].hostname_hash, Some(expected));
+        assert!(handler.take_sni_audit().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_partial_hello_is_buffered() {
+        let mut handler = handler(true);
+        let hello = client_hello(Some("blocked.example"));
+        let (first, rest) = hello.split_at(10);
+
+        let (response, zombie) = handler.process_tunnel_bytes(&metadata(2), first.to_vec(), [0u8; 32]).await.unwrap();
+        assert!(response.is_empty() && !zombie);
+        assert!(!handler.has_session(&[2u8; 32]));
+        assert!(handler.take_sni_audit().is_empty());
+
+        let result = handler.process_tunnel_bytes(&metadata(2), rest.to_vec(), [0u8; 32]).await;
+        assert!(matches!(result, Err(ExitError::BlockedDestination(_))));
+    }
+
+    #[tokio::test]
+    async fn test_missing_sni_refused_when_required() {
+        let mut handler = handler(true);
+        let result = handler.process_tunnel_bytes(&metadata(3), client_hello(None), [0u8; 32]).await;
+        assert!(matches!(result, Err(ExitError::BlockedDestination(_))));
+        let audit = handler.take_sni_audit();
+        assert_eq!(audit[0].hostname_hash, None);
+    }
+
+    #[test]
+    fn test_policy_check() {
+        let policy = SniPolicy { blocked_domains: vec!["bad.example".into()], require_sni: false };
+        assert!(policy.check(Some("good.example")).is_ok());
+        assert!(policy.check(Some("cdn.bad.example")).is_err());
+        assert!(policy.check(None).is_ok());
+        assert!(SniPolicy { require_sni: true, ..policy }.check(None).is_err());
+    }
+}
@@ -0,0 +1,355 @@
+//! WebSocket handler for exit node
+//!
+//! Manages WebSocket sessions opened by `PAYLOAD_MODE_WEBSOCKET` bursts (see
+//! `craftnet_core::websocket`). The exit performs the upgrade against the
+//! origin; a bridge task per session then owns the origin connection,
+//! queueing inbound messages until the client's next burst collects them
+//! and forwarding the messages each burst carries.
+//!
+//! Keepalive is handled here, not end to end: the origin's pings are
+//! answered by the bridge, which also pings the origin every
+//! `ping_interval` and drops a session whose origin missed a pong. Sessions
+//! the client stops polling for `idle_timeout` are closed.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use futures::{SinkExt, StreamExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::{HeaderName, HeaderValue};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use tracing::{debug, info};
+
+use craftnet_core::{Id, PublicKey, WsClose, WsMessage, WS_CLOSE_ABNORMAL};
+
+use crate::{ExitError, Result};
+
+/// Most message bytes returned per burst (the rest waits for the next one)
+const MAX_REPLY_BYTES: usize = 256 * 1024;
+
+/// Inbound messages queued per session before the origin is no longer read
+const INBOUND_QUEUE: usize = 256;
+
+/// How long a burst waits for the origin once no message is queued
+const READ_IDLE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// What the bridge task hands to the session
+enum Inbound {
+    Message(WsMessage),
+    Closed(WsClose),
+}
+
+/// Live WebSocket session to an origin
+struct WsSession {
+    /// Messages for the origin (dropping it closes the session)
+    outgoing: mpsc::Sender<Message>,
+    /// Messages and the close from the origin
+    inbound: mpsc::Receiver<Inbound>,
+    last_activity: Instant,
+    /// Pool pubkey of the user who owns this session (for resource tracking)
+    pool_pubkey: PublicKey,
+}
+
+/// WebSocket handler managing the session pool
+pub struct WebSocketHandler {
+    sessions: HashMap<Id, WsSession>,
+    connect_timeout: Duration,
+    ping_interval: Duration,
+    idle_timeout: Duration,
+}
+
+impl WebSocketHandler {
+    /// Create a handler opening origins within `connect_timeout`
+    pub fn new(connect_timeout: Duration, ping_interval: Duration, idle_timeout: Duration) -> Self {
+        Self {
+            sessions: HashMap::new(),
+            connect_timeout,
+            ping_interval,
+            idle_timeout,
+        }
+    }
+
+    /// Upgrade a connection to `url` and start bridging it as `session_id`.
+    ///
+    /// Returns the subprotocol the origin picked. The caller checks `url`
+    /// against the exit policy first.
+    pub async fn open(
+        &mut self,
+        session_id: Id,
+        url: &str,
+        headers: &[(String, String)],
+        protocols: &[String],
+        pool_pubkey: PublicKey,
+    ) -> Result<Option<String>> {
+        if self.sessions.contains_key(&session_id) {
+            return Err(ExitError::InvalidRequest("WebSocket session already open".to_string()));
+        }
+
+        let mut request = url
+            .into_client_request()
+            .map_err(|e| ExitError::InvalidRequest(format!("Invalid WebSocket URL: {}", e)))?;
+        for (key, value) in headers {
+            let name = HeaderName::from_bytes(key.as_bytes())
+                .map_err(|e| ExitError::InvalidRequest(format!("Invalid header {}: {}", key, e)))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|e| ExitError::InvalidRequest(format!("Invalid header {}: {}", key, e)))?;
+            request.headers_mut().insert(name, value);
+        }
+        if !protocols.is_empty() {
+            let offered = HeaderValue::from_str(&protocols.join(", "))
+                .map_err(|e| ExitError::InvalidRequest(format!("Invalid subprotocol: {}", e)))?;
+            request.headers_mut().insert("sec-websocket-protocol", offered);
+        }
+
+        let (stream, response) = tokio::time::timeout(self.connect_timeout, tokio_tungstenite::connect_async(request))
+            .await
+            .map_err(|_| ExitError::Timeout)?
+            .map_err(|e| ExitError::TunnelConnectFailed(format!("{}: {}", url, e)))?;
+        let protocol = response
+            .headers()
+            .get("sec-websocket-protocol")
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+
+        let (outgoing_tx, outgoing_rx) = mpsc::channel(INBOUND_QUEUE);
+        let (inbound_tx, inbound_rx) = mpsc::channel(INBOUND_QUEUE);
+        tokio::spawn(bridge(stream, outgoing_rx, inbound_tx, self.ping_interval));
+        self.sessions.insert(session_id, WsSession {
+            outgoing: outgoing_tx,
+            inbound: inbound_rx,
+            last_activity: Instant::now(),
+            pool_pubkey,
+        });
+
+        info!("WebSocket session {} open to {}", hex::encode(&session_id[..8]), url);
+        Ok(protocol)
+    }
+
+    /// Forward `messages` to the origin and collect what it sent meanwhile.
+    ///
+    /// Returns the origin's messages and, once the session ended, its close
+    /// (the session is then removed). Only the pool that opened the session
+    /// may use it.
+    pub async fn exchange(
+        &mut self,
+        session_id: &Id,
+        pool_pubkey: &PublicKey,
+        messages: Vec<WsMessage>,
+    ) -> Result<(Vec<WsMessage>, Option<WsClose>)> {
+        let session = self.sessions.get_mut(session_id)
+            .filter(|s| s.pool_pubkey == *pool_pubkey)
+            .ok_or_else(|| ExitError::InvalidRequest("unknown WebSocket session".to_string()))?;
+        session.last_activity = Instant::now();
+
+        let mut close = None;
+        for message in messages {
+            let message = match message {
+                WsMessage::Text(text) => Message::Text(text),
+                WsMessage::Binary(data) => Message::Binary(data),
+            };
+            if session.outgoing.send(message).await.is_err() {
+                close = Some(WsClose { code: WS_CLOSE_ABNORMAL, reason: "origin connection lost".to_string() });
+                break;
+            }
+        }
+
+        let mut received = Vec::new();
+        let mut bytes = 0;
+        while close.is_none() && bytes < MAX_REPLY_BYTES {
+            match tokio::time::timeout(READ_IDLE_TIMEOUT, session.inbound.recv()).await {
+                Ok(Some(Inbound::Message(message))) => {
+                    bytes += message.len();
+                    received.push(message);
+                }
+                Ok(Some(Inbound::Closed(c))) => close = Some(c),
+                Ok(None) => {
+                    close = Some(WsClose { code: WS_CLOSE_ABNORMAL, reason: "origin connection lost".to_string() });
+                }
+                Err(_) => break,
+            }
+        }
+
+        if let Some(c) = &close {
+            self.sessions.remove(session_id);
+            debug!("WebSocket session {} closed by origin ({})", hex::encode(&session_id[..8]), c.code);
+        }
+        Ok((received, close))
+    }
+
+    /// Close a session at the client's request. Returns whether it existed.
+    pub async fn close(&mut self, session_id: &Id, pool_pubkey: &PublicKey, close: &WsClose) -> bool {
+        if self.sessions.get(session_id).is_none_or(|s| s.pool_pubkey != *pool_pubkey) {
+            return false;
+        }
+        let Some(session) = self.sessions.remove(session_id) else {
+            return false;
+        };
+        let frame = CloseFrame { code: CloseCode::from(close.code), reason: close.reason.clone().into() };
+        let _ = session.outgoing.send(Message::Close(Some(frame))).await;
+        debug!("WebSocket session {} closed by client", hex::encode(&session_id[..8]));
+        true
+    }
+
+    /// Close sessions the client has not polled for the idle timeout.
+    ///
+    /// Returns pool_pubkeys of evicted sessions so the caller can decrement
+    /// per-user concurrent_tunnels counters.
+    pub fn clear_stale(&mut self) -> Vec<PublicKey> {
+        let now = Instant::now();
+        let stale: Vec<Id> = self.sessions.iter()
+            .filter(|(_, s)| now.duration_since(s.last_activity) >= self.idle_timeout)
+            .map(|(id, _)| *id)
+            .collect();
+        // Dropping a session's sender makes its bridge close the origin
+        stale.iter()
+            .filter_map(|id| self.sessions.remove(id))
+            .map(|s| s.pool_pubkey)
+            .collect()
+    }
+
+    /// Check if a session exists
+    pub fn has_session(&self, session_id: &Id) -> bool {
+        self.sessions.contains_key(session_id)
+    }
+
+    /// Number of open sessions
+    pub fn session_count(&self) -> usize {
+        self.sessions.len()
+    }
+}
+
+/// Pump one origin connection until either side closes it.
+///
+/// Data messages go to `inbound`; origin pings are answered by tungstenite.
+/// Every `ping_interval` the origin is pinged, and a ping still unanswered
+/// at the next one ends the session.
+async fn bridge(
+    stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    mut outgoing: mpsc::Receiver<Message>,
+    inbound: mpsc::Sender<Inbound>,
+    ping_interval: Duration,
+) {
+    let (mut sink, mut source) = stream.split();
+    let mut keepalive = tokio::time::interval_at(tokio::time::Instant::now() + ping_interval, ping_interval);
+    let mut awaiting_pong = false;
+
+    let close = loop {
+        tokio::select! {
+            message = outgoing.recv() => match message {
+                Some(message) => {
+                    let closing = matches!(message, Message::Close(_));
+                    if let Err(e) = sink.send(message).await {
+                        break WsClose { code: WS_CLOSE_ABNORMAL, reason: e.to_string() };
+                    }
+                    if closing {
+                        return;
+                    }
+                }
+                // Session dropped (idle or exit shutting down)
+                None => {
+                    let _ = sink.send(Message::Close(None)).await;
+                    return;
+                }
+            },
+            frame = source.next() => match frame {
+                Some(Ok(Message::Text(text))) => {
+                    if inbound.send(Inbound::Message(WsMessage::Text(text))).await.is_err() {
+                        return;
+                    }
+                }
+                Some(Ok(Message::Binary(data))) => {
+                    if inbound.send(Inbound::Message(WsMessage::Binary(data))).await.is_err() {
+                        return;
+                    }
+                }
+                Some(Ok(Message::Pong(_))) => awaiting_pong = false,
+                Some(Ok(Message::Ping(_) | Message::Frame(_))) => {}
+                Some(Ok(Message::Close(frame))) => {
+                    break frame
+                        .map(|f| WsClose { code: f.code.into(), reason: f.reason.into_owned() })
+                        .unwrap_or(WsClose { code: WS_CLOSE_ABNORMAL, reason: String::new() });
+                }
+                Some(Err(e)) => break WsClose { code: WS_CLOSE_ABNORMAL, reason: e.to_string() },
+                None => break WsClose { code: WS_CLOSE_ABNORMAL, reason: "origin connection lost".to_string() },
+            },
+            _ = keepalive.tick() => {
+                if awaiting_pong {
+                    break WsClose { code: WS_CLOSE_ABNORMAL, reason: "origin missed keepalive".to_string() };
+                }
+                awaiting_pong = true;
+                if let Err(e) = sink.send(Message::Ping(Vec::new())).await {
+                    break WsClose { code: WS_CLOSE_ABNORMAL, reason: e.to_string() };
+                }
+            }
+        }
+    };
+    let _ = inbound.send(Inbound::Closed(close)).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// Origin echoing every data message, closing after `close_after` of them
+    async fn echo_origin(close_after: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+            let mut echoed = 0;
+            while let Some(Ok(message)) = ws.next().await {
+                if message.is_text() || message.is_binary() {
+                    ws.send(message).await.unwrap();
+                    echoed += 1;
+                    if echoed == close_after {
+                        ws.close(Some(CloseFrame { code: CloseCode::Normal, reason: "done".into() })).await.unwrap();
+                    }
+                }
+            }
+        });
+        format!("ws://{}/", addr)
+    }
+
+    fn handler() -> WebSocketHandler {
+        WebSocketHandler::new(Duration::from_secs(5), Duration::from_secs(30), Duration::from_secs(60))
+    }
+
+    #[tokio::test]
+    async fn test_websocket_exchange_and_origin_close() {
+        let url = echo_origin(2).await;
+        let mut handler = handler();
+        let session = [1u8; 32];
+        handler.open(session, &url, &[], &[], [7u8; 32]).await.unwrap();
+        assert!(handler.open(session, &url, &[], &[], [7u8; 32]).await.is_err());
+
+        // Another pool can't use the session
+        assert!(handler.exchange(&session, &[8u8; 32], Vec::new()).await.is_err());
+
+        let (received, close) = handler.exchange(&session, &[7u8; 32], vec![WsMessage::Text("one".into())]).await.unwrap();
+        assert_eq!(received, vec![WsMessage::Text("one".into())]);
+        assert!(close.is_none());
+
+        let (received, close) = handler.exchange(&session, &[7u8; 32], vec![WsMessage::Binary(vec![2])]).await.unwrap();
+        assert_eq!(received, vec![WsMessage::Binary(vec![2])]);
+        assert_eq!(close, Some(WsClose { code: 1000, reason: "done".into() }));
+        assert!(!handler.has_session(&session));
+        assert!(handler.exchange(&session, &[7u8; 32], Vec::new()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_idle_sessions_cleared() {
+        let url = echo_origin(usize::MAX).await;
+        let mut handler = WebSocketHandler::new(Duration::from_secs(5), Duration::from_secs(30), Duration::ZERO);
+        handler.open([2u8; 32], &url, &[], &[], [9u8; 32]).await.unwrap();
+        assert_eq!(handler.clear_stale(), vec![[9u8; 32]]);
+        assert_eq!(handler.session_count(), 0);
+        assert!(!handler.close(&[2u8; 32], &[9u8; 32], &WsClose { code: 1000, reason: String::new() }).await);
+    }
+}
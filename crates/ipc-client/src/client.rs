@@ -3,22 +3,29 @@
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering};
 
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
 #[cfg(unix)]
 use tokio::net::UnixStream;
 use tracing::debug;
 
 use crate::protocol::{
-    AvailableExitsResult, ConnectParams, ConnectResult, ConnectionHistoryResult, CreditsResult,
-    EarningsHistoryResult, KeyExportResult, KeyImportResult, NodeStatsResult, RequestResult,
-    RpcRequest, RpcResponse, SpeedTestResponse, StatusResult,
+    AuditLogResult, AvailableExitsResult, BuildAttestationResult, ConnectParams, ConnectResult, ConnectionHistoryResult,
+    CreditLedgerResult, CreditsResult, DhtRecordResult, DhtSnapshotResult, DistributionBatchProofResult, DrainResult, EarningsHistoryResult, ExitAbuseResult, ExitBlockResult, KeyExportResult, KeyImportResult,
+    KillSwitchResult, NodeStatsResult, PeerListResult, QuotaTokensResult, RecentLogsResult, RequestOptions, RequestResult, RequestStreamResult, RpcRequest,
+    RpcResponse, SpeedTestResponse, StatusResult, ThroughputSeriesResult, TopologyResult,
 };
 use crate::{IpcError, Result};
 
+type StreamReader = BufReader<Box<dyn AsyncRead + Unpin + Send>>;
+type StreamWriter = Box<dyn AsyncWrite + Unpin + Send>;
+
 /// IPC Client for communicating with the CraftNet daemon
 pub struct IpcClient {
     socket_path: PathBuf,
     request_id: AtomicU64,
+    /// Connection shared by all requests (see [`IpcClient::persistent`]);
+    /// None inside the mutex until (re)opened
+    connection: Option<tokio::sync::Mutex<Option<(StreamReader, StreamWriter)>>>,
 }
 
 impl IpcClient {
@@ -29,9 +36,20 @@ impl IpcClient {
         Self {
             socket_path,
             request_id: AtomicU64::new(1),
+            connection: None,
         }
     }
 
+    /// Create a client that sends every request over one connection,
+    /// opened now. If the connection breaks, the failing request returns
+    /// the error and the next one reconnects.
+    pub async fn persistent(socket_path: &Path) -> Result<Self> {
+        let mut client = Self::new(socket_path.to_path_buf());
+        let stream = client.open_stream().await?;
+        client.connection = Some(tokio::sync::Mutex::new(Some(stream)));
+        Ok(client)
+    }
+
     /// Connect to the daemon and verify it's running
     pub async fn connect(socket_path: &Path) -> Result<Self> {
         let client = Self::new(socket_path.to_path_buf());
@@ -53,16 +71,28 @@ impl IpcClient {
         method: &str,
         params: Option<serde_json::Value>,
     ) -> Result<serde_json::Value> {
-        self.send_request_impl(method, params).await
+        let Some(connection) = &self.connection else {
+            let (mut reader, mut writer) = self.open_stream().await?;
+            return self.send_and_receive(&mut reader, &mut writer, method, params).await;
+        };
+
+        let mut connection = connection.lock().await;
+        let stream = match connection.take() {
+            Some(stream) => stream,
+            None => self.open_stream().await?,
+        };
+        let (reader, writer) = connection.insert(stream);
+        let result = self.send_and_receive(reader, writer, method, params).await;
+        if matches!(result, Err(IpcError::Io(_) | IpcError::ConnectionFailed(_))) {
+            *connection = None;
+        }
+        result
     }
 
     /// Unix implementation using UnixStream
     #[cfg(unix)]
-    async fn send_request_impl(
-        &self,
-        method: &str,
-        params: Option<serde_json::Value>,
-    ) -> Result<serde_json::Value> {
+    async fn open_stream(&self) -> Result<(StreamReader, StreamWriter)> {
+        crate::check_socket_owner(&self.socket_path)?;
         let stream = UnixStream::connect(&self.socket_path)
             .await
             .map_err(|e| {
@@ -75,17 +105,13 @@ impl IpcClient {
                 }
             })?;
 
-        let (reader, mut writer) = stream.into_split();
-        self.send_and_receive(reader, &mut writer, method, params).await
+        let (reader, writer) = stream.into_split();
+        Ok((BufReader::new(Box::new(reader)), Box::new(writer)))
     }
 
     /// Windows implementation using Named Pipes
     #[cfg(windows)]
-    async fn send_request_impl(
-        &self,
-        method: &str,
-        params: Option<serde_json::Value>,
-    ) -> Result<serde_json::Value> {
+    async fn open_stream(&self) -> Result<(StreamReader, StreamWriter)> {
         use tokio::net::windows::named_pipe::ClientOptions;
 
         let pipe_name = self.socket_path.to_string_lossy();
@@ -99,24 +125,25 @@ impl IpcClient {
                 }
             })?;
 
-        let (reader, mut writer) = tokio::io::split(client);
-        self.send_and_receive(reader, &mut writer, method, params).await
+        let (reader, writer) = tokio::io::split(client);
+        Ok((BufReader::new(Box::new(reader)), Box::new(writer)))
     }
 
     /// Common send/receive logic shared between Unix and Windows
     async fn send_and_receive<R, W>(
         &self,
-        reader: R,
+        reader: &mut R,
         writer: &mut W,
         method: &str,
         params: Option<serde_json::Value>,
     ) -> Result<serde_json::Value>
     where
-        R: tokio::io::AsyncRead + Unpin,
-        W: tokio::io::AsyncWrite + Unpin,
+        R: AsyncBufRead + Unpin,
+        W: AsyncWrite + Unpin,
     {
         // Build and send request
-        let request = RpcRequest::new(method, params, self.next_id());
+        let id = self.next_id();
+        let request = RpcRequest::new(method, params, id);
         let request_json = serde_json::to_string(&request)?;
         debug!("Sending request: {}", request_json);
 
@@ -124,18 +151,32 @@ impl IpcClient {
         writer.write_all(b"\n").await?;
         writer.flush().await?;
 
-        // Read response
-        let mut reader = BufReader::new(reader);
-        let mut response_str = String::new();
-        reader.read_line(&mut response_str).await?;
-        debug!("Received response: {}", response_str.trim());
-
-        let response: RpcResponse = serde_json::from_str(&response_str)
-            .map_err(|e| IpcError::InvalidResponse(e.to_string()))?;
+        // Read frames until our response; broadcast events and replies to
+        // abandoned requests are interleaved on the same connection
+        let response = loop {
+            let mut response_str = String::new();
+            if reader.read_line(&mut response_str).await? == 0 {
+                return Err(IpcError::ConnectionFailed("Daemon closed the connection".to_string()));
+            }
+            debug!("Received response: {}", response_str.trim());
+
+            let frame: serde_json::Value = serde_json::from_str(&response_str)
+                .map_err(|e| IpcError::InvalidResponse(e.to_string()))?;
+            if frame.get("event").is_some() && frame.get("id").is_none() {
+                continue;
+            }
+            let response: RpcResponse = serde_json::from_value(frame)
+                .map_err(|e| IpcError::InvalidResponse(e.to_string()))?;
+            // Parse errors come back with a null id
+            if response.id == serde_json::json!(id) || response.id.is_null() {
+                break response;
+            }
+        };
 
         // Check for error
         if let Some(error) = response.error {
             return Err(IpcError::DaemonError {
+                error_code: error.error_code().map(str::to_string),
                 code: error.code,
                 message: error.message,
             });
@@ -150,13 +191,35 @@ impl IpcClient {
     ///
     /// * `hops` - Number of relay hops (0 = direct, 1 = light, 2 = standard, 3+ = paranoid)
     pub async fn connect_vpn(&self, hops: u8) -> Result<ConnectResult> {
-        let params = ConnectParams { hops };
+        let params = ConnectParams { hops, full_device: false };
+        let result = self
+            .send_request("connect", Some(serde_json::to_value(params)?))
+            .await?;
+        serde_json::from_value(result).map_err(|e| IpcError::InvalidResponse(e.to_string()))
+    }
+
+    /// Connect and route all device traffic through the tunnel (requires a
+    /// daemon built with the `tun` feature, running with administrator
+    /// rights)
+    pub async fn connect_full_device(&self, hops: u8) -> Result<ConnectResult> {
+        let params = ConnectParams { hops, full_device: true };
         let result = self
             .send_request("connect", Some(serde_json::to_value(params)?))
             .await?;
         serde_json::from_value(result).map_err(|e| IpcError::InvalidResponse(e.to_string()))
     }
 
+    /// Take down the full-device TUN interface (the tunnel stays connected)
+    pub async fn stop_tun(&self) -> Result<()> {
+        self.send_request("stop_tun", None).await?;
+        Ok(())
+    }
+
+    /// Get TUN interface status (`{"running": false}` when down)
+    pub async fn tun_status(&self) -> Result<serde_json::Value> {
+        self.send_request("tun_status", None).await
+    }
+
     /// Disconnect from the VPN network
     pub async fn disconnect(&self) -> Result<()> {
         self.send_request("disconnect", None).await?;
@@ -175,6 +238,12 @@ impl IpcClient {
         serde_json::from_value(result).map_err(|e| IpcError::InvalidResponse(e.to_string()))
     }
 
+    /// Fetch the day's free-tier quota tokens from the selected exit if due
+    pub async fn refresh_quota_tokens(&self) -> Result<QuotaTokensResult> {
+        let result = self.send_request("refresh_quota_tokens", None).await?;
+        serde_json::from_value(result).map_err(|e| IpcError::InvalidResponse(e.to_string()))
+    }
+
     /// Purchase credits
     ///
     /// # Arguments
@@ -205,6 +274,92 @@ impl IpcClient {
         serde_json::from_value(result).map_err(|e| IpcError::InvalidResponse(e.to_string()))
     }
 
+    /// Credit ledger totals, per-pool credits and the newest `limit` entries
+    pub async fn get_credit_ledger(&self, limit: Option<usize>) -> Result<CreditLedgerResult> {
+        let params = serde_json::json!({ "limit": limit });
+        let result = self.send_request("get_credit_ledger", Some(params)).await?;
+        serde_json::from_value(result).map_err(|e| IpcError::InvalidResponse(e.to_string()))
+    }
+
+    /// Exit request volume of the `limit` busiest destinations (daemon
+    /// default 50), throttled pools and operator blocks
+    pub async fn get_exit_abuse(&self, limit: Option<usize>) -> Result<ExitAbuseResult> {
+        let params = serde_json::json!({ "limit": limit });
+        let result = self.send_request("get_exit_abuse", Some(params)).await?;
+        serde_json::from_value(result).map_err(|e| IpcError::InvalidResponse(e.to_string()))
+    }
+
+    /// Refuse exit requests to a destination (hostname or hash) or from a
+    /// pool (hex pubkey); give exactly one
+    pub async fn exit_block(&self, destination: Option<&str>, pool: Option<&str>) -> Result<ExitBlockResult> {
+        let params = serde_json::json!({ "destination": destination, "pool": pool });
+        let result = self.send_request("exit_block", Some(params)).await?;
+        serde_json::from_value(result).map_err(|e| IpcError::InvalidResponse(e.to_string()))
+    }
+
+    /// Lift a block set with [`exit_block`](Self::exit_block)
+    pub async fn exit_unblock(&self, destination: Option<&str>, pool: Option<&str>) -> Result<ExitBlockResult> {
+        let params = serde_json::json!({ "destination": destination, "pool": pool });
+        let result = self.send_request("exit_unblock", Some(params)).await?;
+        serde_json::from_value(result).map_err(|e| IpcError::InvalidResponse(e.to_string()))
+    }
+
+    /// Per-second throughput of the last `window_secs` seconds (daemon
+    /// default 60)
+    pub async fn get_throughput_series(&self, window_secs: Option<u64>) -> Result<ThroughputSeriesResult> {
+        let params = serde_json::json!({ "window_secs": window_secs });
+        let result = self.send_request("get_throughput_series", Some(params)).await?;
+        serde_json::from_value(result).map_err(|e| IpcError::InvalidResponse(e.to_string()))
+    }
+
+    /// Connected peers with address, direction, transport, protocols, age
+    /// and shard bytes exchanged
+    pub async fn list_peers(&self) -> Result<PeerListResult> {
+        let result = self.send_request("list_peers", None).await?;
+        serde_json::from_value(result).map_err(|e| IpcError::InvalidResponse(e.to_string()))
+    }
+
+    /// Up to `limit` of the daemon's newest log lines after line `since`
+    pub async fn get_recent_logs(&self, since: Option<u64>, limit: Option<usize>) -> Result<RecentLogsResult> {
+        let params = serde_json::json!({ "since": since, "limit": limit });
+        let result = self.send_request("get_recent_logs", Some(params)).await?;
+        serde_json::from_value(result).map_err(|e| IpcError::InvalidResponse(e.to_string()))
+    }
+
+    /// The daemon's build manifest, checked against the canonical manifests
+    /// in the network parameters
+    pub async fn get_build_attestation(&self) -> Result<BuildAttestationResult> {
+        let result = self.send_request("get_build_attestation", None).await?;
+        serde_json::from_value(result).map_err(|e| IpcError::InvalidResponse(e.to_string()))
+    }
+
+    /// Kademlia routing tables (bucket occupancy) and registry providers
+    pub async fn get_dht(&self) -> Result<DhtSnapshotResult> {
+        let result = self.send_request("get_dht", None).await?;
+        serde_json::from_value(result).map_err(|e| IpcError::InvalidResponse(e.to_string()))
+    }
+
+    /// Add a peer address (multiaddr ending in `/p2p/<peer_id>`) to the DHT
+    pub async fn dht_add_address(&self, addr: &str) -> Result<()> {
+        let params = serde_json::json!({ "addr": addr });
+        self.send_request("dht_add_address", Some(params)).await?;
+        Ok(())
+    }
+
+    /// Start a DHT bootstrap (`table`: `main` or `registry`, default `registry`)
+    pub async fn dht_bootstrap(&self, table: Option<&str>) -> Result<()> {
+        let params = serde_json::json!({ "table": table });
+        self.send_request("dht_bootstrap", Some(params)).await?;
+        Ok(())
+    }
+
+    /// Look up one DHT record
+    pub async fn dht_get_record(&self, key: &str, table: Option<&str>) -> Result<DhtRecordResult> {
+        let params = serde_json::json!({ "key": key, "table": table });
+        let result = self.send_request("dht_get_record", Some(params)).await?;
+        serde_json::from_value(result).map_err(|e| IpcError::InvalidResponse(e.to_string()))
+    }
+
     /// Make an HTTP request through the tunnel
     pub async fn request(
         &self,
@@ -212,17 +367,58 @@ impl IpcClient {
         url: &str,
         body: Option<&str>,
         headers: Option<std::collections::HashMap<String, String>>,
+    ) -> Result<RequestResult> {
+        self.request_with(method, url, body, headers, &RequestOptions::default()).await
+    }
+
+    /// Make an HTTP request through the tunnel with a hop mode, exit or
+    /// timeout of its own
+    pub async fn request_with(
+        &self,
+        method: &str,
+        url: &str,
+        body: Option<&str>,
+        headers: Option<std::collections::HashMap<String, String>>,
+        options: &RequestOptions,
     ) -> Result<RequestResult> {
         let params = serde_json::json!({
             "method": method,
             "url": url,
             "body": body,
             "headers": headers,
+            "hop_mode": options.hop_mode,
+            "exit": options.exit,
+            "timeout_ms": options.timeout_ms,
+            "trace": options.trace,
         });
         let result = self.send_request("request", Some(params)).await?;
         serde_json::from_value(result).map_err(|e| IpcError::InvalidResponse(e.to_string()))
     }
 
+    /// Start a streamed HTTP request.
+    ///
+    /// Returns the response head. The body is broadcast as events tagged
+    /// with `stream_id`, so pass your own id when you are already listening
+    /// for events and want to match chunks that race this reply.
+    pub async fn request_stream(
+        &self,
+        method: &str,
+        url: &str,
+        body: Option<&str>,
+        headers: Option<std::collections::HashMap<String, String>>,
+        stream_id: Option<&str>,
+    ) -> Result<RequestStreamResult> {
+        let params = serde_json::json!({
+            "method": method,
+            "url": url,
+            "body": body,
+            "headers": headers,
+            "stream_id": stream_id,
+        });
+        let result = self.send_request("request_stream", Some(params)).await?;
+        serde_json::from_value(result).map_err(|e| IpcError::InvalidResponse(e.to_string()))
+    }
+
     /// Set preferred exit node geography
     pub async fn set_exit_node(
         &self,
@@ -246,6 +442,31 @@ impl IpcClient {
         Ok(())
     }
 
+    /// Choose the pools the node's aggregator follows: `mode` is "all",
+    /// "own" or "only" (with `pools` as hex pubkeys). Returns the number
+    /// of chains re-synced because the selection widened.
+    pub async fn set_aggregator_pools(&self, mode: &str, pools: &[String]) -> Result<u64> {
+        let params = serde_json::json!({ "mode": mode, "pools": pools });
+        let result = self.send_request("set_aggregator_pools", Some(params)).await?;
+        Ok(result.get("resynced").and_then(|v| v.as_u64()).unwrap_or(0))
+    }
+
+    /// Get one Merkle multiproof for `relays`' claims (hex pubkeys) in a
+    /// pool's current distribution; `pool_type` is "subscribed" or "free".
+    /// None when the daemon runs no aggregator or none of the relays has a
+    /// claim.
+    pub async fn get_distribution_batch_proof(
+        &self,
+        pool: &str,
+        pool_type: &str,
+        relays: &[String],
+    ) -> Result<Option<DistributionBatchProofResult>> {
+        let params = serde_json::json!({ "pool": pool, "pool_type": pool_type, "relays": relays });
+        let result = self.send_request("get_distribution_batch_proof", Some(params)).await?;
+        serde_json::from_value(result.get("proof").cloned().unwrap_or_default())
+            .map_err(|e| IpcError::InvalidResponse(e.to_string()))
+    }
+
     /// Get available exit nodes
     pub async fn get_available_exits(&self) -> Result<AvailableExitsResult> {
         let result = self.send_request("get_available_exits", None).await?;
@@ -254,10 +475,22 @@ impl IpcClient {
 
     /// Get connection history
     pub async fn get_connection_history(&self) -> Result<ConnectionHistoryResult> {
-        let result = self.send_request("get_connection_history", None).await?;
+        self.get_connection_history_limited(None).await
+    }
+
+    /// Get the newest `limit` connection history entries (None = all kept)
+    pub async fn get_connection_history_limited(&self, limit: Option<usize>) -> Result<ConnectionHistoryResult> {
+        let params = serde_json::json!({ "limit": limit });
+        let result = self.send_request("get_connection_history", Some(params)).await?;
         serde_json::from_value(result).map_err(|e| IpcError::InvalidResponse(e.to_string()))
     }
 
+    /// Delete the connection history; returns the number of entries removed
+    pub async fn clear_connection_history(&self) -> Result<u64> {
+        let result = self.send_request("clear_connection_history", None).await?;
+        Ok(result.get("cleared").and_then(|v| v.as_u64()).unwrap_or(0))
+    }
+
     /// Get earnings history
     pub async fn get_earnings_history(&self) -> Result<EarningsHistoryResult> {
         let result = self.send_request("get_earnings_history", None).await?;
@@ -270,6 +503,85 @@ impl IpcClient {
         serde_json::from_value(result).map_err(|e| IpcError::InvalidResponse(e.to_string()))
     }
 
+    /// Get the live network topology graph
+    pub async fn get_topology(&self) -> Result<TopologyResult> {
+        let result = self.send_request("get_topology", None).await?;
+        serde_json::from_value(result).map_err(|e| IpcError::InvalidResponse(e.to_string()))
+    }
+
+    /// Drain in-flight work and stop the node (relays/exits).
+    ///
+    /// `timeout_secs` overrides the daemon's drain deadline.
+    pub async fn drain(&self, timeout_secs: Option<u64>) -> Result<DrainResult> {
+        let params = serde_json::json!({ "timeout_secs": timeout_secs });
+        let result = self.send_request("drain", Some(params)).await?;
+        serde_json::from_value(result).map_err(|e| IpcError::InvalidResponse(e.to_string()))
+    }
+
+    /// Kill switch status
+    pub async fn get_kill_switch(&self) -> Result<KillSwitchResult> {
+        let result = self.send_request("get_kill_switch", None).await?;
+        serde_json::from_value(result).map_err(|e| IpcError::InvalidResponse(e.to_string()))
+    }
+
+    /// Enable or disable automatic kill switch engagement
+    pub async fn set_kill_switch(&self, enabled: bool) -> Result<KillSwitchResult> {
+        let params = serde_json::json!({ "enabled": enabled });
+        let result = self.send_request("set_kill_switch", Some(params)).await?;
+        serde_json::from_value(result).map_err(|e| IpcError::InvalidResponse(e.to_string()))
+    }
+
+    /// Block traffic outside the tunnel now
+    pub async fn engage_kill_switch(&self, reason: Option<&str>) -> Result<KillSwitchResult> {
+        let params = serde_json::json!({ "reason": reason });
+        let result = self.send_request("engage_kill_switch", Some(params)).await?;
+        serde_json::from_value(result).map_err(|e| IpcError::InvalidResponse(e.to_string()))
+    }
+
+    /// Release an engaged kill switch until the tunnel is back
+    pub async fn release_kill_switch(&self) -> Result<KillSwitchResult> {
+        let result = self.send_request("release_kill_switch", None).await?;
+        serde_json::from_value(result).map_err(|e| IpcError::InvalidResponse(e.to_string()))
+    }
+
+    /// Turn the daemon's request audit log on or off
+    pub async fn set_audit_log(&self, enabled: bool) -> Result<()> {
+        let params = serde_json::json!({ "enabled": enabled });
+        self.send_request("set_audit_log", Some(params)).await?;
+        Ok(())
+    }
+
+    /// Turn the daemon's cookie jar on or off
+    pub async fn set_cookie_jar(&self, enabled: bool) -> Result<()> {
+        let params = serde_json::json!({ "enabled": enabled });
+        self.send_request("set_cookie_jar", Some(params)).await?;
+        Ok(())
+    }
+
+    /// Forget stored cookies of one identity (None = of every identity)
+    pub async fn clear_session_data(&self, identity: Option<&str>) -> Result<()> {
+        let params = serde_json::json!({ "identity": identity });
+        self.send_request("clear_session_data", Some(params)).await?;
+        Ok(())
+    }
+
+    /// Audit log entries since a unix time (newest `limit` ones)
+    pub async fn get_audit_log(&self, since: Option<u64>, limit: Option<usize>) -> Result<AuditLogResult> {
+        let params = serde_json::json!({ "since": since, "limit": limit });
+        let result = self.send_request("get_audit_log", Some(params)).await?;
+        serde_json::from_value(result).map_err(|e| IpcError::InvalidResponse(e.to_string()))
+    }
+
+    /// Export the audit log as `"jsonl"` or `"csv"` text
+    pub async fn export_audit_log(&self, format: &str, since: Option<u64>) -> Result<String> {
+        let params = serde_json::json!({ "format": format, "since": since });
+        let result = self.send_request("export_audit_log", Some(params)).await?;
+        result.get("data")
+            .and_then(|d| d.as_str())
+            .map(str::to_string)
+            .ok_or_else(|| IpcError::InvalidResponse("missing export data".to_string()))
+    }
+
     /// Set bandwidth limit (in kbps, None to remove limit)
     pub async fn set_bandwidth_limit(&self, limit_kbps: Option<u64>) -> Result<()> {
         let params = serde_json::json!({ "limit_kbps": limit_kbps });
@@ -309,4 +621,46 @@ mod tests {
         assert_eq!(client.next_id(), 2);
         assert_eq!(client.next_id(), 3);
     }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_persistent_connection_reused() {
+        let socket_path = std::env::temp_dir().join(format!("craftnet-ipc-test-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&socket_path);
+        let listener = tokio::net::UnixListener::bind(&socket_path).unwrap();
+
+        // Accepts a single connection: a second connect would hang the test
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            while let Some(line) = lines.next_line().await.unwrap() {
+                let request: serde_json::Value = serde_json::from_str(&line).unwrap();
+                let event = serde_json::json!({"event": "state_change", "data": {"state": "ready"}});
+                let response = serde_json::json!({"jsonrpc": "2.0", "result": {"credits": 7}, "id": request["id"]});
+                writer.write_all(format!("{}\n{}\n", event, response).as_bytes()).await.unwrap();
+            }
+        });
+
+        let client = IpcClient::persistent(&socket_path).await.unwrap();
+        for _ in 0..3 {
+            assert_eq!(client.get_credits().await.unwrap().credits, 7);
+        }
+        drop(client);
+        server.await.unwrap();
+        let _ = std::fs::remove_file(&socket_path);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_socket_owner_checked() {
+        let path = std::env::temp_dir().join(format!("craftnet-ipc-owner-{}.sock", std::process::id()));
+        assert!(matches!(crate::check_socket_owner(&path), Err(IpcError::DaemonNotRunning)));
+
+        // Ours, or root's, is trusted
+        std::fs::write(&path, b"").unwrap();
+        assert!(crate::check_socket_owner(&path).is_ok());
+        assert!(crate::check_socket_owner(Path::new("/")).is_ok());
+        let _ = std::fs::remove_file(&path);
+    }
 }
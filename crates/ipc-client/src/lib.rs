@@ -10,12 +10,11 @@
 //! ## Usage
 //!
 //! ```ignore
-//! use craftnet_ipc_client::{IpcClient, DEFAULT_SOCKET_PATH};
-//! use std::path::PathBuf;
+//! use craftnet_ipc_client::{default_socket_path, IpcClient};
 //!
 //! #[tokio::main]
 //! async fn main() -> Result<(), Box<dyn std::error::Error>> {
-//!     let client = IpcClient::connect(&PathBuf::from(DEFAULT_SOCKET_PATH)).await?;
+//!     let client = IpcClient::connect(&default_socket_path()).await?;
 //!
 //!     // Connect to VPN with 2 hops
 //!     let result = client.connect_vpn(2).await?;
@@ -34,16 +33,67 @@ mod protocol;
 
 pub use client::IpcClient;
 pub use protocol::{
-    AvailableExitsResult, ConnectParams, ConnectResult, CreditsResult, ExitNodeInfo,
-    NodeStatsResult, RequestResult, RpcError, RpcRequest, RpcResponse, StatusResult,
+    AttestationResult, AuditEntryResult, AuditLogResult, AvailableExitsResult, BuildAttestationResult, BuildManifestResult, ConnectParams, ConnectResult,
+    CreditEntryResult, CreditLedgerResult, CreditTotalsResult, CreditsResult, DhtBucketPeerResult, DhtBucketResult,
+    DestinationVolumeResult, DistributionBatchProofResult, DistributionEntryResult, DhtProvidersResult, DhtRecordResult, DhtSnapshotResult, DhtTableResult, DrainResult, ExitAbuseResult,
+    ExitBlockResult, ExitNodeInfo, HopTimingResult,
+    FetchPoolResult, KillSwitchResult, LogLineResult, NodeStatsResult, PeerConnectionResult, PeerListResult, PoolClaimResult, PoolCreditsResult, PoolQueueResult, QosClassResult,
+    QuotaTokensResult, RecentLogsResult, RequestOptions, RequestResult, RequestStreamResult, RpcError, RpcRequest, RpcResponse, StatusResult,
+    ThrottledPoolResult, ThroughputSampleResult, ThroughputSeriesResult, TopologyEdge, TopologyNode, TopologyResult,
 };
 
 use thiserror::Error;
 
-/// Default socket path for Unix systems
+/// Shared socket path for Unix systems (the daemon's default is per user,
+/// see [`default_socket_path`])
 #[cfg(unix)]
 pub const DEFAULT_SOCKET_PATH: &str = "/tmp/craftnet.sock";
 
+/// Effective uid of this process
+#[cfg(unix)]
+pub fn current_uid() -> u32 {
+    // SAFETY: geteuid has no preconditions and cannot fail
+    unsafe { libc::geteuid() }
+}
+
+/// The daemon's default socket for the current user:
+/// `$XDG_RUNTIME_DIR/craftnet.sock` on Linux when set, otherwise
+/// `/tmp/craftnet-<uid>.sock`
+#[cfg(unix)]
+pub fn default_socket_path() -> std::path::PathBuf {
+    if cfg!(target_os = "linux") {
+        if let Ok(xdg_runtime) = std::env::var("XDG_RUNTIME_DIR") {
+            return std::path::PathBuf::from(format!("{}/craftnet.sock", xdg_runtime));
+        }
+    }
+    std::path::PathBuf::from(format!("/tmp/craftnet-{}.sock", current_uid()))
+}
+
+/// Refuse a socket owned by anyone but us or root.
+///
+/// `/tmp` paths are predictable, so another local user could bind the
+/// socket before our daemon starts and collect our requests.
+#[cfg(unix)]
+pub fn check_socket_owner(path: &std::path::Path) -> Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    let owner = match std::fs::metadata(path) {
+        Ok(metadata) => metadata.uid(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(IpcError::DaemonNotRunning),
+        Err(e) => return Err(IpcError::ConnectionFailed(e.to_string())),
+    };
+    if owner != current_uid() && owner != 0 {
+        return Err(IpcError::UntrustedSocket { path: path.display().to_string(), owner });
+    }
+    Ok(())
+}
+
+/// The daemon's default named pipe
+#[cfg(windows)]
+pub fn default_socket_path() -> std::path::PathBuf {
+    std::path::PathBuf::from(DEFAULT_PIPE_PATH)
+}
+
 /// Default named pipe path for Windows
 #[cfg(windows)]
 pub const DEFAULT_PIPE_PATH: &str = r"\\.\pipe\craftnet";
@@ -61,7 +111,13 @@ pub enum IpcError {
     RequestFailed(String),
 
     #[error("Daemon error: {message} (code: {code})")]
-    DaemonError { code: i32, message: String },
+    DaemonError {
+        code: i32,
+        message: String,
+        /// Machine-readable error code from `error.data.code`
+        /// (e.g. `"INSUFFICIENT_CREDITS"`), if the daemon sent one
+        error_code: Option<String>,
+    },
 
     #[error("Invalid response: {0}")]
     InvalidResponse(String),
@@ -74,6 +130,19 @@ pub enum IpcError {
 
     #[error("Daemon not running")]
     DaemonNotRunning,
+
+    #[error("Socket {path} is owned by uid {owner}, not by us or root")]
+    UntrustedSocket { path: String, owner: u32 },
+}
+
+impl IpcError {
+    /// Machine-readable error code reported by the daemon, if any
+    pub fn error_code(&self) -> Option<&str> {
+        match self {
+            Self::DaemonError { error_code, .. } => error_code.as_deref(),
+            _ => None,
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, IpcError>;
@@ -40,11 +40,21 @@ pub struct RpcError {
     pub data: Option<serde_json::Value>,
 }
 
+impl RpcError {
+    /// Machine-readable error code (`data.code`, e.g. `"EXIT_UNREACHABLE"`)
+    pub fn error_code(&self) -> Option<&str> {
+        self.data.as_ref()?.get("code")?.as_str()
+    }
+}
+
 /// Parameters for the `connect` method
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConnectParams {
     #[serde(default = "default_hops")]
     pub hops: u8,
+    /// Also route all device traffic through the tunnel (TUN interface)
+    #[serde(default)]
+    pub full_device: bool,
 }
 
 fn default_hops() -> u8 {
@@ -53,7 +63,7 @@ fn default_hops() -> u8 {
 
 impl Default for ConnectParams {
     fn default() -> Self {
-        Self { hops: default_hops() }
+        Self { hops: default_hops(), full_device: false }
     }
 }
 
@@ -63,6 +73,8 @@ pub struct ConnectResult {
     pub connected: bool,
     pub exit_node: Option<String>,
     pub hops: Option<u8>,
+    #[serde(default)]
+    pub full_device: bool,
 }
 
 /// Result of the `status` method
@@ -96,6 +108,13 @@ pub struct CreditsResult {
     pub credits: u64,
 }
 
+/// Result of the `refresh_quota_tokens` method
+#[derive(Debug, Clone, Deserialize)]
+pub struct QuotaTokensResult {
+    /// Free-tier quota tokens held for the selected exit
+    pub tokens: usize,
+}
+
 /// Result of the `get_node_stats` method
 #[derive(Debug, Clone, Deserialize)]
 pub struct NodeStatsResult {
@@ -115,6 +134,428 @@ pub struct NodeStatsResult {
     pub bytes_received: u64,
     #[serde(default)]
     pub bytes_relayed: u64,
+    #[serde(default)]
+    pub cover_shards_sent: u64,
+    #[serde(default)]
+    pub cover_shards_dropped: u64,
+    #[serde(default)]
+    pub replays_rejected: u64,
+    /// Relay hops of our request shards that acked with a valid signature
+    #[serde(default)]
+    pub chain_hops_verified: u64,
+    /// Relay hops of our request shards that never acked
+    #[serde(default)]
+    pub chain_hops_missing: u64,
+    /// Forward receipts waiting to be proven
+    #[serde(default)]
+    pub proof_queue_depth: usize,
+    /// Bandwidth cap (None: unlimited)
+    #[serde(default)]
+    pub bandwidth_limit_kbps: Option<u64>,
+    #[serde(default)]
+    pub exit_queues: Vec<PoolQueueResult>,
+    #[serde(default)]
+    pub exit_fetch_pool: FetchPoolResult,
+    #[serde(default)]
+    pub credit_ledger: CreditTotalsResult,
+    /// Relay forwarding per tier, highest first
+    #[serde(default)]
+    pub relay_qos: Vec<QosClassResult>,
+    /// On-chain claim status of pools we earned from
+    #[serde(default)]
+    pub pool_claims: Vec<PoolClaimResult>,
+}
+
+/// Credit ledger totals (in `NodeStatsResult` and `CreditLedgerResult`)
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CreditTotalsResult {
+    /// Relay and exit earnings after reconciliation adjustments
+    #[serde(default)]
+    pub earned: u64,
+    #[serde(default)]
+    pub spent: u64,
+    #[serde(default)]
+    pub relay_earned: u64,
+    #[serde(default)]
+    pub exit_earned: u64,
+    #[serde(default)]
+    pub adjustment: i64,
+    #[serde(default)]
+    pub unreconciled_pools: usize,
+}
+
+/// Result of the `get_credit_ledger` method
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CreditLedgerResult {
+    #[serde(default)]
+    pub totals: CreditTotalsResult,
+    #[serde(default)]
+    pub pools: Vec<PoolCreditsResult>,
+    /// Latest per-request entries, newest first
+    #[serde(default)]
+    pub recent: Vec<CreditEntryResult>,
+}
+
+/// Result of the `get_exit_abuse` method
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ExitAbuseResult {
+    /// Busiest destinations first
+    #[serde(default)]
+    pub destinations: Vec<DestinationVolumeResult>,
+    /// Destinations in the exit's log
+    #[serde(default)]
+    pub tracked_destinations: usize,
+    #[serde(default)]
+    pub throttled: Vec<ThrottledPoolResult>,
+    /// Blocked destination hashes (hex)
+    #[serde(default)]
+    pub blocked_destinations: Vec<String>,
+    /// Blocked pool pubkeys (hex)
+    #[serde(default)]
+    pub blocked_pools: Vec<String>,
+}
+
+/// Request volume of one destination (in `ExitAbuseResult`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct DestinationVolumeResult {
+    /// SHA-256 of the hostname (hex)
+    pub destination: String,
+    #[serde(default)]
+    pub requests: u64,
+    #[serde(default)]
+    pub refused: u64,
+    #[serde(default)]
+    pub age_secs: u64,
+    #[serde(default)]
+    pub idle_secs: u64,
+}
+
+/// A pool throttled for one destination (in `ExitAbuseResult`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct ThrottledPoolResult {
+    /// Pool pubkey (hex)
+    pub pool: String,
+    /// SHA-256 of the hostname (hex)
+    pub destination: String,
+    #[serde(default)]
+    pub remaining_secs: u64,
+}
+
+/// Result of the `exit_block` and `exit_unblock` methods
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExitBlockResult {
+    /// False if the target was already (un)blocked
+    pub changed: bool,
+}
+
+/// Bytes moved during one second (in `ThroughputSeriesResult`)
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct ThroughputSampleResult {
+    /// Unix second the sample covers
+    pub timestamp: u64,
+    #[serde(default)]
+    pub client_up: u64,
+    #[serde(default)]
+    pub client_down: u64,
+    #[serde(default)]
+    pub relay_up: u64,
+    #[serde(default)]
+    pub relay_down: u64,
+    #[serde(default)]
+    pub exit_up: u64,
+    #[serde(default)]
+    pub exit_down: u64,
+}
+
+/// Result of the `get_throughput_series` method
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ThroughputSeriesResult {
+    pub window_secs: u64,
+    /// One sample per second, oldest first
+    #[serde(default)]
+    pub samples: Vec<ThroughputSampleResult>,
+}
+
+/// A connected peer (in `PeerListResult`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct PeerConnectionResult {
+    pub peer_id: String,
+    #[serde(default)]
+    pub address: Option<String>,
+    /// `inbound` / `outbound` (None when the daemon shares its swarm)
+    #[serde(default)]
+    pub direction: Option<String>,
+    /// `tcp`, `quic`, `relay`, `webrtc` or `unknown`
+    pub transport: String,
+    #[serde(default)]
+    pub protocols: Vec<String>,
+    #[serde(default)]
+    pub connections: u32,
+    pub connected_at: u64,
+    #[serde(default)]
+    pub age_secs: u64,
+    #[serde(default)]
+    pub bytes_sent: u64,
+    #[serde(default)]
+    pub bytes_received: u64,
+}
+
+/// Result of the `list_peers` method
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PeerListResult {
+    /// Longest connected first
+    #[serde(default)]
+    pub peers: Vec<PeerConnectionResult>,
+}
+
+/// One daemon log line (in `RecentLogsResult`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct LogLineResult {
+    pub seq: u64,
+    #[serde(default)]
+    pub timestamp_ms: u64,
+    #[serde(default)]
+    pub level: String,
+    #[serde(default)]
+    pub target: String,
+    #[serde(default)]
+    pub message: String,
+}
+
+/// Result of the `get_recent_logs` method
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RecentLogsResult {
+    /// Oldest first
+    #[serde(default)]
+    pub lines: Vec<LogLineResult>,
+}
+
+/// What a binary was built from (in `BuildAttestationResult`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BuildManifestResult {
+    #[serde(default)]
+    pub version: String,
+    #[serde(default)]
+    pub git_commit: String,
+    #[serde(default)]
+    pub cargo_lock_sha256: String,
+    #[serde(default)]
+    pub target: String,
+    #[serde(default)]
+    pub rustc: String,
+}
+
+/// How a build compares with the canonical manifests (in `BuildAttestationResult`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct AttestationResult {
+    /// `verified`, `mismatch` or `unknown` (no canonical manifest for this
+    /// version and target)
+    pub status: String,
+    /// Fields that differ (`mismatch` only)
+    #[serde(default)]
+    pub fields: Vec<String>,
+}
+
+/// Result of the `get_build_attestation` method
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct BuildAttestationResult {
+    #[serde(default)]
+    pub build: BuildManifestResult,
+    /// Sequence of the network parameters checked against
+    #[serde(default)]
+    pub params_sequence: Option<u64>,
+    /// None when the node isn't running or has no network parameters yet
+    #[serde(default)]
+    pub attestation: Option<AttestationResult>,
+}
+
+/// A routing table entry (in `DhtBucketResult`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct DhtBucketPeerResult {
+    pub peer_id: String,
+    #[serde(default)]
+    pub addresses: Vec<String>,
+}
+
+/// One non-empty k-bucket (in `DhtTableResult`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct DhtBucketResult {
+    /// log2 of the bucket's lower distance bound
+    pub index: u32,
+    #[serde(default)]
+    pub peers: Vec<DhtBucketPeerResult>,
+}
+
+/// Routing table of one Kademlia instance (in `DhtSnapshotResult`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct DhtTableResult {
+    /// `main` or `registry`
+    pub table: String,
+    #[serde(default)]
+    pub peer_count: usize,
+    #[serde(default)]
+    pub buckets: Vec<DhtBucketResult>,
+}
+
+/// Providers stored locally for one registry key (in `DhtSnapshotResult`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct DhtProvidersResult {
+    pub key: String,
+    #[serde(default)]
+    pub providers: Vec<String>,
+}
+
+/// Result of the `get_dht` method
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DhtSnapshotResult {
+    #[serde(default)]
+    pub tables: Vec<DhtTableResult>,
+    #[serde(default)]
+    pub providers: Vec<DhtProvidersResult>,
+}
+
+/// Result of the `dht_get_record` method
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DhtRecordResult {
+    pub key: String,
+    pub found: bool,
+    #[serde(default)]
+    pub value: Option<String>,
+    #[serde(default)]
+    pub publisher: Option<String>,
+    /// Why the daemon's record validators would drop the record
+    #[serde(default)]
+    pub rejection: Option<String>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// Credits booked against one pool (in `CreditLedgerResult`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct PoolCreditsResult {
+    /// Pool pubkey (hex)
+    pub pool: String,
+    #[serde(default)]
+    pub pending_bytes: u64,
+    #[serde(default)]
+    pub pending_credits: u64,
+    #[serde(default)]
+    pub settled_credits: u64,
+    #[serde(default)]
+    pub spent_bytes: u64,
+    #[serde(default)]
+    pub spent_credits: u64,
+    /// Root of the last distribution reconciled against (hex)
+    #[serde(default)]
+    pub distribution_root: Option<String>,
+}
+
+/// One priced request (in `CreditLedgerResult`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreditEntryResult {
+    /// "client", "relay" or "exit"
+    pub role: String,
+    /// Request ID (hex)
+    pub request_id: String,
+    /// Pool pubkey (hex)
+    pub pool: String,
+    /// Subscription tier name (None = free)
+    #[serde(default)]
+    pub tier: Option<String>,
+    #[serde(default)]
+    pub bytes: u64,
+    #[serde(default)]
+    pub hops: u8,
+    #[serde(default)]
+    pub credits: u64,
+    #[serde(default)]
+    pub timestamp: u64,
+}
+
+/// Upstream connection reuse at the exit (in `NodeStatsResult`)
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FetchPoolResult {
+    #[serde(default)]
+    pub requests: u64,
+    #[serde(default)]
+    pub pool_hits: u64,
+    #[serde(default)]
+    pub pool_misses: u64,
+    #[serde(default)]
+    pub dns_hits: u64,
+    #[serde(default)]
+    pub dns_misses: u64,
+}
+
+/// Exit request queue depth of one pool (in `NodeStatsResult`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct PoolQueueResult {
+    /// Pool pubkey (hex)
+    pub pool: String,
+    #[serde(default)]
+    pub weight: u32,
+    #[serde(default)]
+    pub queued: usize,
+    #[serde(default)]
+    pub in_flight: usize,
+}
+
+/// On-chain claim status of one pool (in `NodeStatsResult` and
+/// `EarningsHistoryResult`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct PoolClaimResult {
+    /// Pool pubkey (hex)
+    pub pool: String,
+    #[serde(default)]
+    pub balance: u64,
+    #[serde(default)]
+    pub original_balance: u64,
+    /// "active", "grace", "claimable" or "closed" (None: no account found)
+    #[serde(default)]
+    pub phase: Option<String>,
+    /// "claimable_at", "awaiting_distribution", "claimable_now", "claimed",
+    /// "closed" or "not_found"
+    pub status: String,
+    /// Unix time claims open (status "claimable_at")
+    #[serde(default)]
+    pub claimable_at: Option<u64>,
+    #[serde(default)]
+    pub checked_at: u64,
+}
+
+/// Relay forwarding counters of one tier (in `NodeStatsResult`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct QosClassResult {
+    /// "ultra", "premium", "standard", "basic" or "free"
+    pub class: String,
+    #[serde(default)]
+    pub queued: usize,
+    #[serde(default)]
+    pub forwarded: u64,
+    #[serde(default)]
+    pub forwarded_bytes: u64,
+    #[serde(default)]
+    pub dropped: u64,
+    #[serde(default)]
+    pub throttled: u64,
+}
+
+/// Per-request overrides for the `request` method (None = the daemon's
+/// settings)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RequestOptions {
+    /// Privacy level for this request: `direct`, `single`, `double`, `triple` or `quad`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hop_mode: Option<String>,
+    /// Exit signing pubkey (hex) for this request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit: Option<String>,
+    /// Idle timeout for this request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+    /// Return per-hop timings (diagnostic requests only)
+    #[serde(default)]
+    pub trace: bool,
 }
 
 /// Result of the `request` method
@@ -124,6 +565,33 @@ pub struct RequestResult {
     #[serde(default)]
     pub headers: std::collections::HashMap<String, String>,
     pub body: String,
+    /// Per-hop timings, in path order (traced requests only)
+    #[serde(default)]
+    pub hop_timings: Vec<HopTimingResult>,
+    /// Times the request moved to an alternate exit before it was answered
+    #[serde(default)]
+    pub exit_failovers: u32,
+}
+
+/// One hop's timing of a traced request (unix ms, on that hop's clock)
+#[derive(Debug, Clone, Deserialize)]
+pub struct HopTimingResult {
+    /// `relay` or `exit`
+    pub role: String,
+    pub received_at_ms: u64,
+    pub sent_at_ms: u64,
+}
+
+/// Result of the `request_stream` method.
+///
+/// The body follows as `response_chunk` events (`stream_id`, `seq`, hex
+/// `data`) ending with `response_end` or `response_error`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RequestStreamResult {
+    pub stream_id: String,
+    pub status: u16,
+    #[serde(default)]
+    pub headers: std::collections::HashMap<String, String>,
 }
 
 /// Info about an available exit node
@@ -136,6 +604,9 @@ pub struct ExitNodeInfo {
     pub score: u8,
     pub load: u8,
     pub latency_ms: Option<u64>,
+    /// PeerId bound to the exit's pubkey by a verified binding record
+    #[serde(default)]
+    pub peer_id: Option<String>,
 }
 
 /// Result of the `get_available_exits` method
@@ -144,6 +615,28 @@ pub struct AvailableExitsResult {
     pub exits: Vec<ExitNodeInfo>,
 }
 
+/// A relay's claim proven by a distribution multiproof
+#[derive(Debug, Clone, Deserialize)]
+pub struct DistributionEntryResult {
+    /// Relay pubkey (hex)
+    pub relay: String,
+    pub cumulative_bytes: u64,
+}
+
+/// Merkle multiproof returned by `get_distribution_batch_proof`
+#[derive(Debug, Clone, Deserialize)]
+pub struct DistributionBatchProofResult {
+    /// Distribution Merkle root (hex)
+    pub root: String,
+    /// Proven claims, in `leaf_indices` order
+    pub entries: Vec<DistributionEntryResult>,
+    pub leaf_indices: Vec<usize>,
+    /// Leaves in the tree (including padding)
+    pub leaf_count: usize,
+    /// Sibling hashes shared by all entries (hex)
+    pub hashes: Vec<String>,
+}
+
 /// Connection history entry
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConnectionHistoryEntry {
@@ -151,9 +644,18 @@ pub struct ConnectionHistoryEntry {
     pub connected_at: u64,
     pub disconnected_at: Option<u64>,
     pub duration_secs: Option<u64>,
+    /// Exit signing pubkey (hex) the session used
+    #[serde(default)]
+    pub exit: Option<String>,
     pub exit_region: Option<String>,
+    /// Privacy level of the session
+    #[serde(default)]
+    pub hop_mode: Option<String>,
     pub bytes_sent: u64,
     pub bytes_received: u64,
+    /// Why the connect failed (None = the session connected)
+    #[serde(default)]
+    pub failure_reason: Option<String>,
 }
 
 /// Result of the `get_connection_history` method
@@ -176,6 +678,9 @@ pub struct EarningsEntry {
 #[derive(Debug, Clone, Deserialize)]
 pub struct EarningsHistoryResult {
     pub entries: Vec<EarningsEntry>,
+    /// On-chain claim status of pools we earned from
+    #[serde(default)]
+    pub pools: Vec<PoolClaimResult>,
 }
 
 /// Speed test result
@@ -207,6 +712,131 @@ pub struct KeyImportResult {
     pub public_key: String,
 }
 
+/// Peer in the network topology graph
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopologyNode {
+    pub peer_id: String,
+    #[serde(default)]
+    pub roles: Vec<String>,
+    pub last_seen: u64,
+}
+
+/// Link between two peers in the network topology graph
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopologyEdge {
+    pub source: String,
+    pub target: String,
+    pub last_seen: u64,
+}
+
+/// Result of the `get_topology` method
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopologyResult {
+    #[serde(default)]
+    pub local_peer_id: Option<String>,
+    pub nodes: Vec<TopologyNode>,
+    pub edges: Vec<TopologyEdge>,
+    #[serde(default)]
+    pub generated_at: u64,
+}
+
+impl TopologyResult {
+    /// Render the topology as a Graphviz DOT graph.
+    ///
+    /// Nodes are shaped by role (exit = box, relay = ellipse, other = circle)
+    /// and the local node is highlighted.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("graph craftnet {\n");
+        out.push_str("    node [fontsize=10];\n");
+        for node in &self.nodes {
+            let short = short_peer_id(&node.peer_id);
+            let roles = if node.roles.is_empty() {
+                "peer".to_string()
+            } else {
+                node.roles.join(",")
+            };
+            let shape = if node.roles.iter().any(|r| r == "exit") {
+                "box"
+            } else if node.roles.iter().any(|r| r == "relay") {
+                "ellipse"
+            } else {
+                "circle"
+            };
+            let style = if self.local_peer_id.as_deref() == Some(node.peer_id.as_str()) {
+                ", style=filled, fillcolor=lightblue"
+            } else {
+                ""
+            };
+            out.push_str(&format!(
+                "    \"{}\" [label=\"{}\\n{}\", shape={}{}];\n",
+                node.peer_id, short, roles, shape, style,
+            ));
+        }
+        for edge in &self.edges {
+            out.push_str(&format!("    \"{}\" -- \"{}\";\n", edge.source, edge.target));
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+/// Shorten a peer ID for display (last 8 characters)
+fn short_peer_id(peer_id: &str) -> &str {
+    let len = peer_id.len();
+    if len > 8 { &peer_id[len - 8..] } else { peer_id }
+}
+
+/// Result of the `drain` method
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DrainResult {
+    /// Everything in flight finished before the deadline
+    pub completed: bool,
+    #[serde(default)]
+    pub pending_assemblies: usize,
+    #[serde(default)]
+    pub exit_tasks: usize,
+    #[serde(default)]
+    pub queued_receipts: usize,
+    #[serde(default)]
+    pub unpublished_proofs: usize,
+    #[serde(default)]
+    pub unflushed_receipts: usize,
+    #[serde(default)]
+    pub outbound_queued: usize,
+}
+
+/// Kill switch status (`get_kill_switch` and friends, `kill_switch` events)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KillSwitchResult {
+    /// Engages automatically on unexpected disconnects
+    pub enabled: bool,
+    /// `armed`, `engaged` or `released`
+    pub state: String,
+    pub reason: Option<String>,
+    pub engaged_at: Option<u64>,
+}
+
+/// One entry of the request audit log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntryResult {
+    pub timestamp: u64,
+    pub host: String,
+    pub bytes_up: u64,
+    pub bytes_down: u64,
+    pub hops: u8,
+    pub exit: Option<String>,
+    /// `{"kind": "response", "status": 200}` or `{"kind": "error", "code": "TIMEOUT"}`
+    pub outcome: serde_json::Value,
+}
+
+/// Result of the `get_audit_log` method
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditLogResult {
+    pub enabled: bool,
+    #[serde(default)]
+    pub entries: Vec<AuditEntryResult>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -248,4 +878,37 @@ mod tests {
         assert!(response.error.is_some());
         assert_eq!(response.error.as_ref().unwrap().code, -32600);
     }
+
+    #[test]
+    fn test_rpc_error_code() {
+        let json = r#"{"jsonrpc":"2.0","error":{"code":-32000,"message":"Connect error: Insufficient credits","data":{"code":"INSUFFICIENT_CREDITS"}},"id":1}"#;
+        let response: RpcResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(response.error.unwrap().error_code(), Some("INSUFFICIENT_CREDITS"));
+
+        let json = r#"{"jsonrpc":"2.0","error":{"code":-32600,"message":"Invalid request"},"id":1}"#;
+        let response: RpcResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(response.error.unwrap().error_code(), None);
+    }
+
+    #[test]
+    fn test_topology_to_dot() {
+        let json = r#"{
+            "local_peer_id": "12D3KooWLocal",
+            "nodes": [
+                {"peer_id": "12D3KooWLocal", "roles": ["client"], "last_seen": 10},
+                {"peer_id": "12D3KooWRelay1", "roles": ["relay"], "last_seen": 5},
+                {"peer_id": "12D3KooWExit01", "roles": ["exit"], "last_seen": 5}
+            ],
+            "edges": [{"source": "12D3KooWLocal", "target": "12D3KooWRelay1", "last_seen": 10}],
+            "generated_at": 10
+        }"#;
+        let topology: TopologyResult = serde_json::from_str(json).unwrap();
+        let dot = topology.to_dot();
+
+        assert!(dot.starts_with("graph craftnet {"));
+        assert!(dot.contains("\"12D3KooWLocal\" -- \"12D3KooWRelay1\";"));
+        assert!(dot.contains("shape=box"));
+        assert!(dot.contains("fillcolor=lightblue"));
+        assert!(dot.trim_end().ends_with('}'));
+    }
 }
@@ -0,0 +1,141 @@
+//! Shard frame pipeline benchmark.
+//!
+//! Measures what a relay does per shard on the stream transport: read a
+//! frame, decode the shard and encode it again for the next hop. Compares
+//! the pooled `Bytes` path (`read_frame` / `encode_shard_frame`) with the
+//! per-frame `Vec` path it replaced, plus the same relay step with sealed
+//! frames on both links.
+//!
+//! Run with `cargo bench -p craftnet-network --bench shard_frames`. Before
+//! the timings it prints heap allocations per relayed frame.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use bytes::Bytes;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use futures::executor::block_on;
+use futures::io::Cursor;
+
+use craftnet_core::Shard;
+use craftnet_network::{encode_shard_frame, frame_pool, read_frame, FrameCipher, FrameKeyExchange, StreamFrame};
+
+/// Counts heap allocations so the report can show allocations per frame
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn sample_shard() -> Shard {
+    Shard::new([7; 32], vec![1; 512], vec![2; 3 * 1024], vec![3; 92], 3, 2)
+}
+
+/// A shard frame as the `Vec` path built it: shard bytes, then a copy into the frame
+fn vec_encode(shard: &Shard, seq_id: u64) -> Vec<u8> {
+    let shard_bytes = shard.to_bytes().unwrap();
+    let payload_len = 8 + shard_bytes.len();
+    let mut buf = Vec::with_capacity(1 + 4 + payload_len);
+    buf.push(0x01);
+    buf.extend_from_slice(&(payload_len as u32).to_be_bytes());
+    buf.extend_from_slice(&seq_id.to_be_bytes());
+    buf.extend_from_slice(&shard_bytes);
+    buf
+}
+
+/// Relay step on the `Vec` path: a fresh payload buffer per frame read
+fn vec_relay(frame: &[u8]) -> Vec<u8> {
+    let len = u32::from_be_bytes(frame[1..5].try_into().unwrap()) as usize;
+    let payload = frame[5..5 + len].to_vec();
+    let seq_id = u64::from_be_bytes(payload[..8].try_into().unwrap());
+    let shard = Shard::from_bytes(&payload[8..]).unwrap();
+    vec_encode(&shard, seq_id)
+}
+
+/// Relay step on the pooled path; the written frame goes back to the pool
+fn pooled_relay(frame: &[u8]) {
+    let StreamFrame::Shard { seq_id, shard } = block_on(read_frame(&mut Cursor::new(frame))).unwrap() else {
+        unreachable!()
+    };
+    frame_pool().give(encode_shard_frame(&shard, seq_id).unwrap());
+}
+
+/// Relay step with sealed frames on the inbound and the outbound link
+fn sealed_relay(frame: Bytes, inbound: &mut FrameCipher, outbound: &mut FrameCipher) {
+    let StreamFrame::Sealed { counter, ciphertext } = block_on(read_frame(&mut Cursor::new(frame))).unwrap() else {
+        unreachable!()
+    };
+    let StreamFrame::Shard { seq_id, shard } = inbound.open(counter, ciphertext).unwrap() else {
+        unreachable!()
+    };
+    frame_pool().give(outbound.seal(encode_shard_frame(&shard, seq_id).unwrap()).unwrap());
+}
+
+/// A sealer/opener pair for one link
+fn link() -> (FrameCipher, FrameCipher) {
+    let (opener, responder) = (FrameKeyExchange::generate(), FrameKeyExchange::generate());
+    let (opener_key, responder_key) = (opener.public_key(), responder.public_key());
+    (opener.opener_cipher(&responder_key).unwrap(), responder.responder_cipher(&opener_key).unwrap())
+}
+
+fn allocations_per_frame(mut relay: impl FnMut()) -> f64 {
+    const FRAMES: u64 = 1000;
+    // Warm the pool first, like a relay that has been running for a while
+    for _ in 0..16 {
+        relay();
+    }
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for _ in 0..FRAMES {
+        relay();
+    }
+    (ALLOCATIONS.load(Ordering::Relaxed) - before) as f64 / FRAMES as f64
+}
+
+fn report_allocations() {
+    let shard = sample_shard();
+    let vec_frame = vec_encode(&shard, 1);
+    let pooled_frame = encode_shard_frame(&shard, 1).unwrap();
+
+    let vec_allocs = allocations_per_frame(|| drop(vec_relay(&vec_frame)));
+    let pooled_allocs = allocations_per_frame(|| pooled_relay(&pooled_frame));
+    eprintln!("allocations per relayed frame: vec {:.1}, pooled {:.1}", vec_allocs, pooled_allocs);
+}
+
+fn bench_relay(c: &mut Criterion) {
+    report_allocations();
+
+    let shard = sample_shard();
+    let vec_frame = vec_encode(&shard, 1);
+    let pooled_frame = encode_shard_frame(&shard, 1).unwrap();
+
+    let mut group = c.benchmark_group("relay_frame");
+    group.throughput(Throughput::Bytes(vec_frame.len() as u64));
+    group.bench_function("vec", |b| b.iter(|| vec_relay(&vec_frame)));
+    group.bench_function("pooled", |b| b.iter(|| pooled_relay(&pooled_frame)));
+
+    let (mut upstream_sealer, mut inbound) = link();
+    let (mut outbound, _) = link();
+    group.bench_function("pooled_sealed", |b| {
+        b.iter_batched(
+            || upstream_sealer.seal(encode_shard_frame(&shard, 1).unwrap()).unwrap(),
+            |frame| sealed_relay(frame, &mut inbound, &mut outbound),
+            criterion::BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_relay);
+criterion_main!(benches);
@@ -0,0 +1,142 @@
+//! Shard stream transport benchmark.
+//!
+//! Two swarms in one process, connected over loopback TCP or QUIC, send
+//! shard frames on `/craftnet/shard-stream` and wait for the receiver's
+//! acks. Compares frame sizes and ack strategies: one shard in flight
+//! (`sync`) against a pipelined window of 64 (`window`).
+//!
+//! Run with `cargo bench -p craftnet-network --bench shard_stream`. For
+//! numbers across two processes, with latency percentiles as JSON, use the
+//! `shard-stream-bench` binary.
+
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use futures::{AsyncReadExt, AsyncWriteExt, StreamExt};
+use libp2p::identity::Keypair;
+use libp2p::swarm::SwarmEvent;
+use tokio::runtime::Runtime;
+use tokio::sync::Semaphore;
+
+use craftnet_core::Shard;
+use craftnet_network::{
+    build_swarm, encode_shard_frame, frame_pool, read_frame, write_ack_frame, write_shard_frame, Multiaddr,
+    NetworkConfig, PeerId, StreamFrame, SHARD_STREAM_PROTOCOL,
+};
+
+const FRAME_SIZES: [usize; 3] = [1024, 4096, 10 * 1024];
+
+/// Shards in flight for the pipelined strategy
+const WINDOW: usize = 64;
+
+fn shard_for_frame(frame_bytes: usize) -> Shard {
+    let base = Shard::new([7; 32], vec![1; 512], vec![], vec![3; 92], 3, 2);
+    let frame = encode_shard_frame(&base, 0).unwrap();
+    let overhead = frame.len();
+    frame_pool().give(frame);
+    Shard::new([7; 32], vec![1; 512], vec![2; frame_bytes.saturating_sub(overhead)], vec![3; 92], 3, 2)
+}
+
+async fn ack_shards(mut stream: libp2p::Stream) {
+    while let Ok(frame) = read_frame(&mut stream).await {
+        if let StreamFrame::Shard { seq_id, .. } = frame {
+            if write_ack_frame(&mut stream, seq_id, None).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Start an acking receiver on `listen` and connect a sender to it.
+/// Returns the sender's stream control and the receiver's peer id.
+async fn connect(listen: Multiaddr) -> (libp2p_stream::Control, PeerId) {
+    let config = NetworkConfig { listen_addrs: vec![listen], bootstrap_peers: vec![], webrtc_cert_path: None };
+    let (mut receiver, receiver_id, mut incoming) = build_swarm(Keypair::generate_ed25519(), config).await.unwrap();
+    tokio::spawn(async move {
+        while let Some((_peer, stream)) = incoming.next().await {
+            tokio::spawn(ack_shards(stream));
+        }
+    });
+    let addr = loop {
+        if let SwarmEvent::NewListenAddr { address, .. } = receiver.select_next_some().await {
+            break address;
+        }
+    };
+    tokio::spawn(async move {
+        loop {
+            receiver.select_next_some().await;
+        }
+    });
+
+    let config = NetworkConfig { listen_addrs: vec![], bootstrap_peers: vec![], webrtc_cert_path: None };
+    let (mut sender, _, _incoming) = build_swarm(Keypair::generate_ed25519(), config).await.unwrap();
+    sender.dial(addr).unwrap();
+    loop {
+        if let SwarmEvent::ConnectionEstablished { peer_id, .. } = sender.select_next_some().await {
+            if peer_id == receiver_id {
+                break;
+            }
+        }
+    }
+    let control = sender.behaviour().stream_control();
+    tokio::spawn(async move {
+        loop {
+            sender.select_next_some().await;
+        }
+    });
+    (control, receiver_id)
+}
+
+/// Send `count` shards on a fresh stream with at most `in_flight` unacked
+async fn send_shards(control: &mut libp2p_stream::Control, peer: PeerId, shard: &Shard, count: u64, in_flight: usize) -> Duration {
+    let stream = control.open_stream(peer, SHARD_STREAM_PROTOCOL).await.unwrap();
+    let (mut reader, mut writer) = stream.split();
+    let permits = std::sync::Arc::new(Semaphore::new(in_flight));
+    let start = Instant::now();
+    let acks = {
+        let permits = permits.clone();
+        tokio::spawn(async move {
+            let mut acked = 0;
+            while acked < count {
+                if let StreamFrame::Ack { .. } = read_frame(&mut reader).await.unwrap() {
+                    acked += 1;
+                    permits.add_permits(1);
+                }
+            }
+        })
+    };
+    for seq_id in 0..count {
+        permits.acquire().await.unwrap().forget();
+        write_shard_frame(&mut writer, shard, seq_id).await.unwrap();
+    }
+    acks.await.unwrap();
+    let elapsed = start.elapsed();
+    let _ = writer.close().await;
+    elapsed
+}
+
+fn bench_transport(c: &mut Criterion, rt: &Runtime, name: &str, listen: &str) {
+    let (mut control, peer) = rt.block_on(connect(listen.parse().unwrap()));
+
+    let mut group = c.benchmark_group(format!("shard_stream/{}", name));
+    group.sample_size(20).measurement_time(Duration::from_secs(5));
+    for frame_bytes in FRAME_SIZES {
+        let shard = shard_for_frame(frame_bytes);
+        group.throughput(Throughput::Bytes(frame_bytes as u64));
+        for (strategy, in_flight) in [("sync", 1), ("window", WINDOW)] {
+            group.bench_with_input(BenchmarkId::new(strategy, frame_bytes), &shard, |b, shard| {
+                b.iter_custom(|iters| rt.block_on(send_shards(&mut control, peer, shard, iters, in_flight)))
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_shard_stream(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    bench_transport(c, &rt, "tcp", "/ip4/127.0.0.1/tcp/0");
+    bench_transport(c, &rt, "quic", "/ip4/127.0.0.1/udp/0/quic-v1");
+}
+
+criterion_group!(benches, bench_shard_stream);
+criterion_main!(benches);
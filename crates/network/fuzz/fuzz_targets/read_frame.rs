@@ -0,0 +1,32 @@
+//! Feeds arbitrary bytes to `read_frame` until it errors. Every frame that
+//! decodes must re-encode with the matching `write_*_frame` and decode again.
+
+#![no_main]
+
+use futures::executor::block_on;
+use futures::io::Cursor;
+use libfuzzer_sys::fuzz_target;
+
+use craftnet_network::{
+    read_frame, write_ack_frame, write_chain_ack_frame, write_hello_frame, write_nack_frame, write_shard_frame,
+    StreamFrame,
+};
+
+fuzz_target!(|data: &[u8]| {
+    let mut input = Cursor::new(data);
+    while let Ok(frame) = block_on(read_frame(&mut input)) {
+        let mut buffer = Vec::new();
+        let mut output = Cursor::new(&mut buffer);
+        let written = match &frame {
+            StreamFrame::Shard { seq_id, shard } => block_on(write_shard_frame(&mut output, shard, *seq_id)),
+            StreamFrame::Ack { seq_id, receipt } => block_on(write_ack_frame(&mut output, *seq_id, receipt.as_ref())),
+            StreamFrame::Nack { seq_id, reason } => block_on(write_nack_frame(&mut output, *seq_id, reason)),
+            StreamFrame::Hello(version) => block_on(write_hello_frame(&mut output, version)),
+            StreamFrame::ChainAck(ack) => block_on(write_chain_ack_frame(&mut output, ack)),
+            // Opaque without the link key
+            StreamFrame::Sealed { .. } => continue,
+        };
+        written.expect("decoded frame must re-encode");
+        block_on(read_frame(&mut Cursor::new(&buffer))).expect("re-encoded frame must decode");
+    }
+});
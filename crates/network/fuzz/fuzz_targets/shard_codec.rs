@@ -0,0 +1,27 @@
+//! Feeds arbitrary bytes to the request-response shard codec. Decoding must
+//! fail cleanly, and a decoded shard must survive a write/read round trip.
+
+#![no_main]
+
+use futures::executor::block_on;
+use futures::io::Cursor;
+use libfuzzer_sys::fuzz_target;
+use libp2p::request_response::Codec;
+
+use craftnet_network::{ShardCodec, SHARD_PROTOCOL_ID};
+
+fuzz_target!(|data: &[u8]| {
+    let mut codec = ShardCodec::new();
+    let _ = block_on(codec.read_response(&SHARD_PROTOCOL_ID, &mut Cursor::new(data)));
+
+    let Ok(request) = block_on(codec.read_request(&SHARD_PROTOCOL_ID, &mut Cursor::new(data))) else {
+        return;
+    };
+    let shard_bytes = request.shard.to_bytes().expect("decoded shard must serialize");
+    let mut buffer = Vec::new();
+    block_on(codec.write_request(&SHARD_PROTOCOL_ID, &mut Cursor::new(&mut buffer), request))
+        .expect("decoded shard must re-encode");
+    let read = block_on(codec.read_request(&SHARD_PROTOCOL_ID, &mut Cursor::new(&buffer)))
+        .expect("re-encoded shard must decode");
+    assert_eq!(read.shard.to_bytes().unwrap(), shard_bytes);
+});
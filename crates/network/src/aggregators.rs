@@ -0,0 +1,204 @@
+//! Aggregator registry
+//!
+//! Anyone can run an aggregator and race to post a pool's distribution.
+//! Maintainers publish an [`AggregatorRegistry`] under the well-known DHT
+//! key [`AGGREGATOR_REGISTRY_KEY`] listing the aggregator keys approved for
+//! each epoch, and nodes configured to only trust registered aggregators
+//! ignore distributions posted by anyone else.
+//!
+//! Like the network parameter beacon, the record is a [`SignedDhtRecord`]
+//! that only validates when signed by a maintainer key, and nodes keep the
+//! one with the highest `sequence`. The set is rotated by publishing a new
+//! sequence whose entries start or end at a future epoch
+//! ([`AggregatorRegistry::rotate`]), so both sets overlap until the switch.
+
+use std::time::Duration;
+
+use craftec_crypto::SigningKeypair;
+use craftnet_core::PublicKey;
+use serde::{Deserialize, Serialize};
+
+use crate::behaviour::{DhtRecordValidator, RecordRejection, SignedDhtRecord};
+
+/// Well-known DHT key of the aggregator registry record
+pub const AGGREGATOR_REGISTRY_KEY: &[u8] = b"/craftnet/aggregator-registry";
+
+/// How long a signed registry record is accepted
+pub const AGGREGATOR_REGISTRY_TTL: Duration = Duration::from_secs(30 * 24 * 3600);
+
+/// Length of a registry epoch
+pub const AGGREGATOR_EPOCH_SECS: u64 = 7 * 24 * 3600;
+
+/// Registry epoch of a unix timestamp
+pub fn aggregator_epoch(unix_secs: u64) -> u64 {
+    unix_secs / AGGREGATOR_EPOCH_SECS
+}
+
+/// An aggregator key approved for a range of epochs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegisteredAggregator {
+    /// Settlement signing key (hex ed25519 public key)
+    pub pubkey: String,
+    /// Operator name, for humans
+    #[serde(default)]
+    pub label: String,
+    /// First epoch the key may post in
+    #[serde(default)]
+    pub from_epoch: u64,
+    /// First epoch the key may no longer post in (None = open-ended)
+    #[serde(default)]
+    pub until_epoch: Option<u64>,
+}
+
+impl RegisteredAggregator {
+    pub fn new(pubkey: &PublicKey, label: impl Into<String>, from_epoch: u64) -> Self {
+        Self { pubkey: hex::encode(pubkey), label: label.into(), from_epoch, until_epoch: None }
+    }
+
+    /// Decoded public key (None if the entry is malformed)
+    pub fn pubkey_bytes(&self) -> Option<PublicKey> {
+        hex::decode(&self.pubkey).ok()?.try_into().ok()
+    }
+
+    /// Whether the entry covers `epoch`
+    pub fn active_in(&self, epoch: u64) -> bool {
+        epoch >= self.from_epoch && self.until_epoch.is_none_or(|until| epoch < until)
+    }
+}
+
+/// Aggregators approved by the maintainers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct AggregatorRegistry {
+    /// Increases with every published change; lower ones are ignored
+    pub sequence: u64,
+    #[serde(default)]
+    pub aggregators: Vec<RegisteredAggregator>,
+}
+
+impl AggregatorRegistry {
+    /// Sign for publication under [`AGGREGATOR_REGISTRY_KEY`]
+    pub fn sign(&self, keypair: &SigningKeypair) -> SignedDhtRecord {
+        SignedDhtRecord::sign(keypair, AGGREGATOR_REGISTRY_KEY, serde_json::to_string(self).unwrap_or_default())
+    }
+
+    /// Whether `pubkey` may post distributions in `epoch`
+    pub fn is_registered(&self, pubkey: &PublicKey, epoch: u64) -> bool {
+        self.aggregators
+            .iter()
+            .any(|a| a.active_in(epoch) && a.pubkey_bytes().as_ref() == Some(pubkey))
+    }
+
+    /// Keys approved for `epoch`
+    pub fn active(&self, epoch: u64) -> Vec<PublicKey> {
+        self.aggregators
+            .iter()
+            .filter(|a| a.active_in(epoch))
+            .filter_map(|a| a.pubkey_bytes())
+            .collect()
+    }
+
+    /// Next version of the registry: from `at_epoch` on, `add` are approved
+    /// and `remove` are not. Entries that ended before `at_epoch` are
+    /// dropped; everything else keeps its window.
+    pub fn rotate(&self, at_epoch: u64, add: &[(PublicKey, String)], remove: &[PublicKey]) -> Self {
+        let mut aggregators: Vec<RegisteredAggregator> = self
+            .aggregators
+            .iter()
+            .filter(|a| a.until_epoch.is_none_or(|until| until >= at_epoch))
+            .cloned()
+            .collect();
+        for entry in &mut aggregators {
+            let removed = entry.pubkey_bytes().is_some_and(|k| remove.contains(&k));
+            if removed && entry.until_epoch.is_none_or(|until| until > at_epoch) {
+                entry.until_epoch = Some(at_epoch);
+            }
+        }
+        for (pubkey, label) in add {
+            if !aggregators.iter().any(|a| a.pubkey_bytes().as_ref() == Some(pubkey) && a.until_epoch.is_none()) {
+                aggregators.push(RegisteredAggregator::new(pubkey, label.clone(), at_epoch));
+            }
+        }
+        Self { sequence: self.sequence + 1, aggregators }
+    }
+}
+
+/// Validator for [`AGGREGATOR_REGISTRY_KEY`]: the signer must be a
+/// maintainer and the body must parse as [`AggregatorRegistry`]
+pub struct AggregatorRegistryValidator {
+    keys: Vec<PublicKey>,
+}
+
+impl AggregatorRegistryValidator {
+    pub fn new(keys: Vec<PublicKey>) -> Self {
+        Self { keys }
+    }
+}
+
+impl DhtRecordValidator for AggregatorRegistryValidator {
+    fn validate(&self, key_suffix: &str, record: &SignedDhtRecord) -> Result<(), RecordRejection> {
+        if !key_suffix.is_empty() {
+            return Err(RecordRejection::Malformed);
+        }
+        let signer = record.pubkey_bytes().ok_or(RecordRejection::BadSignature)?;
+        if !self.keys.contains(&signer) {
+            return Err(RecordRejection::IdentityMismatch);
+        }
+        serde_json::from_str::<AggregatorRegistry>(&record.data)
+            .map(|_| ())
+            .map_err(|e| RecordRejection::Schema(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::behaviour::DhtRecordValidators;
+
+    #[test]
+    fn test_only_maintainer_registries_validate() {
+        let maintainer = SigningKeypair::generate();
+        let stranger = SigningKeypair::generate();
+        let validators = DhtRecordValidators::with_maintainer_keys(vec![maintainer.public_key_bytes()]);
+        let registry = AggregatorRegistry {
+            sequence: 1,
+            aggregators: vec![RegisteredAggregator::new(&[7; 32], "a", 0)],
+        };
+
+        let body = validators.validate(AGGREGATOR_REGISTRY_KEY, &registry.sign(&maintainer).to_bytes()).unwrap();
+        assert_eq!(serde_json::from_slice::<AggregatorRegistry>(&body).unwrap(), registry);
+        assert_eq!(
+            validators.validate(AGGREGATOR_REGISTRY_KEY, &registry.sign(&stranger).to_bytes()),
+            Err(RecordRejection::IdentityMismatch)
+        );
+    }
+
+    #[test]
+    fn test_registration_windows() {
+        let mut entry = RegisteredAggregator::new(&[1; 32], "a", 10);
+        entry.until_epoch = Some(12);
+        let registry = AggregatorRegistry { sequence: 1, aggregators: vec![entry] };
+        assert!(!registry.is_registered(&[1; 32], 9));
+        assert!(registry.is_registered(&[1; 32], 10));
+        assert!(registry.is_registered(&[1; 32], 11));
+        assert!(!registry.is_registered(&[1; 32], 12));
+        assert!(!registry.is_registered(&[2; 32], 11));
+    }
+
+    #[test]
+    fn test_rotation_overlaps_until_switch() {
+        let registry = AggregatorRegistry {
+            sequence: 4,
+            aggregators: vec![RegisteredAggregator::new(&[1; 32], "old", 0)],
+        };
+        let next = registry.rotate(20, &[([2; 32], "new".to_string())], &[[1; 32]]);
+
+        assert_eq!(next.sequence, 5);
+        assert_eq!(next.active(19), vec![[1; 32]]);
+        assert_eq!(next.active(20), vec![[2; 32]]);
+
+        // Entries that already ended are dropped by the following rotation
+        let after = next.rotate(30, &[], &[]);
+        assert_eq!(after.aggregators.len(), 1);
+        assert!(after.is_registered(&[2; 32], 30));
+    }
+}
@@ -3,12 +3,23 @@
 //! Re-exports CraftBehaviour from craftec-network as CraftNetBehaviour.
 //! CraftNet-specific constants (gossipsub topics, DHT key prefixes) are defined here.
 //! Gossipsub/DHT helper methods are provided via the CraftNetExt trait.
+//! Exit/relay DHT records are signed envelopes checked by [`DhtRecordValidators`].
 
 use libp2p::{
     gossipsub, kad,
     PeerId, StreamProtocol,
 };
-use std::time::Duration;
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use craftec_crypto::{sign_data, verify_signature, SigningKeypair};
+use craftnet_core::{ExitInfo, PeerBinding, PublicKey, RelayInfo};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::aggregators::{AggregatorRegistryValidator, AGGREGATOR_REGISTRY_KEY, AGGREGATOR_REGISTRY_TTL};
+use crate::params::{maintainer_keys, NetworkParamsValidator, NETWORK_PARAMS_KEY, NETWORK_PARAMS_TTL};
+use crate::peer_binding::{verify_peer_binding_for, PEER_BINDING_MAX_AGE};
 
 // Re-export the generic behaviour as CraftNet's behaviour
 pub use craftec_network::CraftBehaviour as CraftNetBehaviour;
@@ -24,7 +35,8 @@ pub const RENDEZVOUS_NAMESPACE: &str = "craftnet";
 pub const EXIT_DHT_KEY_PREFIX: &str = "/craftnet/exits/";
 
 /// DHT key prefix for peer pubkey → PeerId records
-/// Used by clients to announce themselves so relays can route response shards
+/// Used by clients to announce themselves so relays can route response shards.
+/// Record value is a JSON `PeerBinding` (legacy records hold raw PeerId bytes).
 pub const PEER_DHT_KEY_PREFIX: &str = "/craftnet/peers/";
 
 /// TTL for peer records (5 minutes, same as exit records)
@@ -52,7 +64,9 @@ pub const EXIT_RECORD_TTL: Duration = Duration::from_secs(300);
 /// Gossipsub topic for exit node status (heartbeat, load, online/offline)
 pub const EXIT_STATUS_TOPIC: &str = "craftnet/exit-status/1.0.0";
 
-/// Gossipsub topic for ZK-proven receipt summaries
+/// Gossipsub topic for ZK-proven receipt summaries (legacy, unsharded:
+/// relays publish to [`proof_topic_for_pool`] and, while older aggregators
+/// only follow this one, here as well)
 pub const PROOF_TOPIC: &str = "craftnet/proofs/1.0.0";
 
 /// Gossipsub topic for subscription announcements
@@ -61,6 +75,9 @@ pub const SUBSCRIPTION_TOPIC: &str = "craftnet/subscriptions/1.0.0";
 /// Gossipsub topic for aggregator history sync (new aggregators catching up)
 pub const AGGREGATOR_SYNC_TOPIC: &str = "craftnet/aggregator-sync/1.0.0";
 
+/// Gossipsub topic for distribution announcements, challenges and responses
+pub const DISPUTE_TOPIC: &str = "craftnet/disputes/1.0.0";
+
 /// Heartbeat interval for exit nodes (30 seconds)
 pub const EXIT_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
 
@@ -94,6 +111,410 @@ pub fn relay_dht_key(peer_id: &PeerId) -> Vec<u8> {
     format!("{}{}", RELAY_DHT_KEY_PREFIX, peer_id).into_bytes()
 }
 
+// ============================================================================
+// Sharded registries
+// ============================================================================
+
+/// Number of registry shards per node kind.
+///
+/// A node provides the shard key chosen by its settlement pubkey (as well as
+/// the legacy single registry key), so one provider query returns roughly
+/// 1/16th of the network and discovery can page through the shards.
+pub const REGISTRY_SHARDS: u8 = 16;
+
+/// Registry shard for a settlement pubkey
+pub fn registry_shard(pubkey: &PublicKey) -> u8 {
+    pubkey[0] % REGISTRY_SHARDS
+}
+
+/// DHT provider key for one exit registry shard: `/craftnet/exit-registry/<shard>`
+pub fn exit_registry_shard_key(shard: u8) -> Vec<u8> {
+    registry_shard_key(EXIT_REGISTRY_KEY, shard)
+}
+
+/// DHT provider key for one relay registry shard: `/craftnet/relay-registry/<shard>`
+pub fn relay_registry_shard_key(shard: u8) -> Vec<u8> {
+    registry_shard_key(RELAY_REGISTRY_KEY, shard)
+}
+
+fn registry_shard_key(registry: &[u8], shard: u8) -> Vec<u8> {
+    let mut key = registry.to_vec();
+    key.extend_from_slice(format!("/{:x}", shard % REGISTRY_SHARDS).as_bytes());
+    key
+}
+
+/// Shard number of an exit or relay registry shard key
+pub fn parse_registry_shard_key(key: &[u8]) -> Option<u8> {
+    let rest = key.strip_prefix(EXIT_REGISTRY_KEY)
+        .or_else(|| key.strip_prefix(RELAY_REGISTRY_KEY))?;
+    let hex = std::str::from_utf8(rest.strip_prefix(b"/")?).ok()?;
+    u8::from_str_radix(hex, 16).ok().filter(|s| *s < REGISTRY_SHARDS)
+}
+
+// ============================================================================
+// Sharded proof topic
+// ============================================================================
+
+/// Number of proof topic shards.
+///
+/// Proofs are published on the shard chosen by their pool pubkey, so an
+/// aggregator following a few pools only receives those pools' shards
+/// instead of every proof on the network.
+pub const PROOF_TOPIC_SHARDS: u8 = 16;
+
+/// Proof topic shard for a pool pubkey
+pub fn proof_topic_shard(pool: &PublicKey) -> u8 {
+    pool[0] % PROOF_TOPIC_SHARDS
+}
+
+/// Gossipsub topic of one proof shard: `craftnet/proofs/1.0.0/<shard>`
+pub fn proof_shard_topic(shard: u8) -> String {
+    format!("{}/{:x}", PROOF_TOPIC, shard % PROOF_TOPIC_SHARDS)
+}
+
+/// Gossipsub topic proofs for `pool` are published on
+pub fn proof_topic_for_pool(pool: &PublicKey) -> String {
+    proof_shard_topic(proof_topic_shard(pool))
+}
+
+/// Proof shards covering `pools`, ascending and without duplicates
+pub fn proof_topic_shards<'a>(pools: impl IntoIterator<Item = &'a PublicKey>) -> Vec<u8> {
+    let mut shards: Vec<u8> = pools.into_iter().map(proof_topic_shard).collect();
+    shards.sort_unstable();
+    shards.dedup();
+    shards
+}
+
+/// Shard number of a proof shard topic
+pub fn parse_proof_shard_topic(topic: &str) -> Option<u8> {
+    let hex = topic.strip_prefix(PROOF_TOPIC)?.strip_prefix('/')?;
+    u8::from_str_radix(hex, 16).ok().filter(|s| *s < PROOF_TOPIC_SHARDS)
+}
+
+/// Whether `topic` carries proofs (a shard topic or the legacy one)
+pub fn is_proof_topic(topic: &str) -> bool {
+    topic == PROOF_TOPIC || parse_proof_shard_topic(topic).is_some()
+}
+
+// ============================================================================
+// DHT record validation
+// ============================================================================
+
+/// Schema version of signed exit/relay DHT records
+pub const DHT_RECORD_VERSION: u8 = 1;
+
+/// How far in the future a record timestamp may be (publisher clock skew)
+pub const DHT_RECORD_MAX_CLOCK_SKEW: Duration = Duration::from_secs(60);
+
+/// Invalid records a peer may serve within `RECORD_STRIKE_WINDOW` before
+/// it is disconnected and dropped from the routing tables
+pub const RECORD_STRIKE_LIMIT: u32 = 3;
+
+/// Window over which record strikes accumulate
+pub const RECORD_STRIKE_WINDOW: Duration = Duration::from_secs(600);
+
+/// Domain separator for record signatures
+const DHT_RECORD_DOMAIN: &[u8] = b"craftnet-dht-record-v1";
+
+/// Why a DHT record was rejected
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum RecordRejection {
+    #[error("malformed record envelope")]
+    Malformed,
+
+    #[error("unsupported record version {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("invalid record signature")]
+    BadSignature,
+
+    #[error("record expired ({age_secs}s old)")]
+    Expired { age_secs: u64 },
+
+    #[error("record timestamp is in the future")]
+    FromFuture,
+
+    #[error("record body does not match schema: {0}")]
+    Schema(String),
+
+    #[error("record is not bound to the peer in its key")]
+    IdentityMismatch,
+}
+
+/// Signed envelope for exit/relay DHT records.
+///
+/// The signature covers the DHT key as well, so a valid record can't be
+/// replayed under another node's key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedDhtRecord {
+    /// Schema version (`DHT_RECORD_VERSION`)
+    pub version: u8,
+    /// Publisher's settlement (ed25519) pubkey, hex encoded
+    pub pubkey: String,
+    /// Unix timestamp (seconds) when the record was signed
+    pub timestamp: u64,
+    /// Record body (JSON `ExitInfo` / `RelayInfo`)
+    pub data: String,
+    /// Settlement key's signature over `signable_data()`, hex encoded
+    pub signature: String,
+}
+
+impl SignedDhtRecord {
+    /// Sign `data` for publication under `key`
+    pub fn sign(keypair: &SigningKeypair, key: &[u8], data: String) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        Self::sign_at(keypair, key, data, timestamp)
+    }
+
+    /// Sign with an explicit timestamp
+    pub fn sign_at(keypair: &SigningKeypair, key: &[u8], data: String, timestamp: u64) -> Self {
+        let signable = Self::signable_data(key, DHT_RECORD_VERSION, timestamp, &data);
+        Self {
+            version: DHT_RECORD_VERSION,
+            pubkey: hex::encode(keypair.public_key_bytes()),
+            timestamp,
+            signature: hex::encode(sign_data(keypair, &signable)),
+            data,
+        }
+    }
+
+    /// Data covered by the signature
+    pub fn signable_data(key: &[u8], version: u8, timestamp: u64, data: &str) -> Vec<u8> {
+        let mut out = Vec::with_capacity(DHT_RECORD_DOMAIN.len() + key.len() + 9 + data.len());
+        out.extend_from_slice(DHT_RECORD_DOMAIN);
+        out.extend_from_slice(&(key.len() as u32).to_le_bytes());
+        out.extend_from_slice(key);
+        out.push(version);
+        out.extend_from_slice(&timestamp.to_le_bytes());
+        out.extend_from_slice(data.as_bytes());
+        out
+    }
+
+    /// Publisher pubkey as bytes
+    pub fn pubkey_bytes(&self) -> Option<PublicKey> {
+        hex::decode(&self.pubkey).ok()?.try_into().ok()
+    }
+
+    /// Check the signature for publication under `key`
+    pub fn verify(&self, key: &[u8]) -> bool {
+        let Some(pubkey) = self.pubkey_bytes() else {
+            return false;
+        };
+        let Some(signature) = hex::decode(&self.signature).ok()
+            .and_then(|s| <[u8; 64]>::try_from(s).ok())
+        else {
+            return false;
+        };
+        let signable = Self::signable_data(key, self.version, self.timestamp, &self.data);
+        verify_signature(&pubkey, &signable, &signature)
+    }
+
+    /// Serialize to JSON bytes for the DHT
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap_or_default()
+    }
+
+    /// Parse from JSON bytes
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        serde_json::from_slice(data).ok()
+    }
+}
+
+/// Schema check for the body of a signed record.
+///
+/// Implement this to validate records under a new key prefix and register it
+/// with [`DhtRecordValidators::register`].
+pub trait DhtRecordValidator: Send + Sync {
+    /// Check `record` (signature and freshness already verified) published
+    /// under the key whose text after the prefix is `key_suffix`
+    fn validate(&self, key_suffix: &str, record: &SignedDhtRecord) -> Result<(), RecordRejection>;
+}
+
+/// Exit/relay info bodies: settlement pubkey plus a binding to the key's PeerId
+trait NodeInfoRecord: serde::de::DeserializeOwned {
+    fn pubkey(&self) -> &PublicKey;
+    fn peer_binding(&self) -> Option<&PeerBinding>;
+}
+
+impl NodeInfoRecord for ExitInfo {
+    fn pubkey(&self) -> &PublicKey {
+        &self.pubkey
+    }
+    fn peer_binding(&self) -> Option<&PeerBinding> {
+        self.peer_binding.as_ref()
+    }
+}
+
+impl NodeInfoRecord for RelayInfo {
+    fn pubkey(&self) -> &PublicKey {
+        &self.pubkey
+    }
+    fn peer_binding(&self) -> Option<&PeerBinding> {
+        self.peer_binding.as_ref()
+    }
+}
+
+/// Validator for `/craftnet/exits/<peer_id>` and `/craftnet/relays/<peer_id>`.
+///
+/// The body must parse as the info type, name the signing pubkey, and carry
+/// a peer binding from that pubkey to the PeerId in the key — so only the
+/// node itself can publish under its key.
+struct NodeInfoValidator<T>(std::marker::PhantomData<T>);
+
+impl<T: NodeInfoRecord + Send + Sync> DhtRecordValidator for NodeInfoValidator<T> {
+    fn validate(&self, key_suffix: &str, record: &SignedDhtRecord) -> Result<(), RecordRejection> {
+        let info: T = serde_json::from_str(&record.data)
+            .map_err(|e| RecordRejection::Schema(e.to_string()))?;
+        if Some(*info.pubkey()) != record.pubkey_bytes() {
+            return Err(RecordRejection::Schema("pubkey differs from signer".to_string()));
+        }
+        let key_peer: PeerId = key_suffix.parse().map_err(|_| RecordRejection::IdentityMismatch)?;
+        let binding = info.peer_binding().ok_or(RecordRejection::IdentityMismatch)?;
+        // The binding is signed along with the record, so it must be as fresh
+        match verify_peer_binding_for(binding, info.pubkey(), PEER_BINDING_MAX_AGE, record.timestamp) {
+            Ok(bound) if bound == key_peer => Ok(()),
+            _ => Err(RecordRejection::IdentityMismatch),
+        }
+    }
+}
+
+/// Record validators by DHT key prefix.
+///
+/// Records under a registered prefix must be a [`SignedDhtRecord`] with a
+/// current version, a valid signature and a timestamp within the prefix's
+/// TTL, and must pass the prefix's [`DhtRecordValidator`]. Keys without a
+/// validator are passed through unchanged.
+pub struct DhtRecordValidators {
+    validators: Vec<(String, Duration, Box<dyn DhtRecordValidator>)>,
+}
+
+impl DhtRecordValidators {
+    /// No validators (every record passes through)
+    pub fn empty() -> Self {
+        Self { validators: Vec::new() }
+    }
+
+    /// Validate records under `prefix`, accepting them for `ttl` after signing
+    pub fn register(&mut self, prefix: &str, ttl: Duration, validator: Box<dyn DhtRecordValidator>) {
+        self.validators.push((prefix.to_string(), ttl, validator));
+    }
+
+    /// Whether records under `key` are validated
+    pub fn covers(&self, key: &[u8]) -> bool {
+        self.find(key).is_some()
+    }
+
+    fn find(&self, key: &[u8]) -> Option<(&str, Duration, &dyn DhtRecordValidator)> {
+        self.validators.iter()
+            .find(|(prefix, _, _)| key.starts_with(prefix.as_bytes()))
+            .map(|(prefix, ttl, v)| (prefix.as_str(), *ttl, v.as_ref()))
+    }
+
+    /// Validate a record value, returning the body the application should
+    /// parse (the raw value for keys without a validator)
+    pub fn validate(&self, key: &[u8], value: &[u8]) -> Result<Vec<u8>, RecordRejection> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        self.validate_at(key, value, now)
+    }
+
+    /// [`validate`](Self::validate) at unix time `now`
+    pub fn validate_at(&self, key: &[u8], value: &[u8], now: u64) -> Result<Vec<u8>, RecordRejection> {
+        let Some((prefix, ttl, validator)) = self.find(key) else {
+            return Ok(value.to_vec());
+        };
+        let record = SignedDhtRecord::from_bytes(value).ok_or(RecordRejection::Malformed)?;
+        if record.version != DHT_RECORD_VERSION {
+            return Err(RecordRejection::UnsupportedVersion(record.version));
+        }
+        if !record.verify(key) {
+            return Err(RecordRejection::BadSignature);
+        }
+        if record.timestamp > now + DHT_RECORD_MAX_CLOCK_SKEW.as_secs() {
+            return Err(RecordRejection::FromFuture);
+        }
+        let age_secs = now.saturating_sub(record.timestamp);
+        if age_secs > ttl.as_secs() {
+            return Err(RecordRejection::Expired { age_secs });
+        }
+        let suffix = std::str::from_utf8(&key[prefix.len()..])
+            .map_err(|_| RecordRejection::Malformed)?;
+        validator.validate(suffix, &record)?;
+        Ok(record.data.into_bytes())
+    }
+}
+
+impl DhtRecordValidators {
+    /// Exit and relay info records, plus the network parameter and
+    /// aggregator registry records signed by one of `keys`
+    pub fn with_maintainer_keys(keys: Vec<PublicKey>) -> Self {
+        let mut validators = Self::empty();
+        validators.register(
+            EXIT_DHT_KEY_PREFIX,
+            EXIT_RECORD_TTL,
+            Box::new(NodeInfoValidator::<ExitInfo>(std::marker::PhantomData)),
+        );
+        validators.register(
+            RELAY_DHT_KEY_PREFIX,
+            RELAY_RECORD_TTL,
+            Box::new(NodeInfoValidator::<RelayInfo>(std::marker::PhantomData)),
+        );
+        validators.register(
+            std::str::from_utf8(NETWORK_PARAMS_KEY).unwrap_or_default(),
+            NETWORK_PARAMS_TTL,
+            Box::new(NetworkParamsValidator::new(keys.clone())),
+        );
+        validators.register(
+            std::str::from_utf8(AGGREGATOR_REGISTRY_KEY).unwrap_or_default(),
+            AGGREGATOR_REGISTRY_TTL,
+            Box::new(AggregatorRegistryValidator::new(keys)),
+        );
+        validators
+    }
+}
+
+impl Default for DhtRecordValidators {
+    /// Exit and relay info records, and network parameters signed by the
+    /// embedded maintainer keys
+    fn default() -> Self {
+        Self::with_maintainer_keys(maintainer_keys())
+    }
+}
+
+/// Strikes against peers that served or pushed invalid DHT records
+#[derive(Debug, Default)]
+pub struct RecordPenalties {
+    strikes: HashMap<PeerId, (u32, Instant)>,
+}
+
+impl RecordPenalties {
+    /// Record a strike. Returns true when the peer reached
+    /// `RECORD_STRIKE_LIMIT` and should be dropped.
+    pub fn strike(&mut self, peer: PeerId) -> bool {
+        let now = Instant::now();
+        let entry = self.strikes.entry(peer).or_insert((0, now));
+        if now.duration_since(entry.1) > RECORD_STRIKE_WINDOW {
+            *entry = (0, now);
+        }
+        entry.0 += 1;
+        if entry.0 >= RECORD_STRIKE_LIMIT {
+            self.strikes.remove(&peer);
+            return true;
+        }
+        false
+    }
+
+    /// Current strikes against a peer
+    pub fn strikes(&self, peer: &PeerId) -> u32 {
+        self.strikes.get(peer).map(|(n, _)| *n).unwrap_or(0)
+    }
+}
+
 // ============================================================================
 // Extension trait for CraftNet-specific gossipsub + DHT operations
 // ============================================================================
@@ -107,7 +528,9 @@ pub trait CraftNetExt {
     fn publish_exit_status(&mut self, data: Vec<u8>) -> Result<gossipsub::MessageId, gossipsub::PublishError>;
     fn subscribe_proofs(&mut self) -> Result<bool, gossipsub::SubscriptionError>;
     fn unsubscribe_proofs(&mut self) -> bool;
-    fn publish_proof(&mut self, data: Vec<u8>) -> Result<gossipsub::MessageId, gossipsub::PublishError>;
+    fn subscribe_proof_shard(&mut self, shard: u8) -> Result<bool, gossipsub::SubscriptionError>;
+    fn unsubscribe_proof_shard(&mut self, shard: u8) -> bool;
+    fn publish_proof(&mut self, pool: &PublicKey, data: Vec<u8>) -> Result<gossipsub::MessageId, gossipsub::PublishError>;
     fn subscribe_subscriptions(&mut self) -> Result<bool, gossipsub::SubscriptionError>;
     fn unsubscribe_subscriptions(&mut self) -> bool;
     fn publish_subscription(&mut self, data: Vec<u8>) -> Result<gossipsub::MessageId, gossipsub::PublishError>;
@@ -133,8 +556,16 @@ pub trait CraftNetExt {
     fn get_relay_providers(&mut self) -> kad::QueryId;
 
     // DHT: peer records
-    fn put_peer_record(&mut self, pubkey: &[u8; 32], peer_id: &PeerId) -> Result<kad::QueryId, kad::store::Error>;
+    fn put_peer_record(&mut self, binding: &PeerBinding) -> Result<kad::QueryId, kad::store::Error>;
     fn get_peer_record(&mut self, pubkey: &[u8; 32]) -> kad::QueryId;
+
+    // DHT: network parameters (maintainer-signed)
+    fn put_network_params(&mut self, record: &SignedDhtRecord) -> Result<kad::QueryId, kad::store::Error>;
+    fn get_network_params(&mut self) -> kad::QueryId;
+
+    // DHT: aggregator registry (maintainer-signed)
+    fn put_aggregator_registry(&mut self, record: &SignedDhtRecord) -> Result<kad::QueryId, kad::store::Error>;
+    fn get_aggregator_registry(&mut self) -> kad::QueryId;
 }
 
 impl CraftNetExt for CraftNetBehaviour {
@@ -154,8 +585,14 @@ impl CraftNetExt for CraftNetBehaviour {
     fn unsubscribe_proofs(&mut self) -> bool {
         self.unsubscribe_topic(PROOF_TOPIC)
     }
-    fn publish_proof(&mut self, data: Vec<u8>) -> Result<gossipsub::MessageId, gossipsub::PublishError> {
-        self.publish_to_topic(PROOF_TOPIC, data)
+    fn subscribe_proof_shard(&mut self, shard: u8) -> Result<bool, gossipsub::SubscriptionError> {
+        self.subscribe_topic(&proof_shard_topic(shard))
+    }
+    fn unsubscribe_proof_shard(&mut self, shard: u8) -> bool {
+        self.unsubscribe_topic(&proof_shard_topic(shard))
+    }
+    fn publish_proof(&mut self, pool: &PublicKey, data: Vec<u8>) -> Result<gossipsub::MessageId, gossipsub::PublishError> {
+        self.publish_to_topic(&proof_topic_for_pool(pool), data)
     }
     fn subscribe_subscriptions(&mut self) -> Result<bool, gossipsub::SubscriptionError> {
         self.subscribe_topic(SUBSCRIPTION_TOPIC)
@@ -186,6 +623,8 @@ impl CraftNetExt for CraftNetBehaviour {
     }
 
     // === DHT: exit ===
+    // Exit/relay record values must be `SignedDhtRecord` bytes; others'
+    // validators reject anything else.
     fn put_exit_record(&mut self, peer_id: &PeerId, record_value: Vec<u8>) -> Result<kad::QueryId, kad::store::Error> {
         let key = kad::RecordKey::new(&exit_dht_key(peer_id));
         let expires = std::time::Instant::now() + EXIT_RECORD_TTL;
@@ -244,13 +683,13 @@ impl CraftNetExt for CraftNetBehaviour {
     }
 
     // === DHT: peer records ===
-    fn put_peer_record(&mut self, pubkey: &[u8; 32], peer_id: &PeerId) -> Result<kad::QueryId, kad::store::Error> {
-        let key = kad::RecordKey::new(&peer_dht_key(pubkey));
+    fn put_peer_record(&mut self, binding: &PeerBinding) -> Result<kad::QueryId, kad::store::Error> {
+        let key = kad::RecordKey::new(&peer_dht_key(&binding.settlement_pubkey));
         let expires = std::time::Instant::now() + PEER_RECORD_TTL;
         let record = kad::Record {
             key,
-            value: peer_id.to_bytes(),
-            publisher: Some(*peer_id),
+            value: binding.to_bytes(),
+            publisher: PeerId::from_bytes(&binding.peer_id).ok(),
             expires: Some(expires),
         };
         self.kademlia.put_record(record, kad::Quorum::One)
@@ -259,6 +698,34 @@ impl CraftNetExt for CraftNetBehaviour {
         let key = kad::RecordKey::new(&peer_dht_key(pubkey));
         self.kademlia.get_record(key)
     }
+
+    // === DHT: network parameters ===
+    fn put_network_params(&mut self, record: &SignedDhtRecord) -> Result<kad::QueryId, kad::store::Error> {
+        let record = kad::Record {
+            key: kad::RecordKey::new(&NETWORK_PARAMS_KEY),
+            value: record.to_bytes(),
+            publisher: None,
+            expires: Some(std::time::Instant::now() + NETWORK_PARAMS_TTL),
+        };
+        self.kademlia.put_record(record, kad::Quorum::One)
+    }
+    fn get_network_params(&mut self) -> kad::QueryId {
+        self.kademlia.get_record(kad::RecordKey::new(&NETWORK_PARAMS_KEY))
+    }
+
+    // === DHT: aggregator registry ===
+    fn put_aggregator_registry(&mut self, record: &SignedDhtRecord) -> Result<kad::QueryId, kad::store::Error> {
+        let record = kad::Record {
+            key: kad::RecordKey::new(&AGGREGATOR_REGISTRY_KEY),
+            value: record.to_bytes(),
+            publisher: None,
+            expires: Some(std::time::Instant::now() + AGGREGATOR_REGISTRY_TTL),
+        };
+        self.kademlia.put_record(record, kad::Quorum::One)
+    }
+    fn get_aggregator_registry(&mut self) -> kad::QueryId {
+        self.kademlia.get_record(kad::RecordKey::new(&AGGREGATOR_REGISTRY_KEY))
+    }
 }
 
 #[cfg(test)]
@@ -274,4 +741,181 @@ mod tests {
     fn test_rendezvous_namespace() {
         assert_eq!(RENDEZVOUS_NAMESPACE, "craftnet");
     }
+
+    #[test]
+    fn test_registry_shard_keys() {
+        assert_eq!(exit_registry_shard_key(10), b"/craftnet/exit-registry/a".to_vec());
+        assert_eq!(relay_registry_shard_key(0), b"/craftnet/relay-registry/0".to_vec());
+        for shard in 0..REGISTRY_SHARDS {
+            assert_eq!(parse_registry_shard_key(&exit_registry_shard_key(shard)), Some(shard));
+            assert_eq!(parse_registry_shard_key(&relay_registry_shard_key(shard)), Some(shard));
+        }
+        assert_eq!(parse_registry_shard_key(EXIT_REGISTRY_KEY), None);
+        assert_eq!(parse_registry_shard_key(b"/craftnet/exit-registry/zz"), None);
+
+        let mut pubkey = [0u8; 32];
+        pubkey[0] = 0x35;
+        assert_eq!(registry_shard(&pubkey), 0x35 % REGISTRY_SHARDS);
+    }
+
+    #[test]
+    fn test_proof_shard_topics() {
+        assert_eq!(proof_shard_topic(10), "craftnet/proofs/1.0.0/a");
+        for shard in 0..PROOF_TOPIC_SHARDS {
+            assert_eq!(parse_proof_shard_topic(&proof_shard_topic(shard)), Some(shard));
+            assert!(is_proof_topic(&proof_shard_topic(shard)));
+        }
+        assert!(is_proof_topic(PROOF_TOPIC));
+        assert_eq!(parse_proof_shard_topic(PROOF_TOPIC), None);
+        assert_eq!(parse_proof_shard_topic("craftnet/proofs/1.0.0/zz"), None);
+        assert!(!is_proof_topic(SUBSCRIPTION_TOPIC));
+
+        let (mut a, mut b, mut c) = ([0u8; 32], [0u8; 32], [0u8; 32]);
+        a[0] = 0x35;
+        b[0] = 0x05;
+        c[0] = 0x12;
+        assert_eq!(proof_topic_for_pool(&a), proof_shard_topic(0x35 % PROOF_TOPIC_SHARDS));
+        assert_eq!(proof_topic_shards([&a, &b, &c]), vec![2, 5]);
+    }
+
+    fn relay_record(timestamp: u64) -> (Vec<u8>, SignedDhtRecord, SigningKeypair) {
+        relay_record_bound_at(timestamp, timestamp)
+    }
+
+    fn relay_record_bound_at(timestamp: u64, bound_at: u64) -> (Vec<u8>, SignedDhtRecord, SigningKeypair) {
+        let libp2p_keypair = libp2p::identity::Keypair::generate_ed25519();
+        let peer_id = PeerId::from(libp2p_keypair.public());
+        let settlement = SigningKeypair::generate();
+        let info = RelayInfo {
+            pubkey: settlement.public_key_bytes(),
+            address: "/ip4/127.0.0.1/tcp/9000".to_string(),
+            allows_last_hop: true,
+            reputation: 0,
+            encryption_pubkey: Some([7u8; 32]),
+            peer_binding: crate::sign_peer_binding_at(&libp2p_keypair, &settlement, bound_at),
+            capacity: None,
+            country_code: None,
+            as_number: None,
+        };
+        let key = relay_dht_key(&peer_id);
+        let record = SignedDhtRecord::sign_at(
+            &settlement,
+            &key,
+            serde_json::to_string(&info).unwrap(),
+            timestamp,
+        );
+        (key, record, settlement)
+    }
+
+    #[test]
+    fn test_signed_record_accepted() {
+        let validators = DhtRecordValidators::default();
+        let (key, record, _) = relay_record(1_000);
+        let body = validators.validate_at(&key, &record.to_bytes(), 1_010).unwrap();
+        let info: RelayInfo = serde_json::from_slice(&body).unwrap();
+        assert_eq!(Some(info.pubkey), record.pubkey_bytes());
+    }
+
+    #[test]
+    fn test_unsigned_or_tampered_records_rejected() {
+        let validators = DhtRecordValidators::default();
+        let (key, record, _) = relay_record(1_000);
+
+        // Legacy raw JSON
+        assert_eq!(
+            validators.validate_at(&key, record.data.as_bytes(), 1_000),
+            Err(RecordRejection::Malformed),
+        );
+
+        let mut tampered = record.clone();
+        tampered.data = tampered.data.replace("9000", "9001");
+        assert_eq!(
+            validators.validate_at(&key, &tampered.to_bytes(), 1_000),
+            Err(RecordRejection::BadSignature),
+        );
+
+        let mut future_version = record.clone();
+        future_version.version = DHT_RECORD_VERSION + 1;
+        assert_eq!(
+            validators.validate_at(&key, &future_version.to_bytes(), 1_000),
+            Err(RecordRejection::UnsupportedVersion(DHT_RECORD_VERSION + 1)),
+        );
+
+        // Replayed under another node's key
+        let other_key = relay_dht_key(&PeerId::random());
+        assert_eq!(
+            validators.validate_at(&other_key, &record.to_bytes(), 1_000),
+            Err(RecordRejection::BadSignature),
+        );
+    }
+
+    #[test]
+    fn test_record_freshness() {
+        let validators = DhtRecordValidators::default();
+        let (key, record, _) = relay_record(1_000);
+        let ttl = RELAY_RECORD_TTL.as_secs();
+
+        assert!(validators.validate_at(&key, &record.to_bytes(), 1_000 + ttl).is_ok());
+        assert!(matches!(
+            validators.validate_at(&key, &record.to_bytes(), 1_001 + ttl),
+            Err(RecordRejection::Expired { .. }),
+        ));
+        assert_eq!(
+            validators.validate_at(&key, &record.to_bytes(), 1_000 - DHT_RECORD_MAX_CLOCK_SKEW.as_secs() - 1),
+            Err(RecordRejection::FromFuture),
+        );
+    }
+
+    #[test]
+    fn test_record_signed_by_other_key_rejected() {
+        let validators = DhtRecordValidators::default();
+        let (key, record, _) = relay_record(1_000);
+
+        // Valid signature, but the signer isn't the node the body describes
+        let impostor = SigningKeypair::generate();
+        let forged = SignedDhtRecord::sign_at(&impostor, &key, record.data.clone(), 1_000);
+        assert!(matches!(
+            validators.validate_at(&key, &forged.to_bytes(), 1_000),
+            Err(RecordRejection::Schema(_)),
+        ));
+
+        // Body rewritten to the impostor's pubkey: binding no longer matches
+        let mut info: RelayInfo = serde_json::from_str(&record.data).unwrap();
+        info.pubkey = impostor.public_key_bytes();
+        let forged = SignedDhtRecord::sign_at(&impostor, &key, serde_json::to_string(&info).unwrap(), 1_000);
+        assert_eq!(
+            validators.validate_at(&key, &forged.to_bytes(), 1_000),
+            Err(RecordRejection::IdentityMismatch),
+        );
+    }
+
+    #[test]
+    fn test_stale_binding_rejected() {
+        let validators = DhtRecordValidators::default();
+        // A fresh record re-using a binding signed long before it
+        let (key, record, _) = relay_record_bound_at(100_000, 100_000 - PEER_BINDING_MAX_AGE.as_secs() - 1);
+        assert_eq!(
+            validators.validate_at(&key, &record.to_bytes(), 100_000),
+            Err(RecordRejection::IdentityMismatch),
+        );
+    }
+
+    #[test]
+    fn test_unvalidated_keys_pass_through() {
+        let validators = DhtRecordValidators::default();
+        let key = peer_dht_key(&[1u8; 32]);
+        assert!(!validators.covers(&key));
+        assert_eq!(validators.validate_at(&key, b"raw", 0).unwrap(), b"raw".to_vec());
+    }
+
+    #[test]
+    fn test_penalties_reach_limit() {
+        let mut penalties = RecordPenalties::default();
+        let peer = PeerId::random();
+        for _ in 1..RECORD_STRIKE_LIMIT {
+            assert!(!penalties.strike(peer));
+        }
+        assert!(penalties.strike(peer));
+        assert_eq!(penalties.strikes(&peer), 0);
+    }
 }
@@ -0,0 +1,452 @@
+//! Two-process shard stream benchmark
+//!
+//! Measures shard throughput and ack latency on `/craftnet/shard-stream`
+//! between two real swarms in separate processes, over TCP and QUIC on
+//! loopback. For every transport, frame size and ack strategy the client
+//! opens a fresh stream, sends shard frames and times the server's acks.
+//!
+//! Ack strategies:
+//! - `sync`: one shard in flight, the next goes once the previous is acked
+//! - `window`: up to `--window` shards in flight (how relays pipeline)
+//! - `none`: fire and forget; acks are only drained, never waited for
+//!
+//! Results go to stdout as JSON (and to `--out` if given), a summary to
+//! stderr. Usage (build with `--release`):
+//!
+//! ```text
+//! shard-stream-bench [run] [--transports tcp,quic] [CLIENT OPTIONS]
+//! shard-stream-bench server --transport tcp|quic
+//! shard-stream-bench client --connect <multiaddr/p2p/peer> [CLIENT OPTIONS]
+//!
+//! CLIENT OPTIONS: [--frame-sizes 1024,4096,10240] [--acks sync,window,none]
+//!                 [--window 64] [--shards 5000] [--out PATH]
+//! ```
+//!
+//! `run` (the default) starts a `server` child process per transport and
+//! runs the client against it.
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::{AsyncReadExt, AsyncWriteExt, StreamExt};
+use libp2p::identity::Keypair;
+use libp2p::multiaddr::Protocol;
+use libp2p::swarm::SwarmEvent;
+use serde::Serialize;
+use tokio::sync::Semaphore;
+
+use craftnet_core::Shard;
+use craftnet_network::{
+    build_swarm, encode_shard_frame, frame_pool, read_frame, write_ack_frame, write_shard_frame, Multiaddr,
+    NetworkConfig, PeerId, StreamFrame, PROTOCOL_VERSION, SHARD_STREAM_PROTOCOL,
+};
+
+/// Shards sent on a throwaway stream before measuring
+const WARMUP_SHARDS: usize = 200;
+
+/// How long the client waits for the server to come up
+const SERVER_START_TIMEOUT: Duration = Duration::from_secs(30);
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transport {
+    Tcp,
+    Quic,
+}
+
+impl Transport {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "tcp" => Some(Transport::Tcp),
+            "quic" => Some(Transport::Quic),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Transport::Tcp => "tcp",
+            Transport::Quic => "quic",
+        }
+    }
+
+    fn listen_addr(&self) -> Multiaddr {
+        match self {
+            Transport::Tcp => "/ip4/127.0.0.1/tcp/0".parse().unwrap(),
+            Transport::Quic => "/ip4/127.0.0.1/udp/0/quic-v1".parse().unwrap(),
+        }
+    }
+
+    fn of(addr: &Multiaddr) -> Option<Self> {
+        addr.iter().find_map(|p| match p {
+            Protocol::Tcp(_) => Some(Transport::Tcp),
+            Protocol::QuicV1 => Some(Transport::Quic),
+            _ => None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AckStrategy {
+    Sync,
+    Window,
+    None,
+}
+
+impl AckStrategy {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "sync" => Some(AckStrategy::Sync),
+            "window" => Some(AckStrategy::Window),
+            "none" => Some(AckStrategy::None),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            AckStrategy::Sync => "sync",
+            AckStrategy::Window => "window",
+            AckStrategy::None => "none",
+        }
+    }
+
+    /// Shards allowed in flight
+    fn in_flight(&self, window: usize, shards: usize) -> usize {
+        match self {
+            AckStrategy::Sync => 1,
+            AckStrategy::Window => window.max(1),
+            AckStrategy::None => shards.max(1),
+        }
+    }
+}
+
+struct ClientOptions {
+    frame_sizes: Vec<usize>,
+    acks: Vec<AckStrategy>,
+    window: usize,
+    shards: usize,
+    out: Option<String>,
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        Self {
+            frame_sizes: vec![1024, 4096, 10 * 1024],
+            acks: vec![AckStrategy::Sync, AckStrategy::Window, AckStrategy::None],
+            window: 64,
+            shards: 5_000,
+            out: None,
+        }
+    }
+}
+
+/// One measured (transport, frame size, ack strategy) combination
+#[derive(Debug, Serialize)]
+struct RunResult {
+    transport: &'static str,
+    frame_bytes: usize,
+    ack_strategy: &'static str,
+    in_flight: usize,
+    shards: usize,
+    secs: f64,
+    shards_per_sec: f64,
+    mbit_per_sec: f64,
+    latency_p50_us: u64,
+    latency_p90_us: u64,
+    latency_p99_us: u64,
+    latency_max_us: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    protocol: String,
+    protocol_version: u16,
+    results: Vec<RunResult>,
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: shard-stream-bench [run] [--transports tcp,quic] [CLIENT OPTIONS]\n\
+         \x20      shard-stream-bench server --transport tcp|quic\n\
+         \x20      shard-stream-bench client --connect <multiaddr/p2p/peer> [CLIENT OPTIONS]\n\
+         CLIENT OPTIONS: [--frame-sizes 1024,4096,10240] [--acks sync,window,none]\n\
+         \x20               [--window 64] [--shards 5000] [--out PATH]"
+    );
+    std::process::exit(2);
+}
+
+fn list<T>(raw: &str, parse: impl Fn(&str) -> Option<T>) -> Vec<T> {
+    raw.split(',').map(|s| parse(s.trim()).unwrap_or_else(|| usage())).collect()
+}
+
+/// A shard whose stream frame is `frame_bytes` long (or as close as the
+/// fixed header allows)
+fn shard_for_frame(frame_bytes: usize) -> Shard {
+    let base = Shard::new([7; 32], vec![1; 512], vec![], vec![3; 92], 3, 2);
+    let frame = encode_shard_frame(&base, 0).expect("empty shard encodes");
+    let overhead = frame.len();
+    frame_pool().give(frame);
+    Shard::new([7; 32], vec![1; 512], vec![2; frame_bytes.saturating_sub(overhead)], vec![3; 92], 3, 2)
+}
+
+fn quantile_us(sorted: &[Duration], q: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    sorted[((sorted.len() - 1) as f64 * q).round() as usize].as_micros() as u64
+}
+
+// ============================================================================
+// Server
+// ============================================================================
+
+/// Ack every shard on one inbound stream until it closes
+async fn ack_shards(mut stream: libp2p::Stream) {
+    loop {
+        match read_frame(&mut stream).await {
+            Ok(StreamFrame::Shard { seq_id, .. }) => {
+                if write_ack_frame(&mut stream, seq_id, None).await.is_err() {
+                    return;
+                }
+            }
+            Ok(_) => {}
+            Err(_) => return,
+        }
+    }
+}
+
+/// Listen on `transport`, print `listening <addr>` once bound, ack shards forever
+async fn serve(transport: Transport) -> Result<(), BoxError> {
+    let config = NetworkConfig {
+        listen_addrs: vec![transport.listen_addr()],
+        bootstrap_peers: vec![],
+        webrtc_cert_path: None,
+    };
+    let (mut swarm, peer_id, mut incoming) = build_swarm(Keypair::generate_ed25519(), config).await?;
+
+    tokio::spawn(async move {
+        while let Some((_peer, stream)) = incoming.next().await {
+            tokio::spawn(ack_shards(stream));
+        }
+    });
+
+    let mut announced = false;
+    loop {
+        if let SwarmEvent::NewListenAddr { address, .. } = swarm.select_next_some().await {
+            if !announced && Transport::of(&address) == Some(transport) {
+                println!("listening {}", address.with(Protocol::P2p(peer_id)));
+                std::io::stdout().flush()?;
+                announced = true;
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Client
+// ============================================================================
+
+/// Send `shards` shard frames on a fresh stream, at most `in_flight`
+/// unacked at a time. Returns the elapsed time and per-shard ack latency.
+async fn send_shards(
+    control: &mut libp2p_stream::Control,
+    peer: PeerId,
+    shard: &Shard,
+    shards: usize,
+    in_flight: usize,
+) -> Result<(Duration, Vec<Duration>), BoxError> {
+    let stream = control.open_stream(peer, SHARD_STREAM_PROTOCOL).await?;
+    let (mut reader, mut writer) = stream.split();
+    let permits = Arc::new(Semaphore::new(in_flight));
+    // Send time of each shard, in nanoseconds since `start`
+    let sent_at: Arc<Vec<AtomicU64>> = Arc::new((0..shards).map(|_| AtomicU64::new(0)).collect());
+    let start = Instant::now();
+
+    let acks = {
+        let permits = permits.clone();
+        let sent_at = sent_at.clone();
+        tokio::spawn(async move {
+            let mut latencies = Vec::with_capacity(shards);
+            while latencies.len() < shards {
+                match read_frame(&mut reader).await? {
+                    StreamFrame::Ack { seq_id, .. } => {
+                        let Some(sent) = sent_at.get(seq_id as usize) else { continue };
+                        let sent = Duration::from_nanos(sent.load(Ordering::Acquire));
+                        latencies.push(start.elapsed().saturating_sub(sent));
+                        permits.add_permits(1);
+                    }
+                    StreamFrame::Nack { reason, .. } => {
+                        return Err(std::io::Error::other(format!("shard nacked: {}", reason)));
+                    }
+                    _ => {}
+                }
+            }
+            Ok(latencies)
+        })
+    };
+
+    for seq_id in 0..shards {
+        permits.acquire().await?.forget();
+        sent_at[seq_id].store(start.elapsed().as_nanos() as u64, Ordering::Release);
+        write_shard_frame(&mut writer, shard, seq_id as u64).await?;
+    }
+    let latencies = acks.await??;
+    let elapsed = start.elapsed();
+    let _ = writer.close().await;
+    Ok((elapsed, latencies))
+}
+
+async fn run_client(addr: Multiaddr, options: &ClientOptions) -> Result<Vec<RunResult>, BoxError> {
+    let transport = Transport::of(&addr).ok_or("address is neither TCP nor QUIC")?;
+    let peer = match addr.iter().last() {
+        Some(Protocol::P2p(peer)) => peer,
+        _ => return Err("address must end with /p2p/<peer id>".into()),
+    };
+
+    let config = NetworkConfig { listen_addrs: vec![], bootstrap_peers: vec![], webrtc_cert_path: None };
+    let (mut swarm, _peer_id, _incoming) = build_swarm(Keypair::generate_ed25519(), config).await?;
+    swarm.dial(addr.clone())?;
+    tokio::time::timeout(SERVER_START_TIMEOUT, async {
+        loop {
+            match swarm.select_next_some().await {
+                SwarmEvent::ConnectionEstablished { peer_id, .. } if peer_id == peer => return Ok(()),
+                SwarmEvent::OutgoingConnectionError { peer_id: Some(peer_id), error, .. } if peer_id == peer => {
+                    return Err(error.to_string());
+                }
+                _ => {}
+            }
+        }
+    })
+    .await
+    .map_err(|_| "timed out connecting to the server")??;
+
+    let mut control = swarm.behaviour().stream_control();
+    tokio::spawn(async move {
+        loop {
+            swarm.select_next_some().await;
+        }
+    });
+
+    let mut results = Vec::new();
+    for &frame_bytes in &options.frame_sizes {
+        let shard = shard_for_frame(frame_bytes);
+        send_shards(&mut control, peer, &shard, WARMUP_SHARDS, 1).await?;
+        for &strategy in &options.acks {
+            let in_flight = strategy.in_flight(options.window, options.shards);
+            let (elapsed, mut latencies) = send_shards(&mut control, peer, &shard, options.shards, in_flight).await?;
+            latencies.sort();
+            let secs = elapsed.as_secs_f64().max(f64::EPSILON);
+            let result = RunResult {
+                transport: transport.name(),
+                frame_bytes,
+                ack_strategy: strategy.name(),
+                in_flight,
+                shards: options.shards,
+                secs,
+                shards_per_sec: options.shards as f64 / secs,
+                mbit_per_sec: (options.shards * frame_bytes) as f64 * 8.0 / secs / 1e6,
+                latency_p50_us: quantile_us(&latencies, 0.5),
+                latency_p90_us: quantile_us(&latencies, 0.9),
+                latency_p99_us: quantile_us(&latencies, 0.99),
+                latency_max_us: quantile_us(&latencies, 1.0),
+            };
+            eprintln!(
+                "{:<5} {:>6} B  {:<6} {:>9.0} shards/s {:>8.1} Mbit/s  p50 {:>6} us  p99 {:>6} us",
+                result.transport,
+                result.frame_bytes,
+                result.ack_strategy,
+                result.shards_per_sec,
+                result.mbit_per_sec,
+                result.latency_p50_us,
+                result.latency_p99_us,
+            );
+            results.push(result);
+        }
+    }
+    Ok(results)
+}
+
+/// Start a server child process on `transport`; returns it with its address
+fn spawn_server(transport: Transport) -> Result<(std::process::Child, Multiaddr), BoxError> {
+    let mut child = Command::new(std::env::current_exe()?)
+        .args(["server", "--transport", transport.name()])
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let stdout = child.stdout.take().ok_or("server stdout not captured")?;
+    let mut line = String::new();
+    BufReader::new(stdout).read_line(&mut line)?;
+    match line.trim().strip_prefix("listening ").map(str::parse::<Multiaddr>) {
+        Some(Ok(addr)) => Ok((child, addr)),
+        _ => {
+            let _ = child.kill();
+            Err(format!("server did not report its address (got {:?})", line.trim()).into())
+        }
+    }
+}
+
+fn emit(results: Vec<RunResult>, out: Option<&str>) -> Result<(), BoxError> {
+    let report = Report { protocol: SHARD_STREAM_PROTOCOL.to_string(), protocol_version: PROTOCOL_VERSION, results };
+    let json = serde_json::to_string_pretty(&report)?;
+    if let Some(path) = out {
+        std::fs::write(path, &json)?;
+    }
+    println!("{}", json);
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<(), BoxError> {
+    let mut args = std::env::args().skip(1).peekable();
+    let mode = match args.peek().map(String::as_str) {
+        Some("server") | Some("client") | Some("run") => args.next().unwrap(),
+        _ => "run".to_string(),
+    };
+
+    let mut options = ClientOptions::default();
+    let mut transports = vec![Transport::Tcp, Transport::Quic];
+    let mut connect: Option<Multiaddr> = None;
+    while let Some(flag) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage());
+        match flag.as_str() {
+            "--transport" | "--transports" => transports = list(&value(), Transport::parse),
+            "--connect" => connect = Some(value().parse().unwrap_or_else(|_| usage())),
+            "--frame-sizes" => options.frame_sizes = list(&value(), |s| s.parse().ok()),
+            "--acks" => options.acks = list(&value(), AckStrategy::parse),
+            "--window" => options.window = value().parse().unwrap_or_else(|_| usage()),
+            "--shards" => options.shards = value().parse().unwrap_or_else(|_| usage()),
+            "--out" => options.out = Some(value()),
+            _ => usage(),
+        }
+    }
+    if options.shards == 0 {
+        usage();
+    }
+
+    match mode.as_str() {
+        "server" => {
+            let &[transport] = transports.as_slice() else { usage() };
+            serve(transport).await
+        }
+        "client" => {
+            let Some(addr) = connect else { usage() };
+            let results = run_client(addr, &options).await?;
+            emit(results, options.out.as_deref())
+        }
+        _ => {
+            let mut results = Vec::new();
+            for transport in transports {
+                let (mut server, addr) = spawn_server(transport)?;
+                let run = run_client(addr, &options).await;
+                let _ = server.kill();
+                let _ = server.wait();
+                results.extend(run?);
+            }
+            emit(results, options.out.as_deref())
+        }
+    }
+}
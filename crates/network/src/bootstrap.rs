@@ -2,7 +2,19 @@
 //!
 //! Default bootstrap nodes for joining the CraftNet network.
 //! These are public nodes that act as entry points for peer discovery.
+//!
+//! Besides the static list, bootstrap nodes can be seeded from DNS: the
+//! `_dnsaddr.<domain>` TXT records of a seed domain carry
+//! `dnsaddr=<multiaddr>` entries, so the network can rotate its bootstrap
+//! infrastructure without shipping new binaries. DNS seeding is opt-in:
+//! whoever controls the seed domain chooses the peers new nodes join
+//! through, so only a domain the operator trusts should be configured
+//! (`NodeConfig::dns_seed`).
+
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
 
+use libp2p::multiaddr::Protocol;
 use libp2p::{Multiaddr, PeerId};
 
 /// Default bootstrap nodes for the CraftNet network
@@ -77,6 +89,224 @@ pub fn make_bootstrap_addr(ip: &str, port: u16, peer_id: &str) -> String {
     format!("/ip4/{}/tcp/{}/p2p/{}", ip, port, peer_id)
 }
 
+/// Default UDP port for WebRTC-direct listeners (browser clients)
+pub const DEFAULT_WEBRTC_PORT: u16 = 9001;
+
+/// WebRTC-direct bootstrap nodes for browser clients.
+///
+/// Browsers can't dial TCP, so they bootstrap from relays that also listen
+/// on WebRTC-direct. Each entry must carry the relay's `/certhash`, which
+/// stays stable as long as the relay keeps its certificate file.
+///
+/// Format: /ip4/<IP>/udp/<PORT>/webrtc-direct/certhash/<HASH>/p2p/<PEER_ID>
+pub const DEFAULT_BROWSER_BOOTSTRAP_NODES: &[&str] = &[];
+
+/// Whether `addr` is a WebRTC-direct address
+pub fn is_webrtc_addr(addr: &Multiaddr) -> bool {
+    addr.iter().any(|proto| matches!(proto, Protocol::WebRTCDirect))
+}
+
+/// Whether a browser can dial `addr` directly: WebRTC-direct with the
+/// certhash the browser needs to authenticate the self-signed certificate.
+pub fn is_browser_dialable(addr: &Multiaddr) -> bool {
+    is_webrtc_addr(addr) && addr.iter().any(|proto| matches!(proto, Protocol::Certhash(_)))
+}
+
+/// WebRTC-direct listen address on all interfaces
+pub fn webrtc_listen_addr(port: u16) -> Multiaddr {
+    Multiaddr::empty()
+        .with(Protocol::Ip4(std::net::Ipv4Addr::UNSPECIFIED))
+        .with(Protocol::Udp(port))
+        .with(Protocol::WebRTCDirect)
+}
+
+/// Parse bootstrap nodes a browser client can dial, skipping the rest
+pub fn browser_bootstrap_peers(addrs: &[&str]) -> Vec<(PeerId, Multiaddr)> {
+    parse_bootstrap_nodes(addrs)
+        .into_iter()
+        .filter(|(_, addr)| is_browser_dialable(addr))
+        .collect()
+}
+
+/// Create a WebRTC-direct bootstrap multiaddr string from components.
+///
+/// `certhash` is the multibase-encoded certificate hash as it appears in
+/// the relay's listen address.
+pub fn make_webrtc_bootstrap_addr(ip: &str, port: u16, certhash: &str, peer_id: &str) -> String {
+    format!("/ip4/{}/udp/{}/webrtc-direct/certhash/{}/p2p/{}", ip, port, certhash, peer_id)
+}
+
+/// Lower bound on how long resolved seed records are cached, so a zero
+/// TTL doesn't make every reconnect hit DNS
+pub const DNS_SEED_MIN_TTL: Duration = Duration::from_secs(60);
+
+/// Upper bound on how long resolved seed records are cached
+pub const DNS_SEED_MAX_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How deep `/dnsaddr/` entries pointing at other domains are followed
+const DNSADDR_MAX_DEPTH: usize = 2;
+
+/// Prefix of a dnsaddr TXT record value
+const DNSADDR_PREFIX: &str = "dnsaddr=";
+
+/// A dnsaddr TXT record value
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum DnsaddrEntry {
+    /// A dialable bootstrap node
+    Peer(PeerId, Multiaddr),
+    /// A `/dnsaddr/<domain>` entry to resolve further
+    Domain(String),
+}
+
+/// Parse one TXT record value of the form `dnsaddr=<multiaddr>`
+fn parse_dnsaddr_entry(txt: &str) -> Option<DnsaddrEntry> {
+    let addr_str = txt.trim().strip_prefix(DNSADDR_PREFIX)?;
+    let addr: Multiaddr = addr_str.parse().ok()?;
+    if let Some(Protocol::Dnsaddr(domain)) = addr.iter().next() {
+        return Some(DnsaddrEntry::Domain(domain.to_string()));
+    }
+    let (peer_id, dial_addr) = parse_bootstrap_addr(addr_str)?;
+    Some(DnsaddrEntry::Peer(peer_id, dial_addr))
+}
+
+/// Parse dialable bootstrap nodes out of dnsaddr TXT record values,
+/// skipping malformed entries and nested `/dnsaddr/` indirections
+pub fn parse_dnsaddr_records<S: AsRef<str>>(records: &[S]) -> Vec<(PeerId, Multiaddr)> {
+    records
+        .iter()
+        .filter_map(|txt| match parse_dnsaddr_entry(txt.as_ref())? {
+            DnsaddrEntry::Peer(peer_id, addr) => Some((peer_id, addr)),
+            DnsaddrEntry::Domain(_) => None,
+        })
+        .collect()
+}
+
+/// Resolve the bootstrap nodes published under a DNS seed domain.
+///
+/// Looks up the TXT records at `_dnsaddr.<domain>` and follows nested
+/// `/dnsaddr/` entries up to a small depth. Returns the peers and how long
+/// they may be cached (the lowest record TTL seen, clamped to
+/// [`DNS_SEED_MIN_TTL`]..=[`DNS_SEED_MAX_TTL`]).
+pub async fn resolve_dns_seed(domain: &str) -> Result<(Vec<(PeerId, Multiaddr)>, Duration), String> {
+    let resolver = hickory_resolver::Resolver::builder_tokio()
+        .map_err(|e| format!("DNS resolver: {}", e))?
+        .build();
+
+    let mut peers = Vec::new();
+    let mut ttl = DNS_SEED_MAX_TTL;
+    let mut visited = HashSet::new();
+    let mut pending = vec![(domain.trim_end_matches('.').to_string(), 0usize)];
+
+    while let Some((name, depth)) = pending.pop() {
+        if !visited.insert(name.clone()) {
+            continue;
+        }
+        let lookup = match resolver.txt_lookup(format!("_dnsaddr.{}.", name)).await {
+            Ok(lookup) => lookup,
+            // Only the seed domain itself failing is an error
+            Err(e) if depth == 0 => return Err(format!("TXT lookup for {}: {}", name, e)),
+            Err(e) => {
+                tracing::debug!("Skipping dnsaddr domain {}: {}", name, e);
+                continue;
+            }
+        };
+        ttl = ttl.min(lookup.valid_until().saturating_duration_since(Instant::now()));
+
+        for txt in lookup.iter() {
+            let value: String = txt
+                .txt_data()
+                .iter()
+                .map(|chunk| String::from_utf8_lossy(chunk))
+                .collect();
+            match parse_dnsaddr_entry(&value) {
+                Some(DnsaddrEntry::Peer(peer_id, addr)) => peers.push((peer_id, addr)),
+                Some(DnsaddrEntry::Domain(next)) if depth < DNSADDR_MAX_DEPTH => {
+                    pending.push((next, depth + 1));
+                }
+                Some(DnsaddrEntry::Domain(next)) => {
+                    tracing::debug!("dnsaddr nesting too deep, skipping {}", next);
+                }
+                None => tracing::debug!("Ignoring malformed dnsaddr record: {}", value),
+            }
+        }
+    }
+
+    Ok((peers, ttl.clamp(DNS_SEED_MIN_TTL, DNS_SEED_MAX_TTL)))
+}
+
+/// Merge bootstrap peer lists, keeping the first address seen for each
+/// (peer, address) pair in order
+pub fn merge_bootstrap_peers(
+    lists: impl IntoIterator<Item = Vec<(PeerId, Multiaddr)>>,
+) -> Vec<(PeerId, Multiaddr)> {
+    let mut seen = HashSet::new();
+    lists
+        .into_iter()
+        .flatten()
+        .filter(|entry| seen.insert(entry.clone()))
+        .collect()
+}
+
+/// Seed records resolved for one domain
+#[derive(Debug, Clone)]
+struct CachedSeed {
+    peers: Vec<(PeerId, Multiaddr)>,
+    expires_at: Instant,
+}
+
+/// Cache of DNS seed resolutions, honoring record TTLs.
+///
+/// Expired entries are kept so a node that can't reach DNS still has the
+/// last known bootstrap nodes to fall back on.
+#[derive(Debug, Clone, Default)]
+pub struct DnsSeedCache {
+    entries: HashMap<String, CachedSeed>,
+}
+
+impl DnsSeedCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Peers for `domain` if resolved and not yet expired
+    pub fn fresh(&self, domain: &str, now: Instant) -> Option<&[(PeerId, Multiaddr)]> {
+        self.entries
+            .get(domain)
+            .filter(|seed| now < seed.expires_at)
+            .map(|seed| seed.peers.as_slice())
+    }
+
+    /// Last peers resolved for `domain`, expired or not
+    pub fn last_known(&self, domain: &str) -> Option<&[(PeerId, Multiaddr)]> {
+        self.entries.get(domain).map(|seed| seed.peers.as_slice())
+    }
+
+    /// Record a resolution, clamping `ttl` to the cache bounds
+    pub fn insert(&mut self, domain: &str, peers: Vec<(PeerId, Multiaddr)>, ttl: Duration, now: Instant) {
+        let expires_at = now + ttl.clamp(DNS_SEED_MIN_TTL, DNS_SEED_MAX_TTL);
+        self.entries.insert(domain.to_string(), CachedSeed { peers, expires_at });
+    }
+
+    /// Resolve `domain` unless a fresh entry is cached. A failed lookup
+    /// falls back to the last known peers (empty if none).
+    pub async fn resolve(&mut self, domain: &str) -> Vec<(PeerId, Multiaddr)> {
+        if let Some(peers) = self.fresh(domain, Instant::now()) {
+            return peers.to_vec();
+        }
+        match resolve_dns_seed(domain).await {
+            Ok((peers, ttl)) => {
+                tracing::info!("Resolved {} bootstrap peers from DNS seed {}", peers.len(), domain);
+                self.insert(domain, peers.clone(), ttl, Instant::now());
+                peers
+            }
+            Err(e) => {
+                tracing::warn!("DNS seed {} failed: {}", domain, e);
+                self.last_known(domain).map(<[_]>::to_vec).unwrap_or_default()
+            }
+        }
+    }
+}
+
 /// Check if we have any bootstrap nodes configured
 pub fn has_bootstrap_nodes() -> bool {
     !DEFAULT_BOOTSTRAP_NODES.is_empty() &&
@@ -137,4 +367,81 @@ mod tests {
     fn test_has_bootstrap_nodes() {
         assert!(has_bootstrap_nodes());
     }
+
+    #[test]
+    fn test_parse_dnsaddr_records() {
+        let peer = "12D3KooWDpJ7As7BWAwRMfu1VU2WCqNjvq387JEYKDBj4kx6nXTN";
+        let records = vec![
+            format!("dnsaddr=/ip4/123.45.67.89/tcp/9000/p2p/{}", peer),
+            format!("dnsaddr=/dnsaddr/eu.bootstrap.example/p2p/{}", peer),
+            "dnsaddr=/ip4/123.45.67.89/tcp/9000".to_string(), // no peer ID
+            "v=spf1 -all".to_string(),
+        ];
+        let peers = parse_dnsaddr_records(&records);
+        assert_eq!(peers.len(), 1);
+        assert_eq!(peers[0].0.to_string(), peer);
+        assert_eq!(peers[0].1.to_string(), "/ip4/123.45.67.89/tcp/9000");
+
+        assert_eq!(
+            parse_dnsaddr_entry(&records[1]),
+            Some(DnsaddrEntry::Domain("eu.bootstrap.example".to_string())),
+        );
+    }
+
+    #[test]
+    fn test_merge_bootstrap_peers() {
+        let peer = "12D3KooWDpJ7As7BWAwRMfu1VU2WCqNjvq387JEYKDBj4kx6nXTN";
+        let a = parse_bootstrap_nodes(&[make_bootstrap_addr("1.1.1.1", 9000, peer).as_str()]);
+        let b = parse_bootstrap_nodes(&[
+            make_bootstrap_addr("1.1.1.1", 9000, peer).as_str(),
+            make_bootstrap_addr("2.2.2.2", 9000, peer).as_str(),
+        ]);
+        let merged = merge_bootstrap_peers([a, b]);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].1.to_string(), "/ip4/1.1.1.1/tcp/9000");
+    }
+
+    #[test]
+    fn test_dns_seed_cache_ttl() {
+        let peer = "12D3KooWDpJ7As7BWAwRMfu1VU2WCqNjvq387JEYKDBj4kx6nXTN";
+        let peers = parse_bootstrap_nodes(&[make_bootstrap_addr("1.1.1.1", 9000, peer).as_str()]);
+        let now = Instant::now();
+        let mut cache = DnsSeedCache::new();
+        assert!(cache.fresh("seed.example", now).is_none());
+
+        // A zero TTL is clamped up to the minimum
+        cache.insert("seed.example", peers.clone(), Duration::ZERO, now);
+        assert_eq!(cache.fresh("seed.example", now).map(|p| p.len()), Some(1));
+        assert!(cache.fresh("seed.example", now + DNS_SEED_MIN_TTL).is_none());
+        // Expired entries remain available as a fallback
+        assert_eq!(cache.last_known("seed.example").map(|p| p.len()), Some(1));
+
+        cache.insert("seed.example", peers, Duration::from_secs(365 * 24 * 3600), now);
+        assert!(cache.fresh("seed.example", now + DNS_SEED_MAX_TTL).is_none());
+    }
+
+    #[test]
+    fn test_webrtc_addrs() {
+        let listen = webrtc_listen_addr(DEFAULT_WEBRTC_PORT);
+        assert_eq!(listen.to_string(), "/ip4/0.0.0.0/udp/9001/webrtc-direct");
+        assert!(is_webrtc_addr(&listen));
+        // No certhash yet: the browser couldn't verify the certificate
+        assert!(!is_browser_dialable(&listen));
+
+        let tcp: Multiaddr = "/ip4/127.0.0.1/tcp/9000".parse().unwrap();
+        assert!(!is_webrtc_addr(&tcp));
+    }
+
+    #[test]
+    fn test_browser_bootstrap_peers() {
+        let peer = "12D3KooWDpJ7As7BWAwRMfu1VU2WCqNjvq387JEYKDBj4kx6nXTN";
+        let certhash = "uEiDDq4_xNyDorZBH3TlGazyJdOWSwvo4PUo5YHFMrvDE8g";
+        let webrtc = make_webrtc_bootstrap_addr("123.45.67.89", DEFAULT_WEBRTC_PORT, certhash, peer);
+        let tcp = make_bootstrap_addr("123.45.67.89", DEFAULT_PORT, peer);
+
+        let peers = browser_bootstrap_peers(&[webrtc.as_str(), tcp.as_str()]);
+        assert_eq!(peers.len(), 1);
+        assert!(is_browser_dialable(&peers[0].1));
+        assert_eq!(peers[0].0.to_string(), peer);
+    }
 }
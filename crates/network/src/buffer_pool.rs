@@ -0,0 +1,139 @@
+//! Reusable frame buffers for the shard stream.
+//!
+//! Every shard frame read or written needs a buffer of a few KB. Allocating
+//! a fresh `Vec` per frame (and copying between the shard encoding, the frame
+//! and the sealed frame) dominated allocations on busy relays. Frames are now
+//! built in and read into [`BytesMut`] buffers taken from a [`BufferPool`];
+//! the frozen [`Bytes`] are cheap to clone into retry buffers, and a buffer
+//! whose last handle is dropped back into the pool is reused by the next
+//! frame.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use bytes::{Bytes, BytesMut};
+
+/// Buffers kept by the shared frame pool
+const FRAME_POOL_BUFFERS: usize = 256;
+
+/// Buffers above this capacity are not kept (they were grown for a rare
+/// oversized frame and would pin memory)
+const MAX_POOLED_CAPACITY: usize = 128 * 1024;
+
+/// Initial capacity of a new buffer: fits a padded shard frame
+const DEFAULT_CAPACITY: usize = 16 * 1024;
+
+/// A bounded free list of [`BytesMut`] buffers
+pub struct BufferPool {
+    free: Mutex<Vec<BytesMut>>,
+    max_buffers: usize,
+    allocated: AtomicU64,
+    reused: AtomicU64,
+}
+
+/// Counters of a [`BufferPool`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BufferPoolStats {
+    /// Buffers created because the pool was empty
+    pub allocated: u64,
+    /// Buffers handed out from the pool
+    pub reused: u64,
+    /// Buffers currently in the pool
+    pub pooled: usize,
+}
+
+impl BufferPool {
+    /// Create a pool keeping at most `max_buffers` free buffers
+    pub fn new(max_buffers: usize) -> Self {
+        Self {
+            free: Mutex::new(Vec::with_capacity(max_buffers)),
+            max_buffers,
+            allocated: AtomicU64::new(0),
+            reused: AtomicU64::new(0),
+        }
+    }
+
+    /// An empty buffer with room for at least `capacity` bytes
+    pub fn take(&self, capacity: usize) -> BytesMut {
+        let pooled = self.free.lock().unwrap_or_else(|e| e.into_inner()).pop();
+        match pooled {
+            Some(mut buf) => {
+                self.reused.fetch_add(1, Ordering::Relaxed);
+                buf.reserve(capacity);
+                buf
+            }
+            None => {
+                self.allocated.fetch_add(1, Ordering::Relaxed);
+                BytesMut::with_capacity(capacity.max(DEFAULT_CAPACITY))
+            }
+        }
+    }
+
+    /// Return a buffer to the pool.
+    ///
+    /// Only reclaimed when `buf` is the last handle to its memory; shared
+    /// buffers are left to be freed by their other holders.
+    pub fn give(&self, buf: Bytes) {
+        if let Ok(buf) = buf.try_into_mut() {
+            self.give_mut(buf);
+        }
+    }
+
+    /// Return a mutable buffer to the pool
+    pub fn give_mut(&self, mut buf: BytesMut) {
+        if buf.capacity() > MAX_POOLED_CAPACITY {
+            return;
+        }
+        buf.clear();
+        let mut free = self.free.lock().unwrap_or_else(|e| e.into_inner());
+        if free.len() < self.max_buffers {
+            free.push(buf);
+        }
+    }
+
+    /// Allocation and reuse counters
+    pub fn stats(&self) -> BufferPoolStats {
+        BufferPoolStats {
+            allocated: self.allocated.load(Ordering::Relaxed),
+            reused: self.reused.load(Ordering::Relaxed),
+            pooled: self.free.lock().unwrap_or_else(|e| e.into_inner()).len(),
+        }
+    }
+}
+
+/// The pool shard frames are read into and encoded in
+pub fn frame_pool() -> &'static BufferPool {
+    static POOL: OnceLock<BufferPool> = OnceLock::new();
+    POOL.get_or_init(|| BufferPool::new(FRAME_POOL_BUFFERS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffer_pool_reuse() {
+        let pool = BufferPool::new(1);
+        let mut buf = pool.take(10);
+        buf.extend_from_slice(b"frame");
+        let frozen = buf.freeze();
+
+        // A shared buffer is not reclaimed
+        let shared = frozen.clone();
+        pool.give(frozen);
+        assert_eq!(pool.stats().pooled, 0);
+
+        pool.give(shared);
+        assert_eq!(pool.stats().pooled, 1);
+        let reused = pool.take(10);
+        assert!(reused.is_empty());
+        assert_eq!(pool.stats(), BufferPoolStats { allocated: 1, reused: 1, pooled: 0 });
+
+        // The pool stays bounded
+        pool.give_mut(reused);
+        pool.give_mut(BytesMut::with_capacity(10));
+        assert_eq!(pool.stats().pooled, 1);
+        pool.give_mut(BytesMut::with_capacity(MAX_POOLED_CAPACITY + 1));
+        assert_eq!(pool.stats().pooled, 1);
+    }
+}
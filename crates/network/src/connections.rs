@@ -0,0 +1,247 @@
+//! Peer connection table
+//!
+//! One row per connected peer: where the first connection goes, who dialed
+//! it, the transport carrying it, the protocols the peer announced over
+//! identify, and the shard bytes exchanged on its streams. The swarm driver
+//! fills in connection details, the stream manager counts bytes, and the
+//! daemon serves the rows through the `list_peers` IPC method.
+//!
+//! Nodes attached to a shared swarm only learn the peer ID of a connection;
+//! their rows have no address or direction.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use libp2p::core::ConnectedPoint;
+use libp2p::multiaddr::Protocol;
+use libp2p::{Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+
+/// Connection table shared by the swarm driver, stream manager and node
+pub type SharedConnectionTable = Arc<RwLock<ConnectionTable>>;
+
+/// Which side opened the connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionDirection {
+    /// The peer dialed us
+    Inbound,
+    /// We dialed the peer
+    Outbound,
+}
+
+/// Transport a connection runs over
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionTransport {
+    Tcp,
+    Quic,
+    /// Circuit through a relay (`/p2p-circuit`)
+    Relay,
+    WebRtc,
+    Unknown,
+}
+
+impl ConnectionTransport {
+    /// Transport of a connection to/from `addr`
+    pub fn from_multiaddr(addr: &Multiaddr) -> Self {
+        let mut transport = Self::Unknown;
+        for protocol in addr.iter() {
+            match protocol {
+                // A circuit's relay leg may itself be TCP or QUIC
+                Protocol::P2pCircuit => return Self::Relay,
+                Protocol::Tcp(_) => transport = Self::Tcp,
+                Protocol::Quic | Protocol::QuicV1 => transport = Self::Quic,
+                Protocol::WebRTCDirect => transport = Self::WebRtc,
+                _ => {}
+            }
+        }
+        transport
+    }
+}
+
+/// A connected peer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerConnectionInfo {
+    pub peer_id: PeerId,
+    /// Remote address of the first connection (None on a shared swarm)
+    pub address: Option<Multiaddr>,
+    pub direction: Option<ConnectionDirection>,
+    pub transport: ConnectionTransport,
+    /// Protocols the peer announced over identify
+    pub protocols: Vec<String>,
+    /// Open connections to the peer
+    pub connections: u32,
+    /// Unix seconds the first connection was established
+    pub connected_at: u64,
+    /// Shard bytes written to the peer
+    pub bytes_sent: u64,
+    /// Shard bytes read from the peer
+    pub bytes_received: u64,
+}
+
+impl PeerConnectionInfo {
+    fn new(peer_id: PeerId, connected_at: u64) -> Self {
+        Self {
+            peer_id,
+            address: None,
+            direction: None,
+            transport: ConnectionTransport::Unknown,
+            protocols: Vec::new(),
+            connections: 0,
+            connected_at,
+            bytes_sent: 0,
+            bytes_received: 0,
+        }
+    }
+
+    /// Seconds since the first connection was established
+    pub fn age_secs(&self, now: u64) -> u64 {
+        now.saturating_sub(self.connected_at)
+    }
+}
+
+/// Connected peers by PeerId
+#[derive(Debug, Default)]
+pub struct ConnectionTable {
+    peers: HashMap<PeerId, PeerConnectionInfo>,
+}
+
+impl ConnectionTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A connection to `peer` was established at `endpoint`
+    pub fn connection_established(&mut self, peer: PeerId, endpoint: &ConnectedPoint, now: u64) {
+        let info = self.peers.entry(peer).or_insert_with(|| PeerConnectionInfo::new(peer, now));
+        info.connections += 1;
+        if info.address.is_none() {
+            let (address, direction) = match endpoint {
+                ConnectedPoint::Dialer { address, .. } => (address, ConnectionDirection::Outbound),
+                ConnectedPoint::Listener { send_back_addr, .. } => (send_back_addr, ConnectionDirection::Inbound),
+            };
+            // An inbound circuit shows up on our relayed listen address
+            info.transport = match endpoint {
+                ConnectedPoint::Listener { local_addr, .. } if local_addr.iter().any(|p| p == Protocol::P2pCircuit) => {
+                    ConnectionTransport::Relay
+                }
+                _ => ConnectionTransport::from_multiaddr(address),
+            };
+            info.address = Some(address.clone());
+            info.direction = Some(direction);
+        }
+    }
+
+    /// `peer` is connected, details unknown (keeps an existing row)
+    pub fn peer_connected(&mut self, peer: PeerId, now: u64) {
+        let info = self.peers.entry(peer).or_insert_with(|| PeerConnectionInfo::new(peer, now));
+        info.connections = info.connections.max(1);
+    }
+
+    /// A connection to `peer` closed, `remaining` are still open
+    pub fn connection_closed(&mut self, peer: &PeerId, remaining: u32) {
+        if remaining == 0 {
+            self.peers.remove(peer);
+        } else if let Some(info) = self.peers.get_mut(peer) {
+            info.connections = remaining;
+        }
+    }
+
+    /// Protocols `peer` announced over identify
+    pub fn set_protocols(&mut self, peer: &PeerId, protocols: Vec<String>) {
+        if let Some(info) = self.peers.get_mut(peer) {
+            info.protocols = protocols;
+        }
+    }
+
+    pub fn record_sent(&mut self, peer: &PeerId, bytes: u64) {
+        if let Some(info) = self.peers.get_mut(peer) {
+            info.bytes_sent += bytes;
+        }
+    }
+
+    pub fn record_received(&mut self, peer: &PeerId, bytes: u64) {
+        if let Some(info) = self.peers.get_mut(peer) {
+            info.bytes_received += bytes;
+        }
+    }
+
+    pub fn get(&self, peer: &PeerId) -> Option<&PeerConnectionInfo> {
+        self.peers.get(peer)
+    }
+
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+
+    /// All connected peers, longest connected first
+    pub fn peers(&self) -> Vec<PeerConnectionInfo> {
+        let mut peers: Vec<_> = self.peers.values().cloned().collect();
+        peers.sort_by_key(|p| (p.connected_at, p.peer_id.to_bytes()));
+        peers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p::core::transport::PortUse;
+    use libp2p::core::Endpoint;
+
+    fn dialer(addr: &str) -> ConnectedPoint {
+        ConnectedPoint::Dialer {
+            address: addr.parse().unwrap(),
+            role_override: Endpoint::Dialer,
+            port_use: PortUse::Reuse,
+        }
+    }
+
+    #[test]
+    fn test_transport_from_multiaddr() {
+        let transport = |addr: &str| ConnectionTransport::from_multiaddr(&addr.parse().unwrap());
+        assert_eq!(transport("/ip4/1.2.3.4/tcp/9000"), ConnectionTransport::Tcp);
+        assert_eq!(transport("/ip4/1.2.3.4/udp/9000/quic-v1"), ConnectionTransport::Quic);
+        assert_eq!(transport("/ip4/1.2.3.4/udp/9000/webrtc-direct"), ConnectionTransport::WebRtc);
+        assert_eq!(
+            transport("/ip4/1.2.3.4/tcp/9000/p2p/12D3KooWDpJ7As7BWAwRMfu1VU2WCqNjvq387JEYKDBj4kx6nXTN/p2p-circuit"),
+            ConnectionTransport::Relay,
+        );
+        assert_eq!(transport("/dns4/example.com"), ConnectionTransport::Unknown);
+    }
+
+    #[test]
+    fn test_table_tracks_connections_and_bytes() {
+        let mut table = ConnectionTable::new();
+        let peer = PeerId::random();
+        table.connection_established(peer, &dialer("/ip4/1.2.3.4/tcp/9000"), 100);
+        table.connection_established(peer, &dialer("/ip4/1.2.3.4/udp/9000/quic-v1"), 150);
+        table.set_protocols(&peer, vec!["/craftnet/shard/1.0.0".to_string()]);
+        table.record_sent(&peer, 1000);
+        table.record_received(&peer, 4000);
+
+        let info = table.get(&peer).unwrap();
+        assert_eq!(info.connections, 2);
+        // The first connection describes the peer
+        assert_eq!(info.transport, ConnectionTransport::Tcp);
+        assert_eq!(info.direction, Some(ConnectionDirection::Outbound));
+        assert_eq!(info.age_secs(160), 60);
+        assert_eq!((info.bytes_sent, info.bytes_received), (1000, 4000));
+
+        // A shared-swarm notification doesn't overwrite the row
+        table.peer_connected(peer, 200);
+        assert_eq!(table.get(&peer).unwrap().connected_at, 100);
+
+        table.connection_closed(&peer, 1);
+        assert_eq!(table.get(&peer).unwrap().connections, 1);
+        table.connection_closed(&peer, 0);
+        assert!(table.is_empty());
+        // Bytes for peers that are gone are ignored
+        table.record_sent(&peer, 1);
+        assert!(table.peers().is_empty());
+    }
+}
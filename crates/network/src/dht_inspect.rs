@@ -0,0 +1,302 @@
+//! Kademlia inspection for debugging discovery
+//!
+//! A [`DhtHandle`] sends [`DhtQuery`]s to the task driving the swarm, which
+//! answers them with [`serve_dht_query`]: a snapshot of both routing tables
+//! (bucket occupancy) and of the providers stored locally for the exit and
+//! relay registry keys, plus manual actions — add a peer address, start a
+//! bootstrap, look up one record. Record lookups finish asynchronously; the
+//! swarm driver hands their query progress to [`PendingDhtLookups::complete`].
+//!
+//! Only standalone swarms are inspectable: a shared swarm is driven by its
+//! owner, which doesn't serve these queries.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use libp2p::kad::{self, GetRecordOk, QueryId, QueryResult, RecordKey};
+use libp2p::{Multiaddr, PeerId, Swarm};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::behaviour::{
+    exit_registry_shard_key, relay_registry_shard_key, CraftNetBehaviour, DhtRecordValidators,
+    EXIT_REGISTRY_KEY, REGISTRY_SHARDS, RELAY_REGISTRY_KEY,
+};
+use crate::node::NetworkError;
+
+const REGISTRY_DISABLED: &str = "registry DHT not enabled";
+
+/// Which Kademlia instance a query targets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DhtTable {
+    /// `/craftnet/kad` — peer records, network parameters, aggregator registry
+    Main,
+    /// `/craftnet-reg/kad` — exit/relay records and registry providers
+    Registry,
+}
+
+impl FromStr for DhtTable {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "main" => Ok(Self::Main),
+            "registry" => Ok(Self::Registry),
+            other => Err(format!("Unknown DHT table '{}' (main, registry)", other)),
+        }
+    }
+}
+
+/// A routing table entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DhtBucketPeer {
+    pub peer_id: String,
+    pub addresses: Vec<String>,
+}
+
+/// One non-empty k-bucket
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DhtBucket {
+    /// log2 of the bucket's lower distance bound (0 = closest)
+    pub index: u32,
+    pub peers: Vec<DhtBucketPeer>,
+}
+
+/// Routing table of one Kademlia instance
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DhtTableSnapshot {
+    pub table: DhtTable,
+    pub peer_count: usize,
+    pub buckets: Vec<DhtBucket>,
+}
+
+/// Providers stored locally for one registry key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DhtKeyProviders {
+    pub key: String,
+    pub providers: Vec<String>,
+}
+
+/// Routing tables and registry providers
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DhtSnapshot {
+    pub tables: Vec<DhtTableSnapshot>,
+    /// Exit/relay registry keys (and their shards) with known providers
+    pub providers: Vec<DhtKeyProviders>,
+}
+
+/// Outcome of a record lookup
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DhtRecordLookup {
+    pub key: String,
+    pub found: bool,
+    /// Record value (lossy UTF-8; records are JSON)
+    #[serde(default)]
+    pub value: Option<String>,
+    #[serde(default)]
+    pub publisher: Option<String>,
+    /// Why the record validators would drop it (None = accepted)
+    #[serde(default)]
+    pub rejection: Option<String>,
+    /// Query error other than "not found"
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// Query served by the swarm driver
+#[derive(Debug)]
+pub enum DhtQuery {
+    Snapshot(oneshot::Sender<DhtSnapshot>),
+    /// Add a peer address to both routing tables
+    AddAddress {
+        peer: PeerId,
+        addr: Multiaddr,
+    },
+    Bootstrap {
+        table: DhtTable,
+        reply: oneshot::Sender<Result<(), NetworkError>>,
+    },
+    GetRecord {
+        table: DhtTable,
+        key: Vec<u8>,
+        reply: oneshot::Sender<DhtRecordLookup>,
+    },
+}
+
+/// Sends [`DhtQuery`]s to the swarm driver
+#[derive(Debug, Clone)]
+pub struct DhtHandle {
+    tx: mpsc::Sender<DhtQuery>,
+}
+
+impl DhtHandle {
+    /// Handle and the receiver the swarm driver serves
+    pub fn channel() -> (Self, mpsc::Receiver<DhtQuery>) {
+        let (tx, rx) = mpsc::channel(16);
+        (Self { tx }, rx)
+    }
+
+    pub async fn snapshot(&self) -> Result<DhtSnapshot, NetworkError> {
+        let (reply, rx) = oneshot::channel();
+        self.send(DhtQuery::Snapshot(reply)).await?;
+        rx.await.map_err(|_| NetworkError::ChannelClosed)
+    }
+
+    pub async fn add_address(&self, peer: PeerId, addr: Multiaddr) -> Result<(), NetworkError> {
+        self.send(DhtQuery::AddAddress { peer, addr }).await
+    }
+
+    pub async fn bootstrap(&self, table: DhtTable) -> Result<(), NetworkError> {
+        let (reply, rx) = oneshot::channel();
+        self.send(DhtQuery::Bootstrap { table, reply }).await?;
+        rx.await.map_err(|_| NetworkError::ChannelClosed)?
+    }
+
+    pub async fn get_record(&self, table: DhtTable, key: Vec<u8>) -> Result<DhtRecordLookup, NetworkError> {
+        let (reply, rx) = oneshot::channel();
+        self.send(DhtQuery::GetRecord { table, key, reply }).await?;
+        rx.await.map_err(|_| NetworkError::ChannelClosed)
+    }
+
+    async fn send(&self, query: DhtQuery) -> Result<(), NetworkError> {
+        self.tx.send(query).await.map_err(|_| NetworkError::ChannelClosed)
+    }
+}
+
+/// Record lookups started by [`serve_dht_query`], by table and query
+#[derive(Debug, Default)]
+pub struct PendingDhtLookups {
+    lookups: HashMap<(DhtTable, QueryId), (Vec<u8>, oneshot::Sender<DhtRecordLookup>)>,
+}
+
+impl PendingDhtLookups {
+    /// Whether query `id` of `table` is a lookup started here
+    pub fn contains(&self, table: DhtTable, id: QueryId) -> bool {
+        self.lookups.contains_key(&(table, id))
+    }
+
+    /// Answer the lookup with the first result of its query
+    pub fn complete(&mut self, table: DhtTable, id: QueryId, result: &QueryResult, validators: &DhtRecordValidators) {
+        let Some((key, reply)) = self.lookups.remove(&(table, id)) else {
+            return;
+        };
+        let mut lookup = DhtRecordLookup { key: String::from_utf8_lossy(&key).into_owned(), ..Default::default() };
+        match result {
+            QueryResult::GetRecord(Ok(GetRecordOk::FoundRecord(found))) => {
+                lookup.found = true;
+                lookup.value = Some(String::from_utf8_lossy(&found.record.value).into_owned());
+                lookup.publisher = found.record.publisher.or(found.peer).map(|p| p.to_string());
+                lookup.rejection = validators.validate(&key, &found.record.value).err().map(|e| e.to_string());
+            }
+            QueryResult::GetRecord(Ok(GetRecordOk::FinishedWithNoAdditionalRecord { .. }))
+            | QueryResult::GetRecord(Err(kad::GetRecordError::NotFound { .. })) => {}
+            QueryResult::GetRecord(Err(e)) => lookup.error = Some(e.to_string()),
+            _ => lookup.error = Some("unexpected query result".to_string()),
+        }
+        let _ = reply.send(lookup);
+    }
+}
+
+/// Answer `query` against `swarm`
+pub fn serve_dht_query(swarm: &mut Swarm<CraftNetBehaviour>, query: DhtQuery, pending: &mut PendingDhtLookups) {
+    match query {
+        DhtQuery::Snapshot(reply) => {
+            let _ = reply.send(snapshot(swarm));
+        }
+        DhtQuery::AddAddress { peer, addr } => {
+            swarm.behaviour_mut().add_address(&peer, addr);
+        }
+        DhtQuery::Bootstrap { table, reply } => {
+            let behaviour = swarm.behaviour_mut();
+            let result = match table {
+                DhtTable::Main => behaviour.kademlia.bootstrap().map(|_| ()).map_err(|_| NetworkError::BootstrapNoPeers),
+                DhtTable::Registry => match behaviour.kademlia_secondary.as_mut() {
+                    Some(k) => k.bootstrap().map(|_| ()).map_err(|_| NetworkError::BootstrapNoPeers),
+                    None => Err(NetworkError::Transport(REGISTRY_DISABLED.to_string())),
+                },
+            };
+            let _ = reply.send(result);
+        }
+        DhtQuery::GetRecord { table, key, reply } => {
+            let behaviour = swarm.behaviour_mut();
+            let record_key = RecordKey::new(&key);
+            let id = match table {
+                DhtTable::Main => Some(behaviour.kademlia.get_record(record_key)),
+                DhtTable::Registry => behaviour.kademlia_secondary.as_mut().map(|k| k.get_record(record_key)),
+            };
+            match id {
+                Some(id) => {
+                    pending.lookups.insert((table, id), (key, reply));
+                }
+                None => {
+                    let _ = reply.send(DhtRecordLookup {
+                        key: String::from_utf8_lossy(&key).into_owned(),
+                        error: Some(REGISTRY_DISABLED.to_string()),
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn snapshot(swarm: &mut Swarm<CraftNetBehaviour>) -> DhtSnapshot {
+    let behaviour = swarm.behaviour_mut();
+    let mut tables = vec![table_snapshot(DhtTable::Main, &mut behaviour.kademlia)];
+    let mut providers = Vec::new();
+    if let Some(registry) = behaviour.kademlia_secondary.as_mut() {
+        tables.push(table_snapshot(DhtTable::Registry, registry));
+        for key in registry_keys() {
+            let known: Vec<String> = kad::store::RecordStore::providers(registry.store_mut(), &RecordKey::new(&key))
+                .into_iter()
+                .map(|p| p.provider.to_string())
+                .collect();
+            if !known.is_empty() {
+                providers.push(DhtKeyProviders { key: String::from_utf8_lossy(&key).into_owned(), providers: known });
+            }
+        }
+    }
+    DhtSnapshot { tables, providers }
+}
+
+fn table_snapshot<S: kad::store::RecordStore + Send + 'static>(table: DhtTable, kademlia: &mut kad::Behaviour<S>) -> DhtTableSnapshot {
+    let mut buckets = Vec::new();
+    for bucket in kademlia.kbuckets() {
+        if bucket.num_entries() == 0 {
+            continue;
+        }
+        let peers = bucket
+            .iter()
+            .map(|entry| DhtBucketPeer {
+                peer_id: entry.node.key.preimage().to_string(),
+                addresses: entry.node.value.iter().map(|a| a.to_string()).collect(),
+            })
+            .collect();
+        buckets.push(DhtBucket { index: bucket.range().0.ilog2().unwrap_or(0), peers });
+    }
+    let peer_count = buckets.iter().map(|b| b.peers.len()).sum();
+    DhtTableSnapshot { table, peer_count, buckets }
+}
+
+/// Exit and relay registry keys, unsharded first
+fn registry_keys() -> Vec<Vec<u8>> {
+    let mut keys = vec![EXIT_REGISTRY_KEY.to_vec(), RELAY_REGISTRY_KEY.to_vec()];
+    keys.extend((0..REGISTRY_SHARDS).map(exit_registry_shard_key));
+    keys.extend((0..REGISTRY_SHARDS).map(relay_registry_shard_key));
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dht_table_names() {
+        assert_eq!("main".parse::<DhtTable>().unwrap(), DhtTable::Main);
+        assert_eq!("registry".parse::<DhtTable>().unwrap(), DhtTable::Registry);
+        assert!("secondary".parse::<DhtTable>().is_err());
+        assert_eq!(serde_json::to_string(&DhtTable::Registry).unwrap(), "\"registry\"");
+        assert_eq!(registry_keys().len(), 2 + 2 * REGISTRY_SHARDS as usize);
+    }
+}
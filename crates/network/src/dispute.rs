@@ -0,0 +1,269 @@
+//! Distribution dispute message types
+//!
+//! Before an aggregator proves and posts a pool's distribution it
+//! announces it on the `craftnet/disputes/1.0.0` gossipsub topic and waits
+//! out a challenge window. A relay whose bytes are under-counted answers
+//! with a signed [`DistributionChallenge`] carrying the count and chain
+//! root it can prove; the aggregator replies with a [`ChallengeResponse`]
+//! listing the exact proof chain its count is built from. Everything on the
+//! topic is public, so anyone can follow disputes and see which were never
+//! resolved.
+
+use serde::{Deserialize, Serialize};
+
+use crate::proof_message::PoolType;
+use crate::wire::{self, Extensions};
+
+/// Aggregator's signed summary of a distribution it is about to post
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DistributionAnnouncement {
+    /// Aggregator's signing pubkey
+    pub aggregator_pubkey: [u8; 32],
+    pub pool_pubkey: [u8; 32],
+    pub pool_type: PoolType,
+    /// Merkle root of the (relay, bytes) entries
+    pub distribution_root: [u8; 32],
+    pub total_bytes: u64,
+    /// (relay_pubkey, counted bytes), sorted by relay
+    pub entries: Vec<([u8; 32], u64)>,
+    /// Unix timestamp the distribution was built
+    pub built_at: u64,
+    /// Challenges after this unix timestamp are ignored
+    pub challenge_deadline: u64,
+    /// Aggregator's ed25519 signature over [`Self::signable_data`]
+    pub signature: Vec<u8>,
+}
+
+impl DistributionAnnouncement {
+    /// Data that gets signed (excludes signature field)
+    pub fn signable_data(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(1 + 32 + 32 + 1 + 32 + 8 + 8 + 8 + self.entries.len() * 40);
+        data.push(b'A');
+        data.extend_from_slice(&self.aggregator_pubkey);
+        data.extend_from_slice(&self.pool_pubkey);
+        data.push(pool_type_byte(self.pool_type));
+        data.extend_from_slice(&self.distribution_root);
+        data.extend_from_slice(&self.total_bytes.to_le_bytes());
+        data.extend_from_slice(&self.built_at.to_le_bytes());
+        data.extend_from_slice(&self.challenge_deadline.to_le_bytes());
+        for (relay, bytes) in &self.entries {
+            data.extend_from_slice(relay);
+            data.extend_from_slice(&bytes.to_le_bytes());
+        }
+        data
+    }
+
+    /// Bytes counted for `relay` (0 if it isn't in the distribution)
+    pub fn counted_for(&self, relay: &[u8; 32]) -> u64 {
+        self.entries.iter().find(|(r, _)| r == relay).map_or(0, |(_, bytes)| *bytes)
+    }
+}
+
+/// Relay's signed claim that a distribution under-counts its bytes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DistributionChallenge {
+    pub relay_pubkey: [u8; 32],
+    /// Aggregator whose announcement is challenged
+    pub aggregator_pubkey: [u8; 32],
+    pub pool_pubkey: [u8; 32],
+    pub pool_type: PoolType,
+    /// Root of the challenged distribution
+    pub distribution_root: [u8; 32],
+    /// Bytes the distribution counts for the relay
+    pub counted_bytes: u64,
+    /// Cumulative bytes the relay's proof chain reaches
+    pub claimed_bytes: u64,
+    /// Root at the end of the relay's proof chain
+    pub claimed_root: [u8; 32],
+    pub timestamp: u64,
+    /// Relay's ed25519 signature over [`Self::signable_data`]
+    pub signature: Vec<u8>,
+}
+
+impl DistributionChallenge {
+    /// Data that gets signed (excludes signature field)
+    pub fn signable_data(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(1 + 32 * 5 + 1 + 8 * 3);
+        data.push(b'C');
+        data.extend_from_slice(&self.relay_pubkey);
+        data.extend_from_slice(&self.aggregator_pubkey);
+        data.extend_from_slice(&self.pool_pubkey);
+        data.push(pool_type_byte(self.pool_type));
+        data.extend_from_slice(&self.distribution_root);
+        data.extend_from_slice(&self.counted_bytes.to_le_bytes());
+        data.extend_from_slice(&self.claimed_bytes.to_le_bytes());
+        data.extend_from_slice(&self.claimed_root);
+        data.extend_from_slice(&self.timestamp.to_le_bytes());
+        data
+    }
+}
+
+/// One accepted proof of a relay's chain, as the aggregator recorded it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofChainLink {
+    pub prev_root: [u8; 32],
+    pub new_root: [u8; 32],
+    pub batch_bytes: u64,
+    pub cumulative_bytes: u64,
+    /// Timestamp of the relay's proof
+    pub timestamp: u64,
+}
+
+/// Aggregator's signed answer to a challenge: the proof chain its count
+/// for the relay is built from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChallengeResponse {
+    pub aggregator_pubkey: [u8; 32],
+    pub relay_pubkey: [u8; 32],
+    pub pool_pubkey: [u8; 32],
+    pub pool_type: PoolType,
+    pub distribution_root: [u8; 32],
+    /// Bytes the aggregator counts for the relay
+    pub counted_bytes: u64,
+    /// Accepted proofs, oldest first
+    pub chain: Vec<ProofChainLink>,
+    pub timestamp: u64,
+    /// Aggregator's ed25519 signature over [`Self::signable_data`]
+    pub signature: Vec<u8>,
+}
+
+impl ChallengeResponse {
+    /// Data that gets signed (excludes signature field)
+    pub fn signable_data(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(1 + 32 * 4 + 1 + 16 + self.chain.len() * 88);
+        data.push(b'R');
+        data.extend_from_slice(&self.aggregator_pubkey);
+        data.extend_from_slice(&self.relay_pubkey);
+        data.extend_from_slice(&self.pool_pubkey);
+        data.push(pool_type_byte(self.pool_type));
+        data.extend_from_slice(&self.distribution_root);
+        data.extend_from_slice(&self.counted_bytes.to_le_bytes());
+        data.extend_from_slice(&self.timestamp.to_le_bytes());
+        for link in &self.chain {
+            data.extend_from_slice(&link.prev_root);
+            data.extend_from_slice(&link.new_root);
+            data.extend_from_slice(&link.batch_bytes.to_le_bytes());
+            data.extend_from_slice(&link.cumulative_bytes.to_le_bytes());
+            data.extend_from_slice(&link.timestamp.to_le_bytes());
+        }
+        data
+    }
+
+    /// Whether the chain links up from the zero root and ends at
+    /// `counted_bytes`
+    pub fn is_consistent(&self) -> bool {
+        let mut root = [0u8; 32];
+        let mut cumulative = 0u64;
+        for link in &self.chain {
+            if link.prev_root != root || link.cumulative_bytes != cumulative.saturating_add(link.batch_bytes) {
+                return false;
+            }
+            root = link.new_root;
+            cumulative = link.cumulative_bytes;
+        }
+        cumulative == self.counted_bytes
+    }
+
+    /// Whether the chain reaches the root and count the challenge claims
+    /// (the aggregator has every proof the relay says it published)
+    pub fn backs_claim(&self, challenge: &DistributionChallenge) -> bool {
+        self.is_consistent()
+            && self.chain.last().is_some_and(|link| {
+                link.new_root == challenge.claimed_root && link.cumulative_bytes == challenge.claimed_bytes
+            })
+    }
+}
+
+/// Everything published on the dispute topic
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DisputeMessage {
+    Announcement(DistributionAnnouncement),
+    Challenge(DistributionChallenge),
+    Response(ChallengeResponse),
+}
+
+impl DisputeMessage {
+    /// Serialize to bytes (bincode body + [`Extensions`] trailer)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        wire::encode(self, &Extensions::new())
+    }
+
+    /// Deserialize from bytes, ignoring extension fields
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, bincode::Error> {
+        wire::decode(bytes).map(|(msg, _)| msg)
+    }
+}
+
+fn pool_type_byte(pool_type: PoolType) -> u8 {
+    match pool_type {
+        PoolType::Subscribed => 0,
+        PoolType::Free => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(prev: u8, new: u8, batch: u64, cumulative: u64) -> ProofChainLink {
+        ProofChainLink { prev_root: [prev; 32], new_root: [new; 32], batch_bytes: batch, cumulative_bytes: cumulative, timestamp: 0 }
+    }
+
+    fn response(chain: Vec<ProofChainLink>, counted_bytes: u64) -> ChallengeResponse {
+        ChallengeResponse {
+            aggregator_pubkey: [1; 32],
+            relay_pubkey: [2; 32],
+            pool_pubkey: [3; 32],
+            pool_type: PoolType::Subscribed,
+            distribution_root: [4; 32],
+            counted_bytes,
+            chain,
+            timestamp: 0,
+            signature: vec![],
+        }
+    }
+
+    fn challenge(claimed_root: u8, claimed_bytes: u64) -> DistributionChallenge {
+        DistributionChallenge {
+            relay_pubkey: [2; 32],
+            aggregator_pubkey: [1; 32],
+            pool_pubkey: [3; 32],
+            pool_type: PoolType::Subscribed,
+            distribution_root: [4; 32],
+            counted_bytes: 100,
+            claimed_bytes,
+            claimed_root: [claimed_root; 32],
+            timestamp: 0,
+            signature: vec![],
+        }
+    }
+
+    #[test]
+    fn test_response_backs_claim() {
+        let full = response(vec![link(0, 1, 100, 100), link(1, 2, 50, 150)], 150);
+        assert!(full.is_consistent());
+        assert!(full.backs_claim(&challenge(2, 150)));
+
+        // Aggregator is missing the relay's last proof
+        let short = response(vec![link(0, 1, 100, 100)], 100);
+        assert!(short.is_consistent());
+        assert!(!short.backs_claim(&challenge(2, 150)));
+
+        // Broken chain or wrong total
+        assert!(!response(vec![link(0, 1, 100, 100), link(7, 2, 50, 150)], 150).is_consistent());
+        assert!(!response(vec![link(0, 1, 100, 100)], 150).is_consistent());
+    }
+
+    #[test]
+    fn test_dispute_message_roundtrip() {
+        let msg = DisputeMessage::Challenge(challenge(2, 150));
+        let decoded = DisputeMessage::from_bytes(&msg.to_bytes()).unwrap();
+        match decoded {
+            DisputeMessage::Challenge(c) => {
+                assert_eq!(c.claimed_bytes, 150);
+                assert_eq!(c.signable_data(), challenge(2, 150).signable_data());
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,262 @@
+//! Gossip dedup cache that survives restarts
+//!
+//! Gossipsub only remembers the messages it saw since the process started,
+//! so a restarted node handles recently gossiped proofs and heartbeats a
+//! second time when peers forward them again. [`GossipDedup`] remembers
+//! message ids (SHA-256 of the message data) in two bloom filter
+//! generations, each covering half of the time horizon: a message is
+//! remembered for between half the horizon and the whole of it. The node
+//! saves the filters on its maintenance tick (when dirty) and on shutdown,
+//! so a crash loses at most one save interval, and loads them at startup;
+//! generations older than the horizon are dropped on load.
+//!
+//! Ids are content addresses, so a message seen on two topics (a proof on
+//! the legacy and the sharded proof topic) is handled once. A false
+//! positive drops a message that was never seen, at roughly the filter's
+//! rate of [`GOSSIP_DEDUP_FP_RATE`].
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{debug, warn};
+
+use craftnet_core::{system_clock, SharedClock};
+
+use crate::registry::BloomFilter;
+
+/// How long a handled message is remembered (at least half of it)
+pub const GOSSIP_DEDUP_HORIZON: Duration = Duration::from_secs(600);
+
+/// Messages one generation holds before it is rotated early
+pub const GOSSIP_DEDUP_CAPACITY: usize = 100_000;
+
+/// Target false positive rate of a full generation
+pub const GOSSIP_DEDUP_FP_RATE: f64 = 1e-4;
+
+/// Id of a gossip message: SHA-256 of its data
+pub fn gossip_message_id(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Generation {
+    /// Unix seconds the generation started
+    started_at: u64,
+    items: usize,
+    filter: BloomFilter,
+}
+
+impl Generation {
+    fn new(started_at: u64) -> Self {
+        Self {
+            started_at,
+            items: 0,
+            filter: BloomFilter::with_capacity(GOSSIP_DEDUP_CAPACITY, GOSSIP_DEDUP_FP_RATE),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct GossipDedupFile {
+    horizon_secs: u64,
+    generations: Vec<Generation>,
+}
+
+/// Message ids handled within the horizon, newest generation first
+pub struct GossipDedup {
+    horizon: Duration,
+    current: Option<Generation>,
+    previous: Option<Generation>,
+    path: Option<PathBuf>,
+    dirty: bool,
+    clock: SharedClock,
+}
+
+impl std::fmt::Debug for GossipDedup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GossipDedup")
+            .field("horizon", &self.horizon)
+            .field("path", &self.path)
+            .field("dirty", &self.dirty)
+            .finish_non_exhaustive()
+    }
+}
+
+impl GossipDedup {
+    /// Empty cache saved to `path` (None = memory only)
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Self {
+            horizon: GOSSIP_DEDUP_HORIZON,
+            current: None,
+            previous: None,
+            path,
+            dirty: false,
+            clock: system_clock(),
+        }
+    }
+
+    /// Load the cache from `path` (if it exists), dropping generations
+    /// older than the horizon
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let mut cache = Self::new(path);
+        let Some(ref path) = cache.path else { return cache };
+        if !path.exists() {
+            return cache;
+        }
+        let file: GossipDedupFile = match std::fs::read(path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| bincode::deserialize(&data).map_err(|e| e.to_string()))
+        {
+            Ok(file) => file,
+            Err(e) => {
+                warn!("Failed to load gossip dedup cache from {}: {}", path.display(), e);
+                return cache;
+            }
+        };
+        // A horizon change invalidates the generation boundaries
+        if file.horizon_secs != cache.horizon.as_secs() {
+            return cache;
+        }
+        let now = cache.clock.unix_now();
+        let mut generations = file.generations.into_iter().filter(|g| cache.is_live(g, now));
+        cache.current = generations.next();
+        cache.previous = generations.next();
+        debug!(
+            "Loaded gossip dedup cache ({} messages)",
+            cache.current.iter().chain(&cache.previous).map(|g| g.items).sum::<usize>(),
+        );
+        cache
+    }
+
+    /// Read time from `clock` instead of the system clock
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+    }
+
+    /// Whether a generation started at `started_at` still has messages
+    /// within the horizon
+    fn is_live(&self, generation: &Generation, now: u64) -> bool {
+        generation.started_at <= now && now - generation.started_at < self.horizon.as_secs()
+    }
+
+    /// Whether `data` is new (not handled within the horizon); records it
+    pub fn first_seen(&mut self, data: &[u8]) -> bool {
+        self.first_seen_at(data, self.clock.unix_now())
+    }
+
+    /// [`Self::first_seen`] at unix time `now`
+    pub fn first_seen_at(&mut self, data: &[u8], now: u64) -> bool {
+        let id = gossip_message_id(data);
+        self.rotate(now);
+        if self.current.iter().chain(&self.previous).any(|g| g.filter.contains(&id)) {
+            return false;
+        }
+        let current = self.current.get_or_insert_with(|| Generation::new(now));
+        current.filter.insert(&id);
+        current.items += 1;
+        self.dirty = true;
+        true
+    }
+
+    /// Messages recorded since the last save
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Start a new generation once the current one spans half the horizon
+    /// or is full, and drop generations past the horizon
+    fn rotate(&mut self, now: u64) {
+        if self.previous.as_ref().is_some_and(|g| !self.is_live(g, now)) {
+            self.previous = None;
+        }
+        let half = (self.horizon.as_secs() / 2).max(1);
+        let due = self.current.as_ref().is_some_and(|g| {
+            now.saturating_sub(g.started_at) >= half || g.items >= GOSSIP_DEDUP_CAPACITY
+        });
+        if due {
+            self.previous = self.current.take().filter(|g| self.is_live(g, now));
+        }
+    }
+
+    /// Persist the cache (write to tmp, then rename)
+    pub fn save(&mut self) {
+        let Some(ref path) = self.path else { return };
+        let file = GossipDedupFile {
+            horizon_secs: self.horizon.as_secs(),
+            generations: self.current.iter().chain(&self.previous).cloned().collect(),
+        };
+        match bincode::serialize(&file) {
+            Ok(data) => {
+                if save_to(path, &data) {
+                    self.dirty = false;
+                }
+            }
+            Err(e) => warn!("Failed to serialize gossip dedup cache: {}", e),
+        }
+    }
+}
+
+fn save_to(path: &Path, data: &[u8]) -> bool {
+    let tmp_path = path.with_extension("bin.tmp");
+    if let Err(e) = std::fs::write(&tmp_path, data) {
+        warn!("Failed to write gossip dedup cache: {}", e);
+        return false;
+    }
+    if let Err(e) = std::fs::rename(&tmp_path, path) {
+        warn!("Failed to rename gossip dedup cache file: {}", e);
+        return false;
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_remembers_within_horizon() {
+        let mut dedup = GossipDedup::new(None);
+        let start = 1_000_000;
+        assert!(dedup.first_seen_at(b"proof", start));
+        assert!(!dedup.first_seen_at(b"proof", start + 1));
+        assert!(dedup.first_seen_at(b"heartbeat", start + 1));
+
+        // Rotated into the previous generation, still remembered
+        let half = GOSSIP_DEDUP_HORIZON.as_secs() / 2;
+        assert!(!dedup.first_seen_at(b"proof", start + half + 1));
+        // Gone once its generation is past the horizon
+        assert!(dedup.first_seen_at(b"proof", start + 2 * GOSSIP_DEDUP_HORIZON.as_secs()));
+    }
+
+    #[test]
+    fn test_survives_restart() {
+        let dir = std::env::temp_dir().join(format!("craftnet-gossip-dedup-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("gossip-dedup.bin");
+        // `load` ages generations by the system clock
+        let clock = Arc::new(craftnet_core::SimClock::new(system_clock().unix_now()));
+
+        let mut dedup = GossipDedup::new(Some(path.clone()));
+        dedup.set_clock(clock.clone());
+        assert!(!dedup.is_dirty());
+        assert!(dedup.first_seen(b"proof"));
+        assert!(dedup.is_dirty());
+        dedup.save();
+        assert!(!dedup.is_dirty());
+
+        let mut loaded = GossipDedup::load(Some(path.clone()));
+        loaded.set_clock(clock.clone());
+        clock.advance(Duration::from_secs(60));
+        assert!(!loaded.first_seen(b"proof"));
+        assert!(loaded.first_seen(b"other"));
+
+        // Past the horizon the saved generation is gone
+        clock.advance(GOSSIP_DEDUP_HORIZON);
+        assert!(loaded.first_seen(b"proof"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -7,19 +7,49 @@
 //! - Peer discovery via Kademlia DHT
 //! - Local discovery via mDNS
 //! - Decentralized discovery via rendezvous protocol
+//! - DNS-seeded bootstrap nodes (`dnsaddr` TXT records)
 //! - NAT traversal (relay, DCUtR)
 //! - Secure transport (Noise protocol)
+//! - Sharded exit/relay registries with peer-to-peer delta sync
+//! - Proof gossip sharded by pool pubkey prefix
+//! - Direct relay-to-aggregator proof submission when gossip can't deliver
+//! - Proof chain backfill for aggregators that missed proofs
+//! - Maintainer-signed network parameter beacon (bootstrap list, minimum
+//!   protocol version, emergency notices)
+//! - Maintainer-signed registry of aggregators approved per epoch
+//! - Distribution dispute messages (announcement, challenge, response)
+//! - WebRTC-direct listeners for browser clients (`webrtc` feature)
 //! - Shard routing and delivery
+//! - Per-peer connection table (address, direction, transport, protocols, bytes)
+//! - Kademlia inspection (bucket occupancy, registry providers, manual lookups)
+//! - Per-peer traffic accounting by protocol (shard stream, gossip, DHT)
 
+mod aggregators;
 mod behaviour;
 mod bootstrap;
+mod buffer_pool;
+mod connections;
+mod dht_inspect;
+mod dispute;
+mod gossip_dedup;
 mod node;
+mod onion_key;
+mod params;
+mod peer_binding;
+mod proof_backfill;
 mod proof_message;
+mod proof_submit;
 mod protocol;
+mod registry;
 mod relay_status;
 mod status;
+mod status_auth;
 pub mod stream_manager;
 mod subscription;
+mod traffic;
+mod wire;
+#[cfg(feature = "webrtc")]
+pub mod webrtc;
 
 pub use behaviour::{
     CraftNetBehaviour, CraftNetBehaviourEvent, CraftNetExt,
@@ -28,26 +58,87 @@ pub use behaviour::{
     PEER_DHT_KEY_PREFIX, PEER_RECORD_TTL, peer_dht_key,
     EXIT_STATUS_TOPIC, EXIT_HEARTBEAT_INTERVAL, EXIT_OFFLINE_THRESHOLD,
     PROOF_TOPIC, SUBSCRIPTION_TOPIC,
+    PROOF_TOPIC_SHARDS, proof_topic_shard, proof_shard_topic, proof_topic_for_pool, proof_topic_shards,
+    parse_proof_shard_topic, is_proof_topic,
     RELAY_DHT_KEY_PREFIX, RELAY_REGISTRY_KEY, RELAY_RECORD_TTL,
     RELAY_STATUS_TOPIC, RELAY_HEARTBEAT_INTERVAL, RELAY_OFFLINE_THRESHOLD,
     relay_dht_key,
-    AGGREGATOR_SYNC_TOPIC,
+    REGISTRY_SHARDS, registry_shard, exit_registry_shard_key, relay_registry_shard_key,
+    parse_registry_shard_key,
+    AGGREGATOR_SYNC_TOPIC, DISPUTE_TOPIC,
+    SignedDhtRecord, DhtRecordValidator, DhtRecordValidators, RecordRejection, RecordPenalties,
+    DHT_RECORD_VERSION, DHT_RECORD_MAX_CLOCK_SKEW, RECORD_STRIKE_LIMIT, RECORD_STRIKE_WINDOW,
+};
+pub use aggregators::{
+    AggregatorRegistry, AggregatorRegistryValidator, RegisteredAggregator, aggregator_epoch,
+    AGGREGATOR_REGISTRY_KEY, AGGREGATOR_REGISTRY_TTL, AGGREGATOR_EPOCH_SECS,
+};
+pub use connections::{
+    ConnectionDirection, ConnectionTable, ConnectionTransport, PeerConnectionInfo, SharedConnectionTable,
+};
+pub use dht_inspect::{
+    serve_dht_query, DhtBucket, DhtBucketPeer, DhtHandle, DhtKeyProviders, DhtQuery, DhtRecordLookup, DhtSnapshot,
+    DhtTable, DhtTableSnapshot, PendingDhtLookups,
+};
+pub use dispute::{
+    ChallengeResponse, DisputeMessage, DistributionAnnouncement, DistributionChallenge, ProofChainLink,
+};
+pub use params::{
+    NetworkParameters, NetworkNotice, NoticeSeverity, NetworkParamsValidator, maintainer_keys,
+    NETWORK_PARAMS_KEY, NETWORK_PARAMS_TTL, NETWORK_PARAMS_REFRESH_INTERVAL,
+};
+pub use gossip_dedup::{
+    gossip_message_id, GossipDedup, GOSSIP_DEDUP_CAPACITY, GOSSIP_DEDUP_FP_RATE, GOSSIP_DEDUP_HORIZON,
+};
+pub use onion_key::OnionKeyOffer;
+pub use peer_binding::{
+    sign_peer_binding, sign_peer_binding_at, verify_peer_binding, verify_peer_binding_for, BindingRejection, PeerBindings,
+    PEER_BINDING_MAX_AGE,
+};
+pub use proof_message::{
+    ProofMessage, PoolType, ProofStateQuery, ProofStateResponse, ProofBackfillRequest, ProofBackfillResponse,
+    HistorySyncRequest, HistorySyncResponse,
+};
+pub use proof_backfill::{
+    serve_proof_backfill, ProofArchive, ProofBackfillClient, PROOF_BACKFILL_PROTOCOL, MAX_BACKFILL_PROOFS,
+};
+pub use proof_submit::{
+    InboundProofSubmission, ProofSubmission, ProofSubmitClient, ProofSubmitResponse, ProofSubmitStatus,
+    serve_proof_submissions, PROOF_SUBMIT_PROTOCOL, MAX_SUBMITTED_PROOFS,
+};
+pub use registry::{
+    BloomFilter, RegistryClient, RegistryEntry, RegistryKind, RegistryStore,
+    RegistrySyncRequest, RegistrySyncResponse, record_fingerprint, serve_registry_sync,
+    REGISTRY_SYNC_PROTOCOL, REGISTRY_SYNC_PAGE_SIZE, REGISTRY_SYNC_MAX_PAGES,
 };
-pub use proof_message::{ProofMessage, PoolType, ProofStateQuery, ProofStateResponse, HistorySyncRequest, HistorySyncResponse};
 pub use relay_status::{RelayStatusMessage, RelayStatusType};
 pub use status::{ExitStatusMessage, ExitStatusType};
+pub use status_auth::{StatusFreshness, StatusFreshnessPolicy, StatusRejection, MAX_TRACKED_SIGNERS};
 pub use subscription::SubscriptionAnnouncement;
+pub use traffic::{
+    FreeloaderPolicy, PeerTraffic, SharedTrafficAccounting, TrafficAccounting, TrafficCounters, TrafficProtocol,
+    MAX_TRACKED_PEERS,
+};
+pub use wire::{Extensions, EXT_PROTOCOL_VERSION, WIRE_VERSION};
 pub use bootstrap::{
     DEFAULT_BOOTSTRAP_NODES, DEFAULT_PORT,
     default_bootstrap_peers, parse_bootstrap_nodes, parse_bootstrap_addr,
     make_bootstrap_addr, has_bootstrap_nodes,
+    DEFAULT_WEBRTC_PORT, DEFAULT_BROWSER_BOOTSTRAP_NODES,
+    is_webrtc_addr, is_browser_dialable, webrtc_listen_addr,
+    browser_bootstrap_peers, make_webrtc_bootstrap_addr,
+    DnsSeedCache, DNS_SEED_MIN_TTL, DNS_SEED_MAX_TTL,
+    parse_dnsaddr_records, resolve_dns_seed, merge_bootstrap_peers,
 };
 pub use node::{build_swarm, NetworkConfig, NetworkEvent, NetworkError};
 pub use protocol::{
-    ShardResponse, SHARD_PROTOCOL_ID, MAX_SHARD_SIZE,
-    StreamFrame, SHARD_STREAM_PROTOCOL,
-    read_frame, write_shard_frame, write_ack_frame, write_nack_frame,
+    ShardCodec, ShardRequest, ShardResponse, SHARD_PROTOCOL_ID, MAX_SHARD_SIZE,
+    StreamFrame, SHARD_STREAM_PROTOCOL, NACK_DRAINING, NACK_REPLAY,
+    PeerVersion, PROTOCOL_VERSION, USER_AGENT, FrameCipher, FrameKeyExchange,
+    read_frame, write_shard_frame, write_ack_frame, write_nack_frame, write_hello_frame,
+    write_chain_ack_frame, encode_shard_frame,
 };
+pub use buffer_pool::{frame_pool, BufferPool, BufferPoolStats};
 pub use stream_manager::{StreamManager, InboundShard, OutboundShard, AckResult};
 pub use libp2p_stream::IncomingStreams;
 
@@ -3,15 +3,20 @@
 //! Main entry point for P2P networking functionality.
 //! Delegates to craftec-network for swarm construction.
 
+use std::path::PathBuf;
+
 use libp2p::{
     identity::Keypair,
     Multiaddr, PeerId,
 };
+use craftnet_core::ErrorCode;
 use thiserror::Error;
 use tracing::info;
 
 use crate::behaviour::CraftNetBehaviour;
+use crate::bootstrap::is_webrtc_addr;
 use crate::protocol::SHARD_STREAM_PROTOCOL;
+use crate::traffic::PeerTraffic;
 
 #[derive(Error, Debug)]
 pub enum NetworkError {
@@ -40,6 +45,17 @@ pub enum NetworkError {
     SendError(String),
 }
 
+impl NetworkError {
+    /// Machine-readable code of this error
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::Transport(_) | Self::Listen(_) | Self::SwarmBuild(_) | Self::ChannelClosed => ErrorCode::NetworkError,
+            Self::Dial(_) | Self::NotConnected(_) | Self::SendError(_) => ErrorCode::PeerUnreachable,
+            Self::BootstrapNoPeers => ErrorCode::NoPeers,
+        }
+    }
+}
+
 /// Network configuration
 #[derive(Debug, Clone)]
 pub struct NetworkConfig {
@@ -47,6 +63,10 @@ pub struct NetworkConfig {
     pub listen_addrs: Vec<Multiaddr>,
     /// Bootstrap peers to connect to
     pub bootstrap_peers: Vec<(PeerId, Multiaddr)>,
+    /// Where the WebRTC-direct certificate is kept, so the listen address
+    /// certhash survives restarts. None = new certificate every start.
+    /// Only used when `listen_addrs` has a `/webrtc-direct` address.
+    pub webrtc_cert_path: Option<PathBuf>,
 }
 
 impl Default for NetworkConfig {
@@ -54,6 +74,7 @@ impl Default for NetworkConfig {
         Self {
             listen_addrs: vec!["/ip4/0.0.0.0/tcp/0".parse().expect("valid hardcoded multiaddr")],
             bootstrap_peers: crate::bootstrap::default_bootstrap_peers(),
+            webrtc_cert_path: None,
         }
     }
 }
@@ -116,6 +137,9 @@ pub enum NetworkEvent {
     RendezvousPeerRegistered {
         peer: PeerId,
     },
+    /// Traffic exchanged with each tracked peer, most bytes first
+    /// (see [`TrafficAccounting::report`](crate::TrafficAccounting::report))
+    TrafficReport(Vec<PeerTraffic>),
 }
 
 /// Build a CraftNet swarm using the generic CraftBehaviour from craftec-network.
@@ -123,11 +147,37 @@ pub enum NetworkEvent {
 /// The swarm uses protocol prefix "craftnet" for Kademlia (`/craftnet/kad/1.0.0`),
 /// identify (`/craftnet/id/1.0.0`), etc.
 ///
+/// `/udp/<port>/webrtc-direct` listen addresses need the `webrtc` feature;
+/// the swarm then also accepts browser peers, authenticated by the
+/// certificate from `webrtc_cert_path`.
+///
+/// Kademlia record filtering is on: the caller must store inbound records
+/// and provider announcements itself (after validating them).
+///
 /// Returns the swarm, local peer ID, and incoming streams for the shard protocol.
 pub async fn build_swarm(
     keypair: Keypair,
     config: NetworkConfig,
 ) -> Result<(libp2p::Swarm<CraftNetBehaviour>, PeerId, libp2p_stream::IncomingStreams), NetworkError> {
+    let wants_webrtc = config.listen_addrs.iter().any(is_webrtc_addr);
+
+    #[cfg(not(feature = "webrtc"))]
+    if wants_webrtc {
+        return Err(NetworkError::Listen(
+            "WebRTC listen address requires the `webrtc` feature".to_string(),
+        ));
+    }
+
+    #[cfg(feature = "webrtc")]
+    let webrtc_certificate = if wants_webrtc {
+        Some(match &config.webrtc_cert_path {
+            Some(path) => crate::webrtc::load_or_generate_certificate(path)?,
+            None => crate::webrtc::generate_certificate()?,
+        })
+    } else {
+        None
+    };
+
     let craftec_config = craftec_network::NetworkConfig {
         protocol_prefix: "craftnet".to_string(),
         // Enable secondary Kademlia for the exit/relay provider registry.
@@ -137,6 +187,12 @@ pub async fn build_swarm(
         listen_addrs: config.listen_addrs,
         bootstrap_peers: config.bootstrap_peers,
         enable_mdns: true,
+        // Hand inbound PUT_VALUE / ADD_PROVIDER requests to the swarm owner
+        // instead of storing them directly, so exit/relay records can be
+        // checked against `DhtRecordValidators` before they enter the store.
+        record_filtering: true,
+        #[cfg(feature = "webrtc")]
+        webrtc_certificate,
     };
 
     let (swarm, peer_id) = craftec_network::build_swarm(keypair, craftec_config)
@@ -178,6 +234,7 @@ mod tests {
         let config = NetworkConfig {
             listen_addrs: vec!["/ip4/0.0.0.0/tcp/8000".parse().unwrap()],
             bootstrap_peers: vec![(peer_id, addr)],
+            webrtc_cert_path: None,
         };
 
         assert_eq!(config.listen_addrs.len(), 1);
@@ -198,6 +255,18 @@ mod tests {
         assert_eq!(swarm.connected_peers().count(), 0);
     }
 
+    #[cfg(not(feature = "webrtc"))]
+    #[tokio::test]
+    async fn test_webrtc_listen_needs_feature() {
+        let config = NetworkConfig {
+            listen_addrs: vec![crate::webrtc_listen_addr(0)],
+            bootstrap_peers: vec![],
+            webrtc_cert_path: None,
+        };
+        let result = build_swarm(Keypair::generate_ed25519(), config).await;
+        assert!(matches!(result, Err(NetworkError::Listen(_))));
+    }
+
     #[test]
     fn test_network_error_display() {
         let err = NetworkError::NotConnected(PeerId::random());
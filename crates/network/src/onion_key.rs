@@ -0,0 +1,100 @@
+//! Signed onion key offers
+//!
+//! A relay rotates its onion key (see `craftnet_core::OnionKeyRing`) and
+//! announces the current one on every heartbeat as an [`OnionKeyOffer`],
+//! signed with its ed25519 identity. The heartbeat signature covers the
+//! offer too, but the offer carries its own so it can be checked wherever
+//! it is relayed; it is what stops a peer from steering clients onto an
+//! onion key it controls.
+
+use serde::{Deserialize, Serialize};
+
+use craftec_crypto::{sign_data, verify_signature, SigningKeypair};
+use craftnet_core::PublicKey;
+
+/// Domain separator for offer signatures
+const ONION_KEY_DOMAIN: &[u8] = b"craftnet-onion-key-v1";
+
+/// A relay's current onion key, carried in its heartbeat
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OnionKeyOffer {
+    /// X25519 onion pubkey (hex encoded)
+    pub onion_pubkey: String,
+    /// Relay's rotation counter
+    pub epoch: u64,
+    /// Unix timestamp (seconds) after which the relay may no longer peel
+    /// layers sealed to this key
+    pub expires_at: u64,
+    /// Relay's ed25519 signature over `signable_data()` (hex encoded)
+    pub signature: String,
+}
+
+impl OnionKeyOffer {
+    /// Sign an offer for `onion_pubkey` with the relay's identity
+    pub fn sign(keypair: &SigningKeypair, onion_pubkey: [u8; 32], epoch: u64, expires_at: u64) -> Self {
+        let signable = Self::signable_data(&keypair.public_key_bytes(), &onion_pubkey, epoch, expires_at);
+        Self {
+            onion_pubkey: hex::encode(onion_pubkey),
+            epoch,
+            expires_at,
+            signature: hex::encode(sign_data(keypair, &signable)),
+        }
+    }
+
+    /// Data signed by the relay; binds the offer to the relay's pubkey
+    pub fn signable_data(relay_pubkey: &PublicKey, onion_pubkey: &[u8; 32], epoch: u64, expires_at: u64) -> Vec<u8> {
+        let mut data = Vec::with_capacity(ONION_KEY_DOMAIN.len() + 32 + 32 + 8 + 8);
+        data.extend_from_slice(ONION_KEY_DOMAIN);
+        data.extend_from_slice(relay_pubkey);
+        data.extend_from_slice(onion_pubkey);
+        data.extend_from_slice(&epoch.to_le_bytes());
+        data.extend_from_slice(&expires_at.to_le_bytes());
+        data
+    }
+
+    /// Onion pubkey as bytes
+    pub fn onion_pubkey_bytes(&self) -> Option<[u8; 32]> {
+        hex::decode(&self.onion_pubkey).ok()?.try_into().ok()
+    }
+
+    /// Check that `relay_pubkey` signed this offer
+    pub fn verify(&self, relay_pubkey: &PublicKey) -> bool {
+        let Some(onion_pubkey) = self.onion_pubkey_bytes() else {
+            return false;
+        };
+        let Some(signature) = hex::decode(&self.signature).ok()
+            .and_then(|s| <[u8; 64]>::try_from(s).ok())
+        else {
+            return false;
+        };
+        let signable = Self::signable_data(relay_pubkey, &onion_pubkey, self.epoch, self.expires_at);
+        verify_signature(relay_pubkey, &signable, &signature)
+    }
+
+    /// Whether the key can still be used at unix time `now`
+    pub fn is_valid_at(&self, now: u64) -> bool {
+        now < self.expires_at
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offer_sign_verify() {
+        let relay = SigningKeypair::generate();
+        let offer = OnionKeyOffer::sign(&relay, [7u8; 32], 3, 1_000);
+        assert!(offer.verify(&relay.public_key_bytes()));
+        assert_eq!(offer.onion_pubkey_bytes(), Some([7u8; 32]));
+        assert!(offer.is_valid_at(999));
+        assert!(!offer.is_valid_at(1_000));
+
+        // Another relay's identity, a swapped key or a stretched expiry fail
+        assert!(!offer.verify(&SigningKeypair::generate().public_key_bytes()));
+        let swapped = OnionKeyOffer { onion_pubkey: hex::encode([8u8; 32]), ..offer.clone() };
+        assert!(!swapped.verify(&relay.public_key_bytes()));
+        let stretched = OnionKeyOffer { expires_at: 5_000, ..offer };
+        assert!(!stretched.verify(&relay.public_key_bytes()));
+    }
+}
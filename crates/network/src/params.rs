@@ -0,0 +1,197 @@
+//! Network parameter beacon
+//!
+//! Maintainers publish one [`NetworkParameters`] record under the
+//! well-known DHT key [`NETWORK_PARAMS_KEY`] to change network-wide settings
+//! without a release: extra bootstrap nodes, the oldest protocol version
+//! still supported, and emergency notices for operators and users.
+//!
+//! The record is a [`SignedDhtRecord`] like exit/relay records, but only
+//! signatures by a maintainer key ([`maintainer_keys`], embedded at build
+//! time from `CRAFTNET_MAINTAINER_KEYS`) are accepted. Nodes read it at
+//! startup and every [`NETWORK_PARAMS_REFRESH_INTERVAL`], and keep the one
+//! with the highest `sequence`, so an older record replayed into the DHT
+//! cannot roll parameters back.
+
+use std::time::Duration;
+
+use craftec_crypto::SigningKeypair;
+use craftnet_core::{attest_build, BuildAttestation, BuildManifest, PublicKey};
+use libp2p::{Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+
+use crate::behaviour::{DhtRecordValidator, RecordRejection, SignedDhtRecord};
+use crate::bootstrap::parse_bootstrap_addr;
+
+/// Well-known DHT key of the network parameter record
+pub const NETWORK_PARAMS_KEY: &[u8] = b"/craftnet/network-params";
+
+/// How long a signed parameter record is accepted (maintainers re-sign
+/// before it runs out)
+pub const NETWORK_PARAMS_TTL: Duration = Duration::from_secs(30 * 24 * 3600);
+
+/// How often nodes look the record up again
+pub const NETWORK_PARAMS_REFRESH_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+/// Maintainer keys embedded at build time (`CRAFTNET_MAINTAINER_KEYS`,
+/// comma-separated hex ed25519 public keys)
+pub fn maintainer_keys() -> Vec<PublicKey> {
+    option_env!("CRAFTNET_MAINTAINER_KEYS")
+        .unwrap_or("")
+        .split(',')
+        .filter_map(|k| hex::decode(k.trim()).ok()?.try_into().ok())
+        .collect()
+}
+
+/// How urgent a notice is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum NoticeSeverity {
+    #[default]
+    Info,
+    Warning,
+    /// Act now (e.g. upgrade, key compromise)
+    Critical,
+}
+
+/// Announcement shown to operators and users
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkNotice {
+    /// Stable id so frontends can dismiss it
+    pub id: String,
+    #[serde(default)]
+    pub severity: NoticeSeverity,
+    pub message: String,
+    /// Unix seconds after which the notice is hidden
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+}
+
+/// Network-wide parameters set by the maintainers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct NetworkParameters {
+    /// Increases with every published change; lower ones are ignored
+    pub sequence: u64,
+    /// Bootstrap nodes (`/ip4/<IP>/tcp/<PORT>/p2p/<PEER_ID>`) added to the
+    /// built-in list
+    #[serde(default)]
+    pub bootstrap_nodes: Vec<String>,
+    /// Oldest shard protocol version still served by the network
+    #[serde(default)]
+    pub min_protocol_version: Option<u16>,
+    #[serde(default)]
+    pub notices: Vec<NetworkNotice>,
+    /// Manifests of the canonical release builds, one per version and
+    /// target, for operators to check their binary against
+    #[serde(default)]
+    pub builds: Vec<BuildManifest>,
+}
+
+impl NetworkParameters {
+    /// Sign for publication under [`NETWORK_PARAMS_KEY`]
+    pub fn sign(&self, keypair: &SigningKeypair) -> SignedDhtRecord {
+        SignedDhtRecord::sign(keypair, NETWORK_PARAMS_KEY, serde_json::to_string(self).unwrap_or_default())
+    }
+
+    /// Parsed bootstrap nodes (unparseable entries are skipped)
+    pub fn bootstrap_peers(&self) -> Vec<(PeerId, Multiaddr)> {
+        self.bootstrap_nodes.iter().filter_map(|a| parse_bootstrap_addr(a)).collect()
+    }
+
+    /// Whether a node speaking `protocol_version` is too old for the network
+    pub fn requires_upgrade(&self, protocol_version: u16) -> bool {
+        self.min_protocol_version.is_some_and(|min| protocol_version < min)
+    }
+
+    /// Compare `build` with the canonical build manifests
+    pub fn attest(&self, build: &BuildManifest) -> BuildAttestation {
+        attest_build(build, &self.builds)
+    }
+
+    /// Notices not yet expired at unix time `now`
+    pub fn active_notices(&self, now: u64) -> impl Iterator<Item = &NetworkNotice> {
+        self.notices.iter().filter(move |n| n.expires_at.is_none_or(|t| t > now))
+    }
+}
+
+/// Validator for [`NETWORK_PARAMS_KEY`]: the signer must be a maintainer and
+/// the body must parse as [`NetworkParameters`]
+pub struct NetworkParamsValidator {
+    keys: Vec<PublicKey>,
+}
+
+impl NetworkParamsValidator {
+    pub fn new(keys: Vec<PublicKey>) -> Self {
+        Self { keys }
+    }
+}
+
+impl DhtRecordValidator for NetworkParamsValidator {
+    fn validate(&self, key_suffix: &str, record: &SignedDhtRecord) -> Result<(), RecordRejection> {
+        if !key_suffix.is_empty() {
+            return Err(RecordRejection::Malformed);
+        }
+        let signer = record.pubkey_bytes().ok_or(RecordRejection::BadSignature)?;
+        if !self.keys.contains(&signer) {
+            return Err(RecordRejection::IdentityMismatch);
+        }
+        serde_json::from_str::<NetworkParameters>(&record.data)
+            .map(|_| ())
+            .map_err(|e| RecordRejection::Schema(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::behaviour::DhtRecordValidators;
+
+    fn release_build() -> BuildManifest {
+        BuildManifest { git_commit: "ab".repeat(20), ..BuildManifest::current() }
+    }
+
+    fn params() -> NetworkParameters {
+        NetworkParameters {
+            sequence: 3,
+            bootstrap_nodes: vec![
+                "/ip4/10.0.0.1/tcp/9000/p2p/12D3KooWQNV9B3aYrwqXfzQA9K6c1AzPLQVLyZsyYqNqXcT7Th5E".to_string(),
+                "not-an-addr".to_string(),
+            ],
+            min_protocol_version: Some(2),
+            notices: vec![
+                NetworkNotice { id: "a".into(), severity: NoticeSeverity::Critical, message: "upgrade".into(), expires_at: None },
+                NetworkNotice { id: "b".into(), severity: NoticeSeverity::Info, message: "old".into(), expires_at: Some(100) },
+            ],
+            builds: vec![release_build()],
+        }
+    }
+
+    #[test]
+    fn test_only_maintainer_records_validate() {
+        let maintainer = SigningKeypair::generate();
+        let stranger = SigningKeypair::generate();
+        let validators = DhtRecordValidators::with_maintainer_keys(vec![maintainer.public_key_bytes()]);
+
+        let body = validators.validate(NETWORK_PARAMS_KEY, &params().sign(&maintainer).to_bytes()).unwrap();
+        assert_eq!(serde_json::from_slice::<NetworkParameters>(&body).unwrap(), params());
+
+        assert_eq!(
+            validators.validate(NETWORK_PARAMS_KEY, &params().sign(&stranger).to_bytes()),
+            Err(RecordRejection::IdentityMismatch)
+        );
+        assert!(DhtRecordValidators::with_maintainer_keys(Vec::new())
+            .validate(NETWORK_PARAMS_KEY, &params().sign(&maintainer).to_bytes())
+            .is_err());
+    }
+
+    #[test]
+    fn test_parameters_accessors() {
+        let params = params();
+        assert_eq!(params.bootstrap_peers().len(), 1);
+        assert!(params.requires_upgrade(1));
+        assert!(!params.requires_upgrade(2));
+        assert_eq!(params.active_notices(50).count(), 2);
+        assert_eq!(params.active_notices(200).map(|n| n.id.as_str()).collect::<Vec<_>>(), vec!["a"]);
+        assert_eq!(params.attest(&release_build()), BuildAttestation::Verified);
+        assert_eq!(NetworkParameters::default().attest(&release_build()), BuildAttestation::Unknown);
+    }
+}
@@ -0,0 +1,272 @@
+//! PeerId half of the peer identity ↔ settlement pubkey binding
+//!
+//! `craftnet_core::PeerBinding` carries signatures from both keys; this
+//! module produces and checks the libp2p side. The binding is published as
+//! the value of the `/craftnet/peers/<pubkey_hex>` DHT record and embedded
+//! in exit/relay info records.
+//!
+//! Nodes re-sign their binding with every announcement, so a binding is
+//! only accepted within [`PEER_BINDING_MAX_AGE`] of its signed timestamp,
+//! and [`PeerBindings`] keeps an old binding from displacing a newer one
+//! after a node moved its settlement key to another PeerId.
+
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use craftec_crypto::SigningKeypair;
+use craftnet_core::{sign_peer_binding_settlement, PeerBinding, PublicKey};
+use libp2p::identity::{Keypair, PublicKey as Libp2pPublicKey};
+use libp2p::PeerId;
+
+/// How far a binding's timestamp may be from the time it is checked.
+///
+/// Bindings are re-signed every announcement (a couple of minutes), so an
+/// hour covers record TTLs and clock skew.
+pub const PEER_BINDING_MAX_AGE: Duration = Duration::from_secs(3600);
+
+/// Why a peer binding was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindingRejection {
+    /// A signature fails, or the binding names another settlement key
+    Invalid,
+    /// Signed further than the max age from the check time; `age_secs` is
+    /// the check time minus the binding's timestamp
+    Expired { age_secs: i64 },
+    /// Older than a binding already accepted for the same settlement key,
+    /// and naming a different PeerId
+    Superseded,
+}
+
+impl std::fmt::Display for BindingRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BindingRejection::Invalid => write!(f, "invalid signature"),
+            BindingRejection::Expired { age_secs } => write!(f, "expired (signed {}s ago)", age_secs),
+            BindingRejection::Superseded => write!(f, "superseded by a newer binding"),
+        }
+    }
+}
+
+/// Sign a binding between our libp2p identity and our settlement key.
+///
+/// Returns `None` if the libp2p key type cannot sign (never for ed25519).
+pub fn sign_peer_binding(libp2p_keypair: &Keypair, settlement_keypair: &SigningKeypair) -> Option<PeerBinding> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    sign_peer_binding_at(libp2p_keypair, settlement_keypair, timestamp)
+}
+
+/// [`sign_peer_binding`] with an explicit timestamp
+pub fn sign_peer_binding_at(
+    libp2p_keypair: &Keypair,
+    settlement_keypair: &SigningKeypair,
+    timestamp: u64,
+) -> Option<PeerBinding> {
+    let peer_id = PeerId::from(libp2p_keypair.public()).to_bytes();
+    let settlement_pubkey = settlement_keypair.public_key_bytes();
+
+    let data = PeerBinding::signable_data(&peer_id, &settlement_pubkey, timestamp);
+    let peer_signature = libp2p_keypair.sign(&data).ok()?;
+    let settlement_signature = sign_peer_binding_settlement(settlement_keypair, &peer_id, timestamp);
+
+    Some(PeerBinding {
+        peer_id,
+        settlement_pubkey,
+        timestamp,
+        settlement_signature,
+        peer_public_key: libp2p_keypair.public().encode_protobuf(),
+        peer_signature,
+    })
+}
+
+/// Verify both signatures of a binding.
+///
+/// Returns the bound PeerId if `peer_public_key` derives `peer_id` and both
+/// the libp2p and settlement signatures check out.
+pub fn verify_peer_binding(binding: &PeerBinding) -> Option<PeerId> {
+    let peer_id = PeerId::from_bytes(&binding.peer_id).ok()?;
+    let public_key = Libp2pPublicKey::try_decode_protobuf(&binding.peer_public_key).ok()?;
+    if PeerId::from_public_key(&public_key) != peer_id {
+        return None;
+    }
+    if !public_key.verify(&binding.signable(), &binding.peer_signature) {
+        return None;
+    }
+    if !binding.verify_settlement_signature() {
+        return None;
+    }
+    Some(peer_id)
+}
+
+/// Verify a binding claimed for a specific settlement pubkey, signed
+/// within `max_age` of unix time `now`
+pub fn verify_peer_binding_for(
+    binding: &PeerBinding,
+    settlement_pubkey: &PublicKey,
+    max_age: Duration,
+    now: u64,
+) -> Result<PeerId, BindingRejection> {
+    if &binding.settlement_pubkey != settlement_pubkey {
+        return Err(BindingRejection::Invalid);
+    }
+    let age_secs = now as i64 - binding.timestamp as i64;
+    if age_secs.unsigned_abs() > max_age.as_secs() {
+        return Err(BindingRejection::Expired { age_secs });
+    }
+    verify_peer_binding(binding).ok_or(BindingRejection::Invalid)
+}
+
+/// Newest verified binding per settlement pubkey
+#[derive(Debug)]
+pub struct PeerBindings {
+    max_age: Duration,
+    /// Settlement pubkey → (bound PeerId, binding timestamp)
+    bound: HashMap<PublicKey, (PeerId, u64)>,
+}
+
+impl Default for PeerBindings {
+    fn default() -> Self {
+        Self::new(PEER_BINDING_MAX_AGE)
+    }
+}
+
+impl PeerBindings {
+    pub fn new(max_age: Duration) -> Self {
+        Self { max_age, bound: HashMap::new() }
+    }
+
+    /// Verify `binding` for `pubkey` at unix time `now` without
+    /// remembering it.
+    ///
+    /// A binding older than the stored one is accepted only for the same
+    /// PeerId (records arrive out of order).
+    pub fn check(&self, pubkey: &PublicKey, binding: &PeerBinding, now: u64) -> Result<PeerId, BindingRejection> {
+        let peer_id = verify_peer_binding_for(binding, pubkey, self.max_age, now)?;
+        match self.bound.get(pubkey) {
+            Some((bound, timestamp)) if binding.timestamp < *timestamp && *bound != peer_id => {
+                Err(BindingRejection::Superseded)
+            }
+            _ => Ok(peer_id),
+        }
+    }
+
+    /// [`Self::check`] a binding and remember it
+    pub fn accept(&mut self, pubkey: &PublicKey, binding: &PeerBinding, now: u64) -> Result<PeerId, BindingRejection> {
+        let peer_id = self.check(pubkey, binding, now)?;
+        self.insert(*pubkey, peer_id, binding.timestamp);
+        Ok(peer_id)
+    }
+
+    /// Remember a verified binding signed at `timestamp`, unless a newer
+    /// one is already stored
+    pub fn insert(&mut self, pubkey: PublicKey, peer_id: PeerId, timestamp: u64) {
+        let entry = self.bound.entry(pubkey).or_insert((peer_id, timestamp));
+        if timestamp >= entry.1 {
+            *entry = (peer_id, timestamp);
+        }
+    }
+
+    /// PeerId bound to `pubkey`
+    pub fn get(&self, pubkey: &PublicKey) -> Option<&PeerId> {
+        self.bound.get(pubkey).map(|(peer_id, _)| peer_id)
+    }
+
+    pub fn contains_key(&self, pubkey: &PublicKey) -> bool {
+        self.bound.contains_key(pubkey)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify_binding() {
+        let libp2p_keypair = Keypair::generate_ed25519();
+        let settlement = SigningKeypair::generate();
+
+        let binding = sign_peer_binding(&libp2p_keypair, &settlement).unwrap();
+        let peer_id = verify_peer_binding(&binding).unwrap();
+        assert_eq!(peer_id, PeerId::from(libp2p_keypair.public()));
+        assert_eq!(
+            verify_peer_binding_for(&binding, &settlement.public_key_bytes(), PEER_BINDING_MAX_AGE, binding.timestamp),
+            Ok(peer_id),
+        );
+    }
+
+    #[test]
+    fn test_binding_for_other_pubkey_rejected() {
+        let libp2p_keypair = Keypair::generate_ed25519();
+        let settlement = SigningKeypair::generate();
+        let binding = sign_peer_binding(&libp2p_keypair, &settlement).unwrap();
+
+        let other = SigningKeypair::generate().public_key_bytes();
+        assert_eq!(
+            verify_peer_binding_for(&binding, &other, PEER_BINDING_MAX_AGE, binding.timestamp),
+            Err(BindingRejection::Invalid),
+        );
+    }
+
+    #[test]
+    fn test_expired_binding_rejected() {
+        let settlement = SigningKeypair::generate();
+        let binding = sign_peer_binding(&Keypair::generate_ed25519(), &settlement).unwrap();
+        let pubkey = settlement.public_key_bytes();
+        let max_age = PEER_BINDING_MAX_AGE.as_secs();
+
+        assert!(verify_peer_binding_for(&binding, &pubkey, PEER_BINDING_MAX_AGE, binding.timestamp + max_age).is_ok());
+        assert_eq!(
+            verify_peer_binding_for(&binding, &pubkey, PEER_BINDING_MAX_AGE, binding.timestamp + max_age + 1),
+            Err(BindingRejection::Expired { age_secs: max_age as i64 + 1 }),
+        );
+        // Nor one dated far ahead of our clock
+        assert!(matches!(
+            verify_peer_binding_for(&binding, &pubkey, PEER_BINDING_MAX_AGE, binding.timestamp - max_age - 1),
+            Err(BindingRejection::Expired { .. }),
+        ));
+    }
+
+    #[test]
+    fn test_superseded_binding_rejected() {
+        let settlement = SigningKeypair::generate();
+        let pubkey = settlement.public_key_bytes();
+        let (old_key, new_key) = (Keypair::generate_ed25519(), Keypair::generate_ed25519());
+        let now = 1_700_000_000;
+        let old = sign_peer_binding_at(&old_key, &settlement, now - 60).unwrap();
+        // The node moved its settlement key to a new PeerId a minute later
+        let new = sign_peer_binding_at(&new_key, &settlement, now).unwrap();
+
+        let mut bindings = PeerBindings::default();
+        let new_peer = bindings.accept(&pubkey, &new, now).unwrap();
+        assert_eq!(bindings.accept(&pubkey, &old, now), Err(BindingRejection::Superseded));
+        assert_eq!(bindings.get(&pubkey), Some(&new_peer));
+
+        // An older binding to the current PeerId is fine (out-of-order records)
+        let earlier = sign_peer_binding_at(&new_key, &settlement, now - 120).unwrap();
+        assert_eq!(bindings.accept(&pubkey, &earlier, now), Ok(new_peer));
+    }
+
+    #[test]
+    fn test_swapped_peer_id_rejected() {
+        let settlement = SigningKeypair::generate();
+        let mut binding = sign_peer_binding(&Keypair::generate_ed25519(), &settlement).unwrap();
+
+        // Claim a different PeerId while keeping the original libp2p key
+        binding.peer_id = PeerId::from(Keypair::generate_ed25519().public()).to_bytes();
+        assert!(verify_peer_binding(&binding).is_none());
+    }
+
+    #[test]
+    fn test_foreign_libp2p_key_rejected() {
+        let settlement = SigningKeypair::generate();
+        let mut binding = sign_peer_binding(&Keypair::generate_ed25519(), &settlement).unwrap();
+
+        // Replace the libp2p half with another key that never signed this payload
+        let other = Keypair::generate_ed25519();
+        binding.peer_id = PeerId::from(other.public()).to_bytes();
+        binding.peer_public_key = other.public().encode_protobuf();
+        assert!(verify_peer_binding(&binding).is_none());
+    }
+}
@@ -0,0 +1,240 @@
+//! Proof chain backfill
+//!
+//! An aggregator that missed some of a relay's proofs buffers the later
+//! ones until the gap is filled. Rather than waiting for the missing
+//! proofs to show up by chance, it opens `/craftnet/proof-backfill/1.0.0`
+//! to the relay (or another aggregator) and asks for the segment between
+//! the root its chain ends at and the root its held-back proofs start at.
+//!
+//! Both sides answer from a [`ProofArchive`] of recent signed proofs:
+//! relays keep what they published, aggregators what they accepted.
+//!
+//! Wire format: `[len:4 BE][bincode message]`, one
+//! [`ProofBackfillRequest`] then one [`ProofBackfillResponse`] per stream.
+
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::{AsyncWriteExt, StreamExt};
+use libp2p::{PeerId, StreamProtocol};
+use tokio::sync::Semaphore;
+use tracing::debug;
+
+use crate::registry::{read_message, write_message};
+use crate::{NetworkError, PoolType, ProofBackfillRequest, ProofBackfillResponse, ProofMessage};
+
+/// Protocol identifier for proof chain backfill
+pub const PROOF_BACKFILL_PROTOCOL: StreamProtocol = StreamProtocol::new("/craftnet/proof-backfill/1.0.0");
+
+/// Most proofs returned for one request
+pub const MAX_BACKFILL_PROOFS: usize = 64;
+
+/// Proofs archived per (relay, pool) chain
+const MAX_ARCHIVED_PER_CHAIN: usize = 256;
+
+/// Chains archived at once; the least recently extended one goes first
+const MAX_ARCHIVED_CHAINS: usize = 4096;
+
+/// Backfill streams served at once
+const MAX_BACKFILL_SESSIONS: usize = 16;
+
+/// Time allowed for a backfill round trip
+const PROOF_BACKFILL_TIMEOUT: Duration = Duration::from_secs(15);
+
+type ChainKey = ([u8; 32], [u8; 32], PoolType);
+
+/// Recent signed proofs per (relay, pool) chain
+#[derive(Debug, Default)]
+pub struct ProofArchive {
+    chains: HashMap<ChainKey, VecDeque<ProofMessage>>,
+    /// Chain keys, least recently extended first
+    order: VecDeque<ChainKey>,
+}
+
+impl ProofArchive {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep `msg` (a proof seen before is ignored)
+    pub fn insert(&mut self, msg: ProofMessage) {
+        let key = (msg.relay_pubkey, msg.pool_pubkey, msg.pool_type);
+        if let Some(pos) = self.order.iter().position(|k| *k == key) {
+            self.order.remove(pos);
+        } else if self.chains.len() >= MAX_ARCHIVED_CHAINS {
+            if let Some(oldest) = self.order.pop_front() {
+                self.chains.remove(&oldest);
+            }
+        }
+        self.order.push_back(key);
+
+        let chain = self.chains.entry(key).or_default();
+        if chain.iter().any(|p| p.new_root == msg.new_root && p.prev_root == msg.prev_root) {
+            return;
+        }
+        if chain.len() >= MAX_ARCHIVED_PER_CHAIN {
+            chain.pop_front();
+        }
+        chain.push_back(msg);
+    }
+
+    /// The archived proofs linking `request.from_root` towards
+    /// `request.to_root`, in chain order (at most [`MAX_BACKFILL_PROOFS`];
+    /// the segment may stop short where the archive has no next link)
+    pub fn segment(&self, request: &ProofBackfillRequest) -> Vec<ProofMessage> {
+        let key = (request.relay_pubkey, request.pool_pubkey, request.pool_type);
+        let Some(chain) = self.chains.get(&key) else {
+            return Vec::new();
+        };
+        let mut segment = Vec::new();
+        let mut root = request.from_root;
+        while root != request.to_root && segment.len() < MAX_BACKFILL_PROOFS {
+            let Some(next) = chain.iter().find(|p| p.prev_root == root) else {
+                break;
+            };
+            root = next.new_root;
+            segment.push(next.clone());
+        }
+        segment
+    }
+
+    /// Number of archived proofs
+    pub fn len(&self) -> usize {
+        self.chains.values().map(VecDeque::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chains.is_empty()
+    }
+}
+
+/// Client side of the proof backfill protocol
+#[derive(Clone)]
+pub struct ProofBackfillClient {
+    control: libp2p_stream::Control,
+}
+
+impl ProofBackfillClient {
+    pub fn new(control: libp2p_stream::Control) -> Self {
+        Self { control }
+    }
+
+    /// Ask `peer` for the segment described by `request`
+    pub async fn request(
+        &mut self,
+        peer: PeerId,
+        request: &ProofBackfillRequest,
+    ) -> Result<ProofBackfillResponse, NetworkError> {
+        tokio::time::timeout(PROOF_BACKFILL_TIMEOUT, async {
+            let mut stream = self
+                .control
+                .open_stream(peer, PROOF_BACKFILL_PROTOCOL)
+                .await
+                .map_err(|e| NetworkError::SendError(format!("proof backfill open: {}", e)))?;
+            let response: ProofBackfillResponse = async {
+                write_message(&mut stream, request).await?;
+                read_message(&mut stream).await
+            }
+            .await
+            .map_err(|e| NetworkError::SendError(format!("proof backfill: {}", e)))?;
+            let _ = stream.close().await;
+            Ok(response)
+        })
+        .await
+        .map_err(|_| NetworkError::SendError("proof backfill timed out".to_string()))?
+    }
+}
+
+/// Answer backfill streams from `archive` until `incoming` ends
+pub async fn serve_proof_backfill(
+    mut incoming: libp2p_stream::IncomingStreams,
+    archive: Arc<Mutex<ProofArchive>>,
+) {
+    let sessions = Arc::new(Semaphore::new(MAX_BACKFILL_SESSIONS));
+    while let Some((peer, stream)) = incoming.next().await {
+        let Ok(permit) = sessions.clone().try_acquire_owned() else {
+            debug!("Proof backfill from {} refused: too many sessions", peer);
+            continue;
+        };
+        let archive = archive.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve_stream(stream, &archive).await {
+                debug!("Proof backfill for {} failed: {}", peer, e);
+            }
+            drop(permit);
+        });
+    }
+}
+
+async fn serve_stream(mut stream: libp2p::Stream, archive: &Mutex<ProofArchive>) -> io::Result<()> {
+    let request: ProofBackfillRequest = tokio::time::timeout(PROOF_BACKFILL_TIMEOUT, read_message(&mut stream))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "request timed out"))??;
+    let segment = archive.lock().unwrap_or_else(|e| e.into_inner()).segment(&request);
+    let response = ProofBackfillResponse { proofs: segment.iter().map(ProofMessage::to_bytes).collect() };
+    write_message(&mut stream, &response).await?;
+    stream.close().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proof(prev: u8, new: u8, cumulative: u64) -> ProofMessage {
+        ProofMessage {
+            relay_pubkey: [1; 32],
+            pool_pubkey: [2; 32],
+            pool_type: PoolType::Subscribed,
+            batch_bytes: 10,
+            cumulative_bytes: cumulative,
+            prev_root: [prev; 32],
+            new_root: [new; 32],
+            proof: vec![],
+            timestamp: 0,
+            signature: vec![],
+        }
+    }
+
+    fn request(from: u8, to: u8) -> ProofBackfillRequest {
+        ProofBackfillRequest {
+            relay_pubkey: [1; 32],
+            pool_pubkey: [2; 32],
+            pool_type: PoolType::Subscribed,
+            from_root: [from; 32],
+            to_root: [to; 32],
+        }
+    }
+
+    #[test]
+    fn test_archive_segment_follows_chain() {
+        let mut archive = ProofArchive::new();
+        for (prev, new) in [(0, 1), (1, 2), (2, 3), (3, 4)] {
+            archive.insert(proof(prev, new, new as u64 * 10));
+        }
+        archive.insert(proof(1, 2, 20)); // seen before
+        assert_eq!(archive.len(), 4);
+
+        let segment = archive.segment(&request(1, 4));
+        let roots: Vec<u8> = segment.iter().map(|p| p.new_root[0]).collect();
+        assert_eq!(roots, vec![2, 3, 4]);
+
+        // Nothing chains from an unknown root
+        assert!(archive.segment(&request(9, 4)).is_empty());
+        // Other pool type: a different chain
+        let mut other = request(1, 4);
+        other.pool_type = PoolType::Free;
+        assert!(archive.segment(&other).is_empty());
+    }
+
+    #[test]
+    fn test_archive_segment_stops_at_missing_link() {
+        let mut archive = ProofArchive::new();
+        archive.insert(proof(0, 1, 10));
+        archive.insert(proof(2, 3, 30));
+        let segment = archive.segment(&request(0, 3));
+        assert_eq!(segment.len(), 1);
+        assert_eq!(segment[0].new_root, [1; 32]);
+    }
+}
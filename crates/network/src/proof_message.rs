@@ -1,12 +1,16 @@
 //! Proof gossipsub message types
 //!
 //! Relays gossip ZK-proven summaries (not individual receipts) via
-//! the `craftnet/proofs/1.0.0` gossipsub topic. An aggregator
-//! collects these and builds per-pool Merkle distributions for on-chain
-//! settlement.
+//! the `craftnet/proofs/1.0.0/<shard>` gossipsub topics, one shard per
+//! pool pubkey prefix. An aggregator subscribes to the shards of the pools
+//! it follows, collects these and builds per-pool Merkle distributions for
+//! on-chain settlement.
 
 use serde::{Deserialize, Serialize};
 
+use crate::protocol::PROTOCOL_VERSION;
+use crate::wire::{self, Extensions};
+
 /// Whether the user has an active subscription or is free-tier
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum PoolType {
@@ -47,14 +51,25 @@ pub struct ProofMessage {
 }
 
 impl ProofMessage {
-    /// Serialize to bytes (bincode)
+    /// Serialize to bytes (bincode body + [`Extensions`] trailer).
+    /// Announces this node's protocol version to aggregators.
     pub fn to_bytes(&self) -> Vec<u8> {
-        bincode::serialize(self).expect("ProofMessage serialization should not fail")
+        self.to_bytes_with_extensions(&Extensions::new().with_protocol_version(PROTOCOL_VERSION))
+    }
+
+    /// Serialize with extension fields
+    pub fn to_bytes_with_extensions(&self, extensions: &Extensions) -> Vec<u8> {
+        wire::encode(self, extensions)
     }
 
-    /// Deserialize from bytes (bincode)
+    /// Deserialize from bytes, ignoring extension fields
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, bincode::Error> {
-        bincode::deserialize(bytes)
+        Self::from_bytes_with_extensions(bytes).map(|(msg, _)| msg)
+    }
+
+    /// Deserialize from bytes along with the sender's extension fields
+    pub fn from_bytes_with_extensions(bytes: &[u8]) -> Result<(Self, Extensions), bincode::Error> {
+        wire::decode(bytes)
     }
 
     /// Data that gets signed by the relay (everything except signature)
@@ -127,6 +142,31 @@ impl ProofStateResponse {
     }
 }
 
+/// Ask for the proofs of a relay's chain between two roots.
+///
+/// Sent by an aggregator whose proofs for a (relay, pool) chain have been
+/// stuck behind a gap: it has the chain up to `from_root` and holds proofs
+/// starting at `to_root`. The relay (or another aggregator) answers with
+/// the signed proofs linking the two, which the aggregator verifies and
+/// applies like gossiped ones.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofBackfillRequest {
+    pub relay_pubkey: [u8; 32],
+    pub pool_pubkey: [u8; 32],
+    pub pool_type: PoolType,
+    /// Root the requester's chain ends at
+    pub from_root: [u8; 32],
+    /// Root its first held-back proof starts at
+    pub to_root: [u8; 32],
+}
+
+/// Response to a [`ProofBackfillRequest`]: encoded [`ProofMessage`]s in
+/// chain order, starting at `from_root` (empty if the segment is unknown)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct ProofBackfillResponse {
+    pub proofs: Vec<Vec<u8>>,
+}
+
 // =========================================================================
 // Aggregator history sync types
 // =========================================================================
@@ -299,6 +339,42 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_proof_message_extensions_forward_compatible() {
+        let msg = ProofMessage {
+            relay_pubkey: [1u8; 32],
+            pool_pubkey: [2u8; 32],
+            pool_type: PoolType::Subscribed,
+            batch_bytes: 100,
+            cumulative_bytes: 100,
+            prev_root: [0u8; 32],
+            new_root: [0xBB; 32],
+            proof: vec![0xCC; 8],
+            timestamp: 1700000000,
+            signature: vec![0xDD; 64],
+        };
+        // A newer relay sends a field this version has no tag for
+        let mut extensions = Extensions::new();
+        extensions.insert(0x7FFF, b"future field".to_vec());
+        let bytes = msg.to_bytes_with_extensions(&extensions);
+
+        let decoded = ProofMessage::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.new_root, msg.new_root);
+        assert_eq!(decoded.signable_data(), msg.signable_data());
+
+        let (_, ext) = ProofMessage::from_bytes_with_extensions(&msg.to_bytes()).unwrap();
+        assert_eq!(ext.protocol_version(), Some(PROTOCOL_VERSION));
+
+        // Peers from before versioning decode plain bincode
+        let legacy: ProofMessage = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(legacy.signature, msg.signature);
+
+        // And their messages still decode here, at version 0
+        let (decoded, ext) = ProofMessage::from_bytes_with_extensions(&bincode::serialize(&msg).unwrap()).unwrap();
+        assert_eq!(decoded.batch_bytes, 100);
+        assert_eq!(ext.version(), 0);
+    }
+
     #[test]
     fn test_proof_state_query_roundtrip() {
         let query = ProofStateQuery {
@@ -339,4 +415,32 @@ mod tests {
         let decoded = ProofStateResponse::from_bytes(&bytes).unwrap();
         assert!(!decoded.found);
     }
+
+    #[test]
+    fn test_signable_data_golden() {
+        // Shared with the prover and the aggregator (test-vectors/hashing.json)
+        let vectors: serde_json::Value =
+            serde_json::from_str(include_str!("../../../test-vectors/hashing.json")).unwrap();
+        let key = |v: &serde_json::Value| -> [u8; 32] {
+            hex::decode(v.as_str().unwrap()).unwrap().try_into().unwrap()
+        };
+        for v in vectors["proof_messages"].as_array().unwrap() {
+            let msg = ProofMessage {
+                relay_pubkey: key(&v["relay_pubkey"]),
+                pool_pubkey: key(&v["pool_pubkey"]),
+                pool_type: match v["pool_type"].as_str().unwrap() {
+                    "subscribed" => PoolType::Subscribed,
+                    _ => PoolType::Free,
+                },
+                batch_bytes: v["batch_bytes"].as_u64().unwrap(),
+                cumulative_bytes: v["cumulative_bytes"].as_u64().unwrap(),
+                prev_root: key(&v["prev_root"]),
+                new_root: key(&v["new_root"]),
+                proof: vec![],
+                timestamp: v["timestamp"].as_u64().unwrap(),
+                signature: vec![],
+            };
+            assert_eq!(hex::encode(msg.signable_data()), v["signable"].as_str().unwrap());
+        }
+    }
 }
@@ -0,0 +1,211 @@
+//! Direct proof submission
+//!
+//! Proofs normally reach aggregators over the proof gossip topic, but a
+//! relay in a small mesh (or behind a NAT with few connections) may have
+//! no peer subscribed to it, and its proofs would never arrive. When a
+//! publish fails that way the relay opens `/craftnet/proof-submit/1.0.0`
+//! to aggregators it knows from the aggregator registry and hands the
+//! proofs over directly.
+//!
+//! The proofs travel as their gossip encoding ([`ProofMessage`] wire bytes
+//! with extensions), so the aggregator runs them through the same checks
+//! as gossiped ones. A proof can arrive both ways; the aggregator answers
+//! a copy it already has with [`ProofSubmitStatus::Duplicate`].
+//!
+//! Wire format: `[len:4 BE][bincode message]`, one [`ProofSubmission`]
+//! then one [`ProofSubmitResponse`] per stream.
+//!
+//! [`ProofMessage`]: crate::ProofMessage
+
+use std::io;
+use std::time::Duration;
+
+use futures::{AsyncWriteExt, StreamExt};
+use libp2p::{PeerId, StreamProtocol};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot, Semaphore};
+use tracing::debug;
+
+use crate::registry::{read_message, write_message};
+use crate::NetworkError;
+
+/// Protocol identifier for direct proof submission
+pub const PROOF_SUBMIT_PROTOCOL: StreamProtocol = StreamProtocol::new("/craftnet/proof-submit/1.0.0");
+
+/// Most proofs in one submission
+pub const MAX_SUBMITTED_PROOFS: usize = 32;
+
+/// Submission streams served at once
+const MAX_SUBMIT_SESSIONS: usize = 32;
+
+/// Time allowed for a submission round trip (and for the node to answer it)
+const PROOF_SUBMIT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Proofs handed to an aggregator directly
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofSubmission {
+    /// Encoded proof messages, oldest first
+    pub proofs: Vec<Vec<u8>>,
+}
+
+/// What the aggregator did with one submitted proof
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProofSubmitStatus {
+    /// Applied, or held until the proofs it chains from arrive
+    Accepted,
+    /// Already received (by gossip or an earlier submission)
+    Duplicate,
+    /// Refused, with the reason
+    Rejected(String),
+}
+
+impl ProofSubmitStatus {
+    /// Whether the aggregator now has the proof
+    pub fn is_delivered(&self) -> bool {
+        matches!(self, Self::Accepted | Self::Duplicate)
+    }
+}
+
+/// The aggregator's answer, one status per submitted proof
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct ProofSubmitResponse {
+    pub results: Vec<ProofSubmitStatus>,
+}
+
+/// A submission waiting for the node to answer
+pub struct InboundProofSubmission {
+    /// Peer that opened the stream
+    pub peer: PeerId,
+    pub proofs: Vec<Vec<u8>>,
+    /// One status per proof, in order
+    pub reply: oneshot::Sender<Vec<ProofSubmitStatus>>,
+}
+
+/// Client side of the proof submission protocol
+#[derive(Clone)]
+pub struct ProofSubmitClient {
+    control: libp2p_stream::Control,
+}
+
+impl ProofSubmitClient {
+    pub fn new(control: libp2p_stream::Control) -> Self {
+        Self { control }
+    }
+
+    /// Hand `proofs` (at most [`MAX_SUBMITTED_PROOFS`]) to the aggregator
+    /// at `peer` and return its status for each
+    pub async fn submit(&mut self, peer: PeerId, proofs: Vec<Vec<u8>>) -> Result<Vec<ProofSubmitStatus>, NetworkError> {
+        if proofs.len() > MAX_SUBMITTED_PROOFS {
+            return Err(NetworkError::SendError(format!("{} proofs in one submission", proofs.len())));
+        }
+        let count = proofs.len();
+        let response: ProofSubmitResponse = tokio::time::timeout(PROOF_SUBMIT_TIMEOUT, async {
+            let mut stream = self
+                .control
+                .open_stream(peer, PROOF_SUBMIT_PROTOCOL)
+                .await
+                .map_err(|e| NetworkError::SendError(format!("proof submit open: {}", e)))?;
+            let response = async {
+                write_message(&mut stream, &ProofSubmission { proofs }).await?;
+                read_message(&mut stream).await
+            }
+            .await
+            .map_err(|e| NetworkError::SendError(format!("proof submit: {}", e)))?;
+            let _ = stream.close().await;
+            Ok::<_, NetworkError>(response)
+        })
+        .await
+        .map_err(|_| NetworkError::SendError("proof submit timed out".to_string()))??;
+
+        if response.results.len() != count {
+            return Err(NetworkError::SendError(format!(
+                "proof submit: {} results for {} proofs",
+                response.results.len(),
+                count,
+            )));
+        }
+        Ok(response.results)
+    }
+}
+
+/// Pass proof submissions from `incoming` to `submissions` and return
+/// the node's answers, until `incoming` ends
+pub async fn serve_proof_submissions(
+    mut incoming: libp2p_stream::IncomingStreams,
+    submissions: mpsc::Sender<InboundProofSubmission>,
+) {
+    let sessions = std::sync::Arc::new(Semaphore::new(MAX_SUBMIT_SESSIONS));
+    while let Some((peer, stream)) = incoming.next().await {
+        let Ok(permit) = sessions.clone().try_acquire_owned() else {
+            debug!("Proof submission from {} refused: too many sessions", peer);
+            continue;
+        };
+        let submissions = submissions.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve_stream(peer, stream, &submissions).await {
+                debug!("Proof submission from {} failed: {}", peer, e);
+            }
+            drop(permit);
+        });
+    }
+}
+
+async fn serve_stream(
+    peer: PeerId,
+    mut stream: libp2p::Stream,
+    submissions: &mpsc::Sender<InboundProofSubmission>,
+) -> io::Result<()> {
+    let timed_out = || io::Error::new(io::ErrorKind::TimedOut, "submission timed out");
+    let submission: ProofSubmission = tokio::time::timeout(PROOF_SUBMIT_TIMEOUT, read_message(&mut stream))
+        .await
+        .map_err(|_| timed_out())??;
+    if submission.proofs.len() > MAX_SUBMITTED_PROOFS {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "too many proofs"));
+    }
+
+    let count = submission.proofs.len();
+    let (reply, answer) = oneshot::channel();
+    let inbound = InboundProofSubmission { peer, proofs: submission.proofs, reply };
+    let results = match submissions.try_send(inbound) {
+        Ok(()) => tokio::time::timeout(PROOF_SUBMIT_TIMEOUT, answer)
+            .await
+            .map_err(|_| timed_out())?
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "node stopped"))?,
+        Err(_) => vec![ProofSubmitStatus::Rejected("busy".to_string()); count],
+    };
+    write_message(&mut stream, &ProofSubmitResponse { results }).await?;
+    stream.close().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_submission_roundtrip() {
+        let submission = ProofSubmission { proofs: vec![vec![1, 2, 3], vec![4; 300]] };
+        let mut buf = Vec::new();
+        write_message(&mut futures::io::Cursor::new(&mut buf), &submission).await.unwrap();
+        let decoded: ProofSubmission = read_message(&mut futures::io::Cursor::new(&buf)).await.unwrap();
+        assert_eq!(decoded, submission);
+
+        let response = ProofSubmitResponse {
+            results: vec![
+                ProofSubmitStatus::Accepted,
+                ProofSubmitStatus::Duplicate,
+                ProofSubmitStatus::Rejected("bad signature".to_string()),
+            ],
+        };
+        let mut buf = Vec::new();
+        write_message(&mut futures::io::Cursor::new(&mut buf), &response).await.unwrap();
+        let decoded: ProofSubmitResponse = read_message(&mut futures::io::Cursor::new(&buf)).await.unwrap();
+        assert_eq!(decoded, response);
+    }
+
+    #[test]
+    fn test_status_delivered() {
+        assert!(ProofSubmitStatus::Accepted.is_delivered());
+        assert!(ProofSubmitStatus::Duplicate.is_delivered());
+        assert!(!ProofSubmitStatus::Rejected("no".to_string()).is_delivered());
+    }
+}
@@ -5,11 +5,16 @@
 use std::io;
 
 use async_trait::async_trait;
+use bytes::{BufMut, Bytes};
+use chacha20poly1305::aead::AeadInPlace;
+use chacha20poly1305::{ChaCha20Poly1305, KeyInit, Nonce};
 use futures::{AsyncRead, AsyncWrite, AsyncReadExt, AsyncWriteExt};
 #[allow(unused_imports)]
 use libp2p::request_response::{self, Codec};
 use libp2p::StreamProtocol;
-use craftnet_core::{ForwardReceipt, Shard, SHARD_MAGIC, SHARD_VERSION};
+use craftnet_core::{ChainAck, ForwardReceipt, Shard, SHARD_MAGIC, SHARD_VERSION};
+
+use crate::buffer_pool::frame_pool;
 
 /// Protocol identifier for shard messages
 pub const SHARD_PROTOCOL_ID: StreamProtocol = StreamProtocol::new("/craftnet/shard/2.0.0");
@@ -17,6 +22,42 @@ pub const SHARD_PROTOCOL_ID: StreamProtocol = StreamProtocol::new("/craftnet/sha
 /// Maximum shard message size (10KB — onion header + payload per shard)
 pub const MAX_SHARD_SIZE: usize = 10 * 1024;
 
+/// Longest nack / rejection reason on the wire (bytes)
+const MAX_REASON_LEN: usize = 1024;
+
+/// Longest receipt in a shard response (bytes)
+const MAX_RECEIPT_LEN: usize = 4096;
+
+/// Longest user agent in a hello frame (bytes)
+const MAX_USER_AGENT_LEN: usize = 256;
+
+/// Read exactly `len` bytes.
+///
+/// The buffer grows as data arrives instead of being allocated from the
+/// length prefix, so a peer announcing a large frame and then stalling or
+/// hanging up costs only what it actually sent.
+async fn read_payload<T: AsyncRead + Unpin>(io: &mut T, len: usize) -> io::Result<Vec<u8>> {
+    let mut payload = Vec::with_capacity(len.min(4096));
+    (&mut *io).take(len as u64).read_to_end(&mut payload).await?;
+    if payload.len() < len {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("Frame truncated: {} of {} bytes", payload.len(), len),
+        ));
+    }
+    Ok(payload)
+}
+
+/// Longest prefix of `s` with at most `max` bytes that ends on a char
+/// boundary, so truncated strings still decode as UTF-8
+fn truncate_utf8(s: &str, max: usize) -> &str {
+    let mut end = s.len().min(max);
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
 /// Shard protocol handler (marker type for request-response behaviour)
 #[allow(dead_code)]
 #[derive(Debug, Clone, Default)]
@@ -120,8 +161,7 @@ impl Codec for ShardCodec {
         }
 
         // Read shard data
-        let mut data = vec![0u8; len];
-        io.read_exact(&mut data).await?;
+        let data = read_payload(io, len).await?;
 
         // Deserialize shard
         let shard = Shard::from_bytes(&data).map_err(|e| {
@@ -154,7 +194,7 @@ impl Codec for ShardCodec {
                 io.read_exact(&mut len_bytes).await?;
                 let len = u16::from_be_bytes(len_bytes) as usize;
 
-                if len > 1024 {
+                if len > MAX_REASON_LEN {
                     return Err(io::Error::new(
                         io::ErrorKind::InvalidData,
                         "Rejection reason too long",
@@ -162,8 +202,7 @@ impl Codec for ShardCodec {
                 }
 
                 // Read reason string
-                let mut reason_bytes = vec![0u8; len];
-                io.read_exact(&mut reason_bytes).await?;
+                let reason_bytes = read_payload(io, len).await?;
                 let reason = String::from_utf8(reason_bytes).map_err(|_| {
                     io::Error::new(io::ErrorKind::InvalidData, "Invalid UTF-8 in rejection reason")
                 })?;
@@ -176,15 +215,14 @@ impl Codec for ShardCodec {
                 io.read_exact(&mut receipt_len_bytes).await?;
                 let receipt_len = u32::from_be_bytes(receipt_len_bytes) as usize;
 
-                if receipt_len > 4096 {
+                if receipt_len > MAX_RECEIPT_LEN {
                     return Err(io::Error::new(
                         io::ErrorKind::InvalidData,
                         "Receipt too large",
                     ));
                 }
 
-                let mut receipt_bytes = vec![0u8; receipt_len];
-                io.read_exact(&mut receipt_bytes).await?;
+                let receipt_bytes = read_payload(io, receipt_len).await?;
 
                 let receipt: ForwardReceipt = bincode::deserialize(&receipt_bytes).map_err(|e| {
                     io::Error::new(io::ErrorKind::InvalidData, format!("Invalid receipt: {}", e))
@@ -265,10 +303,9 @@ impl Codec for ShardCodec {
                 io.write_all(&[1]).await?;
 
                 // Truncate reason if too long
-                let reason_bytes = reason.as_bytes();
-                let len = reason_bytes.len().min(1024) as u16;
-                io.write_all(&len.to_be_bytes()).await?;
-                io.write_all(&reason_bytes[..len as usize]).await?;
+                let reason_bytes = truncate_utf8(&reason, MAX_REASON_LEN).as_bytes();
+                io.write_all(&(reason_bytes.len() as u16).to_be_bytes()).await?;
+                io.write_all(reason_bytes).await?;
             }
         }
 
@@ -290,10 +327,107 @@ pub const SHARD_STREAM_PROTOCOL: libp2p::StreamProtocol =
 const FRAME_TYPE_SHARD: u8 = 0x01;
 const FRAME_TYPE_ACK: u8 = 0x02;
 const FRAME_TYPE_NACK: u8 = 0x03;
+const FRAME_TYPE_SEALED: u8 = 0x04;
+
+/// Nack reason sent by a node that is draining before shutdown.
+///
+/// The sender should stop routing new traffic through that peer rather
+/// than retry it.
+pub const NACK_DRAINING: &str = "Draining";
+
+/// Nack reason sent by a relay for a shard id it already forwarded.
+///
+/// The shard was replayed (or resent after a lost ack); it is not
+/// forwarded again.
+pub const NACK_REPLAY: &str = "Replay";
 
 /// Maximum frame payload size (64KB — generous for onion-wrapped shards)
 const MAX_FRAME_PAYLOAD: usize = 64 * 1024;
 
+/// Maximum sealed frame payload: a whole inner frame plus counter and AEAD tag
+const MAX_SEALED_PAYLOAD: usize = 8 + 1 + 4 + MAX_FRAME_PAYLOAD + 16;
+
+/// Domain separation for frame key derivation
+const FRAME_KEY_LABEL: &[u8] = b"craftnet/shard-stream/frame-key/v1";
+
+/// CraftNet protocol version this node speaks.
+///
+/// Announced in the hello frame that opens every outbound shard stream.
+/// Bumped when routing behaviour changes in a way peers must agree on.
+/// Version 2 answers a hello carrying a frame key (sealed frames).
+pub const PROTOCOL_VERSION: u16 = 2;
+
+/// User agent announced in the hello frame
+pub const USER_AGENT: &str = concat!("craftnet/", env!("CARGO_PKG_VERSION"));
+
+/// Seq id of the hello frame.
+///
+/// The hello travels as an ack frame for this seq id, which no shard ever
+/// uses: readers that predate it drop it as an ack for an unknown shard
+/// (and ignore the payload after the receipt flag).
+const HELLO_SEQ_ID: u64 = u64::MAX;
+
+/// Seq id of a chain ack frame.
+///
+/// Chain acks travel as ack frames for this reserved seq id, followed by
+/// `[ack_ref: 32][signature: 64]` after the receipt flag, so readers that
+/// predate them drop them like the hello.
+const CHAIN_ACK_SEQ_ID: u64 = u64::MAX - 1;
+
+/// Hello TLV tags (`[tag: u8] [length: u16 BE] [value]`); unknown tags are skipped
+const HELLO_TAG_VERSION: u8 = 0x01;
+const HELLO_TAG_USER_AGENT: u8 = 0x02;
+const HELLO_TAG_FRAME_KEY: u8 = 0x03;
+
+/// What a peer announced about itself in its hello frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerVersion {
+    /// 0 for peers that predate version negotiation
+    pub protocol_version: u16,
+    pub user_agent: Option<String>,
+    /// Ephemeral X25519 key offering sealed frames (see [`FrameKeyExchange`])
+    pub frame_key: Option<[u8; 32]>,
+}
+
+impl PeerVersion {
+    /// This node's version
+    pub fn local() -> Self {
+        Self { protocol_version: PROTOCOL_VERSION, user_agent: Some(USER_AGENT.to_string()), frame_key: None }
+    }
+
+    /// A peer that sent frames without a hello first
+    pub fn legacy() -> Self {
+        Self { protocol_version: 0, user_agent: None, frame_key: None }
+    }
+
+    fn decode(mut tlvs: &[u8]) -> io::Result<Self> {
+        let truncated = || io::Error::new(io::ErrorKind::InvalidData, "Hello TLV truncated");
+        let mut version = Self::legacy();
+        while !tlvs.is_empty() {
+            if tlvs.len() < 3 {
+                return Err(truncated());
+            }
+            let tag = tlvs[0];
+            let len = u16::from_be_bytes([tlvs[1], tlvs[2]]) as usize;
+            let value = tlvs.get(3..3 + len).ok_or_else(truncated)?;
+            match tag {
+                HELLO_TAG_VERSION if len == 2 => {
+                    version.protocol_version = u16::from_be_bytes([value[0], value[1]]);
+                }
+                HELLO_TAG_USER_AGENT => {
+                    version.user_agent = Some(String::from_utf8_lossy(value).into_owned());
+                }
+                HELLO_TAG_FRAME_KEY if len == 32 => {
+                    version.frame_key = Some(value.try_into().unwrap());
+                }
+                _ => {}
+            }
+            tlvs = &tlvs[3 + len..];
+        }
+        Ok(version)
+    }
+}
+
 /// A frame on a persistent shard stream.
 ///
 /// Wire format: `[type: u8] [length: u32 BE] [payload: length bytes]`
@@ -314,9 +448,22 @@ pub enum StreamFrame {
         seq_id: u64,
         reason: String,
     },
+    /// The sender's protocol version, first frame on a stream
+    Hello(PeerVersion),
+    /// A relay chain ack travelling back towards the client
+    ChainAck(ChainAck),
+    /// An encrypted frame, opened with the link's [`FrameCipher`]
+    Sealed {
+        counter: u64,
+        ciphertext: Bytes,
+    },
 }
 
 /// Read a single frame from an async stream (futures::io).
+///
+/// The payload is read into a buffer from the [`frame_pool`], which gets it
+/// back once the frame is decoded. A sealed frame keeps its buffer: the
+/// ciphertext is a view into it, opened in place by [`FrameCipher::open`].
 pub async fn read_frame<T: AsyncRead + Unpin>(io: &mut T) -> io::Result<StreamFrame> {
     // Read type byte
     let mut ty = [0u8; 1];
@@ -327,18 +474,39 @@ pub async fn read_frame<T: AsyncRead + Unpin>(io: &mut T) -> io::Result<StreamFr
     io.read_exact(&mut len_bytes).await?;
     let len = u32::from_be_bytes(len_bytes) as usize;
 
-    if len > MAX_FRAME_PAYLOAD {
+    let max_len = if ty[0] == FRAME_TYPE_SEALED { MAX_SEALED_PAYLOAD } else { MAX_FRAME_PAYLOAD };
+    if len > max_len {
         return Err(io::Error::new(
             io::ErrorKind::InvalidData,
-            format!("Frame payload too large: {} > {}", len, MAX_FRAME_PAYLOAD),
+            format!("Frame payload too large: {} > {}", len, max_len),
         ));
     }
 
     // Read payload
-    let mut payload = vec![0u8; len];
+    let mut payload = frame_pool().take(len);
+    payload.resize(len, 0);
     io.read_exact(&mut payload).await?;
 
-    match ty[0] {
+    if ty[0] == FRAME_TYPE_SEALED {
+        if payload.len() < 8 + 16 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Sealed frame too short",
+            ));
+        }
+        let counter = u64::from_be_bytes(payload[..8].try_into().unwrap());
+        let ciphertext = payload.freeze().slice(8..);
+        return Ok(StreamFrame::Sealed { counter, ciphertext });
+    }
+
+    let frame = decode_frame(ty[0], &payload);
+    frame_pool().give_mut(payload);
+    frame
+}
+
+/// Decode the payload of a plaintext frame of type `ty`
+fn decode_frame(ty: u8, payload: &[u8]) -> io::Result<StreamFrame> {
+    match ty {
         FRAME_TYPE_SHARD => {
             if payload.len() < 8 {
                 return Err(io::Error::new(
@@ -361,6 +529,18 @@ pub async fn read_frame<T: AsyncRead + Unpin>(io: &mut T) -> io::Result<StreamFr
                 ));
             }
             let seq_id = u64::from_be_bytes(payload[..8].try_into().unwrap());
+            if seq_id == HELLO_SEQ_ID {
+                return PeerVersion::decode(&payload[9..]).map(StreamFrame::Hello);
+            }
+            if seq_id == CHAIN_ACK_SEQ_ID {
+                if payload.len() < 9 + 32 + 64 {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "Chain ack frame too short"));
+                }
+                return Ok(StreamFrame::ChainAck(ChainAck {
+                    ack_ref: payload[9..41].try_into().unwrap(),
+                    signature: payload[41..105].try_into().unwrap(),
+                }));
+            }
             let has_receipt = payload[8];
             let receipt = if has_receipt == 1 && payload.len() > 9 {
                 let receipt: ForwardReceipt =
@@ -400,28 +580,17 @@ pub async fn read_frame<T: AsyncRead + Unpin>(io: &mut T) -> io::Result<StreamFr
         }
         _ => Err(io::Error::new(
             io::ErrorKind::InvalidData,
-            format!("Unknown frame type: 0x{:02x}", ty[0]),
+            format!("Unknown frame type: 0x{:02x}", ty),
         )),
     }
 }
 
-/// Write a shard frame to an async stream.
+/// Encode a shard frame: `[type:1][length:4][seq_id:8][shard_bytes:N]`.
 ///
-/// Builds the entire frame in memory first, then writes it in a single
-/// `write_all` call. This prevents stream desync if the connection dies
-/// mid-frame (partial header would leave the reader misaligned).
-pub async fn write_shard_frame<T: AsyncWrite + Unpin>(
-    io: &mut T,
-    shard: &Shard,
-    seq_id: u64,
-) -> io::Result<()> {
-    let shard_bytes = shard.to_bytes().map_err(|e| {
-        io::Error::new(
-            io::ErrorKind::InvalidData,
-            format!("Failed to serialize shard: {}", e),
-        )
-    })?;
-    let payload_len = 8 + shard_bytes.len();
+/// The shard is serialized straight into a pooled frame buffer.
+pub fn encode_shard_frame(shard: &Shard, seq_id: u64) -> io::Result<Bytes> {
+    let shard_len = shard.encoded_len();
+    let payload_len = shard_len.saturating_add(8);
 
     // Enforce the same limit the reader enforces — fail fast, don't desync
     if payload_len > MAX_FRAME_PAYLOAD {
@@ -429,31 +598,39 @@ pub async fn write_shard_frame<T: AsyncWrite + Unpin>(
             io::ErrorKind::InvalidData,
             format!(
                 "Shard frame payload too large: {} > {} (shard_bytes={})",
-                payload_len, MAX_FRAME_PAYLOAD, shard_bytes.len()
+                payload_len, MAX_FRAME_PAYLOAD, shard_len
             ),
         ));
     }
 
-    // Build complete frame in one buffer: [type:1][length:4][seq_id:8][shard_bytes:N]
-    let frame_len = 1 + 4 + payload_len;
-    let mut buf = Vec::with_capacity(frame_len);
-    buf.push(FRAME_TYPE_SHARD);
-    buf.extend_from_slice(&(payload_len as u32).to_be_bytes());
-    buf.extend_from_slice(&seq_id.to_be_bytes());
-    buf.extend_from_slice(&shard_bytes);
-
-    io.write_all(&buf).await?;
-    io.flush().await?;
-
-    Ok(())
+    let mut buf = frame_pool().take(1 + 4 + payload_len);
+    buf.put_u8(FRAME_TYPE_SHARD);
+    buf.put_u32(payload_len as u32);
+    buf.put_u64(seq_id);
+    bincode::serialize_into((&mut buf).writer(), shard).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Failed to serialize shard: {}", e),
+        )
+    })?;
+    Ok(buf.freeze())
 }
 
-/// Write an ack frame to an async stream (atomic single write).
-pub async fn write_ack_frame<T: AsyncWrite + Unpin>(
+/// Write a shard frame to an async stream.
+///
+/// Builds the entire frame in memory first, then writes it in a single
+/// `write_all` call. This prevents stream desync if the connection dies
+/// mid-frame (partial header would leave the reader misaligned).
+pub async fn write_shard_frame<T: AsyncWrite + Unpin>(
     io: &mut T,
+    shard: &Shard,
     seq_id: u64,
-    receipt: Option<&ForwardReceipt>,
 ) -> io::Result<()> {
+    write_encoded(io, encode_shard_frame(shard, seq_id)?).await
+}
+
+/// Encode an ack frame: `[type:1][length:4][seq_id:8][has_receipt:1][receipt]`.
+pub fn encode_ack_frame(seq_id: u64, receipt: Option<&ForwardReceipt>) -> io::Result<Bytes> {
     let receipt_bytes = match receipt {
         Some(r) => bincode::serialize(r).map_err(|e| {
             io::Error::new(
@@ -473,18 +650,95 @@ pub async fn write_ack_frame<T: AsyncWrite + Unpin>(
         ));
     }
 
-    let frame_len = 1 + 4 + payload_len;
-    let mut buf = Vec::with_capacity(frame_len);
-    buf.push(FRAME_TYPE_ACK);
-    buf.extend_from_slice(&(payload_len as u32).to_be_bytes());
-    buf.extend_from_slice(&seq_id.to_be_bytes());
-    buf.push(has_receipt);
+    let mut buf = frame_pool().take(1 + 4 + payload_len);
+    buf.put_u8(FRAME_TYPE_ACK);
+    buf.put_u32(payload_len as u32);
+    buf.put_u64(seq_id);
+    buf.put_u8(has_receipt);
     buf.extend_from_slice(&receipt_bytes);
+    Ok(buf.freeze())
+}
+
+/// Write an ack frame to an async stream (atomic single write).
+pub async fn write_ack_frame<T: AsyncWrite + Unpin>(
+    io: &mut T,
+    seq_id: u64,
+    receipt: Option<&ForwardReceipt>,
+) -> io::Result<()> {
+    write_encoded(io, encode_ack_frame(seq_id, receipt)?).await
+}
+
+/// Encode a hello frame announcing `version`.
+pub fn encode_hello_frame(version: &PeerVersion) -> Bytes {
+    let mut tlvs = Vec::new();
+    tlvs.push(HELLO_TAG_VERSION);
+    tlvs.extend_from_slice(&2u16.to_be_bytes());
+    tlvs.extend_from_slice(&version.protocol_version.to_be_bytes());
+    if let Some(agent) = &version.user_agent {
+        let agent = truncate_utf8(agent, MAX_USER_AGENT_LEN).as_bytes();
+        tlvs.push(HELLO_TAG_USER_AGENT);
+        tlvs.extend_from_slice(&(agent.len() as u16).to_be_bytes());
+        tlvs.extend_from_slice(agent);
+    }
+    if let Some(key) = &version.frame_key {
+        tlvs.push(HELLO_TAG_FRAME_KEY);
+        tlvs.extend_from_slice(&(key.len() as u16).to_be_bytes());
+        tlvs.extend_from_slice(key);
+    }
+    let payload_len = 8 + 1 + tlvs.len();
+
+    let mut buf = frame_pool().take(1 + 4 + payload_len);
+    buf.put_u8(FRAME_TYPE_ACK);
+    buf.put_u32(payload_len as u32);
+    buf.put_u64(HELLO_SEQ_ID);
+    buf.put_u8(0); // no receipt
+    buf.extend_from_slice(&tlvs);
+    buf.freeze()
+}
+
+/// Write a hello frame announcing `version` (atomic single write).
+pub async fn write_hello_frame<T: AsyncWrite + Unpin>(
+    io: &mut T,
+    version: &PeerVersion,
+) -> io::Result<()> {
+    write_encoded(io, encode_hello_frame(version)).await
+}
+
+/// Encode a chain ack frame.
+pub fn encode_chain_ack_frame(ack: &ChainAck) -> Bytes {
+    let payload_len = 8 + 1 + 32 + 64;
+
+    let mut buf = frame_pool().take(1 + 4 + payload_len);
+    buf.put_u8(FRAME_TYPE_ACK);
+    buf.put_u32(payload_len as u32);
+    buf.put_u64(CHAIN_ACK_SEQ_ID);
+    buf.put_u8(0); // no receipt
+    buf.extend_from_slice(&ack.ack_ref);
+    buf.extend_from_slice(&ack.signature);
+    buf.freeze()
+}
 
-    io.write_all(&buf).await?;
-    io.flush().await?;
+/// Write a chain ack frame (atomic single write).
+pub async fn write_chain_ack_frame<T: AsyncWrite + Unpin>(
+    io: &mut T,
+    ack: &ChainAck,
+) -> io::Result<()> {
+    write_encoded(io, encode_chain_ack_frame(ack)).await
+}
+
+/// Encode a nack frame: `[type:1][length:4][seq_id:8][reason_len:2][reason]`.
+pub fn encode_nack_frame(seq_id: u64, reason: &str) -> Bytes {
+    let reason_bytes = truncate_utf8(reason, MAX_REASON_LEN).as_bytes();
+    let reason_len = reason_bytes.len();
+    let payload_len = 8 + 2 + reason_len;
 
-    Ok(())
+    let mut buf = frame_pool().take(1 + 4 + payload_len);
+    buf.put_u8(FRAME_TYPE_NACK);
+    buf.put_u32(payload_len as u32);
+    buf.put_u64(seq_id);
+    buf.put_u16(reason_len as u16);
+    buf.extend_from_slice(reason_bytes);
+    buf.freeze()
 }
 
 /// Write a nack frame to an async stream (atomic single write).
@@ -493,22 +747,166 @@ pub async fn write_nack_frame<T: AsyncWrite + Unpin>(
     seq_id: u64,
     reason: &str,
 ) -> io::Result<()> {
-    let reason_bytes = reason.as_bytes();
-    let reason_len = reason_bytes.len().min(1024);
-    let payload_len = 8 + 2 + reason_len;
+    write_encoded(io, encode_nack_frame(seq_id, reason)).await
+}
 
-    let frame_len = 1 + 4 + payload_len;
-    let mut buf = Vec::with_capacity(frame_len);
-    buf.push(FRAME_TYPE_NACK);
-    buf.extend_from_slice(&(payload_len as u32).to_be_bytes());
-    buf.extend_from_slice(&seq_id.to_be_bytes());
-    buf.extend_from_slice(&(reason_len as u16).to_be_bytes());
-    buf.extend_from_slice(&reason_bytes[..reason_len]);
+/// Write an already encoded frame in a single `write_all`, then flush.
+///
+/// The frame's buffer goes back to the [`frame_pool`] afterwards.
+pub async fn write_encoded<T: AsyncWrite + Unpin>(io: &mut T, frame: Bytes) -> io::Result<()> {
+    let result = async {
+        io.write_all(&frame).await?;
+        io.flush().await
+    }
+    .await;
+    frame_pool().give(frame);
+    result
+}
 
-    io.write_all(&buf).await?;
-    io.flush().await?;
+// ============================================================================
+// Frame encryption
+// ============================================================================
 
-    Ok(())
+/// Derives a link's frame key from an X25519 exchange in the hello frames.
+///
+/// The opener of a stream puts [`FrameKeyExchange::public_key`] in its hello
+/// ([`PeerVersion::frame_key`]); a receiver that supports sealed frames
+/// answers with a hello carrying its own key on the reverse half of the same
+/// stream. Both sides then derive the same [`FrameCipher`], and everything
+/// the opener writes afterwards travels in sealed frames. Keys are fresh per
+/// stream, so a reopened stream never reuses a key.
+pub struct FrameKeyExchange {
+    secret: [u8; 32],
+    public: [u8; 32],
+}
+
+impl FrameKeyExchange {
+    /// Generate an ephemeral key pair
+    pub fn generate() -> Self {
+        let keypair = craftec_crypto::EncryptionKeypair::generate();
+        Self { secret: keypair.secret_key_bytes(), public: keypair.public_key_bytes() }
+    }
+
+    /// Key to announce in the hello
+    pub fn public_key(&self) -> [u8; 32] {
+        self.public
+    }
+
+    /// Cipher for the stream we opened, given the receiver's reply key
+    pub fn opener_cipher(self, responder_key: &[u8; 32]) -> io::Result<FrameCipher> {
+        self.derive(&self.public, responder_key, responder_key)
+    }
+
+    /// Cipher for a peer's stream, given the key in the opener's hello
+    pub fn responder_cipher(self, opener_key: &[u8; 32]) -> io::Result<FrameCipher> {
+        self.derive(opener_key, &self.public, opener_key)
+    }
+
+    fn derive(&self, opener_key: &[u8; 32], responder_key: &[u8; 32], peer_key: &[u8; 32]) -> io::Result<FrameCipher> {
+        use sha2::{Digest, Sha256};
+
+        let shared = x25519_dalek::x25519(self.secret, *peer_key);
+        // A low-order peer key forces an all-zero secret
+        if shared == [0u8; 32] {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Weak frame key"));
+        }
+        let mut hasher = Sha256::new();
+        hasher.update(FRAME_KEY_LABEL);
+        hasher.update(shared);
+        hasher.update(opener_key);
+        hasher.update(responder_key);
+        let key: [u8; 32] = hasher.finalize().into();
+        Ok(FrameCipher::new(&key))
+    }
+}
+
+/// Seals and opens the frames of one stream direction.
+///
+/// Each sealed frame carries a counter that starts at 0 and goes up by one
+/// per frame. The counter is the AEAD nonce, so a frame only opens at the
+/// position it was sealed for: replayed, dropped or reordered frames fail.
+pub struct FrameCipher {
+    aead: ChaCha20Poly1305,
+    counter: u64,
+}
+
+impl FrameCipher {
+    fn new(key: &[u8; 32]) -> Self {
+        Self { aead: ChaCha20Poly1305::new((&key[..]).into()), counter: 0 }
+    }
+
+    fn nonce(counter: u64) -> Nonce {
+        let mut nonce = [0u8; 12];
+        nonce[4..].copy_from_slice(&counter.to_be_bytes());
+        nonce.into()
+    }
+
+    /// Wrap an encoded frame in a sealed frame: `[type:1][length:4][counter:8][ciphertext][tag:16]`.
+    ///
+    /// Encrypts in place in a pooled buffer; `frame`'s buffer goes back to the pool.
+    pub fn seal(&mut self, frame: Bytes) -> io::Result<Bytes> {
+        let counter = self.counter;
+        let counter_bytes = counter.to_be_bytes();
+        let payload_len = 8 + frame.len() + 16;
+
+        let mut buf = frame_pool().take(1 + 4 + payload_len);
+        buf.put_u8(FRAME_TYPE_SEALED);
+        buf.put_u32(payload_len as u32);
+        buf.extend_from_slice(&counter_bytes);
+        buf.extend_from_slice(&frame);
+        frame_pool().give(frame);
+
+        let tag = self
+            .aead
+            .encrypt_in_place_detached(&Self::nonce(counter), &counter_bytes, &mut buf[13..])
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Frame seal failed"))?;
+        buf.extend_from_slice(&tag);
+        self.counter += 1;
+        Ok(buf.freeze())
+    }
+
+    /// Open a [`StreamFrame::Sealed`] and decode the frame inside
+    pub fn open(&mut self, counter: u64, ciphertext: Bytes) -> io::Result<StreamFrame> {
+        if counter != self.counter {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Sealed frame out of order: counter {} (expected {})", counter, self.counter),
+            ));
+        }
+        // Decrypt in the read buffer unless someone else still holds it
+        let mut buf = ciphertext.try_into_mut().unwrap_or_else(|shared| {
+            let mut copy = frame_pool().take(shared.len());
+            copy.extend_from_slice(&shared);
+            copy
+        });
+        if buf.len() < 16 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Sealed frame too short"));
+        }
+        let tag = buf.split_off(buf.len() - 16);
+        self.aead
+            .decrypt_in_place_detached(&Self::nonce(counter), &counter.to_be_bytes(), &mut buf, tag[..].into())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Sealed frame failed authentication"))?;
+        self.counter += 1;
+
+        let frame = Self::decode_inner(&buf);
+        frame_pool().give_mut(buf);
+        frame
+    }
+
+    /// Decode the plaintext frame inside a sealed frame
+    fn decode_inner(frame: &[u8]) -> io::Result<StreamFrame> {
+        if frame.len() < 5 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Sealed frame content truncated"));
+        }
+        let len = u32::from_be_bytes(frame[1..5].try_into().unwrap()) as usize;
+        if len != frame.len() - 5 || len > MAX_FRAME_PAYLOAD {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Sealed frame content has a bad length"));
+        }
+        if frame[0] == FRAME_TYPE_SEALED {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Nested sealed frame"));
+        }
+        decode_frame(frame[0], &frame[5..])
+    }
 }
 
 #[cfg(test)]
@@ -745,7 +1143,7 @@ mod tests {
             StreamFrame::Shard { seq_id, shard: decoded } => {
                 assert_eq!(seq_id, 42);
                 assert_eq!(decoded.ephemeral_pubkey, [1u8; 32]);
-                assert_eq!(decoded.payload, b"stream payload");
+                assert_eq!(decoded.payload, &b"stream payload"[..]);
             }
             _ => panic!("Expected Shard frame"),
         }
@@ -823,6 +1221,76 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_stream_hello_frame_roundtrip() {
+        let mut buffer = Vec::new();
+        {
+            let mut cursor = futures::io::Cursor::new(&mut buffer);
+            write_hello_frame(&mut cursor, &PeerVersion::local()).await.unwrap();
+            write_ack_frame(&mut cursor, 0, None).await.unwrap();
+        }
+
+        let mut cursor = futures::io::Cursor::new(&buffer);
+        match read_frame(&mut cursor).await.unwrap() {
+            StreamFrame::Hello(version) => {
+                assert_eq!(version.protocol_version, PROTOCOL_VERSION);
+                assert_eq!(version.user_agent.as_deref(), Some(USER_AGENT));
+            }
+            _ => panic!("Expected Hello frame"),
+        }
+        assert!(matches!(read_frame(&mut cursor).await.unwrap(), StreamFrame::Ack { seq_id: 0, .. }));
+
+        // Readers that predate the hello see a receipt-less ack
+        assert_eq!(buffer[0], FRAME_TYPE_ACK);
+        assert_eq!(buffer[5..13], HELLO_SEQ_ID.to_be_bytes());
+        assert_eq!(buffer[13], 0);
+    }
+
+    #[tokio::test]
+    async fn test_stream_chain_ack_frame_roundtrip() {
+        let ack = ChainAck { ack_ref: [7u8; 32], signature: [9u8; 64] };
+        let mut buffer = Vec::new();
+        {
+            let mut cursor = futures::io::Cursor::new(&mut buffer);
+            write_chain_ack_frame(&mut cursor, &ack).await.unwrap();
+        }
+
+        let mut cursor = futures::io::Cursor::new(&buffer);
+        match read_frame(&mut cursor).await.unwrap() {
+            StreamFrame::ChainAck(read) => assert_eq!(read, ack),
+            _ => panic!("Expected ChainAck frame"),
+        }
+
+        // Readers that predate chain acks see a receipt-less ack
+        assert_eq!(buffer[0], FRAME_TYPE_ACK);
+        assert_eq!(buffer[5..13], CHAIN_ACK_SEQ_ID.to_be_bytes());
+        assert_eq!(buffer[13], 0);
+
+        // Truncated acks are rejected
+        let mut short = buffer[..buffer.len() - 1].to_vec();
+        short[1..5].copy_from_slice(&((buffer.len() - 6) as u32).to_be_bytes());
+        assert!(read_frame(&mut futures::io::Cursor::new(&short)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stream_hello_skips_unknown_tags() {
+        let mut payload = HELLO_SEQ_ID.to_be_bytes().to_vec();
+        payload.push(0);
+        payload.extend_from_slice(&[0x7F, 0, 3, 1, 2, 3]); // future tag
+        payload.extend_from_slice(&[HELLO_TAG_VERSION, 0, 2, 0, 9]);
+        let mut buffer = vec![FRAME_TYPE_ACK];
+        buffer.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        buffer.extend_from_slice(&payload);
+
+        let mut cursor = futures::io::Cursor::new(&buffer);
+        match read_frame(&mut cursor).await.unwrap() {
+            StreamFrame::Hello(version) => {
+                assert_eq!(version, PeerVersion { protocol_version: 9, user_agent: None, frame_key: None });
+            }
+            _ => panic!("Expected Hello frame"),
+        }
+    }
+
     #[tokio::test]
     async fn test_stream_unknown_frame_type() {
         let mut buffer = Vec::new();
@@ -847,4 +1315,214 @@ mod tests {
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("too large"));
     }
+
+    #[tokio::test]
+    async fn test_stream_truncated_frame_is_eof() {
+        // Announces a full-size frame, then hangs up after 3 bytes
+        let mut buffer = vec![FRAME_TYPE_SHARD];
+        buffer.extend_from_slice(&(MAX_FRAME_PAYLOAD as u32).to_be_bytes());
+        buffer.extend_from_slice(&[1, 2, 3]);
+
+        let err = read_frame(&mut futures::io::Cursor::new(&buffer)).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_truncate_utf8() {
+        assert_eq!(truncate_utf8("héllo", 2), "h");
+        assert_eq!(truncate_utf8("héllo", 3), "hé");
+        assert_eq!(truncate_utf8("abc", 10), "abc");
+    }
+
+    #[tokio::test]
+    async fn test_stream_hello_frame_key_roundtrip() {
+        let exchange = FrameKeyExchange::generate();
+        let hello = PeerVersion { frame_key: Some(exchange.public_key()), ..PeerVersion::local() };
+        let mut buffer = Vec::new();
+        write_hello_frame(&mut futures::io::Cursor::new(&mut buffer), &hello).await.unwrap();
+
+        match read_frame(&mut futures::io::Cursor::new(&buffer)).await.unwrap() {
+            StreamFrame::Hello(read) => assert_eq!(read, hello),
+            other => panic!("Expected Hello, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sealed_frames() {
+        let opener = FrameKeyExchange::generate();
+        let responder = FrameKeyExchange::generate();
+        let (opener_key, responder_key) = (opener.public_key(), responder.public_key());
+        let mut sealer = opener.opener_cipher(&responder_key).unwrap();
+        let mut opener_side = responder.responder_cipher(&opener_key).unwrap();
+
+        let first = sealer.seal(encode_nack_frame(7, "Busy")).unwrap();
+        let second = sealer.seal(encode_ack_frame(8, None).unwrap()).unwrap();
+        // Frame contents are not visible on the wire
+        assert!(!first.windows(4).any(|w| w == b"Busy"));
+
+        let read_sealed = |bytes: Bytes| async move {
+            match read_frame(&mut futures::io::Cursor::new(bytes)).await.unwrap() {
+                StreamFrame::Sealed { counter, ciphertext } => (counter, ciphertext),
+                other => panic!("Expected Sealed, got {:?}", other),
+            }
+        };
+        let (counter, ciphertext) = read_sealed(first.clone()).await;
+        match opener_side.open(counter, ciphertext).unwrap() {
+            StreamFrame::Nack { seq_id, reason } => assert_eq!((seq_id, reason.as_str()), (7, "Busy")),
+            other => panic!("Expected Nack, got {:?}", other),
+        }
+
+        // Replaying the first frame is rejected
+        let (counter, ciphertext) = read_sealed(first.clone()).await;
+        assert!(opener_side.open(counter, ciphertext).is_err());
+
+        // So is a tampered frame at the right position
+        let (counter, ciphertext) = read_sealed(second).await;
+        let mut tampered = ciphertext.to_vec();
+        tampered[0] ^= 1;
+        assert!(opener_side.open(counter, Bytes::from(tampered)).is_err());
+
+        // A key agreed with someone else cannot open it
+        let mut stranger = FrameKeyExchange::generate().responder_cipher(&opener_key).unwrap();
+        let (counter, ciphertext) = read_sealed(first).await;
+        assert!(stranger.open(counter, ciphertext).is_err());
+    }
+
+    #[test]
+    fn test_weak_frame_key_rejected() {
+        assert!(FrameKeyExchange::generate().opener_cipher(&[0u8; 32]).is_err());
+    }
+
+    mod prop {
+        use super::super::*;
+        use futures::executor::block_on;
+        use proptest::prelude::*;
+
+        fn arb_shard() -> impl Strategy<Value = Shard> {
+            (
+                any::<[u8; 32]>(),
+                proptest::collection::vec(any::<u8>(), 0..512),
+                proptest::collection::vec(any::<u8>(), 0..2048),
+                proptest::collection::vec(any::<u8>(), 0..128),
+                any::<u8>(),
+                any::<u8>(),
+            )
+                .prop_map(|(ephemeral, header, payload, tag, total, remaining)| {
+                    Shard::new(ephemeral, header, payload, tag, total, remaining)
+                })
+        }
+
+        fn arb_receipt() -> impl Strategy<Value = ForwardReceipt> {
+            (any::<[u8; 32]>(), any::<[u8; 32]>(), any::<u32>(), any::<u64>()).prop_map(
+                |(shard_id, pool_pubkey, payload_size, timestamp)| ForwardReceipt {
+                    shard_id,
+                    sender_pubkey: [1u8; 32],
+                    receiver_pubkey: [2u8; 32],
+                    pool_pubkey,
+                    payload_size,
+                    timestamp,
+                    signature: [3u8; 64],
+                },
+            )
+        }
+
+        proptest! {
+            #[test]
+            fn shard_frame_roundtrip(shard in arb_shard(), seq in any::<u64>()) {
+                let mut buffer = Vec::new();
+                block_on(write_shard_frame(&mut futures::io::Cursor::new(&mut buffer), &shard, seq)).unwrap();
+                match block_on(read_frame(&mut futures::io::Cursor::new(&buffer))).unwrap() {
+                    StreamFrame::Shard { seq_id, shard: read } => {
+                        prop_assert_eq!(seq_id, seq);
+                        prop_assert_eq!(read.to_bytes().unwrap(), shard.to_bytes().unwrap());
+                    }
+                    other => prop_assert!(false, "unexpected frame {:?}", other),
+                }
+            }
+
+            #[test]
+            fn ack_frame_roundtrip(receipt in proptest::option::of(arb_receipt()), seq in 0..CHAIN_ACK_SEQ_ID) {
+                let mut buffer = Vec::new();
+                block_on(write_ack_frame(&mut futures::io::Cursor::new(&mut buffer), seq, receipt.as_ref())).unwrap();
+                match block_on(read_frame(&mut futures::io::Cursor::new(&buffer))).unwrap() {
+                    StreamFrame::Ack { seq_id, receipt: read } => {
+                        prop_assert_eq!(seq_id, seq);
+                        prop_assert_eq!(read.map(|r| r.shard_id), receipt.map(|r| r.shard_id));
+                    }
+                    other => prop_assert!(false, "unexpected frame {:?}", other),
+                }
+            }
+
+            #[test]
+            fn nack_frame_roundtrip(reason in ".{0,1100}", seq in any::<u64>()) {
+                let mut buffer = Vec::new();
+                block_on(write_nack_frame(&mut futures::io::Cursor::new(&mut buffer), seq, &reason)).unwrap();
+                match block_on(read_frame(&mut futures::io::Cursor::new(&buffer))).unwrap() {
+                    StreamFrame::Nack { seq_id, reason: read } => {
+                        prop_assert_eq!(seq_id, seq);
+                        prop_assert_eq!(read.as_str(), truncate_utf8(&reason, MAX_REASON_LEN));
+                    }
+                    other => prop_assert!(false, "unexpected frame {:?}", other),
+                }
+            }
+
+            #[test]
+            fn hello_frame_roundtrip(version in any::<u16>(), agent in proptest::option::of(".{0,300}")) {
+                let hello = PeerVersion { protocol_version: version, user_agent: agent.clone(), frame_key: None };
+                let mut buffer = Vec::new();
+                block_on(write_hello_frame(&mut futures::io::Cursor::new(&mut buffer), &hello)).unwrap();
+                match block_on(read_frame(&mut futures::io::Cursor::new(&buffer))).unwrap() {
+                    StreamFrame::Hello(read) => {
+                        prop_assert_eq!(read.protocol_version, version);
+                        prop_assert_eq!(read.user_agent.as_deref(), agent.as_deref().map(|a| truncate_utf8(a, MAX_USER_AGENT_LEN)));
+                    }
+                    other => prop_assert!(false, "unexpected frame {:?}", other),
+                }
+            }
+
+            #[test]
+            fn truncated_frames_error(shard in arb_shard(), cut in any::<prop::sample::Index>()) {
+                let mut buffer = Vec::new();
+                block_on(write_shard_frame(&mut futures::io::Cursor::new(&mut buffer), &shard, 1)).unwrap();
+                let truncated = &buffer[..cut.index(buffer.len())];
+                prop_assert!(block_on(read_frame(&mut futures::io::Cursor::new(truncated))).is_err());
+            }
+
+            #[test]
+            fn read_frame_never_panics(bytes in proptest::collection::vec(any::<u8>(), 0..4096)) {
+                let mut cursor = futures::io::Cursor::new(&bytes);
+                while block_on(read_frame(&mut cursor)).is_ok() {}
+            }
+
+            #[test]
+            fn adversarial_length_prefix(ty in 0u8..5, len in any::<u32>(), body in proptest::collection::vec(any::<u8>(), 0..256)) {
+                let mut buffer = vec![ty];
+                buffer.extend_from_slice(&len.to_be_bytes());
+                buffer.extend_from_slice(&body);
+                let result = block_on(read_frame(&mut futures::io::Cursor::new(&buffer)));
+                if len as usize > body.len() {
+                    prop_assert!(result.is_err());
+                }
+            }
+
+            #[test]
+            fn codec_never_panics(bytes in proptest::collection::vec(any::<u8>(), 0..4096)) {
+                let mut codec = ShardCodec::new();
+                let _ = block_on(codec.read_request(&SHARD_PROTOCOL_ID, &mut futures::io::Cursor::new(&bytes)));
+                let _ = block_on(codec.read_response(&SHARD_PROTOCOL_ID, &mut futures::io::Cursor::new(&bytes)));
+            }
+
+            #[test]
+            fn rejection_reason_roundtrip(reason in ".{0,1100}") {
+                let mut codec = ShardCodec::new();
+                let mut buffer = Vec::new();
+                block_on(codec.write_response(&SHARD_PROTOCOL_ID, &mut futures::io::Cursor::new(&mut buffer), ShardResponse::Rejected(reason.clone()))).unwrap();
+                let read = block_on(ShardCodec::new().read_response(&SHARD_PROTOCOL_ID, &mut futures::io::Cursor::new(&buffer))).unwrap();
+                match read {
+                    ShardResponse::Rejected(read) => prop_assert_eq!(read.as_str(), truncate_utf8(&reason, MAX_REASON_LEN)),
+                    other => prop_assert!(false, "unexpected response {:?}", other),
+                }
+            }
+        }
+    }
 }
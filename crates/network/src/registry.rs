@@ -0,0 +1,616 @@
+//! Exit/relay registry delta sync
+//!
+//! Walking the DHT registry means one provider query per shard plus one
+//! record lookup per provider, which gets slow past a few thousand nodes.
+//! Nodes that already hold the records can hand them over directly:
+//! a client opens `/craftnet/registry-sync/1.0.0` to a connected peer and
+//! asks for the records of one kind (and optionally a subset of shards)
+//! that the peer received since the last sync and that are not in the
+//! client's bloom filter of records it already has. Results are paged with
+//! a key cursor over the same stream.
+//!
+//! Records travel as the original `SignedDhtRecord` bytes, so the receiving
+//! [`RegistryStore`] runs the same validation as for records from the DHT
+//! and a peer can't inject anything it couldn't have put in the DHT.
+//!
+//! Wire format: `[len:4 BE][bincode message]`, one request then one
+//! response, repeated until the client closes the stream.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::ops::Bound;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, StreamExt};
+use libp2p::{PeerId, StreamProtocol};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
+use tracing::debug;
+
+use craftnet_core::{system_clock, SharedClock};
+
+use crate::behaviour::{
+    registry_shard, DhtRecordValidators, RecordRejection, SignedDhtRecord,
+    EXIT_DHT_KEY_PREFIX, EXIT_RECORD_TTL, RELAY_DHT_KEY_PREFIX, RELAY_RECORD_TTL,
+};
+use crate::NetworkError;
+
+/// Protocol identifier for registry delta sync
+pub const REGISTRY_SYNC_PROTOCOL: StreamProtocol = StreamProtocol::new("/craftnet/registry-sync/1.0.0");
+
+/// Most records returned in one response page
+pub const REGISTRY_SYNC_PAGE_SIZE: usize = 256;
+
+/// Most pages served (or requested) on one stream
+pub const REGISTRY_SYNC_MAX_PAGES: usize = 64;
+
+/// Sync streams served at once
+const MAX_SYNC_SESSIONS: usize = 32;
+
+/// Largest sync message on the wire
+const MAX_SYNC_MESSAGE: usize = 4 * 1024 * 1024;
+
+/// Largest bloom filter a server will evaluate
+const MAX_BLOOM_BYTES: usize = 256 * 1024;
+
+/// Time allowed for each request/response round trip
+const REGISTRY_SYNC_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Which registry a record belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RegistryKind {
+    Exit,
+    Relay,
+}
+
+impl RegistryKind {
+    /// Kind of an exit/relay info record key
+    pub fn of_record_key(key: &[u8]) -> Option<Self> {
+        if key.starts_with(EXIT_DHT_KEY_PREFIX.as_bytes()) {
+            Some(Self::Exit)
+        } else if key.starts_with(RELAY_DHT_KEY_PREFIX.as_bytes()) {
+            Some(Self::Relay)
+        } else {
+            None
+        }
+    }
+
+    /// How long after signing a record of this kind stays valid
+    pub fn record_ttl(self) -> Duration {
+        match self {
+            Self::Exit => EXIT_RECORD_TTL,
+            Self::Relay => RELAY_RECORD_TTL,
+        }
+    }
+}
+
+/// Bloom filter over record fingerprints.
+///
+/// Hashing is FNV-1a with double hashing so every node computes the same
+/// bit positions regardless of platform or Rust version.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BloomFilter {
+    bits: Vec<u8>,
+    hashes: u8,
+}
+
+impl BloomFilter {
+    /// Filter sized for `items` entries at roughly `fp_rate` false positives
+    pub fn with_capacity(items: usize, fp_rate: f64) -> Self {
+        let items = items.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let bits = (-(items * fp_rate.ln()) / (ln2 * ln2)).ceil().max(64.0) as usize;
+        let bytes = bits.div_ceil(8).min(MAX_BLOOM_BYTES);
+        let hashes = ((bytes * 8) as f64 / items * ln2).round().clamp(1.0, 16.0) as u8;
+        Self { bits: vec![0u8; bytes], hashes }
+    }
+
+    fn positions(&self, item: &[u8]) -> impl Iterator<Item = usize> {
+        let m = (self.bits.len() * 8) as u64;
+        let h1 = fnv1a(item, 0xcbf2_9ce4_8422_2325);
+        let h2 = fnv1a(item, 0x8422_2325_cbf2_9ce4) | 1;
+        (0..self.hashes as u64).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % m) as usize)
+    }
+
+    pub fn insert(&mut self, item: &[u8]) {
+        if self.bits.is_empty() {
+            return;
+        }
+        for pos in self.positions(item).collect::<Vec<_>>() {
+            self.bits[pos / 8] |= 1 << (pos % 8);
+        }
+    }
+
+    pub fn contains(&self, item: &[u8]) -> bool {
+        !self.bits.is_empty()
+            && self.positions(item).all(|pos| self.bits[pos / 8] & (1 << (pos % 8)) != 0)
+    }
+
+    /// Whether a filter received from a peer is within the served limits
+    fn is_sane(&self) -> bool {
+        !self.bits.is_empty() && self.bits.len() <= MAX_BLOOM_BYTES && (1..=16).contains(&self.hashes)
+    }
+}
+
+fn fnv1a(data: &[u8], basis: u64) -> u64 {
+    data.iter().fold(basis, |hash, b| (hash ^ *b as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// Identity of one signed version of a record (key + signature), so a
+/// re-announced record no longer matches the filter entry of the old one
+pub fn record_fingerprint(key: &[u8], record: &SignedDhtRecord) -> Vec<u8> {
+    let mut out = Vec::with_capacity(key.len() + 1 + record.signature.len());
+    out.extend_from_slice(key);
+    out.push(0);
+    out.extend_from_slice(record.signature.as_bytes());
+    out
+}
+
+/// One record as published in the DHT
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegistryEntry {
+    pub key: Vec<u8>,
+    /// `SignedDhtRecord` bytes
+    pub value: Vec<u8>,
+}
+
+/// Ask a peer for registry records it has that we don't
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistrySyncRequest {
+    pub kind: RegistryKind,
+    /// Shards to include (empty = all)
+    pub shards: Vec<u8>,
+    /// Only records the server received at or after this unix time
+    /// (its own clock, from a previous `server_time`; 0 = everything)
+    pub since: u64,
+    /// Fingerprints of records the client already holds
+    pub known: Option<BloomFilter>,
+    /// Page size (capped at `REGISTRY_SYNC_PAGE_SIZE`)
+    pub limit: u32,
+    /// Resume after this record key (`next_cursor` of the previous page)
+    pub cursor: Option<Vec<u8>>,
+}
+
+impl RegistrySyncRequest {
+    /// Every current record of `kind` not covered by `known`
+    pub fn new(kind: RegistryKind, since: u64, known: Option<BloomFilter>) -> Self {
+        Self {
+            kind,
+            shards: Vec::new(),
+            since,
+            known,
+            limit: REGISTRY_SYNC_PAGE_SIZE as u32,
+            cursor: None,
+        }
+    }
+}
+
+/// One page of registry records
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RegistrySyncResponse {
+    pub records: Vec<RegistryEntry>,
+    /// Set when more records follow; send it back as `cursor`
+    pub next_cursor: Option<Vec<u8>>,
+    /// Server's unix time when the page was built; use as the next `since`
+    pub server_time: u64,
+}
+
+#[derive(Debug, Clone)]
+struct StoredRecord {
+    value: Vec<u8>,
+    kind: RegistryKind,
+    shard: u8,
+    /// Publisher's signing time
+    timestamp: u64,
+    /// Local unix time when this version arrived
+    received: u64,
+    fingerprint: Vec<u8>,
+}
+
+/// Validated exit/relay records, indexed for delta sync
+pub struct RegistryStore {
+    records: BTreeMap<Vec<u8>, StoredRecord>,
+    validators: DhtRecordValidators,
+    /// Time source for validation and sync answers
+    clock: SharedClock,
+}
+
+impl RegistryStore {
+    pub fn new() -> Self {
+        Self::with_validators(DhtRecordValidators::default())
+    }
+
+    pub fn with_validators(validators: DhtRecordValidators) -> Self {
+        Self {
+            records: BTreeMap::new(),
+            validators,
+            clock: system_clock(),
+        }
+    }
+
+    /// Read time from `clock` instead of the system clock
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+    }
+
+    /// Add a record if it is valid and newer than the one held.
+    ///
+    /// Returns whether the store changed. Keys outside the exit/relay
+    /// registries are ignored.
+    pub fn insert(&mut self, key: &[u8], value: &[u8]) -> Result<bool, RecordRejection> {
+        self.insert_at(key, value, self.clock.unix_now())
+    }
+
+    /// [`insert`](Self::insert) at unix time `now`
+    pub fn insert_at(&mut self, key: &[u8], value: &[u8], now: u64) -> Result<bool, RecordRejection> {
+        let Some(kind) = RegistryKind::of_record_key(key) else {
+            return Ok(false);
+        };
+        self.validators.validate_at(key, value, now)?;
+        let record = SignedDhtRecord::from_bytes(value).ok_or(RecordRejection::Malformed)?;
+        let pubkey = record.pubkey_bytes().ok_or(RecordRejection::Malformed)?;
+
+        if let Some(existing) = self.records.get(key) {
+            if existing.timestamp >= record.timestamp {
+                return Ok(false);
+            }
+        }
+        self.records.insert(key.to_vec(), StoredRecord {
+            value: value.to_vec(),
+            kind,
+            shard: registry_shard(&pubkey),
+            timestamp: record.timestamp,
+            received: now,
+            fingerprint: record_fingerprint(key, &record),
+        });
+        Ok(true)
+    }
+
+    /// Drop records past their TTL; returns how many were removed
+    pub fn prune_at(&mut self, now: u64) -> usize {
+        let before = self.records.len();
+        self.records.retain(|_, r| now.saturating_sub(r.timestamp) <= r.kind.record_ttl().as_secs());
+        before - self.records.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Number of records held of one kind
+    pub fn count(&self, kind: RegistryKind) -> usize {
+        self.records.values().filter(|r| r.kind == kind).count()
+    }
+
+    /// Bloom filter of the records held of one kind (1% false positives)
+    pub fn known(&self, kind: RegistryKind) -> BloomFilter {
+        let mut filter = BloomFilter::with_capacity(self.count(kind), 0.01);
+        for record in self.records.values().filter(|r| r.kind == kind) {
+            filter.insert(&record.fingerprint);
+        }
+        filter
+    }
+
+    /// Build the response page for a sync request
+    pub fn answer(&self, request: &RegistrySyncRequest) -> RegistrySyncResponse {
+        self.answer_at(request, self.clock.unix_now())
+    }
+
+    /// [`answer`](Self::answer) at unix time `now`
+    pub fn answer_at(&self, request: &RegistrySyncRequest, now: u64) -> RegistrySyncResponse {
+        let limit = (request.limit as usize).clamp(1, REGISTRY_SYNC_PAGE_SIZE);
+        let known = request.known.as_ref().filter(|k| k.is_sane());
+        let start = match &request.cursor {
+            Some(cursor) => Bound::Excluded(cursor.clone()),
+            None => Bound::Unbounded,
+        };
+
+        let mut records = Vec::new();
+        let mut next_cursor = None;
+        for (key, record) in self.records.range((start, Bound::Unbounded)) {
+            if record.kind != request.kind
+                || (!request.shards.is_empty() && !request.shards.contains(&record.shard))
+                || record.received < request.since
+                || now.saturating_sub(record.timestamp) > record.kind.record_ttl().as_secs()
+                || known.is_some_and(|k| k.contains(&record.fingerprint))
+            {
+                continue;
+            }
+            if records.len() == limit {
+                next_cursor = records.last().map(|e: &RegistryEntry| e.key.clone());
+                break;
+            }
+            records.push(RegistryEntry { key: key.clone(), value: record.value.clone() });
+        }
+
+        RegistrySyncResponse { records, next_cursor, server_time: now }
+    }
+}
+
+impl Default for RegistryStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Client side of the registry sync protocol
+#[derive(Clone)]
+pub struct RegistryClient {
+    control: libp2p_stream::Control,
+}
+
+impl RegistryClient {
+    pub fn new(control: libp2p_stream::Control) -> Self {
+        Self { control }
+    }
+
+    /// Pull every record matching `request` from `peer`, following cursors
+    /// for up to `REGISTRY_SYNC_MAX_PAGES` pages.
+    ///
+    /// The returned `server_time` is from the first page, so records that
+    /// arrive at the peer mid-sync are picked up next time. `next_cursor` is
+    /// set only if the page limit cut the sync short.
+    pub async fn sync(
+        &mut self,
+        peer: PeerId,
+        mut request: RegistrySyncRequest,
+    ) -> Result<RegistrySyncResponse, NetworkError> {
+        let mut stream = tokio::time::timeout(
+            REGISTRY_SYNC_TIMEOUT,
+            self.control.open_stream(peer, REGISTRY_SYNC_PROTOCOL),
+        )
+        .await
+        .map_err(|_| NetworkError::SendError("registry sync open timed out".to_string()))?
+        .map_err(|e| NetworkError::SendError(format!("registry sync open: {}", e)))?;
+
+        let mut out = RegistrySyncResponse::default();
+        for page in 0..REGISTRY_SYNC_MAX_PAGES {
+            let response: RegistrySyncResponse = tokio::time::timeout(REGISTRY_SYNC_TIMEOUT, async {
+                write_message(&mut stream, &request).await?;
+                read_message(&mut stream).await
+            })
+            .await
+            .map_err(|_| NetworkError::SendError("registry sync timed out".to_string()))?
+            .map_err(|e| NetworkError::SendError(format!("registry sync: {}", e)))?;
+
+            if page == 0 {
+                out.server_time = response.server_time;
+            }
+            out.records.extend(response.records);
+            out.next_cursor = response.next_cursor;
+            match &out.next_cursor {
+                Some(cursor) => request.cursor = Some(cursor.clone()),
+                None => break,
+            }
+        }
+        let _ = stream.close().await;
+        Ok(out)
+    }
+}
+
+/// Answer registry sync streams from `store` until `incoming` ends
+pub async fn serve_registry_sync(
+    mut incoming: libp2p_stream::IncomingStreams,
+    store: Arc<Mutex<RegistryStore>>,
+) {
+    let sessions = Arc::new(Semaphore::new(MAX_SYNC_SESSIONS));
+    while let Some((peer, stream)) = incoming.next().await {
+        let Ok(permit) = sessions.clone().try_acquire_owned() else {
+            debug!("Registry sync from {} refused: too many sessions", peer);
+            continue;
+        };
+        let store = store.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve_stream(stream, &store).await {
+                debug!("Registry sync with {} ended: {}", peer, e);
+            }
+            drop(permit);
+        });
+    }
+}
+
+async fn serve_stream(mut stream: libp2p::Stream, store: &Mutex<RegistryStore>) -> io::Result<()> {
+    for _ in 0..REGISTRY_SYNC_MAX_PAGES {
+        let request: RegistrySyncRequest = match tokio::time::timeout(
+            REGISTRY_SYNC_TIMEOUT,
+            read_message(&mut stream),
+        ).await {
+            Ok(Ok(request)) => request,
+            Ok(Err(e)) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Ok(Err(e)) => return Err(e),
+            Err(_) => return Err(io::Error::new(io::ErrorKind::TimedOut, "request timed out")),
+        };
+        let response = store.lock().unwrap_or_else(|e| e.into_inner()).answer(&request);
+        write_message(&mut stream, &response).await?;
+    }
+    stream.close().await
+}
+
+pub(crate) async fn write_message<T: AsyncWrite + Unpin, M: Serialize>(io: &mut T, message: &M) -> io::Result<()> {
+    let data = bincode::serialize(message)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    if data.len() > MAX_SYNC_MESSAGE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "sync message too large"));
+    }
+    io.write_all(&(data.len() as u32).to_be_bytes()).await?;
+    io.write_all(&data).await?;
+    io.flush().await
+}
+
+pub(crate) async fn read_message<T: AsyncRead + Unpin, M: DeserializeOwned>(io: &mut T) -> io::Result<M> {
+    let mut len_bytes = [0u8; 4];
+    io.read_exact(&mut len_bytes).await?;
+    let len = u32::from_be_bytes(len_bytes) as usize;
+    if len > MAX_SYNC_MESSAGE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "sync message too large"));
+    }
+    let mut data = vec![0u8; len];
+    io.read_exact(&mut data).await?;
+    bincode::deserialize(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use craftec_crypto::SigningKeypair;
+    use craftnet_core::RelayInfo;
+
+    use crate::{relay_dht_key, sign_peer_binding_at};
+
+    fn relay_entry(timestamp: u64) -> (Vec<u8>, Vec<u8>) {
+        let libp2p_keypair = libp2p::identity::Keypair::generate_ed25519();
+        let peer_id = PeerId::from(libp2p_keypair.public());
+        let settlement = SigningKeypair::generate();
+        let info = RelayInfo {
+            pubkey: settlement.public_key_bytes(),
+            address: "/ip4/127.0.0.1/tcp/9000".to_string(),
+            allows_last_hop: true,
+            reputation: 0,
+            encryption_pubkey: None,
+            peer_binding: sign_peer_binding_at(&libp2p_keypair, &settlement, timestamp),
+            capacity: None,
+            country_code: None,
+            as_number: None,
+        };
+        let key = relay_dht_key(&peer_id);
+        let record = SignedDhtRecord::sign_at(
+            &settlement,
+            &key,
+            serde_json::to_string(&info).unwrap(),
+            timestamp,
+        );
+        (key, record.to_bytes())
+    }
+
+    #[test]
+    fn test_bloom_filter() {
+        let mut filter = BloomFilter::with_capacity(100, 0.01);
+        for i in 0..100u32 {
+            filter.insert(&i.to_le_bytes());
+        }
+        assert!((0..100u32).all(|i| filter.contains(&i.to_le_bytes())));
+        let false_positives = (1000..2000u32).filter(|i| filter.contains(&i.to_le_bytes())).count();
+        assert!(false_positives < 50, "{} false positives", false_positives);
+
+        let empty = BloomFilter { bits: vec![], hashes: 1 };
+        assert!(!empty.contains(b"x"));
+        assert!(!empty.is_sane());
+    }
+
+    #[test]
+    fn test_store_rejects_invalid_and_keeps_newest() {
+        let now = 1_000_000;
+        let mut store = RegistryStore::new();
+        let (key, value) = relay_entry(now);
+
+        assert_eq!(store.insert_at(&key, b"garbage", now), Err(RecordRejection::Malformed));
+        assert_eq!(store.insert_at(b"/craftnet/peers/ab", b"x", now), Ok(false));
+        assert_eq!(store.insert_at(&key, &value, now), Ok(true));
+        assert_eq!(store.insert_at(&key, &value, now), Ok(false));
+        assert_eq!(store.count(RegistryKind::Relay), 1);
+        assert_eq!(store.count(RegistryKind::Exit), 0);
+
+        assert_eq!(store.prune_at(now + RELAY_RECORD_TTL.as_secs() + 1), 1);
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn test_store_reads_its_clock() {
+        let mut store = RegistryStore::new();
+        let (key, value) = relay_entry(1_000_000);
+        // Long expired by the system clock
+        assert!(store.insert(&key, &value).is_err());
+        store.set_clock(Arc::new(craftnet_core::SimClock::new(1_000_000)));
+        assert_eq!(store.insert(&key, &value), Ok(true));
+    }
+
+    #[test]
+    fn test_delta_sync_skips_known_and_old() {
+        let now = 1_000_000;
+        let mut server = RegistryStore::new();
+        let mut client = RegistryStore::new();
+        let entries: Vec<_> = (0..5).map(|_| relay_entry(now)).collect();
+        for (key, value) in &entries {
+            server.insert_at(key, value, now).unwrap();
+        }
+        // Client already has two of them
+        for (key, value) in &entries[..2] {
+            client.insert_at(key, value, now).unwrap();
+        }
+
+        let request = RegistrySyncRequest::new(RegistryKind::Relay, 0, Some(client.known(RegistryKind::Relay)));
+        let response = server.answer_at(&request, now);
+        assert_eq!(response.records.len(), 3);
+        assert!(response.records.iter().all(|e| !entries[..2].iter().any(|(k, _)| *k == e.key)));
+        assert_eq!(response.server_time, now);
+
+        // Nothing arrived since the last sync
+        let request = RegistrySyncRequest::new(RegistryKind::Relay, now + 1, None);
+        assert!(server.answer_at(&request, now + 1).records.is_empty());
+
+        // Wrong kind
+        let request = RegistrySyncRequest::new(RegistryKind::Exit, 0, None);
+        assert!(server.answer_at(&request, now).records.is_empty());
+    }
+
+    #[test]
+    fn test_answer_pages_with_cursor() {
+        let now = 1_000_000;
+        let mut server = RegistryStore::new();
+        for _ in 0..5 {
+            let (key, value) = relay_entry(now);
+            server.insert_at(&key, &value, now).unwrap();
+        }
+
+        let mut request = RegistrySyncRequest::new(RegistryKind::Relay, 0, None);
+        request.limit = 2;
+        let mut seen = Vec::new();
+        loop {
+            let page = server.answer_at(&request, now);
+            assert!(page.records.len() <= 2);
+            seen.extend(page.records.into_iter().map(|e| e.key));
+            match page.next_cursor {
+                Some(cursor) => request.cursor = Some(cursor),
+                None => break,
+            }
+        }
+        assert_eq!(seen.len(), 5);
+        let mut sorted = seen.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(sorted, seen);
+    }
+
+    #[test]
+    fn test_answer_filters_shards() {
+        let now = 1_000_000;
+        let mut server = RegistryStore::new();
+        let (key, value) = relay_entry(now);
+        server.insert_at(&key, &value, now).unwrap();
+        let pubkey = SignedDhtRecord::from_bytes(&value).unwrap().pubkey_bytes().unwrap();
+        let shard = registry_shard(&pubkey);
+
+        let mut request = RegistrySyncRequest::new(RegistryKind::Relay, 0, None);
+        request.shards = vec![shard];
+        assert_eq!(server.answer_at(&request, now).records.len(), 1);
+        request.shards = vec![(shard + 1) % crate::REGISTRY_SHARDS];
+        assert!(server.answer_at(&request, now).records.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_message_roundtrip() {
+        let request = RegistrySyncRequest::new(RegistryKind::Exit, 42, Some(BloomFilter::with_capacity(10, 0.01)));
+        let mut buf = Vec::new();
+        write_message(&mut futures::io::Cursor::new(&mut buf), &request).await.unwrap();
+        let decoded: RegistrySyncRequest = read_message(&mut futures::io::Cursor::new(&buf)).await.unwrap();
+        assert_eq!(decoded.kind, RegistryKind::Exit);
+        assert_eq!(decoded.since, 42);
+        assert_eq!(decoded.known, request.known);
+    }
+}
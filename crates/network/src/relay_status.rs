@@ -6,9 +6,23 @@
 //!
 //! Relays announce their self-reported capacity. Clients score relays
 //! using a weighted formula over load, queue, bandwidth, and uptime.
+//!
+//! Messages are signed with the relay's identity; receivers check the
+//! timestamp and nonce with [`StatusFreshness`](crate::StatusFreshness).
 
 use serde::{Deserialize, Serialize};
 
+use craftec_crypto::{sign_data, verify_signature, SigningKeypair};
+
+use craftnet_core::RelayCapacity;
+
+use crate::onion_key::OnionKeyOffer;
+use crate::status_auth::StatusRejection;
+use crate::wire::WIRE_VERSION;
+
+/// Domain separator for relay status signatures
+const RELAY_STATUS_DOMAIN: &[u8] = b"craftnet-relay-status-v1";
+
 /// Relay status event type
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -24,6 +38,10 @@ pub enum RelayStatusType {
 /// Contains load and throughput information for relay selection scoring.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RelayStatusMessage {
+    /// Wire version of the sender (0 = predates versioning).
+    /// New fields must be `#[serde(default)]` so older peers' messages parse.
+    #[serde(default)]
+    pub version: u8,
     /// Type of status update
     pub status: RelayStatusType,
     /// Relay node's public key (32 bytes, hex encoded)
@@ -42,12 +60,27 @@ pub struct RelayStatusMessage {
     pub uptime_secs: u64,
     /// X25519 encryption pubkey (hex-encoded, for onion routing)
     pub encryption_pubkey: Option<String>,
+    /// Signed rotating onion key; preferred over `encryption_pubkey`
+    /// (None from relays with rotation off or that predate it)
+    #[serde(default)]
+    pub onion_key: Option<OnionKeyOffer>,
     /// Connected peers with active streams (peer ID strings).
     /// Carries topology data so the separate topology topic is not needed.
     #[serde(default)]
     pub connected_peers: Vec<String>,
+    /// Self-reported capacity (None from relays that predate it)
+    #[serde(default)]
+    pub capacity: Option<RelayCapacity>,
     /// Unix timestamp (seconds)
     pub timestamp: u64,
+    /// Random value making each signed message unique (see
+    /// [`StatusFreshness`](crate::StatusFreshness))
+    #[serde(default)]
+    pub nonce: u64,
+    /// Sender's ed25519 signature over `signable_data()` (hex encoded;
+    /// None from relays that predate signed heartbeats)
+    #[serde(default)]
+    pub signature: Option<String>,
 }
 
 impl RelayStatusMessage {
@@ -64,6 +97,7 @@ impl RelayStatusMessage {
         connected_peers: Vec<String>,
     ) -> Self {
         Self {
+            version: WIRE_VERSION,
             status: RelayStatusType::Heartbeat,
             pubkey: hex::encode(pubkey),
             peer_id: peer_id.to_string(),
@@ -73,17 +107,22 @@ impl RelayStatusMessage {
             bandwidth_available_kbps,
             uptime_secs,
             encryption_pubkey: None,
+            onion_key: None,
             connected_peers,
+            capacity: None,
             timestamp: std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap_or_default()
                 .as_secs(),
+            nonce: 0,
+            signature: None,
         }
     }
 
     /// Create an offline announcement
     pub fn offline(pubkey: [u8; 32], peer_id: &str) -> Self {
         Self {
+            version: WIRE_VERSION,
             status: RelayStatusType::Offline,
             pubkey: hex::encode(pubkey),
             peer_id: peer_id.to_string(),
@@ -93,11 +132,62 @@ impl RelayStatusMessage {
             bandwidth_available_kbps: 0,
             uptime_secs: 0,
             encryption_pubkey: None,
+            onion_key: None,
             connected_peers: vec![],
+            capacity: None,
             timestamp: std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap_or_default()
                 .as_secs(),
+            nonce: 0,
+            signature: None,
+        }
+    }
+
+    /// Set `nonce` and sign the message with the relay's identity. Sign
+    /// last: later changes to any signed field invalidate the signature.
+    pub fn sign(&mut self, keypair: &SigningKeypair, nonce: u64) {
+        self.nonce = nonce;
+        self.signature = Some(hex::encode(sign_data(keypair, &self.signable_data())));
+    }
+
+    /// Data signed by the relay: status, timestamp and nonce, bound to its
+    /// pubkey and peer ID, plus every announced value. `version` isn't
+    /// covered; a field added later needs a new domain separator.
+    pub fn signable_data(&self) -> Vec<u8> {
+        let fields = (
+            &self.pubkey,
+            &self.peer_id,
+            self.status,
+            self.timestamp,
+            self.nonce,
+            self.load_percent,
+            self.active_connections,
+            self.queue_depth,
+            self.bandwidth_available_kbps,
+            self.uptime_secs,
+            &self.encryption_pubkey,
+            &self.onion_key,
+            &self.connected_peers,
+            &self.capacity,
+        );
+        let mut data = RELAY_STATUS_DOMAIN.to_vec();
+        data.extend_from_slice(&bincode::serialize(&fields).unwrap_or_default());
+        data
+    }
+
+    /// Check the signature against the message's own pubkey
+    pub fn verify(&self) -> Result<(), StatusRejection> {
+        let signature = self.signature.as_ref().ok_or(StatusRejection::Unsigned)?;
+        let signature: [u8; 64] = hex::decode(signature)
+            .ok()
+            .and_then(|s| s.try_into().ok())
+            .ok_or(StatusRejection::BadSignature)?;
+        let pubkey = self.pubkey_bytes().ok_or(StatusRejection::BadSignature)?;
+        if verify_signature(&pubkey, &self.signable_data(), &signature) {
+            Ok(())
+        } else {
+            Err(StatusRejection::BadSignature)
         }
     }
 
@@ -181,6 +271,40 @@ mod tests {
         assert_eq!(parsed.uptime_secs, msg.uptime_secs);
     }
 
+    #[test]
+    fn test_unknown_fields_and_missing_version() {
+        let msg = RelayStatusMessage::heartbeat([3u8; 32], "peer123", 30, 5, 12, 80000, 7200, vec![]);
+        assert_eq!(msg.version, WIRE_VERSION);
+
+        // A newer relay adds a field: it is ignored
+        let mut json: serde_json::Value = serde_json::from_slice(&msg.to_bytes()).unwrap();
+        json["future_field"] = serde_json::json!({ "nested": [1, 2, 3] });
+        let parsed = RelayStatusMessage::from_bytes(&serde_json::to_vec(&json).unwrap()).unwrap();
+        assert_eq!(parsed.queue_depth, 12);
+
+        // A relay from before versioning sends no version
+        json.as_object_mut().unwrap().remove("version");
+        let parsed = RelayStatusMessage::from_bytes(&serde_json::to_vec(&json).unwrap()).unwrap();
+        assert_eq!(parsed.version, 0);
+        assert_eq!(parsed.capacity, None);
+        assert_eq!(parsed.onion_key, None);
+    }
+
+    #[test]
+    fn test_capacity_roundtrip() {
+        use craftnet_core::BandwidthClass;
+
+        let mut msg = RelayStatusMessage::heartbeat([3u8; 32], "peer123", 0, 0, 0, 0, 0, vec![]);
+        let capacity = RelayCapacity {
+            bandwidth_class: BandwidthClass::High,
+            max_concurrent_streams: 512,
+            max_shard_size: 10 * 1024,
+        };
+        msg.capacity = Some(capacity);
+        let parsed = RelayStatusMessage::from_bytes(&msg.to_bytes()).unwrap();
+        assert_eq!(parsed.capacity, Some(capacity));
+    }
+
     #[test]
     fn test_load_clamped_to_100() {
         let msg = RelayStatusMessage::heartbeat([4u8; 32], "peer", 150, 0, 0, 0, 0, vec![]);
@@ -193,4 +317,21 @@ mod tests {
         let msg = RelayStatusMessage::heartbeat(pubkey, "peer", 0, 0, 0, 0, 0, vec![]);
         assert_eq!(msg.pubkey_bytes(), Some(pubkey));
     }
+
+    #[test]
+    fn test_sign_verify() {
+        let relay = SigningKeypair::generate();
+        let mut msg = RelayStatusMessage::heartbeat(relay.public_key_bytes(), "peer", 10, 1, 5, 200, 60, vec![]);
+        msg.onion_key = Some(OnionKeyOffer::sign(&relay, [2u8; 32], 1, 1_000));
+        assert_eq!(msg.verify(), Err(StatusRejection::Unsigned));
+        msg.sign(&relay, 7);
+        let parsed = RelayStatusMessage::from_bytes(&msg.to_bytes()).unwrap();
+        assert_eq!(parsed.verify(), Ok(()));
+
+        // Dropping the onion key offer or changing the status breaks it
+        let stripped = RelayStatusMessage { onion_key: None, ..parsed.clone() };
+        assert_eq!(stripped.verify(), Err(StatusRejection::BadSignature));
+        let offline = RelayStatusMessage { status: RelayStatusType::Offline, ..parsed };
+        assert_eq!(offline.verify(), Err(StatusRejection::BadSignature));
+    }
 }
@@ -6,9 +6,20 @@
 //!
 //! Exits announce their self-reported capacity. Clients measure actual
 //! throughput and compare against announced values for trust scoring.
+//!
+//! Messages are signed with the exit's identity; receivers check the
+//! timestamp and nonce with [`StatusFreshness`](crate::StatusFreshness).
 
 use serde::{Deserialize, Serialize};
 
+use craftec_crypto::{sign_data, verify_signature, SigningKeypair};
+
+use crate::status_auth::StatusRejection;
+use crate::wire::WIRE_VERSION;
+
+/// Domain separator for exit status signatures
+const EXIT_STATUS_DOMAIN: &[u8] = b"craftnet-exit-status-v1";
+
 /// Exit status event type
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -25,6 +36,10 @@ pub enum ExitStatusType {
 /// New exits start with base 50% score; measurements adjust over time.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExitStatusMessage {
+    /// Wire version of the sender (0 = predates versioning).
+    /// New fields must be `#[serde(default)]` so older peers' messages parse.
+    #[serde(default)]
+    pub version: u8,
     /// Type of status update
     pub status: ExitStatusType,
     /// Exit node's public key (32 bytes, hex encoded)
@@ -51,6 +66,14 @@ pub struct ExitStatusMessage {
     pub connected_peers: Vec<String>,
     /// Unix timestamp (seconds)
     pub timestamp: u64,
+    /// Random value making each signed message unique (see
+    /// [`StatusFreshness`](crate::StatusFreshness))
+    #[serde(default)]
+    pub nonce: u64,
+    /// Sender's ed25519 signature over `signable_data()` (hex encoded;
+    /// None from exits that predate signed heartbeats)
+    #[serde(default)]
+    pub signature: Option<String>,
 }
 
 impl ExitStatusMessage {
@@ -68,6 +91,7 @@ impl ExitStatusMessage {
         connected_peers: Vec<String>,
     ) -> Self {
         Self {
+            version: WIRE_VERSION,
             status: ExitStatusType::Heartbeat,
             pubkey: hex::encode(pubkey),
             peer_id: peer_id.to_string(),
@@ -83,12 +107,15 @@ impl ExitStatusMessage {
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap_or_default()
                 .as_secs(),
+            nonce: 0,
+            signature: None,
         }
     }
 
     /// Create an offline announcement
     pub fn offline(pubkey: [u8; 32], peer_id: &str) -> Self {
         Self {
+            version: WIRE_VERSION,
             status: ExitStatusType::Offline,
             pubkey: hex::encode(pubkey),
             peer_id: peer_id.to_string(),
@@ -104,6 +131,54 @@ impl ExitStatusMessage {
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap_or_default()
                 .as_secs(),
+            nonce: 0,
+            signature: None,
+        }
+    }
+
+    /// Set `nonce` and sign the message with the exit's identity. Sign
+    /// last: later changes to any signed field invalidate the signature.
+    pub fn sign(&mut self, keypair: &SigningKeypair, nonce: u64) {
+        self.nonce = nonce;
+        self.signature = Some(hex::encode(sign_data(keypair, &self.signable_data())));
+    }
+
+    /// Data signed by the exit: status, timestamp and nonce, bound to its
+    /// pubkey and peer ID, plus every announced value. `version` isn't
+    /// covered; a field added later needs a new domain separator.
+    pub fn signable_data(&self) -> Vec<u8> {
+        let fields = (
+            &self.pubkey,
+            &self.peer_id,
+            self.status,
+            self.timestamp,
+            self.nonce,
+            self.load_percent,
+            self.active_connections,
+            self.uplink_kbps,
+            self.downlink_kbps,
+            self.uptime_secs,
+            &self.region,
+            &self.encryption_pubkey,
+            &self.connected_peers,
+        );
+        let mut data = EXIT_STATUS_DOMAIN.to_vec();
+        data.extend_from_slice(&bincode::serialize(&fields).unwrap_or_default());
+        data
+    }
+
+    /// Check the signature against the message's own pubkey
+    pub fn verify(&self) -> Result<(), StatusRejection> {
+        let signature = self.signature.as_ref().ok_or(StatusRejection::Unsigned)?;
+        let signature: [u8; 64] = hex::decode(signature)
+            .ok()
+            .and_then(|s| s.try_into().ok())
+            .ok_or(StatusRejection::BadSignature)?;
+        let pubkey = self.pubkey_bytes().ok_or(StatusRejection::BadSignature)?;
+        if verify_signature(&pubkey, &self.signable_data(), &signature) {
+            Ok(())
+        } else {
+            Err(StatusRejection::BadSignature)
         }
     }
 
@@ -193,6 +268,23 @@ mod tests {
         assert_eq!(parsed.region, msg.region);
     }
 
+    #[test]
+    fn test_unknown_fields_and_missing_version() {
+        let msg = ExitStatusMessage::heartbeat([3u8; 32], "peer123", 50, 10, 5000, 25000, 86400, None, vec![]);
+        assert_eq!(msg.version, WIRE_VERSION);
+
+        // A newer exit adds a field: it is ignored
+        let mut json: serde_json::Value = serde_json::from_slice(&msg.to_bytes()).unwrap();
+        json["future_field"] = serde_json::json!("anything");
+        let parsed = ExitStatusMessage::from_bytes(&serde_json::to_vec(&json).unwrap()).unwrap();
+        assert_eq!(parsed.uplink_kbps, 5000);
+
+        // An exit from before versioning sends no version
+        json.as_object_mut().unwrap().remove("version");
+        let parsed = ExitStatusMessage::from_bytes(&serde_json::to_vec(&json).unwrap()).unwrap();
+        assert_eq!(parsed.version, 0);
+    }
+
     #[test]
     fn test_load_clamped_to_100() {
         let msg = ExitStatusMessage::heartbeat([4u8; 32], "peer", 150, 0, 0, 0, 0, None, vec![]);
@@ -205,4 +297,27 @@ mod tests {
         let msg = ExitStatusMessage::heartbeat(pubkey, "peer", 0, 0, 0, 0, 0, None, vec![]);
         assert_eq!(msg.pubkey_bytes(), Some(pubkey));
     }
+
+    #[test]
+    fn test_sign_verify() {
+        let exit = SigningKeypair::generate();
+        let mut msg = ExitStatusMessage::heartbeat(exit.public_key_bytes(), "peer", 10, 1, 100, 200, 60, None, vec![]);
+        assert_eq!(msg.verify(), Err(StatusRejection::Unsigned));
+        msg.sign(&exit, 42);
+        let parsed = ExitStatusMessage::from_bytes(&msg.to_bytes()).unwrap();
+        assert_eq!(parsed.nonce, 42);
+        assert_eq!(parsed.verify(), Ok(()));
+
+        // Announced values, the timestamp and the nonce are all covered
+        let lighter = ExitStatusMessage { load_percent: 0, ..parsed.clone() };
+        assert_eq!(lighter.verify(), Err(StatusRejection::BadSignature));
+        let later = ExitStatusMessage { timestamp: parsed.timestamp + 60, ..parsed.clone() };
+        assert_eq!(later.verify(), Err(StatusRejection::BadSignature));
+        let renonced = ExitStatusMessage { nonce: 43, ..parsed.clone() };
+        assert_eq!(renonced.verify(), Err(StatusRejection::BadSignature));
+        // Signed by someone other than the announced exit
+        let mut forged = ExitStatusMessage::offline(exit.public_key_bytes(), "peer");
+        forged.sign(&SigningKeypair::generate(), 1);
+        assert_eq!(forged.verify(), Err(StatusRejection::BadSignature));
+    }
 }
@@ -0,0 +1,227 @@
+//! Freshness, replay and clock skew checks for signed status heartbeats
+//!
+//! Exit and relay status messages are signed by the sender's ed25519
+//! identity over their status, timestamp, a random nonce and the announced
+//! values (see [`ExitStatusMessage::verify`](crate::ExitStatusMessage::verify)
+//! and [`RelayStatusMessage::verify`](crate::RelayStatusMessage::verify)).
+//! A signature alone doesn't stop a peer from re-gossiping an old
+//! heartbeat to make an exit that went offline look alive, so
+//! [`StatusFreshness`] also checks each signed message against its signer's
+//! clock:
+//!
+//! - The timestamp may be off our clock by at most
+//!   [`StatusFreshnessPolicy::max_clock_skew`].
+//! - Each signer's clock offset is tracked, and a timestamp must lie within
+//!   [`StatusFreshnessPolicy::skew_window`] of it. A node whose clock is
+//!   steadily ten minutes off is accepted; a heartbeat that lags its
+//!   signer's usual offset by more than the window is not.
+//! - A (timestamp, nonce) pair already accepted is a replay.
+//!
+//! Liveness (`EXIT_OFFLINE_THRESHOLD`, `RELAY_OFFLINE_THRESHOLD`) runs on
+//! local receipt times, so once a skewed node's heartbeats are accepted its
+//! clock no longer matters. A signer that hasn't had a status accepted for
+//! [`StatusFreshnessPolicy::resync_after`] (it stepped its clock, or went
+//! away) is re-baselined on its next message.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+use craftnet_core::PublicKey;
+
+/// Signers whose clocks are tracked before the least recently accepted is
+/// forgotten
+pub const MAX_TRACKED_SIGNERS: usize = 4096;
+
+/// Accepted (timestamp, nonce) pairs remembered per signer; at one
+/// heartbeat per 30s for exit and relay status each, covers an hour
+const MAX_SEEN_PER_SIGNER: usize = 256;
+
+/// Weight of a new observation in the tracked offset (1/n)
+const OFFSET_SMOOTHING: i64 = 4;
+
+/// Bounds on status message timestamps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatusFreshnessPolicy {
+    /// How far a timestamp may stray from its signer's tracked offset
+    pub skew_window: Duration,
+    /// Largest clock offset accepted from any signer
+    pub max_clock_skew: Duration,
+    /// Idle time after which a signer's offset is learned afresh
+    pub resync_after: Duration,
+}
+
+impl Default for StatusFreshnessPolicy {
+    fn default() -> Self {
+        Self {
+            skew_window: Duration::from_secs(60),
+            max_clock_skew: Duration::from_secs(3600),
+            resync_after: Duration::from_secs(300),
+        }
+    }
+}
+
+/// Why a status message was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusRejection {
+    /// No signature (the sender predates signed heartbeats)
+    Unsigned,
+    /// Signature doesn't verify against the message's pubkey
+    BadSignature,
+    /// Timestamp outside the accepted skew; `offset_secs` is the message
+    /// timestamp minus our clock
+    Stale { offset_secs: i64 },
+    /// Timestamp and nonce were already accepted
+    Replay,
+}
+
+impl std::fmt::Display for StatusRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StatusRejection::Unsigned => write!(f, "unsigned"),
+            StatusRejection::BadSignature => write!(f, "bad signature"),
+            StatusRejection::Stale { offset_secs } => write!(f, "stale (clock offset {}s)", offset_secs),
+            StatusRejection::Replay => write!(f, "replayed"),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SignerClock {
+    /// Smoothed offset of the signer's clock from ours (seconds, positive =
+    /// signer ahead)
+    offset: i64,
+    /// Our unix time when a status was last accepted
+    last_accepted: u64,
+    /// Accepted (timestamp, nonce) pairs, oldest first
+    seen: VecDeque<(u64, u64)>,
+}
+
+/// Per-signer clock tracking for status messages
+#[derive(Debug, Default)]
+pub struct StatusFreshness {
+    policy: StatusFreshnessPolicy,
+    signers: HashMap<PublicKey, SignerClock>,
+}
+
+impl StatusFreshness {
+    pub fn new(policy: StatusFreshnessPolicy) -> Self {
+        Self { policy, signers: HashMap::new() }
+    }
+
+    pub fn policy(&self) -> &StatusFreshnessPolicy {
+        &self.policy
+    }
+
+    /// Check a verified status from `signer` stamped `timestamp` with
+    /// `nonce` at our unix time `now`, and record it if accepted
+    pub fn check(&mut self, signer: &PublicKey, timestamp: u64, nonce: u64, now: u64) -> Result<(), StatusRejection> {
+        let offset = timestamp as i64 - now as i64;
+        if offset.unsigned_abs() > self.policy.max_clock_skew.as_secs() {
+            return Err(StatusRejection::Stale { offset_secs: offset });
+        }
+        let resync_after = self.policy.resync_after.as_secs();
+        let window = self.policy.skew_window.as_secs();
+        match self.signers.get_mut(signer) {
+            Some(clock) => {
+                if clock.seen.contains(&(timestamp, nonce)) {
+                    return Err(StatusRejection::Replay);
+                }
+                let tracking = now.saturating_sub(clock.last_accepted) < resync_after;
+                if tracking && (offset - clock.offset).unsigned_abs() > window {
+                    return Err(StatusRejection::Stale { offset_secs: offset });
+                }
+                clock.offset = if tracking {
+                    clock.offset + (offset - clock.offset) / OFFSET_SMOOTHING
+                } else {
+                    offset
+                };
+                clock.last_accepted = now;
+                clock.seen.push_back((timestamp, nonce));
+                if clock.seen.len() > MAX_SEEN_PER_SIGNER {
+                    clock.seen.pop_front();
+                }
+            }
+            None => {
+                self.signers.insert(*signer, SignerClock {
+                    offset,
+                    last_accepted: now,
+                    seen: VecDeque::from([(timestamp, nonce)]),
+                });
+                if self.signers.len() > MAX_TRACKED_SIGNERS {
+                    self.evict_idlest();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn evict_idlest(&mut self) {
+        if let Some(idlest) = self.signers.iter().min_by_key(|(_, c)| c.last_accepted).map(|(k, _)| *k) {
+            self.signers.remove(&idlest);
+        }
+    }
+
+    /// Tracked clock offset of `signer` in seconds (positive = its clock is
+    /// ahead of ours)
+    pub fn skew(&self, signer: &PublicKey) -> Option<i64> {
+        self.signers.get(signer).map(|c| c.offset)
+    }
+
+    /// Tracked signers
+    pub fn len(&self) -> usize {
+        self.signers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.signers.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    #[test]
+    fn test_skewed_clock_is_tracked() {
+        let mut freshness = StatusFreshness::default();
+        let signer = [1u8; 32];
+        // Ten minutes ahead, steadily: accepted and learned
+        for i in 0..5 {
+            let now = NOW + i * 30;
+            assert_eq!(freshness.check(&signer, now + 600, i, now), Ok(()));
+        }
+        assert_eq!(freshness.skew(&signer), Some(600));
+
+        // A heartbeat lagging the learned offset by more than the window
+        let now = NOW + 300;
+        assert_eq!(freshness.check(&signer, now, 99, now), Err(StatusRejection::Stale { offset_secs: 0 }));
+        // Nor is any clock past the maximum skew
+        assert!(matches!(freshness.check(&[2u8; 32], NOW + 7200, 0, NOW), Err(StatusRejection::Stale { .. })));
+    }
+
+    #[test]
+    fn test_replay_rejected() {
+        let mut freshness = StatusFreshness::default();
+        let signer = [1u8; 32];
+        assert_eq!(freshness.check(&signer, NOW, 7, NOW), Ok(()));
+        assert_eq!(freshness.check(&signer, NOW, 7, NOW + 5), Err(StatusRejection::Replay));
+        // Same second, another nonce (a heartbeat and an offline notice)
+        assert_eq!(freshness.check(&signer, NOW, 8, NOW + 5), Ok(()));
+    }
+
+    #[test]
+    fn test_resync_after_idle() {
+        let mut freshness = StatusFreshness::default();
+        let signer = [1u8; 32];
+        assert_eq!(freshness.check(&signer, NOW, 1, NOW), Ok(()));
+        // The signer steps its clock back five minutes and goes quiet
+        let policy = *freshness.policy();
+        let later = NOW + policy.resync_after.as_secs();
+        assert_eq!(freshness.check(&signer, later - 300, 2, later), Ok(()));
+        assert_eq!(freshness.skew(&signer), Some(-300));
+        // Replays of what was accepted before are still recognised
+        assert_eq!(freshness.check(&signer, NOW, 1, later + 1), Err(StatusRejection::Replay));
+    }
+}
@@ -9,12 +9,32 @@
 //!
 //! Acks for shards received on our inbound are sent on our outbound.
 //! The peer reads acks from their inbound (our outbound) and matches by seq_id.
+//!
+//! Every outbound opens with a hello frame carrying our protocol version;
+//! the versions peers announce are reported through `take_peer_versions`.
+//!
+//! With frame encryption on (the default), a hello to a peer known to speak
+//! v2+ (from the hello on its own outbound) also offers an ephemeral frame
+//! key. The receiver answers with its own key on the reverse half of the
+//! same stream, and from then on every frame the opener writes is sealed
+//! (see [`FrameCipher`]). An outbound opened before the peer's version was
+//! known starts in plaintext and offers a key in a second hello once it is.
+//! A known-v2 peer that doesn't answer within [`HELLO_REPLY_TIMEOUT`] fails
+//! the open (or the upgrade) rather than getting plaintext frames, since it
+//! may already have agreed on a key. A receiver that agreed on a key rejects
+//! plaintext frames for the rest of the stream.
+//!
+//! Shard bytes written and read per peer are added to the
+//! [`ConnectionTable`](crate::ConnectionTable) given to `with_connection_table`,
+//! and to the [`TrafficAccounting`](crate::TrafficAccounting) given to
+//! `with_accounting`.
 
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
+use bytes::Bytes;
 use libp2p::PeerId;
 use tokio::sync::{mpsc, oneshot, Mutex};
 use tokio::task::JoinHandle;
@@ -23,22 +43,108 @@ use tracing::{debug, warn};
 /// Cooldown after a failed outbound open before retrying (seconds).
 const OPEN_RETRY_COOLDOWN_SECS: u64 = 1;
 
-use craftnet_core::{ForwardReceipt, Shard};
+/// How long an opener waits for the receiver's hello after offering a frame key.
+const HELLO_REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// First protocol version that answers frame key offers
+const FRAME_KEY_MIN_VERSION: u16 = 2;
 
+use craftnet_core::{ChainAck, ForwardReceipt, Shard};
+
+use crate::connections::SharedConnectionTable;
+use crate::traffic::{SharedTrafficAccounting, TrafficProtocol};
 use crate::protocol::{
-    read_frame, write_ack_frame, write_nack_frame, write_shard_frame, StreamFrame,
-    SHARD_STREAM_PROTOCOL,
+    encode_ack_frame, encode_chain_ack_frame, encode_nack_frame, encode_shard_frame, read_frame,
+    write_encoded, write_hello_frame, FrameCipher, FrameKeyExchange, PeerVersion, StreamFrame,
+    NACK_DRAINING, SHARD_STREAM_PROTOCOL,
 };
 
+/// Where shard bytes are counted: the connection table row and the
+/// traffic accounting of the peer
+#[derive(Clone)]
+struct ShardCounters {
+    connections: SharedConnectionTable,
+    traffic: SharedTrafficAccounting,
+}
+
+impl ShardCounters {
+    fn sent(&self, peer: &PeerId, bytes: u64) {
+        self.connections.write().unwrap().record_sent(peer, bytes);
+        self.traffic.write().unwrap().record_out(*peer, TrafficProtocol::ShardStream, bytes);
+    }
+
+    fn received(&self, peer: &PeerId, bytes: u64) {
+        self.connections.write().unwrap().record_received(peer, bytes);
+        self.traffic.write().unwrap().record_in(*peer, TrafficProtocol::ShardStream, bytes);
+    }
+}
+
 /// Outbound shard queued for writing by the background writer task.
+///
+/// The shard's header and payload are shared `Bytes`, so a shard kept for
+/// retry or sent on several paths is not copied.
 pub struct OutboundShard {
     pub peer: PeerId,
     pub shard: Shard,
 }
 
+/// Our outbound stream, sealing frames once a frame key was agreed.
+struct LinkWriter {
+    stream: libp2p::Stream,
+    cipher: Option<FrameCipher>,
+    /// A frame key was offered on this stream (whether or not it was taken)
+    key_offered: bool,
+}
+
+impl LinkWriter {
+    async fn write_frame(&mut self, frame: Bytes) -> std::io::Result<()> {
+        let frame = match &mut self.cipher {
+            Some(cipher) => cipher.seal(frame)?,
+            None => frame,
+        };
+        write_encoded(&mut self.stream, frame).await
+    }
+
+    async fn write_shard(&mut self, shard: &Shard, seq_id: u64) -> std::io::Result<()> {
+        self.write_frame(encode_shard_frame(shard, seq_id)?).await
+    }
+
+    /// Offer a frame key in a second hello on this plaintext stream and seal
+    /// everything written after the receiver's answer
+    async fn offer_frame_key(&mut self) -> std::io::Result<()> {
+        let exchange = FrameKeyExchange::generate();
+        let mut hello = PeerVersion::local();
+        hello.frame_key = Some(exchange.public_key());
+        self.key_offered = true;
+        write_hello_frame(&mut self.stream, &hello).await?;
+        self.cipher = read_hello_reply(&mut self.stream, exchange).await?;
+        Ok(())
+    }
+}
+
+/// Wait for the receiver's hello answering our frame key offer. Returns the
+/// cipher for our frames (None if the receiver doesn't take sealed frames).
+async fn read_hello_reply(
+    stream: &mut libp2p::Stream,
+    exchange: FrameKeyExchange,
+) -> std::io::Result<Option<FrameCipher>> {
+    match tokio::time::timeout(HELLO_REPLY_TIMEOUT, read_frame(stream)).await {
+        Ok(Ok(StreamFrame::Hello(reply))) => reply.frame_key.map(|key| exchange.opener_cipher(&key)).transpose(),
+        Ok(Ok(_)) => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "Expected a hello in reply to ours",
+        )),
+        Ok(Err(e)) => Err(e),
+        Err(_) => Err(std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            "No hello reply to our frame key offer",
+        )),
+    }
+}
+
 /// Per-peer writer handle for the background writer task.
 struct PeerWriterHandle {
-    writer: Arc<Mutex<libp2p::Stream>>,
+    writer: Arc<Mutex<LinkWriter>>,
     next_seq: Arc<AtomicU64>,
     /// Set to true on first write failure — prevents cascade of doomed writes.
     poisoned: Arc<AtomicBool>,
@@ -56,7 +162,17 @@ pub enum AckResult {
     Rejected(String),
 }
 
+impl AckResult {
+    /// Whether the peer rejected the shard because it is shutting down
+    pub fn is_draining(&self) -> bool {
+        matches!(self, AckResult::Rejected(reason) if reason == NACK_DRAINING)
+    }
+}
+
 /// An inbound shard received from a peer stream
+///
+/// Its payload is handed to the relay handler and on to the next
+/// `OutboundShard` without being copied again.
 #[derive(Debug)]
 pub struct InboundShard {
     pub peer: PeerId,
@@ -77,8 +193,10 @@ struct PeerConnection {
 }
 
 struct OutboundHandle {
-    writer: Arc<Mutex<libp2p::Stream>>,
+    writer: Arc<Mutex<LinkWriter>>,
     next_seq: Arc<AtomicU64>,
+    /// A frame key was offered on this stream (at open or later)
+    key_offered: bool,
 }
 
 struct InboundHandle {
@@ -96,9 +214,9 @@ pub struct StreamManager {
     /// Channel for receipts from ack frames
     receipt_tx: mpsc::Sender<ForwardReceipt>,
     /// Channel for receiving streams opened by background tasks
-    open_result_rx: mpsc::UnboundedReceiver<(PeerId, Result<libp2p::Stream, std::io::Error>)>,
+    open_result_rx: mpsc::UnboundedReceiver<(PeerId, Result<LinkWriter, std::io::Error>)>,
     /// Sender clone given to background tasks
-    open_result_tx: mpsc::UnboundedSender<(PeerId, Result<libp2p::Stream, std::io::Error>)>,
+    open_result_tx: mpsc::UnboundedSender<(PeerId, Result<LinkWriter, std::io::Error>)>,
     /// Outbound opens in flight (prevents duplicate spawns)
     opening: HashSet<PeerId>,
     /// Cooldown after failed opens — don't retry until Instant passes
@@ -109,6 +227,28 @@ pub struct StreamManager {
     write_fail_rx: mpsc::UnboundedReceiver<PeerId>,
     /// Channel for writer loop to request stream opens for buffered peers
     need_stream_rx: mpsc::UnboundedReceiver<PeerId>,
+    /// Peers that nacked a shard with `NACK_DRAINING` (reported by reader loops)
+    draining_rx: mpsc::UnboundedReceiver<PeerId>,
+    /// Sender clone given to reader loops
+    draining_tx: mpsc::UnboundedSender<PeerId>,
+    /// Versions announced by peers on their outbound (reported by reader loops)
+    version_rx: mpsc::UnboundedReceiver<(PeerId, PeerVersion)>,
+    /// Sender clone given to reader loops
+    version_tx: mpsc::UnboundedSender<(PeerId, PeerVersion)>,
+    /// Versions read from `version_rx` not yet taken by `take_peer_versions`
+    announced_versions: Vec<(PeerId, PeerVersion)>,
+    /// Protocol version each connected peer announced
+    peer_protocols: HashMap<PeerId, u16>,
+    /// Sender clone for frame key upgrades that fail (outbound is reopened)
+    write_fail_tx: mpsc::UnboundedSender<PeerId>,
+    /// Chain acks read from peers' streams (reported by reader loops)
+    chain_ack_rx: mpsc::Receiver<(PeerId, ChainAck)>,
+    /// Sender clone given to reader loops
+    chain_ack_tx: mpsc::Sender<(PeerId, ChainAck)>,
+    /// Per-peer shard byte counters (shared with the writer and reader loops)
+    counters: ShardCounters,
+    /// Offer and accept sealed frames on newly opened streams
+    frame_encryption: bool,
 }
 
 impl StreamManager {
@@ -124,6 +264,37 @@ impl StreamManager {
         mpsc::Receiver<ForwardReceipt>,
         mpsc::Sender<OutboundShard>,
     ) {
+        Self::with_connection_table(control, SharedConnectionTable::default())
+    }
+
+    /// Create a stream manager that counts shard bytes per peer in `connections`.
+    pub fn with_connection_table(
+        control: libp2p_stream::Control,
+        connections: SharedConnectionTable,
+    ) -> (
+        Self,
+        mpsc::Receiver<InboundShard>,
+        mpsc::Receiver<InboundShard>,
+        mpsc::Receiver<ForwardReceipt>,
+        mpsc::Sender<OutboundShard>,
+    ) {
+        Self::with_accounting(control, connections, SharedTrafficAccounting::default())
+    }
+
+    /// Create a stream manager that counts shard bytes per peer in
+    /// `connections` and in `traffic`.
+    pub fn with_accounting(
+        control: libp2p_stream::Control,
+        connections: SharedConnectionTable,
+        traffic: SharedTrafficAccounting,
+    ) -> (
+        Self,
+        mpsc::Receiver<InboundShard>,
+        mpsc::Receiver<InboundShard>,
+        mpsc::Receiver<ForwardReceipt>,
+        mpsc::Sender<OutboundShard>,
+    ) {
+        let counters = ShardCounters { connections, traffic };
         let (inbound_high_tx, inbound_high_rx) = mpsc::channel(16384);
         let (inbound_low_tx, inbound_low_rx) = mpsc::channel(8192);
         let (receipt_tx, receipt_rx) = mpsc::channel(8192);
@@ -131,14 +302,18 @@ impl StreamManager {
         let (outbound_tx, outbound_rx) = mpsc::channel::<OutboundShard>(8192);
         let (write_fail_tx, write_fail_rx) = mpsc::unbounded_channel();
         let (need_stream_tx, need_stream_rx) = mpsc::unbounded_channel();
+        let (draining_tx, draining_rx) = mpsc::unbounded_channel();
+        let (version_tx, version_rx) = mpsc::unbounded_channel();
+        let (chain_ack_tx, chain_ack_rx) = mpsc::channel(8192);
 
         let writer_registry: WriterRegistry = Arc::new(std::sync::RwLock::new(HashMap::new()));
 
         tokio::spawn(Self::outbound_writer_loop(
             writer_registry.clone(),
             outbound_rx,
-            write_fail_tx,
+            write_fail_tx.clone(),
             need_stream_tx,
+            counters.clone(),
         ));
 
         let mgr = Self {
@@ -154,11 +329,28 @@ impl StreamManager {
             writer_registry,
             write_fail_rx,
             need_stream_rx,
+            draining_rx,
+            draining_tx,
+            version_rx,
+            version_tx,
+            announced_versions: Vec::new(),
+            peer_protocols: HashMap::new(),
+            write_fail_tx,
+            chain_ack_rx,
+            chain_ack_tx,
+            counters,
+            frame_encryption: true,
         };
 
         (mgr, inbound_high_rx, inbound_low_rx, receipt_rx, outbound_tx)
     }
 
+    /// Offer (and accept) sealed frames on streams opened from now on.
+    /// Default: true. Streams already open keep what they negotiated.
+    pub fn set_frame_encryption(&mut self, enabled: bool) {
+        self.frame_encryption = enabled;
+    }
+
     /// Send a shard to a peer on our outbound stream.
     ///
     /// If no outbound exists, initiates a background open and returns `WouldBlock`.
@@ -192,11 +384,12 @@ impl StreamManager {
 
         let write_result = {
             let mut writer = out.writer.lock().await;
-            write_shard_frame(&mut *writer, shard, seq_id).await
+            writer.write_shard(shard, seq_id).await
         };
 
         match write_result {
             Ok(()) => {
+                self.counters.sent(&peer, shard.encoded_len() as u64);
                 if let Some(rx) = ack_rx {
                     match rx.await {
                         Ok(result) => Ok(Some(result)),
@@ -232,7 +425,11 @@ impl StreamManager {
                 let writer = out.writer.clone();
                 tokio::spawn(async move {
                     let mut w = writer.lock().await;
-                    if let Err(e) = write_ack_frame(&mut *w, seq_id, receipt.as_ref()).await {
+                    let result = match encode_ack_frame(seq_id, receipt.as_ref()) {
+                        Ok(frame) => w.write_frame(frame).await,
+                        Err(e) => Err(e),
+                    };
+                    if let Err(e) = result {
                         warn!("Ack write to {} failed: {}", peer, e);
                     }
                 });
@@ -244,6 +441,21 @@ impl StreamManager {
         }
     }
 
+    /// Send a chain ack to a peer on our outbound stream (fire-and-forget).
+    pub fn send_chain_ack(&self, peer: PeerId, ack: ChainAck) {
+        if let Some(out) = self.peers.get(&peer).and_then(|pc| pc.outbound.as_ref()) {
+            let writer = out.writer.clone();
+            tokio::spawn(async move {
+                let mut w = writer.lock().await;
+                if let Err(e) = w.write_frame(encode_chain_ack_frame(&ack)).await {
+                    warn!("Chain ack write to {} failed: {}", peer, e);
+                }
+            });
+        } else {
+            debug!("No outbound to peer {} for chain ack", peer);
+        }
+    }
+
     /// Send a nack frame to a peer on our outbound stream (fire-and-forget).
     ///
     /// Spawns the write in a background task to avoid blocking the drain loop.
@@ -254,7 +466,7 @@ impl StreamManager {
                 let reason = reason.to_owned();
                 tokio::spawn(async move {
                     let mut w = writer.lock().await;
-                    if let Err(e) = write_nack_frame(&mut *w, seq_id, &reason).await {
+                    if let Err(e) = w.write_frame(encode_nack_frame(seq_id, &reason)).await {
                         warn!("Nack write to {} failed: {}", peer, e);
                     }
                 });
@@ -325,6 +537,7 @@ impl StreamManager {
         self.opening.insert(peer);
         let mut control = self.control.clone();
         let tx = self.open_result_tx.clone();
+        let offer_key = self.offers_frame_key(&peer);
         tokio::spawn(async move {
             debug!("Background: opening outbound to {} ...", peer);
             match tokio::time::timeout(
@@ -334,7 +547,12 @@ impl StreamManager {
             .await
             {
                 Ok(Ok(stream)) => {
-                    let _ = tx.send((peer, Ok(stream)));
+                    // Announce our version before any shard can be written
+                    let result = Self::open_link(peer, stream, offer_key).await;
+                    if let Err(e) = &result {
+                        warn!("Background: hello to {} failed: {}", peer, e);
+                    }
+                    let _ = tx.send((peer, result));
                 }
                 Ok(Err(e)) => {
                     warn!("Background: outbound open to {} failed: {}", peer, e);
@@ -360,20 +578,81 @@ impl StreamManager {
         });
     }
 
+    /// Send our hello on a new outbound and, if we offer a frame key, wait
+    /// for the receiver's answer to set up sealed frames.
+    async fn open_link(peer: PeerId, mut stream: libp2p::Stream, offer_key: bool) -> std::io::Result<LinkWriter> {
+        let exchange = offer_key.then(FrameKeyExchange::generate);
+        let mut hello = PeerVersion::local();
+        hello.frame_key = exchange.as_ref().map(FrameKeyExchange::public_key);
+        write_hello_frame(&mut stream, &hello).await?;
+
+        let Some(exchange) = exchange else {
+            return Ok(LinkWriter { stream, cipher: None, key_offered: false });
+        };
+        let cipher = read_hello_reply(&mut stream, exchange).await?;
+        debug!("Outbound to {}: sealed frames {}", peer, if cipher.is_some() { "on" } else { "off" });
+        Ok(LinkWriter { stream, cipher, key_offered: true })
+    }
+
+    /// Whether a hello to `peer` offers a frame key: only to peers known to
+    /// answer one, so an older peer is never left to time out
+    fn offers_frame_key(&self, peer: &PeerId) -> bool {
+        self.frame_encryption && self.peer_protocols.get(peer).is_some_and(|v| *v >= FRAME_KEY_MIN_VERSION)
+    }
+
+    /// Record versions reported by reader loops, offering a frame key on
+    /// plaintext outbounds to peers now known to take one
+    fn drain_versions(&mut self) {
+        while let Ok((peer, version)) = self.version_rx.try_recv() {
+            self.peer_protocols.insert(peer, version.protocol_version);
+            self.announced_versions.push((peer, version));
+            self.maybe_offer_frame_key(peer);
+        }
+    }
+
+    /// Offer a frame key on our plaintext outbound to `peer` if it takes
+    /// one. A failed offer closes the outbound; it is reopened sealed.
+    fn maybe_offer_frame_key(&mut self, peer: PeerId) {
+        if !self.offers_frame_key(&peer) {
+            return;
+        }
+        let Some(out) = self.peers.get_mut(&peer).and_then(|pc| pc.outbound.as_mut()) else { return };
+        if out.key_offered {
+            return;
+        }
+        out.key_offered = true;
+        let writer = out.writer.clone();
+        let write_fail_tx = self.write_fail_tx.clone();
+        tokio::spawn(async move {
+            // Holding the writer keeps plaintext frames from following the offer
+            let mut w = writer.lock().await;
+            match w.offer_frame_key().await {
+                Ok(()) => debug!("Outbound to {}: sealed frames {}", peer, if w.cipher.is_some() { "on" } else { "off" }),
+                Err(e) => {
+                    warn!("Frame key offer to {} failed: {} — reopening", peer, e);
+                    let _ = write_fail_tx.send(peer);
+                }
+            }
+        });
+    }
+
     /// Collect completed background outbound opens and drain write failures.
     pub fn poll_open_streams(&mut self) -> usize {
+        self.drain_versions();
         let mut opened = 0;
         while let Ok((peer, result)) = self.open_result_rx.try_recv() {
             self.opening.remove(&peer);
             match result {
-                Ok(stream) => {
+                Ok(writer) => {
                     if self.peers.get(&peer).map_or(false, |pc| pc.outbound.is_some()) {
                         debug!("Outbound to {} ready but already have one — dropping", peer);
                         continue;
                     }
-                    self.register_outbound(peer, stream);
+                    self.register_outbound(peer, writer);
                     debug!("Opened outbound to peer {}", peer);
                     opened += 1;
+                    // The version may have been learned while the open ran
+                    self.maybe_offer_frame_key(peer);
                 }
                 Err(e) => {
                     debug!("Background outbound open to {} failed: {}", peer, e);
@@ -439,6 +718,42 @@ impl StreamManager {
         self.peers.get(peer).map_or(false, |pc| pc.outbound.is_some())
     }
 
+    /// Return all peers with a live inbound stream (peers sending to us).
+    pub fn inbound_peers(&self) -> Vec<PeerId> {
+        self.peers
+            .iter()
+            .filter(|(_, pc)| pc.inbound.as_ref().is_some_and(|i| !i.reader_handle.is_finished()))
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    /// Peers that reported they are draining since the last call.
+    pub fn take_draining_peers(&mut self) -> HashSet<PeerId> {
+        let mut peers = HashSet::new();
+        while let Ok(peer) = self.draining_rx.try_recv() {
+            peers.insert(peer);
+        }
+        peers
+    }
+
+    /// Versions peers announced since the last call.
+    ///
+    /// A peer whose inbound starts without a hello predates version
+    /// negotiation and is reported as [`PeerVersion::legacy`].
+    pub fn take_peer_versions(&mut self) -> Vec<(PeerId, PeerVersion)> {
+        self.drain_versions();
+        std::mem::take(&mut self.announced_versions)
+    }
+
+    /// Chain acks peers sent us since the last call, with the peer each came from.
+    pub fn take_chain_acks(&mut self) -> Vec<(PeerId, ChainAck)> {
+        let mut acks = Vec::new();
+        while let Ok(entry) = self.chain_ack_rx.try_recv() {
+            acks.push(entry);
+        }
+        acks
+    }
+
     /// Return all peers we have outbound streams to.
     pub fn stream_peers(&self) -> Vec<PeerId> {
         self.peers
@@ -483,6 +798,7 @@ impl StreamManager {
     pub fn on_peer_disconnected(&mut self, peer: &PeerId) {
         self.opening.remove(peer);
         self.open_cooldown.remove(peer);
+        self.peer_protocols.remove(peer);
         // Close both directions independently
         if let Some(pc) = self.peers.remove(peer) {
             if pc.outbound.is_some() {
@@ -528,8 +844,9 @@ impl StreamManager {
     }
 
     /// Register a newly opened stream as our outbound to a peer.
-    fn register_outbound(&mut self, peer: PeerId, stream: libp2p::Stream) {
-        let writer_arc = Arc::new(Mutex::new(stream));
+    fn register_outbound(&mut self, peer: PeerId, writer: LinkWriter) {
+        let key_offered = writer.key_offered;
+        let writer_arc = Arc::new(Mutex::new(writer));
         let seq_arc = Arc::new(AtomicU64::new(0));
         let poisoned_arc = Arc::new(AtomicBool::new(false));
 
@@ -546,6 +863,7 @@ impl StreamManager {
         pc.outbound = Some(OutboundHandle {
             writer: writer_arc,
             next_seq: seq_arc,
+            key_offered,
         });
     }
 
@@ -568,7 +886,12 @@ impl StreamManager {
             self.inbound_high_tx.clone(),
             self.inbound_low_tx.clone(),
             self.receipt_tx.clone(),
+            self.draining_tx.clone(),
+            self.version_tx.clone(),
+            self.chain_ack_tx.clone(),
+            self.counters.clone(),
             tier,
+            self.frame_encryption,
         ));
 
         self.peers.get_mut(&peer).unwrap().inbound = Some(InboundHandle { reader_handle });
@@ -585,6 +908,7 @@ impl StreamManager {
         mut rx: mpsc::Receiver<OutboundShard>,
         write_fail_tx: mpsc::UnboundedSender<PeerId>,
         need_stream_tx: mpsc::UnboundedSender<PeerId>,
+        counters: ShardCounters,
     ) {
         let mut retry_buf: VecDeque<OutboundShard> = VecDeque::new();
         let mut flush_interval = tokio::time::interval(std::time::Duration::from_millis(100));
@@ -601,7 +925,7 @@ impl StreamManager {
                         debug!("Outbound writer channel closed, exiting");
                         break;
                     };
-                    Self::try_write_or_buffer(&registry, &write_fail_tx, &need_stream_tx, &write_retry_tx, outbound, &mut retry_buf);
+                    Self::try_write_or_buffer(&registry, &write_fail_tx, &need_stream_tx, &write_retry_tx, &counters, outbound, &mut retry_buf);
                 }
                 // Reclaim shards from failed writes for retry on fresh streams.
                 retry_msg = write_retry_rx.recv() => {
@@ -612,7 +936,7 @@ impl StreamManager {
                     }
                 }
                 _ = flush_interval.tick() => {
-                    Self::flush_retry_buffer(&registry, &write_fail_tx, &need_stream_tx, &write_retry_tx, &mut retry_buf);
+                    Self::flush_retry_buffer(&registry, &write_fail_tx, &need_stream_tx, &write_retry_tx, &counters, &mut retry_buf);
                 }
             }
         }
@@ -624,6 +948,7 @@ impl StreamManager {
         write_fail_tx: &mpsc::UnboundedSender<PeerId>,
         need_stream_tx: &mpsc::UnboundedSender<PeerId>,
         write_retry_tx: &mpsc::UnboundedSender<OutboundShard>,
+        counters: &ShardCounters,
         outbound: OutboundShard,
         retry_buf: &mut VecDeque<OutboundShard>,
     ) {
@@ -646,6 +971,7 @@ impl StreamManager {
             let wf_tx = write_fail_tx.clone();
             let retry_tx = write_retry_tx.clone();
             let reg = registry.clone();
+            let counters = counters.clone();
             tokio::spawn(async move {
                 // Double-check poison after acquiring mutex (another task may have failed first).
                 if poisoned.load(Ordering::Relaxed) {
@@ -653,7 +979,7 @@ impl StreamManager {
                     return;
                 }
                 let mut w = writer.lock().await;
-                if let Err(e) = write_shard_frame(&mut *w, &outbound.shard, seq_id).await {
+                if let Err(e) = w.write_shard(&outbound.shard, seq_id).await {
                     warn!("Outbound write to {} failed: {}", peer, e);
                     // Poison the handle so other in-flight tasks skip immediately.
                     poisoned.store(true, Ordering::Relaxed);
@@ -662,6 +988,8 @@ impl StreamManager {
                     let _ = wf_tx.send(peer);
                     // Return the shard for retry on a fresh stream.
                     let _ = retry_tx.send(outbound);
+                } else {
+                    counters.sent(&peer, outbound.shard.encoded_len() as u64);
                 }
             });
         } else {
@@ -682,6 +1010,7 @@ impl StreamManager {
         write_fail_tx: &mpsc::UnboundedSender<PeerId>,
         need_stream_tx: &mpsc::UnboundedSender<PeerId>,
         write_retry_tx: &mpsc::UnboundedSender<OutboundShard>,
+        counters: &ShardCounters,
         retry_buf: &mut VecDeque<OutboundShard>,
     ) {
         let mut remaining = VecDeque::new();
@@ -703,19 +1032,22 @@ impl StreamManager {
                 let wf_tx = write_fail_tx.clone();
                 let retry_tx = write_retry_tx.clone();
                 let reg = registry.clone();
+                let counters = counters.clone();
                 tokio::spawn(async move {
                     if poisoned.load(Ordering::Relaxed) {
                         let _ = retry_tx.send(outbound);
                         return;
                     }
                     let mut w = writer.lock().await;
-                    if let Err(e) = write_shard_frame(&mut *w, &outbound.shard, seq_id).await {
+                    if let Err(e) = w.write_shard(&outbound.shard, seq_id).await {
                         warn!("Outbound write to {} failed: {}", peer, e);
                         poisoned.store(true, Ordering::Relaxed);
                         drop(w);
                         reg.write().unwrap().remove(&peer);
                         let _ = wf_tx.send(peer);
                         let _ = retry_tx.send(outbound);
+                    } else {
+                        counters.sent(&peer, outbound.shard.encoded_len() as u64);
                     }
                 });
             } else {
@@ -739,6 +1071,10 @@ impl StreamManager {
     ///
     /// Reads frames in a loop. Shard frames dispatch to priority channels.
     /// Ack/nack frames resolve pending_acks (from shards we sent on our outbound).
+    /// The first frame's hello (or its absence) is reported on `version_tx`,
+    /// chain acks on `chain_ack_tx`. A hello offering a frame key is answered
+    /// on the same stream; after that only sealed frames are read.
+    #[allow(clippy::too_many_arguments)]
     async fn reader_loop(
         peer: PeerId,
         mut stream: libp2p::Stream,
@@ -746,11 +1082,55 @@ impl StreamManager {
         inbound_high_tx: mpsc::Sender<InboundShard>,
         inbound_low_tx: mpsc::Sender<InboundShard>,
         receipt_tx: mpsc::Sender<ForwardReceipt>,
+        draining_tx: mpsc::UnboundedSender<PeerId>,
+        version_tx: mpsc::UnboundedSender<(PeerId, PeerVersion)>,
+        chain_ack_tx: mpsc::Sender<(PeerId, ChainAck)>,
+        counters: ShardCounters,
         tier: Arc<AtomicU8>,
+        frame_encryption: bool,
     ) {
+        let mut announced = false;
+        let mut cipher: Option<FrameCipher> = None;
         loop {
-            match read_frame(&mut stream).await {
+            let frame = match read_frame(&mut stream).await {
+                Ok(StreamFrame::Sealed { counter, ciphertext }) => match cipher.as_mut() {
+                    Some(cipher) => cipher.open(counter, ciphertext),
+                    None => Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "Sealed frame without a frame key",
+                    )),
+                },
+                // No downgrade: once keyed, everything must be sealed
+                Ok(_) if cipher.is_some() => Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "Plaintext frame on a sealed stream",
+                )),
+                frame => frame,
+            };
+            if !announced && frame.is_ok() {
+                announced = true;
+                if !matches!(frame, Ok(StreamFrame::Hello(_))) {
+                    let _ = version_tx.send((peer, PeerVersion::legacy()));
+                }
+            }
+            match frame {
+                Ok(StreamFrame::Hello(version)) => {
+                    debug!("Peer {} speaks protocol v{} ({:?})", peer, version.protocol_version, version.user_agent);
+                    // The offer comes in the first hello, or in a later one
+                    // once the opener learned our version
+                    if let Some(opener_key) = version.frame_key.filter(|_| cipher.is_none()) {
+                        match Self::answer_hello(&mut stream, opener_key, frame_encryption).await {
+                            Ok(agreed) => cipher = agreed,
+                            Err(e) => {
+                                warn!("Hello answer to {} failed: {}", peer, e);
+                                break;
+                            }
+                        }
+                    }
+                    let _ = version_tx.send((peer, PeerVersion { frame_key: None, ..version }));
+                }
                 Ok(StreamFrame::Shard { seq_id, shard }) => {
+                    counters.received(&peer, shard.encoded_len() as u64);
                     let inbound = InboundShard {
                         peer,
                         seq_id,
@@ -787,13 +1167,22 @@ impl StreamManager {
                         let _ = receipt_tx.try_send(r);
                     }
                 }
+                Ok(StreamFrame::ChainAck(ack)) => {
+                    if chain_ack_tx.try_send((peer, ack)).is_err() {
+                        debug!("Chain ack channel full — dropping ack from {}", peer);
+                    }
+                }
                 Ok(StreamFrame::Nack { seq_id, reason }) => {
                     let sender = pending_acks.lock().unwrap().remove(&seq_id);
+                    if reason == NACK_DRAINING {
+                        let _ = draining_tx.send(peer);
+                    }
                     if let Some(tx) = sender {
                         let _ = tx.send(AckResult::Rejected(reason.clone()));
                     }
                     debug!("Nack from {} (seq={}): {}", peer, seq_id, reason);
                 }
+                Ok(StreamFrame::Sealed { .. }) => unreachable!("sealed frames are opened above"),
                 Err(e) => {
                     if e.kind() == std::io::ErrorKind::UnexpectedEof {
                         debug!("Inbound from {} closed (EOF)", peer);
@@ -807,6 +1196,25 @@ impl StreamManager {
 
         debug!("Reader loop ended for peer {}", peer);
     }
+
+    /// Answer a hello that offered `opener_key`, with our own key if we
+    /// take sealed frames. Returns the cipher for the opener's frames.
+    async fn answer_hello(
+        stream: &mut libp2p::Stream,
+        opener_key: [u8; 32],
+        frame_encryption: bool,
+    ) -> std::io::Result<Option<FrameCipher>> {
+        let mut reply = PeerVersion::local();
+        let cipher = if frame_encryption {
+            let exchange = FrameKeyExchange::generate();
+            reply.frame_key = Some(exchange.public_key());
+            Some(exchange.responder_cipher(&opener_key)?)
+        } else {
+            None
+        };
+        write_hello_frame(stream, &reply).await?;
+        Ok(cipher)
+    }
 }
 
 #[cfg(test)]
@@ -886,4 +1294,37 @@ mod tests {
         let (mgr, _, _, _, _) = make_manager();
         assert!(mgr.stream_peers().is_empty());
     }
+
+    #[tokio::test]
+    async fn test_draining_nack() {
+        let (mut mgr, _, _, _, _) = make_manager();
+        assert!(mgr.inbound_peers().is_empty());
+        assert!(mgr.take_draining_peers().is_empty());
+
+        assert!(AckResult::Rejected(NACK_DRAINING.to_string()).is_draining());
+        assert!(!AckResult::Rejected("Not in relay mode".to_string()).is_draining());
+        assert!(!AckResult::Accepted(None).is_draining());
+    }
+
+    #[tokio::test]
+    async fn test_frame_key_offered_only_to_known_v2_peers() {
+        let (mut mgr, _, _, _, _) = make_manager();
+        let (current, legacy) = (test_peer(), test_peer());
+        // Unknown peers get a plaintext hello rather than a 2s wait
+        assert!(!mgr.offers_frame_key(&current));
+
+        mgr.version_tx.send((current, PeerVersion::local())).unwrap();
+        mgr.version_tx.send((legacy, PeerVersion::legacy())).unwrap();
+        // Versions seen while polling opens are still reported
+        mgr.poll_open_streams();
+        assert_eq!(mgr.take_peer_versions().len(), 2);
+        assert!(mgr.offers_frame_key(&current));
+        assert!(!mgr.offers_frame_key(&legacy));
+
+        mgr.set_frame_encryption(false);
+        assert!(!mgr.offers_frame_key(&current));
+        mgr.set_frame_encryption(true);
+        mgr.on_peer_disconnected(&current);
+        assert!(!mgr.offers_frame_key(&current));
+    }
 }
@@ -7,6 +7,8 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::wire::{self, Extensions};
+
 /// Subscription announcement broadcast by clients via gossipsub.
 ///
 /// When a client connects, it announces its subscription status.
@@ -27,14 +29,24 @@ pub struct SubscriptionAnnouncement {
 }
 
 impl SubscriptionAnnouncement {
-    /// Serialize to bytes (bincode)
+    /// Serialize to bytes (bincode body + [`Extensions`] trailer)
     pub fn to_bytes(&self) -> Vec<u8> {
-        bincode::serialize(self).expect("SubscriptionAnnouncement serialization cannot fail")
+        self.to_bytes_with_extensions(&Extensions::new())
+    }
+
+    /// Serialize with extension fields
+    pub fn to_bytes_with_extensions(&self, extensions: &Extensions) -> Vec<u8> {
+        wire::encode(self, extensions)
     }
 
-    /// Deserialize from bytes
+    /// Deserialize from bytes, ignoring extension fields
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, bincode::Error> {
-        bincode::deserialize(bytes)
+        Self::from_bytes_with_extensions(bytes).map(|(msg, _)| msg)
+    }
+
+    /// Deserialize from bytes along with the sender's extension fields
+    pub fn from_bytes_with_extensions(bytes: &[u8]) -> Result<(Self, Extensions), bincode::Error> {
+        wire::decode(bytes)
     }
 
     /// Data that gets signed (excludes signature field)
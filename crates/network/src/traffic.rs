@@ -0,0 +1,277 @@
+//! Per-peer traffic accounting
+//!
+//! Bytes exchanged with each peer, split by protocol: shard streams, gossip
+//! and the DHT. Unlike the [`ConnectionTable`](crate::ConnectionTable), rows
+//! outlive the connection, so a peer can't clear its history by
+//! reconnecting; the least recently active rows are evicted once more than
+//! [`MAX_TRACKED_PEERS`] peers are tracked.
+//!
+//! The stream manager counts shard frames. The swarm driver counts gossip
+//! (messages read from a peer, and our publications once per mesh peer of
+//! the topic) and DHT record payloads (records a peer pushed to us or
+//! answered a lookup with). Nodes attached to a shared swarm only count
+//! shard streams.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+
+use crate::node::NetworkEvent;
+
+/// Traffic accounting shared by the swarm driver, stream manager and node
+pub type SharedTrafficAccounting = Arc<RwLock<TrafficAccounting>>;
+
+/// Peers with a traffic row before the least recently active is evicted
+pub const MAX_TRACKED_PEERS: usize = 4096;
+
+/// Number of accounted protocols
+const PROTOCOLS: usize = 3;
+
+/// Protocol traffic is accounted under
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrafficProtocol {
+    ShardStream,
+    Gossip,
+    Dht,
+}
+
+impl TrafficProtocol {
+    /// All protocols (the index order of [`PeerTraffic::protocols`])
+    pub const ALL: [TrafficProtocol; PROTOCOLS] = [
+        TrafficProtocol::ShardStream,
+        TrafficProtocol::Gossip,
+        TrafficProtocol::Dht,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TrafficProtocol::ShardStream => "shard_stream",
+            TrafficProtocol::Gossip => "gossip",
+            TrafficProtocol::Dht => "dht",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Bytes in each direction
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrafficCounters {
+    /// Bytes read from the peer
+    pub bytes_in: u64,
+    /// Bytes written to the peer
+    pub bytes_out: u64,
+}
+
+impl TrafficCounters {
+    pub fn total(&self) -> u64 {
+        self.bytes_in.saturating_add(self.bytes_out)
+    }
+
+    fn add(&mut self, other: &TrafficCounters) {
+        self.bytes_in = self.bytes_in.saturating_add(other.bytes_in);
+        self.bytes_out = self.bytes_out.saturating_add(other.bytes_out);
+    }
+}
+
+/// Traffic exchanged with one peer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerTraffic {
+    pub peer_id: PeerId,
+    /// Counters per protocol, indexed like [`TrafficProtocol::ALL`]
+    pub protocols: [TrafficCounters; PROTOCOLS],
+    /// Accounting sequence number of the last recorded bytes (orders rows
+    /// by recency for eviction)
+    last_active: u64,
+}
+
+impl PeerTraffic {
+    fn new(peer_id: PeerId) -> Self {
+        Self { peer_id, protocols: [TrafficCounters::default(); PROTOCOLS], last_active: 0 }
+    }
+
+    /// Counters of one protocol
+    pub fn get(&self, protocol: TrafficProtocol) -> TrafficCounters {
+        self.protocols[protocol.index()]
+    }
+
+    /// Counters summed over all protocols
+    pub fn total(&self) -> TrafficCounters {
+        let mut total = TrafficCounters::default();
+        for counters in &self.protocols {
+            total.add(counters);
+        }
+        total
+    }
+}
+
+/// When a peer counts as a freeloader: it pushed at least `min_bytes` of
+/// shards to us while taking less than `min_ratio` as much back
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FreeloaderPolicy {
+    pub min_bytes: u64,
+    pub min_ratio: f64,
+}
+
+impl Default for FreeloaderPolicy {
+    fn default() -> Self {
+        Self { min_bytes: 64 * 1024 * 1024, min_ratio: 0.05 }
+    }
+}
+
+impl FreeloaderPolicy {
+    /// Whether `traffic` matches the policy (shard streams only: gossip and
+    /// DHT traffic isn't carried on anyone's behalf)
+    pub fn matches(&self, traffic: &PeerTraffic) -> bool {
+        let shards = traffic.get(TrafficProtocol::ShardStream);
+        shards.bytes_in >= self.min_bytes && (shards.bytes_out as f64) < shards.bytes_in as f64 * self.min_ratio
+    }
+}
+
+/// Traffic by peer, plus totals that survive eviction
+#[derive(Debug, Default)]
+pub struct TrafficAccounting {
+    peers: HashMap<PeerId, PeerTraffic>,
+    totals: [TrafficCounters; PROTOCOLS],
+    /// Bumped on every record, see [`PeerTraffic::last_active`]
+    seq: u64,
+}
+
+impl TrafficAccounting {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `bytes` of `protocol` read from `peer`
+    pub fn record_in(&mut self, peer: PeerId, protocol: TrafficProtocol, bytes: u64) {
+        self.record(peer, protocol, TrafficCounters { bytes_in: bytes, bytes_out: 0 });
+    }
+
+    /// `bytes` of `protocol` written to `peer`
+    pub fn record_out(&mut self, peer: PeerId, protocol: TrafficProtocol, bytes: u64) {
+        self.record(peer, protocol, TrafficCounters { bytes_in: 0, bytes_out: bytes });
+    }
+
+    fn record(&mut self, peer: PeerId, protocol: TrafficProtocol, counters: TrafficCounters) {
+        self.seq += 1;
+        self.totals[protocol.index()].add(&counters);
+        let row = self.peers.entry(peer).or_insert_with(|| PeerTraffic::new(peer));
+        row.protocols[protocol.index()].add(&counters);
+        row.last_active = self.seq;
+        if self.peers.len() > MAX_TRACKED_PEERS {
+            self.evict_idlest();
+        }
+    }
+
+    fn evict_idlest(&mut self) {
+        if let Some(idlest) = self.peers.values().min_by_key(|p| p.last_active).map(|p| p.peer_id) {
+            self.peers.remove(&idlest);
+        }
+    }
+
+    pub fn get(&self, peer: &PeerId) -> Option<&PeerTraffic> {
+        self.peers.get(peer)
+    }
+
+    /// Tracked peers
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+
+    /// All tracked peers, most bytes first
+    pub fn peers(&self) -> Vec<PeerTraffic> {
+        let mut peers: Vec<_> = self.peers.values().cloned().collect();
+        peers.sort_by_key(|p| (std::cmp::Reverse(p.total().total()), p.peer_id.to_bytes()));
+        peers
+    }
+
+    /// Counters of one protocol over all peers, evicted ones included
+    pub fn total(&self, protocol: TrafficProtocol) -> TrafficCounters {
+        self.totals[protocol.index()]
+    }
+
+    /// Whether `peer` is a freeloader under `policy`
+    pub fn is_freeloader(&self, peer: &PeerId, policy: &FreeloaderPolicy) -> bool {
+        self.peers.get(peer).is_some_and(|p| policy.matches(p))
+    }
+
+    /// Tracked peers that are freeloaders under `policy`
+    pub fn freeloaders(&self, policy: &FreeloaderPolicy) -> Vec<PeerId> {
+        self.peers.values().filter(|p| policy.matches(p)).map(|p| p.peer_id).collect()
+    }
+
+    /// Snapshot of all tracked peers as a [`NetworkEvent::TrafficReport`]
+    pub fn report(&self) -> NetworkEvent {
+        NetworkEvent::TrafficReport(self.peers())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_per_peer_and_protocol() {
+        let mut traffic = TrafficAccounting::new();
+        let (a, b) = (PeerId::random(), PeerId::random());
+        traffic.record_in(a, TrafficProtocol::ShardStream, 1000);
+        traffic.record_out(a, TrafficProtocol::ShardStream, 300);
+        traffic.record_in(a, TrafficProtocol::Gossip, 50);
+        traffic.record_out(b, TrafficProtocol::Dht, 20);
+
+        let row = traffic.get(&a).unwrap();
+        assert_eq!(row.get(TrafficProtocol::ShardStream), TrafficCounters { bytes_in: 1000, bytes_out: 300 });
+        assert_eq!(row.total(), TrafficCounters { bytes_in: 1050, bytes_out: 300 });
+        assert_eq!(traffic.total(TrafficProtocol::Dht).bytes_out, 20);
+
+        let peers = traffic.peers();
+        assert_eq!(peers.iter().map(|p| p.peer_id).collect::<Vec<_>>(), vec![a, b]);
+        assert!(matches!(traffic.report(), NetworkEvent::TrafficReport(rows) if rows.len() == 2));
+    }
+
+    #[test]
+    fn test_freeloaders() {
+        let mut traffic = TrafficAccounting::new();
+        let policy = FreeloaderPolicy { min_bytes: 1000, min_ratio: 0.1 };
+        let (taker, fair, small) = (PeerId::random(), PeerId::random(), PeerId::random());
+        traffic.record_in(taker, TrafficProtocol::ShardStream, 5000);
+        traffic.record_out(taker, TrafficProtocol::ShardStream, 100);
+        // Gossip we send doesn't make up for shards we carry
+        traffic.record_out(taker, TrafficProtocol::Gossip, 10_000);
+        traffic.record_in(fair, TrafficProtocol::ShardStream, 5000);
+        traffic.record_out(fair, TrafficProtocol::ShardStream, 4000);
+        traffic.record_in(small, TrafficProtocol::ShardStream, 999);
+
+        assert!(traffic.is_freeloader(&taker, &policy));
+        assert!(!traffic.is_freeloader(&fair, &policy));
+        assert!(!traffic.is_freeloader(&small, &policy));
+        assert_eq!(traffic.freeloaders(&policy), vec![taker]);
+    }
+
+    #[test]
+    fn test_evicts_least_recently_active() {
+        let mut traffic = TrafficAccounting::new();
+        let first = PeerId::random();
+        traffic.record_in(first, TrafficProtocol::Gossip, 1);
+        for _ in 1..MAX_TRACKED_PEERS {
+            traffic.record_in(PeerId::random(), TrafficProtocol::Gossip, 1);
+        }
+        // Activity keeps the first row over the rows added after it
+        traffic.record_in(first, TrafficProtocol::Gossip, 1);
+        traffic.record_in(PeerId::random(), TrafficProtocol::Gossip, 1);
+
+        assert_eq!(traffic.len(), MAX_TRACKED_PEERS);
+        assert!(traffic.get(&first).is_some());
+        // Totals still cover evicted rows
+        assert_eq!(traffic.total(TrafficProtocol::Gossip).bytes_in, MAX_TRACKED_PEERS as u64 + 2);
+    }
+}
@@ -0,0 +1,118 @@
+//! WebRTC-direct certificates for browser clients
+//!
+//! Browsers dial a WebRTC-direct listener using an address that embeds the
+//! hash of the listener's self-signed certificate
+//! (`/udp/<port>/webrtc-direct/certhash/<hash>`). If the certificate changed
+//! on every restart, every published browser bootstrap address would go
+//! stale, so relays keep theirs in a PEM file and reuse it.
+
+use std::path::Path;
+
+use libp2p::multiaddr::Protocol;
+use libp2p::Multiaddr;
+use libp2p_webrtc::tokio::Certificate;
+use tracing::{info, warn};
+
+use crate::NetworkError;
+
+/// Load the certificate at `path`, or generate one and save it there.
+///
+/// A file that can't be parsed is replaced with a fresh certificate (the
+/// certhash changes, which is logged).
+pub fn load_or_generate_certificate(path: &Path) -> Result<Certificate, NetworkError> {
+    if path.exists() {
+        let pem = std::fs::read_to_string(path)
+            .map_err(|e| NetworkError::Transport(format!("read {}: {}", path.display(), e)))?;
+        match Certificate::from_pem(&pem) {
+            Ok(cert) => return Ok(cert),
+            Err(e) => warn!("Invalid WebRTC certificate {} ({}), generating a new one", path.display(), e),
+        }
+    }
+
+    let cert = generate_certificate()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| NetworkError::Transport(format!("create {}: {}", parent.display(), e)))?;
+    }
+    let tmp = path.with_extension("pem.tmp");
+    std::fs::write(&tmp, cert.serialize_pem())
+        .and_then(|_| std::fs::rename(&tmp, path))
+        .map_err(|e| NetworkError::Transport(format!("write {}: {}", path.display(), e)))?;
+    info!("Generated WebRTC certificate {} (certhash {})", path.display(), certhash_string(&cert));
+    Ok(cert)
+}
+
+/// Generate a throwaway certificate (certhash changes on every start)
+pub fn generate_certificate() -> Result<Certificate, NetworkError> {
+    Certificate::generate(&mut rand::thread_rng())
+        .map_err(|e| NetworkError::Transport(format!("WebRTC certificate: {}", e)))
+}
+
+/// `/certhash` protocol component for a certificate
+pub fn certhash(cert: &Certificate) -> Protocol<'static> {
+    Protocol::Certhash(cert.fingerprint().to_multihash())
+}
+
+/// Certhash as it appears in a multiaddr string
+pub fn certhash_string(cert: &Certificate) -> String {
+    Multiaddr::empty()
+        .with(certhash(cert))
+        .to_string()
+        .trim_start_matches("/certhash/")
+        .to_string()
+}
+
+/// `addr` with its certhash replaced by (or extended with) `cert`'s.
+///
+/// Any `/p2p` suffix stays last so the result is still dialable.
+pub fn with_certhash(addr: &Multiaddr, cert: &Certificate) -> Multiaddr {
+    let mut out = Multiaddr::empty();
+    let mut peer = None;
+    for proto in addr.iter() {
+        match proto {
+            Protocol::Certhash(_) => {}
+            Protocol::P2p(id) => peer = Some(id),
+            other => out.push(other),
+        }
+    }
+    out.push(certhash(cert));
+    if let Some(id) = peer {
+        out.push(Protocol::P2p(id));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{is_browser_dialable, webrtc_listen_addr};
+
+    #[test]
+    fn test_certificate_persisted() {
+        let path = std::env::temp_dir().join(format!("craftnet-webrtc-{}.pem", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let first = load_or_generate_certificate(&path).unwrap();
+        let second = load_or_generate_certificate(&path).unwrap();
+        assert_eq!(certhash_string(&first), certhash_string(&second));
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_with_certhash() {
+        let cert = generate_certificate().unwrap();
+        let peer = libp2p::PeerId::random();
+        let addr = webrtc_listen_addr(9001).with(Protocol::P2p(peer));
+
+        let dialable = with_certhash(&addr, &cert);
+        assert!(is_browser_dialable(&dialable));
+        assert!(matches!(dialable.iter().last(), Some(Protocol::P2p(p)) if p == peer));
+
+        // Replacing keeps a single certhash
+        let other = generate_certificate().unwrap();
+        let replaced = with_certhash(&dialable, &other);
+        assert_eq!(replaced.iter().filter(|p| matches!(p, Protocol::Certhash(_))).count(), 1);
+        assert!(replaced.to_string().contains(&certhash_string(&other)));
+    }
+}
@@ -0,0 +1,194 @@
+//! Versioned gossip payload encoding
+//!
+//! Bincode payloads are not self-describing: a field appended to a message
+//! struct breaks every peer that has not upgraded. Bincode gossip messages
+//! therefore keep their original fields as the body and carry everything
+//! newer in a trailer of tagged extension fields:
+//!
+//! ```text
+//! body (bincode) | TRAILER_MAGIC | version: u8 | extension*
+//! extension = tag: u16 LE | len: u32 LE | value: [u8; len]
+//! ```
+//!
+//! Bincode's default decoder ignores trailing bytes, so peers that predate
+//! the trailer still decode the body, and decoders here skip tags they do
+//! not know. A new field gets a fresh tag and must be optional.
+//!
+//! Extensions are not covered by the message signature (old verifiers could
+//! not check it); a field that needs integrity carries its own.
+//!
+//! JSON messages (status heartbeats) need no trailer: unknown fields are
+//! ignored, and new fields are added with `#[serde(default)]`.
+
+use std::collections::BTreeMap;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Version this node writes into gossip payloads.
+/// 0 is reported for payloads from peers that predate versioning.
+pub const WIRE_VERSION: u8 = 1;
+
+/// First trailer byte
+const TRAILER_MAGIC: u8 = 0xCE;
+
+/// Extension tag: the sender's [`PROTOCOL_VERSION`](crate::PROTOCOL_VERSION) (u16 LE)
+pub const EXT_PROTOCOL_VERSION: u16 = 1;
+
+/// Extension fields carried after a message body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Extensions {
+    version: u8,
+    fields: BTreeMap<u16, Vec<u8>>,
+}
+
+impl Default for Extensions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Extensions {
+    /// No extensions, at this node's [`WIRE_VERSION`]
+    pub fn new() -> Self {
+        Self { version: WIRE_VERSION, fields: BTreeMap::new() }
+    }
+
+    /// Wire version of the sender (0 = no trailer)
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Value of extension `tag`, if the sender set it
+    pub fn get(&self, tag: u16) -> Option<&[u8]> {
+        self.fields.get(&tag).map(Vec::as_slice)
+    }
+
+    /// Set extension `tag`
+    pub fn insert(&mut self, tag: u16, value: Vec<u8>) {
+        self.fields.insert(tag, value);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Set [`EXT_PROTOCOL_VERSION`]
+    pub fn with_protocol_version(mut self, version: u16) -> Self {
+        self.insert(EXT_PROTOCOL_VERSION, version.to_le_bytes().to_vec());
+        self
+    }
+
+    /// The sender's protocol version, if it announced one
+    pub fn protocol_version(&self) -> Option<u16> {
+        let bytes = self.get(EXT_PROTOCOL_VERSION)?;
+        Some(u16::from_le_bytes(bytes.try_into().ok()?))
+    }
+
+    fn legacy() -> Self {
+        Self { version: 0, fields: BTreeMap::new() }
+    }
+
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        out.push(TRAILER_MAGIC);
+        out.push(self.version);
+        for (tag, value) in &self.fields {
+            out.extend_from_slice(&tag.to_le_bytes());
+            out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            out.extend_from_slice(value);
+        }
+    }
+
+    /// Parse a trailer. Bytes that do not start with the magic are
+    /// ignored, as a pre-trailer decoder would.
+    fn decode(mut bytes: &[u8]) -> Result<Self, bincode::Error> {
+        let malformed = || Box::new(bincode::ErrorKind::Custom("malformed extension trailer".to_string()));
+
+        let [TRAILER_MAGIC, version, rest @ ..] = bytes else {
+            return Ok(Self::legacy());
+        };
+        let mut extensions = Self { version: *version, fields: BTreeMap::new() };
+        bytes = rest;
+        while !bytes.is_empty() {
+            if bytes.len() < 6 {
+                return Err(malformed());
+            }
+            let tag = u16::from_le_bytes([bytes[0], bytes[1]]);
+            let len = u32::from_le_bytes([bytes[2], bytes[3], bytes[4], bytes[5]]) as usize;
+            let value = bytes.get(6..6 + len).ok_or_else(malformed)?;
+            extensions.fields.insert(tag, value.to_vec());
+            bytes = &bytes[6 + len..];
+        }
+        Ok(extensions)
+    }
+}
+
+/// Encode `body` followed by the extension trailer
+pub(crate) fn encode<T: Serialize>(body: &T, extensions: &Extensions) -> Vec<u8> {
+    let mut out = bincode::serialize(body).expect("gossip message serialization should not fail");
+    extensions.encode_into(&mut out);
+    out
+}
+
+/// Decode a body and its extension trailer (legacy payloads have none)
+pub(crate) fn decode<T: Serialize + DeserializeOwned>(bytes: &[u8]) -> Result<(T, Extensions), bincode::Error> {
+    let body: T = bincode::deserialize(bytes)?;
+    let body_len = bincode::serialized_size(&body)? as usize;
+    let extensions = Extensions::decode(bytes.get(body_len..).unwrap_or_default())?;
+    Ok((body, extensions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Body {
+        a: u64,
+        b: Vec<u8>,
+    }
+
+    #[test]
+    fn test_roundtrip_with_extensions() {
+        let body = Body { a: 7, b: vec![1, 2, 3] };
+        let mut extensions = Extensions::new();
+        extensions.insert(1, vec![0xAA; 4]);
+        extensions.insert(300, vec![]);
+
+        let (decoded, ext): (Body, _) = decode(&encode(&body, &extensions)).unwrap();
+        assert_eq!(decoded, body);
+        assert_eq!(ext, extensions);
+        assert_eq!(ext.version(), WIRE_VERSION);
+        assert_eq!(ext.get(1), Some(&[0xAA; 4][..]));
+        assert_eq!(ext.get(2), None);
+    }
+
+    #[test]
+    fn test_legacy_payload_has_no_trailer() {
+        let body = Body { a: 1, b: vec![] };
+        let (decoded, ext): (Body, _) = decode(&bincode::serialize(&body).unwrap()).unwrap();
+        assert_eq!(decoded, body);
+        assert_eq!(ext.version(), 0);
+        assert!(ext.is_empty());
+    }
+
+    #[test]
+    fn test_plain_bincode_ignores_trailer() {
+        // What a peer without trailer support does with a new payload
+        let body = Body { a: 9, b: vec![5] };
+        let mut extensions = Extensions::new();
+        extensions.insert(42, b"future field".to_vec());
+        let decoded: Body = bincode::deserialize(&encode(&body, &extensions)).unwrap();
+        assert_eq!(decoded, body);
+    }
+
+    #[test]
+    fn test_truncated_extension_rejected() {
+        let mut extensions = Extensions::new();
+        extensions.insert(1, vec![0; 16]);
+        let mut bytes = encode(&Body { a: 1, b: vec![] }, &extensions);
+        bytes.truncate(bytes.len() - 1);
+        assert!(decode::<Body>(&bytes).is_err());
+    }
+}
@@ -4,4 +4,11 @@ fn main() {
         sp1_build::build_program("../prover-guest");
         sp1_build::build_program("../distribution-guest");
     }
+
+    #[cfg(feature = "risc0")]
+    {
+        // Builds guests listed in [package.metadata.risc0] and writes
+        // methods.rs (ELF + image ID constants) to OUT_DIR
+        risc0_build::embed_methods();
+    }
 }
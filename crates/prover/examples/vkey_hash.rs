@@ -12,6 +12,7 @@
 fn main() {
     #[cfg(feature = "sp1")]
     {
+        use craftnet_prover::DistributionProving;
         let prover = craftnet_prover::DistributionProver::new();
         let hash = prover.vkey_hash();
         println!("Distribution guest vkey hash: {}", hash);
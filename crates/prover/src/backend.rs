@@ -0,0 +1,88 @@
+//! Distribution prover backend selection.
+//!
+//! Operators pick a backend with `CRAFTNET_PROVER_BACKEND` (`sp1` or
+//! `risc0`). When unset, the first compiled-in backend is used, SP1 first.
+//! With feature `remote` and `CRAFTNET_REMOTE_PROVER_URL` set, the local
+//! backend is wrapped in a `RemoteProver`. `CRAFTNET_PROVER_MAX_CYCLES`
+//! caps the guest cycles a local proof may take.
+
+use crate::traits::{DistributionProving, ProverBackend, ProvingError};
+
+/// Environment variable selecting the distribution prover backend
+pub const PROVER_BACKEND_ENV: &str = "CRAFTNET_PROVER_BACKEND";
+
+/// Environment variable capping the guest cycles of a local proof
+pub const PROVER_MAX_CYCLES_ENV: &str = "CRAFTNET_PROVER_MAX_CYCLES";
+
+/// Cycle limit from `CRAFTNET_PROVER_MAX_CYCLES` (None = unset or invalid)
+pub fn max_cycles_from_env() -> Option<u64> {
+    std::env::var(PROVER_MAX_CYCLES_ENV).ok()?.trim().parse().ok()
+}
+
+/// Backends compiled into this build, in preference order
+pub fn available_backends() -> Vec<ProverBackend> {
+    #[allow(unused_mut)]
+    let mut backends = Vec::new();
+    #[cfg(feature = "sp1")]
+    backends.push(ProverBackend::Sp1);
+    #[cfg(feature = "risc0")]
+    backends.push(ProverBackend::Risc0);
+    backends
+}
+
+/// Backend from `CRAFTNET_PROVER_BACKEND`, or the first available one
+pub fn backend_from_env() -> Option<ProverBackend> {
+    match std::env::var(PROVER_BACKEND_ENV) {
+        Ok(name) => ProverBackend::parse(&name),
+        Err(_) => available_backends().first().copied(),
+    }
+}
+
+/// Construct a distribution prover for a backend.
+pub fn distribution_prover(backend: ProverBackend) -> Result<Box<dyn DistributionProving>, ProvingError> {
+    match backend {
+        #[cfg(feature = "sp1")]
+        ProverBackend::Sp1 => Ok(Box::new(
+            crate::distribution::DistributionProver::new().with_max_cycles(max_cycles_from_env()),
+        )),
+        #[cfg(feature = "risc0")]
+        ProverBackend::Risc0 => Ok(Box::new(
+            crate::risc0::Risc0DistributionProver::new().with_max_cycles(max_cycles_from_env()),
+        )),
+        #[allow(unreachable_patterns)]
+        other => Err(ProvingError::BackendUnavailable(other.as_str())),
+    }
+}
+
+/// Construct the prover the node should use: the local backend, wrapped in
+/// a `RemoteProver` when a proving service is configured.
+pub fn configured_distribution_prover(backend: ProverBackend) -> Result<Box<dyn DistributionProving>, ProvingError> {
+    let local = distribution_prover(backend)?;
+    #[cfg(feature = "remote")]
+    if let Some(config) = crate::remote::RemoteProverConfig::from_env() {
+        tracing::info!("Offloading distribution proofs to {}", config.endpoint);
+        return Ok(Box::new(crate::remote::RemoteProver::new(config, local)));
+    }
+    Ok(local)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_backend() {
+        assert_eq!(ProverBackend::parse("sp1"), Some(ProverBackend::Sp1));
+        assert_eq!(ProverBackend::parse(" RISC0 "), Some(ProverBackend::Risc0));
+        assert_eq!(ProverBackend::parse("groth16"), None);
+    }
+
+    #[cfg(not(feature = "risc0"))]
+    #[test]
+    fn test_missing_backend_is_an_error() {
+        assert!(matches!(
+            distribution_prover(ProverBackend::Risc0),
+            Err(ProvingError::BackendUnavailable("risc0"))
+        ));
+    }
+}
@@ -0,0 +1,99 @@
+//! Chunked receipt compression.
+//!
+//! A relay that falls behind can cut batches of hundreds of thousands of
+//! receipts, which is more than one proving run should take on (and more
+//! than a zkVM guest can hash in a practical cycle budget). The batch is
+//! split into fixed-size chunks that are compressed one after another;
+//! each chunk's root extends the pool's proof chain, so the relay emits one
+//! chained `ProofMessage` per chunk and aggregators verify them like any
+//! other consecutive batches.
+
+use craftnet_core::ForwardReceipt;
+
+use crate::traits::{CompressionError, ReceiptCompression};
+
+/// Default receipts per chunk
+pub const DEFAULT_RECEIPT_CHUNK_SIZE: usize = 16_384;
+
+/// One compressed chunk of a batch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressedChunk {
+    /// Merkle root of the chunk's receipts
+    pub root: [u8; 32],
+    /// Receipts in the chunk
+    pub receipts: usize,
+    /// Payload bytes of the chunk's receipts
+    pub bytes: u64,
+}
+
+/// Compress `batch` in chunks of at most `chunk_size` receipts (0 = the
+/// whole batch in one chunk), in batch order.
+pub fn compress_chunked(
+    compressor: &dyn ReceiptCompression,
+    batch: &[ForwardReceipt],
+    chunk_size: usize,
+) -> Result<Vec<CompressedChunk>, CompressionError> {
+    if batch.is_empty() {
+        return Err(CompressionError::EmptyBatch);
+    }
+    let chunk_size = if chunk_size == 0 { batch.len() } else { chunk_size };
+    batch
+        .chunks(chunk_size)
+        .map(|chunk| {
+            let compressed = compressor.compress(chunk)?;
+            Ok(CompressedChunk {
+                root: compressed.root,
+                receipts: chunk.len(),
+                bytes: chunk.iter().map(|r| r.payload_size as u64).sum(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compressor::ReceiptCompressor;
+
+    fn make_receipt(shard_id: u8) -> ForwardReceipt {
+        ForwardReceipt {
+            shard_id: [shard_id; 32],
+            sender_pubkey: [0xFFu8; 32],
+            receiver_pubkey: [2u8; 32],
+            pool_pubkey: [0u8; 32],
+            payload_size: 100 + shard_id as u32,
+            timestamp: 1700000000,
+            signature: [0u8; 64],
+        }
+    }
+
+    #[test]
+    fn test_chunks_cover_batch_in_order() {
+        let compressor = ReceiptCompressor::new();
+        let batch: Vec<ForwardReceipt> = (0..10).map(make_receipt).collect();
+
+        let chunks = compress_chunked(&compressor, &batch, 4).unwrap();
+        assert_eq!(chunks.iter().map(|c| c.receipts).collect::<Vec<_>>(), vec![4, 4, 2]);
+        assert_eq!(
+            chunks.iter().map(|c| c.bytes).sum::<u64>(),
+            batch.iter().map(|r| r.payload_size as u64).sum::<u64>(),
+        );
+        assert_eq!(chunks[1].root, compressor.compress(&batch[4..8]).unwrap().root);
+    }
+
+    #[test]
+    fn test_zero_chunk_size_is_one_chunk() {
+        let compressor = ReceiptCompressor::new();
+        let batch: Vec<ForwardReceipt> = (0..5).map(make_receipt).collect();
+
+        let chunks = compress_chunked(&compressor, &batch, 0).unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].root, compressor.compress(&batch).unwrap().root);
+    }
+
+    #[test]
+    fn test_empty_batch() {
+        let result = compress_chunked(&ReceiptCompressor::new(), &[], 4);
+        assert!(matches!(result, Err(CompressionError::EmptyBatch)));
+    }
+}
@@ -20,7 +20,7 @@ impl ReceiptCompressor {
     }
 
     /// Hash a receipt into a leaf: SHA256(shard_id || sender_pubkey || receiver_pubkey || pool_pubkey || payload_size_le || timestamp_le)
-    fn receipt_leaf(receipt: &ForwardReceipt) -> [u8; 32] {
+    pub fn receipt_leaf(receipt: &ForwardReceipt) -> [u8; 32] {
         let mut hasher = Sha256::new();
         hasher.update(receipt.shard_id);
         hasher.update(receipt.sender_pubkey);
@@ -113,4 +113,28 @@ mod tests {
         let out2 = compressor.compress(&batch2).unwrap();
         assert_ne!(out1.root, out2.root);
     }
+
+    #[test]
+    fn test_golden_receipt_leaves() {
+        use crate::golden::{array, bytes32, vectors};
+
+        let vectors = vectors();
+        let batch: Vec<ForwardReceipt> = array(&vectors["receipts"])
+            .iter()
+            .map(|r| ForwardReceipt {
+                shard_id: bytes32(&r["shard_id"]),
+                sender_pubkey: bytes32(&r["sender_pubkey"]),
+                receiver_pubkey: bytes32(&r["receiver_pubkey"]),
+                pool_pubkey: bytes32(&r["pool_pubkey"]),
+                payload_size: r["payload_size"].as_u64().unwrap() as u32,
+                timestamp: r["timestamp"].as_u64().unwrap(),
+                signature: [0u8; 64],
+            })
+            .collect();
+        for (receipt, expected) in batch.iter().zip(array(&vectors["receipts"])) {
+            assert_eq!(ReceiptCompressor::receipt_leaf(receipt), bytes32(&expected["leaf"]));
+        }
+        let root = ReceiptCompressor::new().compress(&batch).unwrap().root;
+        assert_eq!(root, bytes32(&vectors["receipt_batch_root"]));
+    }
 }
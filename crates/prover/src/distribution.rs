@@ -11,49 +11,62 @@
 //! - `network` — Succinct Prover Network (production, needs `NETWORK_PRIVATE_KEY`)
 //! - `cpu`     — local CPU (very slow for Groth16, not recommended)
 //! - unset     — defaults to local CPU
+//!
+//! Every prove first executes the guest to count its cycles (logged and
+//! returned with the proof); with a cycle limit set, inputs over the limit
+//! fail fast instead of tying up the prover.
 
 use tracing::info;
 
 use sp1_sdk::{include_elf, EnvProver, HashableKey, ProverClient, SP1Stdin};
+use sp1_verifier::{Groth16Verifier, GROTH16_VK_BYTES};
 use craftnet_distribution_guest_types::DistributionInput;
 
+use crate::traits::{DistributionProof, DistributionProving, ProverBackend, ProvingError};
+
 /// The distribution guest ELF binary, embedded at build time by sp1_build.
 const DISTRIBUTION_ELF: &[u8] = include_elf!("craftnet-distribution-guest");
 
 /// A Groth16 proof over the distribution Merkle tree construction.
 ///
-/// Contains the raw proof bytes and public values that the on-chain
-/// program needs for verification via `sp1-solana`.
-#[derive(Debug, Clone)]
-pub struct DistributionGroth16Proof {
-    /// Raw Groth16 proof bytes (~256 bytes)
-    pub proof_bytes: Vec<u8>,
-    /// Public values committed by the guest (76 bytes fixed layout)
-    pub public_values: Vec<u8>,
-    /// Verification key hash ("0x..." hex string)
-    pub vkey_hash: String,
-}
+/// Kept as an alias of the backend-agnostic `DistributionProof`.
+pub type DistributionGroth16Proof = DistributionProof;
 
-/// Groth16 distribution prover.
+/// SP1 Groth16 distribution prover.
 ///
 /// Generates proofs that the distribution Merkle tree was correctly
 /// constructed from relay entries. These proofs are verified on-chain.
 pub struct DistributionProver {
     client: EnvProver,
+    /// Guest cycles a proof may take (None = unlimited)
+    max_cycles: Option<u64>,
 }
 
 impl DistributionProver {
     pub fn new() -> Self {
         Self {
             client: ProverClient::from_env(),
+            max_cycles: None,
         }
     }
 
+    /// Refuse inputs whose execution takes more than `max_cycles`
+    pub fn with_max_cycles(mut self, max_cycles: Option<u64>) -> Self {
+        self.max_cycles = max_cycles;
+        self
+    }
+}
+
+impl DistributionProving for DistributionProver {
+    fn backend(&self) -> ProverBackend {
+        ProverBackend::Sp1
+    }
+
     /// Get the verification key hash for the distribution guest program.
     ///
     /// This value must match the `DISTRIBUTION_VKEY_HASH` constant in
     /// the on-chain program. Run the `vkey_hash` example to compute it.
-    pub fn vkey_hash(&self) -> String {
+    fn vkey_hash(&self) -> String {
         let (_pk, vk) = self.client.setup(DISTRIBUTION_ELF);
         vk.bytes32()
     }
@@ -65,13 +78,13 @@ impl DistributionProver {
     /// 2. Merkle tree was correctly built (matching off-chain `MerkleTree`)
     /// 3. Total bytes and entry count are correct
     /// 4. Pool pubkey is bound to the proof
-    pub fn prove_distribution(
+    fn prove_distribution(
         &self,
         entries: &[([u8; 32], u64)],
         pool_pubkey: [u8; 32],
-    ) -> Result<DistributionGroth16Proof, String> {
+    ) -> Result<DistributionProof, ProvingError> {
         if entries.is_empty() {
-            return Err("empty distribution entries".to_string());
+            return Err(ProvingError::EmptyEntries);
         }
 
         let input = DistributionInput {
@@ -90,13 +103,23 @@ impl DistributionProver {
         let mut stdin = SP1Stdin::new();
         stdin.write(&input);
 
+        let (_, report) = self.client
+            .execute(DISTRIBUTION_ELF, &stdin)
+            .run()
+            .map_err(|e| ProvingError::ProveFailed(format!("Distribution guest execution failed: {}", e)))?;
+        let cycles = report.total_instruction_count();
+        info!("Distribution guest executed in {} cycles ({} entries)", cycles, entries.len());
+        if let Some(limit) = self.max_cycles.filter(|limit| cycles > *limit) {
+            return Err(ProvingError::CycleLimitExceeded { cycles, limit });
+        }
+
         let (pk, vk) = self.client.setup(DISTRIBUTION_ELF);
 
         let proof = self.client
             .prove(&pk, &stdin)
             .groth16()
             .run()
-            .map_err(|e| format!("Distribution Groth16 prove failed: {}", e))?;
+            .map_err(|e| ProvingError::ProveFailed(format!("Distribution Groth16 prove failed: {}", e)))?;
 
         let public_values = proof.public_values.as_slice().to_vec();
         let proof_bytes = proof.bytes();
@@ -111,12 +134,33 @@ impl DistributionProver {
             t0.elapsed(),
         );
 
-        Ok(DistributionGroth16Proof {
+        Ok(DistributionProof {
+            backend: ProverBackend::Sp1,
             proof_bytes,
             public_values,
             vkey_hash,
+            cycles: Some(cycles),
         })
     }
+
+    /// Verify with the same Groth16 verifier `sp1-solana` uses on-chain.
+    fn verify_distribution(&self, proof: &DistributionProof) -> Result<(), ProvingError> {
+        if proof.backend != ProverBackend::Sp1 {
+            return Err(ProvingError::VerificationFailed(format!("not an sp1 proof ({})", proof.backend)));
+        }
+        let vkey_hash = self.vkey_hash();
+        if proof.vkey_hash != vkey_hash {
+            return Err(ProvingError::VerificationFailed(format!(
+                "vkey mismatch: expected {}, got {}", vkey_hash, proof.vkey_hash,
+            )));
+        }
+        Groth16Verifier::verify(
+            &proof.proof_bytes,
+            &proof.public_values,
+            &vkey_hash,
+            *GROTH16_VK_BYTES,
+        ).map_err(|e| ProvingError::VerificationFailed(e.to_string()))
+    }
 }
 
 impl Default for DistributionProver {
@@ -124,3 +168,102 @@ impl Default for DistributionProver {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden::{array, bytes, bytes32, entries, vectors};
+
+    /// The guest's own leaf and tree code commits the golden public values
+    #[test]
+    fn test_guest_matches_golden_vectors() {
+        let client = ProverClient::from_env();
+        for vector in array(&vectors()["distributions"]) {
+            let input = DistributionInput {
+                entries: entries(&vector["entries"]),
+                pool_pubkey: bytes32(&vector["pool"]),
+            };
+            let mut stdin = SP1Stdin::new();
+            stdin.write(&input);
+            let (public_values, _) = client.execute(DISTRIBUTION_ELF, &stdin).run().unwrap();
+            assert_eq!(public_values.as_slice(), bytes(&vector["public_values"]).as_slice(), "{}", vector["name"]);
+        }
+    }
+
+    /// Randomized receipt batches, summed per forwarding relay the way the
+    /// aggregator does, give the same distribution through the host path
+    /// (`ReceiptCompressor` + `MerkleTree`, what relays and aggregators run
+    /// without a zkVM) and through the guest in execute mode.
+    ///
+    /// Nightly tier: each case executes the guest. Run with
+    /// `PROPTEST_CASES=<n> cargo test -p craftnet-prover --features sp1 -- --ignored`.
+    mod prop {
+        use std::collections::HashMap;
+
+        use craftnet_core::ForwardReceipt;
+        use proptest::prelude::*;
+        use proptest::sample::Index;
+        use proptest::test_runner::TestRunner;
+
+        use super::*;
+        use crate::{expected_public_values, MerkleTree, ReceiptCompressor, ReceiptCompression};
+
+        fn arb_batch() -> impl Strategy<Value = ([u8; 32], Vec<ForwardReceipt>)> {
+            let relays = proptest::collection::vec(any::<[u8; 32]>(), 1..24);
+            let receipts = proptest::collection::vec((any::<Index>(), any::<[u8; 32]>(), any::<u32>(), any::<u64>()), 1..256);
+            (any::<[u8; 32]>(), relays, receipts).prop_map(|(pool, relays, receipts)| {
+                let batch = receipts
+                    .into_iter()
+                    .map(|(sender, shard_id, payload_size, timestamp)| ForwardReceipt {
+                        shard_id,
+                        sender_pubkey: *sender.get(&relays),
+                        receiver_pubkey: [0xAB; 32],
+                        pool_pubkey: pool,
+                        payload_size,
+                        timestamp,
+                        signature: [0u8; 64],
+                    })
+                    .collect();
+                (pool, batch)
+            })
+        }
+
+        #[test]
+        #[ignore = "nightly: executes the SP1 guest for every case"]
+        fn prop_guest_matches_host() {
+            let client = ProverClient::from_env();
+            let compressor = ReceiptCompressor::new();
+            TestRunner::default()
+                .run(&arb_batch(), |(pool, batch)| {
+                    // Relay side: per-relay batches compress to a chain root
+                    let mut per_relay: HashMap<[u8; 32], Vec<ForwardReceipt>> = HashMap::new();
+                    for receipt in &batch {
+                        per_relay.entry(receipt.sender_pubkey).or_default().push(receipt.clone());
+                    }
+                    let mut entries = Vec::with_capacity(per_relay.len());
+                    for (relay, receipts) in &per_relay {
+                        let leaves = receipts.iter().map(ReceiptCompressor::receipt_leaf).collect();
+                        prop_assert_eq!(compressor.compress(receipts).unwrap().root, MerkleTree::from_leaves(leaves).root());
+                        entries.push((*relay, receipts.iter().map(|r| r.payload_size as u64).sum::<u64>()));
+                    }
+
+                    // Aggregator side (host) against the guest, entries in
+                    // arbitrary order as the guest must sort them itself
+                    let expected = expected_public_values(&entries, &pool);
+                    let mut stdin = SP1Stdin::new();
+                    stdin.write(&DistributionInput { entries: entries.clone(), pool_pubkey: pool });
+                    let (public_values, _) = client
+                        .execute(DISTRIBUTION_ELF, &stdin)
+                        .run()
+                        .map_err(|e| TestCaseError::fail(format!("guest execution failed: {e}")))?;
+                    prop_assert_eq!(public_values.as_slice(), expected.as_slice());
+
+                    let mut sorted = entries;
+                    sorted.sort_by(|a, b| a.0.cmp(&b.0));
+                    prop_assert_eq!(&public_values.as_slice()[..32], MerkleTree::from_entries(&sorted).root().as_slice());
+                    Ok(())
+                })
+                .unwrap();
+        }
+    }
+}
@@ -0,0 +1,32 @@
+//! Test access to `test-vectors/hashing.json`, the golden vectors the
+//! aggregator generates (see `craftnet_aggregator::test_vectors`)
+
+use serde_json::Value;
+
+const GOLDEN: &str = include_str!("../../../test-vectors/hashing.json");
+
+pub fn vectors() -> Value {
+    serde_json::from_str(GOLDEN).expect("golden vectors parse")
+}
+
+pub fn bytes(value: &Value) -> Vec<u8> {
+    hex::decode(value.as_str().expect("hex string")).expect("valid hex")
+}
+
+pub fn bytes32(value: &Value) -> [u8; 32] {
+    bytes(value).try_into().expect("32 bytes")
+}
+
+/// `[{relay, bytes}]` as `(relay, bytes)` pairs
+pub fn entries(value: &Value) -> Vec<([u8; 32], u64)> {
+    value
+        .as_array()
+        .expect("entry array")
+        .iter()
+        .map(|e| (bytes32(&e["relay"]), e["bytes"].as_u64().expect("bytes")))
+        .collect()
+}
+
+pub fn array(value: &Value) -> &Vec<Value> {
+    value.as_array().expect("array")
+}
@@ -5,19 +5,45 @@
 //! The `MerkleTree` is used by both the aggregator (to build distribution
 //! roots with proofs for each relay) and by the on-chain program (to
 //! verify claims). The `ReceiptCompressor` hashes receipts into a Merkle
-//! tree for ProofMessage chain continuity. The `DistributionProver`
-//! generates Groth16 proofs for on-chain distribution verification.
+//! tree for ProofMessage chain continuity; `compress_chunked` splits
+//! oversized batches into chained chunks. Distribution Groth16 proofs
+//! come from a `DistributionProving` backend: SP1 (feature `sp1`) or
+//! RISC Zero (feature `risc0`), both committing the same public values.
+//! With feature `remote`, proving can be offloaded to a GPU proving service
+//! whose proofs are verified locally before use.
 
 pub mod merkle;
 pub mod compressor;
+pub mod chunking;
 pub mod traits;
+pub mod public_values;
+pub mod backend;
+
+#[cfg(test)]
+mod golden;
 
 #[cfg(feature = "sp1")]
 pub mod distribution;
 
-pub use merkle::{hash_pair, merkle_leaf, MerkleProof, MerkleTree};
+#[cfg(feature = "risc0")]
+pub mod risc0;
+
+#[cfg(feature = "remote")]
+pub mod remote;
+
+pub use merkle::{hash_pair, merkle_leaf, MerkleMultiProof, MerkleProof, MerkleTree};
 pub use compressor::ReceiptCompressor;
+pub use chunking::{compress_chunked, CompressedChunk, DEFAULT_RECEIPT_CHUNK_SIZE};
 pub use traits::{CompressedBatch, ReceiptCompression, CompressionError};
+pub use traits::{DistributionProof, DistributionProving, ProverBackend, ProvingError};
+pub use public_values::{distribution_public_values, expected_public_values, DISTRIBUTION_PUBLIC_VALUES_LEN};
+pub use backend::{available_backends, backend_from_env, configured_distribution_prover, distribution_prover, max_cycles_from_env, PROVER_BACKEND_ENV, PROVER_MAX_CYCLES_ENV};
 
 #[cfg(feature = "sp1")]
 pub use distribution::{DistributionProver, DistributionGroth16Proof};
+
+#[cfg(feature = "risc0")]
+pub use risc0::Risc0DistributionProver;
+
+#[cfg(feature = "remote")]
+pub use remote::{RemoteProver, RemoteProverConfig, REMOTE_PROVER_API_KEY_ENV, REMOTE_PROVER_URL_ENV};
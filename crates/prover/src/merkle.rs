@@ -3,6 +3,10 @@
 //! Leaf formula: `SHA256(relay_pubkey || count_le_bytes)`.
 //! Internal nodes: `SHA256(left || right)`.
 //! If the leaf count is not a power of 2, pad with `[0u8; 32]`.
+//!
+//! Many leaves can be proven at once with a `MerkleMultiProof`, which
+//! carries each needed sibling hash only once instead of one full path
+//! per leaf.
 
 use sha2::{Digest, Sha256};
 
@@ -15,6 +19,21 @@ pub struct MerkleProof {
     pub leaf_index: usize,
 }
 
+/// A compressed proof for several leaves of the same tree.
+///
+/// `hashes` holds only the nodes the verifier cannot compute from the
+/// proven leaves themselves, ordered level by level (bottom-up) and left to
+/// right within a level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleMultiProof {
+    /// Proven leaf indices, sorted ascending and deduplicated.
+    pub leaf_indices: Vec<usize>,
+    /// Number of leaves in the tree (including padding).
+    pub leaf_count: usize,
+    /// Sibling hashes not derivable from the proven leaves.
+    pub hashes: Vec<[u8; 32]>,
+}
+
 /// A binary Merkle tree.
 #[derive(Debug, Clone)]
 pub struct MerkleTree {
@@ -147,6 +166,103 @@ impl MerkleTree {
         current == *root
     }
 
+    /// Generate one compressed proof for several leaves.
+    ///
+    /// Indices may be unsorted and repeated. Returns `None` if `indices` is
+    /// empty or any index is out of range.
+    pub fn batch_proofs(&self, indices: &[usize]) -> Option<MerkleMultiProof> {
+        let mut leaf_indices = indices.to_vec();
+        leaf_indices.sort_unstable();
+        leaf_indices.dedup();
+        if leaf_indices.is_empty() || *leaf_indices.last()? >= self.leaf_count() {
+            return None;
+        }
+
+        let mut hashes = Vec::new();
+        let mut known = leaf_indices.clone();
+
+        for layer in &self.layers[..self.layers.len() - 1] {
+            let mut parents = Vec::with_capacity(known.len());
+            let mut i = 0;
+            while i < known.len() {
+                let idx = known[i];
+                let sibling_idx = idx ^ 1;
+                if known.get(i + 1) == Some(&sibling_idx) {
+                    // Both children are known — nothing to send
+                    i += 2;
+                } else {
+                    hashes.push(layer[sibling_idx]);
+                    i += 1;
+                }
+                parents.push(idx / 2);
+            }
+            known = parents;
+        }
+
+        Some(MerkleMultiProof {
+            leaf_indices,
+            leaf_count: self.leaf_count(),
+            hashes,
+        })
+    }
+
+    /// Verify a multiproof against a root.
+    ///
+    /// `leaves` are the leaf hashes in the order of `proof.leaf_indices`.
+    pub fn verify_batch(root: &[u8; 32], leaves: &[[u8; 32]], proof: &MerkleMultiProof) -> bool {
+        if leaves.is_empty() || leaves.len() != proof.leaf_indices.len() {
+            return false;
+        }
+        if !proof.leaf_count.is_power_of_two() {
+            return false;
+        }
+        if proof.leaf_indices.windows(2).any(|w| w[0] >= w[1])
+            || proof.leaf_indices.last().is_some_and(|&i| i >= proof.leaf_count)
+        {
+            return false;
+        }
+
+        let mut level: Vec<(usize, [u8; 32])> = proof.leaf_indices.iter()
+            .copied()
+            .zip(leaves.iter().copied())
+            .collect();
+        let mut hashes = proof.hashes.iter();
+        let mut width = proof.leaf_count;
+
+        while width > 1 {
+            let mut parents = Vec::with_capacity(level.len());
+            let mut i = 0;
+            while i < level.len() {
+                let (idx, hash) = level[i];
+                let sibling_idx = idx ^ 1;
+                let sibling = match level.get(i + 1) {
+                    Some((next_idx, next_hash)) if *next_idx == sibling_idx => {
+                        i += 2;
+                        *next_hash
+                    }
+                    _ => {
+                        i += 1;
+                        match hashes.next() {
+                            Some(h) => *h,
+                            None => return false,
+                        }
+                    }
+                };
+                let parent = if idx.is_multiple_of(2) {
+                    hash_pair(&hash, &sibling)
+                } else {
+                    hash_pair(&sibling, &hash)
+                };
+                parents.push((idx / 2, parent));
+            }
+            level = parents;
+            width /= 2;
+        }
+
+        // Every supplied hash must have been consumed
+        hashes.next().is_none() && level.len() == 1 && level[0].1 == *root
+    }
+
     /// Number of leaves (including padding).
     pub fn leaf_count(&self) -> usize {
         self.layers[0].len()
@@ -272,6 +388,93 @@ mod tests {
         assert_eq!(tree1.root(), tree2.root());
     }
 
+    #[test]
+    fn test_batch_proof_roundtrip() {
+        let entries: Vec<_> = (0..13u8).map(|i| ([i; 32], i as u64 * 10)).collect();
+        let tree = MerkleTree::from_entries(&entries);
+        let root = tree.root();
+
+        let indices = [9, 0, 3, 2, 12, 3];
+        let proof = tree.batch_proofs(&indices).unwrap();
+        assert_eq!(proof.leaf_indices, vec![0, 2, 3, 9, 12]);
+
+        let leaves: Vec<[u8; 32]> = proof.leaf_indices.iter()
+            .map(|&i| merkle_leaf(&entries[i].0, entries[i].1))
+            .collect();
+        assert!(MerkleTree::verify_batch(&root, &leaves, &proof));
+    }
+
+    #[test]
+    fn test_batch_proof_smaller_than_single_proofs() {
+        let entries: Vec<_> = (0..64u8).map(|i| ([i; 32], i as u64)).collect();
+        let tree = MerkleTree::from_entries(&entries);
+
+        let indices: Vec<usize> = (0..16).collect();
+        let proof = tree.batch_proofs(&indices).unwrap();
+        let single_total: usize = indices.iter()
+            .map(|&i| tree.proof(i).unwrap().siblings.len())
+            .sum();
+        // 16 adjacent leaves form a full subtree: only the 2 hashes above it
+        assert_eq!(proof.hashes.len(), 2);
+        assert!(proof.hashes.len() < single_total);
+    }
+
+    #[test]
+    fn test_batch_proof_all_leaves_needs_no_hashes() {
+        let entries = vec![([1u8; 32], 10), ([2u8; 32], 20), ([3u8; 32], 30), ([4u8; 32], 40)];
+        let tree = MerkleTree::from_entries(&entries);
+        let proof = tree.batch_proofs(&[0, 1, 2, 3]).unwrap();
+        assert!(proof.hashes.is_empty());
+
+        let leaves: Vec<_> = entries.iter().map(|(pk, c)| merkle_leaf(pk, *c)).collect();
+        assert!(MerkleTree::verify_batch(&tree.root(), &leaves, &proof));
+    }
+
+    #[test]
+    fn test_batch_proof_single_leaf_tree() {
+        let tree = MerkleTree::from_entries(&[([1u8; 32], 100)]);
+        let proof = tree.batch_proofs(&[0]).unwrap();
+        assert!(proof.hashes.is_empty());
+        assert!(MerkleTree::verify_batch(&tree.root(), &[merkle_leaf(&[1u8; 32], 100)], &proof));
+    }
+
+    #[test]
+    fn test_batch_proof_rejects_tampering() {
+        let entries: Vec<_> = (0..8u8).map(|i| ([i; 32], i as u64)).collect();
+        let tree = MerkleTree::from_entries(&entries);
+        let root = tree.root();
+        let proof = tree.batch_proofs(&[1, 6]).unwrap();
+        let leaves = vec![merkle_leaf(&[1u8; 32], 1), merkle_leaf(&[6u8; 32], 6)];
+        assert!(MerkleTree::verify_batch(&root, &leaves, &proof));
+
+        // Wrong leaf
+        let wrong = vec![merkle_leaf(&[1u8; 32], 2), leaves[1]];
+        assert!(!MerkleTree::verify_batch(&root, &wrong, &proof));
+
+        // Leaf claimed at another index
+        let mut moved = proof.clone();
+        moved.leaf_indices = vec![0, 6];
+        assert!(!MerkleTree::verify_batch(&root, &leaves, &moved));
+
+        // Extra or missing hashes
+        let mut extra = proof.clone();
+        extra.hashes.push([0u8; 32]);
+        assert!(!MerkleTree::verify_batch(&root, &leaves, &extra));
+        let mut short = proof.clone();
+        short.hashes.pop();
+        assert!(!MerkleTree::verify_batch(&root, &leaves, &short));
+
+        // Leaf count mismatch with indices
+        assert!(!MerkleTree::verify_batch(&root, &leaves[..1], &proof));
+    }
+
+    #[test]
+    fn test_batch_proof_out_of_range() {
+        let tree = MerkleTree::from_entries(&[([1u8; 32], 10), ([2u8; 32], 20)]);
+        assert!(tree.batch_proofs(&[0, 2]).is_none());
+        assert!(tree.batch_proofs(&[]).is_none());
+    }
+
     #[test]
     fn test_large_tree() {
         let entries: Vec<_> = (0..17u8).map(|i| ([i; 32], i as u64 * 100)).collect();
@@ -287,4 +490,29 @@ mod tests {
             assert!(MerkleTree::verify(&root, &leaf, &proof));
         }
     }
+
+    #[test]
+    fn test_golden_vectors() {
+        use crate::golden::{array, bytes32, entries, vectors};
+
+        let vectors = vectors();
+        for vector in array(&vectors["merkle"]) {
+            let name = vector["name"].as_str().unwrap();
+            let entries = entries(&vector["entries"]);
+            for ((relay, bytes), leaf) in entries.iter().zip(array(&vector["leaves"])) {
+                assert_eq!(merkle_leaf(relay, *bytes), bytes32(leaf), "{name}");
+            }
+            let tree = MerkleTree::from_entries(&entries);
+            let root = bytes32(&vector["root"]);
+            assert_eq!(tree.root(), root, "{name}");
+            for expected in array(&vector["proofs"]) {
+                let index = expected["leaf_index"].as_u64().unwrap() as usize;
+                let proof = tree.proof(index).unwrap();
+                let siblings: Vec<[u8; 32]> = array(&expected["siblings"]).iter().map(bytes32).collect();
+                assert_eq!(proof.siblings, siblings, "{name} leaf {index}");
+                let (relay, bytes) = entries[index];
+                assert!(MerkleTree::verify(&root, &merkle_leaf(&relay, bytes), &proof));
+            }
+        }
+    }
 }
@@ -0,0 +1,78 @@
+//! Distribution public-value layout shared by all proving backends.
+//!
+//! Guests commit fields individually so the layout is fixed:
+//! `root (32B) || total_bytes (8B LE) || entry_count (4B LE) || pool_pubkey (32B)`
+//! = 76 bytes. The on-chain program parses this layout, so every backend
+//! must produce it byte-for-byte.
+
+use crate::merkle::{merkle_leaf, MerkleTree};
+
+/// Size of the committed public values
+pub const DISTRIBUTION_PUBLIC_VALUES_LEN: usize = 76;
+
+/// Encode distribution public values in the committed layout
+pub fn distribution_public_values(
+    root: &[u8; 32],
+    total_bytes: u64,
+    entry_count: u32,
+    pool_pubkey: &[u8; 32],
+) -> Vec<u8> {
+    let mut out = Vec::with_capacity(DISTRIBUTION_PUBLIC_VALUES_LEN);
+    out.extend_from_slice(root);
+    out.extend_from_slice(&total_bytes.to_le_bytes());
+    out.extend_from_slice(&entry_count.to_le_bytes());
+    out.extend_from_slice(pool_pubkey);
+    out
+}
+
+/// Public values a correct guest commits for these entries.
+///
+/// Mirrors the guest: sort by relay pubkey, build the Merkle tree, sum bytes.
+/// Backends compare this against the proof's committed values before
+/// returning it.
+pub fn expected_public_values(entries: &[([u8; 32], u64)], pool_pubkey: &[u8; 32]) -> Vec<u8> {
+    let mut sorted = entries.to_vec();
+    sorted.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let total_bytes: u64 = sorted.iter().map(|(_, bytes)| bytes).sum();
+    let leaves: Vec<[u8; 32]> = sorted.iter()
+        .map(|(pubkey, bytes)| merkle_leaf(pubkey, *bytes))
+        .collect();
+    let root = MerkleTree::from_leaves(leaves).root();
+
+    distribution_public_values(&root, total_bytes, sorted.len() as u32, pool_pubkey)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout() {
+        let values = distribution_public_values(&[1u8; 32], 500, 3, &[2u8; 32]);
+        assert_eq!(values.len(), DISTRIBUTION_PUBLIC_VALUES_LEN);
+        assert_eq!(&values[..32], &[1u8; 32]);
+        assert_eq!(u64::from_le_bytes(values[32..40].try_into().unwrap()), 500);
+        assert_eq!(u32::from_le_bytes(values[40..44].try_into().unwrap()), 3);
+        assert_eq!(&values[44..], &[2u8; 32]);
+    }
+
+    #[test]
+    fn test_expected_values_independent_of_entry_order() {
+        let pool = [9u8; 32];
+        let a = expected_public_values(&[([1u8; 32], 100), ([2u8; 32], 200)], &pool);
+        let b = expected_public_values(&[([2u8; 32], 200), ([1u8; 32], 100)], &pool);
+        assert_eq!(a, b);
+        assert_eq!(u64::from_le_bytes(a[32..40].try_into().unwrap()), 300);
+    }
+
+    #[test]
+    fn test_golden_public_values() {
+        use crate::golden::{array, bytes, bytes32, entries, vectors};
+
+        for vector in array(&vectors()["distributions"]) {
+            let values = expected_public_values(&entries(&vector["entries"]), &bytes32(&vector["pool"]));
+            assert_eq!(values, bytes(&vector["public_values"]), "{}", vector["name"]);
+        }
+    }
+}
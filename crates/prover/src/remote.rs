@@ -0,0 +1,393 @@
+//! Remote (GPU / proving network) distribution prover.
+//!
+//! Groth16 proving on a relay-class box is slow. `RemoteProver` submits the
+//! `DistributionInput` to a proving service over HTTPS, polls until the job
+//! finishes, verifies the returned proof locally against the pinned program
+//! (never trusting the service), and falls back to the local backend when
+//! anything goes wrong.
+//!
+//! Service API (JSON):
+//! - `POST {endpoint}/v1/distribution/jobs` with
+//!   `{ "backend", "entries": [[pubkey_hex, bytes], ...], "pool_pubkey" }`
+//!   → `{ "job_id" }`
+//! - `GET {endpoint}/v1/distribution/jobs/{job_id}`
+//!   → `{ "status": "pending" | "running" | "done" | "failed", "proof"?, "error"? }`
+//!   where `proof` is `{ "proof_bytes", "public_values", "vkey_hash" }` (hex)
+//!   plus an optional `"cycles"` count.
+//!
+//! Configured with `CRAFTNET_REMOTE_PROVER_URL` and optionally
+//! `CRAFTNET_REMOTE_PROVER_API_KEY` (sent as a bearer token).
+
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::public_values::expected_public_values;
+use crate::traits::{DistributionProof, DistributionProving, ProverBackend, ProvingError};
+
+/// Environment variable with the proving service base URL
+pub const REMOTE_PROVER_URL_ENV: &str = "CRAFTNET_REMOTE_PROVER_URL";
+
+/// Environment variable with the proving service API key
+pub const REMOTE_PROVER_API_KEY_ENV: &str = "CRAFTNET_REMOTE_PROVER_API_KEY";
+
+/// Remote proving service settings
+#[derive(Debug, Clone)]
+pub struct RemoteProverConfig {
+    /// Service base URL, e.g. `https://prover.example.com`
+    pub endpoint: String,
+    /// Optional bearer token
+    pub api_key: Option<String>,
+    /// Delay between job status polls
+    pub poll_interval: Duration,
+    /// Give up on the remote job (and prove locally) after this long
+    pub timeout: Duration,
+}
+
+impl RemoteProverConfig {
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        Self {
+            endpoint: endpoint.into().trim_end_matches('/').to_string(),
+            api_key: None,
+            poll_interval: Duration::from_secs(5),
+            timeout: Duration::from_secs(600),
+        }
+    }
+
+    /// Read `CRAFTNET_REMOTE_PROVER_URL` / `CRAFTNET_REMOTE_PROVER_API_KEY`
+    pub fn from_env() -> Option<Self> {
+        let endpoint = std::env::var(REMOTE_PROVER_URL_ENV).ok().filter(|s| !s.is_empty())?;
+        let mut config = Self::new(endpoint);
+        config.api_key = std::env::var(REMOTE_PROVER_API_KEY_ENV).ok().filter(|s| !s.is_empty());
+        Some(config)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct SubmitRequest<'a> {
+    backend: &'a str,
+    entries: Vec<(String, u64)>,
+    pool_pubkey: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubmitResponse {
+    job_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum JobStatus {
+    Pending,
+    Running,
+    Done,
+    Failed,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoteProof {
+    proof_bytes: String,
+    public_values: String,
+    vkey_hash: String,
+    #[serde(default)]
+    cycles: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JobResponse {
+    status: JobStatus,
+    #[serde(default)]
+    proof: Option<RemoteProof>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// Distribution prover that offloads proving to a remote service.
+///
+/// The wrapped local prover pins the program (vkey / image ID), verifies
+/// every remote proof, and proves locally when the service fails.
+pub struct RemoteProver {
+    config: RemoteProverConfig,
+    local: Box<dyn DistributionProving>,
+}
+
+impl RemoteProver {
+    pub fn new(config: RemoteProverConfig, local: Box<dyn DistributionProving>) -> Self {
+        Self { config, local }
+    }
+
+    fn remote_err(e: impl std::fmt::Display) -> ProvingError {
+        ProvingError::Remote(e.to_string())
+    }
+
+    /// Submit, poll and decode a remote proof (blocking HTTP).
+    fn prove_remote(
+        &self,
+        entries: &[([u8; 32], u64)],
+        pool_pubkey: [u8; 32],
+    ) -> Result<DistributionProof, ProvingError> {
+        let http = reqwest::blocking::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .map_err(Self::remote_err)?;
+        let with_auth = |req: reqwest::blocking::RequestBuilder| match self.config.api_key {
+            Some(ref key) => req.bearer_auth(key),
+            None => req,
+        };
+
+        let backend = self.local.backend();
+        let body = SubmitRequest {
+            backend: backend.as_str(),
+            entries: entries.iter().map(|(pk, bytes)| (hex::encode(pk), *bytes)).collect(),
+            pool_pubkey: hex::encode(pool_pubkey),
+        };
+        let submit: SubmitResponse = with_auth(http.post(format!("{}/v1/distribution/jobs", self.config.endpoint)))
+            .json(&body)
+            .send()
+            .and_then(|r| r.error_for_status())
+            .and_then(|r| r.json())
+            .map_err(Self::remote_err)?;
+
+        info!("Submitted distribution proof job {} to {}", submit.job_id, self.config.endpoint);
+
+        let started = Instant::now();
+        let job_url = format!("{}/v1/distribution/jobs/{}", self.config.endpoint, submit.job_id);
+        loop {
+            if started.elapsed() >= self.config.timeout {
+                return Err(ProvingError::Remote(format!("job {} timed out after {:?}", submit.job_id, self.config.timeout)));
+            }
+            std::thread::sleep(self.config.poll_interval);
+
+            let job: JobResponse = with_auth(http.get(&job_url))
+                .send()
+                .and_then(|r| r.error_for_status())
+                .and_then(|r| r.json())
+                .map_err(Self::remote_err)?;
+
+            match job.status {
+                JobStatus::Pending | JobStatus::Running => continue,
+                JobStatus::Failed => {
+                    return Err(ProvingError::Remote(job.error.unwrap_or_else(|| "job failed".to_string())));
+                }
+                JobStatus::Done => {
+                    let proof = job.proof
+                        .ok_or_else(|| ProvingError::Remote("job done without proof".to_string()))?;
+                    return Ok(DistributionProof {
+                        backend,
+                        proof_bytes: decode_hex(&proof.proof_bytes)?,
+                        public_values: decode_hex(&proof.public_values)?,
+                        vkey_hash: proof.vkey_hash,
+                        cycles: proof.cycles,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Remote proof, checked locally: public values must match what a
+    /// correct guest commits for these entries, and the proof must verify
+    /// against the local backend's program.
+    fn prove_and_verify_remote(
+        &self,
+        entries: &[([u8; 32], u64)],
+        pool_pubkey: [u8; 32],
+    ) -> Result<DistributionProof, ProvingError> {
+        // Run blocking HTTP on its own thread: callers may be on an async
+        // runtime, where reqwest's blocking client must not be driven.
+        let proof = std::thread::scope(|s| {
+            s.spawn(|| self.prove_remote(entries, pool_pubkey))
+                .join()
+                .unwrap_or_else(|_| Err(ProvingError::Remote("remote prover thread panicked".to_string())))
+        })?;
+
+        if proof.public_values != expected_public_values(entries, &pool_pubkey) {
+            return Err(ProvingError::PublicValuesMismatch);
+        }
+        self.local.verify_distribution(&proof)?;
+        Ok(proof)
+    }
+}
+
+fn decode_hex(s: &str) -> Result<Vec<u8>, ProvingError> {
+    hex::decode(s.trim_start_matches("0x")).map_err(|e| ProvingError::Remote(format!("invalid hex: {}", e)))
+}
+
+impl DistributionProving for RemoteProver {
+    fn backend(&self) -> ProverBackend {
+        self.local.backend()
+    }
+
+    fn vkey_hash(&self) -> String {
+        self.local.vkey_hash()
+    }
+
+    fn prove_distribution(
+        &self,
+        entries: &[([u8; 32], u64)],
+        pool_pubkey: [u8; 32],
+    ) -> Result<DistributionProof, ProvingError> {
+        if entries.is_empty() {
+            return Err(ProvingError::EmptyEntries);
+        }
+
+        match self.prove_and_verify_remote(entries, pool_pubkey) {
+            Ok(proof) => {
+                info!("Remote distribution proof verified ({} proof bytes)", proof.proof_bytes.len());
+                Ok(proof)
+            }
+            Err(e) => {
+                warn!("Remote distribution proving failed: {} — falling back to local prover", e);
+                self.local.prove_distribution(entries, pool_pubkey)
+            }
+        }
+    }
+
+    fn verify_distribution(&self, proof: &DistributionProof) -> Result<(), ProvingError> {
+        self.local.verify_distribution(proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Local stand-in backend: "proves" by returning the expected public
+    /// values, verifies by checking a marker proof.
+    struct FakeLocal {
+        local_proves: Arc<AtomicUsize>,
+    }
+
+    impl DistributionProving for FakeLocal {
+        fn backend(&self) -> ProverBackend {
+            ProverBackend::Sp1
+        }
+        fn vkey_hash(&self) -> String {
+            "0xfake".to_string()
+        }
+        fn prove_distribution(&self, entries: &[([u8; 32], u64)], pool_pubkey: [u8; 32]) -> Result<DistributionProof, ProvingError> {
+            self.local_proves.fetch_add(1, Ordering::SeqCst);
+            Ok(DistributionProof {
+                backend: ProverBackend::Sp1,
+                proof_bytes: b"local".to_vec(),
+                public_values: expected_public_values(entries, &pool_pubkey),
+                vkey_hash: self.vkey_hash(),
+                cycles: None,
+            })
+        }
+        fn verify_distribution(&self, proof: &DistributionProof) -> Result<(), ProvingError> {
+            if proof.proof_bytes == b"good" {
+                Ok(())
+            } else {
+                Err(ProvingError::VerificationFailed("bad seal".to_string()))
+            }
+        }
+    }
+
+    /// Minimal HTTP server answering each request with the next canned body
+    fn serve(bodies: Vec<String>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for body in bodies {
+                let Ok((mut stream, _)) = listener.accept() else { return };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap_or(0) == 0 || line == "\r\n" {
+                        break;
+                    }
+                    if let Some(v) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                        content_length = v.trim().parse().unwrap_or(0);
+                    }
+                }
+                let mut buf = vec![0u8; content_length];
+                let _ = reader.read_exact(&mut buf);
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(), body,
+                );
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    fn prover(endpoint: String, local_proves: Arc<AtomicUsize>) -> RemoteProver {
+        let mut config = RemoteProverConfig::new(endpoint);
+        config.poll_interval = Duration::from_millis(10);
+        RemoteProver::new(config, Box::new(FakeLocal { local_proves }))
+    }
+
+    fn done_body(proof_hex: &str, public_values: &[u8]) -> String {
+        format!(
+            r#"{{"status":"done","proof":{{"proof_bytes":"{}","public_values":"{}","vkey_hash":"0xfake"}}}}"#,
+            proof_hex, hex::encode(public_values),
+        )
+    }
+
+    #[test]
+    fn test_remote_proof_accepted_after_polling() {
+        let entries = [([1u8; 32], 100), ([2u8; 32], 50)];
+        let pool = [7u8; 32];
+        let endpoint = serve(vec![
+            r#"{"job_id":"j1"}"#.to_string(),
+            r#"{"status":"running"}"#.to_string(),
+            done_body(&hex::encode(b"good"), &expected_public_values(&entries, &pool)),
+        ]);
+        let local_proves = Arc::new(AtomicUsize::new(0));
+
+        let proof = prover(endpoint, local_proves.clone()).prove_distribution(&entries, pool).unwrap();
+        assert_eq!(proof.proof_bytes, b"good");
+        assert_eq!(local_proves.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_unverifiable_remote_proof_falls_back() {
+        let entries = [([1u8; 32], 100)];
+        let pool = [7u8; 32];
+        let endpoint = serve(vec![
+            r#"{"job_id":"j1"}"#.to_string(),
+            done_body(&hex::encode(b"forged"), &expected_public_values(&entries, &pool)),
+        ]);
+        let local_proves = Arc::new(AtomicUsize::new(0));
+
+        let proof = prover(endpoint, local_proves.clone()).prove_distribution(&entries, pool).unwrap();
+        assert_eq!(proof.proof_bytes, b"local");
+        assert_eq!(local_proves.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_wrong_public_values_fall_back() {
+        let entries = [([1u8; 32], 100)];
+        let pool = [7u8; 32];
+        let other = expected_public_values(&[([1u8; 32], 999)], &pool);
+        let endpoint = serve(vec![
+            r#"{"job_id":"j1"}"#.to_string(),
+            done_body(&hex::encode(b"good"), &other),
+        ]);
+        let local_proves = Arc::new(AtomicUsize::new(0));
+
+        let proof = prover(endpoint, local_proves.clone()).prove_distribution(&entries, pool).unwrap();
+        assert_eq!(proof.proof_bytes, b"local");
+        assert_eq!(local_proves.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_failed_job_falls_back() {
+        let endpoint = serve(vec![
+            r#"{"job_id":"j1"}"#.to_string(),
+            r#"{"status":"failed","error":"out of gpu memory"}"#.to_string(),
+        ]);
+        let local_proves = Arc::new(AtomicUsize::new(0));
+
+        let proof = prover(endpoint, local_proves.clone()).prove_distribution(&[([1u8; 32], 1)], [0u8; 32]).unwrap();
+        assert_eq!(proof.proof_bytes, b"local");
+        assert_eq!(local_proves.load(Ordering::SeqCst), 1);
+    }
+}
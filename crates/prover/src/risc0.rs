@@ -0,0 +1,172 @@
+//! RISC Zero Groth16 distribution prover.
+//!
+//! Alternative to the SP1 backend for operators whose hardware or licensing
+//! constraints favour RISC Zero. The guest (`distribution-guest-risc0`) runs
+//! the same algorithm as the SP1 guest and commits the same 76-byte
+//! public-value layout, so the aggregator and settlement code are unchanged.
+//!
+//! Note: the on-chain program verifies SP1 proofs. Posting RISC Zero proofs
+//! requires a deployment configured with the RISC Zero Groth16 verifier and
+//! this backend's image ID.
+//!
+//! `default_prover()` reads `RISC0_PROVER`:
+//! - `bonsai` — Bonsai proving service (needs `BONSAI_API_KEY` / `BONSAI_API_URL`)
+//! - `local`  — local prover (Groth16 wrapping needs Docker on x86_64)
+//! - unset    — local prover
+
+use tracing::info;
+
+use risc0_zkvm::sha::{Digest, Digestible};
+use risc0_zkvm::{
+    default_prover, ExecutorEnv, Groth16Receipt, Groth16ReceiptVerifierParameters, InnerReceipt,
+    MaybePruned, ProverOpts, Receipt, ReceiptClaim,
+};
+use craftnet_distribution_guest_types::DistributionInput;
+
+use crate::public_values::expected_public_values;
+use crate::traits::{DistributionProof, DistributionProving, ProverBackend, ProvingError};
+
+mod methods {
+    include!(concat!(env!("OUT_DIR"), "/methods.rs"));
+}
+
+use methods::{CRAFTNET_DISTRIBUTION_GUEST_RISC0_ELF as DISTRIBUTION_ELF, CRAFTNET_DISTRIBUTION_GUEST_RISC0_ID as DISTRIBUTION_ID};
+
+/// RISC Zero Groth16 distribution prover.
+pub struct Risc0DistributionProver {
+    /// Guest cycles a proof may take (None = unlimited)
+    max_cycles: Option<u64>,
+}
+
+impl Risc0DistributionProver {
+    pub fn new() -> Self {
+        Self { max_cycles: None }
+    }
+
+    /// Stop the guest once it runs past `max_cycles`
+    pub fn with_max_cycles(mut self, max_cycles: Option<u64>) -> Self {
+        self.max_cycles = max_cycles;
+        self
+    }
+}
+
+impl Default for Risc0DistributionProver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DistributionProving for Risc0DistributionProver {
+    fn backend(&self) -> ProverBackend {
+        ProverBackend::Risc0
+    }
+
+    /// Image ID of the distribution guest ("0x..." hex)
+    fn vkey_hash(&self) -> String {
+        format!("0x{}", hex::encode(Digest::from(DISTRIBUTION_ID).as_bytes()))
+    }
+
+    fn prove_distribution(
+        &self,
+        entries: &[([u8; 32], u64)],
+        pool_pubkey: [u8; 32],
+    ) -> Result<DistributionProof, ProvingError> {
+        if entries.is_empty() {
+            return Err(ProvingError::EmptyEntries);
+        }
+
+        let input = DistributionInput {
+            entries: entries.to_vec(),
+            pool_pubkey,
+        };
+
+        info!(
+            "Starting RISC Zero distribution Groth16 prove for {} entries, pool={}",
+            entries.len(),
+            hex::encode(&pool_pubkey[..8]),
+        );
+
+        let t0 = std::time::Instant::now();
+
+        let env = ExecutorEnv::builder()
+            .session_limit(self.max_cycles)
+            .write(&input)
+            .and_then(|b| b.build())
+            .map_err(|e| ProvingError::ProveFailed(format!("RISC Zero executor env: {}", e)))?;
+
+        let prove_info = default_prover()
+            .prove_with_opts(env, DISTRIBUTION_ELF, &ProverOpts::groth16())
+            .map_err(|e| ProvingError::ProveFailed(format!("RISC Zero Groth16 prove failed: {}", e)))?;
+        let cycles = prove_info.stats.total_cycles;
+        let receipt = prove_info.receipt;
+
+        receipt.verify(DISTRIBUTION_ID)
+            .map_err(|e| ProvingError::ProveFailed(format!("RISC Zero receipt verification failed: {}", e)))?;
+
+        let public_values = receipt.journal.bytes.clone();
+        if public_values != expected_public_values(entries, &pool_pubkey) {
+            return Err(ProvingError::PublicValuesMismatch);
+        }
+
+        let proof_bytes = receipt.inner.groth16()
+            .map_err(|e| ProvingError::ProveFailed(format!("RISC Zero receipt is not Groth16: {}", e)))?
+            .seal
+            .clone();
+
+        let vkey_hash = self.vkey_hash();
+
+        info!(
+            "RISC Zero distribution Groth16 prove complete: proof_size={}, public_values_size={}, image_id={}, cycles={}, elapsed={:?}",
+            proof_bytes.len(),
+            public_values.len(),
+            &vkey_hash[..16],
+            cycles,
+            t0.elapsed(),
+        );
+
+        Ok(DistributionProof {
+            backend: ProverBackend::Risc0,
+            proof_bytes,
+            public_values,
+            vkey_hash,
+            cycles: Some(cycles),
+        })
+    }
+
+    /// Rebuild a Groth16 receipt from the seal + journal and verify it
+    /// against the distribution guest's image ID.
+    fn verify_distribution(&self, proof: &DistributionProof) -> Result<(), ProvingError> {
+        if proof.backend != ProverBackend::Risc0 {
+            return Err(ProvingError::VerificationFailed(format!("not a risc0 proof ({})", proof.backend)));
+        }
+        let claim = ReceiptClaim::ok(DISTRIBUTION_ID, MaybePruned::Value(proof.public_values.clone()));
+        let groth16 = Groth16Receipt::new(
+            proof.proof_bytes.clone(),
+            MaybePruned::Value(claim),
+            Groth16ReceiptVerifierParameters::default().digest(),
+        );
+        Receipt::new(InnerReceipt::Groth16(groth16), proof.public_values.clone())
+            .verify(DISTRIBUTION_ID)
+            .map_err(|e| ProvingError::VerificationFailed(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden::{array, bytes, bytes32, entries, vectors};
+
+    /// The guest's own leaf and tree code commits the golden public values
+    #[test]
+    fn test_guest_matches_golden_vectors() {
+        for vector in array(&vectors()["distributions"]) {
+            let input = DistributionInput {
+                entries: entries(&vector["entries"]),
+                pool_pubkey: bytes32(&vector["pool"]),
+            };
+            let env = ExecutorEnv::builder().write(&input).and_then(|b| b.build()).unwrap();
+            let session = risc0_zkvm::default_executor().execute(env, DISTRIBUTION_ELF).unwrap();
+            assert_eq!(session.journal.bytes, bytes(&vector["public_values"]), "{}", vector["name"]);
+        }
+    }
+}
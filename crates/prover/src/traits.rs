@@ -32,3 +32,104 @@ pub trait ReceiptCompression: Send + Sync {
     /// Compress a batch of receipts into a Merkle root.
     fn compress(&self, batch: &[ForwardReceipt]) -> Result<CompressedBatch, CompressionError>;
 }
+
+/// A distribution proof from any proving backend.
+///
+/// Every backend commits the same 76-byte public-value layout (see
+/// `public_values`), so the aggregator and settlement code don't care
+/// which zkVM produced it.
+#[derive(Debug, Clone)]
+pub struct DistributionProof {
+    /// Backend that produced the proof
+    pub backend: ProverBackend,
+    /// Raw Groth16 proof bytes (seal)
+    pub proof_bytes: Vec<u8>,
+    /// Public values committed by the guest (76 bytes fixed layout)
+    pub public_values: Vec<u8>,
+    /// Program identifier ("0x..." hex): SP1 vkey hash or RISC Zero image ID
+    pub vkey_hash: String,
+    /// Guest cycles the proof took, when the backend reports them
+    pub cycles: Option<u64>,
+}
+
+/// Errors from distribution proving.
+#[derive(Debug, thiserror::Error)]
+pub enum ProvingError {
+    #[error("Empty distribution entries")]
+    EmptyEntries,
+
+    #[error("Backend not compiled in: {0} (enable the `{0}` feature)")]
+    BackendUnavailable(&'static str),
+
+    #[error("Public values mismatch: guest committed a different layout")]
+    PublicValuesMismatch,
+
+    #[error("Proof verification failed: {0}")]
+    VerificationFailed(String),
+
+    #[error("Guest needs {cycles} cycles, over the {limit} cycle limit")]
+    CycleLimitExceeded { cycles: u64, limit: u64 },
+
+    #[error("Remote prover error: {0}")]
+    Remote(String),
+
+    #[error("Proving failed: {0}")]
+    ProveFailed(String),
+}
+
+/// zkVM backend used for distribution proofs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProverBackend {
+    /// Succinct SP1 (feature `sp1`)
+    Sp1,
+    /// RISC Zero (feature `risc0`)
+    Risc0,
+}
+
+impl ProverBackend {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProverBackend::Sp1 => "sp1",
+            ProverBackend::Risc0 => "risc0",
+        }
+    }
+
+    /// Parse a backend name ("sp1" / "risc0", case-insensitive)
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "sp1" => Some(ProverBackend::Sp1),
+            "risc0" | "risczero" => Some(ProverBackend::Risc0),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ProverBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Pluggable distribution proving trait.
+///
+/// Implementations prove that the distribution Merkle tree was built
+/// correctly from `(relay_pubkey, bytes)` entries for a pool, and commit
+/// the public values from `public_values::distribution_public_values`.
+pub trait DistributionProving: Send + Sync {
+    /// Backend identifier
+    fn backend(&self) -> ProverBackend;
+
+    /// Program identifier the on-chain verifier must pin
+    fn vkey_hash(&self) -> String;
+
+    /// Generate a Groth16 proof over the distribution construction.
+    fn prove_distribution(
+        &self,
+        entries: &[([u8; 32], u64)],
+        pool_pubkey: [u8; 32],
+    ) -> Result<DistributionProof, ProvingError>;
+
+    /// Verify a proof produced for this backend's program (e.g. one returned
+    /// by a remote proving service).
+    fn verify_distribution(&self, proof: &DistributionProof) -> Result<(), ProvingError>;
+}
@@ -1,10 +1,19 @@
 //! Request cache for destination verification
 //!
 //! Caches request_id → user_pubkey mappings to verify response destinations.
+//! Entries expire individually (their TTL should follow the request timeout)
+//! and the cache can be saved to and reloaded from a small file, so a relay
+//! restart doesn't break verification of requests still in flight.
+//!
+//! File format: a sequence of 72-byte records
+//! `[request_id: 32][user_pubkey: 32][expires_at: u64 LE unix secs]`,
+//! oldest first.
 
 use std::collections::{HashMap, VecDeque};
+use std::io::{self, Read, Write};
+use std::path::Path;
 use std::time::{Duration, Instant};
-use craftnet_core::{Id, PublicKey};
+use craftnet_core::{Clock, Id, PublicKey, SystemClock};
 
 /// Default TTL for cached entries (5 minutes)
 const DEFAULT_TTL: Duration = Duration::from_secs(300);
@@ -12,10 +21,33 @@ const DEFAULT_TTL: Duration = Duration::from_secs(300);
 /// Maximum cache size
 const DEFAULT_MAX_SIZE: usize = 10000;
 
+/// Size of one persisted entry
+const RECORD_LEN: usize = 32 + 32 + 8;
+
 /// A cached request entry
 struct CacheEntry {
     user_pubkey: PublicKey,
-    created_at: Instant,
+    expires_at: Instant,
+}
+
+/// Outcome of verifying a response destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verification {
+    /// The destination is the original requester
+    Match,
+    /// The destination is someone else — the exit misrouted the response
+    Mismatch,
+    /// Unknown or expired request id
+    Unknown,
+}
+
+/// Verification counters, for spotting misbehaving exits
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RequestCacheStats {
+    pub entries: usize,
+    pub verified: u64,
+    pub mismatches: u64,
+    pub unknown: u64,
 }
 
 /// LRU cache for request → user_pubkey mappings
@@ -27,6 +59,7 @@ pub struct RequestCache {
     insertion_order: VecDeque<Id>,
     ttl: Duration,
     max_size: usize,
+    stats: RequestCacheStats,
 }
 
 impl RequestCache {
@@ -37,6 +70,7 @@ impl RequestCache {
             insertion_order: VecDeque::new(),
             ttl: DEFAULT_TTL,
             max_size: DEFAULT_MAX_SIZE,
+            stats: RequestCacheStats::default(),
         }
     }
 
@@ -47,18 +81,26 @@ impl RequestCache {
             insertion_order: VecDeque::new(),
             ttl,
             max_size,
+            stats: RequestCacheStats::default(),
         }
     }
 
-    /// Store a request_id → user_pubkey mapping
+    /// Store a request_id → user_pubkey mapping with the default TTL
     pub fn insert(&mut self, request_id: Id, user_pubkey: PublicKey) {
+        self.insert_with_ttl(request_id, user_pubkey, self.ttl);
+    }
+
+    /// Store a mapping that expires after `ttl` (e.g. the request timeout)
+    pub fn insert_with_ttl(&mut self, request_id: Id, user_pubkey: PublicKey, ttl: Duration) {
+        let expires_at = Instant::now() + ttl;
+
         // If this key already exists, update in place without pushing to deque
         if self.entries.contains_key(&request_id) {
             self.entries.insert(
                 request_id,
                 CacheEntry {
                     user_pubkey,
-                    created_at: Instant::now(),
+                    expires_at,
                 },
             );
             return;
@@ -82,7 +124,7 @@ impl RequestCache {
             request_id,
             CacheEntry {
                 user_pubkey,
-                created_at: Instant::now(),
+                expires_at,
             },
         );
         self.insertion_order.push_back(request_id);
@@ -91,7 +133,7 @@ impl RequestCache {
     /// Get the user_pubkey for a request_id
     pub fn get(&self, request_id: &Id) -> Option<PublicKey> {
         self.entries.get(request_id).and_then(|entry| {
-            if entry.created_at.elapsed() < self.ttl {
+            if entry.expires_at > Instant::now() {
                 Some(entry.user_pubkey)
             } else {
                 None
@@ -99,6 +141,30 @@ impl RequestCache {
         })
     }
 
+    /// Check that a response for `request_id` goes to `destination`,
+    /// counting the outcome
+    pub fn verify(&mut self, request_id: &Id, destination: &PublicKey) -> Verification {
+        let verification = match self.get(request_id) {
+            Some(user_pubkey) if user_pubkey == *destination => Verification::Match,
+            Some(_) => Verification::Mismatch,
+            None => Verification::Unknown,
+        };
+        match verification {
+            Verification::Match => self.stats.verified += 1,
+            Verification::Mismatch => self.stats.mismatches += 1,
+            Verification::Unknown => self.stats.unknown += 1,
+        }
+        verification
+    }
+
+    /// Entry count and verification counters
+    pub fn stats(&self) -> RequestCacheStats {
+        RequestCacheStats {
+            entries: self.entries.len(),
+            ..self.stats
+        }
+    }
+
     /// Check if a request_id exists and is not expired
     pub fn contains(&self, request_id: &Id) -> bool {
         self.get(request_id).is_some()
@@ -124,8 +190,7 @@ impl RequestCache {
     /// Remove all expired entries and drain stale front entries from deque
     pub fn evict_expired(&mut self) {
         let now = Instant::now();
-        self.entries
-            .retain(|_, entry| now.duration_since(entry.created_at) < self.ttl);
+        self.entries.retain(|_, entry| entry.expires_at > now);
 
         // Drain stale front entries from deque (already removed from map or expired)
         while let Some(front) = self.insertion_order.front() {
@@ -142,6 +207,58 @@ impl RequestCache {
         self.entries.clear();
         self.insertion_order.clear();
     }
+
+    /// Write live entries to `path` (atomically, via a temp file).
+    /// Returns the number of entries written.
+    pub fn save(&self, path: &Path) -> io::Result<usize> {
+        let now = Instant::now();
+        let unix_now = SystemClock.unix_now();
+        let mut buf = Vec::with_capacity(self.entries.len() * RECORD_LEN);
+        let mut written = 0;
+        for request_id in &self.insertion_order {
+            let Some(entry) = self.entries.get(request_id) else { continue };
+            if entry.expires_at <= now {
+                continue;
+            }
+            let expires_at = unix_now + entry.expires_at.duration_since(now).as_secs().max(1);
+            buf.extend_from_slice(request_id);
+            buf.extend_from_slice(&entry.user_pubkey);
+            buf.extend_from_slice(&expires_at.to_le_bytes());
+            written += 1;
+        }
+
+        let tmp = path.with_extension("tmp");
+        let mut file = std::fs::File::create(&tmp)?;
+        file.write_all(&buf)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, path)?;
+        Ok(written)
+    }
+
+    /// Load entries saved by [`Self::save`], skipping expired ones. A missing
+    /// file loads nothing. Returns the number of entries loaded.
+    pub fn load(&mut self, path: &Path) -> io::Result<usize> {
+        let mut buf = Vec::new();
+        match std::fs::File::open(path) {
+            Ok(mut file) => file.read_to_end(&mut buf)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e),
+        };
+
+        let unix_now = SystemClock.unix_now();
+        let mut loaded = 0;
+        for record in buf.chunks_exact(RECORD_LEN) {
+            let request_id: Id = record[..32].try_into().unwrap();
+            let user_pubkey: PublicKey = record[32..64].try_into().unwrap();
+            let expires_at = u64::from_le_bytes(record[64..].try_into().unwrap());
+            if expires_at <= unix_now {
+                continue;
+            }
+            self.insert_with_ttl(request_id, user_pubkey, Duration::from_secs(expires_at - unix_now));
+            loaded += 1;
+        }
+        Ok(loaded)
+    }
 }
 
 impl Default for RequestCache {
@@ -278,4 +395,52 @@ mod tests {
         assert!(cache.contains(&test_id(4)));
         assert!(cache.contains(&test_id(3)));
     }
+
+    #[test]
+    fn test_per_entry_ttl() {
+        let mut cache = RequestCache::new();
+        cache.insert_with_ttl(test_id(1), test_pubkey(1), Duration::from_millis(10));
+        cache.insert(test_id(2), test_pubkey(2));
+
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(!cache.contains(&test_id(1)));
+        assert!(cache.contains(&test_id(2)));
+
+        cache.evict_expired();
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_verify_counts_outcomes() {
+        let mut cache = RequestCache::new();
+        cache.insert(test_id(1), test_pubkey(1));
+
+        assert_eq!(cache.verify(&test_id(1), &test_pubkey(1)), Verification::Match);
+        assert_eq!(cache.verify(&test_id(1), &test_pubkey(2)), Verification::Mismatch);
+        assert_eq!(cache.verify(&test_id(9), &test_pubkey(1)), Verification::Unknown);
+
+        let stats = cache.stats();
+        assert_eq!(stats.entries, 1);
+        assert_eq!((stats.verified, stats.mismatches, stats.unknown), (1, 1, 1));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!("craftnet-request-cache-{}.bin", std::process::id()));
+        let mut cache = RequestCache::new();
+        cache.insert(test_id(1), test_pubkey(1));
+        cache.insert_with_ttl(test_id(2), test_pubkey(2), Duration::from_secs(60));
+        cache.insert_with_ttl(test_id(3), test_pubkey(3), Duration::ZERO);
+        assert_eq!(cache.save(&path).unwrap(), 2);
+
+        let mut restored = RequestCache::new();
+        assert_eq!(restored.load(&path).unwrap(), 2);
+        assert_eq!(restored.get(&test_id(1)), Some(test_pubkey(1)));
+        assert_eq!(restored.get(&test_id(2)), Some(test_pubkey(2)));
+        assert!(!restored.contains(&test_id(3)));
+        std::fs::remove_file(&path).unwrap();
+
+        // Missing file is not an error
+        assert_eq!(restored.load(&path).unwrap(), 0);
+    }
 }
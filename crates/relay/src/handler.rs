@@ -9,16 +9,20 @@
 //! looks up the registered client PeerId and forwards directly.
 
 use std::collections::HashMap;
-use std::sync::Arc;
-use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 use tracing::{debug, info, warn};
-use craftnet_core::{Id, PublicKey, Shard, ForwardReceipt, CraftNetError};
+use craftnet_core::{Id, PublicKey, Shard, ForwardReceipt, CraftNetError, ChainAck, sign_chain_ack};
+use craftnet_core::{seal_hop_timing, trace_now_ms, HopRole, HopTiming, MAX_SHARD_TRACE_ENTRIES};
+use craftnet_core::{OnionLayer, OnionKeyInfo, OnionKeyRing, DEFAULT_ONION_KEY_GRACE, DEFAULT_ONION_KEY_ROTATION};
 use craftec_crypto::{SigningKeypair, EncryptionKeypair};
 use craftnet_core::onion_crypto::{peel_onion_layer};
 use craftnet_core::receipt_crypto::{sign_forward_receipt};
 use craftnet_settlement::SettlementClient;
 
+use crate::replay::{ReplayCache, DEFAULT_REPLAY_CAPACITY, DEFAULT_REPLAY_WINDOW};
+
 #[derive(Error, Debug)]
 pub enum RelayError {
     /// Failed to peel onion layer (corrupted header or wrong key)
@@ -29,6 +33,14 @@ pub enum RelayError {
     #[error("Tunnel not found: {0}")]
     TunnelNotFound(String),
 
+    /// Shard is cover traffic: ack it, but don't forward or sign a receipt
+    #[error("Cover shard")]
+    CoverShard,
+
+    /// Shard id was already seen recently: a replay, don't forward it again
+    #[error("Replayed shard: {0}")]
+    Replay(String),
+
     /// Internal relay error
     #[error("Internal error: {0}")]
     Internal(String),
@@ -42,17 +54,41 @@ impl From<CraftNetError> for RelayError {
 
 pub type Result<T> = std::result::Result<T, RelayError>;
 
+/// Chain ack a relay owes the client for a forwarded shard
+/// (see `craftnet_core::chain_ack`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RelayChainAck {
+    /// Our signed ack, to send to the peer the shard came from
+    pub ack: ChainAck,
+    /// Ref the next relay's acks come back with; pass those on re-tagged
+    /// `ack.ack_ref` (None when we are the last relay)
+    pub down_ref: Option<Id>,
+}
+
 /// Relay configuration
 #[derive(Debug, Clone)]
 pub struct RelayConfig {
     /// Whether this relay can act as the last hop
     pub can_be_last_hop: bool,
+    /// Shard ids remembered per replay window
+    pub replay_cache_capacity: usize,
+    /// Replay window; a shard id is remembered for one to two windows
+    pub replay_window: Duration,
+    /// How often a new onion key is generated (None: only the static
+    /// encryption key is used)
+    pub onion_key_rotation: Option<Duration>,
+    /// How long a rotated-out onion key still peels shards
+    pub onion_key_grace: Duration,
 }
 
 impl Default for RelayConfig {
     fn default() -> Self {
         Self {
             can_be_last_hop: true,
+            replay_cache_capacity: DEFAULT_REPLAY_CAPACITY,
+            replay_window: DEFAULT_REPLAY_WINDOW,
+            onion_key_rotation: Some(DEFAULT_ONION_KEY_ROTATION),
+            onion_key_grace: DEFAULT_ONION_KEY_GRACE,
         }
     }
 }
@@ -72,8 +108,11 @@ struct TunnelRegistration {
 pub struct RelayHandler {
     /// This relay's signing keypair (for ForwardReceipts)
     keypair: SigningKeypair,
-    /// This relay's encryption keypair (for onion layer decryption)
+    /// This relay's static encryption keypair (for onion layer decryption
+    /// by clients that don't have a rotated key, and response gateway layers)
     encryption_keypair: EncryptionKeypair,
+    /// Rotating onion keys (None when rotation is off)
+    onion_keys: Option<OnionKeyRing>,
     /// Tunnel registrations: tunnel_id → client PeerId (gateway mode)
     tunnel_registrations: HashMap<Id, TunnelRegistration>,
     /// Relay configuration
@@ -81,28 +120,29 @@ pub struct RelayHandler {
     config: RelayConfig,
     /// Settlement client (optional)
     settlement_client: Option<Arc<SettlementClient>>,
+    /// Recently forwarded shard ids (handle_shard only takes &self)
+    replay_cache: Mutex<ReplayCache>,
 }
 
 impl RelayHandler {
     /// Create a new relay handler with signing and encryption keypairs
     pub fn new(keypair: SigningKeypair, encryption_keypair: EncryptionKeypair) -> Self {
-        Self {
-            keypair,
-            encryption_keypair,
-            tunnel_registrations: HashMap::new(),
-            config: RelayConfig::default(),
-            settlement_client: None,
-        }
+        Self::with_config(keypair, encryption_keypair, RelayConfig::default())
     }
 
     /// Create a relay handler with custom config
     pub fn with_config(keypair: SigningKeypair, encryption_keypair: EncryptionKeypair, config: RelayConfig) -> Self {
+        let replay_cache = ReplayCache::new(config.replay_cache_capacity, config.replay_window);
+        let onion_keys = config.onion_key_rotation
+            .map(|rotation| OnionKeyRing::new(rotation, config.onion_key_grace, Instant::now()));
         Self {
             keypair,
             encryption_keypair,
+            onion_keys,
             tunnel_registrations: HashMap::new(),
             config,
             settlement_client: None,
+            replay_cache: Mutex::new(replay_cache),
         }
     }
 
@@ -113,13 +153,9 @@ impl RelayHandler {
         config: RelayConfig,
         settlement_client: Arc<SettlementClient>,
     ) -> Self {
-        Self {
-            keypair,
-            encryption_keypair,
-            tunnel_registrations: HashMap::new(),
-            config,
-            settlement_client: Some(settlement_client),
-        }
+        let mut handler = Self::with_config(keypair, encryption_keypair, config);
+        handler.settlement_client = Some(settlement_client);
+        handler
     }
 
     /// Set the settlement client
@@ -137,6 +173,39 @@ impl RelayHandler {
         self.encryption_keypair.public_key_bytes()
     }
 
+    /// Current rotating onion key (None when rotation is off)
+    pub fn onion_key(&self) -> Option<OnionKeyInfo> {
+        self.onion_keys.as_ref().map(|ring| ring.current(Instant::now()))
+    }
+
+    /// Rotate the onion key if its epoch is over. Returns whether a new
+    /// key was generated (and should be announced).
+    pub fn maybe_rotate_onion_key(&mut self, now: Instant) -> bool {
+        let rotated = self.onion_keys.as_mut().is_some_and(|ring| ring.maybe_rotate(now));
+        if rotated {
+            if let Some(key) = self.onion_key() {
+                info!("Rotated onion key: epoch {} ({})", key.epoch, hex::encode(&key.public_key[..8]));
+            }
+        }
+        rotated
+    }
+
+    /// Peel with the current onion key, then the previous one, then the
+    /// static encryption key
+    fn peel_layer(&self, shard: &Shard) -> Result<OnionLayer> {
+        let mut secrets = self.onion_keys.as_ref().map(|ring| ring.secrets()).unwrap_or_default();
+        secrets.push(self.encryption_keypair.secret_key_bytes());
+
+        let mut last_err = None;
+        for secret in &secrets {
+            match peel_onion_layer(secret, &shard.ephemeral_pubkey, &shard.header) {
+                Ok(layer) => return Ok(layer),
+                Err(e) => last_err = Some(e),
+            }
+        }
+        Err(RelayError::OnionPeelFailed(last_err.map(|e| e.to_string()).unwrap_or_default()))
+    }
+
     /// Handle an incoming shard by peeling one onion layer.
     ///
     /// Returns `(modified_shard, next_peer_id_bytes, forward_receipt, pool_pubkey)`.
@@ -144,17 +213,44 @@ impl RelayHandler {
     /// pool_pubkey to route the receipt into the correct proof queue.
     ///
     /// `sender_pubkey` comes from the libp2p connection (authenticated via Noise).
+    /// The payload is forwarded in the buffer it arrived in; only the header
+    /// is replaced with the peeled remainder.
     pub fn handle_shard(
         &self,
-        mut shard: Shard,
+        shard: Shard,
         sender_pubkey: PublicKey,
     ) -> Result<(Shard, Vec<u8>, ForwardReceipt, PublicKey)> {
+        self.handle_shard_with_ack(shard, sender_pubkey)
+            .map(|(shard, next_peer, receipt, pool_pubkey, _)| (shard, next_peer, receipt, pool_pubkey))
+    }
+
+    /// Like [`handle_shard`](Self::handle_shard), also returning the chain ack
+    /// to send back to the sender when the client asked for one.
+    pub fn handle_shard_with_ack(
+        &self,
+        mut shard: Shard,
+        sender_pubkey: PublicKey,
+    ) -> Result<(Shard, Vec<u8>, ForwardReceipt, PublicKey, Option<RelayChainAck>)> {
+        let received_at_ms = trace_now_ms();
+
         // Peel one onion layer
-        let layer = peel_onion_layer(
-            &self.encryption_keypair.secret_key_bytes(),
-            &shard.ephemeral_pubkey,
-            &shard.header,
-        ).map_err(|e| RelayError::OnionPeelFailed(e.to_string()))?;
+        let layer = self.peel_layer(&shard)?;
+
+        // Cover traffic ends here — no receipt, so it never earns credits
+        if layer.settlement.cover {
+            return Err(RelayError::CoverShard);
+        }
+
+        // A shard id we already forwarded is a replay: forwarding it again
+        // would earn a second receipt for the same work
+        let fresh = self.replay_cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .check_and_insert(&layer.settlement.shard_id);
+        if !fresh {
+            warn!("Rejected replayed shard {}", hex::encode(&layer.settlement.shard_id[..8]));
+            return Err(RelayError::Replay(hex::encode(&layer.settlement.shard_id[..8])));
+        }
 
         // Extract pool routing info before moving layer fields
         let pool_pubkey = layer.settlement.pool_pubkey;
@@ -179,14 +275,35 @@ impl RelayHandler {
             );
             self.lookup_tunnel(tunnel_id)?
         } else {
-            layer.next_peer_id.clone()
+            layer.next_peer_id
         };
 
+        let chain_ack = layer.settlement.chain_ack.map(|request| RelayChainAck {
+            ack: sign_chain_ack(&self.keypair, &request, &layer.settlement.shard_id),
+            down_ref: request.down_ref,
+        });
+
         // Update shard for next hop
-        shard.header = layer.remaining_header;
+        shard.header = layer.remaining_header.into();
         shard.ephemeral_pubkey = layer.next_ephemeral_pubkey;
 
-        Ok((shard, next_peer, receipt, pool_pubkey))
+        // Traced diagnostic request: append our timing, sealed for the client
+        if let Some(trace_pubkey) = layer.settlement.trace_pubkey {
+            if shard.trace.len() < MAX_SHARD_TRACE_ENTRIES {
+                let timing = HopTiming { role: HopRole::Relay, received_at_ms, sent_at_ms: trace_now_ms() };
+                match seal_hop_timing(&trace_pubkey, &timing) {
+                    Ok(blob) => shard.trace.push(blob),
+                    Err(e) => debug!("Failed to seal hop timing: {}", e),
+                }
+            }
+        }
+
+        // Peeling shrinks the header; keep padded shards on a bucket boundary
+        if shard.is_padded() {
+            shard.pad_to_bucket();
+        }
+
+        Ok((shard, next_peer, receipt, pool_pubkey, chain_ack))
     }
 
     /// Register a tunnel_id → client PeerId mapping (called via TunnelSetup message).
@@ -239,6 +356,11 @@ impl RelayHandler {
         Ok(reg.client_peer_id.clone())
     }
 
+    /// Replayed shards rejected since startup
+    pub fn replays_rejected(&self) -> u64 {
+        self.replay_cache.lock().unwrap_or_else(|e| e.into_inner()).rejected()
+    }
+
     /// Get the number of active tunnel registrations
     pub fn tunnel_count(&self) -> usize {
         self.tunnel_registrations.len()
@@ -261,7 +383,7 @@ mod tests {
     use craftec_crypto::{EncryptionKeypair};
 use craftnet_core::onion_crypto::{build_onion_header};
 
-    use craftnet_core::OnionSettlement;
+    use craftnet_core::{verify_chain_ack, ChainAckRequest, OnionSettlement};
 
     fn make_handler() -> RelayHandler {
         let keypair = SigningKeypair::generate();
@@ -274,6 +396,9 @@ use craftnet_core::onion_crypto::{build_onion_header};
             shard_id: [idx + 100; 32],
             payload_size: 1024,
             pool_pubkey: [0u8; 32],
+            cover: false,
+            trace_pubkey: None,
+            chain_ack: None,
         }
     }
 
@@ -350,6 +475,139 @@ use craftnet_core::onion_crypto::{build_onion_header};
         assert!(shard3.header.is_empty());
     }
 
+    #[test]
+    fn test_cover_shard_has_no_receipt() {
+        let relay1 = EncryptionKeypair::generate();
+        let handler = RelayHandler::new(SigningKeypair::generate(), relay1.clone());
+
+        let mut settlement = make_settlement(1);
+        settlement.cover = true;
+        let (header, ephemeral) = build_onion_header(
+            &[(b"relay1_pid".as_slice(), &relay1.public_key_bytes())],
+            (b"exit_pid".as_slice(), &EncryptionKeypair::generate().public_key_bytes()),
+            &[settlement],
+            None,
+        ).unwrap();
+
+        let shard = Shard::new(ephemeral, header, vec![0; 64], vec![0; 92], 1, 1);
+        let result = handler.handle_shard(shard, [9u8; 32]);
+        assert!(matches!(result, Err(RelayError::CoverShard)));
+    }
+
+    #[test]
+    fn test_traced_shard_gets_sealed_timing() {
+        let relay1 = EncryptionKeypair::generate();
+        let handler = RelayHandler::new(SigningKeypair::generate(), relay1.clone());
+        let trace_keys = EncryptionKeypair::generate();
+
+        let mut settlement = make_settlement(1);
+        settlement.trace_pubkey = Some(trace_keys.public_key_bytes());
+        let (header, ephemeral) = build_onion_header(
+            &[(b"relay1_pid".as_slice(), &relay1.public_key_bytes())],
+            (b"exit_pid".as_slice(), &EncryptionKeypair::generate().public_key_bytes()),
+            &[settlement],
+            None,
+        ).unwrap();
+
+        let shard = Shard::new(ephemeral, header, vec![0; 64], vec![0; 92], 1, 1);
+        let (forwarded, _, _, _) = handler.handle_shard(shard, [9u8; 32]).unwrap();
+        assert_eq!(forwarded.trace.len(), 1);
+        let timing = craftnet_core::open_hop_timing(&trace_keys.secret_key_bytes(), &forwarded.trace[0]).unwrap();
+        assert_eq!(timing.role, HopRole::Relay);
+        assert!(timing.sent_at_ms >= timing.received_at_ms);
+
+        // Untraced shards stay untouched
+        let (header, ephemeral) = build_onion_header(
+            &[(b"relay1_pid".as_slice(), &relay1.public_key_bytes())],
+            (b"exit_pid".as_slice(), &EncryptionKeypair::generate().public_key_bytes()),
+            &[make_settlement(2)],
+            None,
+        ).unwrap();
+        let shard = Shard::new(ephemeral, header, vec![0; 64], vec![0; 92], 1, 1);
+        let (forwarded, _, _, _) = handler.handle_shard(shard, [9u8; 32]).unwrap();
+        assert!(forwarded.trace.is_empty());
+    }
+
+    #[test]
+    fn test_chain_ack_signed_for_client() {
+        let relay1 = EncryptionKeypair::generate();
+        let relay1_signing = SigningKeypair::generate();
+        let relay1_pubkey = relay1_signing.public_key_bytes();
+        let handler = RelayHandler::new(relay1_signing, relay1.clone());
+
+        let request = ChainAckRequest { up_ref: [1u8; 32], down_ref: Some([2u8; 32]), nonce: [3u8; 32] };
+        let mut settlement = make_settlement(1);
+        settlement.chain_ack = Some(request);
+        let (header, ephemeral) = build_onion_header(
+            &[(b"relay1_pid".as_slice(), &relay1.public_key_bytes())],
+            (b"exit_pid".as_slice(), &EncryptionKeypair::generate().public_key_bytes()),
+            &[settlement.clone()],
+            None,
+        ).unwrap();
+
+        let shard = Shard::new(ephemeral, header, vec![0; 64], vec![0; 92], 1, 1);
+        let (_, _, _, _, chain_ack) = handler.handle_shard_with_ack(shard, [9u8; 32]).unwrap();
+        let chain_ack = chain_ack.unwrap();
+        assert_eq!(chain_ack.ack.ack_ref, request.up_ref);
+        assert_eq!(chain_ack.down_ref, request.down_ref);
+        assert!(verify_chain_ack(&relay1_pubkey, &request.nonce, &settlement.shard_id, &chain_ack.ack));
+
+        // No request, no ack
+        let (header, ephemeral) = build_onion_header(
+            &[(b"relay1_pid".as_slice(), &relay1.public_key_bytes())],
+            (b"exit_pid".as_slice(), &EncryptionKeypair::generate().public_key_bytes()),
+            &[make_settlement(2)],
+            None,
+        ).unwrap();
+        let shard = Shard::new(ephemeral, header, vec![0; 64], vec![0; 92], 1, 1);
+        let (_, _, _, _, chain_ack) = handler.handle_shard_with_ack(shard, [9u8; 32]).unwrap();
+        assert!(chain_ack.is_none());
+    }
+
+    #[test]
+    fn test_padded_shard_repadded_after_peel() {
+        let relay1 = EncryptionKeypair::generate();
+        let relay2 = EncryptionKeypair::generate();
+        let handler = RelayHandler::new(SigningKeypair::generate(), relay1.clone());
+
+        let (header, ephemeral) = build_onion_header(
+            &[
+                (b"r1".as_slice(), &relay1.public_key_bytes()),
+                (b"r2".as_slice(), &relay2.public_key_bytes()),
+            ],
+            (b"exit".as_slice(), &EncryptionKeypair::generate().public_key_bytes()),
+            &[make_settlement(1), make_settlement(2)],
+            None,
+        ).unwrap();
+
+        let mut shard = Shard::new(ephemeral, header, vec![1; 3000], vec![0; 92], 2, 2);
+        shard.pad_to_bucket();
+        let padded_len = shard.encoded_len();
+
+        let (modified, _, _, _) = handler.handle_shard(shard, [9u8; 32]).unwrap();
+        assert!(modified.is_padded());
+        assert_eq!(modified.encoded_len(), padded_len);
+    }
+
+    #[test]
+    fn test_replayed_shard_rejected() {
+        let relay1 = EncryptionKeypair::generate();
+        let handler = RelayHandler::new(SigningKeypair::generate(), relay1.clone());
+
+        let (header, ephemeral) = build_onion_header(
+            &[(b"relay1_pid".as_slice(), &relay1.public_key_bytes())],
+            (b"exit_pid".as_slice(), &EncryptionKeypair::generate().public_key_bytes()),
+            &[make_settlement(1)],
+            None,
+        ).unwrap();
+        let shard = Shard::new(ephemeral, header, vec![1, 2, 3], vec![0; 92], 0, 0);
+
+        assert!(handler.handle_shard(shard.clone(), [9u8; 32]).is_ok());
+        let result = handler.handle_shard(shard, [9u8; 32]);
+        assert!(matches!(result, Err(RelayError::Replay(_))));
+        assert_eq!(handler.replays_rejected(), 1);
+    }
+
     #[test]
     fn test_wrong_key_fails() {
         let relay1 = EncryptionKeypair::generate();
@@ -379,6 +637,45 @@ use craftnet_core::onion_crypto::{build_onion_header};
         assert!(matches!(result.unwrap_err(), RelayError::OnionPeelFailed(_)));
     }
 
+    #[test]
+    fn test_rotated_onion_keys_peel() {
+        let handler_config = RelayConfig::default();
+        let rotation = handler_config.onion_key_rotation.unwrap();
+        let grace = handler_config.onion_key_grace;
+        let mut handler = RelayHandler::with_config(
+            SigningKeypair::generate(),
+            EncryptionKeypair::generate(),
+            handler_config,
+        );
+        let exit = EncryptionKeypair::generate();
+        let shard_for = |key: [u8; 32], idx: u8| {
+            let (header, ephemeral) = build_onion_header(
+                &[(b"r1".as_slice(), &key)],
+                (b"exit".as_slice(), &exit.public_key_bytes()),
+                &[make_settlement(idx)],
+                None,
+            ).unwrap();
+            Shard::new(ephemeral, header, vec![1, 2, 3], vec![0; 92], 0, 0)
+        };
+
+        let first = handler.onion_key().unwrap();
+        assert!(handler.handle_shard(shard_for(first.public_key, 1), [0u8; 32]).is_ok());
+        // Clients without a rotated key still reach us on the static key
+        assert!(handler.handle_shard(shard_for(handler.encryption_pubkey(), 2), [0u8; 32]).is_ok());
+
+        let start = Instant::now();
+        assert!(handler.maybe_rotate_onion_key(start + rotation));
+        let second = handler.onion_key().unwrap();
+        assert_eq!(second.epoch, first.epoch + 1);
+        assert!(handler.handle_shard(shard_for(second.public_key, 3), [0u8; 32]).is_ok());
+        // Shards built against the previous key peel during the grace period
+        assert!(handler.handle_shard(shard_for(first.public_key, 4), [0u8; 32]).is_ok());
+
+        assert!(!handler.maybe_rotate_onion_key(start + rotation + grace));
+        let result = handler.handle_shard(shard_for(first.public_key, 5), [0u8; 32]);
+        assert!(matches!(result, Err(RelayError::OnionPeelFailed(_))));
+    }
+
     #[test]
     fn test_tunnel_registration_and_gateway() {
         let relay = EncryptionKeypair::generate();
@@ -4,6 +4,12 @@
 //! No plaintext routing metadata is visible. Gateway mode delivers shards to
 //! registered clients via tunnel_id.
 
+mod cache;
 mod handler;
+mod qos;
+mod replay;
 
-pub use handler::{RelayHandler, RelayConfig, RelayError};
+pub use cache::{RequestCache, RequestCacheStats, Verification};
+pub use handler::{RelayHandler, RelayConfig, RelayError, RelayChainAck};
+pub use qos::{QosClass, QosClassStats, QosConfig, TierQueues};
+pub use replay::{ReplayCache, DEFAULT_REPLAY_CAPACITY, DEFAULT_REPLAY_WINDOW};
@@ -0,0 +1,318 @@
+//! Tier-based QoS for forwarded shards
+//!
+//! Shards a relay forwards wait in one queue per service class, the
+//! verified subscription tier of the shard's pool (Ultra, Premium,
+//! Standard, Basic, then Free for unsubscribed or unverified pools).
+//! Classes are served by weighted round robin, highest first: a class
+//! with weight `w` may send up to `w` shards per round before the next
+//! class's turn. A class can also be capped to a byte rate (a token
+//! bucket holding one second of traffic); while over it, its shards wait
+//! and the other classes go ahead. A full class queue refuses new shards.
+
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use craftnet_core::SubscriptionTier;
+
+/// Number of service classes
+const CLASSES: usize = 5;
+
+/// Service class of a forwarded shard
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QosClass {
+    Ultra,
+    Premium,
+    Standard,
+    Basic,
+    Free,
+}
+
+impl QosClass {
+    /// All classes, highest first (the index order of `QosConfig` arrays)
+    pub const ALL: [QosClass; CLASSES] = [
+        QosClass::Ultra,
+        QosClass::Premium,
+        QosClass::Standard,
+        QosClass::Basic,
+        QosClass::Free,
+    ];
+
+    /// Class of a pool with verified tier `tier` (None = free)
+    pub fn from_tier(tier: Option<SubscriptionTier>) -> Self {
+        match tier {
+            Some(SubscriptionTier::Ultra) => QosClass::Ultra,
+            Some(SubscriptionTier::Premium) => QosClass::Premium,
+            Some(SubscriptionTier::Standard) => QosClass::Standard,
+            Some(SubscriptionTier::Basic) => QosClass::Basic,
+            None => QosClass::Free,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            QosClass::Ultra => "ultra",
+            QosClass::Premium => "premium",
+            QosClass::Standard => "standard",
+            QosClass::Basic => "basic",
+            QosClass::Free => "free",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Scheduling weights and limits per class, indexed like [`QosClass::ALL`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QosConfig {
+    /// Shards a class may send per round. Default: 16 / 8 / 4 / 2 / 1.
+    pub weights: [u32; CLASSES],
+    /// Bytes per second a class may forward (None = unlimited).
+    /// Default: unlimited for every class.
+    pub rate_limits: [Option<u64>; CLASSES],
+    /// Shards queued per class before new ones are refused. Default: 1024.
+    pub max_queued: usize,
+}
+
+impl Default for QosConfig {
+    fn default() -> Self {
+        Self {
+            weights: [16, 8, 4, 2, 1],
+            rate_limits: [None; CLASSES],
+            max_queued: 1024,
+        }
+    }
+}
+
+/// Forwarding counters of one class
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QosClassStats {
+    pub class: QosClass,
+    /// Shards waiting to be sent
+    pub queued: usize,
+    pub forwarded: u64,
+    pub forwarded_bytes: u64,
+    /// Shards refused because the class queue was full
+    pub dropped: u64,
+    /// Shards that had to wait for the class rate limit
+    pub throttled: u64,
+}
+
+struct Queued<T> {
+    item: T,
+    bytes: usize,
+    /// Already counted in `throttled`
+    waited: bool,
+}
+
+struct ClassQueue<T> {
+    queue: VecDeque<Queued<T>>,
+    /// Shards the class may still send this round
+    deficit: u32,
+    /// Rate limit budget in bytes (unused without a limit)
+    tokens: f64,
+    refilled: Instant,
+    forwarded: u64,
+    forwarded_bytes: u64,
+    dropped: u64,
+    throttled: u64,
+}
+
+impl<T> ClassQueue<T> {
+    fn new(rate: Option<u64>, now: Instant) -> Self {
+        Self {
+            queue: VecDeque::new(),
+            deficit: 0,
+            tokens: rate.unwrap_or(0) as f64,
+            refilled: now,
+            forwarded: 0,
+            forwarded_bytes: 0,
+            dropped: 0,
+            throttled: 0,
+        }
+    }
+
+    /// Whether the front shard fits the rate limit at `now`
+    fn front_allowed(&mut self, rate: Option<u64>, now: Instant) -> bool {
+        let Some(rate) = rate else { return true };
+        let burst = rate as f64;
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * burst).min(burst);
+        self.refilled = now;
+        let Some(front) = self.queue.front_mut() else { return false };
+        // A shard larger than the whole burst goes once the bucket is full
+        if self.tokens >= (front.bytes as f64).min(burst) {
+            return true;
+        }
+        if !front.waited {
+            front.waited = true;
+            self.throttled += 1;
+        }
+        false
+    }
+}
+
+/// Per-class queues of shards waiting to be forwarded
+pub struct TierQueues<T> {
+    config: QosConfig,
+    classes: [ClassQueue<T>; CLASSES],
+    /// Index of the class whose turn it is
+    turn: usize,
+}
+
+impl<T> TierQueues<T> {
+    pub fn new(config: QosConfig) -> Self {
+        let now = Instant::now();
+        let classes = std::array::from_fn(|i| ClassQueue::new(config.rate_limits[i], now));
+        Self { config, classes, turn: 0 }
+    }
+
+    /// Replace weights and limits; queued shards stay
+    pub fn set_config(&mut self, config: QosConfig) {
+        self.config = config;
+    }
+
+    pub fn config(&self) -> &QosConfig {
+        &self.config
+    }
+
+    /// Queue `item` (a shard of `bytes`) in `class`. Returns it back if
+    /// the class queue is full.
+    pub fn push(&mut self, class: QosClass, item: T, bytes: usize) -> Result<(), T> {
+        let queue = &mut self.classes[class.index()];
+        if queue.queue.len() >= self.config.max_queued {
+            queue.dropped += 1;
+            return Err(item);
+        }
+        queue.queue.push_back(Queued { item, bytes, waited: false });
+        Ok(())
+    }
+
+    /// Next shard to send at `now`, if any class has one within its rate
+    pub fn pop(&mut self, now: Instant) -> Option<(QosClass, T)> {
+        for _ in 0..CLASSES {
+            let i = self.turn;
+            let weight = self.config.weights[i].max(1);
+            let rate = self.config.rate_limits[i];
+            let class = &mut self.classes[i];
+            if class.queue.is_empty() || !class.front_allowed(rate, now) {
+                class.deficit = 0;
+                self.turn = (i + 1) % CLASSES;
+                continue;
+            }
+            if class.deficit == 0 {
+                class.deficit = weight;
+            }
+            let queued = class.queue.pop_front()?;
+            class.deficit -= 1;
+            if rate.is_some() {
+                class.tokens -= queued.bytes as f64;
+            }
+            class.forwarded += 1;
+            class.forwarded_bytes += queued.bytes as u64;
+            if class.deficit == 0 || class.queue.is_empty() {
+                class.deficit = 0;
+                self.turn = (i + 1) % CLASSES;
+            }
+            return Some((QosClass::ALL[i], queued.item));
+        }
+        None
+    }
+
+    /// Return a shard `pop` handed out but that couldn't be sent; it goes
+    /// first next time
+    pub fn unpop(&mut self, class: QosClass, item: T, bytes: usize) {
+        let queue = &mut self.classes[class.index()];
+        queue.forwarded = queue.forwarded.saturating_sub(1);
+        queue.forwarded_bytes = queue.forwarded_bytes.saturating_sub(bytes as u64);
+        if self.config.rate_limits[class.index()].is_some() {
+            queue.tokens += bytes as f64;
+        }
+        queue.queue.push_front(Queued { item, bytes, waited: true });
+    }
+
+    /// Shards waiting across classes
+    pub fn len(&self) -> usize {
+        self.classes.iter().map(|c| c.queue.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.classes.iter().all(|c| c.queue.is_empty())
+    }
+
+    /// Counters per class, highest first
+    pub fn stats(&self) -> Vec<QosClassStats> {
+        QosClass::ALL
+            .iter()
+            .zip(&self.classes)
+            .map(|(class, q)| QosClassStats {
+                class: *class,
+                queued: q.queue.len(),
+                forwarded: q.forwarded,
+                forwarded_bytes: q.forwarded_bytes,
+                dropped: q.dropped,
+                throttled: q.throttled,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn drain(queues: &mut TierQueues<u32>, now: Instant) -> Vec<u32> {
+        std::iter::from_fn(|| queues.pop(now).map(|(_, item)| item)).collect()
+    }
+
+    #[test]
+    fn test_weighted_rounds() {
+        let config = QosConfig { weights: [2, 1, 1, 1, 1], ..QosConfig::default() };
+        let mut queues = TierQueues::new(config);
+        for i in 0..3 {
+            queues.push(QosClass::Free, 100 + i, 10).unwrap();
+            queues.push(QosClass::Ultra, i, 10).unwrap();
+        }
+        // Ultra sends two per round, Free one
+        assert_eq!(drain(&mut queues, Instant::now()), vec![0, 1, 100, 2, 101, 102]);
+        let stats = queues.stats();
+        assert_eq!(stats[0].forwarded, 3);
+        assert_eq!(stats[4].forwarded_bytes, 30);
+    }
+
+    #[test]
+    fn test_full_queue_refuses() {
+        let config = QosConfig { max_queued: 1, ..QosConfig::default() };
+        let mut queues = TierQueues::new(config);
+        queues.push(QosClass::Basic, 1, 10).unwrap();
+        assert_eq!(queues.push(QosClass::Basic, 2, 10), Err(2));
+        queues.push(QosClass::Standard, 3, 10).unwrap();
+        assert_eq!(queues.stats()[3].dropped, 1);
+        assert_eq!(queues.len(), 2);
+    }
+
+    #[test]
+    fn test_rate_limit_defers_class() {
+        let mut rate_limits = [None; CLASSES];
+        rate_limits[4] = Some(1000);
+        let mut queues = TierQueues::new(QosConfig { rate_limits, ..QosConfig::default() });
+        let start = Instant::now();
+        queues.push(QosClass::Free, 1, 800).unwrap();
+        queues.push(QosClass::Free, 2, 800).unwrap();
+        queues.push(QosClass::Basic, 3, 800).unwrap();
+
+        // Free has budget for one shard; the second waits, Basic doesn't
+        assert_eq!(drain(&mut queues, start), vec![3, 1]);
+        assert_eq!(queues.stats()[4].throttled, 1);
+        assert_eq!(drain(&mut queues, start + Duration::from_millis(700)), vec![2]);
+
+        // A shard that couldn't be sent goes back to the front
+        queues.push(QosClass::Free, 4, 10).unwrap();
+        let (class, item) = queues.pop(start + Duration::from_secs(5)).unwrap();
+        queues.unpop(class, item, 10);
+        assert_eq!(queues.stats()[4].forwarded, 2);
+        assert_eq!(drain(&mut queues, start + Duration::from_secs(5)), vec![4]);
+    }
+}
@@ -0,0 +1,208 @@
+//! Shard replay protection
+//!
+//! An upstream attacker can replay a shard it has seen to make a relay
+//! forward it — and sign a receipt for it — a second time. `ReplayCache`
+//! remembers recently seen shard ids in two rotating bloom filters: an id is
+//! remembered for at least one window and at most two, in constant memory.
+//!
+//! A bloom filter can mistake a fresh shard for a replay; while a window
+//! stays within its capacity that happens at most at `FALSE_POSITIVE_RATE`.
+//! A window that fills up early is rotated early, shortening how long ids
+//! are remembered rather than raising the false positive rate.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::time::{Duration, Instant};
+
+use craftnet_core::Id;
+
+/// Default number of shard ids per window
+pub const DEFAULT_REPLAY_CAPACITY: usize = 500_000;
+
+/// Default window (ids are remembered for one to two windows)
+pub const DEFAULT_REPLAY_WINDOW: Duration = Duration::from_secs(300);
+
+/// Target false positive rate of a filter at capacity
+const FALSE_POSITIVE_RATE: f64 = 1e-6;
+
+/// Fixed-size bloom filter over pre-hashed keys (double hashing)
+struct BloomFilter {
+    bits: Vec<u64>,
+    num_bits: u64,
+    num_hashes: u64,
+    len: usize,
+}
+
+impl BloomFilter {
+    fn new(capacity: usize) -> Self {
+        let n = capacity.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = (-n * FALSE_POSITIVE_RATE.ln() / (ln2 * ln2)).ceil().max(64.0) as u64;
+        let num_hashes = (num_bits as f64 / n * ln2).round().clamp(1.0, 32.0) as u64;
+        Self {
+            bits: vec![0; num_bits.div_ceil(64) as usize],
+            num_bits,
+            num_hashes,
+            len: 0,
+        }
+    }
+
+    fn bit(&self, (h1, h2): (u64, u64), i: u64) -> (usize, u64) {
+        let bit = h1.wrapping_add(i.wrapping_mul(h2)) % self.num_bits;
+        ((bit / 64) as usize, 1 << (bit % 64))
+    }
+
+    fn contains(&self, hashes: (u64, u64)) -> bool {
+        (0..self.num_hashes).all(|i| {
+            let (word, mask) = self.bit(hashes, i);
+            self.bits[word] & mask != 0
+        })
+    }
+
+    fn insert(&mut self, hashes: (u64, u64)) {
+        for i in 0..self.num_hashes {
+            let (word, mask) = self.bit(hashes, i);
+            self.bits[word] |= mask;
+        }
+        self.len += 1;
+    }
+
+    fn clear(&mut self) {
+        self.bits.fill(0);
+        self.len = 0;
+    }
+}
+
+/// Recently seen shard ids, for rejecting replays
+pub struct ReplayCache {
+    /// Randomly keyed per process so ids can't be crafted to collide
+    hasher: RandomState,
+    current: BloomFilter,
+    previous: BloomFilter,
+    capacity: usize,
+    window: Duration,
+    rotated_at: Instant,
+    rejected: u64,
+}
+
+impl ReplayCache {
+    /// Cache remembering up to `capacity` ids per `window`
+    pub fn new(capacity: usize, window: Duration) -> Self {
+        Self {
+            hasher: RandomState::new(),
+            current: BloomFilter::new(capacity),
+            previous: BloomFilter::new(capacity),
+            capacity: capacity.max(1),
+            window,
+            rotated_at: Instant::now(),
+            rejected: 0,
+        }
+    }
+
+    /// Record `shard_id`; returns false if it was already seen (a replay)
+    pub fn check_and_insert(&mut self, shard_id: &Id) -> bool {
+        self.check_and_insert_at(shard_id, Instant::now())
+    }
+
+    fn check_and_insert_at(&mut self, shard_id: &Id, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.rotated_at);
+        if elapsed >= self.window * 2 {
+            self.current.clear();
+            self.previous.clear();
+            self.rotated_at = now;
+        } else if elapsed >= self.window || self.current.len >= self.capacity {
+            std::mem::swap(&mut self.current, &mut self.previous);
+            self.current.clear();
+            self.rotated_at = now;
+        }
+
+        let hashes = self.hashes(shard_id);
+        if self.current.contains(hashes) || self.previous.contains(hashes) {
+            self.rejected += 1;
+            return false;
+        }
+        self.current.insert(hashes);
+        true
+    }
+
+    fn hashes(&self, shard_id: &Id) -> (u64, u64) {
+        let hash = |seed: u8| {
+            let mut hasher = self.hasher.build_hasher();
+            seed.hash(&mut hasher);
+            shard_id.hash(&mut hasher);
+            hasher.finish()
+        };
+        // Odd step so the probe sequence doesn't collapse
+        (hash(0), hash(1) | 1)
+    }
+
+    /// Replays rejected since creation
+    pub fn rejected(&self) -> u64 {
+        self.rejected
+    }
+
+    /// Ids currently remembered
+    pub fn tracked(&self) -> usize {
+        self.current.len + self.previous.len
+    }
+}
+
+impl Default for ReplayCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_REPLAY_CAPACITY, DEFAULT_REPLAY_WINDOW)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(n: u32) -> Id {
+        let mut id = [0u8; 32];
+        id[..4].copy_from_slice(&n.to_le_bytes());
+        id
+    }
+
+    #[test]
+    fn test_rejects_replay() {
+        let mut cache = ReplayCache::new(1000, Duration::from_secs(60));
+        assert!(cache.check_and_insert(&id(1)));
+        assert!(cache.check_and_insert(&id(2)));
+        assert!(!cache.check_and_insert(&id(1)));
+        assert_eq!(cache.rejected(), 1);
+        assert_eq!(cache.tracked(), 2);
+    }
+
+    #[test]
+    fn test_remembered_for_one_to_two_windows() {
+        let window = Duration::from_secs(60);
+        let mut cache = ReplayCache::new(1000, window);
+        let t0 = cache.rotated_at;
+        assert!(cache.check_and_insert_at(&id(1), t0));
+
+        // Rotated into the previous generation, still remembered
+        assert!(!cache.check_and_insert_at(&id(1), t0 + window));
+        // Two windows later it is forgotten
+        assert!(cache.check_and_insert_at(&id(1), t0 + window * 3));
+    }
+
+    #[test]
+    fn test_full_window_rotates_early() {
+        let mut cache = ReplayCache::new(10, Duration::from_secs(3600));
+        let now = cache.rotated_at;
+        for n in 0..25 {
+            assert!(cache.check_and_insert_at(&id(n), now));
+        }
+        assert!(cache.tracked() <= 20);
+        // The most recent ids are still remembered
+        assert!(!cache.check_and_insert_at(&id(24), now));
+    }
+
+    #[test]
+    fn test_low_false_positive_rate() {
+        let mut cache = ReplayCache::new(10_000, Duration::from_secs(60));
+        let now = cache.rotated_at;
+        let false_positives = (0..10_000).filter(|n| !cache.check_and_insert_at(&id(*n), now)).count();
+        assert!(false_positives <= 1, "{} false positives", false_positives);
+    }
+}
@@ -183,6 +183,8 @@ pub struct SettlementClient {
     rpc_client: Option<Arc<RpcClient>>,
     /// Mock state (only used in Mock mode)
     mock_state: Arc<RwLock<MockState>>,
+    /// Aggregators allowed to post distributions (None = anyone)
+    aggregator_allowlist: Arc<RwLock<Option<HashSet<PublicKey>>>>,
 }
 
 impl SettlementClient {
@@ -201,6 +203,7 @@ impl SettlementClient {
                 None
             },
             mock_state: Arc::new(RwLock::new(MockState::default())),
+            aggregator_allowlist: Arc::new(RwLock::new(None)),
         }
     }
 
@@ -223,6 +226,7 @@ impl SettlementClient {
             signer_pubkey,
             rpc_client,
             mock_state: Arc::new(RwLock::new(MockState::default())),
+            aggregator_allowlist: Arc::new(RwLock::new(None)),
         }
     }
 
@@ -254,6 +258,19 @@ impl SettlementClient {
             .map_err(|e| SettlementError::RpcError(format!("get_balance: {}", e)))
     }
 
+    /// Check that the settlement RPC endpoint is reachable and healthy
+    pub async fn health_check(&self) -> Result<()> {
+        if self.is_mock() {
+            return Ok(());
+        }
+
+        let rpc = self.rpc_client.as_ref()
+            .ok_or_else(|| SettlementError::RpcError("RPC client not initialized".to_string()))?;
+
+        rpc.get_health().await
+            .map_err(|e| SettlementError::RpcError(format!("get_health: {}", e)))
+    }
+
     /// Request a devnet airdrop of the given lamports amount
     pub async fn request_airdrop(&self, lamports: u64) -> Result<()> {
         if self.is_mock() {
@@ -282,6 +299,40 @@ impl SettlementClient {
         &self.signer_pubkey
     }
 
+    /// Restrict distribution posting to `allowlist` (None = anyone).
+    ///
+    /// Set from the maintainer-signed aggregator registry for the current
+    /// epoch. Posting with a key outside the list is refused before any
+    /// transaction is sent, and [`Self::is_trusted_distribution`] reports
+    /// distributions posted by other keys as untrusted.
+    pub fn set_aggregator_allowlist(&self, allowlist: Option<HashSet<PublicKey>>) {
+        *self.aggregator_allowlist.write().expect("settlement lock poisoned") = allowlist;
+    }
+
+    /// Whether `pubkey` may post distributions under the current allowlist
+    pub fn is_allowed_aggregator(&self, pubkey: &PublicKey) -> bool {
+        self.aggregator_allowlist
+            .read()
+            .expect("settlement lock poisoned")
+            .as_ref()
+            .is_none_or(|keys| keys.contains(pubkey))
+    }
+
+    /// Whether a posted distribution came from an allowed aggregator.
+    /// Without an allowlist every posted distribution is trusted; with one,
+    /// distributions of unknown posters are not.
+    pub fn is_trusted_distribution(&self, subscription: &SubscriptionState) -> bool {
+        if !subscription.distribution_posted {
+            return false;
+        }
+        let allowlist = self.aggregator_allowlist.read().expect("settlement lock poisoned");
+        match (allowlist.as_ref(), subscription.distribution_poster) {
+            (None, _) => true,
+            (Some(keys), Some(poster)) => keys.contains(&poster),
+            (Some(_), None) => false,
+        }
+    }
+
     /// Check if running in mock mode
     pub fn is_mock(&self) -> bool {
         self.config.mode == SettlementMode::Mock
@@ -730,6 +781,7 @@ impl SettlementClient {
                 total_bytes: 0,
                 distribution_posted: false,
                 distribution_root: [0u8; 32],
+                distribution_poster: None,
             };
             state.subscriptions.insert(sub.user_pubkey, subscription);
 
@@ -876,6 +928,10 @@ impl SettlementClient {
             dist.total_bytes,
         );
 
+        if !self.is_allowed_aggregator(&self.signer_pubkey) {
+            return Err(SettlementError::AggregatorNotRegistered);
+        }
+
         if self.is_mock() {
             let mut state = self.mock_state.write().expect("settlement lock poisoned");
 
@@ -899,6 +955,7 @@ impl SettlementClient {
             let subscription = state.subscriptions.get_mut(&dist.pool_pubkey).unwrap();
             subscription.distribution_posted = true;
             subscription.distribution_root = dist.distribution_root;
+            subscription.distribution_poster = Some(self.signer_pubkey);
             subscription.total_bytes = dist.total_bytes;
             subscription.original_pool_balance = subscription.pool_balance;
 
@@ -1137,6 +1194,33 @@ impl SettlementClient {
         self.send_transaction_multi(vec![create_ata_ix, claim_ix]).await
     }
 
+    /// Whether `node_pubkey` has already claimed its share of a pool.
+    ///
+    /// Live mode looks up the compressed ClaimReceipt created by the claim
+    /// instruction (requires `light_trees` config).
+    pub async fn has_claimed(
+        &self,
+        pool_pubkey: PublicKey,
+        node_pubkey: PublicKey,
+    ) -> Result<bool> {
+        if self.is_mock() {
+            let state = self.mock_state.read().expect("settlement lock poisoned");
+            return Ok(state.claimed_relays.contains(&(pool_pubkey, node_pubkey)));
+        }
+
+        let trees = self.config.light_trees.as_ref()
+            .ok_or_else(|| SettlementError::RpcError(
+                "light_trees config required for claim lookup".to_string()
+            ))?;
+        let address = light::derive_claim_receipt_address(
+            &pool_pubkey,
+            &node_pubkey,
+            &trees.address_tree,
+            &self.config.program_id,
+        );
+        self.photon_client()?.compressed_account_exists(&address).await
+    }
+
     // ==================== Query Methods ====================
 
     /// Get subscription state for a pool
@@ -1170,6 +1254,8 @@ impl SettlementClient {
                 //  73..81:  total_bytes u64
                 //  81..113: distribution_root [u8; 32]
                 // 113..114: distribution_posted bool
+                // 114..146: distribution_poster Pubkey (accounts created
+                //           before poster tracking end at 114)
                 const MIN_LEN: usize = 8 + 32 + 1 + 8 + 8 + 8 + 8 + 8 + 8 + 32 + 1; // = 122
                 if data.len() < MIN_LEN {
                     return Ok(None);
@@ -1197,6 +1283,10 @@ impl SettlementClient {
                 let mut distribution_root = [0u8; 32];
                 distribution_root.copy_from_slice(&d[81..113]);
                 let distribution_posted = d[113] != 0;
+                let distribution_poster = d
+                    .get(114..146)
+                    .and_then(|b| <PublicKey>::try_from(b).ok())
+                    .filter(|k| distribution_posted && *k != [0u8; 32]);
 
                 Ok(Some(SubscriptionState {
                     pool_pubkey: pubkey,
@@ -1209,6 +1299,7 @@ impl SettlementClient {
                     total_bytes,
                     distribution_posted,
                     distribution_root,
+                    distribution_poster,
                 }))
             }
             Err(e) => {
@@ -1280,6 +1371,7 @@ impl SettlementClient {
             total_bytes: 0,
             distribution_posted: false,
             distribution_root: [0u8; 32],
+            distribution_poster: None,
         });
         info!(
             "[MOCK] Added subscription for {} ({:?}, pool: {})",
@@ -1316,6 +1408,7 @@ impl SettlementClient {
             total_bytes: 0,
             distribution_posted: false,
             distribution_root: [0u8; 32],
+            distribution_poster: None,
         });
         info!(
             "[MOCK] Added subscription with expiry for {} ({:?}, pool: {}, expires: {})",
@@ -1586,6 +1679,60 @@ mod tests {
         assert!(matches!(result, Err(SettlementError::DistributionNotPosted)));
     }
 
+    #[tokio::test]
+    async fn test_claim_with_golden_distribution_proofs() {
+        // Roots and proofs generated by the aggregator (test-vectors/hashing.json)
+        let vectors: serde_json::Value =
+            serde_json::from_str(include_str!("../../../test-vectors/hashing.json")).unwrap();
+        let key = |v: &serde_json::Value| -> [u8; 32] {
+            let s = v.as_str().unwrap();
+            let bytes: Vec<u8> = (0..s.len()).step_by(2)
+                .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+                .collect();
+            bytes.try_into().unwrap()
+        };
+
+        let client = SettlementClient::new(SettlementConfig::mock(), [0u8; 32]);
+        let now = SettlementClient::now();
+        for vector in vectors["distributions"].as_array().unwrap() {
+            let pool = key(&vector["pool"]);
+            client.add_mock_subscription_with_expiry(
+                pool,
+                SubscriptionTier::Standard,
+                1_000_000,
+                now - 40 * 24 * 3600,
+                now - 10 * 24 * 3600,
+            ).unwrap();
+            client.post_distribution(PostDistribution {
+                pool_pubkey: pool,
+                distribution_root: key(&vector["root"]),
+                total_bytes: vector["total_bytes"].as_u64().unwrap(),
+                groth16_proof: vec![],
+                sp1_public_inputs: vec![],
+            }).await.unwrap();
+
+            for claim in vector["claims"].as_array().unwrap() {
+                let merkle_proof: Vec<[u8; 32]> = claim["siblings"].as_array().unwrap().iter().map(key).collect();
+                let claim_for = |merkle_proof: Vec<[u8; 32]>| ClaimRewards {
+                    pool_pubkey: pool,
+                    node_pubkey: key(&claim["relay"]),
+                    relay_bytes: claim["bytes"].as_u64().unwrap(),
+                    leaf_index: claim["leaf_index"].as_u64().unwrap() as u32,
+                    merkle_proof,
+                    light_params: None,
+                };
+                if !merkle_proof.is_empty() {
+                    // A wrong sibling fails; the golden proof passes
+                    let mut tampered = merkle_proof.clone();
+                    tampered[0][0] ^= 1;
+                    let result = client.claim_rewards(claim_for(tampered)).await;
+                    assert!(matches!(result, Err(SettlementError::InvalidMerkleProof)));
+                }
+                client.claim_rewards(claim_for(merkle_proof)).await.unwrap();
+            }
+        }
+    }
+
     #[tokio::test]
     async fn test_double_claim_rejected() {
         let config = SettlementConfig::mock();
@@ -1611,6 +1758,8 @@ mod tests {
             sp1_public_inputs: vec![],
         }).await.unwrap();
 
+        assert!(!client.has_claimed(user_pubkey, node).await.unwrap());
+
         // First claim succeeds
         client.claim_rewards(ClaimRewards {
             pool_pubkey: user_pubkey,
@@ -1632,6 +1781,7 @@ mod tests {
         }).await;
 
         assert!(matches!(result, Err(SettlementError::AlreadyClaimed)));
+        assert!(client.has_claimed(user_pubkey, node).await.unwrap());
     }
 
     #[tokio::test]
@@ -1703,6 +1853,43 @@ mod tests {
         assert_eq!(sub.total_bytes, 100);
     }
 
+    #[tokio::test]
+    async fn test_aggregator_allowlist() {
+        let client = SettlementClient::new(SettlementConfig::mock(), [5u8; 32]);
+        let pool = [1u8; 32];
+        let now = SettlementClient::now();
+        client.add_mock_subscription_with_expiry(
+            pool, SubscriptionTier::Standard, 1_000_000,
+            now - 40 * 24 * 3600, now - 10 * 24 * 3600,
+        ).unwrap();
+        let dist = PostDistribution {
+            pool_pubkey: pool,
+            distribution_root: [0xAA; 32],
+            total_bytes: 100,
+            groth16_proof: vec![],
+            sp1_public_inputs: vec![],
+        };
+
+        // Not on the list: refused before touching the pool
+        client.set_aggregator_allowlist(Some(HashSet::from([[6u8; 32]])));
+        let result = client.post_distribution(dist.clone()).await;
+        assert!(matches!(result, Err(SettlementError::AggregatorNotRegistered)));
+        assert!(!client.get_subscription_state(pool).await.unwrap().unwrap().distribution_posted);
+
+        // Registered: posted, and the poster is recorded
+        client.set_aggregator_allowlist(Some(HashSet::from([[5u8; 32]])));
+        client.post_distribution(dist).await.unwrap();
+        let sub = client.get_subscription_state(pool).await.unwrap().unwrap();
+        assert_eq!(sub.distribution_poster, Some([5u8; 32]));
+        assert!(client.is_trusted_distribution(&sub));
+
+        // Rotated out: the distribution is no longer trusted
+        client.set_aggregator_allowlist(Some(HashSet::from([[6u8; 32]])));
+        assert!(!client.is_trusted_distribution(&sub));
+        client.set_aggregator_allowlist(None);
+        assert!(client.is_trusted_distribution(&sub));
+    }
+
     #[tokio::test]
     async fn test_per_pool_isolation() {
         let config = SettlementConfig::mock();
@@ -21,6 +21,7 @@ mod types;
 pub use client::{SettlementClient, SettlementConfig, SettlementMode};
 pub use types::*;
 
+use craftnet_core::ErrorCode;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -55,6 +56,9 @@ pub enum SettlementError {
     #[error("Invalid Merkle proof")]
     InvalidMerkleProof,
 
+    #[error("Signer is not a registered aggregator")]
+    AggregatorNotRegistered,
+
     #[error("Pricing plan not found")]
     PlanNotFound,
 
@@ -65,4 +69,24 @@ pub enum SettlementError {
     SerializationError(String),
 }
 
+impl SettlementError {
+    /// Machine-readable code of this error
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::RpcError(_) => ErrorCode::SettlementUnavailable,
+            Self::InsufficientCredits => ErrorCode::InsufficientCredits,
+            Self::SubscriptionNotFound(_) => ErrorCode::SubscriptionNotFound,
+            Self::NotAuthorized | Self::AggregatorNotRegistered => ErrorCode::NotAuthorized,
+            Self::PlanNotFound | Self::PriceMismatch { .. } => ErrorCode::InvalidRequest,
+            Self::TransactionFailed(_)
+            | Self::PoolNotClaimable
+            | Self::DistributionNotPosted
+            | Self::AlreadyClaimed
+            | Self::DistributionAlreadyPosted
+            | Self::InvalidMerkleProof => ErrorCode::SettlementError,
+            Self::SerializationError(_) => ErrorCode::Internal,
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, SettlementError>;
@@ -166,6 +166,32 @@ struct PhotonRpcError {
     message: String,
 }
 
+/// Photon `getCompressedAccount` response
+#[derive(Debug, Clone, Deserialize)]
+struct PhotonAccountResponse {
+    result: Option<PhotonAccountResult>,
+    error: Option<PhotonRpcError>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct PhotonAccountResult {
+    value: Option<serde_json::Value>,
+}
+
+/// Photon `getCompressedAccount` request body
+#[derive(Serialize)]
+struct PhotonAccountRequest {
+    jsonrpc: &'static str,
+    id: &'static str,
+    method: &'static str,
+    params: PhotonAccountParams,
+}
+
+#[derive(Serialize)]
+struct PhotonAccountParams {
+    address: String,
+}
+
 /// Photon JSON-RPC request body
 #[derive(Serialize)]
 struct PhotonRequest {
@@ -322,6 +348,35 @@ impl PhotonClient {
 
         Err(last_err)
     }
+
+    /// Whether a compressed account exists at `address`.
+    ///
+    /// Calls Photon's `getCompressedAccount`; a null value means the address
+    /// is still unused. Single attempt — callers poll periodically.
+    pub async fn compressed_account_exists(&self, address: &[u8; 32]) -> Result<bool> {
+        let body = PhotonAccountRequest {
+            jsonrpc: "2.0",
+            id: "craftnet-1",
+            method: "getCompressedAccount",
+            params: PhotonAccountParams {
+                address: bs58::encode(address).into_string(),
+            },
+        };
+
+        let resp = self.http.post(&self.url).json(&body).send().await
+            .map_err(|e| SettlementError::RpcError(format!("Photon request error: {}", e)))?;
+        if !resp.status().is_success() {
+            return Err(SettlementError::RpcError(format!("Photon HTTP {}", resp.status())));
+        }
+        let parsed: PhotonAccountResponse = resp.json().await
+            .map_err(|e| SettlementError::RpcError(format!("Photon parse error: {}", e)))?;
+        if let Some(err) = parsed.error {
+            return Err(SettlementError::RpcError(
+                format!("Photon RPC error {}: {}", err.code, err.message),
+            ));
+        }
+        Ok(parsed.result.and_then(|r| r.value).is_some_and(|v| !v.is_null()))
+    }
 }
 
 /// Parsed validity proof from Photon.
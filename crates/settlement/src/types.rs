@@ -174,6 +174,9 @@ pub struct SubscriptionState {
     pub distribution_posted: bool,
     /// Merkle root of the distribution (set by post_distribution)
     pub distribution_root: [u8; 32],
+    /// Aggregator that posted the distribution (None until posted, or when
+    /// the account predates poster tracking)
+    pub distribution_poster: Option<PublicKey>,
 }
 
 impl SubscriptionState {
@@ -290,6 +293,7 @@ mod tests {
             total_bytes: 0,
             distribution_posted: false,
             distribution_root: [0u8; 32],
+            distribution_poster: None,
         };
 
         assert_eq!(state.tier, SubscriptionTier::Premium);
@@ -312,6 +316,7 @@ mod tests {
             total_bytes: 0,
             distribution_posted: false,
             distribution_root: [0u8; 32],
+            distribution_poster: None,
         };
 
         assert_eq!(state.phase(now + 100), EpochPhase::Active);
@@ -333,6 +338,7 @@ mod tests {
             total_bytes: 0,
             distribution_posted: false,
             distribution_root: [0u8; 32],
+            distribution_poster: None,
         };
 
         // Just after expiry — should be Grace
@@ -357,6 +363,7 @@ mod tests {
             total_bytes: 0,
             distribution_posted: false,
             distribution_root: [0u8; 32],
+            distribution_poster: None,
         };
 
         // After grace period, with balance remaining
@@ -379,6 +386,7 @@ mod tests {
             total_bytes: 100,
             distribution_posted: true,
             distribution_root: [0xAA; 32],
+            distribution_poster: None,
         };
 
         // After grace, pool drained → Closed
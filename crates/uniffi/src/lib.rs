@@ -13,8 +13,8 @@ use parking_lot::{Mutex, RwLock};
 use tokio::runtime::Runtime;
 use tracing::{debug, info};
 
-use craftnet_client::{Capabilities, CraftNetNode};
-use craftnet_core::HopMode;
+use craftnet_client::{Capabilities, ClientError, CraftNetNode, KillSwitch, KillSwitchState, KillSwitchStatus};
+use craftnet_core::{ErrorCode, HopMode};
 
 // Export UniFFI scaffolding
 uniffi::setup_scaffolding!();
@@ -67,6 +67,36 @@ pub enum PrivacyLevel {
     Quad,      // 4 hops
 }
 
+/// Kill switch state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum KillSwitchMode {
+    /// Not blocking; engages if the tunnel drops unexpectedly (when enabled)
+    Armed,
+    /// Traffic outside the tunnel must be blocked
+    Engaged,
+    /// Released by the user while the tunnel is still down
+    Released,
+}
+
+impl From<KillSwitchState> for KillSwitchMode {
+    fn from(state: KillSwitchState) -> Self {
+        match state {
+            KillSwitchState::Armed => Self::Armed,
+            KillSwitchState::Engaged => Self::Engaged,
+            KillSwitchState::Released => Self::Released,
+        }
+    }
+}
+
+/// Notified on every kill switch transition.
+///
+/// The Network Extension / VpnService implements this to install or remove
+/// its block-all rules (e.g. `includeAllNetworks`, lockdown routes).
+#[uniffi::export(callback_interface)]
+pub trait KillSwitchListener: Send + Sync {
+    fn on_kill_switch_changed(&self, mode: KillSwitchMode, reason: Option<String>);
+}
+
 /// Individual capability flags exposed to FFI.
 ///
 /// UniFFI doesn't support bitflags, so capabilities are represented as
@@ -130,6 +160,8 @@ pub struct UnifiedNodeConfig {
     pub bootstrap_peer: Option<String>,
     /// Request timeout in seconds
     pub request_timeout_secs: u64,
+    /// Keep cookies between requests while the node runs (never persisted)
+    pub cookie_jar: bool,
 }
 
 impl Default for UnifiedNodeConfig {
@@ -139,6 +171,34 @@ impl Default for UnifiedNodeConfig {
             privacy_level: PrivacyLevel::Triple,
             bootstrap_peer: None,
             request_timeout_secs: 30,
+            cookie_jar: false,
+        }
+    }
+}
+
+/// Bytes moved during one second, by capability (for live graphs)
+#[derive(Debug, Clone, Default, uniffi::Record)]
+pub struct ThroughputSample {
+    /// Unix second the sample covers
+    pub timestamp: u64,
+    pub client_up: u64,
+    pub client_down: u64,
+    pub relay_up: u64,
+    pub relay_down: u64,
+    pub exit_up: u64,
+    pub exit_down: u64,
+}
+
+impl From<craftnet_client::ThroughputSample> for ThroughputSample {
+    fn from(s: craftnet_client::ThroughputSample) -> Self {
+        Self {
+            timestamp: s.timestamp,
+            client_up: s.client_up,
+            client_down: s.client_down,
+            relay_up: s.relay_up,
+            relay_down: s.relay_down,
+            exit_up: s.exit_up,
+            exit_down: s.exit_down,
         }
     }
 }
@@ -154,6 +214,11 @@ pub struct UnifiedNodeStats {
     pub requests_exited: u64,
     pub credits_earned: u64,
     pub credits_spent: u64,
+    // Credit ledger breakdown (earned = relay + exit + adjustment)
+    pub credits_relay_earned: u64,
+    pub credits_exit_earned: u64,
+    pub credit_adjustment: i64,
+    pub unreconciled_pools: u32,
     // Connection stats
     pub connected_peers: u32,
     pub uptime_secs: u64,
@@ -167,6 +232,19 @@ pub struct TunnelResponse {
     pub status: u16,
     pub body: Vec<u8>,
     pub headers: Vec<String>,
+    /// Times the request moved to an alternate exit before this response
+    pub exit_failovers: u32,
+}
+
+/// Per-request overrides for [`CraftNetUnifiedNode::request_with`]
+#[derive(Debug, Clone, Default, uniffi::Record)]
+pub struct RequestOptions {
+    /// Privacy level for this request (None = the configured level)
+    pub privacy_level: Option<PrivacyLevel>,
+    /// Exit public key hex for this request (None = the selected exit)
+    pub exit: Option<String>,
+    /// Idle timeout for this request (None = adaptive)
+    pub timeout_ms: Option<u64>,
 }
 
 /// Information about an available exit node
@@ -179,6 +257,8 @@ pub struct ExitNodeInfo {
     pub city: Option<String>,
     pub reputation: u64,
     pub latency_ms: u32,
+    /// PeerId bound to the exit's pubkey by a verified binding record
+    pub peer_id: Option<String>,
 }
 
 /// Error types for VPN operations
@@ -210,6 +290,71 @@ pub enum CraftNetError {
 
     #[error("Internal error: {msg}")]
     InternalError { msg: String },
+
+    /// Any other SDK failure, with the code shared with the daemon IPC
+    #[error("{msg} ({code})")]
+    Failed { code: ErrorCode, msg: String },
+}
+
+impl CraftNetError {
+    /// Machine-readable code of this error
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::NotInitialized | Self::InternalError { .. } => ErrorCode::Internal,
+            Self::AlreadyConnected => ErrorCode::AlreadyRunning,
+            Self::NotConnected => ErrorCode::NotConnected,
+            Self::ConnectionFailed { .. } => ErrorCode::ConnectionFailed,
+            Self::NoExitNodes => ErrorCode::NoExitNodes,
+            Self::Timeout => ErrorCode::Timeout,
+            Self::InsufficientCredits => ErrorCode::InsufficientCredits,
+            Self::InvalidConfig { .. } => ErrorCode::InvalidRequest,
+            Self::Failed { code, .. } => *code,
+        }
+    }
+}
+
+impl From<ClientError> for CraftNetError {
+    fn from(e: ClientError) -> Self {
+        match e {
+            ClientError::NotConnected => Self::NotConnected,
+            ClientError::Timeout => Self::Timeout,
+            ClientError::InsufficientCredits { .. } => Self::InsufficientCredits,
+            ClientError::NoExitNodes | ClientError::NoExitsInRegion(_) => Self::NoExitNodes,
+            e => Self::Failed { code: e.code(), msg: e.to_string() },
+        }
+    }
+}
+
+/// Machine-readable error codes (mirrors `craftnet_core::ErrorCode`)
+#[uniffi::remote(Enum)]
+pub enum ErrorCode {
+    Internal,
+    InvalidRequest,
+    NotConnected,
+    NotRunning,
+    AlreadyRunning,
+    ConnectionFailed,
+    NoPeers,
+    PeerUnreachable,
+    NetworkError,
+    Timeout,
+    RequestFailed,
+    NoExitNodes,
+    ExitUnreachable,
+    RateLimited,
+    BlockedDestination,
+    UpstreamError,
+    ResponseTooLarge,
+    RequestTooLarge,
+    InvalidResponse,
+    ResponseTampered,
+    CryptoError,
+    InsufficientCredits,
+    SettlementUnavailable,
+    SettlementError,
+    SubscriptionNotFound,
+    NotAuthorized,
+    IoError,
 }
 
 /// Create a default unified node configuration
@@ -230,6 +375,7 @@ pub fn create_unified_config(
         privacy_level,
         bootstrap_peer,
         request_timeout_secs: 30,
+        cookie_jar: false,
     }
 }
 
@@ -241,6 +387,7 @@ struct UnifiedNodeState {
     error: Option<String>,
     stats: UnifiedNodeStats,
     start_time: Option<Instant>,
+    kill_switch: KillSwitch,
 }
 
 impl Default for UnifiedNodeState {
@@ -252,6 +399,7 @@ impl Default for UnifiedNodeState {
             error: None,
             stats: UnifiedNodeStats::default(),
             start_time: None,
+            kill_switch: KillSwitch::default(),
         }
     }
 }
@@ -271,6 +419,7 @@ unsafe impl Send for UnifiedNodeState {}
 pub struct CraftNetUnifiedNode {
     config: RwLock<UnifiedNodeConfig>,
     state: Mutex<UnifiedNodeState>,
+    kill_switch_listener: Mutex<Option<Box<dyn KillSwitchListener>>>,
 }
 
 #[uniffi::export]
@@ -290,6 +439,7 @@ impl CraftNetUnifiedNode {
         Ok(Arc::new(Self {
             config: RwLock::new(config),
             state: Mutex::new(state),
+            kill_switch_listener: Mutex::new(None),
         }))
     }
 
@@ -312,6 +462,7 @@ impl CraftNetUnifiedNode {
         let node_config = craftnet_client::NodeConfig {
             capabilities: caps,
             hop_mode: config.privacy_level.into(),
+            cookie_jar: config.cookie_jar.then(craftnet_client::CookieJarConfig::in_memory),
             ..Default::default()
         };
 
@@ -335,6 +486,9 @@ impl CraftNetUnifiedNode {
                 state.node = Some(node);
                 state.state = ConnectionState::Connected;
                 state.start_time = Some(Instant::now());
+                let transition = state.kill_switch.on_connected();
+                drop(state);
+                self.notify_kill_switch(transition);
                 info!("CraftNetUnifiedNode started successfully");
                 Ok(())
             }
@@ -356,6 +510,7 @@ impl CraftNetUnifiedNode {
 
         info!("Stopping CraftNetUnifiedNode...");
         state.state = ConnectionState::Disconnecting;
+        let transition = state.kill_switch.on_user_disconnect();
 
         if let Some(mut node) = state.node.take() {
             drop(state);
@@ -371,6 +526,7 @@ impl CraftNetUnifiedNode {
             state.state = ConnectionState::Disconnected;
         }
 
+        self.notify_kill_switch(transition);
         info!("CraftNetUnifiedNode stopped");
         Ok(())
     }
@@ -437,12 +593,25 @@ impl CraftNetUnifiedNode {
             stats.requests_exited = node_stats.requests_exited;
             stats.credits_earned = node_stats.credits_earned;
             stats.credits_spent = node_stats.credits_spent;
+            stats.credits_relay_earned = node_stats.credit_ledger.relay_earned;
+            stats.credits_exit_earned = node_stats.credit_ledger.exit_earned;
+            stats.credit_adjustment = node_stats.credit_ledger.adjustment;
+            stats.unreconciled_pools = node_stats.credit_ledger.unreconciled_pools as u32;
             stats.connected_peers = node_stats.peers_connected as u32;
         }
 
         stats
     }
 
+    /// Per-second throughput of the last `window_secs` seconds, oldest first
+    pub fn get_throughput_series(&self, window_secs: u64) -> Vec<ThroughputSample> {
+        let state = self.state.lock();
+        match state.node {
+            Some(ref node) => node.throughput_series(window_secs).into_iter().map(ThroughputSample::from).collect(),
+            None => Vec::new(),
+        }
+    }
+
     /// Get peer ID
     pub fn get_peer_id(&self) -> String {
         let state = self.state.lock();
@@ -506,6 +675,34 @@ impl CraftNetUnifiedNode {
         url: String,
         body: Option<Vec<u8>>,
     ) -> Result<TunnelResponse, CraftNetError> {
+        self.request_with(method, url, body, RequestOptions::default())
+    }
+
+    /// Make an HTTP request through the tunnel with its own privacy level,
+    /// exit or timeout (e.g. Direct for latency-sensitive calls, Quad for
+    /// sensitive ones)
+    pub fn request_with(
+        &self,
+        method: String,
+        url: String,
+        body: Option<Vec<u8>>,
+        options: RequestOptions,
+    ) -> Result<TunnelResponse, CraftNetError> {
+        let exit = options.exit
+            .map(|pubkey| {
+                hex::decode(&pubkey)
+                    .ok()
+                    .and_then(|b| <[u8; 32]>::try_from(b).ok())
+                    .ok_or_else(|| CraftNetError::InvalidConfig { msg: format!("Invalid exit pubkey: {}", pubkey) })
+            })
+            .transpose()?;
+        let options = craftnet_client::RequestOptions {
+            hop_mode: options.privacy_level.map(HopMode::from),
+            exit,
+            timeout: options.timeout_ms.map(std::time::Duration::from_millis),
+            trace: false,
+        };
+
         let state = self.state.lock();
 
         if state.state != ConnectionState::Connected {
@@ -524,9 +721,9 @@ impl CraftNetUnifiedNode {
                 let mut state = self.state.lock();
                 state.node.take().ok_or(CraftNetError::NotConnected)?
             };
-            let res = node.fetch(&method, &url, body, None)
+            let res = node.fetch_with(&method, &url, body, None, options)
                 .await
-                .map_err(|e| CraftNetError::InternalError { msg: e.to_string() });
+                .map_err(CraftNetError::from);
             // Put the node back
             self.state.lock().node = Some(node);
             res
@@ -536,9 +733,17 @@ impl CraftNetUnifiedNode {
             status: r.status,
             body: r.body,
             headers: r.headers.into_iter().map(|(k, v)| format!("{}: {}", k, v)).collect(),
+            exit_failovers: r.exit_failovers,
         })
     }
 
+    /// Forget the cookies the node stored (see `UnifiedNodeConfig::cookie_jar`)
+    pub fn clear_session_data(&self) {
+        if let Some(ref mut node) = self.state.lock().node {
+            node.clear_session_data(None);
+        }
+    }
+
     /// Get available exit nodes from the network
     pub fn get_available_exits(&self) -> Vec<ExitNodeInfo> {
         let state = self.state.lock();
@@ -553,6 +758,7 @@ impl CraftNetUnifiedNode {
                     city: e.city.clone(),
                     reputation: e.reputation,
                     latency_ms: e.latency_ms,
+                    peer_id: node.bound_peer_id(&e.pubkey).map(|p| p.to_string()),
                 })
                 .collect()
         } else {
@@ -598,6 +804,38 @@ impl CraftNetUnifiedNode {
         }
     }
 
+    /// Engage the kill switch automatically when the tunnel drops unexpectedly
+    pub fn set_kill_switch_enabled(&self, enabled: bool) {
+        self.state.lock().kill_switch.set_enabled(enabled);
+    }
+
+    /// Whether the kill switch engages automatically
+    pub fn is_kill_switch_enabled(&self) -> bool {
+        self.state.lock().kill_switch.status().enabled
+    }
+
+    /// Current kill switch state
+    pub fn get_kill_switch_mode(&self) -> KillSwitchMode {
+        self.state.lock().kill_switch.state().into()
+    }
+
+    /// Block traffic outside the tunnel now
+    pub fn engage_kill_switch(&self, reason: String) {
+        let transition = self.state.lock().kill_switch.engage(&reason);
+        self.notify_kill_switch(transition);
+    }
+
+    /// User override: stop blocking until the tunnel is back
+    pub fn release_kill_switch(&self) {
+        let transition = self.state.lock().kill_switch.release();
+        self.notify_kill_switch(transition);
+    }
+
+    /// Register the listener notified on kill switch transitions
+    pub fn set_kill_switch_listener(&self, listener: Box<dyn KillSwitchListener>) {
+        *self.kill_switch_listener.lock() = Some(listener);
+    }
+
     /// Poll the network once (for manual event loop control)
     ///
     /// Call this periodically when you want to manually drive the event loop.
@@ -619,7 +857,18 @@ impl CraftNetUnifiedNode {
 
             // Put node back
             let mut state = self.state.lock();
+            let connected = node.as_ref().is_some_and(|n| n.is_connected());
             state.node = node;
+            // Engage the kill switch if the tunnel dropped while it should be up
+            let transition = if state.state != ConnectionState::Connected {
+                None
+            } else if connected {
+                state.kill_switch.on_connected()
+            } else {
+                state.kill_switch.on_unexpected_disconnect("lost all peers")
+            };
+            drop(state);
+            self.notify_kill_switch(transition);
             true
         } else {
             false
@@ -627,10 +876,32 @@ impl CraftNetUnifiedNode {
     }
 }
 
+impl CraftNetUnifiedNode {
+    fn notify_kill_switch(&self, transition: Option<KillSwitchStatus>) {
+        let Some(status) = transition else { return };
+        info!("Kill switch {:?}", status.state);
+        if let Some(ref listener) = *self.kill_switch_listener.lock() {
+            listener.on_kill_switch_changed(status.state.into(), status.reason);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_error_codes() {
+        assert_eq!(CraftNetError::from(ClientError::Timeout).code(), ErrorCode::Timeout);
+        assert!(matches!(
+            CraftNetError::from(ClientError::InsufficientCredits { have: 0, need: 1 }),
+            CraftNetError::InsufficientCredits
+        ));
+        let err = CraftNetError::from(ClientError::ExitUnreachable("gone".to_string()));
+        assert_eq!(err.code(), ErrorCode::ExitUnreachable);
+        assert!(matches!(err, CraftNetError::Failed { .. }));
+    }
+
     #[test]
     fn test_privacy_level_conversion() {
         assert_eq!(HopMode::from(PrivacyLevel::Direct), HopMode::Direct);
@@ -694,6 +965,32 @@ mod tests {
         assert!(node.is_node_active());
     }
 
+    #[test]
+    fn test_unified_node_kill_switch() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct Counter(Arc<AtomicUsize>);
+        impl KillSwitchListener for Counter {
+            fn on_kill_switch_changed(&self, _mode: KillSwitchMode, _reason: Option<String>) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        init_library();
+
+        let node = CraftNetUnifiedNode::new(UnifiedNodeConfig::default()).unwrap();
+        let calls = Arc::new(AtomicUsize::new(0));
+        node.set_kill_switch_listener(Box::new(Counter(calls.clone())));
+        assert!(!node.is_kill_switch_enabled());
+        assert_eq!(node.get_kill_switch_mode(), KillSwitchMode::Armed);
+
+        node.engage_kill_switch("test".to_string());
+        assert_eq!(node.get_kill_switch_mode(), KillSwitchMode::Engaged);
+        node.release_kill_switch();
+        assert_eq!(node.get_kill_switch_mode(), KillSwitchMode::Released);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
     #[test]
     fn test_unified_node_set_privacy_level() {
         init_library();
@@ -146,6 +146,7 @@ pub mod craftnet_settlement {
         subscription.total_receipts = 0;
         subscription.distribution_root = [0u8; 32];
         subscription.distribution_posted = false;
+        subscription.distribution_poster = Pubkey::default();
 
         // Transfer USDC from payer to pool token account
         token::transfer(
@@ -256,11 +257,13 @@ pub mod craftnet_settlement {
         subscription.total_receipts = total_receipts;
         subscription.original_pool_balance = subscription.pool_balance;
         subscription.distribution_posted = true;
+        subscription.distribution_poster = ctx.accounts.signer.key();
 
         emit!(DistributionPosted {
             pool_pubkey: subscription.pool_pubkey,
             total_receipts,
             distribution_root,
+            poster: subscription.distribution_poster,
         });
 
         Ok(())
@@ -659,6 +662,9 @@ pub struct SubscriptionAccount {
     pub distribution_root: [u8; 32],
     /// Whether distribution has been posted
     pub distribution_posted: bool,
+    /// Aggregator that posted the distribution (clients that only trust
+    /// registered aggregators check it off-chain)
+    pub distribution_poster: Pubkey,
 }
 
 // ============================================================================
@@ -772,6 +778,7 @@ pub struct DistributionPosted {
     pub pool_pubkey: [u8; 32],
     pub total_receipts: u64,
     pub distribution_root: [u8; 32],
+    pub poster: Pubkey,
 }
 
 #[event]
@@ -816,3 +823,38 @@ pub enum SettlementError {
     #[msg("Price must be > 0")]
     InvalidPrice,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes32(value: &serde_json::Value) -> [u8; 32] {
+        let s = value.as_str().unwrap();
+        let bytes: Vec<u8> = (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect();
+        bytes.try_into().unwrap()
+    }
+
+    /// Proofs built off-chain by the aggregator (test-vectors/hashing.json)
+    /// verify against their roots, and nothing else does
+    #[test]
+    fn test_verify_merkle_proof_golden() {
+        let vectors: serde_json::Value =
+            serde_json::from_str(include_str!("../../../test-vectors/hashing.json")).unwrap();
+        let trees = vectors["merkle"].as_array().unwrap().iter().map(|v| (v, "proofs"));
+        let distributions = vectors["distributions"].as_array().unwrap().iter().map(|v| (v, "claims"));
+        for (vector, proofs) in trees.chain(distributions) {
+            let root = bytes32(&vector["root"]);
+            for p in vector[proofs].as_array().unwrap() {
+                let relay = bytes32(&p["relay"]);
+                let count = p["bytes"].as_u64().unwrap();
+                let index = p["leaf_index"].as_u64().unwrap() as usize;
+                let siblings: Vec<[u8; 32]> = p["siblings"].as_array().unwrap().iter().map(bytes32).collect();
+                assert!(verify_merkle_proof(&relay, count, &siblings, index, &root), "{}", vector["name"]);
+                assert!(!verify_merkle_proof(&relay, count + 1, &siblings, index, &root));
+            }
+        }
+    }
+}
@@ -734,6 +734,8 @@ fn test_exit_payload_encrypt_decrypt_roundtrip() {
         mode: 0x00,
         data: b"GET\nhttps://example.com\n0\n0\n".to_vec(),
         response_enc_pubkey: [0u8; 32],
+        pool_pubkey: [2u8; 32],
+        quota_tokens: vec![],
     };
 
     let encrypted = encrypt_exit_payload(
@@ -33,7 +33,7 @@ use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::signature::{Keypair as SolanaKeypair, Signer as _};
 use solana_system_interface::instruction as system_instruction;
 use solana_sdk::transaction::Transaction;
-use craftnet_client::{Capabilities, NodeConfig, CraftNetNode, NodeStats};
+use craftnet_client::{Capabilities, NodeConfig, CraftNetNode, NodeStats, ProofPublishPolicy};
 use craftnet_core::HopMode;
 use craftnet_aggregator::{BandwidthBucket, Granularity, NetworkStats};
 use craftnet_network::PoolType;
@@ -576,6 +576,7 @@ async fn ten_node_live_network() {
         listen_addr: format!("/ip4/127.0.0.1/tcp/{}", base_port).parse().unwrap(),
         proof_batch_size: 5,
         proof_deadline: Duration::from_secs(30),
+        proof_publish: ProofPublishPolicy { min_interval: Duration::ZERO, ..Default::default() },
         maintenance_interval: Duration::from_secs(15),
         ..Default::default()
     };
@@ -600,6 +601,7 @@ async fn ten_node_live_network() {
             bootstrap_peers: bootstrap_peers.clone(),
             proof_batch_size: 5,
             proof_deadline: Duration::from_secs(30),
+            proof_publish: ProofPublishPolicy { min_interval: Duration::ZERO, ..Default::default() },
             maintenance_interval: Duration::from_secs(15),
             ..Default::default()
         };
@@ -620,6 +622,7 @@ async fn ten_node_live_network() {
             exit_allow_private_ips: true,        // Allow 127.0.0.1 for localhost test server
             proof_batch_size: 5,
             proof_deadline: Duration::from_secs(30),
+            proof_publish: ProofPublishPolicy { min_interval: Duration::ZERO, ..Default::default() },
             maintenance_interval: Duration::from_secs(15),
             ..Default::default()
         };
@@ -649,6 +652,7 @@ async fn ten_node_live_network() {
             bootstrap_peers: bootstrap_peers.clone(),
             proof_batch_size: 5,
             proof_deadline: Duration::from_secs(30),
+            proof_publish: ProofPublishPolicy { min_interval: Duration::ZERO, ..Default::default() },
             maintenance_interval: Duration::from_secs(15),
             ..Default::default()
         };
@@ -700,6 +704,7 @@ async fn ten_node_live_network() {
             hop_mode: spec.hop_mode,
             proof_batch_size: 5,
             proof_deadline: Duration::from_secs(30),
+            proof_publish: ProofPublishPolicy { min_interval: Duration::ZERO, ..Default::default() },
             maintenance_interval: Duration::from_secs(15),
             ..Default::default()
         };